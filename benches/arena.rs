@@ -0,0 +1,90 @@
+//! What a node arena costs on top of parsing, on real-world source.
+//!
+//! The parser builds the boxed tree and `Arena::new` takes it apart, so an
+//! arena is made with more allocations than the parse alone, not fewer;
+//! the allocations made for each are printed before the timings.
+
+#[macro_use]
+extern crate criterion;
+extern crate rustpy;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, Criterion};
+use rustpy::ast::Arena;
+use rustpy::parser;
+
+const TYPING: &str = include_str!("data/typing.py");
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn report_allocations(name: &str, run: &dyn Fn()) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    run();
+    println!(
+        "{}: {} allocations, {} bytes allocated",
+        name,
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes
+    );
+}
+
+fn arena(c: &mut Criterion) {
+    report_allocations("parse typing.py", &|| {
+        black_box(parser::parse(TYPING).unwrap());
+    });
+    report_allocations("parse typing.py into an arena", &|| {
+        black_box(Arena::new(parser::parse(TYPING).unwrap()));
+    });
+    let module = parser::parse(TYPING).unwrap();
+    let arena = Arena::new(module.clone());
+    report_allocations("rebuild the module of typing.py", &|| {
+        black_box(arena.to_module());
+    });
+
+    let mut group = c.benchmark_group("arena");
+    group.bench_function("parse typing.py", |b| {
+        b.iter(|| parser::parse(black_box(TYPING)).unwrap())
+    });
+    group.bench_function("build the arena of typing.py", |b| {
+        b.iter(|| Arena::new(black_box(module.clone())))
+    });
+    group.bench_function("clone the module of typing.py", |b| {
+        b.iter(|| black_box(&module).clone())
+    });
+    group.bench_function("visit the arena of typing.py", |b| {
+        b.iter(|| {
+            black_box(&arena)
+                .iter()
+                .map(|(_, node)| node.end.line - node.start.line)
+                .sum::<usize>()
+        })
+    });
+    group.bench_function("rebuild the module of typing.py", |b| {
+        b.iter(|| black_box(&arena).to_module())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, arena);
+criterion_main!(benches);
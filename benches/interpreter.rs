@@ -0,0 +1,91 @@
+//! Classic interpreter micro-benchmarks, timed in rustpy and, for
+//! comparison, in CPython.
+//!
+//! Each program in `interpreter/` defines `run()`, which does one round of
+//! the benchmark and checks its result. rustpy runs the program once and
+//! then times calls of `run`. CPython, `python3` on the path or the one
+//! `RUSTPY_BENCH_PYTHON` names, is given the program with a loop that
+//! times the same number of calls by its own clock, so that starting it is
+//! not counted; its timings are left out when there is no CPython to run.
+
+#[macro_use]
+extern crate criterion;
+extern crate rustpy;
+
+use std::env;
+use std::process::Command;
+use std::time::Duration;
+
+use criterion::{black_box, Criterion};
+use rustpy::vm::Vm;
+
+const PROGRAMS: [(&str, &str); 4] = [
+    ("fib", include_str!("interpreter/fib.py")),
+    (
+        "spectral_norm",
+        include_str!("interpreter/spectral_norm.py"),
+    ),
+    ("deltablue", include_str!("interpreter/deltablue.py")),
+    ("string_ops", include_str!("interpreter/string_ops.py")),
+];
+
+/// The CPython to compare with, if it runs.
+fn cpython() -> Option<String> {
+    let python = env::var("RUSTPY_BENCH_PYTHON").unwrap_or_else(|_| "python3".to_string());
+    let runs = Command::new(&python)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success());
+    if runs {
+        Some(python)
+    } else {
+        println!("{} does not run: timing rustpy alone", python);
+        None
+    }
+}
+
+/// The time CPython takes for `iterations` calls of `run`, after one call
+/// to warm up.
+fn cpython_time(python: &str, source: &str, iterations: u64) -> Duration {
+    let program = format!(
+        "{}\nimport time\nrun()\nstart = time.perf_counter()\nfor _ in range({}):\n    run()\nprint(time.perf_counter() - start)\n",
+        source, iterations
+    );
+    let output = Command::new(python)
+        .arg("-c")
+        .arg(program)
+        .output()
+        .expect("CPython did not start");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let seconds: f64 = String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .expect("CPython printed no time");
+    Duration::from_secs_f64(seconds)
+}
+
+fn programs(c: &mut Criterion) {
+    let python = cpython();
+    for &(name, source) in &PROGRAMS {
+        let mut vm = Vm::new();
+        vm.run_source(source, &format!("{}.py", name)).unwrap();
+        let mut group = c.benchmark_group(name);
+        group.sample_size(10);
+        group.bench_function("rustpy", |b| {
+            b.iter(|| vm.run_source(black_box("run()"), "<bench>").unwrap())
+        });
+        if let Some(ref python) = python {
+            group.bench_function("cpython", |b| {
+                b.iter_custom(|iterations| cpython_time(python, source, iterations))
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, programs);
+criterion_main!(benches);
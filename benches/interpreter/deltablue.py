@@ -0,0 +1,397 @@
+"""DeltaBlue, the incremental dataflow constraint solver of Freeman-Benson,
+Maloney and Borning, after the Smalltalk benchmark by Mario Wolczko:
+method calls, attribute access and lists of small objects.
+
+Strengths are ints, the lower the stronger."""
+
+REQUIRED = 0
+STRONG_PREFERRED = 1
+PREFERRED = 2
+STRONG_DEFAULT = 3
+NORMAL = 4
+WEAK_DEFAULT = 5
+WEAKEST = 6
+
+NONE = 0
+FORWARD = 1
+BACKWARD = -1
+
+
+def stronger(s1, s2):
+    return s1 < s2
+
+
+def weaker(s1, s2):
+    return s1 > s2
+
+
+def weakest_of(s1, s2):
+    return s1 if weaker(s1, s2) else s2
+
+
+class Constraint(object):
+    def __init__(self, strength):
+        self.strength = strength
+
+    def add_constraint(self):
+        self.add_to_graph()
+        planner.incremental_add(self)
+
+    def satisfy(self, mark):
+        self.choose_method(mark)
+        if not self.is_satisfied():
+            if self.strength == REQUIRED:
+                raise RuntimeError("could not satisfy a required constraint")
+            return None
+        self.mark_inputs(mark)
+        out = self.output()
+        overridden = out.determined_by
+        if overridden is not None:
+            overridden.mark_unsatisfied()
+        out.determined_by = self
+        if not planner.add_propagate(self, mark):
+            raise RuntimeError("cycle encountered")
+        out.mark = mark
+        return overridden
+
+    def destroy_constraint(self):
+        if self.is_satisfied():
+            planner.incremental_remove(self)
+        else:
+            self.remove_from_graph()
+
+    def is_input(self):
+        return False
+
+
+class UnaryConstraint(Constraint):
+    def __init__(self, v, strength):
+        super().__init__(strength)
+        self.my_output = v
+        self.satisfied = False
+        self.add_constraint()
+
+    def add_to_graph(self):
+        self.my_output.add_constraint(self)
+        self.satisfied = False
+
+    def choose_method(self, mark):
+        self.satisfied = self.my_output.mark != mark and stronger(
+            self.strength, self.my_output.walk_strength
+        )
+
+    def is_satisfied(self):
+        return self.satisfied
+
+    def mark_inputs(self, mark):
+        pass
+
+    def output(self):
+        return self.my_output
+
+    def recalculate(self):
+        self.my_output.walk_strength = self.strength
+        self.my_output.stay = not self.is_input()
+        if self.my_output.stay:
+            self.execute()
+
+    def mark_unsatisfied(self):
+        self.satisfied = False
+
+    def inputs_known(self, mark):
+        return True
+
+    def remove_from_graph(self):
+        self.my_output.remove_constraint(self)
+        self.satisfied = False
+
+
+class StayConstraint(UnaryConstraint):
+    def execute(self):
+        pass
+
+
+class EditConstraint(UnaryConstraint):
+    def is_input(self):
+        return True
+
+    def execute(self):
+        pass
+
+
+class BinaryConstraint(Constraint):
+    def __init__(self, v1, v2, strength):
+        super().__init__(strength)
+        self.v1 = v1
+        self.v2 = v2
+        self.direction = NONE
+        self.add_constraint()
+
+    def choose_method(self, mark):
+        if self.v1.mark == mark:
+            if self.v2.mark != mark and stronger(self.strength, self.v2.walk_strength):
+                self.direction = FORWARD
+            else:
+                self.direction = NONE
+        if self.v2.mark == mark:
+            if self.v1.mark != mark and stronger(self.strength, self.v1.walk_strength):
+                self.direction = BACKWARD
+            else:
+                self.direction = NONE
+        if weaker(self.v1.walk_strength, self.v2.walk_strength):
+            if stronger(self.strength, self.v1.walk_strength):
+                self.direction = BACKWARD
+            else:
+                self.direction = NONE
+        elif stronger(self.strength, self.v2.walk_strength):
+            self.direction = FORWARD
+        else:
+            self.direction = BACKWARD
+
+    def add_to_graph(self):
+        self.v1.add_constraint(self)
+        self.v2.add_constraint(self)
+        self.direction = NONE
+
+    def is_satisfied(self):
+        return self.direction != NONE
+
+    def mark_inputs(self, mark):
+        self.input().mark = mark
+
+    def input(self):
+        return self.v1 if self.direction == FORWARD else self.v2
+
+    def output(self):
+        return self.v2 if self.direction == FORWARD else self.v1
+
+    def recalculate(self):
+        ihn = self.input()
+        out = self.output()
+        out.walk_strength = weakest_of(self.strength, ihn.walk_strength)
+        out.stay = ihn.stay
+        if out.stay:
+            self.execute()
+
+    def mark_unsatisfied(self):
+        self.direction = NONE
+
+    def inputs_known(self, mark):
+        i = self.input()
+        return i.mark == mark or i.stay or i.determined_by is None
+
+    def remove_from_graph(self):
+        self.v1.remove_constraint(self)
+        self.v2.remove_constraint(self)
+        self.direction = NONE
+
+
+class ScaleConstraint(BinaryConstraint):
+    def __init__(self, src, scale, offset, dest, strength):
+        self.scale = scale
+        self.offset = offset
+        super().__init__(src, dest, strength)
+
+    def add_to_graph(self):
+        super().add_to_graph()
+        self.scale.add_constraint(self)
+        self.offset.add_constraint(self)
+
+    def remove_from_graph(self):
+        super().remove_from_graph()
+        self.scale.remove_constraint(self)
+        self.offset.remove_constraint(self)
+
+    def mark_inputs(self, mark):
+        super().mark_inputs(mark)
+        self.scale.mark = mark
+        self.offset.mark = mark
+
+    def execute(self):
+        if self.direction == FORWARD:
+            self.v2.value = self.v1.value * self.scale.value + self.offset.value
+        else:
+            self.v1.value = (self.v2.value - self.offset.value) // self.scale.value
+
+    def recalculate(self):
+        ihn = self.input()
+        out = self.output()
+        out.walk_strength = weakest_of(self.strength, ihn.walk_strength)
+        out.stay = ihn.stay and self.scale.stay and self.offset.stay
+        if out.stay:
+            self.execute()
+
+
+class EqualityConstraint(BinaryConstraint):
+    def execute(self):
+        self.output().value = self.input().value
+
+
+class Variable(object):
+    def __init__(self, name, initial_value=0):
+        self.value = initial_value
+        self.constraints = []
+        self.determined_by = None
+        self.mark = 0
+        self.walk_strength = WEAKEST
+        self.stay = True
+        self.name = name
+
+    def add_constraint(self, constraint):
+        self.constraints.append(constraint)
+
+    def remove_constraint(self, constraint):
+        self.constraints.remove(constraint)
+        if self.determined_by is constraint:
+            self.determined_by = None
+
+
+class Planner(object):
+    def __init__(self):
+        self.current_mark = 0
+
+    def incremental_add(self, constraint):
+        mark = self.new_mark()
+        overridden = constraint.satisfy(mark)
+        while overridden is not None:
+            overridden = overridden.satisfy(mark)
+
+    def incremental_remove(self, constraint):
+        out = constraint.output()
+        constraint.mark_unsatisfied()
+        constraint.remove_from_graph()
+        unsatisfied = self.remove_propagate_from(out)
+        strength = REQUIRED
+        while strength != WEAKEST:
+            for u in unsatisfied:
+                if u.strength == strength:
+                    self.incremental_add(u)
+            strength += 1
+
+    def new_mark(self):
+        self.current_mark += 1
+        return self.current_mark
+
+    def make_plan(self, sources):
+        mark = self.new_mark()
+        plan = []
+        todo = sources
+        while todo:
+            constraint = todo.pop(0)
+            if constraint.output().mark != mark and constraint.inputs_known(mark):
+                plan.append(constraint)
+                constraint.output().mark = mark
+                self.add_constraints_consuming_to(constraint.output(), todo)
+        return plan
+
+    def extract_plan_from_constraints(self, constraints):
+        sources = [c for c in constraints if c.is_input() and c.is_satisfied()]
+        return self.make_plan(sources)
+
+    def add_propagate(self, constraint, mark):
+        todo = [constraint]
+        while todo:
+            d = todo.pop(0)
+            if d.output().mark == mark:
+                self.incremental_remove(constraint)
+                return False
+            d.recalculate()
+            self.add_constraints_consuming_to(d.output(), todo)
+        return True
+
+    def remove_propagate_from(self, out):
+        out.determined_by = None
+        out.walk_strength = WEAKEST
+        out.stay = True
+        unsatisfied = []
+        todo = [out]
+        while todo:
+            v = todo.pop(0)
+            for constraint in v.constraints:
+                if not constraint.is_satisfied():
+                    unsatisfied.append(constraint)
+            determining = v.determined_by
+            for constraint in v.constraints:
+                if constraint is not determining and constraint.is_satisfied():
+                    constraint.recalculate()
+                    todo.append(constraint.output())
+        return unsatisfied
+
+    def add_constraints_consuming_to(self, v, todo):
+        determining = v.determined_by
+        for constraint in v.constraints:
+            if constraint is not determining and constraint.is_satisfied():
+                todo.append(constraint)
+
+
+def execute(plan):
+    for constraint in plan:
+        constraint.execute()
+
+
+def chain_test(n):
+    """A chain of equality constraints, with an edit at the first variable
+    and a stay at the last."""
+    global planner
+    planner = Planner()
+    prev = first = last = None
+    for i in range(n + 1):
+        v = Variable("v%d" % i)
+        if prev is not None:
+            EqualityConstraint(prev, v, REQUIRED)
+        if i == 0:
+            first = v
+        if i == n:
+            last = v
+        prev = v
+    StayConstraint(last, STRONG_DEFAULT)
+    edit = EditConstraint(first, PREFERRED)
+    plan = planner.extract_plan_from_constraints([edit])
+    for i in range(100):
+        first.value = i
+        execute(plan)
+        assert last.value == i, "chain test failed"
+
+
+def projection_test(n):
+    """Pairs of variables related by a scale and an offset, changed from
+    either end."""
+    global planner
+    planner = Planner()
+    scale = Variable("scale", 10)
+    offset = Variable("offset", 1000)
+    src = dst = None
+    dests = []
+    for i in range(n):
+        src = Variable("src%d" % i, i)
+        dst = Variable("dst%d" % i, i)
+        dests.append(dst)
+        StayConstraint(src, NORMAL)
+        ScaleConstraint(src, scale, offset, dst, REQUIRED)
+    change(src, 17)
+    assert dst.value == 1170, "projection 1 failed"
+    change(dst, 1050)
+    assert src.value == 5, "projection 2 failed"
+    change(scale, 5)
+    for i in range(n - 1):
+        assert dests[i].value == i * 5 + 1000, "projection 3 failed"
+    change(offset, 2000)
+    for i in range(n - 1):
+        assert dests[i].value == i * 5 + 2000, "projection 4 failed"
+
+
+def change(v, new_value):
+    edit = EditConstraint(v, PREFERRED)
+    plan = planner.extract_plan_from_constraints([edit])
+    for _ in range(10):
+        v.value = new_value
+        execute(plan)
+    edit.destroy_constraint()
+
+
+planner = None
+
+
+def run():
+    chain_test(100)
+    projection_test(100)
@@ -0,0 +1,11 @@
+"""Recursive Fibonacci: function calls and small-int arithmetic."""
+
+
+def fib(n):
+    if n < 2:
+        return n
+    return fib(n - 1) + fib(n - 2)
+
+
+def run():
+    assert fib(20) == 6765
@@ -0,0 +1,31 @@
+"""The spectral norm of an infinite matrix, from the Computer Language
+Benchmarks Game: float arithmetic in nested loops."""
+
+N = 40
+
+
+def a(i, j):
+    return 1.0 / ((i + j) * (i + j + 1) // 2 + i + 1)
+
+
+def times(u):
+    return [sum(a(i, j) * u_j for j, u_j in enumerate(u)) for i in range(len(u))]
+
+
+def times_transposed(u):
+    return [sum(a(j, i) * u_j for j, u_j in enumerate(u)) for i in range(len(u))]
+
+
+def times_both(u):
+    return times_transposed(times(u))
+
+
+def run():
+    u = [1.0] * N
+    for _ in range(10):
+        v = times_both(u)
+        u = times_both(v)
+    vbv = sum(u_i * v_i for u_i, v_i in zip(u, v))
+    vv = sum(v_i * v_i for v_i in v)
+    norm = (vbv / vv) ** 0.5
+    assert abs(norm - 1.2742) < 1e-3, norm
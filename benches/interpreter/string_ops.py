@@ -0,0 +1,26 @@
+"""Building, splitting, searching and formatting strings."""
+
+WORDS = "the quick brown fox jumps over the lazy dog".split()
+
+
+def run():
+    lines = []
+    for i in range(500):
+        word = WORDS[i % len(WORDS)]
+        lines.append("%d:%s:%s" % (i, word.upper(), word[::-1]))
+    text = "\n".join(lines)
+    total = 0
+    for line in text.split("\n"):
+        number, upper, backwards = line.split(":")
+        total += int(number) + len(upper)
+        if "O" in upper:
+            total += upper.find("O")
+        total += len(backwards.replace("o", "00"))
+    words = {}
+    for word in text.lower().replace(":", " ").split():
+        words[word] = words.get(word, 0) + 1
+    summary = ", ".join(
+        "{}={}".format(word, count) for word, count in sorted(words.items())
+    )
+    assert total > 0 and summary.startswith("0=1")
+    return total, len(summary)
@@ -0,0 +1,63 @@
+//! Numeric kernels with the JIT tier on and off.
+//!
+//! Each kernel is defined once and warmed up past the point at which it
+//! is compiled, so that the timings compare compiled code with the
+//! interpreter rather than include the compilation.
+
+#[macro_use]
+extern crate criterion;
+extern crate rustpy;
+
+use criterion::{black_box, Criterion};
+use rustpy::vm::Vm;
+
+const KERNELS: &str = "
+def fib(n):
+    if n < 2:
+        return n
+    return fib(n - 1) + fib(n - 2)
+
+def kernel(n):
+    total = 0.0
+    for i in range(n):
+        if i % 3 == 0 or 0 < i < 5:
+            total += i * 0.5
+        else:
+            total -= 1
+    return total
+
+def gcd(a, b):
+    while b:
+        a, b = b, a % b
+    return a
+
+for i in range(1000):
+    fib(2)
+    kernel(2)
+    gcd(i, 7)
+";
+
+const CALLS: [(&str, &str); 3] = [
+    ("fib(20)", "fib(20)"),
+    ("kernel(10000)", "kernel(10000)"),
+    // Consecutive Fibonacci numbers take the most steps.
+    ("gcd", "gcd(4660046610375530309, 7540113804746346429)"),
+];
+
+fn kernels(c: &mut Criterion) {
+    for &enabled in &[true, false] {
+        let mut vm = Vm::new();
+        vm.set_jit_enabled(enabled);
+        vm.run_source(KERNELS, "kernels.py").unwrap();
+        let mut group = c.benchmark_group(if enabled { "jit" } else { "interpreter" });
+        for &(name, source) in &CALLS {
+            group.bench_function(name, |b| {
+                b.iter(|| vm.run_source(black_box(source), "<bench>").unwrap())
+            });
+        }
+        group.finish();
+    }
+}
+
+criterion_group!(benches, kernels);
+criterion_main!(benches);
@@ -0,0 +1,100 @@
+//! Tokenizer throughput on real-world source.
+//!
+//! `data/typing.py` is a vendored copy of the CPython 3.11 standard library
+//! module (PSF license). Throughput is reported both in tokens and in bytes;
+//! the allocations made per run are printed before the timings.
+
+#[macro_use]
+extern crate criterion;
+extern crate rustpy;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{black_box, Criterion, Throughput};
+use rustpy::format;
+use rustpy::tokenizer::{self, Tokenizer, TokenizerConfig};
+
+const TYPING: &str = include_str!("data/typing.py");
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED_BYTES: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED_BYTES.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn report_allocations(name: &str, run: &dyn Fn()) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = ALLOCATED_BYTES.load(Ordering::Relaxed);
+    run();
+    println!(
+        "{}: {} allocations, {} bytes allocated",
+        name,
+        ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        ALLOCATED_BYTES.load(Ordering::Relaxed) - bytes
+    );
+}
+
+fn tokenize(c: &mut Criterion) {
+    let tokens = tokenizer::tokenize(TYPING).unwrap().len() as u64;
+    report_allocations("tokenize typing.py", &|| {
+        black_box(tokenizer::tokenize(TYPING).unwrap());
+    });
+
+    let mut group = c.benchmark_group("tokens");
+    group.throughput(Throughput::Elements(tokens));
+    group.bench_function("tokenize typing.py", |b| {
+        b.iter(|| tokenizer::tokenize(black_box(TYPING)).unwrap())
+    });
+    group.bench_function("iterate typing.py", |b| {
+        b.iter(|| Tokenizer::new(black_box(TYPING)).count())
+    });
+    group.bench_function("interactive typing.py", |b| {
+        let config = TokenizerConfig {
+            interactive: true,
+            ..TokenizerConfig::default()
+        };
+        b.iter(|| Tokenizer::with_config(black_box(TYPING), config.clone()).count())
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("bytes");
+    group.throughput(Throughput::Bytes(TYPING.len() as u64));
+    group.bench_function("tokenize typing.py", |b| {
+        b.iter(|| tokenizer::tokenize(black_box(TYPING)).unwrap())
+    });
+    group.bench_function("decode typing.py", |b| {
+        b.iter(|| tokenizer::decode(black_box(TYPING.as_bytes())).unwrap())
+    });
+    group.finish();
+}
+
+fn format(c: &mut Criterion) {
+    report_allocations("format typing.py", &|| {
+        black_box(format::format(TYPING).unwrap());
+    });
+
+    let mut group = c.benchmark_group("bytes");
+    group.throughput(Throughput::Bytes(TYPING.len() as u64));
+    group.bench_function("format typing.py", |b| {
+        b.iter(|| format::format(black_box(TYPING)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, tokenize, format);
+criterion_main!(benches);
@@ -0,0 +1,24 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustpy::tokenizer::{self, TokenizerConfig, Tokenizer};
+
+// Any string must tokenize to tokens or an error, never a panic, in both the
+// batch and interactive modes.
+// `tests/fuzz_regressions.rs` replays the regressions through a copy of
+// this body; change both together.
+fuzz_target!(|source: &str| {
+    if let Ok(tokens) = tokenizer::tokenize(source) {
+        let text = Tokenizer::new(source).source();
+        for token in &tokens {
+            assert_eq!(&text[token.span.start..token.span.end], token.value);
+        }
+    }
+    let config = TokenizerConfig {
+        interactive: true,
+        ..TokenizerConfig::default()
+    };
+    for _ in Tokenizer::with_config(source, config) {}
+    let _ = tokenizer::input_status(source);
+    let _ = tokenizer::shebang(source);
+});
@@ -0,0 +1,15 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rustpy::tokenizer;
+
+// Raw file contents go through encoding detection and decoding first, the way
+// `tokenize_file` reads them.
+// `tests/fuzz_regressions.rs` replays the regressions through a copy of
+// this body; change both together.
+fuzz_target!(|bytes: &[u8]| {
+    let _ = tokenizer::detect_encoding(bytes);
+    if let Ok(decoded) = tokenizer::decode(bytes) {
+        let _ = tokenizer::tokenize(&decoded.text);
+    }
+});
@@ -0,0 +1,59 @@
+/*
+ * C interface to the rustpy tokenizer and parser.
+ *
+ * Build the shared library with
+ *
+ *     cargo rustc --release --features capi --lib --crate-type cdylib
+ *
+ * and link against target/release/librustpy.so (or .dylib / .dll).
+ */
+#ifndef RUSTPY_H
+#define RUSTPY_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* The source was processed and `json` holds the tokens or the tree. */
+#define RUSTPY_OK 0
+/* The source could not be decoded, tokenized or parsed; `json` holds an
+ * error object. */
+#define RUSTPY_SYNTAX_ERROR 1
+/* The source pointer was null with a non-zero length. */
+#define RUSTPY_INVALID_ARGUMENT 2
+/* rustpy panicked. This is a bug; `json` holds an error object. */
+#define RUSTPY_INTERNAL_ERROR 3
+
+/* The version of the JSON documents this header describes. */
+#define RUSTPY_SCHEMA_VERSION 3
+
+/*
+ * The outcome of a call. `json` is a NUL-terminated UTF-8 string of `len`
+ * bytes, not counting the NUL: an object whose members are
+ * `schema_version`, `kind` ("tokens", "module" or "error") and `data`, in
+ * that order. On failure `data` is an object with `type`, `code`,
+ * `message`, `line` and `column`; fields that do not apply are null.
+ */
+typedef struct RustpyResult {
+    int status;
+    char *json;
+    size_t len;
+} RustpyResult;
+
+/* Decodes and tokenizes `len` bytes of source into a JSON array of tokens. */
+RustpyResult *tokenize_to_json(const uint8_t *source, size_t len);
+
+/* Decodes and parses `len` bytes of source into a JSON syntax tree. */
+RustpyResult *parse_to_json(const uint8_t *source, size_t len);
+
+/* Releases a result. Passing NULL does nothing. */
+void free_result(RustpyResult *result);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* RUSTPY_H */
@@ -0,0 +1,96 @@
+"""Compares rustpy with CPython on every Python file under the given paths.
+
+    python compare.py [--tokens] PATH...
+
+Each file is parsed by both `rustpy.parse` and `ast.parse`, and the files
+whose `ast.dump`, including locations, differ are listed with the first
+line that differs. Files CPython rejects must be rejected by rustpy too.
+With `--tokens`, the significant tokens of `tokenize` are compared instead.
+"""
+
+import ast
+import io
+import os
+import sys
+import tokenize
+
+import rustpy
+
+# Tokens rustpy does not produce.
+SKIPPED_TOKENS = {tokenize.ENCODING}
+
+
+def python_files(paths):
+    for path in paths:
+        if os.path.isfile(path):
+            yield path
+            continue
+        for root, _, files in os.walk(path):
+            for name in sorted(files):
+                if name.endswith(".py"):
+                    yield os.path.join(root, name)
+
+
+def dump_tree(parse, source):
+    try:
+        return ast.dump(parse(source), include_attributes=True, indent=1)
+    except (SyntaxError, ValueError) as err:
+        return "%s: %s" % (type(err).__name__, err.msg if hasattr(err, "msg") else err)
+
+
+def dump_tokens(source, tokens):
+    lines = []
+    for token in tokens:
+        if token.type in SKIPPED_TOKENS:
+            continue
+        # CPython gives some NEWLINE and DEDENT tokens empty or differing
+        # text, so only their positions are compared.
+        string = token.string if token.type == tokenize.OP or token.string.strip() else ""
+        lines.append("%s %r %s %s" % (tokenize.tok_name[token.exact_type], string, token.start, token.end))
+    return "\n".join(lines)
+
+
+def cpython_tokens(source):
+    try:
+        return dump_tokens(source, tokenize.tokenize(io.BytesIO(source).readline))
+    except (SyntaxError, tokenize.TokenError) as err:
+        return "error: %s" % err
+
+
+def rustpy_tokens(source):
+    try:
+        return dump_tokens(source, rustpy.tokenize(source))
+    except SyntaxError as err:
+        return "error: %s" % err
+
+
+def first_difference(expected, actual):
+    for index, (a, b) in enumerate(zip(expected.splitlines(), actual.splitlines())):
+        if a != b:
+            return "line %d:\n  cpython: %s\n  rustpy:  %s" % (index + 1, a.strip(), b.strip())
+    return "lengths differ"
+
+
+def main(argv):
+    tokens = "--tokens" in argv
+    paths = [arg for arg in argv if arg != "--tokens"]
+    checked = differing = 0
+    for path in python_files(paths):
+        with open(path, "rb") as f:
+            source = f.read()
+        if tokens:
+            expected, actual = cpython_tokens(source), rustpy_tokens(source)
+        else:
+            expected, actual = dump_tree(ast.parse, source), dump_tree(rustpy.parse, source)
+            if expected.startswith("SyntaxError") and actual.startswith("SyntaxError"):
+                expected = actual
+        checked += 1
+        if expected != actual:
+            differing += 1
+            print("%s: %s" % (path, first_difference(expected, actual)))
+    print("%d of %d files differ" % (differing, checked))
+    return 1 if differing else 0
+
+
+if __name__ == "__main__":
+    sys.exit(main(sys.argv[1:]))
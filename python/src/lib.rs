@@ -0,0 +1,338 @@
+//! The `rustpy` Python extension module: the rustpy tokenizer, parser and
+//! formatter, callable from CPython.
+//!
+//! `parse` returns the same `ast` node classes CPython's own `ast.parse`
+//! does, with the same locations, so `ast.dump` of either tree can be
+//! compared directly. `tokenize` returns `tokenize.TokenInfo` tuples.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyBytes, PyDict, PyList, PyString, PyTuple};
+
+use rustpy::capi::to_json;
+use rustpy::format::{format_with_config, FormatConfig, QuoteStyle};
+use rustpy::parser::{parse as parse_module, ParseError};
+use rustpy::tokenizer::{decode, tokenize as tokenize_source, SourceError, TokenError};
+
+/// A decoding, tokenizing or parsing failure, raised in Python as the
+/// exception CPython would raise for the same source.
+struct Failure {
+    exception: &'static str,
+    message: String,
+    location: Option<(usize, usize)>,
+}
+
+impl From<TokenError> for Failure {
+    fn from(err: TokenError) -> Failure {
+        Failure {
+            exception: err.kind.exception_name(),
+            message: err.message,
+            location: Some((err.location.line, err.location.column)),
+        }
+    }
+}
+
+impl From<ParseError> for Failure {
+    fn from(err: ParseError) -> Failure {
+        Failure {
+            exception: err.kind.exception_name(),
+            message: err.message,
+            location: Some((err.location.line, err.location.column)),
+        }
+    }
+}
+
+impl From<SourceError> for Failure {
+    fn from(err: SourceError) -> Failure {
+        match err {
+            SourceError::Token(err) => Failure::from(err),
+            SourceError::Decode { line, .. } => Failure {
+                exception: "SyntaxError",
+                message: err.to_string(),
+                location: Some((line, 0)),
+            },
+            _ => Failure {
+                exception: "SyntaxError",
+                message: err.to_string(),
+                location: None,
+            },
+        }
+    }
+}
+
+impl Failure {
+    /// The exception, with CPython's `(filename, lineno, offset, text)`
+    /// details. `offset` is 1-based.
+    fn into_err(self, py: Python, filename: &str, text: &str) -> PyErr {
+        let class = match py
+            .import("builtins")
+            .and_then(|b| b.getattr(self.exception))
+        {
+            Ok(class) => class,
+            Err(err) => return err,
+        };
+        let args = match self.location {
+            Some((line, column)) => {
+                let source_line = text.split('\n').nth(line.wrapping_sub(1)).unwrap_or("");
+                let details = (filename, line, column + 1, format!("{}\n", source_line));
+                class.call1((self.message, details))
+            }
+            None => class.call1((self.message,)),
+        };
+        match args {
+            Ok(value) => PyErr::from_value(value),
+            Err(err) => err,
+        }
+    }
+}
+
+/// The text of `source`, which may be `str` or `bytes`. Bytes are decoded
+/// using their coding cookie or BOM, as the interpreter does.
+fn source_text(py: Python, source: &Bound<PyAny>, filename: &str) -> PyResult<String> {
+    if let Ok(text) = source.cast::<PyString>() {
+        return Ok(text.to_str()?.to_string());
+    }
+    if let Ok(bytes) = source.cast::<PyBytes>() {
+        return decode(bytes.as_bytes())
+            .map(|decoded| decoded.text)
+            .map_err(|err| Failure::from(err).into_err(py, filename, ""));
+    }
+    Err(PyValueError::new_err("source must be str or bytes"))
+}
+
+/// Tokenizes `source` into a list of `tokenize.TokenInfo`.
+///
+/// Columns count characters, as in the `tokenize` module. Unlike it, there
+/// are no `ENCODING` or `ERRORTOKEN` tokens.
+#[pyfunction]
+#[pyo3(signature = (source, filename = "<unknown>"))]
+fn tokenize<'py>(
+    py: Python<'py>,
+    source: &Bound<'py, PyAny>,
+    filename: &str,
+) -> PyResult<Bound<'py, PyList>> {
+    let text = source_text(py, source, filename)?;
+    let tokens = py
+        .detach(|| tokenize_source(&text))
+        .map_err(|err| Failure::from(err).into_err(py, filename, &text))?;
+
+    let token_module = py.import("token")?;
+    let token_info = py.import("tokenize")?.getattr("TokenInfo")?;
+    let lines: Vec<&str> = text.split_inclusive('\n').collect();
+    let result = PyList::empty(py);
+    for token in tokens {
+        let kind = token_module.getattr(token.kind.name())?;
+        let line = lines
+            .get(token.start.line.wrapping_sub(1))
+            .map_or("", |l| l);
+        let start = (token.start.line, token.start.column);
+        let end = (token.end.line, token.end.column);
+        result.append(token_info.call1((kind, token.value, start, end, line))?)?;
+    }
+    Ok(result)
+}
+
+/// Parses `source` as a module into a tree of `ast` nodes, like
+/// `ast.parse(source, filename)`.
+#[pyfunction]
+#[pyo3(signature = (source, filename = "<unknown>"))]
+fn parse<'py>(
+    py: Python<'py>,
+    source: &Bound<'py, PyAny>,
+    filename: &str,
+) -> PyResult<Bound<'py, PyAny>> {
+    let text = source_text(py, source, filename)?;
+    let json = py
+        .detach(|| parse_module(&text).map(|module| to_json(&module)))
+        .map_err(|err| Failure::from(err).into_err(py, filename, &text))?;
+    let tree = py.import("json")?.call_method1("loads", (json,))?;
+    let builder = Builder {
+        ast: py.import("ast")?,
+        lines: text.split('\n').collect(),
+    };
+    builder.value(&tree, None)
+}
+
+/// Formats `source` with the options of `format::FormatConfig`.
+/// `quote_style` is `"double"`, `"single"` or `"preserve"`.
+#[pyfunction]
+#[pyo3(signature = (
+    source,
+    *,
+    line_length = 88,
+    quote_style = "double",
+    normalize_string_prefixes = true,
+    magic_trailing_comma = true,
+))]
+fn format(
+    py: Python,
+    source: &str,
+    line_length: usize,
+    quote_style: &str,
+    normalize_string_prefixes: bool,
+    magic_trailing_comma: bool,
+) -> PyResult<String> {
+    let quote_style = match quote_style {
+        "double" => QuoteStyle::Double,
+        "single" => QuoteStyle::Single,
+        "preserve" => QuoteStyle::Preserve,
+        _ => {
+            let message = format!("unknown quote style {:?}", quote_style);
+            return Err(PyValueError::new_err(message));
+        }
+    };
+    let config = FormatConfig {
+        line_length,
+        quote_style,
+        normalize_string_prefixes,
+        magic_trailing_comma,
+    };
+    py.detach(|| format_with_config(source, &config))
+        .map_err(|err| Failure::from(err).into_err(py, "<unknown>", source))
+}
+
+/// Turns the JSON tree of `capi::to_json`, as loaded by Python's `json`
+/// module, into `ast` nodes.
+struct Builder<'py, 'a> {
+    ast: Bound<'py, PyModule>,
+    lines: Vec<&'a str>,
+}
+
+impl<'py, 'a> Builder<'py, 'a> {
+    /// Converts a loaded JSON value. `field` is the name of the node field
+    /// it is the value of, which tells operators apart from plain strings.
+    fn value(&self, value: &Bound<'py, PyAny>, field: Option<&str>) -> PyResult<Bound<'py, PyAny>> {
+        if let Ok(object) = value.cast::<PyDict>() {
+            return self.node(object);
+        }
+        if let Ok(list) = value.cast::<PyList>() {
+            let items = list
+                .iter()
+                .map(|item| self.value(&item, field))
+                .collect::<PyResult<Vec<_>>>()?;
+            return Ok(PyList::new(value.py(), items)?.into_any());
+        }
+        match field {
+            Some("op") | Some("ops") | Some("ctx") if value.is_instance_of::<PyString>() => self
+                .ast
+                .getattr(value.cast::<PyString>()?.to_str()?)?
+                .call0(),
+            _ => Ok(value.clone()),
+        }
+    }
+
+    fn node(&self, object: &Bound<'py, PyDict>) -> PyResult<Bound<'py, PyAny>> {
+        let py = object.py();
+        let kind: String = item(object, "_type")?.extract()?;
+        let kwargs = PyDict::new(py);
+        for (key, value) in object.iter() {
+            let key: String = key.extract()?;
+            match key.as_str() {
+                "_type" => {}
+                "start" => {
+                    let (line, column) = self.location(&value)?;
+                    kwargs.set_item("lineno", line)?;
+                    kwargs.set_item("col_offset", column)?;
+                }
+                "end" => {
+                    let (line, column) = self.location(&value)?;
+                    kwargs.set_item("end_lineno", line)?;
+                    kwargs.set_item("end_col_offset", column)?;
+                }
+                "kind" if kind == "Constant" => {}
+                "value" if kind == "Constant" => {
+                    let constant = constant(&item(object, "kind")?, &value)?;
+                    kwargs.set_item("value", constant)?;
+                }
+                // CPython stores the conversion character as its code point.
+                "conversion" => {
+                    let conversion = if value.is_none() {
+                        -1
+                    } else {
+                        let conversion: char = value.extract()?;
+                        conversion as i64
+                    };
+                    kwargs.set_item("conversion", conversion)?;
+                }
+                // CPython stores these flags as ints.
+                "is_async" | "simple" => {
+                    let flag: bool = value.extract()?;
+                    kwargs.set_item(&key, i64::from(flag))?;
+                }
+                _ => kwargs.set_item(&key, self.value(&value, Some(&key))?)?,
+            }
+        }
+        // rustpy does not keep `# type: ignore` comments.
+        if kind == "Module" {
+            kwargs.set_item("type_ignores", PyList::empty(py))?;
+        }
+        self.ast.getattr(kind.as_str())?.call((), Some(&kwargs))
+    }
+
+    /// A line and column in characters, as the line and UTF-8 byte offset
+    /// CPython uses.
+    fn location(&self, value: &Bound<'py, PyAny>) -> PyResult<(usize, usize)> {
+        let object = value.cast::<PyDict>()?;
+        let line: usize = item(object, "line")?.extract()?;
+        let column: usize = item(object, "column")?.extract()?;
+        let text = self.lines.get(line.wrapping_sub(1)).map_or("", |l| l);
+        let offset = text
+            .char_indices()
+            .nth(column)
+            .map_or(text.len(), |(offset, _)| offset);
+        Ok((line, offset))
+    }
+}
+
+fn item<'py>(object: &Bound<'py, PyDict>, key: &str) -> PyResult<Bound<'py, PyAny>> {
+    object
+        .get_item(key)?
+        .ok_or_else(|| PyValueError::new_err(format!("missing {:?} in syntax tree", key)))
+}
+
+/// The Python value of a constant from its JSON `kind` and `value`.
+fn constant<'py>(
+    kind: &Bound<'py, PyAny>,
+    value: &Bound<'py, PyAny>,
+) -> PyResult<Bound<'py, PyAny>> {
+    let py = value.py();
+    let builtins = py.import("builtins")?;
+    let kind: String = kind.extract()?;
+    match kind.as_str() {
+        // Infinities and NaN arrive as strings, which `float` also accepts.
+        "float" => builtins.getattr("float")?.call1((value,)),
+        "complex" => {
+            let object = value.cast::<PyDict>()?;
+            let float = builtins.getattr("float")?;
+            let real = float.call1((item(object, "real")?,))?;
+            let imag = float.call1((item(object, "imag")?,))?;
+            builtins.getattr("complex")?.call1((real, imag))
+        }
+        "bytes" => {
+            let bytes: Vec<u8> = value.extract()?;
+            Ok(PyBytes::new(py, &bytes).into_any())
+        }
+        "Ellipsis" => builtins.getattr("Ellipsis"),
+        "tuple" => {
+            let elts = value
+                .cast::<PyList>()?
+                .iter()
+                .map(|elt| {
+                    let object = elt.cast::<PyDict>()?;
+                    constant(&item(object, "kind")?, &item(object, "value")?)
+                })
+                .collect::<PyResult<Vec<_>>>()?;
+            Ok(PyTuple::new(py, elts)?.into_any())
+        }
+        _ => Ok(value.clone()),
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "rustpy")]
+fn rustpy_module(module: &Bound<PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(tokenize, module)?)?;
+    module.add_function(wrap_pyfunction!(parse, module)?)?;
+    module.add_function(wrap_pyfunction!(format, module)?)?;
+    Ok(())
+}
@@ -0,0 +1,97 @@
+"""Writes the table of character names behind `\\N{...}` escapes.
+
+    python3.11 unicode_names.py [NAME_ALIASES]
+
+The names are those of `unicodedata` in the Python running the script,
+which must be the release whose Unicode version rustpy follows. The aliases
+that `\\N{...}` also accepts are read from `NAME_ALIASES`, the Unicode
+database's `NameAliases.txt`, if it is given. The names of Hangul
+syllables and CJK unified ideographs are made from their code points, so
+they are left out.
+
+The table, `src/parser/unicode_names.bin`, has the words of the names,
+those used most first, and then each name as the indexes of its words:
+
+    u16 word count, then each word as a byte of length and its bytes
+    u32 name count, then each name, in code point order, as
+        the code point less the one before it, in 7-bit groups with the
+        high bit set on all but the last
+        a byte of word count
+        each word index as a byte if below 0x80, or else two bytes, the
+        first with the high bit set
+"""
+
+import os
+import struct
+import sys
+import unicodedata
+from collections import Counter
+
+OUTPUT = os.path.join(os.path.dirname(__file__), "..", "src", "parser", "unicode_names.bin")
+
+
+def computed(name):
+    return name.startswith(("CJK UNIFIED IDEOGRAPH-", "HANGUL SYLLABLE "))
+
+
+def names():
+    for code in range(0x110000):
+        name = unicodedata.name(chr(code), None)
+        if name is not None and not computed(name):
+            yield code, name
+
+
+def aliases(path):
+    with open(path, encoding="utf-8") as lines:
+        for line in lines:
+            line = line.split("#")[0].strip()
+            if not line:
+                continue
+            code, alias, _ = line.split(";")
+            code = int(code, 16)
+            assert unicodedata.lookup(alias) == chr(code), alias
+            yield code, alias
+
+
+def varint(value):
+    out = bytearray()
+    while value >= 0x80:
+        out.append(value & 0x7F | 0x80)
+        value >>= 7
+    out.append(value)
+    return out
+
+
+def main():
+    entries = sorted(names())
+    if len(sys.argv) > 1:
+        entries = sorted(entries + list(aliases(sys.argv[1])))
+    counts = Counter(word for _, name in entries for word in name.split(" "))
+    words = [word for word, _ in counts.most_common()]
+    index = {word: position for position, word in enumerate(words)}
+    assert len(words) < 0x8000
+
+    out = bytearray(struct.pack("<H", len(words)))
+    for word in words:
+        out.append(len(word))
+        out += word.encode("ascii")
+    out += struct.pack("<I", len(entries))
+    previous = 0
+    for code, name in entries:
+        out += varint(code - previous)
+        previous = code
+        parts = name.split(" ")
+        out.append(len(parts))
+        for part in parts:
+            position = index[part]
+            if position < 0x80:
+                out.append(position)
+            else:
+                out += bytes([0x80 | position >> 8, position & 0xFF])
+    with open(OUTPUT, "wb") as table:
+        table.write(out)
+    print("%d names, %d words, %d bytes" % (len(entries), len(words), len(out)))
+
+
+if __name__ == "__main__":
+    main()
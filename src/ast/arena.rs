@@ -0,0 +1,1450 @@
+//! The syntax tree stored in one vector, each node referring to its
+//! children by `NodeId`, for analysis passes that visit every node with a
+//! loop rather than a walk, and keep what they learn about each node in a
+//! vector indexed by its id rather than a map keyed by its address.
+//!
+//! `Arena::new` takes a parsed `Module` apart into the arena, numbering
+//! the nodes in preorder with the children of each in the order of their
+//! fields, so the nodes under a node are the run of ids after it.
+//! `Arena::to_module` puts the boxed tree back together for the compiler
+//! and everything else that reads one. Comprehensions, `with` items, match
+//! cases and parameter lists, which have no location, are not nodes but
+//! parts of the node they are in, as operators and contexts are.
+//!
+//! The parser does not allocate into an arena: it builds the boxed tree,
+//! which `Arena::new` then moves into the vector, so an arena costs
+//! allocations on top of the parse. What it gives is ids, not speed.
+
+use std::iter;
+use std::ops::Index;
+
+use tokenizer::Location;
+
+use super::{
+    Alias, Arg, BoolOperator, CmpOperator, Constant, Context, ExceptHandler, Expr, ExprKind,
+    Keyword, Module, Operator, Pattern, PatternKind, Stmt, StmtKind, TypeIgnore, TypeParam,
+    TypeParamKind, UnaryOperator,
+};
+
+/// The id of a node in an `Arena`, from 0 in preorder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    /// The position of the node in the arena, for indexing a vector of
+    /// facts about each node.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A node of an `Arena`: a statement, expression, pattern, exception
+/// handler, parameter, keyword argument, alias or type parameter.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub start: Location,
+    pub end: Location,
+    parent: Option<NodeId>,
+    /// The id after the last node of this node's subtree.
+    subtree_end: u32,
+}
+
+/// What a node is, with the fields of the tree's node of that kind, its
+/// children given by id.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeKind {
+    FunctionDef {
+        name: String,
+        args: Box<Arguments>,
+        body: Vec<NodeId>,
+        decorator_list: Vec<NodeId>,
+        returns: Option<NodeId>,
+        type_comment: Option<String>,
+        type_params: Vec<NodeId>,
+        is_async: bool,
+    },
+    ClassDef {
+        name: String,
+        bases: Vec<NodeId>,
+        keywords: Vec<NodeId>,
+        body: Vec<NodeId>,
+        decorator_list: Vec<NodeId>,
+        type_params: Vec<NodeId>,
+    },
+    Return(Option<NodeId>),
+    Delete(Vec<NodeId>),
+    Assign {
+        targets: Vec<NodeId>,
+        value: NodeId,
+        type_comment: Option<String>,
+    },
+    TypeAlias {
+        name: NodeId,
+        type_params: Vec<NodeId>,
+        value: NodeId,
+    },
+    AugAssign {
+        target: NodeId,
+        op: Operator,
+        value: NodeId,
+    },
+    AnnAssign {
+        target: NodeId,
+        annotation: NodeId,
+        value: Option<NodeId>,
+        simple: bool,
+    },
+    For {
+        target: NodeId,
+        iter: NodeId,
+        body: Vec<NodeId>,
+        orelse: Vec<NodeId>,
+        type_comment: Option<String>,
+        is_async: bool,
+    },
+    While {
+        test: NodeId,
+        body: Vec<NodeId>,
+        orelse: Vec<NodeId>,
+    },
+    If {
+        test: NodeId,
+        body: Vec<NodeId>,
+        orelse: Vec<NodeId>,
+    },
+    With {
+        items: Vec<WithItem>,
+        body: Vec<NodeId>,
+        type_comment: Option<String>,
+        is_async: bool,
+    },
+    Match {
+        subject: NodeId,
+        cases: Vec<MatchCase>,
+    },
+    Raise {
+        exc: Option<NodeId>,
+        cause: Option<NodeId>,
+    },
+    Try {
+        body: Vec<NodeId>,
+        handlers: Vec<NodeId>,
+        orelse: Vec<NodeId>,
+        finalbody: Vec<NodeId>,
+        is_star: bool,
+    },
+    Assert {
+        test: NodeId,
+        msg: Option<NodeId>,
+    },
+    Import(Vec<NodeId>),
+    ImportFrom {
+        module: Option<String>,
+        names: Vec<NodeId>,
+        level: usize,
+    },
+    Global(Vec<String>),
+    Nonlocal(Vec<String>),
+    Expr(NodeId),
+    Pass,
+    Break,
+    Continue,
+    Error,
+    BoolOp {
+        op: BoolOperator,
+        values: Vec<NodeId>,
+    },
+    NamedExpr {
+        target: NodeId,
+        value: NodeId,
+    },
+    BinOp {
+        left: NodeId,
+        op: Operator,
+        right: NodeId,
+    },
+    UnaryOp {
+        op: UnaryOperator,
+        operand: NodeId,
+    },
+    Lambda {
+        args: Box<Arguments>,
+        body: NodeId,
+    },
+    IfExp {
+        test: NodeId,
+        body: NodeId,
+        orelse: NodeId,
+    },
+    Dict {
+        keys: Vec<Option<NodeId>>,
+        values: Vec<NodeId>,
+    },
+    Set(Vec<NodeId>),
+    ListComp {
+        elt: NodeId,
+        generators: Vec<Comprehension>,
+    },
+    SetComp {
+        elt: NodeId,
+        generators: Vec<Comprehension>,
+    },
+    DictComp {
+        key: NodeId,
+        value: NodeId,
+        generators: Vec<Comprehension>,
+    },
+    GeneratorExp {
+        elt: NodeId,
+        generators: Vec<Comprehension>,
+    },
+    Await(NodeId),
+    Yield(Option<NodeId>),
+    YieldFrom(NodeId),
+    Compare {
+        left: NodeId,
+        ops: Vec<CmpOperator>,
+        comparators: Vec<NodeId>,
+    },
+    Call {
+        func: NodeId,
+        args: Vec<NodeId>,
+        keywords: Vec<NodeId>,
+    },
+    FormattedValue {
+        value: NodeId,
+        conversion: Option<char>,
+        format_spec: Option<NodeId>,
+    },
+    JoinedStr(Vec<NodeId>),
+    Constant(Constant),
+    Attribute {
+        value: NodeId,
+        attr: String,
+        ctx: Context,
+    },
+    Subscript {
+        value: NodeId,
+        slice: NodeId,
+        ctx: Context,
+    },
+    Starred {
+        value: NodeId,
+        ctx: Context,
+    },
+    Name {
+        id: String,
+        ctx: Context,
+    },
+    List {
+        elts: Vec<NodeId>,
+        ctx: Context,
+    },
+    Tuple {
+        elts: Vec<NodeId>,
+        ctx: Context,
+    },
+    Slice {
+        lower: Option<NodeId>,
+        upper: Option<NodeId>,
+        step: Option<NodeId>,
+    },
+    MatchValue(NodeId),
+    MatchSingleton(Constant),
+    MatchSequence(Vec<NodeId>),
+    MatchMapping {
+        keys: Vec<NodeId>,
+        patterns: Vec<NodeId>,
+        rest: Option<String>,
+    },
+    MatchClass {
+        cls: NodeId,
+        patterns: Vec<NodeId>,
+        kwd_attrs: Vec<String>,
+        kwd_patterns: Vec<NodeId>,
+    },
+    MatchStar(Option<String>),
+    MatchAs {
+        pattern: Option<NodeId>,
+        name: Option<String>,
+    },
+    MatchOr(Vec<NodeId>),
+    ExceptHandler {
+        type_: Option<NodeId>,
+        name: Option<String>,
+        body: Vec<NodeId>,
+    },
+    Arg {
+        arg: String,
+        annotation: Option<NodeId>,
+        type_comment: Option<String>,
+    },
+    Keyword {
+        arg: Option<String>,
+        value: NodeId,
+    },
+    Alias {
+        name: String,
+        asname: Option<String>,
+    },
+    TypeVar {
+        name: String,
+        bound: Option<NodeId>,
+    },
+    ParamSpec(String),
+    TypeVarTuple(String),
+}
+
+/// The parameters of a function or lambda, each an `Arg` node.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Arguments {
+    pub posonlyargs: Vec<NodeId>,
+    pub args: Vec<NodeId>,
+    pub vararg: Option<NodeId>,
+    pub kwonlyargs: Vec<NodeId>,
+    pub kw_defaults: Vec<Option<NodeId>>,
+    pub kwarg: Option<NodeId>,
+    pub defaults: Vec<NodeId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comprehension {
+    pub target: NodeId,
+    pub iter: NodeId,
+    pub ifs: Vec<NodeId>,
+    pub is_async: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithItem {
+    pub context_expr: NodeId,
+    pub optional_vars: Option<NodeId>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchCase {
+    pub pattern: NodeId,
+    pub guard: Option<NodeId>,
+    pub body: Vec<NodeId>,
+}
+
+/// The nodes of a module, numbered in preorder.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Arena {
+    nodes: Vec<Node>,
+    body: Vec<NodeId>,
+    type_ignores: Vec<TypeIgnore>,
+}
+
+impl Arena {
+    pub fn new(module: Module) -> Arena {
+        let mut builder = Builder { nodes: Vec::new() };
+        let body = builder.stmts(module.body, None);
+        Arena {
+            nodes: builder.nodes,
+            body,
+            type_ignores: module.type_ignores,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The statements of the module.
+    pub fn body(&self) -> &[NodeId] {
+        &self.body
+    }
+
+    pub fn type_ignores(&self) -> &[TypeIgnore] {
+        &self.type_ignores
+    }
+
+    /// The node `id` is directly in, or `None` for a statement of the
+    /// module.
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self[id].parent
+    }
+
+    /// Every node with its id, in preorder.
+    pub fn iter(&self) -> impl Iterator<Item = (NodeId, &Node)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .map(|(index, node)| (NodeId(index as u32), node))
+    }
+
+    /// The nodes directly in `id`, in the order of their fields, as
+    /// Python's `ast.iter_child_nodes` gives them.
+    pub fn children(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let end = self[id].subtree_end;
+        let mut next = id.0 + 1;
+        iter::from_fn(move || {
+            if next == end {
+                return None;
+            }
+            let child = NodeId(next);
+            next = self[child].subtree_end;
+            Some(child)
+        })
+    }
+
+    /// The nodes anywhere under `id`, which are the ones after it up to
+    /// the end of its subtree.
+    pub fn descendants(&self, id: NodeId) -> impl Iterator<Item = NodeId> {
+        (id.0 + 1..self[id].subtree_end).map(NodeId)
+    }
+
+    /// The nodes `id` is in, innermost first.
+    pub fn ancestors(&self, id: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        let mut next = self.parent(id);
+        iter::from_fn(move || {
+            let id = next?;
+            next = self.parent(id);
+            Some(id)
+        })
+    }
+
+    /// The tree of boxed nodes the arena was made from.
+    pub fn to_module(&self) -> Module {
+        Module {
+            body: self.stmts(&self.body),
+            type_ignores: self.type_ignores.clone(),
+        }
+    }
+
+    fn stmts(&self, ids: &[NodeId]) -> Vec<Stmt> {
+        ids.iter().map(|&id| self.stmt(id)).collect()
+    }
+
+    fn stmt(&self, id: NodeId) -> Stmt {
+        let node = &self[id];
+        let kind = match node.kind {
+            NodeKind::FunctionDef {
+                ref name,
+                ref args,
+                ref body,
+                ref decorator_list,
+                returns,
+                ref type_comment,
+                ref type_params,
+                is_async,
+            } => StmtKind::FunctionDef {
+                name: name.clone(),
+                args: Box::new(self.arguments(args)),
+                body: self.stmts(body),
+                decorator_list: self.exprs(decorator_list),
+                returns: self.boxed(returns),
+                type_comment: type_comment.clone(),
+                type_params: self.type_params(type_params),
+                is_async,
+            },
+            NodeKind::ClassDef {
+                ref name,
+                ref bases,
+                ref keywords,
+                ref body,
+                ref decorator_list,
+                ref type_params,
+            } => StmtKind::ClassDef {
+                name: name.clone(),
+                bases: self.exprs(bases),
+                keywords: self.keywords(keywords),
+                body: self.stmts(body),
+                decorator_list: self.exprs(decorator_list),
+                type_params: self.type_params(type_params),
+            },
+            NodeKind::Return(value) => StmtKind::Return(self.optional(value)),
+            NodeKind::Delete(ref targets) => StmtKind::Delete(self.exprs(targets)),
+            NodeKind::Assign {
+                ref targets,
+                value,
+                ref type_comment,
+            } => StmtKind::Assign {
+                targets: self.exprs(targets),
+                value: self.expr(value),
+                type_comment: type_comment.clone(),
+            },
+            NodeKind::TypeAlias {
+                name,
+                ref type_params,
+                value,
+            } => StmtKind::TypeAlias {
+                name: self.expr(name),
+                type_params: self.type_params(type_params),
+                value: self.expr(value),
+            },
+            NodeKind::AugAssign { target, op, value } => StmtKind::AugAssign {
+                target: self.expr(target),
+                op,
+                value: self.expr(value),
+            },
+            NodeKind::AnnAssign {
+                target,
+                annotation,
+                value,
+                simple,
+            } => StmtKind::AnnAssign {
+                target: self.expr(target),
+                annotation: self.expr(annotation),
+                value: self.optional(value),
+                simple,
+            },
+            NodeKind::For {
+                target,
+                iter,
+                ref body,
+                ref orelse,
+                ref type_comment,
+                is_async,
+            } => StmtKind::For {
+                target: self.expr(target),
+                iter: self.expr(iter),
+                body: self.stmts(body),
+                orelse: self.stmts(orelse),
+                type_comment: type_comment.clone(),
+                is_async,
+            },
+            NodeKind::While {
+                test,
+                ref body,
+                ref orelse,
+            } => StmtKind::While {
+                test: self.expr(test),
+                body: self.stmts(body),
+                orelse: self.stmts(orelse),
+            },
+            NodeKind::If {
+                test,
+                ref body,
+                ref orelse,
+            } => StmtKind::If {
+                test: self.expr(test),
+                body: self.stmts(body),
+                orelse: self.stmts(orelse),
+            },
+            NodeKind::With {
+                ref items,
+                ref body,
+                ref type_comment,
+                is_async,
+            } => StmtKind::With {
+                items: items
+                    .iter()
+                    .map(|item| super::WithItem {
+                        context_expr: self.expr(item.context_expr),
+                        optional_vars: self.optional(item.optional_vars),
+                    })
+                    .collect(),
+                body: self.stmts(body),
+                type_comment: type_comment.clone(),
+                is_async,
+            },
+            NodeKind::Match { subject, ref cases } => StmtKind::Match {
+                subject: self.expr(subject),
+                cases: cases
+                    .iter()
+                    .map(|case| super::MatchCase {
+                        pattern: self.pattern(case.pattern),
+                        guard: self.optional(case.guard),
+                        body: self.stmts(&case.body),
+                    })
+                    .collect(),
+            },
+            NodeKind::Raise { exc, cause } => StmtKind::Raise {
+                exc: self.optional(exc),
+                cause: self.optional(cause),
+            },
+            NodeKind::Try {
+                ref body,
+                ref handlers,
+                ref orelse,
+                ref finalbody,
+                is_star,
+            } => StmtKind::Try {
+                body: self.stmts(body),
+                handlers: handlers.iter().map(|&id| self.handler(id)).collect(),
+                orelse: self.stmts(orelse),
+                finalbody: self.stmts(finalbody),
+                is_star,
+            },
+            NodeKind::Assert { test, msg } => StmtKind::Assert {
+                test: self.expr(test),
+                msg: self.optional(msg),
+            },
+            NodeKind::Import(ref names) => StmtKind::Import(self.aliases(names)),
+            NodeKind::ImportFrom {
+                ref module,
+                ref names,
+                level,
+            } => StmtKind::ImportFrom {
+                module: module.clone(),
+                names: self.aliases(names),
+                level,
+            },
+            NodeKind::Global(ref names) => StmtKind::Global(names.clone()),
+            NodeKind::Nonlocal(ref names) => StmtKind::Nonlocal(names.clone()),
+            NodeKind::Expr(value) => StmtKind::Expr(self.expr(value)),
+            NodeKind::Pass => StmtKind::Pass,
+            NodeKind::Break => StmtKind::Break,
+            NodeKind::Continue => StmtKind::Continue,
+            NodeKind::Error => StmtKind::Error,
+            ref kind => panic!("{:?} is not a statement", kind),
+        };
+        Stmt {
+            kind,
+            start: node.start,
+            end: node.end,
+        }
+    }
+
+    fn exprs(&self, ids: &[NodeId]) -> Vec<Expr> {
+        ids.iter().map(|&id| self.expr(id)).collect()
+    }
+
+    fn optional(&self, id: Option<NodeId>) -> Option<Expr> {
+        id.map(|id| self.expr(id))
+    }
+
+    fn boxed(&self, id: Option<NodeId>) -> Option<Box<Expr>> {
+        id.map(|id| Box::new(self.expr(id)))
+    }
+
+    fn expr(&self, id: NodeId) -> Expr {
+        let node = &self[id];
+        let boxed = |id: NodeId| Box::new(self.expr(id));
+        let kind = match node.kind {
+            NodeKind::BoolOp { op, ref values } => ExprKind::BoolOp {
+                op,
+                values: self.exprs(values),
+            },
+            NodeKind::NamedExpr { target, value } => ExprKind::NamedExpr {
+                target: boxed(target),
+                value: boxed(value),
+            },
+            NodeKind::BinOp { left, op, right } => ExprKind::BinOp {
+                left: boxed(left),
+                op,
+                right: boxed(right),
+            },
+            NodeKind::UnaryOp { op, operand } => ExprKind::UnaryOp {
+                op,
+                operand: boxed(operand),
+            },
+            NodeKind::Lambda { ref args, body } => ExprKind::Lambda {
+                args: Box::new(self.arguments(args)),
+                body: boxed(body),
+            },
+            NodeKind::IfExp { test, body, orelse } => ExprKind::IfExp {
+                test: boxed(test),
+                body: boxed(body),
+                orelse: boxed(orelse),
+            },
+            NodeKind::Dict {
+                ref keys,
+                ref values,
+            } => ExprKind::Dict {
+                keys: keys.iter().map(|&key| self.optional(key)).collect(),
+                values: self.exprs(values),
+            },
+            NodeKind::Set(ref elts) => ExprKind::Set(self.exprs(elts)),
+            NodeKind::ListComp {
+                elt,
+                ref generators,
+            } => ExprKind::ListComp {
+                elt: boxed(elt),
+                generators: self.comprehensions(generators),
+            },
+            NodeKind::SetComp {
+                elt,
+                ref generators,
+            } => ExprKind::SetComp {
+                elt: boxed(elt),
+                generators: self.comprehensions(generators),
+            },
+            NodeKind::DictComp {
+                key,
+                value,
+                ref generators,
+            } => ExprKind::DictComp {
+                key: boxed(key),
+                value: boxed(value),
+                generators: self.comprehensions(generators),
+            },
+            NodeKind::GeneratorExp {
+                elt,
+                ref generators,
+            } => ExprKind::GeneratorExp {
+                elt: boxed(elt),
+                generators: self.comprehensions(generators),
+            },
+            NodeKind::Await(value) => ExprKind::Await(boxed(value)),
+            NodeKind::Yield(value) => ExprKind::Yield(self.boxed(value)),
+            NodeKind::YieldFrom(value) => ExprKind::YieldFrom(boxed(value)),
+            NodeKind::Compare {
+                left,
+                ref ops,
+                ref comparators,
+            } => ExprKind::Compare {
+                left: boxed(left),
+                ops: ops.clone(),
+                comparators: self.exprs(comparators),
+            },
+            NodeKind::Call {
+                func,
+                ref args,
+                ref keywords,
+            } => ExprKind::Call {
+                func: boxed(func),
+                args: self.exprs(args),
+                keywords: self.keywords(keywords),
+            },
+            NodeKind::FormattedValue {
+                value,
+                conversion,
+                format_spec,
+            } => ExprKind::FormattedValue {
+                value: boxed(value),
+                conversion,
+                format_spec: self.boxed(format_spec),
+            },
+            NodeKind::JoinedStr(ref values) => ExprKind::JoinedStr(self.exprs(values)),
+            NodeKind::Constant(ref value) => ExprKind::Constant(value.clone()),
+            NodeKind::Attribute {
+                value,
+                ref attr,
+                ctx,
+            } => ExprKind::Attribute {
+                value: boxed(value),
+                attr: attr.clone(),
+                ctx,
+            },
+            NodeKind::Subscript { value, slice, ctx } => ExprKind::Subscript {
+                value: boxed(value),
+                slice: boxed(slice),
+                ctx,
+            },
+            NodeKind::Starred { value, ctx } => ExprKind::Starred {
+                value: boxed(value),
+                ctx,
+            },
+            NodeKind::Name { ref id, ctx } => ExprKind::Name {
+                id: id.clone(),
+                ctx,
+            },
+            NodeKind::List { ref elts, ctx } => ExprKind::List {
+                elts: self.exprs(elts),
+                ctx,
+            },
+            NodeKind::Tuple { ref elts, ctx } => ExprKind::Tuple {
+                elts: self.exprs(elts),
+                ctx,
+            },
+            NodeKind::Slice { lower, upper, step } => ExprKind::Slice {
+                lower: self.boxed(lower),
+                upper: self.boxed(upper),
+                step: self.boxed(step),
+            },
+            ref kind => panic!("{:?} is not an expression", kind),
+        };
+        Expr {
+            kind,
+            start: node.start,
+            end: node.end,
+        }
+    }
+
+    fn comprehensions(&self, generators: &[Comprehension]) -> Vec<super::Comprehension> {
+        generators
+            .iter()
+            .map(|generator| super::Comprehension {
+                target: self.expr(generator.target),
+                iter: self.expr(generator.iter),
+                ifs: self.exprs(&generator.ifs),
+                is_async: generator.is_async,
+            })
+            .collect()
+    }
+
+    fn patterns(&self, ids: &[NodeId]) -> Vec<Pattern> {
+        ids.iter().map(|&id| self.pattern(id)).collect()
+    }
+
+    fn pattern(&self, id: NodeId) -> Pattern {
+        let node = &self[id];
+        let kind = match node.kind {
+            NodeKind::MatchValue(value) => PatternKind::MatchValue(self.expr(value)),
+            NodeKind::MatchSingleton(ref value) => PatternKind::MatchSingleton(value.clone()),
+            NodeKind::MatchSequence(ref patterns) => {
+                PatternKind::MatchSequence(self.patterns(patterns))
+            }
+            NodeKind::MatchMapping {
+                ref keys,
+                ref patterns,
+                ref rest,
+            } => PatternKind::MatchMapping {
+                keys: self.exprs(keys),
+                patterns: self.patterns(patterns),
+                rest: rest.clone(),
+            },
+            NodeKind::MatchClass {
+                cls,
+                ref patterns,
+                ref kwd_attrs,
+                ref kwd_patterns,
+            } => PatternKind::MatchClass {
+                cls: self.expr(cls),
+                patterns: self.patterns(patterns),
+                kwd_attrs: kwd_attrs.clone(),
+                kwd_patterns: self.patterns(kwd_patterns),
+            },
+            NodeKind::MatchStar(ref name) => PatternKind::MatchStar(name.clone()),
+            NodeKind::MatchAs { pattern, ref name } => PatternKind::MatchAs {
+                pattern: pattern.map(|pattern| Box::new(self.pattern(pattern))),
+                name: name.clone(),
+            },
+            NodeKind::MatchOr(ref patterns) => PatternKind::MatchOr(self.patterns(patterns)),
+            ref kind => panic!("{:?} is not a pattern", kind),
+        };
+        Pattern {
+            kind,
+            start: node.start,
+            end: node.end,
+        }
+    }
+
+    fn handler(&self, id: NodeId) -> ExceptHandler {
+        let node = &self[id];
+        match node.kind {
+            NodeKind::ExceptHandler {
+                type_,
+                ref name,
+                ref body,
+            } => ExceptHandler {
+                type_: self.optional(type_),
+                name: name.clone(),
+                body: self.stmts(body),
+                start: node.start,
+                end: node.end,
+            },
+            ref kind => panic!("{:?} is not an exception handler", kind),
+        }
+    }
+
+    fn arguments(&self, arguments: &Arguments) -> super::Arguments {
+        let args = |ids: &[NodeId]| ids.iter().map(|&id| self.arg(id)).collect();
+        super::Arguments {
+            posonlyargs: args(&arguments.posonlyargs),
+            args: args(&arguments.args),
+            vararg: arguments.vararg.map(|id| self.arg(id)),
+            kwonlyargs: args(&arguments.kwonlyargs),
+            kw_defaults: arguments
+                .kw_defaults
+                .iter()
+                .map(|&default| self.optional(default))
+                .collect(),
+            kwarg: arguments.kwarg.map(|id| self.arg(id)),
+            defaults: self.exprs(&arguments.defaults),
+        }
+    }
+
+    fn arg(&self, id: NodeId) -> Arg {
+        let node = &self[id];
+        match node.kind {
+            NodeKind::Arg {
+                ref arg,
+                annotation,
+                ref type_comment,
+            } => Arg {
+                arg: arg.clone(),
+                annotation: self.boxed(annotation),
+                type_comment: type_comment.clone(),
+                start: node.start,
+                end: node.end,
+            },
+            ref kind => panic!("{:?} is not a parameter", kind),
+        }
+    }
+
+    fn keywords(&self, ids: &[NodeId]) -> Vec<Keyword> {
+        ids.iter()
+            .map(|&id| {
+                let node = &self[id];
+                match node.kind {
+                    NodeKind::Keyword { ref arg, value } => Keyword {
+                        arg: arg.clone(),
+                        value: self.expr(value),
+                        start: node.start,
+                        end: node.end,
+                    },
+                    ref kind => panic!("{:?} is not a keyword argument", kind),
+                }
+            })
+            .collect()
+    }
+
+    fn aliases(&self, ids: &[NodeId]) -> Vec<Alias> {
+        ids.iter()
+            .map(|&id| {
+                let node = &self[id];
+                match node.kind {
+                    NodeKind::Alias {
+                        ref name,
+                        ref asname,
+                    } => Alias {
+                        name: name.clone(),
+                        asname: asname.clone(),
+                        start: node.start,
+                        end: node.end,
+                    },
+                    ref kind => panic!("{:?} is not an alias", kind),
+                }
+            })
+            .collect()
+    }
+
+    fn type_params(&self, ids: &[NodeId]) -> Vec<TypeParam> {
+        ids.iter()
+            .map(|&id| {
+                let node = &self[id];
+                let kind = match node.kind {
+                    NodeKind::TypeVar { ref name, bound } => TypeParamKind::TypeVar {
+                        name: name.clone(),
+                        bound: self.optional(bound),
+                    },
+                    NodeKind::ParamSpec(ref name) => TypeParamKind::ParamSpec(name.clone()),
+                    NodeKind::TypeVarTuple(ref name) => TypeParamKind::TypeVarTuple(name.clone()),
+                    ref kind => panic!("{:?} is not a type parameter", kind),
+                };
+                TypeParam {
+                    kind,
+                    start: node.start,
+                    end: node.end,
+                }
+            })
+            .collect()
+    }
+}
+
+impl Index<NodeId> for Arena {
+    type Output = Node;
+
+    /// The node `id`. Panics if the arena has no such node.
+    fn index(&self, id: NodeId) -> &Node {
+        &self.nodes[id.index()]
+    }
+}
+
+/// Moves the nodes of a tree into an arena, each before its children.
+struct Builder {
+    nodes: Vec<Node>,
+}
+
+impl Builder {
+    /// Adds a node under `parent`, of a kind given by `close` once its
+    /// children are added.
+    fn open(&mut self, start: Location, end: Location, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len() as u32);
+        self.nodes.push(Node {
+            kind: NodeKind::Error,
+            start,
+            end,
+            parent,
+            subtree_end: id.0 + 1,
+        });
+        id
+    }
+
+    fn close(&mut self, id: NodeId, kind: NodeKind) -> NodeId {
+        let subtree_end = self.nodes.len() as u32;
+        let node = &mut self.nodes[id.index()];
+        node.kind = kind;
+        node.subtree_end = subtree_end;
+        id
+    }
+
+    fn stmts(&mut self, stmts: Vec<Stmt>, parent: Option<NodeId>) -> Vec<NodeId> {
+        stmts
+            .into_iter()
+            .map(|stmt| self.stmt(stmt, parent))
+            .collect()
+    }
+
+    fn stmt(&mut self, stmt: Stmt, parent: Option<NodeId>) -> NodeId {
+        let id = self.open(stmt.start, stmt.end, parent);
+        let parent = Some(id);
+        let kind = match stmt.kind {
+            StmtKind::FunctionDef {
+                name,
+                args,
+                body,
+                decorator_list,
+                returns,
+                type_comment,
+                type_params,
+                is_async,
+            } => {
+                let args = Box::new(self.arguments(*args, parent));
+                let body = self.stmts(body, parent);
+                let decorator_list = self.exprs(decorator_list, parent);
+                let returns = returns.map(|returns| self.expr(*returns, parent));
+                let type_params = self.type_params(type_params, parent);
+                NodeKind::FunctionDef {
+                    name,
+                    args,
+                    body,
+                    decorator_list,
+                    returns,
+                    type_comment,
+                    type_params,
+                    is_async,
+                }
+            }
+            StmtKind::ClassDef {
+                name,
+                bases,
+                keywords,
+                body,
+                decorator_list,
+                type_params,
+            } => {
+                let bases = self.exprs(bases, parent);
+                let keywords = self.keywords(keywords, parent);
+                let body = self.stmts(body, parent);
+                let decorator_list = self.exprs(decorator_list, parent);
+                let type_params = self.type_params(type_params, parent);
+                NodeKind::ClassDef {
+                    name,
+                    bases,
+                    keywords,
+                    body,
+                    decorator_list,
+                    type_params,
+                }
+            }
+            StmtKind::Return(value) => NodeKind::Return(self.optional(value, parent)),
+            StmtKind::Delete(targets) => NodeKind::Delete(self.exprs(targets, parent)),
+            StmtKind::Assign {
+                targets,
+                value,
+                type_comment,
+            } => NodeKind::Assign {
+                targets: self.exprs(targets, parent),
+                value: self.expr(value, parent),
+                type_comment,
+            },
+            StmtKind::TypeAlias {
+                name,
+                type_params,
+                value,
+            } => NodeKind::TypeAlias {
+                name: self.expr(name, parent),
+                type_params: self.type_params(type_params, parent),
+                value: self.expr(value, parent),
+            },
+            StmtKind::AugAssign { target, op, value } => NodeKind::AugAssign {
+                target: self.expr(target, parent),
+                op,
+                value: self.expr(value, parent),
+            },
+            StmtKind::AnnAssign {
+                target,
+                annotation,
+                value,
+                simple,
+            } => NodeKind::AnnAssign {
+                target: self.expr(target, parent),
+                annotation: self.expr(annotation, parent),
+                value: self.optional(value, parent),
+                simple,
+            },
+            StmtKind::For {
+                target,
+                iter,
+                body,
+                orelse,
+                type_comment,
+                is_async,
+            } => NodeKind::For {
+                target: self.expr(target, parent),
+                iter: self.expr(iter, parent),
+                body: self.stmts(body, parent),
+                orelse: self.stmts(orelse, parent),
+                type_comment,
+                is_async,
+            },
+            StmtKind::While { test, body, orelse } => NodeKind::While {
+                test: self.expr(test, parent),
+                body: self.stmts(body, parent),
+                orelse: self.stmts(orelse, parent),
+            },
+            StmtKind::If { test, body, orelse } => NodeKind::If {
+                test: self.expr(test, parent),
+                body: self.stmts(body, parent),
+                orelse: self.stmts(orelse, parent),
+            },
+            StmtKind::With {
+                items,
+                body,
+                type_comment,
+                is_async,
+            } => NodeKind::With {
+                items: items
+                    .into_iter()
+                    .map(|item| WithItem {
+                        context_expr: self.expr(item.context_expr, parent),
+                        optional_vars: self.optional(item.optional_vars, parent),
+                    })
+                    .collect(),
+                body: self.stmts(body, parent),
+                type_comment,
+                is_async,
+            },
+            StmtKind::Match { subject, cases } => NodeKind::Match {
+                subject: self.expr(subject, parent),
+                cases: cases
+                    .into_iter()
+                    .map(|case| MatchCase {
+                        pattern: self.pattern(case.pattern, parent),
+                        guard: self.optional(case.guard, parent),
+                        body: self.stmts(case.body, parent),
+                    })
+                    .collect(),
+            },
+            StmtKind::Raise { exc, cause } => NodeKind::Raise {
+                exc: self.optional(exc, parent),
+                cause: self.optional(cause, parent),
+            },
+            StmtKind::Try {
+                body,
+                handlers,
+                orelse,
+                finalbody,
+                is_star,
+            } => NodeKind::Try {
+                body: self.stmts(body, parent),
+                handlers: handlers
+                    .into_iter()
+                    .map(|handler| self.handler(handler, parent))
+                    .collect(),
+                orelse: self.stmts(orelse, parent),
+                finalbody: self.stmts(finalbody, parent),
+                is_star,
+            },
+            StmtKind::Assert { test, msg } => NodeKind::Assert {
+                test: self.expr(test, parent),
+                msg: self.optional(msg, parent),
+            },
+            StmtKind::Import(names) => NodeKind::Import(self.aliases(names, parent)),
+            StmtKind::ImportFrom {
+                module,
+                names,
+                level,
+            } => NodeKind::ImportFrom {
+                module,
+                names: self.aliases(names, parent),
+                level,
+            },
+            StmtKind::Global(names) => NodeKind::Global(names),
+            StmtKind::Nonlocal(names) => NodeKind::Nonlocal(names),
+            StmtKind::Expr(value) => NodeKind::Expr(self.expr(value, parent)),
+            StmtKind::Pass => NodeKind::Pass,
+            StmtKind::Break => NodeKind::Break,
+            StmtKind::Continue => NodeKind::Continue,
+            StmtKind::Error => NodeKind::Error,
+        };
+        self.close(id, kind)
+    }
+
+    fn exprs(&mut self, exprs: Vec<Expr>, parent: Option<NodeId>) -> Vec<NodeId> {
+        exprs
+            .into_iter()
+            .map(|expr| self.expr(expr, parent))
+            .collect()
+    }
+
+    fn optional(&mut self, expr: Option<Expr>, parent: Option<NodeId>) -> Option<NodeId> {
+        expr.map(|expr| self.expr(expr, parent))
+    }
+
+    fn boxed(&mut self, expr: Option<Box<Expr>>, parent: Option<NodeId>) -> Option<NodeId> {
+        expr.map(|expr| self.expr(*expr, parent))
+    }
+
+    fn expr(&mut self, expr: Expr, parent: Option<NodeId>) -> NodeId {
+        let id = self.open(expr.start, expr.end, parent);
+        let parent = Some(id);
+        let kind = match expr.kind {
+            ExprKind::BoolOp { op, values } => NodeKind::BoolOp {
+                op,
+                values: self.exprs(values, parent),
+            },
+            ExprKind::NamedExpr { target, value } => NodeKind::NamedExpr {
+                target: self.expr(*target, parent),
+                value: self.expr(*value, parent),
+            },
+            ExprKind::BinOp { left, op, right } => NodeKind::BinOp {
+                left: self.expr(*left, parent),
+                op,
+                right: self.expr(*right, parent),
+            },
+            ExprKind::UnaryOp { op, operand } => NodeKind::UnaryOp {
+                op,
+                operand: self.expr(*operand, parent),
+            },
+            ExprKind::Lambda { args, body } => NodeKind::Lambda {
+                args: Box::new(self.arguments(*args, parent)),
+                body: self.expr(*body, parent),
+            },
+            ExprKind::IfExp { test, body, orelse } => NodeKind::IfExp {
+                test: self.expr(*test, parent),
+                body: self.expr(*body, parent),
+                orelse: self.expr(*orelse, parent),
+            },
+            ExprKind::Dict { keys, values } => NodeKind::Dict {
+                keys: keys
+                    .into_iter()
+                    .map(|key| self.optional(key, parent))
+                    .collect(),
+                values: self.exprs(values, parent),
+            },
+            ExprKind::Set(elts) => NodeKind::Set(self.exprs(elts, parent)),
+            ExprKind::ListComp { elt, generators } => NodeKind::ListComp {
+                elt: self.expr(*elt, parent),
+                generators: self.comprehensions(generators, parent),
+            },
+            ExprKind::SetComp { elt, generators } => NodeKind::SetComp {
+                elt: self.expr(*elt, parent),
+                generators: self.comprehensions(generators, parent),
+            },
+            ExprKind::DictComp {
+                key,
+                value,
+                generators,
+            } => NodeKind::DictComp {
+                key: self.expr(*key, parent),
+                value: self.expr(*value, parent),
+                generators: self.comprehensions(generators, parent),
+            },
+            ExprKind::GeneratorExp { elt, generators } => NodeKind::GeneratorExp {
+                elt: self.expr(*elt, parent),
+                generators: self.comprehensions(generators, parent),
+            },
+            ExprKind::Await(value) => NodeKind::Await(self.expr(*value, parent)),
+            ExprKind::Yield(value) => NodeKind::Yield(self.boxed(value, parent)),
+            ExprKind::YieldFrom(value) => NodeKind::YieldFrom(self.expr(*value, parent)),
+            ExprKind::Compare {
+                left,
+                ops,
+                comparators,
+            } => NodeKind::Compare {
+                left: self.expr(*left, parent),
+                ops,
+                comparators: self.exprs(comparators, parent),
+            },
+            ExprKind::Call {
+                func,
+                args,
+                keywords,
+            } => NodeKind::Call {
+                func: self.expr(*func, parent),
+                args: self.exprs(args, parent),
+                keywords: self.keywords(keywords, parent),
+            },
+            ExprKind::FormattedValue {
+                value,
+                conversion,
+                format_spec,
+            } => NodeKind::FormattedValue {
+                value: self.expr(*value, parent),
+                conversion,
+                format_spec: self.boxed(format_spec, parent),
+            },
+            ExprKind::JoinedStr(values) => NodeKind::JoinedStr(self.exprs(values, parent)),
+            ExprKind::Constant(value) => NodeKind::Constant(value),
+            ExprKind::Attribute { value, attr, ctx } => NodeKind::Attribute {
+                value: self.expr(*value, parent),
+                attr,
+                ctx,
+            },
+            ExprKind::Subscript { value, slice, ctx } => NodeKind::Subscript {
+                value: self.expr(*value, parent),
+                slice: self.expr(*slice, parent),
+                ctx,
+            },
+            ExprKind::Starred { value, ctx } => NodeKind::Starred {
+                value: self.expr(*value, parent),
+                ctx,
+            },
+            ExprKind::Name { id, ctx } => NodeKind::Name { id, ctx },
+            ExprKind::List { elts, ctx } => NodeKind::List {
+                elts: self.exprs(elts, parent),
+                ctx,
+            },
+            ExprKind::Tuple { elts, ctx } => NodeKind::Tuple {
+                elts: self.exprs(elts, parent),
+                ctx,
+            },
+            ExprKind::Slice { lower, upper, step } => NodeKind::Slice {
+                lower: self.boxed(lower, parent),
+                upper: self.boxed(upper, parent),
+                step: self.boxed(step, parent),
+            },
+        };
+        self.close(id, kind)
+    }
+
+    fn comprehensions(
+        &mut self,
+        generators: Vec<super::Comprehension>,
+        parent: Option<NodeId>,
+    ) -> Vec<Comprehension> {
+        generators
+            .into_iter()
+            .map(|generator| Comprehension {
+                target: self.expr(generator.target, parent),
+                iter: self.expr(generator.iter, parent),
+                ifs: self.exprs(generator.ifs, parent),
+                is_async: generator.is_async,
+            })
+            .collect()
+    }
+
+    fn patterns(&mut self, patterns: Vec<Pattern>, parent: Option<NodeId>) -> Vec<NodeId> {
+        patterns
+            .into_iter()
+            .map(|pattern| self.pattern(pattern, parent))
+            .collect()
+    }
+
+    fn pattern(&mut self, pattern: Pattern, parent: Option<NodeId>) -> NodeId {
+        let id = self.open(pattern.start, pattern.end, parent);
+        let parent = Some(id);
+        let kind = match pattern.kind {
+            PatternKind::MatchValue(value) => NodeKind::MatchValue(self.expr(value, parent)),
+            PatternKind::MatchSingleton(value) => NodeKind::MatchSingleton(value),
+            PatternKind::MatchSequence(patterns) => {
+                NodeKind::MatchSequence(self.patterns(patterns, parent))
+            }
+            PatternKind::MatchMapping {
+                keys,
+                patterns,
+                rest,
+            } => NodeKind::MatchMapping {
+                keys: self.exprs(keys, parent),
+                patterns: self.patterns(patterns, parent),
+                rest,
+            },
+            PatternKind::MatchClass {
+                cls,
+                patterns,
+                kwd_attrs,
+                kwd_patterns,
+            } => NodeKind::MatchClass {
+                cls: self.expr(cls, parent),
+                patterns: self.patterns(patterns, parent),
+                kwd_attrs,
+                kwd_patterns: self.patterns(kwd_patterns, parent),
+            },
+            PatternKind::MatchStar(name) => NodeKind::MatchStar(name),
+            PatternKind::MatchAs { pattern, name } => NodeKind::MatchAs {
+                pattern: pattern.map(|pattern| self.pattern(*pattern, parent)),
+                name,
+            },
+            PatternKind::MatchOr(patterns) => NodeKind::MatchOr(self.patterns(patterns, parent)),
+        };
+        self.close(id, kind)
+    }
+
+    fn handler(&mut self, handler: ExceptHandler, parent: Option<NodeId>) -> NodeId {
+        let id = self.open(handler.start, handler.end, parent);
+        let parent = Some(id);
+        let kind = NodeKind::ExceptHandler {
+            type_: self.optional(handler.type_, parent),
+            name: handler.name,
+            body: self.stmts(handler.body, parent),
+        };
+        self.close(id, kind)
+    }
+
+    fn arguments(&mut self, arguments: super::Arguments, parent: Option<NodeId>) -> Arguments {
+        Arguments {
+            posonlyargs: self.args(arguments.posonlyargs, parent),
+            args: self.args(arguments.args, parent),
+            vararg: arguments.vararg.map(|arg| self.arg(arg, parent)),
+            kwonlyargs: self.args(arguments.kwonlyargs, parent),
+            kw_defaults: arguments
+                .kw_defaults
+                .into_iter()
+                .map(|default| self.optional(default, parent))
+                .collect(),
+            kwarg: arguments.kwarg.map(|arg| self.arg(arg, parent)),
+            defaults: self.exprs(arguments.defaults, parent),
+        }
+    }
+
+    fn args(&mut self, args: Vec<Arg>, parent: Option<NodeId>) -> Vec<NodeId> {
+        args.into_iter().map(|arg| self.arg(arg, parent)).collect()
+    }
+
+    fn arg(&mut self, arg: Arg, parent: Option<NodeId>) -> NodeId {
+        let id = self.open(arg.start, arg.end, parent);
+        let kind = NodeKind::Arg {
+            arg: arg.arg,
+            annotation: self.boxed(arg.annotation, Some(id)),
+            type_comment: arg.type_comment,
+        };
+        self.close(id, kind)
+    }
+
+    fn keywords(&mut self, keywords: Vec<Keyword>, parent: Option<NodeId>) -> Vec<NodeId> {
+        keywords
+            .into_iter()
+            .map(|keyword| {
+                let id = self.open(keyword.start, keyword.end, parent);
+                let kind = NodeKind::Keyword {
+                    arg: keyword.arg,
+                    value: self.expr(keyword.value, Some(id)),
+                };
+                self.close(id, kind)
+            })
+            .collect()
+    }
+
+    fn aliases(&mut self, aliases: Vec<Alias>, parent: Option<NodeId>) -> Vec<NodeId> {
+        aliases
+            .into_iter()
+            .map(|alias| {
+                let id = self.open(alias.start, alias.end, parent);
+                let kind = NodeKind::Alias {
+                    name: alias.name,
+                    asname: alias.asname,
+                };
+                self.close(id, kind)
+            })
+            .collect()
+    }
+
+    fn type_params(&mut self, params: Vec<TypeParam>, parent: Option<NodeId>) -> Vec<NodeId> {
+        params
+            .into_iter()
+            .map(|param| {
+                let id = self.open(param.start, param.end, parent);
+                let kind = match param.kind {
+                    TypeParamKind::TypeVar { name, bound } => NodeKind::TypeVar {
+                        name,
+                        bound: self.optional(bound, Some(id)),
+                    },
+                    TypeParamKind::ParamSpec(name) => NodeKind::ParamSpec(name),
+                    TypeParamKind::TypeVarTuple(name) => NodeKind::TypeVarTuple(name),
+                };
+                self.close(id, kind)
+            })
+            .collect()
+    }
+}
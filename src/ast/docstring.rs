@@ -0,0 +1,104 @@
+//! Docstrings, as `ast.get_docstring` finds them.
+
+use ast::{Constant, ExprKind, Module, Stmt, StmtKind};
+
+impl Module {
+    /// The module's docstring, exactly as written.
+    pub fn docstring(&self) -> Option<&str> {
+        body_docstring(&self.body)
+    }
+}
+
+impl Stmt {
+    /// The docstring of a function or class definition, exactly as written,
+    /// or `None` for any other statement.
+    pub fn docstring(&self) -> Option<&str> {
+        match self.kind {
+            StmtKind::FunctionDef { ref body, .. } | StmtKind::ClassDef { ref body, .. } => {
+                body_docstring(body)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The docstring of a module, class or function `body`: its first
+/// statement, when that is a string literal. With `clean`, indentation is
+/// removed as `clean_docstring` does, like `ast.get_docstring`.
+pub fn get_docstring(body: &[Stmt], clean: bool) -> Option<String> {
+    body_docstring(body).map(|doc| {
+        if clean {
+            clean_docstring(doc)
+        } else {
+            doc.to_string()
+        }
+    })
+}
+
+pub(crate) fn body_docstring(body: &[Stmt]) -> Option<&str> {
+    match body.first()?.kind {
+        StmtKind::Expr(ref expr) => match expr.kind {
+            ExprKind::Constant(Constant::Str(ref doc)) => Some(doc),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// `doc` with tabs expanded, the first line's leading whitespace and the
+/// other lines' common indentation removed, and blank lines at either end
+/// dropped, as `inspect.cleandoc` does.
+pub fn clean_docstring(doc: &str) -> String {
+    let expanded = expand_tabs(doc);
+    let mut lines: Vec<&str> = expanded.split('\n').collect();
+    let margin = lines[1..]
+        .iter()
+        .filter_map(|line| {
+            let content = line.trim_start();
+            if content.is_empty() {
+                None
+            } else {
+                Some(line[..line.len() - content.len()].chars().count())
+            }
+        })
+        .min();
+    lines[0] = lines[0].trim_start();
+    if let Some(margin) = margin {
+        for line in &mut lines[1..] {
+            *line = match line.char_indices().nth(margin) {
+                Some((index, _)) => &line[index..],
+                None => "",
+            };
+        }
+    }
+    while lines.last() == Some(&"") {
+        lines.pop();
+    }
+    let start = lines.iter().take_while(|line| line.is_empty()).count();
+    lines[start..].join("\n")
+}
+
+/// `text` with each tab replaced by spaces up to the next multiple of 8
+/// columns, as `str.expandtabs` does.
+fn expand_tabs(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let spaces = 8 - column % 8;
+                expanded.extend((0..spaces).map(|_| ' '));
+                column += spaces;
+            }
+            '\n' | '\r' => {
+                expanded.push(c);
+                column = 0;
+            }
+            _ => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+    expanded
+}
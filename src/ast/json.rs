@@ -0,0 +1,1080 @@
+//! The AST as JSON in the layout of `ast2json`, which is that of
+//! `ast.dump(tree, include_attributes=True)`: each node is an object whose
+//! `_type` is its CPython class name, with its fields under their CPython
+//! names, and, for the nodes that have a position, `lineno`, `col_offset`,
+//! `end_lineno` and `end_col_offset`. Operators and contexts are nodes
+//! without fields, such as `{"_type": "Add"}`.
+//!
+//! Field values are written as `ast2json` writes them: flags such as
+//! `is_async` and `simple` are `0` or `1`, a `FormattedValue`'s
+//! `conversion` is the code of its character or `-1`, a complex constant is
+//! its `str`, bytes are their UTF-8 text or else their hex digits, and the
+//! ellipsis is `"..."`. Infinite floats are `Infinity`, as Python's `json`
+//! module writes them, though that is not standard JSON. Nothing records
+//! the `u` prefix of strings, so a constant's `kind` is always `null`.
+//! `type_comment` and `type_ignores` are empty unless the module was parsed
+//! with `ParserConfig::type_comments`.
+//!
+//! The module is first turned into a tree of `Node`s in this layout, which
+//! `debugging` also draws.
+
+use super::{
+    float_literal, Alias, Arg, Arguments, BoolOperator, CmpOperator, Comprehension, Constant,
+    Context, ExceptHandler, Expr, ExprKind, Keyword, MatchCase, Module, Operator, Pattern,
+    PatternKind, Stmt, StmtKind, TypeParam, TypeParamKind, UnaryOperator, WithItem,
+};
+use tokenizer::Location;
+
+/// The JSON for `module`, with columns counted in characters, which are
+/// CPython's `col_offset`s only where the lines are ASCII before them.
+pub fn to_json(module: &Module) -> String {
+    let mut out = String::new();
+    write_node(&mut out, &tree(module, None));
+    out
+}
+
+/// The JSON for `module`, parsed from `source`, with columns counted in
+/// UTF-8 bytes as CPython counts them.
+pub fn to_json_with_source(module: &Module, source: &str) -> String {
+    let mut out = String::new();
+    write_node(&mut out, &tree(module, Some(source)));
+    out
+}
+
+/// A value in the JSON layout of a node's field.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    /// A number or boolean, as its JSON text.
+    Raw(String),
+    Str(String),
+    List(Vec<Value>),
+    Node(Node),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Node {
+    /// The CPython class name.
+    pub(crate) name: &'static str,
+    pub(crate) fields: Vec<(&'static str, Value)>,
+    /// `lineno`, `col_offset`, `end_lineno` and `end_col_offset`, for the
+    /// nodes that have them.
+    pub(crate) position: Option<[usize; 4]>,
+}
+
+/// The tree of `module` in the JSON layout, with columns counted in UTF-8
+/// bytes if `source` is given and in characters if not.
+pub(crate) fn tree(module: &Module, source: Option<&str>) -> Node {
+    let mut builder = Builder {
+        stack: Vec::new(),
+        root: None,
+        lines: source.map(|source| source.split('\n').collect()),
+    };
+    builder.module(module);
+    builder.root.unwrap()
+}
+
+/// The names of the position attributes, in order.
+pub(crate) const POSITION: [&str; 4] = ["lineno", "col_offset", "end_lineno", "end_col_offset"];
+
+fn write_node(out: &mut String, node: &Node) {
+    out.push_str("{\"_type\":");
+    write_string(out, node.name);
+    let position = node.position.iter().flat_map(|position| {
+        POSITION
+            .iter()
+            .zip(position)
+            .map(|(&name, value)| (name, Value::Raw(value.to_string())))
+    });
+    let fields = node.fields.iter().cloned().chain(position);
+    for (name, value) in fields {
+        out.push(',');
+        write_string(out, name);
+        out.push(':');
+        write_value(out, &value);
+    }
+    out.push('}');
+}
+
+fn write_value(out: &mut String, value: &Value) {
+    match *value {
+        Value::Null => out.push_str("null"),
+        Value::Raw(ref text) => out.push_str(text),
+        Value::Str(ref text) => write_string(out, text),
+        Value::List(ref items) => {
+            out.push('[');
+            for (index, item) in items.iter().enumerate() {
+                if index > 0 {
+                    out.push(',');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Node(ref node) => write_node(out, node),
+    }
+}
+
+fn write_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// A node or list being built.
+enum Frame {
+    Node(Node),
+    List(Vec<Value>),
+}
+
+struct Builder<'a> {
+    stack: Vec<Frame>,
+    root: Option<Node>,
+    /// The lines of the source, to count columns in bytes, if it is known.
+    lines: Option<Vec<&'a str>>,
+}
+
+impl<'a> Builder<'a> {
+    /// Adds `value` to the list being built, or as the value of the field
+    /// last started.
+    fn value(&mut self, value: Value) {
+        match self.stack.last_mut() {
+            Some(Frame::List(ref mut items)) => items.push(value),
+            Some(Frame::Node(ref mut node)) => node.fields.last_mut().unwrap().1 = value,
+            None => match value {
+                Value::Node(node) => self.root = Some(node),
+                _ => unreachable!("the root is a node"),
+            },
+        }
+    }
+
+    fn node(&mut self, name: &'static str) {
+        self.stack.push(Frame::Node(Node {
+            name,
+            fields: Vec::new(),
+            position: None,
+        }));
+    }
+
+    fn field(&mut self, name: &'static str) {
+        if let Some(Frame::Node(ref mut node)) = self.stack.last_mut() {
+            node.fields.push((name, Value::Null));
+        }
+    }
+
+    fn end(&mut self) {
+        match self.stack.pop() {
+            Some(Frame::Node(node)) => self.value(Value::Node(node)),
+            _ => unreachable!("a node ends where it started"),
+        }
+    }
+
+    /// Sets the position of the node being built, and ends it.
+    fn end_at(&mut self, start: Location, end: Location) {
+        let position = [start.line, self.column(start), end.line, self.column(end)];
+        if let Some(Frame::Node(ref mut node)) = self.stack.last_mut() {
+            node.position = Some(position);
+        }
+        self.end();
+    }
+
+    fn column(&self, location: Location) -> usize {
+        let line = match self.lines {
+            Some(ref lines) if location.line >= 1 => lines.get(location.line - 1),
+            _ => None,
+        };
+        match line {
+            Some(line) => line
+                .char_indices()
+                .nth(location.column)
+                .map_or(line.len(), |(offset, _)| offset),
+            None => location.column,
+        }
+    }
+
+    fn string(&mut self, value: &str) {
+        self.value(Value::Str(value.to_string()));
+    }
+
+    fn optional_string(&mut self, value: &Option<String>) {
+        match *value {
+            Some(ref value) => self.string(value),
+            None => self.null(),
+        }
+    }
+
+    fn strings(&mut self, values: &[String]) {
+        self.list(values, |builder, value| builder.string(value));
+    }
+
+    fn raw<S: Into<String>>(&mut self, text: S) {
+        self.value(Value::Raw(text.into()));
+    }
+
+    fn flag(&mut self, value: bool) {
+        self.raw(if value { "1" } else { "0" });
+    }
+
+    fn null(&mut self) {
+        self.value(Value::Null);
+    }
+
+    fn list<T, F: FnMut(&mut Builder<'a>, &T)>(&mut self, values: &[T], mut build: F) {
+        self.stack
+            .push(Frame::List(Vec::with_capacity(values.len())));
+        for value in values {
+            build(self, value);
+        }
+        match self.stack.pop() {
+            Some(Frame::List(items)) => self.value(Value::List(items)),
+            _ => unreachable!("a list ends where it started"),
+        }
+    }
+
+    /// A node with no fields or position: an operator or a context.
+    fn unit(&mut self, name: &'static str) {
+        self.node(name);
+        self.end();
+    }
+
+    fn module(&mut self, module: &Module) {
+        self.node("Module");
+        self.field("body");
+        self.stmts(&module.body);
+        self.field("type_ignores");
+        self.list(&module.type_ignores, |builder, ignore| {
+            builder.node("TypeIgnore");
+            builder.field("lineno");
+            builder.raw(ignore.line.to_string());
+            builder.field("tag");
+            builder.string(&ignore.tag);
+            builder.end();
+        });
+        self.end();
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt]) {
+        self.list(stmts, Builder::stmt);
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        self.list(exprs, Builder::expr);
+    }
+
+    fn optional_expr(&mut self, expr: Option<&Expr>) {
+        match expr {
+            Some(expr) => self.expr(expr),
+            None => self.null(),
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::FunctionDef {
+                ref name,
+                ref args,
+                ref body,
+                ref decorator_list,
+                ref returns,
+                ref type_comment,
+                ref type_params,
+                is_async,
+            } => {
+                self.node(if is_async {
+                    "AsyncFunctionDef"
+                } else {
+                    "FunctionDef"
+                });
+                self.field("name");
+                self.string(name);
+                self.field("args");
+                self.arguments(args);
+                self.field("body");
+                self.stmts(body);
+                self.field("decorator_list");
+                self.exprs(decorator_list);
+                self.field("returns");
+                self.optional_expr(returns.as_deref());
+                self.field("type_comment");
+                self.optional_string(type_comment);
+                self.field("type_params");
+                self.list(type_params, Builder::type_param);
+            }
+            StmtKind::ClassDef {
+                ref name,
+                ref bases,
+                ref keywords,
+                ref body,
+                ref decorator_list,
+                ref type_params,
+            } => {
+                self.node("ClassDef");
+                self.field("name");
+                self.string(name);
+                self.field("bases");
+                self.exprs(bases);
+                self.field("keywords");
+                self.list(keywords, Builder::keyword);
+                self.field("body");
+                self.stmts(body);
+                self.field("decorator_list");
+                self.exprs(decorator_list);
+                self.field("type_params");
+                self.list(type_params, Builder::type_param);
+            }
+            StmtKind::Return(ref value) => {
+                self.node("Return");
+                self.field("value");
+                self.optional_expr(value.as_ref());
+            }
+            StmtKind::Delete(ref targets) => {
+                self.node("Delete");
+                self.field("targets");
+                self.exprs(targets);
+            }
+            StmtKind::Assign {
+                ref targets,
+                ref value,
+                ref type_comment,
+            } => {
+                self.node("Assign");
+                self.field("targets");
+                self.exprs(targets);
+                self.field("value");
+                self.expr(value);
+                self.field("type_comment");
+                self.optional_string(type_comment);
+            }
+            StmtKind::TypeAlias {
+                ref name,
+                ref type_params,
+                ref value,
+            } => {
+                self.node("TypeAlias");
+                self.field("name");
+                self.expr(name);
+                self.field("type_params");
+                self.list(type_params, Builder::type_param);
+                self.field("value");
+                self.expr(value);
+            }
+            StmtKind::AugAssign {
+                ref target,
+                op,
+                ref value,
+            } => {
+                self.node("AugAssign");
+                self.field("target");
+                self.expr(target);
+                self.field("op");
+                self.operator(op);
+                self.field("value");
+                self.expr(value);
+            }
+            StmtKind::AnnAssign {
+                ref target,
+                ref annotation,
+                ref value,
+                simple,
+            } => {
+                self.node("AnnAssign");
+                self.field("target");
+                self.expr(target);
+                self.field("annotation");
+                self.expr(annotation);
+                self.field("value");
+                self.optional_expr(value.as_ref());
+                self.field("simple");
+                self.flag(simple);
+            }
+            StmtKind::For {
+                ref target,
+                ref iter,
+                ref body,
+                ref orelse,
+                ref type_comment,
+                is_async,
+            } => {
+                self.node(if is_async { "AsyncFor" } else { "For" });
+                self.field("target");
+                self.expr(target);
+                self.field("iter");
+                self.expr(iter);
+                self.field("body");
+                self.stmts(body);
+                self.field("orelse");
+                self.stmts(orelse);
+                self.field("type_comment");
+                self.optional_string(type_comment);
+            }
+            StmtKind::While {
+                ref test,
+                ref body,
+                ref orelse,
+            }
+            | StmtKind::If {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                self.node(match stmt.kind {
+                    StmtKind::While { .. } => "While",
+                    _ => "If",
+                });
+                self.field("test");
+                self.expr(test);
+                self.field("body");
+                self.stmts(body);
+                self.field("orelse");
+                self.stmts(orelse);
+            }
+            StmtKind::With {
+                ref items,
+                ref body,
+                ref type_comment,
+                is_async,
+            } => {
+                self.node(if is_async { "AsyncWith" } else { "With" });
+                self.field("items");
+                self.list(items, Builder::with_item);
+                self.field("body");
+                self.stmts(body);
+                self.field("type_comment");
+                self.optional_string(type_comment);
+            }
+            StmtKind::Match {
+                ref subject,
+                ref cases,
+            } => {
+                self.node("Match");
+                self.field("subject");
+                self.expr(subject);
+                self.field("cases");
+                self.list(cases, Builder::match_case);
+            }
+            StmtKind::Raise { ref exc, ref cause } => {
+                self.node("Raise");
+                self.field("exc");
+                self.optional_expr(exc.as_ref());
+                self.field("cause");
+                self.optional_expr(cause.as_ref());
+            }
+            StmtKind::Try {
+                ref body,
+                ref handlers,
+                ref orelse,
+                ref finalbody,
+                is_star,
+            } => {
+                self.node(if is_star { "TryStar" } else { "Try" });
+                self.field("body");
+                self.stmts(body);
+                self.field("handlers");
+                self.list(handlers, Builder::except_handler);
+                self.field("orelse");
+                self.stmts(orelse);
+                self.field("finalbody");
+                self.stmts(finalbody);
+            }
+            StmtKind::Assert { ref test, ref msg } => {
+                self.node("Assert");
+                self.field("test");
+                self.expr(test);
+                self.field("msg");
+                self.optional_expr(msg.as_ref());
+            }
+            StmtKind::Import(ref names) => {
+                self.node("Import");
+                self.field("names");
+                self.list(names, Builder::alias);
+            }
+            StmtKind::ImportFrom {
+                ref module,
+                ref names,
+                level,
+            } => {
+                self.node("ImportFrom");
+                self.field("module");
+                self.optional_string(module);
+                self.field("names");
+                self.list(names, Builder::alias);
+                self.field("level");
+                self.raw(level.to_string());
+            }
+            StmtKind::Global(ref names) | StmtKind::Nonlocal(ref names) => {
+                self.node(match stmt.kind {
+                    StmtKind::Global(_) => "Global",
+                    _ => "Nonlocal",
+                });
+                self.field("names");
+                self.strings(names);
+            }
+            StmtKind::Expr(ref value) => {
+                self.node("Expr");
+                self.field("value");
+                self.expr(value);
+            }
+            StmtKind::Pass => self.node("Pass"),
+            StmtKind::Break => self.node("Break"),
+            StmtKind::Continue => self.node("Continue"),
+            StmtKind::Error => self.node("Error"),
+        }
+        self.end_at(stmt.start, stmt.end);
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr.kind {
+            ExprKind::BoolOp { op, ref values } => {
+                self.node("BoolOp");
+                self.field("op");
+                self.unit(match op {
+                    BoolOperator::And => "And",
+                    BoolOperator::Or => "Or",
+                });
+                self.field("values");
+                self.exprs(values);
+            }
+            ExprKind::NamedExpr {
+                ref target,
+                ref value,
+            } => {
+                self.node("NamedExpr");
+                self.field("target");
+                self.expr(target);
+                self.field("value");
+                self.expr(value);
+            }
+            ExprKind::BinOp {
+                ref left,
+                op,
+                ref right,
+            } => {
+                self.node("BinOp");
+                self.field("left");
+                self.expr(left);
+                self.field("op");
+                self.operator(op);
+                self.field("right");
+                self.expr(right);
+            }
+            ExprKind::UnaryOp { op, ref operand } => {
+                self.node("UnaryOp");
+                self.field("op");
+                self.unit(match op {
+                    UnaryOperator::Invert => "Invert",
+                    UnaryOperator::Not => "Not",
+                    UnaryOperator::UAdd => "UAdd",
+                    UnaryOperator::USub => "USub",
+                });
+                self.field("operand");
+                self.expr(operand);
+            }
+            ExprKind::Lambda { ref args, ref body } => {
+                self.node("Lambda");
+                self.field("args");
+                self.arguments(args);
+                self.field("body");
+                self.expr(body);
+            }
+            ExprKind::IfExp {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                self.node("IfExp");
+                self.field("test");
+                self.expr(test);
+                self.field("body");
+                self.expr(body);
+                self.field("orelse");
+                self.expr(orelse);
+            }
+            ExprKind::Dict {
+                ref keys,
+                ref values,
+            } => {
+                self.node("Dict");
+                self.field("keys");
+                self.list(keys, |builder, key| builder.optional_expr(key.as_ref()));
+                self.field("values");
+                self.exprs(values);
+            }
+            ExprKind::Set(ref elts) => {
+                self.node("Set");
+                self.field("elts");
+                self.exprs(elts);
+            }
+            ExprKind::ListComp {
+                ref elt,
+                ref generators,
+            }
+            | ExprKind::SetComp {
+                ref elt,
+                ref generators,
+            }
+            | ExprKind::GeneratorExp {
+                ref elt,
+                ref generators,
+            } => {
+                self.node(match expr.kind {
+                    ExprKind::ListComp { .. } => "ListComp",
+                    ExprKind::SetComp { .. } => "SetComp",
+                    _ => "GeneratorExp",
+                });
+                self.field("elt");
+                self.expr(elt);
+                self.field("generators");
+                self.list(generators, Builder::comprehension);
+            }
+            ExprKind::DictComp {
+                ref key,
+                ref value,
+                ref generators,
+            } => {
+                self.node("DictComp");
+                self.field("key");
+                self.expr(key);
+                self.field("value");
+                self.expr(value);
+                self.field("generators");
+                self.list(generators, Builder::comprehension);
+            }
+            ExprKind::Await(ref value) | ExprKind::YieldFrom(ref value) => {
+                self.node(match expr.kind {
+                    ExprKind::Await(_) => "Await",
+                    _ => "YieldFrom",
+                });
+                self.field("value");
+                self.expr(value);
+            }
+            ExprKind::Yield(ref value) => {
+                self.node("Yield");
+                self.field("value");
+                self.optional_expr(value.as_deref());
+            }
+            ExprKind::Compare {
+                ref left,
+                ref ops,
+                ref comparators,
+            } => {
+                self.node("Compare");
+                self.field("left");
+                self.expr(left);
+                self.field("ops");
+                self.list(ops, |builder, &op| {
+                    builder.unit(match op {
+                        CmpOperator::Eq => "Eq",
+                        CmpOperator::NotEq => "NotEq",
+                        CmpOperator::Lt => "Lt",
+                        CmpOperator::LtE => "LtE",
+                        CmpOperator::Gt => "Gt",
+                        CmpOperator::GtE => "GtE",
+                        CmpOperator::Is => "Is",
+                        CmpOperator::IsNot => "IsNot",
+                        CmpOperator::In => "In",
+                        CmpOperator::NotIn => "NotIn",
+                    })
+                });
+                self.field("comparators");
+                self.exprs(comparators);
+            }
+            ExprKind::Call {
+                ref func,
+                ref args,
+                ref keywords,
+            } => {
+                self.node("Call");
+                self.field("func");
+                self.expr(func);
+                self.field("args");
+                self.exprs(args);
+                self.field("keywords");
+                self.list(keywords, Builder::keyword);
+            }
+            ExprKind::FormattedValue {
+                ref value,
+                conversion,
+                ref format_spec,
+            } => {
+                self.node("FormattedValue");
+                self.field("value");
+                self.expr(value);
+                self.field("conversion");
+                match conversion {
+                    Some(conversion) => self.raw((conversion as u32).to_string()),
+                    None => self.raw("-1"),
+                }
+                self.field("format_spec");
+                self.optional_expr(format_spec.as_deref());
+            }
+            ExprKind::JoinedStr(ref values) => {
+                self.node("JoinedStr");
+                self.field("values");
+                self.exprs(values);
+            }
+            ExprKind::Constant(ref value) => {
+                self.node("Constant");
+                self.field("value");
+                self.constant(value);
+                self.field("kind");
+                self.null();
+            }
+            ExprKind::Attribute {
+                ref value,
+                ref attr,
+                ctx,
+            } => {
+                self.node("Attribute");
+                self.field("value");
+                self.expr(value);
+                self.field("attr");
+                self.string(attr);
+                self.field("ctx");
+                self.context(ctx);
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ctx,
+            } => {
+                self.node("Subscript");
+                self.field("value");
+                self.expr(value);
+                self.field("slice");
+                self.expr(slice);
+                self.field("ctx");
+                self.context(ctx);
+            }
+            ExprKind::Starred { ref value, ctx } => {
+                self.node("Starred");
+                self.field("value");
+                self.expr(value);
+                self.field("ctx");
+                self.context(ctx);
+            }
+            ExprKind::Name { ref id, ctx } => {
+                self.node("Name");
+                self.field("id");
+                self.string(id);
+                self.field("ctx");
+                self.context(ctx);
+            }
+            ExprKind::List { ref elts, ctx } | ExprKind::Tuple { ref elts, ctx } => {
+                self.node(match expr.kind {
+                    ExprKind::List { .. } => "List",
+                    _ => "Tuple",
+                });
+                self.field("elts");
+                self.exprs(elts);
+                self.field("ctx");
+                self.context(ctx);
+            }
+            ExprKind::Slice {
+                ref lower,
+                ref upper,
+                ref step,
+            } => {
+                self.node("Slice");
+                self.field("lower");
+                self.optional_expr(lower.as_deref());
+                self.field("upper");
+                self.optional_expr(upper.as_deref());
+                self.field("step");
+                self.optional_expr(step.as_deref());
+            }
+        }
+        self.end_at(expr.start, expr.end);
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match *constant {
+            Constant::None => self.null(),
+            Constant::Bool(value) => self.raw(if value { "true" } else { "false" }),
+            Constant::Str(ref value) => self.string(value),
+            Constant::Bytes(ref value) => match ::std::str::from_utf8(value) {
+                Ok(text) => self.string(text),
+                Err(_) => {
+                    let hex: String = value.iter().map(|byte| format!("{:02x}", byte)).collect();
+                    self.string(&hex);
+                }
+            },
+            Constant::Int(value) => self.raw(value.to_string()),
+            Constant::LongInt(ref digits) => self.raw(digits.as_str()),
+            Constant::Float(value) => self.float(value),
+            Constant::Complex { real, imag } => {
+                let imag = complex_part(imag) + "j";
+                let text = if real == 0.0 && real.is_sign_positive() {
+                    imag
+                } else if imag.starts_with('-') {
+                    format!("({}{})", complex_part(real), imag)
+                } else {
+                    format!("({}+{})", complex_part(real), imag)
+                };
+                self.string(&text);
+            }
+            Constant::Ellipsis => self.string("..."),
+            Constant::Tuple(ref items) => self.list(items, Builder::constant),
+        }
+    }
+
+    fn float(&mut self, value: f64) {
+        if value.is_nan() {
+            self.raw("NaN");
+        } else if value.is_infinite() {
+            self.raw(if value > 0.0 { "Infinity" } else { "-Infinity" });
+        } else {
+            self.raw(float_literal(value, true));
+        }
+    }
+
+    fn operator(&mut self, op: Operator) {
+        self.unit(match op {
+            Operator::Add => "Add",
+            Operator::Sub => "Sub",
+            Operator::Mult => "Mult",
+            Operator::MatMult => "MatMult",
+            Operator::Div => "Div",
+            Operator::Mod => "Mod",
+            Operator::Pow => "Pow",
+            Operator::LShift => "LShift",
+            Operator::RShift => "RShift",
+            Operator::BitOr => "BitOr",
+            Operator::BitXor => "BitXor",
+            Operator::BitAnd => "BitAnd",
+            Operator::FloorDiv => "FloorDiv",
+        });
+    }
+
+    fn context(&mut self, ctx: Context) {
+        self.unit(match ctx {
+            Context::Load => "Load",
+            Context::Store => "Store",
+            Context::Del => "Del",
+        });
+    }
+
+    fn comprehension(&mut self, comprehension: &Comprehension) {
+        self.node("comprehension");
+        self.field("target");
+        self.expr(&comprehension.target);
+        self.field("iter");
+        self.expr(&comprehension.iter);
+        self.field("ifs");
+        self.exprs(&comprehension.ifs);
+        self.field("is_async");
+        self.flag(comprehension.is_async);
+        self.end();
+    }
+
+    fn except_handler(&mut self, handler: &ExceptHandler) {
+        self.node("ExceptHandler");
+        self.field("type");
+        self.optional_expr(handler.type_.as_ref());
+        self.field("name");
+        self.optional_string(&handler.name);
+        self.field("body");
+        self.stmts(&handler.body);
+        self.end_at(handler.start, handler.end);
+    }
+
+    fn arguments(&mut self, arguments: &Arguments) {
+        self.node("arguments");
+        self.field("posonlyargs");
+        self.list(&arguments.posonlyargs, Builder::arg);
+        self.field("args");
+        self.list(&arguments.args, Builder::arg);
+        self.field("vararg");
+        self.optional_arg(arguments.vararg.as_ref());
+        self.field("kwonlyargs");
+        self.list(&arguments.kwonlyargs, Builder::arg);
+        self.field("kw_defaults");
+        self.list(&arguments.kw_defaults, |builder, default| {
+            builder.optional_expr(default.as_ref())
+        });
+        self.field("kwarg");
+        self.optional_arg(arguments.kwarg.as_ref());
+        self.field("defaults");
+        self.exprs(&arguments.defaults);
+        self.end();
+    }
+
+    fn optional_arg(&mut self, arg: Option<&Arg>) {
+        match arg {
+            Some(arg) => self.arg(arg),
+            None => self.null(),
+        }
+    }
+
+    fn arg(&mut self, arg: &Arg) {
+        self.node("arg");
+        self.field("arg");
+        self.string(&arg.arg);
+        self.field("annotation");
+        self.optional_expr(arg.annotation.as_deref());
+        self.field("type_comment");
+        self.optional_string(&arg.type_comment);
+        self.end_at(arg.start, arg.end);
+    }
+
+    fn type_param(&mut self, param: &TypeParam) {
+        match param.kind {
+            TypeParamKind::TypeVar {
+                ref name,
+                ref bound,
+            } => {
+                self.node("TypeVar");
+                self.field("name");
+                self.string(name);
+                self.field("bound");
+                self.optional_expr(bound.as_ref());
+            }
+            TypeParamKind::ParamSpec(ref name) => {
+                self.node("ParamSpec");
+                self.field("name");
+                self.string(name);
+            }
+            TypeParamKind::TypeVarTuple(ref name) => {
+                self.node("TypeVarTuple");
+                self.field("name");
+                self.string(name);
+            }
+        }
+        self.end_at(param.start, param.end);
+    }
+
+    fn keyword(&mut self, keyword: &Keyword) {
+        self.node("keyword");
+        self.field("arg");
+        self.optional_string(&keyword.arg);
+        self.field("value");
+        self.expr(&keyword.value);
+        self.end_at(keyword.start, keyword.end);
+    }
+
+    fn alias(&mut self, alias: &Alias) {
+        self.node("alias");
+        self.field("name");
+        self.string(&alias.name);
+        self.field("asname");
+        self.optional_string(&alias.asname);
+        self.end_at(alias.start, alias.end);
+    }
+
+    fn with_item(&mut self, item: &WithItem) {
+        self.node("withitem");
+        self.field("context_expr");
+        self.expr(&item.context_expr);
+        self.field("optional_vars");
+        self.optional_expr(item.optional_vars.as_ref());
+        self.end();
+    }
+
+    fn match_case(&mut self, case: &MatchCase) {
+        self.node("match_case");
+        self.field("pattern");
+        self.pattern(&case.pattern);
+        self.field("guard");
+        self.optional_expr(case.guard.as_ref());
+        self.field("body");
+        self.stmts(&case.body);
+        self.end();
+    }
+
+    fn patterns(&mut self, patterns: &[Pattern]) {
+        self.list(patterns, Builder::pattern);
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        match pattern.kind {
+            PatternKind::MatchValue(ref value) => {
+                self.node("MatchValue");
+                self.field("value");
+                self.expr(value);
+            }
+            PatternKind::MatchSingleton(ref value) => {
+                self.node("MatchSingleton");
+                self.field("value");
+                self.constant(value);
+            }
+            PatternKind::MatchSequence(ref patterns) => {
+                self.node("MatchSequence");
+                self.field("patterns");
+                self.patterns(patterns);
+            }
+            PatternKind::MatchMapping {
+                ref keys,
+                ref patterns,
+                ref rest,
+            } => {
+                self.node("MatchMapping");
+                self.field("keys");
+                self.exprs(keys);
+                self.field("patterns");
+                self.patterns(patterns);
+                self.field("rest");
+                self.optional_string(rest);
+            }
+            PatternKind::MatchClass {
+                ref cls,
+                ref patterns,
+                ref kwd_attrs,
+                ref kwd_patterns,
+            } => {
+                self.node("MatchClass");
+                self.field("cls");
+                self.expr(cls);
+                self.field("patterns");
+                self.patterns(patterns);
+                self.field("kwd_attrs");
+                self.strings(kwd_attrs);
+                self.field("kwd_patterns");
+                self.patterns(kwd_patterns);
+            }
+            PatternKind::MatchStar(ref name) => {
+                self.node("MatchStar");
+                self.field("name");
+                self.optional_string(name);
+            }
+            PatternKind::MatchAs {
+                ref pattern,
+                ref name,
+            } => {
+                self.node("MatchAs");
+                self.field("pattern");
+                match *pattern {
+                    Some(ref pattern) => self.pattern(pattern),
+                    None => self.null(),
+                }
+                self.field("name");
+                self.optional_string(name);
+            }
+            PatternKind::MatchOr(ref patterns) => {
+                self.node("MatchOr");
+                self.field("patterns");
+                self.patterns(patterns);
+            }
+        }
+        self.end_at(pattern.start, pattern.end);
+    }
+}
+
+/// A part of a complex number as Python's `str` of the number writes it.
+fn complex_part(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        (if value > 0.0 { "inf" } else { "-inf" }).to_string()
+    } else {
+        float_literal(value, false)
+    }
+}
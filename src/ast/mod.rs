@@ -0,0 +1,563 @@
+//! The abstract syntax tree, modelled on CPython's `ast` module.
+//!
+//! Node and field names follow CPython where Rust allows it (`type_` for
+//! `type`, `is_async` instead of separate `Async*` nodes). Every node records
+//! where it starts and ends in the source. Child nodes are boxed and owned
+//! by their parents; `arena::Arena` stores a tree's nodes in one vector
+//! instead, referring to children by `NodeId`.
+
+pub mod arena;
+mod docstring;
+pub(crate) mod json;
+mod unparse;
+
+pub use self::arena::{Arena, NodeId};
+pub(crate) use self::docstring::body_docstring;
+pub use self::docstring::{clean_docstring, get_docstring};
+pub use self::json::{to_json, to_json_with_source};
+pub(crate) use self::unparse::{float_literal, is_printable, repr_bytes, repr_str};
+pub use self::unparse::{unparse, unparse_constant, unparse_expr};
+
+use tokenizer::Location;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Module {
+    pub body: Vec<Stmt>,
+    /// The `# type: ignore` comments, kept only when parsing with
+    /// `ParserConfig::type_comments`.
+    pub type_ignores: Vec<TypeIgnore>,
+}
+
+/// A `# type: ignore` comment on `line`, with whatever follows `ignore`,
+/// such as `[attr]`, as its tag.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TypeIgnore {
+    pub line: usize,
+    pub tag: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Stmt {
+    pub kind: StmtKind,
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum StmtKind {
+    FunctionDef {
+        name: String,
+        args: Box<Arguments>,
+        body: Vec<Stmt>,
+        decorator_list: Vec<Expr>,
+        returns: Option<Box<Expr>>,
+        /// The type of a `# type:` comment after the colon or on the first
+        /// line of the body, such as `(int) -> str`. Type comments are only
+        /// kept when parsing with `ParserConfig::type_comments`, as are
+        /// those of the other statements and of parameters.
+        type_comment: Option<String>,
+        type_params: Vec<TypeParam>,
+        is_async: bool,
+    },
+    ClassDef {
+        name: String,
+        bases: Vec<Expr>,
+        keywords: Vec<Keyword>,
+        body: Vec<Stmt>,
+        decorator_list: Vec<Expr>,
+        type_params: Vec<TypeParam>,
+    },
+    Return(Option<Expr>),
+    Delete(Vec<Expr>),
+    Assign {
+        targets: Vec<Expr>,
+        value: Expr,
+        type_comment: Option<String>,
+    },
+    /// `type name[type_params] = value`. The name is a `Name` in the
+    /// `Store` context.
+    TypeAlias {
+        name: Expr,
+        type_params: Vec<TypeParam>,
+        value: Expr,
+    },
+    AugAssign {
+        target: Expr,
+        op: Operator,
+        value: Expr,
+    },
+    AnnAssign {
+        target: Expr,
+        annotation: Expr,
+        value: Option<Expr>,
+        /// Whether the target is a plain name, not in parentheses.
+        simple: bool,
+    },
+    For {
+        target: Expr,
+        iter: Expr,
+        body: Vec<Stmt>,
+        orelse: Vec<Stmt>,
+        type_comment: Option<String>,
+        is_async: bool,
+    },
+    While {
+        test: Expr,
+        body: Vec<Stmt>,
+        orelse: Vec<Stmt>,
+    },
+    If {
+        test: Expr,
+        body: Vec<Stmt>,
+        orelse: Vec<Stmt>,
+    },
+    With {
+        items: Vec<WithItem>,
+        body: Vec<Stmt>,
+        type_comment: Option<String>,
+        is_async: bool,
+    },
+    Match {
+        subject: Expr,
+        cases: Vec<MatchCase>,
+    },
+    Raise {
+        exc: Option<Expr>,
+        cause: Option<Expr>,
+    },
+    Try {
+        body: Vec<Stmt>,
+        handlers: Vec<ExceptHandler>,
+        orelse: Vec<Stmt>,
+        finalbody: Vec<Stmt>,
+        /// Whether the handlers are `except*` clauses, which match the
+        /// exceptions of an exception group.
+        is_star: bool,
+    },
+    Assert {
+        test: Expr,
+        msg: Option<Expr>,
+    },
+    Import(Vec<Alias>),
+    ImportFrom {
+        module: Option<String>,
+        names: Vec<Alias>,
+        /// The number of leading dots of a relative import.
+        level: usize,
+    },
+    Global(Vec<String>),
+    Nonlocal(Vec<String>),
+    Expr(Expr),
+    Pass,
+    Break,
+    Continue,
+    /// A statement or lines that could not be parsed, which
+    /// `parser::parse_recovering` skipped. No other parse gives one.
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ExprKind {
+    BoolOp {
+        op: BoolOperator,
+        values: Vec<Expr>,
+    },
+    NamedExpr {
+        target: Box<Expr>,
+        value: Box<Expr>,
+    },
+    BinOp {
+        left: Box<Expr>,
+        op: Operator,
+        right: Box<Expr>,
+    },
+    UnaryOp {
+        op: UnaryOperator,
+        operand: Box<Expr>,
+    },
+    Lambda {
+        args: Box<Arguments>,
+        body: Box<Expr>,
+    },
+    IfExp {
+        test: Box<Expr>,
+        body: Box<Expr>,
+        orelse: Box<Expr>,
+    },
+    /// A `None` key stands for a `**mapping` entry.
+    Dict {
+        keys: Vec<Option<Expr>>,
+        values: Vec<Expr>,
+    },
+    Set(Vec<Expr>),
+    ListComp {
+        elt: Box<Expr>,
+        generators: Vec<Comprehension>,
+    },
+    SetComp {
+        elt: Box<Expr>,
+        generators: Vec<Comprehension>,
+    },
+    DictComp {
+        key: Box<Expr>,
+        value: Box<Expr>,
+        generators: Vec<Comprehension>,
+    },
+    GeneratorExp {
+        elt: Box<Expr>,
+        generators: Vec<Comprehension>,
+    },
+    Await(Box<Expr>),
+    Yield(Option<Box<Expr>>),
+    YieldFrom(Box<Expr>),
+    Compare {
+        left: Box<Expr>,
+        ops: Vec<CmpOperator>,
+        comparators: Vec<Expr>,
+    },
+    Call {
+        func: Box<Expr>,
+        args: Vec<Expr>,
+        keywords: Vec<Keyword>,
+    },
+    /// A replacement field of an f-string. `conversion` is `'s'`, `'r'` or
+    /// `'a'` when given.
+    FormattedValue {
+        value: Box<Expr>,
+        conversion: Option<char>,
+        format_spec: Option<Box<Expr>>,
+    },
+    JoinedStr(Vec<Expr>),
+    Constant(Constant),
+    Attribute {
+        value: Box<Expr>,
+        attr: String,
+        ctx: Context,
+    },
+    Subscript {
+        value: Box<Expr>,
+        slice: Box<Expr>,
+        ctx: Context,
+    },
+    Starred {
+        value: Box<Expr>,
+        ctx: Context,
+    },
+    Name {
+        id: String,
+        ctx: Context,
+    },
+    List {
+        elts: Vec<Expr>,
+        ctx: Context,
+    },
+    Tuple {
+        elts: Vec<Expr>,
+        ctx: Context,
+    },
+    Slice {
+        lower: Option<Box<Expr>>,
+        upper: Option<Box<Expr>>,
+        step: Option<Box<Expr>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Constant {
+    None,
+    Bool(bool),
+    Str(String),
+    Bytes(Vec<u8>),
+    Int(i64),
+    /// An integer literal too large for `Int`, as decimal digits.
+    LongInt(String),
+    Float(f64),
+    Complex {
+        real: f64,
+        imag: f64,
+    },
+    Ellipsis,
+    /// A tuple of constants, which only the optimizer creates.
+    Tuple(Vec<Constant>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Context {
+    Load,
+    Store,
+    Del,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BoolOperator {
+    And,
+    Or,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Operator {
+    Add,
+    Sub,
+    Mult,
+    MatMult,
+    Div,
+    Mod,
+    Pow,
+    LShift,
+    RShift,
+    BitOr,
+    BitXor,
+    BitAnd,
+    FloorDiv,
+}
+
+impl Operator {
+    /// Maps a binary operator token, such as `+`, to its operator.
+    pub fn from_symbol(symbol: &str) -> Option<Operator> {
+        let op = match symbol {
+            "+" => Operator::Add,
+            "-" => Operator::Sub,
+            "*" => Operator::Mult,
+            "@" => Operator::MatMult,
+            "/" => Operator::Div,
+            "%" => Operator::Mod,
+            "**" => Operator::Pow,
+            "<<" => Operator::LShift,
+            ">>" => Operator::RShift,
+            "|" => Operator::BitOr,
+            "^" => Operator::BitXor,
+            "&" => Operator::BitAnd,
+            "//" => Operator::FloorDiv,
+            _ => return None,
+        };
+        Some(op)
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mult => "*",
+            Operator::MatMult => "@",
+            Operator::Div => "/",
+            Operator::Mod => "%",
+            Operator::Pow => "**",
+            Operator::LShift => "<<",
+            Operator::RShift => ">>",
+            Operator::BitOr => "|",
+            Operator::BitXor => "^",
+            Operator::BitAnd => "&",
+            Operator::FloorDiv => "//",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum UnaryOperator {
+    Invert,
+    Not,
+    UAdd,
+    USub,
+}
+
+impl UnaryOperator {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnaryOperator::Invert => "~",
+            UnaryOperator::Not => "not",
+            UnaryOperator::UAdd => "+",
+            UnaryOperator::USub => "-",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CmpOperator {
+    Eq,
+    NotEq,
+    Lt,
+    LtE,
+    Gt,
+    GtE,
+    Is,
+    IsNot,
+    In,
+    NotIn,
+}
+
+impl CmpOperator {
+    pub fn symbol(self) -> &'static str {
+        match self {
+            CmpOperator::Eq => "==",
+            CmpOperator::NotEq => "!=",
+            CmpOperator::Lt => "<",
+            CmpOperator::LtE => "<=",
+            CmpOperator::Gt => ">",
+            CmpOperator::GtE => ">=",
+            CmpOperator::Is => "is",
+            CmpOperator::IsNot => "is not",
+            CmpOperator::In => "in",
+            CmpOperator::NotIn => "not in",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Comprehension {
+    pub target: Expr,
+    pub iter: Expr,
+    pub ifs: Vec<Expr>,
+    pub is_async: bool,
+}
+
+/// A parameter of a generic function, class or type alias, such as the `T`
+/// of `def first[T](items: list[T]) -> T`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TypeParam {
+    pub kind: TypeParamKind,
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TypeParamKind {
+    /// `T`, or `T: bound`, whose bound may be a tuple of constraints.
+    TypeVar { name: String, bound: Option<Expr> },
+    /// `**P`.
+    ParamSpec(String),
+    /// `*Ts`.
+    TypeVarTuple(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MatchCase {
+    pub pattern: Pattern,
+    pub guard: Option<Expr>,
+    pub body: Vec<Stmt>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Pattern {
+    pub kind: PatternKind,
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PatternKind {
+    /// A literal or dotted name compared by equality.
+    MatchValue(Expr),
+    /// `None`, `True` or `False`, compared by identity.
+    MatchSingleton(Constant),
+    MatchSequence(Vec<Pattern>),
+    MatchMapping {
+        keys: Vec<Expr>,
+        patterns: Vec<Pattern>,
+        rest: Option<String>,
+    },
+    MatchClass {
+        cls: Expr,
+        patterns: Vec<Pattern>,
+        kwd_attrs: Vec<String>,
+        kwd_patterns: Vec<Pattern>,
+    },
+    /// `*name` in a sequence pattern; `None` for `*_`.
+    MatchStar(Option<String>),
+    /// A capture (`name`), `pattern as name`, or the wildcard `_` when both
+    /// are `None`.
+    MatchAs {
+        pattern: Option<Box<Pattern>>,
+        name: Option<String>,
+    },
+    MatchOr(Vec<Pattern>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ExceptHandler {
+    pub type_: Option<Expr>,
+    pub name: Option<String>,
+    pub body: Vec<Stmt>,
+    pub start: Location,
+    pub end: Location,
+}
+
+/// The parameters of a function or lambda.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Arguments {
+    pub posonlyargs: Vec<Arg>,
+    pub args: Vec<Arg>,
+    pub vararg: Option<Arg>,
+    pub kwonlyargs: Vec<Arg>,
+    /// Defaults of `kwonlyargs`, `None` where a parameter has none.
+    pub kw_defaults: Vec<Option<Expr>>,
+    pub kwarg: Option<Arg>,
+    /// Defaults of the last positional parameters.
+    pub defaults: Vec<Expr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Arg {
+    pub arg: String,
+    pub annotation: Option<Box<Expr>>,
+    pub type_comment: Option<String>,
+    pub start: Location,
+    pub end: Location,
+}
+
+/// A keyword argument of a call or class definition. `arg` is `None` for
+/// `**mapping`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Keyword {
+    pub arg: Option<String>,
+    pub value: Expr,
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Alias {
+    pub name: String,
+    pub asname: Option<String>,
+    pub start: Location,
+    pub end: Location,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct WithItem {
+    pub context_expr: Expr,
+    pub optional_vars: Option<Expr>,
+}
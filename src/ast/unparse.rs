@@ -0,0 +1,1280 @@
+use std::collections::HashMap;
+use std::mem;
+
+use parser::Precedence;
+
+use super::{
+    Arguments, Comprehension, Constant, ExceptHandler, Expr, ExprKind, MatchCase, Module, Pattern,
+    PatternKind, Stmt, StmtKind, TypeParam, TypeParamKind,
+};
+
+/// What CPython writes for an infinite float, a literal too large to
+/// represent.
+const INFINITY: &str = "1e309";
+
+const MULTI_QUOTES: &[&str] = &["\"\"\"", "'''"];
+const ALL_QUOTES: &[&str] = &["'", "\"", "\"\"\"", "'''"];
+
+/// Generates Python source for a module, as `ast.unparse` does.
+///
+/// Formatting and comments are not preserved, but parsing the result gives
+/// back an equivalent tree. Parentheses are written only where precedence
+/// requires them. Type comments of statements are written after them, or
+/// a `# type: ignore` comment instead on its line, as CPython writes
+/// them; those of parameters are left out.
+pub fn unparse(module: &Module) -> String {
+    let mut unparser = Unparser::new(false);
+    unparser.type_ignores = module
+        .type_ignores
+        .iter()
+        .map(|ignore| (ignore.line, format!("ignore{}", ignore.tag)))
+        .collect();
+    unparser.docstring_and_body(&module.body);
+    unparser.source
+}
+
+/// Generates Python source for a single expression.
+pub fn unparse_expr(expr: &Expr) -> String {
+    let mut unparser = Unparser::new(false);
+    unparser.expr(expr, Precedence::Test);
+    unparser.source
+}
+
+/// The source of a constant, which is its `repr` for most values.
+pub fn unparse_constant(constant: &Constant) -> String {
+    let mut unparser = Unparser::new(false);
+    unparser.constant(constant);
+    unparser.source
+}
+
+struct Unparser {
+    source: String,
+    indent: usize,
+    /// Set inside f-string replacement fields, which cannot contain a
+    /// backslash before Python 3.12.
+    avoid_backslashes: bool,
+    /// What follows `# type: ` in the `# type: ignore` comments, by line.
+    type_ignores: HashMap<usize, String>,
+}
+
+impl Unparser {
+    fn new(avoid_backslashes: bool) -> Unparser {
+        Unparser {
+            source: String::new(),
+            indent: 0,
+            avoid_backslashes,
+            type_ignores: HashMap::new(),
+        }
+    }
+
+    fn write(&mut self, text: &str) {
+        self.source.push_str(text);
+    }
+
+    fn maybe_newline(&mut self) {
+        if !self.source.is_empty() {
+            self.source.push('\n');
+        }
+    }
+
+    /// Starts a new line at the current indentation.
+    fn fill(&mut self, text: &str) {
+        self.maybe_newline();
+        for _ in 0..self.indent {
+            self.source.push_str("    ");
+        }
+        self.source.push_str(text);
+    }
+
+    /// Writes `:` and an indented block.
+    fn block(&mut self, body: &[Stmt]) {
+        self.typed_block(body, None);
+    }
+
+    /// Writes `:`, the comment of a statement that `type_comment` found,
+    /// and an indented block.
+    fn typed_block(&mut self, body: &[Stmt], comment: Option<String>) {
+        self.write(":");
+        self.write_type_comment(comment);
+        self.indent += 1;
+        self.statements(body);
+        self.indent -= 1;
+    }
+
+    fn statements(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            self.stmt(stmt);
+        }
+    }
+
+    /// Writes a body, with a leading docstring in triple quotes.
+    fn docstring_and_body(&mut self, body: &[Stmt]) {
+        if let Some(&Stmt {
+            kind: StmtKind::Expr(ref expr),
+            ..
+        }) = body.first()
+        {
+            if let ExprKind::Constant(Constant::Str(ref value)) = expr.kind {
+                self.fill("");
+                self.str_avoiding_backslashes(value, MULTI_QUOTES);
+                self.statements(&body[1..]);
+                return;
+            }
+        }
+        self.statements(body);
+    }
+
+    fn block_with_docstring(&mut self, body: &[Stmt], comment: Option<String>) {
+        self.write(":");
+        self.write_type_comment(comment);
+        self.indent += 1;
+        self.docstring_and_body(body);
+        self.indent -= 1;
+    }
+
+    /// The type comment to write for the statement on `line`: its
+    /// `# type: ignore` comment, if it has one, or else `type_comment`.
+    fn type_comment(&self, line: usize, type_comment: &Option<String>) -> Option<String> {
+        self.type_ignores
+            .get(&line)
+            .or(type_comment.as_ref())
+            .cloned()
+    }
+
+    fn write_type_comment(&mut self, comment: Option<String>) {
+        if let Some(comment) = comment {
+            self.write(" # type: ");
+            self.write(&comment);
+        }
+    }
+
+    fn separated<T, F>(&mut self, items: &[T], separator: &str, mut write: F)
+    where
+        F: FnMut(&mut Unparser, &T),
+    {
+        for (index, item) in items.iter().enumerate() {
+            if index > 0 {
+                self.write(separator);
+            }
+            write(self, item);
+        }
+    }
+
+    /// Comma-separated expressions, with a trailing comma after a single one
+    /// as a tuple needs.
+    fn items(&mut self, elts: &[Expr]) {
+        self.separated(elts, ", ", |this, elt| this.expr(elt, Precedence::Test));
+        if elts.len() == 1 {
+            self.write(",");
+        }
+    }
+
+    fn type_params(&mut self, params: &[TypeParam]) {
+        if params.is_empty() {
+            return;
+        }
+        self.write("[");
+        self.separated(params, ", ", |this, param| match param.kind {
+            TypeParamKind::TypeVar {
+                ref name,
+                ref bound,
+            } => {
+                this.write(name);
+                if let Some(ref bound) = *bound {
+                    this.write(": ");
+                    this.expr(bound, Precedence::Test);
+                }
+            }
+            TypeParamKind::ParamSpec(ref name) => {
+                this.write("**");
+                this.write(name);
+            }
+            TypeParamKind::TypeVarTuple(ref name) => {
+                this.write("*");
+                this.write(name);
+            }
+        });
+        self.write("]");
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt.kind {
+            StmtKind::FunctionDef {
+                ref name,
+                ref args,
+                ref body,
+                ref decorator_list,
+                ref returns,
+                ref type_comment,
+                ref type_params,
+                is_async,
+            } => {
+                self.decorators(decorator_list);
+                self.fill(if is_async { "async def " } else { "def " });
+                self.write(name);
+                self.type_params(type_params);
+                self.write("(");
+                self.arguments(args);
+                self.write(")");
+                if let Some(ref returns) = *returns {
+                    self.write(" -> ");
+                    self.expr(returns, Precedence::Test);
+                }
+                let comment = self.type_comment(stmt.start.line, type_comment);
+                self.block_with_docstring(body, comment);
+            }
+            StmtKind::ClassDef {
+                ref name,
+                ref bases,
+                ref keywords,
+                ref body,
+                ref decorator_list,
+                ref type_params,
+            } => {
+                self.decorators(decorator_list);
+                self.fill("class ");
+                self.write(name);
+                self.type_params(type_params);
+                if !bases.is_empty() || !keywords.is_empty() {
+                    self.write("(");
+                    self.call_arguments(bases, keywords);
+                    self.write(")");
+                }
+                self.block_with_docstring(body, None);
+            }
+            StmtKind::Return(ref value) => {
+                self.fill("return");
+                if let Some(ref value) = *value {
+                    self.write(" ");
+                    self.expr(value, Precedence::Test);
+                }
+            }
+            StmtKind::Delete(ref targets) => {
+                self.fill("del ");
+                self.separated(targets, ", ", |this, target| {
+                    this.expr(target, Precedence::Test)
+                });
+            }
+            StmtKind::Assign {
+                ref targets,
+                ref value,
+                ref type_comment,
+            } => {
+                self.fill("");
+                for target in targets {
+                    self.expr(target, Precedence::Tuple);
+                    self.write(" = ");
+                }
+                self.expr(value, Precedence::Test);
+                let comment = self.type_comment(stmt.start.line, type_comment);
+                self.write_type_comment(comment);
+            }
+            StmtKind::TypeAlias {
+                ref name,
+                ref type_params,
+                ref value,
+            } => {
+                self.fill("type ");
+                self.expr(name, Precedence::Test);
+                self.type_params(type_params);
+                self.write(" = ");
+                self.expr(value, Precedence::Test);
+            }
+            StmtKind::AugAssign {
+                ref target,
+                op,
+                ref value,
+            } => {
+                self.fill("");
+                self.expr(target, Precedence::Test);
+                self.write(" ");
+                self.write(op.symbol());
+                self.write("= ");
+                self.expr(value, Precedence::Test);
+            }
+            StmtKind::AnnAssign {
+                ref target,
+                ref annotation,
+                ref value,
+                simple,
+            } => {
+                self.fill("");
+                let parenthesize = !simple && matches!(target.kind, ExprKind::Name { .. });
+                self.parenthesize_if(parenthesize, |this| this.expr(target, Precedence::Test));
+                self.write(": ");
+                self.expr(annotation, Precedence::Test);
+                if let Some(ref value) = *value {
+                    self.write(" = ");
+                    self.expr(value, Precedence::Test);
+                }
+            }
+            StmtKind::For {
+                ref target,
+                ref iter,
+                ref body,
+                ref orelse,
+                ref type_comment,
+                is_async,
+            } => {
+                self.fill(if is_async { "async for " } else { "for " });
+                self.expr(target, Precedence::Tuple);
+                self.write(" in ");
+                self.expr(iter, Precedence::Test);
+                let comment = self.type_comment(stmt.start.line, type_comment);
+                self.typed_block(body, comment);
+                self.else_block(orelse);
+            }
+            StmtKind::While {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                self.fill("while ");
+                self.expr(test, Precedence::Test);
+                self.block(body);
+                self.else_block(orelse);
+            }
+            StmtKind::If {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                self.fill("if ");
+                self.expr(test, Precedence::Test);
+                self.block(body);
+                // Collapse an `else` holding only an `if` into `elif`.
+                let mut orelse = orelse;
+                while let [Stmt {
+                    kind:
+                        StmtKind::If {
+                            ref test,
+                            ref body,
+                            orelse: ref next,
+                        },
+                    ..
+                }] = orelse[..]
+                {
+                    self.fill("elif ");
+                    self.expr(test, Precedence::Test);
+                    self.block(body);
+                    orelse = next;
+                }
+                self.else_block(orelse);
+            }
+            StmtKind::With {
+                ref items,
+                ref body,
+                ref type_comment,
+                is_async,
+            } => {
+                self.fill(if is_async { "async with " } else { "with " });
+                self.separated(items, ", ", |this, item| {
+                    this.expr(&item.context_expr, Precedence::Test);
+                    if let Some(ref vars) = item.optional_vars {
+                        this.write(" as ");
+                        this.expr(vars, Precedence::Test);
+                    }
+                });
+                let comment = self.type_comment(stmt.start.line, type_comment);
+                self.typed_block(body, comment);
+            }
+            StmtKind::Match {
+                ref subject,
+                ref cases,
+            } => {
+                self.fill("match ");
+                self.expr(subject, Precedence::Test);
+                self.write(":");
+                self.indent += 1;
+                for case in cases {
+                    self.match_case(case);
+                }
+                self.indent -= 1;
+            }
+            StmtKind::Raise { ref exc, ref cause } => {
+                self.fill("raise");
+                if let Some(ref exc) = *exc {
+                    self.write(" ");
+                    self.expr(exc, Precedence::Test);
+                    if let Some(ref cause) = *cause {
+                        self.write(" from ");
+                        self.expr(cause, Precedence::Test);
+                    }
+                }
+            }
+            StmtKind::Try {
+                ref body,
+                ref handlers,
+                ref orelse,
+                ref finalbody,
+                is_star,
+            } => {
+                self.fill("try");
+                self.block(body);
+                for handler in handlers {
+                    self.except_handler(handler, is_star);
+                }
+                self.else_block(orelse);
+                if !finalbody.is_empty() {
+                    self.fill("finally");
+                    self.block(finalbody);
+                }
+            }
+            StmtKind::Assert { ref test, ref msg } => {
+                self.fill("assert ");
+                self.expr(test, Precedence::Test);
+                if let Some(ref msg) = *msg {
+                    self.write(", ");
+                    self.expr(msg, Precedence::Test);
+                }
+            }
+            StmtKind::Import(ref names) => {
+                self.fill("import ");
+                self.aliases(names);
+            }
+            StmtKind::ImportFrom {
+                ref module,
+                ref names,
+                level,
+            } => {
+                self.fill("from ");
+                for _ in 0..level {
+                    self.write(".");
+                }
+                if let Some(ref module) = *module {
+                    self.write(module);
+                }
+                self.write(" import ");
+                self.aliases(names);
+            }
+            StmtKind::Global(ref names) => {
+                self.fill("global ");
+                self.write(&names.join(", "));
+            }
+            StmtKind::Nonlocal(ref names) => {
+                self.fill("nonlocal ");
+                self.write(&names.join(", "));
+            }
+            StmtKind::Expr(ref value) => {
+                self.fill("");
+                self.expr(value, Precedence::Yield);
+            }
+            StmtKind::Pass => self.fill("pass"),
+            StmtKind::Break => self.fill("break"),
+            StmtKind::Continue => self.fill("continue"),
+            // The source of what could not be parsed is not kept, so this
+            // stands for it, and does not parse either.
+            StmtKind::Error => self.fill("<error>"),
+        }
+    }
+
+    /// Decorators, after a blank line separating the definition from what
+    /// comes before.
+    fn decorators(&mut self, decorator_list: &[Expr]) {
+        self.maybe_newline();
+        for decorator in decorator_list {
+            self.fill("@");
+            self.expr(decorator, Precedence::Test);
+        }
+    }
+
+    fn else_block(&mut self, orelse: &[Stmt]) {
+        if !orelse.is_empty() {
+            self.fill("else");
+            self.block(orelse);
+        }
+    }
+
+    fn except_handler(&mut self, handler: &ExceptHandler, is_star: bool) {
+        self.fill(if is_star { "except*" } else { "except" });
+        if let Some(ref type_) = handler.type_ {
+            self.write(" ");
+            self.expr(type_, Precedence::Test);
+        }
+        if let Some(ref name) = handler.name {
+            self.write(" as ");
+            self.write(name);
+        }
+        self.block(&handler.body);
+    }
+
+    fn aliases(&mut self, names: &[super::Alias]) {
+        self.separated(names, ", ", |this, alias| {
+            this.write(&alias.name);
+            if let Some(ref asname) = alias.asname {
+                this.write(" as ");
+                this.write(asname);
+            }
+        });
+    }
+
+    fn arguments(&mut self, arguments: &Arguments) {
+        let mut first = true;
+        let positional = arguments
+            .posonlyargs
+            .iter()
+            .chain(arguments.args.iter())
+            .collect::<Vec<_>>();
+        let without_default = positional.len() - arguments.defaults.len();
+        for (index, arg) in positional.iter().enumerate() {
+            if !mem::replace(&mut first, false) {
+                self.write(", ");
+            }
+            self.arg(arg);
+            if index >= without_default {
+                self.write("=");
+                self.expr(
+                    &arguments.defaults[index - without_default],
+                    Precedence::Test,
+                );
+            }
+            if index + 1 == arguments.posonlyargs.len() {
+                self.write(", /");
+            }
+        }
+        if arguments.vararg.is_some() || !arguments.kwonlyargs.is_empty() {
+            if !mem::replace(&mut first, false) {
+                self.write(", ");
+            }
+            self.write("*");
+            if let Some(ref vararg) = arguments.vararg {
+                self.arg(vararg);
+            }
+        }
+        for (arg, default) in arguments.kwonlyargs.iter().zip(&arguments.kw_defaults) {
+            self.write(", ");
+            self.arg(arg);
+            if let Some(ref default) = *default {
+                self.write("=");
+                self.expr(default, Precedence::Test);
+            }
+        }
+        if let Some(ref kwarg) = arguments.kwarg {
+            if !first {
+                self.write(", ");
+            }
+            self.write("**");
+            self.arg(kwarg);
+        }
+    }
+
+    fn arg(&mut self, arg: &super::Arg) {
+        self.write(&arg.arg);
+        if let Some(ref annotation) = arg.annotation {
+            self.write(": ");
+            self.expr(annotation, Precedence::Test);
+        }
+    }
+
+    fn call_arguments(&mut self, args: &[Expr], keywords: &[super::Keyword]) {
+        self.separated(args, ", ", |this, arg| this.expr(arg, Precedence::Test));
+        if !args.is_empty() && !keywords.is_empty() {
+            self.write(", ");
+        }
+        self.separated(keywords, ", ", |this, keyword| {
+            match keyword.arg {
+                Some(ref arg) => {
+                    this.write(arg);
+                    this.write("=");
+                }
+                None => this.write("**"),
+            }
+            this.expr(&keyword.value, Precedence::Test);
+        });
+    }
+
+    fn parenthesize_if<F: FnOnce(&mut Unparser)>(&mut self, condition: bool, write: F) {
+        if condition {
+            self.write("(");
+        }
+        write(self);
+        if condition {
+            self.write(")");
+        }
+    }
+
+    /// Writes an expression appearing where anything binding at least as
+    /// tightly as `precedence` can go without parentheses.
+    fn expr(&mut self, expr: &Expr, precedence: Precedence) {
+        match expr.kind {
+            ExprKind::BoolOp { op, ref values } => {
+                let (symbol, mut operand) = match op {
+                    super::BoolOperator::And => (" and ", Precedence::And),
+                    super::BoolOperator::Or => (" or ", Precedence::Or),
+                };
+                self.parenthesize_if(precedence > operand, |this| {
+                    // Each operand binds tighter than the last, so a nested
+                    // operation of the same kind keeps its parentheses.
+                    this.separated(values, symbol, |this, value| {
+                        operand = operand.next();
+                        this.expr(value, operand);
+                    });
+                });
+            }
+            ExprKind::NamedExpr {
+                ref target,
+                ref value,
+            } => {
+                self.parenthesize_if(precedence > Precedence::NamedExpr, |this| {
+                    this.expr(target, Precedence::Atom);
+                    this.write(" := ");
+                    this.expr(value, Precedence::Atom);
+                });
+            }
+            ExprKind::BinOp {
+                ref left,
+                op,
+                ref right,
+            } => {
+                let symbol = op.symbol();
+                let level = Precedence::of_binary_operator(symbol).unwrap();
+                let (left_level, right_level) = if level.is_right_associative() {
+                    (level.next(), level)
+                } else {
+                    (level, level.next())
+                };
+                self.parenthesize_if(precedence > level, |this| {
+                    this.expr(left, left_level);
+                    this.write(" ");
+                    this.write(symbol);
+                    this.write(" ");
+                    this.expr(right, right_level);
+                });
+            }
+            ExprKind::UnaryOp { op, ref operand } => {
+                let symbol = op.symbol();
+                let level = Precedence::of_unary_operator(symbol).unwrap();
+                self.parenthesize_if(precedence > level, |this| {
+                    this.write(symbol);
+                    // `+1`, but `not x`.
+                    if level != Precedence::Factor {
+                        this.write(" ");
+                    }
+                    this.expr(operand, level);
+                });
+            }
+            ExprKind::Lambda { ref args, ref body } => {
+                self.parenthesize_if(precedence > Precedence::Test, |this| {
+                    this.write("lambda");
+                    let mut parameters = Unparser::new(this.avoid_backslashes);
+                    parameters.arguments(args);
+                    if !parameters.source.is_empty() {
+                        this.write(" ");
+                        this.write(&parameters.source);
+                    }
+                    this.write(": ");
+                    this.expr(body, Precedence::Test);
+                });
+            }
+            ExprKind::IfExp {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                self.parenthesize_if(precedence > Precedence::Test, |this| {
+                    this.expr(body, Precedence::Test.next());
+                    this.write(" if ");
+                    this.expr(test, Precedence::Test.next());
+                    this.write(" else ");
+                    this.expr(orelse, Precedence::Test);
+                });
+            }
+            ExprKind::Dict {
+                ref keys,
+                ref values,
+            } => {
+                self.write("{");
+                for (index, (key, value)) in keys.iter().zip(values).enumerate() {
+                    if index > 0 {
+                        self.write(", ");
+                    }
+                    match *key {
+                        Some(ref key) => {
+                            self.expr(key, Precedence::Test);
+                            self.write(": ");
+                            self.expr(value, Precedence::Test);
+                        }
+                        None => {
+                            self.write("**");
+                            self.expr(value, Precedence::BitOr);
+                        }
+                    }
+                }
+                self.write("}");
+            }
+            ExprKind::Set(ref elts) => {
+                if elts.is_empty() {
+                    // `{}` is a dict, and `set` may be shadowed.
+                    self.write("{*()}");
+                } else {
+                    self.write("{");
+                    self.separated(elts, ", ", |this, elt| this.expr(elt, Precedence::Test));
+                    self.write("}");
+                }
+            }
+            ExprKind::ListComp {
+                ref elt,
+                ref generators,
+            } => {
+                self.write("[");
+                self.expr(elt, Precedence::Test);
+                self.generators(generators);
+                self.write("]");
+            }
+            ExprKind::SetComp {
+                ref elt,
+                ref generators,
+            } => {
+                self.write("{");
+                self.expr(elt, Precedence::Test);
+                self.generators(generators);
+                self.write("}");
+            }
+            ExprKind::DictComp {
+                ref key,
+                ref value,
+                ref generators,
+            } => {
+                self.write("{");
+                self.expr(key, Precedence::Test);
+                self.write(": ");
+                self.expr(value, Precedence::Test);
+                self.generators(generators);
+                self.write("}");
+            }
+            ExprKind::GeneratorExp {
+                ref elt,
+                ref generators,
+            } => {
+                self.write("(");
+                self.expr(elt, Precedence::Test);
+                self.generators(generators);
+                self.write(")");
+            }
+            ExprKind::Await(ref value) => {
+                self.parenthesize_if(precedence > Precedence::Await, |this| {
+                    this.write("await ");
+                    this.expr(value, Precedence::Atom);
+                });
+            }
+            ExprKind::Yield(ref value) => {
+                self.parenthesize_if(precedence > Precedence::Yield, |this| {
+                    this.write("yield");
+                    if let Some(ref value) = *value {
+                        this.write(" ");
+                        this.expr(value, Precedence::Atom);
+                    }
+                });
+            }
+            ExprKind::YieldFrom(ref value) => {
+                self.parenthesize_if(precedence > Precedence::Yield, |this| {
+                    this.write("yield from ");
+                    this.expr(value, Precedence::Atom);
+                });
+            }
+            ExprKind::Compare {
+                ref left,
+                ref ops,
+                ref comparators,
+            } => {
+                self.parenthesize_if(precedence > Precedence::Comparison, |this| {
+                    let operand = Precedence::Comparison.next();
+                    this.expr(left, operand);
+                    for (op, comparator) in ops.iter().zip(comparators) {
+                        this.write(" ");
+                        this.write(op.symbol());
+                        this.write(" ");
+                        this.expr(comparator, operand);
+                    }
+                });
+            }
+            ExprKind::Call {
+                ref func,
+                ref args,
+                ref keywords,
+            } => {
+                self.expr(func, Precedence::Atom);
+                self.write("(");
+                self.call_arguments(args, keywords);
+                self.write(")");
+            }
+            ExprKind::FormattedValue { .. } | ExprKind::JoinedStr(_) => self.fstring(expr),
+            ExprKind::Constant(ref constant) => {
+                let negative = match *constant {
+                    Constant::Int(value) => value < 0,
+                    Constant::Float(value) => value.is_sign_negative(),
+                    _ => false,
+                };
+                self.parenthesize_if(negative && precedence > Precedence::Factor, |this| {
+                    this.constant(constant)
+                });
+            }
+            ExprKind::Attribute {
+                ref value,
+                ref attr,
+                ..
+            } => {
+                self.expr(value, Precedence::Atom);
+                // `1.real` would lex as a float followed by a name.
+                if let ExprKind::Constant(Constant::Int(_))
+                | ExprKind::Constant(Constant::LongInt(_)) = value.kind
+                {
+                    self.write(" ");
+                }
+                self.write(".");
+                self.write(attr);
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => {
+                self.expr(value, Precedence::Atom);
+                self.write("[");
+                match slice.kind {
+                    // A non-empty tuple needs no parentheses in a subscript.
+                    ExprKind::Tuple { ref elts, .. } if !elts.is_empty() => self.items(elts),
+                    _ => self.expr(slice, Precedence::Test),
+                }
+                self.write("]");
+            }
+            ExprKind::Starred { ref value, .. } => {
+                self.write("*");
+                self.expr(value, Precedence::BitOr);
+            }
+            ExprKind::Name { ref id, .. } => self.write(id),
+            ExprKind::List { ref elts, .. } => {
+                self.write("[");
+                self.separated(elts, ", ", |this, elt| this.expr(elt, Precedence::Test));
+                self.write("]");
+            }
+            ExprKind::Tuple { ref elts, .. } => {
+                self.parenthesize_if(elts.is_empty() || precedence > Precedence::Tuple, |this| {
+                    this.items(elts)
+                });
+            }
+            ExprKind::Slice {
+                ref lower,
+                ref upper,
+                ref step,
+            } => {
+                if let Some(ref lower) = *lower {
+                    self.expr(lower, Precedence::Test);
+                }
+                self.write(":");
+                if let Some(ref upper) = *upper {
+                    self.expr(upper, Precedence::Test);
+                }
+                if let Some(ref step) = *step {
+                    self.write(":");
+                    self.expr(step, Precedence::Test);
+                }
+            }
+        }
+    }
+
+    fn generators(&mut self, generators: &[Comprehension]) {
+        for generator in generators {
+            self.write(if generator.is_async {
+                " async for "
+            } else {
+                " for "
+            });
+            self.expr(&generator.target, Precedence::Tuple);
+            self.write(" in ");
+            self.expr(&generator.iter, Precedence::Test.next());
+            for condition in &generator.ifs {
+                self.write(" if ");
+                self.expr(condition, Precedence::Test.next());
+            }
+        }
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match *constant {
+            Constant::None => self.write("None"),
+            Constant::Bool(true) => self.write("True"),
+            Constant::Bool(false) => self.write("False"),
+            Constant::Str(ref value) if self.avoid_backslashes => {
+                self.str_avoiding_backslashes(value, ALL_QUOTES)
+            }
+            Constant::Str(ref value) => self.write(&repr_str(value)),
+            Constant::Bytes(ref value) => self.write(&repr_bytes(value)),
+            Constant::Int(value) => self.write(&value.to_string()),
+            Constant::LongInt(ref digits) => self.write(digits),
+            Constant::Float(value) => self.write(&float_literal(value, true)),
+            Constant::Complex { real, imag } => {
+                let imag = float_literal(imag, false) + "j";
+                if real == 0.0 && real.is_sign_positive() {
+                    self.write(&imag);
+                } else {
+                    self.write("(");
+                    self.write(&float_literal(real, false));
+                    if !imag.starts_with('-') {
+                        self.write("+");
+                    }
+                    self.write(&imag);
+                    self.write(")");
+                }
+            }
+            Constant::Ellipsis => self.write("..."),
+            Constant::Tuple(ref elts) => {
+                self.write("(");
+                self.separated(elts, ", ", |this, elt| this.constant(elt));
+                if elts.len() == 1 {
+                    self.write(",");
+                }
+                self.write(")");
+            }
+        }
+    }
+
+    fn str_avoiding_backslashes(&mut self, value: &str, quote_types: &[&'static str]) {
+        let (literal, quotes) = str_literal(value, quote_types, false);
+        self.write(quotes[0]);
+        self.write(&literal);
+        self.write(quotes[0]);
+    }
+
+    fn fstring(&mut self, expr: &Expr) {
+        self.write("f");
+        let parts = match expr.kind {
+            ExprKind::JoinedStr(ref values) => &values[..],
+            _ => ::std::slice::from_ref(expr),
+        };
+        if self.avoid_backslashes {
+            let mut inner = String::new();
+            for part in parts {
+                fstring_inner(part, &mut inner);
+            }
+            self.str_avoiding_backslashes(&inner, ALL_QUOTES);
+            return;
+        }
+        // Escapes are only forbidden inside replacement fields, so literal
+        // text can keep `\n` rather than forcing triple quotes.
+        let mut quote_types = ALL_QUOTES.to_vec();
+        let mut literal = String::new();
+        for part in parts {
+            let mut inner = String::new();
+            fstring_inner(part, &mut inner);
+            let is_constant = matches!(part.kind, ExprKind::Constant(_));
+            let (escaped, quotes) = str_literal(&inner, &quote_types, is_constant);
+            literal.push_str(&escaped);
+            quote_types = quotes;
+        }
+        self.write(quote_types[0]);
+        self.write(&literal);
+        self.write(quote_types[0]);
+    }
+
+    fn match_case(&mut self, case: &MatchCase) {
+        self.fill("case ");
+        self.pattern(&case.pattern, Precedence::Test);
+        if let Some(ref guard) = case.guard {
+            self.write(" if ");
+            self.expr(guard, Precedence::Test);
+        }
+        self.block(&case.body);
+    }
+
+    fn pattern(&mut self, pattern: &Pattern, precedence: Precedence) {
+        match pattern.kind {
+            PatternKind::MatchValue(ref value) => self.expr(value, Precedence::Test),
+            PatternKind::MatchSingleton(ref value) => self.constant(value),
+            PatternKind::MatchSequence(ref patterns) => {
+                self.write("[");
+                self.separated(patterns, ", ", |this, pattern| {
+                    this.pattern(pattern, Precedence::Test)
+                });
+                self.write("]");
+            }
+            PatternKind::MatchMapping {
+                ref keys,
+                ref patterns,
+                ref rest,
+            } => {
+                self.write("{");
+                for (index, (key, pattern)) in keys.iter().zip(patterns).enumerate() {
+                    if index > 0 {
+                        self.write(", ");
+                    }
+                    self.expr(key, Precedence::Test);
+                    self.write(": ");
+                    self.pattern(pattern, Precedence::Test);
+                }
+                if let Some(ref rest) = *rest {
+                    if !keys.is_empty() {
+                        self.write(", ");
+                    }
+                    self.write("**");
+                    self.write(rest);
+                }
+                self.write("}");
+            }
+            PatternKind::MatchClass {
+                ref cls,
+                ref patterns,
+                ref kwd_attrs,
+                ref kwd_patterns,
+            } => {
+                self.expr(cls, Precedence::Atom);
+                self.write("(");
+                self.separated(patterns, ", ", |this, pattern| {
+                    this.pattern(pattern, Precedence::Test)
+                });
+                for (index, (attr, pattern)) in kwd_attrs.iter().zip(kwd_patterns).enumerate() {
+                    if index > 0 || !patterns.is_empty() {
+                        self.write(", ");
+                    }
+                    self.write(attr);
+                    self.write("=");
+                    self.pattern(pattern, Precedence::Test);
+                }
+                self.write(")");
+            }
+            PatternKind::MatchStar(ref name) => {
+                self.write("*");
+                self.write(name.as_ref().map_or("_", |name| &name[..]));
+            }
+            PatternKind::MatchAs {
+                ref pattern,
+                ref name,
+            } => match (pattern.as_ref(), name.as_ref()) {
+                (_, None) => self.write("_"),
+                (None, Some(name)) => self.write(name),
+                (Some(pattern), Some(name)) => {
+                    self.parenthesize_if(precedence > Precedence::Test, |this| {
+                        this.pattern(pattern, Precedence::BitOr);
+                        this.write(" as ");
+                        this.write(name);
+                    });
+                }
+            },
+            PatternKind::MatchOr(ref patterns) => {
+                self.parenthesize_if(precedence > Precedence::BitOr, |this| {
+                    this.separated(patterns, " | ", |this, pattern| {
+                        this.pattern(pattern, Precedence::BitOr.next())
+                    });
+                });
+            }
+        }
+    }
+}
+
+/// The text of an f-string part, with braces in literal text doubled and
+/// replacement fields written out.
+fn fstring_inner(expr: &Expr, out: &mut String) {
+    match expr.kind {
+        ExprKind::JoinedStr(ref values) => {
+            for value in values {
+                fstring_inner(value, out);
+            }
+        }
+        ExprKind::Constant(Constant::Str(ref value)) => {
+            out.push_str(&value.replace('{', "{{").replace('}', "}}"));
+        }
+        ExprKind::FormattedValue {
+            ref value,
+            conversion,
+            ref format_spec,
+        } => {
+            let mut unparser = Unparser::new(true);
+            unparser.expr(value, Precedence::Test.next());
+            out.push('{');
+            // `{{` would be an escaped brace.
+            if unparser.source.starts_with('{') {
+                out.push(' ');
+            }
+            out.push_str(&unparser.source);
+            if let Some(conversion) = conversion {
+                out.push('!');
+                out.push(conversion);
+            }
+            if let Some(ref format_spec) = *format_spec {
+                out.push(':');
+                fstring_inner(format_spec, out);
+            }
+            out.push('}');
+        }
+        _ => {}
+    }
+}
+
+/// Escapes a string for a literal, choosing among `quote_types` those that
+/// need no further escaping. Returns the escaped text and the usable quotes,
+/// best first.
+fn str_literal(
+    value: &str,
+    quote_types: &[&'static str],
+    escape_special_whitespace: bool,
+) -> (String, Vec<&'static str>) {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if !escape_special_whitespace && (c == '\n' || c == '\t') {
+            escaped.push(c);
+        } else if c == '\\' || !is_printable(c) {
+            unicode_escape(c, &mut escaped);
+        } else {
+            escaped.push(c);
+        }
+    }
+    let multiline = escaped.contains('\n');
+    let mut quotes = quote_types
+        .iter()
+        .cloned()
+        .filter(|quote| !multiline || MULTI_QUOTES.contains(quote))
+        .filter(|quote| !escaped.contains(quote))
+        .collect::<Vec<_>>();
+    if quotes.is_empty() {
+        let repr = repr_str(value);
+        let first = &repr[..1];
+        let quote = quote_types
+            .iter()
+            .cloned()
+            .find(|quote| quote.contains(first))
+            .unwrap_or(if first == "'" { "'" } else { "\"" });
+        return (repr[1..repr.len() - 1].to_string(), vec![quote]);
+    }
+    if let Some(last) = escaped.chars().last() {
+        // Prefer a quote that differs from the final character, and escape
+        // that character when only triple quotes of its kind remain.
+        quotes.sort_by_key(|quote| quote.starts_with(last));
+        if quotes[0].starts_with(last) {
+            escaped.pop();
+            escaped.push('\\');
+            escaped.push(last);
+        }
+    }
+    (escaped, quotes)
+}
+
+/// Whether Python's `str.isprintable` holds for a character. Separators,
+/// control and format characters and private use characters are not
+/// printable.
+pub(crate) fn is_printable(c: char) -> bool {
+    if c == ' ' {
+        return true;
+    }
+    !(c.is_control()
+        || c.is_whitespace()
+        || matches!(
+            c,
+            '\u{ad}'
+                | '\u{600}'..='\u{605}'
+                | '\u{61c}'
+                | '\u{6dd}'
+                | '\u{70f}'
+                | '\u{180e}'
+                | '\u{200b}'..='\u{200f}'
+                | '\u{202a}'..='\u{202e}'
+                | '\u{2060}'..='\u{2064}'
+                | '\u{2066}'..='\u{206f}'
+                | '\u{e000}'..='\u{f8ff}'
+                | '\u{feff}'
+                | '\u{fff9}'..='\u{fffb}'
+                | '\u{fffe}'..='\u{ffff}'
+                | '\u{f0000}'..='\u{10ffff}'
+        ))
+}
+
+/// Writes a character the way Python's `unicode_escape` codec does.
+fn unicode_escape(c: char, out: &mut String) {
+    match c {
+        '\\' => out.push_str("\\\\"),
+        '\t' => out.push_str("\\t"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        ' '..='~' => out.push(c),
+        '\0'..='\u{ff}' => out.push_str(&format!("\\x{:02x}", c as u32)),
+        '\u{100}'..='\u{ffff}' => out.push_str(&format!("\\u{:04x}", c as u32)),
+        _ => out.push_str(&format!("\\U{:08x}", c as u32)),
+    }
+}
+
+/// The `repr` of a string: single quotes unless the string contains a
+/// single quote and no double quote.
+pub(crate) fn repr_str(value: &str) -> String {
+    let quote = if value.contains('\'') && !value.contains('"') {
+        '"'
+    } else {
+        '\''
+    };
+    let mut repr = String::with_capacity(value.len() + 2);
+    repr.push(quote);
+    for c in value.chars() {
+        if c == quote {
+            repr.push('\\');
+            repr.push(c);
+        } else if c == '\\' || !is_printable(c) {
+            unicode_escape(c, &mut repr);
+        } else {
+            repr.push(c);
+        }
+    }
+    repr.push(quote);
+    repr
+}
+
+/// The `repr` of a bytes object.
+pub(crate) fn repr_bytes(value: &[u8]) -> String {
+    let quote = if value.contains(&b'\'') && !value.contains(&b'"') {
+        b'"'
+    } else {
+        b'\''
+    };
+    let mut repr = String::with_capacity(value.len() + 3);
+    repr.push('b');
+    repr.push(quote as char);
+    for &byte in value {
+        match byte {
+            b'\\' => repr.push_str("\\\\"),
+            b'\t' => repr.push_str("\\t"),
+            b'\n' => repr.push_str("\\n"),
+            b'\r' => repr.push_str("\\r"),
+            _ if byte == quote => {
+                repr.push('\\');
+                repr.push(byte as char);
+            }
+            b' '..=b'~' => repr.push(byte as char),
+            _ => repr.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    repr.push(quote as char);
+    repr
+}
+
+/// A float as Python's `repr` writes it, with infinities written as a
+/// literal that overflows to infinity. `point` adds `.0` to integral values
+/// in positional notation, which `repr` does for floats but not for the
+/// parts of a complex number.
+pub(crate) fn float_literal(value: f64, point: bool) -> String {
+    let sign = if value.is_sign_negative() { "-" } else { "" };
+    if value.is_nan() {
+        return format!("({}-{})", INFINITY, INFINITY);
+    }
+    if value.is_infinite() {
+        return format!("{}{}", sign, INFINITY);
+    }
+    // Rust's `{:e}` gives the shortest digits that round-trip, as `repr`
+    // does, leaving only the layout to adjust.
+    let scientific = format!("{:e}", value.abs());
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let digits = mantissa.replace('.', "");
+    let exponent: i32 = exponent[1..].parse().unwrap();
+    let text = if (-4..16).contains(&exponent) {
+        let point_position = exponent + 1;
+        if point_position <= 0 {
+            format!("0.{}{}", "0".repeat(-point_position as usize), digits)
+        } else if point_position as usize >= digits.len() {
+            let zeros = "0".repeat(point_position as usize - digits.len());
+            format!("{}{}{}", digits, zeros, if point { ".0" } else { "" })
+        } else {
+            let (whole, fraction) = digits.split_at(point_position as usize);
+            format!("{}.{}", whole, fraction)
+        }
+    } else {
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", mantissa, exponent_sign, exponent.abs())
+    };
+    format!("{}{}", sign, text)
+}
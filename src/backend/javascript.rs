@@ -0,0 +1,2116 @@
+//! An experimental backend that lowers a small subset of Python to readable
+//! JavaScript.
+//!
+//! The subset is arithmetic and comparisons, `if`, `while` and `for`,
+//! functions and lambdas, classes with at most one base and no metaclass,
+//! f-strings, and lists, tuples, sets and string-keyed dicts. Python
+//! builtins such as `print` and `range` become small functions written out
+//! at the top of the program. Anything else is a `TranspileError` rather
+//! than a guess.
+//!
+//! The output keeps JavaScript's meaning where the two languages differ in
+//! ways a readable translation cannot hide: numbers are doubles, so
+//! integers are exact only up to 2**53 and bitwise operators work on 32
+//! bits; `==` compares lists by identity; empty lists are true; and class
+//! attributes are static fields, read through the class.
+//!
+//! Where Python's operators mean different things for different types, the
+//! backend lowers them by the type it can tell the operands have: from
+//! literals, annotations, the builtins that make them, and variables whose
+//! every assignment gives one type. `in` and `*` on operands whose types it
+//! cannot tell are a `TranspileError`, and floats are shown as Python shows
+//! them, as in `3.0`, where it can tell they are floats.
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use ast::{
+    float_literal, Arguments, BoolOperator, CmpOperator, Constant, Expr, ExprKind, Module,
+    Operator, Stmt, StmtKind, UnaryOperator,
+};
+use tokenizer::Location;
+
+use super::Backend;
+
+/// The JavaScript backend.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct JavaScript;
+
+impl Backend for JavaScript {
+    type Output = String;
+    type Error = TranspileError;
+
+    fn name(&self) -> &'static str {
+        "javascript"
+    }
+
+    fn compile(&self, module: &Module, _filename: &str) -> Result<String> {
+        transpile(module)
+    }
+}
+
+/// A construct outside the subset the JavaScript backend supports.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranspileError {
+    pub message: String,
+    pub location: Location,
+}
+
+impl TranspileError {
+    pub fn new<S: Into<String>>(message: S, location: Location) -> TranspileError {
+        TranspileError {
+            message: message.into(),
+            location,
+        }
+    }
+}
+
+impl fmt::Display for TranspileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} (line {}, column {})",
+            self.message, self.location.line, self.location.column
+        )
+    }
+}
+
+impl Error for TranspileError {}
+
+type Result<T> = ::std::result::Result<T, TranspileError>;
+
+/// Lowers `module` to a JavaScript program.
+pub fn transpile(module: &Module) -> Result<String> {
+    let mut classes = HashSet::new();
+    collect_classes(&module.body, &mut classes);
+    let mut module_names: HashSet<String> =
+        declared_names(&module.body, true).into_iter().collect();
+    let mut returns = HashMap::new();
+    for stmt in &module.body {
+        match stmt.kind {
+            StmtKind::FunctionDef {
+                ref name,
+                returns: ref annotation,
+                ..
+            } => {
+                module_names.insert(name.clone());
+                if let Some(ref annotation) = *annotation {
+                    returns.insert(name.clone(), Type::of_annotation(annotation));
+                }
+            }
+            StmtKind::ClassDef { ref name, .. } => {
+                module_names.insert(name.clone());
+            }
+            _ => {}
+        }
+    }
+    let mut transpiler = Transpiler {
+        out: String::new(),
+        indent: 0,
+        classes,
+        module_names,
+        helpers: Vec::new(),
+        this: None,
+        this_is_class: false,
+        returns,
+        scopes: Vec::new(),
+    };
+    let scope = transpiler.scope(&module.body, HashMap::new());
+    transpiler.scopes.push(scope);
+    transpiler.declarations(&module.body, true, &[]);
+    let mut previous_definition = false;
+    for (position, stmt) in module.body.iter().enumerate() {
+        let definition = matches!(
+            stmt.kind,
+            StmtKind::FunctionDef { .. } | StmtKind::ClassDef { .. }
+        );
+        if position > 0 && (definition || previous_definition) {
+            transpiler.out.push('\n');
+        }
+        previous_definition = definition;
+        transpiler.stmt(stmt, true)?;
+    }
+
+    let mut program = String::new();
+    for &(name, _, code) in HELPERS {
+        if transpiler.helpers.contains(&name) {
+            program.push_str(code);
+            program.push('\n');
+        }
+    }
+    program.push_str(&transpiler.out);
+    Ok(program)
+}
+
+/// The functions that stand in for Python builtins, in the order they are
+/// written out, with the helpers each one calls.
+const HELPERS: &[(&str, &[&str], &str)] = &[
+    (
+        "print",
+        &["str"],
+        "function print(...values) {
+  console.log(values.map(str).join(\" \"));
+}
+",
+    ),
+    (
+        "str",
+        &["repr"],
+        "function str(value) {
+  if (value === null || value === undefined) {
+    return \"None\";
+  }
+  if (typeof value === \"boolean\") {
+    return value ? \"True\" : \"False\";
+  }
+  if (Array.isArray(value)) {
+    return \"[\" + value.map(repr).join(\", \") + \"]\";
+  }
+  return String(value);
+}
+",
+    ),
+    (
+        "repr",
+        &["str"],
+        "function repr(value) {
+  return typeof value === \"string\" ? \"'\" + value + \"'\" : str(value);
+}
+",
+    ),
+    (
+        "len",
+        &[],
+        "function len(value) {
+  if (value instanceof Set || value instanceof Map) {
+    return value.size;
+  }
+  if (typeof value === \"object\" && !Array.isArray(value)) {
+    return Object.keys(value).length;
+  }
+  return value.length;
+}
+",
+    ),
+    (
+        "range",
+        &[],
+        "function range(start, stop, step = 1) {
+  if (stop === undefined) {
+    [start, stop] = [0, start];
+  }
+  const values = [];
+  for (let i = start; step > 0 ? i < stop : i > stop; i += step) {
+    values.push(i);
+  }
+  return values;
+}
+",
+    ),
+    (
+        "int",
+        &[],
+        "function int(value) {
+  return Math.trunc(Number(value));
+}
+",
+    ),
+    (
+        "reprFloat",
+        &[],
+        "// A float as Python shows it, which JavaScript shows as an integer if
+// it is one, and in exponent notation from 1e21 rather than from 1e16.
+function reprFloat(value) {
+  if (!Number.isFinite(value)) {
+    return Number.isNaN(value) ? \"nan\" : value > 0 ? \"inf\" : \"-inf\";
+  }
+  const [digits, exponent] = value.toExponential().split(\"e\");
+  const power = Number(exponent);
+  if (power < -4 || power >= 16) {
+    const sign = power < 0 ? \"-\" : \"+\";
+    return digits + \"e\" + sign + String(Math.abs(power)).padStart(2, \"0\");
+  }
+  const text = Object.is(value, -0) ? \"-0\" : String(value);
+  return text.includes(\".\") ? text : text + \".0\";
+}
+",
+    ),
+    (
+        "mod",
+        &[],
+        "// Python's `%`, whose result takes the sign of the divisor.
+function mod(a, b) {
+  return ((a % b) + b) % b;
+}
+",
+    ),
+];
+
+/// Builtin exceptions, which are all raised as a plain `Error`.
+const EXCEPTIONS: &[&str] = &[
+    "ArithmeticError",
+    "AssertionError",
+    "AttributeError",
+    "Exception",
+    "IndexError",
+    "KeyError",
+    "LookupError",
+    "NameError",
+    "NotImplementedError",
+    "RuntimeError",
+    "TypeError",
+    "ValueError",
+    "ZeroDivisionError",
+];
+
+/// Words JavaScript reserves that Python allows as names. Names that are
+/// one of these get a `_` appended.
+const RESERVED: &[&str] = &[
+    "Infinity",
+    "NaN",
+    "arguments",
+    "case",
+    "catch",
+    "const",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "enum",
+    "eval",
+    "export",
+    "extends",
+    "false",
+    "function",
+    "implements",
+    "instanceof",
+    "interface",
+    "let",
+    "new",
+    "null",
+    "package",
+    "private",
+    "protected",
+    "public",
+    "static",
+    "super",
+    "switch",
+    "this",
+    "throw",
+    "true",
+    "typeof",
+    "undefined",
+    "var",
+    "void",
+];
+
+/// The largest integer a double holds exactly.
+const MAX_SAFE_INTEGER: i64 = (1 << 53) - 1;
+
+// How tightly JavaScript operators bind, loosest first.
+const ASSIGNMENT: u8 = 2;
+const CONDITIONAL: u8 = 3;
+const OR: u8 = 4;
+const AND: u8 = 5;
+const BIT_OR: u8 = 6;
+const BIT_XOR: u8 = 7;
+const BIT_AND: u8 = 8;
+const EQUALITY: u8 = 9;
+const RELATIONAL: u8 = 10;
+const SHIFT: u8 = 11;
+const ADDITIVE: u8 = 12;
+const MULTIPLICATIVE: u8 = 13;
+const EXPONENT: u8 = 14;
+const UNARY: u8 = 15;
+const CALL: u8 = 17;
+const PRIMARY: u8 = 18;
+
+struct Transpiler {
+    out: String,
+    indent: usize,
+    /// The classes the module defines, whose calls become `new`.
+    classes: HashSet<String>,
+    /// The names the module binds, which hide the builtins of the same name.
+    module_names: HashSet<String>,
+    /// The helpers the program calls.
+    helpers: Vec<&'static str>,
+    /// The name of `self` in the method being lowered, which becomes
+    /// `this`.
+    this: Option<String>,
+    /// Whether `this` is a class, in a classmethod.
+    this_is_class: bool,
+    /// The types the module's functions are annotated to return.
+    returns: HashMap<String, Type>,
+    /// The types of the variables of the module and of each function being
+    /// lowered in it, innermost last.
+    scopes: Vec<HashMap<String, Type>>,
+}
+
+/// How a `def` is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FunctionKind {
+    /// A function declaration, at module level.
+    Declaration,
+    /// An arrow function assigned to the name, inside another function so
+    /// that it sees the same `this`.
+    Arrow,
+    /// A method, which has no `self` parameter if it is static.
+    Method { is_static: bool },
+    /// A classmethod, whose `cls` parameter becomes `this`.
+    ClassMethod,
+}
+
+/// What the backend can tell of the Python type of a value. Tuples are
+/// arrays, as lists are.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Type {
+    Bool,
+    Int,
+    Float,
+    /// An int or a float, as a variable assigned both is.
+    Number,
+    Str,
+    List,
+    Set,
+    Dict,
+    Unknown,
+}
+
+impl Type {
+    fn is_number(self) -> bool {
+        matches!(self, Type::Bool | Type::Int | Type::Float | Type::Number)
+    }
+
+    /// The type of a variable assigned values of types `a` and `b`.
+    fn merge(a: Type, b: Type) -> Type {
+        match (a, b) {
+            _ if a == b => a,
+            _ if a.is_number() && b.is_number() => Type::Number,
+            _ => Type::Unknown,
+        }
+    }
+
+    /// The type of `a op b`.
+    fn of_binary(a: Type, op: Operator, b: Type) -> Type {
+        match op {
+            Operator::Add if a == Type::Str || b == Type::Str => Type::Str,
+            Operator::Add if a == Type::List || b == Type::List => Type::List,
+            Operator::Mult if a == Type::Str || b == Type::Str => Type::Str,
+            Operator::Mult if a == Type::List || b == Type::List => Type::List,
+            _ if !(a.is_number() && b.is_number()) => Type::Unknown,
+            Operator::Div => Type::Float,
+            _ if a == Type::Number || b == Type::Number => Type::Number,
+            _ if a == Type::Float || b == Type::Float => Type::Float,
+            Operator::MatMult => Type::Unknown,
+            _ => Type::Int,
+        }
+    }
+
+    /// The type an annotation names, as `int` and `list[str]` do.
+    fn of_annotation(annotation: &Expr) -> Type {
+        match annotation.kind {
+            ExprKind::Name { ref id, .. } => match &id[..] {
+                "bool" => Type::Bool,
+                "int" => Type::Int,
+                "float" => Type::Float,
+                "str" => Type::Str,
+                "list" | "tuple" => Type::List,
+                "set" => Type::Set,
+                "dict" => Type::Dict,
+                _ => Type::Unknown,
+            },
+            ExprKind::Subscript { ref value, .. } => Type::of_annotation(value),
+            _ => Type::Unknown,
+        }
+    }
+}
+
+impl Transpiler {
+    fn line(&mut self, text: &str) {
+        for _ in 0..self.indent {
+            self.out.push_str("  ");
+        }
+        self.out.push_str(text);
+        self.out.push('\n');
+    }
+
+    fn use_helper(&mut self, name: &'static str) {
+        if self.helpers.contains(&name) {
+            return;
+        }
+        self.helpers.push(name);
+        let &(_, needs, _) = HELPERS.iter().find(|helper| helper.0 == name).unwrap();
+        for &need in needs {
+            self.use_helper(need);
+        }
+    }
+
+    /// Whether `name` means the builtin of that name.
+    fn is_builtin(&self, name: &str) -> bool {
+        !self.module_names.contains(name)
+    }
+
+    /// Declares the variables a body assigns, other than `parameters`.
+    fn declarations(&mut self, body: &[Stmt], top: bool, parameters: &[String]) {
+        let names: Vec<String> = declared_names(body, top)
+            .into_iter()
+            .filter(|name| !parameters.contains(name))
+            .map(|name| identifier(&name))
+            .collect();
+        if !names.is_empty() {
+            self.line(&format!("let {};", names.join(", ")));
+        }
+    }
+
+    /// The types of the variables `body` binds, starting from `types`, the
+    /// types of its parameters. A variable assigned values of different
+    /// types, or declared `global` or `nonlocal` anywhere in `body`, is
+    /// `Unknown`.
+    fn scope(&mut self, body: &[Stmt], types: HashMap<String, Type>) -> HashMap<String, Type> {
+        self.scopes.push(types);
+        self.bindings(body);
+        let mut types = self.scopes.pop().unwrap();
+        for name in rebound_names(body) {
+            types.insert(name, Type::Unknown);
+        }
+        types
+    }
+
+    fn bindings(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            match stmt.kind {
+                StmtKind::Assign {
+                    ref targets,
+                    ref value,
+                    ..
+                } => {
+                    let value = self.type_of(value);
+                    for target in targets {
+                        self.assign(target, value);
+                    }
+                }
+                StmtKind::AnnAssign {
+                    ref target,
+                    ref annotation,
+                    ref value,
+                    ..
+                } => {
+                    let value = match (Type::of_annotation(annotation), value) {
+                        (Type::Unknown, Some(value)) => self.type_of(value),
+                        (annotated, _) => annotated,
+                    };
+                    self.assign(target, value);
+                }
+                StmtKind::AugAssign {
+                    ref target,
+                    op,
+                    ref value,
+                } => {
+                    let value = Type::of_binary(self.type_of(target), op, self.type_of(value));
+                    self.assign(target, value);
+                }
+                StmtKind::For {
+                    ref target,
+                    ref iter,
+                    ref body,
+                    ref orelse,
+                    ..
+                } => {
+                    let element = self.element_type(iter);
+                    self.assign(target, element);
+                    self.bindings(body);
+                    self.bindings(orelse);
+                }
+                StmtKind::While {
+                    ref body,
+                    ref orelse,
+                    ..
+                }
+                | StmtKind::If {
+                    ref body,
+                    ref orelse,
+                    ..
+                } => {
+                    self.bindings(body);
+                    self.bindings(orelse);
+                }
+                StmtKind::FunctionDef { ref name, .. } | StmtKind::ClassDef { ref name, .. } => {
+                    let scope = self.scopes.last_mut().unwrap();
+                    scope.insert(name.clone(), Type::Unknown);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Records in the innermost scope that `target` is assigned a value of
+    /// type `value`.
+    fn assign(&mut self, target: &Expr, value: Type) {
+        let mut bound = HashMap::new();
+        bind(target, value, &mut bound);
+        let scope = self.scopes.last_mut().unwrap();
+        for (name, value) in bound {
+            let merged = scope
+                .get(&name)
+                .map_or(value, |&old| Type::merge(old, value));
+            scope.insert(name, merged);
+        }
+    }
+
+    /// The type the backend can tell `expr` has.
+    fn type_of(&self, expr: &Expr) -> Type {
+        match expr.kind {
+            ExprKind::Constant(ref constant) => match *constant {
+                Constant::Bool(_) => Type::Bool,
+                Constant::Int(_) | Constant::LongInt(_) => Type::Int,
+                Constant::Float(_) => Type::Float,
+                Constant::Str(_) => Type::Str,
+                Constant::Tuple(_) => Type::List,
+                _ => Type::Unknown,
+            },
+            ExprKind::JoinedStr(_) | ExprKind::FormattedValue { .. } => Type::Str,
+            ExprKind::List { .. }
+            | ExprKind::Tuple { .. }
+            | ExprKind::ListComp { .. }
+            | ExprKind::GeneratorExp { .. } => Type::List,
+            ExprKind::Set(_) | ExprKind::SetComp { .. } => Type::Set,
+            ExprKind::Dict { .. } | ExprKind::DictComp { .. } => Type::Dict,
+            ExprKind::Compare { .. } => Type::Bool,
+            ExprKind::UnaryOp { op, ref operand } => match (op, self.type_of(operand)) {
+                (UnaryOperator::Not, _) => Type::Bool,
+                (_, Type::Bool) | (UnaryOperator::Invert, Type::Int) => Type::Int,
+                (UnaryOperator::Invert, _) => Type::Unknown,
+                (_, operand) if operand.is_number() => operand,
+                _ => Type::Unknown,
+            },
+            // An int to a negative power is a float.
+            ExprKind::BinOp {
+                op: Operator::Pow,
+                ref right,
+                ..
+            } if !matches!(right.kind, ExprKind::Constant(Constant::Int(power)) if power >= 0) => {
+                Type::Unknown
+            }
+            ExprKind::BinOp {
+                ref left,
+                op,
+                ref right,
+            } => Type::of_binary(self.type_of(left), op, self.type_of(right)),
+            ExprKind::BoolOp { ref values, .. } => values[1..]
+                .iter()
+                .fold(self.type_of(&values[0]), |merged, value| {
+                    Type::merge(merged, self.type_of(value))
+                }),
+            ExprKind::IfExp {
+                ref body,
+                ref orelse,
+                ..
+            } => Type::merge(self.type_of(body), self.type_of(orelse)),
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => match (self.type_of(value), &slice.kind) {
+                (Type::Str, _) => Type::Str,
+                (Type::List, &ExprKind::Slice { .. }) => Type::List,
+                _ => Type::Unknown,
+            },
+            ExprKind::Call {
+                ref func, ref args, ..
+            } => match func.kind {
+                ExprKind::Name { ref id, .. } if self.is_builtin(id) => match &id[..] {
+                    "str" | "repr" => Type::Str,
+                    "int" | "len" => Type::Int,
+                    "float" => Type::Float,
+                    "isinstance" => Type::Bool,
+                    "range" => Type::List,
+                    "abs" => match args.first().map(|arg| self.type_of(arg)) {
+                        Some(Type::Bool) => Type::Int,
+                        Some(arg) if arg.is_number() => arg,
+                        _ => Type::Unknown,
+                    },
+                    _ => Type::Unknown,
+                },
+                ExprKind::Name { ref id, .. } => {
+                    self.returns.get(id).cloned().unwrap_or(Type::Unknown)
+                }
+                _ => Type::Unknown,
+            },
+            ExprKind::Name { ref id, .. } if self.this.as_ref() != Some(id) => self
+                .scopes
+                .iter()
+                .rev()
+                .find_map(|scope| scope.get(id).cloned())
+                .unwrap_or(Type::Unknown),
+            _ => Type::Unknown,
+        }
+    }
+
+    /// The type of the elements of `iter`, as a `for` loop gives them.
+    fn element_type(&self, iter: &Expr) -> Type {
+        match iter.kind {
+            ExprKind::Call { ref func, .. } if matches!(func.kind, ExprKind::Name { ref id, .. } if id == "range" && self.is_builtin(id)) => {
+                Type::Int
+            }
+            _ if self.type_of(iter) == Type::Str => Type::Str,
+            _ => Type::Unknown,
+        }
+    }
+
+    fn block(&mut self, body: &[Stmt]) -> Result<()> {
+        self.indent += 1;
+        for stmt in body {
+            self.stmt(stmt, false)?;
+        }
+        self.indent -= 1;
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, top: bool) -> Result<()> {
+        let unsupported = |what: &str| {
+            Err(TranspileError::new(
+                format!("{} are not supported", what),
+                stmt.start,
+            ))
+        };
+        match stmt.kind {
+            StmtKind::FunctionDef {
+                ref name,
+                ref args,
+                ref body,
+                ref decorator_list,
+                is_async,
+                ..
+            } => {
+                if is_async {
+                    return unsupported("async functions");
+                }
+                if let Some(decorator) = decorator_list.first() {
+                    return Err(TranspileError::new(
+                        "decorators are not supported outside classes",
+                        decorator.start,
+                    ));
+                }
+                let kind = if top {
+                    FunctionKind::Declaration
+                } else {
+                    FunctionKind::Arrow
+                };
+                self.function(name, args, body, kind)
+            }
+            StmtKind::ClassDef {
+                ref name,
+                ref bases,
+                ref keywords,
+                ref body,
+                ref decorator_list,
+                ..
+            } => self.class(stmt, name, bases, keywords.is_empty(), body, decorator_list),
+            StmtKind::Return(ref value) => {
+                match *value {
+                    Some(ref value) => {
+                        let value = self.operand(value, ASSIGNMENT)?;
+                        self.line(&format!("return {};", value));
+                    }
+                    None => self.line("return;"),
+                }
+                Ok(())
+            }
+            StmtKind::Delete(ref targets) => {
+                for target in targets {
+                    if let ExprKind::Name { .. } = target.kind {
+                        return Err(TranspileError::new(
+                            "deleting variables is not supported",
+                            target.start,
+                        ));
+                    }
+                    let target = self.target(target)?;
+                    self.line(&format!("delete {};", target));
+                }
+                Ok(())
+            }
+            StmtKind::Assign {
+                ref targets,
+                ref value,
+                ..
+            } => {
+                let mut text = String::new();
+                for target in targets {
+                    text.push_str(&self.target(target)?);
+                    text.push_str(" = ");
+                }
+                text.push_str(&self.operand(value, ASSIGNMENT)?);
+                text.push(';');
+                self.line(&text);
+                Ok(())
+            }
+            StmtKind::AugAssign {
+                ref target,
+                op,
+                ref value,
+            } => {
+                let target_text = self.target(target)?;
+                let line = match op {
+                    Operator::FloorDiv | Operator::Mod => {
+                        let value = self.binary(target, op, value)?.0;
+                        format!("{} = {};", target_text, value)
+                    }
+                    Operator::Add | Operator::Mult => match self.sequence(target, op, value)? {
+                        Some((value, _)) => format!("{} = {};", target_text, value),
+                        None => {
+                            let value = self.operand(value, ASSIGNMENT)?;
+                            format!("{} {}= {};", target_text, op.symbol(), value)
+                        }
+                    },
+                    Operator::MatMult => return unsupported("matrix multiplications"),
+                    _ => {
+                        let value = self.operand(value, ASSIGNMENT)?;
+                        format!("{} {}= {};", target_text, op.symbol(), value)
+                    }
+                };
+                self.line(&line);
+                Ok(())
+            }
+            StmtKind::AnnAssign {
+                ref target,
+                ref value,
+                ..
+            } => {
+                if let Some(ref value) = *value {
+                    let target = self.target(target)?;
+                    let value = self.operand(value, ASSIGNMENT)?;
+                    self.line(&format!("{} = {};", target, value));
+                }
+                Ok(())
+            }
+            StmtKind::For {
+                ref target,
+                ref iter,
+                ref body,
+                ref orelse,
+                is_async,
+                ..
+            } => {
+                if is_async {
+                    return unsupported("async loops");
+                }
+                if !orelse.is_empty() {
+                    return unsupported("'else' clauses of loops");
+                }
+                let target = self.target(target)?;
+                let iter = self.operand(iter, ASSIGNMENT)?;
+                self.line(&format!("for ({} of {}) {{", target, iter));
+                self.block(body)?;
+                self.line("}");
+                Ok(())
+            }
+            StmtKind::While {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                if !orelse.is_empty() {
+                    return unsupported("'else' clauses of loops");
+                }
+                let test = self.operand(test, ASSIGNMENT)?;
+                self.line(&format!("while ({}) {{", test));
+                self.block(body)?;
+                self.line("}");
+                Ok(())
+            }
+            StmtKind::If { .. } => {
+                let mut stmt = stmt;
+                let mut keyword = "if";
+                loop {
+                    let (test, body, orelse) = match stmt.kind {
+                        StmtKind::If {
+                            ref test,
+                            ref body,
+                            ref orelse,
+                        } => (test, body, orelse),
+                        _ => unreachable!(),
+                    };
+                    let test = self.operand(test, ASSIGNMENT)?;
+                    self.line(&format!("{} ({}) {{", keyword, test));
+                    self.block(body)?;
+                    match orelse.len() {
+                        0 => {}
+                        1 if matches!(orelse[0].kind, StmtKind::If { .. }) => {
+                            stmt = &orelse[0];
+                            keyword = "} else if";
+                            continue;
+                        }
+                        _ => {
+                            self.line("} else {");
+                            self.block(orelse)?;
+                        }
+                    }
+                    self.line("}");
+                    return Ok(());
+                }
+            }
+            StmtKind::Raise { ref exc, ref cause } => {
+                if cause.is_some() {
+                    return unsupported("exception causes");
+                }
+                let exc = match *exc {
+                    Some(ref exc) => exc,
+                    None => return unsupported("re-raising exceptions"),
+                };
+                let text = match exc.kind {
+                    ExprKind::Name { ref id, .. } if self.classes.contains(id) => {
+                        format!("new {}()", identifier(id))
+                    }
+                    ExprKind::Name { ref id, .. }
+                        if EXCEPTIONS.contains(&&id[..]) && self.is_builtin(id) =>
+                    {
+                        "new Error()".to_string()
+                    }
+                    _ => self.operand(exc, ASSIGNMENT)?,
+                };
+                self.line(&format!("throw {};", text));
+                Ok(())
+            }
+            StmtKind::Assert { ref test, ref msg } => {
+                let test = self.operand(test, UNARY)?;
+                let msg = match *msg {
+                    Some(ref msg) => self.operand(msg, ASSIGNMENT)?,
+                    None => String::new(),
+                };
+                self.line(&format!("if (!{}) {{", test));
+                self.indent += 1;
+                self.line(&format!("throw new Error({});", msg));
+                self.indent -= 1;
+                self.line("}");
+                Ok(())
+            }
+            // Closures see the variables of enclosing functions, and the
+            // module's variables, without being told.
+            StmtKind::Global(_) | StmtKind::Nonlocal(_) | StmtKind::Pass => Ok(()),
+            // Docstrings and other strings used as comments.
+            StmtKind::Expr(Expr {
+                kind: ExprKind::Constant(Constant::Str(_)),
+                ..
+            }) => Ok(()),
+            StmtKind::Expr(ref value) => {
+                let text = self.operand(value, ASSIGNMENT)?;
+                // Braces start a block, not an object, at the start of a
+                // statement.
+                if text.starts_with('{') || text.starts_with("function") {
+                    self.line(&format!("({});", text));
+                } else {
+                    self.line(&format!("{};", text));
+                }
+                Ok(())
+            }
+            StmtKind::Break => {
+                self.line("break;");
+                Ok(())
+            }
+            StmtKind::Continue => {
+                self.line("continue;");
+                Ok(())
+            }
+            StmtKind::With { .. } => unsupported("'with' statements"),
+            StmtKind::Error => unsupported("statements with syntax errors"),
+            StmtKind::Match { .. } => unsupported("'match' statements"),
+            StmtKind::TypeAlias { .. } => unsupported("'type' statements"),
+            StmtKind::Try { .. } => unsupported("'try' statements"),
+            StmtKind::Import(_) | StmtKind::ImportFrom { .. } => unsupported("imports"),
+        }
+    }
+
+    fn function(
+        &mut self,
+        name: &str,
+        args: &Arguments,
+        body: &[Stmt],
+        kind: FunctionKind,
+    ) -> Result<()> {
+        let outer_this = (self.this.clone(), self.this_is_class);
+        let has_self = match kind {
+            FunctionKind::Method { is_static } => !is_static,
+            FunctionKind::ClassMethod => true,
+            _ => false,
+        };
+        let (parameters, names) = self.parameters(args, has_self)?;
+        let mut types = HashMap::new();
+        for arg in args.posonlyargs.iter().chain(&args.args) {
+            let annotation = arg.annotation.as_ref();
+            types.insert(
+                arg.arg.clone(),
+                annotation.map_or(Type::Unknown, |a| Type::of_annotation(a)),
+            );
+        }
+        if let Some(ref arg) = args.vararg {
+            types.insert(arg.arg.clone(), Type::List);
+        }
+        let scope = self.scope(body, types);
+        self.scopes.push(scope);
+        match kind {
+            FunctionKind::Declaration => self.this = None,
+            FunctionKind::Arrow => {}
+            FunctionKind::Method { .. } | FunctionKind::ClassMethod => {
+                let positional = args.posonlyargs.iter().chain(&args.args).next();
+                self.this = positional.filter(|_| has_self).map(|arg| arg.arg.clone());
+                self.this_is_class = kind == FunctionKind::ClassMethod;
+            }
+        }
+        let name = identifier(name);
+        let head = match kind {
+            FunctionKind::Declaration => format!("function {}({}) {{", name, parameters),
+            FunctionKind::Arrow => format!("{} = ({}) => {{", name, parameters),
+            FunctionKind::Method { is_static } => {
+                let name = match &name[..] {
+                    "__init__" => "constructor",
+                    "__str__" => "toString",
+                    name => name,
+                };
+                let prefix = if is_static { "static " } else { "" };
+                format!("{}{}({}) {{", prefix, name, parameters)
+            }
+            FunctionKind::ClassMethod => format!("static {}({}) {{", name, parameters),
+        };
+        self.line(&head);
+        self.indent += 1;
+        self.declarations(body, false, &names);
+        self.indent -= 1;
+        let result = self.block(body);
+        self.scopes.pop();
+        self.this = outer_this.0;
+        self.this_is_class = outer_this.1;
+        result?;
+        self.line(if kind == FunctionKind::Arrow {
+            "};"
+        } else {
+            "}"
+        });
+        Ok(())
+    }
+
+    /// The parameter list of a function, and the names it binds. The first
+    /// parameter is left out if it is `self`.
+    fn parameters(&mut self, args: &Arguments, skip_self: bool) -> Result<(String, Vec<String>)> {
+        if let Some(arg) = args.kwonlyargs.first().or(args.kwarg.as_ref()) {
+            return Err(TranspileError::new(
+                "keyword parameters are not supported",
+                arg.start,
+            ));
+        }
+        let positional: Vec<_> = args.posonlyargs.iter().chain(&args.args).collect();
+        let first_default = positional.len() - args.defaults.len();
+        let mut parameters = Vec::new();
+        let mut names = Vec::new();
+        for (position, arg) in positional.iter().enumerate() {
+            names.push(arg.arg.clone());
+            if skip_self && position == 0 {
+                continue;
+            }
+            let mut text = identifier(&arg.arg);
+            if position >= first_default {
+                let default = &args.defaults[position - first_default];
+                text.push_str(" = ");
+                text.push_str(&self.operand(default, ASSIGNMENT)?);
+            }
+            parameters.push(text);
+        }
+        if let Some(ref arg) = args.vararg {
+            names.push(arg.arg.clone());
+            parameters.push(format!("...{}", identifier(&arg.arg)));
+        }
+        Ok((parameters.join(", "), names))
+    }
+
+    fn class(
+        &mut self,
+        stmt: &Stmt,
+        name: &str,
+        bases: &[Expr],
+        no_keywords: bool,
+        body: &[Stmt],
+        decorator_list: &[Expr],
+    ) -> Result<()> {
+        if !no_keywords {
+            return Err(TranspileError::new(
+                "metaclasses and class keywords are not supported",
+                stmt.start,
+            ));
+        }
+        if let Some(decorator) = decorator_list.first() {
+            return Err(TranspileError::new(
+                "class decorators are not supported",
+                decorator.start,
+            ));
+        }
+        let name = identifier(name);
+        let head = match bases.len() {
+            0 => format!("class {} {{", name),
+            1 => format!(
+                "class {} extends {} {{",
+                name,
+                self.operand(&bases[0], CALL)?
+            ),
+            _ => {
+                return Err(TranspileError::new(
+                    "multiple inheritance is not supported",
+                    bases[1].start,
+                ))
+            }
+        };
+        self.line(&head);
+        self.indent += 1;
+        let body_start = self.out.len();
+        for stmt in body {
+            match stmt.kind {
+                StmtKind::FunctionDef {
+                    ref name,
+                    ref args,
+                    ref body,
+                    ref decorator_list,
+                    is_async,
+                    ..
+                } => {
+                    if is_async {
+                        return Err(TranspileError::new(
+                            "async functions are not supported",
+                            stmt.start,
+                        ));
+                    }
+                    let kind = match decorator_list.len() {
+                        0 => FunctionKind::Method { is_static: false },
+                        1 => match decorator_list[0].kind {
+                            ExprKind::Name { ref id, .. } if id == "staticmethod" => {
+                                FunctionKind::Method { is_static: true }
+                            }
+                            ExprKind::Name { ref id, .. } if id == "classmethod" => {
+                                FunctionKind::ClassMethod
+                            }
+                            _ => return Err(TranspileError::new(
+                                "only 'staticmethod' and 'classmethod' decorators are supported",
+                                decorator_list[0].start,
+                            )),
+                        },
+                        _ => {
+                            return Err(TranspileError::new(
+                                "methods with more than one decorator are not supported",
+                                decorator_list[1].start,
+                            ))
+                        }
+                    };
+                    if self.out.len() > body_start {
+                        self.out.push('\n');
+                    }
+                    if name == "__init__" && !bases.is_empty() && !calls_super_init(body) {
+                        // A derived constructor must call `super` before it
+                        // uses `this`.
+                        let mut body = body.to_vec();
+                        body.insert(0, super_init_call(stmt.start));
+                        self.function(name, args, &body, kind)?;
+                    } else {
+                        self.function(name, args, body, kind)?;
+                    }
+                }
+                StmtKind::Assign {
+                    ref targets,
+                    ref value,
+                    ..
+                } if targets.len() == 1 => self.class_attribute(&targets[0], value)?,
+                StmtKind::AnnAssign {
+                    ref target,
+                    value: Some(ref value),
+                    ..
+                } => self.class_attribute(target, value)?,
+                StmtKind::AnnAssign { value: None, .. }
+                | StmtKind::Pass
+                | StmtKind::Expr(Expr {
+                    kind: ExprKind::Constant(Constant::Str(_)),
+                    ..
+                }) => {}
+                _ => {
+                    return Err(TranspileError::new(
+                        "only methods and assignments are supported in a class body",
+                        stmt.start,
+                    ))
+                }
+            }
+        }
+        self.indent -= 1;
+        self.line("}");
+        Ok(())
+    }
+
+    /// An assignment in a class body, as a static field.
+    fn class_attribute(&mut self, target: &Expr, value: &Expr) -> Result<()> {
+        let target = match target.kind {
+            ExprKind::Name { ref id, .. } => identifier(id),
+            _ => {
+                return Err(TranspileError::new(
+                    "only names can be assigned in a class body",
+                    target.start,
+                ))
+            }
+        };
+        let value = self.operand(value, ASSIGNMENT)?;
+        self.line(&format!("static {} = {};", target, value));
+        Ok(())
+    }
+
+    /// The left-hand side of an assignment.
+    fn target(&mut self, target: &Expr) -> Result<String> {
+        match target.kind {
+            ExprKind::Name { ref id, .. } => Ok(identifier(id)),
+            ExprKind::Tuple { ref elts, .. } | ExprKind::List { ref elts, .. } => {
+                let mut parts = Vec::new();
+                for (position, elt) in elts.iter().enumerate() {
+                    match elt.kind {
+                        ExprKind::Starred { ref value, .. } if position + 1 == elts.len() => {
+                            parts.push(format!("...{}", self.target(value)?));
+                        }
+                        ExprKind::Starred { .. } => {
+                            return Err(TranspileError::new(
+                                "a starred target must come last",
+                                elt.start,
+                            ))
+                        }
+                        _ => parts.push(self.target(elt)?),
+                    }
+                }
+                Ok(format!("[{}]", parts.join(", ")))
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => {
+                // `.at` cannot be assigned to, so count from the end by
+                // hand.
+                if let (Some(offset), ExprKind::Name { .. }) = (negative_index(slice), &value.kind)
+                {
+                    let value = self.operand(value, CALL)?;
+                    return Ok(format!("{}[{}.length - {}]", value, value, offset));
+                }
+                self.operand(target, CALL)
+            }
+            ExprKind::Attribute { .. } => self.operand(target, CALL),
+            _ => Err(TranspileError::new(
+                "this assignment target is not supported",
+                target.start,
+            )),
+        }
+    }
+
+    /// `expr`, in parentheses unless it binds at least as tightly as
+    /// `level`.
+    fn operand(&mut self, expr: &Expr, level: u8) -> Result<String> {
+        let (text, precedence) = self.expr(expr)?;
+        if precedence < level {
+            Ok(format!("({})", text))
+        } else {
+            Ok(text)
+        }
+    }
+
+    /// The JavaScript for `expr`, and how tightly it binds.
+    fn expr(&mut self, expr: &Expr) -> Result<(String, u8)> {
+        let unsupported = |what: &str| {
+            Err(TranspileError::new(
+                format!("{} are not supported", what),
+                expr.start,
+            ))
+        };
+        match expr.kind {
+            ExprKind::BoolOp { op, ref values } => {
+                let (symbol, level) = match op {
+                    BoolOperator::And => ("&&", AND),
+                    BoolOperator::Or => ("||", OR),
+                };
+                let mut parts = Vec::new();
+                for value in values {
+                    parts.push(self.operand(value, level + 1)?);
+                }
+                Ok((parts.join(&format!(" {} ", symbol)), level))
+            }
+            ExprKind::BinOp {
+                ref left,
+                op,
+                ref right,
+            } => self.binary(left, op, right),
+            ExprKind::UnaryOp { op, ref operand } => {
+                let symbol = match op {
+                    UnaryOperator::Not => "!",
+                    UnaryOperator::Invert => "~",
+                    UnaryOperator::UAdd => "+",
+                    UnaryOperator::USub => "-",
+                };
+                let operand = self.operand(operand, UNARY)?;
+                // `- -x` is not `--x`.
+                let doubled = op != UnaryOperator::Not && operand.starts_with(symbol);
+                let space = if doubled { " " } else { "" };
+                Ok((format!("{}{}{}", symbol, space, operand), UNARY))
+            }
+            ExprKind::Lambda { ref args, ref body } => {
+                let (parameters, names) = self.parameters(args, false)?;
+                let unknown = names.into_iter().map(|name| (name, Type::Unknown));
+                self.scopes.push(unknown.collect());
+                let body = self.operand(body, ASSIGNMENT);
+                self.scopes.pop();
+                let body = body?;
+                let body = if body.starts_with('{') {
+                    format!("({})", body)
+                } else {
+                    body
+                };
+                Ok((format!("({}) => {}", parameters, body), ASSIGNMENT))
+            }
+            ExprKind::IfExp {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                let test = self.operand(test, OR)?;
+                let body = self.operand(body, ASSIGNMENT)?;
+                let orelse = self.operand(orelse, ASSIGNMENT)?;
+                Ok((format!("{} ? {} : {}", test, body, orelse), CONDITIONAL))
+            }
+            ExprKind::Dict {
+                ref keys,
+                ref values,
+            } => {
+                let mut entries = Vec::new();
+                for (key, value) in keys.iter().zip(values) {
+                    let value = self.operand(value, ASSIGNMENT)?;
+                    let entry = match *key {
+                        None => format!("...{}", value),
+                        Some(Expr {
+                            kind: ExprKind::Constant(Constant::Str(ref key)),
+                            ..
+                        }) => format!("{}: {}", string_literal(key), value),
+                        Some(ref key) => {
+                            return Err(TranspileError::new(
+                                "only string keys are supported in dicts",
+                                key.start,
+                            ))
+                        }
+                    };
+                    entries.push(entry);
+                }
+                if entries.is_empty() {
+                    return Ok(("{}".to_string(), PRIMARY));
+                }
+                Ok((format!("{{ {} }}", entries.join(", ")), PRIMARY))
+            }
+            ExprKind::Set(ref elts) => {
+                let elts = self.elements(elts)?;
+                Ok((format!("new Set([{}])", elts), CALL))
+            }
+            ExprKind::List { ref elts, .. } | ExprKind::Tuple { ref elts, .. } => {
+                let elts = self.elements(elts)?;
+                Ok((format!("[{}]", elts), PRIMARY))
+            }
+            ExprKind::ListComp {
+                ref elt,
+                ref generators,
+            }
+            | ExprKind::GeneratorExp {
+                ref elt,
+                ref generators,
+            } => Ok((self.comprehension(expr, elt, generators)?, CALL)),
+            ExprKind::SetComp {
+                ref elt,
+                ref generators,
+            } => {
+                let values = self.comprehension(expr, elt, generators)?;
+                Ok((format!("new Set({})", values), CALL))
+            }
+            ExprKind::DictComp {
+                ref key,
+                ref value,
+                ref generators,
+            } => {
+                let entry = Expr {
+                    kind: ExprKind::List {
+                        elts: vec![(**key).clone(), (**value).clone()],
+                        ctx: ::ast::Context::Load,
+                    },
+                    start: key.start,
+                    end: value.end,
+                };
+                let entries = self.comprehension(expr, &entry, generators)?;
+                Ok((format!("Object.fromEntries({})", entries), CALL))
+            }
+            ExprKind::Compare {
+                ref left,
+                ref ops,
+                ref comparators,
+            } => {
+                let mut parts = Vec::new();
+                let mut left = &**left;
+                for (position, (&op, right)) in ops.iter().zip(comparators).enumerate() {
+                    let middle = position + 1 < comparators.len();
+                    if middle && !is_simple(right) {
+                        return Err(TranspileError::new(
+                            "chained comparisons are only supported between names and constants",
+                            right.start,
+                        ));
+                    }
+                    parts.push(self.comparison(left, op, right)?);
+                    left = right;
+                }
+                if parts.len() == 1 {
+                    return Ok(parts.pop().unwrap());
+                }
+                let mut texts = Vec::new();
+                for (text, level) in parts {
+                    texts.push(if level <= AND {
+                        format!("({})", text)
+                    } else {
+                        text
+                    });
+                }
+                Ok((texts.join(" && "), AND))
+            }
+            ExprKind::Call {
+                ref func,
+                ref args,
+                ref keywords,
+            } => {
+                if let Some(keyword) = keywords.first() {
+                    return Err(TranspileError::new(
+                        "keyword arguments are not supported",
+                        keyword.start,
+                    ));
+                }
+                self.call(func, args)
+            }
+            ExprKind::JoinedStr(ref values) => {
+                let mut text = String::from("`");
+                for value in values {
+                    match value.kind {
+                        ExprKind::Constant(Constant::Str(ref part)) => {
+                            text.push_str(&template_text(part))
+                        }
+                        ExprKind::FormattedValue { .. } => {
+                            text.push_str("${");
+                            text.push_str(&self.formatted_value(value)?);
+                            text.push('}');
+                        }
+                        _ => unreachable!(),
+                    }
+                }
+                text.push('`');
+                Ok((text, PRIMARY))
+            }
+            ExprKind::FormattedValue { .. } => {
+                Ok((format!("`${{{}}}`", self.formatted_value(expr)?), PRIMARY))
+            }
+            ExprKind::Constant(ref constant) => self.constant(expr, constant),
+            ExprKind::Attribute {
+                ref value,
+                ref attr,
+                ..
+            } => {
+                if is_super(value) {
+                    return Ok((format!("super.{}", attr), CALL));
+                }
+                let value = match value.kind {
+                    // `1.x` is a number followed by `x`.
+                    ExprKind::Constant(Constant::Int(_)) => format!("({})", self.expr(value)?.0),
+                    _ => self.operand(value, CALL)?,
+                };
+                Ok((format!("{}.{}", value, attr), CALL))
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => {
+                let value = self.operand(value, CALL)?;
+                if let ExprKind::Slice {
+                    ref lower,
+                    ref upper,
+                    ref step,
+                } = slice.kind
+                {
+                    if step.is_some() {
+                        return unsupported("slices with a step");
+                    }
+                    let lower = match *lower {
+                        Some(ref lower) => self.operand(lower, ASSIGNMENT)?,
+                        None => "0".to_string(),
+                    };
+                    let arguments = match *upper {
+                        Some(ref upper) => {
+                            format!("{}, {}", lower, self.operand(upper, ASSIGNMENT)?)
+                        }
+                        None => lower,
+                    };
+                    return Ok((format!("{}.slice({})", value, arguments), CALL));
+                }
+                if negative_index(slice).is_some() {
+                    let index = self.operand(slice, ASSIGNMENT)?;
+                    return Ok((format!("{}.at({})", value, index), CALL));
+                }
+                let index = self.operand(slice, ASSIGNMENT)?;
+                Ok((format!("{}[{}]", value, index), CALL))
+            }
+            ExprKind::Starred { ref value, .. } => {
+                let value = self.operand(value, ASSIGNMENT)?;
+                Ok((format!("...{}", value), ASSIGNMENT))
+            }
+            ExprKind::Name { ref id, .. } => {
+                if self.this.as_ref() == Some(id) {
+                    return Ok(("this".to_string(), PRIMARY));
+                }
+                if self.is_builtin(id) {
+                    if let Some(&(name, _, _)) = HELPERS.iter().find(|helper| helper.0 == id) {
+                        self.use_helper(name);
+                    }
+                }
+                Ok((identifier(id), PRIMARY))
+            }
+            ExprKind::NamedExpr { .. } => unsupported("assignment expressions"),
+            ExprKind::Await(_) => unsupported("'await' expressions"),
+            ExprKind::Yield(_) | ExprKind::YieldFrom(_) => unsupported("'yield' expressions"),
+            ExprKind::Slice { .. } => unsupported("slices outside subscripts"),
+        }
+    }
+
+    fn binary(&mut self, left: &Expr, op: Operator, right: &Expr) -> Result<(String, u8)> {
+        if let Some(lowered) = self.sequence(left, op, right)? {
+            return Ok(lowered);
+        }
+        let level = match op {
+            Operator::Add | Operator::Sub => ADDITIVE,
+            Operator::Mult | Operator::Div => MULTIPLICATIVE,
+            Operator::LShift | Operator::RShift => SHIFT,
+            Operator::BitAnd => BIT_AND,
+            Operator::BitXor => BIT_XOR,
+            Operator::BitOr => BIT_OR,
+            Operator::Pow => {
+                // JavaScript refuses `-a ** b` rather than guess what it
+                // means.
+                let left = self.operand(left, UNARY + 1)?;
+                let right = self.operand(right, EXPONENT)?;
+                return Ok((format!("{} ** {}", left, right), EXPONENT));
+            }
+            Operator::FloorDiv => {
+                let left = self.operand(left, MULTIPLICATIVE)?;
+                let right = self.operand(right, MULTIPLICATIVE + 1)?;
+                return Ok((format!("Math.floor({} / {})", left, right), CALL));
+            }
+            Operator::Mod => {
+                self.use_helper("mod");
+                let left = self.operand(left, ASSIGNMENT)?;
+                let right = self.operand(right, ASSIGNMENT)?;
+                return Ok((format!("mod({}, {})", left, right), CALL));
+            }
+            Operator::MatMult => {
+                return Err(TranspileError::new(
+                    "matrix multiplications are not supported",
+                    left.end,
+                ))
+            }
+        };
+        let left = self.operand(left, level)?;
+        let right = self.operand(right, level + 1)?;
+        Ok((format!("{} {} {}", left, op.symbol(), right), level))
+    }
+
+    /// `+` on lists and `*` on a str, which JavaScript's operators do not
+    /// concatenate and repeat, or `None` for the operators JavaScript's
+    /// mean. `*` needs operands it can tell the types of.
+    fn sequence(
+        &mut self,
+        left: &Expr,
+        op: Operator,
+        right: &Expr,
+    ) -> Result<Option<(String, u8)>> {
+        let (left_type, right_type) = (self.type_of(left), self.type_of(right));
+        match op {
+            Operator::Add if left_type == Type::List || right_type == Type::List => {
+                let left = self.operand(left, ASSIGNMENT)?;
+                let right = self.operand(right, ASSIGNMENT)?;
+                Ok(Some((format!("[...{}, ...{}]", left, right), PRIMARY)))
+            }
+            Operator::Mult if left_type == Type::Str || right_type == Type::Str => {
+                let (text, count) = if left_type == Type::Str {
+                    (left, right)
+                } else {
+                    (right, left)
+                };
+                let text = self.operand(text, CALL)?;
+                // Python repeats a str a negative number of times into "".
+                let count = match count.kind {
+                    ExprKind::Constant(Constant::Int(count)) if count >= 0 => count.to_string(),
+                    _ => format!("Math.max({}, 0)", self.operand(count, ASSIGNMENT)?),
+                };
+                Ok(Some((format!("{}.repeat({})", text, count), CALL)))
+            }
+            Operator::Mult if left_type == Type::List || right_type == Type::List => Err(
+                TranspileError::new("repeating lists is not supported", left.start),
+            ),
+            Operator::Mult if !(left_type.is_number() && right_type.is_number()) => {
+                let unknown = if left_type.is_number() { right } else { left };
+                Err(TranspileError::new(
+                    "'*' is only supported on operands known to be numbers, or a str and an int",
+                    unknown.start,
+                ))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn comparison(&mut self, left: &Expr, op: CmpOperator, right: &Expr) -> Result<(String, u8)> {
+        let (symbol, level) = match op {
+            CmpOperator::Eq | CmpOperator::Is => ("===", EQUALITY),
+            CmpOperator::NotEq | CmpOperator::IsNot => ("!==", EQUALITY),
+            CmpOperator::Lt => ("<", RELATIONAL),
+            CmpOperator::LtE => ("<=", RELATIONAL),
+            CmpOperator::Gt => (">", RELATIONAL),
+            CmpOperator::GtE => (">=", RELATIONAL),
+            CmpOperator::In | CmpOperator::NotIn => {
+                let container_type = self.type_of(right);
+                let container = self.operand(right, CALL)?;
+                let item = self.operand(left, ASSIGNMENT)?;
+                let text = match container_type {
+                    Type::List | Type::Str => format!("{}.includes({})", container, item),
+                    Type::Set => format!("{}.has({})", container, item),
+                    Type::Dict => format!("Object.hasOwn({}, {})", container, item),
+                    _ => return Err(TranspileError::new(
+                        "'in' is only supported on a list, tuple, str, set or dict known to be one",
+                        right.start,
+                    )),
+                };
+                if op == CmpOperator::NotIn {
+                    return Ok((format!("!{}", text), UNARY));
+                }
+                return Ok((text, CALL));
+            }
+        };
+        let left = self.operand(left, level)?;
+        let right = self.operand(right, level + 1)?;
+        Ok((format!("{} {} {}", left, symbol, right), level))
+    }
+
+    fn call(&mut self, func: &Expr, args: &[Expr]) -> Result<(String, u8)> {
+        let arguments = self.elements(args)?;
+        if let ExprKind::Attribute {
+            ref value,
+            ref attr,
+            ..
+        } = func.kind
+        {
+            if is_super(value) && attr == "__init__" {
+                return Ok((format!("super({})", arguments), CALL));
+            }
+        }
+        if let ExprKind::Name { ref id, .. } = func.kind {
+            if self.classes.contains(id) {
+                return Ok((format!("new {}({})", identifier(id), arguments), CALL));
+            }
+            if self.this_is_class && self.this.as_ref() == Some(id) {
+                return Ok((format!("new this({})", arguments), CALL));
+            }
+            if self.is_builtin(id) {
+                let spread = args.len() == 1 && !matches!(args[0].kind, ExprKind::Starred { .. });
+                let text = match &id[..] {
+                    "print" => {
+                        self.use_helper("print");
+                        let mut shown = Vec::new();
+                        for arg in args {
+                            shown.push(self.shown(arg)?);
+                        }
+                        format!("print({})", shown.join(", "))
+                    }
+                    "str" | "repr" if spread && self.type_of(&args[0]) == Type::Float => {
+                        self.shown(&args[0])?
+                    }
+                    "float" => format!("Number({})", arguments),
+                    "abs" => format!("Math.abs({})", arguments),
+                    // `min(values)` takes the smallest of the values.
+                    "min" | "max" if spread => format!("Math.{}(...{})", id, arguments),
+                    "min" | "max" => format!("Math.{}({})", id, arguments),
+                    "isinstance" if args.len() == 2 => {
+                        let value = self.operand(&args[0], RELATIONAL)?;
+                        let class = self.operand(&args[1], RELATIONAL + 1)?;
+                        return Ok((format!("{} instanceof {}", value, class), RELATIONAL));
+                    }
+                    name if EXCEPTIONS.contains(&name) => format!("new Error({})", arguments),
+                    _ => {
+                        let func = self.operand(func, CALL)?;
+                        format!("{}({})", func, arguments)
+                    }
+                };
+                return Ok((text, CALL));
+            }
+        }
+        let func = self.operand(func, CALL)?;
+        Ok((format!("{}({})", func, arguments), CALL))
+    }
+
+    /// `expr` for a function that shows it as text, through `reprFloat` if
+    /// it is a float.
+    fn shown(&mut self, expr: &Expr) -> Result<String> {
+        if self.type_of(expr) != Type::Float {
+            return self.operand(expr, ASSIGNMENT);
+        }
+        self.use_helper("reprFloat");
+        Ok(format!("reprFloat({})", self.operand(expr, ASSIGNMENT)?))
+    }
+
+    /// The elements of a list or the arguments of a call, separated by
+    /// commas.
+    fn elements(&mut self, elts: &[Expr]) -> Result<String> {
+        let mut parts = Vec::new();
+        for elt in elts {
+            parts.push(self.operand(elt, ASSIGNMENT)?);
+        }
+        Ok(parts.join(", "))
+    }
+
+    /// A comprehension with one `for`, as an array of its elements.
+    fn comprehension(
+        &mut self,
+        expr: &Expr,
+        elt: &Expr,
+        generators: &[::ast::Comprehension],
+    ) -> Result<String> {
+        let generator = match generators {
+            [generator] if !generator.is_async => generator,
+            [_] => {
+                return Err(TranspileError::new(
+                    "async comprehensions are not supported",
+                    expr.start,
+                ))
+            }
+            _ => {
+                return Err(TranspileError::new(
+                    "comprehensions with more than one 'for' are not supported",
+                    generators[1].target.start,
+                ))
+            }
+        };
+        let parameter = self.target(&generator.target)?;
+        let text = self.operand(&generator.iter, CALL)?;
+        let mut types = HashMap::new();
+        bind(
+            &generator.target,
+            self.element_type(&generator.iter),
+            &mut types,
+        );
+        self.scopes.push(types);
+        let text = self.comprehension_body(text, &parameter, elt, &generator.ifs);
+        self.scopes.pop();
+        text
+    }
+
+    /// The filters and map of a comprehension over `text`, in the scope of
+    /// its target.
+    fn comprehension_body(
+        &mut self,
+        mut text: String,
+        parameter: &str,
+        elt: &Expr,
+        ifs: &[Expr],
+    ) -> Result<String> {
+        for test in ifs {
+            let test = self.operand(test, ASSIGNMENT)?;
+            text = format!("{}.filter(({}) => {})", text, parameter, test);
+        }
+        let elt = self.operand(elt, ASSIGNMENT)?;
+        if elt != parameter {
+            text = format!("{}.map(({}) => {})", text, parameter, elt);
+        }
+        Ok(text)
+    }
+
+    /// The expression that a replacement field of an f-string shows.
+    fn formatted_value(&mut self, expr: &Expr) -> Result<String> {
+        let (value, conversion, format_spec) = match expr.kind {
+            ExprKind::FormattedValue {
+                ref value,
+                conversion,
+                ref format_spec,
+            } => (value, conversion, format_spec),
+            _ => unreachable!(),
+        };
+        let mut text = match conversion {
+            None | Some('s') => self.shown(value)?,
+            Some('r') if self.type_of(value) == Type::Float => self.shown(value)?,
+            Some('r') => {
+                self.use_helper("repr");
+                format!("repr({})", self.operand(value, ASSIGNMENT)?)
+            }
+            Some(_) => {
+                return Err(TranspileError::new(
+                    "the '!a' conversion is not supported",
+                    expr.start,
+                ))
+            }
+        };
+        let spec =
+            match *format_spec {
+                None => return Ok(text),
+                Some(ref spec) => match spec.kind {
+                    ExprKind::JoinedStr(ref values) => match values[..] {
+                        [] => String::new(),
+                        [Expr {
+                            kind: ExprKind::Constant(Constant::Str(ref spec)),
+                            ..
+                        }] => spec.clone(),
+                        _ => return Err(TranspileError::new(
+                            "nested replacement fields in format specifications are not supported",
+                            spec.start,
+                        )),
+                    },
+                    // A specification the optimizer joined.
+                    ExprKind::Constant(Constant::Str(ref spec)) => spec.clone(),
+                    _ => unreachable!(),
+                },
+            };
+        let digits = spec
+            .strip_prefix('.')
+            .and_then(|rest| rest.strip_suffix('f'))
+            .filter(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()));
+        if let Some(digits) = digits {
+            text = format!("{}.toFixed({})", self.operand(value, CALL)?, digits);
+        } else if !(spec.is_empty() || spec == "d" || spec == "s") {
+            return Err(TranspileError::new(
+                format!("the format specification '{}' is not supported", spec),
+                expr.start,
+            ));
+        }
+        Ok(text)
+    }
+
+    fn constant(&mut self, expr: &Expr, constant: &Constant) -> Result<(String, u8)> {
+        let text = match *constant {
+            Constant::None => "null".to_string(),
+            Constant::Bool(value) => value.to_string(),
+            Constant::Str(ref value) => string_literal(value),
+            Constant::Int(value) if value.abs() <= MAX_SAFE_INTEGER => value.to_string(),
+            Constant::Int(_) | Constant::LongInt(_) => {
+                return Err(TranspileError::new(
+                    "integers beyond 2**53 are not supported",
+                    expr.start,
+                ))
+            }
+            Constant::Float(value) => float_literal(value, true),
+            Constant::Bytes(_) => {
+                return Err(TranspileError::new("bytes are not supported", expr.start))
+            }
+            Constant::Complex { .. } => {
+                return Err(TranspileError::new(
+                    "complex numbers are not supported",
+                    expr.start,
+                ))
+            }
+            Constant::Ellipsis => {
+                return Err(TranspileError::new("'...' is not supported", expr.start))
+            }
+            Constant::Tuple(ref elts) => {
+                let mut parts = Vec::new();
+                for elt in elts {
+                    parts.push(self.constant(expr, elt)?.0);
+                }
+                return Ok((format!("[{}]", parts.join(", ")), PRIMARY));
+            }
+        };
+        let level = if text.starts_with('-') || text.starts_with('(') {
+            UNARY
+        } else {
+            PRIMARY
+        };
+        Ok((text, level))
+    }
+}
+
+/// The names a body assigns, in the order they are first assigned, other
+/// than those it declares `global` or `nonlocal` and the classes it
+/// defines. Functions defined at module level (`top`) are declarations of
+/// their own.
+fn declared_names(body: &[Stmt], top: bool) -> Vec<String> {
+    fn targets(target: &Expr, names: &mut Vec<String>) {
+        match target.kind {
+            ExprKind::Name { ref id, .. } => names.push(id.clone()),
+            ExprKind::Tuple { ref elts, .. } | ExprKind::List { ref elts, .. } => {
+                for elt in elts {
+                    targets(elt, names);
+                }
+            }
+            ExprKind::Starred { ref value, .. } => targets(value, names),
+            _ => {}
+        }
+    }
+
+    fn walk(body: &[Stmt], top: bool, names: &mut Vec<String>, outer: &mut Vec<String>) {
+        for stmt in body {
+            match stmt.kind {
+                StmtKind::FunctionDef { ref name, .. } if !top => names.push(name.clone()),
+                StmtKind::Assign {
+                    targets: ref assigned,
+                    ..
+                } => {
+                    for target in assigned {
+                        targets(target, names);
+                    }
+                }
+                StmtKind::AugAssign { ref target, .. } | StmtKind::AnnAssign { ref target, .. } => {
+                    targets(target, names)
+                }
+                StmtKind::For {
+                    ref target,
+                    ref body,
+                    ref orelse,
+                    ..
+                } => {
+                    targets(target, names);
+                    walk(body, top, names, outer);
+                    walk(orelse, top, names, outer);
+                }
+                StmtKind::While {
+                    ref body,
+                    ref orelse,
+                    ..
+                }
+                | StmtKind::If {
+                    ref body,
+                    ref orelse,
+                    ..
+                } => {
+                    walk(body, top, names, outer);
+                    walk(orelse, top, names, outer);
+                }
+                StmtKind::Global(ref declared) | StmtKind::Nonlocal(ref declared) => {
+                    outer.extend(declared.iter().cloned())
+                }
+                _ => {}
+            }
+        }
+    }
+
+    let mut names = Vec::new();
+    let mut outer = Vec::new();
+    walk(body, top, &mut names, &mut outer);
+    let mut seen = HashSet::new();
+    names
+        .into_iter()
+        .filter(|name| !outer.contains(name) && seen.insert(name.clone()))
+        .collect()
+}
+
+/// Adds the names `target` binds to `types`: the name it is, of type
+/// `value`, or the names an unpacking assigns, whose types are unknown.
+fn bind(target: &Expr, value: Type, types: &mut HashMap<String, Type>) {
+    match target.kind {
+        ExprKind::Name { ref id, .. } => {
+            types.insert(id.clone(), value);
+        }
+        ExprKind::Tuple { ref elts, .. } | ExprKind::List { ref elts, .. } => {
+            for elt in elts {
+                bind(elt, Type::Unknown, types);
+            }
+        }
+        ExprKind::Starred { ref value, .. } => bind(value, Type::List, types),
+        _ => {}
+    }
+}
+
+/// The names declared `global` or `nonlocal` in `body` or the functions
+/// and classes in it.
+fn rebound_names(body: &[Stmt]) -> Vec<String> {
+    let mut names = Vec::new();
+    for stmt in body {
+        match stmt.kind {
+            StmtKind::Global(ref declared) | StmtKind::Nonlocal(ref declared) => {
+                names.extend(declared.iter().cloned())
+            }
+            StmtKind::FunctionDef { ref body, .. } | StmtKind::ClassDef { ref body, .. } => {
+                names.extend(rebound_names(body))
+            }
+            StmtKind::For {
+                ref body,
+                ref orelse,
+                ..
+            }
+            | StmtKind::While {
+                ref body,
+                ref orelse,
+                ..
+            }
+            | StmtKind::If {
+                ref body,
+                ref orelse,
+                ..
+            } => {
+                names.extend(rebound_names(body));
+                names.extend(rebound_names(orelse));
+            }
+            _ => {}
+        }
+    }
+    names
+}
+
+/// Adds the names of the classes `body` defines, at any depth, to `classes`.
+fn collect_classes(body: &[Stmt], classes: &mut HashSet<String>) {
+    for stmt in body {
+        match stmt.kind {
+            StmtKind::ClassDef {
+                ref name, ref body, ..
+            } => {
+                classes.insert(name.clone());
+                collect_classes(body, classes);
+            }
+            StmtKind::FunctionDef { ref body, .. } => collect_classes(body, classes),
+            StmtKind::For {
+                ref body,
+                ref orelse,
+                ..
+            }
+            | StmtKind::While {
+                ref body,
+                ref orelse,
+                ..
+            }
+            | StmtKind::If {
+                ref body,
+                ref orelse,
+                ..
+            } => {
+                collect_classes(body, classes);
+                collect_classes(orelse, classes);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Whether `expr` is the call `super()`.
+fn is_super(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::Call {
+            ref func,
+            ref args,
+            ref keywords,
+        } => {
+            args.is_empty()
+                && keywords.is_empty()
+                && matches!(func.kind, ExprKind::Name { ref id, .. } if id == "super")
+        }
+        _ => false,
+    }
+}
+
+/// Whether the body of an `__init__` calls `super().__init__`.
+fn calls_super_init(body: &[Stmt]) -> bool {
+    body.iter().any(|stmt| match stmt.kind {
+        StmtKind::Expr(Expr {
+            kind: ExprKind::Call { ref func, .. },
+            ..
+        }) => matches!(
+            func.kind,
+            ExprKind::Attribute { ref value, ref attr, .. } if attr == "__init__" && is_super(value)
+        ),
+        _ => false,
+    })
+}
+
+/// The statement `super().__init__()`.
+fn super_init_call(location: Location) -> Stmt {
+    let expr = |kind| Expr {
+        kind,
+        start: location,
+        end: location,
+    };
+    let super_call = expr(ExprKind::Call {
+        func: Box::new(expr(ExprKind::Name {
+            id: "super".to_string(),
+            ctx: ::ast::Context::Load,
+        })),
+        args: Vec::new(),
+        keywords: Vec::new(),
+    });
+    let call = expr(ExprKind::Call {
+        func: Box::new(expr(ExprKind::Attribute {
+            value: Box::new(super_call),
+            attr: "__init__".to_string(),
+            ctx: ::ast::Context::Load,
+        })),
+        args: Vec::new(),
+        keywords: Vec::new(),
+    });
+    Stmt {
+        kind: StmtKind::Expr(call),
+        start: location,
+        end: location,
+    }
+}
+
+/// Whether `expr` is a name or a constant, which a chained comparison may
+/// evaluate twice.
+fn is_simple(expr: &Expr) -> bool {
+    matches!(expr.kind, ExprKind::Name { .. } | ExprKind::Constant(_))
+}
+
+/// `n` if `index` is the literal `-n`.
+fn negative_index(index: &Expr) -> Option<i64> {
+    match index.kind {
+        ExprKind::UnaryOp {
+            op: UnaryOperator::USub,
+            ref operand,
+        } => match operand.kind {
+            ExprKind::Constant(Constant::Int(value)) if value > 0 => Some(value),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn identifier(name: &str) -> String {
+    if RESERVED.contains(&name) {
+        format!("{}_", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// `value` as a double-quoted JavaScript string.
+fn string_literal(value: &str) -> String {
+    let mut text = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => text.push_str("\\\""),
+            '\\' => text.push_str("\\\\"),
+            '\n' => text.push_str("\\n"),
+            '\r' => text.push_str("\\r"),
+            '\t' => text.push_str("\\t"),
+            c if (c as u32) < 0x20 || c == '\u{2028}' || c == '\u{2029}' => {
+                text.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => text.push(c),
+        }
+    }
+    text.push('"');
+    text
+}
+
+/// `value` as the literal text of a template string.
+fn template_text(value: &str) -> String {
+    let mut text = String::new();
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '`' => text.push_str("\\`"),
+            '\\' => text.push_str("\\\\"),
+            '$' if chars.peek() == Some(&'{') => text.push_str("\\$"),
+            '\r' => text.push_str("\\r"),
+            c if (c as u32) < 0x20 && c != '\n' && c != '\t' => {
+                text.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => text.push(c),
+        }
+    }
+    text
+}
@@ -0,0 +1,52 @@
+//! Targets a parsed module can be lowered to.
+//!
+//! Every target implements `Backend`, so that a driver can pick one without
+//! knowing what it produces. `Bytecode` is the compiler the VM runs;
+//! `javascript::JavaScript` is an experimental transpiler for a small
+//! subset of the language.
+
+pub mod javascript;
+
+use std::error::Error;
+
+use ast::Module;
+use compiler::{self, CodeObject, CompilerConfig, CompilerError};
+
+/// A code generator for one target.
+pub trait Backend {
+    /// What a compiled module becomes.
+    type Output;
+    /// Why a module could not be compiled.
+    type Error: Error;
+
+    /// A short name for the target, such as `"bytecode"`.
+    fn name(&self) -> &'static str;
+
+    /// Lowers `module`, read from `filename`, to the target.
+    fn compile(&self, module: &Module, filename: &str) -> Result<Self::Output, Self::Error>;
+}
+
+/// The bytecode compiler, as a backend.
+#[derive(Debug, Clone, Default)]
+pub struct Bytecode {
+    pub config: CompilerConfig,
+}
+
+impl Bytecode {
+    pub fn new(config: CompilerConfig) -> Bytecode {
+        Bytecode { config }
+    }
+}
+
+impl Backend for Bytecode {
+    type Output = CodeObject;
+    type Error = CompilerError;
+
+    fn name(&self) -> &'static str {
+        "bytecode"
+    }
+
+    fn compile(&self, module: &Module, filename: &str) -> Result<CodeObject, CompilerError> {
+        compiler::compile_with_config(module, filename, &self.config)
+    }
+}
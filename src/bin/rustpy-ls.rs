@@ -0,0 +1,36 @@
+//! `rustpy-ls`: a language server for Python, speaking the Language Server
+//! Protocol over stdin and stdout. Built with the `ls` feature.
+
+extern crate rustpy;
+
+use std::io;
+use std::panic;
+use std::process;
+use std::thread;
+
+use rustpy::STACK_SIZE;
+
+fn main() {
+    // On a thread with room for the deepest code the parser accepts, as
+    // the main thread's stack may not be.
+    let status = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(serve)
+        .unwrap()
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload));
+    process::exit(status);
+}
+
+/// Serves requests until the client exits, returning the exit status.
+fn serve() -> i32 {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    match rustpy::ls::serve(stdin.lock(), stdout.lock()) {
+        Ok(status) => status,
+        Err(err) => {
+            eprintln!("rustpy-ls: {}", err);
+            1
+        }
+    }
+}
@@ -0,0 +1,485 @@
+//! Standalone executables, for `rustpy build`.
+//!
+//! A script is compiled together with the modules it imports from its own
+//! directory into a `Bundle` of code objects. `build_executable` writes the
+//! bundle into a small Cargo project whose `main` embeds it with
+//! `include_bytes!` and hands it to `run`, which imports the modules as
+//! frozen modules and runs the script as `__main__`. Modules the VM provides
+//! itself, such as `sys`, are part of every executable; imports that are
+//! not found when building are left to fail at run time, as they would
+//! when running the script.
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ast::Constant;
+use compiler::{self, dump_code, load_code, CodeConstant, CodeObject, Instruction, MarshalError};
+use optimizer::optimize;
+use parser::parse;
+use tokenizer::decode;
+use vm::{Finder, FrozenFinder, FrozenModule, ModuleSpec, PathFinder, Vm};
+
+const MAGIC: &[u8; 4] = b"RPYB";
+
+/// Why a script could not be bundled or built.
+#[derive(Debug)]
+pub enum BuildError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    /// A file that does not decode, parse or compile.
+    Syntax {
+        path: PathBuf,
+        message: String,
+    },
+    /// Bundle data that `Bundle::from_bytes` cannot read.
+    Marshal(MarshalError),
+    /// Cargo failed to build the executable, with what it printed.
+    Cargo(String),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BuildError::Io {
+                ref path,
+                ref error,
+            } => write!(f, "{}: {}", path.display(), error),
+            BuildError::Syntax {
+                ref path,
+                ref message,
+            } => write!(f, "{}: {}", path.display(), message),
+            BuildError::Marshal(ref error) => write!(f, "{}", error),
+            BuildError::Cargo(ref output) => write!(f, "cargo build failed:\n{}", output),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+impl From<MarshalError> for BuildError {
+    fn from(error: MarshalError) -> BuildError {
+        BuildError::Marshal(error)
+    }
+}
+
+fn io_error(path: &Path) -> impl FnOnce(io::Error) -> BuildError + '_ {
+    move |error| BuildError::Io {
+        path: path.to_path_buf(),
+        error,
+    }
+}
+
+/// A script compiled with the modules it imports.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bundle {
+    /// The code of the script, run as `__main__`.
+    pub main: Arc<CodeObject>,
+    pub modules: Vec<FrozenModule>,
+}
+
+impl Bundle {
+    /// Compiles `script` and, transitively, the modules it imports that are
+    /// found in its directory.
+    pub fn from_script(script: &Path) -> Result<Bundle, BuildError> {
+        let directory = match script.parent() {
+            Some(parent) if parent.as_os_str().is_empty() => PathBuf::from("."),
+            Some(parent) => parent.to_path_buf(),
+            None => PathBuf::from("."),
+        };
+        let main = Arc::new(compile_file(script)?);
+        let mut collector = Collector {
+            search_path: vec![directory],
+            modules: HashMap::new(),
+            order: Vec::new(),
+        };
+        collector.imports_of(&main, "")?;
+        let mut modules = collector.modules;
+        let modules = collector
+            .order
+            .iter()
+            .filter_map(|name| modules.remove(name).and_then(|module| module))
+            .collect();
+        Ok(Bundle { main, modules })
+    }
+
+    /// The bundle as bytes, for `from_bytes`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        let part = |out: &mut Vec<u8>, bytes: &[u8]| {
+            out.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+            out.extend_from_slice(bytes);
+        };
+        part(&mut out, &dump_code(&self.main));
+        for module in &self.modules {
+            part(&mut out, module.name.as_bytes());
+            part(&mut out, &[module.is_package as u8]);
+            part(&mut out, &dump_code(&module.code));
+        }
+        out
+    }
+
+    /// Reads a bundle written by `to_bytes`.
+    pub fn from_bytes(data: &[u8]) -> Result<Bundle, MarshalError> {
+        if !data.starts_with(MAGIC) {
+            return Err(MarshalError {
+                message: "not a bundle".to_string(),
+                offset: 0,
+            });
+        }
+        let mut offset = MAGIC.len();
+        let main = Arc::new(load_code(read_part(data, &mut offset)?)?);
+        let mut modules = Vec::new();
+        while offset < data.len() {
+            let name = String::from_utf8_lossy(read_part(data, &mut offset)?).into_owned();
+            let is_package = read_part(data, &mut offset)? != [0];
+            let code = Arc::new(load_code(read_part(data, &mut offset)?)?);
+            modules.push(FrozenModule {
+                name,
+                is_package,
+                code,
+            });
+        }
+        Ok(Bundle { main, modules })
+    }
+}
+
+/// The part of a bundle at `offset`, after its length, moving `offset`
+/// past it.
+fn read_part<'a>(data: &'a [u8], offset: &mut usize) -> Result<&'a [u8], MarshalError> {
+    let truncated = || MarshalError {
+        message: "truncated bundle".to_string(),
+        offset: *offset,
+    };
+    let mut len = [0; 4];
+    len.copy_from_slice(data.get(*offset..*offset + 4).ok_or_else(truncated)?);
+    let start = *offset + 4;
+    let end = start + u32::from_le_bytes(len) as usize;
+    let part = data.get(start..end).ok_or_else(truncated)?;
+    *offset = end;
+    Ok(part)
+}
+
+/// Finds and compiles the modules that code imports.
+struct Collector {
+    search_path: Vec<PathBuf>,
+    /// Each module looked for, by name, with `None` for those not found.
+    modules: HashMap<String, Option<FrozenModule>>,
+    /// The modules in the order they were first looked for.
+    order: Vec<String>,
+}
+
+impl Collector {
+    /// Collects the modules imported by `code` and the code nested in it,
+    /// which belongs to a module of the package `package`.
+    fn imports_of(&mut self, code: &CodeObject, package: &str) -> Result<(), BuildError> {
+        for (index, instruction) in code.instructions.iter().enumerate() {
+            let name = match *instruction {
+                Instruction::ImportName(name) => &code.names[name],
+                _ => continue,
+            };
+            // The level and from-list are the constants loaded just before.
+            let constant = |back: usize| match index.checked_sub(back) {
+                Some(position) => match code.instructions[position] {
+                    Instruction::LoadConst(constant) => match code.constants[constant] {
+                        CodeConstant::Value(ref value) => Some(value),
+                        _ => None,
+                    },
+                    _ => None,
+                },
+                None => None,
+            };
+            let level = match constant(2) {
+                Some(&Constant::Int(level)) => level as usize,
+                _ => 0,
+            };
+            let full_name = match resolve(name, package, level) {
+                Some(full_name) => full_name,
+                None => continue,
+            };
+            if !self.import(&full_name)? {
+                continue;
+            }
+            if let Some(Constant::Tuple(ref fromlist)) = constant(1) {
+                for item in fromlist {
+                    match *item {
+                        Constant::Str(ref item) if item != "*" => {
+                            self.import(&format!("{}.{}", full_name, item))?;
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        for constant in &code.constants {
+            if let CodeConstant::Code(ref nested) = *constant {
+                self.imports_of(nested, package)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the module `name` and its parent packages, returning
+    /// whether it was found.
+    fn import(&mut self, name: &str) -> Result<bool, BuildError> {
+        if let Some(module) = self.modules.get(name) {
+            return Ok(module.is_some());
+        }
+        let path = match name.rfind('.') {
+            Some(dot) => {
+                if !self.import(&name[..dot])? {
+                    return Ok(false);
+                }
+                match self.modules[&name[..dot]] {
+                    Some(ref parent) if parent.is_package => {
+                        let origin = Path::new(&parent.code.filename);
+                        origin.parent().map(Path::to_path_buf).into_iter().collect()
+                    }
+                    _ => return Ok(false),
+                }
+            }
+            None => self.search_path.clone(),
+        };
+        self.order.push(name.to_string());
+        let spec = match PathFinder.find_spec(name, &path) {
+            Some(spec) => spec,
+            None => {
+                self.modules.insert(name.to_string(), None);
+                return Ok(false);
+            }
+        };
+        let source = PathFinder
+            .get_source(&spec)
+            .map_err(io_error(&spec.origin))?;
+        let code = Arc::new(compile_source(&source, &spec.origin)?);
+        let is_package = spec.submodule_search_locations.is_some();
+        self.modules.insert(
+            name.to_string(),
+            Some(FrozenModule {
+                name: name.to_string(),
+                is_package,
+                code: code.clone(),
+            }),
+        );
+        let package = package_of(&spec, is_package);
+        self.imports_of(&code, package)?;
+        Ok(true)
+    }
+}
+
+/// The package whose submodules relative imports in a module are relative
+/// to.
+fn package_of(spec: &ModuleSpec, is_package: bool) -> &str {
+    if is_package {
+        return &spec.name;
+    }
+    spec.name.rfind('.').map_or("", |dot| &spec.name[..dot])
+}
+
+/// The absolute name of the module `name` imported `level` packages up
+/// from `package`, or `None` for a relative import that would fail.
+fn resolve(name: &str, package: &str, level: usize) -> Option<String> {
+    if level == 0 {
+        return Some(name.to_string());
+    }
+    let parts: Vec<&str> = package.split('.').collect();
+    if package.is_empty() || level > parts.len() {
+        return None;
+    }
+    let base = parts[..parts.len() - (level - 1)].join(".");
+    Some(if name.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, name)
+    })
+}
+
+/// Reads, parses and compiles a source file.
+fn compile_file(path: &Path) -> Result<CodeObject, BuildError> {
+    let bytes = fs::read(path).map_err(io_error(path))?;
+    compile_source(&bytes, path)
+}
+
+/// Parses and compiles the source of the file `path`, as bytes to be
+/// decoded.
+fn compile_source(bytes: &[u8], path: &Path) -> Result<CodeObject, BuildError> {
+    let syntax_error = |message: String| BuildError::Syntax {
+        path: path.to_path_buf(),
+        message,
+    };
+    let source = decode(bytes).map_err(|err| syntax_error(err.to_string()))?;
+    let mut module = parse(&source.text).map_err(|err| syntax_error(err.to_string()))?;
+    optimize(&mut module);
+    let filename = path.to_string_lossy();
+    compiler::compile(&module, &filename).map_err(|err| syntax_error(err.to_string()))
+}
+
+/// Runs a bundle written by `Bundle::to_bytes`, returning the exit status
+/// of the process.
+pub fn run(data: &[u8]) -> i32 {
+    let bundle = match Bundle::from_bytes(data) {
+        Ok(bundle) => bundle,
+        Err(err) => {
+            eprintln!("rustpy: {}", err);
+            return 1;
+        }
+    };
+    let mut vm = Vm::new();
+    let argv: Vec<String> = env::args().collect();
+    vm.set_argv(&argv);
+    vm.add_finder(FrozenFinder::new(bundle.modules));
+    let globals = vm.main_module().dict().unwrap().clone();
+    match vm.run_code(bundle.main, &globals) {
+        Ok(_) => 0,
+        Err(exception) => vm.exit_status(&exception),
+    }
+}
+
+/// Builds a native executable at `output` that runs `script`, with Cargo.
+///
+/// Each build writes its Cargo project to a directory of its own under the
+/// system's temporary directory, so that builds may run at once. They
+/// share a target directory, which Cargo locks, so that later builds reuse
+/// the compiled VM, and each names its binary after its project. The VM is
+/// built, with the features this one has, from the sources this one was
+/// built from, or from those in the directory `RUSTPY_SOURCE` names if it
+/// is set. Set `CARGO` to choose the Cargo to run.
+pub fn build_executable(script: &Path, output: &Path) -> Result<(), BuildError> {
+    let bundle = Bundle::from_script(script)?;
+    let (project, name) = project_directory()?;
+    let result = build_project(&project, &name, &bundle, output);
+    let _ = fs::remove_dir_all(&project);
+    result
+}
+
+/// A new, empty directory for the project of one build, and the name of
+/// the binary it builds.
+fn project_directory() -> Result<(PathBuf, String), BuildError> {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+    for attempt in 0u32.. {
+        let name = format!("rustpy-bundle-{}-{}-{}", process::id(), nanos, attempt);
+        let project = env::temp_dir().join(&name);
+        match fs::create_dir(&project) {
+            Ok(()) => return Ok((project, name)),
+            Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(error) => {
+                return Err(BuildError::Io {
+                    path: project,
+                    error,
+                })
+            }
+        }
+    }
+    unreachable!("ran out of build directory names")
+}
+
+fn build_project(
+    project: &Path,
+    name: &str,
+    bundle: &Bundle,
+    output: &Path,
+) -> Result<(), BuildError> {
+    let src = project.join("src");
+    fs::create_dir_all(&src).map_err(io_error(&src))?;
+    let write =
+        |path: PathBuf, contents: &[u8]| fs::write(&path, contents).map_err(io_error(&path));
+    let source = env::var_os("RUSTPY_SOURCE")
+        .map_or_else(|| PathBuf::from(env!("CARGO_MANIFEST_DIR")), PathBuf::from);
+    if !source.join("Cargo.toml").is_file() {
+        return Err(BuildError::Io {
+            error: io::Error::new(
+                io::ErrorKind::NotFound,
+                "no rustpy sources to build the executable with; set RUSTPY_SOURCE",
+            ),
+            path: source,
+        });
+    }
+    write(
+        project.join("Cargo.toml"),
+        manifest(name, &source).as_bytes(),
+    )?;
+    write(src.join("main.rs"), MAIN.as_bytes())?;
+    write(project.join("bundle.bin"), &bundle.to_bytes())?;
+
+    let target = env::temp_dir().join("rustpy-build-target");
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let result = Command::new(cargo)
+        .args(["build", "--release", "--quiet", "--manifest-path"])
+        .arg(project.join("Cargo.toml"))
+        .arg("--target-dir")
+        .arg(&target)
+        .output()
+        .map_err(io_error(Path::new("cargo")))?;
+    if !result.status.success() {
+        return Err(BuildError::Cargo(
+            String::from_utf8_lossy(&result.stderr).into_owned(),
+        ));
+    }
+    let binary = target
+        .join("release")
+        .join(format!("{}{}", name, env::consts::EXE_SUFFIX));
+    fs::copy(&binary, output).map_err(io_error(output))?;
+    let _ = fs::remove_file(&binary);
+    Ok(())
+}
+
+/// The features of this rustpy, and whether each is enabled, for the
+/// executable's rustpy to be built with the same.
+const FEATURES: &[(&str, bool)] = &[
+    ("capi", cfg!(feature = "capi")),
+    ("tracing", cfg!(feature = "tracing")),
+    ("chrome-trace", cfg!(feature = "chrome-trace")),
+    ("jit", cfg!(feature = "jit")),
+    ("sqlite", cfg!(feature = "sqlite")),
+    ("serde", cfg!(feature = "serde")),
+    ("ls", cfg!(feature = "ls")),
+];
+
+/// The manifest of the project `name`, which depends on the rustpy in the
+/// directory `source` with the features of this one.
+fn manifest(name: &str, source: &Path) -> String {
+    let features = FEATURES
+        .iter()
+        .filter(|&&(_, enabled)| enabled)
+        .map(|&(feature, _)| format!("{:?}", feature))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "[package]\n\
+         name = {:?}\n\
+         version = \"0.0.0\"\n\
+         edition = \"2021\"\n\
+         \n\
+         [dependencies]\n\
+         rustpy = {{ path = {:?}, features = [{}] }}\n\
+         \n\
+         [profile.release]\n\
+         strip = true\n\
+         \n\
+         # Not part of any workspace around the temporary directory.\n\
+         [workspace]\n",
+        name,
+        source.to_string_lossy(),
+        features
+    )
+}
+
+const MAIN: &str = "extern crate rustpy;
+
+static BUNDLE: &[u8] = include_bytes!(\"../bundle.bin\");
+
+fn main() {
+    std::process::exit(rustpy::bundle::run(BUNDLE));
+}
+";
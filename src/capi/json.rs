@@ -0,0 +1,870 @@
+//! JSON for tokens and syntax trees, as returned through the C ABI.
+//!
+//! AST nodes are objects whose `_type` is the node name, followed by its
+//! fields under their CPython names and then its `start` and `end`
+//! locations. Operators and contexts are plain strings such as `"Add"` or
+//! `"Load"`. A constant has a `kind` (`None`, `bool`, `str`, `bytes`, `int`,
+//! `float`, `complex`, `Ellipsis` or `tuple`) next to its `value`, since JSON
+//! cannot tell them apart on its own.
+
+use ast::{
+    Alias, Arg, Arguments, BoolOperator, CmpOperator, Comprehension, Constant, Context,
+    ExceptHandler, Expr, ExprKind, Keyword, MatchCase, Module, Operator, Pattern, PatternKind,
+    Stmt, StmtKind, TypeParam, TypeParamKind, UnaryOperator, WithItem,
+};
+use tokenizer::{Location, Span, Token};
+
+pub trait ToJson {
+    fn write_json(&self, out: &mut String);
+}
+
+pub fn to_json<T: ToJson + ?Sized>(value: &T) -> String {
+    let mut out = String::new();
+    value.write_json(&mut out);
+    out
+}
+
+/// Writes the members of a JSON object one at a time.
+pub struct Object<'a> {
+    out: &'a mut String,
+    empty: bool,
+}
+
+impl<'a> Object<'a> {
+    pub fn new(out: &'a mut String) -> Object<'a> {
+        out.push('{');
+        Object { out, empty: true }
+    }
+
+    /// Starts an AST node object named `node`.
+    pub fn node(out: &'a mut String, node: &str) -> Object<'a> {
+        let mut object = Object::new(out);
+        object.field("_type", node);
+        object
+    }
+
+    pub fn field<T: ToJson + ?Sized>(&mut self, name: &str, value: &T) -> &mut Object<'a> {
+        if !self.empty {
+            self.out.push(',');
+        }
+        self.empty = false;
+        name.write_json(self.out);
+        self.out.push(':');
+        value.write_json(self.out);
+        self
+    }
+
+    pub fn end(&mut self) {
+        self.out.push('}');
+    }
+}
+
+impl ToJson for str {
+    fn write_json(&self, out: &mut String) {
+        out.push('"');
+        for c in self.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if c < ' ' => out.push_str(&format!("\\u{:04x}", c as u32)),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+}
+
+impl ToJson for String {
+    fn write_json(&self, out: &mut String) {
+        self.as_str().write_json(out);
+    }
+}
+
+impl ToJson for char {
+    fn write_json(&self, out: &mut String) {
+        self.to_string().write_json(out);
+    }
+}
+
+impl ToJson for bool {
+    fn write_json(&self, out: &mut String) {
+        out.push_str(if *self { "true" } else { "false" });
+    }
+}
+
+impl ToJson for usize {
+    fn write_json(&self, out: &mut String) {
+        out.push_str(&self.to_string());
+    }
+}
+
+impl ToJson for f64 {
+    /// Infinities and NaN have no JSON number, so they are written as the
+    /// strings `"inf"`, `"-inf"` and `"nan"`.
+    fn write_json(&self, out: &mut String) {
+        if self.is_nan() {
+            "nan".write_json(out);
+        } else if self.is_infinite() {
+            (if *self > 0.0 { "inf" } else { "-inf" }).write_json(out);
+        } else {
+            out.push_str(&format!("{:?}", self));
+        }
+    }
+}
+
+impl<T: ToJson> ToJson for Option<T> {
+    fn write_json(&self, out: &mut String) {
+        match *self {
+            Some(ref value) => value.write_json(out),
+            None => out.push_str("null"),
+        }
+    }
+}
+
+impl<T: ToJson + ?Sized> ToJson for Box<T> {
+    fn write_json(&self, out: &mut String) {
+        (**self).write_json(out);
+    }
+}
+
+impl<T: ToJson> ToJson for [T] {
+    fn write_json(&self, out: &mut String) {
+        out.push('[');
+        for (index, value) in self.iter().enumerate() {
+            if index > 0 {
+                out.push(',');
+            }
+            value.write_json(out);
+        }
+        out.push(']');
+    }
+}
+
+impl<T: ToJson> ToJson for Vec<T> {
+    fn write_json(&self, out: &mut String) {
+        self[..].write_json(out);
+    }
+}
+
+impl ToJson for Location {
+    fn write_json(&self, out: &mut String) {
+        Object::new(out)
+            .field("line", &self.line)
+            .field("column", &self.column)
+            .end();
+    }
+}
+
+impl ToJson for Span {
+    fn write_json(&self, out: &mut String) {
+        Object::new(out)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .end();
+    }
+}
+
+impl ToJson for Token {
+    fn write_json(&self, out: &mut String) {
+        Object::new(out)
+            .field("type", self.kind.name())
+            .field("value", &self.value)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .field("span", &self.span)
+            .end();
+    }
+}
+
+macro_rules! json_as_debug_name {
+    ($($ty:ty),*) => {
+        $(impl ToJson for $ty {
+            fn write_json(&self, out: &mut String) {
+                format!("{:?}", self).write_json(out);
+            }
+        })*
+    };
+}
+
+json_as_debug_name!(Context, BoolOperator, Operator, UnaryOperator, CmpOperator);
+
+/// The value of a constant, without its kind.
+struct ConstantValue<'a>(&'a Constant);
+
+impl<'a> ToJson for ConstantValue<'a> {
+    fn write_json(&self, out: &mut String) {
+        match *self.0 {
+            Constant::None => out.push_str("null"),
+            Constant::Bool(value) => value.write_json(out),
+            Constant::Str(ref value) => value.write_json(out),
+            Constant::Bytes(ref value) => {
+                let bytes: Vec<usize> = value.iter().map(|&b| usize::from(b)).collect();
+                bytes.write_json(out);
+            }
+            Constant::Int(value) => out.push_str(&value.to_string()),
+            Constant::LongInt(ref digits) => out.push_str(digits),
+            Constant::Float(value) => value.write_json(out),
+            Constant::Complex { real, imag } => {
+                Object::new(out)
+                    .field("real", &real)
+                    .field("imag", &imag)
+                    .end();
+            }
+            Constant::Ellipsis => "...".write_json(out),
+            Constant::Tuple(ref elts) => {
+                out.push('[');
+                for (index, elt) in elts.iter().enumerate() {
+                    if index > 0 {
+                        out.push(',');
+                    }
+                    Object::new(out)
+                        .field("kind", constant_kind(elt))
+                        .field("value", &ConstantValue(elt))
+                        .end();
+                }
+                out.push(']');
+            }
+        }
+    }
+}
+
+fn constant_kind(constant: &Constant) -> &'static str {
+    match *constant {
+        Constant::None => "None",
+        Constant::Bool(_) => "bool",
+        Constant::Str(_) => "str",
+        Constant::Bytes(_) => "bytes",
+        Constant::Int(_) | Constant::LongInt(_) => "int",
+        Constant::Float(_) => "float",
+        Constant::Complex { .. } => "complex",
+        Constant::Ellipsis => "Ellipsis",
+        Constant::Tuple(_) => "tuple",
+    }
+}
+
+impl ToJson for Module {
+    fn write_json(&self, out: &mut String) {
+        Object::node(out, "Module").field("body", &self.body).end();
+    }
+}
+
+impl ToJson for Stmt {
+    fn write_json(&self, out: &mut String) {
+        let mut node = match self.kind {
+            StmtKind::FunctionDef {
+                ref name,
+                ref args,
+                ref body,
+                ref decorator_list,
+                ref returns,
+                ref type_params,
+                is_async,
+                ..
+            } => {
+                let kind = if is_async {
+                    "AsyncFunctionDef"
+                } else {
+                    "FunctionDef"
+                };
+                let mut node = Object::node(out, kind);
+                node.field("name", name)
+                    .field("args", args)
+                    .field("body", body)
+                    .field("decorator_list", decorator_list)
+                    .field("returns", returns)
+                    .field("type_params", type_params);
+                node
+            }
+            StmtKind::ClassDef {
+                ref name,
+                ref bases,
+                ref keywords,
+                ref body,
+                ref decorator_list,
+                ref type_params,
+            } => {
+                let mut node = Object::node(out, "ClassDef");
+                node.field("name", name)
+                    .field("bases", bases)
+                    .field("keywords", keywords)
+                    .field("body", body)
+                    .field("decorator_list", decorator_list)
+                    .field("type_params", type_params);
+                node
+            }
+            StmtKind::Return(ref value) => {
+                let mut node = Object::node(out, "Return");
+                node.field("value", value);
+                node
+            }
+            StmtKind::Delete(ref targets) => {
+                let mut node = Object::node(out, "Delete");
+                node.field("targets", targets);
+                node
+            }
+            StmtKind::Assign {
+                ref targets,
+                ref value,
+                ..
+            } => {
+                let mut node = Object::node(out, "Assign");
+                node.field("targets", targets).field("value", value);
+                node
+            }
+            StmtKind::TypeAlias {
+                ref name,
+                ref type_params,
+                ref value,
+            } => {
+                let mut node = Object::node(out, "TypeAlias");
+                node.field("name", name)
+                    .field("type_params", type_params)
+                    .field("value", value);
+                node
+            }
+            StmtKind::AugAssign {
+                ref target,
+                op,
+                ref value,
+            } => {
+                let mut node = Object::node(out, "AugAssign");
+                node.field("target", target)
+                    .field("op", &op)
+                    .field("value", value);
+                node
+            }
+            StmtKind::AnnAssign {
+                ref target,
+                ref annotation,
+                ref value,
+                simple,
+            } => {
+                let mut node = Object::node(out, "AnnAssign");
+                node.field("target", target)
+                    .field("annotation", annotation)
+                    .field("value", value)
+                    .field("simple", &simple);
+                node
+            }
+            StmtKind::For {
+                ref target,
+                ref iter,
+                ref body,
+                ref orelse,
+                is_async,
+                ..
+            } => {
+                let mut node = Object::node(out, if is_async { "AsyncFor" } else { "For" });
+                node.field("target", target)
+                    .field("iter", iter)
+                    .field("body", body)
+                    .field("orelse", orelse);
+                node
+            }
+            StmtKind::While {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                let mut node = Object::node(out, "While");
+                node.field("test", test)
+                    .field("body", body)
+                    .field("orelse", orelse);
+                node
+            }
+            StmtKind::If {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                let mut node = Object::node(out, "If");
+                node.field("test", test)
+                    .field("body", body)
+                    .field("orelse", orelse);
+                node
+            }
+            StmtKind::With {
+                ref items,
+                ref body,
+                is_async,
+                ..
+            } => {
+                let mut node = Object::node(out, if is_async { "AsyncWith" } else { "With" });
+                node.field("items", items).field("body", body);
+                node
+            }
+            StmtKind::Match {
+                ref subject,
+                ref cases,
+            } => {
+                let mut node = Object::node(out, "Match");
+                node.field("subject", subject).field("cases", cases);
+                node
+            }
+            StmtKind::Raise { ref exc, ref cause } => {
+                let mut node = Object::node(out, "Raise");
+                node.field("exc", exc).field("cause", cause);
+                node
+            }
+            StmtKind::Try {
+                ref body,
+                ref handlers,
+                ref orelse,
+                ref finalbody,
+                is_star,
+            } => {
+                let mut node = Object::node(out, if is_star { "TryStar" } else { "Try" });
+                node.field("body", body)
+                    .field("handlers", handlers)
+                    .field("orelse", orelse)
+                    .field("finalbody", finalbody);
+                node
+            }
+            StmtKind::Assert { ref test, ref msg } => {
+                let mut node = Object::node(out, "Assert");
+                node.field("test", test).field("msg", msg);
+                node
+            }
+            StmtKind::Import(ref names) => {
+                let mut node = Object::node(out, "Import");
+                node.field("names", names);
+                node
+            }
+            StmtKind::ImportFrom {
+                ref module,
+                ref names,
+                level,
+            } => {
+                let mut node = Object::node(out, "ImportFrom");
+                node.field("module", module)
+                    .field("names", names)
+                    .field("level", &level);
+                node
+            }
+            StmtKind::Global(ref names) => {
+                let mut node = Object::node(out, "Global");
+                node.field("names", names);
+                node
+            }
+            StmtKind::Nonlocal(ref names) => {
+                let mut node = Object::node(out, "Nonlocal");
+                node.field("names", names);
+                node
+            }
+            StmtKind::Expr(ref value) => {
+                let mut node = Object::node(out, "Expr");
+                node.field("value", value);
+                node
+            }
+            StmtKind::Pass => Object::node(out, "Pass"),
+            StmtKind::Break => Object::node(out, "Break"),
+            StmtKind::Continue => Object::node(out, "Continue"),
+            StmtKind::Error => Object::node(out, "Error"),
+        };
+        node.field("start", &self.start)
+            .field("end", &self.end)
+            .end();
+    }
+}
+
+impl ToJson for Expr {
+    fn write_json(&self, out: &mut String) {
+        let mut node = match self.kind {
+            ExprKind::BoolOp { op, ref values } => {
+                let mut node = Object::node(out, "BoolOp");
+                node.field("op", &op).field("values", values);
+                node
+            }
+            ExprKind::NamedExpr {
+                ref target,
+                ref value,
+            } => {
+                let mut node = Object::node(out, "NamedExpr");
+                node.field("target", target).field("value", value);
+                node
+            }
+            ExprKind::BinOp {
+                ref left,
+                op,
+                ref right,
+            } => {
+                let mut node = Object::node(out, "BinOp");
+                node.field("left", left)
+                    .field("op", &op)
+                    .field("right", right);
+                node
+            }
+            ExprKind::UnaryOp { op, ref operand } => {
+                let mut node = Object::node(out, "UnaryOp");
+                node.field("op", &op).field("operand", operand);
+                node
+            }
+            ExprKind::Lambda { ref args, ref body } => {
+                let mut node = Object::node(out, "Lambda");
+                node.field("args", args).field("body", body);
+                node
+            }
+            ExprKind::IfExp {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                let mut node = Object::node(out, "IfExp");
+                node.field("test", test)
+                    .field("body", body)
+                    .field("orelse", orelse);
+                node
+            }
+            ExprKind::Dict {
+                ref keys,
+                ref values,
+            } => {
+                let mut node = Object::node(out, "Dict");
+                node.field("keys", keys).field("values", values);
+                node
+            }
+            ExprKind::Set(ref elts) => {
+                let mut node = Object::node(out, "Set");
+                node.field("elts", elts);
+                node
+            }
+            ExprKind::ListComp {
+                ref elt,
+                ref generators,
+            } => {
+                let mut node = Object::node(out, "ListComp");
+                node.field("elt", elt).field("generators", generators);
+                node
+            }
+            ExprKind::SetComp {
+                ref elt,
+                ref generators,
+            } => {
+                let mut node = Object::node(out, "SetComp");
+                node.field("elt", elt).field("generators", generators);
+                node
+            }
+            ExprKind::DictComp {
+                ref key,
+                ref value,
+                ref generators,
+            } => {
+                let mut node = Object::node(out, "DictComp");
+                node.field("key", key)
+                    .field("value", value)
+                    .field("generators", generators);
+                node
+            }
+            ExprKind::GeneratorExp {
+                ref elt,
+                ref generators,
+            } => {
+                let mut node = Object::node(out, "GeneratorExp");
+                node.field("elt", elt).field("generators", generators);
+                node
+            }
+            ExprKind::Await(ref value) => {
+                let mut node = Object::node(out, "Await");
+                node.field("value", value);
+                node
+            }
+            ExprKind::Yield(ref value) => {
+                let mut node = Object::node(out, "Yield");
+                node.field("value", value);
+                node
+            }
+            ExprKind::YieldFrom(ref value) => {
+                let mut node = Object::node(out, "YieldFrom");
+                node.field("value", value);
+                node
+            }
+            ExprKind::Compare {
+                ref left,
+                ref ops,
+                ref comparators,
+            } => {
+                let mut node = Object::node(out, "Compare");
+                node.field("left", left)
+                    .field("ops", ops)
+                    .field("comparators", comparators);
+                node
+            }
+            ExprKind::Call {
+                ref func,
+                ref args,
+                ref keywords,
+            } => {
+                let mut node = Object::node(out, "Call");
+                node.field("func", func)
+                    .field("args", args)
+                    .field("keywords", keywords);
+                node
+            }
+            ExprKind::FormattedValue {
+                ref value,
+                conversion,
+                ref format_spec,
+            } => {
+                let mut node = Object::node(out, "FormattedValue");
+                node.field("value", value)
+                    .field("conversion", &conversion)
+                    .field("format_spec", format_spec);
+                node
+            }
+            ExprKind::JoinedStr(ref values) => {
+                let mut node = Object::node(out, "JoinedStr");
+                node.field("values", values);
+                node
+            }
+            ExprKind::Constant(ref value) => {
+                let mut node = Object::node(out, "Constant");
+                node.field("kind", constant_kind(value))
+                    .field("value", &ConstantValue(value));
+                node
+            }
+            ExprKind::Attribute {
+                ref value,
+                ref attr,
+                ctx,
+            } => {
+                let mut node = Object::node(out, "Attribute");
+                node.field("value", value)
+                    .field("attr", attr)
+                    .field("ctx", &ctx);
+                node
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ctx,
+            } => {
+                let mut node = Object::node(out, "Subscript");
+                node.field("value", value)
+                    .field("slice", slice)
+                    .field("ctx", &ctx);
+                node
+            }
+            ExprKind::Starred { ref value, ctx } => {
+                let mut node = Object::node(out, "Starred");
+                node.field("value", value).field("ctx", &ctx);
+                node
+            }
+            ExprKind::Name { ref id, ctx } => {
+                let mut node = Object::node(out, "Name");
+                node.field("id", id).field("ctx", &ctx);
+                node
+            }
+            ExprKind::List { ref elts, ctx } => {
+                let mut node = Object::node(out, "List");
+                node.field("elts", elts).field("ctx", &ctx);
+                node
+            }
+            ExprKind::Tuple { ref elts, ctx } => {
+                let mut node = Object::node(out, "Tuple");
+                node.field("elts", elts).field("ctx", &ctx);
+                node
+            }
+            ExprKind::Slice {
+                ref lower,
+                ref upper,
+                ref step,
+            } => {
+                let mut node = Object::node(out, "Slice");
+                node.field("lower", lower)
+                    .field("upper", upper)
+                    .field("step", step);
+                node
+            }
+        };
+        node.field("start", &self.start)
+            .field("end", &self.end)
+            .end();
+    }
+}
+
+impl ToJson for Comprehension {
+    fn write_json(&self, out: &mut String) {
+        Object::node(out, "comprehension")
+            .field("target", &self.target)
+            .field("iter", &self.iter)
+            .field("ifs", &self.ifs)
+            .field("is_async", &self.is_async)
+            .end();
+    }
+}
+
+impl ToJson for MatchCase {
+    fn write_json(&self, out: &mut String) {
+        Object::node(out, "match_case")
+            .field("pattern", &self.pattern)
+            .field("guard", &self.guard)
+            .field("body", &self.body)
+            .end();
+    }
+}
+
+impl ToJson for Pattern {
+    fn write_json(&self, out: &mut String) {
+        let mut node = match self.kind {
+            PatternKind::MatchValue(ref value) => {
+                let mut node = Object::node(out, "MatchValue");
+                node.field("value", value);
+                node
+            }
+            PatternKind::MatchSingleton(ref value) => {
+                let mut node = Object::node(out, "MatchSingleton");
+                node.field("value", &ConstantValue(value));
+                node
+            }
+            PatternKind::MatchSequence(ref patterns) => {
+                let mut node = Object::node(out, "MatchSequence");
+                node.field("patterns", patterns);
+                node
+            }
+            PatternKind::MatchMapping {
+                ref keys,
+                ref patterns,
+                ref rest,
+            } => {
+                let mut node = Object::node(out, "MatchMapping");
+                node.field("keys", keys)
+                    .field("patterns", patterns)
+                    .field("rest", rest);
+                node
+            }
+            PatternKind::MatchClass {
+                ref cls,
+                ref patterns,
+                ref kwd_attrs,
+                ref kwd_patterns,
+            } => {
+                let mut node = Object::node(out, "MatchClass");
+                node.field("cls", cls)
+                    .field("patterns", patterns)
+                    .field("kwd_attrs", kwd_attrs)
+                    .field("kwd_patterns", kwd_patterns);
+                node
+            }
+            PatternKind::MatchStar(ref name) => {
+                let mut node = Object::node(out, "MatchStar");
+                node.field("name", name);
+                node
+            }
+            PatternKind::MatchAs {
+                ref pattern,
+                ref name,
+            } => {
+                let mut node = Object::node(out, "MatchAs");
+                node.field("pattern", pattern).field("name", name);
+                node
+            }
+            PatternKind::MatchOr(ref patterns) => {
+                let mut node = Object::node(out, "MatchOr");
+                node.field("patterns", patterns);
+                node
+            }
+        };
+        node.field("start", &self.start)
+            .field("end", &self.end)
+            .end();
+    }
+}
+
+impl ToJson for ExceptHandler {
+    fn write_json(&self, out: &mut String) {
+        Object::node(out, "ExceptHandler")
+            .field("type", &self.type_)
+            .field("name", &self.name)
+            .field("body", &self.body)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .end();
+    }
+}
+
+impl ToJson for Arguments {
+    fn write_json(&self, out: &mut String) {
+        Object::node(out, "arguments")
+            .field("posonlyargs", &self.posonlyargs)
+            .field("args", &self.args)
+            .field("vararg", &self.vararg)
+            .field("kwonlyargs", &self.kwonlyargs)
+            .field("kw_defaults", &self.kw_defaults)
+            .field("kwarg", &self.kwarg)
+            .field("defaults", &self.defaults)
+            .end();
+    }
+}
+
+impl ToJson for Arg {
+    fn write_json(&self, out: &mut String) {
+        Object::node(out, "arg")
+            .field("arg", &self.arg)
+            .field("annotation", &self.annotation)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .end();
+    }
+}
+
+impl ToJson for TypeParam {
+    fn write_json(&self, out: &mut String) {
+        let mut node = match self.kind {
+            TypeParamKind::TypeVar {
+                ref name,
+                ref bound,
+            } => {
+                let mut node = Object::node(out, "TypeVar");
+                node.field("name", name).field("bound", bound);
+                node
+            }
+            TypeParamKind::ParamSpec(ref name) => {
+                let mut node = Object::node(out, "ParamSpec");
+                node.field("name", name);
+                node
+            }
+            TypeParamKind::TypeVarTuple(ref name) => {
+                let mut node = Object::node(out, "TypeVarTuple");
+                node.field("name", name);
+                node
+            }
+        };
+        node.field("start", &self.start)
+            .field("end", &self.end)
+            .end();
+    }
+}
+
+impl ToJson for Keyword {
+    fn write_json(&self, out: &mut String) {
+        Object::node(out, "keyword")
+            .field("arg", &self.arg)
+            .field("value", &self.value)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .end();
+    }
+}
+
+impl ToJson for Alias {
+    fn write_json(&self, out: &mut String) {
+        Object::node(out, "alias")
+            .field("name", &self.name)
+            .field("asname", &self.asname)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .end();
+    }
+}
+
+impl ToJson for WithItem {
+    fn write_json(&self, out: &mut String) {
+        Object::node(out, "withitem")
+            .field("context_expr", &self.context_expr)
+            .field("optional_vars", &self.optional_vars)
+            .end();
+    }
+}
@@ -0,0 +1,180 @@
+//! A C ABI over the tokenizer and parser, for embedding the front end in
+//! programs not written in Rust. It is compiled in with the `capi` feature,
+//! and built as a shared library with
+//!
+//! ```text
+//! cargo rustc --release --features capi --lib --crate-type cdylib
+//! ```
+//!
+//! `include/rustpy.h` declares the functions and `RustpyResult`. Both entry
+//! points take the raw bytes of a source file, decode them as
+//! `tokenizer::decode` does, and return a heap-allocated result that the
+//! caller must release with `free_result`. The JSON is a document of the
+//! versioned schema `schema` describes. The bare JSON of a value is
+//! available to Rust callers through `to_json`.
+
+mod json;
+pub mod schema;
+
+pub use self::json::{to_json, ToJson};
+pub use self::schema::{to_document, DocumentKind, SCHEMA_VERSION};
+
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int};
+use std::panic;
+use std::ptr;
+use std::slice;
+
+use parser::{parse, ParseError};
+use tokenizer::{decode, tokenize, SourceError, TokenError};
+
+use self::json::Object;
+
+/// The source was processed and `json` holds the tokens or the tree.
+pub const RUSTPY_OK: c_int = 0;
+/// The source could not be decoded, tokenized or parsed, and `json` holds an
+/// error object.
+pub const RUSTPY_SYNTAX_ERROR: c_int = 1;
+/// The source pointer was null with a non-zero length.
+pub const RUSTPY_INVALID_ARGUMENT: c_int = 2;
+/// rustpy panicked. This is a bug and `json` holds an error object.
+pub const RUSTPY_INTERNAL_ERROR: c_int = 3;
+
+/// The outcome of a call, laid out as the C struct of the same name.
+///
+/// `json` is a NUL-terminated UTF-8 string of `len` bytes, not counting the
+/// NUL, holding a document of schema version `SCHEMA_VERSION`. On failure
+/// its `data` is an object with `type` (the Python exception name), `code`,
+/// `message`, `line` and `column`; fields that do not apply are null.
+#[repr(C)]
+pub struct RustpyResult {
+    pub status: c_int,
+    pub json: *mut c_char,
+    pub len: usize,
+}
+
+/// Tokenizes the `len` bytes at `source` into a JSON array of tokens, each
+/// with its `type`, `value`, `start` and `end` locations and byte `span`
+/// within the decoded text.
+///
+/// # Safety
+///
+/// `source` must point to `len` readable bytes, or be null if `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn tokenize_to_json(source: *const u8, len: usize) -> *mut RustpyResult {
+    call(source, len, |bytes| {
+        let decoded = decode(bytes).map_err(|err| source_error(&err))?;
+        let tokens = tokenize(&decoded.text).map_err(|err| token_error(&err))?;
+        Ok(to_document(DocumentKind::Tokens, &tokens))
+    })
+}
+
+/// Parses the `len` bytes at `source` as a module into a JSON syntax tree.
+///
+/// # Safety
+///
+/// `source` must point to `len` readable bytes, or be null if `len` is zero.
+#[no_mangle]
+pub unsafe extern "C" fn parse_to_json(source: *const u8, len: usize) -> *mut RustpyResult {
+    call(source, len, |bytes| {
+        let decoded = decode(bytes).map_err(|err| source_error(&err))?;
+        let module = parse(&decoded.text).map_err(|err| parse_error(&err))?;
+        Ok(to_document(DocumentKind::Module, &module))
+    })
+}
+
+/// Releases a result returned by `tokenize_to_json` or `parse_to_json`.
+/// Passing null does nothing.
+///
+/// # Safety
+///
+/// `result` must be null or a result from this library that has not already
+/// been freed.
+#[no_mangle]
+pub unsafe extern "C" fn free_result(result: *mut RustpyResult) {
+    if result.is_null() {
+        return;
+    }
+    let result = Box::from_raw(result);
+    if !result.json.is_null() {
+        drop(CString::from_raw(result.json));
+    }
+}
+
+unsafe fn call<F>(source: *const u8, len: usize, f: F) -> *mut RustpyResult
+where
+    F: FnOnce(&[u8]) -> Result<String, String> + panic::UnwindSafe,
+{
+    let (status, json) = if source.is_null() && len > 0 {
+        (
+            RUSTPY_INVALID_ARGUMENT,
+            error_object("ValueError", None, "source is a null pointer", None),
+        )
+    } else {
+        let bytes = if len == 0 {
+            &[][..]
+        } else {
+            slice::from_raw_parts(source, len)
+        };
+        match panic::catch_unwind(|| f(bytes)) {
+            Ok(Ok(json)) => (RUSTPY_OK, json),
+            Ok(Err(json)) => (RUSTPY_SYNTAX_ERROR, json),
+            Err(_) => (
+                RUSTPY_INTERNAL_ERROR,
+                error_object("SystemError", None, "rustpy panicked", None),
+            ),
+        }
+    };
+    let len = json.len();
+    // The JSON writer escapes control characters, so there is no NUL to
+    // reject.
+    let json = CString::new(json).map_or(ptr::null_mut(), CString::into_raw);
+    Box::into_raw(Box::new(RustpyResult { status, json, len }))
+}
+
+fn error_object(
+    type_: &str,
+    code: Option<&str>,
+    message: &str,
+    location: Option<(usize, usize)>,
+) -> String {
+    let mut out = String::new();
+    Object::new(&mut out)
+        .field("type", type_)
+        .field("code", &code.map(str::to_string))
+        .field("message", message)
+        .field("line", &location.map(|(line, _)| line))
+        .field("column", &location.map(|(_, column)| column))
+        .end();
+    schema::wrap(DocumentKind::Error, &out)
+}
+
+fn token_error(err: &TokenError) -> String {
+    let location = (err.location.line, err.location.column);
+    error_object(
+        err.kind.exception_name(),
+        Some(err.kind.code()),
+        &err.message,
+        Some(location),
+    )
+}
+
+fn parse_error(err: &ParseError) -> String {
+    let location = (err.location.line, err.location.column);
+    error_object(
+        err.kind.exception_name(),
+        Some(err.kind.code()),
+        &err.message,
+        Some(location),
+    )
+}
+
+fn source_error(err: &SourceError) -> String {
+    match *err {
+        SourceError::Token(ref err) => token_error(err),
+        SourceError::Decode { line, .. } => {
+            error_object("SyntaxError", None, &err.to_string(), Some((line, 0)))
+        }
+        _ => error_object("SyntaxError", None, &err.to_string(), None),
+    }
+}
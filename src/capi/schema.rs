@@ -0,0 +1,431 @@
+//! The versioned wire format of the JSON documents the C ABI returns.
+//!
+//! Each document is an object with three members, always in this order:
+//!
+//! ```text
+//! {"schema_version":3,"kind":"module","data":{"_type":"Module",...}}
+//! ```
+//!
+//! `kind` is `tokens`, `module` or `error`, and `data` is what `to_json`
+//! writes for it. Since `schema_version` comes first, a reader can check it
+//! before parsing the rest.
+//!
+//! # Stability
+//!
+//! Within one schema version:
+//!
+//! - fields are never removed or renamed, and never change type;
+//! - new fields may be added, after the existing ones of their object;
+//! - new node types, token types, operators and constant kinds may appear
+//!   as Python grammar grows.
+//!
+//! Readers should therefore ignore fields and names they do not know. Any
+//! other change bumps `SCHEMA_VERSION`, and `upgrade` learns to bring older
+//! documents to it. The output for the same input is the same byte for
+//! byte: members are written in a fixed order, with no whitespace unless
+//! `pretty` adds it.
+//!
+//! # Versions
+//!
+//! 1. The bare `data`, without the envelope. Documents without a
+//!    `schema_version` are version 1.
+//! 2. The envelope above.
+//! 3. In `tokens` documents, a line with no code, only blanks or a comment,
+//!    ends with an `NL` token rather than a `NEWLINE`, and each line break
+//!    inside brackets is an `NL` token where version 2 had none, as in
+//!    CPython's `tokenize`. `upgrade` relabels the newlines of lines with
+//!    no code, but cannot restore the line breaks inside brackets, whose
+//!    positions version 2 does not record.
+
+use std::error::Error;
+use std::fmt;
+
+use super::json::{Object, ToJson};
+
+/// The schema version the C ABI writes.
+pub const SCHEMA_VERSION: usize = 3;
+
+/// What a document holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DocumentKind {
+    Tokens,
+    Module,
+    Error,
+}
+
+impl DocumentKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            DocumentKind::Tokens => "tokens",
+            DocumentKind::Module => "module",
+            DocumentKind::Error => "error",
+        }
+    }
+}
+
+/// Why a document could not be read or converted.
+#[derive(Debug, PartialEq, Eq)]
+pub enum SchemaError {
+    /// The document is not JSON this module can read.
+    Malformed,
+    /// The document was written by a newer rustpy.
+    TooNew(usize),
+    /// Version 1 documents of this shape cannot be told apart.
+    UnknownKind,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SchemaError::Malformed => write!(f, "malformed JSON document"),
+            SchemaError::TooNew(version) => write!(
+                f,
+                "schema version {} is newer than {}, the newest this rustpy reads",
+                version, SCHEMA_VERSION
+            ),
+            SchemaError::UnknownKind => write!(f, "cannot tell what the document holds"),
+        }
+    }
+}
+
+impl Error for SchemaError {}
+
+/// JSON that has already been written.
+struct Raw<'a>(&'a str);
+
+impl<'a> ToJson for Raw<'a> {
+    fn write_json(&self, out: &mut String) {
+        out.push_str(self.0);
+    }
+}
+
+/// `value` as a document of the current schema version.
+pub fn to_document<T: ToJson + ?Sized>(kind: DocumentKind, value: &T) -> String {
+    envelope(SCHEMA_VERSION, kind.name(), value)
+}
+
+fn envelope<T: ToJson + ?Sized>(version: usize, kind: &str, value: &T) -> String {
+    let mut out = String::new();
+    Object::new(&mut out)
+        .field("schema_version", &version)
+        .field("kind", kind)
+        .field("data", value)
+        .end();
+    out
+}
+
+/// Wraps JSON written by `to_json` in a document.
+pub(super) fn wrap(kind: DocumentKind, data: &str) -> String {
+    to_document(kind, &Raw(data))
+}
+
+/// The `kind` and `data` of a document of version 2 or later.
+fn contents(document: &str) -> Result<(&str, &str), SchemaError> {
+    let members = members(document.trim())?;
+    let member = |wanted: &str| {
+        members
+            .iter()
+            .find(|&&(name, _)| name == wanted)
+            .map(|&(_, value)| value)
+            .ok_or(SchemaError::Malformed)
+    };
+    let kind = member("kind")?;
+    if !kind.starts_with('"') || !kind.ends_with('"') || kind.len() < 2 {
+        return Err(SchemaError::Malformed);
+    }
+    Ok((&kind[1..kind.len() - 1], member("data")?))
+}
+
+/// The schema version of a document.
+pub fn schema_version(document: &str) -> Result<usize, SchemaError> {
+    let document = document.trim();
+    if !document.starts_with('{') {
+        return Ok(1);
+    }
+    match members(document)?.first() {
+        Some(&("schema_version", value)) => value.parse().map_err(|_| SchemaError::Malformed),
+        _ => Ok(1),
+    }
+}
+
+/// Brings a document of any earlier schema version to `SCHEMA_VERSION`.
+/// Documents that already are at it are returned unchanged.
+pub fn upgrade(document: &str) -> Result<String, SchemaError> {
+    let mut version = schema_version(document)?;
+    if version > SCHEMA_VERSION {
+        return Err(SchemaError::TooNew(version));
+    }
+    let mut document = document.to_string();
+    if version == 1 {
+        let data = document.trim();
+        let kind = if data.starts_with('[') {
+            DocumentKind::Tokens
+        } else {
+            match members(data)?.first() {
+                Some(&("_type", "\"Module\"")) => DocumentKind::Module,
+                Some(&("type", _)) => DocumentKind::Error,
+                _ => return Err(SchemaError::UnknownKind),
+            }
+        };
+        document = envelope(2, kind.name(), &Raw(data));
+        version = 2;
+    }
+    if version == 2 {
+        let (kind, data) = contents(&document)?;
+        let data = if kind == DocumentKind::Tokens.name() {
+            relabel_newlines(data)?
+        } else {
+            data.to_string()
+        };
+        document = envelope(3, kind, &Raw(&data));
+    }
+    Ok(document)
+}
+
+/// Converts a document to the bare version 1 form, for readers written
+/// against it.
+pub fn downgrade_to_v1(document: &str) -> Result<String, SchemaError> {
+    match schema_version(document)? {
+        1 => Ok(document.to_string()),
+        2 => contents(document).map(|(_, data)| data.to_string()),
+        SCHEMA_VERSION => {
+            let (kind, data) = contents(document)?;
+            if kind == DocumentKind::Tokens.name() {
+                unlabel_newlines(data)
+            } else {
+                Ok(data.to_string())
+            }
+        }
+        version => Err(SchemaError::TooNew(version)),
+    }
+}
+
+/// The token objects of a `tokens` document's data, as written.
+fn token_items(data: &str) -> Result<Vec<&str>, SchemaError> {
+    let bytes = data.as_bytes();
+    if bytes.first() != Some(&b'[') || bytes.last() != Some(&b']') {
+        return Err(SchemaError::Malformed);
+    }
+    let mut items = Vec::new();
+    let mut index = skip_whitespace(bytes, 1);
+    while index < bytes.len() - 1 {
+        let end = value_end(bytes, index)?;
+        items.push(&data[index..end]);
+        index = skip_whitespace(bytes, end);
+        if bytes[index] == b',' {
+            index = skip_whitespace(bytes, index + 1);
+        }
+    }
+    Ok(items)
+}
+
+/// The `type` of a token object, with its quotes.
+fn token_type(item: &str) -> Result<&str, SchemaError> {
+    members(item)?
+        .into_iter()
+        .find(|&(name, _)| name == "type")
+        .map(|(_, value)| value)
+        .ok_or(SchemaError::Malformed)
+}
+
+/// `item` with the `type` `from` replaced by `to`. `type` is the first
+/// member of a token, so the first string `from` is its value.
+fn retype(item: &str, from: &str, to: &str) -> String {
+    item.replacen(from, to, 1)
+}
+
+/// Version 2 token data as version 3: the `NEWLINE` of a line with no
+/// code becomes `NL`.
+fn relabel_newlines(data: &str) -> Result<String, SchemaError> {
+    let mut out = Vec::new();
+    let mut line_has_code = false;
+    for item in token_items(data)? {
+        match token_type(item)? {
+            "\"NEWLINE\"" if !line_has_code => out.push(retype(item, "\"NEWLINE\"", "\"NL\"")),
+            "\"NEWLINE\"" => {
+                line_has_code = false;
+                out.push(item.to_string());
+            }
+            kind => {
+                line_has_code |= !NOT_CODE.contains(&kind);
+                out.push(item.to_string());
+            }
+        }
+    }
+    Ok(format!("[{}]", out.join(",")))
+}
+
+/// Version 3 token data as version 1: the line breaks inside brackets are
+/// dropped, and the other `NL`s become `NEWLINE`.
+fn unlabel_newlines(data: &str) -> Result<String, SchemaError> {
+    let mut out = Vec::new();
+    let mut depth = 0usize;
+    for item in token_items(data)? {
+        match token_type(item)? {
+            "\"NL\"" if depth > 0 => continue,
+            "\"NL\"" => out.push(retype(item, "\"NL\"", "\"NEWLINE\"")),
+            "\"OP\"" => {
+                let value = members(item)?
+                    .into_iter()
+                    .find(|&(name, _)| name == "value")
+                    .map_or("", |(_, value)| value);
+                match value {
+                    "\"(\"" | "\"[\"" | "\"{\"" => depth += 1,
+                    "\")\"" | "\"]\"" | "\"}\"" => depth = depth.saturating_sub(1),
+                    _ => {}
+                }
+                out.push(item.to_string());
+            }
+            _ => out.push(item.to_string()),
+        }
+    }
+    Ok(format!("[{}]", out.join(",")))
+}
+
+/// The token types that do not make a line one with code.
+const NOT_CODE: [&str; 5] = [
+    "\"COMMENT\"",
+    "\"NL\"",
+    "\"INDENT\"",
+    "\"DEDENT\"",
+    "\"ENDMARKER\"",
+];
+
+/// The names and raw values of the members of a JSON object, without
+/// parsing the values.
+fn members(object: &str) -> Result<Vec<(&str, &str)>, SchemaError> {
+    let bytes = object.as_bytes();
+    if bytes.first() != Some(&b'{') || bytes.last() != Some(&b'}') {
+        return Err(SchemaError::Malformed);
+    }
+    let mut members = Vec::new();
+    let mut index = skip_whitespace(bytes, 1);
+    if bytes[index] == b'}' {
+        return Ok(members);
+    }
+    loop {
+        if bytes[index] != b'"' {
+            return Err(SchemaError::Malformed);
+        }
+        let name_end = value_end(bytes, index)?;
+        let name = &object[index + 1..name_end - 1];
+        index = skip_whitespace(bytes, name_end);
+        if bytes.get(index) != Some(&b':') {
+            return Err(SchemaError::Malformed);
+        }
+        let start = skip_whitespace(bytes, index + 1);
+        let end = value_end(bytes, start)?;
+        members.push((name, object[start..end].trim_end()));
+        index = skip_whitespace(bytes, end);
+        match bytes.get(index) {
+            Some(&b',') => index = skip_whitespace(bytes, index + 1),
+            Some(&b'}') if index == bytes.len() - 1 => return Ok(members),
+            _ => return Err(SchemaError::Malformed),
+        }
+    }
+}
+
+fn skip_whitespace(bytes: &[u8], mut index: usize) -> usize {
+    while index < bytes.len() && bytes[index].is_ascii_whitespace() {
+        index += 1;
+    }
+    index
+}
+
+/// The end of the JSON value starting at `start`: past its closing quote
+/// or bracket, or at the `,` or `}` that ends a scalar.
+fn value_end(bytes: &[u8], start: usize) -> Result<usize, SchemaError> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut index = start;
+    while index < bytes.len() {
+        let byte = bytes[index];
+        if in_string {
+            match byte {
+                b'\\' => index += 1,
+                b'"' => {
+                    in_string = false;
+                    if depth == 0 {
+                        return Ok(index + 1);
+                    }
+                }
+                _ => {}
+            }
+        } else {
+            match byte {
+                b'"' => in_string = true,
+                b'{' | b'[' => depth += 1,
+                b'}' | b']' if depth == 0 => return Ok(index),
+                b'}' | b']' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(index + 1);
+                    }
+                }
+                b',' if depth == 0 => return Ok(index),
+                _ => {}
+            }
+        }
+        index += 1;
+    }
+    Err(SchemaError::Malformed)
+}
+
+/// Indents JSON by two spaces per level, one member or item per line.
+/// Empty objects and arrays stay on one line.
+pub fn pretty(json: &str) -> String {
+    let mut out = String::with_capacity(json.len() * 2);
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut chars = json.chars().peekable();
+    let newline = |out: &mut String, depth: usize| {
+        out.push('\n');
+        for _ in 0..depth {
+            out.push_str("  ");
+        }
+    };
+    while let Some(c) = chars.next() {
+        if in_string {
+            out.push(c);
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_string = true;
+                out.push(c);
+            }
+            '{' | '[' => {
+                while chars.peek().is_some_and(|c| c.is_whitespace()) {
+                    chars.next();
+                }
+                out.push(c);
+                if chars.peek() == Some(&if c == '{' { '}' } else { ']' }) {
+                    out.push(chars.next().unwrap());
+                } else {
+                    depth += 1;
+                    newline(&mut out, depth);
+                }
+            }
+            '}' | ']' => {
+                depth = usize::saturating_sub(depth, 1);
+                newline(&mut out, depth);
+                out.push(c);
+            }
+            ',' => {
+                out.push(c);
+                newline(&mut out, depth);
+            }
+            ':' => out.push_str(": "),
+            c if c.is_whitespace() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
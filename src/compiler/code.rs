@@ -0,0 +1,487 @@
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use ast::{unparse_constant, CmpOperator, Constant, Operator, UnaryOperator};
+use tokenizer::Location;
+
+/// Set on code that runs in a new local namespace of fast locals, such as a
+/// function body.
+pub const CO_OPTIMIZED: u32 = 0x1;
+pub const CO_NEWLOCALS: u32 = 0x2;
+/// The code takes `*args`.
+pub const CO_VARARGS: u32 = 0x4;
+/// The code takes `**kwargs`.
+pub const CO_VARKEYWORDS: u32 = 0x8;
+pub const CO_NESTED: u32 = 0x10;
+pub const CO_GENERATOR: u32 = 0x20;
+/// Set on the code of an `async def`, a call of which makes a coroutine.
+pub const CO_COROUTINE: u32 = 0x80;
+/// Set on all code of a module with `from __future__ import
+/// barry_as_FLUFL`.
+pub const CO_FUTURE_BARRY_AS_BDFL: u32 = 0x40_0000;
+/// Set on all code of a module with `from __future__ import annotations`,
+/// whose annotations are kept as the strings of their source instead of
+/// being evaluated.
+pub const CO_FUTURE_ANNOTATIONS: u32 = 0x100_0000;
+
+/// `MakeFunction` flags, saying which optional parts are on the stack below
+/// the code object and qualified name.
+pub const MAKE_DEFAULTS: u8 = 0x1;
+pub const MAKE_KWDEFAULTS: u8 = 0x2;
+pub const MAKE_ANNOTATIONS: u8 = 0x4;
+pub const MAKE_CLOSURE: u8 = 0x8;
+
+/// A bytecode instruction, modelled on CPython 3.10's.
+///
+/// Operands index `CodeObject::constants`, `names` or `varnames`, or, for
+/// jumps, `CodeObject::instructions`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Instruction {
+    Nop,
+    PopTop,
+    /// Pops a value and shows it as the interactive prompt does, for the
+    /// expression statements of code compiled in `single` mode.
+    PrintExpr,
+    RotTwo,
+    RotThree,
+    DupTop,
+    DupTopTwo,
+    LoadConst(usize),
+    LoadName(usize),
+    StoreName(usize),
+    DeleteName(usize),
+    LoadGlobal(usize),
+    StoreGlobal(usize),
+    DeleteGlobal(usize),
+    LoadFast(usize),
+    StoreFast(usize),
+    DeleteFast(usize),
+    LoadAttr(usize),
+    StoreAttr(usize),
+    DeleteAttr(usize),
+    BinarySubscr,
+    StoreSubscr,
+    DeleteSubscr,
+    BinaryOp(Operator),
+    InplaceOp(Operator),
+    UnaryOp(UnaryOperator),
+    CompareOp(CmpOperator),
+    BuildTuple(usize),
+    BuildList(usize),
+    BuildSet(usize),
+    /// Builds a dict from that many key and value pairs.
+    BuildMap(usize),
+    /// Builds a dict from that many values and a tuple of their keys on top.
+    BuildConstKeyMap(usize),
+    BuildSlice(usize),
+    BuildString(usize),
+    /// Extends the list that many items down with the iterable on top.
+    ListExtend(usize),
+    SetUpdate(usize),
+    DictUpdate(usize),
+    /// Like `DictUpdate`, but rejects duplicate keys, for `f(**a, **b)`.
+    DictMerge(usize),
+    ListToTuple,
+    /// Appends the value on top to the list that many items down.
+    ListAppend(usize),
+    SetAdd(usize),
+    /// Adds the key and value on top to the dict that many items down.
+    MapAdd(usize),
+    UnpackSequence(usize),
+    /// Unpacks into `before` values, a list of the rest, and `after` values.
+    UnpackEx {
+        before: usize,
+        after: usize,
+    },
+    /// Formats the value on top, or the value under a format spec.
+    FormatValue {
+        conversion: Option<char>,
+        has_spec: bool,
+    },
+    Jump(usize),
+    PopJumpIfFalse(usize),
+    PopJumpIfTrue(usize),
+    JumpIfFalseOrPop(usize),
+    JumpIfTrueOrPop(usize),
+    GetIter,
+    /// Pushes the next item of the iterator on top, or pops the iterator and
+    /// jumps when it is exhausted.
+    ForIter(usize),
+    CallFunction(usize),
+    /// Calls with that many arguments, the last of them named by the tuple
+    /// of strings on top.
+    CallFunctionKw(usize),
+    /// Calls with a tuple of positional arguments, and a dict of keyword
+    /// arguments above it if the operand is true.
+    CallFunctionEx(bool),
+    /// Makes a function from the code object and qualified name on top, with
+    /// the optional parts given by the `MAKE_*` flags below them.
+    MakeFunction(u8),
+    /// Pushes `__build_class__`, which a class statement calls with the
+    /// function of its body, its name and its bases.
+    LoadBuildClass,
+    /// Creates an empty `__annotations__` dict in the local namespace,
+    /// unless it has one already, for a module or class body with annotated
+    /// assignments.
+    SetupAnnotations,
+    /// Pushes the cell of a cell or free variable, to close over it. Cells
+    /// are numbered through `cellvars` and then `freevars`.
+    LoadClosure(usize),
+    /// Pushes the value in the cell of a cell or free variable.
+    LoadDeref(usize),
+    /// Stores the value on top in the cell of a cell or free variable.
+    StoreDeref(usize),
+    /// Empties the cell of a cell or free variable.
+    DeleteDeref(usize),
+    /// Pushes the value of a free variable of a class body, or the value
+    /// the class namespace has for its name if there is one.
+    LoadClassDeref(usize),
+    ReturnValue,
+    /// Suspends a generator, handing the value on top to its caller. The
+    /// value sent in when it resumes is pushed in its place.
+    YieldValue,
+    /// Replaces the iterable on top with its iterator, unless it is a
+    /// generator already.
+    GetYieldFromIter,
+    /// Sends the value on top to the iterator below it. While the iterator
+    /// yields, the generator yields the same values and runs this
+    /// instruction again when resumed; once it is exhausted, the iterator
+    /// is replaced by its return value.
+    YieldFrom,
+    /// Replaces the object of an `await` on top with the iterator that
+    /// `YieldFrom` awaits it through: a coroutine itself, or what its
+    /// `__await__` returns.
+    GetAwaitable,
+    /// Replaces the asynchronous context manager on top with its bound
+    /// `__aexit__` and the awaitable its `__aenter__` returns.
+    BeforeAsyncWith,
+    /// Sets up a block like `SetupFinally` under the result of `__aenter__`
+    /// on top, once it has been awaited.
+    SetupAsyncWith(usize),
+    /// Imports the named module, with the level and from-list below.
+    ImportName(usize),
+    ImportFrom(usize),
+    ImportStar,
+    LoadAssertionError,
+    /// Raises with up to two operands: the exception and its cause.
+    RaiseVarargs(usize),
+    /// Pushes a block that sends an exception raised before the matching
+    /// `PopBlock` to the handler, with the previously handled exception and
+    /// the new one on the stack.
+    SetupFinally(usize),
+    /// Replaces the context manager on top with its bound `__exit__` and the
+    /// result of calling its `__enter__`, and sets up a block like
+    /// `SetupFinally` that sends exceptions to the handler.
+    SetupWith(usize),
+    /// Calls the `__exit__` below the previously handled exception and the
+    /// exception on top, and pushes what it returns.
+    WithExceptStart,
+    PopBlock,
+    /// Ends an exception handler, restoring the previously handled exception
+    /// from the stack.
+    PopExcept,
+    /// Raises the exception on top again, without adding to its traceback.
+    Reraise,
+    /// Pops an exception class and an exception, and jumps unless the
+    /// exception is an instance of the class.
+    JumpIfNotExcMatch(usize),
+    /// Pops an exception class and splits the exception below it by the
+    /// class, for an `except*` clause. When part of it matches, the rest
+    /// (or `None`) replaces it and the part that matches is pushed and
+    /// becomes the exception being handled; otherwise it jumps.
+    CheckEgMatch(usize),
+    /// Pops a list of the exceptions raised in the `except*` clauses of a
+    /// `try` statement, followed by what they left unhandled, and the
+    /// exception it caught, and pushes the exception to raise again for
+    /// them, or `None`.
+    PrepReraiseStar,
+}
+
+impl Instruction {
+    /// The instruction this one may jump to.
+    pub fn target(&self) -> Option<usize> {
+        match *self {
+            Instruction::Jump(target)
+            | Instruction::PopJumpIfFalse(target)
+            | Instruction::PopJumpIfTrue(target)
+            | Instruction::JumpIfFalseOrPop(target)
+            | Instruction::JumpIfTrueOrPop(target)
+            | Instruction::ForIter(target)
+            | Instruction::SetupFinally(target)
+            | Instruction::SetupWith(target)
+            | Instruction::SetupAsyncWith(target)
+            | Instruction::JumpIfNotExcMatch(target)
+            | Instruction::CheckEgMatch(target) => Some(target),
+            _ => None,
+        }
+    }
+
+    pub fn target_mut(&mut self) -> Option<&mut usize> {
+        match *self {
+            Instruction::Jump(ref mut target)
+            | Instruction::PopJumpIfFalse(ref mut target)
+            | Instruction::PopJumpIfTrue(ref mut target)
+            | Instruction::JumpIfFalseOrPop(ref mut target)
+            | Instruction::JumpIfTrueOrPop(ref mut target)
+            | Instruction::ForIter(ref mut target)
+            | Instruction::SetupFinally(ref mut target)
+            | Instruction::SetupWith(ref mut target)
+            | Instruction::SetupAsyncWith(ref mut target)
+            | Instruction::JumpIfNotExcMatch(ref mut target)
+            | Instruction::CheckEgMatch(ref mut target) => Some(target),
+            _ => None,
+        }
+    }
+
+    /// Whether execution never continues with the next instruction.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            *self,
+            Instruction::Jump(_)
+                | Instruction::ReturnValue
+                | Instruction::RaiseVarargs(_)
+                | Instruction::Reraise
+        )
+    }
+}
+
+/// A constant referenced by code: a value, or the code of a nested function.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum CodeConstant {
+    Value(Constant),
+    Code(Arc<CodeObject>),
+}
+
+impl CodeConstant {
+    /// Whether two constants are interchangeable. Unlike `==`, this tells
+    /// `0` from `False` and `0.0` from `-0.0`.
+    pub fn same(&self, other: &CodeConstant) -> bool {
+        match (self, other) {
+            (CodeConstant::Value(a), CodeConstant::Value(b)) => same_value(a, b),
+            (CodeConstant::Code(a), CodeConstant::Code(b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+fn same_value(a: &Constant, b: &Constant) -> bool {
+    match (a, b) {
+        (Constant::Float(a), Constant::Float(b)) => a.to_bits() == b.to_bits(),
+        (
+            Constant::Complex { real, imag },
+            Constant::Complex {
+                real: other_real,
+                imag: other_imag,
+            },
+        ) => real.to_bits() == other_real.to_bits() && imag.to_bits() == other_imag.to_bits(),
+        (Constant::Tuple(a), Constant::Tuple(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_value(a, b))
+        }
+        _ => a == b,
+    }
+}
+
+/// Compiled code for a module, class body or function, like CPython's code
+/// objects.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CodeObject {
+    pub name: String,
+    pub qualname: String,
+    pub filename: String,
+    pub first_line: usize,
+    pub argcount: usize,
+    pub posonlyargcount: usize,
+    pub kwonlyargcount: usize,
+    /// `CO_*` flags.
+    pub flags: u32,
+    pub instructions: Vec<Instruction>,
+    /// Where in the source each instruction came from.
+    pub locations: Vec<Location>,
+    pub constants: Vec<CodeConstant>,
+    /// Attribute, global and module-level names.
+    pub names: Vec<String>,
+    /// Parameters, then the other local variables.
+    pub varnames: Vec<String>,
+    /// Variables of this code that nested functions close over.
+    pub cellvars: Vec<String>,
+    /// Variables of enclosing code that this code closes over.
+    pub freevars: Vec<String>,
+}
+
+impl CodeObject {
+    pub fn new(name: &str, qualname: &str, filename: &str, first_line: usize) -> CodeObject {
+        CodeObject {
+            name: name.to_string(),
+            qualname: qualname.to_string(),
+            filename: filename.to_string(),
+            first_line,
+            argcount: 0,
+            posonlyargcount: 0,
+            kwonlyargcount: 0,
+            flags: 0,
+            instructions: Vec::new(),
+            locations: Vec::new(),
+            constants: Vec::new(),
+            names: Vec::new(),
+            varnames: Vec::new(),
+            cellvars: Vec::new(),
+            freevars: Vec::new(),
+        }
+    }
+
+    /// The index of `constant`, adding it if it is not there yet.
+    pub fn add_constant(&mut self, constant: CodeConstant) -> usize {
+        match self.constants.iter().position(|c| c.same(&constant)) {
+            Some(index) => index,
+            None => {
+                self.constants.push(constant);
+                self.constants.len() - 1
+            }
+        }
+    }
+
+    pub fn add_name(&mut self, name: &str) -> usize {
+        add_name(&mut self.names, name)
+    }
+
+    pub fn add_varname(&mut self, name: &str) -> usize {
+        add_name(&mut self.varnames, name)
+    }
+
+    /// The name of the cell at `index`, numbered as `LoadClosure` numbers
+    /// them.
+    pub fn cell_name(&self, index: usize) -> Option<&str> {
+        self.cellvars
+            .iter()
+            .chain(&self.freevars)
+            .nth(index)
+            .map(String::as_str)
+    }
+
+    /// Removes `Nop`s, moving jumps that pointed at one to the instruction
+    /// that follows it.
+    pub fn remove_nops(&mut self) {
+        let mut new_index = Vec::with_capacity(self.instructions.len() + 1);
+        let mut kept = 0;
+        for instruction in &self.instructions {
+            new_index.push(kept);
+            if *instruction != Instruction::Nop {
+                kept += 1;
+            }
+        }
+        new_index.push(kept);
+
+        let mut instructions = Vec::with_capacity(kept);
+        let mut locations = Vec::with_capacity(kept);
+        for (mut instruction, location) in self.instructions.drain(..).zip(self.locations.drain(..))
+        {
+            if instruction == Instruction::Nop {
+                continue;
+            }
+            if let Some(target) = instruction.target_mut() {
+                *target = new_index[*target];
+            }
+            instructions.push(instruction);
+            locations.push(location);
+        }
+        self.instructions = instructions;
+        self.locations = locations;
+    }
+
+    pub(crate) fn operand(&self, instruction: &Instruction) -> String {
+        match *instruction {
+            Instruction::LoadConst(index) => match self.constants.get(index) {
+                Some(CodeConstant::Value(value)) => format!("({})", unparse_constant(value)),
+                Some(CodeConstant::Code(code)) => format!("(<code object {}>)", code.name),
+                None => String::new(),
+            },
+            Instruction::LoadName(index)
+            | Instruction::StoreName(index)
+            | Instruction::DeleteName(index)
+            | Instruction::LoadGlobal(index)
+            | Instruction::StoreGlobal(index)
+            | Instruction::DeleteGlobal(index)
+            | Instruction::LoadAttr(index)
+            | Instruction::StoreAttr(index)
+            | Instruction::DeleteAttr(index)
+            | Instruction::ImportName(index)
+            | Instruction::ImportFrom(index) => self
+                .names
+                .get(index)
+                .map_or(String::new(), |name| format!("({})", name)),
+            Instruction::LoadFast(index)
+            | Instruction::StoreFast(index)
+            | Instruction::DeleteFast(index) => self
+                .varnames
+                .get(index)
+                .map_or(String::new(), |name| format!("({})", name)),
+            Instruction::LoadClosure(index)
+            | Instruction::LoadDeref(index)
+            | Instruction::StoreDeref(index)
+            | Instruction::DeleteDeref(index)
+            | Instruction::LoadClassDeref(index) => self
+                .cell_name(index)
+                .map_or(String::new(), |name| format!("({})", name)),
+            Instruction::BinaryOp(op) | Instruction::InplaceOp(op) => format!("({})", op.symbol()),
+            Instruction::UnaryOp(op) => format!("({})", op.symbol()),
+            Instruction::CompareOp(op) => format!("({})", op.symbol()),
+            _ => match instruction.target() {
+                Some(target) => format!("(to {})", target),
+                None => String::new(),
+            },
+        }
+    }
+}
+
+fn add_name(names: &mut Vec<String>, name: &str) -> usize {
+    match names.iter().position(|n| n == name) {
+        Some(index) => index,
+        None => {
+            names.push(name.to_string());
+            names.len() - 1
+        }
+    }
+}
+
+/// A listing in the style of the `dis` module, followed by the listings of
+/// any nested code objects.
+impl fmt::Display for CodeObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let targets: Vec<usize> = self
+            .instructions
+            .iter()
+            .filter_map(|i| i.target())
+            .collect();
+        let mut line = 0;
+        for (index, instruction) in self.instructions.iter().enumerate() {
+            let location = self.locations[index];
+            if location.line != line {
+                if index > 0 {
+                    writeln!(f)?;
+                }
+                line = location.line;
+                write!(f, "{:>4}", line)?;
+            } else {
+                write!(f, "    ")?;
+            }
+            let marker = if targets.contains(&index) { ">>" } else { "  " };
+            let text = format!("{:?}", instruction);
+            let operand = self.operand(instruction);
+            writeln!(f, " {} {:>4} {:<28} {}", marker, index, text, operand)?;
+        }
+        for constant in &self.constants {
+            if let CodeConstant::Code(ref code) = *constant {
+                writeln!(f)?;
+                writeln!(f, "Disassembly of <code object {}>:", code.name)?;
+                write!(f, "{}", code)?;
+            }
+        }
+        Ok(())
+    }
+}
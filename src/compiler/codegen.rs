@@ -0,0 +1,2145 @@
+use std::sync::Arc;
+
+use ast::{
+    body_docstring, unparse_expr, Arguments, BoolOperator, CmpOperator, Comprehension, Constant,
+    Context, ExceptHandler, Expr, ExprKind, Keyword, Module, Stmt, StmtKind, UnaryOperator,
+    WithItem,
+};
+use tokenizer::Location;
+
+use super::code::{
+    CodeConstant, CodeObject, Instruction, CO_COROUTINE, CO_FUTURE_ANNOTATIONS, CO_GENERATOR,
+    CO_NESTED, CO_NEWLOCALS, CO_OPTIMIZED, CO_VARARGS, CO_VARKEYWORDS, MAKE_ANNOTATIONS,
+    MAKE_CLOSURE, MAKE_DEFAULTS, MAKE_KWDEFAULTS,
+};
+use super::future::{is_future_import, FutureFeatures, LATE_FUTURE};
+use super::symtable::{Scope, ScopeKind, SymbolScope, SymbolTable};
+use super::{CompilerConfig, CompilerError, CompilerWarning, Mode};
+
+type Result<T> = ::std::result::Result<T, CompilerError>;
+
+/// A construct that `break`, `continue` and `return` must leave properly.
+enum FBlock<'a> {
+    Loop {
+        start: usize,
+        /// Jumps to patch to the end of the loop.
+        breaks: Vec<usize>,
+        /// Whether an iterator sits on the stack while the body runs.
+        for_loop: bool,
+    },
+    /// The body of a `try` with `except` clauses.
+    TryExcept,
+    /// The body of a `with` statement, whose `__exit__` is on the stack.
+    With,
+    /// The body of an `async with` statement, whose `__aexit__` is on the
+    /// stack.
+    AsyncWith,
+    /// The body of a `try` with a `finally` clause, which runs on the way out.
+    FinallyTry { finalbody: &'a [Stmt] },
+    /// A `finally` clause run for an exception, which sits on the stack.
+    FinallyEnd,
+    /// The body of an `except` clause, with the name it binds.
+    HandlerCleanup { name: Option<&'a str> },
+    /// The body of an `except*` clause, which `break`, `continue` and
+    /// `return` may not leave.
+    ExceptStarHandler,
+    /// A value kept on the stack while a `finally` clause runs for `return`.
+    PopValue,
+}
+
+/// The code being generated for one module, class body or function.
+struct Unit<'a> {
+    code: CodeObject,
+    scope: &'a Scope,
+    fblocks: Vec<FBlock<'a>>,
+}
+
+pub struct Codegen<'a> {
+    table: &'a SymbolTable,
+    config: &'a CompilerConfig,
+    filename: &'a str,
+    future: FutureFeatures,
+    units: Vec<Unit<'a>>,
+    location: Location,
+    /// Whether expression statements at module level print their values.
+    interactive: bool,
+    warnings: Vec<CompilerWarning>,
+}
+
+impl<'a> Codegen<'a> {
+    pub fn new(
+        table: &'a SymbolTable,
+        config: &'a CompilerConfig,
+        filename: &'a str,
+        future: FutureFeatures,
+    ) -> Codegen<'a> {
+        Codegen {
+            table,
+            config,
+            filename,
+            future,
+            units: Vec::new(),
+            location: Location::new(1, 0),
+            interactive: false,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Compiles `module` as `mode` asks, with the warnings found.
+    pub fn compile(
+        mut self,
+        module: &'a Module,
+        mode: Mode,
+    ) -> Result<(CodeObject, Vec<CompilerWarning>)> {
+        let code = match mode {
+            Mode::Exec => self.module(module)?,
+            Mode::Eval => self.expression(module)?,
+            Mode::Single => {
+                self.interactive = true;
+                self.module(module)?
+            }
+        };
+        Ok((code, self.warnings))
+    }
+
+    fn module(&mut self, module: &'a Module) -> Result<CodeObject> {
+        self.enter(self.table.module(), "<module>", "<module>", 1);
+        self.setup_annotations(&module.body);
+        if !self.interactive {
+            self.store_docstring(&module.body);
+        }
+        self.stmts(&module.body)?;
+        self.load_const(Constant::None);
+        self.emit(Instruction::ReturnValue);
+        Ok(self.leave())
+    }
+
+    /// Code that returns the value of the module's only statement, an
+    /// expression statement.
+    fn expression(&mut self, module: &'a Module) -> Result<CodeObject> {
+        let expr = match module.body[..] {
+            [Stmt {
+                kind: StmtKind::Expr(ref expr),
+                ..
+            }] => expr,
+            _ => {
+                let location = module
+                    .body
+                    .first()
+                    .map_or(Location::new(1, 0), |stmt| stmt.start);
+                return Err(CompilerError::new("invalid syntax", location));
+            }
+        };
+        self.enter(self.table.module(), "<module>", "<module>", 1);
+        self.expr(expr)?;
+        self.emit(Instruction::ReturnValue);
+        Ok(self.leave())
+    }
+
+    fn enter(&mut self, scope: &'a Scope, name: &str, qualname: &str, first_line: usize) {
+        let mut code = CodeObject::new(name, qualname, self.filename, first_line);
+        code.flags = self.future.flags;
+        code.varnames = scope.varnames.clone();
+        code.cellvars = scope.cellvars.clone();
+        code.freevars = scope.freevars.clone();
+        self.units.push(Unit {
+            code,
+            scope,
+            fblocks: Vec::new(),
+        });
+    }
+
+    fn leave(&mut self) -> CodeObject {
+        let mut code = self.units.pop().unwrap().code;
+        self.config.peephole.run(&mut code);
+        code
+    }
+
+    fn unit(&mut self) -> &mut Unit<'a> {
+        self.units.last_mut().unwrap()
+    }
+
+    fn code(&mut self) -> &mut CodeObject {
+        &mut self.unit().code
+    }
+
+    fn emit(&mut self, instruction: Instruction) -> usize {
+        let location = self.location;
+        let code = self.code();
+        code.instructions.push(instruction);
+        code.locations.push(location);
+        code.instructions.len() - 1
+    }
+
+    /// The index the next instruction will have.
+    fn here(&mut self) -> usize {
+        self.code().instructions.len()
+    }
+
+    /// Points the jumps at `jumps` to the next instruction.
+    fn patch(&mut self, jumps: &[usize]) {
+        let here = self.here();
+        for &jump in jumps {
+            *self.code().instructions[jump].target_mut().unwrap() = here;
+        }
+    }
+
+    fn load_const(&mut self, value: Constant) {
+        let index = self.code().add_constant(CodeConstant::Value(value));
+        self.emit(Instruction::LoadConst(index));
+    }
+
+    fn error<T, S: Into<String>>(&self, message: S) -> Result<T> {
+        Err(CompilerError::new(message, self.location))
+    }
+
+    /// Reports code that compiles but is most likely a mistake, or, in
+    /// strict mode, refuses it.
+    fn warn(&mut self, message: &str) -> Result<()> {
+        if self.config.strict {
+            return self.error(message);
+        }
+        let warning = CompilerWarning::new(message, self.location);
+        self.warnings.push(warning);
+        Ok(())
+    }
+
+    fn unsupported<T>(&self, what: &str) -> Result<T> {
+        self.error(format!("{} are not supported yet", what))
+    }
+
+    /// The qualified name of a function or class defined in the current unit.
+    fn qualname(&self, name: &str) -> String {
+        let unit = self.units.last().unwrap();
+        let global = unit.scope.symbol(name) == SymbolScope::GlobalExplicit;
+        match unit.scope.kind {
+            ScopeKind::Module => name.to_string(),
+            _ if global => name.to_string(),
+            ScopeKind::Class => format!("{}.{}", unit.code.qualname, name),
+            ScopeKind::Function => format!("{}.<locals>.{}", unit.code.qualname, name),
+        }
+    }
+
+    fn stmts(&mut self, body: &'a [Stmt]) -> Result<()> {
+        for stmt in body {
+            self.stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    fn stmt(&mut self, stmt: &'a Stmt) -> Result<()> {
+        self.location = stmt.start;
+        match stmt.kind {
+            StmtKind::FunctionDef {
+                ref name,
+                ref args,
+                ref body,
+                ref decorator_list,
+                ref returns,
+                ref type_params,
+                is_async,
+                ..
+            } => {
+                if is_async && self.table.stmt_scope(stmt).is_generator {
+                    return self.unsupported("asynchronous generators");
+                }
+                if !type_params.is_empty() {
+                    return self.unsupported("type parameter lists");
+                }
+                self.forbidden_name(name, Context::Store)?;
+                let start = self.decorators(decorator_list, stmt.start)?;
+                let scope = self.table.stmt_scope(stmt);
+                let qualname = self.qualname(name);
+                let docstring = body_docstring(body);
+                let returns = returns.as_deref();
+                self.function(
+                    scope,
+                    name,
+                    &qualname,
+                    args,
+                    returns,
+                    docstring,
+                    start,
+                    is_async,
+                    |codegen| {
+                        codegen.stmts(body)?;
+                        codegen.load_const(Constant::None);
+                        codegen.emit(Instruction::ReturnValue);
+                        Ok(())
+                    },
+                )?;
+                self.apply_decorators(decorator_list);
+                self.location = stmt.start;
+                self.store_name(name);
+            }
+            StmtKind::ClassDef {
+                ref name,
+                ref bases,
+                ref keywords,
+                ref body,
+                ref decorator_list,
+                ref type_params,
+            } => {
+                if !type_params.is_empty() {
+                    return self.unsupported("type parameter lists");
+                }
+                self.forbidden_name(name, Context::Store)?;
+                let start = self.decorators(decorator_list, stmt.start)?;
+                self.class(stmt, start, name, bases, keywords, body)?;
+                self.apply_decorators(decorator_list);
+                self.location = stmt.start;
+                self.store_name(name);
+            }
+            StmtKind::Return(ref value) => {
+                if self.unit().scope.kind != ScopeKind::Function {
+                    return self.error("'return' outside function");
+                }
+                match *value {
+                    Some(ref value) => self.expr(value)?,
+                    None => self.load_const(Constant::None),
+                }
+                self.unwind(0, true)?;
+                self.emit(Instruction::ReturnValue);
+            }
+            StmtKind::Delete(ref targets) => {
+                for target in targets {
+                    self.delete(target)?;
+                }
+            }
+            StmtKind::Assign {
+                ref targets,
+                ref value,
+                ..
+            } => {
+                self.expr(value)?;
+                for (index, target) in targets.iter().enumerate() {
+                    if index + 1 < targets.len() {
+                        self.emit(Instruction::DupTop);
+                    }
+                    self.store(target)?;
+                }
+            }
+            StmtKind::AugAssign {
+                ref target,
+                op,
+                ref value,
+            } => self.aug_assign(target, op, value)?,
+            StmtKind::AnnAssign {
+                ref target,
+                ref annotation,
+                ref value,
+                simple,
+            } => self.ann_assign(target, annotation, value.as_ref(), simple)?,
+            StmtKind::For {
+                ref target,
+                ref iter,
+                ref body,
+                ref orelse,
+                is_async,
+                ..
+            } => {
+                if is_async {
+                    return self.unsupported("async for loops");
+                }
+                self.expr(iter)?;
+                self.emit(Instruction::GetIter);
+                let start = self.here();
+                let exit = self.emit(Instruction::ForIter(0));
+                self.store(target)?;
+                self.loop_body(start, true, body)?;
+                self.location = stmt.start;
+                self.emit(Instruction::Jump(start));
+                self.patch(&[exit]);
+                self.finish_loop(orelse)?;
+            }
+            StmtKind::While {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                let start = self.here();
+                let exits = if is_constant_true(test) {
+                    Vec::new()
+                } else {
+                    self.jump_if(test, false)?
+                };
+                self.loop_body(start, false, body)?;
+                self.location = stmt.start;
+                self.emit(Instruction::Jump(start));
+                self.patch(&exits);
+                self.finish_loop(orelse)?;
+            }
+            StmtKind::If {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                let otherwise = self.jump_if(test, false)?;
+                self.stmts(body)?;
+                if orelse.is_empty() {
+                    self.patch(&otherwise);
+                } else {
+                    let end = self.emit(Instruction::Jump(0));
+                    self.patch(&otherwise);
+                    self.stmts(orelse)?;
+                    self.patch(&[end]);
+                }
+            }
+            StmtKind::With {
+                ref items,
+                ref body,
+                is_async,
+                ..
+            } => {
+                if is_async {
+                    if !self.in_coroutine() {
+                        return self.error("'async with' outside async function");
+                    }
+                    self.async_with(stmt, items, body)?;
+                } else {
+                    self.with(stmt, items, body)?;
+                }
+            }
+            StmtKind::Match { .. } => return self.unsupported("match statements"),
+            StmtKind::TypeAlias { .. } => return self.unsupported("type alias statements"),
+            StmtKind::Raise { ref exc, ref cause } => {
+                let mut count = 0;
+                if let Some(ref exc) = *exc {
+                    self.expr(exc)?;
+                    count += 1;
+                    if let Some(ref cause) = *cause {
+                        self.expr(cause)?;
+                        count += 1;
+                    }
+                }
+                self.emit(Instruction::RaiseVarargs(count));
+            }
+            StmtKind::Try {
+                ref body,
+                ref handlers,
+                ref orelse,
+                ref finalbody,
+                is_star,
+            } => {
+                if !finalbody.is_empty() {
+                    self.try_finally(body, handlers, orelse, finalbody, is_star)?;
+                } else if is_star {
+                    self.try_except_star(body, handlers, orelse)?;
+                } else {
+                    self.try_except(body, handlers, orelse)?;
+                }
+            }
+            StmtKind::Assert { ref test, ref msg } => {
+                let passed = self.jump_if(test, true)?;
+                self.emit(Instruction::LoadAssertionError);
+                if let Some(ref msg) = *msg {
+                    self.expr(msg)?;
+                    self.emit(Instruction::CallFunction(1));
+                }
+                self.emit(Instruction::RaiseVarargs(1));
+                self.patch(&passed);
+            }
+            StmtKind::Import(ref names) => {
+                for alias in names {
+                    let stored = alias.name.split('.').next().unwrap();
+                    self.forbidden_name(alias.asname.as_deref().unwrap_or(stored), Context::Store)?;
+                    self.load_const(Constant::Int(0));
+                    self.load_const(Constant::None);
+                    let index = self.code().add_name(&alias.name);
+                    self.emit(Instruction::ImportName(index));
+                    match alias.asname {
+                        Some(ref asname) => {
+                            for attr in alias.name.split('.').skip(1) {
+                                let index = self.code().add_name(attr);
+                                self.emit(Instruction::ImportFrom(index));
+                                self.emit(Instruction::RotTwo);
+                                self.emit(Instruction::PopTop);
+                            }
+                            self.store_name(asname);
+                        }
+                        None => self.store_name(alias.name.split('.').next().unwrap()),
+                    }
+                }
+            }
+            StmtKind::ImportFrom {
+                ref module,
+                ref names,
+                level,
+            } => {
+                if stmt.start.line > self.future.line && is_future_import(stmt).is_some() {
+                    return self.error(LATE_FUTURE);
+                }
+                self.load_const(Constant::Int(level as i64));
+                let fromlist = names
+                    .iter()
+                    .map(|alias| Constant::Str(alias.name.clone()))
+                    .collect();
+                self.load_const(Constant::Tuple(fromlist));
+                let module = module.as_ref().map_or("", |module| module.as_str());
+                let index = self.code().add_name(module);
+                self.emit(Instruction::ImportName(index));
+                if names.len() == 1 && names[0].name == "*" {
+                    self.emit(Instruction::ImportStar);
+                } else {
+                    for alias in names {
+                        let stored = alias.asname.as_ref().unwrap_or(&alias.name);
+                        self.forbidden_name(stored, Context::Store)?;
+                        let index = self.code().add_name(&alias.name);
+                        self.emit(Instruction::ImportFrom(index));
+                        self.store_name(alias.asname.as_ref().unwrap_or(&alias.name));
+                    }
+                    self.emit(Instruction::PopTop);
+                }
+            }
+            StmtKind::Global(_) | StmtKind::Nonlocal(_) | StmtKind::Pass => {}
+            StmtKind::Expr(ref value) if self.interactive && self.units.len() == 1 => {
+                self.expr(value)?;
+                self.emit(Instruction::PrintExpr);
+            }
+            StmtKind::Expr(ref value) => {
+                if let ExprKind::Constant(_) = value.kind {
+                    return Ok(());
+                }
+                self.expr(value)?;
+                self.emit(Instruction::PopTop);
+            }
+            StmtKind::Break => {
+                let depth = self.innermost_loop("'break' outside loop")?;
+                self.unwind(depth + 1, false)?;
+                if let FBlock::Loop { for_loop: true, .. } = self.unit().fblocks[depth] {
+                    self.emit(Instruction::PopTop);
+                }
+                let jump = self.emit(Instruction::Jump(0));
+                if let FBlock::Loop { ref mut breaks, .. } = self.unit().fblocks[depth] {
+                    breaks.push(jump);
+                }
+            }
+            StmtKind::Continue => {
+                let depth = self.innermost_loop("'continue' not properly in loop")?;
+                self.unwind(depth + 1, false)?;
+                if let FBlock::Loop { start, .. } = self.unit().fblocks[depth] {
+                    self.emit(Instruction::Jump(start));
+                }
+            }
+            StmtKind::Error => return self.error("invalid syntax"),
+        }
+        Ok(())
+    }
+
+    fn loop_body(&mut self, start: usize, for_loop: bool, body: &'a [Stmt]) -> Result<()> {
+        self.unit().fblocks.push(FBlock::Loop {
+            start,
+            breaks: Vec::new(),
+            for_loop,
+        });
+        self.stmts(body)
+    }
+
+    /// Compiles the `else` block of the loop on top of the block stack, and
+    /// points its `break`s past it.
+    fn finish_loop(&mut self, orelse: &'a [Stmt]) -> Result<()> {
+        let breaks = match self.unit().fblocks.pop() {
+            Some(FBlock::Loop { breaks, .. }) => breaks,
+            _ => unreachable!(),
+        };
+        self.stmts(orelse)?;
+        self.patch(&breaks);
+        Ok(())
+    }
+
+    fn innermost_loop(&mut self, message: &str) -> Result<usize> {
+        let found = self
+            .unit()
+            .fblocks
+            .iter()
+            .rposition(|block| matches!(*block, FBlock::Loop { .. }));
+        match found {
+            Some(depth) => Ok(depth),
+            None => self.error(message),
+        }
+    }
+
+    /// Emits the cleanup for leaving the blocks from `depth` up. With
+    /// `preserve_top`, the value on top of the stack is kept above it all.
+    fn unwind(&mut self, depth: usize, preserve_top: bool) -> Result<()> {
+        let location = self.location;
+        // Each block is off the stack while its cleanup is compiled, so a
+        // `return` in a `finally` clause only leaves the blocks around it.
+        let mut left = Vec::new();
+        let mut result = Ok(());
+        while self.unit().fblocks.len() > depth {
+            let block = self.unit().fblocks.pop().unwrap();
+            result = self.unwind_block(&block, preserve_top);
+            left.push(block);
+            if result.is_err() {
+                break;
+            }
+        }
+        while let Some(block) = left.pop() {
+            self.unit().fblocks.push(block);
+        }
+        self.location = location;
+        result
+    }
+
+    fn unwind_block(&mut self, block: &FBlock<'a>, preserve_top: bool) -> Result<()> {
+        match *block {
+            FBlock::Loop { for_loop, .. } => {
+                if for_loop {
+                    if preserve_top {
+                        self.emit(Instruction::RotTwo);
+                    }
+                    self.emit(Instruction::PopTop);
+                }
+            }
+            FBlock::TryExcept => {
+                self.emit(Instruction::PopBlock);
+            }
+            FBlock::With => {
+                self.emit(Instruction::PopBlock);
+                if preserve_top {
+                    self.emit(Instruction::RotTwo);
+                }
+                self.call_exit();
+            }
+            FBlock::AsyncWith => {
+                self.emit(Instruction::PopBlock);
+                if preserve_top {
+                    self.emit(Instruction::RotTwo);
+                }
+                self.call_async_exit();
+            }
+            FBlock::FinallyTry { finalbody } => {
+                self.emit(Instruction::PopBlock);
+                if preserve_top {
+                    self.unit().fblocks.push(FBlock::PopValue);
+                }
+                self.stmts(finalbody)?;
+                if preserve_top {
+                    self.unit().fblocks.pop();
+                }
+            }
+            // The stack holds the previously handled exception and the one
+            // the clause runs for.
+            FBlock::FinallyEnd => {
+                if preserve_top {
+                    self.emit(Instruction::RotTwo);
+                }
+                self.emit(Instruction::PopTop);
+                if preserve_top {
+                    self.emit(Instruction::RotTwo);
+                }
+                self.emit(Instruction::PopExcept);
+            }
+            FBlock::HandlerCleanup { name } => {
+                if name.is_some() {
+                    self.emit(Instruction::PopBlock);
+                }
+                if preserve_top {
+                    self.emit(Instruction::RotTwo);
+                }
+                self.emit(Instruction::PopExcept);
+                if let Some(name) = name {
+                    self.clear_name(name);
+                }
+            }
+            FBlock::PopValue => {
+                if preserve_top {
+                    self.emit(Instruction::RotTwo);
+                }
+                self.emit(Instruction::PopTop);
+            }
+            FBlock::ExceptStarHandler => {
+                return self
+                    .error("'break', 'continue' and 'return' cannot appear in an except* block");
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles `try` with `except` clauses and no `finally`.
+    ///
+    /// The handlers run with the previously handled exception and the new
+    /// one on the stack. Each tests the exception against its class in turn,
+    /// and the exception is raised again if none matches.
+    fn try_except(
+        &mut self,
+        body: &'a [Stmt],
+        handlers: &'a [ExceptHandler],
+        orelse: &'a [Stmt],
+    ) -> Result<()> {
+        let setup = self.emit(Instruction::SetupFinally(0));
+        self.unit().fblocks.push(FBlock::TryExcept);
+        self.stmts(body)?;
+        self.unit().fblocks.pop();
+        self.emit(Instruction::PopBlock);
+        self.stmts(orelse)?;
+        let mut ends = vec![self.emit(Instruction::Jump(0))];
+        self.patch(&[setup]);
+        for (index, handler) in handlers.iter().enumerate() {
+            self.location = handler.start;
+            let next = match handler.type_ {
+                Some(ref type_) => {
+                    self.emit(Instruction::DupTop);
+                    self.expr(type_)?;
+                    Some(self.emit(Instruction::JumpIfNotExcMatch(0)))
+                }
+                None if index + 1 < handlers.len() => {
+                    return self.error("default 'except:' must be last")
+                }
+                None => None,
+            };
+            match handler.name {
+                // The name is deleted afterwards, even if the body raises,
+                // so the exception does not keep the frame's values alive
+                // through its traceback.
+                Some(ref name) => {
+                    self.forbidden_name(name, Context::Store)?;
+                    self.store_name(name);
+                    let cleanup = self.emit(Instruction::SetupFinally(0));
+                    self.unit()
+                        .fblocks
+                        .push(FBlock::HandlerCleanup { name: Some(name) });
+                    self.stmts(&handler.body)?;
+                    self.unit().fblocks.pop();
+                    self.emit(Instruction::PopBlock);
+                    self.emit(Instruction::PopExcept);
+                    self.clear_name(name);
+                    ends.push(self.emit(Instruction::Jump(0)));
+                    self.patch(&[cleanup]);
+                    self.clear_name(name);
+                    self.emit(Instruction::Reraise);
+                }
+                None => {
+                    self.emit(Instruction::PopTop);
+                    self.unit()
+                        .fblocks
+                        .push(FBlock::HandlerCleanup { name: None });
+                    self.stmts(&handler.body)?;
+                    self.unit().fblocks.pop();
+                    self.emit(Instruction::PopExcept);
+                    ends.push(self.emit(Instruction::Jump(0)));
+                }
+            }
+            if let Some(next) = next {
+                self.patch(&[next]);
+            }
+        }
+        self.emit(Instruction::Reraise);
+        self.patch(&ends);
+        Ok(())
+    }
+
+    /// Compiles `try` with `except*` clauses and no `finally`.
+    ///
+    /// The handlers run with the previously handled exception, the one
+    /// caught, a list of the exceptions their bodies raise and what is left
+    /// unhandled on the stack. Each splits off the part of what is left
+    /// that its classes match and runs for that; at the end, what the
+    /// bodies raised and the rest are raised again together, if anything.
+    fn try_except_star(
+        &mut self,
+        body: &'a [Stmt],
+        handlers: &'a [ExceptHandler],
+        orelse: &'a [Stmt],
+    ) -> Result<()> {
+        let setup = self.emit(Instruction::SetupFinally(0));
+        self.unit().fblocks.push(FBlock::TryExcept);
+        self.stmts(body)?;
+        self.unit().fblocks.pop();
+        self.emit(Instruction::PopBlock);
+        self.stmts(orelse)?;
+        let end = self.emit(Instruction::Jump(0));
+        self.patch(&[setup]);
+        self.emit(Instruction::DupTop);
+        self.emit(Instruction::BuildList(0));
+        self.emit(Instruction::RotTwo);
+        for handler in handlers {
+            self.location = handler.start;
+            match handler.type_ {
+                Some(ref type_) => self.expr(type_)?,
+                None => return self.error("expected one or more exception types"),
+            }
+            let no_match = self.emit(Instruction::CheckEgMatch(0));
+            match handler.name {
+                Some(ref name) => {
+                    self.forbidden_name(name, Context::Store)?;
+                    self.store_name(name);
+                }
+                None => {
+                    self.emit(Instruction::PopTop);
+                }
+            }
+            let cleanup = self.emit(Instruction::SetupFinally(0));
+            self.unit().fblocks.push(FBlock::ExceptStarHandler);
+            self.stmts(&handler.body)?;
+            self.unit().fblocks.pop();
+            self.emit(Instruction::PopBlock);
+            if let Some(ref name) = handler.name {
+                self.clear_name(name);
+            }
+            let next = self.emit(Instruction::Jump(0));
+            // An exception the body raises is kept in the list, and the
+            // part it ran for is handled again until the next clause.
+            self.patch(&[cleanup]);
+            self.emit(Instruction::RotTwo);
+            self.emit(Instruction::PopExcept);
+            self.emit(Instruction::ListAppend(2));
+            if let Some(ref name) = handler.name {
+                self.clear_name(name);
+            }
+            self.patch(&[no_match, next]);
+        }
+        self.emit(Instruction::ListAppend(1));
+        self.emit(Instruction::PrepReraiseStar);
+        self.emit(Instruction::DupTop);
+        self.load_const(Constant::None);
+        self.emit(Instruction::CompareOp(CmpOperator::Is));
+        let reraise = self.emit(Instruction::PopJumpIfFalse(0));
+        self.emit(Instruction::PopTop);
+        self.emit(Instruction::PopExcept);
+        let handled = self.emit(Instruction::Jump(0));
+        self.patch(&[reraise]);
+        self.emit(Instruction::Reraise);
+        self.patch(&[end, handled]);
+        Ok(())
+    }
+
+    /// Compiles `try` with a `finally` clause, which is compiled twice: once
+    /// for leaving the body normally, and once as the handler that runs it
+    /// for an exception and raises the exception again.
+    fn try_finally(
+        &mut self,
+        body: &'a [Stmt],
+        handlers: &'a [ExceptHandler],
+        orelse: &'a [Stmt],
+        finalbody: &'a [Stmt],
+        is_star: bool,
+    ) -> Result<()> {
+        let setup = self.emit(Instruction::SetupFinally(0));
+        self.unit().fblocks.push(FBlock::FinallyTry { finalbody });
+        if handlers.is_empty() {
+            self.stmts(body)?;
+        } else if is_star {
+            self.try_except_star(body, handlers, orelse)?;
+        } else {
+            self.try_except(body, handlers, orelse)?;
+        }
+        self.unit().fblocks.pop();
+        self.emit(Instruction::PopBlock);
+        self.stmts(finalbody)?;
+        let end = self.emit(Instruction::Jump(0));
+        self.patch(&[setup]);
+        self.unit().fblocks.push(FBlock::FinallyEnd);
+        self.stmts(finalbody)?;
+        self.unit().fblocks.pop();
+        self.emit(Instruction::Reraise);
+        self.patch(&[end]);
+        Ok(())
+    }
+
+    /// Compiles `with` for the first of `items`, and the body with the rest
+    /// of them nested inside.
+    ///
+    /// On the way out of the body `__exit__` is called with three `None`s.
+    /// For an exception, the handler calls it with the exception instead,
+    /// and raises the exception again unless it returns true.
+    fn with(&mut self, stmt: &Stmt, items: &'a [WithItem], body: &'a [Stmt]) -> Result<()> {
+        let (item, rest) = items.split_first().unwrap();
+        self.expr(&item.context_expr)?;
+        let setup = self.emit(Instruction::SetupWith(0));
+        match item.optional_vars {
+            Some(ref target) => self.store(target)?,
+            None => {
+                self.emit(Instruction::PopTop);
+            }
+        }
+        self.unit().fblocks.push(FBlock::With);
+        if rest.is_empty() {
+            self.stmts(body)?;
+        } else {
+            self.with(stmt, rest, body)?;
+        }
+        self.unit().fblocks.pop();
+        self.location = stmt.start;
+        self.emit(Instruction::PopBlock);
+        self.call_exit();
+        let end = self.emit(Instruction::Jump(0));
+
+        self.patch(&[setup]);
+        self.emit(Instruction::WithExceptStart);
+        let suppress = self.emit(Instruction::PopJumpIfTrue(0));
+        self.emit(Instruction::Reraise);
+        self.patch(&[suppress]);
+        self.emit(Instruction::PopTop);
+        self.emit(Instruction::PopExcept);
+        self.emit(Instruction::PopTop);
+        self.patch(&[end]);
+        Ok(())
+    }
+
+    /// `async with`, as `with` but awaiting what `__aenter__` and
+    /// `__aexit__` return.
+    fn async_with(&mut self, stmt: &Stmt, items: &'a [WithItem], body: &'a [Stmt]) -> Result<()> {
+        let (item, rest) = items.split_first().unwrap();
+        self.expr(&item.context_expr)?;
+        self.emit(Instruction::BeforeAsyncWith);
+        self.await_top();
+        let setup = self.emit(Instruction::SetupAsyncWith(0));
+        match item.optional_vars {
+            Some(ref target) => self.store(target)?,
+            None => {
+                self.emit(Instruction::PopTop);
+            }
+        }
+        self.unit().fblocks.push(FBlock::AsyncWith);
+        if rest.is_empty() {
+            self.stmts(body)?;
+        } else {
+            self.async_with(stmt, rest, body)?;
+        }
+        self.unit().fblocks.pop();
+        self.location = stmt.start;
+        self.emit(Instruction::PopBlock);
+        self.call_async_exit();
+        let end = self.emit(Instruction::Jump(0));
+
+        self.patch(&[setup]);
+        self.emit(Instruction::WithExceptStart);
+        self.await_top();
+        let suppress = self.emit(Instruction::PopJumpIfTrue(0));
+        self.emit(Instruction::Reraise);
+        self.patch(&[suppress]);
+        self.emit(Instruction::PopTop);
+        self.emit(Instruction::PopExcept);
+        self.emit(Instruction::PopTop);
+        self.patch(&[end]);
+        Ok(())
+    }
+
+    /// Calls the `__exit__` on top with three `None`s, and discards what it
+    /// returns.
+    fn call_exit(&mut self) {
+        self.load_const(Constant::None);
+        self.emit(Instruction::DupTop);
+        self.emit(Instruction::DupTop);
+        self.emit(Instruction::CallFunction(3));
+        self.emit(Instruction::PopTop);
+    }
+
+    /// Calls the `__aexit__` on top with three `None`s, awaits what it
+    /// returns and discards the result.
+    fn call_async_exit(&mut self) {
+        self.load_const(Constant::None);
+        self.emit(Instruction::DupTop);
+        self.emit(Instruction::DupTop);
+        self.emit(Instruction::CallFunction(3));
+        self.await_top();
+        self.emit(Instruction::PopTop);
+    }
+
+    /// Awaits the object on top, leaving its result in its place.
+    fn await_top(&mut self) {
+        self.emit(Instruction::GetAwaitable);
+        self.load_const(Constant::None);
+        self.emit(Instruction::YieldFrom);
+    }
+
+    /// Whether the code being generated is the body of an `async def`.
+    fn in_coroutine(&mut self) -> bool {
+        self.code().flags & CO_COROUTINE != 0
+    }
+
+    /// Sets a name bound by `except ... as name` to `None` and deletes it.
+    fn clear_name(&mut self, name: &str) {
+        self.load_const(Constant::None);
+        self.store_name(name);
+        let instruction = self.name_instruction(name, Context::Del);
+        self.emit(instruction);
+    }
+
+    /// Compiles a function or lambda whose body `body` generates, leaving
+    /// the new function on the stack. The docstring, or `None`, is the
+    /// code's first constant, where `MakeFunction` finds it.
+    #[allow(clippy::too_many_arguments)]
+    fn function<F>(
+        &mut self,
+        scope: &'a Scope,
+        name: &str,
+        qualname: &str,
+        args: &Arguments,
+        returns: Option<&Expr>,
+        docstring: Option<&str>,
+        start: Location,
+        is_async: bool,
+        body: F,
+    ) -> Result<()>
+    where
+        F: FnOnce(&mut Codegen<'a>) -> Result<()>,
+    {
+        let parameters = args
+            .posonlyargs
+            .iter()
+            .chain(&args.args)
+            .chain(&args.vararg);
+        for arg in parameters.chain(&args.kwonlyargs).chain(&args.kwarg) {
+            self.forbidden_name(&arg.arg, Context::Store)?;
+        }
+        let mut flags = 0;
+        if !args.defaults.is_empty() {
+            for default in &args.defaults {
+                self.expr(default)?;
+            }
+            self.emit(Instruction::BuildTuple(args.defaults.len()));
+            flags |= MAKE_DEFAULTS;
+        }
+        let mut kwdefaults = 0;
+        for (arg, default) in args.kwonlyargs.iter().zip(&args.kw_defaults) {
+            if let Some(ref default) = *default {
+                self.load_const(Constant::Str(arg.arg.clone()));
+                self.expr(default)?;
+                kwdefaults += 1;
+            }
+        }
+        if kwdefaults > 0 {
+            self.emit(Instruction::BuildMap(kwdefaults));
+            flags |= MAKE_KWDEFAULTS;
+        }
+        if self.argument_annotations(args, returns)? {
+            flags |= MAKE_ANNOTATIONS;
+        }
+        flags |= self.closure(scope);
+
+        let nested = self.unit().scope.kind == ScopeKind::Function;
+        self.enter(scope, name, qualname, start.line);
+        {
+            let code = self.code();
+            code.argcount = args.posonlyargs.len() + args.args.len();
+            code.posonlyargcount = args.posonlyargs.len();
+            code.kwonlyargcount = args.kwonlyargs.len();
+            code.flags |= CO_OPTIMIZED | CO_NEWLOCALS;
+            if args.vararg.is_some() {
+                code.flags |= CO_VARARGS;
+            }
+            if args.kwarg.is_some() {
+                code.flags |= CO_VARKEYWORDS;
+            }
+            if nested {
+                code.flags |= CO_NESTED;
+            }
+            if is_async {
+                code.flags |= CO_COROUTINE;
+            } else if scope.is_generator {
+                code.flags |= CO_GENERATOR;
+            }
+            let doc = docstring.map_or(Constant::None, |doc| Constant::Str(doc.to_string()));
+            code.add_constant(CodeConstant::Value(doc));
+        }
+        let location = self.location;
+        body(self)?;
+        let code = self.leave();
+        self.location = location;
+
+        let index = self.code().add_constant(CodeConstant::Code(Arc::new(code)));
+        self.emit(Instruction::LoadConst(index));
+        self.load_const(Constant::Str(qualname.to_string()));
+        self.emit(Instruction::MakeFunction(flags));
+        Ok(())
+    }
+
+    /// Pushes the decorators of a definition that starts at `start`, in
+    /// source order, and returns the line its code starts at, which is that
+    /// of the first decorator.
+    fn decorators(&mut self, decorators: &[Expr], start: Location) -> Result<Location> {
+        for decorator in decorators {
+            self.location = decorator.start;
+            self.expr(decorator)?;
+        }
+        self.location = start;
+        Ok(decorators
+            .first()
+            .map_or(start, |decorator| decorator.start))
+    }
+
+    /// Calls the decorators under the new function or class on the stack,
+    /// the innermost first.
+    fn apply_decorators(&mut self, decorators: &[Expr]) {
+        for decorator in decorators.iter().rev() {
+            self.location = decorator.start;
+            self.emit(Instruction::CallFunction(1));
+        }
+    }
+
+    /// Creates `__annotations__` for a module or class body with annotated
+    /// assignments, even if none of them runs.
+    fn setup_annotations(&mut self, body: &[Stmt]) {
+        if has_annotations(body) {
+            if let Some(first) = body.first() {
+                self.location = first.start;
+            }
+            self.emit(Instruction::SetupAnnotations);
+        }
+    }
+
+    /// Assigns the docstring of a module or class body, if it has one, to
+    /// `__doc__`.
+    fn store_docstring(&mut self, body: &[Stmt]) {
+        if let Some(doc) = body_docstring(body) {
+            self.location = body[0].start;
+            self.load_const(Constant::Str(doc.to_string()));
+            self.store_name("__doc__");
+        }
+    }
+
+    /// Compiles a class statement: its body becomes a function that
+    /// `__build_class__` runs in the namespace of the new class, and then
+    /// calls the metaclass with, leaving the class on the stack.
+    fn class(
+        &mut self,
+        stmt: &'a Stmt,
+        start: Location,
+        name: &str,
+        bases: &[Expr],
+        keywords: &[Keyword],
+        body: &'a [Stmt],
+    ) -> Result<()> {
+        let scope = self.table.stmt_scope(stmt);
+        let qualname = self.qualname(name);
+        self.emit(Instruction::LoadBuildClass);
+        self.enter(scope, name, &qualname, start.line);
+        let location = self.location;
+        self.load_name("__name__");
+        self.store_name("__module__");
+        self.load_const(Constant::Str(qualname.clone()));
+        self.store_name("__qualname__");
+        self.setup_annotations(body);
+        self.store_docstring(body);
+        self.stmts(body)?;
+        // The cell of `__class__` is returned, for `__build_class__` to fill
+        // in with the new class.
+        if scope.cellvars.is_empty() {
+            self.load_const(Constant::None);
+        } else {
+            self.emit(Instruction::LoadClosure(0));
+            self.emit(Instruction::DupTop);
+            self.store_name("__classcell__");
+        }
+        self.emit(Instruction::ReturnValue);
+        let code = self.leave();
+        self.location = location;
+
+        let flags = self.closure(scope);
+        let index = self.code().add_constant(CodeConstant::Code(Arc::new(code)));
+        self.emit(Instruction::LoadConst(index));
+        self.load_const(Constant::Str(qualname));
+        self.emit(Instruction::MakeFunction(flags));
+        self.load_const(Constant::Str(name.to_string()));
+        self.call_arguments(2, bases, keywords)?;
+        Ok(())
+    }
+
+    /// Pushes a tuple of the cells that the code of `scope` closes over,
+    /// and returns the `MakeFunction` flag for it, if there are any.
+    fn closure(&mut self, scope: &Scope) -> u8 {
+        if scope.freevars.is_empty() {
+            return 0;
+        }
+        for name in &scope.freevars {
+            let index = self.cell_index(name);
+            self.emit(Instruction::LoadClosure(index));
+        }
+        self.emit(Instruction::BuildTuple(scope.freevars.len()));
+        MAKE_CLOSURE
+    }
+
+    /// The number of the cell of `name` in the current code.
+    fn cell_index(&mut self, name: &str) -> usize {
+        let code = self.code();
+        match code.cellvars.iter().position(|cell| cell == name) {
+            Some(index) => index,
+            None => {
+                code.cellvars.len() + code.freevars.iter().position(|free| free == name).unwrap()
+            }
+        }
+    }
+
+    /// Compiles a comprehension into a function that takes the iterator of
+    /// the first `for` clause as its only argument, `.0`, and calls it. The
+    /// rest of the clauses are evaluated inside it. A generator expression
+    /// is a generator function; the others build their collection and
+    /// return it.
+    fn comprehension(
+        &mut self,
+        expr: &Expr,
+        kind: ComprehensionKind,
+        elt: &Expr,
+        generators: &[Comprehension],
+    ) -> Result<()> {
+        if generators.iter().any(|generator| generator.is_async) {
+            return self.unsupported("asynchronous comprehensions");
+        }
+        let name = match kind {
+            ComprehensionKind::Generator => "<genexpr>",
+            ComprehensionKind::List => "<listcomp>",
+            ComprehensionKind::Set => "<setcomp>",
+            ComprehensionKind::Dict(_) => "<dictcomp>",
+        };
+        let scope = self.table.expr_scope(expr);
+        let qualname = self.qualname(name);
+        let nested = self.unit().scope.kind == ScopeKind::Function;
+        self.enter(scope, name, &qualname, expr.start.line);
+        {
+            let code = self.code();
+            code.argcount = 1;
+            code.flags |= CO_OPTIMIZED | CO_NEWLOCALS;
+            if let ComprehensionKind::Generator = kind {
+                code.flags |= CO_GENERATOR;
+            }
+            if nested {
+                code.flags |= CO_NESTED;
+            }
+        }
+        let location = self.location;
+        match kind {
+            ComprehensionKind::Generator => {}
+            ComprehensionKind::List => {
+                self.emit(Instruction::BuildList(0));
+            }
+            ComprehensionKind::Set => {
+                self.emit(Instruction::BuildSet(0));
+            }
+            ComprehensionKind::Dict(_) => {
+                self.emit(Instruction::BuildMap(0));
+            }
+        }
+        self.comprehension_clause(kind, generators, 0, elt)?;
+        if let ComprehensionKind::Generator = kind {
+            self.load_const(Constant::None);
+        }
+        self.emit(Instruction::ReturnValue);
+        let code = self.leave();
+        self.location = location;
+
+        let flags = self.closure(scope);
+        let index = self.code().add_constant(CodeConstant::Code(Arc::new(code)));
+        self.emit(Instruction::LoadConst(index));
+        self.load_const(Constant::Str(qualname));
+        self.emit(Instruction::MakeFunction(flags));
+        self.expr(&generators[0].iter)?;
+        self.emit(Instruction::GetIter);
+        self.emit(Instruction::CallFunction(1));
+        Ok(())
+    }
+
+    /// Compiles the `for` clause at `index` and those after it, adding
+    /// `elt` to the collection, or yielding it, in the innermost loop. The
+    /// collection is below the iterators of all the clauses on the stack.
+    fn comprehension_clause(
+        &mut self,
+        kind: ComprehensionKind,
+        generators: &[Comprehension],
+        index: usize,
+        elt: &Expr,
+    ) -> Result<()> {
+        let generator = &generators[index];
+        if index == 0 {
+            self.emit(Instruction::LoadFast(0));
+        } else {
+            self.expr(&generator.iter)?;
+            self.emit(Instruction::GetIter);
+        }
+        let start = self.here();
+        let exit = self.emit(Instruction::ForIter(0));
+        self.store(&generator.target)?;
+        let mut skips = Vec::new();
+        for condition in &generator.ifs {
+            skips.extend(self.jump_if(condition, false)?);
+        }
+        if index + 1 < generators.len() {
+            self.comprehension_clause(kind, generators, index + 1, elt)?;
+        } else {
+            let depth = generators.len() + 1;
+            self.expr(elt)?;
+            match kind {
+                ComprehensionKind::Generator => {
+                    self.emit(Instruction::YieldValue);
+                    self.emit(Instruction::PopTop);
+                }
+                ComprehensionKind::List => {
+                    self.emit(Instruction::ListAppend(depth));
+                }
+                ComprehensionKind::Set => {
+                    self.emit(Instruction::SetAdd(depth));
+                }
+                ComprehensionKind::Dict(value) => {
+                    self.expr(value)?;
+                    self.emit(Instruction::MapAdd(depth));
+                }
+            }
+        }
+        self.patch(&skips);
+        self.emit(Instruction::Jump(start));
+        self.patch(&[exit]);
+        Ok(())
+    }
+
+    /// Compiles an annotated assignment. In a module or class body, the
+    /// annotation of a `simple` target, a name without parentheses, is
+    /// stored in `__annotations__`, and those of other targets are only
+    /// evaluated. Function bodies do not evaluate their annotations.
+    fn ann_assign(
+        &mut self,
+        target: &Expr,
+        annotation: &Expr,
+        value: Option<&Expr>,
+        simple: bool,
+    ) -> Result<()> {
+        match value {
+            Some(value) => {
+                self.expr(value)?;
+                self.store(target)?;
+            }
+            // The object of an attribute or subscript target is still
+            // evaluated.
+            None => match target.kind {
+                ExprKind::Attribute { ref value, .. } => {
+                    self.expr(value)?;
+                    self.emit(Instruction::PopTop);
+                }
+                ExprKind::Subscript {
+                    ref value,
+                    ref slice,
+                    ..
+                } => {
+                    self.expr(value)?;
+                    self.emit(Instruction::PopTop);
+                    self.expr(slice)?;
+                    self.emit(Instruction::PopTop);
+                }
+                _ => {}
+            },
+        }
+        if self.unit().scope.kind == ScopeKind::Function {
+            return Ok(());
+        }
+        match target.kind {
+            ExprKind::Name { ref id, .. } if simple => {
+                self.annotation(annotation)?;
+                let index = self.code().add_name("__annotations__");
+                self.emit(Instruction::LoadName(index));
+                self.load_const(Constant::Str(id.clone()));
+                self.emit(Instruction::StoreSubscr);
+            }
+            _ if self.future.flags & CO_FUTURE_ANNOTATIONS == 0 => {
+                self.expr(annotation)?;
+                self.emit(Instruction::PopTop);
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Pushes the value of an annotation, or under `from __future__ import
+    /// annotations` its source as a string.
+    fn annotation(&mut self, annotation: &Expr) -> Result<()> {
+        if self.future.flags & CO_FUTURE_ANNOTATIONS != 0 {
+            self.load_const(Constant::Str(unparse_expr(annotation)));
+            return Ok(());
+        }
+        match annotation.kind {
+            // `*args: *Ts` is annotated with the one item of `Ts`.
+            ExprKind::Starred { ref value, .. } => {
+                self.expr(value)?;
+                self.emit(Instruction::UnpackSequence(1));
+            }
+            _ => self.expr(annotation)?,
+        }
+        Ok(())
+    }
+
+    /// Pushes a tuple of the names of annotated parameters, and `return`,
+    /// each followed by its annotation, for `MakeFunction`. Returns whether
+    /// there were any.
+    fn argument_annotations(&mut self, args: &Arguments, returns: Option<&Expr>) -> Result<bool> {
+        // CPython takes the positional-only parameters after the others.
+        let all = args
+            .args
+            .iter()
+            .chain(&args.posonlyargs)
+            .chain(&args.vararg)
+            .chain(&args.kwonlyargs)
+            .chain(&args.kwarg);
+        let mut count = 0;
+        for arg in all {
+            if let Some(ref annotation) = arg.annotation {
+                self.load_const(Constant::Str(arg.arg.clone()));
+                self.annotation(annotation)?;
+                count += 2;
+            }
+        }
+        if let Some(returns) = returns {
+            self.load_const(Constant::Str("return".to_string()));
+            self.annotation(returns)?;
+            count += 2;
+        }
+        if count > 0 {
+            self.emit(Instruction::BuildTuple(count));
+        }
+        Ok(count > 0)
+    }
+
+    fn aug_assign(&mut self, target: &Expr, op: ::ast::Operator, value: &Expr) -> Result<()> {
+        match target.kind {
+            ExprKind::Name { ref id, .. } => {
+                self.forbidden_name(id, Context::Store)?;
+                self.load_name(id);
+                self.expr(value)?;
+                self.emit(Instruction::InplaceOp(op));
+                self.store_name(id);
+            }
+            ExprKind::Attribute {
+                value: ref object,
+                ref attr,
+                ..
+            } => {
+                self.expr(object)?;
+                self.emit(Instruction::DupTop);
+                let index = self.code().add_name(attr);
+                self.emit(Instruction::LoadAttr(index));
+                self.expr(value)?;
+                self.emit(Instruction::InplaceOp(op));
+                self.emit(Instruction::RotTwo);
+                self.emit(Instruction::StoreAttr(index));
+            }
+            ExprKind::Subscript {
+                value: ref object,
+                ref slice,
+                ..
+            } => {
+                self.expr(object)?;
+                self.expr(slice)?;
+                self.emit(Instruction::DupTopTwo);
+                self.emit(Instruction::BinarySubscr);
+                self.expr(value)?;
+                self.emit(Instruction::InplaceOp(op));
+                self.emit(Instruction::RotThree);
+                self.emit(Instruction::StoreSubscr);
+            }
+            _ => return self.error("illegal expression for augmented assignment"),
+        }
+        Ok(())
+    }
+
+    fn name_instruction(&mut self, name: &str, ctx: Context) -> Instruction {
+        let scope = self.unit().scope;
+        let symbol = scope.symbol(name);
+        if symbol == SymbolScope::Free || symbol == SymbolScope::Cell {
+            let index = self.cell_index(name);
+            return match ctx {
+                Context::Load if scope.kind == ScopeKind::Class => {
+                    Instruction::LoadClassDeref(index)
+                }
+                Context::Load => Instruction::LoadDeref(index),
+                Context::Store => Instruction::StoreDeref(index),
+                Context::Del => Instruction::DeleteDeref(index),
+            };
+        }
+        if scope.kind == ScopeKind::Function && symbol == SymbolScope::Local {
+            let index = self.code().add_varname(name);
+            return match ctx {
+                Context::Load => Instruction::LoadFast(index),
+                Context::Store => Instruction::StoreFast(index),
+                Context::Del => Instruction::DeleteFast(index),
+            };
+        }
+        let index = self.code().add_name(name);
+        let global = match scope.kind {
+            ScopeKind::Function => true,
+            ScopeKind::Module | ScopeKind::Class => symbol == SymbolScope::GlobalExplicit,
+        };
+        match (ctx, global) {
+            (Context::Load, true) => Instruction::LoadGlobal(index),
+            (Context::Store, true) => Instruction::StoreGlobal(index),
+            (Context::Del, true) => Instruction::DeleteGlobal(index),
+            (Context::Load, false) => Instruction::LoadName(index),
+            (Context::Store, false) => Instruction::StoreName(index),
+            (Context::Del, false) => Instruction::DeleteName(index),
+        }
+    }
+
+    fn load_name(&mut self, name: &str) {
+        let instruction = self.name_instruction(name, Context::Load);
+        self.emit(instruction);
+    }
+
+    /// Fails if `name`, as a variable or attribute, is `__debug__` and is
+    /// being assigned to or deleted.
+    fn forbidden_name(&self, name: &str, context: Context) -> Result<()> {
+        match context {
+            _ if name != "__debug__" => Ok(()),
+            Context::Load => Ok(()),
+            Context::Store => self.error("cannot assign to __debug__"),
+            Context::Del => self.error("cannot delete __debug__"),
+        }
+    }
+
+    fn store_name(&mut self, name: &str) {
+        let instruction = self.name_instruction(name, Context::Store);
+        self.emit(instruction);
+    }
+
+    /// Stores the value on top of the stack into an assignment target.
+    fn store(&mut self, target: &Expr) -> Result<()> {
+        let location = self.location;
+        self.location = target.start;
+        match target.kind {
+            ExprKind::Name { ref id, .. } => {
+                self.forbidden_name(id, Context::Store)?;
+                self.store_name(id);
+            }
+            ExprKind::Attribute {
+                ref value,
+                ref attr,
+                ..
+            } => {
+                self.forbidden_name(attr, Context::Store)?;
+                self.expr(value)?;
+                let index = self.code().add_name(attr);
+                self.emit(Instruction::StoreAttr(index));
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => {
+                self.expr(value)?;
+                self.expr(slice)?;
+                self.emit(Instruction::StoreSubscr);
+            }
+            ExprKind::Tuple { ref elts, .. } | ExprKind::List { ref elts, .. } => {
+                let starred: Vec<usize> = elts
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, elt)| matches!(elt.kind, ExprKind::Starred { .. }))
+                    .map(|(index, _)| index)
+                    .collect();
+                match starred.len() {
+                    0 => {
+                        self.emit(Instruction::UnpackSequence(elts.len()));
+                    }
+                    1 => {
+                        let before = starred[0];
+                        let after = elts.len() - before - 1;
+                        if before >= 256 || after >= 1 << 24 {
+                            return self.error("too many expressions in star-unpacking assignment");
+                        }
+                        self.emit(Instruction::UnpackEx { before, after });
+                    }
+                    _ => return self.error("multiple starred expressions in assignment"),
+                }
+                for elt in elts {
+                    match elt.kind {
+                        ExprKind::Starred { ref value, .. } => self.store(value)?,
+                        _ => self.store(elt)?,
+                    }
+                }
+            }
+            ExprKind::Starred { .. } => {
+                return self.error("starred assignment target must be in a list or tuple")
+            }
+            _ => return self.error("cannot assign to expression"),
+        }
+        self.location = location;
+        Ok(())
+    }
+
+    fn delete(&mut self, target: &Expr) -> Result<()> {
+        self.location = target.start;
+        match target.kind {
+            ExprKind::Name { ref id, .. } => {
+                self.forbidden_name(id, Context::Del)?;
+                let instruction = self.name_instruction(id, Context::Del);
+                self.emit(instruction);
+            }
+            ExprKind::Attribute {
+                ref value,
+                ref attr,
+                ..
+            } => {
+                self.expr(value)?;
+                let index = self.code().add_name(attr);
+                self.emit(Instruction::DeleteAttr(index));
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => {
+                self.expr(value)?;
+                self.expr(slice)?;
+                self.emit(Instruction::DeleteSubscr);
+            }
+            ExprKind::Tuple { ref elts, .. } | ExprKind::List { ref elts, .. } => {
+                for elt in elts {
+                    self.delete(elt)?;
+                }
+            }
+            _ => return self.error("cannot delete expression"),
+        }
+        Ok(())
+    }
+
+    /// Compiles a jump taken when `test` is `condition`, returning the jumps
+    /// to patch with the target. Nothing is left on the stack either way.
+    fn jump_if(&mut self, test: &Expr, condition: bool) -> Result<Vec<usize>> {
+        match test.kind {
+            ExprKind::UnaryOp {
+                op: UnaryOperator::Not,
+                ref operand,
+            } => return self.jump_if(operand, !condition),
+            ExprKind::BoolOp { op, ref values } => {
+                // `a and b` is false as soon as one value is; it is true only
+                // once the last value is.
+                let short_circuit = (op == BoolOperator::Or) == condition;
+                let mut jumps = Vec::new();
+                let mut fall_through = Vec::new();
+                let (last, rest) = values.split_last().unwrap();
+                for value in rest {
+                    if short_circuit {
+                        jumps.extend(self.jump_if(value, condition)?);
+                    } else {
+                        fall_through.extend(self.jump_if(value, !condition)?);
+                    }
+                }
+                jumps.extend(self.jump_if(last, condition)?);
+                self.patch(&fall_through);
+                return Ok(jumps);
+            }
+            _ => {}
+        }
+        self.expr(test)?;
+        let jump = if condition {
+            Instruction::PopJumpIfTrue(0)
+        } else {
+            Instruction::PopJumpIfFalse(0)
+        };
+        Ok(vec![self.emit(jump)])
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Result<()> {
+        for expr in exprs {
+            self.expr(expr)?;
+        }
+        Ok(())
+    }
+
+    fn optional_expr(&mut self, expr: &Option<Box<Expr>>) -> Result<()> {
+        match *expr {
+            Some(ref expr) => self.expr(expr),
+            None => {
+                self.load_const(Constant::None);
+                Ok(())
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<()> {
+        let location = self.location;
+        self.location = expr.start;
+        match expr.kind {
+            ExprKind::BoolOp { op, ref values } => {
+                let mut jumps = Vec::new();
+                let (last, rest) = values.split_last().unwrap();
+                for value in rest {
+                    self.expr(value)?;
+                    jumps.push(self.emit(match op {
+                        BoolOperator::And => Instruction::JumpIfFalseOrPop(0),
+                        BoolOperator::Or => Instruction::JumpIfTrueOrPop(0),
+                    }));
+                }
+                self.expr(last)?;
+                self.patch(&jumps);
+            }
+            ExprKind::NamedExpr {
+                ref target,
+                ref value,
+            } => {
+                self.expr(value)?;
+                self.emit(Instruction::DupTop);
+                self.store(target)?;
+            }
+            ExprKind::BinOp {
+                ref left,
+                op,
+                ref right,
+            } => {
+                self.expr(left)?;
+                self.expr(right)?;
+                self.emit(Instruction::BinaryOp(op));
+            }
+            ExprKind::UnaryOp { op, ref operand } => {
+                self.expr(operand)?;
+                self.emit(Instruction::UnaryOp(op));
+            }
+            ExprKind::Lambda { ref args, ref body } => {
+                let scope = self.table.expr_scope(expr);
+                let qualname = self.qualname("<lambda>");
+                self.function(
+                    scope,
+                    "<lambda>",
+                    &qualname,
+                    args,
+                    None,
+                    None,
+                    expr.start,
+                    false,
+                    |codegen| {
+                        codegen.expr(body)?;
+                        codegen.emit(Instruction::ReturnValue);
+                        Ok(())
+                    },
+                )?;
+            }
+            ExprKind::IfExp {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                let otherwise = self.jump_if(test, false)?;
+                self.expr(body)?;
+                let end = self.emit(Instruction::Jump(0));
+                self.patch(&otherwise);
+                self.expr(orelse)?;
+                self.patch(&[end]);
+            }
+            ExprKind::Dict {
+                ref keys,
+                ref values,
+            } => self.dict(keys, values)?,
+            ExprKind::Set(ref elts) => self.starred_collection(0, elts, Collection::Set)?,
+            ExprKind::GeneratorExp {
+                ref elt,
+                ref generators,
+            } => self.comprehension(expr, ComprehensionKind::Generator, elt, generators)?,
+            ExprKind::ListComp {
+                ref elt,
+                ref generators,
+            } => self.comprehension(expr, ComprehensionKind::List, elt, generators)?,
+            ExprKind::SetComp {
+                ref elt,
+                ref generators,
+            } => self.comprehension(expr, ComprehensionKind::Set, elt, generators)?,
+            ExprKind::DictComp {
+                ref key,
+                ref value,
+                ref generators,
+            } => self.comprehension(expr, ComprehensionKind::Dict(value), key, generators)?,
+            ExprKind::Await(ref value) => {
+                if !self.in_coroutine() {
+                    let name = self.code().name.clone();
+                    if ["<genexpr>", "<listcomp>", "<setcomp>", "<dictcomp>"].contains(&&*name) {
+                        return self.unsupported("asynchronous comprehensions");
+                    }
+                    return self.error("'await' outside async function");
+                }
+                self.expr(value)?;
+                self.await_top();
+            }
+            ExprKind::Yield(ref value) => {
+                self.optional_expr(value)?;
+                self.emit(Instruction::YieldValue);
+            }
+            ExprKind::YieldFrom(ref value) => {
+                self.expr(value)?;
+                self.emit(Instruction::GetYieldFromIter);
+                self.load_const(Constant::None);
+                self.emit(Instruction::YieldFrom);
+            }
+            ExprKind::Compare {
+                ref left,
+                ref ops,
+                ref comparators,
+            } => self.compare(left, ops, comparators)?,
+            ExprKind::Call {
+                ref func,
+                ref args,
+                ref keywords,
+            } => {
+                self.expr(func)?;
+                self.call_arguments(0, args, keywords)?;
+            }
+            ExprKind::FormattedValue {
+                ref value,
+                conversion,
+                ref format_spec,
+            } => {
+                self.expr(value)?;
+                if let Some(ref format_spec) = *format_spec {
+                    self.expr(format_spec)?;
+                }
+                self.emit(Instruction::FormatValue {
+                    conversion,
+                    has_spec: format_spec.is_some(),
+                });
+            }
+            ExprKind::JoinedStr(ref values) => {
+                self.exprs(values)?;
+                if values.len() != 1 {
+                    self.emit(Instruction::BuildString(values.len()));
+                }
+            }
+            ExprKind::Constant(ref value) => self.load_const(value.clone()),
+            ExprKind::Attribute {
+                ref value,
+                ref attr,
+                ctx,
+            } => {
+                if ctx != Context::Load {
+                    return self.error("attribute target used as a value");
+                }
+                self.expr(value)?;
+                let index = self.code().add_name(attr);
+                self.emit(Instruction::LoadAttr(index));
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => {
+                self.expr(value)?;
+                self.expr(slice)?;
+                self.emit(Instruction::BinarySubscr);
+            }
+            ExprKind::Starred { .. } => return self.error("can't use starred expression here"),
+            ExprKind::Name { ref id, .. } => self.load_name(id),
+            ExprKind::List { ref elts, .. } => {
+                self.starred_collection(0, elts, Collection::List)?
+            }
+            ExprKind::Tuple { ref elts, .. } => {
+                self.starred_collection(0, elts, Collection::Tuple)?
+            }
+            ExprKind::Slice {
+                ref lower,
+                ref upper,
+                ref step,
+            } => {
+                self.optional_expr(lower)?;
+                self.optional_expr(upper)?;
+                if step.is_some() {
+                    self.optional_expr(step)?;
+                    self.emit(Instruction::BuildSlice(3));
+                } else {
+                    self.emit(Instruction::BuildSlice(2));
+                }
+            }
+        }
+        self.location = location;
+        Ok(())
+    }
+
+    /// Warns about `is` or `is not` with a literal, whose result depends on
+    /// whether equal constants happen to be shared, as CPython's
+    /// `check_compare` does. A chain gets one warning at most.
+    fn check_identity(
+        &mut self,
+        left: &Expr,
+        ops: &[CmpOperator],
+        comparators: &[Expr],
+    ) -> Result<()> {
+        let mut left = is_literal(left);
+        for (&op, comparator) in ops.iter().zip(comparators) {
+            let right = is_literal(comparator);
+            if left || right {
+                match op {
+                    CmpOperator::Is => {
+                        return self.warn("\"is\" with a literal. Did you mean \"==\"?")
+                    }
+                    CmpOperator::IsNot => {
+                        return self.warn("\"is not\" with a literal. Did you mean \"!=\"?")
+                    }
+                    _ => {}
+                }
+            }
+            left = right;
+        }
+        Ok(())
+    }
+
+    fn compare(&mut self, left: &Expr, ops: &[CmpOperator], comparators: &[Expr]) -> Result<()> {
+        self.check_identity(left, ops, comparators)?;
+        self.expr(left)?;
+        let (last_op, ops) = ops.split_last().unwrap();
+        let (last, comparators) = comparators.split_last().unwrap();
+        let mut cleanups = Vec::new();
+        for (&op, comparator) in ops.iter().zip(comparators) {
+            self.expr(comparator)?;
+            self.emit(Instruction::DupTop);
+            self.emit(Instruction::RotThree);
+            self.emit(Instruction::CompareOp(op));
+            cleanups.push(self.emit(Instruction::JumpIfFalseOrPop(0)));
+        }
+        self.expr(last)?;
+        self.emit(Instruction::CompareOp(*last_op));
+        if !cleanups.is_empty() {
+            let end = self.emit(Instruction::Jump(0));
+            self.patch(&cleanups);
+            self.emit(Instruction::RotTwo);
+            self.emit(Instruction::PopTop);
+            self.patch(&[end]);
+        }
+        Ok(())
+    }
+
+    /// Builds a list, tuple or set display, unpacking starred elements.
+    /// Builds a list, tuple or set of `elts`, after `pushed` items already
+    /// on the stack.
+    fn starred_collection(
+        &mut self,
+        pushed: usize,
+        elts: &[Expr],
+        collection: Collection,
+    ) -> Result<()> {
+        let first_star = elts
+            .iter()
+            .position(|elt| matches!(elt.kind, ExprKind::Starred { .. }));
+        let first_star = match first_star {
+            Some(first_star) => first_star,
+            None => {
+                self.exprs(elts)?;
+                let count = pushed + elts.len();
+                self.emit(match collection {
+                    Collection::List => Instruction::BuildList(count),
+                    Collection::Tuple => Instruction::BuildTuple(count),
+                    Collection::Set => Instruction::BuildSet(count),
+                });
+                return Ok(());
+            }
+        };
+        self.exprs(&elts[..first_star])?;
+        let set = collection == Collection::Set;
+        self.emit(if set {
+            Instruction::BuildSet(pushed + first_star)
+        } else {
+            Instruction::BuildList(pushed + first_star)
+        });
+        for elt in &elts[first_star..] {
+            match elt.kind {
+                ExprKind::Starred { ref value, .. } => {
+                    self.expr(value)?;
+                    self.emit(if set {
+                        Instruction::SetUpdate(1)
+                    } else {
+                        Instruction::ListExtend(1)
+                    });
+                }
+                _ => {
+                    self.expr(elt)?;
+                    self.emit(if set {
+                        Instruction::SetAdd(1)
+                    } else {
+                        Instruction::ListAppend(1)
+                    });
+                }
+            }
+        }
+        if collection == Collection::Tuple {
+            self.emit(Instruction::ListToTuple);
+        }
+        Ok(())
+    }
+
+    fn dict(&mut self, keys: &[Option<Expr>], values: &[Expr]) -> Result<()> {
+        let mut have_dict = false;
+        let mut pending = 0;
+        for (key, value) in keys.iter().zip(values) {
+            match *key {
+                Some(ref key) => {
+                    self.expr(key)?;
+                    self.expr(value)?;
+                    pending += 1;
+                }
+                None => {
+                    if pending > 0 || !have_dict {
+                        self.emit(Instruction::BuildMap(pending));
+                        if have_dict {
+                            self.emit(Instruction::DictUpdate(1));
+                        }
+                        have_dict = true;
+                        pending = 0;
+                    }
+                    self.expr(value)?;
+                    self.emit(Instruction::DictUpdate(1));
+                }
+            }
+        }
+        if pending > 0 || !have_dict {
+            self.emit(Instruction::BuildMap(pending));
+            if have_dict {
+                self.emit(Instruction::DictUpdate(1));
+            }
+        }
+        Ok(())
+    }
+
+    /// Compiles the arguments of a call to the function on the stack, and
+    /// the call itself. The first `pushed` positional arguments are on the
+    /// stack already.
+    fn call_arguments(&mut self, pushed: usize, args: &[Expr], keywords: &[Keyword]) -> Result<()> {
+        let mut seen: Vec<&str> = Vec::new();
+        for keyword in keywords {
+            if let Some(ref arg) = keyword.arg {
+                // CPython points at the call for `__debug__`, and at the
+                // keyword for one repeated.
+                self.forbidden_name(arg, Context::Store)?;
+                if seen.contains(&arg.as_str()) {
+                    self.location = keyword.start;
+                    return self.error(format!("keyword argument repeated: {}", arg));
+                }
+                seen.push(arg);
+            }
+        }
+
+        let starred = args
+            .iter()
+            .any(|arg| matches!(arg.kind, ExprKind::Starred { .. }));
+        let double_starred = keywords.iter().any(|keyword| keyword.arg.is_none());
+        if !starred && !double_starred {
+            self.exprs(args)?;
+            let count = pushed + args.len();
+            if keywords.is_empty() {
+                self.emit(Instruction::CallFunction(count));
+            } else {
+                let mut names = Vec::new();
+                for keyword in keywords {
+                    self.expr(&keyword.value)?;
+                    names.push(Constant::Str(keyword.arg.clone().unwrap()));
+                }
+                self.load_const(Constant::Tuple(names));
+                self.emit(Instruction::CallFunctionKw(count + keywords.len()));
+            }
+            return Ok(());
+        }
+
+        // A lone starred argument is passed as it is, and the call makes a
+        // tuple of it.
+        match args {
+            [Expr {
+                kind: ExprKind::Starred { ref value, .. },
+                ..
+            }] if pushed == 0 => self.expr(value)?,
+            _ => self.starred_collection(pushed, args, Collection::Tuple)?,
+        }
+        if !keywords.is_empty() {
+            let mut have_dict = false;
+            let mut pending = 0;
+            for keyword in keywords {
+                match keyword.arg {
+                    Some(ref arg) => {
+                        self.load_const(Constant::Str(arg.clone()));
+                        self.expr(&keyword.value)?;
+                        pending += 1;
+                    }
+                    None => {
+                        if pending > 0 || !have_dict {
+                            self.emit(Instruction::BuildMap(pending));
+                            if have_dict {
+                                self.emit(Instruction::DictMerge(1));
+                            }
+                            have_dict = true;
+                            pending = 0;
+                        }
+                        self.expr(&keyword.value)?;
+                        self.emit(Instruction::DictMerge(1));
+                    }
+                }
+            }
+            if pending > 0 {
+                self.emit(Instruction::BuildMap(pending));
+                if have_dict {
+                    self.emit(Instruction::DictMerge(1));
+                }
+            }
+        }
+        self.emit(Instruction::CallFunctionEx(!keywords.is_empty()));
+        Ok(())
+    }
+}
+
+/// What a comprehension makes. A dict comprehension's `elt` is the key,
+/// and it holds the value.
+#[derive(Clone, Copy)]
+enum ComprehensionKind<'e> {
+    Generator,
+    List,
+    Set,
+    Dict(&'e Expr),
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Collection {
+    List,
+    Tuple,
+    Set,
+}
+
+/// Whether `body` has an annotated assignment, outside any function or
+/// class it defines.
+fn has_annotations(body: &[Stmt]) -> bool {
+    body.iter().any(|stmt| match stmt.kind {
+        StmtKind::AnnAssign { .. } => true,
+        StmtKind::For {
+            ref body,
+            ref orelse,
+            ..
+        }
+        | StmtKind::While {
+            ref body,
+            ref orelse,
+            ..
+        }
+        | StmtKind::If {
+            ref body,
+            ref orelse,
+            ..
+        } => has_annotations(body) || has_annotations(orelse),
+        StmtKind::With { ref body, .. } => has_annotations(body),
+        StmtKind::Try {
+            ref body,
+            ref handlers,
+            ref orelse,
+            ref finalbody,
+            ..
+        } => {
+            has_annotations(body)
+                || handlers
+                    .iter()
+                    .any(|handler| has_annotations(&handler.body))
+                || has_annotations(orelse)
+                || has_annotations(finalbody)
+        }
+        StmtKind::Match { ref cases, .. } => cases.iter().any(|case| has_annotations(&case.body)),
+        _ => false,
+    })
+}
+
+/// Whether `expr` is a constant such as `True` or `1`, which a `while` loop
+/// need not test.
+fn is_constant_true(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::Constant(Constant::Bool(value)) => value,
+        ExprKind::Constant(Constant::Int(value)) => value != 0,
+        _ => false,
+    }
+}
+
+/// Whether `expr` is a constant that `is` should not be used with: any but
+/// the singletons `None`, `True`, `False` and `...`.
+fn is_literal(expr: &Expr) -> bool {
+    match expr.kind {
+        ExprKind::Constant(ref value) => !matches!(
+            *value,
+            Constant::None | Constant::Bool(_) | Constant::Ellipsis
+        ),
+        _ => false,
+    }
+}
@@ -0,0 +1,95 @@
+//! The features a module turns on with `from __future__ import`, like
+//! CPython's `future.c`.
+//!
+//! Only the imports at the beginning of a module, after its docstring, are
+//! future statements. Codegen refuses any other import from `__future__`.
+
+use ast::{body_docstring, Module, Stmt, StmtKind};
+
+use super::code::{CO_FUTURE_ANNOTATIONS, CO_FUTURE_BARRY_AS_BDFL};
+use super::CompilerError;
+
+/// The features `__future__` knows, with the flags they set. The others
+/// are the default in Python 3, and change nothing.
+const FEATURES: [(&str, u32); 10] = [
+    ("nested_scopes", 0),
+    ("generators", 0),
+    ("division", 0),
+    ("absolute_import", 0),
+    ("with_statement", 0),
+    ("print_function", 0),
+    ("unicode_literals", 0),
+    ("barry_as_FLUFL", CO_FUTURE_BARRY_AS_BDFL),
+    ("generator_stop", 0),
+    ("annotations", CO_FUTURE_ANNOTATIONS),
+];
+
+/// The error for an import from `__future__` that is not a future
+/// statement.
+pub(super) const LATE_FUTURE: &str =
+    "from __future__ imports must occur at the beginning of the file";
+
+/// The future statements of a module.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FutureFeatures {
+    /// The `CO_FUTURE_` flags of the features turned on, which all code of
+    /// the module has.
+    pub flags: u32,
+    /// The line of the last future statement, or 0 without one. An import
+    /// from `__future__` after it is too late.
+    pub line: usize,
+}
+
+/// The features turned on by the future statements of `module`. Fails for
+/// a feature `__future__` does not have, or for an import from it that
+/// follows another statement on the same line as a future statement.
+pub fn future_features(module: &Module) -> Result<FutureFeatures, CompilerError> {
+    let mut features = FutureFeatures::default();
+    let skip = body_docstring(&module.body).map_or(0, |_| 1);
+    // Codegen finds the late imports on a later line than the last future
+    // statement. Those on the same line are found here.
+    let mut done = false;
+    let mut previous_line = 0;
+    for stmt in &module.body[skip..] {
+        if done && stmt.start.line > previous_line {
+            break;
+        }
+        previous_line = stmt.start.line;
+        match is_future_import(stmt) {
+            Some(_) if done => return Err(CompilerError::new(LATE_FUTURE, stmt.start)),
+            Some(names) => {
+                for name in names {
+                    features.flags |= feature_flag(name, stmt)?;
+                }
+                features.line = stmt.start.line;
+            }
+            None => done = true,
+        }
+    }
+    Ok(features)
+}
+
+/// The names imported by `stmt`, if it imports from `__future__`. The
+/// module is compared whatever the level of the import, as CPython does.
+pub(super) fn is_future_import(stmt: &Stmt) -> Option<impl Iterator<Item = &str>> {
+    match stmt.kind {
+        StmtKind::ImportFrom {
+            module: Some(ref module),
+            ref names,
+            ..
+        } if module == "__future__" => Some(names.iter().map(|alias| alias.name.as_str())),
+        _ => None,
+    }
+}
+
+/// The flag of the feature `name`, imported by `stmt`.
+fn feature_flag(name: &str, stmt: &Stmt) -> Result<u32, CompilerError> {
+    match FEATURES.iter().find(|&&(feature, _)| feature == name) {
+        Some(&(_, flag)) => Ok(flag),
+        None if name == "braces" => Err(CompilerError::new("not a chance", stmt.start)),
+        None => Err(CompilerError::new(
+            format!("future feature {} is not defined", name),
+            stmt.start,
+        )),
+    }
+}
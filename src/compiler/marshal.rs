@@ -0,0 +1,575 @@
+//! A binary format for code objects, after CPython's `marshal`, so that
+//! compiled code can be stored and run without compiling its source again.
+//!
+//! Integers are unsigned LEB128, and signed ones zigzag-encoded first;
+//! floats are their bits, little-endian; strings and sequences are their
+//! length followed by their contents. The format belongs to this version of
+//! rustpy, whose version it starts with, and is not compatible with
+//! CPython's.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use ast::{CmpOperator, Constant, Operator, UnaryOperator};
+use tokenizer::Location;
+
+use super::code::{CodeConstant, CodeObject, Instruction};
+
+const MAGIC: &[u8; 4] = b"RPYC";
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Data that `load_code` cannot read as a code object.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarshalError {
+    pub message: String,
+    /// The offset in the data where reading failed.
+    pub offset: usize,
+}
+
+impl fmt::Display for MarshalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "bad marshal data at byte {}: {}",
+            self.offset, self.message
+        )
+    }
+}
+
+impl Error for MarshalError {}
+
+/// Writes a code object and the code nested in it.
+pub fn dump_code(code: &CodeObject) -> Vec<u8> {
+    let mut writer = Writer { out: Vec::new() };
+    writer.out.extend_from_slice(MAGIC);
+    writer.string(VERSION);
+    writer.code(code);
+    writer.out
+}
+
+/// Reads a code object written by `dump_code`, by the same version of
+/// rustpy.
+pub fn load_code(data: &[u8]) -> Result<CodeObject, MarshalError> {
+    let mut reader = Reader { data, offset: 0 };
+    if reader.bytes(MAGIC.len())? != MAGIC {
+        return Err(reader.error("not a code object"));
+    }
+    let version = reader.string()?;
+    if version != VERSION {
+        let message = format!("written by rustpy {}, not {}", version, VERSION);
+        return Err(reader.error(&message));
+    }
+    let code = reader.code()?;
+    if reader.offset != data.len() {
+        return Err(reader.error("trailing data"));
+    }
+    Ok(code)
+}
+
+const OPERATORS: [Operator; 13] = [
+    Operator::Add,
+    Operator::Sub,
+    Operator::Mult,
+    Operator::MatMult,
+    Operator::Div,
+    Operator::Mod,
+    Operator::Pow,
+    Operator::LShift,
+    Operator::RShift,
+    Operator::BitOr,
+    Operator::BitXor,
+    Operator::BitAnd,
+    Operator::FloorDiv,
+];
+
+const UNARY_OPERATORS: [UnaryOperator; 4] = [
+    UnaryOperator::Invert,
+    UnaryOperator::Not,
+    UnaryOperator::UAdd,
+    UnaryOperator::USub,
+];
+
+const CMP_OPERATORS: [CmpOperator; 10] = [
+    CmpOperator::Eq,
+    CmpOperator::NotEq,
+    CmpOperator::Lt,
+    CmpOperator::LtE,
+    CmpOperator::Gt,
+    CmpOperator::GtE,
+    CmpOperator::Is,
+    CmpOperator::IsNot,
+    CmpOperator::In,
+    CmpOperator::NotIn,
+];
+
+/// Defines `Writer::instruction` and `Reader::instruction` from the opcode
+/// of each instruction without an operand, and of each with a `usize` one.
+/// The others are written out by hand after them.
+macro_rules! instructions {
+    (
+        plain: { $($plain:ident = $plain_op:expr,)* }
+        indexed: { $($indexed:ident = $indexed_op:expr,)* }
+    ) => {
+        impl Writer {
+            fn instruction(&mut self, instruction: &Instruction) {
+                match *instruction {
+                    $(Instruction::$plain => self.byte($plain_op),)*
+                    $(Instruction::$indexed(operand) => {
+                        self.byte($indexed_op);
+                        self.unsigned(operand);
+                    })*
+                    _ => self.other_instruction(instruction),
+                }
+            }
+        }
+
+        impl<'a> Reader<'a> {
+            fn instruction(&mut self) -> Result<Instruction, MarshalError> {
+                Ok(match self.byte()? {
+                    $($plain_op => Instruction::$plain,)*
+                    $($indexed_op => Instruction::$indexed(self.unsigned()?),)*
+                    opcode => self.other_instruction(opcode)?,
+                })
+            }
+        }
+    };
+}
+
+instructions! {
+    plain: {
+        Nop = 0,
+        PopTop = 1,
+        RotTwo = 2,
+        RotThree = 3,
+        DupTop = 4,
+        DupTopTwo = 5,
+        BinarySubscr = 6,
+        StoreSubscr = 7,
+        DeleteSubscr = 8,
+        ListToTuple = 9,
+        GetIter = 10,
+        LoadBuildClass = 11,
+        ReturnValue = 12,
+        YieldValue = 13,
+        GetYieldFromIter = 14,
+        YieldFrom = 15,
+        ImportStar = 16,
+        LoadAssertionError = 17,
+        WithExceptStart = 18,
+        PopBlock = 19,
+        PopExcept = 20,
+        Reraise = 21,
+        PrintExpr = 22,
+        SetupAnnotations = 23,
+        PrepReraiseStar = 24,
+        GetAwaitable = 25,
+        BeforeAsyncWith = 26,
+    }
+    indexed: {
+        LoadConst = 32,
+        LoadName = 33,
+        StoreName = 34,
+        DeleteName = 35,
+        LoadGlobal = 36,
+        StoreGlobal = 37,
+        DeleteGlobal = 38,
+        LoadFast = 39,
+        StoreFast = 40,
+        DeleteFast = 41,
+        LoadAttr = 42,
+        StoreAttr = 43,
+        DeleteAttr = 44,
+        BuildTuple = 45,
+        BuildList = 46,
+        BuildSet = 47,
+        BuildMap = 48,
+        BuildConstKeyMap = 49,
+        BuildSlice = 50,
+        BuildString = 51,
+        ListExtend = 52,
+        SetUpdate = 53,
+        DictUpdate = 54,
+        DictMerge = 55,
+        ListAppend = 56,
+        SetAdd = 57,
+        MapAdd = 58,
+        UnpackSequence = 59,
+        Jump = 60,
+        PopJumpIfFalse = 61,
+        PopJumpIfTrue = 62,
+        JumpIfFalseOrPop = 63,
+        JumpIfTrueOrPop = 64,
+        ForIter = 65,
+        CallFunction = 66,
+        CallFunctionKw = 67,
+        LoadClosure = 68,
+        LoadDeref = 69,
+        ImportName = 70,
+        ImportFrom = 71,
+        RaiseVarargs = 72,
+        SetupFinally = 73,
+        SetupWith = 74,
+        JumpIfNotExcMatch = 75,
+        StoreDeref = 76,
+        DeleteDeref = 77,
+        LoadClassDeref = 78,
+        CheckEgMatch = 79,
+        SetupAsyncWith = 80,
+    }
+}
+
+const BINARY_OP: u8 = 96;
+const INPLACE_OP: u8 = 97;
+const UNARY_OP: u8 = 98;
+const COMPARE_OP: u8 = 99;
+const UNPACK_EX: u8 = 100;
+const FORMAT_VALUE: u8 = 101;
+const CALL_FUNCTION_EX: u8 = 102;
+const MAKE_FUNCTION: u8 = 103;
+
+const NONE: u8 = b'N';
+const FALSE: u8 = b'F';
+const TRUE: u8 = b'T';
+const STR: u8 = b's';
+const BYTES: u8 = b'b';
+const INT: u8 = b'i';
+const LONG_INT: u8 = b'l';
+const FLOAT: u8 = b'f';
+const COMPLEX: u8 = b'x';
+const ELLIPSIS: u8 = b'.';
+const TUPLE: u8 = b'(';
+const CODE: u8 = b'c';
+
+struct Writer {
+    out: Vec<u8>,
+}
+
+impl Writer {
+    fn byte(&mut self, byte: u8) {
+        self.out.push(byte);
+    }
+
+    fn unsigned(&mut self, mut value: usize) {
+        while value >= 0x80 {
+            self.out.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.out.push(value as u8);
+    }
+
+    fn signed(&mut self, value: i64) {
+        self.unsigned(((value << 1) ^ (value >> 63)) as u64 as usize);
+    }
+
+    fn float(&mut self, value: f64) {
+        self.out.extend_from_slice(&value.to_bits().to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.unsigned(bytes.len());
+        self.out.extend_from_slice(bytes);
+    }
+
+    fn string(&mut self, string: &str) {
+        self.bytes(string.as_bytes());
+    }
+
+    fn strings(&mut self, strings: &[String]) {
+        self.unsigned(strings.len());
+        for string in strings {
+            self.string(string);
+        }
+    }
+
+    fn code(&mut self, code: &CodeObject) {
+        self.string(&code.name);
+        self.string(&code.qualname);
+        self.string(&code.filename);
+        self.unsigned(code.first_line);
+        self.unsigned(code.argcount);
+        self.unsigned(code.posonlyargcount);
+        self.unsigned(code.kwonlyargcount);
+        self.unsigned(code.flags as usize);
+        self.unsigned(code.instructions.len());
+        for instruction in &code.instructions {
+            self.instruction(instruction);
+        }
+        self.unsigned(code.locations.len());
+        for location in &code.locations {
+            self.unsigned(location.line);
+            self.unsigned(location.column);
+        }
+        self.unsigned(code.constants.len());
+        for constant in &code.constants {
+            match *constant {
+                CodeConstant::Value(ref value) => self.constant(value),
+                CodeConstant::Code(ref code) => {
+                    self.byte(CODE);
+                    self.code(code);
+                }
+            }
+        }
+        self.strings(&code.names);
+        self.strings(&code.varnames);
+        self.strings(&code.cellvars);
+        self.strings(&code.freevars);
+    }
+
+    fn constant(&mut self, constant: &Constant) {
+        match *constant {
+            Constant::None => self.byte(NONE),
+            Constant::Bool(false) => self.byte(FALSE),
+            Constant::Bool(true) => self.byte(TRUE),
+            Constant::Str(ref value) => {
+                self.byte(STR);
+                self.string(value);
+            }
+            Constant::Bytes(ref value) => {
+                self.byte(BYTES);
+                self.bytes(value);
+            }
+            Constant::Int(value) => {
+                self.byte(INT);
+                self.signed(value);
+            }
+            Constant::LongInt(ref digits) => {
+                self.byte(LONG_INT);
+                self.string(digits);
+            }
+            Constant::Float(value) => {
+                self.byte(FLOAT);
+                self.float(value);
+            }
+            Constant::Complex { real, imag } => {
+                self.byte(COMPLEX);
+                self.float(real);
+                self.float(imag);
+            }
+            Constant::Ellipsis => self.byte(ELLIPSIS),
+            Constant::Tuple(ref items) => {
+                self.byte(TUPLE);
+                self.unsigned(items.len());
+                for item in items {
+                    self.constant(item);
+                }
+            }
+        }
+    }
+
+    fn other_instruction(&mut self, instruction: &Instruction) {
+        match *instruction {
+            Instruction::BinaryOp(op) | Instruction::InplaceOp(op) => {
+                let opcode = match *instruction {
+                    Instruction::BinaryOp(_) => BINARY_OP,
+                    _ => INPLACE_OP,
+                };
+                self.byte(opcode);
+                self.byte(OPERATORS.iter().position(|&other| other == op).unwrap() as u8);
+            }
+            Instruction::UnaryOp(op) => {
+                self.byte(UNARY_OP);
+                self.byte(
+                    UNARY_OPERATORS
+                        .iter()
+                        .position(|&other| other == op)
+                        .unwrap() as u8,
+                );
+            }
+            Instruction::CompareOp(op) => {
+                self.byte(COMPARE_OP);
+                self.byte(CMP_OPERATORS.iter().position(|&other| other == op).unwrap() as u8);
+            }
+            Instruction::UnpackEx { before, after } => {
+                self.byte(UNPACK_EX);
+                self.unsigned(before);
+                self.unsigned(after);
+            }
+            Instruction::FormatValue {
+                conversion,
+                has_spec,
+            } => {
+                self.byte(FORMAT_VALUE);
+                self.unsigned(conversion.map_or(0, |conversion| conversion as usize + 1));
+                self.byte(has_spec as u8);
+            }
+            Instruction::CallFunctionEx(has_keywords) => {
+                self.byte(CALL_FUNCTION_EX);
+                self.byte(has_keywords as u8);
+            }
+            Instruction::MakeFunction(flags) => {
+                self.byte(MAKE_FUNCTION);
+                self.byte(flags);
+            }
+            _ => unreachable!("{:?} has an opcode in `instructions!`", instruction),
+        }
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self, message: &str) -> MarshalError {
+        MarshalError {
+            message: message.to_string(),
+            offset: self.offset,
+        }
+    }
+
+    fn byte(&mut self) -> Result<u8, MarshalError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], MarshalError> {
+        if self.data.len() - self.offset < len {
+            return Err(self.error("unexpected end of data"));
+        }
+        let bytes = &self.data[self.offset..self.offset + len];
+        self.offset += len;
+        Ok(bytes)
+    }
+
+    fn unsigned(&mut self) -> Result<usize, MarshalError> {
+        let mut value: u64 = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            if shift >= 64 {
+                return Err(self.error("integer too large"));
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value as usize);
+            }
+            shift += 7;
+        }
+    }
+
+    fn signed(&mut self) -> Result<i64, MarshalError> {
+        let value = self.unsigned()? as u64;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn float(&mut self) -> Result<f64, MarshalError> {
+        let mut bits = [0; 8];
+        bits.copy_from_slice(self.bytes(8)?);
+        Ok(f64::from_bits(u64::from_le_bytes(bits)))
+    }
+
+    fn byte_string(&mut self) -> Result<Vec<u8>, MarshalError> {
+        let len = self.unsigned()?;
+        Ok(self.bytes(len)?.to_vec())
+    }
+
+    fn string(&mut self) -> Result<String, MarshalError> {
+        let bytes = self.byte_string()?;
+        String::from_utf8(bytes).map_err(|_| self.error("string is not UTF-8"))
+    }
+
+    fn strings(&mut self) -> Result<Vec<String>, MarshalError> {
+        let len = self.unsigned()?;
+        (0..len).map(|_| self.string()).collect()
+    }
+
+    fn code(&mut self) -> Result<CodeObject, MarshalError> {
+        let name = self.string()?;
+        let qualname = self.string()?;
+        let filename = self.string()?;
+        let mut code = CodeObject::new(&name, &qualname, &filename, self.unsigned()?);
+        code.argcount = self.unsigned()?;
+        code.posonlyargcount = self.unsigned()?;
+        code.kwonlyargcount = self.unsigned()?;
+        code.flags = self.unsigned()? as u32;
+        let len = self.unsigned()?;
+        for _ in 0..len {
+            code.instructions.push(self.instruction()?);
+        }
+        let len = self.unsigned()?;
+        for _ in 0..len {
+            code.locations
+                .push(Location::new(self.unsigned()?, self.unsigned()?));
+        }
+        let len = self.unsigned()?;
+        for _ in 0..len {
+            let constant = match self.byte()? {
+                CODE => CodeConstant::Code(Arc::new(self.code()?)),
+                tag => CodeConstant::Value(self.constant(tag)?),
+            };
+            code.constants.push(constant);
+        }
+        code.names = self.strings()?;
+        code.varnames = self.strings()?;
+        code.cellvars = self.strings()?;
+        code.freevars = self.strings()?;
+        Ok(code)
+    }
+
+    /// The constant after its tag, `tag`.
+    fn constant(&mut self, tag: u8) -> Result<Constant, MarshalError> {
+        Ok(match tag {
+            NONE => Constant::None,
+            FALSE => Constant::Bool(false),
+            TRUE => Constant::Bool(true),
+            STR => Constant::Str(self.string()?),
+            BYTES => Constant::Bytes(self.byte_string()?),
+            INT => Constant::Int(self.signed()?),
+            LONG_INT => Constant::LongInt(self.string()?),
+            FLOAT => Constant::Float(self.float()?),
+            COMPLEX => Constant::Complex {
+                real: self.float()?,
+                imag: self.float()?,
+            },
+            ELLIPSIS => Constant::Ellipsis,
+            TUPLE => {
+                let len = self.unsigned()?;
+                let mut items = Vec::with_capacity(len.min(self.data.len()));
+                for _ in 0..len {
+                    let tag = self.byte()?;
+                    items.push(self.constant(tag)?);
+                }
+                Constant::Tuple(items)
+            }
+            _ => return Err(self.error("unknown constant type")),
+        })
+    }
+
+    fn operator<T: Copy>(&mut self, operators: &[T]) -> Result<T, MarshalError> {
+        let index = self.byte()? as usize;
+        match operators.get(index) {
+            Some(&op) => Ok(op),
+            None => Err(self.error("unknown operator")),
+        }
+    }
+
+    fn other_instruction(&mut self, opcode: u8) -> Result<Instruction, MarshalError> {
+        Ok(match opcode {
+            BINARY_OP => Instruction::BinaryOp(self.operator(&OPERATORS)?),
+            INPLACE_OP => Instruction::InplaceOp(self.operator(&OPERATORS)?),
+            UNARY_OP => Instruction::UnaryOp(self.operator(&UNARY_OPERATORS)?),
+            COMPARE_OP => Instruction::CompareOp(self.operator(&CMP_OPERATORS)?),
+            UNPACK_EX => Instruction::UnpackEx {
+                before: self.unsigned()?,
+                after: self.unsigned()?,
+            },
+            FORMAT_VALUE => {
+                let conversion = match self.unsigned()? {
+                    0 => None,
+                    code => match ::std::char::from_u32(code as u32 - 1) {
+                        Some(conversion) => Some(conversion),
+                        None => return Err(self.error("bad conversion")),
+                    },
+                };
+                Instruction::FormatValue {
+                    conversion,
+                    has_spec: self.byte()? != 0,
+                }
+            }
+            CALL_FUNCTION_EX => Instruction::CallFunctionEx(self.byte()? != 0),
+            MAKE_FUNCTION => Instruction::MakeFunction(self.byte()?),
+            _ => return Err(self.error("unknown opcode")),
+        })
+    }
+}
@@ -0,0 +1,149 @@
+//! Compiles syntax trees to bytecode, like CPython's `compile.c`.
+//!
+//! The future statements of the module are read first, and a symbol table
+//! pass then decides where each name lives. Code is generated one scope at
+//! a time, and each finished code object goes through the peephole passes
+//! of the configuration.
+
+mod code;
+mod codegen;
+mod future;
+mod marshal;
+mod peephole;
+mod symtable;
+
+pub use self::code::{
+    CodeConstant, CodeObject, Instruction, CO_COROUTINE, CO_FUTURE_ANNOTATIONS,
+    CO_FUTURE_BARRY_AS_BDFL, CO_GENERATOR, CO_NESTED, CO_NEWLOCALS, CO_OPTIMIZED, CO_VARARGS,
+    CO_VARKEYWORDS, MAKE_ANNOTATIONS, MAKE_CLOSURE, MAKE_DEFAULTS, MAKE_KWDEFAULTS,
+};
+pub use self::future::{future_features, FutureFeatures};
+pub use self::marshal::{dump_code, load_code, MarshalError};
+pub use self::peephole::{jump_targets, ConstantTuples, DeadCode, JumpThreading, Pass, Peephole};
+
+use std::error::Error;
+use std::fmt;
+
+use ast::Module;
+use tokenizer::Location;
+
+use self::codegen::Codegen;
+use self::symtable::SymbolTable;
+
+/// Options for `compile_with_config`.
+#[derive(Debug, Clone, Default)]
+pub struct CompilerConfig {
+    /// The passes run over each code object once it is generated.
+    pub peephole: Peephole,
+    /// Whether code CPython warns about, such as `x is 1`, is refused as
+    /// an error instead, as CPython does under `-W error::SyntaxWarning`.
+    pub strict: bool,
+}
+
+/// What `compile_mode` compiles a module as, after the `mode` argument of
+/// Python's `compile()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// A module, whose code returns `None`.
+    Exec,
+    /// A single expression statement, whose code returns its value.
+    Eval,
+    /// A statement typed at the interactive prompt, whose expression
+    /// statements print their values.
+    Single,
+}
+
+/// A program the parser accepts but that cannot be compiled, such as
+/// `return` outside a function. CPython raises these as `SyntaxError`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilerError {
+    pub message: String,
+    pub location: Location,
+}
+
+impl CompilerError {
+    pub fn new<S: Into<String>>(message: S, location: Location) -> CompilerError {
+        CompilerError {
+            message: message.into(),
+            location,
+        }
+    }
+}
+
+impl fmt::Display for CompilerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SyntaxError: {} (line {}, column {})",
+            self.message, self.location.line, self.location.column
+        )
+    }
+}
+
+impl Error for CompilerError {}
+
+/// Code that compiles but is most likely a mistake, such as `x is 1`.
+/// CPython warns about these with a `SyntaxWarning`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilerWarning {
+    pub message: String,
+    pub location: Location,
+}
+
+impl CompilerWarning {
+    pub fn new<S: Into<String>>(message: S, location: Location) -> CompilerWarning {
+        CompilerWarning {
+            message: message.into(),
+            location,
+        }
+    }
+}
+
+impl fmt::Display for CompilerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "SyntaxWarning: {} (line {}, column {})",
+            self.message, self.location.line, self.location.column
+        )
+    }
+}
+
+/// Compiles a module with the default passes. Run `optimizer::optimize` on
+/// the module first for the AST-level optimizations.
+pub fn compile(module: &Module, filename: &str) -> Result<CodeObject, CompilerError> {
+    compile_with_config(module, filename, &CompilerConfig::default())
+}
+
+pub fn compile_with_config(
+    module: &Module,
+    filename: &str,
+    config: &CompilerConfig,
+) -> Result<CodeObject, CompilerError> {
+    compile_with_warnings(module, filename, Mode::Exec, config).map(|(code, _)| code)
+}
+
+/// Compiles a module with the default passes, as `mode` asks. In `Eval`
+/// mode the module must hold one expression statement, as a module made
+/// from what `parser::parse_expression` returns does.
+pub fn compile_mode(
+    module: &Module,
+    filename: &str,
+    mode: Mode,
+) -> Result<CodeObject, CompilerError> {
+    compile_with_warnings(module, filename, mode, &CompilerConfig::default()).map(|(code, _)| code)
+}
+
+/// Compiles a module as `mode` asks, with the warnings about it, which are
+/// errors instead if the configuration is strict.
+pub fn compile_with_warnings(
+    module: &Module,
+    filename: &str,
+    mode: Mode,
+    config: &CompilerConfig,
+) -> Result<(CodeObject, Vec<CompilerWarning>), CompilerError> {
+    let _span = stage_span!("compile", filename);
+    let future = future_features(module)?;
+    let table = SymbolTable::build(module)?;
+    Codegen::new(&table, config, filename, future).compile(module, mode)
+}
@@ -0,0 +1,228 @@
+//! Rewrites of the instruction list of finished code, like CPython's
+//! peephole optimizer.
+//!
+//! Each rewrite is a `Pass`. Passes replace the instructions they remove with
+//! `Nop`, so jump targets stay put; `Peephole::run` drops the `Nop`s once all
+//! passes are done.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use ast::Constant;
+
+use super::code::{CodeConstant, CodeObject, Instruction};
+
+/// The largest tuple of constants built at compile time.
+const MAX_CONSTANT_TUPLE: usize = 256;
+
+/// A rewrite of the instructions of a code object.
+pub trait Pass {
+    fn name(&self) -> &str;
+
+    /// Rewrites `code`. Removed instructions should become `Nop` rather than
+    /// being deleted.
+    fn run(&self, code: &mut CodeObject);
+}
+
+/// An ordered list of passes.
+#[derive(Clone)]
+pub struct Peephole {
+    passes: Vec<Arc<dyn Pass + Send + Sync>>,
+}
+
+impl Peephole {
+    /// A pipeline without any passes.
+    pub fn new() -> Peephole {
+        Peephole { passes: Vec::new() }
+    }
+
+    /// Adds a pass to run after the existing ones.
+    pub fn pass<P: Pass + Send + Sync + 'static>(mut self, pass: P) -> Peephole {
+        self.passes.push(Arc::new(pass));
+        self
+    }
+
+    /// The names of the passes, in the order they run.
+    pub fn names(&self) -> Vec<&str> {
+        self.passes.iter().map(|pass| pass.name()).collect()
+    }
+
+    /// Runs every pass over `code` and the code nested in it, then removes
+    /// the `Nop`s they left.
+    pub fn run(&self, code: &mut CodeObject) {
+        for constant in &mut code.constants {
+            if let CodeConstant::Code(ref mut nested) = *constant {
+                self.run(Arc::make_mut(nested));
+            }
+        }
+        for pass in &self.passes {
+            pass.run(code);
+        }
+        code.remove_nops();
+    }
+}
+
+/// The passes CPython applies: constant tuples, then jump threading, then
+/// dead code removal.
+impl Default for Peephole {
+    fn default() -> Peephole {
+        Peephole::new()
+            .pass(ConstantTuples)
+            .pass(JumpThreading)
+            .pass(DeadCode)
+    }
+}
+
+impl fmt::Debug for Peephole {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list().entries(self.names()).finish()
+    }
+}
+
+/// The indices of the instructions some jump lands on.
+pub fn jump_targets(code: &CodeObject) -> HashSet<usize> {
+    code.instructions
+        .iter()
+        .filter_map(|i| i.target())
+        .collect()
+}
+
+/// Turns `LoadConst` of each item followed by `BuildTuple` into a single
+/// `LoadConst` of the tuple.
+pub struct ConstantTuples;
+
+impl Pass for ConstantTuples {
+    fn name(&self) -> &str {
+        "constant-tuples"
+    }
+
+    fn run(&self, code: &mut CodeObject) {
+        let targets = jump_targets(code);
+        for index in 0..code.instructions.len() {
+            let count = match code.instructions[index] {
+                Instruction::BuildTuple(count) if count <= MAX_CONSTANT_TUPLE && count <= index => {
+                    count
+                }
+                _ => continue,
+            };
+            let start = index - count;
+            // A jump into the middle would skip some of the loads.
+            if (start + 1..=index).any(|i| targets.contains(&i)) {
+                continue;
+            }
+            let mut items = Vec::with_capacity(count);
+            for instruction in &code.instructions[start..index] {
+                match *instruction {
+                    Instruction::LoadConst(constant) => match code.constants[constant] {
+                        CodeConstant::Value(ref value) => items.push(value.clone()),
+                        CodeConstant::Code(_) => break,
+                    },
+                    _ => break,
+                }
+            }
+            if items.len() != count {
+                continue;
+            }
+            for instruction in &mut code.instructions[start..index] {
+                *instruction = Instruction::Nop;
+            }
+            let constant = code.add_constant(CodeConstant::Value(Constant::Tuple(items)));
+            code.instructions[index] = Instruction::LoadConst(constant);
+        }
+    }
+}
+
+/// Points jumps that land on another jump at its destination, and removes
+/// jumps to the next instruction.
+pub struct JumpThreading;
+
+impl Pass for JumpThreading {
+    fn name(&self) -> &str {
+        "jump-threading"
+    }
+
+    fn run(&self, code: &mut CodeObject) {
+        let instructions = &mut code.instructions;
+        for index in 0..instructions.len() {
+            let mut hops = 0;
+            while let Some(target) = instructions[index].target() {
+                let threaded = match (instructions[index], instructions[target]) {
+                    (_, Instruction::Jump(next)) if next != target => {
+                        let mut instruction = instructions[index];
+                        *instruction.target_mut().unwrap() = next;
+                        instruction
+                    }
+                    (Instruction::Jump(_), Instruction::ReturnValue) => Instruction::ReturnValue,
+                    (Instruction::JumpIfFalseOrPop(_), Instruction::JumpIfFalseOrPop(next))
+                    | (Instruction::JumpIfTrueOrPop(_), Instruction::JumpIfTrueOrPop(next))
+                        if next != target =>
+                    {
+                        let mut instruction = instructions[index];
+                        *instruction.target_mut().unwrap() = next;
+                        instruction
+                    }
+                    // The value is tested again and popped there.
+                    (Instruction::JumpIfFalseOrPop(_), Instruction::PopJumpIfFalse(next)) => {
+                        Instruction::PopJumpIfFalse(next)
+                    }
+                    (Instruction::JumpIfTrueOrPop(_), Instruction::PopJumpIfTrue(next)) => {
+                        Instruction::PopJumpIfTrue(next)
+                    }
+                    // The test there fails, popping the value.
+                    (Instruction::JumpIfFalseOrPop(_), Instruction::JumpIfTrueOrPop(_))
+                    | (Instruction::JumpIfFalseOrPop(_), Instruction::PopJumpIfTrue(_)) => {
+                        Instruction::PopJumpIfFalse(target + 1)
+                    }
+                    (Instruction::JumpIfTrueOrPop(_), Instruction::JumpIfFalseOrPop(_))
+                    | (Instruction::JumpIfTrueOrPop(_), Instruction::PopJumpIfFalse(_)) => {
+                        Instruction::PopJumpIfTrue(target + 1)
+                    }
+                    _ => break,
+                };
+                instructions[index] = threaded;
+                // A cycle of jumps never settles.
+                hops += 1;
+                if hops > instructions.len() {
+                    break;
+                }
+            }
+            if instructions[index] == Instruction::Jump(index + 1) {
+                instructions[index] = Instruction::Nop;
+            }
+        }
+    }
+}
+
+/// Removes instructions that no path from the start of the code reaches,
+/// such as those after a `return`.
+pub struct DeadCode;
+
+impl Pass for DeadCode {
+    fn name(&self) -> &str {
+        "dead-code"
+    }
+
+    fn run(&self, code: &mut CodeObject) {
+        let instructions = &mut code.instructions;
+        let mut reachable = vec![false; instructions.len()];
+        let mut work = vec![0];
+        while let Some(index) = work.pop() {
+            if index >= instructions.len() || reachable[index] {
+                continue;
+            }
+            reachable[index] = true;
+            if let Some(target) = instructions[index].target() {
+                work.push(target);
+            }
+            if !instructions[index].is_terminal() {
+                work.push(index + 1);
+            }
+        }
+        for (instruction, reachable) in instructions.iter_mut().zip(reachable) {
+            if !reachable {
+                *instruction = Instruction::Nop;
+            }
+        }
+    }
+}
@@ -0,0 +1,1015 @@
+//! Works out, before code generation, which scope each name belongs to, like
+//! CPython's `symtable.c`.
+//!
+//! The tree is walked first to record what each scope does with each name.
+//! Names are then resolved from the module down, since whether a function
+//! closes over a variable depends on the scopes around it, and whether a
+//! variable needs a cell depends on the scopes within it.
+
+use std::collections::{HashMap, HashSet};
+
+use ast::{
+    Arguments, Comprehension, Context, ExceptHandler, Expr, ExprKind, MatchCase, Module, Pattern,
+    PatternKind, Stmt, StmtKind,
+};
+use tokenizer::Location;
+
+use super::CompilerError;
+
+/// How deeply statements, expressions and patterns may nest in a module
+/// being compiled, as in CPython. A level of the parser's nesting is a few
+/// levels of the tree at most, so what parses within the parser's default
+/// limit compiles; a deeper tree, built by hand or parsed with a higher
+/// limit, fails here rather than overflowing the stack.
+const MAX_DEPTH: usize = 3000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    Module,
+    Class,
+    Function,
+}
+
+/// Where a name used in a scope lives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymbolScope {
+    Local,
+    /// Declared `global`.
+    GlobalExplicit,
+    /// Used but never bound in the scope.
+    GlobalImplicit,
+    /// A variable of an enclosing scope, reached through a cell.
+    Free,
+    /// A local variable that nested scopes close over, kept in a cell.
+    Cell,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub symbols: HashMap<String, SymbolScope>,
+    /// The parameters in `co_varnames` order, then the other locals in the
+    /// order they are first bound. Empty outside functions.
+    pub varnames: Vec<String>,
+    /// Whether a function body contains `yield`.
+    pub is_generator: bool,
+    /// Variables that nested scopes close over.
+    pub cellvars: Vec<String>,
+    /// Variables of enclosing scopes that this one closes over.
+    pub freevars: Vec<String>,
+}
+
+impl Scope {
+    pub fn symbol(&self, name: &str) -> SymbolScope {
+        self.symbols
+            .get(name)
+            .cloned()
+            .unwrap_or(SymbolScope::GlobalImplicit)
+    }
+}
+
+/// The scopes of a module, looked up by the node that creates them.
+pub struct SymbolTable {
+    scopes: Vec<Scope>,
+    /// Maps the address of a function, class, lambda or comprehension node
+    /// to its scope.
+    nodes: HashMap<usize, usize>,
+}
+
+impl SymbolTable {
+    pub fn build(module: &Module) -> Result<SymbolTable, CompilerError> {
+        let mut builder = Builder {
+            table: SymbolTable {
+                scopes: Vec::new(),
+                nodes: HashMap::new(),
+            },
+            stack: Vec::new(),
+            walked: HashMap::new(),
+            depth: 0,
+        };
+        builder.enter(ScopeKind::Module, 0);
+        builder.stmts(&module.body)?;
+        builder.leave();
+        builder.analyze(0, &HashSet::new())?;
+        Ok(builder.table)
+    }
+
+    pub fn module(&self) -> &Scope {
+        &self.scopes[0]
+    }
+
+    /// The scope of a function or class definition statement.
+    pub fn stmt_scope(&self, stmt: &Stmt) -> &Scope {
+        &self.scopes[self.nodes[&(stmt as *const Stmt as usize)]]
+    }
+
+    /// The scope of a lambda or comprehension.
+    pub fn expr_scope(&self, expr: &Expr) -> &Scope {
+        &self.scopes[self.nodes[&(expr as *const Expr as usize)]]
+    }
+}
+
+/// What a scope does with a name while it is being walked.
+#[derive(Debug, Clone, Copy, Default)]
+struct Usage {
+    bound: bool,
+    parameter: bool,
+    global: bool,
+    nonlocal: bool,
+    used: bool,
+    /// Where the first `global` or `nonlocal` statement naming it is.
+    declared: Option<Location>,
+}
+
+struct Pending {
+    index: usize,
+    usages: HashMap<String, Usage>,
+    /// Bound names in the order they are first seen.
+    order: Vec<String>,
+    /// What the scope's comprehension is called, if it is one, for the
+    /// error for `yield`, which it may not contain.
+    comprehension: Option<&'static str>,
+    /// The names the `for` clauses of a comprehension bind, which
+    /// assignment expressions in it may not rebind.
+    iteration_names: Vec<String>,
+    /// How many comprehension iterables are being walked in the scope,
+    /// which may not contain assignment expressions.
+    iterables: usize,
+    /// The scopes directly inside this one.
+    children: Vec<usize>,
+}
+
+struct Builder {
+    table: SymbolTable,
+    stack: Vec<Pending>,
+    /// Scopes that have been walked, waiting for their names to be
+    /// resolved.
+    walked: HashMap<usize, Pending>,
+    /// How deeply nested the node being walked is.
+    depth: usize,
+}
+
+impl Builder {
+    fn enter(&mut self, kind: ScopeKind, node: usize) {
+        let index = self.table.scopes.len();
+        self.table.scopes.push(Scope {
+            kind,
+            symbols: HashMap::new(),
+            varnames: Vec::new(),
+            is_generator: false,
+            cellvars: Vec::new(),
+            freevars: Vec::new(),
+        });
+        if kind != ScopeKind::Module {
+            self.table.nodes.insert(node, index);
+        }
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children.push(index);
+        }
+        self.stack.push(Pending {
+            index,
+            usages: HashMap::new(),
+            order: Vec::new(),
+            comprehension: None,
+            iteration_names: Vec::new(),
+            iterables: 0,
+            children: Vec::new(),
+        });
+    }
+
+    fn leave(&mut self) {
+        let pending = self.stack.pop().unwrap();
+        self.walked.insert(pending.index, pending);
+    }
+
+    /// Decides where each name used in the scope at `index` lives, as
+    /// CPython's `analyze_block` does, given the variables `bound` by the
+    /// functions around it. Returns the free variables of the scope and of
+    /// the scopes in it that the scope does not bind itself, which the
+    /// enclosing function must provide.
+    fn analyze(
+        &mut self,
+        index: usize,
+        bound: &HashSet<String>,
+    ) -> Result<HashSet<String>, CompilerError> {
+        let pending = self.walked.remove(&index).unwrap();
+        let kind = self.table.scopes[index].kind;
+        let mut bound = bound.clone();
+        let mut locals = HashSet::new();
+        let mut free = HashSet::new();
+        let mut names: Vec<&String> = pending.usages.keys().collect();
+        names.sort();
+        for name in names {
+            let usage = pending.usages[name];
+            let symbol = if usage.global {
+                bound.remove(name);
+                SymbolScope::GlobalExplicit
+            } else if usage.nonlocal {
+                if !bound.contains(name) {
+                    return Err(CompilerError::new(
+                        format!("no binding for nonlocal '{}' found", name),
+                        usage.declared.unwrap_or_default(),
+                    ));
+                }
+                free.insert(name.clone());
+                SymbolScope::Free
+            } else if usage.bound || usage.parameter {
+                locals.insert(name.clone());
+                SymbolScope::Local
+            } else if bound.contains(name) {
+                free.insert(name.clone());
+                SymbolScope::Free
+            } else {
+                SymbolScope::GlobalImplicit
+            };
+            self.table.scopes[index]
+                .symbols
+                .insert(name.clone(), symbol);
+        }
+
+        // The variables of a class body are not visible in its methods,
+        // which see its `__class__` instead, for zero-argument `super()`.
+        let mut child_bound = bound;
+        match kind {
+            ScopeKind::Function => child_bound.extend(locals.iter().cloned()),
+            ScopeKind::Class => {
+                child_bound.insert("__class__".to_string());
+            }
+            ScopeKind::Module => {}
+        }
+        let mut child_free = HashSet::new();
+        for &child in &pending.children {
+            child_free.extend(self.analyze(child, &child_bound)?);
+        }
+
+        let scope = &mut self.table.scopes[index];
+        match kind {
+            ScopeKind::Function => {
+                for name in &locals {
+                    if child_free.remove(name) {
+                        scope.symbols.insert(name.clone(), SymbolScope::Cell);
+                        scope.cellvars.push(name.clone());
+                    }
+                }
+            }
+            ScopeKind::Class => {
+                if child_free.remove("__class__") {
+                    scope.cellvars.push("__class__".to_string());
+                }
+            }
+            ScopeKind::Module => {}
+        }
+        for name in child_free {
+            match scope.symbols.get(&name) {
+                // A class passes on a variable of the enclosing function to
+                // its methods even when it has a variable of the same name.
+                Some(&SymbolScope::Local) if kind == ScopeKind::Class => {
+                    scope.freevars.push(name.clone());
+                }
+                Some(_) => {}
+                None => {
+                    scope.symbols.insert(name.clone(), SymbolScope::Free);
+                }
+            }
+            free.insert(name);
+        }
+        for (name, &symbol) in &scope.symbols {
+            if symbol == SymbolScope::Free {
+                scope.freevars.push(name.clone());
+            }
+        }
+        scope.cellvars.sort();
+        scope.freevars.sort();
+        if kind == ScopeKind::Function {
+            for name in pending.order {
+                if scope.symbols[&name] == SymbolScope::Local && !scope.varnames.contains(&name) {
+                    scope.varnames.push(name);
+                }
+            }
+        }
+        Ok(free)
+    }
+
+    fn current(&mut self) -> &mut Pending {
+        self.stack.last_mut().unwrap()
+    }
+
+    fn scope_kind(&self) -> ScopeKind {
+        self.table.scopes[self.stack.last().unwrap().index].kind
+    }
+
+    fn usage(&mut self, name: &str) -> &mut Usage {
+        self.current().usages.entry(name.to_string()).or_default()
+    }
+
+    fn bind(&mut self, name: &str) {
+        let usage = self.usage(name);
+        let first = !usage.bound && !usage.parameter;
+        usage.bound = true;
+        if first {
+            self.current().order.push(name.to_string());
+        }
+    }
+
+    fn parameter(&mut self, name: &str, location: Location) -> Result<(), CompilerError> {
+        let index = self.stack.last().unwrap().index;
+        if self.table.scopes[index].varnames.iter().any(|n| n == name) {
+            return Err(CompilerError::new(
+                format!("duplicate argument '{}' in function definition", name),
+                location,
+            ));
+        }
+        self.table.scopes[index].varnames.push(name.to_string());
+        self.usage(name).parameter = true;
+        Ok(())
+    }
+
+    fn use_name(&mut self, name: &str) {
+        self.usage(name).used = true;
+    }
+
+    fn stmts(&mut self, body: &[Stmt]) -> Result<(), CompilerError> {
+        for stmt in body {
+            self.stmt(stmt)?;
+        }
+        Ok(())
+    }
+
+    /// Walks a node one level deeper, failing once that is past
+    /// `MAX_DEPTH`.
+    fn deeper<F>(&mut self, location: Location, walk: F) -> Result<(), CompilerError>
+    where
+        F: FnOnce(&mut Builder) -> Result<(), CompilerError>,
+    {
+        if self.depth >= MAX_DEPTH {
+            return Err(CompilerError::new(
+                "maximum recursion depth exceeded during compilation",
+                location,
+            ));
+        }
+        self.depth += 1;
+        let result = walk(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), CompilerError> {
+        self.deeper(stmt.start, |builder| builder.walk_stmt(stmt))
+    }
+
+    fn walk_stmt(&mut self, stmt: &Stmt) -> Result<(), CompilerError> {
+        match stmt.kind {
+            StmtKind::FunctionDef {
+                ref name,
+                ref args,
+                ref body,
+                ref decorator_list,
+                ref returns,
+                ..
+            } => {
+                self.exprs(decorator_list)?;
+                self.argument_defaults(args)?;
+                self.argument_annotations(args)?;
+                if let Some(ref returns) = *returns {
+                    self.expr(returns)?;
+                }
+                self.bind(name);
+                self.enter(ScopeKind::Function, stmt as *const Stmt as usize);
+                self.parameters(args)?;
+                self.stmts(body)?;
+                self.leave();
+            }
+            StmtKind::ClassDef {
+                ref name,
+                ref bases,
+                ref keywords,
+                ref body,
+                ref decorator_list,
+                ..
+            } => {
+                self.exprs(decorator_list)?;
+                self.exprs(bases)?;
+                for keyword in keywords {
+                    self.expr(&keyword.value)?;
+                }
+                self.bind(name);
+                self.enter(ScopeKind::Class, stmt as *const Stmt as usize);
+                self.stmts(body)?;
+                self.leave();
+            }
+            StmtKind::Return(ref value) => self.optional_expr(value)?,
+            StmtKind::Delete(ref targets) => self.exprs(targets)?,
+            StmtKind::Assign {
+                ref targets,
+                ref value,
+                ..
+            } => {
+                self.exprs(targets)?;
+                self.expr(value)?;
+            }
+            // The value is evaluated lazily, in a scope of its own.
+            StmtKind::TypeAlias { ref name, .. } => {
+                if let ExprKind::Name { ref id, .. } = name.kind {
+                    self.bind(id);
+                }
+            }
+            StmtKind::AugAssign {
+                ref target,
+                ref value,
+                ..
+            } => {
+                if let ExprKind::Name { ref id, .. } = target.kind {
+                    self.use_name(id);
+                }
+                self.expr(target)?;
+                self.expr(value)?;
+            }
+            StmtKind::AnnAssign {
+                ref target,
+                ref annotation,
+                ref value,
+                ..
+            } => {
+                self.expr(target)?;
+                self.expr(annotation)?;
+                self.optional_expr(value)?;
+            }
+            StmtKind::For {
+                ref target,
+                ref iter,
+                ref body,
+                ref orelse,
+                ..
+            } => {
+                self.expr(target)?;
+                self.expr(iter)?;
+                self.stmts(body)?;
+                self.stmts(orelse)?;
+            }
+            StmtKind::While {
+                ref test,
+                ref body,
+                ref orelse,
+            }
+            | StmtKind::If {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                self.expr(test)?;
+                self.stmts(body)?;
+                self.stmts(orelse)?;
+            }
+            StmtKind::With {
+                ref items,
+                ref body,
+                ..
+            } => {
+                for item in items {
+                    self.expr(&item.context_expr)?;
+                    self.optional_expr(&item.optional_vars)?;
+                }
+                self.stmts(body)?;
+            }
+            StmtKind::Match {
+                ref subject,
+                ref cases,
+            } => {
+                self.expr(subject)?;
+                for case in cases {
+                    self.match_case(case)?;
+                }
+            }
+            StmtKind::Raise { ref exc, ref cause } => {
+                self.optional_expr(exc)?;
+                self.optional_expr(cause)?;
+            }
+            StmtKind::Try {
+                ref body,
+                ref handlers,
+                ref orelse,
+                ref finalbody,
+                ..
+            } => {
+                self.stmts(body)?;
+                for handler in handlers {
+                    self.handler(handler)?;
+                }
+                self.stmts(orelse)?;
+                self.stmts(finalbody)?;
+            }
+            StmtKind::Assert { ref test, ref msg } => {
+                self.expr(test)?;
+                self.optional_expr(msg)?;
+            }
+            StmtKind::Import(ref names) => {
+                for alias in names {
+                    let name = alias
+                        .asname
+                        .as_ref()
+                        .unwrap_or(&alias.name)
+                        .split('.')
+                        .next()
+                        .unwrap();
+                    self.bind(name);
+                }
+            }
+            StmtKind::ImportFrom { ref names, .. } => {
+                for alias in names {
+                    if alias.name == "*" {
+                        if self.scope_kind() != ScopeKind::Module {
+                            return Err(CompilerError::new(
+                                "import * only allowed at module level",
+                                stmt.start,
+                            ));
+                        }
+                        continue;
+                    }
+                    self.bind(alias.asname.as_ref().unwrap_or(&alias.name));
+                }
+            }
+            StmtKind::Global(ref names) => {
+                for name in names {
+                    let usage = *self.usage(name);
+                    let problem = if usage.parameter {
+                        Some("is parameter and global")
+                    } else if usage.bound {
+                        Some("is assigned to before global declaration")
+                    } else if usage.used {
+                        Some("is used prior to global declaration")
+                    } else {
+                        None
+                    };
+                    if let Some(problem) = problem {
+                        return Err(CompilerError::new(
+                            format!("name '{}' {}", name, problem),
+                            stmt.start,
+                        ));
+                    }
+                    self.declare(name, stmt.start)?;
+                    self.usage(name).global = true;
+                }
+            }
+            StmtKind::Nonlocal(ref names) => {
+                if self.scope_kind() == ScopeKind::Module {
+                    return Err(CompilerError::new(
+                        "nonlocal declaration not allowed at module level",
+                        stmt.start,
+                    ));
+                }
+                for name in names {
+                    let usage = *self.usage(name);
+                    let problem = if usage.parameter {
+                        Some("is parameter and nonlocal")
+                    } else if usage.bound {
+                        Some("is assigned to before nonlocal declaration")
+                    } else if usage.used {
+                        Some("is used prior to nonlocal declaration")
+                    } else {
+                        None
+                    };
+                    if let Some(problem) = problem {
+                        return Err(CompilerError::new(
+                            format!("name '{}' {}", name, problem),
+                            stmt.start,
+                        ));
+                    }
+                    self.declare(name, stmt.start)?;
+                    self.usage(name).nonlocal = true;
+                }
+            }
+            StmtKind::Expr(ref value) => self.expr(value)?,
+            StmtKind::Pass | StmtKind::Break | StmtKind::Continue | StmtKind::Error => {}
+        }
+        Ok(())
+    }
+
+    /// Records a `global` or `nonlocal` statement at `location` naming
+    /// `name`, which may not be both.
+    fn declare(&mut self, name: &str, location: Location) -> Result<(), CompilerError> {
+        let usage = self.usage(name);
+        match usage.declared {
+            Some(first) if usage.global || usage.nonlocal => Err(CompilerError::new(
+                format!("name '{}' is nonlocal and global", name),
+                first,
+            )),
+            _ => {
+                usage.declared = Some(location);
+                Ok(())
+            }
+        }
+    }
+
+    fn handler(&mut self, handler: &ExceptHandler) -> Result<(), CompilerError> {
+        self.optional_expr(&handler.type_)?;
+        if let Some(ref name) = handler.name {
+            self.bind(name);
+        }
+        self.stmts(&handler.body)
+    }
+
+    fn match_case(&mut self, case: &MatchCase) -> Result<(), CompilerError> {
+        self.pattern(&case.pattern)?;
+        self.optional_expr(&case.guard)?;
+        self.stmts(&case.body)
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) -> Result<(), CompilerError> {
+        self.deeper(pattern.start, |builder| builder.walk_pattern(pattern))
+    }
+
+    fn walk_pattern(&mut self, pattern: &Pattern) -> Result<(), CompilerError> {
+        match pattern.kind {
+            PatternKind::MatchValue(ref value) => self.expr(value)?,
+            PatternKind::MatchSingleton(_) => {}
+            PatternKind::MatchSequence(ref patterns) | PatternKind::MatchOr(ref patterns) => {
+                for pattern in patterns {
+                    self.pattern(pattern)?;
+                }
+            }
+            PatternKind::MatchMapping {
+                ref keys,
+                ref patterns,
+                ref rest,
+            } => {
+                self.exprs(keys)?;
+                for pattern in patterns {
+                    self.pattern(pattern)?;
+                }
+                if let Some(ref rest) = *rest {
+                    self.bind(rest);
+                }
+            }
+            PatternKind::MatchClass {
+                ref cls,
+                ref patterns,
+                ref kwd_patterns,
+                ..
+            } => {
+                self.expr(cls)?;
+                for pattern in patterns.iter().chain(kwd_patterns) {
+                    self.pattern(pattern)?;
+                }
+            }
+            PatternKind::MatchStar(ref name) => {
+                if let Some(ref name) = *name {
+                    self.bind(name);
+                }
+            }
+            PatternKind::MatchAs {
+                ref pattern,
+                ref name,
+            } => {
+                if let Some(ref pattern) = *pattern {
+                    self.pattern(pattern)?;
+                }
+                if let Some(ref name) = *name {
+                    self.bind(name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn argument_defaults(&mut self, args: &Arguments) -> Result<(), CompilerError> {
+        self.exprs(&args.defaults)?;
+        for default in &args.kw_defaults {
+            self.optional_expr(default)?;
+        }
+        Ok(())
+    }
+
+    fn argument_annotations(&mut self, args: &Arguments) -> Result<(), CompilerError> {
+        let all = args
+            .posonlyargs
+            .iter()
+            .chain(&args.args)
+            .chain(&args.vararg)
+            .chain(&args.kwonlyargs)
+            .chain(&args.kwarg);
+        for arg in all {
+            if let Some(ref annotation) = arg.annotation {
+                self.expr(annotation)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Declares parameters in `co_varnames` order: positional, keyword-only,
+    /// then `*args` and `**kwargs`.
+    fn parameters(&mut self, args: &Arguments) -> Result<(), CompilerError> {
+        let all = args
+            .posonlyargs
+            .iter()
+            .chain(&args.args)
+            .chain(&args.kwonlyargs)
+            .chain(&args.vararg)
+            .chain(&args.kwarg);
+        for arg in all {
+            self.parameter(&arg.arg, arg.start)?;
+        }
+        Ok(())
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) -> Result<(), CompilerError> {
+        for expr in exprs {
+            self.expr(expr)?;
+        }
+        Ok(())
+    }
+
+    fn optional_expr(&mut self, expr: &Option<Expr>) -> Result<(), CompilerError> {
+        match *expr {
+            Some(ref expr) => self.expr(expr),
+            None => Ok(()),
+        }
+    }
+
+    fn boxed_expr(&mut self, expr: &Option<Box<Expr>>) -> Result<(), CompilerError> {
+        match *expr {
+            Some(ref expr) => self.expr(expr),
+            None => Ok(()),
+        }
+    }
+
+    /// A comprehension, called `description` in errors, which is a function
+    /// scope of its own. The first iterable is evaluated outside, and
+    /// passed in as the parameter `.0`.
+    fn comprehension(
+        &mut self,
+        expr: &Expr,
+        description: &'static str,
+        elts: &[&Expr],
+        generators: &[Comprehension],
+    ) -> Result<(), CompilerError> {
+        self.iterable(&generators[0].iter)?;
+        self.enter(ScopeKind::Function, expr as *const Expr as usize);
+        self.current().comprehension = Some(description);
+        if description == "generator expression" {
+            let index = self.current().index;
+            self.table.scopes[index].is_generator = true;
+        }
+        self.parameter(".0", expr.start)?;
+        for (position, generator) in generators.iter().enumerate() {
+            self.expr(&generator.target)?;
+            target_names(&generator.target, &mut self.current().iteration_names);
+            if position > 0 {
+                self.iterable(&generator.iter)?;
+            }
+            self.exprs(&generator.ifs)?;
+        }
+        for elt in elts {
+            self.expr(elt)?;
+        }
+        self.leave();
+        Ok(())
+    }
+
+    /// The iterable of a comprehension's `for` clause.
+    fn iterable(&mut self, iter: &Expr) -> Result<(), CompilerError> {
+        self.current().iterables += 1;
+        let result = self.expr(iter);
+        self.current().iterables -= 1;
+        result
+    }
+
+    /// The target of an assignment expression in a comprehension, which
+    /// binds in the scope the comprehension is in, as CPython's
+    /// `symtable_extend_namedexpr_scope` does. The comprehensions reach it
+    /// as a global at module level, and as a nonlocal in a function.
+    fn comprehension_target(&mut self, target: &Expr, id: &str) -> Result<(), CompilerError> {
+        let comprehensions = self
+            .stack
+            .iter()
+            .rev()
+            .take_while(|pending| pending.comprehension.is_some());
+        for pending in comprehensions {
+            if pending.iteration_names.iter().any(|name| name == id) {
+                return Err(CompilerError::new(
+                    format!(
+                        "assignment expression cannot rebind comprehension iteration variable '{}'",
+                        id
+                    ),
+                    target.start,
+                ));
+            }
+        }
+        let outer = self
+            .stack
+            .iter()
+            .rposition(|pending| pending.comprehension.is_none())
+            .unwrap();
+        let global =
+            match self.table.scopes[self.stack[outer].index].kind {
+                ScopeKind::Class => return Err(CompilerError::new(
+                    "assignment expression within a comprehension cannot be used in a class body",
+                    target.start,
+                )),
+                ScopeKind::Function => self.stack[outer]
+                    .usages
+                    .get(id)
+                    .is_some_and(|usage| usage.global),
+                ScopeKind::Module => true,
+            };
+        for pending in &mut self.stack[outer + 1..] {
+            let usage = pending.usages.entry(id.to_string()).or_default();
+            if global {
+                usage.global = true;
+            } else {
+                usage.nonlocal = true;
+            }
+        }
+        let scope = &mut self.stack[outer];
+        let usage = scope.usages.entry(id.to_string()).or_default();
+        if !usage.bound {
+            usage.bound = true;
+            scope.order.push(id.to_string());
+        }
+        Ok(())
+    }
+
+    fn expr(&mut self, expr: &Expr) -> Result<(), CompilerError> {
+        self.deeper(expr.start, |builder| builder.walk_expr(expr))
+    }
+
+    fn walk_expr(&mut self, expr: &Expr) -> Result<(), CompilerError> {
+        match expr.kind {
+            ExprKind::BoolOp { ref values, .. } => self.exprs(values)?,
+            ExprKind::NamedExpr {
+                ref target,
+                ref value,
+            } => {
+                if self.current().iterables > 0 {
+                    return Err(CompilerError::new(
+                        "assignment expression cannot be used in a comprehension iterable expression",
+                        expr.start,
+                    ));
+                }
+                self.expr(value)?;
+                match target.kind {
+                    ExprKind::Name { ref id, .. } if self.current().comprehension.is_some() => {
+                        self.comprehension_target(target, id)?
+                    }
+                    _ => self.expr(target)?,
+                }
+            }
+            ExprKind::BinOp {
+                ref left,
+                ref right,
+                ..
+            } => {
+                self.expr(left)?;
+                self.expr(right)?;
+            }
+            ExprKind::UnaryOp { ref operand, .. } => self.expr(operand)?,
+            ExprKind::Lambda { ref args, ref body } => {
+                self.argument_defaults(args)?;
+                self.enter(ScopeKind::Function, expr as *const Expr as usize);
+                self.parameters(args)?;
+                self.expr(body)?;
+                self.leave();
+            }
+            ExprKind::IfExp {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                self.expr(test)?;
+                self.expr(body)?;
+                self.expr(orelse)?;
+            }
+            ExprKind::Dict {
+                ref keys,
+                ref values,
+            } => {
+                for key in keys {
+                    self.optional_expr(key)?;
+                }
+                self.exprs(values)?;
+            }
+            ExprKind::Set(ref elts) | ExprKind::JoinedStr(ref elts) => self.exprs(elts)?,
+            ExprKind::GeneratorExp {
+                ref elt,
+                ref generators,
+            } => self.comprehension(expr, "generator expression", &[elt], generators)?,
+            ExprKind::ListComp {
+                ref elt,
+                ref generators,
+            } => self.comprehension(expr, "list comprehension", &[elt], generators)?,
+            ExprKind::SetComp {
+                ref elt,
+                ref generators,
+            } => self.comprehension(expr, "set comprehension", &[elt], generators)?,
+            ExprKind::DictComp {
+                ref key,
+                ref value,
+                ref generators,
+            } => self.comprehension(expr, "dict comprehension", &[key, value], generators)?,
+            ExprKind::Await(ref value) => {
+                if self.scope_kind() != ScopeKind::Function {
+                    return Err(CompilerError::new("'await' outside function", expr.start));
+                }
+                self.expr(value)?;
+            }
+            ExprKind::YieldFrom(ref value) => {
+                self.mark_generator(expr)?;
+                self.expr(value)?;
+            }
+            ExprKind::Yield(ref value) => {
+                self.mark_generator(expr)?;
+                self.boxed_expr(value)?;
+            }
+            ExprKind::Compare {
+                ref left,
+                ref comparators,
+                ..
+            } => {
+                self.expr(left)?;
+                self.exprs(comparators)?;
+            }
+            ExprKind::Call {
+                ref func,
+                ref args,
+                ref keywords,
+            } => {
+                self.expr(func)?;
+                self.exprs(args)?;
+                for keyword in keywords {
+                    self.expr(&keyword.value)?;
+                }
+            }
+            ExprKind::FormattedValue {
+                ref value,
+                ref format_spec,
+                ..
+            } => {
+                self.expr(value)?;
+                self.boxed_expr(format_spec)?;
+            }
+            ExprKind::Constant(_) => {}
+            ExprKind::Attribute { ref value, .. } => self.expr(value)?,
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => {
+                self.expr(value)?;
+                self.expr(slice)?;
+            }
+            ExprKind::Starred { ref value, .. } => self.expr(value)?,
+            ExprKind::Name { ref id, ctx } => match ctx {
+                Context::Load => {
+                    // Zero-argument `super()` needs the `__class__` cell.
+                    if id == "super" && self.scope_kind() == ScopeKind::Function {
+                        self.use_name("__class__");
+                    }
+                    self.use_name(id)
+                }
+                Context::Store | Context::Del => self.bind(id),
+            },
+            ExprKind::List { ref elts, .. } | ExprKind::Tuple { ref elts, .. } => {
+                self.exprs(elts)?
+            }
+            ExprKind::Slice {
+                ref lower,
+                ref upper,
+                ref step,
+            } => {
+                self.boxed_expr(lower)?;
+                self.boxed_expr(upper)?;
+                self.boxed_expr(step)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn mark_generator(&mut self, expr: &Expr) -> Result<(), CompilerError> {
+        if let Some(comprehension) = self.current().comprehension {
+            return Err(CompilerError::new(
+                format!("'yield' inside {}", comprehension),
+                expr.start,
+            ));
+        }
+        let index = self.stack.last().unwrap().index;
+        let scope = &mut self.table.scopes[index];
+        if scope.kind != ScopeKind::Function {
+            return Err(CompilerError::new("'yield' outside function", expr.start));
+        }
+        scope.is_generator = true;
+        Ok(())
+    }
+}
+
+/// Adds the names a target binds to `names`.
+fn target_names(target: &Expr, names: &mut Vec<String>) {
+    match target.kind {
+        ExprKind::Name { ref id, .. } => names.push(id.clone()),
+        ExprKind::Tuple { ref elts, .. } | ExprKind::List { ref elts, .. } => {
+            for elt in elts {
+                target_names(elt, names);
+            }
+        }
+        ExprKind::Starred { ref value, .. } => target_names(value, names),
+        _ => {}
+    }
+}
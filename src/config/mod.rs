@@ -0,0 +1,241 @@
+//! Settings for rustpy's tools, read from the `[tool.rustpy]` table of a
+//! project's `pyproject.toml`, so that a project can configure them once
+//! instead of passing flags each time:
+//!
+//! ```text
+//! [tool.rustpy]
+//! target-version = "3.8"
+//! tab-size = 4
+//! exclude = ["build/", "*_pb2.py"]
+//! ignore = ["W301", "E1"]
+//! baseline = "rustpy-baseline.txt"
+//! ```
+
+use std::env;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use parser::ParserConfig;
+use tokenizer::{PythonVersion, TokenizerConfig};
+use toml::{self, Table, Value};
+use walk::Walker;
+
+/// The file the settings are read from.
+pub const FILE_NAME: &str = "pyproject.toml";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    /// The file is not valid TOML.
+    Syntax {
+        line: usize,
+        message: String,
+    },
+    /// A setting is unknown or has a value it cannot have.
+    Invalid {
+        key: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref err) => write!(f, "{}", err),
+            ConfigError::Syntax { line, ref message } => {
+                write!(f, "invalid TOML (line {}): {}", line, message)
+            }
+            ConfigError::Invalid {
+                ref key,
+                ref message,
+            } => write!(f, "tool.rustpy.{}: {}", key, message),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> ConfigError {
+        ConfigError::Io(err)
+    }
+}
+
+/// The settings of a project. Those not given are `None` or empty, for the
+/// tools' own defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Config {
+    /// `target-version`: the version whose grammar code must follow, such
+    /// as `"3.8"` or `"py38"`.
+    pub version: Option<PythonVersion>,
+    /// `tab-size`: the columns a tab in indentation advances to.
+    pub tab_size: Option<usize>,
+    /// `exclude`: globs of paths to leave out, relative to the
+    /// `pyproject.toml`'s directory once `discover` has found it, and to the
+    /// directory being walked otherwise.
+    pub exclude: Vec<String>,
+    /// `ignore`: the codes of diagnostics not to report, or their first
+    /// characters, such as `W` for every warning.
+    pub ignore: Vec<String>,
+    /// `baseline`: the file of accepted diagnostics, as
+    /// `diagnostics::Baseline` saves them, that only new ones are reported
+    /// against. `discover` gives it relative to the `pyproject.toml`'s
+    /// directory, as it is written.
+    pub baseline: Option<PathBuf>,
+    /// The directory of the `pyproject.toml` that `discover` found.
+    pub directory: Option<PathBuf>,
+}
+
+impl Config {
+    /// The settings in the text of a `pyproject.toml`, which are none if
+    /// it has no `[tool.rustpy]` table.
+    pub fn parse(text: &str) -> Result<Config, ConfigError> {
+        let document = toml::parse(text).map_err(|err| syntax_error(text, err))?;
+        match tool_table(&document) {
+            Some(table) => Config::from_table(table),
+            None => Ok(Config::default()),
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, ConfigError> {
+        Config::parse(&fs::read_to_string(path)?)
+    }
+
+    /// The settings for the files in `directory`: those of the nearest
+    /// `pyproject.toml` in it or a directory above it with a
+    /// `[tool.rustpy]` table, and the path of that file.
+    pub fn discover<P: AsRef<Path>>(
+        directory: P,
+    ) -> Result<Option<(PathBuf, Config)>, ConfigError> {
+        let directory = directory.as_ref();
+        let absolute = if directory.is_absolute() {
+            directory.to_path_buf()
+        } else {
+            env::current_dir()?.join(directory)
+        };
+        for ancestor in absolute.ancestors() {
+            let path = ancestor.join(FILE_NAME);
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err.into()),
+            };
+            let document = toml::parse(&text).map_err(|err| syntax_error(&text, err))?;
+            if let Some(table) = tool_table(&document) {
+                let mut config = Config::from_table(table)?;
+                config.baseline = config.baseline.map(|baseline| ancestor.join(baseline));
+                config.directory = Some(ancestor.to_path_buf());
+                return Ok(Some((path, config)));
+            }
+        }
+        Ok(None)
+    }
+
+    fn from_table(table: &Table) -> Result<Config, ConfigError> {
+        let mut config = Config::default();
+        for (key, value) in table {
+            let invalid = |message: String| ConfigError::Invalid {
+                key: key.clone(),
+                message,
+            };
+            match key.as_str() {
+                "target-version" => {
+                    let text = string(value).map_err(invalid)?;
+                    config.version = Some(text.parse().map_err(invalid)?);
+                }
+                "tab-size" => match (value.as_integer(), value) {
+                    (Some(size), _) if size > 0 => config.tab_size = Some(size as usize),
+                    (Some(_), _) => return Err(invalid("must be at least 1".into())),
+                    (None, &Value::Integer(_)) => return Err(invalid("is too large".into())),
+                    (None, _) => return Err(invalid(expected("an integer", value))),
+                },
+                "exclude" => config.exclude = strings(value).map_err(invalid)?,
+                "ignore" => config.ignore = strings(value).map_err(invalid)?,
+                "baseline" => config.baseline = Some(string(value).map_err(invalid)?.into()),
+                _ => return Err(invalid("unknown setting".into())),
+            }
+        }
+        Ok(config)
+    }
+
+    /// Whether diagnostics with `code` are not to be reported.
+    pub fn ignores(&self, code: &str) -> bool {
+        self.ignore.iter().any(|prefix| code.starts_with(&**prefix))
+    }
+
+    /// The options to tokenize with.
+    pub fn tokenizer_config(&self) -> TokenizerConfig {
+        TokenizerConfig {
+            version: self.version.unwrap_or(PythonVersion::LATEST),
+            tab_size: self.tab_size,
+            ..TokenizerConfig::default()
+        }
+    }
+
+    /// The options to parse with.
+    pub fn parser_config(&self) -> ParserConfig {
+        ParserConfig {
+            version: self.version.unwrap_or(PythonVersion::LATEST),
+            tab_size: self.tab_size,
+            ..ParserConfig::default()
+        }
+    }
+
+    /// `walker` with the excluded paths left out.
+    pub fn exclude_from(&self, walker: Walker) -> Walker {
+        self.exclude
+            .iter()
+            .fold(walker, |walker, pattern| match self.directory {
+                Some(ref directory) => walker.exclude_under(directory, pattern),
+                None => walker.exclude(pattern),
+            })
+    }
+}
+
+/// The error for a document that is not valid TOML, on its last line if
+/// the error is at the end of the document.
+fn syntax_error(text: &str, err: toml::Error) -> ConfigError {
+    let line = match err.location {
+        Some((line, _)) => line,
+        None => text.lines().count().max(1),
+    };
+    ConfigError::Syntax {
+        line,
+        message: err.message,
+    }
+}
+
+/// The `[tool.rustpy]` table of a document, if it has one.
+fn tool_table(document: &Table) -> Option<&Table> {
+    match document.get("tool")? {
+        Value::Table(ref tool) => match tool.get("rustpy")? {
+            Value::Table(ref table) => Some(table),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn expected(what: &str, value: &Value) -> String {
+    format!("expected {}, not {}", what, value.type_name())
+}
+
+fn string(value: &Value) -> Result<&str, String> {
+    match *value {
+        Value::String(ref text) => Ok(text),
+        _ => Err(expected("a string", value)),
+    }
+}
+
+fn strings(value: &Value) -> Result<Vec<String>, String> {
+    match *value {
+        Value::Array(ref values) => values
+            .iter()
+            .map(|value| string(value).map(str::to_string))
+            .collect(),
+        _ => Err(expected("an array of strings", value)),
+    }
+}
@@ -0,0 +1,1119 @@
+//! The bundled cases, with the tokens, verdicts and output of CPython
+//! 3.11 for their sources. Sources and expectations are raw strings, so
+//! that they read as they would in a file.
+
+use super::{Case, Check};
+
+pub(super) const CASES: &[Case] = &[
+    Case {
+        feature: "literals",
+        name: "numbers",
+        source: r"x = 0x1F + 1_000 + 3.5e-2 + 2j
+",
+        check: Check::Tokens(
+            r"NAME 'x' 1,0-1,1
+OP '=' 1,2-1,3
+NUMBER '0x1F' 1,4-1,8
+OP '+' 1,9-1,10
+NUMBER '1_000' 1,11-1,16
+OP '+' 1,17-1,18
+NUMBER '3.5e-2' 1,19-1,25
+OP '+' 1,26-1,27
+NUMBER '2j' 1,28-1,30
+NEWLINE 1,30-1,31
+ENDMARKER 2,0-2,0
+",
+        ),
+    },
+    Case {
+        feature: "literals",
+        name: "strings",
+        source: r#"s = r'a\b' + b"c" + '''d'''
+"#,
+        check: Check::Tokens(
+            r#"NAME 's' 1,0-1,1
+OP '=' 1,2-1,3
+STRING "r'a\\b'" 1,4-1,10
+OP '+' 1,11-1,12
+STRING 'b"c"' 1,13-1,17
+OP '+' 1,18-1,19
+STRING "'''d'''" 1,20-1,27
+NEWLINE 1,27-1,28
+ENDMARKER 2,0-2,0
+"#,
+        ),
+    },
+    Case {
+        feature: "literals",
+        name: "numbers",
+        source: r"print(0x1F, 0o17, 0b101, 1_000, 1e3, 2.5j)
+",
+        check: Check::Output(
+            r"31 15 5 1000 1000.0 2.5j
+",
+        ),
+    },
+    Case {
+        feature: "literals",
+        name: "strings",
+        source: r#"print('a' 'b', "\x41\u00e9", r'\n', len('''x
+y'''))
+"#,
+        check: Check::Output(
+            r"ab Aé \n 3
+",
+        ),
+    },
+    Case {
+        feature: "literals",
+        name: "named escapes",
+        source: r#"print(ascii("\N{EM DASH}\N{bullet}\N{LF}"), ascii(f"\N{HANGUL SYLLABLE GAG}{1}"))
+"#,
+        check: Check::Output(
+            r"'\u2014\u2022\n' '\uac011'
+",
+        ),
+    },
+    Case {
+        feature: "literals",
+        name: "bytes",
+        source: r"b = b'ab\x00'
+print(b, len(b), b[0], list(b))
+",
+        check: Check::Output(
+            r"b'ab\x00' 3 97 [97, 98, 0]
+",
+        ),
+    },
+    Case {
+        feature: "literals",
+        name: "big integers",
+        source: r"print(2 ** 100)
+",
+        check: Check::Output(
+            r"1267650600228229401496703205376
+",
+        ),
+    },
+    Case {
+        feature: "literals",
+        name: "containers",
+        source: r"print([1, 2], (3,), {4: 5}, {6}, ())
+",
+        check: Check::Output(
+            r"[1, 2] (3,) {4: 5} {6} ()
+",
+        ),
+    },
+    Case {
+        feature: "f-strings",
+        name: "tokens",
+        source: r"f'{x!r:>{w}}'
+",
+        check: Check::Tokens(
+            r#"STRING "f'{x!r:>{w}}'" 1,0-1,13
+NEWLINE 1,13-1,14
+ENDMARKER 2,0-2,0
+"#,
+        ),
+    },
+    Case {
+        feature: "f-strings",
+        name: "formatting",
+        source: r#"x = 3.14159
+w = 8
+print(f'{x:.2f}|{x!r}|{"hi":>{w}}|{x=}')
+"#,
+        check: Check::Output(
+            r"3.14|3.14159|      hi|x=3.14159
+",
+        ),
+    },
+    Case {
+        feature: "f-strings",
+        name: "nested quotes",
+        source: r#"f'{"a"}{1 + 2}'
+"#,
+        check: Check::Accepts,
+    },
+    Case {
+        feature: "operators",
+        name: "arithmetic",
+        source: r"print(7 // 2, -7 // 2, 7 % -3, 2 ** -1, 7 / 2, divmod(-7, 2))
+",
+        check: Check::Output(
+            r"3 -4 -2 0.5 3.5 (-4, 1)
+",
+        ),
+    },
+    Case {
+        feature: "operators",
+        name: "bitwise",
+        source: r"print(6 & 3, 6 | 3, 6 ^ 3, ~6, 1 << 10, -16 >> 2)
+",
+        check: Check::Output(
+            r"2 7 5 -7 1024 -4
+",
+        ),
+    },
+    Case {
+        feature: "operators",
+        name: "comparison chains",
+        source: r"x = 5
+print(1 < x <= 5, 1 < x > 9, 'a' < 'b' != 'c')
+",
+        check: Check::Output(
+            r"True False True
+",
+        ),
+    },
+    Case {
+        feature: "operators",
+        name: "boolean",
+        source: r"print(0 or 'x', 1 and 2, not [], None or 0)
+",
+        check: Check::Output(
+            r"x 2 True 0
+",
+        ),
+    },
+    Case {
+        feature: "operators",
+        name: "matrix multiply",
+        source: r"a @ b
+a @= b
+",
+        check: Check::Accepts,
+    },
+    Case {
+        feature: "operators",
+        name: "tokens",
+        source: r"a **= b // c -> d := e != f
+",
+        check: Check::Tokens(
+            r"NAME 'a' 1,0-1,1
+OP '**=' 1,2-1,5
+NAME 'b' 1,6-1,7
+OP '//' 1,8-1,10
+NAME 'c' 1,11-1,12
+OP '->' 1,13-1,15
+NAME 'd' 1,16-1,17
+OP ':=' 1,18-1,20
+NAME 'e' 1,21-1,22
+OP '!=' 1,23-1,25
+NAME 'f' 1,26-1,27
+NEWLINE 1,27-1,28
+ENDMARKER 2,0-2,0
+",
+        ),
+    },
+    Case {
+        feature: "assignment",
+        name: "unpacking",
+        source: r"a, (b, *c), d = 1, (2, 3, 4), 5
+print(a, b, c, d)
+",
+        check: Check::Output(
+            r"1 2 [3, 4] 5
+",
+        ),
+    },
+    Case {
+        feature: "assignment",
+        name: "augmented",
+        source: r"x = [1]
+x += [2]
+n = 10
+n -= 3
+n **= 2
+print(x, n)
+",
+        check: Check::Output(
+            r"[1, 2] 49
+",
+        ),
+    },
+    Case {
+        feature: "assignment",
+        name: "walrus",
+        source: r"if (n := len('abc')) > 2:
+    print(n)
+print([y for x in range(3) if (y := x * 2)])
+",
+        check: Check::Output(
+            r"3
+[2, 4]
+",
+        ),
+    },
+    Case {
+        feature: "assignment",
+        name: "chained",
+        source: r"a = b = [0]
+b.append(1)
+print(a, a is b)
+",
+        check: Check::Output(
+            r"[0, 1] True
+",
+        ),
+    },
+    Case {
+        feature: "assignment",
+        name: "annotated",
+        source: r"x: int = 1
+y: list
+print(x, __annotations__)
+",
+        check: Check::Output(
+            r"1 {'x': <class 'int'>, 'y': <class 'list'>}
+",
+        ),
+    },
+    Case {
+        feature: "assignment",
+        name: "invalid target",
+        source: r"f() = 1
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "assignment",
+        name: "walrus at statement level",
+        source: r"x := 1
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "control flow",
+        name: "loops with else",
+        source: r"for i in range(3):
+    if i == 5:
+        break
+else:
+    print('no break')
+while True:
+    break
+else:
+    print('never')
+",
+        check: Check::Output(
+            r"no break
+",
+        ),
+    },
+    Case {
+        feature: "control flow",
+        name: "continue",
+        source: r"out = []
+for i in range(6):
+    if i % 2:
+        continue
+    out.append(i)
+print(out)
+",
+        check: Check::Output(
+            r"[0, 2, 4]
+",
+        ),
+    },
+    Case {
+        feature: "control flow",
+        name: "conditional expression",
+        source: r"print('yes' if [] else 'no')
+",
+        check: Check::Output(
+            r"no
+",
+        ),
+    },
+    Case {
+        feature: "control flow",
+        name: "break outside loop",
+        source: r"break
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "control flow",
+        name: "indentation tokens",
+        source: r"if x:
+    y
+z
+",
+        check: Check::Tokens(
+            r"NAME 'if' 1,0-1,2
+NAME 'x' 1,3-1,4
+OP ':' 1,4-1,5
+NEWLINE 1,5-1,6
+INDENT 2,0-2,4
+NAME 'y' 2,4-2,5
+NEWLINE 2,5-2,6
+DEDENT 3,0-3,0
+NAME 'z' 3,0-3,1
+NEWLINE 3,1-3,2
+ENDMARKER 4,0-4,0
+",
+        ),
+    },
+    Case {
+        feature: "functions",
+        name: "defaults and keywords",
+        source: r"def f(a, b=2, *args, c, d=4, **kw):
+    return a, b, args, c, d, kw
+print(f(1, c=3))
+print(f(1, 2, 3, c=5, e=6))
+",
+        check: Check::Output(
+            r"(1, 2, (), 3, 4, {})
+(1, 2, (3,), 5, 4, {'e': 6})
+",
+        ),
+    },
+    Case {
+        feature: "functions",
+        name: "positional-only",
+        source: r"def f(a, /, b):
+    return a + b
+print(f(1, b=2))
+try:
+    f(a=1, b=2)
+except TypeError as e:
+    print('TypeError')
+",
+        check: Check::Output(
+            r"3
+TypeError
+",
+        ),
+    },
+    Case {
+        feature: "functions",
+        name: "closures",
+        source: r"def counter():
+    n = 0
+    def inc():
+        nonlocal n
+        n += 1
+        return n
+    return inc
+c = counter()
+c()
+print(c())
+",
+        check: Check::Output(
+            r"2
+",
+        ),
+    },
+    Case {
+        feature: "functions",
+        name: "decorators",
+        source: r"def twice(f):
+    return lambda *a: f(f(*a))
+@twice
+def inc(x):
+    return x + 1
+print(inc(1))
+",
+        check: Check::Output(
+            r"3
+",
+        ),
+    },
+    Case {
+        feature: "functions",
+        name: "lambda",
+        source: r"print(sorted(['bb', 'a', 'ccc'], key=lambda s: -len(s)))
+",
+        check: Check::Output(
+            r"['ccc', 'bb', 'a']
+",
+        ),
+    },
+    Case {
+        feature: "functions",
+        name: "argument unpacking",
+        source: r"def f(*a, **k):
+    return a, k
+print(f(*[1, 2], *(3,), **{'x': 1}, y=2))
+",
+        check: Check::Output(
+            r"((1, 2, 3), {'x': 1, 'y': 2})
+",
+        ),
+    },
+    Case {
+        feature: "functions",
+        name: "return outside function",
+        source: r"return 1
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "functions",
+        name: "duplicate argument",
+        source: r"def f(a, a):
+    pass
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "functions",
+        name: "recursion",
+        source: r"def fib(n):
+    return n if n < 2 else fib(n - 1) + fib(n - 2)
+print(fib(20))
+",
+        check: Check::Output(
+            r"6765
+",
+        ),
+    },
+    Case {
+        feature: "classes",
+        name: "inheritance and super",
+        source: r"class A:
+    def f(self):
+        return 'A'
+class B(A):
+    def f(self):
+        return 'B' + super().f()
+print(B().f(), B.__mro__[1].__name__)
+",
+        check: Check::Output(
+            r"BA A
+",
+        ),
+    },
+    Case {
+        feature: "classes",
+        name: "properties",
+        source: r"class C:
+    def __init__(self):
+        self._x = 1
+    @property
+    def x(self):
+        return self._x
+    @x.setter
+    def x(self, v):
+        self._x = v * 2
+c = C()
+c.x = 5
+print(c.x)
+",
+        check: Check::Output(
+            r"10
+",
+        ),
+    },
+    Case {
+        feature: "classes",
+        name: "dunder methods",
+        source: r"class V:
+    def __init__(self, x):
+        self.x = x
+    def __add__(self, o):
+        return V(self.x + o.x)
+    def __repr__(self):
+        return f'V({self.x})'
+    def __eq__(self, o):
+        return self.x == o.x
+print(V(1) + V(2), V(1) == V(1), len([V(0)]))
+",
+        check: Check::Output(
+            r"V(3) True 1
+",
+        ),
+    },
+    Case {
+        feature: "classes",
+        name: "class and static methods",
+        source: r"class C:
+    n = 3
+    @classmethod
+    def c(cls):
+        return cls.n
+    @staticmethod
+    def s(x):
+        return x * 2
+print(C.c(), C.s(4), C().c())
+",
+        check: Check::Output(
+            r"3 8 3
+",
+        ),
+    },
+    Case {
+        feature: "classes",
+        name: "metaclass keyword",
+        source: r"class C(B, metaclass=M):
+    pass
+",
+        check: Check::Accepts,
+    },
+    Case {
+        feature: "classes",
+        name: "dataclasses",
+        source: r"from dataclasses import dataclass
+@dataclass
+class P:
+    x: int
+    y: int = 0
+print(P(1))
+",
+        check: Check::Output(
+            r"P(x=1, y=0)
+",
+        ),
+    },
+    Case {
+        feature: "exceptions",
+        name: "try statement",
+        source: r"def f(x):
+    try:
+        r = 1 / x
+    except ZeroDivisionError as e:
+        return str(e)
+    else:
+        return r
+    finally:
+        print('finally')
+print(f(0))
+print(f(2))
+",
+        check: Check::Output(
+            r"finally
+division by zero
+finally
+0.5
+",
+        ),
+    },
+    Case {
+        feature: "exceptions",
+        name: "raise from",
+        source: r"try:
+    try:
+        {}['k']
+    except KeyError as e:
+        raise ValueError('bad') from e
+except ValueError as e:
+    print(repr(e), repr(e.__cause__))
+",
+        check: Check::Output(
+            r"ValueError('bad') KeyError('k')
+",
+        ),
+    },
+    Case {
+        feature: "exceptions",
+        name: "custom exceptions",
+        source: r"class E(Exception):
+    pass
+try:
+    raise E('x', 1)
+except Exception as e:
+    print(type(e).__name__, e.args)
+",
+        check: Check::Output(
+            r"E ('x', 1)
+",
+        ),
+    },
+    Case {
+        feature: "exceptions",
+        name: "assert",
+        source: r"try:
+    assert 1 == 2, 'message'
+except AssertionError as e:
+    print(e)
+",
+        check: Check::Output(
+            r"message
+",
+        ),
+    },
+    Case {
+        feature: "exceptions",
+        name: "exception groups",
+        source: r"try:
+    pass
+except* ValueError:
+    pass
+",
+        check: Check::Accepts,
+    },
+    Case {
+        feature: "exceptions",
+        name: "exception groups",
+        source: r"try:
+    raise ExceptionGroup('g', [ValueError(1), TypeError(2)])
+except* ValueError as e:
+    print('value', len(e.exceptions))
+except* TypeError as e:
+    print('type', len(e.exceptions))
+",
+        check: Check::Output(
+            r"value 1
+type 1
+",
+        ),
+    },
+    Case {
+        feature: "generators",
+        name: "yield",
+        source: r"def g():
+    yield 1
+    yield 2
+print(list(g()), sum(x * x for x in range(4)))
+",
+        check: Check::Output(
+            r"[1, 2] 14
+",
+        ),
+    },
+    Case {
+        feature: "generators",
+        name: "yield from and send",
+        source: r"def inner():
+    x = yield 1
+    return x
+def outer():
+    r = yield from inner()
+    yield r
+g = outer()
+print(next(g), g.send('sent'))
+",
+        check: Check::Output(
+            r"1 sent
+",
+        ),
+    },
+    Case {
+        feature: "generators",
+        name: "generator expression argument",
+        source: r"f(x for x in y)
+",
+        check: Check::Accepts,
+    },
+    Case {
+        feature: "generators",
+        name: "unparenthesized generator",
+        source: r"f(x for x in y, 1)
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "comprehensions",
+        name: "list, dict and set",
+        source: r"print([x * 2 for x in range(3)], {x: x % 2 for x in range(3)}, {x % 2 for x in range(4)})
+",
+        check: Check::Output(
+            r"[0, 2, 4] {0: 0, 1: 1, 2: 0} {0, 1}
+",
+        ),
+    },
+    Case {
+        feature: "comprehensions",
+        name: "nested",
+        source: r"print([(x, y) for x in range(3) for y in range(x) if y])
+",
+        check: Check::Output(
+            r"[(2, 1)]
+",
+        ),
+    },
+    Case {
+        feature: "comprehensions",
+        name: "scope",
+        source: r"x = 'outer'
+[x for x in range(3)]
+print(x)
+",
+        check: Check::Output(
+            r"outer
+",
+        ),
+    },
+    Case {
+        feature: "context managers",
+        name: "with statement",
+        source: r"class M:
+    def __enter__(self):
+        print('enter')
+        return 1
+    def __exit__(self, *exc):
+        print('exit', exc[0].__name__ if exc[0] else None)
+        return True
+with M() as a, M() as b:
+    print(a + b)
+    raise KeyError
+print('after')
+",
+        check: Check::Output(
+            r"enter
+enter
+2
+exit KeyError
+exit None
+after
+",
+        ),
+    },
+    Case {
+        feature: "context managers",
+        name: "parenthesized",
+        source: r"with (open(a) as f, open(b) as g):
+    pass
+",
+        check: Check::Accepts,
+    },
+    Case {
+        feature: "match",
+        name: "literal and capture",
+        source: r"def f(x):
+    match x:
+        case 0:
+            return 'zero'
+        case [a, *rest]:
+            return f'list {a} {rest}'
+        case {'k': v}:
+            return f'dict {v}'
+        case str() as s if s:
+            return 's ' + s
+        case _:
+            return 'other'
+print(f(0), f([1, 2, 3]), f({'k': 9}), f('x'), f(None))
+",
+        check: Check::Output(
+            r"zero list 1 [2, 3] dict 9 s x other
+",
+        ),
+    },
+    Case {
+        feature: "match",
+        name: "class patterns",
+        source: r"class P:
+    __match_args__ = ('x', 'y')
+    def __init__(self, x, y):
+        self.x, self.y = x, y
+match P(1, 2):
+    case P(1, y=b):
+        print('matched', b)
+",
+        check: Check::Output(
+            r"matched 2
+",
+        ),
+    },
+    Case {
+        feature: "match",
+        name: "soft keyword",
+        source: r"match = [1]
+case = 2
+print(match, case)
+",
+        check: Check::Output(
+            r"[1] 2
+",
+        ),
+    },
+    Case {
+        feature: "match",
+        name: "tokens",
+        source: r"match x:
+    case 1 | 2:
+        pass
+",
+        check: Check::Tokens(
+            r"NAME 'match' 1,0-1,5
+NAME 'x' 1,6-1,7
+OP ':' 1,7-1,8
+NEWLINE 1,8-1,9
+INDENT 2,0-2,4
+NAME 'case' 2,4-2,8
+NUMBER '1' 2,9-2,10
+OP '|' 2,11-2,12
+NUMBER '2' 2,13-2,14
+OP ':' 2,14-2,15
+NEWLINE 2,15-2,16
+INDENT 3,0-3,8
+NAME 'pass' 3,8-3,12
+NEWLINE 3,12-3,13
+DEDENT 4,0-4,0
+DEDENT 4,0-4,0
+ENDMARKER 4,0-4,0
+",
+        ),
+    },
+    Case {
+        feature: "async",
+        name: "coroutines",
+        source: r"async def f():
+    await g()
+    async with a as b:
+        pass
+    async for x in y:
+        pass
+    return [x async for x in y]
+",
+        check: Check::Accepts,
+    },
+    Case {
+        feature: "async",
+        name: "await outside async",
+        source: r"def f():
+    await g()
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "async",
+        name: "async with",
+        source: r"class Manager:
+    async def __aenter__(self):
+        print('enter')
+        return 'value'
+    async def __aexit__(self, kind, value, traceback):
+        print('exit', kind.__name__)
+        return True
+async def f():
+    async with Manager() as value:
+        print(value)
+        raise ValueError
+    return 'done'
+try:
+    f().send(None)
+except StopIteration as stop:
+    print(stop.value)
+",
+        check: Check::Output(
+            r"enter
+value
+exit ValueError
+done
+",
+        ),
+    },
+    Case {
+        feature: "scoping",
+        name: "global",
+        source: r"n = 1
+def f():
+    global n
+    n += 1
+f()
+print(n)
+",
+        check: Check::Output(
+            r"2
+",
+        ),
+    },
+    Case {
+        feature: "scoping",
+        name: "nonlocal at module level",
+        source: r"nonlocal x
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "scoping",
+        name: "unbound local",
+        source: r"x = 1
+def f():
+    try:
+        print(x)
+    except UnboundLocalError:
+        print('unbound')
+    x = 2
+f()
+",
+        check: Check::Output(
+            r"unbound
+",
+        ),
+    },
+    Case {
+        feature: "imports",
+        name: "standard modules",
+        source: r"import sys
+from json import dumps as d
+print(d({'a': [1, None]}), type(sys.argv).__name__)
+",
+        check: Check::Output(
+            r#"{"a": [1, null]} list
+"#,
+        ),
+    },
+    Case {
+        feature: "imports",
+        name: "star import in function",
+        source: r"def f():
+    from os import *
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "imports",
+        name: "relative imports",
+        source: r"from . import a
+from ..b import c as d
+",
+        check: Check::Accepts,
+    },
+    Case {
+        feature: "builtins",
+        name: "iteration helpers",
+        source: r"print(list(zip('ab', range(5))), list(enumerate('xy', 1)), list(map(abs, [-1, 2])), list(reversed([1, 2])))
+",
+        check: Check::Output(
+            r"[('a', 0), ('b', 1)] [(1, 'x'), (2, 'y')] [1, 2] [2, 1]
+",
+        ),
+    },
+    Case {
+        feature: "builtins",
+        name: "conversions",
+        source: r"print(int('ff', 16), float('1.5'), str(1.0), bool(''), repr('q'), hex(255), chr(97), ord('a'))
+",
+        check: Check::Output(
+            r"255 1.5 1.0 False 'q' 0xff a 97
+",
+        ),
+    },
+    Case {
+        feature: "builtins",
+        name: "min, max and sorted",
+        source: r"print(min(3, 1, 2), max([1, 5], key=lambda x: -x), sorted('cba', reverse=True))
+",
+        check: Check::Output(
+            r"1 1 ['c', 'b', 'a']
+",
+        ),
+    },
+    Case {
+        feature: "builtins",
+        name: "round",
+        source: r"print(round(2.5), round(3.14159, 2))
+",
+        check: Check::Output(
+            r"2 3.14
+",
+        ),
+    },
+    Case {
+        feature: "builtins",
+        name: "globals",
+        source: r"x = 1
+print('x' in globals())
+",
+        check: Check::Output(
+            r"True
+",
+        ),
+    },
+    Case {
+        feature: "builtins",
+        name: "isinstance",
+        source: r"print(isinstance(1, (str, int)), issubclass(bool, int), callable(len))
+",
+        check: Check::Output(
+            r"True True True
+",
+        ),
+    },
+    Case {
+        feature: "builtins",
+        name: "arithmetic",
+        source: r"print(divmod(-7, 2), pow(3, -1, 7), complex('1+2j').conjugate(), bin(5), oct(-8))
+",
+        check: Check::Output(
+            r"(-4, 1) 5 (1-2j) 0b101 -0o10
+",
+        ),
+    },
+    Case {
+        feature: "builtins",
+        name: "bytes types",
+        source: r"b = bytearray(b'ab')
+b += b'c'
+view = memoryview(b)
+view[0] = 65
+print(b, bytes(view[1:]), [1, 2, 3][slice(1, None)])
+",
+        check: Check::Output(
+            r"bytearray(b'Abc') b'bc' [2, 3]
+",
+        ),
+    },
+    Case {
+        feature: "strings",
+        name: "methods",
+        source: r"s = ' Hello, World '
+print(s.strip().lower(), s.split(','), '-'.join('abc'), s.find('W'), 'ab'.upper().center(6, '*'))
+",
+        check: Check::Output(
+            r"hello, world [' Hello', ' World '] a-b-c 8 **AB**
+",
+        ),
+    },
+    Case {
+        feature: "strings",
+        name: "formatting",
+        source: r"print('%s=%d %.1f' % ('x', 3, 2.25), '{0}{1}{0}'.format('a', 'b'), format(1234567, ','))
+",
+        check: Check::Output(
+            r"x=3 2.2 aba 1,234,567
+",
+        ),
+    },
+    Case {
+        feature: "strings",
+        name: "slicing",
+        source: r"s = 'abcdef'
+print(s[1:4], s[::-1], s[-2:], s[::2])
+",
+        check: Check::Output(
+            r"bcd fedcba ef ace
+",
+        ),
+    },
+    Case {
+        feature: "strings",
+        name: "bytes methods",
+        source: r"b = b' Hello, World '
+print(b.strip().lower(), b.split(b','), b'-'.join([b'a', b'b']), b.find(b'W'), b.replace(b'l', b'L', 2))
+print(b'caf\xc3\xa9'.decode(), b'hi'.hex(), type(b).fromhex('6869'), [x for x in b'ab'], b.count(b'l'), b.startswith(b' H'))
+",
+        check: Check::Output(
+            r"b'hello, world' [b' Hello', b' World '] b'a-b' 8 b' HeLLo, World '
+café 6869 b'hi' [97, 98] 3 True
+",
+        ),
+    },
+    Case {
+        feature: "type hints",
+        name: "annotations",
+        source: r"def f(a: int, *b: str, c: 'x' = 1, **d: list[int]) -> dict[str, int]:
+    pass
+",
+        check: Check::Accepts,
+    },
+    Case {
+        feature: "type hints",
+        name: "type statement",
+        source: r"type Point = tuple[float, float]
+",
+        check: Check::Rejects,
+    },
+    Case {
+        feature: "type hints",
+        name: "generic functions",
+        source: r"def f[T](x: T) -> T:
+    return x
+",
+        check: Check::Rejects,
+    },
+];
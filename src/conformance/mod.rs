@@ -0,0 +1,364 @@
+//! A conformance suite, for judging how much of Python rustpy supports.
+//!
+//! The bundled cases are small programs, each for one language feature and
+//! checked by one of three suites: the tokenizer suite compares rustpy's
+//! tokens with those of CPython's `tokenize`, the grammar suite checks that
+//! rustpy accepts or rejects a source as CPython's `compile` does, and the
+//! execution suite runs a program and compares what it prints with what it
+//! prints under CPython. The expectations were recorded with CPython
+//! `PYTHON_VERSION`, so the suites need no Python to run.
+//!
+//! `run` runs every case and returns a `Report` of what passed, by suite
+//! and by feature, which `rustpy conformance` prints.
+
+mod cases;
+
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
+
+use ast::repr_str;
+use compiler::compile;
+use diff::{unified_diff, DiffHeader};
+use parser::parse;
+use tokenizer::{tokenize, Token, TokenType};
+use vm::{ObjectRef, PyResult, Vm};
+
+/// The version of the report's format, raised when it changes in a way
+/// that would matter to a script reading it.
+pub const REPORT_VERSION: u32 = 1;
+
+/// The version of CPython the expectations of the cases were recorded with.
+pub const PYTHON_VERSION: &str = "3.11";
+
+/// The suites, in the order they are reported.
+pub const SUITES: [Suite; 3] = [Suite::Tokenizer, Suite::Grammar, Suite::Execution];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Suite {
+    Tokenizer,
+    Grammar,
+    Execution,
+}
+
+impl Suite {
+    pub fn name(self) -> &'static str {
+        match self {
+            Suite::Tokenizer => "tokenizer",
+            Suite::Grammar => "grammar",
+            Suite::Execution => "execution",
+        }
+    }
+}
+
+impl fmt::Display for Suite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What CPython made of the source of a case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    /// The significant tokens, one per line, as `dump_tokens` writes them.
+    Tokens(&'static str),
+    /// The source compiles.
+    Accepts,
+    /// The source is a `SyntaxError`, from the parser or the compiler.
+    Rejects,
+    /// Running the source prints this to `sys.stdout`.
+    Output(&'static str),
+}
+
+/// A source that exercises `feature`, and what CPython made of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Case {
+    pub feature: &'static str,
+    pub name: &'static str,
+    pub source: &'static str,
+    pub check: Check,
+}
+
+impl Case {
+    /// The suite that checks the case.
+    pub fn suite(&self) -> Suite {
+        match self.check {
+            Check::Tokens(_) => Suite::Tokenizer,
+            Check::Accepts | Check::Rejects => Suite::Grammar,
+            Check::Output(_) => Suite::Execution,
+        }
+    }
+
+    /// Checks rustpy against the case, returning what went differently if
+    /// anything did. A panic counts as a failure rather than ending the
+    /// run.
+    pub fn run(&self) -> Result<(), String> {
+        match panic::catch_unwind(AssertUnwindSafe(|| self.check())) {
+            Ok(result) => result,
+            Err(payload) => {
+                let message = payload
+                    .downcast_ref::<&str>()
+                    .map(|message| message.to_string())
+                    .or_else(|| payload.downcast_ref::<String>().cloned())
+                    .unwrap_or_default();
+                Err(format!("panicked: {}", message))
+            }
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        match self.check {
+            Check::Tokens(expected) => {
+                let tokens = tokenize(self.source).map_err(|err| err.to_string())?;
+                compare(expected, &dump_tokens(&tokens))
+            }
+            Check::Accepts => {
+                let module = parse(self.source).map_err(|err| err.to_string())?;
+                compile(&module, "<conformance>").map_err(|err| err.to_string())?;
+                Ok(())
+            }
+            Check::Rejects => {
+                let accepted = match parse(self.source) {
+                    Ok(module) => compile(&module, "<conformance>").is_ok(),
+                    Err(_) => false,
+                };
+                if accepted {
+                    Err("accepted a source CPython rejects".to_string())
+                } else {
+                    Ok(())
+                }
+            }
+            Check::Output(expected) => compare(expected, &run_source(self.source)?),
+        }
+    }
+}
+
+/// The bundled cases.
+pub fn cases() -> &'static [Case] {
+    cases::CASES
+}
+
+/// The significant tokens of a source, one per line: the name `tokenize`
+/// gives the type, the repr of the text except for those whose text CPython
+/// and rustpy do not agree on, and the range.
+pub fn dump_tokens(tokens: &[Token]) -> String {
+    let mut out = String::new();
+    for token in tokens {
+        let range = format!("{}-{}", token.start, token.end);
+        match token.kind {
+            TokenType::NewlineLogical
+            | TokenType::NewlineNonLogical
+            | TokenType::Indent
+            | TokenType::Dedent
+            | TokenType::EndMarker => out.push_str(&format!("{} {}\n", token.kind, range)),
+            kind => out.push_str(&format!("{} {} {}\n", kind, repr_str(&token.value), range)),
+        }
+    }
+    out
+}
+
+/// Runs `source` as `__main__` in a new VM, returning what it printed, or
+/// the traceback of the exception it did not handle.
+fn run_source(source: &str) -> Result<String, String> {
+    let mut vm = Vm::new();
+    match capture_output(&mut vm, source) {
+        Ok(output) => Ok(output),
+        Err(error) => Err(vm.format_exception(&error).trim_end().to_string()),
+    }
+}
+
+fn capture_output(vm: &mut Vm, source: &str) -> PyResult<String> {
+    let io = vm.import_module("io")?;
+    let string_io = vm.getattr(&io, "StringIO")?;
+    let buffer = vm.call(&string_io, Default::default())?;
+    let sys = vm.import_module("sys")?;
+    vm.setattr(&sys, "stdout", buffer.clone())?;
+    vm.run_source(source, "<conformance>")?;
+    let getvalue = vm.getattr(&buffer, "getvalue")?;
+    let output: ObjectRef = vm.call(&getvalue, Default::default())?;
+    vm.str(&output)
+}
+
+/// `Ok` if `actual` is `expected`, or else the diff between them.
+fn compare(expected: &str, actual: &str) -> Result<(), String> {
+    if expected == actual {
+        return Ok(());
+    }
+    let expected: Vec<&str> = expected.split_inclusive('\n').collect();
+    let actual: Vec<&str> = actual.split_inclusive('\n').collect();
+    let header = DiffHeader {
+        from_file: "cpython",
+        to_file: "rustpy",
+        ..DiffHeader::default()
+    };
+    let lines = unified_diff(&expected, &actual, &header, 3, "\n");
+    let mut diff = String::new();
+    for line in lines {
+        diff.push_str(&line);
+        if !line.ends_with('\n') {
+            diff.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+    Err(diff.trim_end().to_string())
+}
+
+/// How a case went.
+#[derive(Debug, Clone)]
+pub struct Outcome {
+    pub case: &'static Case,
+    pub failure: Option<String>,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.failure.is_none()
+    }
+}
+
+/// How many of the cases of a suite, a feature or the whole run passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Tally {
+    pub name: &'static str,
+    pub passed: usize,
+    pub total: usize,
+}
+
+impl Tally {
+    fn new(name: &'static str) -> Tally {
+        Tally {
+            name,
+            passed: 0,
+            total: 0,
+        }
+    }
+
+    fn add(&mut self, outcome: &Outcome) {
+        self.total += 1;
+        if outcome.passed() {
+            self.passed += 1;
+        }
+    }
+
+    /// The percentage of the cases that passed, 100 for none.
+    pub fn percent(&self) -> f64 {
+        if self.total == 0 {
+            100.0
+        } else {
+            100.0 * self.passed as f64 / self.total as f64
+        }
+    }
+}
+
+/// The outcomes of a run of the cases.
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    /// The tallies of the suites, in the order of `SUITES`.
+    pub fn suites(&self) -> Vec<Tally> {
+        SUITES
+            .iter()
+            .map(|&suite| {
+                let mut tally = Tally::new(suite.name());
+                for outcome in &self.outcomes {
+                    if outcome.case.suite() == suite {
+                        tally.add(outcome);
+                    }
+                }
+                tally
+            })
+            .collect()
+    }
+
+    /// The tallies of the features, in the order the cases first mention
+    /// them.
+    pub fn features(&self) -> Vec<Tally> {
+        let mut tallies: Vec<Tally> = Vec::new();
+        for outcome in &self.outcomes {
+            let feature = outcome.case.feature;
+            let index = match tallies.iter().position(|tally| tally.name == feature) {
+                Some(index) => index,
+                None => {
+                    tallies.push(Tally::new(feature));
+                    tallies.len() - 1
+                }
+            };
+            tallies[index].add(outcome);
+        }
+        tallies
+    }
+
+    /// The tally of every case.
+    pub fn total(&self) -> Tally {
+        let mut tally = Tally::new("total");
+        for outcome in &self.outcomes {
+            tally.add(outcome);
+        }
+        tally
+    }
+
+    /// The outcomes of the cases that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &Outcome> {
+        self.outcomes.iter().filter(|outcome| !outcome.passed())
+    }
+}
+
+/// The report as `rustpy conformance` prints it: a header with the
+/// versions it is for, then a table of the suites and one of the features.
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "rustpy {} conformance report (format {}), against CPython {}",
+            env!("CARGO_PKG_VERSION"),
+            REPORT_VERSION,
+            PYTHON_VERSION
+        )?;
+        let mut suites = self.suites();
+        suites.push(self.total());
+        write_table(f, "suite", &suites)?;
+        write_table(f, "feature", &self.features())
+    }
+}
+
+fn write_table(f: &mut fmt::Formatter, heading: &str, tallies: &[Tally]) -> fmt::Result {
+    let width = tallies
+        .iter()
+        .map(|tally| tally.name.len())
+        .chain(Some(heading.len()))
+        .max()
+        .unwrap_or(0);
+    writeln!(f)?;
+    writeln!(
+        f,
+        "{:<width$}  passed  total  percent",
+        heading,
+        width = width
+    )?;
+    for tally in tallies {
+        writeln!(
+            f,
+            "{:<width$}  {:>6}  {:>5}  {:>6.1}%",
+            tally.name,
+            tally.passed,
+            tally.total,
+            tally.percent(),
+            width = width
+        )?;
+    }
+    Ok(())
+}
+
+/// Runs every bundled case.
+pub fn run() -> Report {
+    Report {
+        outcomes: cases()
+            .iter()
+            .map(|case| Outcome {
+                case,
+                failure: case.run().err(),
+            })
+            .collect(),
+    }
+}
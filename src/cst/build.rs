@@ -0,0 +1,702 @@
+//! Builds a tree from the AST of a source and its tokens.
+//!
+//! The AST gives each node an extent; the builder turns those into byte
+//! ranges, nests them, and hands every token to the innermost node whose
+//! range holds it. Comments, and the newlines of blank and comment-only
+//! lines, are not tokens of the tree but trivia of the token after them.
+
+use ast::{
+    Arguments, Comprehension, ExceptHandler, Expr, ExprKind, Module, Pattern, PatternKind, Stmt,
+    StmtKind,
+};
+use tokenizer::{self, LineIndex, Location, TokenType};
+
+use super::{Element, Node, Token};
+
+/// A node before it is given its tokens: a byte range and the shapes of
+/// the nodes in it, in source order.
+struct Shape {
+    kind: &'static str,
+    start: usize,
+    end: usize,
+    children: Vec<Shape>,
+}
+
+impl Shape {
+    /// A shape that covers at least `start..end` and its children.
+    fn new(kind: &'static str, start: usize, end: usize, mut children: Vec<Shape>) -> Shape {
+        children.sort_by_key(|child| child.start);
+        let start = children
+            .iter()
+            .map(|child| child.start)
+            .fold(start, usize::min);
+        let end = children.iter().map(|child| child.end).fold(end, usize::max);
+        Shape {
+            kind,
+            start,
+            end,
+            children,
+        }
+    }
+}
+
+/// A significant token: its type and byte range.
+struct Span {
+    kind: TokenType,
+    start: usize,
+    end: usize,
+}
+
+pub(super) struct Builder<'a> {
+    source: &'a str,
+    tokens: Vec<Span>,
+    /// Where each line is, to turn AST locations into byte offsets.
+    lines: LineIndex,
+    /// The next token to hand out.
+    position: usize,
+    /// Where the last token handed out ended.
+    last_end: usize,
+}
+
+impl<'a> Builder<'a> {
+    pub(super) fn new(source: &'a str, tokens: &[tokenizer::Token]) -> Builder<'a> {
+        let mut spans: Vec<Span> = Vec::with_capacity(tokens.len());
+        for token in tokens {
+            let (start, end) = match token.kind {
+                TokenType::Comment | TokenType::NewlineNonLogical => continue,
+                // The same newlines the parser drops.
+                TokenType::NewlineLogical
+                    if spans
+                        .last()
+                        .is_none_or(|last| last.kind == TokenType::NewlineLogical) =>
+                {
+                    continue
+                }
+                // Indentation changes go right after the newline before
+                // them, so the lines up to the statement and its
+                // indentation are the statement's trivia.
+                TokenType::Indent | TokenType::Dedent => {
+                    let end = spans.last().map_or(0, |last| last.end);
+                    (end, end)
+                }
+                TokenType::EndMarker => (source.len(), source.len()),
+                _ => (token.span.start, token.span.end),
+            };
+            spans.push(Span {
+                kind: token.kind,
+                start,
+                end,
+            });
+        }
+        Builder {
+            source,
+            tokens: spans,
+            lines: LineIndex::new(source),
+            position: 0,
+            last_end: 0,
+        }
+    }
+
+    /// The `Module` node of a module.
+    pub(super) fn module(mut self, module: &Module) -> Node {
+        let children = module.body.iter().map(|stmt| self.stmt(stmt)).collect();
+        let shape = Shape::new("Module", 0, self.source.len(), children);
+        self.assemble(&shape, true)
+    }
+
+    /// An `Expression` node holding the expression of an `eval` input. The
+    /// expression takes the parentheses around it.
+    pub(super) fn expression(mut self, expression: &Expr) -> Node {
+        let mut shape = self.expr(expression);
+        let significant = self.tokens.iter().filter(|token| {
+            !matches!(
+                token.kind,
+                TokenType::NewlineLogical
+                    | TokenType::Indent
+                    | TokenType::Dedent
+                    | TokenType::EndMarker
+            )
+        });
+        for token in significant {
+            shape.start = shape.start.min(token.start);
+            shape.end = shape.end.max(token.end);
+        }
+        let shape = Shape::new("Expression", 0, self.source.len(), vec![shape]);
+        self.assemble(&shape, true)
+    }
+
+    /// Gives `shape` and the shapes in it their tokens, in order.
+    fn assemble(&mut self, shape: &Shape, root: bool) -> Node {
+        let mut children = Vec::new();
+        for child in &shape.children {
+            while let Some(token) = self.tokens.get(self.position) {
+                let before = token.start < child.start
+                    || (token.start == token.end && token.start <= child.start);
+                if !before {
+                    break;
+                }
+                children.push(Element::Token(self.take()));
+            }
+            children.push(Element::Node(self.assemble(child, false)));
+        }
+        while let Some(token) = self.tokens.get(self.position) {
+            let trailing = match token.kind {
+                TokenType::Dedent | TokenType::EndMarker => root,
+                _ => root || token.end <= shape.end,
+            };
+            if !trailing {
+                break;
+            }
+            children.push(Element::Token(self.take()));
+        }
+        Node::new(shape.kind, children)
+    }
+
+    fn take(&mut self) -> Token {
+        let token = &self.tokens[self.position];
+        self.position += 1;
+        let leading = &self.source[self.last_end.min(token.start)..token.start];
+        self.last_end = token.end;
+        Token {
+            kind: token.kind,
+            leading: leading.to_string(),
+            text: self.source[token.start..token.end].to_string(),
+        }
+    }
+
+    fn offset(&self, location: Location) -> usize {
+        self.lines.offset(self.source, location)
+    }
+
+    /// The index of the first token that starts at or after `offset`.
+    fn token_at(&self, offset: usize) -> usize {
+        self.tokens.partition_point(|token| token.start < offset)
+    }
+
+    fn is_operator(&self, index: usize, op: &str) -> bool {
+        self.tokens.get(index).is_some_and(|token| {
+            token.kind == TokenType::Operator && &self.source[token.start..token.end] == op
+        })
+    }
+
+    fn stmt(&self, stmt: &Stmt) -> Shape {
+        let mut children = Vec::new();
+        let mut compound = true;
+        let kind = match stmt.kind {
+            StmtKind::FunctionDef {
+                ref args,
+                ref body,
+                ref decorator_list,
+                ref returns,
+                is_async,
+                ..
+            } => {
+                self.exprs(decorator_list, &mut children);
+                self.arguments(args, &mut children);
+                children.extend(returns.as_ref().map(|returns| self.expr(returns)));
+                self.stmts(body, &mut children);
+                if is_async {
+                    "AsyncFunctionDef"
+                } else {
+                    "FunctionDef"
+                }
+            }
+            StmtKind::ClassDef {
+                ref bases,
+                ref keywords,
+                ref body,
+                ref decorator_list,
+                ..
+            } => {
+                self.exprs(decorator_list, &mut children);
+                self.exprs(bases, &mut children);
+                for keyword in keywords {
+                    children.push(self.keyword(keyword));
+                }
+                self.stmts(body, &mut children);
+                "ClassDef"
+            }
+            StmtKind::For {
+                ref target,
+                ref iter,
+                ref body,
+                ref orelse,
+                is_async,
+                ..
+            } => {
+                children.push(self.expr(target));
+                children.push(self.expr(iter));
+                self.stmts(body, &mut children);
+                self.stmts(orelse, &mut children);
+                if is_async {
+                    "AsyncFor"
+                } else {
+                    "For"
+                }
+            }
+            StmtKind::While {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                children.push(self.expr(test));
+                self.stmts(body, &mut children);
+                self.stmts(orelse, &mut children);
+                "While"
+            }
+            StmtKind::If {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                children.push(self.expr(test));
+                self.stmts(body, &mut children);
+                self.stmts(orelse, &mut children);
+                "If"
+            }
+            StmtKind::With {
+                ref items,
+                ref body,
+                is_async,
+                ..
+            } => {
+                for item in items {
+                    let mut vars = vec![self.expr(&item.context_expr)];
+                    vars.extend(item.optional_vars.as_ref().map(|vars| self.expr(vars)));
+                    children.push(Shape::new("withitem", usize::MAX, 0, vars));
+                }
+                self.stmts(body, &mut children);
+                if is_async {
+                    "AsyncWith"
+                } else {
+                    "With"
+                }
+            }
+            StmtKind::Match {
+                ref subject,
+                ref cases,
+            } => {
+                children.push(self.expr(subject));
+                for case in cases {
+                    children.push(self.pattern(&case.pattern));
+                    children.extend(case.guard.as_ref().map(|guard| self.expr(guard)));
+                    self.stmts(&case.body, &mut children);
+                }
+                "Match"
+            }
+            StmtKind::Try {
+                ref body,
+                ref handlers,
+                ref orelse,
+                ref finalbody,
+                is_star,
+            } => {
+                self.stmts(body, &mut children);
+                for handler in handlers {
+                    children.push(self.handler(handler));
+                }
+                self.stmts(orelse, &mut children);
+                self.stmts(finalbody, &mut children);
+                if is_star {
+                    "TryStar"
+                } else {
+                    "Try"
+                }
+            }
+            _ => {
+                compound = false;
+                self.simple_stmt(stmt, &mut children)
+            }
+        };
+        let mut shape = Shape::new(
+            kind,
+            self.offset(stmt.start),
+            self.offset(stmt.end),
+            children,
+        );
+        // Decorators, and parentheses around the first expression, start
+        // before the AST says the statement does.
+        let mut first = self.token_at(shape.start);
+        while first > 0 && (self.is_operator(first - 1, "@") || self.is_operator(first - 1, "(")) {
+            first -= 1;
+            shape.start = self.tokens[first].start;
+        }
+        if !compound {
+            let mut next = self.token_at(shape.end);
+            if self.is_operator(next, ";") {
+                shape.end = self.tokens[next].end;
+                next += 1;
+            }
+            if let Some(token) = self.tokens.get(next) {
+                if token.kind == TokenType::NewlineLogical {
+                    shape.end = token.end;
+                }
+            }
+        }
+        shape
+    }
+
+    fn simple_stmt(&self, stmt: &Stmt, children: &mut Vec<Shape>) -> &'static str {
+        match stmt.kind {
+            StmtKind::Return(ref value) => {
+                children.extend(value.as_ref().map(|value| self.expr(value)));
+                "Return"
+            }
+            StmtKind::Delete(ref targets) => {
+                self.exprs(targets, children);
+                "Delete"
+            }
+            StmtKind::Assign {
+                ref targets,
+                ref value,
+                ..
+            } => {
+                self.exprs(targets, children);
+                children.push(self.expr(value));
+                "Assign"
+            }
+            StmtKind::TypeAlias {
+                ref name,
+                ref value,
+                ..
+            } => {
+                children.push(self.expr(name));
+                children.push(self.expr(value));
+                "TypeAlias"
+            }
+            StmtKind::AugAssign {
+                ref target,
+                ref value,
+                ..
+            } => {
+                children.push(self.expr(target));
+                children.push(self.expr(value));
+                "AugAssign"
+            }
+            StmtKind::AnnAssign {
+                ref target,
+                ref annotation,
+                ref value,
+                ..
+            } => {
+                children.push(self.expr(target));
+                children.push(self.expr(annotation));
+                children.extend(value.as_ref().map(|value| self.expr(value)));
+                "AnnAssign"
+            }
+            StmtKind::Raise { ref exc, ref cause } => {
+                children.extend(exc.as_ref().map(|exc| self.expr(exc)));
+                children.extend(cause.as_ref().map(|cause| self.expr(cause)));
+                "Raise"
+            }
+            StmtKind::Assert { ref test, ref msg } => {
+                children.push(self.expr(test));
+                children.extend(msg.as_ref().map(|msg| self.expr(msg)));
+                "Assert"
+            }
+            StmtKind::Import(ref names) | StmtKind::ImportFrom { ref names, .. } => {
+                for alias in names {
+                    let (start, end) = (self.offset(alias.start), self.offset(alias.end));
+                    children.push(Shape::new("alias", start, end, Vec::new()));
+                }
+                match stmt.kind {
+                    StmtKind::Import(_) => "Import",
+                    _ => "ImportFrom",
+                }
+            }
+            StmtKind::Global(_) => "Global",
+            StmtKind::Nonlocal(_) => "Nonlocal",
+            StmtKind::Expr(ref value) => {
+                children.push(self.expr(value));
+                "Expr"
+            }
+            StmtKind::Pass => "Pass",
+            StmtKind::Break => "Break",
+            StmtKind::Continue => "Continue",
+            _ => unreachable!("compound statement {:?}", stmt.kind),
+        }
+    }
+
+    fn stmts(&self, stmts: &[Stmt], children: &mut Vec<Shape>) {
+        for stmt in stmts {
+            children.push(self.stmt(stmt));
+        }
+    }
+
+    fn handler(&self, handler: &ExceptHandler) -> Shape {
+        let mut children = Vec::new();
+        children.extend(handler.type_.as_ref().map(|type_| self.expr(type_)));
+        self.stmts(&handler.body, &mut children);
+        let (start, end) = (self.offset(handler.start), self.offset(handler.end));
+        Shape::new("ExceptHandler", start, end, children)
+    }
+
+    /// The parameters of a function or lambda, as children of it.
+    fn arguments(&self, args: &Arguments, children: &mut Vec<Shape>) {
+        let params = args
+            .posonlyargs
+            .iter()
+            .chain(&args.args)
+            .chain(&args.vararg)
+            .chain(&args.kwonlyargs)
+            .chain(&args.kwarg);
+        for arg in params {
+            let mut annotation = Vec::new();
+            annotation.extend(
+                arg.annotation
+                    .as_ref()
+                    .map(|annotation| self.expr(annotation)),
+            );
+            let (start, end) = (self.offset(arg.start), self.offset(arg.end));
+            children.push(Shape::new("arg", start, end, annotation));
+        }
+        self.exprs(&args.defaults, children);
+        for default in args.kw_defaults.iter().flatten() {
+            children.push(self.expr(default));
+        }
+    }
+
+    fn keyword(&self, keyword: &::ast::Keyword) -> Shape {
+        let value = self.expr(&keyword.value);
+        let (start, end) = (self.offset(keyword.start), self.offset(keyword.end));
+        Shape::new("keyword", start, end, vec![value])
+    }
+
+    /// The clauses of a comprehension, as children of it.
+    fn generators(&self, generators: &[Comprehension], children: &mut Vec<Shape>) {
+        for generator in generators {
+            children.push(self.expr(&generator.target));
+            children.push(self.expr(&generator.iter));
+            self.exprs(&generator.ifs, children);
+        }
+    }
+
+    fn exprs(&self, exprs: &[Expr], children: &mut Vec<Shape>) {
+        for expr in exprs {
+            children.push(self.expr(expr));
+        }
+    }
+
+    fn expr(&self, expr: &Expr) -> Shape {
+        let mut children = Vec::new();
+        let kind = match expr.kind {
+            ExprKind::BoolOp { ref values, .. } => {
+                self.exprs(values, &mut children);
+                "BoolOp"
+            }
+            ExprKind::NamedExpr {
+                ref target,
+                ref value,
+            } => {
+                children.push(self.expr(target));
+                children.push(self.expr(value));
+                "NamedExpr"
+            }
+            ExprKind::BinOp {
+                ref left,
+                ref right,
+                ..
+            } => {
+                children.push(self.expr(left));
+                children.push(self.expr(right));
+                "BinOp"
+            }
+            ExprKind::UnaryOp { ref operand, .. } => {
+                children.push(self.expr(operand));
+                "UnaryOp"
+            }
+            ExprKind::Lambda { ref args, ref body } => {
+                self.arguments(args, &mut children);
+                children.push(self.expr(body));
+                "Lambda"
+            }
+            ExprKind::IfExp {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                children.push(self.expr(test));
+                children.push(self.expr(body));
+                children.push(self.expr(orelse));
+                "IfExp"
+            }
+            ExprKind::Dict {
+                ref keys,
+                ref values,
+            } => {
+                for key in keys.iter().flatten() {
+                    children.push(self.expr(key));
+                }
+                self.exprs(values, &mut children);
+                "Dict"
+            }
+            ExprKind::Set(ref elts) => {
+                self.exprs(elts, &mut children);
+                "Set"
+            }
+            ExprKind::ListComp {
+                ref elt,
+                ref generators,
+            } => {
+                children.push(self.expr(elt));
+                self.generators(generators, &mut children);
+                "ListComp"
+            }
+            ExprKind::SetComp {
+                ref elt,
+                ref generators,
+            } => {
+                children.push(self.expr(elt));
+                self.generators(generators, &mut children);
+                "SetComp"
+            }
+            ExprKind::DictComp {
+                ref key,
+                ref value,
+                ref generators,
+            } => {
+                children.push(self.expr(key));
+                children.push(self.expr(value));
+                self.generators(generators, &mut children);
+                "DictComp"
+            }
+            ExprKind::GeneratorExp {
+                ref elt,
+                ref generators,
+            } => {
+                children.push(self.expr(elt));
+                self.generators(generators, &mut children);
+                "GeneratorExp"
+            }
+            ExprKind::Await(ref value) => {
+                children.push(self.expr(value));
+                "Await"
+            }
+            ExprKind::Yield(ref value) => {
+                children.extend(value.as_ref().map(|value| self.expr(value)));
+                "Yield"
+            }
+            ExprKind::YieldFrom(ref value) => {
+                children.push(self.expr(value));
+                "YieldFrom"
+            }
+            ExprKind::Compare {
+                ref left,
+                ref comparators,
+                ..
+            } => {
+                children.push(self.expr(left));
+                self.exprs(comparators, &mut children);
+                "Compare"
+            }
+            ExprKind::Call {
+                ref func,
+                ref args,
+                ref keywords,
+            } => {
+                children.push(self.expr(func));
+                self.exprs(args, &mut children);
+                for keyword in keywords {
+                    children.push(self.keyword(keyword));
+                }
+                "Call"
+            }
+            // Only reached through `JoinedStr`, which is a leaf.
+            ExprKind::FormattedValue { .. } => "FormattedValue",
+            // An f-string is a single token, so its parts have no tokens
+            // of their own.
+            ExprKind::JoinedStr(_) => "JoinedStr",
+            ExprKind::Constant(_) => "Constant",
+            ExprKind::Attribute { ref value, .. } => {
+                children.push(self.expr(value));
+                "Attribute"
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => {
+                children.push(self.expr(value));
+                children.push(self.expr(slice));
+                "Subscript"
+            }
+            ExprKind::Starred { ref value, .. } => {
+                children.push(self.expr(value));
+                "Starred"
+            }
+            ExprKind::Name { .. } => "Name",
+            ExprKind::List { ref elts, .. } => {
+                self.exprs(elts, &mut children);
+                "List"
+            }
+            ExprKind::Tuple { ref elts, .. } => {
+                self.exprs(elts, &mut children);
+                "Tuple"
+            }
+            ExprKind::Slice {
+                ref lower,
+                ref upper,
+                ref step,
+            } => {
+                for part in lower.iter().chain(upper).chain(step) {
+                    children.push(self.expr(part));
+                }
+                "Slice"
+            }
+        };
+        let (start, end) = (self.offset(expr.start), self.offset(expr.end));
+        Shape::new(kind, start, end, children)
+    }
+
+    fn pattern(&self, pattern: &Pattern) -> Shape {
+        let mut children = Vec::new();
+        let kind = match pattern.kind {
+            PatternKind::MatchValue(ref value) => {
+                children.push(self.expr(value));
+                "MatchValue"
+            }
+            PatternKind::MatchSingleton(_) => "MatchSingleton",
+            PatternKind::MatchSequence(ref patterns) => {
+                self.patterns(patterns, &mut children);
+                "MatchSequence"
+            }
+            PatternKind::MatchMapping {
+                ref keys,
+                ref patterns,
+                ..
+            } => {
+                self.exprs(keys, &mut children);
+                self.patterns(patterns, &mut children);
+                "MatchMapping"
+            }
+            PatternKind::MatchClass {
+                ref cls,
+                ref patterns,
+                ref kwd_patterns,
+                ..
+            } => {
+                children.push(self.expr(cls));
+                self.patterns(patterns, &mut children);
+                self.patterns(kwd_patterns, &mut children);
+                "MatchClass"
+            }
+            PatternKind::MatchStar(_) => "MatchStar",
+            PatternKind::MatchAs { ref pattern, .. } => {
+                children.extend(pattern.as_ref().map(|pattern| self.pattern(pattern)));
+                "MatchAs"
+            }
+            PatternKind::MatchOr(ref patterns) => {
+                self.patterns(patterns, &mut children);
+                "MatchOr"
+            }
+        };
+        let (start, end) = (self.offset(pattern.start), self.offset(pattern.end));
+        Shape::new(kind, start, end, children)
+    }
+
+    fn patterns(&self, patterns: &[Pattern], children: &mut Vec<Shape>) {
+        for pattern in patterns {
+            children.push(self.pattern(pattern));
+        }
+    }
+}
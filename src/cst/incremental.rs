@@ -0,0 +1,389 @@
+//! Reparsing only the statements an edit touches.
+//!
+//! `Tree::edit` looks for the innermost block whose statements hold the
+//! edit and parses just those statements again, those of an indented block
+//! under an `if 1:` header so they parse on their own. Statements start at
+//! the start of a line and end with its newline, with the tokenizer outside
+//! any brackets and strings, so a run of whole statements that parses on
+//! its own parses the same in place, as long as it keeps the indentation of
+//! its block and leaves the same blocks open at its end. When the new text
+//! does not, because the edit opened a bracket or changed the indentation
+//! of a block, the enclosing block is tried, and in the end the whole
+//! source is parsed again.
+
+use parser::ParseError;
+use tokenizer::{Span, TextEdit, TokenType};
+
+use super::{Element, Node, Token, Tree};
+
+/// The header that lets the statements of an indented block parse on
+/// their own.
+const HEADER: &str = "if 1:\n";
+
+const STATEMENTS: &[&str] = &[
+    "FunctionDef",
+    "AsyncFunctionDef",
+    "ClassDef",
+    "Return",
+    "Delete",
+    "Assign",
+    "TypeAlias",
+    "AugAssign",
+    "AnnAssign",
+    "For",
+    "AsyncFor",
+    "While",
+    "If",
+    "With",
+    "AsyncWith",
+    "Match",
+    "Raise",
+    "Try",
+    "TryStar",
+    "Assert",
+    "Import",
+    "ImportFrom",
+    "Global",
+    "Nonlocal",
+    "Expr",
+    "Pass",
+    "Break",
+    "Continue",
+];
+
+impl Tree {
+    /// Makes `edit` to the source of the tree, parsing again only the
+    /// statements it touches, so the tree becomes the one `Tree::parse`
+    /// gives for the edited source. When the edited source does not parse,
+    /// the tree is left as it was. Panics if the range of the edit is not
+    /// within the source or does not fall on character boundaries.
+    pub fn edit(&mut self, edit: &TextEdit) -> Result<(), ParseError> {
+        let _span = stage_span!("reparse");
+        if !edit_block(&mut self.root, 0, edit) {
+            let source = edit.apply(&self.render());
+            *self = Tree::parse(&source)?;
+        }
+        Ok(())
+    }
+}
+
+/// Makes `edit` to `node`, which starts at byte `start`, by parsing again
+/// the statements it touches in one of the node's blocks, or in a block
+/// nested in one of them. Returns false, leaving the node as it was, when
+/// the edit reaches outside the statements of a single block or their new
+/// text does not parse as statements of it.
+fn edit_block(node: &mut Node, start: usize, edit: &TextEdit) -> bool {
+    let root = node.kind == "Module";
+    let mut offsets = Vec::with_capacity(node.children.len() + 1);
+    let mut offset = start;
+    for child in &node.children {
+        offsets.push(offset);
+        offset += element_len(child);
+    }
+    offsets.push(offset);
+    let range = edit.range;
+    let children = &mut node.children;
+    let contains = |index: usize| offsets[index] <= range.start && range.end <= offsets[index + 1];
+
+    // The innermost block holding the edit is tried first.
+    let holders: Vec<usize> = (0..children.len())
+        .filter(|&index| contains(index) && has_blocks(&children[index]))
+        .collect();
+    if let [index] = holders[..] {
+        if let Element::Node(ref mut child) = children[index] {
+            if edit_block(child, offsets[index], edit) {
+                return true;
+            }
+        }
+    }
+
+    let statements = block_statements(node);
+    let children = &node.children;
+    let touches = |index: usize| offsets[index] <= range.end && range.start <= offsets[index + 1];
+    let touched: Vec<usize> = (0..children.len())
+        .filter(|&index| statements[index] && touches(index))
+        .collect();
+    // In a module, an edit after the last statement reparses everything
+    // from the statements it touches to the end.
+    let tail = statements
+        .iter()
+        .rposition(|&statement| statement)
+        .map_or(0, |last| last + 1);
+    let to_end = root && range.end > offsets[tail];
+    let (mut first, mut last) = match (touched.first(), touched.last()) {
+        (Some(&first), Some(&last)) => (first, last),
+        // The last statement goes too, for the dedents after it.
+        _ if to_end => (tail.saturating_sub(1), tail.saturating_sub(1)),
+        _ => return false,
+    };
+    if range.start < offsets[first] || (!to_end && range.end > offsets[last + 1]) {
+        return false;
+    }
+    if !to_end && !(first..last + 1).all(|index| statements[index] || is_dedent(&children[index])) {
+        return false;
+    }
+
+    // The statements are parsed a line at a time.
+    while !ends_line(&children[..first]) {
+        match previous_statement(&statements, children, first) {
+            Some(previous) => first = previous,
+            None => return false,
+        }
+    }
+    while !to_end && !ends_line(&children[first..last + 1]) {
+        match next_statement(&statements, children, last) {
+            Some(next) => last = next,
+            None if root => return edit_to_end(node, &offsets, first, edit),
+            None => return false,
+        }
+    }
+    if to_end {
+        return edit_to_end(node, &offsets, first, edit);
+    }
+
+    let text = edited_text(&children[first..last + 1], offsets[first], edit);
+    let parsed = if root {
+        parse_module(&text)
+    } else {
+        parse_block(&text, &children[first])
+    };
+    let (statements, dedents) = match parsed {
+        Some(parsed) => parsed,
+        None => return false,
+    };
+
+    // Blocks the last statement leaves open are closed by dedents before
+    // the next statement of the block, and otherwise by dedents after the
+    // block, which must then still close the same number.
+    let mut after = last + 1;
+    while children.get(after).is_some_and(is_dedent) {
+        after += 1;
+    }
+    let closed_here = root || node.children.get(after).is_some_and(is_statement);
+    if closed_here {
+        let mut replacement = statements;
+        replacement.extend(dedents);
+        node.children.splice(first..after, replacement);
+    } else {
+        let open = match node.children[last] {
+            Element::Node(ref node) => open_blocks(node),
+            Element::Token(_) => 0,
+        };
+        if dedents.len() != open {
+            return false;
+        }
+        node.children.splice(first..last + 1, statements);
+    }
+    true
+}
+
+/// Parses everything in a module from its child `first` on again, with
+/// `edit` made.
+fn edit_to_end(node: &mut Node, offsets: &[usize], first: usize, edit: &TextEdit) -> bool {
+    let text = edited_text(&node.children[first..], offsets[first], edit);
+    match Tree::parse(&text) {
+        Ok(tree) => {
+            node.children.splice(first.., tree.root.children);
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// The text of `elements`, which start at byte `start`, with `edit` made.
+fn edited_text(elements: &[Element], start: usize, edit: &TextEdit) -> String {
+    let mut text = String::new();
+    for element in elements {
+        element.write(&mut text);
+    }
+    let edit = TextEdit::new(
+        Span::new(edit.range.start - start, edit.range.end - start),
+        edit.text.as_str(),
+    );
+    edit.apply(&text)
+}
+
+/// The statements of `text` parsed as a module, and the dedents that close
+/// the blocks the last one leaves open.
+fn parse_module(text: &str) -> Option<(Vec<Element>, Vec<Element>)> {
+    let tree = Tree::parse(text).ok()?;
+    let mut children = tree.root.children;
+    match children.pop() {
+        Some(Element::Token(ref token))
+            if token.kind == TokenType::EndMarker && token.leading.is_empty() => {}
+        _ => return None,
+    }
+    let statements = children.len()
+        - children
+            .iter()
+            .rev()
+            .take_while(|child| is_dedent(child))
+            .count();
+    let dedents = children.split_off(statements);
+    if !ends_line(&children) {
+        return None;
+    }
+    Some((children, dedents))
+}
+
+/// The statements of `text`, the text of statements of an indented block
+/// starting with `first`, parsed under a header, and the dedents that close
+/// the blocks the last one leaves open.
+fn parse_block(text: &str, first: &Element) -> Option<(Vec<Element>, Vec<Element>)> {
+    let tree = Tree::parse(&format!("{}{}", HEADER, text)).ok()?;
+    let mut children = tree.root.children.into_iter();
+    let mut header = match children.next() {
+        Some(Element::Node(node)) => node,
+        _ => return None,
+    };
+    // The header's own dedent, then those of the blocks left open, and the
+    // end marker.
+    let mut dedents: Vec<Element> = children.collect();
+    match dedents.pop() {
+        Some(Element::Token(ref token))
+            if token.kind == TokenType::EndMarker && token.leading.is_empty() => {}
+        _ => return None,
+    }
+    if dedents.is_empty() || !dedents.iter().all(is_dedent) {
+        return None;
+    }
+    dedents.pop();
+
+    let opening = header.children.iter().position(|child| {
+        child
+            .as_token()
+            .is_some_and(|token| token.kind == TokenType::Indent)
+    })?;
+    let statements = block_statements(&header);
+    let body = header.children.split_off(opening + 1);
+    let in_body = |index: usize| statements[opening + 1 + index] || is_dedent(&body[index]);
+    if header.text() != HEADER
+        || body.is_empty()
+        || !ends_line(&body)
+        || !(0..body.len()).all(in_body)
+    {
+        return None;
+    }
+    // The block must keep its indentation.
+    if indentation(&body[0]) != indentation(first) {
+        return None;
+    }
+    Some((body, dedents))
+}
+
+/// The whitespace before the first token of a statement on its line.
+fn indentation(element: &Element) -> &str {
+    let leading = match *element {
+        Element::Node(ref node) => node.leading_trivia(),
+        Element::Token(ref token) => &token.leading,
+    };
+    &leading[leading.rfind(['\n', '\r']).map_or(0, |index| index + 1)..]
+}
+
+/// Which of the children of `node` are statements on lines of their own in
+/// one of its indented blocks, or of a module.
+fn block_statements(node: &Node) -> Vec<bool> {
+    let mut indented = node.kind == "Module";
+    node.children
+        .iter()
+        .map(|child| match *child {
+            Element::Token(ref token) => {
+                match token.kind {
+                    TokenType::Indent => indented = true,
+                    // The colon of an `else:` or `except:` clause.
+                    TokenType::Operator if token.text == ":" => indented = false,
+                    _ => {}
+                }
+                false
+            }
+            Element::Node(_) => indented && is_statement(child),
+        })
+        .collect()
+}
+
+/// How many indented blocks are still open at the end of a statement.
+fn open_blocks(node: &Node) -> usize {
+    let statements = block_statements(node);
+    let last = node
+        .children
+        .iter()
+        .zip(statements)
+        .rev()
+        .find(|&(child, _)| is_statement(child) || is_elif(child));
+    match last {
+        Some((Element::Node(last), true)) => 1 + open_blocks(last),
+        Some((Element::Node(last), false)) if last.kind == "If" => open_blocks(last),
+        _ => 0,
+    }
+}
+
+/// Whether `element` is a statement, not counting the `If` nodes of
+/// `elif` clauses.
+fn is_statement(element: &Element) -> bool {
+    match *element {
+        Element::Node(ref node) => STATEMENTS.contains(&node.kind) && !is_elif(element),
+        Element::Token(_) => false,
+    }
+}
+
+fn is_elif(element: &Element) -> bool {
+    match *element {
+        Element::Node(ref node) => {
+            node.kind == "If" && node.first_token().is_some_and(|token| token.text == "elif")
+        }
+        Element::Token(_) => false,
+    }
+}
+
+/// Whether `element` is a node that may hold indented blocks.
+fn has_blocks(element: &Element) -> bool {
+    match *element {
+        Element::Node(ref node) => node.kind == "ExceptHandler" || STATEMENTS.contains(&node.kind),
+        Element::Token(_) => false,
+    }
+}
+
+fn is_dedent(element: &Element) -> bool {
+    element
+        .as_token()
+        .is_some_and(|token| token.kind == TokenType::Dedent)
+}
+
+/// The statement before child `index`, if only dedents come between.
+fn previous_statement(statements: &[bool], children: &[Element], index: usize) -> Option<usize> {
+    let previous = (0..index)
+        .rev()
+        .find(|&index| !is_dedent(&children[index]))?;
+    Some(previous).filter(|&previous| statements[previous])
+}
+
+/// The statement after child `index`, if only dedents come between.
+fn next_statement(statements: &[bool], children: &[Element], index: usize) -> Option<usize> {
+    let next = (index + 1..children.len()).find(|&index| !is_dedent(&children[index]))?;
+    Some(next).filter(|&next| statements[next])
+}
+
+/// Whether `elements` end with the newline at the end of a line, so that
+/// what comes after them starts a line.
+fn ends_line(elements: &[Element]) -> bool {
+    last_token(elements)
+        .is_none_or(|token| token.kind == TokenType::NewlineLogical && !token.text.is_empty())
+}
+
+/// The last token in `elements` that is not an indent or a dedent.
+fn last_token(elements: &[Element]) -> Option<&Token> {
+    elements.iter().rev().find_map(|element| match *element {
+        Element::Node(ref node) => last_token(&node.children),
+        Element::Token(ref token) => match token.kind {
+            TokenType::Indent | TokenType::Dedent => None,
+            _ => Some(token),
+        },
+    })
+}
+
+fn element_len(element: &Element) -> usize {
+    match *element {
+        Element::Node(ref node) => node.children.iter().map(element_len).sum(),
+        Element::Token(ref token) => token.leading.len() + token.text.len(),
+    }
+}
@@ -0,0 +1,326 @@
+//! A concrete syntax tree, which unlike `ast` keeps every character of the
+//! source: whitespace, comments, parentheses, line continuations and the
+//! exact spelling of literals.
+//!
+//! The tree is made of `Node`s named after the AST nodes they stand for
+//! (`FunctionDef`, `BinOp`, `arg`, ...), whose children are nodes and
+//! `Token`s in source order. Each token owns the text before it that is not
+//! a token, its leading trivia, so rendering a tree writes the trivia and
+//! text of its tokens in order and gives back the source it was parsed
+//! from. A simple statement also owns the `;` or newline that ends it.
+//!
+//! Trees can be changed in place, through the children of their nodes and
+//! the text of their tokens, or by splicing in nodes parsed with
+//! `parse_expression` and `parse_statements`. Code that edits the tree
+//! keeps the trivia it does not touch, which is what refactoring tools
+//! need. `Tree::edit` follows a text edit by parsing again only the
+//! statements it touches, as an editor does on each keystroke.
+
+mod build;
+mod incremental;
+
+use std::fmt;
+
+use parser::{self, ParseError};
+use tokenizer::{strip_bom, tokenize, TokenType};
+
+use self::build::Builder;
+
+/// The concrete syntax tree of a module.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tree {
+    root: Node,
+}
+
+impl Tree {
+    /// Parses `source` into a tree that renders back to it exactly. A byte
+    /// order mark is leading trivia of the first token.
+    pub fn parse(source: &str) -> Result<Tree, ParseError> {
+        let code = strip_bom(source);
+        let module = parser::parse(code)?;
+        let tokens = tokenize(code).map_err(ParseError::from)?;
+        let mut root = Builder::new(code, &tokens).module(&module);
+        if let Some(first) = root.first_token_mut() {
+            first
+                .leading
+                .insert_str(0, &source[..source.len() - code.len()]);
+        }
+        Ok(Tree { root })
+    }
+
+    /// The `Module` node.
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    pub fn root_mut(&mut self) -> &mut Node {
+        &mut self.root
+    }
+
+    /// The source of the tree, with the edits made to it.
+    pub fn render(&self) -> String {
+        self.root.text()
+    }
+}
+
+impl fmt::Display for Tree {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.root.fmt(f)
+    }
+}
+
+/// Parses an expression, for splicing into a tree. Its first token has no
+/// leading trivia.
+pub fn parse_expression(source: &str) -> Result<Node, ParseError> {
+    let expression = parser::parse_expression(source)?;
+    let tokens = tokenize(source).map_err(ParseError::from)?;
+    let mut root = Builder::new(source, &tokens).expression(&expression);
+    let mut node = root
+        .children
+        .drain(..)
+        .find_map(Element::into_node)
+        .expect("an expression tree holds the expression");
+    node.set_leading_trivia("");
+    Ok(node)
+}
+
+/// Parses statements, for splicing into the body of a module. The
+/// statements of a block must be indented to match it.
+pub fn parse_statements(source: &str) -> Result<Vec<Node>, ParseError> {
+    let tree = Tree::parse(source)?;
+    Ok(tree
+        .root
+        .children
+        .into_iter()
+        .filter_map(Element::into_node)
+        .collect())
+}
+
+/// A child of a node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Element {
+    Node(Node),
+    Token(Token),
+}
+
+impl Element {
+    pub fn as_node(&self) -> Option<&Node> {
+        match *self {
+            Element::Node(ref node) => Some(node),
+            Element::Token(_) => None,
+        }
+    }
+
+    pub fn as_token(&self) -> Option<&Token> {
+        match *self {
+            Element::Token(ref token) => Some(token),
+            Element::Node(_) => None,
+        }
+    }
+
+    pub fn into_node(self) -> Option<Node> {
+        match self {
+            Element::Node(node) => Some(node),
+            Element::Token(_) => None,
+        }
+    }
+
+    fn write(&self, out: &mut String) {
+        match *self {
+            Element::Node(ref node) => node.write(out),
+            Element::Token(ref token) => {
+                out.push_str(&token.leading);
+                out.push_str(&token.text);
+            }
+        }
+    }
+
+    fn first_token_mut(&mut self) -> Option<&mut Token> {
+        match *self {
+            Element::Node(ref mut node) => node.first_token_mut(),
+            Element::Token(ref mut token) => Some(token),
+        }
+    }
+}
+
+/// A token and the trivia before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenType,
+    /// The whitespace, comments and line continuations between the previous
+    /// token and this one.
+    pub leading: String,
+    /// The text of the token as written. Empty for indents, dedents and
+    /// the end marker, whose whitespace is trivia.
+    pub text: String,
+}
+
+impl Token {
+    /// A token without leading trivia.
+    pub fn new<S: Into<String>>(kind: TokenType, text: S) -> Token {
+        Token {
+            kind,
+            leading: String::new(),
+            text: text.into(),
+        }
+    }
+}
+
+/// A syntactic construct and the nodes and tokens it is written with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    kind: &'static str,
+    children: Vec<Element>,
+}
+
+impl Node {
+    pub fn new(kind: &'static str, children: Vec<Element>) -> Node {
+        Node { kind, children }
+    }
+
+    /// The name of the AST node this node stands for, such as `Call`.
+    pub fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    pub fn children(&self) -> &[Element] {
+        &self.children
+    }
+
+    /// The children, to insert, remove or replace nodes and tokens.
+    pub fn children_mut(&mut self) -> &mut Vec<Element> {
+        &mut self.children
+    }
+
+    /// The child nodes, without the tokens between them.
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.children.iter().filter_map(Element::as_node)
+    }
+
+    /// The tokens of the node and of the nodes in it, in source order.
+    pub fn tokens(&self) -> Vec<&Token> {
+        let mut tokens = Vec::new();
+        self.collect_tokens(&mut tokens);
+        tokens
+    }
+
+    fn collect_tokens<'a>(&'a self, tokens: &mut Vec<&'a Token>) {
+        for child in &self.children {
+            match *child {
+                Element::Node(ref node) => node.collect_tokens(tokens),
+                Element::Token(ref token) => tokens.push(token),
+            }
+        }
+    }
+
+    /// This node and the nodes in it of kind `kind`, in source order.
+    pub fn find_all(&self, kind: &str) -> Vec<&Node> {
+        let mut found = Vec::new();
+        self.collect(kind, &mut found);
+        found
+    }
+
+    fn collect<'a>(&'a self, kind: &str, found: &mut Vec<&'a Node>) {
+        if self.kind == kind {
+            found.push(self);
+        }
+        for node in self.nodes() {
+            node.collect(kind, found);
+        }
+    }
+
+    /// Calls `f` on this node and then on each node in it, in source order.
+    /// `f` may change a node's children before they are visited.
+    pub fn walk_mut<F: FnMut(&mut Node)>(&mut self, f: &mut F) {
+        f(self);
+        for child in &mut self.children {
+            if let Element::Node(ref mut node) = *child {
+                node.walk_mut(f);
+            }
+        }
+    }
+
+    /// The bracket pairs among the node's own tokens whose contents end
+    /// with a comma, as those of `[1, 2,]` and `f(a,)` do, each as the
+    /// indices in `children` of its brackets. The comma may end a child
+    /// before the closing bracket instead, as the tuple in `x[a, b,]` does.
+    pub fn trailing_commas(&self) -> Vec<(usize, usize)> {
+        let mut open = Vec::new();
+        let mut pairs = Vec::new();
+        for (index, child) in self.children.iter().enumerate() {
+            let token = match *child {
+                Element::Token(ref token) if token.kind == TokenType::Operator => token,
+                _ => continue,
+            };
+            match token.text.as_str() {
+                "(" | "[" | "{" => open.push(index),
+                ")" | "]" | "}" => {
+                    let opening = match open.pop() {
+                        Some(opening) => opening,
+                        None => continue,
+                    };
+                    let comma = index > opening + 1
+                        && match self.children[index - 1] {
+                            Element::Token(ref token) => token.text == ",",
+                            Element::Node(ref node) => {
+                                node.tokens().last().is_some_and(|token| token.text == ",")
+                            }
+                        };
+                    if comma {
+                        pairs.push((opening, index));
+                    }
+                }
+                _ => {}
+            }
+        }
+        pairs
+    }
+
+    /// The first token of the node, which owns the trivia before it.
+    pub fn first_token(&self) -> Option<&Token> {
+        self.children.iter().find_map(|child| match *child {
+            Element::Node(ref node) => node.first_token(),
+            Element::Token(ref token) => Some(token),
+        })
+    }
+
+    pub fn first_token_mut(&mut self) -> Option<&mut Token> {
+        self.children.iter_mut().find_map(Element::first_token_mut)
+    }
+
+    /// The trivia before the node, such as the comments above a statement.
+    pub fn leading_trivia(&self) -> &str {
+        self.first_token().map_or("", |token| &token.leading)
+    }
+
+    pub fn set_leading_trivia(&mut self, trivia: &str) {
+        if let Some(token) = self.first_token_mut() {
+            token.leading = trivia.to_string();
+        }
+    }
+
+    /// The source of the node, from its leading trivia on.
+    pub fn text(&self) -> String {
+        let mut out = String::new();
+        self.write(&mut out);
+        out
+    }
+
+    /// The source of the node without its leading trivia.
+    pub fn code(&self) -> String {
+        let text = self.text();
+        text[self.leading_trivia().len()..].to_string()
+    }
+
+    fn write(&self, out: &mut String) {
+        for child in &self.children {
+            child.write(out);
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.text())
+    }
+}
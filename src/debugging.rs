@@ -0,0 +1,257 @@
+//! Drawings of what the parser and the compiler make, in Graphviz's DOT
+//! language, for seeing how a tricky input was parsed or compiled.
+//! `ast_to_dot` draws the AST and `cfg_to_dot` the control-flow graph of
+//! compiled code; `dot -Tsvg` renders either.
+//!
+//! The AST is drawn in the layout of `ast::to_json`: each node is a box
+//! with its CPython class name, its fields that hold no nodes and its
+//! position, and edges named after the fields lead to its children.
+//! Operators and contexts are written in the box of the node that holds
+//! them, and empty fields are left out.
+
+use std::fmt::Write;
+
+use ast::json::{self, Node, Value};
+use ast::{repr_str, Module};
+use compiler::{CodeConstant, CodeObject, Instruction};
+
+/// The longest string a label shows in full.
+const LABEL_LEN: usize = 40;
+
+/// How control gets from the end of one basic block to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// On to the next instruction.
+    Next,
+    /// By the jump that ends the block.
+    Jump,
+    /// To the handler set up by the block's last instruction, when an
+    /// exception is raised.
+    Exception,
+}
+
+/// A run of instructions, `start..end`, entered only at its first and left
+/// only after its last.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    /// The blocks control may go to next, by index.
+    pub successors: Vec<(usize, EdgeKind)>,
+}
+
+/// The basic blocks of `code`, in the order of their instructions.
+pub fn basic_blocks(code: &CodeObject) -> Vec<BasicBlock> {
+    let instructions = &code.instructions;
+    let mut leaders = vec![false; instructions.len() + 1];
+    leaders[0] = true;
+    for (index, instruction) in instructions.iter().enumerate() {
+        if let Some(target) = instruction.target() {
+            leaders[target] = true;
+            leaders[index + 1] = true;
+        }
+        if instruction.is_terminal() {
+            leaders[index + 1] = true;
+        }
+    }
+    let starts: Vec<usize> = (0..instructions.len())
+        .filter(|&index| leaders[index])
+        .collect();
+    let block_of = |index: usize| starts.binary_search(&index).ok();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(block, &start)| {
+            let end = starts.get(block + 1).cloned().unwrap_or(instructions.len());
+            let last = instructions[end - 1];
+            let mut successors = Vec::new();
+            if !last.is_terminal() && end < instructions.len() {
+                successors.push((block + 1, EdgeKind::Next));
+            }
+            if let Some(target) = last.target().and_then(block_of) {
+                let kind = match last {
+                    Instruction::SetupFinally(_) | Instruction::SetupWith(_) => EdgeKind::Exception,
+                    _ => EdgeKind::Jump,
+                };
+                successors.push((target, kind));
+            }
+            BasicBlock {
+                start,
+                end,
+                successors,
+            }
+        })
+        .collect()
+}
+
+/// The AST of `module` as a DOT graph, with columns counted in characters.
+pub fn ast_to_dot(module: &Module) -> String {
+    let mut out = String::new();
+    out.push_str("digraph ast {\n");
+    out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    let mut count = 0;
+    ast_node(&mut out, &json::tree(module, None), &mut count);
+    out.push_str("}\n");
+    out
+}
+
+/// Writes `node` and the nodes under it, returning its id.
+fn ast_node(out: &mut String, node: &Node, count: &mut usize) -> usize {
+    let id = *count;
+    *count += 1;
+    let mut label = vec![node.name.to_string()];
+    let mut children = Vec::new();
+    for &(name, ref value) in &node.fields {
+        match *value {
+            Value::Node(ref child) if !is_unit(child) => children.push((name.to_string(), child)),
+            Value::List(ref items) if items.iter().any(is_node) => {
+                for (index, item) in items.iter().enumerate() {
+                    if let Value::Node(ref child) = *item {
+                        children.push((format!("{}[{}]", name, index), child));
+                    }
+                }
+            }
+            Value::Null => {}
+            Value::List(ref items) if items.is_empty() => {}
+            ref value => label.push(format!("{} = {}", name, scalar(value))),
+        }
+    }
+    if let Some(position) = node.position {
+        label.push(format!(
+            "{}:{}-{}:{}",
+            position[0], position[1], position[2], position[3]
+        ));
+    }
+    let _ = writeln!(
+        out,
+        "    n{} [label=\"{}\"];",
+        id,
+        dot_escape(&label.join("\n"))
+    );
+    for (name, child) in children {
+        let child_id = ast_node(out, child, count);
+        let _ = writeln!(
+            out,
+            "    n{} -> n{} [label=\"{}\"];",
+            id,
+            child_id,
+            dot_escape(&name)
+        );
+    }
+    id
+}
+
+/// Whether `node` is an operator or a context, which has no fields or
+/// position.
+fn is_unit(node: &Node) -> bool {
+    node.fields.is_empty() && node.position.is_none()
+}
+
+/// Whether `value` is a node drawn in a box of its own.
+fn is_node(value: &Value) -> bool {
+    match *value {
+        Value::Node(ref node) => !is_unit(node),
+        _ => false,
+    }
+}
+
+/// A value that holds no nodes, as written in a label.
+fn scalar(value: &Value) -> String {
+    match *value {
+        Value::Null => "None".to_string(),
+        Value::Raw(ref text) => text.clone(),
+        Value::Str(ref text) if text.chars().count() > LABEL_LEN => {
+            let prefix: String = text.chars().take(LABEL_LEN).collect();
+            format!("{}...", repr_str(&prefix))
+        }
+        Value::Str(ref text) => repr_str(text),
+        Value::List(ref items) => {
+            let items: Vec<String> = items.iter().map(scalar).collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Node(ref node) => node.name.to_string(),
+    }
+}
+
+/// The control-flow graph of `code` as a DOT graph, with the code of each
+/// function, class body and comprehension in it drawn in a box of its own.
+/// Each block lists its instructions as the disassembly does; jumps that
+/// test a value are labelled with the outcome they are taken for, and the
+/// edges to exception handlers are dashed.
+pub fn cfg_to_dot(code: &CodeObject) -> String {
+    let mut out = String::new();
+    out.push_str("digraph cfg {\n");
+    out.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    let mut count = 0;
+    cfg_code(&mut out, code, &mut count);
+    out.push_str("}\n");
+    out
+}
+
+fn cfg_code(out: &mut String, code: &CodeObject, count: &mut usize) {
+    let id = *count;
+    *count += 1;
+    let _ = writeln!(out, "    subgraph cluster_{} {{", id);
+    let _ = writeln!(
+        out,
+        "        label=\"{}\";",
+        dot_escape(&format!("<code object {}>", code.qualname))
+    );
+    let blocks = basic_blocks(code);
+    for (index, block) in blocks.iter().enumerate() {
+        let mut label = String::new();
+        for position in block.start..block.end {
+            let instruction = &code.instructions[position];
+            let line = format!(
+                "{:>4} {:?} {}",
+                position,
+                instruction,
+                code.operand(instruction)
+            );
+            label.push_str(&dot_escape(line.trim_end()));
+            label.push_str("\\l");
+        }
+        let _ = writeln!(out, "        c{}b{} [label=\"{}\"];", id, index, label);
+    }
+    for (index, block) in blocks.iter().enumerate() {
+        let last = code.instructions[block.end - 1];
+        for &(successor, kind) in &block.successors {
+            let attributes = match kind {
+                EdgeKind::Next => String::new(),
+                EdgeKind::Exception => " [style=dashed, label=\"exception\"]".to_string(),
+                EdgeKind::Jump => match jump_label(last) {
+                    Some(label) => format!(" [label=\"{}\"]", label),
+                    None => String::new(),
+                },
+            };
+            let _ = writeln!(
+                out,
+                "        c{}b{} -> c{}b{}{};",
+                id, index, id, successor, attributes
+            );
+        }
+    }
+    out.push_str("    }\n");
+    for constant in &code.constants {
+        if let CodeConstant::Code(ref nested) = *constant {
+            cfg_code(out, nested, count);
+        }
+    }
+}
+
+/// What a jump is taken for, if it is taken only sometimes.
+fn jump_label(instruction: Instruction) -> Option<&'static str> {
+    match instruction {
+        Instruction::PopJumpIfFalse(_) | Instruction::JumpIfFalseOrPop(_) => Some("false"),
+        Instruction::PopJumpIfTrue(_) | Instruction::JumpIfTrueOrPop(_) => Some("true"),
+        Instruction::ForIter(_) => Some("exhausted"),
+        Instruction::JumpIfNotExcMatch(_) | Instruction::CheckEgMatch(_) => Some("no match"),
+        _ => None,
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
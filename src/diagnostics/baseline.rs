@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::Diagnostic;
+
+const HEADER: &str = "# rustpy baseline v1";
+
+/// Identifies a diagnostic independently of where it sits in the file.
+///
+/// The fingerprint covers the code, the message and the trimmed text of the
+/// offending line, but not the line number, so inserting or removing
+/// unrelated lines above a baselined diagnostic does not resurrect it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(pub u64);
+
+impl Fingerprint {
+    pub fn of(diagnostic: &Diagnostic, source: &str) -> Fingerprint {
+        let line = source
+            .lines()
+            .nth(diagnostic.location.line.saturating_sub(1))
+            .unwrap_or("")
+            .trim();
+        let mut hash = Fnv::new();
+        hash.write(diagnostic.code.as_bytes());
+        hash.write(&[0]);
+        hash.write(diagnostic.message.as_bytes());
+        hash.write(&[0]);
+        hash.write(line.as_bytes());
+        Fingerprint(hash.0)
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+/// FNV-1a, chosen because baseline files must hash identically across builds.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 ^= u64::from(b);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum BaselineError {
+    Io(io::Error),
+    Parse { line: usize, message: String },
+}
+
+impl fmt::Display for BaselineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BaselineError::Io(ref err) => write!(f, "{}", err),
+            BaselineError::Parse { line, ref message } => {
+                write!(f, "invalid baseline (line {}): {}", line, message)
+            }
+        }
+    }
+}
+
+impl Error for BaselineError {}
+
+impl From<io::Error> for BaselineError {
+    fn from(err: io::Error) -> BaselineError {
+        BaselineError::Io(err)
+    }
+}
+
+/// A record of accepted diagnostics, so that only new ones are reported.
+///
+/// Each entry counts how many diagnostics with a given fingerprint a file may
+/// contain; a file that later gains another identical diagnostic reports the
+/// extra one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Baseline {
+    entries: BTreeMap<(String, String, Fingerprint), usize>,
+}
+
+impl Baseline {
+    pub fn new() -> Baseline {
+        Baseline::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.values().sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Records all of a file's current diagnostics as accepted.
+    pub fn add(&mut self, path: &str, source: &str, diagnostics: &[Diagnostic]) {
+        for diagnostic in diagnostics {
+            let key = (
+                path.to_string(),
+                diagnostic.code.to_string(),
+                Fingerprint::of(diagnostic, source),
+            );
+            *self.entries.entry(key).or_insert(0) += 1;
+        }
+    }
+
+    /// Returns the diagnostics of a file that the baseline does not cover.
+    pub fn filter(&self, path: &str, source: &str, diagnostics: &[Diagnostic]) -> Vec<Diagnostic> {
+        let mut remaining = BTreeMap::new();
+        let mut fresh = Vec::new();
+        for diagnostic in diagnostics {
+            let key = (
+                path.to_string(),
+                diagnostic.code.to_string(),
+                Fingerprint::of(diagnostic, source),
+            );
+            let allowed = remaining
+                .entry(key.clone())
+                .or_insert_with(|| self.entries.get(&key).cloned().unwrap_or(0));
+            if *allowed > 0 {
+                *allowed -= 1;
+            } else {
+                fresh.push(diagnostic.clone());
+            }
+        }
+        fresh
+    }
+
+    pub fn parse(text: &str) -> Result<Baseline, BaselineError> {
+        let mut baseline = Baseline::new();
+        for (index, line) in text.lines().enumerate() {
+            let number = index + 1;
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let error = |message: &str| BaselineError::Parse {
+                line: number,
+                message: message.to_string(),
+            };
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 4 {
+                return Err(error("expected 4 tab-separated fields"));
+            }
+            let path = unescape(fields[0]).ok_or_else(|| error("invalid escape in path"))?;
+            let fingerprint =
+                u64::from_str_radix(fields[2], 16).map_err(|_| error("invalid fingerprint"))?;
+            let count: usize = fields[3].parse().map_err(|_| error("invalid count"))?;
+            *baseline
+                .entries
+                .entry((path, fields[1].to_string(), Fingerprint(fingerprint)))
+                .or_insert(0) += count;
+        }
+        Ok(baseline)
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Baseline, BaselineError> {
+        Baseline::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), BaselineError> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+}
+
+impl fmt::Display for Baseline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for (&(ref path, ref code, fingerprint), count) in &self.entries {
+            writeln!(f, "{}\t{}\t{}\t{}", escape(path), code, fingerprint, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// A path with the characters that separate fields and entries, and the
+/// backslash, escaped as in a Rust string, and a `#` that would start a
+/// comment escaped as `\#`.
+fn escape(path: &str) -> String {
+    let mut escaped = String::with_capacity(path.len());
+    if path.starts_with('#') {
+        escaped.push('\\');
+    }
+    for c in path.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The path `escape` gave `text` for, or `None` if it has an escape
+/// `escape` does not make.
+fn unescape(text: &str) -> Option<String> {
+    let mut path = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            path.push(c);
+            continue;
+        }
+        path.push(match chars.next()? {
+            '\\' => '\\',
+            't' => '\t',
+            'n' => '\n',
+            'r' => '\r',
+            '#' if path.is_empty() => '#',
+            _ => return None,
+        });
+    }
+    Some(path)
+}
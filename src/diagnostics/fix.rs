@@ -0,0 +1,149 @@
+//! Fixes for the problems whose remedy is clear from the source alone: a
+//! missing colon, a closing bracket that does not match, a string not
+//! closed, and indentation that matches no block.
+
+use tokenizer::{measure_indentation, LineIndex, Span, Token, TokenType, BOM, TAB_SIZE};
+
+use super::Diagnostic;
+
+/// An edit that fixes what a diagnostic is about: the bytes of the source
+/// in `span` replaced by `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fix {
+    pub span: Span,
+    pub replacement: String,
+}
+
+impl Fix {
+    pub fn new<S: Into<String>>(span: Span, replacement: S) -> Fix {
+        Fix {
+            span,
+            replacement: replacement.into(),
+        }
+    }
+
+    /// `source` with the fix made.
+    pub fn apply(&self, source: &str) -> String {
+        let mut fixed = String::with_capacity(source.len() + self.replacement.len());
+        fixed.push_str(&source[..self.span.start]);
+        fixed.push_str(&self.replacement);
+        fixed.push_str(&source[self.span.end..]);
+        fixed
+    }
+}
+
+/// `source` with the fixes of `diagnostics` made, leaving out any that
+/// overlaps one before it.
+pub fn apply_fixes(source: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut fixes: Vec<&Fix> = diagnostics
+        .iter()
+        .filter_map(|diagnostic| diagnostic.fix.as_ref())
+        .collect();
+    fixes.sort_by_key(|fix| (fix.span.start, fix.span.end));
+    let mut fixed = String::with_capacity(source.len());
+    let mut position = 0;
+    for fix in fixes {
+        if fix.span.start < position {
+            continue;
+        }
+        fixed.push_str(&source[position..fix.span.start]);
+        fixed.push_str(&fix.replacement);
+        position = fix.span.end;
+    }
+    fixed.push_str(&source[position..]);
+    fixed
+}
+
+/// The fix for `diagnostic`, found in `source`, whose `tokens` are those
+/// `tokenizer::tokenize_recovering` gives and whose lines, after any byte
+/// order mark, are `lines`, if it is one with a clear fix.
+pub(super) fn find_fix(
+    diagnostic: &Diagnostic,
+    source: &str,
+    tokens: &[Token],
+    lines: &LineIndex,
+) -> Option<Fix> {
+    let bom = if source.starts_with(BOM) {
+        BOM.len_utf8()
+    } else {
+        0
+    };
+    lines.line_span(diagnostic.location.line)?;
+    let offset = bom + lines.offset(&source[bom..], diagnostic.location);
+    // The tokens from the one at the diagnostic, and the indentation of the
+    // blocks open there.
+    let index = tokens.partition_point(|token| token.start < diagnostic.location);
+    let indents = || {
+        let mut indents = vec![""];
+        for token in &tokens[..index] {
+            match token.kind {
+                TokenType::Indent => indents.push(&token.value[..]),
+                TokenType::Dedent => {
+                    indents.pop();
+                }
+                _ => {}
+            }
+        }
+        indents
+    };
+    match diagnostic.code {
+        // A colon missing at the end of a line goes after its last token.
+        "E201" if diagnostic.message == "expected ':'" => {
+            let at = tokens.get(index)?;
+            if at.kind != TokenType::NewlineLogical && at.kind != TokenType::Comment {
+                return None;
+            }
+            let last = tokens[..index]
+                .iter()
+                .rev()
+                .find(|token| token.kind != TokenType::Comment)?;
+            let end = bom + last.span.end;
+            Some(Fix::new(Span::new(end, end), ":"))
+        }
+        // A closing bracket that does not match the one open is replaced by
+        // the one that does, which the message names last.
+        "E105" => {
+            let opening = diagnostic.message.rsplit('\'').nth(1)?;
+            let closing = match opening {
+                "(" => ")",
+                "[" => "]",
+                "{" => "}",
+                _ => return None,
+            };
+            Some(Fix::new(Span::new(offset, offset + 1), closing))
+        }
+        // A string is closed at the end of the line.
+        "E101" => {
+            let rest = &source[offset..];
+            let quote = rest.chars().find(|&c| c == '\'' || c == '"')?;
+            let end = offset + rest.find(['\n', '\r']).unwrap_or(rest.len());
+            Some(Fix::new(Span::new(end, end), quote.to_string()))
+        }
+        // An unexpected indent is removed, down to the block's.
+        "E004" => {
+            let at = tokens.get(index)?;
+            if at.kind != TokenType::Indent || at.start != diagnostic.location {
+                return None;
+            }
+            let indentation = *indents().last()?;
+            let span = Span::new(bom + at.span.start, bom + at.span.end);
+            Some(Fix::new(span, indentation))
+        }
+        // A dedent to no block's indentation goes back to the nearest
+        // block indented less.
+        "E002" => {
+            let line_start = bom + lines.line_span(diagnostic.location.line)?.start;
+            let rest = &source[line_start..];
+            let (column, width) = measure_indentation(rest, TAB_SIZE);
+            let indentation = indents()
+                .into_iter()
+                .rev()
+                .find(|indentation| measure_indentation(indentation, TAB_SIZE).0 < column)?;
+            Some(Fix::new(
+                Span::new(line_start, line_start + width),
+                indentation,
+            ))
+        }
+        _ => None,
+    }
+}
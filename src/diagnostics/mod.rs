@@ -0,0 +1,230 @@
+//! Errors and warnings about a source, each with a stable code, in one
+//! type, so that everything wrong with a file can be reported at once.
+//!
+//! The codes are grouped by what finds the problem:
+//!
+//! | Code      | Problem                                              |
+//! |-----------|------------------------------------------------------|
+//! | E001      | `TabError`: tabs and spaces mixed in indentation     |
+//! | E002      | a dedent to no block's indentation                   |
+//! | E003      | a block that is not indented                         |
+//! | E004      | an unexpected indent                                 |
+//! | E101-E113 | tokenizer errors, such as an unterminated string     |
+//! | E201-E204 | parser errors, such as invalid syntax                |
+//! | E301      | compiler errors                                      |
+//! | W301      | compiler warnings                                    |
+
+mod baseline;
+mod fix;
+
+pub use self::baseline::{Baseline, BaselineError, Fingerprint};
+pub use self::fix::{apply_fixes, Fix};
+
+use std::fmt;
+
+use ast::Module;
+use compiler::{compile_with_warnings, CompilerConfig, CompilerError, CompilerWarning, Mode};
+use parser::{parse_recovering, ParseError, ParseErrorKind};
+use tokenizer::{
+    strip_bom, tokenize_recovering, LineIndex, Location, Token, TokenError, TokenErrorKind,
+    TokenizerConfig,
+};
+
+/// The code of every error the compiler reports.
+pub const COMPILER_ERROR: &str = "E301";
+/// The code of every warning the compiler reports.
+pub const COMPILER_WARNING: &str = "W301";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Code that cannot run, such as a syntax error.
+    Error,
+    /// Code that runs but is most likely a mistake.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub code: &'static str,
+    pub severity: Severity,
+    pub message: String,
+    pub location: Location,
+    /// Where the code the diagnostic is about ends, which is `location`
+    /// when only its start is known.
+    pub end: Location,
+    /// What might be done about it, such as `indent the block`.
+    pub suggestion: Option<String>,
+    /// An edit that does it, when there is no doubt what that is.
+    pub fix: Option<Fix>,
+}
+
+impl Diagnostic {
+    /// An error at `location`.
+    pub fn new<S: Into<String>>(code: &'static str, message: S, location: Location) -> Diagnostic {
+        Diagnostic {
+            code,
+            severity: Severity::Error,
+            message: message.into(),
+            location,
+            end: location,
+            suggestion: None,
+            fix: None,
+        }
+    }
+
+    /// A warning at `location`.
+    pub fn warning<S: Into<String>>(
+        code: &'static str,
+        message: S,
+        location: Location,
+    ) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::new(code, message, location)
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Extends a diagnostic given only where it starts to the end of the
+    /// token of `tokens` that starts there, if one does.
+    pub fn cover_token(&mut self, tokens: &[Token]) {
+        if self.end != self.location {
+            return;
+        }
+        let index = tokens.partition_point(|token| token.start < self.location);
+        let token = tokens[index..]
+            .iter()
+            .take_while(|token| token.start == self.location)
+            .find(|token| !token.span.is_empty());
+        if let Some(token) = token {
+            self.end = token.end;
+        }
+    }
+}
+
+/// Everything to report about `source`, after any byte order mark: its
+/// syntax errors, all of them, as `parser::parse_recovering` finds them, or
+/// if there are none, the compiler's warnings and its error, if any, each
+/// covering the token it is at and with its fix, if it has one. `filename`
+/// is the one the code is compiled with.
+pub fn check(source: &str, filename: &str) -> Vec<Diagnostic> {
+    let (module, errors) = parse_recovering(strip_bom(source));
+    let mut diagnostics = check_parsed(&module, &errors, filename);
+    let (tokens, _) = tokenize_recovering(strip_bom(source), TokenizerConfig::default());
+    annotate(&mut diagnostics, source, &tokens);
+    diagnostics
+}
+
+/// Extends each of `diagnostics` to the token it is at, as `cover_token`
+/// does, and gives it its fix, if it has one, from `source` and the
+/// `tokens` `tokenizer::tokenize_recovering` gives for it after any byte
+/// order mark.
+pub fn annotate(diagnostics: &mut [Diagnostic], source: &str, tokens: &[Token]) {
+    let lines = LineIndex::new(strip_bom(source));
+    for diagnostic in diagnostics {
+        diagnostic.cover_token(tokens);
+        diagnostic.fix = fix::find_fix(diagnostic, source, tokens, &lines);
+    }
+}
+
+/// The diagnostics for `module`, as `parser::parse_recovering` gave it with
+/// `errors`, as `check` reports them.
+pub fn check_parsed(module: &Module, errors: &[ParseError], filename: &str) -> Vec<Diagnostic> {
+    if !errors.is_empty() {
+        return errors.iter().map(Diagnostic::from).collect();
+    }
+    let config = CompilerConfig::default();
+    match compile_with_warnings(module, filename, Mode::Exec, &config) {
+        Ok((_, warnings)) => warnings.iter().map(Diagnostic::from).collect(),
+        Err(err) => vec![Diagnostic::from(&err)],
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {} {}",
+            self.location.line,
+            self.location.column + 1,
+            self.code,
+            self.message
+        )
+    }
+}
+
+impl<'a> From<&'a TokenError> for Diagnostic {
+    fn from(err: &'a TokenError) -> Diagnostic {
+        Diagnostic {
+            suggestion: token_suggestion(err.kind).map(str::to_string),
+            ..Diagnostic::new(err.kind.code(), err.message.clone(), err.location)
+        }
+    }
+}
+
+impl From<TokenError> for Diagnostic {
+    fn from(err: TokenError) -> Diagnostic {
+        Diagnostic::from(&err)
+    }
+}
+
+impl<'a> From<&'a ParseError> for Diagnostic {
+    fn from(err: &'a ParseError) -> Diagnostic {
+        let suggestion = match err.kind {
+            ParseErrorKind::Token(kind) => token_suggestion(kind),
+            ParseErrorKind::UnexpectedIndent => Some("remove the extra indentation"),
+            ParseErrorKind::ExpectedIndent => Some("indent the block after the ':'"),
+            _ => None,
+        };
+        Diagnostic {
+            suggestion: suggestion.map(str::to_string),
+            ..Diagnostic::new(err.kind.code(), err.message.clone(), err.location)
+        }
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(err: ParseError) -> Diagnostic {
+        Diagnostic::from(&err)
+    }
+}
+
+impl<'a> From<&'a CompilerError> for Diagnostic {
+    fn from(err: &'a CompilerError) -> Diagnostic {
+        Diagnostic::new(COMPILER_ERROR, err.message.clone(), err.location)
+    }
+}
+
+impl<'a> From<&'a CompilerWarning> for Diagnostic {
+    fn from(warning: &'a CompilerWarning) -> Diagnostic {
+        Diagnostic::warning(COMPILER_WARNING, warning.message.clone(), warning.location)
+    }
+}
+
+fn token_suggestion(kind: TokenErrorKind) -> Option<&'static str> {
+    match kind {
+        TokenErrorKind::InconsistentDedent => {
+            Some("indent the line as far as the block it belongs to")
+        }
+        TokenErrorKind::InconsistentTabs => Some("indent with spaces only, or tabs only"),
+        TokenErrorKind::UnterminatedString => Some("close the string on the same line"),
+        TokenErrorKind::EofInMultiLineStatement => Some("close the open bracket"),
+        TokenErrorKind::UnmatchedBracket | TokenErrorKind::MismatchedBracket => {
+            Some("make the brackets match")
+        }
+        TokenErrorKind::NonAsciiBytes => Some("write the character as \\x escapes of its bytes"),
+        _ => None,
+    }
+}
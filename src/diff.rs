@@ -0,0 +1,436 @@
+//! Differences between sequences, with the algorithm of CPython's
+//! `difflib`.
+//!
+//! `SequenceMatcher` finds the longest contiguous matching block that
+//! contains no junk, then does the same on each side of it, as
+//! Ratcliff and Obershelp's "gestalt pattern matching" does. It works on
+//! any slice of hashable items, such as the characters of a string or the
+//! lines of a file, and gives the same blocks, opcodes and ratios as
+//! `difflib.SequenceMatcher` for the same sequences. `unified_diff` shows
+//! the difference between two lists of lines as `diff -u` does.
+
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+/// A block of `size` items that match: `a[a..a + size]` equals
+/// `b[b..b + size]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Match {
+    pub a: usize,
+    pub b: usize,
+    pub size: usize,
+}
+
+/// What to do to a range of `a` to turn it into a range of `b`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tag {
+    Replace,
+    Delete,
+    Insert,
+    Equal,
+}
+
+impl Tag {
+    /// The name `difflib` gives the tag.
+    pub fn name(self) -> &'static str {
+        match self {
+            Tag::Replace => "replace",
+            Tag::Delete => "delete",
+            Tag::Insert => "insert",
+            Tag::Equal => "equal",
+        }
+    }
+}
+
+/// An edit: `a[a_start..a_end]` becomes `b[b_start..b_end]`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Opcode {
+    pub tag: Tag,
+    pub a_start: usize,
+    pub a_end: usize,
+    pub b_start: usize,
+    pub b_end: usize,
+}
+
+/// Compares the sequences `a` and `b`.
+pub struct SequenceMatcher<'a, T: 'a> {
+    a: &'a [T],
+    b: &'a [T],
+    /// The positions in `b` of each item, except junk and popular items.
+    b2j: HashMap<&'a T, Vec<usize>>,
+    /// The junk items of `b`.
+    junk: HashSet<&'a T>,
+    /// The items of `b` left out of `b2j` for being too common.
+    popular: HashSet<&'a T>,
+}
+
+impl<'a, T: Hash + Eq> SequenceMatcher<'a, T> {
+    /// A matcher with no junk and with the heuristic that treats items
+    /// making up more than 1% of a `b` of 200 or more items as junk, as
+    /// `SequenceMatcher(None, a, b)` has.
+    pub fn new(a: &'a [T], b: &'a [T]) -> Self {
+        SequenceMatcher::with_junk(a, b, |_| false, true)
+    }
+
+    /// A matcher for which the items of `b` for which `is_junk` is true
+    /// are junk, and with `autojunk`, the popular items of `b` too.
+    pub fn with_junk<F: FnMut(&T) -> bool>(
+        a: &'a [T],
+        b: &'a [T],
+        mut is_junk: F,
+        autojunk: bool,
+    ) -> Self {
+        let mut b2j: HashMap<&T, Vec<usize>> = HashMap::new();
+        for (index, item) in b.iter().enumerate() {
+            b2j.entry(item).or_default().push(index);
+        }
+        let junk: HashSet<&T> = b2j.keys().copied().filter(|item| is_junk(item)).collect();
+        for item in &junk {
+            b2j.remove(item);
+        }
+        let mut popular = HashSet::new();
+        if autojunk && b.len() >= 200 {
+            let limit = b.len() / 100 + 1;
+            popular = b2j
+                .iter()
+                .filter(|&(_, indices)| indices.len() > limit)
+                .map(|(&item, _)| item)
+                .collect();
+            for item in &popular {
+                b2j.remove(item);
+            }
+        }
+        SequenceMatcher {
+            a,
+            b,
+            b2j,
+            junk,
+            popular,
+        }
+    }
+
+    /// The junk items of `b`.
+    pub fn junk(&self) -> &HashSet<&'a T> {
+        &self.junk
+    }
+
+    /// The items of `b` treated as junk for being too common.
+    pub fn popular(&self) -> &HashSet<&'a T> {
+        &self.popular
+    }
+
+    /// The longest block of `a[a_low..a_high]` and `b[b_low..b_high]` that
+    /// matches, extended with any junk that matches on either side of it.
+    /// Of blocks as long, the one starting earliest in `a`, then in `b`,
+    /// is found. With no match, the block is empty and starts at `a_low`
+    /// and `b_low`.
+    pub fn find_longest_match(
+        &self,
+        a_low: usize,
+        a_high: usize,
+        b_low: usize,
+        b_high: usize,
+    ) -> Match {
+        let (a, b) = (self.a, self.b);
+        let (mut best_i, mut best_j, mut best_size) = (a_low, b_low, 0);
+        // The length of the longest match ending at each position of `b`,
+        // with the previous item of `a`.
+        let mut j2len: HashMap<usize, usize> = HashMap::new();
+        for (i, item) in a.iter().enumerate().take(a_high).skip(a_low) {
+            let mut new_j2len = HashMap::new();
+            if let Some(indices) = self.b2j.get(item) {
+                for &j in indices {
+                    if j < b_low {
+                        continue;
+                    }
+                    if j >= b_high {
+                        break;
+                    }
+                    let previous = if j == 0 { None } else { j2len.get(&(j - 1)) };
+                    let k = previous.copied().unwrap_or(0) + 1;
+                    new_j2len.insert(j, k);
+                    if k > best_size {
+                        best_i = i + 1 - k;
+                        best_j = j + 1 - k;
+                        best_size = k;
+                    }
+                }
+            }
+            j2len = new_j2len;
+        }
+        // Popular items are not in `b2j`, so extend the block with them,
+        // then with the junk next to it, so that a match of junk is only
+        // ever part of a match of other items.
+        for junk in [false, true] {
+            while best_i > a_low
+                && best_j > b_low
+                && self.junk.contains(&b[best_j - 1]) == junk
+                && a[best_i - 1] == b[best_j - 1]
+            {
+                best_i -= 1;
+                best_j -= 1;
+                best_size += 1;
+            }
+            while best_i + best_size < a_high
+                && best_j + best_size < b_high
+                && self.junk.contains(&b[best_j + best_size]) == junk
+                && a[best_i + best_size] == b[best_j + best_size]
+            {
+                best_size += 1;
+            }
+        }
+        Match {
+            a: best_i,
+            b: best_j,
+            size: best_size,
+        }
+    }
+
+    /// The blocks of `a` and `b` that match, in order and with none
+    /// adjacent, followed by an empty block at the ends of both.
+    pub fn matching_blocks(&self) -> Vec<Match> {
+        let (length_a, length_b) = (self.a.len(), self.b.len());
+        let mut queue = vec![(0, length_a, 0, length_b)];
+        let mut blocks = Vec::new();
+        while let Some((a_low, a_high, b_low, b_high)) = queue.pop() {
+            let found = self.find_longest_match(a_low, a_high, b_low, b_high);
+            if found.size == 0 {
+                continue;
+            }
+            blocks.push(found);
+            if a_low < found.a && b_low < found.b {
+                queue.push((a_low, found.a, b_low, found.b));
+            }
+            if found.a + found.size < a_high && found.b + found.size < b_high {
+                queue.push((found.a + found.size, a_high, found.b + found.size, b_high));
+            }
+        }
+        blocks.sort();
+        let mut merged: Vec<Match> = Vec::new();
+        for block in blocks {
+            match merged.last_mut() {
+                Some(last) if last.a + last.size == block.a && last.b + last.size == block.b => {
+                    last.size += block.size;
+                }
+                _ => merged.push(block),
+            }
+        }
+        merged.push(Match {
+            a: length_a,
+            b: length_b,
+            size: 0,
+        });
+        merged
+    }
+
+    /// The edits that turn `a` into `b`, covering both from start to end.
+    pub fn opcodes(&self) -> Vec<Opcode> {
+        let (mut i, mut j) = (0, 0);
+        let mut opcodes = Vec::new();
+        for block in self.matching_blocks() {
+            let tag = if i < block.a && j < block.b {
+                Some(Tag::Replace)
+            } else if i < block.a {
+                Some(Tag::Delete)
+            } else if j < block.b {
+                Some(Tag::Insert)
+            } else {
+                None
+            };
+            if let Some(tag) = tag {
+                opcodes.push(Opcode {
+                    tag,
+                    a_start: i,
+                    a_end: block.a,
+                    b_start: j,
+                    b_end: block.b,
+                });
+            }
+            i = block.a + block.size;
+            j = block.b + block.size;
+            if block.size > 0 {
+                opcodes.push(Opcode {
+                    tag: Tag::Equal,
+                    a_start: block.a,
+                    a_end: i,
+                    b_start: block.b,
+                    b_end: j,
+                });
+            }
+        }
+        opcodes
+    }
+
+    /// The opcodes in groups of changes with up to `context` equal items
+    /// around each, as the hunks of a diff show them.
+    pub fn grouped_opcodes(&self, context: usize) -> Vec<Vec<Opcode>> {
+        let mut codes = self.opcodes();
+        if codes.is_empty() {
+            codes.push(Opcode {
+                tag: Tag::Equal,
+                a_start: 0,
+                a_end: 1,
+                b_start: 0,
+                b_end: 1,
+            });
+        }
+        if codes[0].tag == Tag::Equal {
+            let first = &mut codes[0];
+            first.a_start = first.a_start.max(first.a_end.saturating_sub(context));
+            first.b_start = first.b_start.max(first.b_end.saturating_sub(context));
+        }
+        let last = codes.last_mut().unwrap();
+        if last.tag == Tag::Equal {
+            last.a_end = last.a_end.min(last.a_start + context);
+            last.b_end = last.b_end.min(last.b_start + context);
+        }
+        let mut groups = Vec::new();
+        let mut group = Vec::new();
+        for mut code in codes {
+            // A long run of equal items ends one group and starts the next.
+            if code.tag == Tag::Equal && code.a_end - code.a_start > 2 * context {
+                group.push(Opcode {
+                    a_end: code.a_end.min(code.a_start + context),
+                    b_end: code.b_end.min(code.b_start + context),
+                    ..code
+                });
+                groups.push(::std::mem::take(&mut group));
+                code.a_start = code.a_start.max(code.a_end.saturating_sub(context));
+                code.b_start = code.b_start.max(code.b_end.saturating_sub(context));
+            }
+            group.push(code);
+        }
+        if !(group.is_empty() || group.len() == 1 && group[0].tag == Tag::Equal) {
+            groups.push(group);
+        }
+        groups
+    }
+
+    /// How alike `a` and `b` are, from 0 to 1: twice the number of items
+    /// that match over the number of items in both.
+    pub fn ratio(&self) -> f64 {
+        let matches = self.matching_blocks().iter().map(|block| block.size).sum();
+        calculate_ratio(matches, self.a.len() + self.b.len())
+    }
+
+    /// An upper bound of `ratio`, from the items `a` and `b` have in common
+    /// regardless of their order.
+    pub fn quick_ratio(&self) -> f64 {
+        let mut available: HashMap<&T, isize> = HashMap::new();
+        for item in self.b {
+            *available.entry(item).or_insert(0) += 1;
+        }
+        let mut matches = 0;
+        for item in self.a {
+            if let Some(count) = available.get_mut(item) {
+                if *count > 0 {
+                    matches += 1;
+                }
+                *count -= 1;
+            }
+        }
+        calculate_ratio(matches, self.a.len() + self.b.len())
+    }
+
+    /// An upper bound of `quick_ratio`, from the lengths of `a` and `b`.
+    pub fn real_quick_ratio(&self) -> f64 {
+        let (length_a, length_b) = (self.a.len(), self.b.len());
+        calculate_ratio(length_a.min(length_b), length_a + length_b)
+    }
+}
+
+fn calculate_ratio(matches: usize, length: usize) -> f64 {
+    if length == 0 {
+        1.0
+    } else {
+        2.0 * matches as f64 / length as f64
+    }
+}
+
+/// The names and modification times of the files a diff compares, for its
+/// header.
+#[derive(Clone, Debug, Default)]
+pub struct DiffHeader<'a> {
+    pub from_file: &'a str,
+    pub to_file: &'a str,
+    pub from_date: &'a str,
+    pub to_date: &'a str,
+}
+
+/// The lines of a unified diff turning the lines `a` into the lines `b`,
+/// with `context` lines of context around each change, and `line_term`
+/// ending the lines of the header and of the hunk ranges. The lines of `a`
+/// and `b` are copied as they are, so they should keep their own line
+/// endings. Equal sequences have an empty diff.
+pub fn unified_diff<S: AsRef<str> + Hash + Eq>(
+    a: &[S],
+    b: &[S],
+    header: &DiffHeader,
+    context: usize,
+    line_term: &str,
+) -> Vec<String> {
+    let mut lines = Vec::new();
+    let matcher = SequenceMatcher::new(a, b);
+    for (index, group) in matcher.grouped_opcodes(context).iter().enumerate() {
+        if index == 0 {
+            lines.push(format!(
+                "--- {}{}{}",
+                header.from_file,
+                date_suffix(header.from_date),
+                line_term
+            ));
+            lines.push(format!(
+                "+++ {}{}{}",
+                header.to_file,
+                date_suffix(header.to_date),
+                line_term
+            ));
+        }
+        let (first, last) = (&group[0], &group[group.len() - 1]);
+        lines.push(format!(
+            "@@ -{} +{} @@{}",
+            unified_range(first.a_start, last.a_end),
+            unified_range(first.b_start, last.b_end),
+            line_term
+        ));
+        for code in group {
+            if code.tag == Tag::Equal {
+                for line in &a[code.a_start..code.a_end] {
+                    lines.push(format!(" {}", line.as_ref()));
+                }
+                continue;
+            }
+            if code.tag != Tag::Insert {
+                for line in &a[code.a_start..code.a_end] {
+                    lines.push(format!("-{}", line.as_ref()));
+                }
+            }
+            if code.tag != Tag::Delete {
+                for line in &b[code.b_start..code.b_end] {
+                    lines.push(format!("+{}", line.as_ref()));
+                }
+            }
+        }
+    }
+    lines
+}
+
+fn date_suffix(date: &str) -> String {
+    if date.is_empty() {
+        String::new()
+    } else {
+        format!("\t{}", date)
+    }
+}
+
+/// The range `start..stop` of a hunk, as `diff -u` shows it: the first
+/// line counting from 1 and the number of lines, unless that is 1. An empty
+/// range shows the line before it.
+fn unified_range(start: usize, stop: usize) -> String {
+    let length = stop - start;
+    match length {
+        1 => (start + 1).to_string(),
+        0 => format!("{},0", start),
+        _ => format!("{},{}", start + 1, length),
+    }
+}
@@ -0,0 +1,143 @@
+mod strings;
+mod verify;
+mod wrap;
+
+pub use self::strings::{normalize_string, QuoteStyle};
+pub use self::verify::{verify, verify_file, VerifyError};
+
+use tokenizer::{strip_bom, Token, TokenError, TokenType, Tokenizer, BOM};
+
+use self::wrap::wrap_long_lines;
+
+const MAX_BLANK_LINES: usize = 2;
+
+/// Options controlling how source is formatted.
+#[derive(Debug, Clone)]
+pub struct FormatConfig {
+    /// The length, in characters, that lines are wrapped to where possible.
+    pub line_length: usize,
+    pub quote_style: QuoteStyle,
+    /// Lowercase string prefixes and drop the redundant `u` prefix.
+    pub normalize_string_prefixes: bool,
+    /// Put each element of a collection, call or signature that ends with a
+    /// comma on a line of its own, even if it would fit on one line.
+    pub magic_trailing_comma: bool,
+}
+
+impl Default for FormatConfig {
+    fn default() -> FormatConfig {
+        FormatConfig {
+            line_length: 88,
+            quote_style: QuoteStyle::Double,
+            normalize_string_prefixes: true,
+            magic_trailing_comma: true,
+        }
+    }
+}
+
+/// Formats Python source with the default configuration.
+pub fn format(source: &str) -> Result<String, TokenError> {
+    format_with_config(source, &FormatConfig::default())
+}
+
+/// Formats Python source by rewriting the whitespace between tokens and the
+/// spelling of string literals.
+///
+/// Every other token is copied through unchanged, so the result always has
+/// the same meaning as the input. Trailing whitespace is removed, runs of
+/// blank lines are limited to two, blank lines at the start and end of the
+/// file are dropped, the last line is terminated and long lines are wrapped
+/// inside their brackets, as are brackets whose contents end with a comma.
+/// Line endings and a byte order mark are preserved.
+pub fn format_with_config(source: &str, config: &FormatConfig) -> Result<String, TokenError> {
+    let text = strip_bom(source);
+    let tokenizer = Tokenizer::new(text);
+    let tokens = tokenizer.collect::<Result<Vec<Token>, TokenError>>()?;
+    let line_ending = tokens
+        .iter()
+        .find(|token| token.kind.is_newline() && !token.value.is_empty())
+        .map_or("\n", |token| token.value.as_str());
+
+    let mut output = String::with_capacity(source.len());
+    let mut position = 0;
+    let mut line_empty = true;
+    let mut seen_content = false;
+    let mut blank_lines = 0;
+    let mut held = String::new();
+
+    for token in &tokens {
+        let gap = &text[position..token.span.start];
+        position = token.span.end;
+        match token.kind {
+            kind if kind.is_newline() && line_empty && is_blank(gap) => {
+                blank_lines += 1;
+                if seen_content && blank_lines <= MAX_BLANK_LINES {
+                    held.push_str(&token.value);
+                }
+            }
+            kind if kind.is_newline() => {
+                output.push_str(&strip_trailing_whitespace(gap));
+                output.push_str(if token.value.is_empty() {
+                    line_ending
+                } else {
+                    &token.value
+                });
+                line_empty = true;
+                blank_lines = 0;
+            }
+            TokenType::EndMarker => {
+                if !is_blank(gap) {
+                    output.push_str(&held);
+                    output.push_str(&strip_trailing_whitespace(gap));
+                }
+            }
+            _ => {
+                output.push_str(&held);
+                held.clear();
+                output.push_str(&strip_line_ends(gap));
+                match token.kind {
+                    TokenType::Comment => output.push_str(token.value.trim_end()),
+                    TokenType::String => output.push_str(&normalize_string(&token.value, config)),
+                    _ => output.push_str(&token.value),
+                }
+                if token.kind != TokenType::Indent && token.kind != TokenType::Dedent {
+                    line_empty = false;
+                    seen_content = true;
+                }
+            }
+        }
+    }
+    let mut output = wrap_long_lines(&output, config)?;
+    if source.starts_with(BOM) {
+        output.insert(0, BOM);
+    }
+    Ok(output)
+}
+
+fn is_blank(text: &str) -> bool {
+    text.chars().all(|c| c == ' ' || c == '\t' || c == '\x0c')
+}
+
+/// Removes spaces and tabs before each line break in `text`.
+fn strip_line_ends(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut run_start = 0;
+    for (index, c) in text.char_indices() {
+        match c {
+            ' ' | '\t' | '\x0c' => continue,
+            '\n' | '\r' => {}
+            _ => result.push_str(&text[run_start..index]),
+        }
+        result.push(c);
+        run_start = index + c.len_utf8();
+    }
+    result.push_str(&text[run_start..]);
+    result
+}
+
+fn strip_trailing_whitespace(text: &str) -> String {
+    let mut result = strip_line_ends(text);
+    let length = result.trim_end_matches([' ', '\t', '\x0c']).len();
+    result.truncate(length);
+    result
+}
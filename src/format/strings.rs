@@ -0,0 +1,186 @@
+use std::borrow::Cow;
+
+use super::FormatConfig;
+
+/// The quote character string literals are rewritten to use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    Preserve,
+    Double,
+    Single,
+}
+
+impl QuoteStyle {
+    fn quote(self) -> Option<char> {
+        match self {
+            QuoteStyle::Preserve => None,
+            QuoteStyle::Double => Some('"'),
+            QuoteStyle::Single => Some('\''),
+        }
+    }
+}
+
+/// A string literal token split into its parts.
+struct Literal<'a> {
+    prefix: &'a str,
+    quote: char,
+    triple: bool,
+    body: &'a str,
+}
+
+impl<'a> Literal<'a> {
+    fn parse(value: &'a str) -> Option<Literal<'a>> {
+        let prefix_length = value.find(['\'', '"'])?;
+        let (prefix, quoted) = value.split_at(prefix_length);
+        let quote = quoted.chars().next()?;
+        let delimiter = quote.to_string().repeat(3);
+        let triple = quoted.len() >= 6 && quoted.starts_with(&delimiter[..]);
+        let delimiter_length = if triple { 3 } else { 1 };
+        let body = quoted.get(delimiter_length..quoted.len().checked_sub(delimiter_length)?)?;
+        Some(Literal {
+            prefix,
+            quote,
+            triple,
+            body,
+        })
+    }
+
+    fn is_raw(&self) -> bool {
+        self.prefix.contains(['r', 'R'])
+    }
+
+    fn is_formatted(&self) -> bool {
+        self.prefix.contains(['f', 'F'])
+    }
+}
+
+/// Rewrites a string literal token according to the configured prefix and
+/// quote rules, leaving the value it evaluates to unchanged.
+///
+/// Prefixes are lowercased and the redundant `u` dropped; `R` keeps its case
+/// since some highlighters treat it differently. Quotes are switched only
+/// when that does not add escapes. Raw strings and f-strings are switched
+/// only when their body contains no quote of the new kind, since escaping
+/// would change a raw string's value or an f-string's replacement fields.
+pub fn normalize_string<'a>(value: &'a str, config: &FormatConfig) -> Cow<'a, str> {
+    let literal = match Literal::parse(value) {
+        Some(literal) => literal,
+        None => return Cow::Borrowed(value),
+    };
+
+    let prefix: Cow<str> = if config.normalize_string_prefixes {
+        literal
+            .prefix
+            .chars()
+            .filter(|&c| c != 'u' && c != 'U')
+            .map(|c| if c == 'R' { c } else { c.to_ascii_lowercase() })
+            .collect::<String>()
+            .into()
+    } else {
+        literal.prefix.into()
+    };
+
+    let (quote, body) = match config.quote_style.quote() {
+        Some(quote) if quote != literal.quote => match requote(&literal, quote) {
+            Some(body) => (quote, body),
+            None => (literal.quote, Cow::Borrowed(literal.body)),
+        },
+        _ => (literal.quote, Cow::Borrowed(literal.body)),
+    };
+
+    if prefix == literal.prefix && quote == literal.quote {
+        return Cow::Borrowed(value);
+    }
+    let delimiter = if literal.triple {
+        quote.to_string().repeat(3)
+    } else {
+        quote.to_string()
+    };
+    Cow::Owned(format!("{}{}{}{}", prefix, delimiter, body, delimiter))
+}
+
+/// The body of `literal` rewritten for `quote` delimiters, or `None` if it is
+/// better left alone.
+fn requote<'a>(literal: &Literal<'a>, quote: char) -> Option<Cow<'a, str>> {
+    let body = literal.body;
+    if literal.triple {
+        let delimiter = quote.to_string().repeat(3);
+        if body.contains(&delimiter[..]) || body.ends_with(quote) {
+            return None;
+        }
+        return Some(Cow::Borrowed(body));
+    }
+    if literal.is_raw() || literal.is_formatted() {
+        if body.contains(quote) {
+            return None;
+        }
+        return Some(Cow::Borrowed(body));
+    }
+
+    let mut rewritten = String::with_capacity(body.len());
+    let mut old_escapes = 0;
+    let mut new_escapes = 0;
+    let mut chars = body.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(escaped) if escaped == literal.quote => {
+                    old_escapes += 1;
+                    rewritten.push(escaped);
+                }
+                Some(escaped) => {
+                    rewritten.push(c);
+                    rewritten.push(escaped);
+                }
+                None => rewritten.push(c),
+            }
+        } else if c == quote {
+            new_escapes += 1;
+            rewritten.push('\\');
+            rewritten.push(c);
+        } else {
+            rewritten.push(c);
+        }
+    }
+    if new_escapes > old_escapes {
+        None
+    } else {
+        Some(Cow::Owned(rewritten))
+    }
+}
+
+/// Reduces a string literal token to what `normalize_string` preserves: the
+/// kind of literal and its body with quote escapes resolved. Two literals with
+/// the same key evaluate to the same value.
+pub fn literal_key(value: &str) -> Option<(String, String)> {
+    let literal = Literal::parse(value)?;
+    let mut prefix: Vec<char> = literal
+        .prefix
+        .chars()
+        .map(|c| c.to_ascii_lowercase())
+        .filter(|&c| c != 'u')
+        .collect();
+    prefix.sort();
+    let prefix = prefix.into_iter().collect();
+    if literal.is_raw() {
+        return Some((prefix, literal.body.to_string()));
+    }
+
+    let mut body = String::with_capacity(literal.body.len());
+    let mut chars = literal.body.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some(escaped) if escaped == '\'' || escaped == '"' => body.push(escaped),
+                Some(escaped) => {
+                    body.push(c);
+                    body.push(escaped);
+                }
+                None => body.push(c),
+            }
+        } else {
+            body.push(c);
+        }
+    }
+    Some((prefix, body))
+}
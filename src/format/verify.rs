@@ -0,0 +1,191 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::mem;
+use std::path::Path;
+
+use ast::{unparse, Module, Stmt};
+use parser;
+use tokenizer::{decode, strip_bom, tokenize, Location, SourceError, Token, TokenError, TokenType};
+
+use super::strings::literal_key;
+use super::{format_with_config, FormatConfig};
+
+/// Why formatted output could not be trusted.
+#[derive(Debug)]
+pub enum VerifyError {
+    /// The original source could not be read or tokenized.
+    Source(SourceError),
+    /// The formatted output no longer tokenizes.
+    Invalid(TokenError),
+    /// Formatting the output a second time changed it again.
+    Unstable { line: usize },
+    /// The formatted output has different significant tokens from the
+    /// original, first diverging at `location` in the original.
+    Changed {
+        location: Location,
+        expected: String,
+        found: String,
+    },
+    /// The formatted output parses to a different syntax tree from the
+    /// original, first diverging in the top-level statement at `line` of
+    /// the original.
+    TreeChanged { line: usize },
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VerifyError::Source(ref err) => write!(f, "{}", err),
+            VerifyError::Invalid(ref err) => write!(f, "formatted code is invalid: {}", err),
+            VerifyError::Unstable { line } => write!(
+                f,
+                "formatting is not stable: a second pass changes line {}",
+                line
+            ),
+            VerifyError::Changed {
+                location,
+                ref expected,
+                ref found,
+            } => write!(
+                f,
+                "formatting changed the code at {}: expected {}, found {}",
+                location, expected, found
+            ),
+            VerifyError::TreeChanged { line } => write!(
+                f,
+                "formatting changed the syntax tree of the statement at line {}",
+                line
+            ),
+        }
+    }
+}
+
+impl Error for VerifyError {}
+
+impl From<SourceError> for VerifyError {
+    fn from(err: SourceError) -> VerifyError {
+        VerifyError::Source(err)
+    }
+}
+
+impl From<TokenError> for VerifyError {
+    fn from(err: TokenError) -> VerifyError {
+        VerifyError::Source(SourceError::Token(err))
+    }
+}
+
+/// Formats `source` and checks that the result is safe to write back: it
+/// must carry the same significant tokens as the original and, if the
+/// original parses, the same syntax tree, locations aside, and formatting
+/// it again must leave it unchanged. Returns the formatted source.
+pub fn verify(source: &str, config: &FormatConfig) -> Result<String, VerifyError> {
+    let original = tokenize(strip_bom(source))?;
+    let formatted = format_with_config(source, config)?;
+
+    let reformatted = format_with_config(&formatted, config).map_err(VerifyError::Invalid)?;
+    if reformatted != formatted {
+        let line = formatted
+            .lines()
+            .zip(reformatted.lines())
+            .position(|(a, b)| a != b)
+            .unwrap_or_else(|| formatted.lines().count().min(reformatted.lines().count()));
+        return Err(VerifyError::Unstable { line: line + 1 });
+    }
+
+    let output = tokenize(strip_bom(&formatted)).map_err(VerifyError::Invalid)?;
+    let mut original = significant(&original);
+    let mut output = significant(&output);
+    loop {
+        match (original.next(), output.next()) {
+            (None, None) => break,
+            (Some(a), Some(b)) if same_token(a, b) => {}
+            (a, b) => {
+                return Err(VerifyError::Changed {
+                    location: a.map_or_else(Location::default, |token| token.start),
+                    expected: describe(a),
+                    found: describe(b),
+                })
+            }
+        }
+    }
+    same_tree(source, &formatted)?;
+    Ok(formatted)
+}
+
+/// Reads and decodes a source file, then runs `verify` on it.
+pub fn verify_file<P: AsRef<Path>>(path: P, config: &FormatConfig) -> Result<String, VerifyError> {
+    let bytes = fs::read(path).map_err(SourceError::from)?;
+    let decoded = decode(&bytes)?;
+    verify(&decoded.text, config)
+}
+
+/// Checks that `formatted` parses to the tree `source` does, comparing
+/// their unparsed code, which leaves out locations. Nothing is compared for
+/// a source that does not parse.
+fn same_tree(source: &str, formatted: &str) -> Result<(), VerifyError> {
+    let original = match parser::parse(strip_bom(source)) {
+        Ok(module) => module,
+        Err(_) => return Ok(()),
+    };
+    let output = parser::parse(strip_bom(formatted)).ok();
+    if output
+        .as_ref()
+        .is_some_and(|output| unparse(output) == unparse(&original))
+    {
+        return Ok(());
+    }
+    let output = output.map_or_else(Vec::new, |module| module.body);
+    let statement = |stmt: &Stmt| {
+        unparse(&Module {
+            body: vec![stmt.clone()],
+            type_ignores: Vec::new(),
+        })
+    };
+    let line = original
+        .body
+        .iter()
+        .enumerate()
+        .find(|&(i, stmt)| output.get(i).map(&statement) != Some(statement(stmt)))
+        .map_or_else(
+            || original.body.last().map_or(1, |stmt| stmt.end.line),
+            |(_, stmt)| stmt.start.line,
+        );
+    Err(VerifyError::TreeChanged { line })
+}
+
+/// The tokens that carry meaning: comments and the newlines of blank or
+/// comment-only lines are left out.
+fn significant<'a>(tokens: &'a [Token]) -> impl Iterator<Item = &'a Token> + 'a {
+    let mut after_newline = true;
+    tokens.iter().filter(move |token| match token.kind {
+        TokenType::Comment | TokenType::NewlineNonLogical => false,
+        TokenType::NewlineLogical => !mem::replace(&mut after_newline, true),
+        TokenType::Indent | TokenType::Dedent => true,
+        _ => {
+            after_newline = false;
+            true
+        }
+    })
+}
+
+fn same_token(a: &Token, b: &Token) -> bool {
+    match a.kind {
+        TokenType::NewlineLogical
+        | TokenType::Indent
+        | TokenType::Dedent
+        | TokenType::EndMarker => a.kind == b.kind,
+        TokenType::String => {
+            b.kind == TokenType::String && literal_key(&a.value) == literal_key(&b.value)
+        }
+        _ => a.kind == b.kind && a.value == b.value,
+    }
+}
+
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        Some(token) if token.value.is_empty() => token.kind.to_string(),
+        Some(token) => format!("{} {:?}", token.kind, token.value),
+        None => "end of file".to_string(),
+    }
+}
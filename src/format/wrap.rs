@@ -0,0 +1,414 @@
+use std::collections::HashSet;
+use std::ops::Range;
+
+use cst::{Element, Node, Tree};
+use parser::{is_keyword, Precedence};
+use tokenizer::{tokenize, Token, TokenError, TokenType};
+
+use super::FormatConfig;
+
+const CONTINUATION_INDENT: &str = "    ";
+
+/// Splits logical lines that are longer than the configured line length.
+///
+/// Only lines without comments that sit on a single physical line are
+/// touched, and they are only broken inside brackets, so only the `NL`
+/// tokens of the new line breaks are added. A line is split at one of its
+/// bracket pairs, with the contents moved to an indented line of their own.
+/// Contents that are still too long are split after each comma, or else
+/// before each operator of the loosest precedence present, recursively.
+///
+/// With `magic_trailing_comma`, a bracket pair whose contents end with a
+/// comma is split one element per line however short the line is, and
+/// even if the line already spans several, as long as it has no comments.
+/// The concrete syntax tree tells which commas those are: one that makes a
+/// one-element tuple, as in `(a,)` or `x[a,]`, does not count, and code
+/// that does not parse has none.
+pub fn wrap_long_lines(text: &str, config: &FormatConfig) -> Result<String, TokenError> {
+    let tokens = tokenize(text)?;
+    let magic = if config.magic_trailing_comma {
+        magic_brackets(text)
+    } else {
+        HashSet::new()
+    };
+    let mut output = String::with_capacity(text.len());
+    let mut copied = 0;
+    let mut start = 0;
+    let mut depth = 0;
+    for (index, token) in tokens.iter().enumerate() {
+        depth += token.bracket_depth_change();
+        // A line break inside brackets does not end the line.
+        if !token.kind.is_newline() || depth > 0 {
+            continue;
+        }
+        let line = &tokens[start..index];
+        start = index + 1;
+        let first = match line
+            .iter()
+            .position(|t| t.kind != TokenType::Indent && t.kind != TokenType::Dedent)
+        {
+            Some(first) => first,
+            None => continue,
+        };
+        let line = &line[first..];
+        let line_start = text[..line[0].span.start]
+            .rfind(['\n', '\r'])
+            .map_or(0, |i| i + 1);
+        let physical = &text[line_start..token.span.start];
+        if line.iter().any(|t| t.kind == TokenType::Comment) {
+            continue;
+        }
+        let line: Vec<Token> = line
+            .iter()
+            .filter(|t| t.kind != TokenType::NewlineNonLogical)
+            .cloned()
+            .collect();
+        let wrapper = Wrapper::new(text, &line, &magic, config);
+        let exploded = line[line.len() - 1].end.line != line[0].start.line;
+        if !wrapper.magic.contains(&true)
+            && (exploded || physical.chars().count() <= config.line_length)
+        {
+            continue;
+        }
+
+        let mut lines = Vec::new();
+        let indent = &text[line_start..line[0].span.start];
+        wrapper.layout(0..line.len(), indent.to_string(), false, &mut lines);
+        if lines.len() > 1 {
+            let line_ending = if token.value.is_empty() {
+                "\n"
+            } else {
+                token.value.as_str()
+            };
+            output.push_str(&text[copied..line_start]);
+            output.push_str(&lines.join(line_ending));
+            copied = token.span.start;
+        }
+    }
+    output.push_str(&text[copied..]);
+    Ok(output)
+}
+
+struct Wrapper<'a> {
+    text: &'a str,
+    tokens: &'a [Token],
+    /// For each opening bracket, the index of its closing bracket.
+    closing: Vec<Option<usize>>,
+    /// For each opening bracket, whether its contents end with a magic
+    /// trailing comma.
+    magic: Vec<bool>,
+    limit: usize,
+}
+
+impl<'a> Wrapper<'a> {
+    fn new(
+        text: &'a str,
+        tokens: &'a [Token],
+        magic: &HashSet<usize>,
+        config: &FormatConfig,
+    ) -> Wrapper<'a> {
+        let mut closing = vec![None; tokens.len()];
+        let mut open = Vec::new();
+        for (index, token) in tokens.iter().enumerate() {
+            if token.kind != TokenType::Operator {
+                continue;
+            }
+            match token.value.as_str() {
+                "(" | "[" | "{" => open.push(index),
+                ")" | "]" | "}" => {
+                    if let Some(opening) = open.pop() {
+                        closing[opening] = Some(index);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Wrapper {
+            text,
+            tokens,
+            closing,
+            magic: tokens
+                .iter()
+                .map(|token| magic.contains(&token.span.start))
+                .collect(),
+            limit: config.line_length,
+        }
+    }
+
+    /// Whether `range` is the contents of a bracket pair with a magic
+    /// trailing comma.
+    fn is_magic_contents(&self, range: Range<usize>) -> bool {
+        range.start > 0
+            && self.closing[range.start - 1] == Some(range.end)
+            && self.magic[range.start - 1]
+    }
+
+    /// Whether `range` is, or holds, the contents of a bracket pair with a
+    /// magic trailing comma.
+    fn has_magic(&self, range: Range<usize>) -> bool {
+        self.is_magic_contents(range.clone()) || self.magic[range].contains(&true)
+    }
+
+    /// The tokens in `range` on one line, spaced as in the source. A line
+    /// break between two tokens becomes a space, or nothing inside the
+    /// brackets and before a comma.
+    fn render(&self, range: Range<usize>) -> String {
+        let mut rendered = String::new();
+        for index in range.clone() {
+            if index > range.start {
+                let (before, token) = (&self.tokens[index - 1], &self.tokens[index]);
+                let gap = &self.text[before.span.end..token.span.start];
+                if !gap.contains(['\n', '\r']) {
+                    rendered.push_str(gap);
+                } else if !(self.closing[index - 1].is_some()
+                    || token.is_operator(")")
+                    || token.is_operator("]")
+                    || token.is_operator("}")
+                    || token.is_operator(","))
+                {
+                    rendered.push(' ');
+                }
+            }
+            rendered.push_str(&self.tokens[index].value);
+        }
+        rendered
+    }
+
+    fn layout(&self, range: Range<usize>, indent: String, nested: bool, lines: &mut Vec<String>) {
+        let flat = format!("{}{}", indent, self.render(range.clone()));
+        let fits = flat.chars().count() <= self.limit;
+        let forced = self.has_magic(range.clone());
+        if fits && !forced {
+            lines.push(flat);
+            return;
+        }
+
+        // A magic trailing comma alone only splits the contents at their
+        // commas and at the bracket pairs holding one.
+        if nested {
+            let splits = if fits {
+                self.commas(range.clone())
+            } else {
+                self.split_points(range.clone())
+            };
+            if !splits.is_empty() {
+                let mut start = range.start;
+                for split in splits.into_iter().chain(Some(range.end)) {
+                    self.layout(start..split, indent.clone(), true, lines);
+                    start = split;
+                }
+                return;
+            }
+        }
+
+        let group = if fits {
+            self.groups(range.clone())
+                .into_iter()
+                .find(|&open| self.has_magic(open + 1..self.closing[open].unwrap()))
+        } else {
+            self.group_to_split(range.clone(), &indent)
+        };
+        if let Some(open) = group {
+            let close = self.closing[open].unwrap();
+            lines.push(format!("{}{}", indent, self.render(range.start..open + 1)));
+            let body_indent = format!("{}{}", indent, CONTINUATION_INDENT);
+            self.layout(open + 1..close, body_indent, true, lines);
+            lines.push(format!("{}{}", indent, self.render(close..range.end)));
+            return;
+        }
+
+        lines.push(flat);
+    }
+
+    /// Opening brackets of the non-empty bracket pairs at the top level of
+    /// `range`, in order.
+    fn groups(&self, range: Range<usize>) -> Vec<usize> {
+        let mut groups = Vec::new();
+        let mut index = range.start;
+        while index < range.end {
+            match self.closing[index] {
+                Some(close) if close < range.end => {
+                    if close > index + 1 {
+                        groups.push(index);
+                    }
+                    index = close + 1;
+                }
+                _ => index += 1,
+            }
+        }
+        groups
+    }
+
+    /// Picks the bracket pair to split at: the leftmost one whose closing
+    /// bracket and the rest of the line fit, falling back to the last one.
+    fn group_to_split(&self, range: Range<usize>, indent: &str) -> Option<usize> {
+        let groups = self.groups(range.clone());
+        groups
+            .iter()
+            .cloned()
+            .find(|&open| {
+                let close = self.closing[open].unwrap();
+                indent.chars().count() + self.render(close..range.end).chars().count() <= self.limit
+            })
+            .or_else(|| groups.last().cloned())
+    }
+
+    /// Where to break the contents of a bracket pair: after each top-level
+    /// comma, else before each `for` and `if` clause of a comprehension, else
+    /// before each binary operator of the loosest precedence present.
+    fn split_points(&self, range: Range<usize>) -> Vec<usize> {
+        let commas = self.commas(range.clone());
+        if !commas.is_empty() {
+            return commas;
+        }
+        let top_level = self.top_level(range.clone());
+
+        let mut clauses = Vec::new();
+        for &index in &top_level {
+            let token = &self.tokens[index];
+            if token.is_name("for") {
+                if index > range.start && self.tokens[index - 1].is_name("async") {
+                    clauses.push(index - 1);
+                } else {
+                    clauses.push(index);
+                }
+            } else if token.is_name("if") && !clauses.is_empty() {
+                clauses.push(index);
+            }
+        }
+        clauses.retain(|&index| index > range.start);
+        if !clauses.is_empty() {
+            return clauses;
+        }
+
+        let mut loosest = None;
+        let mut operators = Vec::new();
+        for &index in &top_level {
+            if self.tokens[index].is_name("lambda") {
+                break;
+            }
+            if index == range.start {
+                continue;
+            }
+            let precedence = match self.binary_precedence(index) {
+                Some(precedence) => precedence,
+                None => continue,
+            };
+            if loosest.is_none_or(|loosest| precedence < loosest) {
+                loosest = Some(precedence);
+                operators.clear();
+            }
+            if loosest == Some(precedence) {
+                operators.push(index);
+            }
+        }
+        operators
+    }
+
+    /// The positions just after each top-level comma in `range`, other than
+    /// a trailing one and those in a lambda's parameters.
+    fn commas(&self, range: Range<usize>) -> Vec<usize> {
+        let mut commas = Vec::new();
+        let mut in_lambda = false;
+        for index in self.top_level(range.clone()) {
+            let token = &self.tokens[index];
+            if token.is_name("lambda") {
+                in_lambda = true;
+            } else if in_lambda && token.is_operator(":") {
+                in_lambda = false;
+            } else if !in_lambda && token.is_operator(",") && index + 1 < range.end {
+                commas.push(index + 1);
+            }
+        }
+        commas
+    }
+
+    /// Indices of the tokens in `range` that are not inside a nested bracket
+    /// pair. The brackets themselves are included.
+    fn top_level(&self, range: Range<usize>) -> Vec<usize> {
+        let mut indices = Vec::new();
+        let mut index = range.start;
+        while index < range.end {
+            indices.push(index);
+            match self.closing[index] {
+                Some(close) if close < range.end => {
+                    indices.push(close);
+                    index = close + 1;
+                }
+                _ => index += 1,
+            }
+        }
+        indices
+    }
+
+    /// The precedence of the token at `index` if it starts a binary operator.
+    fn binary_precedence(&self, index: usize) -> Option<Precedence> {
+        let token = &self.tokens[index];
+        if !ends_operand(&self.tokens[index - 1]) {
+            return None;
+        }
+        match token.kind {
+            TokenType::Operator => Precedence::of_binary_operator(&token.value),
+            TokenType::Name if token.value == "not" => {
+                let next = self.tokens.get(index + 1);
+                if next.is_some_and(|next| next.is_name("in")) {
+                    Precedence::of_binary_operator("not in")
+                } else {
+                    None
+                }
+            }
+            TokenType::Name => Precedence::of_binary_operator(&token.value),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a binary operator may follow `token`.
+fn ends_operand(token: &Token) -> bool {
+    match token.kind {
+        TokenType::Name => {
+            !is_keyword(&token.value) || matches!(token.value.as_str(), "True" | "False" | "None")
+        }
+        TokenType::Number | TokenType::String => true,
+        TokenType::Operator => matches!(token.value.as_str(), ")" | "]" | "}" | "..."),
+        _ => false,
+    }
+}
+
+/// The byte offsets in `text` of the opening brackets whose contents end
+/// with a magic trailing comma.
+fn magic_brackets(text: &str) -> HashSet<usize> {
+    let mut magic = HashSet::new();
+    if let Ok(tree) = Tree::parse(text) {
+        collect_magic(tree.root(), &mut 0, &mut magic);
+    }
+    magic
+}
+
+/// Adds the magic brackets of `node` and the nodes in it, which starts
+/// `offset` bytes into the text, and moves `offset` past it.
+fn collect_magic(node: &Node, offset: &mut usize, magic: &mut HashSet<usize>) {
+    let mut starts = Vec::with_capacity(node.children().len());
+    for child in node.children() {
+        match *child {
+            Element::Node(ref child) => {
+                starts.push(*offset + child.leading_trivia().len());
+                collect_magic(child, offset, magic);
+            }
+            Element::Token(ref token) => {
+                starts.push(*offset + token.leading.len());
+                *offset += token.leading.len() + token.text.len();
+            }
+        }
+    }
+    for (open, close) in node.trailing_commas() {
+        // The comma ends either the node or the child before the bracket.
+        let owner = match node.children()[close - 1] {
+            Element::Node(ref child) => child,
+            Element::Token(_) => node,
+        };
+        if owner.kind() != "Tuple" || owner.nodes().count() > 1 {
+            magic.insert(starts[open]);
+        }
+    }
+}
@@ -0,0 +1,553 @@
+//! A high-level interface for Rust programs that embed rustpy for
+//! scripting.
+//!
+//! An `Interpreter` runs code in its `__main__` module and hands values
+//! back as `Value`s, which convert to and from the Rust types they stand
+//! for:
+//!
+//! ```text
+//! let mut python = Interpreter::new();
+//! python.exec("def area(w, h):\n    return w * h\n")?;
+//! let area: i64 = python.call("area", &[3.into(), 4.into()])?.try_into()?;
+//! ```
+//!
+//! Values are copied in both directions, so a list a script changes later
+//! is not changed in a `Value` taken from it. `Interpreter::vm` reaches the
+//! `Vm` underneath for anything else.
+//!
+//! Rust functions and closures become Python builtins with `register`, or
+//! `register_functions!` for several named functions at once:
+//!
+//! ```text
+//! python.register("clamp", |x: f64, low: f64, high: f64| x.max(low).min(high));
+//! python.register("parse", |text: String| -> Result<i64> {
+//!     text.parse().map_err(|_| Error::exception("ValueError", "not a number"))
+//! });
+//! ```
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::hash::Hash;
+
+use vm::{object_id, Args, NativeModule, ObjectRef, Payload, PyResult, Vm};
+
+mod native;
+
+pub use self::native::{new_function, NativeFunction, ReturnValue};
+
+/// A Python value with a Rust counterpart.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    None,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bytes(Vec<u8>),
+    List(Vec<Value>),
+    Tuple(Vec<Value>),
+    /// A dict's items, in its order.
+    Dict(Vec<(Value, Value)>),
+}
+
+impl Value {
+    /// The name of the Python type the value stands for.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::None => "NoneType",
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Str(_) => "str",
+            Value::Bytes(_) => "bytes",
+            Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Dict(_) => "dict",
+        }
+    }
+}
+
+/// Why running code, or converting a value, failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// An exception the code did not handle.
+    Exception {
+        /// The name of the exception's class, such as `ValueError`.
+        class: String,
+        /// The `str` of the exception.
+        message: String,
+        /// The report the interpreter prints for it, with its traceback.
+        report: String,
+    },
+    /// A value that has no Rust counterpart, or not that of the type asked
+    /// for.
+    Conversion(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Exception {
+                ref class,
+                ref message,
+                ..
+            } if message.is_empty() => f.write_str(class),
+            Error::Exception {
+                ref class,
+                ref message,
+                ..
+            } => write!(f, "{}: {}", class, message),
+            Error::Conversion(ref message) => f.write_str(message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl Error {
+    /// An exception of the builtin class `class`, for a native function to
+    /// raise.
+    pub fn exception<C: Into<String>, M: Into<String>>(class: C, message: M) -> Error {
+        Error::Exception {
+            class: class.into(),
+            message: message.into(),
+            report: String::new(),
+        }
+    }
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+/// An interpreter with its own `__main__` module and `sys.modules`. Like
+/// its `Vm`, it stays on the thread that made it, but the `Value`s it
+/// gives can go anywhere.
+pub struct Interpreter {
+    vm: Vm,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter { vm: Vm::new() }
+    }
+
+    /// The VM the interpreter runs code in.
+    pub fn vm(&mut self) -> &mut Vm {
+        &mut self.vm
+    }
+
+    /// Runs statements in the `__main__` module, for the functions and
+    /// variables they define there.
+    pub fn exec(&mut self, source: &str) -> Result<()> {
+        let result = self.vm.run_source(source, "<string>");
+        self.check(result).map(|_| ())
+    }
+
+    /// The value of an expression, evaluated in the `__main__` module.
+    pub fn eval(&mut self, expression: &str) -> Result<Value> {
+        let result = self.vm.eval_source(expression);
+        let value = self.check(result)?;
+        self.to_value(&value)
+    }
+
+    /// Calls the function `name` with `args`, and returns its result. The
+    /// name is that of a variable of `__main__` or a builtin, followed by
+    /// any attributes, as in `os.path.join` for an imported `os`.
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value> {
+        let function = self.lookup(name)?;
+        let mut objects = Vec::with_capacity(args.len());
+        for arg in args {
+            objects.push(self.to_object(arg)?);
+        }
+        let result = self.vm.call(&function, Args::new(objects));
+        let value = self.check(result)?;
+        self.to_value(&value)
+    }
+
+    /// The value of the variable or attribute `name`, looked up as `call`
+    /// looks up functions.
+    pub fn get(&mut self, name: &str) -> Result<Value> {
+        let object = self.lookup(name)?;
+        self.to_value(&object)
+    }
+
+    /// Sets the variable `name` of `__main__`.
+    pub fn set<V: Into<Value>>(&mut self, name: &str, value: V) -> Result<()> {
+        let object = self.to_object(&value.into())?;
+        let globals = self.vm.main_module().dict().unwrap().clone();
+        self.vm
+            .dict_set_str(globals.as_dict().unwrap(), name, object);
+        Ok(())
+    }
+
+    /// Registers the module written in Rust `module`, to be imported as
+    /// `name`, as `Vm::add_native_module` does.
+    pub fn add_module<M: NativeModule + 'static>(&mut self, name: &str, module: M) {
+        self.vm.add_native_module(name, module);
+    }
+
+    /// Makes the Rust function or closure `function` the builtin `name`.
+    /// Python code calls it with positional arguments only, one for each of
+    /// its parameters.
+    pub fn register<Params, F>(&mut self, name: &'static str, function: F)
+    where
+        F: NativeFunction<Params>,
+    {
+        let function = self.function(name, function);
+        let builtins = self.vm.builtins().clone();
+        self.vm
+            .dict_set_str(builtins.as_dict().unwrap(), name, function);
+    }
+
+    /// A builtin function object that calls `function`, to pass to Python
+    /// code. `new_function` makes one in a `ModuleBuilder`'s VM.
+    pub fn function<Params, F>(&mut self, name: &'static str, function: F) -> ObjectRef
+    where
+        F: NativeFunction<Params>,
+    {
+        native::new_function(&self.vm, name, function)
+    }
+
+    /// The Python object for a value.
+    pub fn to_object(&mut self, value: &Value) -> Result<ObjectRef> {
+        to_object(&mut self.vm, value)
+    }
+
+    /// The value of a Python object, which must be of one of the types a
+    /// `Value` stands for, or a subclass of one.
+    pub fn to_value(&mut self, object: &ObjectRef) -> Result<Value> {
+        to_value(&mut self.vm, object)
+    }
+
+    /// The object `call` and `get` find for a name.
+    fn lookup(&mut self, name: &str) -> Result<ObjectRef> {
+        let mut parts = name.split('.');
+        let first = parts.next().unwrap();
+        let found = self
+            .vm
+            .main_module()
+            .dict()
+            .and_then(|globals| globals.as_dict()?.borrow().get_str(first))
+            .or_else(|| self.vm.builtins().as_dict()?.borrow().get_str(first));
+        let mut object = match found {
+            Some(object) => object,
+            None => {
+                let class = self.vm.exceptions.name_error.clone();
+                let exception = self
+                    .vm
+                    .new_error(&class, format!("name '{}' is not defined", first));
+                return self.check(Err(exception));
+            }
+        };
+        for part in parts {
+            let result = self.vm.getattr(&object, part);
+            object = self.check(result)?;
+        }
+        Ok(object)
+    }
+
+    /// The result of running code, with an exception made an `Error`.
+    fn check<T>(&mut self, result: PyResult<T>) -> Result<T> {
+        check(&mut self.vm, result)
+    }
+}
+
+/// The result of running code, with an exception made an `Error`.
+fn check<T>(vm: &mut Vm, result: PyResult<T>) -> Result<T> {
+    result.map_err(|exception| {
+        let class = exception.class();
+        let class = class.as_type().unwrap().name.clone();
+        let message = vm
+            .str(&exception)
+            .unwrap_or_else(|_| "<exception str() failed>".to_string());
+        let report = vm.format_exception(&exception);
+        Error::Exception {
+            class,
+            message,
+            report,
+        }
+    })
+}
+
+fn to_object(vm: &mut Vm, value: &Value) -> Result<ObjectRef> {
+    let object = match *value {
+        Value::None => vm.none(),
+        Value::Bool(value) => vm.new_bool(value),
+        Value::Int(value) => vm.new_int(value),
+        Value::Float(value) => vm.new_float(value),
+        Value::Str(ref value) => vm.new_str(value),
+        Value::Bytes(ref value) => vm.new_bytes(value.clone()),
+        Value::List(ref items) | Value::Tuple(ref items) => {
+            let mut objects = Vec::with_capacity(items.len());
+            for item in items {
+                objects.push(to_object(vm, item)?);
+            }
+            match *value {
+                Value::List(_) => vm.new_list(objects),
+                _ => vm.new_tuple(objects),
+            }
+        }
+        Value::Dict(ref items) => {
+            let dict = vm.new_dict();
+            for (key, value) in items {
+                let key = to_object(vm, key)?;
+                let value = to_object(vm, value)?;
+                let result = vm.dict_set(dict.as_dict().unwrap(), key, value);
+                check(vm, result)?;
+            }
+            dict
+        }
+    };
+    Ok(object)
+}
+
+fn to_value(vm: &mut Vm, object: &ObjectRef) -> Result<Value> {
+    value_within(vm, object, &mut Vec::new())
+}
+
+/// The value of `object`, which is inside the containers whose ids are
+/// `open`. A container inside itself has no value, since it would be
+/// copied into itself forever.
+fn value_within(vm: &mut Vm, object: &ObjectRef, open: &mut Vec<usize>) -> Result<Value> {
+    let container = matches!(
+        object.payload,
+        Payload::List(_) | Payload::Tuple(_) | Payload::Dict(_)
+    );
+    if container {
+        let id = object_id(object);
+        if open.contains(&id) {
+            let message = format!(
+                "cannot convert a '{}' that contains itself",
+                object.type_name()
+            );
+            return Err(Error::Conversion(message));
+        }
+        open.push(id);
+    }
+    let value = match object.payload {
+        Payload::None => Value::None,
+        Payload::Int(value) if Vm::is_instance(object, &vm.types.bool) => Value::Bool(value != 0),
+        Payload::Int(value) => Value::Int(value),
+        Payload::Float(value) => Value::Float(value),
+        Payload::Str(_) => Value::Str(object.as_str().unwrap().to_string()),
+        Payload::Bytes(ref bytes) => Value::Bytes(bytes.clone()),
+        Payload::List(ref items) => {
+            let items = items.borrow().clone();
+            Value::List(values_within(vm, &items, open)?)
+        }
+        Payload::Tuple(ref items) => Value::Tuple(values_within(vm, items, open)?),
+        Payload::Dict(ref dict) => {
+            let entries: Vec<(ObjectRef, ObjectRef)> = dict
+                .borrow()
+                .iter()
+                .map(|entry| (entry.key.clone(), entry.value.clone()))
+                .collect();
+            let mut items = Vec::with_capacity(entries.len());
+            for (key, value) in entries {
+                items.push((
+                    value_within(vm, &key, open)?,
+                    value_within(vm, &value, open)?,
+                ));
+            }
+            Value::Dict(items)
+        }
+        _ => {
+            let message = format!("cannot convert a '{}' to a Value", object.type_name());
+            return Err(Error::Conversion(message));
+        }
+    };
+    if container {
+        open.pop();
+    }
+    Ok(value)
+}
+
+fn values_within(vm: &mut Vm, objects: &[ObjectRef], open: &mut Vec<usize>) -> Result<Vec<Value>> {
+    objects
+        .iter()
+        .map(|object| value_within(vm, object, open))
+        .collect()
+}
+
+/// Registers Rust functions as builtins under their own names.
+///
+/// ```text
+/// fn double(x: i64) -> i64 { x * 2 }
+/// register_functions!(python, double, greet);
+/// ```
+#[macro_export]
+macro_rules! register_functions {
+    ($interpreter:expr, $($function:ident),+ $(,)*) => {
+        $($interpreter.register(stringify!($function), $function);)+
+    };
+}
+
+impl Default for Interpreter {
+    fn default() -> Interpreter {
+        Interpreter::new()
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Value {
+        Value::None
+    }
+}
+
+impl From<bool> for Value {
+    fn from(value: bool) -> Value {
+        Value::Bool(value)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(value: i64) -> Value {
+        Value::Int(value)
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Value {
+        Value::Int(i64::from(value))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(value: f64) -> Value {
+        Value::Float(value)
+    }
+}
+
+impl<'a> From<&'a str> for Value {
+    fn from(value: &'a str) -> Value {
+        Value::Str(value.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(value: String) -> Value {
+        Value::Str(value)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Value {
+        value.map_or(Value::None, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Value {
+        Value::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<K: Into<Value>, V: Into<Value>> From<HashMap<K, V>> for Value {
+    fn from(map: HashMap<K, V>) -> Value {
+        Value::Dict(
+            map.into_iter()
+                .map(|(key, value)| (key.into(), value.into()))
+                .collect(),
+        )
+    }
+}
+
+/// The error for converting `value` to the Rust type for `expected`.
+fn mismatch(expected: &str, value: &Value) -> Error {
+    Error::Conversion(format!(
+        "expected {}, not '{}'",
+        expected,
+        value.type_name()
+    ))
+}
+
+impl TryFrom<Value> for bool {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<bool> {
+        match value {
+            Value::Bool(value) => Ok(value),
+            value => Err(mismatch("bool", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for i64 {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<i64> {
+        match value {
+            Value::Int(value) => Ok(value),
+            value => Err(mismatch("int", &value)),
+        }
+    }
+}
+
+/// An int, or a float as CPython accepts one where it wants a float.
+impl TryFrom<Value> for f64 {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<f64> {
+        match value {
+            Value::Float(value) => Ok(value),
+            Value::Int(value) => Ok(value as f64),
+            value => Err(mismatch("float", &value)),
+        }
+    }
+}
+
+impl TryFrom<Value> for String {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<String> {
+        match value {
+            Value::Str(value) => Ok(value),
+            value => Err(mismatch("str", &value)),
+        }
+    }
+}
+
+/// `None` for `None`, and the converted value otherwise.
+impl<T: TryFrom<Value, Error = Error>> TryFrom<Value> for Option<T> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Option<T>> {
+        match value {
+            Value::None => Ok(None),
+            value => T::try_from(value).map(Some),
+        }
+    }
+}
+
+/// The items of a list or a tuple.
+impl<T: TryFrom<Value, Error = Error>> TryFrom<Value> for Vec<T> {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Vec<T>> {
+        match value {
+            Value::List(items) | Value::Tuple(items) => {
+                items.into_iter().map(T::try_from).collect()
+            }
+            value => Err(mismatch("list or tuple", &value)),
+        }
+    }
+}
+
+impl<K, V> TryFrom<Value> for HashMap<K, V>
+where
+    K: TryFrom<Value, Error = Error> + Eq + Hash,
+    V: TryFrom<Value, Error = Error>,
+{
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<HashMap<K, V>> {
+        match value {
+            Value::Dict(items) => items
+                .into_iter()
+                .map(|(key, value)| Ok((K::try_from(key)?, V::try_from(value)?)))
+                .collect(),
+            value => Err(mismatch("dict", &value)),
+        }
+    }
+}
@@ -0,0 +1,169 @@
+//! Rust functions and closures as Python builtins.
+//!
+//! A function whose parameters convert from `Value`s and whose result
+//! converts to one is a `NativeFunction`, and `Interpreter::register` makes
+//! it callable from Python. Arguments are converted before the call, and a
+//! conversion that fails raises `TypeError`; an `Err` the function returns
+//! raises the exception it names.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use super::{to_object, to_value, Error, Result, Value};
+use vm::{Args, ObjectRef, PyResult, Vm};
+
+/// A Rust function or closure Python code can call, for the parameter
+/// types `Params`, a tuple of them, which only tell the implementations
+/// apart.
+pub trait NativeFunction<Params>: 'static {
+    /// The number of arguments the function takes.
+    fn arity(&self) -> usize;
+
+    /// Calls the function with `args`, of which there are `arity`.
+    fn invoke(&self, args: Vec<Value>) -> Result<Value>;
+}
+
+/// What a native function may return.
+pub trait ReturnValue {
+    fn into_result(self) -> Result<Value>;
+}
+
+macro_rules! return_values {
+    ($($type:ty),+) => {
+        $(
+            impl ReturnValue for $type {
+                fn into_result(self) -> Result<Value> {
+                    Ok(self.into())
+                }
+            }
+        )+
+    };
+}
+
+return_values!(Value, (), bool, i64, i32, f64, String, &'static str);
+
+impl<T: Into<Value>> ReturnValue for Option<T> {
+    fn into_result(self) -> Result<Value> {
+        Ok(self.into())
+    }
+}
+
+impl<T: Into<Value>> ReturnValue for Vec<T> {
+    fn into_result(self) -> Result<Value> {
+        Ok(self.into())
+    }
+}
+
+impl<K: Into<Value>, V: Into<Value>> ReturnValue for HashMap<K, V> {
+    fn into_result(self) -> Result<Value> {
+        Ok(self.into())
+    }
+}
+
+/// An `Err` raises its exception in the calling Python code.
+impl<T: ReturnValue> ReturnValue for Result<T> {
+    fn into_result(self) -> Result<Value> {
+        self.and_then(ReturnValue::into_result)
+    }
+}
+
+macro_rules! native_functions {
+    ($(($($param:ident),*)),+) => {
+        $(
+            #[allow(non_snake_case, unused_mut, unused_variables)]
+            impl<Function, Return, $($param),*> NativeFunction<($($param,)*)> for Function
+            where
+                Function: Fn($($param),*) -> Return + 'static,
+                Return: ReturnValue,
+                $($param: TryFrom<Value, Error = Error>,)*
+            {
+                fn arity(&self) -> usize {
+                    <[&str]>::len(&[$(stringify!($param)),*])
+                }
+
+                fn invoke(&self, args: Vec<Value>) -> Result<Value> {
+                    let mut args = args.into_iter();
+                    $(let $param = $param::try_from(args.next().unwrap())?;)*
+                    self($($param),*).into_result()
+                }
+            }
+        )+
+    };
+}
+
+native_functions!(
+    (),
+    (A),
+    (A, B),
+    (A, B, C),
+    (A, B, C, D),
+    (A, B, C, D, E),
+    (A, B, C, D, E, F)
+);
+
+/// A builtin function object named `name` that calls `function`.
+pub fn new_function<Params, F>(vm: &Vm, name: &'static str, function: F) -> ObjectRef
+where
+    F: NativeFunction<Params>,
+{
+    vm.new_builtin_closure(name, move |vm, args| call(vm, name, &function, args))
+}
+
+fn call<Params, F>(vm: &mut Vm, name: &str, function: &F, args: Args) -> PyResult
+where
+    F: NativeFunction<Params>,
+{
+    if !args.keywords.is_empty() {
+        return Err(vm.new_type_error(format!("{}() takes no keyword arguments", name)));
+    }
+    let arity = function.arity();
+    if args.positional.len() != arity {
+        let message = match arity {
+            0 => format!(
+                "{}() takes no arguments ({} given)",
+                name,
+                args.positional.len()
+            ),
+            1 => format!(
+                "{}() takes exactly one argument ({} given)",
+                name,
+                args.positional.len()
+            ),
+            _ => format!(
+                "{}() takes exactly {} arguments ({} given)",
+                name,
+                arity,
+                args.positional.len()
+            ),
+        };
+        return Err(vm.new_type_error(message));
+    }
+    let mut values = Vec::with_capacity(arity);
+    for arg in &args.positional {
+        values.push(to_value(vm, arg).map_err(|error| raise(vm, error))?);
+    }
+    let result = function
+        .invoke(values)
+        .and_then(|value| to_object(vm, &value));
+    result.map_err(|error| raise(vm, error))
+}
+
+/// The exception for an error from a native function: a `TypeError` for
+/// an argument or result that did not convert, and otherwise an instance of
+/// the builtin exception class the error names, or a `RuntimeError` if
+/// there is none.
+fn raise(vm: &mut Vm, error: Error) -> ObjectRef {
+    match error {
+        Error::Conversion(message) => vm.new_type_error(message),
+        Error::Exception { class, message, .. } => {
+            let builtin = vm.builtins().as_dict().unwrap().borrow().get_str(&class);
+            let class = match builtin {
+                Some(ref builtin) if Vm::is_subclass(builtin, &vm.exceptions.base_exception) => {
+                    builtin.clone()
+                }
+                _ => vm.exceptions.runtime_error.clone(),
+            };
+            vm.new_error(&class, message)
+        }
+    }
+}
@@ -0,0 +1,57 @@
+#[cfg(feature = "jit")]
+extern crate cranelift_codegen;
+#[cfg(feature = "jit")]
+extern crate cranelift_frontend;
+#[cfg(feature = "jit")]
+extern crate cranelift_jit;
+#[cfg(feature = "jit")]
+extern crate cranelift_module;
+extern crate miniz_oxide;
+#[cfg(feature = "sqlite")]
+extern crate rusqlite;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "serde")]
+extern crate serde_json;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "chrome-trace")]
+extern crate tracing_chrome;
+#[cfg(feature = "chrome-trace")]
+extern crate tracing_subscriber;
+extern crate unicode_ident;
+
+// First, so that the other modules can use its macros.
+#[macro_use]
+pub mod trace;
+
+pub mod ast;
+pub mod backend;
+pub mod bundle;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod compiler;
+pub mod config;
+pub mod conformance;
+pub mod cst;
+pub mod debugging;
+pub mod diagnostics;
+pub mod diff;
+pub mod format;
+pub mod interpreter;
+#[cfg(feature = "ls")]
+pub mod ls;
+pub mod optimizer;
+pub mod parser;
+pub mod refactor;
+pub mod tokenizer;
+pub mod toml;
+pub mod vm;
+pub mod walk;
+
+pub use interpreter::Interpreter;
+
+/// The stack a thread that parses, compiles or runs code needs for the
+/// deepest nesting the parser and compiler accept and the deepest recursion
+/// the VM allows by default, even in an unoptimized build.
+pub const STACK_SIZE: usize = 64 << 20;
@@ -0,0 +1,151 @@
+//! Open documents, and conversion between byte offsets and LSP positions,
+//! which count lines from 0 and characters in UTF-16 code units.
+
+use ast::Module;
+use diagnostics::{self, Diagnostic};
+use parser;
+use tokenizer::{
+    self, strip_bom, LineIndex, Location, Span, TextEdit, Token, TokenizerConfig, BOM,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+pub(super) struct Document {
+    pub text: String,
+    /// The tokens of `text`, kept up to date with `tokenizer::relex` as it
+    /// changes, or `None` while it does not tokenize.
+    pub tokens: Option<Vec<Token>>,
+    /// As much of `text` as parses.
+    pub module: Module,
+    /// Its syntax errors, or if it has none, the compiler's warnings and
+    /// error.
+    pub diagnostics: Vec<Diagnostic>,
+    lines: LineIndex,
+}
+
+impl Document {
+    pub fn new(text: String) -> Document {
+        let tokens = tokenizer::tokenize(strip_bom(&text)).ok();
+        let (module, diagnostics) = check(&text, tokens.as_deref());
+        let lines = LineIndex::new(&text);
+        Document {
+            text,
+            tokens,
+            module,
+            diagnostics,
+            lines,
+        }
+    }
+
+    /// Replaces the text from `start` to `end` with `text`, or all of it
+    /// without a range.
+    pub fn change(&mut self, range: Option<(Position, Position)>, text: &str) {
+        let (start, end) = match range {
+            Some((start, end)) => (self.offset(start), self.offset(end)),
+            None => {
+                *self = Document::new(text.to_string());
+                return;
+            }
+        };
+        let end = end.max(start);
+        let bom = self.bom();
+        if start < bom {
+            let mut edited = self.text.clone();
+            edited.replace_range(start..end, text);
+            *self = Document::new(edited);
+            return;
+        }
+        self.text.replace_range(start..end, text);
+        self.lines = LineIndex::new(&self.text);
+        // Token spans do not count the byte order mark.
+        let edit = TextEdit::new(Span::new(start - bom, end - bom), text);
+        self.tokens = match self.tokens.take() {
+            Some(mut tokens) => tokenizer::relex(&mut tokens, &self.text[bom..], &edit)
+                .ok()
+                .map(|_| tokens),
+            None => tokenizer::tokenize(&self.text[bom..]).ok(),
+        };
+        let (module, diagnostics) = check(&self.text, self.tokens.as_deref());
+        self.module = module;
+        self.diagnostics = diagnostics;
+    }
+
+    /// The length of the byte order mark `text` starts with, if any.
+    pub fn bom(&self) -> usize {
+        if self.text.starts_with(BOM) {
+            BOM.len_utf8()
+        } else {
+            0
+        }
+    }
+
+    /// The byte offset of `position`, which is clamped to the end of its
+    /// line and of the text.
+    pub fn offset(&self, position: Position) -> usize {
+        let span = match self.lines.line_span(position.line + 1) {
+            Some(span) => span,
+            None => return self.text.len(),
+        };
+        let mut units = 0;
+        for (index, c) in self.text[span.start..span.end].char_indices() {
+            if units >= position.character {
+                return span.start + index;
+            }
+            units += c.len_utf16();
+        }
+        span.end
+    }
+
+    /// The position of the byte `offset`.
+    pub fn position(&self, offset: usize) -> Position {
+        let line = self.lines.line_at(offset);
+        let start = self.lines.line_span(line).unwrap().start;
+        Position {
+            line: line - 1,
+            character: self.text[start..offset].encode_utf16().count(),
+        }
+    }
+
+    /// The byte offset of a token, node or syntax error's `location`,
+    /// whose line counts from 1 and whose column counts characters.
+    pub fn location_offset(&self, location: Location) -> usize {
+        // Columns on the first line do not count the byte order mark.
+        let mut location = location;
+        if location.line <= 1 && self.bom() > 0 {
+            location.column += 1;
+        }
+        self.lines.offset(&self.text, location)
+    }
+
+    pub fn location_position(&self, location: Location) -> Position {
+        self.position(self.location_offset(location))
+    }
+}
+
+/// Parses `text`, after any byte order mark, and finds what is wrong with
+/// it, with the diagnostics covering the tokens they are at and with their
+/// fixes. `tokens` are those of `text`, if it tokenizes.
+///
+/// The whole document is parsed again, from its tokens if it has them:
+/// the AST cannot be edited in place as `cst::Tree::edit` edits a concrete
+/// syntax tree, and the compiler's diagnostics need all of it anyway.
+fn check(text: &str, tokens: Option<&[Token]>) -> (Module, Vec<Diagnostic>) {
+    let (module, errors) = match tokens {
+        Some(tokens) => parser::parse_tokens_recovering(tokens.to_vec()),
+        None => parser::parse_recovering(strip_bom(text)),
+    };
+    let mut diagnostics = diagnostics::check_parsed(&module, &errors, "<string>");
+    match tokens {
+        Some(tokens) => diagnostics::annotate(&mut diagnostics, text, tokens),
+        None => {
+            let (tokens, _) =
+                tokenizer::tokenize_recovering(strip_bom(text), TokenizerConfig::default());
+            diagnostics::annotate(&mut diagnostics, text, &tokens);
+        }
+    }
+    (module, diagnostics)
+}
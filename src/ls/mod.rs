@@ -0,0 +1,274 @@
+//! A language server for Python, built on the tokenizer and parser, which
+//! the `rustpy-ls` binary runs over stdin and stdout.
+//!
+//! It keeps the text of each open document up to date from incremental
+//! changes, relexing only what an edit touches but parsing the whole
+//! document again from its tokens, and provides:
+//!
+//! - diagnostics: the syntax errors, or the compiler's warnings and
+//!   error, published on each change;
+//! - document symbols: classes, functions and methods, and module and class
+//!   variables, from the AST, which the parser recovers from errors to give;
+//! - semantic tokens for the whole document, from the tokens;
+//! - quick fixes for the diagnostics that have them.
+//!
+//! Positions are in UTF-16 code units, the protocol's default.
+
+mod document;
+mod protocol;
+mod semantic;
+mod symbols;
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+
+use serde_json::{self, json, Value};
+
+use self::document::{Document, Position};
+use self::protocol::{read_message, write_message};
+use diagnostics::{Diagnostic, Severity};
+
+// JSON-RPC error codes.
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+/// Serves requests from `input`, writing responses and notifications to
+/// `output`, until an `exit` notification or the end of the input. Returns
+/// the exit status the protocol asks for: 0 if `shutdown` was requested
+/// first, 1 otherwise.
+pub fn serve<R: BufRead, W: Write>(mut input: R, output: W) -> io::Result<i32> {
+    let mut server = Server {
+        output,
+        documents: HashMap::new(),
+        shut_down: false,
+    };
+    while let Some(body) = read_message(&mut input)? {
+        let message: Value = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(err) => {
+                server.error(Value::Null, PARSE_ERROR, err.to_string())?;
+                continue;
+            }
+        };
+        let params = &message["params"];
+        match (message["method"].as_str(), message.get("id")) {
+            (Some("exit"), _) => return Ok(if server.shut_down { 0 } else { 1 }),
+            (Some(method), Some(id)) => server.request(id.clone(), method, params)?,
+            (Some(method), None) => server.notification(method, params)?,
+            // A response, though the server sends no requests.
+            (None, _) => {}
+        }
+    }
+    Ok(1)
+}
+
+struct Server<W> {
+    output: W,
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+
+impl<W: Write> Server<W> {
+    fn request(&mut self, id: Value, method: &str, params: &Value) -> io::Result<()> {
+        if self.shut_down {
+            return self.error(id, INVALID_REQUEST, "the server has shut down");
+        }
+        let result = match method {
+            "initialize" => initialize_result(),
+            "shutdown" => {
+                self.shut_down = true;
+                Value::Null
+            }
+            "textDocument/documentSymbol"
+            | "textDocument/semanticTokens/full"
+            | "textDocument/codeAction" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or("");
+                let document = match self.documents.get(uri) {
+                    Some(document) => document,
+                    None => return self.error(id, INVALID_PARAMS, "the document is not open"),
+                };
+                match method {
+                    "textDocument/documentSymbol" => {
+                        let tokens = document.tokens.as_deref().unwrap_or(&[]);
+                        Value::from(symbols::document_symbols(document, tokens))
+                    }
+                    "textDocument/semanticTokens/full" => {
+                        let data = match document.tokens {
+                            Some(ref tokens) => semantic::semantic_tokens(document, tokens),
+                            None => Vec::new(),
+                        };
+                        json!({ "data": data })
+                    }
+                    _ => Value::from(code_actions(document, uri, params)),
+                }
+            }
+            _ => {
+                let message = format!("unknown method {}", method);
+                return self.error(id, METHOD_NOT_FOUND, message);
+            }
+        };
+        self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    fn notification(&mut self, method: &str, params: &Value) -> io::Result<()> {
+        let uri = match params["textDocument"]["uri"].as_str() {
+            Some(uri) => uri.to_string(),
+            None => return Ok(()),
+        };
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or("");
+                self.documents
+                    .insert(uri.clone(), Document::new(text.to_string()));
+            }
+            "textDocument/didChange" => {
+                let document = match self.documents.get_mut(&uri) {
+                    Some(document) => document,
+                    None => return Ok(()),
+                };
+                let changes = params["contentChanges"]
+                    .as_array()
+                    .map_or(&[][..], Vec::as_slice);
+                for change in changes {
+                    let range = change
+                        .get("range")
+                        .map(|range| (position(&range["start"]), position(&range["end"])));
+                    document.change(range, change["text"].as_str().unwrap_or(""));
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+            }
+            _ => return Ok(()),
+        }
+        self.publish_diagnostics(&uri)
+    }
+
+    /// Sends the diagnostics of the document at `uri`, or clears those last
+    /// sent, since each publication replaces the one before.
+    fn publish_diagnostics(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics: Vec<Value> = match self.documents.get(uri) {
+            Some(document) => document
+                .diagnostics
+                .iter()
+                .map(|err| diagnostic(document, err))
+                .collect(),
+            None => Vec::new(),
+        };
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/publishDiagnostics",
+            "params": { "uri": uri, "diagnostics": diagnostics },
+        }))
+    }
+
+    fn error<S: Into<String>>(&mut self, id: Value, code: i64, message: S) -> io::Result<()> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": code, "message": message.into() },
+        }))
+    }
+
+    fn send(&mut self, message: Value) -> io::Result<()> {
+        write_message(&mut self.output, &message)
+    }
+}
+
+fn initialize_result() -> Value {
+    json!({
+        "capabilities": {
+            "positionEncoding": "utf-16",
+            // Incremental changes.
+            "textDocumentSync": { "openClose": true, "change": 2 },
+            "documentSymbolProvider": true,
+            "codeActionProvider": { "codeActionKinds": ["quickfix"] },
+            "semanticTokensProvider": {
+                "legend": {
+                    "tokenTypes": semantic::TOKEN_TYPES,
+                    "tokenModifiers": semantic::TOKEN_MODIFIERS,
+                },
+                "full": true,
+            },
+        },
+        "serverInfo": { "name": "rustpy-ls", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// A diagnostic in the protocol's terms. One given only where it starts
+/// covers the character there.
+fn diagnostic(document: &Document, diagnostic: &Diagnostic) -> Value {
+    let start = document.location_offset(diagnostic.location);
+    let mut end = document.location_offset(diagnostic.end);
+    if end == start {
+        end += document.text[start..]
+            .chars()
+            .next()
+            .filter(|&c| c != '\n' && c != '\r')
+            .map_or(0, char::len_utf8);
+    }
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+    };
+    let mut message = diagnostic.message.clone();
+    if let Some(ref suggestion) = diagnostic.suggestion {
+        message = format!("{} ({})", message, suggestion);
+    }
+    json!({
+        "range": range(document.position(start), document.position(end)),
+        "severity": severity,
+        "code": diagnostic.code,
+        "source": "rustpy",
+        "message": message,
+    })
+}
+
+/// Quick fixes for the diagnostics with fixes in the range `params` asks
+/// about.
+fn code_actions(document: &Document, uri: &str, params: &Value) -> Vec<Value> {
+    let start = document.offset(position(&params["range"]["start"]));
+    let end = document.offset(position(&params["range"]["end"]));
+    document
+        .diagnostics
+        .iter()
+        .filter_map(|found| {
+            let fix = found.fix.as_ref()?;
+            if document.location_offset(found.end) < start
+                || document.location_offset(found.location) > end
+            {
+                return None;
+            }
+            let edit = json!({
+                "range": range(
+                    document.position(fix.span.start),
+                    document.position(fix.span.end),
+                ),
+                "newText": fix.replacement,
+            });
+            Some(json!({
+                "title": format!("Fix: {}", found.message),
+                "kind": "quickfix",
+                "diagnostics": [diagnostic(document, found)],
+                "isPreferred": true,
+                "edit": { "changes": { uri: [edit] } },
+            }))
+        })
+        .collect()
+}
+
+fn position(value: &Value) -> Position {
+    let field = |name: &str| value[name].as_u64().unwrap_or(0) as usize;
+    Position {
+        line: field("line"),
+        character: field("character"),
+    }
+}
+
+fn range(start: Position, end: Position) -> Value {
+    let position =
+        |position: Position| json!({ "line": position.line, "character": position.character });
+    json!({ "start": position(start), "end": position(end) })
+}
@@ -0,0 +1,166 @@
+//! Semantic highlighting from the tokens alone: keywords, names being
+//! defined, called or used, literals, comments, operators and decorators.
+
+use parser::{is_keyword, is_soft_keyword};
+use tokenizer::{Token, TokenType};
+
+use super::document::Document;
+
+/// The token types, in the order their indices in `semantic_tokens` refer
+/// to.
+pub(super) const TOKEN_TYPES: &[&str] = &[
+    "keyword",
+    "function",
+    "class",
+    "variable",
+    "string",
+    "number",
+    "comment",
+    "operator",
+    "decorator",
+];
+pub(super) const TOKEN_MODIFIERS: &[&str] = &["declaration"];
+
+const KEYWORD: usize = 0;
+const FUNCTION: usize = 1;
+const CLASS: usize = 2;
+const VARIABLE: usize = 3;
+const STRING: usize = 4;
+const NUMBER: usize = 5;
+const COMMENT: usize = 6;
+const OPERATOR: usize = 7;
+const DECORATOR: usize = 8;
+
+const DECLARATION: usize = 1;
+
+/// Delimiters, which are left to the editor's own highlighting.
+const DELIMITERS: &[&str] = &["(", ")", "[", "]", "{", "}", ",", ":", ";", "."];
+
+/// The `data` of a `SemanticTokens` result: five integers for each token,
+/// its line and start relative to the token before it, its length, type
+/// and modifiers. A token spanning lines, such as a triple-quoted string,
+/// is given line by line.
+pub(super) fn semantic_tokens(document: &Document, tokens: &[Token]) -> Vec<usize> {
+    let source = &document.text[document.bom()..];
+    let mut data = Vec::new();
+    let mut previous = (0, 0);
+    let mut in_decorator = false;
+    for (index, token) in tokens.iter().enumerate() {
+        let kind = classify(tokens, index, &mut in_decorator);
+        let (kind, modifiers) = match kind {
+            Some(kind) => kind,
+            None => continue,
+        };
+        let mut offset = document.bom() + token.span.start;
+        for segment in lines(&source[token.span.start..token.span.end]) {
+            let start = document.position(offset);
+            let length = segment
+                .trim_end_matches(['\n', '\r'])
+                .encode_utf16()
+                .count();
+            offset += segment.len();
+            if length == 0 {
+                continue;
+            }
+            let delta_line = start.line - previous.0;
+            let delta_start = if delta_line == 0 {
+                start.character - previous.1
+            } else {
+                start.character
+            };
+            data.extend_from_slice(&[delta_line, delta_start, length, kind, modifiers]);
+            previous = (start.line, start.character);
+        }
+    }
+    data
+}
+
+/// The type and modifiers of `tokens[index]`, or `None` to leave it alone.
+/// `in_decorator` is set from the `@` starting a decorator to the end of
+/// its dotted name.
+fn classify(tokens: &[Token], index: usize, in_decorator: &mut bool) -> Option<(usize, usize)> {
+    let token = &tokens[index];
+    // Lines without code and line breaks inside brackets are passed over.
+    let previous = tokens[..index].iter().rev().find(|token| {
+        token.kind != TokenType::Comment && token.kind != TokenType::NewlineNonLogical
+    });
+    let next = tokens.get(index + 1);
+    let continues_decorator = *in_decorator
+        && match token.kind {
+            TokenType::Name => {
+                previous.is_some_and(|previous| previous.value == "@" || previous.value == ".")
+            }
+            TokenType::Operator => token.value == "." && previous.is_some_and(is_name),
+            _ => false,
+        };
+    *in_decorator = continues_decorator;
+    if continues_decorator {
+        return Some((DECORATOR, 0));
+    }
+    match token.kind {
+        TokenType::Comment => Some((COMMENT, 0)),
+        TokenType::String => Some((STRING, 0)),
+        TokenType::Number => Some((NUMBER, 0)),
+        TokenType::Operator if token.value == "@" && starts_statement(previous) => {
+            *in_decorator = true;
+            Some((DECORATOR, 0))
+        }
+        TokenType::Operator if DELIMITERS.contains(&token.value.as_str()) => None,
+        TokenType::Operator => Some((OPERATOR, 0)),
+        TokenType::Name if is_keyword(&token.value) => Some((KEYWORD, 0)),
+        TokenType::Name
+            if is_soft_keyword(&token.value)
+                && token.value != "_"
+                && starts_statement(previous)
+                && next.is_some_and(|next| {
+                    matches!(
+                        next.kind,
+                        TokenType::Name | TokenType::Number | TokenType::String
+                    )
+                }) =>
+        {
+            Some((KEYWORD, 0))
+        }
+        TokenType::Name => Some(match previous.map(|previous| previous.value.as_str()) {
+            Some("def") => (FUNCTION, DECLARATION),
+            Some("class") => (CLASS, DECLARATION),
+            _ if next.is_some_and(|next| next.value == "(") => (FUNCTION, 0),
+            _ => (VARIABLE, 0),
+        }),
+        _ => None,
+    }
+}
+
+/// Whether a token after `previous` is the first of a statement.
+fn starts_statement(previous: Option<&Token>) -> bool {
+    previous.is_none_or(|previous| {
+        matches!(
+            previous.kind,
+            TokenType::NewlineLogical | TokenType::Indent | TokenType::Dedent
+        ) || previous.value == ";"
+    })
+}
+
+fn is_name(token: &Token) -> bool {
+    token.kind == TokenType::Name
+}
+
+/// `text` split after each line break.
+fn lines(text: &str) -> Vec<&str> {
+    let bytes = text.as_bytes();
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (index, &byte) in bytes.iter().enumerate() {
+        let ends = match byte {
+            b'\n' => true,
+            b'\r' => bytes.get(index + 1) != Some(&b'\n'),
+            _ => false,
+        };
+        if ends {
+            lines.push(&text[start..index + 1]);
+            start = index + 1;
+        }
+    }
+    lines.push(&text[start..]);
+    lines
+}
@@ -0,0 +1,216 @@
+//! Document symbols: the classes and functions a module defines, and the
+//! variables of the module and its classes, nested as in the source.
+
+use std::collections::HashSet;
+
+use serde_json::{json, Value};
+
+use ast::{Expr, ExprKind, Stmt, StmtKind};
+use tokenizer::{Token, TokenType};
+
+use super::document::Document;
+use super::range;
+
+// The `SymbolKind`s used.
+const CLASS: u32 = 5;
+const METHOD: u32 = 6;
+const FIELD: u32 = 8;
+const FUNCTION: u32 = 12;
+const VARIABLE: u32 = 13;
+const CONSTANT: u32 = 14;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Module,
+    Class,
+    Function,
+}
+
+/// The symbols of `document`, whose `tokens` are used to find the names of
+/// definitions, when it has them.
+pub(super) fn document_symbols(document: &Document, tokens: &[Token]) -> Vec<Value> {
+    let mut symbols = Vec::new();
+    let collector = Collector { document, tokens };
+    collector.body(
+        &document.module.body,
+        Scope::Module,
+        &mut HashSet::new(),
+        &mut symbols,
+    );
+    symbols
+}
+
+struct Collector<'a> {
+    document: &'a Document,
+    tokens: &'a [Token],
+}
+
+impl<'a> Collector<'a> {
+    /// Adds the symbols `body` defines to `symbols`. Definitions in
+    /// compound statements belong to the enclosing scope, and only the first
+    /// assignment to each variable in `seen` is listed.
+    fn body(
+        &self,
+        body: &[Stmt],
+        scope: Scope,
+        seen: &mut HashSet<String>,
+        symbols: &mut Vec<Value>,
+    ) {
+        for stmt in body {
+            match stmt.kind {
+                StmtKind::FunctionDef {
+                    ref name, ref body, ..
+                } => {
+                    let mut children = Vec::new();
+                    self.body(body, Scope::Function, &mut HashSet::new(), &mut children);
+                    let kind = if scope == Scope::Class {
+                        METHOD
+                    } else {
+                        FUNCTION
+                    };
+                    symbols.push(self.definition(stmt, "def", name, kind, children));
+                }
+                StmtKind::ClassDef {
+                    ref name, ref body, ..
+                } => {
+                    let mut children = Vec::new();
+                    self.body(body, Scope::Class, &mut HashSet::new(), &mut children);
+                    symbols.push(self.definition(stmt, "class", name, CLASS, children));
+                }
+                StmtKind::Assign { ref targets, .. } if scope != Scope::Function => {
+                    for target in targets {
+                        self.variables(target, scope, seen, symbols);
+                    }
+                }
+                StmtKind::AnnAssign { ref target, .. }
+                | StmtKind::TypeAlias {
+                    name: ref target, ..
+                } if scope != Scope::Function => {
+                    self.variables(target, scope, seen, symbols);
+                }
+                StmtKind::For {
+                    ref body,
+                    ref orelse,
+                    ..
+                }
+                | StmtKind::While {
+                    ref body,
+                    ref orelse,
+                    ..
+                }
+                | StmtKind::If {
+                    ref body,
+                    ref orelse,
+                    ..
+                } => {
+                    self.body(body, scope, seen, symbols);
+                    self.body(orelse, scope, seen, symbols);
+                }
+                StmtKind::With { ref body, .. } => self.body(body, scope, seen, symbols),
+                StmtKind::Match { ref cases, .. } => {
+                    for case in cases {
+                        self.body(&case.body, scope, seen, symbols);
+                    }
+                }
+                StmtKind::Try {
+                    ref body,
+                    ref handlers,
+                    ref orelse,
+                    ref finalbody,
+                    ..
+                } => {
+                    self.body(body, scope, seen, symbols);
+                    for handler in handlers {
+                        self.body(&handler.body, scope, seen, symbols);
+                    }
+                    self.body(orelse, scope, seen, symbols);
+                    self.body(finalbody, scope, seen, symbols);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// A function or class, selecting the name after its `keyword`.
+    fn definition(
+        &self,
+        stmt: &Stmt,
+        keyword: &str,
+        name: &str,
+        kind: u32,
+        children: Vec<Value>,
+    ) -> Value {
+        let start = self.document.location_position(stmt.start);
+        let end = self.document.location_position(stmt.end);
+        let offset = self.document.location_offset(stmt.start) - self.document.bom();
+        let first = self
+            .tokens
+            .partition_point(|token| token.span.start < offset);
+        let selection = self.tokens[first..]
+            .windows(2)
+            .find(|pair| pair[0].kind == TokenType::Name && pair[0].value == keyword)
+            .map_or((start, start), |pair| {
+                (
+                    self.document.location_position(pair[1].start),
+                    self.document.location_position(pair[1].end),
+                )
+            });
+        json!({
+            "name": name,
+            "kind": kind,
+            "range": range(start, end),
+            "selectionRange": range(selection.0, selection.1),
+            "children": children,
+        })
+    }
+
+    /// The names `target` assigns, each as a variable, or a constant if
+    /// written in capitals.
+    fn variables(
+        &self,
+        target: &Expr,
+        scope: Scope,
+        seen: &mut HashSet<String>,
+        symbols: &mut Vec<Value>,
+    ) {
+        match target.kind {
+            ExprKind::Name { ref id, .. } => {
+                if !seen.insert(id.clone()) {
+                    return;
+                }
+                let kind = if scope == Scope::Class {
+                    FIELD
+                } else if is_constant(id) {
+                    CONSTANT
+                } else {
+                    VARIABLE
+                };
+                let range = range(
+                    self.document.location_position(target.start),
+                    self.document.location_position(target.end),
+                );
+                symbols.push(json!({
+                    "name": id,
+                    "kind": kind,
+                    "range": range,
+                    "selectionRange": range,
+                }));
+            }
+            ExprKind::Tuple { ref elts, .. } | ExprKind::List { ref elts, .. } => {
+                for elt in elts {
+                    self.variables(elt, scope, seen, symbols);
+                }
+            }
+            ExprKind::Starred { ref value, .. } => self.variables(value, scope, seen, symbols),
+            _ => {}
+        }
+    }
+}
+
+/// Whether `name` is written as a constant, such as `MAX_SIZE`.
+fn is_constant(name: &str) -> bool {
+    name.chars().any(|c| c.is_uppercase())
+        && name
+            .chars()
+            .all(|c| c.is_uppercase() || c.is_ascii_digit() || c == '_')
+}
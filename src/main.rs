@@ -1,3 +1,545 @@
+extern crate rustpy;
+
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::panic;
+use std::path::{Component, Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use rustpy::bundle::build_executable;
+use rustpy::config::Config;
+use rustpy::conformance;
+use rustpy::diagnostics::{Baseline, Diagnostic};
+use rustpy::parser::{parse_recovering_with_config, ParserConfig};
+use rustpy::tokenizer::{decode, tokenize_listing, tokenize_with_config, SourceError, TokenError};
+use rustpy::vm::{PyResult, Vm};
+use rustpy::walk::Walker;
+use rustpy::STACK_SIZE;
+
+const USAGE: &str = "usage: rustpy SCRIPT [ARG]...
+       rustpy -c COMMAND [ARG]...
+       rustpy -m MODULE [ARG]...
+       rustpy build SCRIPT [-o OUTPUT]
+       rustpy check [--format compact|json] [--baseline FILE [--write-baseline]]
+                    [PATH]...
+       rustpy conformance [-v]
+       rustpy tokenize [-e] [FILE]
+
+Runs SCRIPT, the program passed in as the string COMMAND, or the module
+MODULE found on sys.path, as __main__, with ARG... in sys.argv after it.
+
+commands:
+  build        compile SCRIPT and the modules it imports into a native
+               executable, named after SCRIPT in the current directory unless
+               -o is given
+  check        tokenize and parse the Python files in each PATH, the current
+               directory by default, and report every syntax error, one per
+               line as PATH:LINE:COLUMN: CODE MESSAGE, or as a JSON array with
+               --format json; the status is 1 if any were found. With a
+               baseline FILE, only errors it does not record are reported,
+               and --write-baseline records all of them in it instead
+  conformance  run the bundled conformance suites and report the share of
+               cases passed for each suite and language feature; -v also
+               shows what went differently in the cases that failed
+  tokenize     print the tokens of FILE, or of the standard input, as
+               python -m tokenize does; -e gives operators their exact type";
+
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+    // The main thread's stack is too small for the deepest code the parser,
+    // compiler and VM accept, so the work is done on one that is not.
+    let status = thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(move || command(&args))
+        .unwrap()
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload));
+    process::exit(status);
+}
+
+/// Runs the command `args` ask for, returning the exit status.
+fn command(args: &[String]) -> i32 {
+    match args.first().map(String::as_str) {
+        Some("build") => build(&args[1..]),
+        Some("check") => check(&args[1..]),
+        Some("conformance") => conformance(&args[1..]),
+        Some("tokenize") => tokenize_command(&args[1..]),
+        Some("-c") if args.len() > 1 => run_command(&args[1], &args[2..]),
+        Some("-m") if args.len() > 1 => run_module(&args[1], &args[2..]),
+        Some("-h") | Some("--help") => {
+            println!("{}", USAGE);
+            0
+        }
+        Some(script) if !script.starts_with('-') => run(args),
+        _ => usage_error(),
+    }
+}
+
+fn usage_error() -> i32 {
+    eprintln!("{}", USAGE);
+    2
+}
+
+/// `rustpy SCRIPT [ARG]...`: runs the script and returns the exit status
+/// CPython would, 1 for an uncaught exception after printing its traceback,
+/// or 2 if the script cannot be read.
+fn run(args: &[String]) -> i32 {
+    let path = Path::new(&args[0]);
+    let source = match fs::read(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!(
+                "rustpy: can't open file '{}': {}",
+                path.display(),
+                os_error_text(&err)
+            );
+            return 2;
+        }
+    };
+    let mut vm = Vm::new();
+    vm.set_argv(args);
+    let result = vm.run_script(&source, path);
+    exit_status(&mut vm, result)
+}
+
+/// `rustpy -c COMMAND [ARG]...`: runs `command` as a program, with
+/// `sys.argv` `['-c', ARG...]` and the current directory on `sys.path`.
+fn run_command(command: &str, args: &[String]) -> i32 {
+    let mut vm = Vm::new();
+    vm.set_argv(&argv("-c", args));
+    vm.add_search_path("");
+    let result = vm.run_source(command, "<string>");
+    exit_status(&mut vm, result)
+}
+
+/// `rustpy -m MODULE [ARG]...`: runs the module `name`, found on
+/// `sys.path` with the current directory first, with `sys.argv`
+/// `[its file, ARG...]`.
+fn run_module(name: &str, args: &[String]) -> i32 {
+    let mut vm = Vm::new();
+    vm.set_argv(&argv("-m", args));
+    match env::current_dir() {
+        Ok(directory) => vm.add_search_path(directory),
+        Err(_) => vm.add_search_path(""),
+    }
+    let result = vm.run_module(name);
+    exit_status(&mut vm, result)
+}
+
+fn argv(first: &str, args: &[String]) -> Vec<String> {
+    let mut argv = vec![first.to_string()];
+    argv.extend_from_slice(args);
+    argv
+}
+
+/// The exit status for the result of running a program, after flushing
+/// what it printed. An `ImportError` raised before anything ran, as for a
+/// module `-m` cannot find, is reported without a traceback, as CPython
+/// reports it.
+fn exit_status(vm: &mut Vm, result: PyResult) -> i32 {
+    let _ = io::stdout().flush();
+    match result {
+        Ok(_) => 0,
+        Err(exception) => {
+            let data = exception.as_exception().unwrap();
+            if data.borrow().traceback.is_none()
+                && Vm::is_instance(&exception, &vm.exceptions.import_error)
+            {
+                let message = vm.str(&exception).unwrap_or_default();
+                eprintln!("rustpy: {}", message);
+                return 1;
+            }
+            vm.exit_status(&exception)
+        }
+    }
+}
+
+/// An I/O error as CPython words it, `[Errno 2] No such file or directory`.
+fn os_error_text(err: &io::Error) -> String {
+    let text = err.to_string();
+    match err.raw_os_error() {
+        Some(code) => {
+            let suffix = format!(" (os error {})", code);
+            format!(
+                "[Errno {}] {}",
+                code,
+                text.strip_suffix(&suffix).unwrap_or(&text)
+            )
+        }
+        None => text,
+    }
+}
+
+/// `rustpy build SCRIPT [-o OUTPUT]`.
+fn build(args: &[String]) -> i32 {
+    let mut script = None;
+    let mut output = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => return usage_error(),
+            },
+            _ if script.is_none() && !arg.starts_with('-') => script = Some(PathBuf::from(arg)),
+            _ => return usage_error(),
+        }
+    }
+    let script = match script {
+        Some(script) => script,
+        None => return usage_error(),
+    };
+    let output = output.unwrap_or_else(|| default_output(&script));
+    match build_executable(&script, &output) {
+        Ok(()) => {
+            println!("built {}", output.display());
+            0
+        }
+        Err(err) => {
+            eprintln!("rustpy build: {}", err);
+            1
+        }
+    }
+}
+
+/// `rustpy check [--format compact|json] [--baseline FILE
+/// [--write-baseline]] [PATH]...`: reports the syntax errors in every file
+/// under the paths, checked in parallel, in the order of the files, with
+/// the settings of the project the first path is in. Those the baseline,
+/// from `--baseline` or the `baseline` setting, records are left out, and
+/// `--write-baseline` records every error in it instead of reporting them.
+/// The status is 1 if any were reported, or a file could not be read or
+/// decoded, 2 if the baseline could not be, and 0 otherwise.
+fn check(args: &[String]) -> i32 {
+    let mut json = false;
+    let mut baseline_path = None;
+    let mut write_baseline = false;
+    let mut roots = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => match args.next().map(String::as_str) {
+                Some("compact") => json = false,
+                Some("json") => json = true,
+                _ => return usage_error(),
+            },
+            "--baseline" => match args.next() {
+                Some(path) => baseline_path = Some(PathBuf::from(path)),
+                None => return usage_error(),
+            },
+            "--write-baseline" => write_baseline = true,
+            _ if !arg.starts_with('-') => roots.push(PathBuf::from(arg)),
+            _ => return usage_error(),
+        }
+    }
+    if roots.is_empty() {
+        roots.push(PathBuf::from("."));
+    }
+    let config = match project_config(&roots[0]) {
+        Ok(config) => config,
+        Err(status) => return status,
+    };
+    let baseline_path = baseline_path.or_else(|| config.baseline.clone());
+    let baseline = match baseline_path {
+        Some(ref path) if !write_baseline => match Baseline::load(path) {
+            Ok(baseline) => Some(baseline),
+            Err(err) => {
+                eprintln!("rustpy check: {}: {}", path.display(), err);
+                return 2;
+            }
+        },
+        None if write_baseline => {
+            eprintln!("rustpy check: --write-baseline needs --baseline FILE or a baseline setting");
+            return 2;
+        }
+        _ => None,
+    };
+    let mut written = Baseline::new();
+    let mut walker = Walker::new(&roots[0]);
+    for root in &roots[1..] {
+        walker = walker.root(root);
+    }
+    let walk = config.exclude_from(walker).walk();
+    let mut failed = !walk.errors.is_empty();
+    for err in &walk.errors {
+        eprintln!("rustpy check: {}", err);
+    }
+
+    let results = check_files(&walk.files, &config.parser_config());
+    let mut out = String::new();
+    let mut first = true;
+    if json {
+        out.push('[');
+    }
+    for (path, result) in walk.files.iter().zip(results) {
+        let (source, mut diagnostics) = match result {
+            Ok(checked) => checked,
+            Err(message) => {
+                eprintln!("rustpy check: {}: {}", path.display(), message);
+                failed = true;
+                continue;
+            }
+        };
+        diagnostics.retain(|diagnostic| !config.ignores(diagnostic.code));
+        if let Some(ref baseline_path) = baseline_path {
+            let key = baseline_key(path, baseline_path);
+            if write_baseline {
+                written.add(&key, &source, &diagnostics);
+                continue;
+            }
+            if let Some(ref baseline) = baseline {
+                diagnostics = baseline.filter(&key, &source, &diagnostics);
+            }
+        }
+        failed |= !diagnostics.is_empty();
+        for diagnostic in &diagnostics {
+            if !json {
+                out.push_str(&format!("{}:{}\n", path.display(), diagnostic));
+                continue;
+            }
+            if !first {
+                out.push(',');
+            }
+            first = false;
+            out.push_str(&format!(
+                "\n  {{\"path\": {}, \"line\": {}, \"column\": {}, \"end_line\": {}, \
+                 \"end_column\": {}, \"code\": {}, \"message\": {}}}",
+                json_string(&path.to_string_lossy()),
+                diagnostic.location.line,
+                diagnostic.location.column + 1,
+                diagnostic.end.line,
+                diagnostic.end.column + 1,
+                json_string(diagnostic.code),
+                json_string(&diagnostic.message),
+            ));
+        }
+    }
+    if let (true, Some(path)) = (write_baseline, baseline_path) {
+        if let Err(err) = written.save(&path) {
+            eprintln!("rustpy check: {}: {}", path.display(), err);
+            return 2;
+        }
+        eprintln!(
+            "rustpy check: recorded {} errors in {}",
+            written.len(),
+            path.display()
+        );
+        return failed as i32;
+    }
+    if json {
+        out.push_str(if first { "]\n" } else { "\n]\n" });
+    }
+    print!("{}", out);
+    failed as i32
+}
+
+/// How a baseline at `baseline` names the file `path`: relative to the
+/// baseline's directory if it is in it, so that the baseline holds
+/// wherever the check is run from, with `/` between the components.
+fn baseline_key(path: &Path, baseline: &Path) -> String {
+    let absolute = |path: &Path| {
+        let path = match env::current_dir() {
+            Ok(directory) => directory.join(path),
+            Err(_) => path.to_path_buf(),
+        };
+        let mut normal = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::ParentDir => {
+                    normal.pop();
+                }
+                component => normal.push(component),
+            }
+        }
+        normal
+    };
+    let path = absolute(path);
+    let directory = absolute(baseline.parent().unwrap_or(Path::new("")));
+    match path.strip_prefix(&directory) {
+        Ok(relative) => relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        Err(_) => path.to_string_lossy().into_owned(),
+    }
+}
+
+/// The decoded text and syntax errors of each of `paths`, tokenized and
+/// parsed with `config` on a thread per core, or why a file could not be
+/// read or decoded.
+fn check_files(
+    paths: &[PathBuf],
+    config: &ParserConfig,
+) -> Vec<Result<(String, Vec<Diagnostic>), String>> {
+    let threads = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(paths.len());
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let worker = thread::Builder::new().stack_size(STACK_SIZE);
+            worker
+                .spawn_scoped(scope, || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let path = match paths.get(index) {
+                        Some(path) => path,
+                        None => break,
+                    };
+                    let result = fs::read(path)
+                        .map_err(|err| os_error_text(&err))
+                        .and_then(|bytes| decode(&bytes).map_err(|err| err.to_string()))
+                        .map(|decoded| {
+                            let (_, errors) = parse_recovering_with_config(&decoded.text, config);
+                            let diagnostics = errors.iter().map(Diagnostic::from).collect();
+                            (decoded.text, diagnostics)
+                        });
+                    results.lock().unwrap()[index] = Some(result);
+                })
+                .unwrap();
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect()
+}
+
+/// The settings of the project `path` is in, from the nearest
+/// `pyproject.toml` with a `[tool.rustpy]` table, or the exit status after
+/// reporting why they could not be read.
+fn project_config(path: &Path) -> Result<Config, i32> {
+    let directory = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(Path::new(""))
+    };
+    match Config::discover(directory) {
+        Ok(found) => Ok(found.map(|(_, config)| config).unwrap_or_default()),
+        Err(err) => {
+            eprintln!("rustpy: {}: {}", rustpy::config::FILE_NAME, err);
+            Err(2)
+        }
+    }
+}
+
+/// `text` as a JSON string.
+fn json_string(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c < ' ' => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// `rustpy conformance [-v]`.
+fn conformance(args: &[String]) -> i32 {
+    let verbose = match args {
+        [] => false,
+        [flag] if flag == "-v" || flag == "--verbose" => true,
+        _ => return usage_error(),
+    };
+    let report = conformance::run();
+    print!("{}", report);
+    if verbose {
+        for outcome in report.failures() {
+            let case = outcome.case;
+            println!();
+            println!("FAIL {} {}: {}", case.suite(), case.feature, case.name);
+            for line in outcome.failure.as_ref().unwrap().lines() {
+                println!("    {}", line);
+            }
+        }
+    }
+    0
+}
+
+/// `rustpy tokenize [-e] [FILE]`: lists the tokens as `python -m tokenize`
+/// does, with an `ENCODING` token first for a file, or if the source does
+/// not tokenize, reports the error as it does and returns 1. The tokens are
+/// those of the target version and tab size of the project the file, or
+/// the current directory, is in.
+fn tokenize_command(args: &[String]) -> i32 {
+    let mut exact = false;
+    let mut path = None;
+    for arg in args {
+        match arg.as_str() {
+            "-e" | "--exact" => exact = true,
+            _ if path.is_none() && !arg.starts_with('-') => path = Some(arg),
+            _ => return usage_error(),
+        }
+    }
+    let (filename, encoding, source) = match path {
+        Some(path) => {
+            let decoded = fs::read(path)
+                .map_err(SourceError::from)
+                .and_then(|bytes| decode(&bytes));
+            match decoded {
+                Ok(decoded) => (path.as_str(), Some(decoded.encoding), decoded.text),
+                Err(SourceError::Token(err)) => return tokenize_error(path, &err),
+                Err(SourceError::Io(err)) => {
+                    eprintln!("error: {}", os_error_text(&err));
+                    return 1;
+                }
+                Err(err) => {
+                    eprintln!("{}: error: {}", path, err);
+                    return 1;
+                }
+            }
+        }
+        None => {
+            let mut source = String::new();
+            if let Err(err) = io::stdin().read_to_string(&mut source) {
+                eprintln!("error: {}", os_error_text(&err));
+                return 1;
+            }
+            ("<stdin>", None, source)
+        }
+    };
+    let config = match project_config(Path::new(filename)) {
+        Ok(config) => config,
+        Err(status) => return status,
+    };
+    match tokenize_with_config(&source, config.tokenizer_config()) {
+        Ok(tokens) => {
+            print!("{}", tokenize_listing(encoding.as_deref(), &tokens, exact));
+            0
+        }
+        Err(err) => tokenize_error(filename, &err),
+    }
+}
+
+fn tokenize_error(filename: &str, err: &TokenError) -> i32 {
+    eprintln!(
+        "{}:{}:{}: error: {}",
+        filename, err.location.line, err.location.column, err.message
+    );
+    1
+}
+
+/// The script's name without `.py`, with the platform's suffix for
+/// executables.
+fn default_output(script: &Path) -> PathBuf {
+    let stem = script
+        .file_stem()
+        .map_or("main".into(), |stem| stem.to_os_string());
+    let mut name = stem;
+    name.push(env::consts::EXE_SUFFIX);
+    PathBuf::from(name)
 }
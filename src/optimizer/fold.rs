@@ -0,0 +1,432 @@
+//! Evaluation of operators on constants, following CPython's semantics.
+//!
+//! Each function returns `None` where the operation is left for run time:
+//! it would raise, its result would be too large to be worth storing, or it
+//! involves an integer that does not fit in an `i64`.
+
+use std::convert::TryFrom;
+
+use ast::{Constant, Operator, UnaryOperator};
+
+use super::format_spec::{format_float, format_int, format_str};
+
+/// The longest tuple a multiplication may produce.
+const MAX_COLLECTION_SIZE: usize = 256;
+/// The longest string or bytes a multiplication may produce.
+const MAX_STR_SIZE: usize = 4096;
+/// The most items, counting those of nested tuples, a multiplication may
+/// produce.
+const MAX_TOTAL_ITEMS: usize = 1024;
+
+/// The most digits a width or precision may have in a replacement field
+/// that is formatted ahead of time, which keeps the result small enough to
+/// build before checking its size.
+const MAX_SPEC_NUMBER_DIGITS: usize = 4;
+
+/// Integers up to this magnitude convert to floats exactly.
+const MAX_EXACT_FLOAT_INT: i64 = 1 << 53;
+
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i64),
+    Float(f64),
+    Complex(f64, f64),
+}
+
+impl Number {
+    fn of(constant: &Constant) -> Option<Number> {
+        match *constant {
+            Constant::Bool(value) => Some(Number::Int(value as i64)),
+            Constant::Int(value) => Some(Number::Int(value)),
+            Constant::Float(value) => Some(Number::Float(value)),
+            Constant::Complex { real, imag } => Some(Number::Complex(real, imag)),
+            _ => None,
+        }
+    }
+
+    fn to_float(self) -> f64 {
+        match self {
+            Number::Int(value) => value as f64,
+            Number::Float(value) => value,
+            Number::Complex(real, _) => real,
+        }
+    }
+
+    fn to_complex(self) -> (f64, f64) {
+        match self {
+            Number::Complex(real, imag) => (real, imag),
+            other => (other.to_float(), 0.0),
+        }
+    }
+
+    fn into_constant(self) -> Constant {
+        match self {
+            Number::Int(value) => Constant::Int(value),
+            Number::Float(value) => Constant::Float(value),
+            Number::Complex(real, imag) => Constant::Complex { real, imag },
+        }
+    }
+}
+
+/// Whether a constant is true in a boolean context.
+pub fn is_true(constant: &Constant) -> bool {
+    match *constant {
+        Constant::None => false,
+        Constant::Bool(value) => value,
+        Constant::Str(ref value) => !value.is_empty(),
+        Constant::Bytes(ref value) => !value.is_empty(),
+        Constant::Int(value) => value != 0,
+        // Only integers too large for `Int` are stored as `LongInt`.
+        Constant::LongInt(_) => true,
+        Constant::Float(value) => value != 0.0,
+        Constant::Complex { real, imag } => real != 0.0 || imag != 0.0,
+        Constant::Ellipsis => true,
+        Constant::Tuple(ref elts) => !elts.is_empty(),
+    }
+}
+
+pub fn unary(op: UnaryOperator, operand: &Constant) -> Option<Constant> {
+    if op == UnaryOperator::Not {
+        return Some(Constant::Bool(!is_true(operand)));
+    }
+    // `-9223372036854775808` is parsed as the negation of a `LongInt`.
+    if let Constant::LongInt(ref digits) = *operand {
+        let negated = format!("-{}", digits).parse().ok()?;
+        return match op {
+            UnaryOperator::USub => Some(Constant::Int(negated)),
+            _ => None,
+        };
+    }
+    let number = match (op, Number::of(operand)?) {
+        (UnaryOperator::Invert, Number::Int(value)) => Number::Int(!value),
+        (UnaryOperator::Invert, _) => return None,
+        (UnaryOperator::UAdd, number) => number,
+        (UnaryOperator::USub, Number::Int(value)) => Number::Int(value.checked_neg()?),
+        (UnaryOperator::USub, Number::Float(value)) => Number::Float(-value),
+        (UnaryOperator::USub, Number::Complex(real, imag)) => Number::Complex(-real, -imag),
+        (UnaryOperator::Not, _) => unreachable!(),
+    };
+    Some(number.into_constant())
+}
+
+pub fn binary(left: &Constant, op: Operator, right: &Constant) -> Option<Constant> {
+    match (left, op, right) {
+        (Constant::Str(a), Operator::Add, Constant::Str(b)) => {
+            Some(Constant::Str(format!("{}{}", a, b)))
+        }
+        (Constant::Bytes(a), Operator::Add, Constant::Bytes(b)) => {
+            Some(Constant::Bytes([&a[..], &b[..]].concat()))
+        }
+        (Constant::Tuple(a), Operator::Add, Constant::Tuple(b)) => {
+            Some(Constant::Tuple([&a[..], &b[..]].concat()))
+        }
+        // `&`, `|` and `^` of two bools give a bool.
+        (&Constant::Bool(a), _, &Constant::Bool(b)) if is_bitwise(op) => {
+            let value = match op {
+                Operator::BitAnd => a & b,
+                Operator::BitOr => a | b,
+                _ => a ^ b,
+            };
+            Some(Constant::Bool(value))
+        }
+        (_, Operator::Mult, _) if is_sequence(left) => repeat(left, right),
+        (_, Operator::Mult, _) if is_sequence(right) => repeat(right, left),
+        _ => {
+            let (a, b) = (Number::of(left)?, Number::of(right)?);
+            let number = match (a, b) {
+                (Number::Int(a), Number::Int(b)) => int_binary(a, op, b)?,
+                (Number::Complex(..), _) | (_, Number::Complex(..)) => {
+                    complex_binary(a.to_complex(), op, b.to_complex())?
+                }
+                _ => float_binary(a.to_float(), op, b.to_float())?,
+            };
+            Some(number.into_constant())
+        }
+    }
+}
+
+fn is_bitwise(op: Operator) -> bool {
+    matches!(op, Operator::BitAnd | Operator::BitOr | Operator::BitXor)
+}
+
+fn is_sequence(constant: &Constant) -> bool {
+    matches!(
+        *constant,
+        Constant::Str(_) | Constant::Bytes(_) | Constant::Tuple(_)
+    )
+}
+
+/// `sequence * count`, within the size limits.
+fn repeat(sequence: &Constant, count: &Constant) -> Option<Constant> {
+    let count = match *count {
+        Constant::Int(count) => count,
+        Constant::Bool(count) => count as i64,
+        _ => return None,
+    };
+    if count < 0 {
+        return None;
+    }
+    let count = count as usize;
+    match *sequence {
+        Constant::Str(ref value) => {
+            let size = value.chars().count();
+            if size > 0 && count > MAX_STR_SIZE / size {
+                return None;
+            }
+            Some(Constant::Str(value.repeat(count)))
+        }
+        Constant::Bytes(ref value) => {
+            if !value.is_empty() && count > MAX_STR_SIZE / value.len() {
+                return None;
+            }
+            Some(Constant::Bytes(value.repeat(count)))
+        }
+        Constant::Tuple(ref elts) => {
+            if !elts.is_empty()
+                && (count > MAX_COLLECTION_SIZE / elts.len()
+                    || (count > 0 && total_items(elts) > MAX_TOTAL_ITEMS / count))
+            {
+                return None;
+            }
+            let repeated = elts.iter().cloned().cycle().take(elts.len() * count);
+            Some(Constant::Tuple(repeated.collect()))
+        }
+        _ => None,
+    }
+}
+
+fn total_items(elts: &[Constant]) -> usize {
+    elts.iter()
+        .map(|elt| match *elt {
+            Constant::Tuple(ref nested) => 1 + total_items(nested),
+            _ => 1,
+        })
+        .sum()
+}
+
+fn int_binary(a: i64, op: Operator, b: i64) -> Option<Number> {
+    let value = match op {
+        Operator::Add => a.checked_add(b)?,
+        Operator::Sub => a.checked_sub(b)?,
+        Operator::Mult => a.checked_mul(b)?,
+        Operator::Div => {
+            let exact = |n: i64| n.checked_abs().is_some_and(|n| n <= MAX_EXACT_FLOAT_INT);
+            if b == 0 || !exact(a) || !exact(b) {
+                return None;
+            }
+            return Some(Number::Float(a as f64 / b as f64));
+        }
+        Operator::FloorDiv => {
+            let quotient = a.checked_div(b)?;
+            if a % b != 0 && (a < 0) != (b < 0) {
+                quotient - 1
+            } else {
+                quotient
+            }
+        }
+        Operator::Mod => {
+            let remainder = a.checked_rem(b)?;
+            if remainder != 0 && (remainder < 0) != (b < 0) {
+                remainder + b
+            } else {
+                remainder
+            }
+        }
+        Operator::Pow if b < 0 => {
+            if a == 0 {
+                return None;
+            }
+            return float_binary(a as f64, op, b as f64);
+        }
+        Operator::Pow => a.checked_pow(u32::try_from(b).ok()?)?,
+        Operator::LShift => {
+            if b < 0 {
+                return None;
+            }
+            if a == 0 {
+                0
+            } else if b >= 63 || (a << b) >> b != a {
+                return None;
+            } else {
+                a << b
+            }
+        }
+        Operator::RShift => {
+            if b < 0 {
+                return None;
+            }
+            a >> b.min(63)
+        }
+        Operator::BitOr => a | b,
+        Operator::BitXor => a ^ b,
+        Operator::BitAnd => a & b,
+        Operator::MatMult => return None,
+    };
+    Some(Number::Int(value))
+}
+
+fn float_binary(a: f64, op: Operator, b: f64) -> Option<Number> {
+    let value = match op {
+        Operator::Add => a + b,
+        Operator::Sub => a - b,
+        Operator::Mult => a * b,
+        Operator::Div if b == 0.0 => return None,
+        Operator::Div => a / b,
+        Operator::FloorDiv | Operator::Mod => {
+            let (quotient, remainder) = float_divmod(a, b)?;
+            if op == Operator::Mod {
+                remainder
+            } else {
+                quotient
+            }
+        }
+        Operator::Pow => {
+            // Python raises for these, or gives a complex result.
+            if (a == 0.0 && b < 0.0) || (a < 0.0 && b.is_finite() && b.fract() != 0.0) {
+                return None;
+            }
+            let value = a.powf(b);
+            if value.is_infinite() && a.is_finite() && b.is_finite() {
+                return None;
+            }
+            value
+        }
+        _ => return None,
+    };
+    Some(Number::Float(value))
+}
+
+/// Floor division and modulo of floats, as CPython's `float_divmod`.
+pub(crate) fn float_divmod(a: f64, b: f64) -> Option<(f64, f64)> {
+    if b == 0.0 {
+        return None;
+    }
+    let mut remainder = a % b;
+    let mut quotient = (a - remainder) / b;
+    if remainder != 0.0 {
+        if (b < 0.0) != (remainder < 0.0) {
+            remainder += b;
+            quotient -= 1.0;
+        }
+    } else {
+        remainder = 0.0_f64.copysign(b);
+    }
+    let quotient = if quotient != 0.0 {
+        let floor = quotient.floor();
+        if quotient - floor > 0.5 {
+            floor + 1.0
+        } else {
+            floor
+        }
+    } else {
+        0.0_f64.copysign(a / b)
+    };
+    Some((quotient, remainder))
+}
+
+fn complex_binary(a: (f64, f64), op: Operator, b: (f64, f64)) -> Option<Number> {
+    let (value_real, value_imag) = match op {
+        Operator::Add => (a.0 + b.0, a.1 + b.1),
+        Operator::Sub => (a.0 - b.0, a.1 - b.1),
+        Operator::Mult => (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0),
+        Operator::Div => {
+            // Smith's method, as CPython's `_Py_c_quot`.
+            if b.0.abs() >= b.1.abs() {
+                if b.0 == 0.0 {
+                    return None;
+                }
+                let ratio = b.1 / b.0;
+                let denominator = b.0 + b.1 * ratio;
+                (
+                    (a.0 + a.1 * ratio) / denominator,
+                    (a.1 - a.0 * ratio) / denominator,
+                )
+            } else if b.1.abs() >= b.0.abs() {
+                let ratio = b.0 / b.1;
+                let denominator = b.0 * ratio + b.1;
+                (
+                    (a.0 * ratio + a.1) / denominator,
+                    (a.1 * ratio - a.0) / denominator,
+                )
+            } else {
+                return None;
+            }
+        }
+        _ => return None,
+    };
+    Some(Number::Complex(value_real, value_imag))
+}
+
+/// `value[index]` for a string, bytes or tuple and an integer index.
+pub fn subscript(value: &Constant, index: &Constant) -> Option<Constant> {
+    let index = match *index {
+        Constant::Int(index) => index,
+        Constant::Bool(index) => index as i64,
+        _ => return None,
+    };
+    let resolve = |length: usize| {
+        let length = length as i64;
+        let index = if index < 0 { index + length } else { index };
+        if (0..length).contains(&index) {
+            Some(index as usize)
+        } else {
+            None
+        }
+    };
+    match *value {
+        Constant::Str(ref value) => {
+            let index = resolve(value.chars().count())?;
+            value
+                .chars()
+                .nth(index)
+                .map(|c| Constant::Str(c.to_string()))
+        }
+        Constant::Bytes(ref value) => {
+            let index = resolve(value.len())?;
+            Some(Constant::Int(value[index] as i64))
+        }
+        Constant::Tuple(ref elts) => {
+            let index = resolve(elts.len())?;
+            Some(elts[index].clone())
+        }
+        _ => None,
+    }
+}
+
+/// An f-string replacement field without a conversion, formatted as
+/// `format(value, spec)`.
+pub fn format(value: &Constant, spec: &str) -> Option<Constant> {
+    let longest_number = spec
+        .split(|c: char| !c.is_ascii_digit())
+        .map(str::len)
+        .max();
+    if longest_number > Some(MAX_SPEC_NUMBER_DIGITS) {
+        return None;
+    }
+    let formatted = match *value {
+        Constant::Bool(value) if spec.is_empty() => {
+            Ok(if value { "True" } else { "False" }.to_string())
+        }
+        Constant::Bool(value) => format_int(value as i64, spec, "bool"),
+        Constant::Int(value) => format_int(value, spec, "int"),
+        Constant::Float(value) => format_float(value, spec, "float"),
+        Constant::Str(ref value) => format_str(value, spec, "str"),
+        _ => return None,
+    };
+    formatted
+        .ok()
+        .filter(|text| text.len() <= MAX_STR_SIZE)
+        .map(Constant::Str)
+}
+
+/// The parts of an f-string joined, when they are all strings.
+pub fn join(values: &[Constant]) -> Option<Constant> {
+    let mut joined = String::new();
+    for value in values {
+        match *value {
+            Constant::Str(ref value) => joined.push_str(value),
+            _ => return None,
+        }
+    }
+    Some(Constant::Str(joined))
+}
@@ -0,0 +1,512 @@
+//! The format specification mini-language of `format()`, `str.format` and
+//! f-strings, for the builtin `str`, `int` and `float`, as CPython's
+//! `Python/formatter_unicode.c`.
+//!
+//! The VM formats values with it, and the optimizer formats constant
+//! f-string fields with it ahead of time. Both pass the name of the value's
+//! type, which errors mention.
+
+use std::char;
+
+/// Why a value could not be formatted: a `ValueError`, or an
+/// `OverflowError` for a `c` code out of range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum FormatError {
+    Value(String),
+    Overflow(String),
+}
+
+fn error<T>(message: String) -> Result<T, FormatError> {
+    Err(FormatError::Value(message))
+}
+
+/// A parsed specification:
+/// `[[fill]align][sign]["z"]["#"]["0"][width][grouping]["." precision][type]`.
+#[derive(Debug, Clone, PartialEq)]
+struct FormatSpec {
+    fill: char,
+    align: char,
+    /// `+`, `-` or a space.
+    sign: Option<char>,
+    /// Whether negative zero is written without its sign, after rounding.
+    no_negative_zero: bool,
+    alternate: bool,
+    width: usize,
+    /// `,` or `_` between groups of digits.
+    grouping: Option<char>,
+    precision: Option<usize>,
+    /// The presentation type, or `None` for the default.
+    kind: Option<char>,
+}
+
+fn is_align(c: char) -> bool {
+    matches!(c, '<' | '>' | '=' | '^')
+}
+
+/// Reads a decimal number at `position`, or `None` if there are no digits.
+fn number(chars: &[char], position: &mut usize) -> Result<Option<usize>, FormatError> {
+    let start = *position;
+    let mut value: usize = 0;
+    while let Some(digit) = chars.get(*position).and_then(|c| c.to_digit(10)) {
+        value = match value
+            .checked_mul(10)
+            .and_then(|value| value.checked_add(digit as usize))
+        {
+            Some(value) if value <= isize::MAX as usize => value,
+            _ => return error("Too many decimal digits in format string".to_string()),
+        };
+        *position += 1;
+    }
+    Ok(if *position == start {
+        None
+    } else {
+        Some(value)
+    })
+}
+
+impl FormatSpec {
+    /// Parses `spec` for a value of the type `type_name`, whose default
+    /// type is `default_kind` and which is aligned `default_align`.
+    fn parse(
+        spec: &str,
+        type_name: &str,
+        default_kind: char,
+        default_align: char,
+    ) -> Result<FormatSpec, FormatError> {
+        let chars: Vec<char> = spec.chars().collect();
+        let mut format = FormatSpec {
+            fill: ' ',
+            align: default_align,
+            sign: None,
+            no_negative_zero: false,
+            alternate: false,
+            width: 0,
+            grouping: None,
+            precision: None,
+            kind: None,
+        };
+        let mut position = 0;
+        let mut fill_given = false;
+        let mut align_given = false;
+        if chars.len() >= 2 && is_align(chars[1]) {
+            format.fill = chars[0];
+            format.align = chars[1];
+            fill_given = true;
+            align_given = true;
+            position = 2;
+        } else if !chars.is_empty() && is_align(chars[0]) {
+            format.align = chars[0];
+            align_given = true;
+            position = 1;
+        }
+        if let Some(&sign) = chars
+            .get(position)
+            .filter(|&&c| matches!(c, '+' | '-' | ' '))
+        {
+            format.sign = Some(sign);
+            position += 1;
+        }
+        if chars.get(position) == Some(&'z') {
+            format.no_negative_zero = true;
+            position += 1;
+        }
+        if chars.get(position) == Some(&'#') {
+            format.alternate = true;
+            position += 1;
+        }
+        // A leading zero pads with zeros, after the sign for numbers.
+        if !fill_given && chars.get(position) == Some(&'0') {
+            format.fill = '0';
+            if !align_given && default_align == '>' {
+                format.align = '=';
+            }
+            position += 1;
+        }
+        format.width = number(&chars, &mut position)?.unwrap_or(0);
+        if chars.get(position) == Some(&',') {
+            format.grouping = Some(',');
+            position += 1;
+        }
+        if chars.get(position) == Some(&'_') {
+            if format.grouping.is_some() {
+                return error("Cannot specify both ',' and '_'.".to_string());
+            }
+            format.grouping = Some('_');
+            position += 1;
+        }
+        if chars.get(position) == Some(&',') && format.grouping == Some('_') {
+            return error("Cannot specify both ',' and '_'.".to_string());
+        }
+        if chars.get(position) == Some(&'.') {
+            position += 1;
+            match number(&chars, &mut position)? {
+                Some(precision) => format.precision = Some(precision),
+                None => return error("Format specifier missing precision".to_string()),
+            }
+        }
+        match chars.len() - position {
+            0 => {}
+            1 => format.kind = Some(chars[position]),
+            _ => {
+                return error(format!(
+                    "Invalid format specifier '{}' for object of type '{}'",
+                    spec, type_name
+                ))
+            }
+        }
+        if let Some(grouping) = format.grouping {
+            let kind = format.kind.unwrap_or(default_kind);
+            let allowed = match kind {
+                'd' | 'e' | 'f' | 'g' | 'E' | 'G' | '%' | 'F' | '\0' => true,
+                'b' | 'o' | 'x' | 'X' => grouping == '_',
+                _ => false,
+            };
+            if !allowed {
+                return error(format!("Cannot specify '{}' with '{}'.", grouping, kind));
+            }
+        }
+        Ok(format)
+    }
+
+    /// Pads a formatted value to the width. For `=` alignment the padding
+    /// goes after `sign`, which is the sign and any base prefix.
+    fn pad(&self, sign: &str, body: &str) -> String {
+        let len = sign.chars().count() + body.chars().count();
+        let padding = self.width.saturating_sub(len);
+        let fill = |count: usize| self.fill.to_string().repeat(count);
+        match self.align {
+            '<' => format!("{}{}{}", sign, body, fill(padding)),
+            '^' => format!(
+                "{}{}{}{}",
+                fill(padding / 2),
+                sign,
+                body,
+                fill(padding - padding / 2)
+            ),
+            '=' => format!("{}{}{}", sign, fill(padding), body),
+            _ => format!("{}{}{}", fill(padding), sign, body),
+        }
+    }
+
+    /// The sign to write before a number.
+    fn sign(&self, negative: bool) -> &'static str {
+        match (negative, self.sign) {
+            (true, _) => "-",
+            (false, Some('+')) => "+",
+            (false, Some(' ')) => " ",
+            _ => "",
+        }
+    }
+
+    /// Groups the digits of the integer part of a number, and pads them
+    /// with zeros, grouped too, to `min_width` for zero padding with `=`
+    /// alignment.
+    fn group(&self, digits: &str, group_len: usize, min_width: usize) -> String {
+        let zero_padded = self.fill == '0' && self.align == '=';
+        let min_width = if zero_padded { min_width } else { 0 };
+        let separator = match self.grouping {
+            Some(separator) => separator,
+            None => {
+                let zeros = min_width.saturating_sub(digits.len());
+                return format!("{}{}", "0".repeat(zeros), digits);
+            }
+        };
+        // Built backwards, a digit or padding zero at a time.
+        let mut digits = digits.chars().rev();
+        let mut grouped = Vec::new();
+        let mut in_group = 0;
+        loop {
+            let digit = digits.next();
+            if digit.is_none() && grouped.len() >= min_width {
+                break;
+            }
+            if in_group == group_len {
+                grouped.push(separator);
+                in_group = 0;
+            }
+            grouped.push(digit.unwrap_or('0'));
+            in_group += 1;
+        }
+        grouped.iter().rev().collect()
+    }
+}
+
+/// Formats a string, as `str.__format__`.
+pub(crate) fn format_str(value: &str, spec: &str, type_name: &str) -> Result<String, FormatError> {
+    let format = FormatSpec::parse(spec, type_name, 's', '<')?;
+    match format.kind {
+        None | Some('s') => {}
+        Some(kind) => return Err(unknown_code(kind, type_name)),
+    }
+    if format.sign.is_some() {
+        return error("Sign not allowed in string format specifier".to_string());
+    }
+    if format.no_negative_zero {
+        let message = "Negative zero coercion (z) not allowed in string format specifier";
+        return error(message.to_string());
+    }
+    if format.alternate {
+        let message = "Alternate form (#) not allowed in string format specifier";
+        return error(message.to_string());
+    }
+    if format.align == '=' {
+        let message = "'=' alignment not allowed in string format specifier";
+        return error(message.to_string());
+    }
+    let value: String = match format.precision {
+        Some(precision) => value.chars().take(precision).collect(),
+        None => value.to_string(),
+    };
+    Ok(format.pad("", &value))
+}
+
+fn unknown_code(kind: char, type_name: &str) -> FormatError {
+    let code = if (' '..'\x7f').contains(&kind) {
+        kind.to_string()
+    } else {
+        format!("\\x{:x}", kind as u32)
+    };
+    FormatError::Value(format!(
+        "Unknown format code '{}' for object of type '{}'",
+        code, type_name
+    ))
+}
+
+/// Formats an integer, as `int.__format__`.
+pub(crate) fn format_int(value: i64, spec: &str, type_name: &str) -> Result<String, FormatError> {
+    let format = FormatSpec::parse(spec, type_name, 'd', '>')?;
+    let radix = match format.kind {
+        None | Some('d') | Some('n') | Some('c') => 10,
+        Some('b') => 2,
+        Some('o') => 8,
+        Some('x') | Some('X') => 16,
+        Some('e') | Some('E') | Some('f') | Some('F') | Some('g') | Some('G') | Some('%') => {
+            return format_float_with(value as f64, &format);
+        }
+        Some(kind) => return Err(unknown_code(kind, type_name)),
+    };
+    if format.precision.is_some() {
+        return error("Precision not allowed in integer format specifier".to_string());
+    }
+    if format.no_negative_zero {
+        let message = "Negative zero coercion (z) not allowed in integer format specifier";
+        return error(message.to_string());
+    }
+    if format.kind == Some('c') {
+        if format.sign.is_some() {
+            return error("Sign not allowed with integer format specifier 'c'".to_string());
+        }
+        if format.alternate {
+            let message = "Alternate form (#) not allowed with integer format specifier 'c'";
+            return error(message.to_string());
+        }
+        let c = Some(value)
+            .filter(|value| (0..0x110000).contains(value))
+            .and_then(|value| char::from_u32(value as u32));
+        return match c {
+            Some(c) => Ok(format.pad("", &c.to_string())),
+            None if (0..0x110000).contains(&value) => {
+                // A surrogate, which strings here cannot hold.
+                error("surrogates not allowed".to_string())
+            }
+            None => Err(FormatError::Overflow(
+                "%c arg not in range(0x110000)".to_string(),
+            )),
+        };
+    }
+    let magnitude = value.unsigned_abs();
+    let digits = match radix {
+        2 => format!("{:b}", magnitude),
+        8 => format!("{:o}", magnitude),
+        16 if format.kind == Some('X') => format!("{:X}", magnitude),
+        16 => format!("{:x}", magnitude),
+        _ => magnitude.to_string(),
+    };
+    let prefix = match (format.alternate, format.kind) {
+        (true, Some('b')) => "0b",
+        (true, Some('o')) => "0o",
+        (true, Some('x')) => "0x",
+        (true, Some('X')) => "0X",
+        _ => "",
+    };
+    let sign = format!("{}{}", format.sign(value < 0), prefix);
+    let group_len = if radix == 10 { 3 } else { 4 };
+    let min_width = format.width.saturating_sub(sign.len());
+    let body = format.group(&digits, group_len, min_width);
+    Ok(format.pad(&sign, &body))
+}
+
+/// Formats a float, as `float.__format__`.
+pub(crate) fn format_float(value: f64, spec: &str, type_name: &str) -> Result<String, FormatError> {
+    let format = FormatSpec::parse(spec, type_name, '\0', '>')?;
+    match format.kind {
+        None | Some('e') | Some('E') | Some('f') | Some('F') | Some('g') | Some('G')
+        | Some('n') | Some('%') => format_float_with(value, &format),
+        Some(kind) => Err(unknown_code(kind, type_name)),
+    }
+}
+
+fn format_float_with(value: f64, format: &FormatSpec) -> Result<String, FormatError> {
+    if format
+        .precision
+        .is_some_and(|precision| precision > i32::MAX as usize)
+    {
+        return error("precision too big".to_string());
+    }
+    let (value, suffix) = match format.kind {
+        Some('%') => (value * 100.0, "%"),
+        _ => (value, ""),
+    };
+    let upper = matches!(format.kind, Some('E') | Some('F') | Some('G'));
+    let magnitude = value.abs();
+    let mut body = if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        "inf".to_string()
+    } else {
+        match (format.kind, format.precision) {
+            // Without a type, as `repr` unless there is a precision.
+            (None, None) => short_repr(magnitude, format.alternate),
+            (None, Some(precision)) => general(magnitude, precision.max(1), format.alternate, true),
+            (Some('e'), _) | (Some('E'), _) => {
+                scientific(magnitude, format.precision.unwrap_or(6), format.alternate)
+            }
+            (Some('f'), _) | (Some('F'), _) | (Some('%'), _) => {
+                let precision = format.precision.unwrap_or(6);
+                let mut text = format!("{:.*}", precision, magnitude);
+                if format.alternate && precision == 0 {
+                    text.push('.');
+                }
+                text
+            }
+            _ => {
+                let precision = format.precision.unwrap_or(6).max(1);
+                general(magnitude, precision, format.alternate, false)
+            }
+        }
+    };
+    if upper {
+        body = body.to_uppercase();
+    }
+    let mut negative = value.is_sign_negative() && !value.is_nan();
+    if negative
+        && format.no_negative_zero
+        && value.is_finite()
+        && body.bytes().all(|b| !(b'1'..=b'9').contains(&b))
+    {
+        negative = false;
+    }
+    let sign = format.sign(negative);
+    // Only the digits before the point or exponent are grouped.
+    let split = body
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(body.len());
+    let (digits, rest) = body.split_at(split);
+    let rest = format!("{}{}", rest, suffix);
+    let min_width = format
+        .width
+        .saturating_sub(sign.len() + rest.chars().count());
+    let digits = if digits.is_empty() {
+        // `inf` or `nan`, which zero padding pads without grouping.
+        let format = FormatSpec {
+            grouping: None,
+            ..format.clone()
+        };
+        format.group("", 3, min_width)
+    } else {
+        format.group(digits, 3, min_width)
+    };
+    Ok(format.pad(sign, &format!("{}{}", digits, rest)))
+}
+
+/// The shortest digits that round-trip, laid out as `repr` does, for a
+/// finite value that is not negative. The alternate form keeps the point
+/// in scientific notation.
+fn short_repr(value: f64, alternate: bool) -> String {
+    let (digits, exponent) = decimal_digits(&format!("{:e}", value));
+    let use_exponent = !(-4..16).contains(&exponent);
+    layout(
+        &digits,
+        exponent,
+        use_exponent,
+        true,
+        alternate && use_exponent,
+    )
+}
+
+/// `e` formatting with `precision` digits after the point.
+fn scientific(value: f64, precision: usize, alternate: bool) -> String {
+    let (digits, exponent) = decimal_digits(&format!("{:.*e}", precision, value));
+    layout(&digits, exponent, true, alternate, alternate)
+}
+
+/// `g` formatting to `precision` significant digits: positional unless the
+/// exponent is below -4 or not below the precision, without trailing zeros
+/// unless `alternate`. `point` keeps at least one digit after the point in
+/// positional notation, for the default type with a precision.
+fn general(value: f64, precision: usize, alternate: bool, point: bool) -> String {
+    let (mut digits, exponent) = decimal_digits(&format!("{:.*e}", precision - 1, value));
+    // Keeping a digit after the point takes a place of the precision.
+    let limit = if point { precision - 1 } else { precision };
+    let use_exponent = exponent < -4 || exponent >= limit as i32;
+    if !alternate {
+        let kept = digits.trim_end_matches('0').len().max(1);
+        digits.truncate(kept);
+    }
+    layout(&digits, exponent, use_exponent, point, alternate)
+}
+
+/// The digits and the exponent of a number written by Rust's `{:e}`.
+fn decimal_digits(scientific: &str) -> (String, i32) {
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    (mantissa.replace('.', ""), exponent[1..].parse().unwrap())
+}
+
+/// Lays out `digits`, the first of which is in the place of `exponent`, in
+/// scientific notation or positionally. Positional notation gets `.0` if
+/// it has no fraction and `point` is set, and either gets a bare point if
+/// it has no fraction and `bare_point` is set.
+fn layout(
+    digits: &str,
+    exponent: i32,
+    use_exponent: bool,
+    point: bool,
+    bare_point: bool,
+) -> String {
+    if use_exponent {
+        let (first, rest) = digits.split_at(1);
+        let fraction = if !rest.is_empty() {
+            format!(".{}", rest)
+        } else if bare_point {
+            ".".to_string()
+        } else {
+            String::new()
+        };
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        return format!(
+            "{}{}e{}{:02}",
+            first,
+            fraction,
+            exponent_sign,
+            exponent.abs()
+        );
+    }
+    let point_position = exponent + 1;
+    if point_position <= 0 {
+        return format!("0.{}{}", "0".repeat(-point_position as usize), digits);
+    }
+    let point_position = point_position as usize;
+    if point_position >= digits.len() {
+        let zeros = "0".repeat(point_position - digits.len());
+        let end = if point && !bare_point {
+            ".0"
+        } else if bare_point {
+            "."
+        } else {
+            ""
+        };
+        return format!("{}{}{}", digits, zeros, end);
+    }
+    let (whole, fraction) = digits.split_at(point_position);
+    format!("{}.{}", whole, fraction)
+}
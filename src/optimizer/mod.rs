@@ -0,0 +1,675 @@
+//! Optimizations on the AST, run between parsing and compilation, like
+//! CPython's `ast_opt.c`.
+//!
+//! Operators on constants are evaluated, tuples of constants become
+//! constants, a list or tuple iterated over or tested with `in` becomes a
+//! tuple, and the branch of an `if` or `while` that a constant test rules out
+//! is removed. Replacement fields of f-strings that format constants are
+//! formatted, and f-strings made only of strings become strings.
+
+mod fold;
+mod format_spec;
+
+pub(crate) use self::fold::float_divmod;
+pub(crate) use self::format_spec::{format_float, format_int, format_str, FormatError};
+
+use std::mem;
+
+use ast::{
+    body_docstring, Arguments, CmpOperator, Comprehension, Constant, Context, Expr, ExprKind,
+    Module, Pattern, PatternKind, Stmt, StmtKind, UnaryOperator,
+};
+use compiler::{future_features, CO_FUTURE_ANNOTATIONS};
+
+/// Optimizes a module in place.
+pub fn optimize(module: &mut Module) {
+    let _span = stage_span!("optimize");
+    let mut optimizer = Optimizer {
+        annotations: !has_future_annotations(module),
+        scope_effects: false,
+    };
+    optimizer.docstring_body(&mut module.body);
+}
+
+/// Optimizes a module made from an expression, for `Mode::Eval`, whose
+/// string is a value rather than a docstring to keep from appearing.
+pub fn optimize_expression(module: &mut Module) {
+    let _span = stage_span!("optimize");
+    let mut optimizer = Optimizer {
+        annotations: true,
+        scope_effects: false,
+    };
+    optimizer.body(&mut module.body);
+}
+
+/// Whether the module has `from __future__ import annotations`, which keeps
+/// annotations as written.
+fn has_future_annotations(module: &Module) -> bool {
+    future_features(module).is_ok_and(|future| future.flags & CO_FUTURE_ANNOTATIONS != 0)
+}
+
+fn is_future_import(stmt: &Stmt) -> bool {
+    match stmt.kind {
+        StmtKind::ImportFrom {
+            module: Some(ref module),
+            ..
+        } => module == "__future__",
+        _ => false,
+    }
+}
+
+struct Optimizer {
+    /// Whether to optimize annotations.
+    annotations: bool,
+    /// Set when code in the current scope yields, declares a name `global`
+    /// or `nonlocal`, or annotates an assignment, which gives a module or
+    /// class `__annotations__`. Such code changes the meaning of the rest of
+    /// the scope, so it is kept even where it can never run. So is an
+    /// import from `__future__`, which the compiler refuses there.
+    scope_effects: bool,
+}
+
+impl Optimizer {
+    fn body(&mut self, body: &mut Vec<Stmt>) {
+        let mut removed = None;
+        for mut stmt in mem::take(body) {
+            match self.stmt(&mut stmt) {
+                Some(live) => {
+                    removed = Some((stmt.start, stmt.end));
+                    body.extend(live);
+                }
+                None => body.push(stmt),
+            }
+        }
+        // A block cannot be empty.
+        if let (true, Some((start, end))) = (body.is_empty(), removed) {
+            body.push(Stmt {
+                kind: StmtKind::Pass,
+                start,
+                end,
+            });
+        }
+    }
+
+    /// Optimizes a branch of a statement, returning whether it has scope
+    /// effects.
+    fn branch(&mut self, body: &mut Vec<Stmt>) -> bool {
+        let outer = mem::replace(&mut self.scope_effects, false);
+        self.body(body);
+        let effects = self.scope_effects;
+        self.scope_effects |= outer;
+        effects
+    }
+
+    /// Optimizes the body of a function or class, whose scope effects are
+    /// its own.
+    fn scope(&mut self, body: &mut Vec<Stmt>) {
+        let outer = mem::replace(&mut self.scope_effects, false);
+        self.docstring_body(body);
+        self.scope_effects = outer;
+    }
+
+    /// Optimizes a module, class or function body without giving it a
+    /// docstring it did not have, by folding a leading f-string or removing
+    /// dead code before a string.
+    fn docstring_body(&mut self, body: &mut Vec<Stmt>) {
+        let had_docstring = body_docstring(body).is_some();
+        self.body(body);
+        if !had_docstring && body_docstring(body).is_some() {
+            let start = body[0].start;
+            body.insert(
+                0,
+                Stmt {
+                    kind: StmtKind::Pass,
+                    start,
+                    end: start,
+                },
+            );
+        }
+    }
+
+    /// Optimizes a statement. Returns the statements to put in its place
+    /// when it is an `if` or `while` with a constant test.
+    fn stmt(&mut self, stmt: &mut Stmt) -> Option<Vec<Stmt>> {
+        match stmt.kind {
+            StmtKind::FunctionDef {
+                ref mut args,
+                ref mut body,
+                ref mut decorator_list,
+                ref mut returns,
+                ..
+            } => {
+                self.exprs(decorator_list);
+                self.arguments(args);
+                if let Some(ref mut returns) = *returns {
+                    self.annotation(returns);
+                }
+                self.scope(body);
+            }
+            StmtKind::ClassDef {
+                ref mut bases,
+                ref mut keywords,
+                ref mut body,
+                ref mut decorator_list,
+                ..
+            } => {
+                self.exprs(decorator_list);
+                self.exprs(bases);
+                for keyword in keywords {
+                    self.expr(&mut keyword.value);
+                }
+                self.scope(body);
+            }
+            StmtKind::Return(ref mut value) => self.optional_expr(value),
+            StmtKind::Delete(ref mut targets) => self.exprs(targets),
+            StmtKind::Assign {
+                ref mut targets,
+                ref mut value,
+                ..
+            } => {
+                self.exprs(targets);
+                self.expr(value);
+            }
+            StmtKind::AugAssign {
+                ref mut target,
+                ref mut value,
+                ..
+            } => {
+                self.expr(target);
+                self.expr(value);
+            }
+            StmtKind::AnnAssign {
+                ref mut target,
+                ref mut annotation,
+                ref mut value,
+                ..
+            } => {
+                self.scope_effects = true;
+                self.expr(target);
+                self.annotation(annotation);
+                self.optional_expr(value);
+            }
+            StmtKind::For {
+                ref mut target,
+                ref mut iter,
+                ref mut body,
+                ref mut orelse,
+                ..
+            } => {
+                self.expr(target);
+                self.expr(iter);
+                iterable_to_tuple(iter);
+                self.branch(body);
+                self.branch(orelse);
+            }
+            StmtKind::While {
+                ref mut test,
+                ref mut body,
+                ref mut orelse,
+            } => {
+                self.expr(test);
+                let body_effects = self.branch(body);
+                self.branch(orelse);
+                if let ExprKind::Constant(ref constant) = test.kind {
+                    if !fold::is_true(constant) && !body_effects {
+                        return Some(mem::take(orelse));
+                    }
+                }
+            }
+            StmtKind::If {
+                ref mut test,
+                ref mut body,
+                ref mut orelse,
+            } => {
+                self.expr(test);
+                let body_effects = self.branch(body);
+                let orelse_effects = self.branch(orelse);
+                if let ExprKind::Constant(ref constant) = test.kind {
+                    if fold::is_true(constant) {
+                        // A future statement must not seem to begin the
+                        // module once the `if` around it is gone.
+                        if !orelse_effects && !body.iter().any(is_future_import) {
+                            return Some(mem::take(body));
+                        }
+                    } else if !body_effects {
+                        return Some(mem::take(orelse));
+                    }
+                }
+            }
+            StmtKind::With {
+                ref mut items,
+                ref mut body,
+                ..
+            } => {
+                for item in items {
+                    self.expr(&mut item.context_expr);
+                    self.optional_expr(&mut item.optional_vars);
+                }
+                self.branch(body);
+            }
+            StmtKind::Match {
+                ref mut subject,
+                ref mut cases,
+            } => {
+                self.expr(subject);
+                for case in cases {
+                    self.pattern(&mut case.pattern);
+                    self.optional_expr(&mut case.guard);
+                    self.branch(&mut case.body);
+                }
+            }
+            StmtKind::Raise {
+                ref mut exc,
+                ref mut cause,
+            } => {
+                self.optional_expr(exc);
+                self.optional_expr(cause);
+            }
+            StmtKind::Try {
+                ref mut body,
+                ref mut handlers,
+                ref mut orelse,
+                ref mut finalbody,
+                ..
+            } => {
+                self.branch(body);
+                for handler in handlers {
+                    self.optional_expr(&mut handler.type_);
+                    self.branch(&mut handler.body);
+                }
+                self.branch(orelse);
+                self.branch(finalbody);
+            }
+            StmtKind::Assert {
+                ref mut test,
+                ref mut msg,
+            } => {
+                self.expr(test);
+                self.optional_expr(msg);
+            }
+            StmtKind::TypeAlias { ref mut value, .. } => self.expr(value),
+            StmtKind::Global(_) | StmtKind::Nonlocal(_) => self.scope_effects = true,
+            StmtKind::ImportFrom { .. } if is_future_import(stmt) => self.scope_effects = true,
+            StmtKind::Expr(ref mut value) => self.expr(value),
+            StmtKind::Import(_)
+            | StmtKind::ImportFrom { .. }
+            | StmtKind::Pass
+            | StmtKind::Break
+            | StmtKind::Continue
+            | StmtKind::Error => {}
+        }
+        None
+    }
+
+    fn exprs(&mut self, exprs: &mut [Expr]) {
+        for expr in exprs {
+            self.expr(expr);
+        }
+    }
+
+    fn optional_expr(&mut self, expr: &mut Option<Expr>) {
+        if let Some(ref mut expr) = *expr {
+            self.expr(expr);
+        }
+    }
+
+    fn annotation(&mut self, annotation: &mut Expr) {
+        if self.annotations {
+            self.expr(annotation);
+        }
+    }
+
+    fn arguments(&mut self, arguments: &mut Arguments) {
+        self.exprs(&mut arguments.defaults);
+        for default in arguments.kw_defaults.iter_mut() {
+            self.optional_expr(default);
+        }
+        let Arguments {
+            ref mut posonlyargs,
+            ref mut args,
+            ref mut vararg,
+            ref mut kwonlyargs,
+            ref mut kwarg,
+            ..
+        } = *arguments;
+        let all = posonlyargs
+            .iter_mut()
+            .chain(args.iter_mut())
+            .chain(vararg.iter_mut())
+            .chain(kwonlyargs.iter_mut())
+            .chain(kwarg.iter_mut());
+        for arg in all {
+            if let Some(ref mut annotation) = arg.annotation {
+                self.annotation(annotation);
+            }
+        }
+    }
+
+    fn generators(&mut self, generators: &mut [Comprehension]) {
+        for generator in generators {
+            self.expr(&mut generator.target);
+            self.expr(&mut generator.iter);
+            iterable_to_tuple(&mut generator.iter);
+            self.exprs(&mut generator.ifs);
+        }
+    }
+
+    fn pattern(&mut self, pattern: &mut Pattern) {
+        match pattern.kind {
+            PatternKind::MatchValue(ref mut value) => self.expr(value),
+            PatternKind::MatchSingleton(_) | PatternKind::MatchStar(_) => {}
+            PatternKind::MatchSequence(ref mut patterns)
+            | PatternKind::MatchOr(ref mut patterns) => {
+                for pattern in patterns {
+                    self.pattern(pattern);
+                }
+            }
+            PatternKind::MatchMapping {
+                ref mut keys,
+                ref mut patterns,
+                ..
+            } => {
+                self.exprs(keys);
+                for pattern in patterns {
+                    self.pattern(pattern);
+                }
+            }
+            PatternKind::MatchClass {
+                ref mut patterns,
+                ref mut kwd_patterns,
+                ..
+            } => {
+                for pattern in patterns.iter_mut().chain(kwd_patterns) {
+                    self.pattern(pattern);
+                }
+            }
+            PatternKind::MatchAs {
+                pattern: Some(ref mut pattern),
+                ..
+            } => self.pattern(pattern),
+            PatternKind::MatchAs { pattern: None, .. } => {}
+        }
+    }
+
+    /// Optimizes the operands of an expression, then the expression itself.
+    fn expr(&mut self, expr: &mut Expr) {
+        let mut negated = None;
+        let folded = match expr.kind {
+            ExprKind::BoolOp { ref mut values, .. } => {
+                self.exprs(values);
+                None
+            }
+            ExprKind::NamedExpr { ref mut value, .. } => {
+                self.expr(value);
+                None
+            }
+            ExprKind::BinOp {
+                ref mut left,
+                op,
+                ref mut right,
+            } => {
+                self.expr(left);
+                self.expr(right);
+                match (&left.kind, &right.kind) {
+                    (ExprKind::Constant(left), ExprKind::Constant(right)) => {
+                        fold::binary(left, op, right)
+                    }
+                    _ => None,
+                }
+            }
+            ExprKind::UnaryOp {
+                op,
+                ref mut operand,
+            } => {
+                self.expr(operand);
+                match operand.kind {
+                    ExprKind::Constant(ref constant) => fold::unary(op, constant),
+                    _ => {
+                        // `not a in b` is `a not in b`.
+                        if op == UnaryOperator::Not && negate_compare(operand) {
+                            let placeholder = ExprKind::Constant(Constant::None);
+                            negated = Some(mem::replace(&mut operand.kind, placeholder));
+                        }
+                        None
+                    }
+                }
+            }
+            ExprKind::Lambda {
+                ref mut args,
+                ref mut body,
+            } => {
+                self.arguments(args);
+                let outer = mem::replace(&mut self.scope_effects, false);
+                self.expr(body);
+                self.scope_effects = outer;
+                None
+            }
+            ExprKind::IfExp {
+                ref mut test,
+                ref mut body,
+                ref mut orelse,
+            } => {
+                self.expr(test);
+                self.expr(body);
+                self.expr(orelse);
+                None
+            }
+            ExprKind::Dict {
+                ref mut keys,
+                ref mut values,
+            } => {
+                for key in keys {
+                    self.optional_expr(key);
+                }
+                self.exprs(values);
+                None
+            }
+            ExprKind::Set(ref mut elts) => {
+                self.exprs(elts);
+                None
+            }
+            ExprKind::ListComp {
+                ref mut elt,
+                ref mut generators,
+            }
+            | ExprKind::SetComp {
+                ref mut elt,
+                ref mut generators,
+            }
+            | ExprKind::GeneratorExp {
+                ref mut elt,
+                ref mut generators,
+            } => {
+                self.generators(generators);
+                self.expr(elt);
+                None
+            }
+            ExprKind::DictComp {
+                ref mut key,
+                ref mut value,
+                ref mut generators,
+            } => {
+                self.generators(generators);
+                self.expr(key);
+                self.expr(value);
+                None
+            }
+            ExprKind::Await(ref mut value) => {
+                self.expr(value);
+                None
+            }
+            ExprKind::Yield(ref mut value) => {
+                self.scope_effects = true;
+                if let Some(ref mut value) = *value {
+                    self.expr(value);
+                }
+                None
+            }
+            ExprKind::YieldFrom(ref mut value) => {
+                self.scope_effects = true;
+                self.expr(value);
+                None
+            }
+            ExprKind::Compare {
+                ref mut left,
+                ref ops,
+                ref mut comparators,
+            } => {
+                self.expr(left);
+                self.exprs(comparators);
+                if let (Some(&op), Some(last)) = (ops.last(), comparators.last_mut()) {
+                    if op == CmpOperator::In || op == CmpOperator::NotIn {
+                        iterable_to_tuple(last);
+                    }
+                }
+                None
+            }
+            ExprKind::Call {
+                ref mut func,
+                ref mut args,
+                ref mut keywords,
+            } => {
+                self.expr(func);
+                self.exprs(args);
+                for keyword in keywords {
+                    self.expr(&mut keyword.value);
+                }
+                None
+            }
+            ExprKind::FormattedValue {
+                ref mut value,
+                conversion,
+                ref mut format_spec,
+            } => {
+                self.expr(value);
+                if let Some(ref mut format_spec) = *format_spec {
+                    self.expr(format_spec);
+                }
+                let spec = match *format_spec {
+                    None => Some(""),
+                    Some(ref spec) => match spec.kind {
+                        ExprKind::Constant(Constant::Str(ref spec)) => Some(&spec[..]),
+                        _ => None,
+                    },
+                };
+                match (&value.kind, conversion, spec) {
+                    (ExprKind::Constant(value), None, Some(spec)) => fold::format(value, spec),
+                    _ => None,
+                }
+            }
+            ExprKind::JoinedStr(ref mut values) => {
+                self.exprs(values);
+                constant_tuple(values).and_then(|constant| match constant {
+                    Constant::Tuple(values) => fold::join(&values),
+                    _ => None,
+                })
+            }
+            ExprKind::Constant(_) | ExprKind::Name { .. } => None,
+            ExprKind::Attribute { ref mut value, .. } | ExprKind::Starred { ref mut value, .. } => {
+                self.expr(value);
+                None
+            }
+            ExprKind::Subscript {
+                ref mut value,
+                ref mut slice,
+                ctx,
+            } => {
+                self.expr(value);
+                self.expr(slice);
+                match (&value.kind, &slice.kind) {
+                    (ExprKind::Constant(value), ExprKind::Constant(index))
+                        if ctx == Context::Load =>
+                    {
+                        fold::subscript(value, index)
+                    }
+                    _ => None,
+                }
+            }
+            ExprKind::List { ref mut elts, .. } => {
+                self.exprs(elts);
+                None
+            }
+            ExprKind::Tuple { ref mut elts, ctx } => {
+                self.exprs(elts);
+                if ctx == Context::Load {
+                    constant_tuple(elts)
+                } else {
+                    None
+                }
+            }
+            ExprKind::Slice {
+                ref mut lower,
+                ref mut upper,
+                ref mut step,
+            } => {
+                for part in [lower, upper, step] {
+                    if let Some(ref mut part) = *part {
+                        self.expr(part);
+                    }
+                }
+                None
+            }
+        };
+        if let Some(constant) = folded {
+            expr.kind = ExprKind::Constant(constant);
+        } else if let Some(compare) = negated {
+            expr.kind = compare;
+        }
+    }
+}
+
+/// Negates a comparison with a single `in`, `not in`, `is` or `is not`.
+fn negate_compare(expr: &mut Expr) -> bool {
+    let ops = match expr.kind {
+        ExprKind::Compare { ref mut ops, .. } if ops.len() == 1 => ops,
+        _ => return false,
+    };
+    ops[0] = match ops[0] {
+        CmpOperator::In => CmpOperator::NotIn,
+        CmpOperator::NotIn => CmpOperator::In,
+        CmpOperator::Is => CmpOperator::IsNot,
+        CmpOperator::IsNot => CmpOperator::Is,
+        _ => return false,
+    };
+    true
+}
+
+fn constant_tuple(elts: &[Expr]) -> Option<Constant> {
+    elts.iter()
+        .map(|elt| match elt.kind {
+            ExprKind::Constant(ref constant) => Some(constant.clone()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(Constant::Tuple)
+}
+
+/// Turns a list that is only iterated over into a tuple, which is cheaper
+/// to build and, when all its elements are constants, is a constant itself.
+fn iterable_to_tuple(iter: &mut Expr) {
+    let constant = match iter.kind {
+        ExprKind::List {
+            ref mut elts,
+            ctx: Context::Load,
+        } => {
+            if elts
+                .iter()
+                .any(|elt| matches!(elt.kind, ExprKind::Starred { .. }))
+            {
+                return;
+            }
+            match constant_tuple(elts) {
+                Some(constant) => constant,
+                None => {
+                    iter.kind = ExprKind::Tuple {
+                        elts: mem::take(elts),
+                        ctx: Context::Load,
+                    };
+                    return;
+                }
+            }
+        }
+        _ => return,
+    };
+    iter.kind = ExprKind::Constant(constant);
+}
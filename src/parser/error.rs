@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::fmt;
+
+use tokenizer::{Location, TokenError, TokenErrorKind};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ParseErrorKind {
+    /// The source could not be tokenized.
+    Token(TokenErrorKind),
+    InvalidSyntax,
+    UnexpectedIndent,
+    ExpectedIndent,
+    /// The parser's `CancellationToken` was cancelled, while tokenizing or
+    /// parsing.
+    Cancelled,
+    /// The syntax tree would have more nodes than `ParserConfig::max_nodes`.
+    TooManyNodes,
+    /// Expressions, blocks or patterns are nested deeper than
+    /// `ParserConfig::max_depth`.
+    TooDeeplyNested,
+    /// Syntax newer than `ParserConfig::version`.
+    UnsupportedSyntax,
+}
+
+impl ParseErrorKind {
+    /// The Python exception type CPython raises for this error.
+    pub fn exception_name(self) -> &'static str {
+        match self {
+            ParseErrorKind::Token(kind) => kind.exception_name(),
+            ParseErrorKind::UnexpectedIndent | ParseErrorKind::ExpectedIndent => "IndentationError",
+            ParseErrorKind::InvalidSyntax | ParseErrorKind::UnsupportedSyntax => "SyntaxError",
+            ParseErrorKind::Cancelled => TokenErrorKind::Cancelled.exception_name(),
+            ParseErrorKind::TooManyNodes => "MemoryError",
+            ParseErrorKind::TooDeeplyNested => "RecursionError",
+        }
+    }
+
+    /// A stable diagnostic code for this error.
+    pub fn code(self) -> &'static str {
+        match self {
+            ParseErrorKind::Token(kind) => kind.code(),
+            ParseErrorKind::UnexpectedIndent => "E004",
+            ParseErrorKind::ExpectedIndent => "E003",
+            ParseErrorKind::InvalidSyntax => "E201",
+            ParseErrorKind::Cancelled => TokenErrorKind::Cancelled.code(),
+            ParseErrorKind::TooManyNodes => "E202",
+            ParseErrorKind::TooDeeplyNested => "E203",
+            ParseErrorKind::UnsupportedSyntax => "E204",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub message: String,
+    pub location: Location,
+}
+
+impl ParseError {
+    pub fn new<S: Into<String>>(
+        kind: ParseErrorKind,
+        message: S,
+        location: Location,
+    ) -> ParseError {
+        ParseError {
+            kind,
+            message: message.into(),
+            location,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} (line {}, column {})",
+            self.kind.exception_name(),
+            self.message,
+            self.location.line,
+            self.location.column
+        )
+    }
+}
+
+impl Error for ParseError {}
+
+impl From<TokenError> for ParseError {
+    fn from(err: TokenError) -> ParseError {
+        let kind = match err.kind {
+            TokenErrorKind::Cancelled => ParseErrorKind::Cancelled,
+            kind => ParseErrorKind::Token(kind),
+        };
+        match err.unclosed {
+            Some((bracket, opened)) => {
+                ParseError::new(kind, format!("'{}' was never closed", bracket), opened)
+            }
+            None => ParseError::new(kind, err.message, err.location),
+        }
+    }
+}
@@ -0,0 +1,893 @@
+use ast::{
+    BoolOperator, CmpOperator, Comprehension, Constant, Context, Expr, ExprKind, Keyword, Operator,
+    UnaryOperator,
+};
+use tokenizer::{Location, TokenType};
+
+use super::statement::set_context;
+use super::target::expr_name;
+use super::{is_keyword, ParseError, Parser, Precedence, PythonVersion};
+
+impl Parser {
+    pub(super) fn expr(&self, kind: ExprKind, start: Location) -> Expr {
+        self.count_node();
+        Expr {
+            kind,
+            start,
+            end: self.last_end,
+        }
+    }
+
+    /// Whether the current token can begin an expression, which decides if a
+    /// comma was a trailing one.
+    fn at_expression_start(&self) -> bool {
+        let token = self.peek();
+        match token.kind {
+            TokenType::Name => {
+                !is_keyword(&token.value)
+                    || matches!(
+                        token.value.as_str(),
+                        "True" | "False" | "None" | "not" | "lambda" | "await"
+                    )
+            }
+            TokenType::Number | TokenType::String => true,
+            TokenType::Operator => matches!(
+                token.value.as_str(),
+                "(" | "[" | "{" | "-" | "+" | "~" | "*" | "..."
+            ),
+            _ => false,
+        }
+    }
+
+    /// `a, *b, c`: one expression, or a tuple of them.
+    pub(super) fn star_expressions(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let first = self.star_expression()?;
+        if !self.at_operator(",") {
+            return Ok(first);
+        }
+        let mut elts = vec![first];
+        while self.eat_operator(",") {
+            if !self.at_expression_start() {
+                break;
+            }
+            elts.push(self.star_expression()?);
+        }
+        Ok(self.expr(
+            ExprKind::Tuple {
+                elts,
+                ctx: Context::Load,
+            },
+            start,
+        ))
+    }
+
+    pub(super) fn star_expression(&mut self) -> Result<Expr, ParseError> {
+        if self.at_operator("*") {
+            let start = self.start();
+            self.advance();
+            let value = self.bitwise_or()?;
+            return Ok(self.starred(value, start));
+        }
+        self.expression()
+    }
+
+    pub(super) fn star_named_expression(&mut self) -> Result<Expr, ParseError> {
+        if self.at_operator("*") {
+            let start = self.start();
+            self.advance();
+            let value = self.bitwise_or()?;
+            return Ok(self.starred(value, start));
+        }
+        self.named_expression()
+    }
+
+    fn starred(&self, value: Expr, start: Location) -> Expr {
+        self.expr(
+            ExprKind::Starred {
+                value: Box::new(value),
+                ctx: Context::Load,
+            },
+            start,
+        )
+    }
+
+    /// An expression that may be an assignment expression, `name := value`.
+    pub(super) fn named_expression(&mut self) -> Result<Expr, ParseError> {
+        // Before 3.8, `:=` is tokenized as `:` and `=`.
+        if self.peek().kind == TokenType::Name
+            && self.peek_nth(1).is_operator(":")
+            && self.peek_nth(2).is_operator("=")
+            && self.peek_nth(1).end == self.peek_nth(2).start
+        {
+            self.require_version(
+                PythonVersion::Py38,
+                "Assignment expressions are",
+                self.start(),
+            )?;
+        }
+        if self.peek().kind == TokenType::Name
+            && self.peek_nth(1).is_operator(":=")
+            && !is_keyword(&self.peek().value)
+        {
+            let start = self.start();
+            let id = self.expect_identifier()?;
+            let target = self.expr(
+                ExprKind::Name {
+                    id,
+                    ctx: Context::Store,
+                },
+                start,
+            );
+            self.advance();
+            let value = self.expression()?;
+            return Ok(self.expr(
+                ExprKind::NamedExpr {
+                    target: Box::new(target),
+                    value: Box::new(value),
+                },
+                start,
+            ));
+        }
+        let expr = self.expression()?;
+        if self.at_operator(":=") {
+            let message = format!(
+                "cannot use assignment expressions with {}",
+                expr_name(&expr)
+            );
+            return Err(self.syntax_error(message, expr.start));
+        }
+        Ok(expr)
+    }
+
+    /// A conditional expression, a lambda, or anything binding tighter.
+    /// Each one counts as a level of nesting.
+    pub(super) fn expression(&mut self) -> Result<Expr, ParseError> {
+        self.deeper(Parser::conditional_expression)
+    }
+
+    fn conditional_expression(&mut self) -> Result<Expr, ParseError> {
+        if self.at_keyword("lambda") {
+            return self.lambda();
+        }
+        let start = self.start();
+        let body = self.disjunction()?;
+        if !self.eat_keyword("if") {
+            return Ok(body);
+        }
+        let test = self.disjunction()?;
+        self.expect_keyword("else")?;
+        let orelse = self.expression()?;
+        Ok(self.expr(
+            ExprKind::IfExp {
+                test: Box::new(test),
+                body: Box::new(body),
+                orelse: Box::new(orelse),
+            },
+            start,
+        ))
+    }
+
+    fn lambda(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        self.advance();
+        let args = self.parameters(false, ":")?;
+        self.expect_operator(":")?;
+        let body = self.expression()?;
+        Ok(self.expr(
+            ExprKind::Lambda {
+                args: Box::new(args),
+                body: Box::new(body),
+            },
+            start,
+        ))
+    }
+
+    pub(super) fn yield_expression(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        self.expect_keyword("yield")?;
+        if self.eat_keyword("from") {
+            let value = self.expression()?;
+            return Ok(self.expr(ExprKind::YieldFrom(Box::new(value)), start));
+        }
+        let value = if self.at_expression_start() {
+            Some(Box::new(self.star_expressions()?))
+        } else {
+            None
+        };
+        Ok(self.expr(ExprKind::Yield(value), start))
+    }
+
+    fn disjunction(&mut self) -> Result<Expr, ParseError> {
+        self.bool_operation("or", BoolOperator::Or, Parser::conjunction)
+    }
+
+    fn conjunction(&mut self) -> Result<Expr, ParseError> {
+        self.bool_operation("and", BoolOperator::And, Parser::inversion)
+    }
+
+    fn bool_operation(
+        &mut self,
+        keyword: &str,
+        op: BoolOperator,
+        operand: fn(&mut Parser) -> Result<Expr, ParseError>,
+    ) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let first = operand(self)?;
+        if !self.at_keyword(keyword) {
+            return Ok(first);
+        }
+        let mut values = vec![first];
+        while self.eat_keyword(keyword) {
+            values.push(operand(self)?);
+        }
+        Ok(self.expr(ExprKind::BoolOp { op, values }, start))
+    }
+
+    fn inversion(&mut self) -> Result<Expr, ParseError> {
+        if self.at_keyword("not") {
+            let start = self.start();
+            self.advance();
+            let operand = self.deeper(Parser::inversion)?;
+            return Ok(self.expr(
+                ExprKind::UnaryOp {
+                    op: UnaryOperator::Not,
+                    operand: Box::new(operand),
+                },
+                start,
+            ));
+        }
+        self.comparison()
+    }
+
+    fn comparison_operator(&self) -> Option<(CmpOperator, usize)> {
+        let token = self.peek();
+        let op = match token.kind {
+            TokenType::Operator => match token.value.as_str() {
+                "==" => CmpOperator::Eq,
+                "!=" => CmpOperator::NotEq,
+                "<" => CmpOperator::Lt,
+                "<=" => CmpOperator::LtE,
+                ">" => CmpOperator::Gt,
+                ">=" => CmpOperator::GtE,
+                _ => return None,
+            },
+            TokenType::Name => match token.value.as_str() {
+                "in" => CmpOperator::In,
+                "not" if self.peek_nth(1).is_name("in") => return Some((CmpOperator::NotIn, 2)),
+                "is" if self.peek_nth(1).is_name("not") => return Some((CmpOperator::IsNot, 2)),
+                "is" => CmpOperator::Is,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some((op, 1))
+    }
+
+    fn comparison(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let left = self.bitwise_or()?;
+        let mut ops = Vec::new();
+        let mut comparators = Vec::new();
+        while let Some((op, length)) = self.comparison_operator() {
+            for _ in 0..length {
+                self.advance();
+            }
+            ops.push(op);
+            comparators.push(self.bitwise_or()?);
+        }
+        if ops.is_empty() {
+            return Ok(left);
+        }
+        Ok(self.expr(
+            ExprKind::Compare {
+                left: Box::new(left),
+                ops,
+                comparators,
+            },
+            start,
+        ))
+    }
+
+    pub(super) fn bitwise_or(&mut self) -> Result<Expr, ParseError> {
+        self.binary(Precedence::BitOr)
+    }
+
+    /// Binary operators from `|` to `*`, by precedence climbing: operators
+    /// at `min` or tighter are consumed, and each right operand only takes
+    /// tighter operators so that equal levels group to the left.
+    fn binary(&mut self, min: Precedence) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let mut left = self.factor()?;
+        // Each operator nests the operands so far one level deeper in the
+        // tree, but not in the source.
+        let mut links = 0;
+        loop {
+            let token = self.peek();
+            if token.kind != TokenType::Operator {
+                break;
+            }
+            let precedence = match Precedence::of_binary_operator(&token.value) {
+                Some(precedence) if precedence >= min && precedence <= Precedence::Term => {
+                    precedence
+                }
+                _ => break,
+            };
+            let op = Operator::from_symbol(&token.value).unwrap();
+            self.link()?;
+            links += 1;
+            self.advance();
+            let right = self.binary(precedence.next())?;
+            left = self.expr(
+                ExprKind::BinOp {
+                    left: Box::new(left),
+                    op,
+                    right: Box::new(right),
+                },
+                start,
+            );
+        }
+        self.unlink(links);
+        Ok(left)
+    }
+
+    fn factor(&mut self) -> Result<Expr, ParseError> {
+        let op = match self.peek().value.as_str() {
+            "+" => UnaryOperator::UAdd,
+            "-" => UnaryOperator::USub,
+            "~" => UnaryOperator::Invert,
+            _ => return self.power(),
+        };
+        if self.peek().kind != TokenType::Operator {
+            return self.power();
+        }
+        let start = self.start();
+        self.advance();
+        let operand = self.deeper(Parser::factor)?;
+        Ok(self.expr(
+            ExprKind::UnaryOp {
+                op,
+                operand: Box::new(operand),
+            },
+            start,
+        ))
+    }
+
+    fn power(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let base = if self.at_keyword("await") {
+            self.advance();
+            let value = self.primary()?;
+            self.expr(ExprKind::Await(Box::new(value)), start)
+        } else {
+            self.primary()?
+        };
+        if !self.eat_operator("**") {
+            return Ok(base);
+        }
+        let exponent = self.deeper(Parser::factor)?;
+        Ok(self.expr(
+            ExprKind::BinOp {
+                left: Box::new(base),
+                op: Operator::Pow,
+                right: Box::new(exponent),
+            },
+            start,
+        ))
+    }
+
+    /// An atom followed by any attribute accesses, calls and subscripts.
+    fn primary(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let mut expr = self.atom()?;
+        let mut links = 0;
+        loop {
+            if self.at_operator(".") || self.at_operator("(") || self.at_operator("[") {
+                self.link()?;
+                links += 1;
+            }
+            if self.eat_operator(".") {
+                let attr = self.expect_identifier()?;
+                expr = self.expr(
+                    ExprKind::Attribute {
+                        value: Box::new(expr),
+                        attr,
+                        ctx: Context::Load,
+                    },
+                    start,
+                );
+            } else if self.eat_operator("(") {
+                let (args, keywords) = self.call_arguments(true)?;
+                expr = self.expr(
+                    ExprKind::Call {
+                        func: Box::new(expr),
+                        args,
+                        keywords,
+                    },
+                    start,
+                );
+            } else if self.eat_operator("[") {
+                let slice = self.slices()?;
+                self.expect_operator("]")?;
+                expr = self.expr(
+                    ExprKind::Subscript {
+                        value: Box::new(expr),
+                        slice: Box::new(slice),
+                        ctx: Context::Load,
+                    },
+                    start,
+                );
+            } else {
+                self.unlink(links);
+                return Ok(expr);
+            }
+        }
+    }
+
+    /// The arguments of a call or class definition, after the opening
+    /// parenthesis and up to and including the closing one. Only a call,
+    /// when `generator` is set, can take a generator expression without
+    /// parentheses of its own, and then only as its sole argument.
+    pub(super) fn call_arguments(
+        &mut self,
+        generator: bool,
+    ) -> Result<(Vec<Expr>, Vec<Keyword>), ParseError> {
+        let open = self.tokens.previous().unwrap().start;
+        let mut args = Vec::new();
+        let mut keywords: Vec<Keyword> = Vec::new();
+        // Like CPython, a positional argument after keywords is reported
+        // where the arguments end, unless something else is wrong first;
+        // the number of keywords before it is kept to tell which `**`
+        // arguments came after it.
+        let mut misplaced = None;
+        while !self.at_operator(")") {
+            let start = self.start();
+            if self.peek_nth(1).is_operator("=")
+                && matches!(self.peek().value.as_str(), "True" | "False" | "None")
+            {
+                let message = format!("cannot assign to {}", self.peek().value);
+                return Err(self.syntax_error(message, start));
+            }
+            if self.eat_operator("*") {
+                let since = misplaced.map_or(0, |(_, keywords)| keywords);
+                if keywords[since..]
+                    .iter()
+                    .any(|keyword| keyword.arg.is_none())
+                {
+                    let message = misplaced.map_or(
+                        "iterable argument unpacking follows keyword argument unpacking",
+                        |(message, _)| message,
+                    );
+                    return Err(self.syntax_error(message, start));
+                }
+                let value = self.expression()?;
+                if self.at_comprehension() {
+                    return Err(self.syntax_error(
+                        "iterable unpacking cannot be used in comprehension",
+                        start,
+                    ));
+                }
+                args.push(self.starred(value, start));
+            } else if self.eat_operator("**") {
+                let value = self.expression()?;
+                keywords.push(Keyword {
+                    arg: None,
+                    value,
+                    start,
+                    end: self.last_end,
+                });
+            } else if self.peek().kind == TokenType::Name
+                && self.peek_nth(1).is_operator("=")
+                && !is_keyword(&self.peek().value)
+            {
+                let arg = self.expect_identifier()?;
+                self.advance();
+                let value = self.expression()?;
+                if self.at_comprehension() {
+                    return Err(self.syntax_error(
+                        "invalid syntax. Maybe you meant '==' or ':=' instead of '='?",
+                        start,
+                    ));
+                }
+                keywords.push(Keyword {
+                    arg: Some(arg),
+                    value,
+                    start,
+                    end: self.last_end,
+                });
+            } else {
+                // CPython names what cannot be assigned to by `:=` everywhere
+                // but in the arguments of a call.
+                let mut arg = match self.named_expression() {
+                    Err(_) if self.at_operator(":=") => {
+                        return Err(self.syntax_error("invalid syntax", self.start()))
+                    }
+                    arg => arg?,
+                };
+                if self.at_operator("=") {
+                    return Err(self.syntax_error(
+                        "expression cannot contain assignment, perhaps you meant \"==\"?",
+                        start,
+                    ));
+                }
+                if self.at_comprehension() {
+                    let keyword_for = self.start();
+                    if let Some((message, _)) = misplaced {
+                        return Err(self.syntax_error(message, keyword_for));
+                    }
+                    let generators = self.generators()?;
+                    arg = self.expr(
+                        ExprKind::GeneratorExp {
+                            elt: Box::new(arg),
+                            generators,
+                        },
+                        start,
+                    );
+                    if !args.is_empty() || !keywords.is_empty() || !self.at_operator(")") {
+                        return Err(
+                            self.syntax_error("Generator expression must be parenthesized", start)
+                        );
+                    }
+                    if !generator {
+                        return Err(self.syntax_error("invalid syntax", keyword_for));
+                    }
+                    // A lone generator argument shares the call's
+                    // parentheses, and CPython includes them in its span.
+                    arg.start = open;
+                    arg.end = self.peek().end;
+                }
+                if !keywords.is_empty() && misplaced.is_none() {
+                    let message = if keywords.iter().any(|keyword| keyword.arg.is_none()) {
+                        "positional argument follows keyword argument unpacking"
+                    } else {
+                        "positional argument follows keyword argument"
+                    };
+                    misplaced = Some((message, keywords.len()));
+                }
+                args.push(arg);
+            }
+            if !self.eat_operator(",") {
+                break;
+            }
+        }
+        if let Some((message, _)) = misplaced {
+            return Err(self.syntax_error(message, self.start()));
+        }
+        self.expect_operator(")")?;
+        Ok((args, keywords))
+    }
+
+    /// The contents of a subscript: one slice, or a tuple of them.
+    fn slices(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let first = self.slice()?;
+        if !self.at_operator(",") {
+            return Ok(first);
+        }
+        let mut elts = vec![first];
+        while self.eat_operator(",") {
+            if self.at_operator("]") {
+                break;
+            }
+            elts.push(self.slice()?);
+        }
+        Ok(self.expr(
+            ExprKind::Tuple {
+                elts,
+                ctx: Context::Load,
+            },
+            start,
+        ))
+    }
+
+    fn slice(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let lower = if self.at_operator(":") {
+            None
+        } else {
+            let lower = self.star_named_expression()?;
+            if !self.at_operator(":") {
+                return Ok(lower);
+            }
+            Some(Box::new(lower))
+        };
+        self.expect_operator(":")?;
+        let upper = self.optional_slice_part()?;
+        let step = if self.eat_operator(":") {
+            self.optional_slice_part()?
+        } else {
+            None
+        };
+        Ok(self.expr(ExprKind::Slice { lower, upper, step }, start))
+    }
+
+    fn optional_slice_part(&mut self) -> Result<Option<Box<Expr>>, ParseError> {
+        if self.at_operator(":") || self.at_operator(",") || self.at_operator("]") {
+            Ok(None)
+        } else {
+            Ok(Some(Box::new(self.expression()?)))
+        }
+    }
+
+    pub(super) fn atom(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let token = self.peek().clone();
+        match token.kind {
+            TokenType::Name => {
+                let constant = match token.value.as_str() {
+                    "True" => Constant::Bool(true),
+                    "False" => Constant::Bool(false),
+                    "None" => Constant::None,
+                    _ => {
+                        let id = self.expect_identifier()?;
+                        return Ok(self.expr(
+                            ExprKind::Name {
+                                id,
+                                ctx: Context::Load,
+                            },
+                            start,
+                        ));
+                    }
+                };
+                self.advance();
+                Ok(self.expr(ExprKind::Constant(constant), start))
+            }
+            TokenType::Number => {
+                self.advance();
+                let constant = super::literal::number(&token.value)
+                    .map_err(|message| self.syntax_error(message, start))?;
+                Ok(self.expr(ExprKind::Constant(constant), start))
+            }
+            TokenType::String => self.strings(),
+            TokenType::Operator => match token.value.as_str() {
+                "(" => self.parenthesized(),
+                "[" => self.list_display(),
+                "{" => self.brace_display(),
+                "..." => {
+                    self.advance();
+                    Ok(self.expr(ExprKind::Constant(Constant::Ellipsis), start))
+                }
+                _ => Err(self.unexpected()),
+            },
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    fn parenthesized(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        self.advance();
+        if self.eat_operator(")") {
+            return Ok(self.expr(
+                ExprKind::Tuple {
+                    elts: Vec::new(),
+                    ctx: Context::Load,
+                },
+                start,
+            ));
+        }
+        if self.at_keyword("yield") {
+            let expr = self.yield_expression()?;
+            self.expect_operator(")")?;
+            return Ok(expr);
+        }
+        if self.at_operator("**") {
+            return Err(
+                self.syntax_error("cannot use double starred expression here", self.start())
+            );
+        }
+        let first = self.star_named_expression()?;
+        if self.at_comprehension() {
+            let generators = self.generators()?;
+            self.expect_operator(")")?;
+            return Ok(self.expr(
+                ExprKind::GeneratorExp {
+                    elt: Box::new(first),
+                    generators,
+                },
+                start,
+            ));
+        }
+        if !self.at_operator(",") {
+            if let ExprKind::Starred { .. } = first.kind {
+                if self.at_operator(")") {
+                    return Err(
+                        self.syntax_error("cannot use starred expression here", first.start)
+                    );
+                }
+            }
+            self.expect_operator(")")?;
+            return Ok(first);
+        }
+        let elts = self.display_elements(first, ")")?;
+        Ok(self.expr(
+            ExprKind::Tuple {
+                elts,
+                ctx: Context::Load,
+            },
+            start,
+        ))
+    }
+
+    /// The remaining comma-separated elements of a display after `first`,
+    /// up to and including `closing`.
+    fn display_elements(&mut self, first: Expr, closing: &str) -> Result<Vec<Expr>, ParseError> {
+        let mut elts = vec![first];
+        while self.eat_operator(",") {
+            if self.at_operator(closing) {
+                break;
+            }
+            elts.push(self.star_named_expression()?);
+        }
+        self.expect_operator(closing)?;
+        Ok(elts)
+    }
+
+    fn list_display(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        self.advance();
+        if self.eat_operator("]") {
+            return Ok(self.expr(
+                ExprKind::List {
+                    elts: Vec::new(),
+                    ctx: Context::Load,
+                },
+                start,
+            ));
+        }
+        let first = self.star_named_expression()?;
+        if self.at_comprehension() {
+            let generators = self.generators()?;
+            self.expect_operator("]")?;
+            return Ok(self.expr(
+                ExprKind::ListComp {
+                    elt: Box::new(first),
+                    generators,
+                },
+                start,
+            ));
+        }
+        let elts = self.display_elements(first, "]")?;
+        Ok(self.expr(
+            ExprKind::List {
+                elts,
+                ctx: Context::Load,
+            },
+            start,
+        ))
+    }
+
+    /// A dict or set display or comprehension.
+    fn brace_display(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        self.advance();
+        if self.eat_operator("}") {
+            return Ok(self.expr(
+                ExprKind::Dict {
+                    keys: Vec::new(),
+                    values: Vec::new(),
+                },
+                start,
+            ));
+        }
+
+        let mut keys = Vec::new();
+        let mut values = Vec::new();
+        if self.eat_operator("**") {
+            keys.push(None);
+            values.push(self.bitwise_or()?);
+        } else {
+            let first = self.star_named_expression()?;
+            if !self.eat_operator(":") {
+                if self.at_comprehension() {
+                    let generators = self.generators()?;
+                    self.expect_operator("}")?;
+                    return Ok(self.expr(
+                        ExprKind::SetComp {
+                            elt: Box::new(first),
+                            generators,
+                        },
+                        start,
+                    ));
+                }
+                let elts = self.display_elements(first, "}")?;
+                return Ok(self.expr(ExprKind::Set(elts), start));
+            }
+            let value = self.expression()?;
+            if self.at_comprehension() {
+                let generators = self.generators()?;
+                self.expect_operator("}")?;
+                return Ok(self.expr(
+                    ExprKind::DictComp {
+                        key: Box::new(first),
+                        value: Box::new(value),
+                        generators,
+                    },
+                    start,
+                ));
+            }
+            keys.push(Some(first));
+            values.push(value);
+        }
+
+        while self.eat_operator(",") {
+            if self.at_operator("}") {
+                break;
+            }
+            if self.eat_operator("**") {
+                keys.push(None);
+                values.push(self.bitwise_or()?);
+            } else {
+                keys.push(Some(self.expression()?));
+                self.expect_operator(":")?;
+                values.push(self.expression()?);
+            }
+        }
+        self.expect_operator("}")?;
+        Ok(self.expr(ExprKind::Dict { keys, values }, start))
+    }
+
+    fn at_comprehension(&self) -> bool {
+        self.at_keyword("for") || (self.at_keyword("async") && self.peek_nth(1).is_name("for"))
+    }
+
+    fn generators(&mut self) -> Result<Vec<Comprehension>, ParseError> {
+        let mut generators = Vec::new();
+        while self.at_comprehension() {
+            let is_async = self.eat_keyword("async");
+            self.expect_keyword("for")?;
+            let target = self.target_list()?;
+            self.expect_keyword("in")?;
+            let iter = self.disjunction()?;
+            let mut ifs = Vec::new();
+            while self.eat_keyword("if") {
+                ifs.push(self.disjunction()?);
+            }
+            generators.push(Comprehension {
+                target,
+                iter,
+                ifs,
+                is_async,
+            });
+        }
+        Ok(generators)
+    }
+
+    /// The targets of a `for` loop or comprehension, which stop before `in`.
+    pub(super) fn target_list(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let first = self.star_target()?;
+        if !self.at_operator(",") {
+            self.check_target(&first, Context::Store)?;
+            return Ok(first);
+        }
+        let mut elts = vec![first];
+        while self.eat_operator(",") {
+            if self.at_keyword("in") || self.at_operator("=") {
+                break;
+            }
+            elts.push(self.star_target()?);
+        }
+        let mut target = self.expr(
+            ExprKind::Tuple {
+                elts,
+                ctx: Context::Load,
+            },
+            start,
+        );
+        set_context(&mut target, Context::Store);
+        self.check_target(&target, Context::Store)?;
+        Ok(target)
+    }
+
+    pub(super) fn star_target(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let mut target = if self.eat_operator("*") {
+            let value = self.star_target()?;
+            self.starred(value, start)
+        } else {
+            self.bitwise_or()?
+        };
+        set_context(&mut target, Context::Store);
+        Ok(target)
+    }
+}
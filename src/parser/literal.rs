@@ -0,0 +1,588 @@
+use ast::{Constant, Expr, ExprKind};
+use tokenizer::{tokenize_with_config, Location, StringPrefix, Token, TokenType, TokenizerConfig};
+
+use super::{unicode_names, ParseError, Parser, PythonVersion};
+
+/// Evaluates a number literal.
+pub(super) fn number(text: &str) -> Result<Constant, String> {
+    let cleaned: String = text
+        .chars()
+        .filter(|&c| c != '_')
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+    if let Some(imag) = cleaned.strip_suffix('j') {
+        let imag = imag
+            .parse::<f64>()
+            .map_err(|_| format!("invalid imaginary literal '{}'", text))?;
+        return Ok(Constant::Complex { real: 0.0, imag });
+    }
+    let (digits, radix) = match cleaned.get(..2) {
+        Some("0x") => (&cleaned[2..], 16),
+        Some("0o") => (&cleaned[2..], 8),
+        Some("0b") => (&cleaned[2..], 2),
+        _ if cleaned.contains(['.', 'e']) => {
+            return cleaned
+                .parse::<f64>()
+                .map(Constant::Float)
+                .map_err(|_| format!("invalid float literal '{}'", text));
+        }
+        _ => (&cleaned[..], 10),
+    };
+    match i64::from_str_radix(digits, radix) {
+        Ok(value) => Ok(Constant::Int(value)),
+        Err(_) if !digits.is_empty() && digits.chars().all(|c| c.is_digit(radix)) => {
+            Ok(Constant::LongInt(to_decimal(digits, radix)))
+        }
+        Err(_) => Err(format!("invalid integer literal '{}'", text)),
+    }
+}
+
+/// Converts the digits of an integer of any size to decimal.
+fn to_decimal(digits: &str, radix: u32) -> String {
+    const BASE: u64 = 1_000_000_000;
+    // Little-endian limbs of nine decimal digits each.
+    let mut limbs: Vec<u64> = vec![0];
+    for c in digits.chars() {
+        let mut carry = u64::from(c.to_digit(radix).unwrap());
+        for limb in &mut limbs {
+            let value = *limb * u64::from(radix) + carry;
+            *limb = value % BASE;
+            carry = value / BASE;
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+    }
+    let mut decimal = limbs.last().unwrap().to_string();
+    for limb in limbs.iter().rev().skip(1) {
+        decimal.push_str(&format!("{:09}", limb));
+    }
+    decimal
+}
+
+/// A string literal token split into its parts.
+struct StringToken<'a> {
+    bytes: bool,
+    raw: bool,
+    formatted: bool,
+    body: &'a str,
+    /// The byte offset of `body` within the token.
+    offset: usize,
+}
+
+impl<'a> StringToken<'a> {
+    fn split(token: &'a Token) -> StringToken<'a> {
+        let value = &token.value;
+        let (prefix, prefix_length) = token
+            .string_prefix()
+            .unwrap_or((StringPrefix::default(), 0));
+        let quoted = &value[prefix_length..];
+        let quote = &quoted[..1];
+        let delimiter_length = if quoted.len() >= 6 && quoted.starts_with(&quote.repeat(3)[..]) {
+            3
+        } else {
+            1
+        };
+        StringToken {
+            bytes: prefix.bytes,
+            raw: prefix.raw,
+            formatted: prefix.formatted,
+            body: &quoted[delimiter_length..quoted.len() - delimiter_length],
+            offset: prefix_length + delimiter_length,
+        }
+    }
+}
+
+/// Processes the backslash escapes of a `str` literal.
+fn decode_str(body: &str) -> Result<String, String> {
+    let mut decoded = String::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some(escaped) => escaped,
+            None => {
+                decoded.push('\\');
+                break;
+            }
+        };
+        match escaped {
+            '\n' => {}
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+            }
+            'x' | 'u' | 'U' => {
+                let length = match escaped {
+                    'x' => 2,
+                    'u' => 4,
+                    _ => 8,
+                };
+                let code = hex_escape(&mut chars, length)
+                    .ok_or_else(|| format!("truncated \\{} escape", escaped))?;
+                if code > 0x10ffff {
+                    return Err("illegal Unicode character".to_string());
+                }
+                // A `String` cannot hold a lone surrogate, which Python allows.
+                decoded.push(::std::char::from_u32(code).unwrap_or('\u{fffd}'));
+            }
+            'N' => {
+                if chars.next() != Some('{') {
+                    return Err("malformed \\N character escape".to_string());
+                }
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') if !name.is_empty() => break,
+                        Some('}') | None => {
+                            return Err("malformed \\N character escape".to_string())
+                        }
+                        Some(c) => name.push(c),
+                    }
+                }
+                let c = unicode_names::lookup(&name)
+                    .ok_or_else(|| "unknown Unicode character name".to_string())?;
+                decoded.push(c);
+            }
+            _ => match simple_escape(escaped, &mut chars) {
+                Some(code) => decoded.push(::std::char::from_u32(code).unwrap()),
+                None => {
+                    decoded.push('\\');
+                    decoded.push(escaped);
+                }
+            },
+        }
+    }
+    Ok(decoded)
+}
+
+/// Processes the backslash escapes of a `bytes` literal.
+fn decode_bytes(body: &str, raw: bool) -> Result<Vec<u8>, String> {
+    if !body.is_ascii() {
+        return Err("bytes can only contain ASCII literal characters".to_string());
+    }
+    if raw {
+        return Ok(body.as_bytes().to_vec());
+    }
+    let mut decoded = Vec::with_capacity(body.len());
+    let mut chars = body.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            decoded.push(c as u8);
+            continue;
+        }
+        let escaped = match chars.next() {
+            Some(escaped) => escaped,
+            None => {
+                decoded.push(b'\\');
+                break;
+            }
+        };
+        match escaped {
+            '\n' => {}
+            '\r' => {
+                if chars.peek() == Some(&'\n') {
+                    chars.next();
+                }
+            }
+            'x' => {
+                let code =
+                    hex_escape(&mut chars, 2).ok_or_else(|| "truncated \\x escape".to_string())?;
+                decoded.push(code as u8);
+            }
+            _ => match simple_escape(escaped, &mut chars) {
+                Some(code) => decoded.push(code as u8),
+                None => {
+                    decoded.push(b'\\');
+                    decoded.push(escaped as u8);
+                }
+            },
+        }
+    }
+    Ok(decoded)
+}
+
+fn hex_escape<I: Iterator<Item = char>>(
+    chars: &mut ::std::iter::Peekable<I>,
+    length: usize,
+) -> Option<u32> {
+    let mut code = 0;
+    for _ in 0..length {
+        let digit = chars.peek()?.to_digit(16)?;
+        chars.next();
+        code = code * 16 + digit;
+    }
+    Some(code)
+}
+
+/// The code of a single-character or octal escape, or `None` if the
+/// backslash is kept as is.
+fn simple_escape<I: Iterator<Item = char>>(
+    escaped: char,
+    chars: &mut ::std::iter::Peekable<I>,
+) -> Option<u32> {
+    let code = match escaped {
+        '\\' | '\'' | '"' => escaped as u32,
+        'a' => 0x07,
+        'b' => 0x08,
+        'f' => 0x0c,
+        'n' => 0x0a,
+        'r' => 0x0d,
+        't' => 0x09,
+        'v' => 0x0b,
+        '0'..='7' => {
+            let mut code = escaped.to_digit(8).unwrap();
+            for _ in 0..2 {
+                match chars.peek().and_then(|c| c.to_digit(8)) {
+                    Some(digit) => {
+                        chars.next();
+                        code = code * 8 + digit;
+                    }
+                    None => break,
+                }
+            }
+            code
+        }
+        _ => return None,
+    };
+    Some(code)
+}
+
+/// Collects the parts of an f-string, merging adjacent literal text.
+#[derive(Default)]
+struct JoinedStr {
+    values: Vec<Expr>,
+    literal: String,
+}
+
+impl JoinedStr {
+    fn flush(&mut self, start: Location, end: Location) {
+        if !self.literal.is_empty() {
+            let value = ::std::mem::take(&mut self.literal);
+            self.values.push(Expr {
+                kind: ExprKind::Constant(Constant::Str(value)),
+                start,
+                end,
+            });
+        }
+    }
+}
+
+/// Where, in the token, the text at `offset` starts.
+fn location_in(token: &Token, offset: usize) -> Location {
+    let before = &token.value[..offset];
+    match before.rfind('\n') {
+        Some(newline) => Location::new(
+            token.start.line + before.matches('\n').count(),
+            before[newline + 1..].chars().count(),
+        ),
+        None => Location::new(
+            token.start.line,
+            token.start.column + before.chars().count(),
+        ),
+    }
+}
+
+impl Parser {
+    /// One or more adjacent string literals, concatenated.
+    pub(super) fn strings(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let mut tokens = Vec::new();
+        while self.peek().kind == TokenType::String {
+            tokens.push(self.advance());
+        }
+        let end = self.last_end;
+
+        let mut bytes: Option<Vec<u8>> = None;
+        let mut joined = JoinedStr::default();
+        let mut formatted = false;
+        for (index, token) in tokens.iter().enumerate() {
+            let string = StringToken::split(token);
+            if index > 0 && string.bytes != bytes.is_some() {
+                return Err(
+                    self.syntax_error("cannot mix bytes and nonbytes literals", token.start)
+                );
+            }
+            let error = |message: String| self.syntax_error(message, token.start);
+            if string.bytes {
+                let decoded = decode_bytes(string.body, string.raw).map_err(error)?;
+                bytes.get_or_insert_with(Vec::new).extend(decoded);
+            } else if string.formatted {
+                formatted = true;
+                self.fstring(
+                    token,
+                    &string,
+                    string.offset,
+                    string.body.len(),
+                    &mut joined,
+                    start,
+                    end,
+                )?;
+            } else if string.raw {
+                joined.literal.push_str(string.body);
+            } else {
+                joined
+                    .literal
+                    .push_str(&decode_str(string.body).map_err(error)?);
+            }
+        }
+
+        let kind = if let Some(bytes) = bytes {
+            ExprKind::Constant(Constant::Bytes(bytes))
+        } else if formatted {
+            joined.flush(start, end);
+            ExprKind::JoinedStr(joined.values)
+        } else {
+            ExprKind::Constant(Constant::Str(joined.literal))
+        };
+        Ok(self.expr(kind, start))
+    }
+
+    /// Adds the literal text and replacement fields of the part of an
+    /// f-string token between byte offsets `from` and `from + length`.
+    #[allow(clippy::too_many_arguments)]
+    fn fstring(
+        &self,
+        token: &Token,
+        string: &StringToken,
+        from: usize,
+        length: usize,
+        joined: &mut JoinedStr,
+        start: Location,
+        end: Location,
+    ) -> Result<(), ParseError> {
+        let text = &token.value[from..from + length];
+        let error = |offset: usize, message: &str| {
+            self.syntax_error(
+                format!("f-string: {}", message),
+                location_in(token, from + offset),
+            )
+        };
+        let mut index = 0;
+        while index < text.len() {
+            let rest = &text[index..];
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                joined.literal.push_str(&rest[..1]);
+                index += 2;
+                continue;
+            }
+            if rest.starts_with('}') {
+                return Err(error(index, "single '}' is not allowed"));
+            }
+            if !rest.starts_with('{') {
+                let length = literal_end(rest, string.raw);
+                let literal = &rest[..length];
+                if string.raw {
+                    joined.literal.push_str(literal);
+                } else {
+                    let decoded = decode_str(literal).map_err(|message| error(index, &message))?;
+                    joined.literal.push_str(&decoded);
+                }
+                index += length;
+                continue;
+            }
+
+            let field = index + 1;
+            let (expression_end, debug) =
+                field_end(text, field).ok_or_else(|| error(index, "expecting '}'"))?;
+            let expression = &text[field..expression_end];
+            if expression.trim().is_empty() {
+                return Err(error(index, "empty expression not allowed"));
+            }
+            let mut value =
+                self.fstring_expression(expression, location_in(token, from + field))?;
+            index = expression_end;
+            if debug {
+                self.require_version(
+                    PythonVersion::Py38,
+                    "f-string: self documenting expressions are",
+                    location_in(token, from + field),
+                )?;
+                index += 1;
+                while text[index..].starts_with([' ', '\t', '\n', '\r']) {
+                    index += 1;
+                }
+                joined.literal.push_str(&text[field..index]);
+            }
+
+            let mut conversion = None;
+            if text[index..].starts_with('!') {
+                conversion = text[index + 1..]
+                    .chars()
+                    .next()
+                    .filter(|c| "sra".contains(*c));
+                if conversion.is_none() {
+                    return Err(error(
+                        index,
+                        "invalid conversion character: expected 's', 'r', or 'a'",
+                    ));
+                }
+                index += 2;
+            }
+
+            let mut format_spec = None;
+            if text[index..].starts_with(':') {
+                let spec_start = index + 1;
+                let spec_end =
+                    spec_end(text, spec_start).ok_or_else(|| error(index, "expecting '}'"))?;
+                let mut spec = JoinedStr::default();
+                self.fstring(
+                    token,
+                    string,
+                    from + spec_start,
+                    spec_end - spec_start,
+                    &mut spec,
+                    start,
+                    end,
+                )?;
+                spec.flush(start, end);
+                format_spec = Some(Box::new(Expr {
+                    kind: ExprKind::JoinedStr(spec.values),
+                    start,
+                    end,
+                }));
+                index = spec_end;
+            }
+            if !text[index..].starts_with('}') {
+                return Err(error(index, "expecting '}'"));
+            }
+            index += 1;
+
+            if debug && conversion.is_none() && format_spec.is_none() {
+                conversion = Some('r');
+            }
+            joined.flush(start, end);
+            value = Expr {
+                kind: ExprKind::FormattedValue {
+                    value: Box::new(value),
+                    conversion,
+                    format_spec,
+                },
+                start,
+                end,
+            };
+            joined.values.push(value);
+        }
+        Ok(())
+    }
+
+    /// Parses the expression of a replacement field that starts at `base`.
+    fn fstring_expression(&self, expression: &str, base: Location) -> Result<Expr, ParseError> {
+        // Parenthesized, as CPython does, so the expression may span lines.
+        let shift = |location: Location| {
+            if location.line == 1 {
+                Location::new(base.line, (base.column + location.column).saturating_sub(1))
+            } else {
+                Location::new(base.line + location.line - 1, location.column)
+            }
+        };
+        let source = format!("({})", expression);
+        let config = TokenizerConfig {
+            version: self.version,
+            ..TokenizerConfig::default()
+        };
+        let mut tokens = tokenize_with_config(&source, config).map_err(|err| {
+            let mut err = ParseError::from(err);
+            err.location = shift(err.location);
+            err
+        })?;
+        for token in &mut tokens {
+            token.start = shift(token.start);
+            token.end = shift(token.end);
+        }
+        let mut parser = self.nested(tokens);
+        let expr = parser.eval_input().map_err(|mut err| {
+            err.message = format!("f-string: {}", err.message);
+            err
+        })?;
+        self.nodes.set(parser.nodes.get());
+        Ok(expr)
+    }
+}
+
+/// Finds where the literal text at the start of an f-string part ends: at
+/// the first `{` or `}` that is not part of a `\N{...}` escape.
+fn literal_end(text: &str, raw: bool) -> usize {
+    let bytes = text.as_bytes();
+    let mut index = 0;
+    while index < bytes.len() {
+        match bytes[index] {
+            b'{' | b'}' => break,
+            b'\\' if !raw && bytes[index + 1..].starts_with(b"N{") => {
+                index = text[index..]
+                    .find('}')
+                    .map_or(text.len(), |end| index + end + 1);
+            }
+            b'\\' if !raw => index += 2,
+            _ => index += 1,
+        }
+    }
+    index.min(text.len())
+}
+
+/// Finds where the expression of a replacement field starting at `from`
+/// ends: at a top-level `!`, `:` or `}`, or at the `=` of a self-documenting
+/// field, which is reported as `true`.
+fn field_end(text: &str, from: usize) -> Option<(usize, bool)> {
+    let bytes = text.as_bytes();
+    let mut depth = 0;
+    let mut index = from;
+    while index < bytes.len() {
+        match bytes[index] {
+            quote @ (b'\'' | b'"') => {
+                let triple = [quote; 3];
+                let delimiter: &[u8] = if bytes[index..].starts_with(&triple) {
+                    &triple
+                } else {
+                    &triple[..1]
+                };
+                index += delimiter.len();
+                while !bytes[index..].starts_with(delimiter) {
+                    index += 1;
+                    if index >= bytes.len() {
+                        return None;
+                    }
+                }
+                index += delimiter.len();
+                continue;
+            }
+            b'(' | b'[' | b'{' => depth += 1,
+            b')' | b']' if depth > 0 => depth -= 1,
+            b'}' if depth > 0 => depth -= 1,
+            b'}' | b':' if depth == 0 => return Some((index, false)),
+            b'!' if depth == 0 && bytes.get(index + 1) != Some(&b'=') => {
+                return Some((index, false))
+            }
+            b'=' if depth == 0 => {
+                let previous = bytes[index - 1];
+                if bytes.get(index + 1) == Some(&b'=') {
+                    index += 1;
+                } else if !b"=!<>".contains(&previous) {
+                    return Some((index, true));
+                }
+            }
+            _ => {}
+        }
+        index += 1;
+    }
+    None
+}
+
+/// Finds the `}` that closes a format spec starting at `from`, skipping
+/// nested replacement fields.
+fn spec_end(text: &str, from: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in text[from..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(from + index),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
@@ -0,0 +1,654 @@
+mod error;
+mod expression;
+mod literal;
+mod pattern;
+mod precedence;
+mod project;
+mod statement;
+mod stream;
+mod target;
+mod unicode_names;
+
+pub use self::error::{ParseError, ParseErrorKind};
+pub use self::precedence::Precedence;
+pub use self::project::{
+    compile_project, compile_project_with_config, parse_file, CompileConfig, CompileError,
+};
+pub use self::stream::{Checkpoint, TokenStream};
+
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::mem;
+
+use ast::{Expr, Module, TypeIgnore};
+pub use tokenizer::PythonVersion;
+
+use tokenizer::{
+    tokenize_recovering, tokenize_with_config, CancellationToken, Location, SkippedLines, Span,
+    Token, TokenType, Tokenizer, TokenizerConfig,
+};
+
+pub const KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+pub fn is_keyword(name: &str) -> bool {
+    KEYWORDS.contains(&name)
+}
+
+/// Names that are keywords only where a statement or pattern they start
+/// is expected, as in CPython's `keyword.softkwlist`.
+pub const SOFT_KEYWORDS: &[&str] = &["_", "case", "match", "type"];
+
+pub fn is_soft_keyword(name: &str) -> bool {
+    SOFT_KEYWORDS.contains(&name)
+}
+
+/// How deeply expressions, blocks and patterns may nest unless
+/// `ParserConfig::max_depth` says otherwise.
+const MAX_DEPTH: usize = 1000;
+
+/// How many times `max_depth` deep the links of chains that group to the
+/// left may nest the tree, with the nesting they are in, as CPython's
+/// compiler allows trees three times as deep as its recursion limit.
+const CHAIN_SCALE: usize = 3;
+
+/// Options for `parse_with_config` and `parse_expression_with_config`.
+#[derive(Debug, Clone, Default)]
+pub struct ParserConfig {
+    /// Stop with a `Cancelled` error once this token is cancelled.
+    pub cancel: Option<CancellationToken>,
+    /// Fail with `TooManyNodes` once the syntax tree would have more
+    /// expressions, statements and patterns than this.
+    pub max_nodes: Option<usize>,
+    /// Fail with `TooDeeplyNested` once expressions, blocks or patterns are
+    /// nested deeper than this, or `None` for 1000, as CPython's recursion
+    /// limit. Chains of binary operators, calls, subscripts, attributes and
+    /// `elif`s are parsed in a loop and do not count, but nest the tree, so
+    /// their links with the nesting they are in may number three times
+    /// this. Nothing recurses deeper than this allows, so no source
+    /// overflows the stack of a thread given `STACK_SIZE`.
+    pub max_depth: Option<usize>,
+    /// Fail with `UnsupportedSyntax` on syntax this version does not have.
+    pub version: PythonVersion,
+    /// Keep `# type:` comments where CPython's grammar allows them, on
+    /// assignments, `for`, `with`, definitions and their parameters, and
+    /// collect `# type: ignore` comments in `Module::type_ignores`, as
+    /// `ast.parse(..., type_comments=True)` does. A type comment anywhere
+    /// else is a syntax error.
+    pub type_comments: bool,
+    /// The columns a tab in indentation advances to, as in
+    /// `TokenizerConfig::tab_size`.
+    pub tab_size: Option<usize>,
+    /// The most levels of indentation and of nested brackets, as in
+    /// `TokenizerConfig::max_indent` and `max_nesting`.
+    pub max_indent: Option<usize>,
+    pub max_nesting: Option<usize>,
+}
+
+/// What a comment says, if it is `#`, `type:` and the rest, with any
+/// spaces and tabs between them.
+enum TypeComment<'a> {
+    Type(&'a str),
+    /// `# type: ignore`, with the tag that follows, such as `[attr]`.
+    Ignore(&'a str),
+}
+
+fn read_type_comment(comment: &str) -> Option<TypeComment<'_>> {
+    let rest = comment.strip_prefix('#')?.trim_start_matches([' ', '\t']);
+    let rest = rest.strip_prefix("type:")?.trim_start_matches([' ', '\t']);
+    match rest.strip_prefix("ignore") {
+        Some(tag) if !tag.starts_with(|c: char| c.is_ascii_alphanumeric() || !c.is_ascii()) => {
+            Some(TypeComment::Ignore(tag))
+        }
+        _ => Some(TypeComment::Type(rest)),
+    }
+}
+
+/// Parses a module.
+pub fn parse(source: &str) -> Result<Module, ParseError> {
+    parse_with_config(source, &ParserConfig::default())
+}
+
+pub fn parse_with_config(source: &str, config: &ParserConfig) -> Result<Module, ParseError> {
+    let _span = stage_span!("parse", bytes = source.len());
+    Parser::run(source, config, Parser::module)
+}
+
+/// Parses a module as `parse` does, but carries on past syntax errors, for
+/// editors and other tools that work on files in the middle of being
+/// edited. A statement that does not parse is skipped up to the end of its
+/// line, along with the indented block and clauses after it, and a line
+/// that does not tokenize is skipped; each leaves a `StmtKind::Error` in
+/// the tree in its place. Returns the tree and every error found, in the
+/// order they are in the source, which are none if `parse` would succeed.
+/// Cancellation and the limits in `ParserConfig` still end the parse, with
+/// the statements parsed before that.
+pub fn parse_recovering(source: &str) -> (Module, Vec<ParseError>) {
+    parse_recovering_with_config(source, &ParserConfig::default())
+}
+
+pub fn parse_recovering_with_config(
+    source: &str,
+    config: &ParserConfig,
+) -> (Module, Vec<ParseError>) {
+    let _span = stage_span!("parse", bytes = source.len());
+    let (tokens, skipped) = tokenize_recovering(source, tokenizer_config(config));
+    recover(
+        Parser::with_skipped(tokens, config.type_comments, skipped),
+        config,
+    )
+}
+
+/// Parses a module from `tokens`, all those of a source that tokenizes, as
+/// `parse_recovering` parses the source, for the language server, which
+/// keeps the tokens of each document up to date as it is edited.
+#[cfg(feature = "ls")]
+pub(crate) fn parse_tokens_recovering(tokens: Vec<Token>) -> (Module, Vec<ParseError>) {
+    let config = ParserConfig::default();
+    recover(
+        Parser::with_skipped(tokens, config.type_comments, Vec::new()),
+        &config,
+    )
+}
+
+fn recover(mut parser: Parser, config: &ParserConfig) -> (Module, Vec<ParseError>) {
+    parser.configure(config);
+    parser.recover = true;
+    let module = parser.module().expect("errors are recovered from");
+    let mut errors = parser.errors;
+    errors.sort_by_key(|error| error.location);
+    (module, errors)
+}
+
+/// Parses a single expression, as `eval` does. Surrounding whitespace and
+/// newlines are allowed.
+pub fn parse_expression(source: &str) -> Result<Expr, ParseError> {
+    parse_expression_with_config(source, &ParserConfig::default())
+}
+
+pub fn parse_expression_with_config(
+    source: &str,
+    config: &ParserConfig,
+) -> Result<Expr, ParseError> {
+    let _span = stage_span!("parse", bytes = source.len());
+    Parser::run(source, config, Parser::eval_input)
+}
+
+/// The options to tokenize with for a parse with `config`: the same
+/// cancellation token, version, tab size and limits.
+fn tokenizer_config(config: &ParserConfig) -> TokenizerConfig {
+    TokenizerConfig {
+        cancel: config.cancel.clone(),
+        version: config.version,
+        tab_size: config.tab_size,
+        max_indent: config.max_indent,
+        max_nesting: config.max_nesting,
+        ..TokenizerConfig::default()
+    }
+}
+
+/// A recursive descent parser over the significant tokens of a source.
+///
+/// Comments and the newlines of blank lines are dropped up front, so the
+/// grammar only sees `NEWLINE` at the end of a logical line.
+struct Parser {
+    tokens: TokenStream,
+    /// Where the last consumed token ended, which is where a node that ends
+    /// with it ends.
+    last_end: Location,
+    cancel: Option<CancellationToken>,
+    max_nodes: Option<usize>,
+    max_depth: usize,
+    version: PythonVersion,
+    /// The `# type: ignore` comments, when type comments are kept.
+    type_ignores: Vec<TypeIgnore>,
+    /// How many nodes have been built, counted as they are built so that
+    /// the builders need not be fallible.
+    nodes: Cell<usize>,
+    /// How deeply nested the node being parsed is.
+    depth: usize,
+    /// The links of the chains being parsed, each of which nests the tree
+    /// one level deeper than the source.
+    links: usize,
+    /// Whether to carry on past syntax errors, collecting them in `errors`.
+    recover: bool,
+    errors: Vec<ParseError>,
+    /// Lines that could not be tokenized, with the index of the token
+    /// before which each was, still to be put in the tree.
+    skipped: VecDeque<SkippedLines>,
+}
+
+impl Parser {
+    /// A parser over the significant tokens of `tokens`. With
+    /// `type_comments`, those include the `# type:` comments, and the
+    /// `# type: ignore` comments are collected.
+    fn new(tokens: Vec<Token>, type_comments: bool) -> Parser {
+        Parser::with_skipped(tokens, type_comments, Vec::new())
+    }
+
+    /// A parser over `tokens` as `new` gives, and the lines skipped between
+    /// them, whose indices are moved to be among the significant tokens.
+    fn with_skipped(
+        tokens: Vec<Token>,
+        type_comments: bool,
+        mut skipped: Vec<SkippedLines>,
+    ) -> Parser {
+        let mut significant: Vec<Token> = Vec::with_capacity(tokens.len());
+        let mut type_ignores = Vec::new();
+        let mut next_skipped = 0;
+        let mut depth = 0;
+        for (index, token) in tokens.into_iter().enumerate() {
+            while let Some(lines) = skipped.get_mut(next_skipped) {
+                if lines.index != index {
+                    break;
+                }
+                lines.index = significant.len();
+                next_skipped += 1;
+            }
+            match token.kind {
+                TokenType::Comment if type_comments => match read_type_comment(&token.value) {
+                    Some(TypeComment::Type(_)) => significant.push(token),
+                    Some(TypeComment::Ignore(tag)) => type_ignores.push(TypeIgnore {
+                        line: token.start.line,
+                        tag: tag.to_string(),
+                    }),
+                    None => continue,
+                },
+                TokenType::Comment => continue,
+                // A type comment alone on its line is ended by the newline
+                // after it, as a statement is; other lines without code and
+                // line breaks inside brackets are dropped.
+                TokenType::NewlineNonLogical
+                    if depth == 0
+                        && significant
+                            .last()
+                            .is_some_and(|last| last.kind == TokenType::Comment) =>
+                {
+                    significant.push(Token {
+                        kind: TokenType::NewlineLogical,
+                        ..token
+                    })
+                }
+                TokenType::NewlineNonLogical => continue,
+                TokenType::NewlineLogical
+                    if significant
+                        .last()
+                        .is_none_or(|last| last.kind == TokenType::NewlineLogical) =>
+                {
+                    continue
+                }
+                _ => {
+                    depth += token.bracket_depth_change();
+                    significant.push(token)
+                }
+            }
+        }
+        for lines in &mut skipped[next_skipped..] {
+            lines.index = significant.len();
+        }
+        Parser {
+            tokens: TokenStream::new(significant),
+            last_end: Location::new(1, 0),
+            cancel: None,
+            max_nodes: None,
+            max_depth: MAX_DEPTH,
+            version: PythonVersion::LATEST,
+            type_ignores,
+            nodes: Cell::new(0),
+            depth: 0,
+            links: 0,
+            recover: false,
+            errors: Vec::new(),
+            skipped: skipped.into(),
+        }
+    }
+
+    /// Parses the tokens of `source` with `parse`, with the options in
+    /// `config`. CPython's parser reads tokens as they are made, so for a
+    /// bracket never closed, an error in the tokens before the end of the
+    /// input is the one reported.
+    fn run<T>(
+        source: &str,
+        config: &ParserConfig,
+        parse: fn(&mut Parser) -> Result<T, ParseError>,
+    ) -> Result<T, ParseError> {
+        let err = match tokenize_with_config(source, tokenizer_config(config)) {
+            Ok(tokens) => {
+                let mut parser = Parser::new(tokens, config.type_comments);
+                parser.configure(config);
+                return parse(&mut parser);
+            }
+            Err(err) if err.unclosed.is_none() => return Err(err.into()),
+            Err(err) => err,
+        };
+        let mut tokens: Vec<Token> = Tokenizer::with_config(source, tokenizer_config(config))
+            .map_while(Result::ok)
+            .collect();
+        tokens.push(Token {
+            kind: TokenType::EndMarker,
+            value: String::new(),
+            start: err.location,
+            end: err.location,
+            span: Span::new(source.len(), source.len()),
+        });
+        let mut parser = Parser::new(tokens, config.type_comments);
+        parser.configure(config);
+        match parse(&mut parser) {
+            Err(early) if early.location < err.location => Err(early),
+            _ => Err(err.into()),
+        }
+    }
+
+    /// Takes the options in `config` other than type comments, which are
+    /// kept or dropped as the parser is made.
+    fn configure(&mut self, config: &ParserConfig) {
+        self.cancel = config.cancel.clone();
+        self.max_nodes = config.max_nodes;
+        self.max_depth = config.max_depth.unwrap_or(MAX_DEPTH);
+        self.version = config.version;
+    }
+
+    /// A parser for the tokens of source nested inside this parser's, such
+    /// as an f-string replacement field, which shares its limits.
+    fn nested(&self, tokens: Vec<Token>) -> Parser {
+        let mut parser = Parser::new(tokens, false);
+        parser.cancel = self.cancel.clone();
+        parser.max_nodes = self.max_nodes;
+        parser.max_depth = self.max_depth;
+        parser.version = self.version;
+        parser.nodes.set(self.nodes.get());
+        parser.depth = self.depth;
+        parser.links = self.links;
+        parser
+    }
+
+    /// Counts a node built at the current position.
+    fn count_node(&self) {
+        self.nodes.set(self.nodes.get() + 1);
+    }
+
+    /// Goes one level deeper, failing if that is past `max_depth` or if the
+    /// tree has grown past `max_nodes`. Each call is undone by `ascend`.
+    fn descend(&mut self) -> Result<(), ParseError> {
+        self.depth += 1;
+        self.check_limits()
+    }
+
+    fn ascend(&mut self, levels: usize) {
+        self.depth -= levels;
+    }
+
+    /// Adds a link to a chain that groups to the left, which nests the tree
+    /// but not the source, failing if the links and the nesting are past
+    /// `CHAIN_SCALE` times `max_depth`. Each call is undone by `unlink`.
+    fn link(&mut self) -> Result<(), ParseError> {
+        self.links += 1;
+        self.check_limits()
+    }
+
+    fn unlink(&mut self, links: usize) {
+        self.links -= links;
+    }
+
+    fn check_limits(&self) -> Result<(), ParseError> {
+        if self.depth > self.max_depth
+            || self.depth + self.links > self.max_depth.saturating_mul(CHAIN_SCALE)
+        {
+            return Err(ParseError::new(
+                ParseErrorKind::TooDeeplyNested,
+                "too deeply nested",
+                self.peek().start,
+            ));
+        }
+        if self.max_nodes.is_some_and(|max| self.nodes.get() > max) {
+            return Err(ParseError::new(
+                ParseErrorKind::TooManyNodes,
+                "too many nodes in the syntax tree",
+                self.peek().start,
+            ));
+        }
+        Ok(())
+    }
+
+    /// Runs `parse` one level deeper.
+    fn deeper<T, F>(&mut self, parse: F) -> Result<T, ParseError>
+    where
+        F: FnOnce(&mut Parser) -> Result<T, ParseError>,
+    {
+        self.descend()?;
+        let result = parse(self);
+        self.ascend(1);
+        result
+    }
+
+    /// Fails if the parse has been cancelled. Checked before each statement.
+    fn check_cancelled(&self) -> Result<(), ParseError> {
+        match self.cancel {
+            Some(ref cancel) if cancel.is_cancelled() => Err(ParseError::new(
+                ParseErrorKind::Cancelled,
+                "operation cancelled",
+                self.peek().start,
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn module(&mut self) -> Result<Module, ParseError> {
+        let mut body = Vec::new();
+        while self.peek().kind != TokenType::EndMarker {
+            if let Err(err) = self.body_statement(&mut body) {
+                if !self.recover {
+                    return Err(err);
+                }
+                // The rest is given up on, but not the lines skipped in it.
+                self.errors.push(err);
+                while !self.tokens.is_at_end() {
+                    self.tokens.advance();
+                }
+                break;
+            }
+        }
+        self.skipped_lines(&mut body);
+        Ok(Module {
+            body,
+            type_ignores: mem::take(&mut self.type_ignores),
+        })
+    }
+
+    fn eval_input(&mut self) -> Result<Expr, ParseError> {
+        let expr = self.star_expressions()?;
+        while self.peek().kind == TokenType::NewlineLogical {
+            self.advance();
+        }
+        if self.peek().kind != TokenType::EndMarker {
+            return Err(self.unexpected());
+        }
+        Ok(expr)
+    }
+
+    fn peek(&self) -> &Token {
+        self.tokens.peek(0)
+    }
+
+    fn peek_nth(&self, n: usize) -> &Token {
+        self.tokens.peek(n)
+    }
+
+    fn advance(&mut self) -> Token {
+        let token = self.tokens.advance();
+        // A statement ends with its last significant token, as in CPython,
+        // not with the newline or dedent that closes it.
+        match token.kind {
+            TokenType::NewlineLogical
+            | TokenType::Indent
+            | TokenType::Dedent
+            | TokenType::EndMarker => {}
+            _ => self.last_end = token.end,
+        }
+        token
+    }
+
+    fn start(&self) -> Location {
+        self.peek().start
+    }
+
+    fn at_operator(&self, operator: &str) -> bool {
+        self.tokens.at_operator(operator)
+    }
+
+    fn at_keyword(&self, keyword: &str) -> bool {
+        self.tokens.at_name(keyword)
+    }
+
+    fn eat_operator(&mut self, operator: &str) -> bool {
+        if self.at_operator(operator) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.at_keyword(keyword) {
+            self.advance();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_operator(&mut self, operator: &str) -> Result<Token, ParseError> {
+        if self.at_operator(operator) {
+            Ok(self.advance())
+        } else {
+            Err(self.expected(&format!("'{}'", operator)))
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<Token, ParseError> {
+        if self.at_keyword(keyword) {
+            Ok(self.advance())
+        } else {
+            Err(self.expected(&format!("'{}'", keyword)))
+        }
+    }
+
+    /// Consumes an identifier: a name that is not a keyword.
+    fn expect_identifier(&mut self) -> Result<String, ParseError> {
+        let token = self.peek();
+        if token.kind == TokenType::Name && !is_keyword(&token.value) {
+            Ok(self.advance().value)
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    /// Consumes a `# type:` comment, if one is next, and returns its type.
+    /// As in CPython, an assignment ends with its type comment.
+    fn type_comment(&mut self) -> Option<String> {
+        if self.peek().kind != TokenType::Comment {
+            return None;
+        }
+        match read_type_comment(&self.advance().value) {
+            Some(TypeComment::Type(text)) => Some(text.to_string()),
+            _ => unreachable!("only type comments are kept"),
+        }
+    }
+
+    fn expect_newline(&mut self) -> Result<(), ParseError> {
+        match self.peek().kind {
+            TokenType::NewlineLogical => {
+                self.advance();
+                Ok(())
+            }
+            TokenType::EndMarker => Ok(()),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// Runs `parse`, and if it fails, goes back to where it started and
+    /// returns `None`, for the tokens to be parsed as something else.
+    /// Running out of budget is not a reason to try another parse, so
+    /// those errors are returned.
+    fn speculate<T, F>(&mut self, parse: F) -> Result<Option<T>, ParseError>
+    where
+        F: FnOnce(&mut Parser) -> Result<T, ParseError>,
+    {
+        let (checkpoint, last_end) = (self.tokens.checkpoint(), self.last_end);
+        let (depth, links, nodes) = (self.depth, self.links, self.nodes.get());
+        match parse(self) {
+            Ok(value) => Ok(Some(value)),
+            Err(err)
+                if err.kind == ParseErrorKind::TooManyNodes
+                    || err.kind == ParseErrorKind::TooDeeplyNested =>
+            {
+                Err(err)
+            }
+            Err(_) => {
+                self.tokens.rollback(checkpoint);
+                self.last_end = last_end;
+                self.depth = depth;
+                self.links = links;
+                self.nodes.set(nodes);
+                Ok(None)
+            }
+        }
+    }
+
+    fn syntax_error<S: Into<String>>(&self, message: S, location: Location) -> ParseError {
+        ParseError::new(ParseErrorKind::InvalidSyntax, message, location)
+    }
+
+    /// Fails at `location` unless the target version is `version` or
+    /// newer, naming what needs it as CPython does: `feature` is a phrase
+    /// such as `Assignment expressions are`.
+    fn require_version(
+        &self,
+        version: PythonVersion,
+        feature: &str,
+        location: Location,
+    ) -> Result<(), ParseError> {
+        if self.version >= version {
+            return Ok(());
+        }
+        Err(ParseError::new(
+            ParseErrorKind::UnsupportedSyntax,
+            format!(
+                "{} only supported in Python {} and greater",
+                feature, version
+            ),
+            location,
+        ))
+    }
+
+    fn expected(&self, what: &str) -> ParseError {
+        match self.peek().kind {
+            TokenType::Indent | TokenType::EndMarker => self.unexpected(),
+            _ => self.syntax_error(format!("expected {}", what), self.start()),
+        }
+    }
+
+    /// An error for the current token, which cannot appear where it is.
+    fn unexpected(&self) -> ParseError {
+        let token = self.peek();
+        match token.kind {
+            TokenType::Indent => ParseError::new(
+                ParseErrorKind::UnexpectedIndent,
+                "unexpected indent",
+                token.start,
+            ),
+            TokenType::Dedent => ParseError::new(
+                ParseErrorKind::InvalidSyntax,
+                "unexpected unindent",
+                token.start,
+            ),
+            TokenType::EndMarker => self.syntax_error("unexpected EOF while parsing", token.start),
+            _ => self.syntax_error("invalid syntax", token.start),
+        }
+    }
+}
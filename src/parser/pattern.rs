@@ -0,0 +1,415 @@
+use ast::{
+    Constant, Context, Expr, ExprKind, MatchCase, Operator, Pattern, PatternKind, Stmt, StmtKind,
+    UnaryOperator,
+};
+use tokenizer::{Location, TokenType};
+
+use super::{ParseError, ParseErrorKind, Parser, PythonVersion};
+
+impl Parser {
+    /// A `match` statement, or `None` when `match` starts some other
+    /// statement: it is only a keyword when followed by a subject and `:`.
+    pub(super) fn match_statement(&mut self) -> Result<Option<Stmt>, ParseError> {
+        let start = self.start();
+        let subject = self.speculate(|parser| {
+            parser.advance();
+            let subject = parser.match_subject()?;
+            if !parser.at_operator(":") {
+                return Err(parser.unexpected());
+            }
+            Ok(subject)
+        })?;
+        let subject = match subject {
+            Some(subject) => subject,
+            None => return Ok(None),
+        };
+        self.require_version(PythonVersion::Py310, "Pattern matching is", start)?;
+        self.advance();
+        if self.peek().kind != TokenType::NewlineLogical {
+            return Err(self.unexpected());
+        }
+        self.advance();
+        if self.peek().kind != TokenType::Indent {
+            return Err(ParseError::new(
+                ParseErrorKind::ExpectedIndent,
+                "expected an indented block",
+                self.start(),
+            ));
+        }
+        self.advance();
+        let mut cases = Vec::new();
+        while self.at_keyword("case") {
+            cases.push(self.match_case()?);
+        }
+        if cases.is_empty() {
+            return Err(self.expected("'case' block"));
+        }
+        match self.peek().kind {
+            TokenType::Dedent => {
+                self.advance();
+            }
+            TokenType::EndMarker => {}
+            _ => return Err(self.unexpected()),
+        }
+        Ok(Some(self.stmt(StmtKind::Match { subject, cases }, start)))
+    }
+
+    fn match_subject(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let first = self.star_named_expression()?;
+        if !self.at_operator(",") {
+            if let ExprKind::Starred { .. } = first.kind {
+                return Err(self.unexpected());
+            }
+            return Ok(first);
+        }
+        let mut elts = vec![first];
+        while self.eat_operator(",") {
+            if self.at_operator(":") {
+                break;
+            }
+            elts.push(self.star_named_expression()?);
+        }
+        Ok(self.expr(
+            ExprKind::Tuple {
+                elts,
+                ctx: Context::Load,
+            },
+            start,
+        ))
+    }
+
+    fn match_case(&mut self) -> Result<MatchCase, ParseError> {
+        self.advance();
+        let pattern = self.patterns()?;
+        let guard = if self.eat_keyword("if") {
+            Some(self.named_expression()?)
+        } else {
+            None
+        };
+        let body = self.block()?;
+        Ok(MatchCase {
+            pattern,
+            guard,
+            body,
+        })
+    }
+
+    fn pattern_node(&self, kind: PatternKind, start: Location) -> Pattern {
+        self.count_node();
+        Pattern {
+            kind,
+            start,
+            end: self.last_end,
+        }
+    }
+
+    /// The pattern of a `case`, where an unparenthesized sequence is allowed.
+    fn patterns(&mut self) -> Result<Pattern, ParseError> {
+        let start = self.start();
+        let first = self.maybe_star_pattern()?;
+        if !self.at_operator(",") {
+            if let PatternKind::MatchStar(_) = first.kind {
+                return Err(self.syntax_error("invalid syntax", first.start));
+            }
+            return Ok(first);
+        }
+        let mut patterns = vec![first];
+        while self.eat_operator(",") {
+            if self.at_operator(":") || self.at_keyword("if") {
+                break;
+            }
+            patterns.push(self.maybe_star_pattern()?);
+        }
+        Ok(self.pattern_node(PatternKind::MatchSequence(patterns), start))
+    }
+
+    fn pattern(&mut self) -> Result<Pattern, ParseError> {
+        let start = self.start();
+        let pattern = self.or_pattern()?;
+        if !self.eat_keyword("as") {
+            return Ok(pattern);
+        }
+        let name = self.capture_name()?;
+        let kind = PatternKind::MatchAs {
+            pattern: Some(Box::new(pattern)),
+            name: Some(name),
+        };
+        Ok(self.pattern_node(kind, start))
+    }
+
+    fn capture_name(&mut self) -> Result<String, ParseError> {
+        let start = self.start();
+        let name = self.expect_identifier()?;
+        if name == "_" {
+            return Err(self.syntax_error("cannot use '_' as a target", start));
+        }
+        Ok(name)
+    }
+
+    fn or_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let start = self.start();
+        let first = self.deeper(Parser::closed_pattern)?;
+        if !self.at_operator("|") {
+            return Ok(first);
+        }
+        let mut patterns = vec![first];
+        while self.eat_operator("|") {
+            patterns.push(self.deeper(Parser::closed_pattern)?);
+        }
+        Ok(self.pattern_node(PatternKind::MatchOr(patterns), start))
+    }
+
+    fn maybe_star_pattern(&mut self) -> Result<Pattern, ParseError> {
+        if !self.at_operator("*") {
+            return self.pattern();
+        }
+        let start = self.start();
+        self.advance();
+        let name = self.expect_identifier()?;
+        let name = if name == "_" { None } else { Some(name) };
+        Ok(self.pattern_node(PatternKind::MatchStar(name), start))
+    }
+
+    fn closed_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let start = self.start();
+        let token = self.peek().clone();
+        let kind = match token.kind {
+            TokenType::Number | TokenType::String => {
+                PatternKind::MatchValue(self.literal_expression()?)
+            }
+            TokenType::Name => match token.value.as_str() {
+                "None" | "True" | "False" => {
+                    self.advance();
+                    PatternKind::MatchSingleton(match token.value.as_str() {
+                        "None" => Constant::None,
+                        value => Constant::Bool(value == "True"),
+                    })
+                }
+                _ if self.peek_nth(1).is_operator(".") || self.peek_nth(1).is_operator("(") => {
+                    let value = self.name_or_attribute()?;
+                    if self.at_operator("(") {
+                        return self.class_pattern(value, start);
+                    }
+                    PatternKind::MatchValue(value)
+                }
+                "_" => {
+                    self.advance();
+                    PatternKind::MatchAs {
+                        pattern: None,
+                        name: None,
+                    }
+                }
+                _ => PatternKind::MatchAs {
+                    pattern: None,
+                    name: Some(self.expect_identifier()?),
+                },
+            },
+            TokenType::Operator => match token.value.as_str() {
+                "-" => PatternKind::MatchValue(self.literal_expression()?),
+                "(" => {
+                    self.advance();
+                    if self.eat_operator(")") {
+                        PatternKind::MatchSequence(Vec::new())
+                    } else {
+                        let first = self.maybe_star_pattern()?;
+                        if self.eat_operator(")") {
+                            if let PatternKind::MatchStar(_) = first.kind {
+                                return Err(self.syntax_error("invalid syntax", first.start));
+                            }
+                            return Ok(first);
+                        }
+                        self.expect_operator(",")?;
+                        let mut patterns = vec![first];
+                        patterns.extend(self.sequence_items(")")?);
+                        PatternKind::MatchSequence(patterns)
+                    }
+                }
+                "[" => {
+                    self.advance();
+                    PatternKind::MatchSequence(self.sequence_items("]")?)
+                }
+                "{" => self.mapping_pattern()?,
+                _ => return Err(self.unexpected()),
+            },
+            _ => return Err(self.unexpected()),
+        };
+        Ok(self.pattern_node(kind, start))
+    }
+
+    /// Comma-separated patterns up to and including `closing`.
+    fn sequence_items(&mut self, closing: &str) -> Result<Vec<Pattern>, ParseError> {
+        let mut patterns = Vec::new();
+        while !self.at_operator(closing) {
+            patterns.push(self.maybe_star_pattern()?);
+            if !self.eat_operator(",") {
+                break;
+            }
+        }
+        self.expect_operator(closing)?;
+        Ok(patterns)
+    }
+
+    fn mapping_pattern(&mut self) -> Result<PatternKind, ParseError> {
+        self.advance();
+        let mut keys = Vec::new();
+        let mut patterns = Vec::new();
+        let mut rest = None;
+        while !self.at_operator("}") {
+            if self.eat_operator("**") {
+                rest = Some(self.capture_name()?);
+                self.eat_operator(",");
+                break;
+            }
+            keys.push(self.literal_expression()?);
+            self.expect_operator(":")?;
+            patterns.push(self.pattern()?);
+            if !self.eat_operator(",") {
+                break;
+            }
+        }
+        self.expect_operator("}")?;
+        Ok(PatternKind::MatchMapping {
+            keys,
+            patterns,
+            rest,
+        })
+    }
+
+    fn class_pattern(&mut self, cls: Expr, start: Location) -> Result<Pattern, ParseError> {
+        self.advance();
+        let mut patterns = Vec::new();
+        let mut kwd_attrs = Vec::new();
+        let mut kwd_patterns = Vec::new();
+        while !self.at_operator(")") {
+            if self.peek().kind == TokenType::Name && self.peek_nth(1).is_operator("=") {
+                kwd_attrs.push(self.expect_identifier()?);
+                self.advance();
+                kwd_patterns.push(self.pattern()?);
+            } else if kwd_attrs.is_empty() {
+                patterns.push(self.pattern()?);
+            } else {
+                return Err(
+                    self.syntax_error("positional patterns follow keyword patterns", self.start())
+                );
+            }
+            if !self.eat_operator(",") {
+                break;
+            }
+        }
+        self.expect_operator(")")?;
+        let kind = PatternKind::MatchClass {
+            cls,
+            patterns,
+            kwd_attrs,
+            kwd_patterns,
+        };
+        Ok(self.pattern_node(kind, start))
+    }
+
+    fn name_or_attribute(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let id = self.expect_identifier()?;
+        let mut expr = self.expr(
+            ExprKind::Name {
+                id,
+                ctx: Context::Load,
+            },
+            start,
+        );
+        while self.eat_operator(".") {
+            let attr = self.expect_identifier()?;
+            expr = self.expr(
+                ExprKind::Attribute {
+                    value: Box::new(expr),
+                    attr,
+                    ctx: Context::Load,
+                },
+                start,
+            );
+        }
+        Ok(expr)
+    }
+
+    /// A value that can be matched against: a string, a signed number, a
+    /// complex literal like `1 - 2j`, a singleton, or a dotted name.
+    fn literal_expression(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        let token = self.peek().clone();
+        match token.kind {
+            TokenType::String => {
+                let value = self.strings()?;
+                if let ExprKind::JoinedStr(_) = value.kind {
+                    return Err(self.syntax_error(
+                        "patterns may only match literals and attribute lookups",
+                        start,
+                    ));
+                }
+                return Ok(value);
+            }
+            TokenType::Name => {
+                let constant = match token.value.as_str() {
+                    "None" => Constant::None,
+                    "True" => Constant::Bool(true),
+                    "False" => Constant::Bool(false),
+                    _ if self.peek_nth(1).is_operator(".") => return self.name_or_attribute(),
+                    _ => return Err(self.unexpected()),
+                };
+                self.advance();
+                return Ok(self.expr(ExprKind::Constant(constant), start));
+            }
+            _ => {}
+        }
+        let mut value = self.signed_number()?;
+        let op = if self.at_operator("+") {
+            Operator::Add
+        } else if self.at_operator("-") {
+            Operator::Sub
+        } else {
+            return Ok(value);
+        };
+        self.advance();
+        let imaginary = self.start();
+        let right = self.number()?;
+        if !matches!(right.kind, ExprKind::Constant(Constant::Complex { .. })) {
+            return Err(
+                self.syntax_error("imaginary number required in complex literal", imaginary)
+            );
+        }
+        value = self.expr(
+            ExprKind::BinOp {
+                left: Box::new(value),
+                op,
+                right: Box::new(right),
+            },
+            start,
+        );
+        Ok(value)
+    }
+
+    fn signed_number(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        if !self.eat_operator("-") {
+            return self.number();
+        }
+        let operand = self.number()?;
+        Ok(self.expr(
+            ExprKind::UnaryOp {
+                op: UnaryOperator::USub,
+                operand: Box::new(operand),
+            },
+            start,
+        ))
+    }
+
+    fn number(&mut self) -> Result<Expr, ParseError> {
+        let start = self.start();
+        if self.peek().kind != TokenType::Number {
+            return Err(self.unexpected());
+        }
+        let token = self.advance();
+        let constant = super::literal::number(&token.value)
+            .map_err(|message| self.syntax_error(message, start))?;
+        Ok(self.expr(ExprKind::Constant(constant), start))
+    }
+}
@@ -0,0 +1,96 @@
+/// Operator precedence, from loosest to tightest binding, in the same order
+/// as CPython's `ast._Precedence`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Precedence {
+    /// `:=`
+    NamedExpr,
+    /// `a, b`
+    Tuple,
+    /// `yield`
+    Yield,
+    /// `x if c else y` and `lambda`
+    Test,
+    Or,
+    And,
+    /// Unary `not`
+    Not,
+    /// Comparisons, `in`, `not in`, `is`, `is not`
+    Comparison,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Shift,
+    /// Binary `+` and `-`
+    Arithmetic,
+    /// `*`, `@`, `/`, `//` and `%`
+    Term,
+    /// Unary `+`, `-` and `~`
+    Factor,
+    Power,
+    Await,
+    Atom,
+}
+
+impl Precedence {
+    /// The precedence of a binary operator, written the way it appears in
+    /// source. Multi-word operators are written with a single space, as in
+    /// `"not in"`.
+    pub fn of_binary_operator(operator: &str) -> Option<Precedence> {
+        let precedence = match operator {
+            ":=" => Precedence::NamedExpr,
+            "if" | "else" => Precedence::Test,
+            "or" => Precedence::Or,
+            "and" => Precedence::And,
+            "<" | ">" | "==" | ">=" | "<=" | "!=" | "in" | "not in" | "is" | "is not" => {
+                Precedence::Comparison
+            }
+            "|" => Precedence::BitOr,
+            "^" => Precedence::BitXor,
+            "&" => Precedence::BitAnd,
+            "<<" | ">>" => Precedence::Shift,
+            "+" | "-" => Precedence::Arithmetic,
+            "*" | "@" | "/" | "//" | "%" => Precedence::Term,
+            "**" => Precedence::Power,
+            _ => return None,
+        };
+        Some(precedence)
+    }
+
+    /// The precedence of a prefix operator.
+    pub fn of_unary_operator(operator: &str) -> Option<Precedence> {
+        match operator {
+            "not" => Some(Precedence::Not),
+            "+" | "-" | "~" => Some(Precedence::Factor),
+            "await" => Some(Precedence::Await),
+            _ => None,
+        }
+    }
+
+    /// The next tighter level. `Atom` is the tightest.
+    pub fn next(self) -> Precedence {
+        match self {
+            Precedence::NamedExpr => Precedence::Tuple,
+            Precedence::Tuple => Precedence::Yield,
+            Precedence::Yield => Precedence::Test,
+            Precedence::Test => Precedence::Or,
+            Precedence::Or => Precedence::And,
+            Precedence::And => Precedence::Not,
+            Precedence::Not => Precedence::Comparison,
+            Precedence::Comparison => Precedence::BitOr,
+            Precedence::BitOr => Precedence::BitXor,
+            Precedence::BitXor => Precedence::BitAnd,
+            Precedence::BitAnd => Precedence::Shift,
+            Precedence::Shift => Precedence::Arithmetic,
+            Precedence::Arithmetic => Precedence::Term,
+            Precedence::Term => Precedence::Factor,
+            Precedence::Factor => Precedence::Power,
+            Precedence::Power => Precedence::Await,
+            Precedence::Await | Precedence::Atom => Precedence::Atom,
+        }
+    }
+
+    /// Whether operators at this level group to the right, as `**` does.
+    pub fn is_right_associative(self) -> bool {
+        self == Precedence::Power
+    }
+}
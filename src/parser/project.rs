@@ -0,0 +1,167 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use ast::Module;
+use optimizer::optimize;
+use tokenizer::{decode, CancellationToken, Location, SourceError};
+use trace;
+use STACK_SIZE;
+
+use super::{parse_with_config, ParseError, ParseErrorKind, ParserConfig, PythonVersion};
+
+/// Options for `compile_project_with_config`.
+#[derive(Debug, Clone)]
+pub struct CompileConfig {
+    /// Run the AST optimizer on each module after parsing it.
+    pub optimize: bool,
+    /// Stop once this token is cancelled. Files that were not finished by
+    /// then fail with a `Cancelled` error.
+    pub cancel: Option<CancellationToken>,
+    /// The limits on each file's syntax tree, indentation and brackets, as
+    /// in `ParserConfig`.
+    pub max_nodes: Option<usize>,
+    pub max_depth: Option<usize>,
+    pub max_indent: Option<usize>,
+    pub max_nesting: Option<usize>,
+    /// The version whose grammar each file must follow.
+    pub version: PythonVersion,
+    /// Keep type comments, as `ParserConfig::type_comments` does.
+    pub type_comments: bool,
+    /// The columns a tab in indentation advances to, as in `ParserConfig`.
+    pub tab_size: Option<usize>,
+}
+
+impl Default for CompileConfig {
+    fn default() -> CompileConfig {
+        CompileConfig {
+            optimize: true,
+            cancel: None,
+            max_nodes: None,
+            max_depth: None,
+            max_indent: None,
+            max_nesting: None,
+            version: PythonVersion::LATEST,
+            type_comments: false,
+            tab_size: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum CompileError {
+    Source(SourceError),
+    Parse(ParseError),
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompileError::Source(ref err) => write!(f, "{}", err),
+            CompileError::Parse(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for CompileError {}
+
+impl From<SourceError> for CompileError {
+    fn from(err: SourceError) -> CompileError {
+        CompileError::Source(err)
+    }
+}
+
+impl From<ParseError> for CompileError {
+    fn from(err: ParseError) -> CompileError {
+        CompileError::Parse(err)
+    }
+}
+
+/// Reads, decodes and parses a source file.
+pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Module, CompileError> {
+    let _span = trace::file_span(path.as_ref());
+    read_and_parse(path.as_ref(), &ParserConfig::default())
+}
+
+fn read_and_parse(path: &Path, config: &ParserConfig) -> Result<Module, CompileError> {
+    let bytes = fs::read(path).map_err(SourceError::from)?;
+    let decoded = decode(&bytes)?;
+    Ok(parse_with_config(&decoded.text, config)?)
+}
+
+/// Parses and optimizes many files with the default configuration.
+pub fn compile_project<P: AsRef<Path> + Sync>(paths: &[P]) -> Vec<Result<Module, CompileError>> {
+    compile_project_with_config(paths, &CompileConfig::default())
+}
+
+/// Parses many files on a pool of threads, one per available core, and
+/// optimizes them unless `config` says not to.
+///
+/// Files are handed out one at a time, so a few large files do not hold up
+/// the rest. The results are in the same order as `paths`.
+pub fn compile_project_with_config<P: AsRef<Path> + Sync>(
+    paths: &[P],
+    config: &CompileConfig,
+) -> Vec<Result<Module, CompileError>> {
+    let _span = stage_span!("compile_project", files = paths.len());
+    let threads = thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(paths.len());
+    let parser_config = ParserConfig {
+        cancel: config.cancel.clone(),
+        max_nodes: config.max_nodes,
+        max_depth: config.max_depth,
+        max_indent: config.max_indent,
+        max_nesting: config.max_nesting,
+        version: config.version,
+        type_comments: config.type_comments,
+        tab_size: config.tab_size,
+    };
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let worker = thread::Builder::new().stack_size(STACK_SIZE);
+            worker
+                .spawn_scoped(scope, || loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let path = match paths.get(index) {
+                        Some(path) => path,
+                        None => break,
+                    };
+                    if let Some(ref cancel) = config.cancel {
+                        if cancel.is_cancelled() {
+                            let err = ParseError::new(
+                                ParseErrorKind::Cancelled,
+                                "operation cancelled",
+                                Location::new(1, 0),
+                            );
+                            results.lock().unwrap()[index] = Some(Err(err.into()));
+                            continue;
+                        }
+                    }
+                    let _span = trace::file_span(path.as_ref());
+                    let result = read_and_parse(path.as_ref(), &parser_config).map(|mut module| {
+                        if config.optimize {
+                            optimize(&mut module);
+                        }
+                        module
+                    });
+                    results.lock().unwrap()[index] = Some(result);
+                })
+                .unwrap();
+        }
+    });
+
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|result| result.unwrap())
+        .collect()
+}
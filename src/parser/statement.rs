@@ -0,0 +1,1032 @@
+use ast::{
+    Alias, Arg, Arguments, Context, ExceptHandler, Expr, ExprKind, Operator, Stmt, StmtKind,
+    TypeParam, TypeParamKind, WithItem,
+};
+use tokenizer::{Location, TokenType};
+
+use super::{is_keyword, ParseError, ParseErrorKind, Parser, PythonVersion};
+
+const AUGMENTED_ASSIGNMENTS: &[&str] = &[
+    "+=", "-=", "*=", "@=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>=", "**=", "//=",
+];
+
+impl Parser {
+    /// Parses one statement, or one line of `;`-separated simple statements,
+    /// into `body`.
+    pub(super) fn statement(&mut self, body: &mut Vec<Stmt>) -> Result<(), ParseError> {
+        self.check_cancelled()?;
+        let token = self.peek();
+        if token.kind == TokenType::Name {
+            let compound = match token.value.as_str() {
+                "if" => Some(self.if_statement()?),
+                "while" => Some(self.while_statement()?),
+                "for" => Some(self.for_statement(self.start(), false)?),
+                "try" => Some(self.try_statement()?),
+                "with" => Some(self.with_statement(self.start(), false)?),
+                "def" => Some(self.function_def(self.start(), Vec::new(), false)?),
+                "class" => Some(self.class_def(self.start(), Vec::new())?),
+                "async" => Some(self.async_statement(self.start(), Vec::new())?),
+                "match" => self.match_statement()?,
+                _ => None,
+            };
+            if let Some(stmt) = compound {
+                body.push(stmt);
+                return Ok(());
+            }
+        } else if token.is_operator("@") {
+            let stmt = self.decorated()?;
+            body.push(stmt);
+            return Ok(());
+        }
+        self.simple_statements(body)
+    }
+
+    fn simple_statements(&mut self, body: &mut Vec<Stmt>) -> Result<(), ParseError> {
+        loop {
+            body.push(self.simple_statement()?);
+            if !self.eat_operator(";") || self.peek().kind == TokenType::NewlineLogical {
+                break;
+            }
+        }
+        self.expect_newline()
+    }
+
+    /// The indented block, or the simple statements on the same line, after
+    /// the `:` of a compound statement.
+    pub(super) fn block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        self.expect_operator(":")?;
+        self.deeper(Parser::block_body)
+    }
+
+    /// A block after a statement that may have a `# type:` comment after
+    /// its colon. That of a function may instead be alone on the line
+    /// after the colon, before the body.
+    fn typed_block(&mut self, function: bool) -> Result<(Option<String>, Vec<Stmt>), ParseError> {
+        self.expect_operator(":")?;
+        let mut type_comment = self.type_comment();
+        if function
+            && self.peek().kind == TokenType::NewlineLogical
+            && self.peek_nth(1).kind == TokenType::Comment
+            && self.peek_nth(2).kind == TokenType::NewlineLogical
+            && self.peek_nth(3).kind == TokenType::Indent
+        {
+            if type_comment.is_some() {
+                let message = "Cannot have two type comments on def";
+                return Err(self.syntax_error(message, self.peek_nth(1).start));
+            }
+            self.advance();
+            type_comment = self.type_comment();
+        }
+        let body = self.deeper(Parser::block_body)?;
+        Ok((type_comment, body))
+    }
+
+    fn block_body(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        let mut body = Vec::new();
+        if self.peek().kind != TokenType::NewlineLogical {
+            self.simple_statements(&mut body)?;
+            return Ok(body);
+        }
+        self.advance();
+        if self.peek().kind != TokenType::Indent {
+            return Err(ParseError::new(
+                ParseErrorKind::ExpectedIndent,
+                "expected an indented block",
+                self.start(),
+            ));
+        }
+        self.advance();
+        while self.peek().kind != TokenType::Dedent && self.peek().kind != TokenType::EndMarker {
+            self.body_statement(&mut body)?;
+        }
+        self.skipped_lines(&mut body);
+        self.advance();
+        Ok(body)
+    }
+
+    /// A statement of a module or block. When recovering, a statement that
+    /// does not parse is skipped and its error kept, leaving a
+    /// `StmtKind::Error` in its place, and any lines that could not be
+    /// tokenized before it are put in the same way.
+    pub(super) fn body_statement(&mut self, body: &mut Vec<Stmt>) -> Result<(), ParseError> {
+        if !self.recover {
+            return self.statement(body);
+        }
+        self.skipped_lines(body);
+        let (checkpoint, depth, links) = (self.tokens.checkpoint(), self.depth, self.links);
+        let length = body.len();
+        let start = self.start();
+        match self.statement(body) {
+            Ok(()) => Ok(()),
+            Err(err)
+                if matches!(
+                    err.kind,
+                    ParseErrorKind::Cancelled
+                        | ParseErrorKind::TooManyNodes
+                        | ParseErrorKind::TooDeeplyNested
+                ) =>
+            {
+                Err(err)
+            }
+            Err(err) => {
+                self.errors.push(err);
+                body.truncate(length);
+                self.tokens.rollback(checkpoint);
+                self.depth = depth;
+                self.links = links;
+                self.synchronize();
+                if self.tokens.checkpoint() == checkpoint && !self.tokens.is_at_end() {
+                    self.advance();
+                }
+                let mut stmt = self.stmt(StmtKind::Error, start);
+                stmt.end = stmt.end.max(start);
+                body.push(stmt);
+                Ok(())
+            }
+        }
+    }
+
+    /// Skips the statement that starts at the current token: the rest of
+    /// its logical line, and the indented block and the clauses, such as
+    /// `else`, that follow it. Stops at a dedent that closes the block the
+    /// statement is in.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.peek().kind {
+                TokenType::EndMarker => return,
+                TokenType::Dedent if depth == 0 => return,
+                _ => {}
+            }
+            match self.advance().kind {
+                TokenType::Indent => depth += 1,
+                TokenType::Dedent => depth -= 1,
+                TokenType::NewlineLogical => {}
+                _ => continue,
+            }
+            let clause = ["elif", "else", "except", "finally"]
+                .iter()
+                .any(|keyword| self.at_keyword(keyword));
+            if depth == 0 && self.peek().kind != TokenType::Indent && !clause {
+                return;
+            }
+        }
+    }
+
+    /// Puts a `StmtKind::Error` in `body` for each line before the
+    /// current token that could not be tokenized, keeping its error.
+    pub(super) fn skipped_lines(&mut self, body: &mut Vec<Stmt>) {
+        while self
+            .skipped
+            .front()
+            .is_some_and(|lines| lines.index <= self.tokens.position())
+        {
+            let lines = self.skipped.pop_front().unwrap();
+            self.count_node();
+            body.push(Stmt {
+                kind: StmtKind::Error,
+                start: lines.start,
+                end: lines.end,
+            });
+            self.errors.push(lines.error.into());
+        }
+    }
+
+    pub(super) fn stmt(&self, kind: StmtKind, start: Location) -> Stmt {
+        self.count_node();
+        Stmt {
+            kind,
+            start,
+            end: self.last_end,
+        }
+    }
+
+    fn simple_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        let token = self.peek();
+        if token.kind == TokenType::Name {
+            let keyword = token.value.clone();
+            let kind = match keyword.as_str() {
+                "pass" => {
+                    self.advance();
+                    StmtKind::Pass
+                }
+                "break" => {
+                    self.advance();
+                    StmtKind::Break
+                }
+                "continue" => {
+                    self.advance();
+                    StmtKind::Continue
+                }
+                "return" => {
+                    self.advance();
+                    let value = if self.at_statement_end() {
+                        None
+                    } else {
+                        Some(self.star_expressions()?)
+                    };
+                    StmtKind::Return(value)
+                }
+                "raise" => self.raise_statement()?,
+                "global" | "nonlocal" => {
+                    self.advance();
+                    let mut names = vec![self.expect_identifier()?];
+                    while self.eat_operator(",") {
+                        names.push(self.expect_identifier()?);
+                    }
+                    if keyword == "global" {
+                        StmtKind::Global(names)
+                    } else {
+                        StmtKind::Nonlocal(names)
+                    }
+                }
+                "del" => {
+                    self.advance();
+                    let mut targets = Vec::new();
+                    loop {
+                        let mut target = self.star_expression()?;
+                        set_context(&mut target, Context::Del);
+                        targets.push(target);
+                        if !self.eat_operator(",") || self.at_statement_end() {
+                            break;
+                        }
+                    }
+                    for target in &targets {
+                        self.check_target(target, Context::Del)?;
+                    }
+                    StmtKind::Delete(targets)
+                }
+                "assert" => {
+                    self.advance();
+                    let test = self.expression()?;
+                    let msg = if self.eat_operator(",") {
+                        Some(self.expression()?)
+                    } else {
+                        None
+                    };
+                    StmtKind::Assert { test, msg }
+                }
+                "import" => self.import_statement()?,
+                "from" => self.import_from_statement()?,
+                // A soft keyword, only when followed by a name and then the
+                // type parameters or `=`.
+                "type"
+                    if self.peek_nth(1).kind == TokenType::Name
+                        && !is_keyword(&self.peek_nth(1).value)
+                        && (self.peek_nth(2).is_operator("[")
+                            || self.peek_nth(2).is_operator("=")) =>
+                {
+                    self.type_alias()?
+                }
+                _ => return self.expression_statement(),
+            };
+            return Ok(self.stmt(kind, start));
+        }
+        self.expression_statement()
+    }
+
+    fn type_alias(&mut self) -> Result<StmtKind, ParseError> {
+        let start = self.start();
+        self.advance();
+        self.require_version(PythonVersion::Py312, "Type statement is", start)?;
+        let name_start = self.start();
+        let id = self.expect_identifier()?;
+        let name = self.expr(
+            ExprKind::Name {
+                id,
+                ctx: Context::Store,
+            },
+            name_start,
+        );
+        let type_params = self.type_params()?;
+        self.expect_operator("=")?;
+        let value = self.expression()?;
+        Ok(StmtKind::TypeAlias {
+            name,
+            type_params,
+            value,
+        })
+    }
+
+    /// The type parameters in brackets after the name of a generic
+    /// function, class or type alias, if there are any.
+    fn type_params(&mut self) -> Result<Vec<TypeParam>, ParseError> {
+        let mut params = Vec::new();
+        if !self.at_operator("[") {
+            return Ok(params);
+        }
+        self.require_version(
+            PythonVersion::Py312,
+            "Type parameter lists are",
+            self.start(),
+        )?;
+        self.advance();
+        loop {
+            let start = self.start();
+            let kind = if self.eat_operator("*") {
+                let name = self.expect_identifier()?;
+                if self.at_operator(":") {
+                    return Err(
+                        self.syntax_error("cannot use bound with TypeVarTuple", self.start())
+                    );
+                }
+                TypeParamKind::TypeVarTuple(name)
+            } else if self.eat_operator("**") {
+                let name = self.expect_identifier()?;
+                if self.at_operator(":") {
+                    return Err(self.syntax_error("cannot use bound with ParamSpec", self.start()));
+                }
+                TypeParamKind::ParamSpec(name)
+            } else {
+                let name = self.expect_identifier()?;
+                let bound = if self.eat_operator(":") {
+                    Some(self.expression()?)
+                } else {
+                    None
+                };
+                TypeParamKind::TypeVar { name, bound }
+            };
+            params.push(TypeParam {
+                kind,
+                start,
+                end: self.last_end,
+            });
+            if !self.eat_operator(",") || self.at_operator("]") {
+                break;
+            }
+        }
+        self.expect_operator("]")?;
+        Ok(params)
+    }
+
+    fn at_statement_end(&self) -> bool {
+        let token = self.peek();
+        token.kind == TokenType::NewlineLogical
+            || token.kind == TokenType::EndMarker
+            || token.is_operator(";")
+    }
+
+    fn raise_statement(&mut self) -> Result<StmtKind, ParseError> {
+        self.advance();
+        if self.at_statement_end() {
+            return Ok(StmtKind::Raise {
+                exc: None,
+                cause: None,
+            });
+        }
+        let exc = self.expression()?;
+        let cause = if self.eat_keyword("from") {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        Ok(StmtKind::Raise {
+            exc: Some(exc),
+            cause,
+        })
+    }
+
+    fn dotted_name(&mut self) -> Result<String, ParseError> {
+        let mut name = self.expect_identifier()?;
+        while self.eat_operator(".") {
+            name.push('.');
+            name.push_str(&self.expect_identifier()?);
+        }
+        Ok(name)
+    }
+
+    fn import_statement(&mut self) -> Result<StmtKind, ParseError> {
+        self.advance();
+        let mut names = Vec::new();
+        loop {
+            let start = self.start();
+            let name = self.dotted_name()?;
+            let asname = if self.eat_keyword("as") {
+                Some(self.expect_identifier()?)
+            } else {
+                None
+            };
+            names.push(Alias {
+                name,
+                asname,
+                start,
+                end: self.last_end,
+            });
+            if !self.eat_operator(",") {
+                break;
+            }
+        }
+        Ok(StmtKind::Import(names))
+    }
+
+    fn import_from_statement(&mut self) -> Result<StmtKind, ParseError> {
+        self.advance();
+        let mut level = 0;
+        loop {
+            if self.eat_operator(".") {
+                level += 1;
+            } else if self.eat_operator("...") {
+                level += 3;
+            } else {
+                break;
+            }
+        }
+        let module = if level > 0 && self.at_keyword("import") {
+            None
+        } else {
+            Some(self.dotted_name()?)
+        };
+        self.expect_keyword("import")?;
+
+        let mut names = Vec::new();
+        if self.at_operator("*") {
+            let token = self.advance();
+            names.push(Alias {
+                name: "*".to_string(),
+                asname: None,
+                start: token.start,
+                end: token.end,
+            });
+            return Ok(StmtKind::ImportFrom {
+                module,
+                names,
+                level,
+            });
+        }
+        let parenthesized = self.eat_operator("(");
+        loop {
+            let start = self.start();
+            let name = self.expect_identifier()?;
+            let asname = if self.eat_keyword("as") {
+                Some(self.expect_identifier()?)
+            } else {
+                None
+            };
+            names.push(Alias {
+                name,
+                asname,
+                start,
+                end: self.last_end,
+            });
+            if !self.eat_operator(",") {
+                break;
+            }
+            if parenthesized && self.at_operator(")") {
+                break;
+            }
+            if !parenthesized && self.at_statement_end() {
+                return Err(self.syntax_error(
+                    "trailing comma not allowed without surrounding parentheses",
+                    self.start(),
+                ));
+            }
+        }
+        if parenthesized {
+            self.expect_operator(")")?;
+        }
+        Ok(StmtKind::ImportFrom {
+            module,
+            names,
+            level,
+        })
+    }
+
+    /// Expression statements and all forms of assignment.
+    fn expression_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        let statement = self.tokens.checkpoint();
+        let parenthesized = self.at_operator("(");
+        let bare_yield = self.at_keyword("yield");
+        let first = if bare_yield {
+            self.yield_expression()?
+        } else {
+            self.star_expressions()?
+        };
+
+        if self.at_operator(":") {
+            let colon = self.advance().start;
+            let annotation = self.expression()?;
+            self.check_annotated_target(&first, colon)?;
+            let mut target = first;
+            set_context(&mut target, Context::Store);
+            let simple = !parenthesized && matches!(target.kind, ExprKind::Name { .. });
+            let value = if self.eat_operator("=") {
+                Some(self.assigned_value()?)
+            } else {
+                None
+            };
+            let kind = StmtKind::AnnAssign {
+                target,
+                annotation,
+                value,
+                simple,
+            };
+            return Ok(self.stmt(kind, start));
+        }
+
+        let token = self.peek();
+        if token.kind == TokenType::Operator && AUGMENTED_ASSIGNMENTS.contains(&&token.value[..]) {
+            let symbol = self.advance().value;
+            let op = Operator::from_symbol(&symbol[..symbol.len() - 1]).unwrap();
+            self.check_augmented_target(&first)?;
+            let mut target = first;
+            set_context(&mut target, Context::Store);
+            let value = self.assigned_value()?;
+            return Ok(self.stmt(StmtKind::AugAssign { target, op, value }, start));
+        }
+
+        if self.at_operator("=") {
+            let mut targets = vec![first];
+            let mut first_yield = if bare_yield { Some(0) } else { None };
+            while self.eat_operator("=") {
+                if first_yield.is_none() && self.at_keyword("yield") {
+                    first_yield = Some(targets.len());
+                }
+                targets.push(self.assigned_value()?);
+            }
+            let value = targets.pop().unwrap();
+            self.check_assignment(statement, &targets, first_yield)?;
+            for target in &mut targets {
+                set_context(target, Context::Store);
+            }
+            let type_comment = self.type_comment();
+            let kind = StmtKind::Assign {
+                targets,
+                value,
+                type_comment,
+            };
+            return Ok(self.stmt(kind, start));
+        }
+
+        Ok(self.stmt(StmtKind::Expr(first), start))
+    }
+
+    fn assigned_value(&mut self) -> Result<Expr, ParseError> {
+        if self.at_keyword("yield") {
+            self.yield_expression()
+        } else {
+            self.star_expressions()
+        }
+    }
+
+    fn if_statement(&mut self) -> Result<Stmt, ParseError> {
+        let mut branches = Vec::new();
+        loop {
+            let start = self.start();
+            self.advance();
+            let test = self.named_expression()?;
+            let body = self.block()?;
+            branches.push((start, test, body));
+            if !self.at_keyword("elif") {
+                break;
+            }
+            self.link()?;
+        }
+        self.unlink(branches.len() - 1);
+        let mut orelse = if self.eat_keyword("else") {
+            self.block()?
+        } else {
+            Vec::new()
+        };
+        // Each `elif` is an `if` in the `else` of the one before.
+        while let Some((start, test, body)) = branches.pop() {
+            orelse = vec![self.stmt(StmtKind::If { test, body, orelse }, start)];
+        }
+        Ok(orelse.pop().unwrap())
+    }
+
+    fn while_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.advance();
+        let test = self.named_expression()?;
+        let body = self.block()?;
+        let orelse = self.else_block()?;
+        Ok(self.stmt(StmtKind::While { test, body, orelse }, start))
+    }
+
+    fn else_block(&mut self) -> Result<Vec<Stmt>, ParseError> {
+        if self.eat_keyword("else") {
+            self.block()
+        } else {
+            Ok(Vec::new())
+        }
+    }
+
+    fn for_statement(&mut self, start: Location, is_async: bool) -> Result<Stmt, ParseError> {
+        self.expect_keyword("for")?;
+        let target = self.target_list()?;
+        self.expect_keyword("in")?;
+        let iter = self.star_expressions()?;
+        let (type_comment, body) = self.typed_block(false)?;
+        let orelse = self.else_block()?;
+        let kind = StmtKind::For {
+            target,
+            iter,
+            body,
+            orelse,
+            type_comment,
+            is_async,
+        };
+        Ok(self.stmt(kind, start))
+    }
+
+    fn try_statement(&mut self) -> Result<Stmt, ParseError> {
+        let start = self.start();
+        self.advance();
+        let body = self.block()?;
+        let mut handlers = Vec::new();
+        let mut is_star = false;
+        while self.at_keyword("except") {
+            let start = self.start();
+            self.advance();
+            let star = self.start();
+            let star_handler = self.eat_operator("*");
+            if handlers.is_empty() {
+                is_star = star_handler;
+                if is_star {
+                    self.require_version(PythonVersion::Py311, "Exception groups are", star)?;
+                }
+            } else if star_handler != is_star {
+                let message = "cannot have both 'except' and 'except*' on the same 'try'";
+                return Err(self.syntax_error(message, start));
+            }
+            let (type_, name) = if self.at_operator(":") {
+                if is_star {
+                    return Err(self.syntax_error("expected one or more exception types", star));
+                }
+                (None, None)
+            } else {
+                let type_ = self.expression()?;
+                let name = if self.eat_keyword("as") {
+                    Some(self.expect_identifier()?)
+                } else {
+                    None
+                };
+                (Some(type_), name)
+            };
+            let body = self.block()?;
+            handlers.push(ExceptHandler {
+                type_,
+                name,
+                body,
+                start,
+                end: self.last_end,
+            });
+        }
+        let orelse = if handlers.is_empty() {
+            Vec::new()
+        } else {
+            self.else_block()?
+        };
+        let finalbody = if self.eat_keyword("finally") {
+            self.block()?
+        } else {
+            Vec::new()
+        };
+        if handlers.is_empty() && finalbody.is_empty() {
+            return Err(self.expected("'except' or 'finally' block"));
+        }
+        let kind = StmtKind::Try {
+            body,
+            handlers,
+            orelse,
+            finalbody,
+            is_star,
+        };
+        Ok(self.stmt(kind, start))
+    }
+
+    fn with_statement(&mut self, start: Location, is_async: bool) -> Result<Stmt, ParseError> {
+        self.expect_keyword("with")?;
+        // Only the form without parentheses can have a type comment.
+        let (items, (type_comment, body)) = match self.parenthesized_with_items()? {
+            Some(items) => (items, (None, self.block()?)),
+            None => {
+                let mut items = vec![self.with_item()?];
+                while self.eat_operator(",") {
+                    items.push(self.with_item()?);
+                }
+                (items, self.typed_block(false)?)
+            }
+        };
+        let kind = StmtKind::With {
+            items,
+            body,
+            type_comment,
+            is_async,
+        };
+        Ok(self.stmt(kind, start))
+    }
+
+    /// The items of `with (a as b, c as d):`, which needs 3.9 unless it is
+    /// a single expression in parentheses, or `None` when the parentheses
+    /// belong to an expression, as in `with (a, b) as c:`.
+    fn parenthesized_with_items(&mut self) -> Result<Option<Vec<WithItem>>, ParseError> {
+        if !self.at_operator("(") {
+            return Ok(None);
+        }
+        let open = self.start();
+        let parsed = self.speculate(|parser| {
+            parser.advance();
+            let mut items = vec![parser.with_item()?];
+            let mut trailing_comma = false;
+            while parser.eat_operator(",") {
+                trailing_comma = parser.at_operator(")");
+                if trailing_comma {
+                    break;
+                }
+                items.push(parser.with_item()?);
+            }
+            parser.expect_operator(")")?;
+            if !parser.at_operator(":") {
+                return Err(parser.unexpected());
+            }
+            Ok((items, trailing_comma))
+        })?;
+        let (items, trailing_comma) = match parsed {
+            Some(parsed) => parsed,
+            None => return Ok(None),
+        };
+        if items.len() > 1 || items[0].optional_vars.is_some() || trailing_comma {
+            self.require_version(
+                PythonVersion::Py39,
+                "Parenthesized context managers are",
+                open,
+            )?;
+        }
+        Ok(Some(items))
+    }
+
+    fn with_item(&mut self) -> Result<WithItem, ParseError> {
+        let context_expr = self.expression()?;
+        let optional_vars = if self.eat_keyword("as") {
+            let mut target = self.star_target()?;
+            set_context(&mut target, Context::Store);
+            self.check_target(&target, Context::Store)?;
+            Some(target)
+        } else {
+            None
+        };
+        Ok(WithItem {
+            context_expr,
+            optional_vars,
+        })
+    }
+
+    fn async_statement(
+        &mut self,
+        start: Location,
+        decorators: Vec<Expr>,
+    ) -> Result<Stmt, ParseError> {
+        self.advance();
+        if self.at_keyword("def") {
+            return self.function_def(start, decorators, true);
+        }
+        if !decorators.is_empty() {
+            return Err(self.expected("'def'"));
+        }
+        if self.at_keyword("for") {
+            self.for_statement(start, true)
+        } else if self.at_keyword("with") {
+            self.with_statement(start, true)
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn decorated(&mut self) -> Result<Stmt, ParseError> {
+        let mut decorators = Vec::new();
+        while self.eat_operator("@") {
+            decorators.push(self.named_expression()?);
+            self.expect_newline()?;
+        }
+        // As in CPython, a decorated definition starts at `def` or `class`.
+        let start = self.start();
+        if self.at_keyword("def") {
+            self.function_def(start, decorators, false)
+        } else if self.at_keyword("class") {
+            self.class_def(start, decorators)
+        } else if self.at_keyword("async") {
+            self.async_statement(start, decorators)
+        } else {
+            Err(self.expected("class or function definition"))
+        }
+    }
+
+    fn function_def(
+        &mut self,
+        start: Location,
+        decorator_list: Vec<Expr>,
+        is_async: bool,
+    ) -> Result<Stmt, ParseError> {
+        self.expect_keyword("def")?;
+        let name = self.expect_identifier()?;
+        let type_params = self.type_params()?;
+        self.expect_operator("(")?;
+        let args = self.parameters(true, ")")?;
+        self.expect_operator(")")?;
+        let returns = if self.eat_operator("->") {
+            Some(Box::new(self.expression()?))
+        } else {
+            None
+        };
+        let (type_comment, body) = self.typed_block(true)?;
+        let kind = StmtKind::FunctionDef {
+            name,
+            args: Box::new(args),
+            body,
+            decorator_list,
+            returns,
+            type_comment,
+            type_params,
+            is_async,
+        };
+        Ok(self.stmt(kind, start))
+    }
+
+    fn class_def(
+        &mut self,
+        start: Location,
+        decorator_list: Vec<Expr>,
+    ) -> Result<Stmt, ParseError> {
+        self.expect_keyword("class")?;
+        let name = self.expect_identifier()?;
+        let type_params = self.type_params()?;
+        let (bases, keywords) = if self.eat_operator("(") {
+            self.call_arguments(false)?
+        } else {
+            (Vec::new(), Vec::new())
+        };
+        let body = self.block()?;
+        let kind = StmtKind::ClassDef {
+            name,
+            bases,
+            keywords,
+            body,
+            decorator_list,
+            type_params,
+        };
+        Ok(self.stmt(kind, start))
+    }
+
+    /// The parameter list of a `def` (with annotations and type comments)
+    /// or `lambda`, up to but not including `closing`.
+    pub(super) fn parameters(
+        &mut self,
+        annotations: bool,
+        closing: &str,
+    ) -> Result<Arguments, ParseError> {
+        /// Where the parameter just parsed went, for its type comment.
+        enum Parsed {
+            Nothing,
+            Positional,
+            KeywordOnly,
+            Star,
+        }
+        let mut arguments = Arguments::default();
+        let mut keyword_only = false;
+        let mut seen_default = false;
+        while !self.at_operator(closing) {
+            let mut parsed = Parsed::Nothing;
+            if self.at_operator("/") {
+                let token = self.advance();
+                if keyword_only || !arguments.posonlyargs.is_empty() || arguments.args.is_empty() {
+                    return Err(self.syntax_error("invalid syntax", token.start));
+                }
+                self.require_version(
+                    PythonVersion::Py38,
+                    "Positional-only parameters are",
+                    token.start,
+                )?;
+                arguments.posonlyargs = arguments.args.split_off(0);
+            } else if self.at_operator("*") {
+                let token = self.advance();
+                if keyword_only {
+                    return Err(self.syntax_error("* argument may appear only once", token.start));
+                }
+                keyword_only = true;
+                if !self.at_operator(",") && !self.at_operator(closing) {
+                    arguments.vararg = Some(self.parameter(annotations, true)?);
+                    parsed = Parsed::Star;
+                }
+            } else if self.at_operator("**") {
+                self.advance();
+                let mut kwarg = self.parameter(annotations, false)?;
+                self.eat_operator(",");
+                if annotations {
+                    kwarg.type_comment = self.type_comment();
+                }
+                arguments.kwarg = Some(kwarg);
+                if !self.at_operator(closing) {
+                    return Err(self.syntax_error(
+                        "arguments cannot follow var-keyword argument",
+                        self.start(),
+                    ));
+                }
+                break;
+            } else {
+                let parameter = self.parameter(annotations, false)?;
+                let default = if self.eat_operator("=") {
+                    Some(self.expression()?)
+                } else {
+                    None
+                };
+                if keyword_only {
+                    arguments.kwonlyargs.push(parameter);
+                    arguments.kw_defaults.push(default);
+                    parsed = Parsed::KeywordOnly;
+                } else {
+                    match default {
+                        Some(default) => {
+                            seen_default = true;
+                            arguments.defaults.push(default);
+                        }
+                        None if seen_default => {
+                            return Err(self.syntax_error(
+                                "non-default argument follows default argument",
+                                parameter.start,
+                            ))
+                        }
+                        None => {}
+                    }
+                    arguments.args.push(parameter);
+                    parsed = Parsed::Positional;
+                }
+            }
+            // A type comment follows the comma after its parameter, or the
+            // last parameter.
+            let comma = self.eat_operator(",");
+            if annotations {
+                let parameter = match parsed {
+                    Parsed::Nothing => None,
+                    Parsed::Positional => arguments.args.last_mut(),
+                    Parsed::KeywordOnly => arguments.kwonlyargs.last_mut(),
+                    Parsed::Star => arguments.vararg.as_mut(),
+                };
+                if let Some(parameter) = parameter {
+                    parameter.type_comment = self.type_comment();
+                }
+            }
+            if !comma {
+                break;
+            }
+        }
+        if keyword_only && arguments.vararg.is_none() && arguments.kwonlyargs.is_empty() {
+            return Err(self.syntax_error("named arguments must follow bare *", self.start()));
+        }
+        Ok(arguments)
+    }
+
+    fn parameter(&mut self, annotations: bool, starred: bool) -> Result<Arg, ParseError> {
+        let start = self.start();
+        let arg = self.expect_identifier()?;
+        let annotation = if annotations && self.eat_operator(":") {
+            if starred && self.at_operator("*") {
+                Some(Box::new(self.star_expression()?))
+            } else {
+                Some(Box::new(self.expression()?))
+            }
+        } else {
+            None
+        };
+        Ok(Arg {
+            arg,
+            annotation,
+            type_comment: None,
+            start,
+            end: self.last_end,
+        })
+    }
+}
+
+/// Marks an expression, and the names inside tuple, list and starred
+/// targets, as being assigned to or deleted.
+pub(super) fn set_context(expr: &mut Expr, context: Context) {
+    match expr.kind {
+        ExprKind::Name { ref mut ctx, .. }
+        | ExprKind::Attribute { ref mut ctx, .. }
+        | ExprKind::Subscript { ref mut ctx, .. } => *ctx = context,
+        ExprKind::Starred {
+            ref mut ctx,
+            ref mut value,
+        } => {
+            *ctx = context;
+            set_context(value, context);
+        }
+        ExprKind::List {
+            ref mut ctx,
+            ref mut elts,
+        }
+        | ExprKind::Tuple {
+            ref mut ctx,
+            ref mut elts,
+        } => {
+            *ctx = context;
+            for elt in elts {
+                set_context(elt, context);
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,160 @@
+//! Tokens read one at a time with lookahead and checkpoints, for the
+//! parser and for tools that parse a little Python of their own.
+
+use tokenizer::{tokenize, Location, Span, Token, TokenError, TokenType};
+
+use super::{ParseError, ParseErrorKind};
+
+/// A place in a `TokenStream` to go back to with `rollback`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint {
+    position: usize,
+}
+
+/// Tokens consumed in order, any number of which can be looked at before
+/// they are.
+///
+/// A stream ends with an `EndMarker` that is never consumed: `peek` gives
+/// it for any distance past the end and `advance` stays on it, so there is
+/// no need to check for the end before looking ahead.
+#[derive(Debug, Clone)]
+pub struct TokenStream {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl TokenStream {
+    /// A stream over `tokens`, with an `EndMarker` added after the last of
+    /// them unless it is one.
+    pub fn new(mut tokens: Vec<Token>) -> TokenStream {
+        if tokens
+            .last()
+            .is_none_or(|last| last.kind != TokenType::EndMarker)
+        {
+            let (end, offset) = tokens
+                .last()
+                .map_or((Location::new(1, 0), 0), |last| (last.end, last.span.end));
+            tokens.push(Token {
+                kind: TokenType::EndMarker,
+                value: String::new(),
+                start: end,
+                end,
+                span: Span::new(offset, offset),
+            });
+        }
+        TokenStream {
+            tokens,
+            position: 0,
+        }
+    }
+
+    /// A stream over the tokens of `source` a grammar reads: those
+    /// `tokenize` gives, less comments and `NL` tokens, the newlines of
+    /// lines without code and line breaks inside brackets.
+    pub fn tokenize(source: &str) -> Result<TokenStream, TokenError> {
+        let tokens = tokenize(source)?
+            .into_iter()
+            .filter(|token| {
+                token.kind != TokenType::Comment && token.kind != TokenType::NewlineNonLogical
+            })
+            .collect();
+        Ok(TokenStream::new(tokens))
+    }
+
+    /// The token `n` ahead, `peek(0)` being the next to be consumed.
+    pub fn peek(&self, n: usize) -> &Token {
+        let index = (self.position + n).min(self.tokens.len() - 1);
+        &self.tokens[index]
+    }
+
+    /// Consumes the next token. At the end, the `EndMarker` is returned
+    /// and stays next.
+    pub fn advance(&mut self) -> Token {
+        let token = self.peek(0).clone();
+        if token.kind != TokenType::EndMarker {
+            self.position += 1;
+        }
+        token
+    }
+
+    /// The token consumed last.
+    pub fn previous(&self) -> Option<&Token> {
+        self.position
+            .checked_sub(1)
+            .map(|index| &self.tokens[index])
+    }
+
+    /// How many tokens have been consumed, which is the index of the next.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.at(TokenType::EndMarker)
+    }
+
+    pub fn at(&self, kind: TokenType) -> bool {
+        self.peek(0).kind == kind
+    }
+
+    pub fn at_operator(&self, operator: &str) -> bool {
+        self.peek(0).is_operator(operator)
+    }
+
+    pub fn at_name(&self, name: &str) -> bool {
+        self.peek(0).is_name(name)
+    }
+
+    /// Consumes the next token if it is of type `kind`.
+    pub fn eat(&mut self, kind: TokenType) -> Option<Token> {
+        if self.at(kind) {
+            Some(self.advance())
+        } else {
+            None
+        }
+    }
+
+    pub fn eat_operator(&mut self, operator: &str) -> bool {
+        let found = self.at_operator(operator);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    pub fn eat_name(&mut self, name: &str) -> bool {
+        let found = self.at_name(name);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    /// Consumes the next token, failing with a syntax error at it unless it
+    /// is of type `kind`.
+    pub fn expect(&mut self, kind: TokenType) -> Result<Token, ParseError> {
+        if let Some(token) = self.eat(kind) {
+            return Ok(token);
+        }
+        let token = self.peek(0);
+        Err(ParseError::new(
+            ParseErrorKind::InvalidSyntax,
+            format!("expected {}, found {}", kind, token.exact_type()),
+            token.start,
+        ))
+    }
+
+    /// Where the stream is, to come back to with `rollback` after looking
+    /// further than `peek` can tell.
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            position: self.position,
+        }
+    }
+
+    /// Goes back to `checkpoint`, so that the tokens consumed since are
+    /// read again.
+    pub fn rollback(&mut self, checkpoint: Checkpoint) {
+        self.position = checkpoint.position;
+    }
+}
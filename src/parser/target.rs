@@ -0,0 +1,229 @@
+//! The checks CPython makes of what is assigned to or deleted, with its
+//! messages: an expression that is not a name, attribute, subscript, or a
+//! tuple or list of them cannot be a target.
+
+use ast::{Constant, Context, Expr, ExprKind};
+use tokenizer::{Location, TokenType};
+
+use super::{is_keyword, Checkpoint, ParseError, Parser};
+
+/// What CPython calls `expr` in its errors: `function call`, `literal`,
+/// `comparison`, and so on.
+pub(super) fn expr_name(expr: &Expr) -> &'static str {
+    match expr.kind {
+        ExprKind::Attribute { .. } => "attribute",
+        ExprKind::Subscript { .. } => "subscript",
+        ExprKind::Starred { .. } => "starred",
+        ExprKind::Name { .. } => "name",
+        ExprKind::List { .. } => "list",
+        ExprKind::Tuple { .. } => "tuple",
+        ExprKind::Lambda { .. } => "lambda",
+        ExprKind::Call { .. } => "function call",
+        ExprKind::BoolOp { .. } | ExprKind::BinOp { .. } | ExprKind::UnaryOp { .. } => "expression",
+        ExprKind::GeneratorExp { .. } => "generator expression",
+        ExprKind::Yield(_) | ExprKind::YieldFrom(_) => "yield expression",
+        ExprKind::Await(_) => "await expression",
+        ExprKind::ListComp { .. } => "list comprehension",
+        ExprKind::SetComp { .. } => "set comprehension",
+        ExprKind::DictComp { .. } => "dict comprehension",
+        ExprKind::Dict { .. } => "dict literal",
+        ExprKind::Set(_) => "set display",
+        ExprKind::JoinedStr(_) | ExprKind::FormattedValue { .. } => "f-string expression",
+        ExprKind::Constant(Constant::None) => "None",
+        ExprKind::Constant(Constant::Bool(true)) => "True",
+        ExprKind::Constant(Constant::Bool(false)) => "False",
+        ExprKind::Constant(Constant::Ellipsis) => "ellipsis",
+        ExprKind::Constant(_) => "literal",
+        ExprKind::Compare { .. } => "comparison",
+        ExprKind::IfExp { .. } => "conditional expression",
+        ExprKind::NamedExpr { .. } => "named expression",
+        ExprKind::Slice { .. } => "slice",
+    }
+}
+
+/// The first part of `target`, in source order, that cannot be assigned to
+/// or, for `Context::Del`, deleted.
+fn invalid_target(target: &Expr, context: Context) -> Option<&Expr> {
+    match target.kind {
+        ExprKind::Name { .. } | ExprKind::Attribute { .. } | ExprKind::Subscript { .. } => None,
+        ExprKind::Tuple { ref elts, .. } | ExprKind::List { ref elts, .. } => {
+            elts.iter().find_map(|elt| invalid_target(elt, context))
+        }
+        ExprKind::Starred { ref value, .. } if context != Context::Del => {
+            invalid_target(value, context)
+        }
+        _ => Some(target),
+    }
+}
+
+impl Parser {
+    /// Fails unless `target` can be assigned to or, for `Context::Del`,
+    /// deleted.
+    pub(super) fn check_target(&self, target: &Expr, context: Context) -> Result<(), ParseError> {
+        match invalid_target(target, context) {
+            Some(invalid) => {
+                let verb = if context == Context::Del {
+                    "delete"
+                } else {
+                    "assign to"
+                };
+                let message = format!("cannot {} {}", verb, expr_name(invalid));
+                Err(self.syntax_error(message, invalid.start))
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Fails unless each of `targets`, those of an assignment statement
+    /// that begins at `statement`, can be assigned to. `first_yield` is the
+    /// index of the first target that is a `yield` expression without
+    /// parentheses.
+    pub(super) fn check_assignment(
+        &mut self,
+        statement: Checkpoint,
+        targets: &[Expr],
+        first_yield: Option<usize>,
+    ) -> Result<(), ParseError> {
+        let (index, invalid) = match targets
+            .iter()
+            .enumerate()
+            .find_map(|(index, target)| Some((index, invalid_target(target, Context::Store)?)))
+        {
+            Some(found) => found,
+            None => return Ok(()),
+        };
+        // CPython reads the statement again as an expression first, and
+        // points out an element followed by `=` and an operand as a likely
+        // comparison.
+        let (end, last_end, nodes) = (self.tokens.checkpoint(), self.last_end, self.nodes.get());
+        self.tokens.rollback(statement);
+        let comparison = self.mistaken_comparison();
+        self.tokens.rollback(end);
+        self.last_end = last_end;
+        self.nodes.set(nodes);
+        if let Some(err) = comparison? {
+            return Err(err);
+        }
+        if first_yield == Some(index) {
+            return Err(self.syntax_error(
+                "assignment to yield expression not possible",
+                targets[index].start,
+            ));
+        }
+        self.check_target(invalid, Context::Store)
+    }
+
+    /// Reads the comma-separated elements of an expression statement for
+    /// one written as if `=` compared: a name or operand followed by `=`
+    /// and a single operand, which is not followed by another `=`.
+    fn mistaken_comparison(&mut self) -> Result<Option<ParseError>, ParseError> {
+        loop {
+            if self.eat_operator("*") {
+                if self.speculate(Parser::bitwise_or)?.is_none() {
+                    return Ok(None);
+                }
+            } else {
+                let element = self.tokens.checkpoint();
+                let start = self.start();
+                if self.peek().kind == TokenType::Name
+                    && !is_keyword(&self.peek().value)
+                    && self.peek_nth(1).is_operator("=")
+                {
+                    self.advance();
+                    self.advance();
+                    if self.single_operand()? {
+                        return Ok(Some(self.syntax_error(
+                            "invalid syntax. Maybe you meant '==' or ':=' instead of '='?",
+                            start,
+                        )));
+                    }
+                    self.tokens.rollback(element);
+                }
+                if !self.at_display() {
+                    if let Some(operand) = self.speculate(Parser::bitwise_or)? {
+                        if self.eat_operator("=") && self.single_operand()? {
+                            let message = format!(
+                                "cannot assign to {} here. Maybe you meant '==' instead of '='?",
+                                expr_name(&operand)
+                            );
+                            return Ok(Some(self.syntax_error(message, operand.start)));
+                        }
+                    }
+                    self.tokens.rollback(element);
+                }
+                if self.speculate(Parser::named_expression)?.is_none() {
+                    return Ok(None);
+                }
+            }
+            if !self.eat_operator(",") {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Whether an operand comes next and is not followed by `=` or `:=`.
+    fn single_operand(&mut self) -> Result<bool, ParseError> {
+        Ok(self.speculate(Parser::bitwise_or)?.is_some()
+            && !self.at_operator("=")
+            && !self.at_operator(":="))
+    }
+
+    /// Whether a list or tuple display, a generator expression, or `True`,
+    /// `False` or `None` begins here, which CPython does not suggest
+    /// comparing.
+    fn at_display(&mut self) -> bool {
+        let token = self.peek();
+        if token.kind == TokenType::Name {
+            return matches!(token.value.as_str(), "True" | "False" | "None");
+        }
+        if !token.is_operator("[") && !token.is_operator("(") {
+            return false;
+        }
+        let checkpoint = self.tokens.checkpoint();
+        let display = match self.speculate(Parser::atom) {
+            Ok(Some(atom)) => matches!(
+                atom.kind,
+                ExprKind::List { .. } | ExprKind::Tuple { .. } | ExprKind::GeneratorExp { .. }
+            ),
+            _ => false,
+        };
+        self.tokens.rollback(checkpoint);
+        display
+    }
+
+    /// Fails unless `target`, followed by the `:` at `colon`, can be
+    /// annotated.
+    pub(super) fn check_annotated_target(
+        &self,
+        target: &Expr,
+        colon: Location,
+    ) -> Result<(), ParseError> {
+        let message = match target.kind {
+            ExprKind::Name { .. } | ExprKind::Attribute { .. } | ExprKind::Subscript { .. } => {
+                return Ok(())
+            }
+            ExprKind::Tuple { .. } => "only single target (not tuple) can be annotated",
+            ExprKind::List { .. } => "only single target (not list) can be annotated",
+            ExprKind::Starred { .. } => return Err(self.syntax_error("invalid syntax", colon)),
+            _ => "illegal target for annotation",
+        };
+        Err(self.syntax_error(message, target.start))
+    }
+
+    /// Fails unless `target` can be assigned to by an augmented
+    /// assignment.
+    pub(super) fn check_augmented_target(&self, target: &Expr) -> Result<(), ParseError> {
+        match target.kind {
+            ExprKind::Name { .. } | ExprKind::Attribute { .. } | ExprKind::Subscript { .. } => {
+                Ok(())
+            }
+            _ => {
+                let message = format!(
+                    "'{}' is an illegal expression for augmented assignment",
+                    expr_name(target)
+                );
+                Err(self.syntax_error(message, target.start))
+            }
+        }
+    }
+}
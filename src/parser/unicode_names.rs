@@ -0,0 +1,128 @@
+//! Character names, for `\N{...}` escapes.
+//!
+//! The names and aliases of Unicode 14.0, the version of Python 3.11, are
+//! in `unicode_names.bin`, written by `python/unicode_names.py`, which
+//! describes its layout. The names of Hangul syllables and CJK unified
+//! ideographs are made from their code points instead, as CPython does.
+
+static TABLE: &[u8] = include_bytes!("unicode_names.bin");
+
+/// The character named `name`, compared without regard to case except in
+/// the names made from code points, as CPython does.
+pub(super) fn lookup(name: &str) -> Option<char> {
+    if let Some(syllable) = name.strip_prefix("HANGUL SYLLABLE ") {
+        return hangul(syllable);
+    }
+    if let Some(code) = name.strip_prefix("CJK UNIFIED IDEOGRAPH-") {
+        return ideograph(code);
+    }
+    if !name.is_ascii() {
+        return None;
+    }
+    let name = name.to_ascii_uppercase();
+    let mut wanted: Vec<Option<usize>> = name.split(' ').map(|_| None).collect();
+    let count = u16::from_le_bytes([TABLE[0], TABLE[1]]) as usize;
+    let mut at = 2;
+    for index in 0..count {
+        let length = TABLE[at] as usize;
+        let word = &TABLE[at + 1..at + 1 + length];
+        for (part, slot) in name.split(' ').zip(wanted.iter_mut()) {
+            if part.as_bytes() == word {
+                *slot = Some(index);
+            }
+        }
+        at += 1 + length;
+    }
+    let wanted = wanted.into_iter().collect::<Option<Vec<usize>>>()?;
+
+    let names = u32::from_le_bytes([TABLE[at], TABLE[at + 1], TABLE[at + 2], TABLE[at + 3]]);
+    at += 4;
+    let mut code = 0;
+    for _ in 0..names {
+        let mut shift = 0;
+        loop {
+            let byte = TABLE[at];
+            at += 1;
+            code += u32::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte < 0x80 {
+                break;
+            }
+        }
+        let words = TABLE[at] as usize;
+        at += 1;
+        let mut matches = words == wanted.len();
+        for slot in 0..words {
+            let index = if TABLE[at] < 0x80 {
+                at += 1;
+                TABLE[at - 1] as usize
+            } else {
+                at += 2;
+                (TABLE[at - 2] as usize & 0x7f) << 8 | TABLE[at - 1] as usize
+            };
+            matches = matches && wanted.get(slot) == Some(&index);
+        }
+        if matches {
+            return ::std::char::from_u32(code);
+        }
+    }
+    None
+}
+
+const LEADS: [&str; 19] = [
+    "G", "GG", "N", "D", "DD", "R", "M", "B", "BB", "S", "SS", "", "J", "JJ", "C", "K", "T", "P",
+    "H",
+];
+const VOWELS: [&str; 21] = [
+    "A", "AE", "YA", "YAE", "EO", "E", "YEO", "YE", "O", "WA", "WAE", "OE", "YO", "U", "WEO", "WE",
+    "WI", "YU", "EU", "YI", "I",
+];
+const TAILS: [&str; 28] = [
+    "", "G", "GG", "GS", "N", "NJ", "NH", "D", "L", "LG", "LM", "LB", "LS", "LT", "LP", "LH", "M",
+    "B", "BS", "S", "SS", "NG", "J", "C", "K", "T", "P", "H",
+];
+
+/// The Hangul syllable spelt by its jamo, each the longest that fits.
+fn hangul(name: &str) -> Option<char> {
+    let longest = |text: &str, jamo: &[&str]| {
+        jamo.iter()
+            .enumerate()
+            .filter(|(_, spelling)| text.starts_with(*spelling))
+            .max_by_key(|(_, spelling)| spelling.len())
+            .map(|(index, spelling)| (index, spelling.len()))
+    };
+    let (lead, length) = longest(name, &LEADS)?;
+    let name = &name[length..];
+    let (vowel, length) = longest(name, &VOWELS)?;
+    let name = &name[length..];
+    let (tail, length) = longest(name, &TAILS)?;
+    if length != name.len() {
+        return None;
+    }
+    let code = 0xac00 + (lead * VOWELS.len() + vowel) * TAILS.len() + tail;
+    ::std::char::from_u32(code as u32)
+}
+
+const IDEOGRAPHS: [(u32, u32); 8] = [
+    (0x3400, 0x4dbf),
+    (0x4e00, 0x9fff),
+    (0x20000, 0x2a6df),
+    (0x2a700, 0x2b738),
+    (0x2b740, 0x2b81d),
+    (0x2b820, 0x2cea1),
+    (0x2ceb0, 0x2ebe0),
+    (0x30000, 0x3134a),
+];
+
+/// The CJK unified ideograph with four or five upper-case hex digits.
+fn ideograph(digits: &str) -> Option<char> {
+    if !(4..=5).contains(&digits.len()) || !digits.bytes().all(|b| b"0123456789ABCDEF".contains(&b))
+    {
+        return None;
+    }
+    let code = u32::from_str_radix(digits, 16).ok()?;
+    IDEOGRAPHS
+        .iter()
+        .find(|&&(first, last)| (first..=last).contains(&code))
+        .and_then(|_| ::std::char::from_u32(code))
+}
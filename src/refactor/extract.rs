@@ -0,0 +1,313 @@
+use std::collections::HashSet;
+
+use ast::{Expr, ExprKind, Stmt, StmtKind};
+use tokenizer::Span;
+
+use super::resolve::SourceIndex;
+use super::{
+    blocks, check_identifier, child_exprs, for_each_expr, Document, RefactorError, TextEdit,
+};
+
+/// Moves the statements selected by the byte range `selection` into a new
+/// module-level function called `name`, and replaces them with a call.
+///
+/// The selection must cover whole statements of one block. Variables of
+/// the enclosing function that the statements read before assigning them
+/// become parameters, and those they assign that are read afterwards are
+/// returned. The new function goes before the top-level statement that
+/// holds the selection. Statements that `return`, `yield`, `await`, declare
+/// `global` or `nonlocal`, or `break` out of a loop they are not in cannot
+/// be extracted.
+pub fn extract_function(
+    source: &str,
+    selection: Span,
+    name: &str,
+) -> Result<Vec<TextEdit>, RefactorError> {
+    check_identifier(name)?;
+    let document = Document::parse(source)?;
+    let index = &document.index;
+    // Comments at the start of the selection stay where they are.
+    let mut text = &source[selection.start..selection.end];
+    while text.trim_start().starts_with('#') {
+        let comment = text.trim_start();
+        text = comment.find('\n').map_or("", |at| &comment[at..]);
+    }
+    let start = selection.end - text.trim_start().len();
+    let end = selection.end - (text.len() - text.trim_end().len());
+    let not_statements =
+        || RefactorError::Refused("the selection must be whole statements".to_string());
+
+    let mut found = None;
+    find_block(
+        index,
+        &document.module.body,
+        (start, end),
+        &Context::default(),
+        &mut found,
+    );
+    let (statements, context) = found.ok_or_else(not_statements)?;
+    if context.in_class {
+        return Err(RefactorError::Refused(
+            "cannot extract statements from a class body".to_string(),
+        ));
+    }
+    for stmt in statements {
+        check_stmt(stmt, false)?;
+    }
+    let line_start = index.line_start(start);
+    let indent = &source[line_start..start];
+    let line_end = source[end..].find('\n').map_or(source.len(), |at| end + at);
+    let rest = source[end..line_end].trim_start();
+    if !indent.trim().is_empty() || !(rest.is_empty() || rest.starts_with('#')) {
+        return Err(not_statements());
+    }
+
+    // The variables of the enclosing scope used by the statements, in the
+    // order they first appear.
+    let analysis = &document.analysis;
+    let scope = context
+        .function
+        .map_or(0, |location| analysis.node_scopes[&location]);
+    let inside = |span: Span| start <= span.start && span.end <= end;
+    let mut seen = HashSet::new();
+    let mut parameters = Vec::new();
+    let mut results = Vec::new();
+    for occurrence in &analysis.occurrences {
+        let binding = occurrence.binding;
+        if !inside(occurrence.span)
+            || analysis.bindings[binding].scope != scope
+            || !seen.insert(binding)
+        {
+            continue;
+        }
+        let mut first_store = None;
+        let mut first_load = None;
+        let mut stored_outside = false;
+        let mut read_later = false;
+        for other in analysis.occurrences_of(binding) {
+            if inside(other.span) {
+                let first = if other.store {
+                    &mut first_store
+                } else {
+                    &mut first_load
+                };
+                first.get_or_insert(other.span.start);
+            } else if other.store {
+                stored_outside = true;
+            } else {
+                // A global may be read by any function, and a variable
+                // read earlier in an enclosing loop is read again after the
+                // statements run.
+                read_later |= scope == 0
+                    || other.span.start >= end
+                    || context.loop_span.is_some_and(|(loop_start, loop_end)| {
+                        loop_start <= other.span.start && other.span.end <= loop_end
+                    })
+                    || other.scope != scope;
+            }
+        }
+        // Globals that are only read can be read by the new function too.
+        if scope == 0 && first_store.is_none() {
+            continue;
+        }
+        let read_first = match (first_load, first_store) {
+            // `x += 1` reads `x` before it assigns it.
+            (Some(load), Some(store)) => load <= store,
+            (load, _) => load.is_some(),
+        };
+        let name = analysis.bindings[binding].name.clone();
+        if read_first && stored_outside {
+            parameters.push(name.clone());
+        }
+        if first_store.is_some() && read_later {
+            results.push(name);
+        }
+    }
+
+    let mut function = format!("def {}({}):\n", name, parameters.join(", "));
+    let body = &source[line_start..end];
+    for line in body.split('\n') {
+        let line = line.trim_end_matches('\r');
+        let stripped = strip_indent(line, indent);
+        if stripped.trim().is_empty() {
+            function.push('\n');
+        } else {
+            function.push_str("    ");
+            function.push_str(stripped);
+            function.push('\n');
+        }
+    }
+    if !results.is_empty() {
+        function.push_str(&format!("    return {}\n", results.join(", ")));
+    }
+    function.push_str("\n\n");
+
+    let call = format!("{}({})", name, parameters.join(", "));
+    let call = if results.is_empty() {
+        call
+    } else {
+        format!("{} = {}", results.join(", "), call)
+    };
+    let top = match context.top {
+        Some(location) => top_stmt(&document, location),
+        None => &statements[0],
+    };
+    let top = index.line_start(statement_start(index, top));
+    Ok(vec![
+        TextEdit::insert(top, function),
+        TextEdit::new(Span::new(start, end), call),
+    ])
+}
+
+/// Where the selection is, as `find_block` goes down into the statements
+/// that hold it.
+#[derive(Debug, Clone, Copy, Default)]
+struct Context {
+    /// The top-level statement holding the selection.
+    top: Option<::tokenizer::Location>,
+    /// The innermost function holding the selection.
+    function: Option<::tokenizer::Location>,
+    /// The innermost loop of that function holding the selection.
+    loop_span: Option<(usize, usize)>,
+    /// Whether the selection is directly in a class body.
+    in_class: bool,
+}
+
+/// Finds the statements of a block that exactly cover `range`.
+fn find_block<'a>(
+    index: &SourceIndex,
+    block: &'a [Stmt],
+    range: (usize, usize),
+    context: &Context,
+    found: &mut Option<(&'a [Stmt], Context)>,
+) {
+    let first = block
+        .iter()
+        .position(|stmt| statement_start(index, stmt) == range.0);
+    if let Some(first) = first {
+        if let Some(last) = block[first..]
+            .iter()
+            .position(|stmt| index.offset(stmt.end) == range.1)
+        {
+            *found = Some((&block[first..=first + last], *context));
+            return;
+        }
+    }
+    let holder = block
+        .iter()
+        .find(|stmt| statement_start(index, stmt) <= range.0 && range.1 <= index.offset(stmt.end));
+    let stmt = match holder {
+        Some(stmt) => stmt,
+        None => return,
+    };
+    let mut inner = *context;
+    inner.top.get_or_insert(stmt.start);
+    inner.in_class = false;
+    match stmt.kind {
+        StmtKind::FunctionDef { .. } => {
+            inner.function = Some(stmt.start);
+            inner.loop_span = None;
+        }
+        StmtKind::ClassDef { .. } => inner.in_class = true,
+        StmtKind::For { .. } | StmtKind::While { .. } => {
+            inner.loop_span = Some((index.offset(stmt.start), index.offset(stmt.end)));
+        }
+        _ => {}
+    }
+    for block in blocks(stmt) {
+        find_block(index, block, range, &inner, found);
+        if found.is_some() {
+            return;
+        }
+    }
+}
+
+/// Where a statement starts, counting its decorators.
+fn statement_start(index: &SourceIndex, stmt: &Stmt) -> usize {
+    let decorators = match stmt.kind {
+        StmtKind::FunctionDef {
+            ref decorator_list, ..
+        }
+        | StmtKind::ClassDef {
+            ref decorator_list, ..
+        } => decorator_list,
+        _ => return index.offset(stmt.start),
+    };
+    match decorators.first() {
+        Some(decorator) => {
+            let before = &index.source[..index.offset(decorator.start)];
+            before.trim_end().len() - 1
+        }
+        None => index.offset(stmt.start),
+    }
+}
+
+/// The top-level statement that starts at `location`.
+fn top_stmt<'a>(document: &'a Document, location: ::tokenizer::Location) -> &'a Stmt {
+    document
+        .module
+        .body
+        .iter()
+        .find(|stmt| stmt.start == location)
+        .unwrap()
+}
+
+/// `line` without the indentation `indent`, or without the whitespace it
+/// starts with if that is less.
+fn strip_indent<'a>(line: &'a str, indent: &str) -> &'a str {
+    if let Some(rest) = line.strip_prefix(indent) {
+        return rest;
+    }
+    line.trim_start()
+}
+
+/// Refuses statements that only work where they are. `in_loop` says
+/// whether they are in a loop that is extracted with them.
+fn check_stmt(stmt: &Stmt, in_loop: bool) -> Result<(), RefactorError> {
+    let refuse = |what: &str| {
+        Err(RefactorError::Refused(format!(
+            "cannot extract statements that contain '{}'",
+            what
+        )))
+    };
+    match stmt.kind {
+        // What happens in a nested function stays there.
+        StmtKind::FunctionDef { .. } | StmtKind::ClassDef { .. } => return Ok(()),
+        StmtKind::Return(_) => return refuse("return"),
+        StmtKind::Global(_) => return refuse("global"),
+        StmtKind::Nonlocal(_) => return refuse("nonlocal"),
+        StmtKind::For { is_async: true, .. } => return refuse("async for"),
+        StmtKind::With { is_async: true, .. } => return refuse("async with"),
+        StmtKind::Break if !in_loop => return refuse("break"),
+        StmtKind::Continue if !in_loop => return refuse("continue"),
+        _ => {}
+    }
+    let mut found = None;
+    for_each_expr(stmt, &mut |expr| {
+        if found.is_none() {
+            found = suspends(expr);
+        }
+    });
+    if let Some(what) = found {
+        return refuse(what);
+    }
+    let loop_body = matches!(stmt.kind, StmtKind::For { .. } | StmtKind::While { .. });
+    for (position, block) in blocks(stmt).into_iter().enumerate() {
+        // The `else` of a loop is not in the loop.
+        let in_loop = in_loop || (loop_body && position == 0);
+        for stmt in block {
+            check_stmt(stmt, in_loop)?;
+        }
+    }
+    Ok(())
+}
+
+/// `yield` or `await` in an expression, outside the lambdas in it.
+fn suspends(expr: &Expr) -> Option<&'static str> {
+    match expr.kind {
+        ExprKind::Yield(_) | ExprKind::YieldFrom(_) => Some("yield"),
+        ExprKind::Await(_) => Some("await"),
+        ExprKind::Lambda { .. } => None,
+        _ => child_exprs(expr).into_iter().find_map(suspends),
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::HashMap;
+
+use ast::{BoolOperator, Constant, Expr, ExprKind, Stmt, StmtKind};
+use parser::Precedence;
+use tokenizer::Span;
+
+use super::resolve::{ScopeKind, SourceIndex};
+use super::{blocks, child_exprs, for_each_expr, Document, RefactorError, TextEdit};
+
+/// Replaces each use of the variable whose name is at byte offset `offset`
+/// with the expression assigned to it, and removes the assignment.
+///
+/// The variable must be assigned exactly once, by an assignment to it
+/// alone, and the variables the expression reads must not be assigned
+/// again after it or mean something else where it is used. The expression
+/// is put in parentheses where it would otherwise bind differently.
+pub fn inline_variable(source: &str, offset: usize) -> Result<Vec<TextEdit>, RefactorError> {
+    let document = Document::parse(source)?;
+    let index = &document.index;
+    let analysis = &document.analysis;
+    let binding = document.binding_at(offset)?;
+    let data = &analysis.bindings[binding];
+    let refuse = |reason: String| {
+        Err(RefactorError::Refused(format!(
+            "cannot inline '{}': {}",
+            data.name, reason
+        )))
+    };
+    if analysis.scopes[data.scope].kind == ScopeKind::Class {
+        return refuse("it is a class attribute".to_string());
+    }
+    let mut stores = analysis
+        .occurrences_of(binding)
+        .filter(|occurrence| occurrence.store);
+    let store = match (stores.next(), stores.next()) {
+        (Some(store), None) => store,
+        (None, _) => return refuse("it is not assigned in this module".to_string()),
+        (Some(_), Some(_)) => return refuse("it is assigned more than once".to_string()),
+    };
+    let (stmt, block) = match find_assignment(index, &document.module.body, store.span.start) {
+        Some(found) => found,
+        None => return refuse("it is not assigned by a simple assignment".to_string()),
+    };
+    let value = match stmt.kind {
+        StmtKind::Assign { ref value, .. } => value,
+        _ => unreachable!(),
+    };
+    let value_span = Span::new(index.offset(value.start), index.offset(value.end));
+    let stmt_span = Span::new(index.offset(stmt.start), index.offset(stmt.end));
+    let uses: Vec<_> = analysis
+        .occurrences_of(binding)
+        .filter(|occurrence| !occurrence.store)
+        .collect();
+
+    for read in &analysis.occurrences {
+        if read.store || read.span.start < value_span.start || read.span.end > value_span.end {
+            continue;
+        }
+        let name = &analysis.bindings[read.binding].name;
+        let reassigned = analysis
+            .occurrences_of(read.binding)
+            .any(|other| other.store && other.span.start >= stmt_span.end);
+        if reassigned {
+            return refuse(format!("'{}' is assigned again after it", name));
+        }
+        for use_ in &uses {
+            if analysis.lookup(use_.scope, name) != Some(read.binding) {
+                return refuse(format!("'{}' means something else where it is used", name));
+            }
+        }
+    }
+
+    let mut slots = HashMap::new();
+    for stmt in &document.module.body {
+        stmt_slots(index, stmt, &mut slots);
+    }
+    let text = &source[value_span.start..value_span.end];
+    let level = precedence(value, text);
+    let mut edits = Vec::new();
+    for use_ in uses {
+        // Names the walk does not reach, such as those in patterns, get
+        // the expression in parentheses unless it is an atom.
+        let (slot, in_string) = slots
+            .get(&use_.span.start)
+            .cloned()
+            .unwrap_or((Precedence::Atom, false));
+        if in_string {
+            return refuse("it is used in an f-string".to_string());
+        }
+        let text = if level < slot {
+            format!("({})", text)
+        } else {
+            text.to_string()
+        };
+        edits.push(TextEdit::new(use_.span, text));
+    }
+    edits.push(removal(source, index, stmt_span, block.len() == 1));
+    Ok(edits)
+}
+
+/// The assignment `name = value` whose target starts at `offset`, and the
+/// block it is in.
+fn find_assignment<'a>(
+    index: &SourceIndex,
+    block: &'a [Stmt],
+    offset: usize,
+) -> Option<(&'a Stmt, &'a [Stmt])> {
+    for stmt in block {
+        if let StmtKind::Assign { ref targets, .. } = stmt.kind {
+            let simple = targets.len() == 1 && matches!(targets[0].kind, ExprKind::Name { .. });
+            if simple && index.offset(targets[0].start) == offset {
+                return Some((stmt, block));
+            }
+        }
+        for block in blocks(stmt) {
+            if let Some(found) = find_assignment(index, block, offset) {
+                return Some(found);
+            }
+        }
+    }
+    None
+}
+
+/// The edit that removes an assignment: its lines if it has them to
+/// itself, or the statement and the `;` that separates it from another.
+/// The only statement of a block becomes `pass`.
+fn removal(source: &str, index: &SourceIndex, span: Span, only: bool) -> TextEdit {
+    if only {
+        return TextEdit::new(span, "pass");
+    }
+    let line_start = index.line_start(span.start);
+    let line_end = source[span.end..]
+        .find('\n')
+        .map_or(source.len(), |at| span.end + at + 1);
+    let before = &source[line_start..span.start];
+    let after = &source[span.end..line_end];
+    if before.trim().is_empty() && after.trim().is_empty() {
+        return TextEdit::new(Span::new(line_start, line_end), "");
+    }
+    if after.trim_start().starts_with(';') {
+        let semicolon = span.end + after.find(';').unwrap() + 1;
+        let next = source[semicolon..line_end]
+            .find(|c: char| c != ' ' && c != '\t')
+            .map_or(line_end, |at| semicolon + at);
+        return TextEdit::new(Span::new(span.start, next), "");
+    }
+    let semicolon = line_start + before.rfind(';').unwrap_or(before.len());
+    TextEdit::new(Span::new(semicolon, span.end), "")
+}
+
+/// How tightly an expression binds, as written.
+fn precedence(expr: &Expr, text: &str) -> Precedence {
+    match expr.kind {
+        ExprKind::BoolOp { op, .. } => match op {
+            BoolOperator::And => Precedence::And,
+            BoolOperator::Or => Precedence::Or,
+        },
+        ExprKind::NamedExpr { .. } => Precedence::NamedExpr,
+        ExprKind::BinOp { op, .. } => Precedence::of_binary_operator(op.symbol()).unwrap(),
+        ExprKind::UnaryOp { op, .. } => Precedence::of_unary_operator(op.symbol()).unwrap(),
+        ExprKind::Lambda { .. } | ExprKind::IfExp { .. } => Precedence::Test,
+        ExprKind::Compare { .. } => Precedence::Comparison,
+        ExprKind::Await(_) => Precedence::Await,
+        ExprKind::Yield(_) | ExprKind::YieldFrom(_) => Precedence::Yield,
+        ExprKind::Tuple { .. } if !text.starts_with('(') => Precedence::Tuple,
+        ExprKind::Starred { .. } => Precedence::Tuple,
+        // `1.real` is not an attribute of `1`.
+        ExprKind::Constant(Constant::Int(_))
+        | ExprKind::Constant(Constant::LongInt(_))
+        | ExprKind::Constant(Constant::Float(_))
+        | ExprKind::Constant(Constant::Complex { .. }) => Precedence::Await,
+        _ => Precedence::Atom,
+    }
+}
+
+/// Records, by the offset of each name read in a statement, how tightly an
+/// expression put in its place must bind, and whether it is in an
+/// f-string.
+fn stmt_slots(index: &SourceIndex, stmt: &Stmt, slots: &mut HashMap<usize, (Precedence, bool)>) {
+    let slot = match stmt.kind {
+        StmtKind::Assign { .. } | StmtKind::Return(_) | StmtKind::Expr(_) => Precedence::Tuple,
+        _ => Precedence::Test,
+    };
+    for_each_expr(stmt, &mut |expr| {
+        expr_slots(index, expr, slot, false, slots)
+    });
+    for block in blocks(stmt) {
+        for stmt in block {
+            stmt_slots(index, stmt, slots);
+        }
+    }
+}
+
+fn expr_slots(
+    index: &SourceIndex,
+    expr: &Expr,
+    slot: Precedence,
+    in_string: bool,
+    slots: &mut HashMap<usize, (Precedence, bool)>,
+) {
+    let mut visit =
+        |child: &Expr, slot: Precedence| expr_slots(index, child, slot, in_string, slots);
+    match expr.kind {
+        ExprKind::Name { .. } => {
+            slots.insert(index.offset(expr.start), (slot, in_string));
+        }
+        ExprKind::BoolOp { op, ref values } => {
+            let level = match op {
+                BoolOperator::And => Precedence::And,
+                BoolOperator::Or => Precedence::Or,
+            };
+            for value in values {
+                visit(value, level.next());
+            }
+        }
+        ExprKind::BinOp {
+            ref left,
+            op,
+            ref right,
+        } => {
+            let level = Precedence::of_binary_operator(op.symbol()).unwrap();
+            if level.is_right_associative() {
+                visit(left, level.next());
+                visit(right, level);
+            } else {
+                visit(left, level);
+                visit(right, level.next());
+            }
+        }
+        ExprKind::UnaryOp { op, ref operand } => {
+            visit(operand, Precedence::of_unary_operator(op.symbol()).unwrap())
+        }
+        ExprKind::IfExp {
+            ref test,
+            ref body,
+            ref orelse,
+        } => {
+            visit(body, Precedence::Or);
+            visit(test, Precedence::Or);
+            visit(orelse, Precedence::Test);
+        }
+        ExprKind::Compare {
+            ref left,
+            ref comparators,
+            ..
+        } => {
+            visit(left, Precedence::BitOr);
+            for comparator in comparators {
+                visit(comparator, Precedence::BitOr);
+            }
+        }
+        ExprKind::Starred { ref value, .. } => visit(value, Precedence::BitOr),
+        ExprKind::Await(ref value) => visit(value, Precedence::Atom),
+        ExprKind::Attribute { ref value, .. } => visit(value, Precedence::Atom),
+        ExprKind::Subscript {
+            ref value,
+            ref slice,
+            ..
+        } => {
+            visit(value, Precedence::Atom);
+            visit(slice, Precedence::Tuple);
+        }
+        ExprKind::Call { ref func, .. } => {
+            visit(func, Precedence::Atom);
+            for child in child_exprs(expr).into_iter().skip(1) {
+                visit(child, Precedence::Test);
+            }
+        }
+        ExprKind::ListComp { .. }
+        | ExprKind::SetComp { .. }
+        | ExprKind::DictComp { .. }
+        | ExprKind::GeneratorExp { .. } => {
+            // The iterables and conditions are `or` expressions; so, to
+            // keep this simple, are the elements.
+            for child in child_exprs(expr) {
+                visit(child, Precedence::Or);
+            }
+        }
+        ExprKind::JoinedStr(ref values) => {
+            for value in values {
+                expr_slots(index, value, Precedence::Test, true, slots);
+            }
+        }
+        _ => {
+            for child in child_exprs(expr) {
+                visit(child, Precedence::Test);
+            }
+        }
+    }
+}
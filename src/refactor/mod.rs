@@ -0,0 +1,733 @@
+//! Refactoring operations: renaming a variable, extracting statements into
+//! a function and inlining a variable.
+//!
+//! Each operation works out which names refer to the same variable with
+//! Python's scoping rules, and returns the changes to make as `TextEdit`s
+//! on the source it was given. Only the names, statements and expressions
+//! involved are rewritten, so comments and formatting elsewhere are kept.
+//! `rename_in_project` also follows imports between the modules of a
+//! project, so a function renamed where it is defined is renamed where it
+//! is imported and used too.
+
+mod extract;
+mod inline;
+mod resolve;
+
+pub use self::extract::extract_function;
+pub use self::inline::inline_variable;
+
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+
+use ast::{Comprehension, Expr, ExprKind, Module, Stmt, StmtKind};
+use parser::{self, is_keyword, ParseError};
+use tokenizer::{tokenize, Span, TokenType};
+
+use self::resolve::{Analysis, Origin, SourceIndex};
+
+/// A change to a source: the text in `span` is replaced by `text`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct TextEdit {
+    pub span: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new<S: Into<String>>(span: Span, text: S) -> TextEdit {
+        TextEdit {
+            span,
+            text: text.into(),
+        }
+    }
+
+    /// An edit that inserts `text` at `offset`.
+    pub fn insert<S: Into<String>>(offset: usize, text: S) -> TextEdit {
+        TextEdit::new(Span::new(offset, offset), text)
+    }
+}
+
+/// Applies edits that do not overlap. Text inserted at the same offset is
+/// inserted in the order of `edits`, before a replacement starting there.
+/// Edits that change the same text are an error.
+pub fn apply_edits(source: &str, edits: &[TextEdit]) -> Result<String, RefactorError> {
+    let mut sorted: Vec<&TextEdit> = edits.iter().collect();
+    sorted.sort_by_key(|edit| (edit.span.start, edit.span.end));
+    let mut out = String::with_capacity(source.len());
+    let mut position = 0;
+    let mut previous: Option<Span> = None;
+    for edit in sorted {
+        if let Some(previous) = previous.filter(|_| edit.span.start < position) {
+            return Err(RefactorError::Overlapping(previous, edit.span));
+        }
+        out.push_str(&source[position..edit.span.start]);
+        out.push_str(&edit.text);
+        position = edit.span.end;
+        previous = Some(edit.span);
+    }
+    out.push_str(&source[position..]);
+    Ok(out)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RefactorError {
+    Parse(ParseError),
+    /// There is no name at the byte offset given.
+    NoName(usize),
+    /// The new name is not an identifier, or is a keyword.
+    InvalidName(String),
+    /// The refactoring cannot be done without changing what the code does,
+    /// or is not supported for this code.
+    Refused(String),
+    /// Two edits given to `apply_edits` change the same text.
+    Overlapping(Span, Span),
+}
+
+impl fmt::Display for RefactorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RefactorError::Parse(ref err) => write!(f, "{}", err),
+            RefactorError::NoName(offset) => write!(f, "no name at offset {}", offset),
+            RefactorError::InvalidName(ref name) => {
+                write!(f, "'{}' is not a valid identifier", name)
+            }
+            RefactorError::Refused(ref message) => f.write_str(message),
+            RefactorError::Overlapping(first, second) => write!(
+                f,
+                "the edits of bytes {} to {} and {} to {} overlap",
+                first.start, first.end, second.start, second.end
+            ),
+        }
+    }
+}
+
+impl Error for RefactorError {}
+
+impl From<ParseError> for RefactorError {
+    fn from(err: ParseError) -> RefactorError {
+        RefactorError::Parse(err)
+    }
+}
+
+/// A module of a project, for `rename_in_project`.
+#[derive(Debug, Clone)]
+pub struct ProjectFile {
+    /// The dotted name the module is imported by, such as `pkg.util`.
+    pub module: String,
+    pub source: String,
+    /// Whether the file is the `__init__.py` of a package, which relative
+    /// imports in it are relative to.
+    pub is_package: bool,
+}
+
+impl ProjectFile {
+    pub fn new<M: Into<String>, S: Into<String>>(module: M, source: S) -> ProjectFile {
+        ProjectFile {
+            module: module.into(),
+            source: source.into(),
+            is_package: false,
+        }
+    }
+}
+
+/// The edits `rename_in_project` makes to one file, by its index in the
+/// files it was given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEdits {
+    pub file: usize,
+    pub edits: Vec<TextEdit>,
+}
+
+/// A parsed source and the names in it.
+struct Document<'a> {
+    index: SourceIndex<'a>,
+    module: Module,
+    analysis: Analysis,
+}
+
+impl<'a> Document<'a> {
+    fn parse(source: &'a str) -> Result<Document<'a>, RefactorError> {
+        let module = parser::parse(source)?;
+        let tokens = tokenize(source).map_err(ParseError::from)?;
+        let index = SourceIndex::new(source, &tokens);
+        let analysis = Analysis::new(&index, &module);
+        Ok(Document {
+            index,
+            module,
+            analysis,
+        })
+    }
+
+    /// The binding of the name at `offset`.
+    fn binding_at(&self, offset: usize) -> Result<usize, RefactorError> {
+        self.analysis
+            .occurrence_at(offset)
+            .map(|occurrence| occurrence.binding)
+            .ok_or(RefactorError::NoName(offset))
+    }
+
+    fn text(&self, span: Span) -> &'a str {
+        &self.index.source[span.start..span.end]
+    }
+}
+
+fn check_identifier(name: &str) -> Result<(), RefactorError> {
+    let valid = !is_keyword(name)
+        && tokenize(name).is_ok_and(|tokens| {
+            tokens
+                .first()
+                .is_some_and(|token| token.kind == TokenType::Name && token.value == name)
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(RefactorError::InvalidName(name.to_string()))
+    }
+}
+
+/// Renames the variable whose name is at byte offset `offset`, everywhere
+/// it is used in `source`.
+///
+/// A name bound by an import is renamed by giving the import an `as`
+/// clause, since the module it comes from is not changed.
+pub fn rename(source: &str, offset: usize, new_name: &str) -> Result<Vec<TextEdit>, RefactorError> {
+    check_identifier(new_name)?;
+    let document = Document::parse(source)?;
+    let binding = document.binding_at(offset)?;
+    rename_binding(&document, binding, new_name, true)
+}
+
+/// The edits that rename every occurrence of `binding`. With `alias`, the
+/// names an import binds get an `as` clause rather than being renamed.
+fn rename_binding(
+    document: &Document,
+    binding: usize,
+    new_name: &str,
+    alias: bool,
+) -> Result<Vec<TextEdit>, RefactorError> {
+    let analysis = &document.analysis;
+    let data = &analysis.bindings[binding];
+    if data.name == new_name {
+        return Ok(Vec::new());
+    }
+    if !analysis
+        .occurrences_of(binding)
+        .any(|occurrence| occurrence.store)
+    {
+        return Err(RefactorError::Refused(format!(
+            "'{}' is not defined in this module",
+            data.name
+        )));
+    }
+    // The new name must not capture a use of another variable of that name.
+    let captured = analysis.occurrences.iter().any(|occurrence| {
+        analysis.bindings[occurrence.binding].name == new_name
+            && analysis.lookup(occurrence.scope, &data.name) == Some(binding)
+    });
+    if captured {
+        return Err(RefactorError::Refused(format!(
+            "renaming '{}' to '{}' would hide the '{}' used in its scope",
+            data.name, new_name, new_name
+        )));
+    }
+    let mut edits = Vec::new();
+    // An augmented assignment both reads and stores its target, which is
+    // one occurrence of each at the same name.
+    let mut renamed = HashSet::new();
+    for occurrence in analysis.occurrences_of(binding) {
+        if !renamed.insert(occurrence.span) {
+            continue;
+        }
+        // The variable must not be shadowed by, or shadow, one of the new
+        // name where it is used.
+        let existing = analysis.lookup(occurrence.scope, new_name);
+        if existing.is_some_and(|existing| {
+            existing != binding
+                && analysis
+                    .occurrences_of(existing)
+                    .any(|occurrence| occurrence.store)
+        }) {
+            return Err(RefactorError::Refused(format!(
+                "renaming '{}' to '{}' would clash with the existing '{}'",
+                data.name, new_name, new_name
+            )));
+        }
+        let text = match data.origin {
+            Some(Origin::Module(ref module)) if alias && occurrence.store => {
+                let span = occurrence.span;
+                if document.index.source[span.end..].starts_with('.') {
+                    return Err(RefactorError::Refused(format!(
+                        "cannot rename '{}', which is bound by importing a submodule",
+                        data.name
+                    )));
+                }
+                if *module == data.name {
+                    format!("{} as {}", data.name, new_name)
+                } else {
+                    new_name.to_string()
+                }
+            }
+            Some(Origin::Imported { ref name, .. })
+                if alias && occurrence.store && document.text(occurrence.span) == name =>
+            {
+                if analysis
+                    .imports
+                    .iter()
+                    .any(|import| import.span == occurrence.span)
+                {
+                    format!("{} as {}", name, new_name)
+                } else {
+                    new_name.to_string()
+                }
+            }
+            _ => new_name.to_string(),
+        };
+        edits.push(TextEdit::new(occurrence.span, text));
+    }
+    Ok(edits)
+}
+
+/// Renames the variable whose name is at byte offset `offset` in the file
+/// at index `file`, in that file and in the files that import it.
+///
+/// A variable of a module's global scope is renamed where it is defined,
+/// in `from` imports of it and where it is read as an attribute of the
+/// module, in every file. A name bound by `from module import name` is
+/// renamed in the module it comes from, if that is part of the project.
+/// Files that do not parse are left alone.
+pub fn rename_in_project(
+    files: &[ProjectFile],
+    file: usize,
+    offset: usize,
+    new_name: &str,
+) -> Result<Vec<FileEdits>, RefactorError> {
+    check_identifier(new_name)?;
+    let target = Document::parse(&files[file].source)?;
+    let binding = target.binding_at(offset)?;
+    let data = &target.analysis.bindings[binding];
+    let is_import = matches!(data.origin, Some(Origin::Imported { .. }));
+    if data.scope != 0 || (data.origin.is_some() && !is_import) {
+        let edits = rename_binding(&target, binding, new_name, true)?;
+        return Ok(vec![FileEdits { file, edits }]);
+    }
+
+    let documents: Vec<Option<Document>> = files
+        .iter()
+        .enumerate()
+        .map(|(index, project_file)| {
+            if index == file {
+                None
+            } else {
+                Document::parse(&project_file.source).ok()
+            }
+        })
+        .collect();
+    let project = Project {
+        files,
+        documents: documents
+            .iter()
+            .enumerate()
+            .map(|(index, document)| {
+                if index == file {
+                    Some(&target)
+                } else {
+                    document.as_ref()
+                }
+            })
+            .collect(),
+        modules: files
+            .iter()
+            .enumerate()
+            .map(|(index, file)| (file.module.as_str(), index))
+            .collect(),
+    };
+    let (home, name) = match project.definition(file, &data.name) {
+        Some(definition) => definition,
+        None => {
+            return Err(RefactorError::Refused(format!(
+                "'{}' is imported from a module outside the project",
+                data.name
+            )))
+        }
+    };
+    if name == new_name {
+        return Ok(Vec::new());
+    }
+    let home_module = files[home].module.as_str();
+
+    let mut result = Vec::new();
+    for (index, document) in project.documents.iter().enumerate() {
+        let document = match *document {
+            Some(document) => document,
+            None => continue,
+        };
+        let mut edits: HashMap<Span, TextEdit> = HashMap::new();
+        for (name_in_file, &binding) in &document.analysis.scopes[0].bindings {
+            if name_in_file != name || project.definition(index, name_in_file) != Some((home, name))
+            {
+                continue;
+            }
+            for edit in rename_binding(document, binding, new_name, false)? {
+                edits.insert(edit.span, edit);
+            }
+        }
+        for import in &document.analysis.imports {
+            let module = project.resolve(index, import.level, import.module.as_ref());
+            if import.name == name && module.as_deref() == Some(home_module) {
+                edits.insert(import.span, TextEdit::new(import.span, new_name));
+            }
+        }
+        for attribute in &document.analysis.attributes {
+            if attribute.attr != name {
+                continue;
+            }
+            let module = project.module_of(index, attribute.root, &attribute.path);
+            if module.as_deref() == Some(home_module) {
+                edits.insert(attribute.span, TextEdit::new(attribute.span, new_name));
+            }
+        }
+        if !edits.is_empty() {
+            let mut edits: Vec<TextEdit> = edits.into_values().collect();
+            edits.sort_by_key(|edit| edit.span.start);
+            result.push(FileEdits { file: index, edits });
+        }
+    }
+    Ok(result)
+}
+
+/// The files of a project, parsed, and their modules by name.
+struct Project<'a> {
+    files: &'a [ProjectFile],
+    documents: Vec<Option<&'a Document<'a>>>,
+    modules: HashMap<&'a str, usize>,
+}
+
+impl<'a> Project<'a> {
+    /// The absolute name of the module imported by a `from` import with
+    /// `level` dots in `file`.
+    fn resolve(&self, file: usize, level: usize, module: Option<&String>) -> Option<String> {
+        if level == 0 {
+            return module.cloned();
+        }
+        let file = &self.files[file];
+        let mut parts: Vec<&str> = file.module.split('.').collect();
+        if !file.is_package {
+            parts.pop();
+        }
+        for _ in 1..level {
+            parts.pop()?;
+        }
+        parts.extend(module.iter().flat_map(|module| module.split('.')));
+        Some(parts.join("."))
+    }
+
+    /// The file and name a global variable of `file` is defined as, through
+    /// `from` imports of it, if that file is part of the project.
+    fn definition<'b>(&'b self, mut file: usize, name: &'b str) -> Option<(usize, &'b str)> {
+        let mut name = name;
+        // Bounded, in case modules import a name from each other.
+        for _ in 0..self.files.len() + 1 {
+            let document = self.documents[file]?;
+            let binding = *document.analysis.scopes[0].bindings.get(name)?;
+            match document.analysis.bindings[binding].origin {
+                Some(Origin::Imported {
+                    level,
+                    ref module,
+                    name: ref imported,
+                }) => {
+                    let module = self.resolve(file, level, module.as_ref())?;
+                    file = *self.modules.get(module.as_str())?;
+                    name = imported;
+                }
+                Some(Origin::Module(_)) => return None,
+                None => return Some((file, name)),
+            }
+        }
+        None
+    }
+
+    /// The module that the chain of names starting with `root` and going
+    /// through the attributes `path` refers to, if it is one.
+    fn module_of(&self, file: usize, root: usize, path: &[String]) -> Option<String> {
+        let document = self.documents[file]?;
+        let mut module = match document.analysis.bindings[root].origin {
+            Some(Origin::Module(ref module)) => module.clone(),
+            Some(Origin::Imported {
+                level,
+                ref module,
+                ref name,
+            }) => {
+                let package = self.resolve(file, level, module.as_ref())?;
+                format!("{}.{}", package, name)
+            }
+            None => return None,
+        };
+        for attr in path {
+            module.push('.');
+            module.push_str(attr);
+        }
+        if self.modules.contains_key(module.as_str()) {
+            Some(module)
+        } else {
+            None
+        }
+    }
+}
+
+/// The blocks of statements directly inside `stmt`.
+fn blocks(stmt: &Stmt) -> Vec<&[Stmt]> {
+    match stmt.kind {
+        StmtKind::FunctionDef { ref body, .. }
+        | StmtKind::ClassDef { ref body, .. }
+        | StmtKind::With { ref body, .. } => vec![body],
+        StmtKind::For {
+            ref body,
+            ref orelse,
+            ..
+        }
+        | StmtKind::While {
+            ref body,
+            ref orelse,
+            ..
+        }
+        | StmtKind::If {
+            ref body,
+            ref orelse,
+            ..
+        } => vec![body, orelse],
+        StmtKind::Try {
+            ref body,
+            ref handlers,
+            ref orelse,
+            ref finalbody,
+            ..
+        } => {
+            let mut blocks: Vec<&[Stmt]> = vec![body];
+            blocks.extend(handlers.iter().map(|handler| &handler.body[..]));
+            blocks.push(orelse);
+            blocks.push(finalbody);
+            blocks
+        }
+        StmtKind::Match { ref cases, .. } => cases.iter().map(|case| &case.body[..]).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Calls `f` on the expressions of a statement, but not on those of the
+/// statements in its blocks.
+fn for_each_expr<F: FnMut(&Expr)>(stmt: &Stmt, f: &mut F) {
+    match stmt.kind {
+        StmtKind::FunctionDef {
+            ref args,
+            ref decorator_list,
+            ref returns,
+            ..
+        } => {
+            decorator_list.iter().for_each(&mut *f);
+            args.defaults.iter().for_each(&mut *f);
+            args.kw_defaults.iter().flatten().for_each(&mut *f);
+            let all = args
+                .posonlyargs
+                .iter()
+                .chain(&args.args)
+                .chain(&args.vararg)
+                .chain(&args.kwonlyargs)
+                .chain(&args.kwarg);
+            for arg in all {
+                arg.annotation.iter().for_each(|annotation| f(annotation));
+            }
+            returns.iter().for_each(|returns| f(returns));
+        }
+        StmtKind::ClassDef {
+            ref bases,
+            ref keywords,
+            ref decorator_list,
+            ..
+        } => {
+            decorator_list.iter().for_each(&mut *f);
+            bases.iter().for_each(&mut *f);
+            keywords.iter().for_each(|keyword| f(&keyword.value));
+        }
+        StmtKind::Return(ref value) => value.iter().for_each(f),
+        StmtKind::Delete(ref exprs) => exprs.iter().for_each(f),
+        StmtKind::Assign {
+            ref targets,
+            ref value,
+            ..
+        } => {
+            targets.iter().for_each(&mut *f);
+            f(value);
+        }
+        StmtKind::AugAssign {
+            ref target,
+            ref value,
+            ..
+        } => {
+            f(target);
+            f(value);
+        }
+        StmtKind::AnnAssign {
+            ref target,
+            ref annotation,
+            ref value,
+            ..
+        } => {
+            f(target);
+            f(annotation);
+            value.iter().for_each(f);
+        }
+        StmtKind::For {
+            ref target,
+            ref iter,
+            ..
+        } => {
+            f(target);
+            f(iter);
+        }
+        StmtKind::While { ref test, .. } | StmtKind::If { ref test, .. } => f(test),
+        StmtKind::With { ref items, .. } => {
+            for item in items {
+                f(&item.context_expr);
+                item.optional_vars.iter().for_each(&mut *f);
+            }
+        }
+        StmtKind::Match {
+            ref subject,
+            ref cases,
+        } => {
+            f(subject);
+            cases.iter().flat_map(|case| &case.guard).for_each(f);
+        }
+        StmtKind::Try { ref handlers, .. } => handlers
+            .iter()
+            .flat_map(|handler| &handler.type_)
+            .for_each(f),
+        StmtKind::Raise { ref exc, ref cause } => {
+            exc.iter().for_each(&mut *f);
+            cause.iter().for_each(f);
+        }
+        StmtKind::Assert { ref test, ref msg } => {
+            f(test);
+            msg.iter().for_each(f);
+        }
+        StmtKind::Expr(ref value) => f(value),
+        _ => {}
+    }
+}
+
+/// The expressions directly inside `expr`, in source order.
+fn child_exprs(expr: &Expr) -> Vec<&Expr> {
+    fn generators<'a>(children: &mut Vec<&'a Expr>, generators: &'a [Comprehension]) {
+        for generator in generators {
+            children.push(&generator.target);
+            children.push(&generator.iter);
+            children.extend(&generator.ifs);
+        }
+    }
+    let mut children: Vec<&Expr> = Vec::new();
+    match expr.kind {
+        ExprKind::BoolOp { ref values, .. } => children.extend(values),
+        ExprKind::NamedExpr {
+            ref target,
+            ref value,
+        } => children.extend([&**target, &**value]),
+        ExprKind::BinOp {
+            ref left,
+            ref right,
+            ..
+        } => children.extend([&**left, &**right]),
+        ExprKind::UnaryOp { ref operand, .. } => children.push(operand),
+        ExprKind::Lambda { ref args, ref body } => {
+            children.extend(&args.defaults);
+            children.extend(args.kw_defaults.iter().flatten());
+            children.push(body);
+        }
+        ExprKind::IfExp {
+            ref test,
+            ref body,
+            ref orelse,
+        } => children.extend([&**body, &**test, &**orelse]),
+        ExprKind::Dict {
+            ref keys,
+            ref values,
+        } => {
+            for (key, value) in keys.iter().zip(values) {
+                children.extend(key);
+                children.push(value);
+            }
+        }
+        ExprKind::Set(ref elts)
+        | ExprKind::JoinedStr(ref elts)
+        | ExprKind::List { ref elts, .. }
+        | ExprKind::Tuple { ref elts, .. } => children.extend(elts),
+        ExprKind::ListComp {
+            ref elt,
+            generators: ref comprehensions,
+        }
+        | ExprKind::SetComp {
+            ref elt,
+            generators: ref comprehensions,
+        }
+        | ExprKind::GeneratorExp {
+            ref elt,
+            generators: ref comprehensions,
+        } => {
+            children.push(elt);
+            generators(&mut children, comprehensions);
+        }
+        ExprKind::DictComp {
+            ref key,
+            ref value,
+            generators: ref comprehensions,
+        } => {
+            children.extend([&**key, &**value]);
+            generators(&mut children, comprehensions);
+        }
+        ExprKind::Await(ref value)
+        | ExprKind::YieldFrom(ref value)
+        | ExprKind::Attribute { ref value, .. }
+        | ExprKind::Starred { ref value, .. } => children.push(value),
+        ExprKind::Yield(ref value) => children.extend(value.as_deref()),
+        ExprKind::Compare {
+            ref left,
+            ref comparators,
+            ..
+        } => {
+            children.push(left);
+            children.extend(comparators);
+        }
+        ExprKind::Call {
+            ref func,
+            ref args,
+            ref keywords,
+        } => {
+            children.push(func);
+            children.extend(args);
+            children.extend(keywords.iter().map(|keyword| &keyword.value));
+        }
+        ExprKind::FormattedValue {
+            ref value,
+            ref format_spec,
+            ..
+        } => {
+            children.push(value);
+            children.extend(format_spec.as_deref());
+        }
+        ExprKind::Subscript {
+            ref value,
+            ref slice,
+            ..
+        } => children.extend([&**value, &**slice]),
+        ExprKind::Slice {
+            ref lower,
+            ref upper,
+            ref step,
+        } => {
+            for part in [lower, upper, step] {
+                children.extend(part.as_deref());
+            }
+        }
+        ExprKind::Constant(_) | ExprKind::Name { .. } => {}
+    }
+    children
+}
@@ -0,0 +1,914 @@
+//! Works out which variable each name in a module refers to, and where the
+//! name is written.
+//!
+//! The compiler's symbol table only needs to know where a name lives in the
+//! scope that uses it. Refactoring also needs every occurrence of the same
+//! variable, across the scopes that share it, so names are resolved here
+//! with Python's rules: comprehensions have their own scope, class bodies
+//! are skipped by the functions in them, and `global` and `nonlocal`
+//! redirect a name to the module or an enclosing function.
+
+use std::collections::{HashMap, HashSet};
+
+use ast::{
+    Alias, Arguments, Comprehension, Context, ExceptHandler, Expr, ExprKind, MatchCase, Module,
+    Pattern, PatternKind, Stmt, StmtKind,
+};
+use tokenizer::{LineIndex, Location, Span, Token, TokenType};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScopeKind {
+    Module,
+    Class,
+    Function,
+    Comprehension,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scope {
+    pub kind: ScopeKind,
+    pub parent: Option<usize>,
+    /// The variables bound in the scope, by name.
+    pub bindings: HashMap<String, usize>,
+    globals: HashSet<String>,
+    nonlocals: HashSet<String>,
+}
+
+/// Where the value of a variable set by an import comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    /// `import a.b` binds `a` to the module `a`; `import a.b as c` binds `c`
+    /// to `a.b`.
+    Module(String),
+    /// `from module import name`, with the dots of a relative import.
+    Imported {
+        level: usize,
+        module: Option<String>,
+        name: String,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub scope: usize,
+    pub name: String,
+    pub origin: Option<Origin>,
+}
+
+/// A name written in the source.
+#[derive(Debug, Clone, Copy)]
+pub struct Occurrence {
+    pub span: Span,
+    pub binding: usize,
+    /// Whether the name is assigned, deleted or declared rather than read.
+    pub store: bool,
+    /// The scope the name is written in.
+    pub scope: usize,
+}
+
+/// The name of an attribute read from a chain of names, such as `f` in
+/// `pkg.mod.f`, which is a name from another module if `pkg` was imported.
+#[derive(Debug, Clone)]
+pub struct AttributeRef {
+    pub span: Span,
+    /// The binding of the name the chain starts with.
+    pub root: usize,
+    /// The attributes between the root and this one.
+    pub path: Vec<String>,
+    pub attr: String,
+}
+
+/// A name imported by `from module import name`, where `name` is written.
+#[derive(Debug, Clone)]
+pub struct ImportedName {
+    pub span: Span,
+    pub level: usize,
+    pub module: Option<String>,
+    pub name: String,
+}
+
+/// The names of a module and what they refer to.
+pub struct Analysis {
+    pub scopes: Vec<Scope>,
+    pub bindings: Vec<Binding>,
+    /// Occurrences in source order.
+    pub occurrences: Vec<Occurrence>,
+    pub attributes: Vec<AttributeRef>,
+    pub imports: Vec<ImportedName>,
+    /// The scope created by each function, class, lambda and comprehension,
+    /// by the location of the node that creates it.
+    pub node_scopes: HashMap<Location, usize>,
+}
+
+impl Analysis {
+    pub fn new(index: &SourceIndex, module: &Module) -> Analysis {
+        let mut resolver = Resolver {
+            index,
+            analysis: Analysis {
+                scopes: Vec::new(),
+                bindings: Vec::new(),
+                occurrences: Vec::new(),
+                attributes: Vec::new(),
+                imports: Vec::new(),
+                node_scopes: HashMap::new(),
+            },
+            pending: Vec::new(),
+            attributes: Vec::new(),
+            scope: 0,
+        };
+        resolver.enter(ScopeKind::Module, None);
+        resolver.stmts(&module.body);
+        resolver.finish()
+    }
+
+    /// The occurrence whose name covers `offset`, or ends there.
+    pub fn occurrence_at(&self, offset: usize) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|occurrence| occurrence.span.start <= offset && offset <= occurrence.span.end)
+    }
+
+    /// The occurrences of a binding.
+    pub fn occurrences_of(&self, binding: usize) -> impl Iterator<Item = &Occurrence> {
+        self.occurrences
+            .iter()
+            .filter(move |occurrence| occurrence.binding == binding)
+    }
+
+    /// The variable `name` refers to in `scope`, if it is bound anywhere the
+    /// scope can see.
+    pub fn lookup(&self, scope: usize, name: &str) -> Option<usize> {
+        let current = &self.scopes[scope];
+        if current.globals.contains(name) {
+            return self.scopes[0].bindings.get(name).cloned();
+        }
+        if !current.nonlocals.contains(name) {
+            if let Some(&binding) = current.bindings.get(name) {
+                return Some(binding);
+            }
+        }
+        let mut parent = current.parent;
+        while let Some(index) = parent {
+            let enclosing = &self.scopes[index];
+            parent = enclosing.parent;
+            if enclosing.kind == ScopeKind::Class {
+                continue;
+            }
+            if enclosing.globals.contains(name) {
+                return self.scopes[0].bindings.get(name).cloned();
+            }
+            if enclosing.nonlocals.contains(name) {
+                continue;
+            }
+            if let Some(&binding) = enclosing.bindings.get(name) {
+                return Some(binding);
+            }
+        }
+        None
+    }
+}
+
+/// Turns AST locations into byte offsets and finds the tokens of names the
+/// AST does not locate, such as the name of a `def`.
+pub struct SourceIndex<'a> {
+    pub source: &'a str,
+    tokens: Vec<(TokenType, Span, &'a str)>,
+    lines: LineIndex,
+}
+
+impl<'a> SourceIndex<'a> {
+    pub fn new(source: &'a str, tokens: &[Token]) -> SourceIndex<'a> {
+        let tokens = tokens
+            .iter()
+            .filter(|token| token.kind != TokenType::Comment)
+            .map(|token| {
+                (
+                    token.kind,
+                    token.span,
+                    &source[token.span.start..token.span.end],
+                )
+            })
+            .collect();
+        SourceIndex {
+            source,
+            tokens,
+            lines: LineIndex::new(source),
+        }
+    }
+
+    pub fn offset(&self, location: Location) -> usize {
+        self.lines.offset(self.source, location)
+    }
+
+    /// Where the line holding `offset` starts.
+    pub fn line_start(&self, offset: usize) -> usize {
+        let line = self.lines.line_at(offset);
+        self.lines.line_span(line).unwrap().start
+    }
+
+    /// The index of the first token that starts at or after `offset`.
+    fn token_at(&self, offset: usize) -> usize {
+        self.tokens.partition_point(|token| token.1.start < offset)
+    }
+
+    /// The span of a name that starts at `location`.
+    fn name_at(&self, location: Location, name: &str) -> Span {
+        let start = self.offset(location);
+        Span::new(start, start + name.len())
+    }
+
+    /// The first name token `name` at or after `offset` and before `end`.
+    fn find_name(&self, offset: usize, end: usize, name: &str) -> Option<Span> {
+        self.tokens[self.token_at(offset)..]
+            .iter()
+            .take_while(|token| token.1.start < end)
+            .find(|token| token.0 == TokenType::Name && token.2 == name)
+            .map(|token| token.1)
+    }
+
+    /// The name token right after the first `keyword` at or after `offset`.
+    fn name_after(&self, offset: usize, end: usize, keyword: &str) -> Option<Span> {
+        let tokens = &self.tokens[self.token_at(offset)..];
+        let position = tokens
+            .iter()
+            .take_while(|token| token.1.start < end)
+            .position(|token| token.2 == keyword)?;
+        tokens
+            .get(position + 1)
+            .filter(|token| token.0 == TokenType::Name)
+            .map(|token| token.1)
+    }
+
+    /// The last name token that ends at or before `offset`.
+    fn name_before(&self, offset: usize) -> Option<Span> {
+        self.tokens[..self.token_at(offset)]
+            .iter()
+            .rev()
+            .find(|token| token.0 == TokenType::Name && token.1.end <= offset)
+            .map(|token| token.1)
+    }
+}
+
+/// A name waiting to be resolved once every binding is known.
+struct Pending {
+    span: Span,
+    scope: usize,
+    name: String,
+    store: bool,
+}
+
+struct Resolver<'a> {
+    index: &'a SourceIndex<'a>,
+    analysis: Analysis,
+    pending: Vec<Pending>,
+    /// Attributes of name chains, with the position in `pending` of the
+    /// name the chain starts with.
+    attributes: Vec<(Span, usize, Vec<String>, String)>,
+    scope: usize,
+}
+
+impl<'a> Resolver<'a> {
+    fn enter(&mut self, kind: ScopeKind, node: Option<Location>) {
+        let index = self.analysis.scopes.len();
+        let parent = if index == 0 { None } else { Some(self.scope) };
+        self.analysis.scopes.push(Scope {
+            kind,
+            parent,
+            bindings: HashMap::new(),
+            globals: HashSet::new(),
+            nonlocals: HashSet::new(),
+        });
+        if let Some(location) = node {
+            self.analysis.node_scopes.insert(location, index);
+        }
+        self.scope = index;
+    }
+
+    fn leave(&mut self) {
+        self.scope = self.analysis.scopes[self.scope].parent.unwrap();
+    }
+
+    /// Binds `name` in the current scope, or where `global` or `nonlocal`
+    /// sends it, and records where it is written.
+    fn bind(&mut self, name: &str, span: Option<Span>, origin: Option<Origin>) {
+        let scope = &self.analysis.scopes[self.scope];
+        let declared = scope.globals.contains(name) || scope.nonlocals.contains(name);
+        if !declared && !scope.bindings.contains_key(name) {
+            self.new_binding(self.scope, name, origin);
+        } else if let (false, Some(origin)) = (declared, origin) {
+            let binding = self.analysis.scopes[self.scope].bindings[name];
+            self.analysis.bindings[binding].origin = Some(origin);
+        }
+        if let Some(span) = span {
+            self.pending.push(Pending {
+                span,
+                scope: self.scope,
+                name: name.to_string(),
+                store: true,
+            });
+        }
+    }
+
+    fn new_binding(&mut self, scope: usize, name: &str, origin: Option<Origin>) -> usize {
+        let binding = self.analysis.bindings.len();
+        self.analysis.bindings.push(Binding {
+            scope,
+            name: name.to_string(),
+            origin,
+        });
+        self.analysis.scopes[scope]
+            .bindings
+            .insert(name.to_string(), binding);
+        binding
+    }
+
+    fn use_name(&mut self, name: &str, span: Span) {
+        self.pending.push(Pending {
+            span,
+            scope: self.scope,
+            name: name.to_string(),
+            store: false,
+        });
+    }
+
+    fn finish(mut self) -> Analysis {
+        let mut positions = Vec::with_capacity(self.pending.len());
+        for pending in ::std::mem::take(&mut self.pending) {
+            let binding = match self.analysis.lookup(pending.scope, &pending.name) {
+                Some(binding) => binding,
+                // A builtin, a name from `import *`, or a name that is
+                // never bound: the module's, which is where assigning it
+                // would put it.
+                None => self.new_binding(0, &pending.name, None),
+            };
+            positions.push(self.analysis.occurrences.len());
+            self.analysis.occurrences.push(Occurrence {
+                span: pending.span,
+                binding,
+                store: pending.store,
+                scope: pending.scope,
+            });
+        }
+        for (span, root, path, attr) in ::std::mem::take(&mut self.attributes) {
+            let root = self.analysis.occurrences[positions[root]].binding;
+            self.analysis.attributes.push(AttributeRef {
+                span,
+                root,
+                path,
+                attr,
+            });
+        }
+        self.analysis
+            .occurrences
+            .sort_by_key(|occurrence| occurrence.span.start);
+        self.analysis
+    }
+
+    fn stmts(&mut self, body: &[Stmt]) {
+        for stmt in body {
+            self.stmt(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let start = self.index.offset(stmt.start);
+        let end = self.index.offset(stmt.end);
+        match stmt.kind {
+            StmtKind::FunctionDef {
+                ref name,
+                ref args,
+                ref body,
+                ref decorator_list,
+                ref returns,
+                ..
+            } => {
+                self.exprs(decorator_list);
+                self.argument_defaults(args);
+                self.argument_annotations(args);
+                if let Some(ref returns) = *returns {
+                    self.expr(returns);
+                }
+                let span = self.index.name_after(start, end, "def");
+                self.bind(name, span, None);
+                self.enter(ScopeKind::Function, Some(stmt.start));
+                self.parameters(args);
+                self.stmts(body);
+                self.leave();
+            }
+            StmtKind::ClassDef {
+                ref name,
+                ref bases,
+                ref keywords,
+                ref body,
+                ref decorator_list,
+                ..
+            } => {
+                self.exprs(decorator_list);
+                self.exprs(bases);
+                for keyword in keywords {
+                    self.expr(&keyword.value);
+                }
+                let span = self.index.name_after(start, end, "class");
+                self.bind(name, span, None);
+                self.enter(ScopeKind::Class, Some(stmt.start));
+                self.stmts(body);
+                self.leave();
+            }
+            StmtKind::Return(ref value) => self.optional_expr(value),
+            StmtKind::Delete(ref targets) => self.exprs(targets),
+            StmtKind::Assign {
+                ref targets,
+                ref value,
+                ..
+            } => {
+                self.expr(value);
+                self.exprs(targets);
+            }
+            StmtKind::TypeAlias {
+                ref name,
+                ref value,
+                ..
+            } => {
+                self.expr(value);
+                self.expr(name);
+            }
+            StmtKind::AugAssign {
+                ref target,
+                ref value,
+                ..
+            } => {
+                self.expr(value);
+                if let ExprKind::Name { ref id, .. } = target.kind {
+                    let span = self.index.name_at(target.start, id);
+                    self.use_name(id, span);
+                }
+                self.expr(target);
+            }
+            StmtKind::AnnAssign {
+                ref target,
+                ref annotation,
+                ref value,
+                ..
+            } => {
+                self.expr(annotation);
+                self.optional_expr(value);
+                self.expr(target);
+            }
+            StmtKind::For {
+                ref target,
+                ref iter,
+                ref body,
+                ref orelse,
+                ..
+            } => {
+                self.expr(iter);
+                self.expr(target);
+                self.stmts(body);
+                self.stmts(orelse);
+            }
+            StmtKind::While {
+                ref test,
+                ref body,
+                ref orelse,
+            }
+            | StmtKind::If {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                self.expr(test);
+                self.stmts(body);
+                self.stmts(orelse);
+            }
+            StmtKind::With {
+                ref items,
+                ref body,
+                ..
+            } => {
+                for item in items {
+                    self.expr(&item.context_expr);
+                    self.optional_expr(&item.optional_vars);
+                }
+                self.stmts(body);
+            }
+            StmtKind::Match {
+                ref subject,
+                ref cases,
+            } => {
+                self.expr(subject);
+                for case in cases {
+                    self.match_case(case);
+                }
+            }
+            StmtKind::Raise { ref exc, ref cause } => {
+                self.optional_expr(exc);
+                self.optional_expr(cause);
+            }
+            StmtKind::Try {
+                ref body,
+                ref handlers,
+                ref orelse,
+                ref finalbody,
+                ..
+            } => {
+                self.stmts(body);
+                for handler in handlers {
+                    self.handler(handler);
+                }
+                self.stmts(orelse);
+                self.stmts(finalbody);
+            }
+            StmtKind::Assert { ref test, ref msg } => {
+                self.expr(test);
+                self.optional_expr(msg);
+            }
+            StmtKind::Import(ref names) => {
+                for alias in names {
+                    self.import(alias);
+                }
+            }
+            StmtKind::ImportFrom {
+                ref module,
+                ref names,
+                level,
+            } => {
+                for alias in names {
+                    if alias.name != "*" {
+                        self.import_from(alias, level, module);
+                    }
+                }
+            }
+            StmtKind::Global(ref names) | StmtKind::Nonlocal(ref names) => {
+                let global = matches!(stmt.kind, StmtKind::Global(_));
+                for name in names {
+                    let scope = &mut self.analysis.scopes[self.scope];
+                    if global {
+                        scope.globals.insert(name.clone());
+                    } else {
+                        scope.nonlocals.insert(name.clone());
+                    }
+                    if let Some(span) = self.index.find_name(start, end, name) {
+                        self.bind(name, Some(span), None);
+                    }
+                }
+            }
+            StmtKind::Expr(ref value) => self.expr(value),
+            StmtKind::Pass | StmtKind::Break | StmtKind::Continue | StmtKind::Error => {}
+        }
+    }
+
+    fn import(&mut self, alias: &Alias) {
+        let start = self.index.offset(alias.start);
+        let end = self.index.offset(alias.end);
+        match alias.asname {
+            Some(ref asname) => {
+                let span = self.index.name_after(start, end, "as");
+                self.bind(asname, span, Some(Origin::Module(alias.name.clone())));
+            }
+            None => {
+                let name = alias.name.split('.').next().unwrap();
+                let span = self.index.name_at(alias.start, name);
+                self.bind(name, Some(span), Some(Origin::Module(name.to_string())));
+            }
+        }
+    }
+
+    fn import_from(&mut self, alias: &Alias, level: usize, module: &Option<String>) {
+        let span = self.index.name_at(alias.start, &alias.name);
+        self.analysis.imports.push(ImportedName {
+            span,
+            level,
+            module: module.clone(),
+            name: alias.name.clone(),
+        });
+        let origin = Origin::Imported {
+            level,
+            module: module.clone(),
+            name: alias.name.clone(),
+        };
+        match alias.asname {
+            Some(ref asname) => {
+                let start = self.index.offset(alias.start);
+                let end = self.index.offset(alias.end);
+                let span = self.index.name_after(start, end, "as");
+                self.bind(asname, span, Some(origin));
+            }
+            // The imported name is also the name of the new variable, which
+            // is renamed with it.
+            None => self.bind(&alias.name, Some(span), Some(origin)),
+        }
+    }
+
+    fn handler(&mut self, handler: &ExceptHandler) {
+        self.optional_expr(&handler.type_);
+        if let Some(ref name) = handler.name {
+            let start = self.index.offset(handler.start);
+            let end = self.index.offset(handler.end);
+            let span = self.index.name_after(start, end, "as");
+            self.bind(name, span, None);
+        }
+        self.stmts(&handler.body);
+    }
+
+    fn match_case(&mut self, case: &MatchCase) {
+        self.pattern(&case.pattern);
+        self.optional_expr(&case.guard);
+        self.stmts(&case.body);
+    }
+
+    fn pattern(&mut self, pattern: &Pattern) {
+        let end = self.index.offset(pattern.end);
+        match pattern.kind {
+            PatternKind::MatchValue(ref value) => self.expr(value),
+            PatternKind::MatchSingleton(_) => {}
+            PatternKind::MatchSequence(ref patterns) | PatternKind::MatchOr(ref patterns) => {
+                for pattern in patterns {
+                    self.pattern(pattern);
+                }
+            }
+            PatternKind::MatchMapping {
+                ref keys,
+                ref patterns,
+                ref rest,
+            } => {
+                self.exprs(keys);
+                for pattern in patterns {
+                    self.pattern(pattern);
+                }
+                if let Some(ref rest) = *rest {
+                    let span = self.index.name_before(end);
+                    self.bind(rest, span, None);
+                }
+            }
+            PatternKind::MatchClass {
+                ref cls,
+                ref patterns,
+                ref kwd_patterns,
+                ..
+            } => {
+                self.expr(cls);
+                for pattern in patterns.iter().chain(kwd_patterns) {
+                    self.pattern(pattern);
+                }
+            }
+            PatternKind::MatchStar(ref name) => {
+                if let Some(ref name) = *name {
+                    let span = self.index.name_before(end);
+                    self.bind(name, span, None);
+                }
+            }
+            PatternKind::MatchAs {
+                ref pattern,
+                ref name,
+            } => {
+                if let Some(ref pattern) = *pattern {
+                    self.pattern(pattern);
+                }
+                if let Some(ref name) = *name {
+                    let span = self.index.name_before(end);
+                    self.bind(name, span, None);
+                }
+            }
+        }
+    }
+
+    fn argument_defaults(&mut self, args: &Arguments) {
+        self.exprs(&args.defaults);
+        for default in &args.kw_defaults {
+            self.optional_expr(default);
+        }
+    }
+
+    fn argument_annotations(&mut self, args: &Arguments) {
+        let all = args
+            .posonlyargs
+            .iter()
+            .chain(&args.args)
+            .chain(&args.vararg)
+            .chain(&args.kwonlyargs)
+            .chain(&args.kwarg);
+        for arg in all {
+            if let Some(ref annotation) = arg.annotation {
+                self.expr(annotation);
+            }
+        }
+    }
+
+    fn parameters(&mut self, args: &Arguments) {
+        let all = args
+            .posonlyargs
+            .iter()
+            .chain(&args.args)
+            .chain(&args.vararg)
+            .chain(&args.kwonlyargs)
+            .chain(&args.kwarg);
+        for arg in all {
+            let span = self.index.name_at(arg.start, &arg.arg);
+            self.bind(&arg.arg, Some(span), None);
+        }
+    }
+
+    fn exprs(&mut self, exprs: &[Expr]) {
+        for expr in exprs {
+            self.expr(expr);
+        }
+    }
+
+    fn optional_expr(&mut self, expr: &Option<Expr>) {
+        if let Some(ref expr) = *expr {
+            self.expr(expr);
+        }
+    }
+
+    fn boxed_expr(&mut self, expr: &Option<Box<Expr>>) {
+        if let Some(ref expr) = *expr {
+            self.expr(expr);
+        }
+    }
+
+    /// A comprehension: the first iterable is evaluated outside, the rest in
+    /// a scope of its own.
+    fn comprehension(&mut self, expr: &Expr, generators: &[Comprehension], elts: &[&Expr]) {
+        self.expr(&generators[0].iter);
+        self.enter(ScopeKind::Comprehension, Some(expr.start));
+        for (position, generator) in generators.iter().enumerate() {
+            if position > 0 {
+                self.expr(&generator.iter);
+            }
+            self.expr(&generator.target);
+            self.exprs(&generator.ifs);
+        }
+        for elt in elts {
+            self.expr(elt);
+        }
+        self.leave();
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr.kind {
+            ExprKind::BoolOp { ref values, .. } => self.exprs(values),
+            ExprKind::NamedExpr {
+                ref target,
+                ref value,
+            } => {
+                self.expr(value);
+                // An assignment expression in a comprehension binds in the
+                // scope around it.
+                let scope = self.scope;
+                while self.analysis.scopes[self.scope].kind == ScopeKind::Comprehension {
+                    self.leave();
+                }
+                if let ExprKind::Name { ref id, .. } = target.kind {
+                    self.bind(id, None, None);
+                    self.scope = scope;
+                    self.pending.push(Pending {
+                        span: self.index.name_at(target.start, id),
+                        scope,
+                        name: id.clone(),
+                        store: true,
+                    });
+                }
+                self.scope = scope;
+            }
+            ExprKind::BinOp {
+                ref left,
+                ref right,
+                ..
+            } => {
+                self.expr(left);
+                self.expr(right);
+            }
+            ExprKind::UnaryOp { ref operand, .. } => self.expr(operand),
+            ExprKind::Lambda { ref args, ref body } => {
+                self.argument_defaults(args);
+                self.enter(ScopeKind::Function, Some(expr.start));
+                self.parameters(args);
+                self.expr(body);
+                self.leave();
+            }
+            ExprKind::IfExp {
+                ref test,
+                ref body,
+                ref orelse,
+            } => {
+                self.expr(test);
+                self.expr(body);
+                self.expr(orelse);
+            }
+            ExprKind::Dict {
+                ref keys,
+                ref values,
+            } => {
+                for (key, value) in keys.iter().zip(values) {
+                    self.optional_expr(key);
+                    self.expr(value);
+                }
+            }
+            ExprKind::Set(ref elts) | ExprKind::JoinedStr(ref elts) => self.exprs(elts),
+            ExprKind::ListComp {
+                ref elt,
+                ref generators,
+            }
+            | ExprKind::SetComp {
+                ref elt,
+                ref generators,
+            }
+            | ExprKind::GeneratorExp {
+                ref elt,
+                ref generators,
+            } => self.comprehension(expr, generators, &[elt]),
+            ExprKind::DictComp {
+                ref key,
+                ref value,
+                ref generators,
+            } => self.comprehension(expr, generators, &[key, value]),
+            ExprKind::Await(ref value) | ExprKind::YieldFrom(ref value) => self.expr(value),
+            ExprKind::Yield(ref value) => self.boxed_expr(value),
+            ExprKind::Compare {
+                ref left,
+                ref comparators,
+                ..
+            } => {
+                self.expr(left);
+                self.exprs(comparators);
+            }
+            ExprKind::Call {
+                ref func,
+                ref args,
+                ref keywords,
+            } => {
+                self.expr(func);
+                self.exprs(args);
+                for keyword in keywords {
+                    self.expr(&keyword.value);
+                }
+            }
+            ExprKind::FormattedValue {
+                ref value,
+                ref format_spec,
+                ..
+            } => {
+                self.expr(value);
+                self.boxed_expr(format_spec);
+            }
+            ExprKind::Constant(_) => {}
+            ExprKind::Attribute {
+                ref value,
+                ref attr,
+                ..
+            } => {
+                let first = self.pending.len();
+                self.expr(value);
+                if let Some(path) = name_chain(value) {
+                    let end = self.index.offset(expr.end);
+                    let span = Span::new(end - attr.len(), end);
+                    self.attributes.push((span, first, path, attr.clone()));
+                }
+            }
+            ExprKind::Subscript {
+                ref value,
+                ref slice,
+                ..
+            } => {
+                self.expr(value);
+                self.expr(slice);
+            }
+            ExprKind::Starred { ref value, .. } => self.expr(value),
+            ExprKind::Name { ref id, ctx } => {
+                let span = self.index.name_at(expr.start, id);
+                match ctx {
+                    Context::Load => self.use_name(id, span),
+                    Context::Store | Context::Del => self.bind(id, Some(span), None),
+                }
+            }
+            ExprKind::List { ref elts, .. } | ExprKind::Tuple { ref elts, .. } => self.exprs(elts),
+            ExprKind::Slice {
+                ref lower,
+                ref upper,
+                ref step,
+            } => {
+                self.boxed_expr(lower);
+                self.boxed_expr(upper);
+                self.boxed_expr(step);
+            }
+        }
+    }
+}
+
+/// The attributes after the first name of a chain of names, such as
+/// `["b"]` for `a.b`, or `None` if `expr` is not such a chain.
+fn name_chain(expr: &Expr) -> Option<Vec<String>> {
+    match expr.kind {
+        ExprKind::Name { .. } => Some(Vec::new()),
+        ExprKind::Attribute {
+            ref value,
+            ref attr,
+            ..
+        } => {
+            let mut path = name_chain(value)?;
+            path.push(attr.clone());
+            Some(path)
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,168 @@
+//! A cache of token streams keyed by a hash of the source they were read
+//! from, so that a tool scanning a project again tokenizes only the files
+//! that changed since the last scan.
+//!
+//! The cache is saved as JSON, with the version of rustpy that made it; a
+//! cache saved by another version is ignored when loaded, since that
+//! version may tokenize differently. Sources that do not tokenize are not
+//! cached, so their errors are reported again each time.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use trace;
+
+use super::{decode, tokenize, SourceError, Token, TokenError};
+
+/// The version of rustpy, which a saved cache must have been made by.
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// The 64-bit FNV-1a hash of `source`, which a `TokenCache` is keyed by.
+/// It is the same in every process and on every platform.
+pub fn content_hash(source: &str) -> u64 {
+    source.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Entry {
+    /// The length of the source, which a source of the same hash must also
+    /// have.
+    len: usize,
+    tokens: Vec<Token>,
+}
+
+/// Token streams by the hash of their source.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenCache {
+    version: String,
+    entries: HashMap<u64, Entry>,
+    /// The entries looked up or added since the cache was made or loaded.
+    #[serde(skip)]
+    used: HashSet<u64>,
+    #[serde(skip)]
+    hits: usize,
+    #[serde(skip)]
+    misses: usize,
+}
+
+impl Default for TokenCache {
+    fn default() -> TokenCache {
+        TokenCache::new()
+    }
+}
+
+impl TokenCache {
+    pub fn new() -> TokenCache {
+        TokenCache {
+            version: VERSION.to_string(),
+            entries: HashMap::new(),
+            used: HashSet::new(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Loads a cache saved by `save`. A missing file, or one saved by
+    /// another version of rustpy, gives an empty cache.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<TokenCache> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(TokenCache::new()),
+            Err(err) => return Err(err),
+        };
+        let cache: TokenCache = serde_json::from_str(&text)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if cache.version != VERSION {
+            return Ok(TokenCache::new());
+        }
+        Ok(cache)
+    }
+
+    /// Saves the cache as JSON.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let text = serde_json::to_string(self)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        fs::write(path, text)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The number of sources found in the cache, and the number tokenized,
+    /// since it was made or loaded.
+    pub fn stats(&self) -> (usize, usize) {
+        (self.hits, self.misses)
+    }
+
+    /// The tokens of `source`, if they are cached.
+    pub fn get(&mut self, source: &str) -> Option<&[Token]> {
+        let hash = content_hash(source);
+        match self.entries.get(&hash) {
+            Some(entry) if entry.len == source.len() => {
+                self.used.insert(hash);
+                Some(&entry.tokens)
+            }
+            _ => None,
+        }
+    }
+
+    /// Caches the tokens of `source`.
+    pub fn insert(&mut self, source: &str, tokens: Vec<Token>) {
+        let hash = content_hash(source);
+        self.used.insert(hash);
+        let entry = Entry {
+            len: source.len(),
+            tokens,
+        };
+        self.entries.insert(hash, entry);
+    }
+
+    /// Tokenizes `source` as `tokenizer::tokenize` does, unless its tokens
+    /// are cached.
+    pub fn tokenize(&mut self, source: &str) -> Result<Vec<Token>, TokenError> {
+        if let Some(tokens) = self.get(source) {
+            let tokens = tokens.to_vec();
+            self.hits += 1;
+            return Ok(tokens);
+        }
+        self.misses += 1;
+        let tokens = tokenize(source)?;
+        self.insert(source, tokens.clone());
+        Ok(tokens)
+    }
+
+    /// Reads, decodes and tokenizes a source file as
+    /// `tokenizer::tokenize_file` does, unless the tokens of its text are
+    /// cached.
+    pub fn tokenize_file<P: AsRef<Path>>(&mut self, path: P) -> Result<Vec<Token>, SourceError> {
+        let _span = trace::file_span(path.as_ref());
+        let bytes = fs::read(path)?;
+        let decoded = decode(&bytes)?;
+        Ok(self.tokenize(&decoded.text)?)
+    }
+
+    /// Drops the entries not looked up or added since the cache was made or
+    /// loaded, those of files that have changed or gone, so that a cache
+    /// saved after each scan holds only what the next scan may use.
+    pub fn prune(&mut self) {
+        let used = &self.used;
+        self.entries.retain(|hash, _| used.contains(hash));
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.used.clear();
+    }
+}
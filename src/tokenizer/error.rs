@@ -0,0 +1,106 @@
+use std::error::Error;
+use std::fmt;
+
+use super::token::Location;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TokenErrorKind {
+    UnterminatedString,
+    UnterminatedTripleQuotedString,
+    EofInMultiLineStatement,
+    UnmatchedBracket,
+    MismatchedBracket,
+    InvalidCharacter,
+    InvalidNumber,
+    InvalidLineContinuation,
+    InconsistentDedent,
+    /// Indentation that is deeper or shallower than the line before it
+    /// with tabs at one column width but not at another.
+    InconsistentTabs,
+    IncompleteInput,
+    /// A character that is not ASCII in a bytes literal.
+    NonAsciiBytes,
+    /// More levels of indentation than `TokenizerConfig::max_indent`.
+    TooDeeplyIndented,
+    /// Brackets nested deeper than `TokenizerConfig::max_nesting`.
+    TooDeeplyNested,
+    /// The tokenizer's `CancellationToken` was cancelled. CPython has no
+    /// such error; `KeyboardInterrupt` is the nearest.
+    Cancelled,
+}
+
+impl TokenErrorKind {
+    /// The Python exception type CPython raises for this error.
+    pub fn exception_name(self) -> &'static str {
+        match self {
+            TokenErrorKind::InconsistentDedent | TokenErrorKind::TooDeeplyIndented => {
+                "IndentationError"
+            }
+            TokenErrorKind::InconsistentTabs => "TabError",
+            TokenErrorKind::Cancelled => "KeyboardInterrupt",
+            _ => "SyntaxError",
+        }
+    }
+
+    /// A stable diagnostic code for this error.
+    pub fn code(self) -> &'static str {
+        match self {
+            TokenErrorKind::InconsistentTabs => "E001",
+            TokenErrorKind::InconsistentDedent => "E002",
+            TokenErrorKind::UnterminatedString => "E101",
+            TokenErrorKind::UnterminatedTripleQuotedString => "E102",
+            TokenErrorKind::EofInMultiLineStatement => "E103",
+            TokenErrorKind::UnmatchedBracket => "E104",
+            TokenErrorKind::MismatchedBracket => "E105",
+            TokenErrorKind::InvalidCharacter => "E106",
+            TokenErrorKind::InvalidNumber => "E107",
+            TokenErrorKind::InvalidLineContinuation => "E108",
+            TokenErrorKind::IncompleteInput => "E109",
+            TokenErrorKind::Cancelled => "E110",
+            TokenErrorKind::NonAsciiBytes => "E111",
+            TokenErrorKind::TooDeeplyIndented => "E112",
+            TokenErrorKind::TooDeeplyNested => "E113",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenError {
+    pub kind: TokenErrorKind,
+    pub message: String,
+    pub location: Location,
+    /// For an `EofInMultiLineStatement` at a bracket left open, the
+    /// innermost such bracket and where it opened, which CPython's compiler
+    /// reports where `tokenize` reports the end of the input.
+    pub unclosed: Option<(char, Location)>,
+}
+
+impl TokenError {
+    pub fn new<S: Into<String>>(
+        kind: TokenErrorKind,
+        message: S,
+        location: Location,
+    ) -> TokenError {
+        TokenError {
+            kind,
+            message: message.into(),
+            location,
+            unclosed: None,
+        }
+    }
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} (line {}, column {})",
+            self.kind.exception_name(),
+            self.message,
+            self.location.line,
+            self.location.column
+        )
+    }
+}
+
+impl Error for TokenError {}
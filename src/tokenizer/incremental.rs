@@ -0,0 +1,176 @@
+//! Re-lexing a source after an edit. Tokenizing starts again at the line
+//! the edit starts on and stops at the first newline after it where the
+//! tokenizer is back in the state it was in before the edit, so an editor
+//! re-lexes a few lines rather than the whole file on each keystroke.
+
+use std::ops::Range;
+
+use super::{
+    indentation_columns, last_line_end, tokenize_with_config, Span, Token, TokenError, TokenType,
+    Tokenizer, TokenizerConfig,
+};
+
+/// A change to a source: the bytes in `range` replaced by `text`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// Byte offsets into the source before the edit.
+    pub range: Span,
+    pub text: String,
+}
+
+impl TextEdit {
+    pub fn new<S: Into<String>>(range: Span, text: S) -> TextEdit {
+        TextEdit {
+            range,
+            text: text.into(),
+        }
+    }
+
+    /// `source` with the edit made. Panics if the range is not within
+    /// `source` or does not fall on character boundaries.
+    pub fn apply(&self, source: &str) -> String {
+        let mut edited = String::with_capacity(source.len() + self.text.len());
+        edited.push_str(&source[..self.range.start]);
+        edited.push_str(&self.text);
+        edited.push_str(&source[self.range.end..]);
+        edited
+    }
+}
+
+/// Updates `tokens`, read from a source before `edit`, to those of
+/// `source`, the source after it, as `tokenize` would give them. Only the
+/// lines from the one the edit starts on to the first newline after it
+/// where the tokenizer's state is as before are tokenized again; the tokens
+/// after that are moved to where they now are. Returns the range of
+/// `tokens` that was tokenized again. On an error, `tokens` are left as
+/// they were.
+pub fn relex(
+    tokens: &mut Vec<Token>,
+    source: &str,
+    edit: &TextEdit,
+) -> Result<Range<usize>, TokenError> {
+    relex_with_config(tokens, source, edit, TokenizerConfig::default())
+}
+
+/// Updates `tokens` as `relex` does, with the options in `config`, which
+/// must be those the tokens were read with. Interactive input is tokenized
+/// again from the start.
+pub fn relex_with_config(
+    tokens: &mut Vec<Token>,
+    source: &str,
+    edit: &TextEdit,
+    config: TokenizerConfig,
+) -> Result<Range<usize>, TokenError> {
+    let _span = stage_span!(
+        "relex",
+        bytes = source.len(),
+        tokens = ::tracing::field::Empty
+    );
+    if config.interactive {
+        *tokens = tokenize_with_config(source, config)?;
+        return Ok(0..tokens.len());
+    }
+
+    // After a newline the tokenizer is at the start of a line and outside
+    // any brackets, so all it needs to carry on from there is the
+    // indentation of the blocks open.
+    let before = tokens.partition_point(|token| token.span.end <= edit.range.start);
+    let restart = last_line_end(&tokens[..before]).map_or(0, |index| index + 1);
+    let tab_size = config.tab_size();
+    let mut indents = vec![(0, 0)];
+    for token in &tokens[..restart] {
+        track_indentation(&mut indents, token, tab_size);
+    }
+    let mut tokenizer = match restart {
+        0 => Tokenizer::with_config(source, config),
+        _ => {
+            let newline = &tokens[restart - 1];
+            let line = newline.start.line + 1;
+            Tokenizer::resume(source, config, newline.span.end, line, indents.clone())
+        }
+    };
+
+    // The tokens read again, until one is a newline after the edit where
+    // the old tokens had the same newline with the same blocks open.
+    let delta = edit.text.len() as isize - edit.range.len() as isize;
+    let edit_end = edit.range.start + edit.text.len();
+    let mut fresh = Vec::new();
+    let mut old = restart;
+    let mut old_indents = indents;
+    // The brackets open in the new and old tokens, both of which start
+    // outside any.
+    let mut depth = 0;
+    let mut old_depth = 0;
+    let mut rejoined = None;
+    while let Some(token) = tokenizer.next() {
+        let token = token?;
+        depth += token.bracket_depth_change();
+        let candidate = ends_line(&token) && depth == 0 && token.span.start >= edit_end;
+        fresh.push(token);
+        if !candidate {
+            continue;
+        }
+        let token = fresh.last().unwrap();
+        let start = (token.span.start as isize - delta) as usize;
+        while tokens.get(old).is_some_and(|old| old.span.start < start) {
+            track_indentation(&mut old_indents, &tokens[old], tab_size);
+            old_depth += tokens[old].bracket_depth_change();
+            old += 1;
+        }
+        match tokens.get(old) {
+            Some(same)
+                if ends_line(same)
+                    && old_depth == 0
+                    && same.span.start == start
+                    && same.value == token.value
+                    && old_indents == tokenizer.indents =>
+            {
+                let lines = token.start.line as isize - same.start.line as isize;
+                rejoined = Some((old, lines));
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    record!(_span, "tokens", fresh.len());
+    let relexed = restart..restart + fresh.len();
+    match rejoined {
+        Some((old, lines)) => {
+            tokens.splice(restart..old + 1, fresh);
+            for token in &mut tokens[relexed.end..] {
+                token.span =
+                    Span::new(shift(token.span.start, delta), shift(token.span.end, delta));
+                token.start.line = shift(token.start.line, lines);
+                token.end.line = shift(token.end.line, lines);
+            }
+        }
+        None => {
+            tokens.splice(restart.., fresh);
+        }
+    }
+    Ok(relexed)
+}
+
+/// Whether `token` is the newline at the end of a line, rather than the
+/// one added at the end of the source. Outside brackets, the tokenizer can
+/// start again after it.
+fn ends_line(token: &Token) -> bool {
+    token.kind.is_newline() && !token.value.is_empty()
+}
+
+/// Opens or closes a block on the stack of indentation columns for an
+/// `Indent` or `Dedent` token, measured as the tokenizer measures them.
+fn track_indentation(indents: &mut Vec<(usize, usize)>, token: &Token, tab_size: usize) {
+    match token.kind {
+        TokenType::Indent => indents.push(indentation_columns(&token.value, tab_size)),
+        TokenType::Dedent => {
+            indents.pop();
+        }
+        _ => {}
+    }
+}
+
+fn shift(value: usize, delta: isize) -> usize {
+    (value as isize + delta) as usize
+}
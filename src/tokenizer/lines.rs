@@ -0,0 +1,103 @@
+//! The physical lines of a source, for turning byte offsets into the
+//! locations of tokens, nodes and errors and back, and for finding the
+//! text of a line, without scanning the source again each time.
+
+use super::{Location, Span};
+
+/// Where each physical line of a source is. Lines end at `\n`, `\r\n` or
+/// `\r`, as the tokenizer counts them, so a line is found under the line
+/// number of a `Location` in it; after a final line break there is one
+/// more, empty line, on which the tokens at the end of the source are.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    /// The span of each line, without its line break.
+    lines: Vec<Span>,
+}
+
+impl LineIndex {
+    pub fn new(source: &str) -> LineIndex {
+        LineIndex::from_starts(source, vec![0])
+    }
+
+    /// The index of `source` given the starts of its first lines, in
+    /// order, finding those of the lines after the last by scanning.
+    pub(super) fn from_starts(source: &str, mut starts: Vec<usize>) -> LineIndex {
+        let bytes = source.as_bytes();
+        let mut index = starts.last().map_or(0, |&start| start);
+        if starts.is_empty() {
+            starts.push(0);
+        }
+        while index < bytes.len() {
+            match bytes[index] {
+                b'\r' if bytes.get(index + 1) == Some(&b'\n') => {
+                    index += 2;
+                    starts.push(index);
+                }
+                b'\n' | b'\r' => {
+                    index += 1;
+                    starts.push(index);
+                }
+                _ => index += 1,
+            }
+        }
+        let mut lines = Vec::with_capacity(starts.len());
+        for (line, &start) in starts.iter().enumerate() {
+            let end = match starts.get(line + 1) {
+                Some(&next) if bytes[..next].ends_with(b"\r\n") => next - 2,
+                Some(&next) => next - 1,
+                None => bytes.len(),
+            };
+            lines.push(Span::new(start, end));
+        }
+        LineIndex { lines }
+    }
+
+    /// How many lines there are, counting the empty one after a final line
+    /// break.
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// The span of line `line`, counted from 1, without its line break.
+    pub fn line_span(&self, line: usize) -> Option<Span> {
+        self.lines.get(line.checked_sub(1)?).copied()
+    }
+
+    /// The text of line `line` of `source`, without its line break.
+    pub fn line_text<'s>(&self, source: &'s str, line: usize) -> Option<&'s str> {
+        let span = self.line_span(line)?;
+        source.get(span.start..span.end)
+    }
+
+    /// The line, counted from 1, that the byte `offset` is in, its line
+    /// break included. An offset past the end is on the last line.
+    pub fn line_at(&self, offset: usize) -> usize {
+        self.lines
+            .partition_point(|span| span.start <= offset)
+            .max(1)
+    }
+
+    /// The location of the byte `offset` of `source`.
+    pub fn location(&self, source: &str, offset: usize) -> Location {
+        let line = self.line_at(offset);
+        let start = self.lines[line - 1].start;
+        let column = source
+            .get(start..offset.min(source.len()))
+            .map_or(0, |text| text.chars().count());
+        Location::new(line, column)
+    }
+
+    /// The byte offset in `source` of `location`. A column past the end
+    /// of its line is at the line's end, and a line past the last at the
+    /// end of the source.
+    pub fn offset(&self, source: &str, location: Location) -> usize {
+        let span = match self.line_span(location.line.max(1)) {
+            Some(span) => span,
+            None => return source.len(),
+        };
+        source[span.start..span.end]
+            .char_indices()
+            .nth(location.column)
+            .map_or(span.end, |(index, _)| span.start + index)
+    }
+}
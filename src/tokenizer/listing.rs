@@ -0,0 +1,36 @@
+//! Tokens listed as CPython's `python -m tokenize` prints them, so that the
+//! two can be diffed.
+
+use ast::repr_str;
+
+use super::Token;
+
+/// `tokens`, one per line, as `python -m tokenize` lists them: the range,
+/// the type and the repr of the text, in columns 20 and 15 characters wide.
+/// `exact` gives operators their exact type, as `-e` does. A source read
+/// from a file starts with an `ENCODING` token for its `encoding`.
+pub fn tokenize_listing(encoding: Option<&str>, tokens: &[Token], exact: bool) -> String {
+    let mut out = String::new();
+    if let Some(encoding) = encoding {
+        line(&mut out, "0,0-0,0:", "ENCODING", encoding);
+    }
+    for token in tokens {
+        let range = format!("{}-{}:", token.start, token.end);
+        let kind = if exact {
+            token.exact_type()
+        } else {
+            token.kind.name()
+        };
+        line(&mut out, &range, kind, &token.value);
+    }
+    out
+}
+
+fn line(out: &mut String, range: &str, kind: &str, text: &str) {
+    out.push_str(&format!(
+        "{:<20}{:<15}{:<15}\n",
+        range,
+        kind,
+        repr_str(text)
+    ));
+}
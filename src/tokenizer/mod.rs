@@ -0,0 +1,975 @@
+#[cfg(feature = "serde")]
+mod cache;
+mod cancel;
+mod error;
+mod incremental;
+mod lines;
+mod listing;
+mod recover;
+mod source;
+mod token;
+mod version;
+
+#[cfg(feature = "serde")]
+pub use self::cache::{content_hash, TokenCache};
+pub use self::cancel::CancellationToken;
+pub use self::error::{TokenError, TokenErrorKind};
+pub use self::incremental::{relex, relex_with_config, TextEdit};
+pub use self::lines::LineIndex;
+pub use self::listing::tokenize_listing;
+pub use self::recover::{tokenize_recovering, SkippedLines};
+pub use self::source::{
+    decode, detect_encoding, shebang, strip_bom, DecodedSource, SourceError, BOM,
+};
+pub use self::token::{exact_type_name, Location, Span, StringPrefix, Token, TokenType};
+pub use self::version::PythonVersion;
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+use ast::is_printable;
+use trace;
+
+const THREE_CHAR_OPERATORS: &[&str] = &["**=", "//=", ">>=", "<<=", "..."];
+const TWO_CHAR_OPERATORS: &[&str] = &[
+    "**", "//", ">>", "<<", "<=", ">=", "==", "!=", "->", "+=", "-=", "*=", "/=", "%=", "&=", "|=",
+    "^=", "@=", ":=",
+];
+const ONE_CHAR_OPERATORS: &str = "+-*/%@&|^~<>()[]{},:;.=";
+
+/// The most levels of indentation, counting the top level, and of nested
+/// brackets, as in CPython.
+pub(crate) const MAX_INDENT: usize = 100;
+pub(crate) const MAX_NESTING: usize = 200;
+
+/// The columns a tab advances indentation to the next multiple of, as in
+/// CPython.
+pub(crate) const TAB_SIZE: usize = 8;
+
+/// Options controlling how source is tokenized.
+#[derive(Debug, Clone, Default)]
+pub struct TokenizerConfig {
+    /// Report input that could be completed by further lines as
+    /// `TokenErrorKind::IncompleteInput` instead of a syntax error, the way
+    /// `codeop.compile_command` does for an interactive prompt. An indented
+    /// block is only considered finished once it is followed by a blank line.
+    pub interactive: bool,
+    /// Stop with a `Cancelled` error once this token is cancelled.
+    pub cancel: Option<CancellationToken>,
+    /// The version whose tokens to produce.
+    pub version: PythonVersion,
+    /// The columns a tab in indentation advances to the next multiple of,
+    /// or `None` for CPython's 8.
+    pub tab_size: Option<usize>,
+    /// Fail with `TooDeeplyIndented` past this many levels of indentation,
+    /// counting the top level, or `None` for CPython's 100. It limits what
+    /// a service tokenizing untrusted input spends on a pathological file.
+    pub max_indent: Option<usize>,
+    /// Fail with `TooDeeplyNested` once brackets are nested deeper than
+    /// this, or `None` for CPython's 200.
+    pub max_nesting: Option<usize>,
+}
+
+impl TokenizerConfig {
+    pub(crate) fn tab_size(&self) -> usize {
+        self.tab_size.unwrap_or(TAB_SIZE).max(1)
+    }
+
+    fn max_indent(&self) -> usize {
+        self.max_indent.unwrap_or(MAX_INDENT)
+    }
+
+    fn max_nesting(&self) -> usize {
+        self.max_nesting.unwrap_or(MAX_NESTING)
+    }
+}
+
+/// Whether a chunk of interactive input is ready to be compiled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InputStatus {
+    Complete,
+    /// More lines are needed: a bracket, triple-quoted string, backslash
+    /// continuation or block is still open, or a decorator has no
+    /// definition after it yet.
+    Incomplete,
+    Invalid(TokenError),
+}
+
+/// Classifies REPL input, so the prompt can decide whether to ask for another
+/// line.
+pub fn input_status(source: &str) -> InputStatus {
+    let config = TokenizerConfig {
+        interactive: true,
+        ..TokenizerConfig::default()
+    };
+    for token in Tokenizer::with_config(source, config) {
+        match token {
+            Ok(_) => {}
+            Err(ref err) if err.kind == TokenErrorKind::IncompleteInput => {
+                return InputStatus::Incomplete
+            }
+            Err(err) => return InputStatus::Invalid(err),
+        }
+    }
+    InputStatus::Complete
+}
+
+/// Tokenizes Python source text. A `#!` shebang line is an ordinary
+/// comment. A byte order mark is an invalid character wherever it is, as in
+/// CPython's `compile`: `decode` removes the one a file starts with.
+pub fn tokenize(source: &str) -> Result<Vec<Token>, TokenError> {
+    tokenize_with_config(source, TokenizerConfig::default())
+}
+
+/// Tokenizes Python source text with the options in `config`.
+pub fn tokenize_with_config(
+    source: &str,
+    config: TokenizerConfig,
+) -> Result<Vec<Token>, TokenError> {
+    let _span = stage_span!(
+        "tokenize",
+        bytes = source.len(),
+        tokens = ::tracing::field::Empty
+    );
+    let tokens: Vec<Token> = Tokenizer::with_config(source, config).collect::<Result<_, _>>()?;
+    record!(_span, "tokens", tokens.len());
+    Ok(tokens)
+}
+
+/// Reads, decodes (honouring a BOM or PEP 263 coding cookie) and tokenizes a
+/// source file.
+pub fn tokenize_file<P: AsRef<Path>>(path: P) -> Result<Vec<Token>, SourceError> {
+    let _span = trace::file_span(path.as_ref());
+    let bytes = fs::read(path)?;
+    let decoded = decode(&bytes)?;
+    Ok(tokenize(&decoded.text)?)
+}
+
+/// Tokenizes a source it borrows, one token at a time. Like the tokens and
+/// errors it gives, it shares nothing, so it can be moved to, or read from,
+/// another thread.
+pub struct Tokenizer<'a> {
+    config: TokenizerConfig,
+    source: &'a str,
+    position: usize,
+    line: usize,
+    line_start: usize,
+    /// The start of each line read so far, from the first. A tokenizer
+    /// moved on to a later line records no more.
+    line_starts: Vec<usize>,
+    /// The indentation of each open block, as the column it reaches with
+    /// tabs at `tab_size` and with tabs as wide as a space.
+    indents: Vec<(usize, usize)>,
+    brackets: Vec<(char, Location)>,
+    pending: VecDeque<Token>,
+    at_line_start: bool,
+    line_has_content: bool,
+    /// Whether the current line has tokens other than comments, and so
+    /// ends with a logical newline.
+    line_has_code: bool,
+    last_line_blank: bool,
+    line_ends_with_colon: bool,
+    block_opened: bool,
+    /// Whether the last logical line started with `@`, so that the
+    /// definition it decorates is still to come.
+    decorated: bool,
+    done: bool,
+}
+
+impl<'a> Tokenizer<'a> {
+    pub fn new(source: &'a str) -> Tokenizer<'a> {
+        Tokenizer::with_config(source, TokenizerConfig::default())
+    }
+
+    pub fn with_config(source: &'a str, config: TokenizerConfig) -> Tokenizer<'a> {
+        Tokenizer {
+            config,
+            source,
+            position: 0,
+            line: 1,
+            line_start: 0,
+            line_starts: vec![0],
+            indents: vec![(0, 0)],
+            brackets: Vec::new(),
+            pending: VecDeque::new(),
+            at_line_start: true,
+            line_has_content: false,
+            line_has_code: false,
+            last_line_blank: false,
+            line_ends_with_colon: false,
+            block_opened: false,
+            decorated: false,
+            done: false,
+        }
+    }
+
+    /// A tokenizer for `source` that starts at the line beginning at byte
+    /// `position`, line number `line`, with the blocks `indents` records
+    /// open, for carrying on from a newline read before. `source` is used
+    /// as it is, so `position` is an offset into the same text.
+    fn resume(
+        source: &'a str,
+        config: TokenizerConfig,
+        position: usize,
+        line: usize,
+        indents: Vec<(usize, usize)>,
+    ) -> Tokenizer<'a> {
+        let mut tokenizer = Tokenizer::with_config(source, config);
+        tokenizer.position = position;
+        tokenizer.line_start = position;
+        tokenizer.line = line;
+        tokenizer.indents = indents;
+        tokenizer
+    }
+
+    /// The source being tokenized.
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// The lines of the source: those read so far as the tokenizer found
+    /// them, and the rest by scanning for their line breaks.
+    pub fn line_index(&self) -> LineIndex {
+        LineIndex::from_starts(self.source, self.line_starts.clone())
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.source[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn location(&self, position: usize) -> Location {
+        let column = self
+            .source
+            .get(self.line_start..position)
+            .map_or(0, |line| line.chars().count());
+        Location::new(self.line, column)
+    }
+
+    /// Queues a token spanning `start_position` to the current position. The
+    /// span is checked rather than sliced, so a scanning bug surfaces as an
+    /// error instead of a panic.
+    fn push(
+        &mut self,
+        kind: TokenType,
+        start_position: usize,
+        start: Location,
+    ) -> Result<(), TokenError> {
+        let value = match self.source.get(start_position..self.position) {
+            Some(value) => value.to_string(),
+            None => return Err(self.boundary_error(start)),
+        };
+        let token = Token {
+            kind,
+            value,
+            start,
+            end: self.location(self.position),
+            span: Span::new(start_position, self.position),
+        };
+        if !kind.is_newline() {
+            self.line_has_content = true;
+        }
+        if !kind.is_newline() && kind != TokenType::Comment {
+            if !self.line_has_code {
+                self.decorated = kind == TokenType::Operator && token.value == "@";
+            }
+            self.line_has_code = true;
+            self.line_ends_with_colon = kind == TokenType::Operator && token.value == ":";
+        }
+        self.pending.push_back(token);
+        Ok(())
+    }
+
+    /// Queues a token made of the next `length` bytes.
+    fn simple(&mut self, kind: TokenType, length: usize) -> Result<(), TokenError> {
+        let start_position = self.position;
+        let start = self.location(start_position);
+        if !self.rest().is_char_boundary(length) {
+            return Err(self.boundary_error(start));
+        }
+        self.position += length;
+        self.push(kind, start_position, start)
+    }
+
+    fn check_cancelled(&self) -> Result<(), TokenError> {
+        match self.config.cancel {
+            Some(ref cancel) if cancel.is_cancelled() => Err(self.error(
+                TokenErrorKind::Cancelled,
+                "operation cancelled",
+                self.location(self.position),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn new_line(&mut self) {
+        self.line += 1;
+        self.line_start = self.position;
+        if self.line_starts.len() + 1 == self.line {
+            self.line_starts.push(self.position);
+        }
+    }
+
+    fn error<S: Into<String>>(
+        &self,
+        kind: TokenErrorKind,
+        message: S,
+        location: Location,
+    ) -> TokenError {
+        TokenError::new(kind, message, location)
+    }
+
+    /// The error for indentation that mixes tabs and spaces so that its
+    /// depth depends on how wide a tab is.
+    fn tab_error(&self, location: Location) -> TokenError {
+        self.error(
+            TokenErrorKind::InconsistentTabs,
+            "inconsistent use of tabs and spaces in indentation",
+            location,
+        )
+    }
+
+    fn boundary_error(&self, location: Location) -> TokenError {
+        self.error(
+            TokenErrorKind::InvalidCharacter,
+            "token does not end on a character boundary",
+            location,
+        )
+    }
+
+    /// An error caused by the input ending too soon, which interactive mode
+    /// reports as incomplete input.
+    fn eof_error<S: Into<String>>(
+        &self,
+        kind: TokenErrorKind,
+        message: S,
+        location: Location,
+    ) -> TokenError {
+        if self.config.interactive {
+            self.error(
+                TokenErrorKind::IncompleteInput,
+                "incomplete input",
+                location,
+            )
+        } else {
+            self.error(kind, message, location)
+        }
+    }
+
+    fn advance(&mut self) -> Result<(), TokenError> {
+        if self.at_line_start && self.brackets.is_empty() {
+            self.at_line_start = false;
+            self.indentation()?;
+            if !self.pending.is_empty() {
+                return Ok(());
+            }
+        }
+
+        while let Some(c) = self.peek() {
+            if c == ' ' || c == '\t' || c == '\x0c' {
+                self.position += 1;
+            } else {
+                break;
+            }
+        }
+
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return self.finish(),
+        };
+
+        match c {
+            '#' => self.comment(),
+            '\n' | '\r' => self.newline(),
+            '\\' => self.continuation(),
+            '\'' | '"' => self.string(0),
+            '0'..='9' => self.number(),
+            '.' if self.peek_nth(1).is_some_and(|c| c.is_ascii_digit()) => self.number(),
+            c if is_identifier_start(c) => self.name(),
+            _ => self.operator(),
+        }
+    }
+
+    fn indentation(&mut self) -> Result<(), TokenError> {
+        let (column, length) = measure_indentation(self.rest(), self.config.tab_size());
+        let end = self.position + length;
+
+        match self.source[end..].chars().next() {
+            None | Some('#') | Some('\n') | Some('\r') => return Ok(()),
+            _ => {}
+        }
+
+        // Indentation is ambiguous when it compares differently with tabs
+        // as wide as a space.
+        let (alt_column, _) = measure_indentation(self.rest(), 1);
+        let current = *self.indents.last().unwrap();
+        if column > current.0 {
+            let start_position = self.position;
+            let start = self.location(start_position);
+            if alt_column <= current.1 {
+                return Err(self.tab_error(start));
+            }
+            if self.indents.len() >= self.config.max_indent() {
+                return Err(self.error(
+                    TokenErrorKind::TooDeeplyIndented,
+                    "too many levels of indentation",
+                    start,
+                ));
+            }
+            self.position = end;
+            self.indents.push((column, alt_column));
+            self.push(TokenType::Indent, start_position, start)?;
+        } else if column < current.0 {
+            let location = self.location(end);
+            while column < self.indents.last().unwrap().0 {
+                self.indents.pop();
+                self.pending.push_back(Token {
+                    kind: TokenType::Dedent,
+                    value: String::new(),
+                    start: location,
+                    end: location,
+                    span: Span::new(end, end),
+                });
+            }
+            let &(outer, outer_alt) = self.indents.last().unwrap();
+            if column != outer {
+                return Err(self.error(
+                    TokenErrorKind::InconsistentDedent,
+                    "unindent does not match any outer indentation level",
+                    location,
+                ));
+            }
+            if alt_column != outer_alt {
+                let start = self.location(self.position);
+                return Err(self.tab_error(start));
+            }
+        } else if alt_column != current.1 {
+            let start = self.location(self.position);
+            return Err(self.tab_error(start));
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), TokenError> {
+        let location = self.location(self.position);
+        if let Some(&bracket) = self.brackets.last() {
+            let mut err = self.eof_error(
+                TokenErrorKind::EofInMultiLineStatement,
+                "EOF in multi-line statement",
+                location,
+            );
+            if err.kind == TokenErrorKind::EofInMultiLineStatement {
+                err.unclosed = Some(bracket);
+            }
+            return Err(err);
+        }
+        if self.config.interactive
+            && (self.line_ends_with_colon
+                || self.block_opened
+                || self.decorated
+                || (self.indents.len() > 1 && !self.last_line_blank))
+        {
+            return Err(self.error(
+                TokenErrorKind::IncompleteInput,
+                "incomplete input",
+                location,
+            ));
+        }
+        let end = self.position;
+        if self.line_has_code {
+            self.line_has_content = false;
+            self.line_has_code = false;
+            self.pending.push_back(Token {
+                kind: TokenType::NewlineLogical,
+                value: String::new(),
+                start: location,
+                end: Location::new(location.line, location.column + 1),
+                span: Span::new(end, end),
+            });
+        } else if self.line_has_content {
+            // A comment on the last line, which has no line break.
+            self.line_has_content = false;
+            self.pending.push_back(Token {
+                kind: TokenType::NewlineNonLogical,
+                value: String::new(),
+                start: location,
+                end: location,
+                span: Span::new(end, end),
+            });
+        }
+        // The closing tokens go at the start of the line after the last one,
+        // which is the current line if the source ended with a newline.
+        let line = if self.position == self.line_start {
+            self.line
+        } else {
+            self.line + 1
+        };
+        let location = Location::new(line, 0);
+        while self.indents.len() > 1 {
+            self.indents.pop();
+            self.pending.push_back(Token {
+                kind: TokenType::Dedent,
+                value: String::new(),
+                start: location,
+                end: location,
+                span: Span::new(end, end),
+            });
+        }
+        self.pending.push_back(Token {
+            kind: TokenType::EndMarker,
+            value: String::new(),
+            start: location,
+            end: location,
+            span: Span::new(end, end),
+        });
+        self.done = true;
+        Ok(())
+    }
+
+    fn comment(&mut self) -> Result<(), TokenError> {
+        let length = self
+            .rest()
+            .find(['\n', '\r'])
+            .unwrap_or_else(|| self.rest().len());
+        self.simple(TokenType::Comment, length)
+    }
+
+    fn newline(&mut self) -> Result<(), TokenError> {
+        let length = if self.rest().starts_with("\r\n") {
+            2
+        } else {
+            1
+        };
+        if self.brackets.is_empty() {
+            self.last_line_blank = !self.line_has_content;
+            if self.line_has_content {
+                self.block_opened = self.line_ends_with_colon;
+            }
+            let kind = if self.line_has_code {
+                TokenType::NewlineLogical
+            } else {
+                TokenType::NewlineNonLogical
+            };
+            self.simple(kind, length)?;
+            self.at_line_start = true;
+            self.line_has_content = false;
+            self.line_has_code = false;
+        } else {
+            // A line break inside brackets does not end the line, so it is
+            // an `NL`, as in CPython.
+            self.simple(TokenType::NewlineNonLogical, length)?;
+        }
+        self.new_line();
+        Ok(())
+    }
+
+    fn continuation(&mut self) -> Result<(), TokenError> {
+        let location = self.location(self.position);
+        self.position += 1;
+        if self.rest().starts_with("\r\n") {
+            self.position += 2;
+        } else if self.rest().starts_with('\n') || self.rest().starts_with('\r') {
+            self.position += 1;
+        } else if self.rest().is_empty() {
+            return Err(self.eof_error(
+                TokenErrorKind::EofInMultiLineStatement,
+                "unexpected EOF while parsing",
+                location,
+            ));
+        } else {
+            return Err(self.error(
+                TokenErrorKind::InvalidLineContinuation,
+                "unexpected character after line continuation character",
+                location,
+            ));
+        }
+        self.new_line();
+        if self.config.interactive && self.rest().is_empty() {
+            return Err(self.error(
+                TokenErrorKind::IncompleteInput,
+                "incomplete input",
+                location,
+            ));
+        }
+        Ok(())
+    }
+
+    fn name(&mut self) -> Result<(), TokenError> {
+        let length = self
+            .rest()
+            .find(|c| !is_identifier_continue(c))
+            .unwrap_or_else(|| self.rest().len());
+        let value = &self.rest()[..length];
+        let next = self.rest()[length..].chars().next();
+        if (next == Some('\'') || next == Some('"')) && StringPrefix::parse(value).is_some() {
+            return self.string(length);
+        }
+        self.simple(TokenType::Name, length)
+    }
+
+    fn string(&mut self, prefix_length: usize) -> Result<(), TokenError> {
+        let start_position = self.position;
+        let start = self.location(start_position);
+        self.position += prefix_length;
+
+        let quote = match self.peek() {
+            Some(quote) => quote,
+            None => return Err(self.boundary_error(start)),
+        };
+        let triple = self.rest().starts_with(&quote.to_string().repeat(3)[..]);
+        let delimiter_length = if triple { 3 } else { 1 };
+        self.position += delimiter_length;
+
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => {
+                    let message = if triple {
+                        format!(
+                            "unterminated triple-quoted string literal (detected at line {})",
+                            self.line
+                        )
+                    } else {
+                        format!(
+                            "unterminated string literal (detected at line {})",
+                            self.line
+                        )
+                    };
+                    if triple {
+                        return Err(self.eof_error(
+                            TokenErrorKind::UnterminatedTripleQuotedString,
+                            message,
+                            start,
+                        ));
+                    }
+                    return Err(self.error(TokenErrorKind::UnterminatedString, message, start));
+                }
+            };
+            match c {
+                '\\' => {
+                    self.position += 1;
+                    if self.rest().starts_with("\r\n") {
+                        self.position += 2;
+                        self.new_line();
+                    } else if let Some(escaped) = self.peek() {
+                        self.position += escaped.len_utf8();
+                        if escaped == '\n' || escaped == '\r' {
+                            self.new_line();
+                        }
+                    }
+                }
+                '\n' | '\r' => {
+                    if !triple {
+                        return Err(self.error(
+                            TokenErrorKind::UnterminatedString,
+                            format!(
+                                "unterminated string literal (detected at line {})",
+                                self.line
+                            ),
+                            start,
+                        ));
+                    }
+                    if self.rest().starts_with("\r\n") {
+                        self.position += 1;
+                    }
+                    self.position += 1;
+                    self.new_line();
+                }
+                c if c == quote => {
+                    if !triple {
+                        self.position += 1;
+                        break;
+                    }
+                    if self.rest().starts_with(&quote.to_string().repeat(3)[..]) {
+                        self.position += 3;
+                        break;
+                    }
+                    self.position += 1;
+                }
+                c => self.position += c.len_utf8(),
+            }
+        }
+
+        let prefix = &self.source[start_position..start_position + prefix_length];
+        let text = &self.source[start_position..self.position];
+        if StringPrefix::parse(prefix).is_some_and(|prefix| prefix.bytes) && !text.is_ascii() {
+            return Err(self.error(
+                TokenErrorKind::NonAsciiBytes,
+                "bytes can only contain ASCII literal characters",
+                start,
+            ));
+        }
+        self.push(TokenType::String, start_position, start)
+    }
+
+    fn number(&mut self) -> Result<(), TokenError> {
+        let start_position = self.position;
+        let start = self.location(start_position);
+        let rest = self.rest();
+        let lowered = rest.get(..2).map(|s| s.to_ascii_lowercase());
+        let radix = match lowered.as_deref() {
+            Some("0x") => 16,
+            Some("0o") => 8,
+            Some("0b") => 2,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            self.position += 2;
+            let digits = self.digits(radix);
+            if digits == 0 {
+                return Err(self.invalid_number(radix, start));
+            }
+        } else {
+            let mut integer = true;
+            let integer_start = self.position;
+            let digits = self.digits(10);
+            if self.peek() == Some('.') {
+                integer = false;
+                self.position += 1;
+                self.digits(10);
+            }
+            if digits == 0 && self.position - integer_start == 1 {
+                return Err(self.invalid_number(radix, start));
+            }
+            if let Some('e') | Some('E') = self.peek() {
+                let after = self.peek_nth(1);
+                let after_sign = self.peek_nth(2);
+                let has_exponent = match after {
+                    Some('+') | Some('-') => after_sign.is_some_and(|c| c.is_ascii_digit()),
+                    Some(c) => c.is_ascii_digit(),
+                    None => false,
+                };
+                if has_exponent {
+                    integer = false;
+                    self.position += 1;
+                    if let Some('+') | Some('-') = self.peek() {
+                        self.position += 1;
+                    }
+                    self.digits(10);
+                }
+            }
+            if let Some('j') | Some('J') = self.peek() {
+                integer = false;
+                self.position += 1;
+            }
+            let literal = &self.source[integer_start..self.position];
+            if integer
+                && literal.len() > 1
+                && literal.starts_with('0')
+                && literal.bytes().any(|b| b != b'0' && b != b'_')
+            {
+                return Err(self.error(
+                    TokenErrorKind::InvalidNumber,
+                    "leading zeros in decimal integer literals are not permitted; \
+                     use an 0o prefix for octal integers",
+                    start,
+                ));
+            }
+        }
+
+        let literal = &self.source[start_position..self.position];
+        if literal.ends_with('_') || literal.contains("__") {
+            return Err(self.invalid_number(radix, start));
+        }
+        if self.peek().is_some_and(is_identifier_continue) {
+            return Err(self.invalid_number(radix, start));
+        }
+
+        self.push(TokenType::Number, start_position, start)
+    }
+
+    fn digits(&mut self, radix: u32) -> usize {
+        let length = self
+            .rest()
+            .find(|c: char| !(c.is_digit(radix) || c == '_'))
+            .unwrap_or_else(|| self.rest().len());
+        self.position += length;
+        length
+    }
+
+    fn invalid_number(&self, radix: u32, location: Location) -> TokenError {
+        let name = match radix {
+            16 => "hexadecimal",
+            8 => "octal",
+            2 => "binary",
+            _ => "decimal",
+        };
+        self.error(
+            TokenErrorKind::InvalidNumber,
+            format!("invalid {} literal", name),
+            location,
+        )
+    }
+
+    fn operator(&mut self) -> Result<(), TokenError> {
+        let rest = self.rest();
+        let operator = THREE_CHAR_OPERATORS
+            .iter()
+            .chain(TWO_CHAR_OPERATORS.iter())
+            .filter(|&&op| op != ":=" || self.config.version >= PythonVersion::Py38)
+            .find(|op| rest.starts_with(*op))
+            .cloned()
+            .or_else(|| {
+                rest.get(..1)
+                    .filter(|first| ONE_CHAR_OPERATORS.contains(*first))
+            });
+
+        let operator = match operator {
+            Some(operator) => operator,
+            None => {
+                let c = self.peek().unwrap();
+                let message = if is_printable(c) {
+                    format!("invalid character '{}' (U+{:04X})", c, c as u32)
+                } else {
+                    format!("invalid non-printable character U+{:04X}", c as u32)
+                };
+                return Err(self.error(
+                    TokenErrorKind::InvalidCharacter,
+                    message,
+                    self.location(self.position),
+                ));
+            }
+        };
+
+        let location = self.location(self.position);
+        match operator {
+            "(" | "[" | "{" if self.brackets.len() >= self.config.max_nesting() => {
+                return Err(self.error(
+                    TokenErrorKind::TooDeeplyNested,
+                    "too many nested parentheses",
+                    location,
+                ))
+            }
+            "(" | "[" | "{" => self
+                .brackets
+                .push((operator.chars().next().unwrap(), location)),
+            ")" | "]" | "}" => {
+                let closing = operator.chars().next().unwrap();
+                match self.brackets.pop() {
+                    None => {
+                        return Err(self.error(
+                            TokenErrorKind::UnmatchedBracket,
+                            format!("unmatched '{}'", closing),
+                            location,
+                        ))
+                    }
+                    Some((opening, _)) if matching_bracket(opening) != closing => {
+                        return Err(self.error(
+                            TokenErrorKind::MismatchedBracket,
+                            format!(
+                                "closing parenthesis '{}' does not match opening parenthesis '{}'",
+                                closing, opening
+                            ),
+                            location,
+                        ))
+                    }
+                    Some(_) => {}
+                }
+            }
+            _ => {}
+        }
+
+        self.simple(TokenType::Operator, operator.len())
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token, TokenError>;
+
+    fn next(&mut self) -> Option<Result<Token, TokenError>> {
+        loop {
+            if let Some(token) = self.pending.pop_front() {
+                return Some(Ok(token));
+            }
+            if self.done {
+                return None;
+            }
+            if let Err(err) = self.check_cancelled().and_then(|_| self.advance()) {
+                self.done = true;
+                self.pending.clear();
+                return Some(Err(err));
+            }
+        }
+    }
+}
+
+/// The column the indentation at the start of `line` reaches, with tabs
+/// to the next multiple of `tab_size` and a form feed starting over, and
+/// the length of the indentation in bytes.
+pub(crate) fn measure_indentation(line: &str, tab_size: usize) -> (usize, usize) {
+    let mut column = 0;
+    let mut length = 0;
+    for b in line.bytes() {
+        match b {
+            b' ' => column += 1,
+            b'\t' => column = (column / tab_size + 1) * tab_size,
+            b'\x0c' => column = 0,
+            _ => break,
+        }
+        length += 1;
+    }
+    (column, length)
+}
+
+/// The indentation a block opened by `line` is kept at: the column it
+/// reaches with tabs at `tab_size`, and with tabs as wide as a space, as
+/// CPython's `altcol`.
+pub(crate) fn indentation_columns(line: &str, tab_size: usize) -> (usize, usize) {
+    (
+        measure_indentation(line, tab_size).0,
+        measure_indentation(line, 1).0,
+    )
+}
+
+/// The index of the last of `tokens` that ends a line outside brackets,
+/// after which a tokenizer can start again knowing only the blocks open.
+/// The newline added at the end of the source ends no line.
+fn last_line_end(tokens: &[Token]) -> Option<usize> {
+    // A `NEWLINE` is never inside brackets, so the brackets are counted
+    // from the last one, for the `NL`s of lines without code after it.
+    let logical = tokens
+        .iter()
+        .rposition(|token| token.kind == TokenType::NewlineLogical);
+    let mut end = logical.filter(|&index| !tokens[index].value.is_empty());
+    let mut depth = 0;
+    for (index, token) in tokens
+        .iter()
+        .enumerate()
+        .skip(logical.map_or(0, |index| index + 1))
+    {
+        depth += token.bracket_depth_change();
+        if token.kind == TokenType::NewlineNonLogical && depth == 0 && !token.value.is_empty() {
+            end = Some(index);
+        }
+    }
+    end
+}
+
+fn matching_bracket(opening: char) -> char {
+    match opening {
+        '(' => ')',
+        '[' => ']',
+        _ => '}',
+    }
+}
+
+/// Whether an identifier may start with `c`: `_` or a character with the
+/// Unicode `XID_Start` property, as PEP 3131 has it.
+pub(crate) fn is_identifier_start(c: char) -> bool {
+    c == '_' || unicode_ident::is_xid_start(c)
+}
+
+/// Whether `c` may be in an identifier after its start: a character with
+/// the `XID_Continue` property, which `_`, digits and combining marks have.
+pub(crate) fn is_identifier_continue(c: char) -> bool {
+    unicode_ident::is_xid_continue(c)
+}
@@ -0,0 +1,113 @@
+//! Tokenizing past errors, for tools that want the tokens of a source in
+//! the middle of being edited.
+
+use super::{
+    indentation_columns, last_line_end, Location, Token, TokenError, TokenErrorKind, TokenType,
+};
+use super::{LineIndex, Tokenizer, TokenizerConfig};
+
+/// Lines `tokenize_recovering` skipped because of an error in them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedLines {
+    pub error: TokenError,
+    /// Where the skipped lines start and end.
+    pub start: Location,
+    pub end: Location,
+    /// The index in the tokens at which the skipped lines' tokens would
+    /// have been.
+    pub index: usize,
+}
+
+/// Tokenizes `source` as `tokenize_with_config` does, but carries on after
+/// an error: the logical line it was found in is skipped, up to the end of
+/// the physical line the error is on, and tokenizing starts again on the
+/// next line with the blocks open as they were. For brackets never
+/// closed, the error is the outermost one's, and tokenizing starts again on
+/// the line after the one it was opened on, and for a bytes literal that is not ASCII, on the line after it
+/// ends; an unterminated triple-quoted string or a cancellation ends the
+/// tokens.
+pub fn tokenize_recovering(
+    source: &str,
+    config: TokenizerConfig,
+) -> (Vec<Token>, Vec<SkippedLines>) {
+    let mut tokens = Vec::new();
+    let mut skipped = Vec::new();
+    let mut tokenizer = Tokenizer::with_config(source, config.clone());
+    // The lines of the source, from the first tokenizer, which reads them
+    // from the start.
+    let mut lines: Option<LineIndex> = None;
+    loop {
+        let mut error = match tokenizer.next() {
+            Some(Ok(token)) => {
+                tokens.push(token);
+                continue;
+            }
+            Some(Err(error)) => error,
+            None => break,
+        };
+        // Back to the end of the last complete logical line, and the
+        // blocks open there.
+        let restart = last_line_end(&tokens).map_or(0, |index| index + 1);
+        tokens.truncate(restart);
+        let (line_start, line) = match tokens.last() {
+            Some(newline) => (newline.span.end, newline.start.line + 1),
+            None => (0, 1),
+        };
+        let mut indents = vec![(0, 0)];
+        for token in &tokens {
+            match token.kind {
+                TokenType::Indent => {
+                    indents.push(indentation_columns(&token.value, config.tab_size()))
+                }
+                TokenType::Dedent => {
+                    indents.pop();
+                }
+                _ => {}
+            }
+        }
+
+        // A bracket left open runs to the end of the source, so the lines
+        // after the one it was opened on are tokenized again, as are those
+        // after any other error but an unterminated triple-quoted string. A
+        // bytes literal with other characters is read to its end first.
+        // The error names the outermost bracket, after whose line
+        // tokenizing starts again, so that the brackets inside it are
+        // reported in turn, each once.
+        let error_line = match error.kind {
+            TokenErrorKind::EofInMultiLineStatement => {
+                error.unclosed = tokenizer.brackets.first().copied();
+                tokenizer.brackets.first().map(|&(_, opened)| opened.line)
+            }
+            TokenErrorKind::NonAsciiBytes => Some(tokenizer.line),
+            TokenErrorKind::UnterminatedTripleQuotedString
+            | TokenErrorKind::IncompleteInput
+            | TokenErrorKind::Cancelled => None,
+            _ => Some(error.location.line),
+        };
+        // The start of the line after the error, or the end of the source.
+        // At least one line is skipped, so that each error is passed.
+        let lines = lines.get_or_insert_with(|| tokenizer.line_index());
+        let (resume, resume_line, end) = if line_start < source.len() {
+            // The last line with any text, that of the source's last byte.
+            let last_line = lines.line_at(source.len() - 1);
+            let last =
+                error_line.map_or(last_line, |error_line| error_line.max(line).min(last_line));
+            let resume = lines
+                .line_span(last + 1)
+                .map_or(source.len(), |span| span.start);
+            let end = lines.location(source, lines.line_span(last).unwrap().end);
+            (resume, last + 1, end)
+        } else {
+            (line_start, line, Location::new(line, 0))
+        };
+        skipped.push(SkippedLines {
+            error,
+            start: Location::new(line, 0),
+            end,
+            index: tokens.len(),
+        });
+
+        tokenizer = Tokenizer::resume(source, config.clone(), resume, resume_line, indents);
+    }
+    (tokens, skipped)
+}
@@ -0,0 +1,240 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use super::error::TokenError;
+
+pub const BOM: char = '\u{feff}';
+const BOM_BYTES: &[u8] = b"\xef\xbb\xbf";
+
+/// Removes a leading UTF-8 byte order mark, if present.
+pub fn strip_bom(source: &str) -> &str {
+    if source.starts_with(BOM) {
+        &source[BOM.len_utf8()..]
+    } else {
+        source
+    }
+}
+
+/// Returns the interpreter directive of a `#!` first line, without the `#!`.
+pub fn shebang(source: &str) -> Option<&str> {
+    let source = strip_bom(source);
+    if !source.starts_with("#!") {
+        return None;
+    }
+    let line = source[2..].lines().next().unwrap_or("");
+    Some(line.trim_end_matches('\r'))
+}
+
+#[derive(Debug)]
+pub enum SourceError {
+    Io(io::Error),
+    UnknownEncoding(String),
+    EncodingMismatch(String),
+    Decode { encoding: String, line: usize },
+    Token(TokenError),
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SourceError::Io(ref err) => write!(f, "{}", err),
+            SourceError::UnknownEncoding(ref name) => write!(f, "unknown encoding: {}", name),
+            SourceError::EncodingMismatch(ref name) => {
+                write!(f, "encoding problem: {} with BOM", name)
+            }
+            SourceError::Decode { ref encoding, line } => write!(
+                f,
+                "(unicode error) '{}' codec can't decode source (line {})",
+                encoding, line
+            ),
+            SourceError::Token(ref err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for SourceError {}
+
+impl From<io::Error> for SourceError {
+    fn from(err: io::Error) -> SourceError {
+        SourceError::Io(err)
+    }
+}
+
+impl From<TokenError> for SourceError {
+    fn from(err: TokenError) -> SourceError {
+        SourceError::Token(err)
+    }
+}
+
+/// Source text decoded according to its BOM and PEP 263 coding cookie.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedSource {
+    pub encoding: String,
+    pub text: String,
+}
+
+/// Detects the encoding of Python source bytes the way `tokenize.detect_encoding`
+/// does: a UTF-8 BOM, or a coding cookie on one of the first two lines.
+pub fn detect_encoding(bytes: &[u8]) -> Result<String, SourceError> {
+    let has_bom = bytes.starts_with(BOM_BYTES);
+    let bytes = if has_bom { &bytes[3..] } else { bytes };
+
+    let mut lines = bytes.split(|&b| b == b'\n');
+    let mut cookie = None;
+    if let Some(first) = lines.next() {
+        cookie = find_cookie(first);
+        if cookie.is_none() && is_blank_or_comment(first) {
+            if let Some(second) = lines.next() {
+                cookie = find_cookie(second);
+            }
+        }
+    }
+
+    match cookie {
+        Some(name) => {
+            let encoding = normalize_encoding(&name)
+                .ok_or_else(|| SourceError::UnknownEncoding(name.clone()))?;
+            if has_bom && encoding != "utf-8" {
+                return Err(SourceError::EncodingMismatch(name));
+            }
+            Ok(encoding.to_string())
+        }
+        None => Ok("utf-8".to_string()),
+    }
+}
+
+/// Decodes source bytes to text, stripping any BOM.
+pub fn decode(bytes: &[u8]) -> Result<DecodedSource, SourceError> {
+    let encoding = detect_encoding(bytes)?;
+    let bytes = if bytes.starts_with(BOM_BYTES) {
+        &bytes[3..]
+    } else {
+        bytes
+    };
+    let text = match encoding.as_str() {
+        "utf-8" => match ::std::str::from_utf8(bytes) {
+            Ok(text) => text.to_string(),
+            Err(err) => return Err(decode_error(&encoding, bytes, err.valid_up_to())),
+        },
+        "ascii" => match bytes.iter().position(|b| !b.is_ascii()) {
+            Some(index) => return Err(decode_error(&encoding, bytes, index)),
+            None => bytes.iter().map(|&b| b as char).collect(),
+        },
+        "iso-8859-1" => bytes.iter().map(|&b| b as char).collect(),
+        _ => {
+            let table = match encoding.as_str() {
+                "cp1252" => &CP1252,
+                "iso-8859-5" => &ISO_8859_5,
+                _ => &KOI8_R,
+            };
+            let mut text = String::with_capacity(bytes.len());
+            for (index, &b) in bytes.iter().enumerate() {
+                match b {
+                    0..=0x7f => text.push(b as char),
+                    _ => match table[b as usize - 0x80] {
+                        0 => return Err(decode_error(&encoding, bytes, index)),
+                        code => text.push(::std::char::from_u32(u32::from(code)).unwrap()),
+                    },
+                }
+            }
+            text
+        }
+    };
+    Ok(DecodedSource { encoding, text })
+}
+
+fn decode_error(encoding: &str, bytes: &[u8], index: usize) -> SourceError {
+    let line = bytes[..index].iter().filter(|&&b| b == b'\n').count() + 1;
+    SourceError::Decode {
+        encoding: encoding.to_string(),
+        line,
+    }
+}
+
+fn is_blank_or_comment(line: &[u8]) -> bool {
+    let trimmed: Vec<u8> = line
+        .iter()
+        .cloned()
+        .skip_while(|&b| b == b' ' || b == b'\t' || b == b'\x0c' || b == b'\r')
+        .collect();
+    trimmed.is_empty() || trimmed[0] == b'#'
+}
+
+fn find_cookie(line: &[u8]) -> Option<String> {
+    let line = String::from_utf8_lossy(line);
+    let trimmed = line.trim_start_matches([' ', '\t', '\x0c']);
+    if !trimmed.starts_with('#') {
+        return None;
+    }
+    let index = trimmed.find("coding")?;
+    let rest = &trimmed[index + "coding".len()..];
+    if !rest.starts_with(':') && !rest.starts_with('=') {
+        return None;
+    }
+    let name: String = rest[1..]
+        .trim_start_matches([' ', '\t'])
+        .chars()
+        .take_while(|&c| c.is_alphanumeric() || c == '-' || c == '_' || c == '.')
+        .collect();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn normalize_encoding(name: &str) -> Option<&'static str> {
+    let lowered = name.to_lowercase().replace('_', "-");
+    match lowered.as_str() {
+        "utf-8" | "utf8" | "utf-8-sig" => Some("utf-8"),
+        "latin-1" | "latin1" | "iso-8859-1" | "iso8859-1" | "iso-latin-1" => Some("iso-8859-1"),
+        "ascii" | "us-ascii" => Some("ascii"),
+        "cp1252" | "windows-1252" | "1252" => Some("cp1252"),
+        "iso-8859-5" | "iso8859-5" | "cyrillic" => Some("iso-8859-5"),
+        "koi8-r" => Some("koi8-r"),
+        _ => None,
+    }
+}
+
+/// The upper halves of the single-byte codecs, by byte less 0x80, with 0
+/// where a byte is undefined.
+const CP1252: [u16; 128] = [
+    0x20ac, 0x0000, 0x201a, 0x0192, 0x201e, 0x2026, 0x2020, 0x2021, 0x02c6, 0x2030, 0x0160, 0x2039,
+    0x0152, 0x0000, 0x017d, 0x0000, 0x0000, 0x2018, 0x2019, 0x201c, 0x201d, 0x2022, 0x2013, 0x2014,
+    0x02dc, 0x2122, 0x0161, 0x203a, 0x0153, 0x0000, 0x017e, 0x0178, 0x00a0, 0x00a1, 0x00a2, 0x00a3,
+    0x00a4, 0x00a5, 0x00a6, 0x00a7, 0x00a8, 0x00a9, 0x00aa, 0x00ab, 0x00ac, 0x00ad, 0x00ae, 0x00af,
+    0x00b0, 0x00b1, 0x00b2, 0x00b3, 0x00b4, 0x00b5, 0x00b6, 0x00b7, 0x00b8, 0x00b9, 0x00ba, 0x00bb,
+    0x00bc, 0x00bd, 0x00be, 0x00bf, 0x00c0, 0x00c1, 0x00c2, 0x00c3, 0x00c4, 0x00c5, 0x00c6, 0x00c7,
+    0x00c8, 0x00c9, 0x00ca, 0x00cb, 0x00cc, 0x00cd, 0x00ce, 0x00cf, 0x00d0, 0x00d1, 0x00d2, 0x00d3,
+    0x00d4, 0x00d5, 0x00d6, 0x00d7, 0x00d8, 0x00d9, 0x00da, 0x00db, 0x00dc, 0x00dd, 0x00de, 0x00df,
+    0x00e0, 0x00e1, 0x00e2, 0x00e3, 0x00e4, 0x00e5, 0x00e6, 0x00e7, 0x00e8, 0x00e9, 0x00ea, 0x00eb,
+    0x00ec, 0x00ed, 0x00ee, 0x00ef, 0x00f0, 0x00f1, 0x00f2, 0x00f3, 0x00f4, 0x00f5, 0x00f6, 0x00f7,
+    0x00f8, 0x00f9, 0x00fa, 0x00fb, 0x00fc, 0x00fd, 0x00fe, 0x00ff,
+];
+const ISO_8859_5: [u16; 128] = [
+    0x0080, 0x0081, 0x0082, 0x0083, 0x0084, 0x0085, 0x0086, 0x0087, 0x0088, 0x0089, 0x008a, 0x008b,
+    0x008c, 0x008d, 0x008e, 0x008f, 0x0090, 0x0091, 0x0092, 0x0093, 0x0094, 0x0095, 0x0096, 0x0097,
+    0x0098, 0x0099, 0x009a, 0x009b, 0x009c, 0x009d, 0x009e, 0x009f, 0x00a0, 0x0401, 0x0402, 0x0403,
+    0x0404, 0x0405, 0x0406, 0x0407, 0x0408, 0x0409, 0x040a, 0x040b, 0x040c, 0x00ad, 0x040e, 0x040f,
+    0x0410, 0x0411, 0x0412, 0x0413, 0x0414, 0x0415, 0x0416, 0x0417, 0x0418, 0x0419, 0x041a, 0x041b,
+    0x041c, 0x041d, 0x041e, 0x041f, 0x0420, 0x0421, 0x0422, 0x0423, 0x0424, 0x0425, 0x0426, 0x0427,
+    0x0428, 0x0429, 0x042a, 0x042b, 0x042c, 0x042d, 0x042e, 0x042f, 0x0430, 0x0431, 0x0432, 0x0433,
+    0x0434, 0x0435, 0x0436, 0x0437, 0x0438, 0x0439, 0x043a, 0x043b, 0x043c, 0x043d, 0x043e, 0x043f,
+    0x0440, 0x0441, 0x0442, 0x0443, 0x0444, 0x0445, 0x0446, 0x0447, 0x0448, 0x0449, 0x044a, 0x044b,
+    0x044c, 0x044d, 0x044e, 0x044f, 0x2116, 0x0451, 0x0452, 0x0453, 0x0454, 0x0455, 0x0456, 0x0457,
+    0x0458, 0x0459, 0x045a, 0x045b, 0x045c, 0x00a7, 0x045e, 0x045f,
+];
+const KOI8_R: [u16; 128] = [
+    0x2500, 0x2502, 0x250c, 0x2510, 0x2514, 0x2518, 0x251c, 0x2524, 0x252c, 0x2534, 0x253c, 0x2580,
+    0x2584, 0x2588, 0x258c, 0x2590, 0x2591, 0x2592, 0x2593, 0x2320, 0x25a0, 0x2219, 0x221a, 0x2248,
+    0x2264, 0x2265, 0x00a0, 0x2321, 0x00b0, 0x00b2, 0x00b7, 0x00f7, 0x2550, 0x2551, 0x2552, 0x0451,
+    0x2553, 0x2554, 0x2555, 0x2556, 0x2557, 0x2558, 0x2559, 0x255a, 0x255b, 0x255c, 0x255d, 0x255e,
+    0x255f, 0x2560, 0x2561, 0x0401, 0x2562, 0x2563, 0x2564, 0x2565, 0x2566, 0x2567, 0x2568, 0x2569,
+    0x256a, 0x256b, 0x256c, 0x00a9, 0x044e, 0x0430, 0x0431, 0x0446, 0x0434, 0x0435, 0x0444, 0x0433,
+    0x0445, 0x0438, 0x0439, 0x043a, 0x043b, 0x043c, 0x043d, 0x043e, 0x043f, 0x044f, 0x0440, 0x0441,
+    0x0442, 0x0443, 0x0436, 0x0432, 0x044c, 0x044b, 0x0437, 0x0448, 0x044d, 0x0449, 0x0447, 0x044a,
+    0x042e, 0x0410, 0x0411, 0x0426, 0x0414, 0x0415, 0x0424, 0x0413, 0x0425, 0x0418, 0x0419, 0x041a,
+    0x041b, 0x041c, 0x041d, 0x041e, 0x041f, 0x042f, 0x0420, 0x0421, 0x0422, 0x0423, 0x0416, 0x0412,
+    0x042c, 0x042b, 0x0417, 0x0428, 0x042d, 0x0429, 0x0427, 0x042a,
+];
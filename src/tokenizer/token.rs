@@ -0,0 +1,246 @@
+use std::fmt;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TokenType {
+    Name,
+    Number,
+    String,
+    Operator,
+    Comment,
+    /// The end of a logical line: of a line with code, and the lines it
+    /// continues onto.
+    NewlineLogical,
+    /// The end of a line with no code, only blanks or a comment, which the
+    /// parser ignores.
+    NewlineNonLogical,
+    Indent,
+    Dedent,
+    EndMarker,
+}
+
+impl TokenType {
+    /// Whether this is the newline at the end of a line, logical or not.
+    pub fn is_newline(self) -> bool {
+        self == TokenType::NewlineLogical || self == TokenType::NewlineNonLogical
+    }
+
+    /// The name CPython's `tokenize` module uses for this token type.
+    pub fn name(self) -> &'static str {
+        match self {
+            TokenType::Name => "NAME",
+            TokenType::Number => "NUMBER",
+            TokenType::String => "STRING",
+            TokenType::Operator => "OP",
+            TokenType::Comment => "COMMENT",
+            TokenType::NewlineLogical => "NEWLINE",
+            TokenType::NewlineNonLogical => "NL",
+            TokenType::Indent => "INDENT",
+            TokenType::Dedent => "DEDENT",
+            TokenType::EndMarker => "ENDMARKER",
+        }
+    }
+}
+
+/// The name CPython's `tokenize` module gives the exact type of the
+/// operator `op`, such as `LPAR` for `(`, or `None` if it is not one.
+pub fn exact_type_name(op: &str) -> Option<&'static str> {
+    Some(match op {
+        "!=" => "NOTEQUAL",
+        "%" => "PERCENT",
+        "%=" => "PERCENTEQUAL",
+        "&" => "AMPER",
+        "&=" => "AMPEREQUAL",
+        "(" => "LPAR",
+        ")" => "RPAR",
+        "*" => "STAR",
+        "**" => "DOUBLESTAR",
+        "**=" => "DOUBLESTAREQUAL",
+        "*=" => "STAREQUAL",
+        "+" => "PLUS",
+        "+=" => "PLUSEQUAL",
+        "," => "COMMA",
+        "-" => "MINUS",
+        "-=" => "MINEQUAL",
+        "->" => "RARROW",
+        "." => "DOT",
+        "..." => "ELLIPSIS",
+        "/" => "SLASH",
+        "//" => "DOUBLESLASH",
+        "//=" => "DOUBLESLASHEQUAL",
+        "/=" => "SLASHEQUAL",
+        ":" => "COLON",
+        ":=" => "COLONEQUAL",
+        ";" => "SEMI",
+        "<" => "LESS",
+        "<<" => "LEFTSHIFT",
+        "<<=" => "LEFTSHIFTEQUAL",
+        "<=" => "LESSEQUAL",
+        "=" => "EQUAL",
+        "==" => "EQEQUAL",
+        ">" => "GREATER",
+        ">=" => "GREATEREQUAL",
+        ">>" => "RIGHTSHIFT",
+        ">>=" => "RIGHTSHIFTEQUAL",
+        "@" => "AT",
+        "@=" => "ATEQUAL",
+        "[" => "LSQB",
+        "]" => "RSQB",
+        "^" => "CIRCUMFLEX",
+        "^=" => "CIRCUMFLEXEQUAL",
+        "{" => "LBRACE",
+        "|" => "VBAR",
+        "|=" => "VBAREQUAL",
+        "}" => "RBRACE",
+        "~" => "TILDE",
+        _ => return None,
+    })
+}
+
+impl fmt::Display for TokenType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A position in the source: 1-based line, 0-based column counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Location {
+    pub fn new(line: usize, column: usize) -> Location {
+        Location { line, column }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{},{}", self.line, self.column)
+    }
+}
+
+/// Byte offsets of a token within the source it was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+/// What the prefix of a string literal makes of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct StringPrefix {
+    /// `b`: a `bytes` literal, which may only hold ASCII characters.
+    pub bytes: bool,
+    /// `r`: backslashes are not escapes.
+    pub raw: bool,
+    /// `f`: an f-string.
+    pub formatted: bool,
+}
+
+impl StringPrefix {
+    /// The prefix `text` is, in any case, or `None` if a string cannot have
+    /// it.
+    pub fn parse(text: &str) -> Option<StringPrefix> {
+        let lowered = text.to_ascii_lowercase();
+        match lowered.as_str() {
+            "" | "r" | "u" | "b" | "f" | "br" | "rb" | "fr" | "rf" => Some(StringPrefix {
+                bytes: lowered.contains('b'),
+                raw: lowered.contains('r'),
+                formatted: lowered.contains('f'),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Token {
+    pub kind: TokenType,
+    pub value: String,
+    pub start: Location,
+    pub end: Location,
+    pub span: Span,
+}
+
+impl Token {
+    pub fn is_operator(&self, op: &str) -> bool {
+        self.kind == TokenType::Operator && self.value == op
+    }
+
+    pub fn is_name(&self, name: &str) -> bool {
+        self.kind == TokenType::Name && self.value == name
+    }
+
+    /// The prefix of a string token, and its length in bytes, or `None` for
+    /// any other token.
+    pub fn string_prefix(&self) -> Option<(StringPrefix, usize)> {
+        if self.kind != TokenType::String {
+            return None;
+        }
+        let length = self.value.find(['\'', '"'])?;
+        let prefix = StringPrefix::parse(&self.value[..length])?;
+        Some((prefix, length))
+    }
+
+    /// How the token changes the depth of brackets: 1 for an opening
+    /// bracket, -1 for a closing one and 0 for any other token. A reader of
+    /// tokens counts it to tell the `NL` of a line break inside brackets
+    /// from that of a line without code.
+    pub fn bracket_depth_change(&self) -> isize {
+        if self.kind != TokenType::Operator {
+            return 0;
+        }
+        match self.value.as_str() {
+            "(" | "[" | "{" => 1,
+            ")" | "]" | "}" => -1,
+            _ => 0,
+        }
+    }
+
+    /// Whether the token is a `bytes` literal.
+    pub fn is_bytes(&self) -> bool {
+        self.string_prefix().is_some_and(|(prefix, _)| prefix.bytes)
+    }
+
+    /// The name of the token's exact type, as `tokenize` gives it: that of
+    /// the operator for an `OP` token, and of its type for any other.
+    pub fn exact_type(&self) -> &'static str {
+        match self.kind {
+            TokenType::Operator => exact_type_name(&self.value).unwrap_or("OP"),
+            kind => kind.name(),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}-{}:\t{}\t{:?}",
+            self.start, self.end, self.kind, self.value
+        )
+    }
+}
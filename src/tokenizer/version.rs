@@ -0,0 +1,82 @@
+use std::fmt;
+use std::str::FromStr;
+
+/// A version of Python whose grammar the tokenizer and parser follow, so
+/// that syntax newer than the target is rejected, as CPython's
+/// `ast.parse(..., feature_version=...)` does.
+///
+/// Only the syntax that versions disagree on is checked: `:=`,
+/// positional-only parameters and `=` in f-string replacement fields from
+/// 3.8, parenthesized context managers from 3.9, `match` from 3.10,
+/// `except*` from 3.11, and type parameter lists and `type` statements
+/// from 3.12. Before 3.8, `:=` is tokenized as `:` and `=`, as CPython's
+/// tokenizer did. `async` and `await` are keywords in every version, as
+/// they are from 3.7.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PythonVersion {
+    Py36,
+    Py37,
+    Py38,
+    Py39,
+    Py310,
+    Py311,
+    Py312,
+}
+
+impl PythonVersion {
+    /// The newest version, whose grammar is the default.
+    pub const LATEST: PythonVersion = PythonVersion::Py312;
+
+    pub const ALL: [PythonVersion; 7] = [
+        PythonVersion::Py36,
+        PythonVersion::Py37,
+        PythonVersion::Py38,
+        PythonVersion::Py39,
+        PythonVersion::Py310,
+        PythonVersion::Py311,
+        PythonVersion::Py312,
+    ];
+
+    /// The minor version: 8 for 3.8.
+    pub fn minor(self) -> u32 {
+        match self {
+            PythonVersion::Py36 => 6,
+            PythonVersion::Py37 => 7,
+            PythonVersion::Py38 => 8,
+            PythonVersion::Py39 => 9,
+            PythonVersion::Py310 => 10,
+            PythonVersion::Py311 => 11,
+            PythonVersion::Py312 => 12,
+        }
+    }
+}
+
+impl Default for PythonVersion {
+    fn default() -> PythonVersion {
+        PythonVersion::LATEST
+    }
+}
+
+/// `3.8`.
+impl fmt::Display for PythonVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "3.{}", self.minor())
+    }
+}
+
+/// Parses `3.8`, or `py38` as tools such as black spell it.
+impl FromStr for PythonVersion {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<PythonVersion, String> {
+        let minor = text
+            .strip_prefix("3.")
+            .or_else(|| text.strip_prefix("py3"))
+            .and_then(|minor| minor.parse::<u32>().ok());
+        PythonVersion::ALL
+            .iter()
+            .cloned()
+            .find(|version| Some(version.minor()) == minor)
+            .ok_or_else(|| format!("unsupported Python version '{}'", text))
+    }
+}
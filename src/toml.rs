@@ -0,0 +1,937 @@
+//! TOML 1.0 documents, read as CPython's `tomllib._parser` reads them, so
+//! that a document is rejected exactly when `tomllib` rejects it, with the
+//! same message at the same place.
+//!
+//! `config` reads `pyproject.toml` with it, and the `tomllib` module turns
+//! the values it gives into Python objects. Numbers, dates and times are
+//! kept as they are written, for each reader to convert as it needs:
+//! `tomllib` hands floats to its `parse_float`.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::slice;
+
+use ast::repr_str;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    /// An integer as written, with its sign, prefix and underscores.
+    Integer(String),
+    /// A float as written, `inf` and `nan` included.
+    Float(String),
+    Boolean(bool),
+    /// An offset or local date-time, a local date or a local time, as
+    /// written.
+    Datetime(String),
+    Array(Vec<Value>),
+    Table(Table),
+}
+
+impl Value {
+    /// What the value is, for errors about a value of the wrong type.
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Value::String(_) => "a string",
+            Value::Integer(_) => "an integer",
+            Value::Float(_) => "a float",
+            Value::Boolean(_) => "a boolean",
+            Value::Datetime(_) => "a date or time",
+            Value::Array(_) => "an array",
+            Value::Table(_) => "a table",
+        }
+    }
+
+    /// The value of an integer, or `None` for other values and for
+    /// integers too large for an `i64`.
+    pub fn as_integer(&self) -> Option<i64> {
+        let text = match *self {
+            Value::Integer(ref text) => text.replace('_', ""),
+            _ => return None,
+        };
+        let radix = match text.get(..2) {
+            Some("0x") => 16,
+            Some("0o") => 8,
+            Some("0b") => 2,
+            _ => return text.parse().ok(),
+        };
+        i64::from_str_radix(&text[2..], radix).ok()
+    }
+}
+
+/// A table, with its keys in the order the document gives them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Table {
+    entries: Vec<(String, Value)>,
+    indices: HashMap<String, usize>,
+}
+
+impl Table {
+    pub fn new() -> Table {
+        Table::default()
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.indices.get(key).map(|&index| &self.entries[index].1)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    pub fn iter(&self) -> slice::Iter<'_, (String, Value)> {
+        self.entries.iter()
+    }
+
+    fn get_mut(&mut self, key: &str) -> Option<&mut Value> {
+        match self.indices.get(key) {
+            Some(&index) => Some(&mut self.entries[index].1),
+            None => None,
+        }
+    }
+
+    /// Adds a key the table does not have.
+    fn insert(&mut self, key: String, value: Value) {
+        self.indices.insert(key.clone(), self.entries.len());
+        self.entries.push((key, value));
+    }
+}
+
+impl<'a> IntoIterator for &'a Table {
+    type Item = &'a (String, Value);
+    type IntoIter = slice::Iter<'a, (String, Value)>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Why a document is not valid TOML, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Error {
+    pub message: String,
+    /// The line and column of the error, counted from 1, or `None` at the
+    /// end of the document.
+    pub location: Option<(usize, usize)>,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => {
+                write!(f, "{} (at line {}, column {})", self.message, line, column)
+            }
+            None => write!(f, "{} (at end of document)", self.message),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+/// The table a document defines. Windows line endings are read as
+/// newlines, as `tomllib` reads them.
+pub fn parse(text: &str) -> Result<Table, Error> {
+    let parser = Parser {
+        src: text.replace("\r\n", "\n").chars().collect(),
+    };
+    parser.document()
+}
+
+/// A dotted key, as its parts.
+type Key = Vec<String>;
+
+/// The table header a key is within, joined with the key's leading parts.
+fn joined(header: &[String], key: &[String]) -> Key {
+    header.iter().chain(key).cloned().collect()
+}
+
+/// A key as a tuple's repr, as the errors show keys.
+fn key_repr(key: &[String]) -> String {
+    let parts: Vec<String> = key.iter().map(|part| repr_str(part)).collect();
+    if parts.len() == 1 {
+        format!("({},)", parts[0])
+    } else {
+        format!("({})", parts.join(", "))
+    }
+}
+
+/// The table was made by a header or by the dotted keys of a table,
+/// so no later header may make it again.
+const EXPLICIT_NEST: u8 = 1;
+/// The value is an inline table or an array, which cannot be added to.
+const FROZEN: u8 = 2;
+
+/// The flags of a key and of the keys below it.
+#[derive(Default)]
+struct FlagNode {
+    flags: u8,
+    recursive_flags: u8,
+    nested: HashMap<String, FlagNode>,
+}
+
+/// The flags of the keys of a document, as `tomllib._parser.Flags` keeps
+/// them: set flags apply to the key, recursive ones to all keys below it.
+#[derive(Default)]
+struct Flags {
+    root: HashMap<String, FlagNode>,
+    pending: Vec<(Key, u8)>,
+}
+
+impl Flags {
+    fn add_pending(&mut self, key: Key, flag: u8) {
+        self.pending.push((key, flag));
+    }
+
+    fn finalize_pending(&mut self) {
+        for (key, flag) in ::std::mem::take(&mut self.pending) {
+            self.set(&key, flag, false);
+        }
+    }
+
+    fn unset_all(&mut self, key: &[String]) {
+        let (stem, parent) = key.split_last().unwrap();
+        let mut nodes = &mut self.root;
+        for part in parent {
+            nodes = match nodes.get_mut(part) {
+                Some(node) => &mut node.nested,
+                None => return,
+            };
+        }
+        nodes.remove(stem);
+    }
+
+    fn set(&mut self, key: &[String], flag: u8, recursive: bool) {
+        let (stem, parent) = key.split_last().unwrap();
+        let mut nodes = &mut self.root;
+        for part in parent {
+            nodes = &mut nodes.entry(part.clone()).or_default().nested;
+        }
+        let node = nodes.entry(stem.clone()).or_default();
+        if recursive {
+            node.recursive_flags |= flag;
+        } else {
+            node.flags |= flag;
+        }
+    }
+
+    fn is(&self, key: &[String], flag: u8) -> bool {
+        let (stem, parent) = match key.split_last() {
+            Some(split) => split,
+            // The document itself has no flags.
+            None => return false,
+        };
+        let mut nodes = &self.root;
+        for part in parent {
+            let node = match nodes.get(part) {
+                Some(node) => node,
+                None => return false,
+            };
+            if node.recursive_flags & flag != 0 {
+                return true;
+            }
+            nodes = &node.nested;
+        }
+        match nodes.get(stem) {
+            Some(node) => (node.flags | node.recursive_flags) & flag != 0,
+            None => false,
+        }
+    }
+}
+
+/// The table at `key` below `root`, made empty where it is missing. The
+/// last table of an array of tables stands for the array if
+/// `access_lists`; `None` if some other value is in the way.
+fn nest<'a>(root: &'a mut Table, key: &[String], access_lists: bool) -> Option<&'a mut Table> {
+    let mut table = root;
+    for part in key {
+        if !table.contains_key(part) {
+            table.insert(part.clone(), Value::Table(Table::new()));
+        }
+        table = match *table.get_mut(part).unwrap() {
+            Value::Table(ref mut table) => table,
+            Value::Array(ref mut items) if access_lists => match items.last_mut() {
+                Some(&mut Value::Table(ref mut table)) => table,
+                _ => return None,
+            },
+            _ => return None,
+        };
+    }
+    Some(table)
+}
+
+/// Adds an empty table to the array of tables at `key`, or makes the
+/// array; `false` if some other value is there.
+fn append_nest_to_list(root: &mut Table, key: &[String]) -> bool {
+    let (stem, parent) = key.split_last().unwrap();
+    let table = match nest(root, parent, true) {
+        Some(table) => table,
+        None => return false,
+    };
+    match table.get_mut(stem) {
+        Some(&mut Value::Array(ref mut items)) => items.push(Value::Table(Table::new())),
+        Some(_) => return false,
+        None => {
+            let items = vec![Value::Table(Table::new())];
+            table.insert(stem.clone(), Value::Array(items));
+        }
+    }
+    true
+}
+
+fn is_whitespace(c: char) -> bool {
+    c == ' ' || c == '\t'
+}
+
+fn is_whitespace_or_newline(c: char) -> bool {
+    is_whitespace(c) || c == '\n'
+}
+
+fn is_bare_key(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '-' || c == '_'
+}
+
+fn is_control(c: char) -> bool {
+    c < ' ' || c == '\x7f'
+}
+
+/// The characters not allowed in one-line strings and comments.
+fn is_illegal(c: char) -> bool {
+    is_control(c) && c != '\t'
+}
+
+/// The characters not allowed in multiline strings.
+fn is_illegal_multiline(c: char) -> bool {
+    is_control(c) && c != '\t' && c != '\n'
+}
+
+/// A document being read, as its characters, so that positions count
+/// characters as the errors do.
+struct Parser {
+    src: Vec<char>,
+}
+
+impl Parser {
+    fn at(&self, pos: usize) -> Option<char> {
+        self.src.get(pos).cloned()
+    }
+
+    fn starts_with(&self, pos: usize, text: &str) -> bool {
+        text.chars()
+            .enumerate()
+            .all(|(index, c)| self.at(pos + index) == Some(c))
+    }
+
+    fn slice(&self, start: usize, end: usize) -> String {
+        let end = end.min(self.src.len());
+        let start = start.min(end);
+        self.src[start..end].iter().collect()
+    }
+
+    fn skip(&self, mut pos: usize, matches: fn(char) -> bool) -> usize {
+        while self.at(pos).is_some_and(matches) {
+            pos += 1;
+        }
+        pos
+    }
+
+    /// An error at `pos`.
+    fn error<T>(&self, pos: usize, message: &str) -> Result<T, Error> {
+        let location = if pos >= self.src.len() {
+            None
+        } else {
+            let line = self.src[..pos].iter().filter(|&&c| c == '\n').count() + 1;
+            let column = match self.src[..pos].iter().rposition(|&c| c == '\n') {
+                Some(newline) => pos - newline,
+                None => pos + 1,
+            };
+            Some((line, column))
+        };
+        Err(Error {
+            message: message.to_string(),
+            location,
+        })
+    }
+
+    /// The position of `expect` from `pos`, failing at characters for
+    /// which `illegal` holds before it.
+    fn skip_until(
+        &self,
+        pos: usize,
+        expect: &str,
+        illegal: fn(char) -> bool,
+        error_on_eof: bool,
+    ) -> Result<usize, Error> {
+        let found = (pos..self.src.len()).find(|&index| self.starts_with(index, expect));
+        let end = match found {
+            Some(end) => end,
+            None if error_on_eof => {
+                let message = format!("Expected {}", repr_str(expect));
+                return self.error(self.src.len(), &message);
+            }
+            None => self.src.len(),
+        };
+        if let Some(bad) = (pos..end).find(|&index| illegal(self.src[index])) {
+            let found = self.src[bad].to_string();
+            let message = format!("Found invalid character {}", repr_str(&found));
+            return self.error(bad, &message);
+        }
+        Ok(end)
+    }
+
+    fn skip_comment(&self, pos: usize) -> Result<usize, Error> {
+        if self.at(pos) == Some('#') {
+            return self.skip_until(pos + 1, "\n", is_illegal, false);
+        }
+        Ok(pos)
+    }
+
+    fn skip_comments_and_array_whitespace(&self, mut pos: usize) -> Result<usize, Error> {
+        loop {
+            let before = pos;
+            pos = self.skip(pos, is_whitespace_or_newline);
+            pos = self.skip_comment(pos)?;
+            if pos == before {
+                return Ok(pos);
+            }
+        }
+    }
+
+    fn document(&self) -> Result<Table, Error> {
+        let mut root = Table::new();
+        let mut flags = Flags::default();
+        let mut header = Key::new();
+        let mut pos = 0;
+        loop {
+            pos = self.skip(pos, is_whitespace);
+            let c = match self.at(pos) {
+                Some(c) => c,
+                None => break,
+            };
+            if c == '\n' {
+                pos += 1;
+                continue;
+            }
+            if is_bare_key(c) || c == '"' || c == '\'' {
+                pos = self.key_value_rule(pos, &mut root, &mut flags, &header)?;
+                pos = self.skip(pos, is_whitespace);
+            } else if c == '[' {
+                flags.finalize_pending();
+                let (end, key) = if self.at(pos + 1) == Some('[') {
+                    self.create_list_rule(pos, &mut root, &mut flags)?
+                } else {
+                    self.create_dict_rule(pos, &mut root, &mut flags)?
+                };
+                header = key;
+                pos = self.skip(end, is_whitespace);
+            } else if c != '#' {
+                return self.error(pos, "Invalid statement");
+            }
+            pos = self.skip_comment(pos)?;
+            match self.at(pos) {
+                None => break,
+                Some('\n') => pos += 1,
+                Some(_) => {
+                    let message = "Expected newline or end of document after a statement";
+                    return self.error(pos, message);
+                }
+            }
+        }
+        Ok(root)
+    }
+
+    /// `[table]`.
+    fn create_dict_rule(
+        &self,
+        pos: usize,
+        root: &mut Table,
+        flags: &mut Flags,
+    ) -> Result<(usize, Key), Error> {
+        let pos = self.skip(pos + 1, is_whitespace);
+        let (pos, key) = self.key(pos)?;
+        if flags.is(&key, EXPLICIT_NEST) || flags.is(&key, FROZEN) {
+            let message = format!("Cannot declare {} twice", key_repr(&key));
+            return self.error(pos, &message);
+        }
+        flags.set(&key, EXPLICIT_NEST, false);
+        if nest(root, &key, true).is_none() {
+            return self.error(pos, "Cannot overwrite a value");
+        }
+        if !self.starts_with(pos, "]") {
+            return self.error(pos, "Expected ']' at the end of a table declaration");
+        }
+        Ok((pos + 1, key))
+    }
+
+    /// `[[array of tables]]`.
+    fn create_list_rule(
+        &self,
+        pos: usize,
+        root: &mut Table,
+        flags: &mut Flags,
+    ) -> Result<(usize, Key), Error> {
+        let pos = self.skip(pos + 2, is_whitespace);
+        let (pos, key) = self.key(pos)?;
+        if flags.is(&key, FROZEN) {
+            let message = format!("Cannot mutate immutable namespace {}", key_repr(&key));
+            return self.error(pos, &message);
+        }
+        // The new table is open to keys again, but the array still cannot
+        // be declared as a table.
+        flags.unset_all(&key);
+        flags.set(&key, EXPLICIT_NEST, false);
+        if !append_nest_to_list(root, &key) {
+            return self.error(pos, "Cannot overwrite a value");
+        }
+        if !self.starts_with(pos, "]]") {
+            return self.error(pos, "Expected ']]' at the end of an array declaration");
+        }
+        Ok((pos + 2, key))
+    }
+
+    /// `key = value` in the table `header`.
+    fn key_value_rule(
+        &self,
+        pos: usize,
+        root: &mut Table,
+        flags: &mut Flags,
+        header: &[String],
+    ) -> Result<usize, Error> {
+        let (pos, key, value) = self.key_value_pair(pos)?;
+        let (stem, parent) = key.split_last().unwrap();
+        let absolute_parent = joined(header, parent);
+        for length in 1..key.len() {
+            let container = joined(header, &key[..length]);
+            // Dotted keys may not add to a table made by a header, nor may
+            // later headers or dotted keys open the tables they make.
+            if flags.is(&container, EXPLICIT_NEST) {
+                let message = format!("Cannot redefine namespace {}", key_repr(&container));
+                return self.error(pos, &message);
+            }
+            flags.add_pending(container, EXPLICIT_NEST);
+        }
+        if flags.is(&absolute_parent, FROZEN) {
+            let message = format!(
+                "Cannot mutate immutable namespace {}",
+                key_repr(&absolute_parent)
+            );
+            return self.error(pos, &message);
+        }
+        let table = match nest(root, &absolute_parent, true) {
+            Some(table) => table,
+            None => return self.error(pos, "Cannot overwrite a value"),
+        };
+        if table.contains_key(stem) {
+            return self.error(pos, "Cannot overwrite a value");
+        }
+        if let Value::Table(_) | Value::Array(_) = value {
+            flags.set(&joined(header, &key), FROZEN, true);
+        }
+        table.insert(stem.clone(), value);
+        Ok(pos)
+    }
+
+    fn key_value_pair(&self, pos: usize) -> Result<(usize, Key, Value), Error> {
+        let (pos, key) = self.key(pos)?;
+        if self.at(pos) != Some('=') {
+            return self.error(pos, "Expected '=' after a key in a key/value pair");
+        }
+        let pos = self.skip(pos + 1, is_whitespace);
+        let (pos, value) = self.value(pos)?;
+        Ok((pos, key, value))
+    }
+
+    fn key(&self, pos: usize) -> Result<(usize, Key), Error> {
+        let (pos, part) = self.key_part(pos)?;
+        let mut key = vec![part];
+        let mut pos = self.skip(pos, is_whitespace);
+        while self.at(pos) == Some('.') {
+            pos = self.skip(pos + 1, is_whitespace);
+            let (end, part) = self.key_part(pos)?;
+            key.push(part);
+            pos = self.skip(end, is_whitespace);
+        }
+        Ok((pos, key))
+    }
+
+    fn key_part(&self, pos: usize) -> Result<(usize, String), Error> {
+        match self.at(pos) {
+            Some(c) if is_bare_key(c) => {
+                let end = self.skip(pos, is_bare_key);
+                Ok((end, self.slice(pos, end)))
+            }
+            Some('\'') => self.literal_str(pos),
+            Some('"') => self.basic_str(pos + 1, false),
+            _ => self.error(pos, "Invalid initial character for a key part"),
+        }
+    }
+
+    fn array(&self, pos: usize) -> Result<(usize, Value), Error> {
+        let mut items = Vec::new();
+        let mut pos = self.skip_comments_and_array_whitespace(pos + 1)?;
+        if self.starts_with(pos, "]") {
+            return Ok((pos + 1, Value::Array(items)));
+        }
+        loop {
+            let (end, item) = self.value(pos)?;
+            items.push(item);
+            pos = self.skip_comments_and_array_whitespace(end)?;
+            match self.at(pos) {
+                Some(']') => return Ok((pos + 1, Value::Array(items))),
+                Some(',') => {}
+                _ => return self.error(pos, "Unclosed array"),
+            }
+            pos = self.skip_comments_and_array_whitespace(pos + 1)?;
+            if self.starts_with(pos, "]") {
+                return Ok((pos + 1, Value::Array(items)));
+            }
+        }
+    }
+
+    fn inline_table(&self, pos: usize) -> Result<(usize, Value), Error> {
+        let mut table = Table::new();
+        let mut flags = Flags::default();
+        let mut pos = self.skip(pos + 1, is_whitespace);
+        if self.starts_with(pos, "}") {
+            return Ok((pos + 1, Value::Table(table)));
+        }
+        loop {
+            let (end, key, value) = self.key_value_pair(pos)?;
+            pos = end;
+            let (stem, parent) = key.split_last().unwrap();
+            if flags.is(&key, FROZEN) {
+                let message = format!("Cannot mutate immutable namespace {}", key_repr(&key));
+                return self.error(pos, &message);
+            }
+            let nested = match nest(&mut table, parent, false) {
+                Some(nested) => nested,
+                None => return self.error(pos, "Cannot overwrite a value"),
+            };
+            if nested.contains_key(stem) {
+                let message = format!("Duplicate inline table key {}", repr_str(stem));
+                return self.error(pos, &message);
+            }
+            let container = matches!(value, Value::Table(_) | Value::Array(_));
+            nested.insert(stem.clone(), value);
+            pos = self.skip(pos, is_whitespace);
+            match self.at(pos) {
+                Some('}') => return Ok((pos + 1, Value::Table(table))),
+                Some(',') => {}
+                _ => return self.error(pos, "Unclosed inline table"),
+            }
+            if container {
+                flags.set(&key, FROZEN, true);
+            }
+            pos = self.skip(pos + 1, is_whitespace);
+        }
+    }
+
+    /// An escape sequence in a basic string, the backslash at `pos`.
+    fn escape(&self, pos: usize, multiline: bool) -> Result<(usize, String), Error> {
+        let escape = self.at(pos + 1);
+        let mut pos = pos + 2;
+        if multiline && matches!(escape, Some(' ') | Some('\t') | Some('\n')) {
+            // A backslash at the end of a line skips the whitespace and
+            // newlines after it.
+            if escape != Some('\n') {
+                pos = self.skip(pos, is_whitespace);
+                match self.at(pos) {
+                    None => return Ok((pos, String::new())),
+                    Some('\n') => pos += 1,
+                    Some(_) => return self.error(pos, "Unescaped '\\' in a string"),
+                }
+            }
+            return Ok((self.skip(pos, is_whitespace_or_newline), String::new()));
+        }
+        let replacement = match escape {
+            Some('u') => return self.hex_char(pos, 4),
+            Some('U') => return self.hex_char(pos, 8),
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            _ => return self.error(pos, "Unescaped '\\' in a string"),
+        };
+        Ok((pos, replacement.to_string()))
+    }
+
+    fn hex_char(&self, pos: usize, length: usize) -> Result<(usize, String), Error> {
+        let digits = self.slice(pos, pos + length);
+        if digits.chars().count() != length || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return self.error(pos, "Invalid hex value");
+        }
+        let pos = pos + length;
+        match u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+        {
+            Some(c) => Ok((pos, c.to_string())),
+            None => self.error(pos, "Escaped character is not a Unicode scalar value"),
+        }
+    }
+
+    fn literal_str(&self, pos: usize) -> Result<(usize, String), Error> {
+        let start = pos + 1;
+        let end = self.skip_until(start, "'", is_illegal, true)?;
+        Ok((end + 1, self.slice(start, end)))
+    }
+
+    fn multiline_str(&self, pos: usize, literal: bool) -> Result<(usize, String), Error> {
+        let mut pos = pos + 3;
+        if self.starts_with(pos, "\n") {
+            pos += 1;
+        }
+        let (delimiter, (mut pos, mut result)) = if literal {
+            let end = self.skip_until(pos, "'''", is_illegal_multiline, true)?;
+            ("'", (end + 3, self.slice(pos, end)))
+        } else {
+            ("\"", self.basic_str(pos, true)?)
+        };
+        // Up to two quotes may end the string just before its closing
+        // three.
+        for _ in 0..2 {
+            if !self.starts_with(pos, delimiter) {
+                break;
+            }
+            pos += 1;
+            result.push_str(delimiter);
+        }
+        Ok((pos, result))
+    }
+
+    /// A basic string whose contents start at `pos`.
+    fn basic_str(&self, mut pos: usize, multiline: bool) -> Result<(usize, String), Error> {
+        let illegal = if multiline {
+            is_illegal_multiline
+        } else {
+            is_illegal
+        };
+        let mut result = String::new();
+        let mut start = pos;
+        loop {
+            let c = match self.at(pos) {
+                Some(c) => c,
+                None => return self.error(pos, "Unterminated string"),
+            };
+            if c == '"' {
+                if !multiline {
+                    result.push_str(&self.slice(start, pos));
+                    return Ok((pos + 1, result));
+                }
+                if self.starts_with(pos, "\"\"\"") {
+                    result.push_str(&self.slice(start, pos));
+                    return Ok((pos + 3, result));
+                }
+                pos += 1;
+                continue;
+            }
+            if c == '\\' {
+                result.push_str(&self.slice(start, pos));
+                let (end, escaped) = self.escape(pos, multiline)?;
+                result.push_str(&escaped);
+                pos = end;
+                start = pos;
+                continue;
+            }
+            if illegal(c) {
+                let message = format!("Illegal character {}", repr_str(&c.to_string()));
+                return self.error(pos, &message);
+            }
+            pos += 1;
+        }
+    }
+
+    fn value(&self, pos: usize) -> Result<(usize, Value), Error> {
+        let string = match self.at(pos) {
+            Some('"') if self.starts_with(pos, "\"\"\"") => Some(self.multiline_str(pos, false)?),
+            Some('"') => Some(self.basic_str(pos + 1, false)?),
+            Some('\'') if self.starts_with(pos, "'''") => Some(self.multiline_str(pos, true)?),
+            Some('\'') => Some(self.literal_str(pos)?),
+            _ => None,
+        };
+        if let Some((end, text)) = string {
+            return Ok((end, Value::String(text)));
+        }
+        match self.at(pos) {
+            Some('t') if self.starts_with(pos, "true") => {
+                return Ok((pos + 4, Value::Boolean(true)));
+            }
+            Some('f') if self.starts_with(pos, "false") => {
+                return Ok((pos + 5, Value::Boolean(false)));
+            }
+            Some('[') => return self.array(pos),
+            Some('{') => return self.inline_table(pos),
+            _ => {}
+        }
+        if let Some(end) = self.datetime(pos) {
+            // `tomllib` gives the error when `datetime` rejects the date.
+            if !self.valid_date(pos) {
+                return self.error(pos, "Invalid date or datetime");
+            }
+            return Ok((end, Value::Datetime(self.slice(pos, end))));
+        }
+        if let Some(end) = self.time(pos) {
+            return Ok((end, Value::Datetime(self.slice(pos, end))));
+        }
+        if let Some((end, float)) = self.number(pos) {
+            let text = self.slice(pos, end);
+            let value = if float {
+                Value::Float(text)
+            } else {
+                Value::Integer(text)
+            };
+            return Ok((end, value));
+        }
+        for length in &[3, 4] {
+            let special = self.slice(pos, pos + length);
+            if ["inf", "nan", "-inf", "+inf", "-nan", "+nan"].contains(&special.as_str()) {
+                return Ok((pos + length, Value::Float(special)));
+            }
+        }
+        self.error(pos, "Invalid value")
+    }
+
+    /// Whether the two digits at `pos` are a number from `low` to `high`.
+    fn two_digits(&self, pos: usize, low: u32, high: u32) -> bool {
+        match (self.at(pos), self.at(pos + 1)) {
+            (Some(tens), Some(ones)) if tens.is_ascii_digit() && ones.is_ascii_digit() => {
+                let value = tens.to_digit(10).unwrap() * 10 + ones.to_digit(10).unwrap();
+                low <= value && value <= high
+            }
+            _ => false,
+        }
+    }
+
+    /// The end of the local time, `HH:MM:SS` with an optional fraction, at
+    /// `pos`, as `tomllib._re.RE_LOCALTIME` matches it.
+    fn time(&self, pos: usize) -> Option<usize> {
+        let time = self.two_digits(pos, 0, 23)
+            && self.at(pos + 2) == Some(':')
+            && self.two_digits(pos + 3, 0, 59)
+            && self.at(pos + 5) == Some(':')
+            && self.two_digits(pos + 6, 0, 59);
+        if !time {
+            return None;
+        }
+        let end = pos + 8;
+        if self.at(end) == Some('.') && self.at(end + 1).is_some_and(|c| c.is_ascii_digit()) {
+            return Some(self.skip(end + 1, |c| c.is_ascii_digit()));
+        }
+        Some(end)
+    }
+
+    /// The end of the date, `YYYY-MM-DD`, at `pos`, with the time and
+    /// offset that may follow it, as `tomllib._re.RE_DATETIME` matches it.
+    fn datetime(&self, pos: usize) -> Option<usize> {
+        let date = (0..4).all(|index| self.at(pos + index).is_some_and(|c| c.is_ascii_digit()))
+            && self.at(pos + 4) == Some('-')
+            && self.two_digits(pos + 5, 1, 12)
+            && self.at(pos + 7) == Some('-')
+            && self.two_digits(pos + 8, 1, 31);
+        if !date {
+            return None;
+        }
+        let end = pos + 10;
+        let time_end = match self.at(end) {
+            Some('T') | Some('t') | Some(' ') => match self.time(end + 1) {
+                Some(time_end) => time_end,
+                None => return Some(end),
+            },
+            _ => return Some(end),
+        };
+        let offset = match self.at(time_end) {
+            Some('Z') | Some('z') => 1,
+            Some('+') | Some('-')
+                if self.two_digits(time_end + 1, 0, 23)
+                    && self.at(time_end + 3) == Some(':')
+                    && self.two_digits(time_end + 4, 0, 59) =>
+            {
+                6
+            }
+            _ => 0,
+        };
+        Some(time_end + offset)
+    }
+
+    /// Whether the date `YYYY-MM-DD` at `pos`, whose month is from 1 to 12
+    /// and whose day is from 1 to 31, is one of the proleptic Gregorian
+    /// calendar from year 1.
+    fn valid_date(&self, pos: usize) -> bool {
+        let number = |start: usize, end: usize| self.slice(start, end).parse::<u32>().unwrap();
+        let (year, month, day) = (
+            number(pos, pos + 4),
+            number(pos + 5, pos + 7),
+            number(pos + 8, pos + 10),
+        );
+        let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+        let days = match month {
+            2 if leap => 29,
+            2 => 28,
+            4 | 6 | 9 | 11 => 30,
+            _ => 31,
+        };
+        year >= 1 && day <= days
+    }
+
+    /// Digits for which `matches` holds, each but the first optionally
+    /// after an underscore, from `pos`.
+    fn digits(&self, pos: usize, matches: fn(char) -> bool) -> Option<usize> {
+        if !self.at(pos).is_some_and(matches) {
+            return None;
+        }
+        let mut pos = pos + 1;
+        loop {
+            if self.at(pos).is_some_and(matches) {
+                pos += 1;
+            } else if self.at(pos) == Some('_') && self.at(pos + 1).is_some_and(matches) {
+                pos += 2;
+            } else {
+                return Some(pos);
+            }
+        }
+    }
+
+    /// The end of the number at `pos` and whether it is a float, as
+    /// `tomllib._re.RE_NUMBER` matches them.
+    fn number(&self, pos: usize) -> Option<(usize, bool)> {
+        if self.at(pos) == Some('0') {
+            let prefixed: Option<fn(char) -> bool> = match self.at(pos + 1) {
+                Some('x') => Some(|c: char| c.is_ascii_hexdigit()),
+                Some('b') => Some(|c: char| c == '0' || c == '1'),
+                Some('o') => Some(|c: char| ('0'..='7').contains(&c)),
+                _ => None,
+            };
+            if let Some(end) = prefixed.and_then(|matches| self.digits(pos + 2, matches)) {
+                return Some((end, false));
+            }
+        }
+        let mut end = pos;
+        if let Some('+') | Some('-') = self.at(end) {
+            end += 1;
+        }
+        end = match self.at(end) {
+            Some('0') => end + 1,
+            Some('1'..='9') => self.digits(end, |c| c.is_ascii_digit())?,
+            _ => return None,
+        };
+        let integer_end = end;
+        if self.at(end) == Some('.') {
+            if let Some(fraction_end) = self.digits(end + 1, |c| c.is_ascii_digit()) {
+                end = fraction_end;
+            }
+        }
+        if let Some('e') | Some('E') = self.at(end) {
+            let mut exponent = end + 1;
+            if let Some('+') | Some('-') = self.at(exponent) {
+                exponent += 1;
+            }
+            if let Some(exponent_end) = self.digits(exponent, |c| c.is_ascii_digit()) {
+                end = exponent_end;
+            }
+        }
+        Some((end, end != integer_end))
+    }
+}
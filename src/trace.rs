@@ -0,0 +1,116 @@
+//! Instrumentation of the pipeline with `tracing` spans.
+//!
+//! With the `tracing` feature, tokenizing, parsing, optimizing, compiling
+//! and executing each run in a span at the `INFO` level, named after the
+//! stage, and files read from disk run in a `file` span whose `path` and
+//! `elapsed_us` fields give the time spent on each one. Any subscriber can
+//! collect them. Without the feature the spans compile to nothing.
+//!
+//! With the `chrome-trace` feature, `chrome_trace` installs a subscriber that
+//! writes them to a file for `chrome://tracing` or Perfetto, whose flame
+//! chart shows where the time goes across a whole project.
+
+use std::path::Path;
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Enters a span for a pipeline stage, with fields written as for
+/// `tracing::info_span!`. The span lasts until the value is dropped.
+#[cfg(feature = "tracing")]
+macro_rules! stage_span {
+    ($name:expr) => {
+        ::tracing::info_span!($name).entered()
+    };
+    ($name:expr, $($fields:tt)+) => {
+        ::tracing::info_span!($name, $($fields)+).entered()
+    };
+}
+
+/// Without the `tracing` feature, the fields are not evaluated.
+#[cfg(not(feature = "tracing"))]
+macro_rules! stage_span {
+    ($($tokens:tt)*) => {
+        $crate::trace::NoSpan
+    };
+}
+
+/// Records a value for a field the span declared as `Empty`.
+#[cfg(feature = "tracing")]
+macro_rules! record {
+    ($span:expr, $field:expr, $value:expr) => {
+        $span.record($field, $value);
+    };
+}
+
+#[cfg(not(feature = "tracing"))]
+macro_rules! record {
+    ($($tokens:tt)*) => {};
+}
+
+/// What `stage_span!` gives without the `tracing` feature.
+#[cfg(not(feature = "tracing"))]
+#[doc(hidden)]
+pub struct NoSpan;
+
+/// A span for the work on one file, which records how long it took in its
+/// `elapsed_us` field when dropped.
+#[cfg(feature = "tracing")]
+pub(crate) struct FileSpan {
+    span: ::tracing::span::EnteredSpan,
+    start: Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for FileSpan {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed().as_micros() as u64;
+        self.span.record("elapsed_us", elapsed);
+    }
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) fn file_span(path: &Path) -> FileSpan {
+    FileSpan {
+        span: stage_span!(
+            "file",
+            path = %path.display(),
+            elapsed_us = ::tracing::field::Empty
+        ),
+        start: Instant::now(),
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) fn file_span(_path: &Path) -> NoSpan {
+    NoSpan
+}
+
+#[cfg(feature = "chrome-trace")]
+pub use self::chrome::{chrome_trace, ChromeTrace};
+
+#[cfg(feature = "chrome-trace")]
+mod chrome {
+    use std::path::Path;
+
+    use tracing_chrome::{ChromeLayerBuilder, FlushGuard};
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::util::{SubscriberInitExt, TryInitError};
+
+    /// Keeps a Chrome trace open. The file is complete once this is
+    /// dropped.
+    pub struct ChromeTrace {
+        _guard: FlushGuard,
+    }
+
+    /// Installs a global subscriber that writes every span to `path` in the
+    /// Chrome trace event format. Fails if a global subscriber is already
+    /// set.
+    pub fn chrome_trace<P: AsRef<Path>>(path: P) -> Result<ChromeTrace, TryInitError> {
+        let (layer, guard) = ChromeLayerBuilder::new()
+            .file(path.as_ref())
+            .include_args(true)
+            .build();
+        tracing_subscriber::registry().with(layer).try_init()?;
+        Ok(ChromeTrace { _guard: guard })
+    }
+}
@@ -0,0 +1,549 @@
+//! The formats of compressed data: zlib and gzip streams, and zip archives.
+//! The `zlib`, `gzip` and `zipfile` modules are built on them, and so is
+//! importing modules from zip archives on `sys.path`.
+//!
+//! DEFLATE itself is `miniz_oxide`'s; this is the framing around it.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use miniz_oxide::deflate::compress_to_vec;
+use miniz_oxide::inflate::core::inflate_flags::{
+    TINFL_FLAG_COMPUTE_ADLER32, TINFL_FLAG_HAS_MORE_INPUT, TINFL_FLAG_PARSE_ZLIB_HEADER,
+    TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF,
+};
+use miniz_oxide::inflate::core::{decompress, DecompressorOxide};
+use miniz_oxide::inflate::TINFLStatus;
+
+/// The CRC-32 of zlib, gzip and zip, continuing from `crc`.
+pub(super) fn crc32(data: &[u8], crc: u32) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut index = 0;
+        while index < 256 {
+            let mut value = index as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                value = if value & 1 != 0 {
+                    0xedb8_8320 ^ (value >> 1)
+                } else {
+                    value >> 1
+                };
+                bit += 1;
+            }
+            table[index] = value;
+            index += 1;
+        }
+        table
+    };
+    let mut crc = !crc;
+    for &byte in data {
+        crc = TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// The Adler-32 checksum of zlib streams, continuing from `value`.
+pub(super) fn adler32(data: &[u8], value: u32) -> u32 {
+    const MOD: u32 = 65521;
+    let (mut a, mut b) = (value & 0xffff, value >> 16);
+    // 5552 bytes is the most that can be summed before `b` could overflow.
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        a %= MOD;
+        b %= MOD;
+    }
+    (b << 16) | a
+}
+
+/// Raw DEFLATE data of `data`, at a level from 0, for none, to 9.
+pub(super) fn deflate(data: &[u8], level: u8) -> Vec<u8> {
+    compress_to_vec(data, level)
+}
+
+/// A zlib stream of `data`: a header, the DEFLATE data and the Adler-32
+/// of `data`.
+pub(super) fn zlib_compress(data: &[u8], level: u8) -> Vec<u8> {
+    // The header flags record the level as zlib does, in four steps.
+    let level_flags: u8 = match level {
+        0 | 1 => 0,
+        2..=5 => 1,
+        6 => 2,
+        _ => 3,
+    };
+    let header = [0x78, level_flags << 6];
+    let check = 31 - (u16::from_be_bytes(header) % 31) as u8;
+    let mut stream = vec![0x78, (level_flags << 6) | check];
+    stream.extend(deflate(data, level));
+    stream.extend(&adler32(data, 1).to_be_bytes());
+    stream
+}
+
+/// Why compressed data could not be decompressed, with zlib's error code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct InflateError {
+    pub code: i32,
+    pub message: &'static str,
+}
+
+impl InflateError {
+    fn new(code: i32, message: &'static str) -> InflateError {
+        InflateError { code, message }
+    }
+}
+
+/// Decompresses the raw DEFLATE data, or with `zlib` the zlib stream, at
+/// the start of `data`: the data, and how many bytes of `data` the stream
+/// took.
+pub(super) fn inflate(data: &[u8], zlib: bool) -> Result<(Vec<u8>, usize), InflateError> {
+    let mut flags = TINFL_FLAG_USING_NON_WRAPPING_OUTPUT_BUF | TINFL_FLAG_HAS_MORE_INPUT;
+    if zlib {
+        if data.len() >= 2 {
+            if data[0] & 0x0f != 8 {
+                return Err(InflateError::new(-3, "unknown compression method"));
+            }
+            if !u16::from_be_bytes([data[0], data[1]]).is_multiple_of(31) {
+                return Err(InflateError::new(-3, "incorrect header check"));
+            }
+        }
+        flags |= TINFL_FLAG_PARSE_ZLIB_HEADER | TINFL_FLAG_COMPUTE_ADLER32;
+    }
+    let mut decompressor = DecompressorOxide::new();
+    let mut out = vec![0; (data.len() * 2).max(64)];
+    let (mut read, mut written) = (0, 0);
+    loop {
+        let (status, consumed, produced) =
+            decompress(&mut decompressor, &data[read..], &mut out, written, flags);
+        read += consumed;
+        written += produced;
+        match status {
+            TINFLStatus::Done => {
+                out.truncate(written);
+                return Ok((out, read));
+            }
+            TINFLStatus::HasMoreOutput => {
+                let size = out.len() * 2;
+                out.resize(size, 0);
+            }
+            TINFLStatus::NeedsMoreInput | TINFLStatus::FailedCannotMakeProgress => {
+                return Err(InflateError::new(-5, "incomplete or truncated stream"));
+            }
+            TINFLStatus::Adler32Mismatch => {
+                return Err(InflateError::new(-3, "incorrect data check"));
+            }
+            _ => return Err(InflateError::new(-3, "invalid compressed data")),
+        }
+    }
+}
+
+/// The seconds since the epoch, now.
+pub(super) fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as u32)
+}
+
+/// A gzip member holding `data`, with the modification time `mtime`.
+pub(super) fn gzip_compress(data: &[u8], level: u8, mtime: u32) -> Vec<u8> {
+    let extra_flags = match level {
+        9 => 2,
+        1 => 4,
+        _ => 0,
+    };
+    let mut member = vec![0x1f, 0x8b, 8, 0];
+    member.extend(&mtime.to_le_bytes());
+    // The operating system is 3, Unix, as zlib writes it.
+    member.extend(&[extra_flags, 3]);
+    member.extend(deflate(data, level));
+    member.extend(&crc32(data, 0).to_le_bytes());
+    member.extend(&(data.len() as u32).to_le_bytes());
+    member
+}
+
+/// Why a gzip file could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum GzipError {
+    /// A member does not start with the gzip magic number, but these
+    /// bytes: `BadGzipFile`.
+    NotGzip([u8; 2]),
+    /// A member is corrupt: `BadGzipFile`.
+    Bad(String),
+    /// The data ends too soon: `EOFError`.
+    Truncated,
+    /// The DEFLATE data is corrupt: `zlib.error`.
+    Inflate(InflateError),
+}
+
+/// The data of the gzip members in `data`, one after the other. Zeros may
+/// pad the end.
+pub(super) fn gzip_decompress(data: &[u8]) -> Result<Vec<u8>, GzipError> {
+    let mut out = Vec::new();
+    let mut rest = data;
+    let mut first = true;
+    while !rest.is_empty() {
+        // Zeros may pad the last member.
+        if !first && rest.iter().all(|&byte| byte == 0) {
+            break;
+        }
+        first = false;
+        if rest.len() < 2 {
+            return Err(GzipError::Truncated);
+        }
+        if rest[..2] != [0x1f, 0x8b] {
+            return Err(GzipError::NotGzip([rest[0], rest[1]]));
+        }
+        let header = rest.get(..10).ok_or(GzipError::Truncated)?;
+        if header[2] != 8 {
+            return Err(GzipError::Bad("Unknown compression method".to_string()));
+        }
+        let flags = header[3];
+        let mut position = 10;
+        let take = |position: &mut usize, count: usize| -> Result<usize, GzipError> {
+            let start = *position;
+            *position += count;
+            if *position > rest.len() {
+                return Err(GzipError::Truncated);
+            }
+            Ok(start)
+        };
+        if flags & 4 != 0 {
+            let start = take(&mut position, 2)?;
+            let length = u16::from_le_bytes([rest[start], rest[start + 1]]);
+            take(&mut position, usize::from(length))?;
+        }
+        // The file name, then the comment, each ending with a zero byte.
+        for flag in &[8, 16] {
+            if flags & flag != 0 {
+                let end = rest[position..]
+                    .iter()
+                    .position(|&byte| byte == 0)
+                    .ok_or(GzipError::Truncated)?;
+                position += end + 1;
+            }
+        }
+        if flags & 2 != 0 {
+            take(&mut position, 2)?;
+        }
+        let (member, used) = match inflate(&rest[position..], false) {
+            Ok(inflated) => inflated,
+            Err(ref error) if error.code == -5 => return Err(GzipError::Truncated),
+            Err(error) => return Err(GzipError::Inflate(error)),
+        };
+        position += used;
+        let start = take(&mut position, 8)?;
+        let trailer = &rest[start..start + 8];
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let size = u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]);
+        let actual = crc32(&member, 0);
+        if crc != actual {
+            return Err(GzipError::Bad(format!(
+                "CRC check failed {:#x} != {:#x}",
+                crc, actual
+            )));
+        }
+        if size != member.len() as u32 {
+            return Err(GzipError::Bad(
+                "Incorrect length of data produced".to_string(),
+            ));
+        }
+        out.extend(member);
+        rest = &rest[position..];
+    }
+    Ok(out)
+}
+
+/// How a zip member is stored.
+pub(super) const ZIP_STORED: u16 = 0;
+pub(super) const ZIP_DEFLATED: u16 = 8;
+
+/// A member of a zip archive, as its central directory describes it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ZipEntry {
+    pub name: String,
+    /// The year, month, day, hour, minute and second, to two seconds.
+    pub date_time: [u16; 6],
+    pub method: u16,
+    pub flags: u16,
+    pub create_system: u8,
+    pub create_version: u8,
+    pub extract_version: u16,
+    pub external_attr: u32,
+    pub crc: u32,
+    pub compressed_size: u32,
+    pub size: u32,
+    /// Where the member's local header is in the archive.
+    pub header_offset: u32,
+    pub comment: Vec<u8>,
+    pub extra: Vec<u8>,
+}
+
+impl ZipEntry {
+    /// A member named `name`, from the seconds since the epoch it was last
+    /// modified. Times are in UTC.
+    pub fn new(name: &str, mtime: u32) -> ZipEntry {
+        ZipEntry {
+            name: name.to_string(),
+            date_time: date_time(mtime),
+            method: ZIP_STORED,
+            flags: 0,
+            create_system: 3,
+            create_version: 20,
+            extract_version: 20,
+            external_attr: 0,
+            crc: 0,
+            compressed_size: 0,
+            size: 0,
+            header_offset: 0,
+            comment: Vec::new(),
+            extra: Vec::new(),
+        }
+    }
+
+    fn dos_time(&self) -> (u16, u16) {
+        let [year, month, day, hour, minute, second] = self.date_time;
+        let date = (year.max(1980) - 1980) << 9 | month << 5 | day;
+        let time = hour << 11 | minute << 5 | (second / 2);
+        (time, date)
+    }
+
+    /// The flags, with the one that says the name is UTF-8 if it is not
+    /// ASCII.
+    fn name_flags(&self) -> u16 {
+        if self.name.is_ascii() {
+            self.flags
+        } else {
+            self.flags | 0x800
+        }
+    }
+
+    /// The local header that comes before the member's data.
+    fn local_header(&self) -> Vec<u8> {
+        let (time, date) = self.dos_time();
+        let mut header = Vec::with_capacity(30 + self.name.len());
+        header.extend(&0x0403_4b50u32.to_le_bytes());
+        header.extend(&self.extract_version.to_le_bytes());
+        header.extend(&self.name_flags().to_le_bytes());
+        header.extend(&self.method.to_le_bytes());
+        header.extend(&time.to_le_bytes());
+        header.extend(&date.to_le_bytes());
+        header.extend(&self.crc.to_le_bytes());
+        header.extend(&self.compressed_size.to_le_bytes());
+        header.extend(&self.size.to_le_bytes());
+        header.extend(&(self.name.len() as u16).to_le_bytes());
+        header.extend(&(self.extra.len() as u16).to_le_bytes());
+        header.extend(self.name.as_bytes());
+        header.extend(&self.extra);
+        header
+    }
+
+    /// The member's record in the central directory.
+    fn central_header(&self) -> Vec<u8> {
+        let (time, date) = self.dos_time();
+        let mut header = Vec::with_capacity(46 + self.name.len());
+        header.extend(&0x0201_4b50u32.to_le_bytes());
+        header.extend(&[self.create_version, self.create_system]);
+        header.extend(&self.extract_version.to_le_bytes());
+        header.extend(&self.name_flags().to_le_bytes());
+        header.extend(&self.method.to_le_bytes());
+        header.extend(&time.to_le_bytes());
+        header.extend(&date.to_le_bytes());
+        header.extend(&self.crc.to_le_bytes());
+        header.extend(&self.compressed_size.to_le_bytes());
+        header.extend(&self.size.to_le_bytes());
+        header.extend(&(self.name.len() as u16).to_le_bytes());
+        header.extend(&(self.extra.len() as u16).to_le_bytes());
+        header.extend(&(self.comment.len() as u16).to_le_bytes());
+        // The disk number and the internal attributes.
+        header.extend(&[0, 0, 0, 0]);
+        header.extend(&self.external_attr.to_le_bytes());
+        header.extend(&self.header_offset.to_le_bytes());
+        header.extend(self.name.as_bytes());
+        header.extend(&self.extra);
+        header.extend(&self.comment);
+        header
+    }
+}
+
+/// The year, month, day, hour, minute and second, in UTC, of a time in
+/// seconds since the epoch.
+fn date_time(seconds: u32) -> [u16; 6] {
+    let days = i64::from(seconds / 86400);
+    let time = seconds % 86400;
+    // Howard Hinnant's `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    [
+        year as u16,
+        month as u16,
+        day as u16,
+        (time / 3600) as u16,
+        (time / 60 % 60) as u16,
+        (time % 60) as u16,
+    ]
+}
+
+fn read_u16(data: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes([data[at], data[at + 1]])
+}
+
+fn read_u32(data: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes([data[at], data[at + 1], data[at + 2], data[at + 3]])
+}
+
+/// Where the central directory of a zip archive starts, and its members.
+pub(super) fn read_zip(data: &[u8]) -> Result<(usize, Vec<ZipEntry>), String> {
+    const END_SIZE: usize = 22;
+    let bad = || "File is not a zip file".to_string();
+    if data.len() < END_SIZE {
+        return Err(bad());
+    }
+    // The end record is last, unless an archive comment follows it.
+    let earliest = data.len().saturating_sub(END_SIZE + 0xffff);
+    let end = (earliest..=data.len() - END_SIZE)
+        .rev()
+        .find(|&at| read_u32(data, at) == 0x0605_4b50)
+        .ok_or_else(bad)?;
+    let count = usize::from(read_u16(data, end + 10));
+    let directory_size = read_u32(data, end + 12) as usize;
+    let directory = read_u32(data, end + 16) as usize;
+    if directory == 0xffff_ffff || count == 0xffff {
+        return Err("Zip64 archives are not supported".to_string());
+    }
+    if directory + directory_size > end {
+        return Err("Bad magic number for central directory".to_string());
+    }
+    let mut entries = Vec::with_capacity(count);
+    let mut at = directory;
+    for _ in 0..count {
+        if at + 46 > end || read_u32(data, at) != 0x0201_4b50 {
+            return Err("Bad magic number for central directory".to_string());
+        }
+        let name_length = usize::from(read_u16(data, at + 28));
+        let extra_length = usize::from(read_u16(data, at + 30));
+        let comment_length = usize::from(read_u16(data, at + 32));
+        let name_start = at + 46;
+        let extra_start = name_start + name_length;
+        let comment_start = extra_start + extra_length;
+        let next = comment_start + comment_length;
+        if next > end {
+            return Err("Bad magic number for central directory".to_string());
+        }
+        let flags = read_u16(data, at + 8);
+        let name = &data[name_start..extra_start];
+        let name = if flags & 0x800 != 0 {
+            String::from_utf8_lossy(name).into_owned()
+        } else {
+            name.iter().map(|&byte| char::from(byte)).collect()
+        };
+        let (time, date) = (read_u16(data, at + 12), read_u16(data, at + 14));
+        entries.push(ZipEntry {
+            name,
+            date_time: [
+                (date >> 9) + 1980,
+                (date >> 5) & 0xf,
+                date & 0x1f,
+                time >> 11,
+                (time >> 5) & 0x3f,
+                (time & 0x1f) * 2,
+            ],
+            method: read_u16(data, at + 10),
+            flags,
+            create_version: data[at + 4],
+            create_system: data[at + 5],
+            extract_version: read_u16(data, at + 6),
+            crc: read_u32(data, at + 16),
+            compressed_size: read_u32(data, at + 20),
+            size: read_u32(data, at + 24),
+            external_attr: read_u32(data, at + 38),
+            header_offset: read_u32(data, at + 42),
+            extra: data[extra_start..comment_start].to_vec(),
+            comment: data[comment_start..next].to_vec(),
+        });
+        at = next;
+    }
+    Ok((directory, entries))
+}
+
+/// The data of the member `entry` of the archive `data`, decompressed and
+/// checked against its CRC.
+pub(super) fn read_zip_member(data: &[u8], entry: &ZipEntry) -> Result<Vec<u8>, String> {
+    let header = entry.header_offset as usize;
+    if header + 30 > data.len() || read_u32(data, header) != 0x0403_4b50 {
+        return Err("Bad magic number for file header".to_string());
+    }
+    let start = header
+        + 30
+        + usize::from(read_u16(data, header + 26))
+        + usize::from(read_u16(data, header + 28));
+    let compressed = data
+        .get(start..start + entry.compressed_size as usize)
+        .ok_or_else(|| "Truncated file header".to_string())?;
+    let member = match entry.method {
+        ZIP_STORED => compressed.to_vec(),
+        ZIP_DEFLATED => match inflate(compressed, false) {
+            Ok((member, _)) => member,
+            Err(error) => {
+                return Err(format!(
+                    "Error {} while decompressing data: {}",
+                    error.code, error.message
+                ))
+            }
+        },
+        method => return Err(format!("compression type {} is not supported", method)),
+    };
+    if crc32(&member, 0) != entry.crc {
+        return Err(format!("Bad CRC-32 for file '{}'", entry.name));
+    }
+    Ok(member)
+}
+
+/// Adds `member` to an archive being written as `archive`, compressed as
+/// `entry` says, and records its size, CRC and offset in `entry`.
+pub(super) fn write_zip_member(
+    archive: &mut Vec<u8>,
+    entry: &mut ZipEntry,
+    member: &[u8],
+    level: u8,
+) {
+    let compressed = match entry.method {
+        ZIP_DEFLATED => deflate(member, level),
+        _ => member.to_vec(),
+    };
+    if entry.method == ZIP_DEFLATED {
+        entry.extract_version = entry.extract_version.max(20);
+    }
+    entry.crc = crc32(member, 0);
+    entry.size = member.len() as u32;
+    entry.compressed_size = compressed.len() as u32;
+    entry.header_offset = archive.len() as u32;
+    archive.extend(entry.local_header());
+    archive.extend(compressed);
+}
+
+/// Ends an archive whose members have been written with the central
+/// directory of `entries`.
+pub(super) fn finish_zip(archive: &mut Vec<u8>, entries: &[ZipEntry]) {
+    let directory = archive.len() as u32;
+    for entry in entries {
+        archive.extend(entry.central_header());
+    }
+    let directory_size = archive.len() as u32 - directory;
+    let count = entries.len() as u16;
+    archive.extend(&0x0605_4b50u32.to_le_bytes());
+    archive.extend(&[0, 0, 0, 0]);
+    archive.extend(&count.to_le_bytes());
+    archive.extend(&count.to_le_bytes());
+    archive.extend(&directory_size.to_le_bytes());
+    archive.extend(&directory.to_le_bytes());
+    archive.extend(&[0, 0]);
+}
@@ -0,0 +1,2137 @@
+//! The builtin functions, such as `print`, `len` and `sorted`, and the
+//! constructors of the builtin types that scripts call by name, such as
+//! `int`, `list` and `range`.
+//!
+//! Argument errors follow CPython's wording, which differs between
+//! functions that take one argument, functions that take them by position,
+//! and functions with keywords.
+
+use std::cell::{Cell, RefCell};
+use std::convert::TryFrom;
+
+use ast::repr_str;
+
+use super::bytes::{bytes_like, decode_arguments};
+use super::dict::Dict;
+use super::frame::Completion;
+use super::modules::{self, io, sys};
+use super::object::{
+    object_id, Args, IteratorState, NativeFunction, ObjectRef, Payload, PyObject, PyResult,
+    ViewKind,
+};
+use super::unicode::ascii_digits_and_spaces;
+use super::Vm;
+
+/// Adds the builtin functions and types to the builtins namespace.
+pub(super) fn add_builtins(vm: &mut Vm) {
+    let functions: [(&'static str, NativeFunction); 39] = [
+        ("abs", abs),
+        ("aiter", aiter),
+        ("all", all),
+        ("any", any),
+        ("ascii", ascii),
+        ("bin", bin),
+        ("breakpoint", breakpoint),
+        ("callable", callable),
+        ("chr", chr),
+        ("delattr", delattr),
+        ("dir", dir),
+        ("divmod", divmod),
+        ("format", format),
+        ("getattr", getattr),
+        ("globals", globals),
+        ("hasattr", hasattr),
+        ("hash", hash),
+        ("hex", hex),
+        ("id", id),
+        ("input", input),
+        ("isinstance", isinstance),
+        ("issubclass", issubclass),
+        ("iter", iter),
+        ("len", len),
+        ("locals", locals),
+        ("max", max),
+        ("min", min),
+        ("next", next),
+        ("oct", oct),
+        ("open", io::open),
+        ("ord", ord),
+        ("pow", pow),
+        ("print", print),
+        ("repr", repr),
+        ("round", round),
+        ("setattr", setattr),
+        ("sorted", sorted),
+        ("sum", sum),
+        ("vars", vars),
+    ];
+    let builtins = vm.builtins().clone();
+    for &(name, function) in &functions {
+        let function = vm.new_builtin(name, function);
+        if ["dir", "locals", "vars"].contains(&name) {
+            vm.locals_functions.push(function.clone());
+        }
+        vm.dict_set_str(builtins.as_dict().unwrap(), name, function);
+    }
+
+    let types = [
+        &vm.types.bool,
+        &vm.types.int,
+        &vm.types.float,
+        &vm.types.complex,
+        &vm.types.str,
+        &vm.types.bytes,
+        &vm.types.bytearray,
+        &vm.types.memoryview,
+        &vm.types.tuple,
+        &vm.types.list,
+        &vm.types.dict,
+        &vm.types.set,
+        &vm.types.frozenset,
+        &vm.types.range,
+        &vm.types.enumerate,
+        &vm.types.zip,
+        &vm.types.map,
+        &vm.types.filter,
+        &vm.types.reversed,
+        &vm.types.slice,
+    ];
+    let types: Vec<ObjectRef> = types.iter().map(|&class| class.clone()).collect();
+    for class in types {
+        let name = class.as_type().unwrap().name.clone();
+        vm.dict_set_str(builtins.as_dict().unwrap(), &name, class);
+    }
+    let complex = vm.types.complex.clone();
+    let slice = vm.types.slice.clone();
+    let methods: [(&ObjectRef, &'static str, NativeFunction); 2] = [
+        (&complex, "conjugate", conjugate),
+        (&slice, "indices", indices),
+    ];
+    for &(class, name, function) in &methods {
+        let method = vm.new_builtin(name, function);
+        vm.dict_set_str(class.dict().unwrap().as_dict().unwrap(), name, method);
+    }
+    let constants = [
+        ("NotImplemented", vm.not_implemented()),
+        ("Ellipsis", vm.ellipsis.clone()),
+    ];
+    for (name, value) in constants {
+        vm.dict_set_str(builtins.as_dict().unwrap(), name, value);
+    }
+}
+
+/// Adds the builtins that are written in Python in CPython: `anext`, whose
+/// awaitable is a class of its own, and the `exit`, `quit` and `help` that
+/// the `site` module adds.
+pub(super) fn add_site_builtins(vm: &mut Vm) {
+    let object = vm.types.object.clone();
+    let awaitable_class = modules::new_native_class(
+        vm,
+        "builtins",
+        "anext_awaitable",
+        &object,
+        &[
+            ("__await__", anext_awaitable_await),
+            ("__init__", anext_awaitable_init),
+            ("__iter__", anext_awaitable_await),
+            ("__next__", anext_awaitable_next),
+            ("send", anext_awaitable_send),
+        ],
+    )
+    .expect("the anext awaitable class is created");
+    let anext = vm.new_builtin_closure("anext", move |vm, args| anext(vm, args, &awaitable_class));
+    let builtins = vm.builtins().clone();
+    vm.dict_set_str(builtins.as_dict().unwrap(), "anext", anext);
+    let site = vm
+        .import_module("_sitebuiltins")
+        .expect("_sitebuiltins is importable");
+    let quitter = vm.getattr(&site, "Quitter").unwrap();
+    let eof = vm.new_str("Ctrl-D (i.e. EOF)");
+    for name in ["exit", "quit"] {
+        let args = vec![vm.new_str(name), eof.clone()];
+        let quitter = vm.call(&quitter, Args::new(args)).unwrap();
+        vm.dict_set_str(builtins.as_dict().unwrap(), name, quitter);
+    }
+    let helper = vm.getattr(&site, "_Helper").unwrap();
+    let help = vm.call(&helper, Args::default()).unwrap();
+    vm.dict_set_str(builtins.as_dict().unwrap(), "help", help);
+}
+
+/// The only argument of a function that takes exactly one.
+fn one_argument(vm: &mut Vm, args: Args, name: &str) -> PyResult {
+    no_keywords(vm, &args, name)?;
+    let count = args.positional.len();
+    if count != 1 {
+        let message = format!("{}() takes exactly one argument ({} given)", name, count);
+        return Err(vm.new_type_error(message));
+    }
+    Ok(args.positional.into_iter().next().unwrap())
+}
+
+/// Checks that a function that takes no arguments was given none.
+fn no_arguments(vm: &mut Vm, args: &Args, name: &str) -> PyResult<()> {
+    no_keywords(vm, args, name)?;
+    let count = args.positional.len();
+    if count == 0 {
+        return Ok(());
+    }
+    let message = format!("{}() takes no arguments ({} given)", name, count);
+    Err(vm.new_type_error(message))
+}
+
+pub(super) fn no_keywords(vm: &mut Vm, args: &Args, name: &str) -> PyResult<()> {
+    if args.keywords.is_empty() {
+        return Ok(());
+    }
+    let message = format!("{}() takes no keyword arguments", name);
+    Err(vm.new_type_error(message))
+}
+
+/// Checks that there are between `min` and `max` positional arguments.
+pub(super) fn check_count(
+    vm: &mut Vm,
+    args: &Args,
+    name: &str,
+    min: usize,
+    max: usize,
+) -> PyResult<()> {
+    let count = args.positional.len();
+    let (bound, limit) = if count < min {
+        ("at least ", min)
+    } else if count > max {
+        ("at most ", max)
+    } else {
+        return Ok(());
+    };
+    let bound = if min == max { "" } else { bound };
+    let message = format!(
+        "{} expected {}{} argument{}, got {}",
+        name,
+        bound,
+        limit,
+        if limit == 1 { "" } else { "s" },
+        count
+    );
+    Err(vm.new_type_error(message))
+}
+
+/// The keyword arguments of a call to `function` that accepts `names`, in
+/// the order of `names`.
+pub(super) fn keyword_arguments(
+    vm: &mut Vm,
+    keywords: Vec<(String, ObjectRef)>,
+    function: &str,
+    names: &[&str],
+) -> PyResult<Vec<Option<ObjectRef>>> {
+    let mut values = vec![None; names.len()];
+    for (name, value) in keywords {
+        match names.iter().position(|&known| known == name) {
+            Some(index) => values[index] = Some(value),
+            None => {
+                let message = format!(
+                    "'{}' is an invalid keyword argument for {}()",
+                    name, function
+                );
+                return Err(vm.new_type_error(message));
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// The arguments of a call to `function`, which has the parameters
+/// `names`, given by position or by name, of which the first `required`
+/// must be given.
+pub(super) fn arguments(
+    vm: &mut Vm,
+    args: Args,
+    function: &str,
+    names: &[&str],
+    required: usize,
+) -> PyResult<Vec<Option<ObjectRef>>> {
+    let given = args.positional.len() + args.keywords.len();
+    if given > names.len() {
+        let message = format!(
+            "{}() takes at most {} argument{} ({} given)",
+            function,
+            names.len(),
+            if names.len() == 1 { "" } else { "s" },
+            given
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let mut values = keyword_arguments(vm, args.keywords, function, names)?;
+    for (position, value) in args.positional.into_iter().enumerate() {
+        if values[position].is_some() {
+            let message = format!(
+                "argument for {}() given by name ('{}') and position ({})",
+                function,
+                names[position],
+                position + 1
+            );
+            return Err(vm.new_type_error(message));
+        }
+        values[position] = Some(value);
+    }
+    if let Some(missing) = values[..required].iter().position(Option::is_none) {
+        let message = format!(
+            "{}() missing required argument '{}' (pos {})",
+            function,
+            names[missing],
+            missing + 1
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(values)
+}
+
+/// An integer argument, as CPython's `__index__` protocol gives it.
+pub(super) fn index_value(vm: &mut Vm, object: &ObjectRef) -> PyResult<i64> {
+    if let Payload::Int(value) = object.payload {
+        return Ok(value);
+    }
+    if let Some(method) = vm.python_method(object, "__index__") {
+        let result = vm.call(&method, Args::default())?;
+        if let Payload::Int(value) = result.payload {
+            return Ok(value);
+        }
+        let message = format!("__index__ returned non-int (type {})", result.type_name());
+        return Err(vm.new_type_error(message));
+    }
+    let message = format!(
+        "'{}' object cannot be interpreted as an integer",
+        object.type_name()
+    );
+    Err(vm.new_type_error(message))
+}
+
+fn attribute_name(vm: &mut Vm, name: &ObjectRef) -> PyResult<String> {
+    match name.as_str() {
+        Some(name) => Ok(name.to_string()),
+        None => {
+            let message = format!("attribute name must be string, not '{}'", name.type_name());
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// An instance of a builtin type or of a subclass of it, which gets an
+/// attribute dictionary if the class was made by a class statement.
+pub(super) fn new_instance(vm: &mut Vm, class: &ObjectRef, payload: Payload) -> ObjectRef {
+    let dict = if class.as_type().is_some_and(|data| data.heap) {
+        Some(vm.new_dict())
+    } else {
+        None
+    };
+    PyObject::new(payload, class.clone(), dict)
+}
+
+/// `abs(x)`.
+fn abs(vm: &mut Vm, args: Args) -> PyResult {
+    let value = one_argument(vm, args, "abs")?;
+    match value.payload {
+        Payload::Int(number) => match number.checked_abs() {
+            Some(number) => Ok(vm.new_int(number)),
+            None => Err(vm.int_overflow()),
+        },
+        Payload::Float(number) => Ok(vm.new_float(number.abs())),
+        Payload::Complex { real, imag } => Ok(vm.new_float(real.hypot(imag))),
+        _ => match vm.python_method(&value, "__abs__") {
+            Some(method) => vm.call(&method, Args::default()),
+            None => {
+                let message = format!("bad operand type for abs(): '{}'", value.type_name());
+                Err(vm.new_type_error(message))
+            }
+        },
+    }
+}
+
+/// `all(iterable)`.
+fn all(vm: &mut Vm, args: Args) -> PyResult {
+    let iterable = one_argument(vm, args, "all")?;
+    let iterator = vm.iter(&iterable)?;
+    while let Some(item) = vm.next(&iterator)? {
+        if !vm.is_true(&item)? {
+            return Ok(vm.new_bool(false));
+        }
+    }
+    Ok(vm.new_bool(true))
+}
+
+/// `any(iterable)`.
+fn any(vm: &mut Vm, args: Args) -> PyResult {
+    let iterable = one_argument(vm, args, "any")?;
+    let iterator = vm.iter(&iterable)?;
+    while let Some(item) = vm.next(&iterator)? {
+        if vm.is_true(&item)? {
+            return Ok(vm.new_bool(true));
+        }
+    }
+    Ok(vm.new_bool(false))
+}
+
+/// `aiter(async_iterable)`.
+fn aiter(vm: &mut Vm, args: Args) -> PyResult {
+    let iterable = one_argument(vm, args, "aiter")?;
+    let method = match vm.python_method(&iterable, "__aiter__") {
+        Some(method) => method,
+        None => {
+            let message = format!("'{}' object is not an async iterable", iterable.type_name());
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let iterator = vm.call(&method, Args::default())?;
+    if vm.python_method(&iterator, "__anext__").is_none() {
+        let message = format!(
+            "aiter() returned not an async iterator of type '{}'",
+            iterator.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(iterator)
+}
+
+/// `anext(async_iterator[, default])`: the awaitable `__anext__` returns,
+/// or with a default, an instance of `awaitable_class` that gives the
+/// default once the iterator is exhausted.
+fn anext(vm: &mut Vm, args: Args, awaitable_class: &ObjectRef) -> PyResult {
+    no_keywords(vm, &args, "anext")?;
+    check_count(vm, &args, "anext", 1, 2)?;
+    let iterator = &args.positional[0];
+    let method = match vm.python_method(iterator, "__anext__") {
+        Some(method) => method,
+        None => {
+            let message = format!("'{}' object is not an async iterator", iterator.type_name());
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let awaitable = vm.call(&method, Args::default())?;
+    match args.positional.get(1) {
+        Some(default) => vm.call(awaitable_class, Args::new(vec![awaitable, default.clone()])),
+        None => Ok(awaitable),
+    }
+}
+
+/// `anext_awaitable.__init__(self, awaitable, default)`.
+fn anext_awaitable_init(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "anext_awaitable", 3, 3)?;
+    let this = &args.positional[0];
+    vm.setattr(this, "_awaitable", args.positional[1].clone())?;
+    vm.setattr(this, "_default", args.positional[2].clone())?;
+    Ok(vm.none())
+}
+
+/// `anext_awaitable.__await__(self)`, which is the iterator itself.
+fn anext_awaitable_await(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__await__", 1, 1)?;
+    Ok(args.positional[0].clone())
+}
+
+/// `anext_awaitable.send(self, value)`: resumes the awaitable of
+/// `__anext__`, turning its `StopAsyncIteration` into a `StopIteration`
+/// that carries the default. `__next__` sends `None`.
+fn anext_awaitable_send(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "send", 2, 2)?;
+    let this = &args.positional[0];
+    let iterator = match vm.getattr(this, "_iterator") {
+        Ok(iterator) => iterator,
+        Err(_) => {
+            let awaitable = vm.getattr(this, "_awaitable")?;
+            let iterator = match vm.python_method(&awaitable, "__await__") {
+                Some(method) => vm.call(&method, Args::default())?,
+                None if matches!(awaitable.payload, Payload::Generator(_)) => awaitable,
+                None => {
+                    let message = format!("'{}' object can't be awaited", awaitable.type_name());
+                    return Err(vm.new_type_error(message));
+                }
+            };
+            vm.setattr(this, "_iterator", iterator.clone())?;
+            iterator
+        }
+    };
+    let result = match vm.send(&iterator, args.positional[1].clone()) {
+        Ok(Completion::Yielded(value)) => Ok(value),
+        Ok(Completion::Returned(value)) => Err(vm.new_stop_iteration(value)),
+        Err(error) => Err(error),
+    };
+    match result {
+        Err(error) if Vm::is_instance(&error, &vm.exceptions.stop_async_iteration) => {
+            let default = vm.getattr(this, "_default")?;
+            Err(vm.new_stop_iteration(default))
+        }
+        result => result,
+    }
+}
+
+/// `anext_awaitable.__next__(self)`.
+fn anext_awaitable_next(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__next__", 1, 1)?;
+    let none = vm.none();
+    let mut positional = args.positional;
+    positional.push(none);
+    anext_awaitable_send(vm, Args::new(positional))
+}
+
+/// `complex.conjugate()`.
+fn conjugate(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "conjugate", 1, 1)?;
+    match args.positional[0].payload {
+        Payload::Complex { real, imag } => Ok(vm.new_complex(real, -imag)),
+        _ => {
+            let message = format!(
+                "descriptor 'conjugate' for 'complex' objects doesn't apply to a '{}' object",
+                args.positional[0].type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// `slice.indices(length)`: the start, stop and step the slice selects
+/// from a sequence of `length` items.
+fn indices(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "indices", 2, 2)?;
+    let this = &args.positional[0];
+    if !matches!(this.payload, Payload::Slice { .. }) {
+        let message = format!(
+            "descriptor 'indices' for 'slice' objects doesn't apply to a '{}' object",
+            this.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let length = index_value(vm, &args.positional[1])?;
+    if length < 0 {
+        return Err(vm.new_value_error("length should not be negative".to_string()));
+    }
+    let (start, stop, step) = vm.slice_bounds(this, length)?;
+    let items = vec![vm.new_int(start), vm.new_int(stop), vm.new_int(step)];
+    Ok(vm.new_tuple(items))
+}
+
+/// `ascii(object)`.
+fn ascii(vm: &mut Vm, args: Args) -> PyResult {
+    let object = one_argument(vm, args, "ascii")?;
+    let text = vm.ascii(&object)?;
+    Ok(vm.new_str(&text))
+}
+
+/// `bin(x)`, `oct(x)` and `hex(x)`: an integer in base 2, 8 or 16, with
+/// the prefix of its literals.
+fn bin(vm: &mut Vm, args: Args) -> PyResult {
+    radix(vm, args, "bin")
+}
+
+fn oct(vm: &mut Vm, args: Args) -> PyResult {
+    radix(vm, args, "oct")
+}
+
+fn hex(vm: &mut Vm, args: Args) -> PyResult {
+    radix(vm, args, "hex")
+}
+
+fn radix(vm: &mut Vm, args: Args, name: &str) -> PyResult {
+    let x = one_argument(vm, args, name)?;
+    let value = index_value(vm, &x)?;
+    let magnitude = value.unsigned_abs();
+    let digits = match name {
+        "bin" => format!("0b{:b}", magnitude),
+        "oct" => format!("0o{:o}", magnitude),
+        _ => format!("0x{:x}", magnitude),
+    };
+    let sign = if value < 0 { "-" } else { "" };
+    Ok(vm.new_str(&format!("{}{}", sign, digits)))
+}
+
+/// `breakpoint(*args, **kws)`, which calls `sys.breakpointhook`.
+fn breakpoint(vm: &mut Vm, args: Args) -> PyResult {
+    let sys = vm.import_module("sys")?;
+    let hook = match vm.getattr(&sys, "breakpointhook") {
+        Ok(hook) => hook,
+        Err(_) => return Err(vm.new_runtime_error("lost sys.breakpointhook".to_string())),
+    };
+    vm.call(&hook, args)
+}
+
+/// `format(value, spec='')`.
+fn format(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "format")?;
+    check_count(vm, &args, "format", 1, 2)?;
+    let spec = match args.positional.get(1) {
+        Some(spec) => match spec.as_str() {
+            Some(spec) => spec.to_string(),
+            None => {
+                let message = format!("format() argument 2 must be str, not {}", spec.type_name());
+                return Err(vm.new_type_error(message));
+            }
+        },
+        None => String::new(),
+    };
+    let text = vm.format(&args.positional[0], &spec)?;
+    Ok(vm.new_str(&text))
+}
+
+/// `callable(object)`.
+fn callable(vm: &mut Vm, args: Args) -> PyResult {
+    let object = one_argument(vm, args, "callable")?;
+    let callable = is_callable(vm, &object);
+    Ok(vm.new_bool(callable))
+}
+
+pub(super) fn is_callable(vm: &mut Vm, object: &ObjectRef) -> bool {
+    match object.payload {
+        Payload::Function(_) | Payload::Builtin(_) | Payload::Method { .. } | Payload::Type(_) => {
+            true
+        }
+        _ => vm.lookup(&object.class(), "__call__").is_some(),
+    }
+}
+
+/// `chr(i)`.
+fn chr(vm: &mut Vm, args: Args) -> PyResult {
+    let code = one_argument(vm, args, "chr")?;
+    let code = index_value(vm, &code)?;
+    if !(0..0x11_0000).contains(&code) {
+        let message = "chr() arg not in range(0x110000)".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    match ::std::char::from_u32(code as u32) {
+        Some(c) => Ok(vm.new_str(&c.to_string())),
+        None => {
+            let message = "surrogate code points are not supported".to_string();
+            Err(vm.new_value_error(message))
+        }
+    }
+}
+
+/// `delattr(object, name)`.
+fn delattr(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "delattr")?;
+    check_count(vm, &args, "delattr", 2, 2)?;
+    let name = attribute_name(vm, &args.positional[1])?;
+    vm.delattr(&args.positional[0], &name)?;
+    Ok(vm.none())
+}
+
+/// `dir([object])`: the sorted names of the local scope, or of the
+/// attributes of `object`, as its `__dir__` gives them if its class
+/// defines one.
+fn dir(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "dir")?;
+    check_count(vm, &args, "dir", 0, 1)?;
+    let object = match args.positional.first() {
+        Some(object) => object,
+        None => {
+            let locals = current_locals(vm);
+            return names_of(vm, &locals);
+        }
+    };
+    if let Some(method) = vm.python_method(object, "__dir__") {
+        let names = vm.call(&method, Args::default())?;
+        let mut names = vm.iterate(&names)?;
+        vm.sort(&mut names, None, false)?;
+        return Ok(vm.new_list(names));
+    }
+    let mut namespaces = Vec::new();
+    if let Some(dict) = object.dict() {
+        namespaces.push(dict.clone());
+    }
+    if !matches!(object.payload, Payload::Module) {
+        let class = if object.as_type().is_some() {
+            object.clone()
+        } else {
+            object.class()
+        };
+        if !Vm::is(&class, object) {
+            namespaces.extend(class.dict().cloned());
+        }
+        for base in &class.as_type().unwrap().mro {
+            namespaces.extend(base.dict().cloned());
+        }
+    }
+    let mut names: Vec<String> = Vec::new();
+    for namespace in &namespaces {
+        if let Some(dict) = namespace.as_dict() {
+            for entry in dict.borrow().iter() {
+                if let Some(name) = entry.key.as_str() {
+                    names.push(name.to_string());
+                }
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    let names = names.iter().map(|name| vm.new_str(name)).collect();
+    Ok(vm.new_list(names))
+}
+
+/// The sorted keys of a namespace, as `dir()` lists them.
+fn names_of(vm: &mut Vm, namespace: &ObjectRef) -> PyResult {
+    let mut names = vm.iterate(namespace)?;
+    vm.sort(&mut names, None, false)?;
+    Ok(vm.new_list(names))
+}
+
+/// `divmod(a, b)`: the quotient of floor division and the remainder.
+fn divmod(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "divmod")?;
+    check_count(vm, &args, "divmod", 2, 2)?;
+    let (a, b) = (&args.positional[0], &args.positional[1]);
+    let numbers =
+        |object: &ObjectRef| matches!(object.payload, Payload::Int(_) | Payload::Float(_));
+    if numbers(a) && numbers(b) {
+        let floats =
+            matches!(a.payload, Payload::Float(_)) || matches!(b.payload, Payload::Float(_));
+        let zero = matches!(b.payload, Payload::Int(0))
+            || matches!(b.payload, Payload::Float(value) if value == 0.0);
+        if floats && zero {
+            return Err(vm.new_zero_division_error("float divmod()".to_string()));
+        }
+        let quotient = vm.binary_op(a, ::ast::Operator::FloorDiv, b, false)?;
+        let remainder = vm.binary_op(a, ::ast::Operator::Mod, b, false)?;
+        return Ok(vm.new_tuple(vec![quotient, remainder]));
+    }
+    for (object, name, other) in [(a, "__divmod__", b), (b, "__rdivmod__", a)] {
+        if let Some(method) = vm.python_method(object, name) {
+            let result = vm.call(&method, Args::new(vec![other.clone()]))?;
+            if !Vm::is(&result, &vm.not_implemented()) {
+                return Ok(result);
+            }
+        }
+    }
+    let message = format!(
+        "unsupported operand type(s) for divmod(): '{}' and '{}'",
+        a.type_name(),
+        b.type_name()
+    );
+    Err(vm.new_type_error(message))
+}
+
+/// `getattr(object, name[, default])`.
+fn getattr(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "getattr")?;
+    check_count(vm, &args, "getattr", 2, 3)?;
+    let name = attribute_name(vm, &args.positional[1])?;
+    match vm.getattr(&args.positional[0], &name) {
+        Err(err)
+            if args.positional.len() == 3
+                && Vm::is_instance(&err, &vm.exceptions.attribute_error) =>
+        {
+            Ok(args.positional[2].clone())
+        }
+        result => result,
+    }
+}
+
+/// `globals()`: the namespace of the module of the running code.
+fn globals(vm: &mut Vm, args: Args) -> PyResult {
+    no_arguments(vm, &args, "globals")?;
+    Ok(vm.current_namespaces().0)
+}
+
+/// `hasattr(object, name)`.
+fn hasattr(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "hasattr")?;
+    check_count(vm, &args, "hasattr", 2, 2)?;
+    let name = attribute_name(vm, &args.positional[1])?;
+    match vm.getattr(&args.positional[0], &name) {
+        Ok(_) => Ok(vm.new_bool(true)),
+        Err(err) if Vm::is_instance(&err, &vm.exceptions.attribute_error) => Ok(vm.new_bool(false)),
+        Err(err) => Err(err),
+    }
+}
+
+/// `hash(object)`.
+fn hash(vm: &mut Vm, args: Args) -> PyResult {
+    let object = one_argument(vm, args, "hash")?;
+    let hash = vm.hash(&object)?;
+    Ok(vm.new_int(hash))
+}
+
+/// `id(object)`, the object's address.
+fn id(vm: &mut Vm, args: Args) -> PyResult {
+    let object = one_argument(vm, args, "id")?;
+    Ok(vm.new_int(object_id(&object) as i64))
+}
+
+/// Whether `class_or_tuple`, a class or a tuple of them nested to any
+/// depth, holds a class `test` accepts. `None` if it holds something else.
+/// `input([prompt])`: a line read from `sys.stdin`, without its newline,
+/// after writing the prompt to `sys.stdout`.
+fn input(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "input")?;
+    check_count(vm, &args, "input", 0, 1)?;
+    if let Some(prompt) = args.positional.first() {
+        let prompt = vm.str(prompt)?;
+        sys::write_standard(vm, "stdout", &[&prompt], true)?;
+    }
+    let sys = vm.import_module("sys")?;
+    let stdin = vm.getattr(&sys, "stdin")?;
+    if Vm::is(&stdin, &vm.none()) {
+        return Err(vm.new_runtime_error("input(): lost sys.stdin".to_string()));
+    }
+    let readline = vm.getattr(&stdin, "readline")?;
+    let line = vm.call(&readline, Args::default())?;
+    let line = match line.as_str() {
+        Some(line) => line.to_string(),
+        None => {
+            let message = "object.readline() returned non-string".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    };
+    if line.is_empty() {
+        let class = vm.exceptions.eof_error.clone();
+        return Err(vm.new_error(&class, "EOF when reading a line".to_string()));
+    }
+    Ok(vm.new_str(line.strip_suffix('\n').unwrap_or(&line)))
+}
+
+fn any_class<F: Fn(&ObjectRef) -> bool>(class_or_tuple: &ObjectRef, test: &F) -> Option<bool> {
+    match class_or_tuple.payload {
+        Payload::Type(_) => Some(test(class_or_tuple)),
+        Payload::Tuple(ref items) => {
+            for item in items {
+                if any_class(item, test)? {
+                    return Some(true);
+                }
+            }
+            Some(false)
+        }
+        _ => None,
+    }
+}
+
+/// `isinstance(object, class_or_tuple)`.
+fn isinstance(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "isinstance")?;
+    check_count(vm, &args, "isinstance", 2, 2)?;
+    let object = &args.positional[0];
+    match any_class(&args.positional[1], &|class| Vm::is_instance(object, class)) {
+        Some(result) => Ok(vm.new_bool(result)),
+        None => {
+            let message = "isinstance() arg 2 must be a type, a tuple of types, or a union";
+            Err(vm.new_type_error(message.to_string()))
+        }
+    }
+}
+
+/// `issubclass(class, class_or_tuple)`.
+fn issubclass(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "issubclass")?;
+    check_count(vm, &args, "issubclass", 2, 2)?;
+    let class = &args.positional[0];
+    if class.as_type().is_none() {
+        let message = "issubclass() arg 1 must be a class".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    match any_class(&args.positional[1], &|base| Vm::is_subclass(class, base)) {
+        Some(result) => Ok(vm.new_bool(result)),
+        None => {
+            let message = "issubclass() arg 2 must be a class, a tuple of classes, or a union";
+            Err(vm.new_type_error(message.to_string()))
+        }
+    }
+}
+
+/// `iter(object)`.
+fn iter(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "iter")?;
+    check_count(vm, &args, "iter", 1, 2)?;
+    if args.positional.len() == 2 {
+        if !is_callable(vm, &args.positional[0]) {
+            let message = "iter(v, w): v must be callable".to_string();
+            return Err(vm.new_type_error(message));
+        }
+        let message = "iter() with a sentinel is not supported yet".to_string();
+        let class = vm.exceptions.not_implemented_error.clone();
+        return Err(vm.new_error(&class, message));
+    }
+    vm.iter(&args.positional[0])
+}
+
+/// `len(object)`.
+fn len(vm: &mut Vm, args: Args) -> PyResult {
+    let object = one_argument(vm, args, "len")?;
+    let len = vm.len(&object)?;
+    Ok(vm.new_int(len as i64))
+}
+
+/// `max(iterable, *, key=None, default=...)` or `max(a, b, *args, key=None)`.
+/// `locals()`: the local namespace. A function keeps its variables in
+/// fast slots, so a call from a function is answered by the frame, which
+/// gives a new dict of them; this is the answer elsewhere.
+fn locals(vm: &mut Vm, args: Args) -> PyResult {
+    no_arguments(vm, &args, "locals")?;
+    Ok(current_locals(vm))
+}
+
+/// The local namespace of the running code, as `locals()` gives it.
+fn current_locals(vm: &mut Vm) -> ObjectRef {
+    let (globals, locals) = vm.current_namespaces();
+    locals.unwrap_or(globals)
+}
+
+/// What `locals()`, `vars()` or `dir()`, the function named `name`, gives
+/// when called without arguments in a frame whose locals are `locals`.
+pub(super) fn call_with_locals(vm: &mut Vm, name: &str, locals: ObjectRef) -> PyResult {
+    match name {
+        "dir" => names_of(vm, &locals),
+        _ => Ok(locals),
+    }
+}
+
+fn max(vm: &mut Vm, args: Args) -> PyResult {
+    min_max(vm, args, "max")
+}
+
+/// `min(iterable, *, key=None, default=...)` or `min(a, b, *args, key=None)`.
+fn min(vm: &mut Vm, args: Args) -> PyResult {
+    min_max(vm, args, "min")
+}
+
+fn min_max(vm: &mut Vm, args: Args, name: &str) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let mut keywords = keyword_arguments(vm, keywords, name, &["key", "default"])?.into_iter();
+    let (key, default) = (keywords.next().unwrap(), keywords.next().unwrap());
+    let key = key.filter(|key| !Vm::is(key, &vm.none()));
+    let items = match positional.len() {
+        0 => {
+            let message = format!("{} expected at least 1 argument, got 0", name);
+            return Err(vm.new_type_error(message));
+        }
+        1 => vm.iterate(&positional[0])?,
+        _ if default.is_some() => {
+            let message = format!(
+                "Cannot specify a default for {}() with multiple positional arguments",
+                name
+            );
+            return Err(vm.new_type_error(message));
+        }
+        _ => positional,
+    };
+    let op = if name == "max" {
+        ::ast::CmpOperator::Gt
+    } else {
+        ::ast::CmpOperator::Lt
+    };
+    let mut best: Option<(ObjectRef, ObjectRef)> = None;
+    for item in items {
+        let item_key = match key {
+            Some(ref key) => vm.call(key, Args::new(vec![item.clone()]))?,
+            None => item.clone(),
+        };
+        let better = match best {
+            Some((_, ref best_key)) => {
+                let result = vm.compare(&item_key, op, best_key)?;
+                vm.is_true(&result)?
+            }
+            None => true,
+        };
+        if better {
+            best = Some((item, item_key));
+        }
+    }
+    match (best, default) {
+        (Some((item, _)), _) => Ok(item),
+        (None, Some(default)) => Ok(default),
+        (None, None) => {
+            let message = format!("{}() arg is an empty sequence", name);
+            Err(vm.new_value_error(message))
+        }
+    }
+}
+
+/// `next(iterator[, default])`.
+fn next(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "next")?;
+    check_count(vm, &args, "next", 1, 2)?;
+    let iterator = &args.positional[0];
+    let is_iterator = match iterator.payload {
+        Payload::Iterator(_) => true,
+        Payload::Generator(ref generator) => !generator.is_coroutine(),
+        _ => vm.python_method(iterator, "__next__").is_some(),
+    };
+    if !is_iterator {
+        let message = format!("'{}' object is not an iterator", iterator.type_name());
+        return Err(vm.new_type_error(message));
+    }
+    match (vm.next(iterator)?, args.positional.get(1)) {
+        (Some(item), _) => Ok(item),
+        (None, Some(default)) => Ok(default.clone()),
+        (None, None) => {
+            let none = vm.none();
+            Err(vm.new_stop_iteration(none))
+        }
+    }
+}
+
+/// `ord(c)`.
+fn ord(vm: &mut Vm, args: Args) -> PyResult {
+    let c = one_argument(vm, args, "ord")?;
+    let (code, length) = match c.payload {
+        Payload::Str(ref value) => {
+            let mut chars = value.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => (Some(c as i64), 1),
+                _ => (None, value.chars().count()),
+            }
+        }
+        Payload::Bytes(ref value) if value.len() == 1 => (Some(i64::from(value[0])), 1),
+        Payload::Bytes(ref value) => (None, value.len()),
+        _ => {
+            let message = format!(
+                "ord() expected string of length 1, but {} found",
+                c.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    match code {
+        Some(code) => Ok(vm.new_int(code)),
+        None => {
+            let message = format!(
+                "ord() expected a character, but string of length {} found",
+                length
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// `pow(base, exp, mod=None)`. With a modulus, all three are ints, and a
+/// negative exponent raises the inverse of the base.
+fn pow(vm: &mut Vm, args: Args) -> PyResult {
+    let values = arguments(vm, args, "pow", &["base", "exp", "mod"], 2)?;
+    let (base, exp) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    let modulus = match values[2] {
+        Some(ref modulus) if !Vm::is(modulus, &vm.none()) => modulus,
+        _ => return vm.binary_op(base, ::ast::Operator::Pow, exp, false),
+    };
+    let (base, exp, modulus) = match (&base.payload, &exp.payload, &modulus.payload) {
+        (&Payload::Int(base), &Payload::Int(exp), &Payload::Int(modulus)) => (base, exp, modulus),
+        _ => {
+            let message = "pow() 3rd argument not allowed unless all arguments are integers";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+    };
+    if modulus == 0 {
+        return Err(vm.new_value_error("pow() 3rd argument cannot be 0".to_string()));
+    }
+    let modulus = i128::from(modulus);
+    let size = modulus.abs();
+    let mut base = i128::from(base).rem_euclid(size);
+    if exp < 0 {
+        base = match modular_inverse(base, size) {
+            Some(inverse) => inverse,
+            None => {
+                let message = "base is not invertible for the given modulus".to_string();
+                return Err(vm.new_value_error(message));
+            }
+        };
+    }
+    let mut exp = exp.unsigned_abs();
+    let mut result = 1 % size;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = result * base % size;
+        }
+        base = base * base % size;
+        exp >>= 1;
+    }
+    // The result takes the sign of the modulus, as `%` gives it.
+    if modulus < 0 && result != 0 {
+        result -= size;
+    }
+    Ok(vm.new_int(result as i64))
+}
+
+/// The inverse of `value` modulo `size`, if they are coprime.
+fn modular_inverse(value: i128, size: i128) -> Option<i128> {
+    let (mut old_r, mut r) = (value, size);
+    let (mut old_s, mut s) = (1, 0);
+    while r != 0 {
+        let quotient = old_r / r;
+        (old_r, r) = (r, old_r - quotient * r);
+        (old_s, s) = (s, old_s - quotient * s);
+    }
+    if old_r == 1 {
+        Some(old_s.rem_euclid(size))
+    } else {
+        None
+    }
+}
+
+/// `print(*objects, sep=' ', end='\n', file=None, flush=False)`. Without a
+/// file, writes to `sys.stdout`.
+fn print(vm: &mut Vm, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let mut keywords =
+        keyword_arguments(vm, keywords, "print", &["sep", "end", "file", "flush"])?.into_iter();
+    let mut separators = Vec::with_capacity(2);
+    for (name, default) in [("sep", " "), ("end", "\n")] {
+        let value = keywords.next().unwrap();
+        separators.push(match value {
+            None => default.to_string(),
+            Some(ref value) if Vm::is(value, &vm.none()) => default.to_string(),
+            Some(ref value) => match value.as_str() {
+                Some(text) => text.to_string(),
+                None => {
+                    let message = format!(
+                        "{} must be None or a string, not {}",
+                        name,
+                        value.type_name()
+                    );
+                    return Err(vm.new_type_error(message));
+                }
+            },
+        });
+    }
+    let file = keywords
+        .next()
+        .unwrap()
+        .filter(|file| !Vm::is(file, &vm.none()));
+    let flush = match keywords.next().unwrap() {
+        Some(flush) => vm.is_true(&flush)?,
+        None => false,
+    };
+    let mut pieces = Vec::with_capacity(2 * positional.len() + 1);
+    for (index, object) in positional.iter().enumerate() {
+        if index > 0 {
+            pieces.push(separators[0].clone());
+        }
+        pieces.push(vm.str(object)?);
+    }
+    pieces.push(separators[1].clone());
+    let pieces: Vec<&str> = pieces.iter().map(String::as_str).collect();
+    match file {
+        Some(file) => sys::write_file(vm, &file, &pieces, flush)?,
+        None => sys::write_standard(vm, "stdout", &pieces, flush)?,
+    }
+    Ok(vm.none())
+}
+
+/// Shows the value of an expression statement at the interactive prompt,
+/// as `sys.displayhook` does: writes its repr unless it is `None`, and
+/// keeps it as `_` in the builtins.
+pub(super) fn display_value(vm: &mut Vm, value: &ObjectRef) -> PyResult<()> {
+    if Vm::is(value, &vm.none()) {
+        return Ok(());
+    }
+    let builtins = vm.builtins().clone();
+    let none = vm.none();
+    vm.dict_set_str(builtins.as_dict().unwrap(), "_", none);
+    let text = vm.repr(value)?;
+    sys::write_standard(vm, "stdout", &[&text, "\n"], false)?;
+    vm.dict_set_str(builtins.as_dict().unwrap(), "_", value.clone());
+    Ok(())
+}
+
+/// `repr(object)`.
+fn repr(vm: &mut Vm, args: Args) -> PyResult {
+    let object = one_argument(vm, args, "repr")?;
+    let text = vm.repr(&object)?;
+    Ok(vm.new_str(&text))
+}
+
+/// `round(number, ndigits=None)`: to an int without `ndigits`, and to
+/// the type of `number` with it, halfway cases going to the even choice.
+fn round(vm: &mut Vm, args: Args) -> PyResult {
+    let values = arguments(vm, args, "round", &["number", "ndigits"], 1)?;
+    let number = values[0].as_ref().unwrap();
+    let ndigits = match values[1] {
+        Some(ref ndigits) if !Vm::is(ndigits, &vm.none()) => Some(index_value(vm, ndigits)?),
+        _ => None,
+    };
+    match (&number.payload, ndigits) {
+        (&Payload::Int(value), None) => Ok(vm.new_int(value)),
+        (&Payload::Int(value), Some(ndigits)) => {
+            let scale = match u32::try_from(ndigits.unsigned_abs())
+                .ok()
+                .and_then(|power| 10i128.checked_pow(power))
+            {
+                Some(scale) if ndigits < 0 => scale,
+                Some(_) => return Ok(vm.new_int(value)),
+                None if ndigits >= 0 => return Ok(vm.new_int(value)),
+                None => return Ok(vm.new_int(0)),
+            };
+            let value = i128::from(value);
+            let (quotient, remainder) = (value.div_euclid(scale), value.rem_euclid(scale));
+            let up = 2 * remainder > scale || (2 * remainder == scale && quotient % 2 != 0);
+            let rounded = (quotient + i128::from(up)) * scale;
+            match i64::try_from(rounded) {
+                Ok(rounded) => Ok(vm.new_int(rounded)),
+                Err(_) => Err(vm.int_overflow()),
+            }
+        }
+        (&Payload::Float(value), None) => {
+            let rounded = float_to_int(vm, value.round_ties_even())?;
+            Ok(vm.new_int(rounded))
+        }
+        (&Payload::Float(value), Some(ndigits)) => Ok(vm.new_float(round_float(value, ndigits))),
+        _ => {
+            let method = match vm.python_method(number, "__round__") {
+                Some(method) => method,
+                None => {
+                    let message = format!(
+                        "type {} doesn't define __round__ method",
+                        number.type_name()
+                    );
+                    return Err(vm.new_type_error(message));
+                }
+            };
+            let args = match values[1] {
+                Some(ref ndigits) => vec![ndigits.clone()],
+                None => Vec::new(),
+            };
+            vm.call(&method, Args::new(args))
+        }
+    }
+}
+
+/// `value` rounded to `ndigits` decimal places, from its exact value, as
+/// formatting with that many places rounds it.
+fn round_float(value: f64, ndigits: i64) -> f64 {
+    if !value.is_finite() || value == 0.0 || ndigits > 330 {
+        return value;
+    }
+    if ndigits >= 0 {
+        let text = format!("{:.*}", ndigits as usize, value);
+        return text.parse().unwrap_or(value);
+    }
+    if ndigits < -330 {
+        return 0.0 * value;
+    }
+    let scale = 10f64.powi(-ndigits as i32);
+    let rounded = (value / scale).round_ties_even() * scale;
+    if rounded == 0.0 {
+        // Keeps the sign of zero.
+        0.0 * value
+    } else {
+        rounded
+    }
+}
+
+/// `setattr(object, name, value)`.
+fn setattr(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "setattr")?;
+    check_count(vm, &args, "setattr", 3, 3)?;
+    let name = attribute_name(vm, &args.positional[1])?;
+    vm.setattr(&args.positional[0], &name, args.positional[2].clone())?;
+    Ok(vm.none())
+}
+
+/// `sorted(iterable, /, *, key=None, reverse=False)`.
+fn sorted(vm: &mut Vm, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let mut keywords = keyword_arguments(vm, keywords, "sort", &["key", "reverse"])?.into_iter();
+    if positional.len() != 1 {
+        let message = format!("sorted expected 1 argument, got {}", positional.len());
+        return Err(vm.new_type_error(message));
+    }
+    let mut items = vm.iterate(&positional[0])?;
+    let key = keywords.next().unwrap();
+    let reverse = match keywords.next().unwrap() {
+        Some(reverse) => index_value(vm, &reverse)? != 0,
+        None => false,
+    };
+    vm.sort(&mut items, key.as_ref(), reverse)?;
+    Ok(vm.new_list(items))
+}
+
+/// `sum(iterable, /, start=0)`.
+fn sum(vm: &mut Vm, args: Args) -> PyResult {
+    let Args {
+        mut positional,
+        keywords,
+    } = args;
+    let start = keyword_arguments(vm, keywords, "sum", &["start"])?
+        .pop()
+        .unwrap();
+    let count = positional.len();
+    if count == 0 {
+        let message = "sum() takes at least 1 positional argument (0 given)".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    if count + start.iter().count() > 2 {
+        let message = format!(
+            "sum() takes at most 2 arguments ({} given)",
+            count + start.iter().count()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let start = match start {
+        Some(start) => start,
+        None if count == 2 => positional.pop().unwrap(),
+        None => vm.new_int(0),
+    };
+    match start.payload {
+        Payload::Str(_) => {
+            let message = "sum() can't sum strings [use ''.join(seq) instead]";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+        Payload::Bytes(_) => {
+            let message = "sum() can't sum bytes [use b''.join(seq) instead]";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+        _ => {}
+    }
+    let iterator = vm.iter(&positional[0])?;
+    let mut total = start;
+    while let Some(item) = vm.next(&iterator)? {
+        total = vm.binary_op(&total, ::ast::Operator::Add, &item, false)?;
+    }
+    Ok(total)
+}
+
+/// `vars([object])`: the `__dict__` of `object`, or the local namespace.
+fn vars(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "vars")?;
+    check_count(vm, &args, "vars", 0, 1)?;
+    let object = match args.positional.first() {
+        Some(object) => object,
+        None => return Ok(current_locals(vm)),
+    };
+    match vm.getattr(object, "__dict__") {
+        Ok(dict) => Ok(dict),
+        Err(error) if Vm::is_instance(&error, &vm.exceptions.attribute_error) => {
+            let message = "vars() argument must have __dict__ attribute".to_string();
+            Err(vm.new_type_error(message))
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// `bool(x=False)`.
+pub(super) fn bool_new(vm: &mut Vm, _class: &ObjectRef, args: Args) -> PyResult {
+    no_keywords(vm, &args, "bool")?;
+    check_count(vm, &args, "bool", 0, 1)?;
+    let value = match args.positional.first() {
+        Some(value) => vm.is_true(value)?,
+        None => false,
+    };
+    Ok(vm.new_bool(value))
+}
+
+/// `int(x=0)` or `int(x, base=10)`.
+pub(super) fn int_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let mut base = keyword_arguments(vm, keywords, "int", &["base"])?
+        .pop()
+        .unwrap();
+    let count = positional.len() + base.iter().count();
+    if count > 2 {
+        let message = format!("int() takes at most 2 arguments ({} given)", count);
+        return Err(vm.new_type_error(message));
+    }
+    let mut positional = positional.into_iter();
+    let x = positional.next();
+    base = base.or_else(|| positional.next());
+    let value = match (x, base) {
+        (None, Some(_)) => {
+            let message = "int() missing string argument".to_string();
+            return Err(vm.new_type_error(message));
+        }
+        (None, None) => 0,
+        (Some(x), Some(base)) => {
+            let base = index_value(vm, &base)?;
+            if base != 0 && !(2..=36).contains(&base) {
+                let message = "int() base must be >= 2 and <= 36, or 0".to_string();
+                return Err(vm.new_value_error(message));
+            }
+            match x.payload {
+                Payload::Str(ref text) => parse_int(vm, text, base as u32)?,
+                Payload::Bytes(ref bytes) => {
+                    let text = String::from_utf8_lossy(bytes).into_owned();
+                    parse_int(vm, &text, base as u32)?
+                }
+                _ => {
+                    let message = "int() can't convert non-string with explicit base";
+                    return Err(vm.new_type_error(message.to_string()));
+                }
+            }
+        }
+        (Some(x), None) => match x.payload {
+            Payload::Int(value) => value,
+            Payload::Float(value) => float_to_int(vm, value)?,
+            Payload::Str(ref text) => parse_int(vm, text, 10)?,
+            Payload::Bytes(ref bytes) => {
+                let text = String::from_utf8_lossy(bytes).into_owned();
+                parse_int(vm, &text, 10)?
+            }
+            _ => {
+                let method = vm
+                    .python_method(&x, "__int__")
+                    .or_else(|| vm.python_method(&x, "__index__"));
+                match method {
+                    Some(method) => {
+                        let result = vm.call(&method, Args::default())?;
+                        match result.payload {
+                            Payload::Int(value) => value,
+                            _ => {
+                                let message = format!(
+                                    "__int__ returned non-int (type {})",
+                                    result.type_name()
+                                );
+                                return Err(vm.new_type_error(message));
+                            }
+                        }
+                    }
+                    None => {
+                        let message = format!(
+                            "int() argument must be a string, a bytes-like object or a real \
+                             number, not '{}'",
+                            x.type_name()
+                        );
+                        return Err(vm.new_type_error(message));
+                    }
+                }
+            }
+        },
+    };
+    if Vm::is(class, &vm.types.int) {
+        return Ok(vm.new_int(value));
+    }
+    Ok(new_instance(vm, class, Payload::Int(value)))
+}
+
+/// Truncates a float towards zero, as `int(x)` does.
+fn float_to_int(vm: &mut Vm, value: f64) -> PyResult<i64> {
+    if value.is_nan() {
+        let message = "cannot convert float NaN to integer".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    if value.is_infinite() {
+        let message = "cannot convert float infinity to integer".to_string();
+        return Err(vm.new_overflow_error(message));
+    }
+    let value = value.trunc();
+    if !(-9_223_372_036_854_775_808.0..9_223_372_036_854_775_808.0).contains(&value) {
+        return Err(vm.int_overflow());
+    }
+    Ok(value as i64)
+}
+
+/// Parses an integer literal as `int(text, base)` does: surrounded by
+/// whitespace, with a sign, underscores between digits, and with base 0 a
+/// prefix that gives the base.
+fn parse_int(vm: &mut Vm, text: &str, base: u32) -> PyResult<i64> {
+    let invalid = |vm: &mut Vm| {
+        let message = format!(
+            "invalid literal for int() with base {}: {}",
+            base,
+            repr_str(text)
+        );
+        vm.new_value_error(message)
+    };
+    let ascii = ascii_digits_and_spaces(text);
+    let trimmed = ascii.trim();
+    let (negative, unsigned) = match trimmed.as_bytes().first() {
+        Some(b'-') => (true, &trimmed[1..]),
+        Some(b'+') => (false, &trimmed[1..]),
+        _ => (false, trimmed),
+    };
+    let lower = unsigned.to_ascii_lowercase();
+    let prefixed = |prefix: &str, prefix_base: u32| {
+        (base == 0 || base == prefix_base) && lower.starts_with(prefix)
+    };
+    let (radix, digits, after_prefix) = if prefixed("0x", 16) {
+        (16, &unsigned[2..], true)
+    } else if prefixed("0o", 8) {
+        (8, &unsigned[2..], true)
+    } else if prefixed("0b", 2) {
+        (2, &unsigned[2..], true)
+    } else if base == 0 {
+        (10, unsigned, false)
+    } else {
+        (base, unsigned, false)
+    };
+    // An underscore may follow a prefix, but otherwise only separates
+    // digits.
+    let digits = match digits.strip_prefix('_') {
+        Some(rest) if after_prefix => rest,
+        _ => digits,
+    };
+    if digits.is_empty()
+        || digits.starts_with('_')
+        || digits.ends_with('_')
+        || digits.contains("__")
+    {
+        return Err(invalid(vm));
+    }
+    let digits: String = digits.chars().filter(|&c| c != '_').collect();
+    if !digits.chars().all(|c| c.is_digit(radix)) {
+        return Err(invalid(vm));
+    }
+    // With base 0, a decimal literal may not have leading zeros.
+    if base == 0 && !after_prefix && digits.starts_with('0') && digits.bytes().any(|b| b != b'0') {
+        return Err(invalid(vm));
+    }
+    let magnitude = match u64::from_str_radix(&digits, radix) {
+        Ok(magnitude) => magnitude,
+        Err(_) => return Err(vm.int_overflow()),
+    };
+    let value = if negative {
+        0i64.checked_sub_unsigned(magnitude)
+    } else {
+        i64::try_from(magnitude).ok()
+    };
+    value.ok_or_else(|| vm.int_overflow())
+}
+
+/// `float(x=0.0)`.
+pub(super) fn float_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    no_keywords(vm, &args, "float")?;
+    check_count(vm, &args, "float", 0, 1)?;
+    let value = match args.positional.first() {
+        None => 0.0,
+        Some(x) => match x.payload {
+            Payload::Int(value) => value as f64,
+            Payload::Float(value) => value,
+            Payload::Str(ref text) => parse_float(vm, text)?,
+            _ => match vm.python_method(x, "__float__") {
+                Some(method) => {
+                    let result = vm.call(&method, Args::default())?;
+                    match result.payload {
+                        Payload::Float(value) => value,
+                        _ => {
+                            let message = format!(
+                                "{}.__float__ returned non-float (type {})",
+                                x.type_name(),
+                                result.type_name()
+                            );
+                            return Err(vm.new_type_error(message));
+                        }
+                    }
+                }
+                None => {
+                    let message = format!(
+                        "float() argument must be a string or a real number, not '{}'",
+                        x.type_name()
+                    );
+                    return Err(vm.new_type_error(message));
+                }
+            },
+        },
+    };
+    if Vm::is(class, &vm.types.float) {
+        return Ok(vm.new_float(value));
+    }
+    Ok(new_instance(vm, class, Payload::Float(value)))
+}
+
+/// Parses a float as `float(text)` does: surrounded by whitespace, with a
+/// sign, underscores between digits, `inf`, `infinity` or `nan` in any
+/// case, and digits of any script.
+fn parse_float(vm: &mut Vm, text: &str) -> PyResult<f64> {
+    let ascii = ascii_digits_and_spaces(text);
+    match float_digits(ascii.trim()) {
+        Some(value) => Ok(value),
+        None => {
+            let message = format!("could not convert string to float: {}", repr_str(text));
+            Err(vm.new_value_error(message))
+        }
+    }
+}
+
+/// Parses the ASCII text of a float, which may have underscores between
+/// digits.
+fn float_digits(text: &str) -> Option<f64> {
+    let bytes = text.as_bytes();
+    // Underscores may only separate digits.
+    let underscores_valid = bytes.iter().enumerate().all(|(index, &byte)| {
+        byte != b'_'
+            || (index > 0
+                && bytes[index - 1].is_ascii_digit()
+                && bytes.get(index + 1).is_some_and(u8::is_ascii_digit))
+    });
+    let digits: String = text.chars().filter(|&c| c != '_').collect();
+    match digits.parse::<f64>() {
+        Ok(value) if underscores_valid => Some(value),
+        _ => None,
+    }
+}
+
+/// `complex(real=0, imag=0)`, or `complex(string)`.
+pub(super) fn complex_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let values = arguments(vm, args, "complex", &["real", "imag"], 0)?;
+    let (real, imag) = match (&values[0], &values[1]) {
+        (Some(real), imag) if real.as_str().is_some() => {
+            if imag.is_some() {
+                let message = "complex() can't take second arg if first is a string";
+                return Err(vm.new_type_error(message.to_string()));
+            }
+            let text = real.as_str().unwrap().to_string();
+            match parse_complex(&text) {
+                Some(value) => value,
+                None => {
+                    let message = "complex() arg is a malformed string".to_string();
+                    return Err(vm.new_value_error(message));
+                }
+            }
+        }
+        (real, imag) => {
+            let real = match real {
+                Some(real) => complex_part(vm, real, "first argument must be a string or")?,
+                None => (0.0, 0.0),
+            };
+            let imag = match imag {
+                Some(imag) if imag.as_str().is_some() => {
+                    let message = "complex() second arg can't be a string";
+                    return Err(vm.new_type_error(message.to_string()));
+                }
+                Some(imag) => complex_part(vm, imag, "second argument must be")?,
+                None => (0.0, 0.0),
+            };
+            (real.0 - imag.1, real.1 + imag.0)
+        }
+    };
+    if Vm::is(class, &vm.types.complex) {
+        return Ok(vm.new_complex(real, imag));
+    }
+    Ok(new_instance(vm, class, Payload::Complex { real, imag }))
+}
+
+/// A number argument of `complex()` as a complex number; `expected` says
+/// what the argument must be in the error for other types.
+fn complex_part(vm: &mut Vm, value: &ObjectRef, expected: &str) -> PyResult<(f64, f64)> {
+    match value.payload {
+        Payload::Int(value) => return Ok((value as f64, 0.0)),
+        Payload::Float(value) => return Ok((value, 0.0)),
+        Payload::Complex { real, imag } => return Ok((real, imag)),
+        _ => {}
+    }
+    for name in ["__complex__", "__float__", "__index__"] {
+        if let Some(method) = vm.python_method(value, name) {
+            let result = vm.call(&method, Args::default())?;
+            match (name, &result.payload) {
+                ("__complex__", &Payload::Complex { real, imag }) => return Ok((real, imag)),
+                ("__float__", &Payload::Float(value)) => return Ok((value, 0.0)),
+                ("__index__", &Payload::Int(value)) => return Ok((value as f64, 0.0)),
+                _ => {
+                    let message = format!(
+                        "{}.{} returned non-{} (type {})",
+                        value.type_name(),
+                        name,
+                        &name[2..name.len() - 2].replace("index", "int"),
+                        result.type_name()
+                    );
+                    return Err(vm.new_type_error(message));
+                }
+            }
+        }
+    }
+    let message = format!(
+        "complex() {} a number, not '{}'",
+        expected,
+        value.type_name()
+    );
+    Err(vm.new_type_error(message))
+}
+
+/// Parses a complex number as `complex(text)` does: a real part, an
+/// imaginary part ending in `j`, or both joined by their sign, optionally
+/// in parentheses and surrounded by whitespace.
+fn parse_complex(text: &str) -> Option<(f64, f64)> {
+    let ascii = ascii_digits_and_spaces(text);
+    let mut text = ascii.trim();
+    if let Some(inner) = text
+        .strip_prefix('(')
+        .and_then(|text| text.strip_suffix(')'))
+    {
+        text = inner.trim();
+    }
+    let body = match text.strip_suffix(['j', 'J']) {
+        Some(body) => body,
+        None => return Some((float_digits(text)?, 0.0)),
+    };
+    // The imaginary part starts at the last sign that is not that of an
+    // exponent.
+    let bytes = body.as_bytes();
+    let split = (1..bytes.len()).rev().find(|&index| {
+        matches!(bytes[index], b'+' | b'-') && !matches!(bytes[index - 1], b'e' | b'E')
+    });
+    let (real, imag) = match split {
+        Some(split) => (float_digits(&body[..split])?, &body[split..]),
+        None => (0.0, body),
+    };
+    let imag = match imag {
+        "" | "+" => 1.0,
+        "-" => -1.0,
+        imag => float_digits(imag)?,
+    };
+    Some((real, imag))
+}
+
+/// `str(object='')`.
+pub(super) fn str_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let keywords = keyword_arguments(vm, keywords, "str", &["object", "encoding", "errors"])?;
+    let count = positional.len() + keywords.iter().flatten().count();
+    if count > 3 {
+        let message = format!("str() takes at most 3 arguments ({} given)", count);
+        return Err(vm.new_type_error(message));
+    }
+    let mut positional = positional.into_iter();
+    let object = positional.next().or_else(|| keywords[0].clone());
+    let encoding = positional.next().or_else(|| keywords[1].clone());
+    let errors = positional.next().or_else(|| keywords[2].clone());
+    let text = match object {
+        Some(ref object) if encoding.is_some() || errors.is_some() => {
+            let data = match object.payload {
+                Payload::Bytes(ref data) => data,
+                Payload::Str(_) => {
+                    let message = "decoding str is not supported".to_string();
+                    return Err(vm.new_type_error(message));
+                }
+                _ => {
+                    let message = format!(
+                        "decoding to str: need a bytes-like object, {} found",
+                        object.type_name()
+                    );
+                    return Err(vm.new_type_error(message));
+                }
+            };
+            decode_arguments(vm, data, "str", encoding.as_ref(), errors.as_ref())?
+        }
+        Some(ref object) if Vm::is(class, &vm.types.str) && Vm::is(&object.class(), class) => {
+            return Ok(object.clone())
+        }
+        Some(ref object) => vm.str(object)?,
+        None => String::new(),
+    };
+    if Vm::is(class, &vm.types.str) {
+        return Ok(vm.new_str(&text));
+    }
+    Ok(new_instance(vm, class, Payload::Str(text)))
+}
+
+/// `bytes(source=b'', encoding='utf-8', errors='strict')`.
+pub(super) fn bytes_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let exact = Vm::is(class, &vm.types.bytes);
+    if let [ref source] = args.positional[..] {
+        if exact && args.keywords.is_empty() && Vm::is(&source.class(), class) {
+            return Ok(source.clone());
+        }
+    }
+    let data = byte_source(vm, args, "bytes")?;
+    if exact {
+        return Ok(vm.new_bytes(data));
+    }
+    Ok(new_instance(vm, class, Payload::Bytes(data)))
+}
+
+/// `bytearray(source=b'', encoding='utf-8', errors='strict')`.
+pub(super) fn bytearray_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let data = byte_source(vm, args, "bytearray")?;
+    Ok(new_instance(
+        vm,
+        class,
+        Payload::ByteArray(RefCell::new(data)),
+    ))
+}
+
+/// The data of a new `bytes` or `bytearray`, the type `name` says: a
+/// string encoded, that many zero bytes for an int, the data of a
+/// bytes-like object, or the ints of an iterable.
+fn byte_source(vm: &mut Vm, args: Args, name: &str) -> PyResult<Vec<u8>> {
+    let values = arguments(vm, args, name, &["source", "encoding", "errors"], 0)?;
+    let source = match values[0] {
+        Some(ref source) => source,
+        None if values[1].is_some() => {
+            let message = "encoding without a string argument".to_string();
+            return Err(vm.new_type_error(message));
+        }
+        None if values[2].is_some() => {
+            let message = "errors without a string argument".to_string();
+            return Err(vm.new_type_error(message));
+        }
+        None => return Ok(Vec::new()),
+    };
+    if let Payload::Str(_) = source.payload {
+        let encoding = match values[1] {
+            Some(ref encoding) => encoding.clone(),
+            None => {
+                let message = "string argument without an encoding".to_string();
+                return Err(vm.new_type_error(message));
+            }
+        };
+        let encode = vm.getattr(source, "encode")?;
+        let mut positional = vec![encoding];
+        positional.extend(values[2].clone());
+        let encoded = vm.call(&encode, Args::new(positional))?;
+        return Ok(bytes_like(&encoded).map_or_else(Vec::new, |data| data.into_owned()));
+    }
+    for (value, message) in [
+        (&values[1], "encoding without a string argument"),
+        (&values[2], "errors without a string argument"),
+    ] {
+        if value.is_some() {
+            return Err(vm.new_type_error(message.to_string()));
+        }
+    }
+    if let Some(data) = bytes_like(source) {
+        return Ok(data.into_owned());
+    }
+    if name == "bytes" {
+        if let Some(method) = vm.python_method(source, "__bytes__") {
+            let result = vm.call(&method, Args::default())?;
+            return match result.payload {
+                Payload::Bytes(ref data) => Ok(data.clone()),
+                _ => {
+                    let message =
+                        format!("__bytes__ returned non-bytes (type {})", result.type_name());
+                    Err(vm.new_type_error(message))
+                }
+            };
+        }
+    }
+    let is_count = matches!(source.payload, Payload::Int(_))
+        || vm.python_method(source, "__index__").is_some();
+    if is_count {
+        let count = index_value(vm, source)?;
+        return match usize::try_from(count) {
+            Ok(count) => Ok(vec![0; count]),
+            Err(_) => Err(vm.new_value_error("negative count".to_string())),
+        };
+    }
+    if !vm.is_iterable(source) {
+        let message = format!("cannot convert '{}' object to {}", source.type_name(), name);
+        return Err(vm.new_type_error(message));
+    }
+    let iterator = vm.iter(source)?;
+    let mut data = Vec::new();
+    while let Some(item) = vm.next(&iterator)? {
+        let value = index_value(vm, &item)?;
+        match u8::try_from(value) {
+            Ok(byte) => data.push(byte),
+            Err(_) => {
+                let message = "bytes must be in range(0, 256)".to_string();
+                return Err(vm.new_value_error(message));
+            }
+        }
+    }
+    Ok(data)
+}
+
+/// `memoryview(object)`, a view of the data of a bytes or bytearray
+/// object.
+pub(super) fn memoryview_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let values = arguments(vm, args, "memoryview", &["object"], 1)?;
+    let object = values.into_iter().next().unwrap().unwrap();
+    let object = match object.payload {
+        Payload::Bytes(_) | Payload::ByteArray(_) => object,
+        Payload::MemoryView {
+            object: ref viewed,
+            ref released,
+        } if !released.get() => viewed.clone(),
+        _ => {
+            let message = format!(
+                "memoryview: a bytes-like object is required, not '{}'",
+                object.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let payload = Payload::MemoryView {
+        object,
+        released: Cell::new(false),
+    };
+    Ok(new_instance(vm, class, payload))
+}
+
+/// The items of the only, optional argument of a container constructor.
+fn container_items(vm: &mut Vm, args: Args, name: &str) -> PyResult<Vec<ObjectRef>> {
+    no_keywords(vm, &args, name)?;
+    check_count(vm, &args, name, 0, 1)?;
+    match args.positional.first() {
+        Some(iterable) => vm.iterate(iterable),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// `tuple(iterable=())`.
+pub(super) fn tuple_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let exact = Vm::is(class, &vm.types.tuple);
+    if let Some(iterable) = args.positional.first() {
+        if exact && Vm::is(&iterable.class(), class) && args.positional.len() == 1 {
+            no_keywords(vm, &args, "tuple")?;
+            return Ok(iterable.clone());
+        }
+    }
+    let items = container_items(vm, args, "tuple")?;
+    if exact {
+        return Ok(vm.new_tuple(items));
+    }
+    Ok(new_instance(vm, class, Payload::Tuple(items)))
+}
+
+/// `list(iterable=())`.
+pub(super) fn list_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let items = container_items(vm, args, "list")?;
+    Ok(new_instance(vm, class, Payload::List(RefCell::new(items))))
+}
+
+/// `set(iterable=())` and `frozenset(iterable=())`.
+pub(super) fn set_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let name = if Vm::is_subclass(class, &vm.types.frozenset) {
+        "frozenset"
+    } else {
+        "set"
+    };
+    let items = container_items(vm, args, name)?;
+    let members = RefCell::new(Dict::new());
+    for item in items {
+        vm.set_add(&members, item)?;
+    }
+    Ok(new_instance(vm, class, Payload::Set(members)))
+}
+
+/// `dict(mapping_or_iterable=(), **kwargs)`.
+pub(super) fn dict_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    check_count(vm, &args, "dict", 0, 1)?;
+    let entries = RefCell::new(Dict::new());
+    if let Some(source) = args.positional.first() {
+        vm.dict_update(&entries, source)?;
+    }
+    for (name, value) in args.keywords {
+        let name = vm.new_str(&name);
+        vm.dict_set(&entries, name, value)?;
+    }
+    Ok(new_instance(vm, class, Payload::Dict(entries)))
+}
+
+/// `range(stop)` or `range(start, stop[, step])`.
+pub(super) fn range_new(vm: &mut Vm, _class: &ObjectRef, args: Args) -> PyResult {
+    no_keywords(vm, &args, "range")?;
+    check_count(vm, &args, "range", 1, 3)?;
+    let mut bounds = Vec::with_capacity(3);
+    for bound in &args.positional {
+        bounds.push(index_value(vm, bound)?);
+    }
+    let (start, stop, step) = match bounds[..] {
+        [stop] => (0, stop, 1),
+        [start, stop] => (start, stop, 1),
+        [start, stop, step] => (start, stop, step),
+        _ => unreachable!(),
+    };
+    if step == 0 {
+        let message = "range() arg 3 must not be zero".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    Ok(vm.new_range(start, stop, step))
+}
+
+/// `slice(stop)` or `slice(start, stop[, step])`.
+pub(super) fn slice_new(vm: &mut Vm, _class: &ObjectRef, args: Args) -> PyResult {
+    no_keywords(vm, &args, "slice")?;
+    check_count(vm, &args, "slice", 1, 3)?;
+    let none = vm.none();
+    let (start, stop, step) = match args.positional[..] {
+        [ref stop] => (none.clone(), stop.clone(), none),
+        [ref start, ref stop] => (start.clone(), stop.clone(), none),
+        [ref start, ref stop, ref step] => (start.clone(), stop.clone(), step.clone()),
+        _ => unreachable!(),
+    };
+    Ok(vm.new_slice(start, stop, step))
+}
+
+/// `enumerate(iterable, start=0)`.
+pub(super) fn enumerate_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let mut keywords =
+        keyword_arguments(vm, keywords, "enumerate", &["iterable", "start"])?.into_iter();
+    let count = positional.len() + keywords.as_slice().iter().flatten().count();
+    if count > 2 {
+        let message = format!("enumerate() takes at most 2 arguments ({} given)", count);
+        return Err(vm.new_type_error(message));
+    }
+    let mut positional = positional.into_iter();
+    let iterable = match positional.next().or_else(|| keywords.next().unwrap()) {
+        Some(iterable) => iterable,
+        None => {
+            let message = "enumerate() missing required argument 'iterable'".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let start = match positional.next().or_else(|| keywords.last().unwrap()) {
+        Some(start) => index_value(vm, &start)?,
+        None => 0,
+    };
+    let iterator = vm.iter(&iterable)?;
+    let state = IteratorState::Enumerate {
+        iterator,
+        count: start,
+    };
+    Ok(vm.new_iterator(state, class.clone()))
+}
+
+/// `zip(*iterables)`.
+pub(super) fn zip_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    if let Some((name, _)) = args.keywords.first() {
+        let message = format!("'{}' is an invalid keyword argument for zip()", name);
+        return Err(vm.new_type_error(message));
+    }
+    let mut iterators = Vec::with_capacity(args.positional.len());
+    for iterable in &args.positional {
+        iterators.push(vm.iter(iterable)?);
+    }
+    Ok(vm.new_iterator(IteratorState::Zip(iterators), class.clone()))
+}
+
+/// `map(function, iterable, *iterables)`.
+pub(super) fn map_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    no_keywords(vm, &args, "map")?;
+    if args.positional.len() < 2 {
+        let message = "map() must have at least two arguments.".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    let mut positional = args.positional.into_iter();
+    let function = positional.next().unwrap();
+    let mut iterators = Vec::with_capacity(positional.len());
+    for iterable in positional {
+        iterators.push(vm.iter(&iterable)?);
+    }
+    let state = IteratorState::Map {
+        function,
+        iterators,
+    };
+    Ok(vm.new_iterator(state, class.clone()))
+}
+
+/// `filter(function, iterable)`.
+pub(super) fn filter_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    no_keywords(vm, &args, "filter")?;
+    check_count(vm, &args, "filter", 2, 2)?;
+    let iterator = vm.iter(&args.positional[1])?;
+    let state = IteratorState::Filter {
+        function: args.positional[0].clone(),
+        iterator,
+    };
+    Ok(vm.new_iterator(state, class.clone()))
+}
+
+/// `reversed(sequence)`.
+pub(super) fn reversed_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    no_keywords(vm, &args, "reversed")?;
+    check_count(vm, &args, "reversed", 1, 1)?;
+    let sequence = &args.positional[0];
+    let (index, class) = match sequence.payload {
+        Payload::List(ref items) => (items.borrow().len(), vm.types.list_reverseiterator.clone()),
+        Payload::Tuple(ref items) => (items.len(), class.clone()),
+        Payload::Str(ref value) => (value.chars().count(), class.clone()),
+        Payload::Range { start, step, .. } => {
+            let len = vm.len(sequence)?;
+            let state = IteratorState::Range {
+                next: start.wrapping_add((len as i64).wrapping_sub(1).wrapping_mul(step)),
+                step: step.wrapping_neg(),
+                remaining: len,
+            };
+            let class = vm.types.range_iterator.clone();
+            return Ok(vm.new_iterator(state, class));
+        }
+        Payload::Dict(_) | Payload::DictView { .. } => {
+            let (dict, kind) = match sequence.payload {
+                Payload::DictView { ref dict, kind } => (dict.clone(), kind),
+                _ => (sequence.clone(), ViewKind::Keys),
+            };
+            let len = dict.as_dict().unwrap().borrow().len();
+            let state = IteratorState::Entries {
+                dict,
+                kind,
+                position: usize::MAX,
+                len,
+                reversed: true,
+            };
+            let class = vm.entries_iterator_class(kind, true);
+            return Ok(vm.new_iterator(state, class));
+        }
+        _ => match vm.python_method(sequence, "__reversed__") {
+            Some(method) => return vm.call(&method, Args::default()),
+            None => {
+                let message = format!("'{}' object is not reversible", sequence.type_name());
+                return Err(vm.new_type_error(message));
+            }
+        },
+    };
+    let state = IteratorState::Reversed {
+        sequence: sequence.clone(),
+        index,
+    };
+    Ok(vm.new_iterator(state, class))
+}
+
+impl Vm {
+    /// Sorts `items` in place and stably, by `key(item)` if `key` is a
+    /// function, comparing with `<`. With `reverse`, items that compare
+    /// equal keep their order.
+    pub fn sort(
+        &mut self,
+        items: &mut Vec<ObjectRef>,
+        key: Option<&ObjectRef>,
+        reverse: bool,
+    ) -> PyResult<()> {
+        let key = key.filter(|key| !Vm::is(key, &self.none));
+        let mut keyed = Vec::with_capacity(items.len());
+        for item in items.drain(..) {
+            let item_key = match key {
+                Some(key) => self.call(key, Args::new(vec![item.clone()]))?,
+                None => item.clone(),
+            };
+            keyed.push((item_key, item));
+        }
+        if reverse {
+            keyed.reverse();
+        }
+        let mut sorted = self.merge_sort(keyed)?;
+        if reverse {
+            sorted.reverse();
+        }
+        items.extend(sorted.into_iter().map(|(_, item)| item));
+        Ok(())
+    }
+
+    /// A stable merge sort whose comparisons may raise.
+    fn merge_sort(
+        &mut self,
+        mut items: Vec<(ObjectRef, ObjectRef)>,
+    ) -> PyResult<Vec<(ObjectRef, ObjectRef)>> {
+        if items.len() <= 1 {
+            return Ok(items);
+        }
+        let right = items.split_off(items.len() / 2);
+        let left = self.merge_sort(items)?;
+        let right = self.merge_sort(right)?;
+        let mut merged = Vec::with_capacity(left.len() + right.len());
+        let mut left = left.into_iter().peekable();
+        let mut right = right.into_iter().peekable();
+        while let (Some(a), Some(b)) = (left.peek(), right.peek()) {
+            let less = self.compare(&b.0, ::ast::CmpOperator::Lt, &a.0)?;
+            let take_right = self.is_true(&less)?;
+            merged.push(if take_right {
+                right.next().unwrap()
+            } else {
+                left.next().unwrap()
+            });
+        }
+        merged.extend(left);
+        merged.extend(right);
+        Ok(merged)
+    }
+}
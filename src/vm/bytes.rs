@@ -0,0 +1,1152 @@
+//! The methods of `bytes` and `bytearray`, and the decoders
+//! `bytes.decode` and `str()` share. The methods they have in common give
+//! results of the type they were called on. Searches take a byte as an int
+//! as well as a bytes-like needle, and whitespace is ASCII whitespace, as
+//! in CPython.
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::convert::TryFrom;
+
+use super::builtins::{arguments, check_count, index_value};
+use super::object::{Args, NativeFunction, ObjectRef, Payload, PyObject, PyResult};
+use super::Vm;
+
+/// Adds the methods of `bytes` and `bytearray` to their classes.
+pub(super) fn add_methods(vm: &mut Vm) {
+    let methods: [(&'static str, NativeFunction); 21] = [
+        ("count", count),
+        ("decode", decode),
+        ("endswith", endswith),
+        ("find", find),
+        ("hex", hex),
+        ("index", index),
+        ("join", join),
+        ("lower", lower),
+        ("lstrip", lstrip),
+        ("removeprefix", removeprefix),
+        ("removesuffix", removesuffix),
+        ("replace", replace),
+        ("rfind", rfind),
+        ("rindex", rindex),
+        ("rsplit", rsplit),
+        ("rstrip", rstrip),
+        ("split", split),
+        ("splitlines", splitlines),
+        ("startswith", startswith),
+        ("strip", strip),
+        ("upper", upper),
+    ];
+    let mutating: [(&'static str, NativeFunction); 8] = [
+        ("append", append),
+        ("clear", clear),
+        ("copy", copy),
+        ("extend", extend),
+        ("insert", insert),
+        ("pop", pop),
+        ("remove", remove),
+        ("reverse", reverse),
+    ];
+    let classes = [vm.types.bytes.clone(), vm.types.bytearray.clone()];
+    for class in &classes {
+        let dict = class.dict().unwrap().clone();
+        let bytearray = Vm::is(class, &vm.types.bytearray);
+        let own: &[(&'static str, NativeFunction)] = if bytearray { &mutating } else { &[] };
+        for &(name, function) in methods.iter().chain(own) {
+            let method = vm.new_builtin(name, function);
+            vm.dict_set_str(dict.as_dict().unwrap(), name, method);
+        }
+        let function = vm.new_builtin("fromhex", fromhex);
+        let fromhex = PyObject::new(
+            Payload::ClassMethod(function),
+            vm.types.classmethod.clone(),
+            None,
+        );
+        vm.dict_set_str(dict.as_dict().unwrap(), "fromhex", fromhex);
+    }
+    let methods: [(&'static str, NativeFunction); 6] = [
+        ("__enter__", memoryview_enter),
+        ("__exit__", memoryview_exit),
+        ("hex", memoryview_hex),
+        ("release", memoryview_release),
+        ("tobytes", tobytes),
+        ("tolist", tolist),
+    ];
+    let dict = vm.types.memoryview.dict().unwrap().clone();
+    for &(name, function) in &methods {
+        let method = vm.new_builtin(name, function);
+        vm.dict_set_str(dict.as_dict().unwrap(), name, method);
+    }
+}
+
+/// Splits the bytes or bytearray a method was called on from the other
+/// arguments.
+fn receiver(vm: &mut Vm, mut args: Args, name: &str) -> PyResult<(ObjectRef, Args)> {
+    if args.positional.is_empty() {
+        let message = format!("unbound method bytes.{}() needs an argument", name);
+        return Err(vm.new_type_error(message));
+    }
+    let this = args.positional.remove(0);
+    if let Payload::Bytes(_) | Payload::ByteArray(_) = this.payload {
+        return Ok((this, args));
+    }
+    let message = format!(
+        "descriptor '{}' for 'bytes' objects doesn't apply to a '{}' object",
+        name,
+        this.type_name()
+    );
+    Err(vm.new_type_error(message))
+}
+
+fn data(bytes: &ObjectRef) -> Cow<'_, [u8]> {
+    bytes_like(bytes).unwrap()
+}
+
+/// The data of a bytes-like object: bytes, a bytearray, or a memoryview
+/// that has not been released. That of a bytearray is copied, as it may
+/// change.
+pub(super) fn bytes_like(object: &ObjectRef) -> Option<Cow<'_, [u8]>> {
+    match object.payload {
+        Payload::Bytes(ref data) => Some(Cow::Borrowed(data)),
+        Payload::ByteArray(ref data) => Some(Cow::Owned(data.borrow().clone())),
+        Payload::MemoryView {
+            ref object,
+            ref released,
+        } if !released.get() => bytes_like(object),
+        _ => None,
+    }
+}
+
+/// A result of a method of `this`: bytes, or a bytearray if `this` is
+/// one.
+fn new_like(vm: &mut Vm, this: &ObjectRef, data: Vec<u8>) -> ObjectRef {
+    match this.payload {
+        Payload::ByteArray(_) => vm.new_bytearray(data),
+        _ => vm.new_bytes(data),
+    }
+}
+
+/// `this` itself for a method that leaves it unchanged, or a copy if it is
+/// a bytearray, which the caller could change.
+fn unchanged(vm: &mut Vm, this: &ObjectRef) -> ObjectRef {
+    match this.payload {
+        Payload::ByteArray(ref data) => vm.new_bytearray(data.borrow().clone()),
+        _ => this.clone(),
+    }
+}
+
+fn reject_keywords(vm: &mut Vm, args: &Args, name: &str) -> PyResult<()> {
+    if args.keywords.is_empty() {
+        return Ok(());
+    }
+    let message = format!("bytes.{}() takes no keyword arguments", name);
+    Err(vm.new_type_error(message))
+}
+
+/// The bytes and the arguments of a method whose arguments are all
+/// positional-only.
+fn positional_arguments(
+    vm: &mut Vm,
+    args: Args,
+    name: &str,
+    min: usize,
+    max: usize,
+) -> PyResult<(ObjectRef, Vec<ObjectRef>)> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    check_count(vm, &args, name, min, max)?;
+    Ok((this, args.positional))
+}
+
+/// The bytes and the arguments, by position or by name, of a method that
+/// takes the optional arguments `names`.
+fn named_arguments(
+    vm: &mut Vm,
+    args: Args,
+    name: &str,
+    names: &[&str],
+) -> PyResult<(ObjectRef, Vec<Option<ObjectRef>>)> {
+    let (this, args) = receiver(vm, args, name)?;
+    let values = arguments(vm, args, name, names, 0)?;
+    Ok((this, values))
+}
+
+/// A bytes-like argument, which no other type may stand for.
+fn bytes_argument<'a>(vm: &mut Vm, object: &'a ObjectRef) -> PyResult<Cow<'a, [u8]>> {
+    match bytes_like(object) {
+        Some(data) => Ok(data),
+        None => {
+            let message = format!(
+                "a bytes-like object is required, not '{}'",
+                object.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// What a search looks for: bytes, or a single byte given as an int.
+fn needle(vm: &mut Vm, object: &ObjectRef) -> PyResult<Vec<u8>> {
+    if let Some(data) = bytes_like(object) {
+        return Ok(data.into_owned());
+    }
+    match object.payload {
+        Payload::Int(_) => {
+            let value = index_value(vm, object)?;
+            match u8::try_from(value) {
+                Ok(byte) => Ok(vec![byte]),
+                Err(_) => Err(vm.new_value_error("byte must be in range(0, 256)".to_string())),
+            }
+        }
+        _ => {
+            let message = format!(
+                "argument should be integer or bytes-like object, not '{}'",
+                object.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// Whether `byte` is whitespace to `split` and `strip`.
+fn is_space(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | b'\r' | b'\x0b' | b'\x0c')
+}
+
+/// The position of the first `needle` in `haystack`, or of the last if
+/// `reverse`.
+fn search(haystack: &[u8], needle: &[u8], reverse: bool) -> Option<usize> {
+    if needle.len() > haystack.len() {
+        return None;
+    }
+    let mut starts = 0..=haystack.len() - needle.len();
+    let matches = |&start: &usize| haystack[start..].starts_with(needle);
+    if reverse {
+        starts.rev().find(matches)
+    } else {
+        starts.find(matches)
+    }
+}
+
+/// The `start` or `end` argument of a search, which may be `None`.
+fn slice_index(vm: &mut Vm, object: Option<&ObjectRef>) -> PyResult<Option<i64>> {
+    let object = match object {
+        Some(object) => object,
+        None => return Ok(None),
+    };
+    match object.payload {
+        Payload::None => Ok(None),
+        Payload::Int(value) => Ok(Some(value)),
+        _ if vm.python_method(object, "__index__").is_some() => index_value(vm, object).map(Some),
+        _ => {
+            let message = "slice indices must be integers or None or have an __index__ method";
+            Err(vm.new_type_error(message.to_string()))
+        }
+    }
+}
+
+/// The part of `data` between the optional `start` and `end` arguments,
+/// and the position it starts at; `None` if the range is too short to
+/// hold `needle`.
+fn search_slice<'a>(
+    vm: &mut Vm,
+    data: &'a [u8],
+    bounds: &[ObjectRef],
+    needle: &[u8],
+) -> PyResult<Option<(&'a [u8], usize)>> {
+    let len = data.len() as i64;
+    let adjust = |bound: i64| {
+        if bound < 0 {
+            (bound + len).max(0)
+        } else {
+            bound
+        }
+    };
+    let start = slice_index(vm, bounds.first())?.map_or(0, adjust);
+    let end = slice_index(vm, bounds.get(1))?.map_or(len, adjust).min(len);
+    if end < start || ((end - start) as usize) < needle.len() {
+        return Ok(None);
+    }
+    let (start, end) = (start as usize, end as usize);
+    Ok(Some((&data[start..end], start)))
+}
+
+/// The position of the needle, the first argument, searching from the end
+/// if `reverse`.
+fn find_position(vm: &mut Vm, args: Args, name: &str, reverse: bool) -> PyResult<Option<usize>> {
+    let (this, rest) = receiver(vm, args, name)?;
+    rest.check(vm, name, 1, 3)?;
+    let rest = rest.positional;
+    let needle = needle(vm, &rest[0])?;
+    let data = data(&this);
+    match search_slice(vm, &data, &rest[1..], &needle)? {
+        Some((slice, start)) => Ok(search(slice, &needle, reverse).map(|at| start + at)),
+        None => Ok(None),
+    }
+}
+
+/// `bytes.find(sub[, start[, end]])`.
+fn find(vm: &mut Vm, args: Args) -> PyResult {
+    let position = find_position(vm, args, "find", false)?;
+    Ok(vm.new_int(position.map_or(-1, |position| position as i64)))
+}
+
+fn rfind(vm: &mut Vm, args: Args) -> PyResult {
+    let position = find_position(vm, args, "rfind", true)?;
+    Ok(vm.new_int(position.map_or(-1, |position| position as i64)))
+}
+
+/// `bytes.index(sub[, start[, end]])`, which is `find` raising
+/// `ValueError` if the subsection is not there.
+fn index(vm: &mut Vm, args: Args) -> PyResult {
+    match find_position(vm, args, "index", false)? {
+        Some(position) => Ok(vm.new_int(position as i64)),
+        None => Err(vm.new_value_error("subsection not found".to_string())),
+    }
+}
+
+fn rindex(vm: &mut Vm, args: Args) -> PyResult {
+    match find_position(vm, args, "rindex", true)? {
+        Some(position) => Ok(vm.new_int(position as i64)),
+        None => Err(vm.new_value_error("subsection not found".to_string())),
+    }
+}
+
+/// `bytes.count(sub[, start[, end]])`, the number of non-overlapping
+/// occurrences.
+fn count(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, rest) = receiver(vm, args, "count")?;
+    rest.check(vm, "count", 1, 3)?;
+    let rest = rest.positional;
+    let needle = needle(vm, &rest[0])?;
+    let data = data(&this);
+    let count = match search_slice(vm, &data, &rest[1..], &needle)? {
+        None => 0,
+        Some((slice, _)) if needle.is_empty() => slice.len() + 1,
+        Some((mut slice, _)) => {
+            let mut count = 0;
+            while let Some(at) = search(slice, &needle, false) {
+                count += 1;
+                slice = &slice[at + needle.len()..];
+            }
+            count
+        }
+    };
+    Ok(vm.new_int(count as i64))
+}
+
+/// `bytes.startswith(prefix[, start[, end]])`, where `prefix` may be a
+/// tuple of bytes to try.
+fn startswith(vm: &mut Vm, args: Args) -> PyResult {
+    affix_match(vm, args, "startswith", |data, affix| {
+        data.starts_with(affix)
+    })
+}
+
+fn endswith(vm: &mut Vm, args: Args) -> PyResult {
+    affix_match(vm, args, "endswith", |data, affix| data.ends_with(affix))
+}
+
+fn affix_match(vm: &mut Vm, args: Args, name: &str, matches: fn(&[u8], &[u8]) -> bool) -> PyResult {
+    let (this, rest) = receiver(vm, args, name)?;
+    rest.check(vm, name, 1, 3)?;
+    let rest = rest.positional;
+    let affixes = match rest[0].payload {
+        Payload::Tuple(ref items) => items.clone(),
+        _ if bytes_like(&rest[0]).is_some() => vec![rest[0].clone()],
+        _ => {
+            let message = format!(
+                "{} first arg must be bytes or a tuple of bytes, not {}",
+                name,
+                rest[0].type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let data = data(&this);
+    for affix in &affixes {
+        let affix = bytes_argument(vm, affix)?;
+        if let Some((slice, _)) = search_slice(vm, &data, &rest[1..], &affix)? {
+            if matches(slice, &affix) {
+                return Ok(vm.new_bool(true));
+            }
+        }
+    }
+    Ok(vm.new_bool(false))
+}
+
+/// The `sep` and `maxsplit` arguments of `split` and `rsplit`: the
+/// separator, `None` for runs of whitespace, and the most splits to make,
+/// `None` for no limit.
+fn split_arguments(
+    vm: &mut Vm,
+    values: &[Option<ObjectRef>],
+) -> PyResult<(Option<Vec<u8>>, Option<usize>)> {
+    let separator = match values[0] {
+        None => None,
+        Some(ref object) if Vm::is(object, &vm.none()) => None,
+        Some(ref object) => {
+            let separator = bytes_argument(vm, object)?;
+            if separator.is_empty() {
+                return Err(vm.new_value_error("empty separator".to_string()));
+            }
+            Some(separator.to_vec())
+        }
+    };
+    let limit = match values[1] {
+        Some(ref object) => usize::try_from(index_value(vm, object)?).ok(),
+        None => None,
+    };
+    Ok((separator, limit))
+}
+
+/// `bytes.split(sep=None, maxsplit=-1)`.
+fn split(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "split", &["sep", "maxsplit"])?;
+    let (separator, limit) = split_arguments(vm, &values)?;
+    let data = data(&this);
+    let mut rest = &data[..];
+    let mut parts = Vec::new();
+    match separator {
+        Some(separator) => {
+            while Some(parts.len()) != limit {
+                match search(rest, &separator, false) {
+                    Some(at) => {
+                        parts.push(&rest[..at]);
+                        rest = &rest[at + separator.len()..];
+                    }
+                    None => break,
+                }
+            }
+            parts.push(rest);
+        }
+        None => {
+            let skip = |data: &[u8]| data.iter().position(|&byte| !is_space(byte));
+            rest = &rest[skip(rest).unwrap_or(rest.len())..];
+            while !rest.is_empty() {
+                if Some(parts.len()) == limit {
+                    parts.push(rest);
+                    break;
+                }
+                let end = rest.iter().position(|&byte| is_space(byte));
+                let end = end.unwrap_or(rest.len());
+                parts.push(&rest[..end]);
+                rest = &rest[end..];
+                rest = &rest[skip(rest).unwrap_or(rest.len())..];
+            }
+        }
+    }
+    let parts = parts
+        .into_iter()
+        .map(|part| new_like(vm, &this, part.to_vec()))
+        .collect();
+    Ok(vm.new_list(parts))
+}
+
+/// `bytes.rsplit(sep=None, maxsplit=-1)`, which splits from the end.
+fn rsplit(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "rsplit", &["sep", "maxsplit"])?;
+    let (separator, limit) = split_arguments(vm, &values)?;
+    let data = data(&this);
+    let mut rest = &data[..];
+    let mut parts = Vec::new();
+    match separator {
+        Some(separator) => {
+            while Some(parts.len()) != limit {
+                match search(rest, &separator, true) {
+                    Some(at) => {
+                        parts.push(&rest[at + separator.len()..]);
+                        rest = &rest[..at];
+                    }
+                    None => break,
+                }
+            }
+            parts.push(rest);
+        }
+        None => {
+            let skip = |data: &[u8]| data.iter().rposition(|&byte| !is_space(byte));
+            rest = &rest[..skip(rest).map_or(0, |at| at + 1)];
+            while !rest.is_empty() {
+                if Some(parts.len()) == limit {
+                    parts.push(rest);
+                    break;
+                }
+                let start = rest.iter().rposition(|&byte| is_space(byte));
+                let start = start.map_or(0, |at| at + 1);
+                parts.push(&rest[start..]);
+                rest = &rest[..start];
+                rest = &rest[..skip(rest).map_or(0, |at| at + 1)];
+            }
+        }
+    }
+    parts.reverse();
+    let parts = parts
+        .into_iter()
+        .map(|part| new_like(vm, &this, part.to_vec()))
+        .collect();
+    Ok(vm.new_list(parts))
+}
+
+/// `bytes.splitlines(keepends=False)`, which splits at `\n`, `\r` and
+/// `\r\n` only.
+fn splitlines(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "splitlines", &["keepends"])?;
+    let keep_ends = match values[0] {
+        Some(ref value) => index_value(vm, value)? != 0,
+        None => false,
+    };
+    let data = data(&this);
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    while offset < data.len() {
+        let end = match data[offset] {
+            b'\r' if data.get(offset + 1) == Some(&b'\n') => offset + 2,
+            b'\r' | b'\n' => offset + 1,
+            _ => {
+                offset += 1;
+                continue;
+            }
+        };
+        let line = if keep_ends {
+            &data[start..end]
+        } else {
+            &data[start..offset]
+        };
+        lines.push(new_like(vm, &this, line.to_vec()));
+        start = end;
+        offset = end;
+    }
+    if start < data.len() {
+        lines.push(new_like(vm, &this, data[start..].to_vec()));
+    }
+    Ok(vm.new_list(lines))
+}
+
+/// `bytes.join(iterable_of_bytes)`.
+fn join(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, rest) = positional_arguments(vm, args, "join", 1, 1)?;
+    if !vm.is_iterable(&rest[0]) {
+        return Err(vm.new_type_error("can only join an iterable".to_string()));
+    }
+    let items = vm.iterate(&rest[0])?;
+    let separator = data(&this);
+    let mut joined = Vec::new();
+    for (index, item) in items.iter().enumerate() {
+        let item = match bytes_like(item) {
+            Some(item) => item,
+            None => {
+                let message = format!(
+                    "sequence item {}: expected a bytes-like object, {} found",
+                    index,
+                    item.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        };
+        if index > 0 {
+            joined.extend_from_slice(&separator);
+        }
+        joined.extend_from_slice(&item);
+    }
+    Ok(new_like(vm, &this, joined))
+}
+
+/// The bytes `strip` and its variants remove: those of the argument, or
+/// whitespace.
+fn strip_with(vm: &mut Vm, args: Args, name: &str, left: bool, right: bool) -> PyResult {
+    let (this, rest) = positional_arguments(vm, args, name, 0, 1)?;
+    let chars = match rest.first() {
+        None => None,
+        Some(object) if Vm::is(object, &vm.none()) => None,
+        Some(object) => Some(bytes_argument(vm, object)?.to_vec()),
+    };
+    let strips = |byte: &u8| match chars {
+        Some(ref chars) => chars.contains(byte),
+        None => is_space(*byte),
+    };
+    let data = data(&this);
+    let mut stripped = &data[..];
+    if left {
+        let start = stripped.iter().position(|byte| !strips(byte));
+        stripped = &stripped[start.unwrap_or(stripped.len())..];
+    }
+    if right {
+        let end = stripped.iter().rposition(|byte| !strips(byte));
+        stripped = &stripped[..end.map_or(0, |end| end + 1)];
+    }
+    Ok(new_like(vm, &this, stripped.to_vec()))
+}
+
+/// `bytes.strip(bytes=None)`.
+fn strip(vm: &mut Vm, args: Args) -> PyResult {
+    strip_with(vm, args, "strip", true, true)
+}
+
+fn lstrip(vm: &mut Vm, args: Args) -> PyResult {
+    strip_with(vm, args, "lstrip", true, false)
+}
+
+fn rstrip(vm: &mut Vm, args: Args) -> PyResult {
+    strip_with(vm, args, "rstrip", false, true)
+}
+
+/// `bytes.removeprefix(prefix)`.
+fn removeprefix(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, rest) = positional_arguments(vm, args, "removeprefix", 1, 1)?;
+    let prefix = bytes_argument(vm, &rest[0])?;
+    let data = data(&this);
+    match data.strip_prefix(&*prefix) {
+        Some(rest) => Ok(new_like(vm, &this, rest.to_vec())),
+        None => Ok(unchanged(vm, &this)),
+    }
+}
+
+fn removesuffix(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, rest) = positional_arguments(vm, args, "removesuffix", 1, 1)?;
+    let suffix = bytes_argument(vm, &rest[0])?;
+    let data = data(&this);
+    match data.strip_suffix(&*suffix) {
+        Some(rest) => Ok(new_like(vm, &this, rest.to_vec())),
+        None => Ok(unchanged(vm, &this)),
+    }
+}
+
+/// `bytes.replace(old, new, count=-1)`.
+fn replace(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, rest) = positional_arguments(vm, args, "replace", 2, 3)?;
+    let old = bytes_argument(vm, &rest[0])?;
+    let new = bytes_argument(vm, &rest[1])?;
+    let mut limit = match rest.get(2) {
+        Some(count) => usize::try_from(index_value(vm, count)?).unwrap_or(usize::MAX),
+        None => usize::MAX,
+    };
+    let original = data(&this);
+    let mut data = &original[..];
+    let mut replaced = Vec::with_capacity(data.len());
+    if old.is_empty() {
+        // An empty `old` matches before each byte and at the end.
+        let (head, tail) = data.split_at(limit.min(data.len()));
+        for &byte in head {
+            replaced.extend_from_slice(&new);
+            replaced.push(byte);
+        }
+        if limit > data.len() {
+            replaced.extend_from_slice(&new);
+        }
+        replaced.extend_from_slice(tail);
+        return Ok(new_like(vm, &this, replaced));
+    }
+    while limit > 0 {
+        match search(data, &old, false) {
+            Some(at) => {
+                replaced.extend_from_slice(&data[..at]);
+                replaced.extend_from_slice(&new);
+                data = &data[at + old.len()..];
+                limit -= 1;
+            }
+            None => break,
+        }
+    }
+    replaced.extend_from_slice(data);
+    Ok(new_like(vm, &this, replaced))
+}
+
+/// `bytes.lower()`, of the ASCII letters only.
+fn lower(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = positional_arguments(vm, args, "lower", 0, 0)?;
+    let lower = data(&this).to_ascii_lowercase();
+    Ok(new_like(vm, &this, lower))
+}
+
+fn upper(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = positional_arguments(vm, args, "upper", 0, 0)?;
+    let upper = data(&this).to_ascii_uppercase();
+    Ok(new_like(vm, &this, upper))
+}
+
+/// `bytes.hex(sep=None, bytes_per_sep=1)`.
+fn hex(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "hex", &["sep", "bytes_per_sep"])?;
+    let separator = hex_separator(vm, values[0].as_ref())?;
+    let group = match values[1] {
+        Some(ref group) => index_value(vm, group)?,
+        None => 1,
+    };
+    let text = hex_with_separator(&data(&this), separator, group);
+    Ok(vm.new_str(&String::from_utf8(text).unwrap()))
+}
+
+/// The `sep` argument of `hex` and `binascii.hexlify`: one ASCII
+/// character, given as str or bytes.
+pub(super) fn hex_separator(vm: &mut Vm, sep: Option<&ObjectRef>) -> PyResult<Option<u8>> {
+    let sep = match sep {
+        Some(sep) if !Vm::is(sep, &vm.none()) => sep,
+        _ => return Ok(None),
+    };
+    let sep: Vec<u32> = match sep.payload {
+        Payload::Str(ref text) => text.chars().map(u32::from).collect(),
+        Payload::Bytes(ref data) => data.iter().map(|&byte| u32::from(byte)).collect(),
+        _ => {
+            let message = format!("sep must be str or bytes, not {}", sep.type_name());
+            return Err(vm.new_type_error(message));
+        }
+    };
+    match sep[..] {
+        [sep] if sep < 0x80 => Ok(Some(sep as u8)),
+        [_] => Err(vm.new_value_error("sep must be ASCII.".to_string())),
+        _ => Err(vm.new_value_error("sep must be length 1.".to_string())),
+    }
+}
+
+/// `data` in lowercase hexadecimal, with `separator` between each group
+/// of `group` bytes, counted from the right, or from the left if it is
+/// negative.
+pub(super) fn hex_with_separator(data: &[u8], separator: Option<u8>, group: i64) -> Vec<u8> {
+    let separator = match separator {
+        Some(separator) if group != 0 => separator,
+        _ => return data.iter().flat_map(|byte| hex_digits(*byte)).collect(),
+    };
+    let size = group.unsigned_abs() as usize;
+    let mut text = Vec::with_capacity(data.len() * 3);
+    for (index, &byte) in data.iter().enumerate() {
+        text.extend(hex_digits(byte));
+        let boundary = if group > 0 {
+            (data.len() - index - 1).is_multiple_of(size)
+        } else {
+            (index + 1).is_multiple_of(size)
+        };
+        if boundary && index + 1 < data.len() {
+            text.push(separator);
+        }
+    }
+    text
+}
+
+fn hex_digits(byte: u8) -> [u8; 2] {
+    const DIGITS: &[u8; 16] = b"0123456789abcdef";
+    [
+        DIGITS[usize::from(byte >> 4)],
+        DIGITS[usize::from(byte & 15)],
+    ]
+}
+
+/// `bytes.fromhex(string)`: the bytes written as pairs of hexadecimal
+/// digits, with whitespace allowed between the pairs.
+fn fromhex(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "fromhex", 2, 2)?;
+    let text = match args.positional[1].payload {
+        Payload::Str(ref text) => text.clone(),
+        _ => {
+            let message = format!(
+                "fromhex() argument must be str, not {}",
+                args.positional[1].type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let chars: Vec<char> = text.chars().collect();
+    let mut data = Vec::with_capacity(chars.len() / 2);
+    let mut position = 0;
+    while position < chars.len() {
+        if chars[position].is_ascii_whitespace() {
+            position += 1;
+            continue;
+        }
+        let digit = |position: usize| chars.get(position).and_then(|c| c.to_digit(16));
+        let high = digit(position);
+        let low = digit(position + 1);
+        match (high, low) {
+            (Some(high), Some(low)) => data.push((high << 4 | low) as u8),
+            (None, _) => return Err(fromhex_error(vm, position)),
+            (_, None) => return Err(fromhex_error(vm, position + 1)),
+        }
+        position += 2;
+    }
+    let class = args.positional[0].clone();
+    if Vm::is(&class, &vm.types.bytes) {
+        return Ok(vm.new_bytes(data));
+    }
+    let bytes = vm.new_bytes(data);
+    vm.call(&class, Args::new(vec![bytes]))
+}
+
+fn fromhex_error(vm: &mut Vm, position: usize) -> ObjectRef {
+    let message = format!(
+        "non-hexadecimal number found in fromhex() arg at position {}",
+        position
+    );
+    vm.new_value_error(message)
+}
+
+/// `bytes.decode(encoding='utf-8', errors='strict')`.
+fn decode(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "decode", &["encoding", "errors"])?;
+    let text = decode_arguments(
+        vm,
+        &data(&this),
+        "decode",
+        values[0].as_ref(),
+        values[1].as_ref(),
+    )?;
+    Ok(vm.new_str(&text))
+}
+
+/// `data` decoded by `function` with its `encoding` and `errors`
+/// arguments, which default to UTF-8 and `strict`.
+pub(super) fn decode_arguments(
+    vm: &mut Vm,
+    data: &[u8],
+    function: &str,
+    encoding: Option<&ObjectRef>,
+    errors: Option<&ObjectRef>,
+) -> PyResult<String> {
+    let mut names = ["utf-8", "strict"];
+    for (name, (value, argument)) in names
+        .iter_mut()
+        .zip([encoding, errors].iter().zip(&["encoding", "errors"]))
+    {
+        if let Some(value) = *value {
+            match value.as_str() {
+                Some(text) => *name = text,
+                None => {
+                    let message = format!(
+                        "{}() argument '{}' must be str, not {}",
+                        function,
+                        argument,
+                        value.type_name()
+                    );
+                    return Err(vm.new_type_error(message));
+                }
+            }
+        }
+    }
+    decode_bytes(vm, data, names[0], names[1])
+}
+
+/// `data` decoded with the codec named `encoding`, handling bytes it
+/// cannot decode as `errors` says: `strict`, `ignore`, `replace` or
+/// `backslashreplace`.
+fn decode_bytes(vm: &mut Vm, data: &[u8], encoding: &str, errors: &str) -> PyResult<String> {
+    let normalized = encoding.to_ascii_lowercase().replace(['_', ' '], "-");
+    match normalized.as_str() {
+        "utf-8" | "utf8" | "u8" | "utf" | "cp65001" => decode_utf8(vm, data, errors),
+        "ascii" | "us-ascii" | "646" => decode_ascii(vm, data, errors),
+        "latin-1" | "latin1" | "latin" | "l1" | "iso-8859-1" | "iso8859-1" | "8859" | "cp819" => {
+            Ok(data.iter().map(|&byte| char::from(byte)).collect())
+        }
+        _ => {
+            let class = vm.exceptions.lookup_error.clone();
+            Err(vm.new_error(&class, format!("unknown encoding: {}", encoding)))
+        }
+    }
+}
+
+/// Adds what `errors` puts for the undecodable `bytes` to `text`, or
+/// fails with `message` if it is `strict`.
+fn decode_error(
+    vm: &mut Vm,
+    text: &mut String,
+    bytes: &[u8],
+    errors: &str,
+    message: String,
+) -> PyResult<()> {
+    match errors {
+        "strict" => {
+            let class = vm.exceptions.unicode_decode_error.clone();
+            Err(vm.new_error(&class, message))
+        }
+        "ignore" => Ok(()),
+        "replace" => {
+            text.push('\u{fffd}');
+            Ok(())
+        }
+        "backslashreplace" => {
+            for byte in bytes {
+                text.push_str(&format!("\\x{:02x}", byte));
+            }
+            Ok(())
+        }
+        _ => {
+            let class = vm.exceptions.lookup_error.clone();
+            let message = format!("unknown error handler name '{}'", errors);
+            Err(vm.new_error(&class, message))
+        }
+    }
+}
+
+/// `data` decoded as UTF-8, failing with CPython's messages.
+pub(super) fn decode_utf8(vm: &mut Vm, data: &[u8], errors: &str) -> PyResult<String> {
+    let mut text = String::with_capacity(data.len());
+    let mut position = 0;
+    for chunk in data.utf8_chunks() {
+        text.push_str(chunk.valid());
+        position += chunk.valid().len();
+        let invalid = chunk.invalid();
+        if invalid.is_empty() {
+            continue;
+        }
+        let at_end = position + invalid.len() == data.len();
+        let reason = if at_end && (0xc2..0xf5).contains(&invalid[0]) {
+            "unexpected end of data"
+        } else if invalid.len() == 1 && !(0xc2..0xf5).contains(&invalid[0]) {
+            "invalid start byte"
+        } else {
+            "invalid continuation byte"
+        };
+        let message = if invalid.len() == 1 {
+            format!(
+                "'utf-8' codec can't decode byte {:#04x} in position {}: {}",
+                invalid[0], position, reason
+            )
+        } else {
+            format!(
+                "'utf-8' codec can't decode bytes in position {}-{}: {}",
+                position,
+                position + invalid.len() - 1,
+                reason
+            )
+        };
+        decode_error(vm, &mut text, invalid, errors, message)?;
+        position += invalid.len();
+    }
+    Ok(text)
+}
+
+fn decode_ascii(vm: &mut Vm, data: &[u8], errors: &str) -> PyResult<String> {
+    let mut text = String::with_capacity(data.len());
+    for (position, &byte) in data.iter().enumerate() {
+        if byte < 0x80 {
+            text.push(char::from(byte));
+            continue;
+        }
+        let message = format!(
+            "'ascii' codec can't decode byte {:#04x} in position {}: ordinal not in range(128)",
+            byte, position
+        );
+        decode_error(vm, &mut text, &[byte], errors, message)?;
+    }
+    Ok(text)
+}
+
+/// The data of the bytearray a method was called on.
+fn buffer(this: &ObjectRef) -> &RefCell<Vec<u8>> {
+    match this.payload {
+        Payload::ByteArray(ref data) => data,
+        _ => unreachable!(),
+    }
+}
+
+/// The bytearray a method was called on, which `bytes` does not share.
+fn mutable_receiver(
+    vm: &mut Vm,
+    args: Args,
+    name: &str,
+    min: usize,
+    max: usize,
+) -> PyResult<(ObjectRef, Vec<ObjectRef>)> {
+    let (this, arguments) = positional_arguments(vm, args, name, min, max)?;
+    if let Payload::Bytes(_) = this.payload {
+        let message = format!(
+            "descriptor '{}' for 'bytearray' objects doesn't apply to a 'bytes' object",
+            name
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok((this, arguments))
+}
+
+/// An int argument that stands for a single byte.
+pub(super) fn byte_value(vm: &mut Vm, object: &ObjectRef) -> PyResult<u8> {
+    let value = index_value(vm, object)?;
+    match u8::try_from(value) {
+        Ok(byte) => Ok(byte),
+        Err(_) => Err(vm.new_value_error("byte must be in range(0, 256)".to_string())),
+    }
+}
+
+/// `bytearray.append(item, /)`.
+fn append(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = mutable_receiver(vm, args, "append", 1, 1)?;
+    let byte = byte_value(vm, &arguments[0])?;
+    buffer(&this).borrow_mut().push(byte);
+    Ok(vm.none())
+}
+
+/// `bytearray.extend(iterable_of_ints, /)`, which takes a bytes-like
+/// object as well.
+fn extend(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = mutable_receiver(vm, args, "extend", 1, 1)?;
+    let source = &arguments[0];
+    if let Some(data) = bytes_like(source) {
+        let data = data.into_owned();
+        buffer(&this).borrow_mut().extend_from_slice(&data);
+        return Ok(vm.none());
+    }
+    if !vm.is_iterable(source) {
+        let message = format!("can't extend bytearray with {}", source.type_name());
+        return Err(vm.new_type_error(message));
+    }
+    let mut data = Vec::new();
+    for item in vm.iterate(source)? {
+        data.push(byte_value(vm, &item)?);
+    }
+    buffer(&this).borrow_mut().extend_from_slice(&data);
+    Ok(vm.none())
+}
+
+/// `bytearray.insert(index, item, /)`.
+fn insert(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = mutable_receiver(vm, args, "insert", 2, 2)?;
+    let index = index_value(vm, &arguments[0])?;
+    let byte = byte_value(vm, &arguments[1])?;
+    let mut data = buffer(&this).borrow_mut();
+    let len = data.len() as i64;
+    let index = if index < 0 {
+        (index + len).max(0)
+    } else {
+        index.min(len)
+    };
+    data.insert(index as usize, byte);
+    drop(data);
+    Ok(vm.none())
+}
+
+/// `bytearray.pop(index=-1, /)`.
+fn pop(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = mutable_receiver(vm, args, "pop", 0, 1)?;
+    let index = match arguments.first() {
+        Some(index) => index_value(vm, index)?,
+        None => -1,
+    };
+    let len = buffer(&this).borrow().len() as i64;
+    if len == 0 {
+        return Err(vm.new_index_error("pop from empty bytearray".to_string()));
+    }
+    let position = if index < 0 { index + len } else { index };
+    if position < 0 || position >= len {
+        return Err(vm.new_index_error("pop index out of range".to_string()));
+    }
+    let byte = buffer(&this).borrow_mut().remove(position as usize);
+    Ok(vm.new_int(i64::from(byte)))
+}
+
+/// `bytearray.remove(value, /)`, of the first byte equal to `value`.
+fn remove(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = mutable_receiver(vm, args, "remove", 1, 1)?;
+    let byte = byte_value(vm, &arguments[0])?;
+    let mut data = buffer(&this).borrow_mut();
+    match data.iter().position(|&other| other == byte) {
+        Some(position) => {
+            data.remove(position);
+            drop(data);
+            Ok(vm.none())
+        }
+        None => Err(vm.new_value_error("value not found in bytearray".to_string())),
+    }
+}
+
+/// `bytearray.clear()`.
+fn clear(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = mutable_receiver(vm, args, "clear", 0, 0)?;
+    buffer(&this).borrow_mut().clear();
+    Ok(vm.none())
+}
+
+/// `bytearray.copy()`.
+fn copy(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = mutable_receiver(vm, args, "copy", 0, 0)?;
+    let data = buffer(&this).borrow().clone();
+    Ok(vm.new_bytearray(data))
+}
+
+/// `bytearray.reverse()`, in place.
+fn reverse(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = mutable_receiver(vm, args, "reverse", 0, 0)?;
+    buffer(&this).borrow_mut().reverse();
+    Ok(vm.none())
+}
+
+/// The memoryview a method was called on and its other arguments. Only
+/// `release` and the context manager methods work on a released view.
+fn view_receiver(
+    vm: &mut Vm,
+    args: Args,
+    name: &str,
+    names: &[&str],
+    released_ok: bool,
+) -> PyResult<(ObjectRef, Vec<Option<ObjectRef>>)> {
+    let mut args = args;
+    let this = match args.positional.first() {
+        Some(this) if matches!(this.payload, Payload::MemoryView { .. }) => {
+            args.positional.remove(0)
+        }
+        _ => {
+            let message = format!(
+                "descriptor '{}' for 'memoryview' objects doesn't apply to this object",
+                name
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    if !released_ok && bytes_like(&this).is_none() {
+        return Err(released_error(vm));
+    }
+    let values = arguments(vm, args, name, names, 0)?;
+    Ok((this, values))
+}
+
+/// The error of an operation on a memoryview that has been released.
+pub(super) fn released_error(vm: &mut Vm) -> ObjectRef {
+    vm.new_value_error("operation forbidden on released memoryview object".to_string())
+}
+
+/// `memoryview.tobytes(order='C')`.
+fn tobytes(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = view_receiver(vm, args, "tobytes", &["order"], false)?;
+    let data = data(&this).into_owned();
+    Ok(vm.new_bytes(data))
+}
+
+/// `memoryview.tolist()`, of ints, as the format is always `B`.
+fn tolist(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = view_receiver(vm, args, "tolist", &[], false)?;
+    let items = data(&this)
+        .iter()
+        .map(|&byte| vm.new_int(i64::from(byte)))
+        .collect();
+    Ok(vm.new_list(items))
+}
+
+/// `memoryview.hex(sep=..., bytes_per_sep=1)`.
+fn memoryview_hex(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = view_receiver(vm, args, "hex", &["sep", "bytes_per_sep"], false)?;
+    let separator = hex_separator(vm, values[0].as_ref())?;
+    let group = match values[1] {
+        Some(ref group) => index_value(vm, group)?,
+        None => 1,
+    };
+    let text = hex_with_separator(&data(&this), separator, group);
+    Ok(vm.new_str(&String::from_utf8(text).unwrap()))
+}
+
+/// `memoryview.release()`, after which the view no longer reads its
+/// object.
+fn memoryview_release(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = view_receiver(vm, args, "release", &[], true)?;
+    if let Payload::MemoryView { ref released, .. } = this.payload {
+        released.set(true);
+    }
+    Ok(vm.none())
+}
+
+fn memoryview_enter(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = view_receiver(vm, args, "__enter__", &[], false)?;
+    Ok(this)
+}
+
+/// `memoryview.__exit__(*args)`, which releases the view.
+fn memoryview_exit(vm: &mut Vm, mut args: Args) -> PyResult {
+    args.positional.truncate(1);
+    memoryview_release(vm, args)
+}
@@ -0,0 +1,334 @@
+//! Inline caches for the instructions that load attributes and globals.
+//!
+//! Each code object has a cache for each of its `LoadAttr` and
+//! `LoadGlobal` instructions, shared by every function made from it, which
+//! remembers what the instruction found the last time it looked. A global
+//! is reused while the globals, and the builtins if it came from there,
+//! have the version they had then. An attribute of an instance is reused
+//! while its class is the same one and no class has changed since: the
+//! lookup in the class and its bases is skipped, though the instance's own
+//! dictionary is still looked in, since it changes far more often than
+//! classes do. An attribute of a module is reused while the module's
+//! dictionary has the version it had.
+//!
+//! The caches hold what they found weakly, as the dictionaries it is in
+//! keep it alive for as long as their versions stay the same.
+
+use std::cell::{Ref, RefCell};
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Weak as WeakCode};
+
+use compiler::{CodeObject, Instruction};
+
+use super::dict::types_version;
+use super::object::{ObjectRef, Payload, PyObject, PyResult};
+use super::ops::hash_str;
+use super::Vm;
+
+/// The slot of an instruction that has no cache.
+const NO_SLOT: u32 = u32::MAX;
+
+/// How often the inline caches have been used, and how often what they
+/// held could not be.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    pub attribute_hits: u64,
+    pub attribute_misses: u64,
+    pub global_hits: u64,
+    pub global_misses: u64,
+}
+
+/// What an instruction found the last time it looked.
+enum Cache {
+    Empty,
+    /// A global, found in the globals at version `globals`, or in the
+    /// builtins dictionary at the version given with it.
+    Global {
+        globals: u64,
+        builtins: Option<(Weak<PyObject>, u64)>,
+        value: Weak<PyObject>,
+    },
+    /// An attribute of a module whose dictionary had version `version`.
+    Module {
+        version: u64,
+        value: Weak<PyObject>,
+    },
+    /// An attribute of an instance of `class`, with what looking it up in
+    /// the class found while `types_version` was `types`.
+    Instance {
+        class: Weak<PyObject>,
+        types: u64,
+        hash: i64,
+        attribute: Option<Weak<PyObject>>,
+        data_descriptor: bool,
+    },
+}
+
+/// The caches of the instructions of one code object.
+pub(super) struct InlineCaches {
+    /// The slot in `entries` of each instruction, or `NO_SLOT`.
+    slots: Box<[u32]>,
+    entries: RefCell<Box<[Cache]>>,
+}
+
+impl InlineCaches {
+    fn new(code: &CodeObject) -> InlineCaches {
+        let mut count = 0;
+        let slots = code
+            .instructions
+            .iter()
+            .map(|instruction| match *instruction {
+                Instruction::LoadAttr(_) | Instruction::LoadGlobal(_) => {
+                    count += 1;
+                    count - 1
+                }
+                _ => NO_SLOT,
+            })
+            .collect();
+        InlineCaches {
+            slots,
+            entries: RefCell::new((0..count).map(|_| Cache::Empty).collect()),
+        }
+    }
+
+    fn get(&self, instruction: usize) -> Ref<'_, Cache> {
+        let slot = self.slots[instruction] as usize;
+        Ref::map(self.entries.borrow(), |entries| &entries[slot])
+    }
+
+    fn set(&self, instruction: usize, cache: Cache) {
+        self.entries.borrow_mut()[self.slots[instruction] as usize] = cache;
+    }
+}
+
+/// The caches of every code object that has run, and how they have done.
+#[derive(Default)]
+pub(super) struct CacheTable {
+    /// The caches by code object. The weak reference keeps the address of
+    /// the code from being reused while its entry is here.
+    codes: HashMap<*const CodeObject, (WeakCode<CodeObject>, Rc<InlineCaches>)>,
+    pruned_len: usize,
+    stats: CacheStats,
+}
+
+/// Whether the attributes of an object of this kind are all found by the
+/// generic lookup, in its class and then its dictionary, rather than some
+/// being computed as those of functions and types are.
+fn has_plain_attributes(payload: &Payload) -> bool {
+    matches!(
+        *payload,
+        Payload::Object
+            | Payload::Int(_)
+            | Payload::Float(_)
+            | Payload::Str(_)
+            | Payload::Bytes(_)
+            | Payload::Tuple(_)
+            | Payload::List(_)
+            | Payload::Dict(_)
+            | Payload::Set(_)
+    )
+}
+
+impl Vm {
+    /// The inline caches of `code`, made the first time it runs.
+    pub(super) fn inline_caches(&mut self, code: &Arc<CodeObject>) -> Rc<InlineCaches> {
+        let table = &mut self.inline_caches;
+        let key = Arc::as_ptr(code);
+        if let Some((_, caches)) = table.codes.get(&key) {
+            return caches.clone();
+        }
+        let caches = Rc::new(InlineCaches::new(code));
+        table
+            .codes
+            .insert(key, (Arc::downgrade(code), caches.clone()));
+        if table.codes.len() > 2 * table.pruned_len.max(256) {
+            table
+                .codes
+                .retain(|_, &mut (ref code, _)| code.strong_count() > 0);
+            table.pruned_len = table.codes.len();
+        }
+        caches
+    }
+
+    /// How the inline caches have done since the VM was made.
+    pub fn inline_cache_stats(&self) -> CacheStats {
+        self.inline_caches.stats
+    }
+
+    /// `LoadGlobal` at `instruction`: the global `name`, or else the
+    /// builtin.
+    pub(super) fn load_global_cached(
+        &mut self,
+        caches: &InlineCaches,
+        instruction: usize,
+        globals: &ObjectRef,
+        name: &str,
+    ) -> PyResult {
+        let namespace = globals.as_dict().unwrap().borrow();
+        let cached = match *caches.get(instruction) {
+            Cache::Global {
+                globals: version,
+                ref builtins,
+                ref value,
+            } if version == namespace.version() => {
+                let builtins_current = match *builtins {
+                    Some((ref builtins, version)) => builtins.upgrade().is_some_and(|builtins| {
+                        builtins.as_dict().unwrap().borrow().version() == version
+                    }),
+                    None => true,
+                };
+                value.upgrade().filter(|_| builtins_current)
+            }
+            _ => None,
+        };
+        if let Some(value) = cached {
+            self.inline_caches.stats.global_hits += 1;
+            return Ok(value);
+        }
+        self.inline_caches.stats.global_misses += 1;
+        let version = namespace.version();
+        let (value, builtins) = match namespace.get_str(name) {
+            Some(value) => (value, None),
+            None => {
+                let builtins = namespace
+                    .get_str("__builtins__")
+                    .filter(|builtins| builtins.as_dict().is_some())
+                    .unwrap_or_else(|| self.builtins.clone());
+                let value = builtins.as_dict().unwrap().borrow().get_str(name);
+                match value {
+                    Some(value) => {
+                        let builtins_version = builtins.as_dict().unwrap().borrow().version();
+                        (value, Some((Rc::downgrade(&builtins), builtins_version)))
+                    }
+                    None => {
+                        drop(namespace);
+                        let message = format!("name '{}' is not defined", name);
+                        return Err(self.new_name_error(message));
+                    }
+                }
+            }
+        };
+        caches.set(
+            instruction,
+            Cache::Global {
+                globals: version,
+                builtins,
+                value: Rc::downgrade(&value),
+            },
+        );
+        Ok(value)
+    }
+
+    /// `LoadAttr` at `instruction`: the attribute `name` of `object`.
+    pub(super) fn load_attr_cached(
+        &mut self,
+        caches: &InlineCaches,
+        instruction: usize,
+        object: &ObjectRef,
+        name: &str,
+    ) -> PyResult {
+        if name == "__class__" || name == "__dict__" {
+            return self.getattr(object, name);
+        }
+        match object.payload {
+            Payload::Module => self.module_attr_cached(caches, instruction, object, name),
+            ref payload if has_plain_attributes(payload) => {
+                self.instance_attr_cached(caches, instruction, object, name)
+            }
+            _ => {
+                self.inline_caches.stats.attribute_misses += 1;
+                self.getattr(object, name)
+            }
+        }
+    }
+
+    fn module_attr_cached(
+        &mut self,
+        caches: &InlineCaches,
+        instruction: usize,
+        module: &ObjectRef,
+        name: &str,
+    ) -> PyResult {
+        let version = module.dict().unwrap().as_dict().unwrap().borrow().version();
+        let cached = match *caches.get(instruction) {
+            Cache::Module {
+                version: cached,
+                ref value,
+            } if cached == version => value.upgrade(),
+            _ => None,
+        };
+        if let Some(value) = cached {
+            self.inline_caches.stats.attribute_hits += 1;
+            return Ok(value);
+        }
+        self.inline_caches.stats.attribute_misses += 1;
+        let value = self.getattr(module, name)?;
+        caches.set(
+            instruction,
+            Cache::Module {
+                version,
+                value: Rc::downgrade(&value),
+            },
+        );
+        Ok(value)
+    }
+
+    fn instance_attr_cached(
+        &mut self,
+        caches: &InlineCaches,
+        instruction: usize,
+        object: &ObjectRef,
+        name: &str,
+    ) -> PyResult {
+        let class = object.class();
+        let types = types_version();
+        let cached = match *caches.get(instruction) {
+            Cache::Instance {
+                class: ref cached_class,
+                types: cached_types,
+                hash,
+                ref attribute,
+                data_descriptor,
+            } if cached_types == types && Weak::as_ptr(cached_class) == Rc::as_ptr(&class) => {
+                Some((
+                    hash,
+                    attribute.as_ref().and_then(Weak::upgrade),
+                    data_descriptor,
+                ))
+            }
+            _ => None,
+        };
+        if let Some((hash, attribute, data_descriptor)) = cached {
+            self.inline_caches.stats.attribute_hits += 1;
+            if !data_descriptor {
+                if let Some(dict) = object.dict() {
+                    let value = dict.as_dict().unwrap().borrow().get_str_hashed(hash, name);
+                    if let Some(value) = value {
+                        return Ok(value);
+                    }
+                }
+                if let Some(attribute) = attribute {
+                    return self.bind(name, attribute, Some(object), &class);
+                }
+            }
+            return self.instance_getattr(object, &class, name, attribute, data_descriptor);
+        }
+        self.inline_caches.stats.attribute_misses += 1;
+        let attribute = self.lookup(&class, name);
+        let data_descriptor = attribute
+            .as_ref()
+            .is_some_and(|attribute| self.is_data_descriptor(attribute));
+        caches.set(
+            instruction,
+            Cache::Instance {
+                class: Rc::downgrade(&class),
+                types,
+                hash: hash_str(name),
+                attribute: attribute.as_ref().map(Rc::downgrade),
+                data_descriptor,
+            },
+        );
+        self.instance_getattr(object, &class, name, attribute, data_descriptor)
+    }
+}
@@ -0,0 +1,970 @@
+//! Classes: the `__build_class__` behind class statements, `type()` and
+//! metaclasses, method resolution order, creating instances, descriptors,
+//! and `super`.
+
+use std::iter;
+
+use ast::CmpOperator;
+
+use super::gc;
+use super::modules::types;
+use super::object::{
+    object_id, Args, NativeConstructor, NativeFunction, ObjectRef, Payload, PyObject, PyResult,
+    TypeData,
+};
+use super::ops::hash_pointer;
+use super::Vm;
+
+/// Adds the methods of `object` and `type` to their classes, and the names
+/// for classes to the builtins.
+pub(super) fn add_builtins(vm: &mut Vm) {
+    let object_methods: [(&'static str, NativeFunction); 8] = [
+        ("__new__", object_new_method),
+        ("__init__", object_init),
+        ("__init_subclass__", object_init_subclass),
+        ("__repr__", object_repr),
+        ("__str__", object_str),
+        ("__eq__", object_eq),
+        ("__ne__", object_ne),
+        ("__hash__", object_hash),
+    ];
+    let type_methods: [(&'static str, NativeFunction); 4] = [
+        ("__new__", type_new_method),
+        ("__init__", type_init),
+        ("__call__", type_call),
+        ("mro", type_mro),
+    ];
+    let object = vm.types.object.clone();
+    let type_ = vm.types.type_.clone();
+    for &(class, methods) in &[(&object, &object_methods[..]), (&type_, &type_methods[..])] {
+        let dict = class.dict().unwrap().clone();
+        for &(name, function) in methods {
+            let method = vm.new_builtin(name, function);
+            vm.dict_set_str(dict.as_dict().unwrap(), name, method);
+        }
+    }
+
+    // `list[int]` and the like, for annotations.
+    for class in [
+        vm.types.type_.clone(),
+        vm.types.tuple.clone(),
+        vm.types.list.clone(),
+        vm.types.dict.clone(),
+        vm.types.set.clone(),
+        vm.types.frozenset.clone(),
+    ] {
+        let function = vm.new_builtin("__class_getitem__", types::class_getitem);
+        let method = PyObject::new(
+            Payload::ClassMethod(function),
+            vm.types.classmethod.clone(),
+            None,
+        );
+        let dict = class.dict().unwrap().clone();
+        vm.dict_set_str(dict.as_dict().unwrap(), "__class_getitem__", method);
+    }
+
+    let builtins = vm.builtins().clone();
+    let build_class = vm.new_builtin("__build_class__", build_class);
+    let super_ = vm.types.super_.clone();
+    for (name, value) in [
+        ("__build_class__", build_class),
+        ("object", object),
+        ("type", type_),
+        ("super", super_),
+    ] {
+        vm.dict_set_str(builtins.as_dict().unwrap(), name, value);
+    }
+}
+
+/// `__build_class__(func, name, *bases, metaclass=None, **kwds)`, which
+/// runs the body of a class statement in the namespace the metaclass
+/// prepares, and then calls the metaclass to make the class.
+fn build_class(vm: &mut Vm, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    if positional.len() < 2 {
+        let message = "__build_class__: not enough arguments".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    let body = positional[0].clone();
+    if !matches!(body.payload, Payload::Function(_)) {
+        let message = "__build_class__: func must be a function".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    let name = positional[1].clone();
+    let class_name = match name.as_str() {
+        Some(class_name) => class_name.to_string(),
+        None => {
+            let message = "__build_class__: name is not a string".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let bases = positional[2..].to_vec();
+
+    let mut metaclass = None;
+    let mut keywords_left = Vec::new();
+    for (key, value) in keywords {
+        if key == "metaclass" {
+            metaclass = Some(value);
+        } else {
+            keywords_left.push((key, value));
+        }
+    }
+    let metaclass = match metaclass {
+        Some(metaclass) if metaclass.as_type().is_none() => metaclass,
+        metaclass => {
+            let metaclass = metaclass.unwrap_or_else(|| match bases.first() {
+                Some(base) => base.class(),
+                None => vm.types.type_.clone(),
+            });
+            vm.calculate_metaclass(&metaclass, &bases)?
+        }
+    };
+    let bases = vm.new_tuple(bases);
+
+    let namespace = match vm.getattr(&metaclass, "__prepare__") {
+        Ok(prepare) => vm.call(
+            &prepare,
+            Args {
+                positional: vec![name.clone(), bases.clone()],
+                keywords: keywords_left.clone(),
+            },
+        )?,
+        Err(error) if Vm::is_instance(&error, &vm.exceptions.attribute_error) => vm.new_dict(),
+        Err(error) => return Err(error),
+    };
+    if namespace.as_dict().is_none() {
+        let message = format!(
+            "{}.__prepare__() must return a mapping, not {}",
+            match metaclass.as_type() {
+                Some(data) => data.name.clone(),
+                None => "<metaclass>".to_string(),
+            },
+            namespace.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+
+    let cell = match body.payload {
+        Payload::Function(ref function) => {
+            let mut frame = vm.function_frame(function, Some(namespace.clone()));
+            vm.execute_call(&mut frame)?
+        }
+        _ => unreachable!(),
+    };
+    let class = vm.call(
+        &metaclass,
+        Args {
+            positional: vec![name, bases, namespace],
+            keywords: keywords_left,
+        },
+    )?;
+    if let (Payload::Cell(ref cell), Some(_)) = (&cell.payload, class.as_type()) {
+        let value = cell.borrow().clone();
+        match value {
+            Some(ref value) if Vm::is(value, &class) => {}
+            Some(value) => {
+                let message = format!(
+                    "__class__ set to {} defining '{}' as {}",
+                    vm.repr(&value)?,
+                    class_name,
+                    vm.repr(&class)?
+                );
+                return Err(vm.new_type_error(message));
+            }
+            None => {
+                let message = format!(
+                    "__class__ not set defining '{}' as {}. Was __classcell__ propagated to \
+                     type.__new__?",
+                    class_name,
+                    vm.repr(&class)?
+                );
+                return Err(vm.new_runtime_error(message));
+            }
+        }
+    }
+    Ok(class)
+}
+
+/// `type(object)`, the class of an object, or `type(name, bases, dict)`, a
+/// new class. The constructor of `type` and of metaclasses.
+pub(super) fn type_new(vm: &mut Vm, metatype: &ObjectRef, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let exact = Vm::is(metatype, &vm.types.type_);
+    if exact && positional.len() == 1 && keywords.is_empty() {
+        return Ok(positional[0].class());
+    }
+    if positional.len() != 3 {
+        let message = if exact {
+            "type() takes 1 or 3 arguments".to_string()
+        } else {
+            format!(
+                "type.__new__() takes exactly 3 arguments ({} given)",
+                positional.len()
+            )
+        };
+        return Err(vm.new_type_error(message));
+    }
+    let name = match positional[0].as_str() {
+        Some(name) => name.to_string(),
+        None => return Err(argument_type_error(vm, 1, "str", &positional[0])),
+    };
+    let bases = match positional[1].payload {
+        Payload::Tuple(ref bases) => bases.clone(),
+        _ => return Err(argument_type_error(vm, 2, "tuple", &positional[1])),
+    };
+    if positional[2].as_dict().is_none() {
+        return Err(argument_type_error(vm, 3, "dict", &positional[2]));
+    }
+    vm.new_class(metatype, name, bases, &positional[2], keywords)
+}
+
+fn argument_type_error(vm: &mut Vm, position: usize, expected: &str, got: &ObjectRef) -> ObjectRef {
+    let message = format!(
+        "type.__new__() argument {} must be {}, not {}",
+        position,
+        expected,
+        got.type_name()
+    );
+    vm.new_type_error(message)
+}
+
+/// The class a method of `object` or `type` was called on, which must be a
+/// class.
+fn class_argument<'a>(vm: &mut Vm, args: &'a Args, method: &str) -> PyResult<&'a ObjectRef> {
+    let class = match args.positional.first() {
+        Some(class) => class,
+        None => {
+            let message = format!("{}(): not enough arguments", method);
+            return Err(vm.new_type_error(message));
+        }
+    };
+    if class.as_type().is_none() {
+        let message = format!(
+            "{}(X): X is not a type object ({})",
+            method,
+            class.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(class)
+}
+
+/// `type.__new__(metatype, name, bases, dict)`, for the `__new__` of
+/// metaclasses to call.
+fn type_new_method(vm: &mut Vm, mut args: Args) -> PyResult {
+    let metatype = class_argument(vm, &args, "type.__new__")?.clone();
+    if !Vm::is_subclass(&metatype, &vm.types.type_) {
+        let message = format!(
+            "type.__new__({}): {} is not a subtype of type",
+            metatype.as_type().unwrap().name,
+            metatype.as_type().unwrap().name
+        );
+        return Err(vm.new_type_error(message));
+    }
+    args.positional.remove(0);
+    type_new(vm, &metatype, args)
+}
+
+/// `type.__init__`, which checks the arguments `type.__new__` took.
+fn type_init(vm: &mut Vm, args: Args) -> PyResult {
+    let count = args.positional.len();
+    if count != 2 && count != 4 {
+        let message = "type.__init__() takes 1 or 3 arguments".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    Ok(vm.none())
+}
+
+/// `type.__call__(class, *args, **kwargs)`, which creates an instance.
+fn type_call(vm: &mut Vm, mut args: Args) -> PyResult {
+    let class = class_argument(vm, &args, "type.__call__")?.clone();
+    args.positional.remove(0);
+    vm.construct(&class, args)
+}
+
+/// `type.mro(class)`, the method resolution order of a class as a list.
+fn type_mro(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "mro", 1, 1)?;
+    let class = class_argument(vm, &args, "type.mro")?;
+    let data = class.as_type().unwrap();
+    let mro = iter::once(class).chain(&data.mro).cloned().collect();
+    Ok(vm.new_list(mro))
+}
+
+/// `object.__new__(class)`, which creates an instance with the builtin
+/// constructor that `class` inherits.
+fn object_new_method(vm: &mut Vm, mut args: Args) -> PyResult {
+    let class = class_argument(vm, &args, "object.__new__")?.clone();
+    args.positional.remove(0);
+    match native_constructor(&class) {
+        Some(constructor) => constructor(vm, &class, args),
+        None => {
+            let message = format!(
+                "cannot create '{}' instances",
+                class.as_type().unwrap().name
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// `object()`, an instance with an empty attribute dictionary for classes
+/// that derive from `object`, and without one for `object` itself.
+///
+/// Arguments are only allowed for a class that defines `__init__` but not
+/// `__new__`, as the arguments are for its `__init__`.
+pub(super) fn object_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    if !args.positional.is_empty() || !args.keywords.is_empty() {
+        if vm.overrides(class, "__new__") {
+            let message = "object.__new__() takes exactly one argument (the type to instantiate)";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+        if !vm.overrides(class, "__init__") {
+            let message = format!("{}() takes no arguments", class.as_type().unwrap().name);
+            return Err(vm.new_type_error(message));
+        }
+    }
+    let dict = if Vm::is(class, &vm.types.object) {
+        None
+    } else {
+        Some(vm.new_dict())
+    };
+    Ok(PyObject::new(Payload::Object, class.clone(), dict))
+}
+
+/// `object.__repr__(self)`: `<module.Class object at 0x...>`, whatever the
+/// type of `self`.
+fn object_repr(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__repr__", 1, 1)?;
+    let object = &args.positional[0];
+    let text = format!(
+        "<{} object at {:#x}>",
+        vm.class_name(&object.class()),
+        object_id(object)
+    );
+    Ok(vm.new_str(&text))
+}
+
+/// `object.__str__(self)`, which is `repr(self)`.
+fn object_str(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__str__", 1, 1)?;
+    let text = vm.repr(&args.positional[0])?;
+    Ok(vm.new_str(&text))
+}
+
+/// `object.__eq__(self, other, /)`: `True` for the object itself, and
+/// otherwise `NotImplemented`, so that `other` is asked.
+fn object_eq(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__eq__", 2, 2)?;
+    if Vm::is(&args.positional[0], &args.positional[1]) {
+        return Ok(vm.new_bool(true));
+    }
+    Ok(vm.not_implemented())
+}
+
+/// `object.__ne__(self, other, /)`: the opposite of what `self == other`
+/// gives, or `NotImplemented` if `__eq__` gives that.
+fn object_ne(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__ne__", 2, 2)?;
+    let (object, other) = (&args.positional[0], &args.positional[1]);
+    let equal = match vm.python_method(object, "__eq__") {
+        Some(method) => vm.call(&method, Args::new(vec![other.clone()]))?,
+        None => {
+            let equal = vm.builtin_compare(object, CmpOperator::Eq, other)?;
+            vm.new_bool(equal)
+        }
+    };
+    if Vm::is(&equal, &vm.not_implemented()) {
+        return Ok(equal);
+    }
+    let equal = vm.is_true(&equal)?;
+    Ok(vm.new_bool(!equal))
+}
+
+/// `object.__hash__(self)`, from the object's identity.
+fn object_hash(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__hash__", 1, 1)?;
+    Ok(vm.new_int(hash_pointer(&args.positional[0])))
+}
+
+/// `object.__init__(self)`, which takes arguments only when they were for
+/// a `__new__` the class defines.
+fn object_init(vm: &mut Vm, args: Args) -> PyResult {
+    let object = match args.positional.first() {
+        Some(object) => object.clone(),
+        None => {
+            let message = "descriptor '__init__' of 'object' object needs an argument";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+    };
+    if args.positional.len() > 1 || !args.keywords.is_empty() {
+        let class = object.class();
+        if vm.overrides(&class, "__init__") {
+            let message =
+                "object.__init__() takes exactly one argument (the instance to initialize)";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+        if !vm.overrides(&class, "__new__") {
+            let message = format!(
+                "{}.__init__() takes exactly one argument (the instance to initialize)",
+                object.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    }
+    Ok(vm.none())
+}
+
+/// `object.__init_subclass__(class)`, called for each new subclass with
+/// the keyword arguments of its class statement, which must be none.
+fn object_init_subclass(vm: &mut Vm, args: Args) -> PyResult {
+    let class = class_argument(vm, &args, "object.__init_subclass__")?;
+    if !args.keywords.is_empty() {
+        let message = format!(
+            "{}.__init_subclass__() takes no keyword arguments",
+            class.as_type().unwrap().name
+        );
+        return Err(vm.new_type_error(message));
+    }
+    if args.positional.len() > 1 {
+        let message = format!(
+            "{}.__init_subclass__() takes no positional arguments",
+            class.as_type().unwrap().name
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(vm.none())
+}
+
+/// `super(class, object)`. Without arguments, `super()` is handled where
+/// it is called, as it takes them from the calling frame.
+pub(super) fn super_new(vm: &mut Vm, _class: &ObjectRef, args: Args) -> PyResult {
+    if !args.keywords.is_empty() {
+        let message = "super() takes no keyword arguments".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    match args.positional.len() {
+        0 => Err(vm.new_runtime_error("super(): no arguments".to_string())),
+        1 => {
+            let message = "super() without a second argument is not supported yet".to_string();
+            Err(vm.new_type_error(message))
+        }
+        2 => vm.new_super(&args.positional[0], &args.positional[1]),
+        count => {
+            let message = format!("super() takes at most 2 arguments ({} given)", count);
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// The builtin constructor a class inherits: its own, or that of the
+/// first class in its MRO that has one.
+fn native_constructor(class: &ObjectRef) -> Option<NativeConstructor> {
+    let data = class.as_type()?;
+    data.constructor.or_else(|| {
+        data.mro
+            .iter()
+            .find_map(|class| class.as_type().and_then(|data| data.constructor))
+    })
+}
+
+/// The class whose builtin constructor `class` inherits, which decides the
+/// layout of its instances.
+fn solid_base(class: &ObjectRef) -> ObjectRef {
+    let data = class.as_type().unwrap();
+    iter::once(class)
+        .chain(&data.mro)
+        .find(|class| {
+            class
+                .as_type()
+                .is_some_and(|data| data.constructor.is_some())
+        })
+        .unwrap_or(class)
+        .clone()
+}
+
+impl Vm {
+    /// Calls a class: its metaclass's `__call__` if it defines one, and
+    /// otherwise `__new__` and `__init__`.
+    pub(super) fn call_type(&mut self, class: &ObjectRef, args: Args) -> PyResult {
+        let metatype = class.class();
+        if !Vm::is(&metatype, &self.types.type_) {
+            if let Some(call) = self.lookup(&metatype, "__call__") {
+                if let Payload::Function(_) = call.payload {
+                    let mut positional = vec![class.clone()];
+                    positional.extend(args.positional);
+                    return self.call(
+                        &call,
+                        Args {
+                            positional,
+                            keywords: args.keywords,
+                        },
+                    );
+                }
+            }
+        }
+        self.construct(class, args)
+    }
+
+    /// Creates an instance of `class` with its `__new__`, then initializes
+    /// it with its `__init__` if it is an instance of `class`.
+    fn construct(&mut self, class: &ObjectRef, args: Args) -> PyResult {
+        if Vm::is(class, &self.types.type_)
+            && args.positional.len() == 1
+            && args.keywords.is_empty()
+        {
+            return Ok(args.positional[0].class());
+        }
+        let with_instance = |instance: &ObjectRef, args: &Args| {
+            let mut positional = vec![instance.clone()];
+            positional.extend(args.positional.iter().cloned());
+            Args {
+                positional,
+                keywords: args.keywords.clone(),
+            }
+        };
+        let instance = match self.lookup(class, "__new__") {
+            Some(ref new) if matches!(new.payload, Payload::Function(_)) => {
+                self.call(new, with_instance(class, &args))?
+            }
+            _ => match native_constructor(class) {
+                Some(constructor) => constructor(
+                    self,
+                    class,
+                    Args {
+                        positional: args.positional.clone(),
+                        keywords: args.keywords.clone(),
+                    },
+                )?,
+                None => {
+                    let message = format!(
+                        "cannot create '{}' instances",
+                        class.as_type().unwrap().name
+                    );
+                    return Err(self.new_type_error(message));
+                }
+            },
+        };
+        if !Vm::is_instance(&instance, class) || !self.overrides(&instance.class(), "__init__") {
+            return Ok(instance);
+        }
+        let init = self.lookup(&instance.class(), "__init__").unwrap();
+        let result = self.call(&init, with_instance(&instance, &args))?;
+        if !Vm::is(&result, &self.none) {
+            let message = format!(
+                "__init__() should return None, not '{}'",
+                result.type_name()
+            );
+            return Err(self.new_type_error(message));
+        }
+        Ok(instance)
+    }
+
+    /// Whether `class` has its own `name`, rather than `object`'s.
+    pub(super) fn overrides(&self, class: &ObjectRef, name: &str) -> bool {
+        match (
+            self.lookup(class, name),
+            self.lookup(&self.types.object, name),
+        ) {
+            (Some(a), Some(b)) => !Vm::is(&a, &b),
+            (a, b) => a.is_some() != b.is_some(),
+        }
+    }
+
+    /// Creates a class, as `type.__new__` does: works out its metaclass and
+    /// MRO, copies its namespace, fills in the `__class__` cell of its
+    /// methods, and tells its attributes and its bases about it.
+    fn new_class(
+        &mut self,
+        metatype: &ObjectRef,
+        name: String,
+        bases: Vec<ObjectRef>,
+        namespace: &ObjectRef,
+        keywords: Vec<(String, ObjectRef)>,
+    ) -> PyResult {
+        let bases = if bases.is_empty() {
+            vec![self.types.object.clone()]
+        } else {
+            bases
+        };
+        let metatype = self.calculate_metaclass(metatype, &bases)?;
+        for (index, base) in bases.iter().enumerate() {
+            let data = match base.as_type() {
+                Some(data) => data,
+                None => return Err(self.new_type_error("bases must be types".to_string())),
+            };
+            if bases[..index].iter().any(|other| Vm::is(other, base)) {
+                let message = format!("duplicate base class {}", data.name);
+                return Err(self.new_type_error(message));
+            }
+        }
+        let mut solid = solid_base(&bases[0]);
+        for base in &bases[1..] {
+            let other = solid_base(base);
+            if Vm::is_subclass(&other, &solid) {
+                solid = other;
+            } else if !Vm::is_subclass(&solid, &other) {
+                let message = "multiple bases have instance lay-out conflict".to_string();
+                return Err(self.new_type_error(message));
+            }
+        }
+        let mro = self.linearize(&bases)?;
+
+        let dict = self.new_dict();
+        self.dict_extend(dict.as_dict().unwrap(), namespace.as_dict().unwrap())?;
+        let key = self.new_str("__qualname__");
+        let qualname = match self.dict_remove(dict.as_dict().unwrap(), &key)? {
+            Some(qualname) => match qualname.as_str() {
+                Some(qualname) => qualname.to_string(),
+                None => {
+                    let message = format!(
+                        "type __qualname__ must be a str, not {}",
+                        qualname.type_name()
+                    );
+                    return Err(self.new_type_error(message));
+                }
+            },
+            None => name.clone(),
+        };
+        let key = self.new_str("__classcell__");
+        let cell = self.dict_remove(dict.as_dict().unwrap(), &key)?;
+        // A class that defines equality but not hashing is unhashable, as
+        // its instances could be equal with different hashes. One without a
+        // docstring has `None` for `__doc__`.
+        {
+            let dict = dict.as_dict().unwrap();
+            let defines = |name: &str| dict.borrow().get_str(name).is_some();
+            if defines("__eq__") && !defines("__hash__") {
+                let none = self.none();
+                self.dict_set_str(dict, "__hash__", none);
+            }
+            if !defines("__doc__") {
+                let none = self.none();
+                self.dict_set_str(dict, "__doc__", none);
+            }
+            if defines("__del__") {
+                gc::define_finalizer();
+            }
+        }
+
+        let class = PyObject::new(
+            Payload::Type(TypeData {
+                name,
+                qualname,
+                heap: true,
+                bases,
+                mro,
+                constructor: None,
+            }),
+            metatype,
+            Some(dict.clone()),
+        );
+        if let Some(cell) = cell {
+            match cell.payload {
+                Payload::Cell(ref value) => *value.borrow_mut() = Some(class.clone()),
+                _ => {
+                    let message = format!(
+                        "__classcell__ must be a nonlocal cell, not {}",
+                        self.repr(&cell.class())?
+                    );
+                    return Err(self.new_type_error(message));
+                }
+            }
+        }
+
+        let attributes: Vec<(ObjectRef, ObjectRef)> = dict
+            .as_dict()
+            .unwrap()
+            .borrow()
+            .iter()
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect();
+        for (key, value) in attributes {
+            if let Some(set_name) = self.special_method(&value, "__set_name__") {
+                self.call(&set_name, Args::new(vec![class.clone(), key]))?;
+            }
+        }
+        let init_subclass = class.as_type().unwrap().mro.iter().find_map(|base| {
+            base.dict()?
+                .as_dict()?
+                .borrow()
+                .get_str("__init_subclass__")
+        });
+        if let Some(mut init_subclass) = init_subclass {
+            // It is a class method whether or not it is declared as one.
+            if let Payload::ClassMethod(ref function) = init_subclass.payload {
+                init_subclass = function.clone();
+            }
+            self.call(
+                &init_subclass,
+                Args {
+                    positional: vec![class.clone()],
+                    keywords,
+                },
+            )?;
+        }
+        Ok(class)
+    }
+
+    /// The most derived of `metatype` and the metaclasses of `bases`, which
+    /// must all be in one line of inheritance.
+    fn calculate_metaclass(&mut self, metatype: &ObjectRef, bases: &[ObjectRef]) -> PyResult {
+        let mut winner = metatype.clone();
+        for base in bases {
+            let candidate = base.class();
+            if Vm::is_subclass(&winner, &candidate) {
+                continue;
+            }
+            if Vm::is_subclass(&candidate, &winner) {
+                winner = candidate;
+                continue;
+            }
+            let message = "metaclass conflict: the metaclass of a derived class must be a \
+                           (non-strict) subclass of the metaclasses of all its bases";
+            return Err(self.new_type_error(message.to_string()));
+        }
+        Ok(winner)
+    }
+
+    /// The MRO of a class with `bases`, without the class itself, by C3
+    /// linearization: each class comes before its bases, and the bases of
+    /// every class keep their order.
+    fn linearize(&mut self, bases: &[ObjectRef]) -> PyResult<Vec<ObjectRef>> {
+        let mut sequences: Vec<Vec<ObjectRef>> = bases
+            .iter()
+            .map(|base| {
+                iter::once(base)
+                    .chain(&base.as_type().unwrap().mro)
+                    .cloned()
+                    .collect()
+            })
+            .collect();
+        sequences.push(bases.to_vec());
+        let mut mro = Vec::new();
+        loop {
+            sequences.retain(|sequence| !sequence.is_empty());
+            if sequences.is_empty() {
+                return Ok(mro);
+            }
+            // The next class is the first head that is in no tail.
+            let next = sequences
+                .iter()
+                .map(|sequence| &sequence[0])
+                .find(|head| {
+                    !sequences
+                        .iter()
+                        .any(|sequence| sequence[1..].iter().any(|class| Vm::is(class, head)))
+                })
+                .cloned();
+            let next = match next {
+                Some(next) => next,
+                None => {
+                    let mut names: Vec<String> = Vec::new();
+                    for sequence in &sequences {
+                        let name = sequence[0].as_type().unwrap().name.clone();
+                        if !names.contains(&name) {
+                            names.push(name);
+                        }
+                    }
+                    let message = format!(
+                        "Cannot create a consistent method resolution\norder (MRO) for bases {}",
+                        names.join(", ")
+                    );
+                    return Err(self.new_type_error(message));
+                }
+            };
+            for sequence in &mut sequences {
+                if Vm::is(&sequence[0], &next) {
+                    sequence.remove(0);
+                }
+            }
+            mro.push(next);
+        }
+    }
+
+    /// `super(class, object)`, checking that `object` is an instance or a
+    /// subclass of `class`.
+    pub fn new_super(&mut self, class: &ObjectRef, object: &ObjectRef) -> PyResult {
+        if class.as_type().is_none() {
+            let message = format!(
+                "super() argument 1 must be a type, not {}",
+                class.type_name()
+            );
+            return Err(self.new_type_error(message));
+        }
+        let subclass = object.as_type().is_some() && Vm::is_subclass(object, class);
+        if !subclass && !Vm::is_instance(object, class) {
+            let message = "super(type, obj): obj must be an instance or subtype of type";
+            return Err(self.new_type_error(message.to_string()));
+        }
+        Ok(PyObject::new(
+            Payload::Super {
+                class: class.clone(),
+                object: object.clone(),
+            },
+            self.types.super_.clone(),
+            None,
+        ))
+    }
+
+    /// Looks `name` up for a `super` object, on the classes after `class`
+    /// in the MRO it starts from. `None` if none of them has it.
+    pub(super) fn super_getattr(
+        &mut self,
+        class: &ObjectRef,
+        object: &ObjectRef,
+        name: &str,
+    ) -> PyResult<Option<ObjectRef>> {
+        let start = super_start(class, object);
+        let data = start.as_type().unwrap();
+        let mro: Vec<ObjectRef> = iter::once(&start).chain(&data.mro).cloned().collect();
+        let position = match mro.iter().position(|other| Vm::is(other, class)) {
+            Some(position) => position,
+            None => return Ok(None),
+        };
+        let value = mro[position + 1..]
+            .iter()
+            .find_map(|class| class.dict()?.as_dict()?.borrow().get_str(name));
+        match value {
+            Some(value) => {
+                let instance = if Vm::is(object, &start) {
+                    None
+                } else {
+                    Some(object)
+                };
+                self.bind(name, value, instance, &start).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// A class attribute as it is seen through `instance`, or through the
+    /// class `owner` itself: functions become methods of the instance, and
+    /// other descriptors have their `__get__` called.
+    ///
+    /// `__new__` is never bound, and `__init_subclass__` and
+    /// `__class_getitem__` are bound to the class, as if they were a static
+    /// and class methods.
+    pub(super) fn bind(
+        &mut self,
+        name: &str,
+        value: ObjectRef,
+        instance: Option<&ObjectRef>,
+        owner: &ObjectRef,
+    ) -> PyResult {
+        match value.payload {
+            _ if name == "__new__" => Ok(value),
+            Payload::StaticMethod(ref function) => Ok(function.clone()),
+            Payload::ClassMethod(ref function) => {
+                Ok(self.new_method(function.clone(), owner.clone()))
+            }
+            Payload::Function(_) | Payload::Builtin(_)
+                if name == "__init_subclass__" || name == "__class_getitem__" =>
+            {
+                Ok(self.new_method(value, owner.clone()))
+            }
+            Payload::Function(_) | Payload::Builtin(_) => Ok(match instance {
+                Some(instance) => self.new_method(value, instance.clone()),
+                None => value,
+            }),
+            _ => match self.lookup(&value.class(), "__get__") {
+                Some(get) => {
+                    let instance = instance.cloned().unwrap_or_else(|| self.none());
+                    self.call(&get, Args::new(vec![value, instance, owner.clone()]))
+                }
+                None => Ok(value),
+            },
+        }
+    }
+
+    /// Whether a class attribute takes precedence over the instance's own
+    /// attributes: it has `__get__`, and `__set__` or `__delete__`.
+    pub(super) fn is_data_descriptor(&self, value: &ObjectRef) -> bool {
+        if matches!(value.payload, Payload::Function(_) | Payload::Builtin(_)) {
+            return false;
+        }
+        let class = value.class();
+        self.lookup(&class, "__get__").is_some()
+            && (self.lookup(&class, "__set__").is_some()
+                || self.lookup(&class, "__delete__").is_some())
+    }
+
+    /// The `__set__` or `__delete__` method of a class attribute, which
+    /// handles that operation on instances instead of their dictionary.
+    pub(super) fn descriptor_method(
+        &self,
+        class: &ObjectRef,
+        name: &str,
+        method: &str,
+    ) -> Option<(ObjectRef, ObjectRef)> {
+        let value = self.lookup(class, name)?;
+        if matches!(value.payload, Payload::Function(_) | Payload::Builtin(_)) {
+            return None;
+        }
+        let method = self.lookup(&value.class(), method)?;
+        Some((method, value))
+    }
+
+    /// A method defined in Python on the class of an object made from a
+    /// class statement, such as its `__repr__`, bound to the object. The
+    /// classes of builtin modules define theirs natively. `None` if the
+    /// first class in the MRO to define `name` is built in, such as
+    /// `object` or the `list` a class derives from, whose method is what
+    /// the VM does without one.
+    pub(super) fn python_method(&mut self, object: &ObjectRef, name: &str) -> Option<ObjectRef> {
+        let class = object.class();
+        let data = class.as_type()?;
+        if !data.heap {
+            return None;
+        }
+        let (owner, method) = iter::once(&class)
+            .chain(data.mro.iter())
+            .find_map(|owner| {
+                let method = owner.dict()?.as_dict()?.borrow().get_str(name)?;
+                Some((owner, method))
+            })?;
+        if !owner.as_type().is_some_and(|data| data.heap) {
+            return None;
+        }
+        match method.payload {
+            Payload::Function(_) | Payload::Builtin(_) => {
+                Some(self.new_method(method, object.clone()))
+            }
+            _ => None,
+        }
+    }
+
+    /// How `repr` names a class: with its module, unless it is built in.
+    pub fn class_name(&self, class: &ObjectRef) -> String {
+        let data = match class.as_type() {
+            Some(data) => data,
+            None => return "?".to_string(),
+        };
+        if !data.heap {
+            return data.name.clone();
+        }
+        let module = class
+            .dict()
+            .and_then(|dict| dict.as_dict()?.borrow().get_str("__module__"));
+        match module.as_ref().and_then(|module| module.as_str()) {
+            Some(module) if module != "builtins" => format!("{}.{}", module, data.qualname),
+            _ => data.qualname.clone(),
+        }
+    }
+}
+
+/// The class whose MRO a `super` object searches: `object` itself if it is
+/// a subclass of `class`, and otherwise the class of `object`.
+fn super_start(class: &ObjectRef, object: &ObjectRef) -> ObjectRef {
+    if object.as_type().is_some() && Vm::is_subclass(object, class) {
+        object.clone()
+    } else {
+        object.class()
+    }
+}
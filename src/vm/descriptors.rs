@@ -0,0 +1,318 @@
+//! The builtin descriptors `property`, `staticmethod` and `classmethod`.
+//!
+//! Attribute lookup finds descriptors by their class's `__get__`, `__set__`
+//! and `__delete__`, so these types define them as methods rather than
+//! being special cased there, and their subclasses can override them.
+
+use std::cell::RefCell;
+
+use ast::repr_str;
+
+use super::builtins::{check_count, keyword_arguments, new_instance};
+use super::object::{Args, NativeFunction, ObjectRef, Payload, Property, PyResult};
+use super::Vm;
+
+/// Adds the methods of the descriptor types to their classes, and their
+/// names to the builtins.
+pub(super) fn add_builtins(vm: &mut Vm) {
+    let property_methods: [(&'static str, NativeFunction); 7] = [
+        ("__get__", property_get),
+        ("__set__", property_set),
+        ("__delete__", property_delete),
+        ("__set_name__", property_set_name),
+        ("getter", property_getter),
+        ("setter", property_setter),
+        ("deleter", property_deleter),
+    ];
+    let staticmethod_methods: [(&'static str, NativeFunction); 1] = [("__get__", staticmethod_get)];
+    let classmethod_methods: [(&'static str, NativeFunction); 1] = [("__get__", classmethod_get)];
+    let property = vm.types.property.clone();
+    let staticmethod = vm.types.staticmethod.clone();
+    let classmethod = vm.types.classmethod.clone();
+    for &(class, methods) in &[
+        (&property, &property_methods[..]),
+        (&staticmethod, &staticmethod_methods[..]),
+        (&classmethod, &classmethod_methods[..]),
+    ] {
+        let dict = class.dict().unwrap().clone();
+        for &(name, function) in methods {
+            let method = vm.new_builtin(name, function);
+            vm.dict_set_str(dict.as_dict().unwrap(), name, method);
+        }
+    }
+
+    let builtins = vm.builtins().clone();
+    for (name, value) in [
+        ("property", property),
+        ("staticmethod", staticmethod),
+        ("classmethod", classmethod),
+    ] {
+        vm.dict_set_str(builtins.as_dict().unwrap(), name, value);
+    }
+}
+
+/// `property(fget=None, fset=None, fdel=None, doc=None)`. Without `doc`,
+/// the docstring is that of the getter.
+pub(super) fn property_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let names = ["fget", "fset", "fdel", "doc"];
+    let mut keywords = keyword_arguments(vm, keywords, "property", &names)?;
+    let count = positional.len() + keywords.iter().flatten().count();
+    if count > names.len() {
+        let message = format!("property() takes at most 4 arguments ({} given)", count);
+        return Err(vm.new_type_error(message));
+    }
+    for (index, value) in positional.into_iter().enumerate() {
+        if keywords[index].is_some() {
+            let message = format!(
+                "argument for property() given by name ('{}') and position ({})",
+                names[index],
+                index + 1
+            );
+            return Err(vm.new_type_error(message));
+        }
+        keywords[index] = Some(value);
+    }
+    let none = vm.none();
+    let mut values = keywords
+        .into_iter()
+        .map(|value| value.filter(|value| !Vm::is(value, &none)));
+    let fget = values.next().unwrap();
+    let fset = values.next().unwrap();
+    let fdel = values.next().unwrap();
+    let mut doc = values.next().unwrap();
+    let getter_doc = doc.is_none() && fget.is_some();
+    if getter_doc {
+        doc = vm.getattr(fget.as_ref().unwrap(), "__doc__").ok();
+    }
+    let property = Property {
+        fget,
+        fset,
+        fdel,
+        doc: RefCell::new(doc.unwrap_or(none)),
+        getter_doc,
+        name: RefCell::new(None),
+    };
+    Ok(new_instance(vm, class, Payload::Property(property)))
+}
+
+/// The property a method of `property` was called on, and its other
+/// arguments.
+fn property_receiver(vm: &mut Vm, args: Args, name: &str) -> PyResult<(ObjectRef, Vec<ObjectRef>)> {
+    let mut positional = args.positional.into_iter();
+    match positional.next() {
+        Some(property) if matches!(property.payload, Payload::Property(_)) => {
+            Ok((property, positional.collect()))
+        }
+        Some(other) => {
+            let message = format!(
+                "descriptor '{}' for 'property' objects doesn't apply to a '{}' object",
+                name,
+                other.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+        None => {
+            let message = format!("unbound method property.{}() needs an argument", name);
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+fn as_property(object: &ObjectRef) -> &Property {
+    match object.payload {
+        Payload::Property(ref property) => property,
+        _ => unreachable!("checked by property_receiver"),
+    }
+}
+
+/// The error for a property used without the function for `action`, such
+/// as "property 'x' of 'C' object has no setter".
+fn missing_function(
+    vm: &mut Vm,
+    property: &Property,
+    object: &ObjectRef,
+    action: &str,
+) -> ObjectRef {
+    let class = object.class();
+    let qualname = match class.as_type() {
+        Some(data) => data.qualname.clone(),
+        None => object.type_name(),
+    };
+    let name = match *property.name.borrow() {
+        Some(ref name) => match name.as_str() {
+            Some(name) => format!("{} ", repr_str(name)),
+            None => String::new(),
+        },
+        None => String::new(),
+    };
+    let message = format!(
+        "property {}of {} object has no {}",
+        name,
+        repr_str(&qualname),
+        action
+    );
+    vm.new_attribute_error(message)
+}
+
+/// `property.__get__(self, instance, owner=None)`: the property itself
+/// when looked up on the class.
+fn property_get(vm: &mut Vm, args: Args) -> PyResult {
+    check_count(vm, &args, "__get__", 2, 3)?;
+    let (property, args) = property_receiver(vm, args, "__get__")?;
+    let instance = &args[0];
+    if Vm::is(instance, &vm.none()) {
+        return Ok(property);
+    }
+    let data = as_property(&property);
+    match data.fget {
+        Some(ref fget) => vm.call(fget, Args::new(vec![instance.clone()])),
+        None => Err(missing_function(vm, data, instance, "getter")),
+    }
+}
+
+/// `property.__set__(self, instance, value)`.
+fn property_set(vm: &mut Vm, args: Args) -> PyResult {
+    check_count(vm, &args, "__set__", 3, 3)?;
+    let (property, args) = property_receiver(vm, args, "__set__")?;
+    let data = as_property(&property);
+    match data.fset {
+        Some(ref fset) => {
+            vm.call(fset, Args::new(args))?;
+            Ok(vm.none())
+        }
+        None => Err(missing_function(vm, data, &args[0], "setter")),
+    }
+}
+
+/// `property.__delete__(self, instance)`.
+fn property_delete(vm: &mut Vm, args: Args) -> PyResult {
+    check_count(vm, &args, "__delete__", 2, 2)?;
+    let (property, args) = property_receiver(vm, args, "__delete__")?;
+    let data = as_property(&property);
+    match data.fdel {
+        Some(ref fdel) => {
+            vm.call(fdel, Args::new(args))?;
+            Ok(vm.none())
+        }
+        None => Err(missing_function(vm, data, &args[0], "deleter")),
+    }
+}
+
+/// `property.__set_name__(self, owner, name)`, called when the class the
+/// property is assigned in is made, which names it in error messages.
+fn property_set_name(vm: &mut Vm, args: Args) -> PyResult {
+    check_count(vm, &args, "__set_name__", 3, 3)?;
+    let (property, mut args) = property_receiver(vm, args, "__set_name__")?;
+    *as_property(&property).name.borrow_mut() = args.pop();
+    Ok(vm.none())
+}
+
+/// A copy of a property with one of its functions replaced, made by
+/// calling its class, as the `getter`, `setter` and `deleter` decorators
+/// do.
+fn property_copy(vm: &mut Vm, args: Args, name: &str, index: usize) -> PyResult {
+    check_count(vm, &args, name, 2, 2)?;
+    let (property, mut args) = property_receiver(vm, args, name)?;
+    let data = as_property(&property);
+    let none = vm.none();
+    let mut functions: Vec<ObjectRef> = [&data.fget, &data.fset, &data.fdel]
+        .iter()
+        .map(|&function| function.clone().unwrap_or_else(|| none.clone()))
+        .collect();
+    functions[index] = args.pop().unwrap();
+    // A docstring taken from the getter is taken from the new one.
+    let doc = if data.getter_doc && index == 0 {
+        none
+    } else {
+        data.doc.borrow().clone()
+    };
+    functions.push(doc);
+    let copy = vm.call(&property.class(), Args::new(functions))?;
+    if let Payload::Property(ref copied) = copy.payload {
+        *copied.name.borrow_mut() = data.name.borrow().clone();
+    }
+    Ok(copy)
+}
+
+/// `property.getter(fget)`.
+fn property_getter(vm: &mut Vm, args: Args) -> PyResult {
+    property_copy(vm, args, "getter", 0)
+}
+
+/// `property.setter(fset)`.
+fn property_setter(vm: &mut Vm, args: Args) -> PyResult {
+    property_copy(vm, args, "setter", 1)
+}
+
+/// `property.deleter(fdel)`.
+fn property_deleter(vm: &mut Vm, args: Args) -> PyResult {
+    property_copy(vm, args, "deleter", 2)
+}
+
+/// The only argument of the constructor of `staticmethod` or
+/// `classmethod`.
+fn wrapped_function(vm: &mut Vm, args: Args, name: &str) -> PyResult {
+    if !args.keywords.is_empty() {
+        let message = format!("{}() takes no keyword arguments", name);
+        return Err(vm.new_type_error(message));
+    }
+    check_count(vm, &args, name, 1, 1)?;
+    Ok(args.positional.into_iter().next().unwrap())
+}
+
+/// `staticmethod(function)`.
+pub(super) fn staticmethod_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let function = wrapped_function(vm, args, "staticmethod")?;
+    Ok(new_instance(vm, class, Payload::StaticMethod(function)))
+}
+
+/// `classmethod(function)`.
+pub(super) fn classmethod_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let function = wrapped_function(vm, args, "classmethod")?;
+    Ok(new_instance(vm, class, Payload::ClassMethod(function)))
+}
+
+/// `staticmethod.__get__(self, instance, owner=None)`: the function.
+fn staticmethod_get(vm: &mut Vm, args: Args) -> PyResult {
+    check_count(vm, &args, "__get__", 2, 3)?;
+    match args.positional[0].payload {
+        Payload::StaticMethod(ref function) => Ok(function.clone()),
+        _ => {
+            let message = format!(
+                "descriptor '__get__' for 'staticmethod' objects doesn't apply to a '{}' object",
+                args.positional[0].type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// `classmethod.__get__(self, instance, owner=None)`: the function bound
+/// to the owner, or else to the class of the instance.
+fn classmethod_get(vm: &mut Vm, args: Args) -> PyResult {
+    check_count(vm, &args, "__get__", 2, 3)?;
+    let function = match args.positional[0].payload {
+        Payload::ClassMethod(ref function) => function.clone(),
+        _ => {
+            let message = format!(
+                "descriptor '__get__' for 'classmethod' objects doesn't apply to a '{}' object",
+                args.positional[0].type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let none = vm.none();
+    let owner = match args.positional.get(2) {
+        Some(owner) if !Vm::is(owner, &none) => owner.clone(),
+        _ if Vm::is(&args.positional[1], &none) => {
+            let message = "__get__(None, None) is invalid".to_string();
+            return Err(vm.new_type_error(message));
+        }
+        _ => args.positional[1].class(),
+    };
+    Ok(vm.new_method(function, owner))
+}
@@ -0,0 +1,678 @@
+//! The insertion-ordered hash table behind dicts, sets and namespaces, and
+//! the methods of `dict` and its views.
+//!
+//! Finding a key may run Python code, for an `__eq__` defined in Python, so
+//! the lookups that compare keys are methods of `Vm` taking the table's
+//! `RefCell`, and release the borrow while comparing.
+//!
+//! Every table has a version, which changes whenever the table does, and
+//! which no other table ever has, so that the inline caches can tell that a
+//! namespace is as it was when they looked in it by comparing one number.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use super::builtins::check_count;
+use super::object::{Args, NativeFunction, ObjectRef, Payload, PyObject, PyResult, ViewKind};
+use super::ops::hash_str;
+use super::Vm;
+
+/// Removed entries are left as holes until they outnumber the live ones.
+const MIN_COMPACT_SIZE: usize = 8;
+
+pub struct Entry {
+    pub hash: i64,
+    pub key: ObjectRef,
+    pub value: ObjectRef,
+}
+
+thread_local! {
+    /// The version given to the next table made or changed.
+    static NEXT_VERSION: Cell<u64> = const { Cell::new(1) };
+    /// The version of the last change to the namespace of any class.
+    static TYPES_VERSION: Cell<u64> = const { Cell::new(0) };
+}
+
+fn next_version() -> u64 {
+    NEXT_VERSION.with(|next| {
+        let version = next.get();
+        next.set(version + 1);
+        version
+    })
+}
+
+/// A number that changes whenever the namespace of any class does, or an
+/// object's class is replaced, so that what was found by looking up an
+/// attribute in classes may be reused while it stays the same.
+pub(super) fn types_version() -> u64 {
+    TYPES_VERSION.with(Cell::get)
+}
+
+/// Notes a change to classes that is not a change to their namespaces.
+pub(super) fn types_changed() {
+    TYPES_VERSION.with(|types| types.set(next_version()));
+}
+
+pub struct Dict {
+    entries: Vec<Option<Entry>>,
+    indices: HashMap<i64, Vec<usize>>,
+    len: usize,
+    version: u64,
+    /// Whether the table is the namespace of a class, whose changes change
+    /// `types_version`.
+    watched: bool,
+}
+
+impl Default for Dict {
+    fn default() -> Dict {
+        Dict {
+            entries: Vec::new(),
+            indices: HashMap::new(),
+            len: 0,
+            version: next_version(),
+            watched: false,
+        }
+    }
+}
+
+impl Dict {
+    pub fn new() -> Dict {
+        Dict::default()
+    }
+
+    /// The table's current version.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Makes the table the namespace of a class.
+    pub(super) fn watch(&mut self) {
+        self.watched = true;
+        self.touch();
+    }
+
+    fn touch(&mut self) {
+        self.version = next_version();
+        if self.watched {
+            TYPES_VERSION.with(|types| types.set(self.version));
+        }
+    }
+
+    /// Empties the table, returning what it held, with the entries freed
+    /// once the borrow of the table has ended.
+    pub fn take(&mut self) -> Dict {
+        let removed = ::std::mem::take(self);
+        self.watched = removed.watched;
+        self.touch();
+        removed
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The entries in insertion order.
+    pub fn iter(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter_map(Option::as_ref)
+    }
+
+    /// The first entry at or after `position`, with its position, for
+    /// iterators that hold on to a place in the table.
+    pub fn entry_from(&self, position: usize) -> Option<(usize, &Entry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .skip(position)
+            .find_map(|(index, entry)| entry.as_ref().map(|entry| (index, entry)))
+    }
+
+    /// The last entry before `position`, with its position, for iterators
+    /// that go backwards.
+    pub fn entry_before(&self, position: usize) -> Option<(usize, &Entry)> {
+        self.entries[..position.min(self.entries.len())]
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(index, entry)| entry.as_ref().map(|entry| (index, entry)))
+    }
+
+    /// Removes the entry added last.
+    pub fn pop_last(&mut self) -> Option<Entry> {
+        let (index, _) = self.entry_before(self.entries.len())?;
+        self.remove_at(index)
+    }
+
+    /// Looks up a string key without comparing it to keys of other types,
+    /// for namespaces.
+    pub fn get_str(&self, key: &str) -> Option<ObjectRef> {
+        self.get_str_hashed(hash_str(key), key)
+    }
+
+    /// `get_str` for a key whose hash is known.
+    pub fn get_str_hashed(&self, hash: i64, key: &str) -> Option<ObjectRef> {
+        let candidates = self.indices.get(&hash)?;
+        candidates.iter().find_map(|&index| {
+            let entry = self.entries[index].as_ref()?;
+            if entry.key.as_str() == Some(key) {
+                Some(entry.value.clone())
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.indices.clear();
+        self.len = 0;
+        self.touch();
+    }
+
+    fn candidates(&self, hash: i64) -> Vec<usize> {
+        self.indices.get(&hash).cloned().unwrap_or_default()
+    }
+
+    fn push(&mut self, hash: i64, key: ObjectRef, value: ObjectRef) {
+        self.indices
+            .entry(hash)
+            .or_default()
+            .push(self.entries.len());
+        self.entries.push(Some(Entry { hash, key, value }));
+        self.len += 1;
+        self.touch();
+    }
+
+    fn replace_value(&mut self, index: usize, value: ObjectRef) {
+        if let Some(ref mut entry) = self.entries[index] {
+            entry.value = value;
+            self.touch();
+        }
+    }
+
+    fn remove_at(&mut self, index: usize) -> Option<Entry> {
+        let entry = self.entries[index].take()?;
+        if let Some(indices) = self.indices.get_mut(&entry.hash) {
+            indices.retain(|&i| i != index);
+            if indices.is_empty() {
+                self.indices.remove(&entry.hash);
+            }
+        }
+        self.len -= 1;
+        self.touch();
+        if self.entries.len() >= MIN_COMPACT_SIZE && self.len * 2 < self.entries.len() {
+            self.compact();
+        }
+        Some(entry)
+    }
+
+    fn compact(&mut self) {
+        let entries: Vec<Entry> = self.entries.drain(..).flatten().collect();
+        self.indices.clear();
+        self.len = 0;
+        for entry in entries {
+            self.push(entry.hash, entry.key, entry.value);
+        }
+    }
+}
+
+impl Vm {
+    /// The position of `key` in the table, if it is there.
+    fn dict_find(
+        &mut self,
+        dict: &RefCell<Dict>,
+        hash: i64,
+        key: &ObjectRef,
+    ) -> PyResult<Option<usize>> {
+        let candidates = dict.borrow().candidates(hash);
+        for index in candidates {
+            let existing = match dict.borrow().entries.get(index) {
+                Some(Some(entry)) => entry.key.clone(),
+                _ => continue,
+            };
+            if Rc::ptr_eq(&existing, key) || self.eq(&existing, key)? {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn dict_get(
+        &mut self,
+        dict: &RefCell<Dict>,
+        key: &ObjectRef,
+    ) -> PyResult<Option<ObjectRef>> {
+        let hash = self.hash(key)?;
+        Ok(match self.dict_find(dict, hash, key)? {
+            Some(index) => dict.borrow().entries[index]
+                .as_ref()
+                .map(|entry| entry.value.clone()),
+            None => None,
+        })
+    }
+
+    pub fn dict_contains(&mut self, dict: &RefCell<Dict>, key: &ObjectRef) -> PyResult<bool> {
+        let hash = self.hash(key)?;
+        Ok(self.dict_find(dict, hash, key)?.is_some())
+    }
+
+    /// Sets `key` to `value`, keeping the original key and position if the
+    /// key is already there.
+    pub fn dict_set(
+        &mut self,
+        dict: &RefCell<Dict>,
+        key: ObjectRef,
+        value: ObjectRef,
+    ) -> PyResult<()> {
+        let hash = self.hash(&key)?;
+        match self.dict_find(dict, hash, &key)? {
+            Some(index) => dict.borrow_mut().replace_value(index, value),
+            None => dict.borrow_mut().push(hash, key, value),
+        }
+        Ok(())
+    }
+
+    pub fn dict_set_str(&mut self, dict: &RefCell<Dict>, key: &str, value: ObjectRef) {
+        let key = self.intern(key);
+        // A string key compares without running Python code.
+        let _ = self.dict_set(dict, key, value);
+    }
+
+    /// Removes `key`, returning its value if it was there.
+    pub fn dict_remove(
+        &mut self,
+        dict: &RefCell<Dict>,
+        key: &ObjectRef,
+    ) -> PyResult<Option<ObjectRef>> {
+        let hash = self.hash(key)?;
+        Ok(match self.dict_find(dict, hash, key)? {
+            Some(index) => dict.borrow_mut().remove_at(index).map(|entry| entry.value),
+            None => None,
+        })
+    }
+
+    /// Adds the entries of `source` to `dict`, as `dict.update` does: the
+    /// entries of a dict, the keys of a mapping with their values, or else
+    /// the `(key, value)` pairs of an iterable.
+    pub fn dict_update(&mut self, dict: &RefCell<Dict>, source: &ObjectRef) -> PyResult<()> {
+        if let Payload::Dict(ref source) = source.payload {
+            return self.dict_extend(dict, source);
+        }
+        if self.lookup(&source.class(), "keys").is_some() {
+            let keys = self.getattr(source, "keys")?;
+            let keys = self.call(&keys, Args::default())?;
+            let iterator = self.iter(&keys)?;
+            while let Some(key) = self.next(&iterator)? {
+                let value = self.getitem(source, &key)?;
+                self.dict_set(dict, key, value)?;
+            }
+            return Ok(());
+        }
+        let iterator = self.iter(source)?;
+        let mut index = 0;
+        while let Some(item) = self.next(&iterator)? {
+            if !self.is_iterable(&item) {
+                let message = format!(
+                    "cannot convert dictionary update sequence element #{} to a sequence",
+                    index
+                );
+                return Err(self.new_type_error(message));
+            }
+            let pair = self.iterate(&item)?;
+            if pair.len() != 2 {
+                let message = format!(
+                    "dictionary update sequence element #{} has length {}; 2 is required",
+                    index,
+                    pair.len()
+                );
+                return Err(self.new_value_error(message));
+            }
+            let mut pair = pair.into_iter();
+            let (key, value) = (pair.next().unwrap(), pair.next().unwrap());
+            self.dict_set(dict, key, value)?;
+            index += 1;
+        }
+        Ok(())
+    }
+
+    /// A view of the keys, values or items of a dict.
+    pub fn new_dict_view(&self, dict: ObjectRef, kind: ViewKind) -> ObjectRef {
+        let class = match kind {
+            ViewKind::Keys => &self.types.dict_keys,
+            ViewKind::Values => &self.types.dict_values,
+            ViewKind::Items => &self.types.dict_items,
+        };
+        PyObject::new(Payload::DictView { dict, kind }, class.clone(), None)
+    }
+
+    /// What a view of `kind` gives for an entry.
+    pub(super) fn view_item(&self, entry: &Entry, kind: ViewKind) -> ObjectRef {
+        match kind {
+            ViewKind::Keys => entry.key.clone(),
+            ViewKind::Values => entry.value.clone(),
+            ViewKind::Items => self.new_tuple(vec![entry.key.clone(), entry.value.clone()]),
+        }
+    }
+
+    /// The items of a view, in the order of the dict.
+    pub(super) fn view_items(&self, dict: &ObjectRef, kind: ViewKind) -> Vec<ObjectRef> {
+        let entries = dict.as_dict().unwrap().borrow();
+        entries
+            .iter()
+            .map(|entry| self.view_item(entry, kind))
+            .collect()
+    }
+
+    /// `item in view`: a key, or a `(key, value)` pair whose key maps to an
+    /// equal value, or for values, any equal value.
+    pub(super) fn view_contains(
+        &mut self,
+        dict: &ObjectRef,
+        kind: ViewKind,
+        item: &ObjectRef,
+    ) -> PyResult<bool> {
+        let entries = dict.as_dict().unwrap();
+        match kind {
+            ViewKind::Keys => self.dict_contains(entries, item),
+            ViewKind::Items => {
+                let (key, value) = match item.payload {
+                    Payload::Tuple(ref pair) if pair.len() == 2 => (&pair[0], &pair[1]),
+                    _ => return Ok(false),
+                };
+                match self.dict_get(entries, key)? {
+                    Some(found) => self.same_or_eq(&found, value),
+                    None => Ok(false),
+                }
+            }
+            ViewKind::Values => {
+                for value in self.view_items(dict, kind) {
+                    if self.same_or_eq(&value, item)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+}
+
+/// Adds the methods of `dict` and of its views to their classes.
+pub(super) fn add_methods(vm: &mut Vm) {
+    let methods: [(&'static str, NativeFunction); 13] = [
+        ("__delitem__", delitem),
+        ("__getitem__", getitem),
+        ("__setitem__", setitem),
+        ("clear", clear),
+        ("copy", copy),
+        ("get", get),
+        ("items", items),
+        ("keys", keys),
+        ("pop", pop),
+        ("popitem", popitem),
+        ("setdefault", setdefault),
+        ("update", update),
+        ("values", values),
+    ];
+    let dict = vm.types.dict.dict().unwrap().clone();
+    for &(name, function) in &methods {
+        let method = vm.new_builtin(name, function);
+        vm.dict_set_str(dict.as_dict().unwrap(), name, method);
+    }
+    let function = vm.new_builtin("fromkeys", fromkeys);
+    let fromkeys = PyObject::new(
+        Payload::ClassMethod(function),
+        vm.types.classmethod.clone(),
+        None,
+    );
+    vm.dict_set_str(dict.as_dict().unwrap(), "fromkeys", fromkeys);
+
+    for class in &[vm.types.dict_keys.clone(), vm.types.dict_items.clone()] {
+        let method = vm.new_builtin("isdisjoint", isdisjoint);
+        vm.dict_set_str(
+            class.dict().unwrap().as_dict().unwrap(),
+            "isdisjoint",
+            method,
+        );
+    }
+}
+
+/// Splits the dict a method was called on from the other arguments.
+fn receiver(vm: &mut Vm, mut args: Args, name: &str) -> PyResult<(ObjectRef, Args)> {
+    if args.positional.is_empty() {
+        let message = format!("unbound method dict.{}() needs an argument", name);
+        return Err(vm.new_type_error(message));
+    }
+    let this = args.positional.remove(0);
+    if this.as_dict().is_some() {
+        return Ok((this, args));
+    }
+    let message = format!(
+        "descriptor '{}' for 'dict' objects doesn't apply to a '{}' object",
+        name,
+        this.type_name()
+    );
+    Err(vm.new_type_error(message))
+}
+
+fn reject_keywords(vm: &mut Vm, args: &Args, name: &str) -> PyResult<()> {
+    if args.keywords.is_empty() {
+        return Ok(());
+    }
+    let message = format!("dict.{}() takes no keyword arguments", name);
+    Err(vm.new_type_error(message))
+}
+
+/// The dict of a method that takes no arguments.
+fn no_arguments(vm: &mut Vm, args: Args, name: &str) -> PyResult<ObjectRef> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    if !args.positional.is_empty() {
+        let message = format!(
+            "dict.{}() takes no arguments ({} given)",
+            name,
+            args.positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(this)
+}
+
+/// The dict and the arguments of a method whose arguments are all
+/// positional-only.
+fn positional_arguments(
+    vm: &mut Vm,
+    args: Args,
+    name: &str,
+    min: usize,
+    max: usize,
+) -> PyResult<(ObjectRef, Vec<ObjectRef>)> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    check_count(vm, &args, name, min, max)?;
+    Ok((this, args.positional))
+}
+
+/// `dict.clear()`.
+fn clear(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "clear")?;
+    let removed = this.as_dict().unwrap().borrow_mut().take();
+    drop(removed);
+    Ok(vm.none())
+}
+
+/// `dict.copy()`, a shallow copy that is a plain dict.
+fn copy(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "copy")?;
+    let copy = vm.new_dict();
+    vm.dict_extend(copy.as_dict().unwrap(), this.as_dict().unwrap())?;
+    Ok(copy)
+}
+
+/// `dict.fromkeys(iterable, value=None, /)`, a class method.
+fn fromkeys(vm: &mut Vm, args: Args) -> PyResult {
+    reject_keywords(vm, &args, "fromkeys")?;
+    if args.positional.is_empty() {
+        let message = "unbound method dict.fromkeys() needs an argument".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    let mut arguments = args.positional.into_iter();
+    let class = arguments.next().unwrap();
+    let args = Args::new(arguments.collect());
+    check_count(vm, &args, "fromkeys", 1, 2)?;
+    let value = args.positional.get(1).cloned().unwrap_or_else(|| vm.none());
+    let result = vm.call(&class, Args::default())?;
+    let iterator = vm.iter(&args.positional[0])?;
+    while let Some(key) = vm.next(&iterator)? {
+        vm.setitem(&result, &key, value.clone())?;
+    }
+    Ok(result)
+}
+
+/// `dict.get(key, default=None, /)`.
+fn get(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = positional_arguments(vm, args, "get", 1, 2)?;
+    match vm.dict_get(this.as_dict().unwrap(), &arguments[0])? {
+        Some(value) => Ok(value),
+        None => Ok(arguments.get(1).cloned().unwrap_or_else(|| vm.none())),
+    }
+}
+
+/// `dict.items()`.
+fn items(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "items")?;
+    Ok(vm.new_dict_view(this, ViewKind::Items))
+}
+
+/// `dict.keys()`.
+fn keys(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "keys")?;
+    Ok(vm.new_dict_view(this, ViewKind::Keys))
+}
+
+/// `dict.values()`.
+fn values(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "values")?;
+    Ok(vm.new_dict_view(this, ViewKind::Values))
+}
+
+/// `dict.pop(key[, default], /)`, which raises `KeyError` for a missing
+/// key only without a default.
+fn pop(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = positional_arguments(vm, args, "pop", 1, 2)?;
+    match vm.dict_remove(this.as_dict().unwrap(), &arguments[0])? {
+        Some(value) => Ok(value),
+        None => match arguments.get(1) {
+            Some(default) => Ok(default.clone()),
+            None => Err(vm.new_key_error(arguments[0].clone())),
+        },
+    }
+}
+
+/// `dict.popitem()`, of the entry added last.
+fn popitem(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "popitem")?;
+    let entry = this.as_dict().unwrap().borrow_mut().pop_last();
+    match entry {
+        Some(entry) => Ok(vm.new_tuple(vec![entry.key, entry.value])),
+        None => {
+            let message = vm.new_str("popitem(): dictionary is empty");
+            Err(vm.new_key_error(message))
+        }
+    }
+}
+
+/// `dict.setdefault(key, default=None, /)`.
+fn setdefault(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = positional_arguments(vm, args, "setdefault", 1, 2)?;
+    let dict = this.as_dict().unwrap();
+    if let Some(value) = vm.dict_get(dict, &arguments[0])? {
+        return Ok(value);
+    }
+    let default = arguments.get(1).cloned().unwrap_or_else(|| vm.none());
+    vm.dict_set(dict, arguments[0].clone(), default.clone())?;
+    Ok(default)
+}
+
+/// `dict.update([other], /, **kwargs)`.
+fn update(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, args) = receiver(vm, args, "update")?;
+    check_count(vm, &args, "update", 0, 1)?;
+    let dict = this.as_dict().unwrap();
+    if let Some(source) = args.positional.first() {
+        vm.dict_update(dict, source)?;
+    }
+    for (name, value) in args.keywords {
+        let name = vm.new_str(&name);
+        vm.dict_set(dict, name, value)?;
+    }
+    Ok(vm.none())
+}
+
+/// `view.isdisjoint(iterable, /)`, for the keys and items views.
+fn isdisjoint(vm: &mut Vm, args: Args) -> PyResult {
+    let (view, dict, kind) = match args.positional.first() {
+        Some(view) => match view.payload {
+            Payload::DictView { ref dict, kind } => (view.clone(), dict.clone(), kind),
+            _ => {
+                let message = format!(
+                    "descriptor 'isdisjoint' for 'dict_keys' objects doesn't apply to a '{}' object",
+                    view.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        },
+        None => {
+            let message = "unbound method dict_keys.isdisjoint() needs an argument".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    };
+    if !args.keywords.is_empty() {
+        let message = format!(
+            "{}.isdisjoint() takes no keyword arguments",
+            view.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    if args.positional.len() != 2 {
+        let message = format!(
+            "{}.isdisjoint() takes exactly one argument ({} given)",
+            view.type_name(),
+            args.positional.len() - 1
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let iterator = vm.iter(&args.positional[1])?;
+    while let Some(item) = vm.next(&iterator)? {
+        if vm.view_contains(&dict, kind, &item)? {
+            return Ok(vm.new_bool(false));
+        }
+    }
+    Ok(vm.new_bool(true))
+}
+
+/// `dict.__getitem__(key, /)`, as `self[key]`.
+fn getitem(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, mut key) = positional_arguments(vm, args, "__getitem__", 1, 1)?;
+    let key = key.pop().unwrap();
+    vm.builtin_getitem(&this, &key)
+}
+
+/// `dict.__setitem__(key, value, /)`, as `self[key] = value`.
+fn setitem(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, mut arguments) = positional_arguments(vm, args, "__setitem__", 2, 2)?;
+    let value = arguments.pop().unwrap();
+    vm.builtin_setitem(&this, &arguments[0], value)?;
+    Ok(vm.none())
+}
+
+/// `dict.__delitem__(key, /)`, as `del self[key]`.
+fn delitem(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, mut key) = positional_arguments(vm, args, "__delitem__", 1, 1)?;
+    let key = key.pop().unwrap();
+    vm.builtin_delitem(&this, &key)?;
+    Ok(vm.none())
+}
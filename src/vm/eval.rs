@@ -0,0 +1,341 @@
+//! `compile`, `exec` and `eval`, which run the parser and compiler on
+//! source given at run time.
+//!
+//! Without namespaces, `exec` and `eval` run in those of the frame calling
+//! them. A function keeps its locals in fast slots rather than a dict, so
+//! there the code sees the function's globals and a fresh dict of locals,
+//! not the function's variables. Only dicts are accepted as locals.
+
+use std::sync::Arc;
+
+use ast::{Module, Stmt, StmtKind};
+use compiler::{CodeObject, Mode};
+use optimizer::{optimize, optimize_expression};
+use parser::{parse, parse_expression};
+
+use super::builtins::{check_count, index_value, keyword_arguments, no_keywords};
+use super::object::{Args, ObjectRef, Payload, PyObject, PyResult};
+use super::Vm;
+
+/// Adds `compile`, `exec` and `eval` to the builtins.
+pub(super) fn add_builtins(vm: &mut Vm) {
+    let builtins = vm.builtins().clone();
+    for (name, function) in [
+        ("compile", compile as fn(&mut Vm, Args) -> PyResult),
+        ("eval", eval),
+        ("exec", exec),
+    ] {
+        let function = vm.new_builtin(name, function);
+        vm.dict_set_str(builtins.as_dict().unwrap(), name, function);
+    }
+}
+
+impl Vm {
+    /// The value of the expression `source` in the namespace of the
+    /// `__main__` module, as `eval` finds it there. Like the source of
+    /// `run_source`, it is kept for tracebacks, as `<string>`.
+    pub fn eval_source(&mut self, source: &str) -> PyResult {
+        let source = source.trim_start_matches([' ', '\t']);
+        self.keep_source("<string>", source);
+        let code = compile_text(self, source, "<string>", Mode::Eval)?;
+        let globals = self.main_module().dict().unwrap().clone();
+        self.run_code(code, &globals)
+    }
+}
+
+/// What `exec` and `eval` run: source, or compiled code.
+enum Source {
+    Text(String),
+    Code(Arc<CodeObject>),
+}
+
+/// The source argument of `compile`, `exec` or `eval`. A code object is
+/// only accepted with `code`; otherwise the error is `message`.
+fn source_argument(vm: &mut Vm, value: &ObjectRef, code: bool, message: &str) -> PyResult<Source> {
+    let text = match value.payload {
+        Payload::Str(_) => value.as_str().unwrap().to_string(),
+        Payload::Bytes(ref bytes) => match String::from_utf8(bytes.clone()) {
+            Ok(text) => text,
+            Err(error) => {
+                let position = error.utf8_error().valid_up_to();
+                let message = format!(
+                    "(unicode error) 'utf-8' codec can't decode byte {:#04x} in position {}: \
+                     invalid start byte",
+                    bytes[position], position
+                );
+                let class = vm.exceptions.syntax_error.clone();
+                return Err(vm.new_error(&class, message));
+            }
+        },
+        Payload::Code(ref compiled) if code => return Ok(Source::Code(compiled.clone())),
+        _ => return Err(vm.new_type_error(message.to_string())),
+    };
+    if text.contains('\0') {
+        let class = vm.exceptions.syntax_error.clone();
+        let message = "source code string cannot contain null bytes".to_string();
+        return Err(vm.new_error(&class, message));
+    }
+    Ok(Source::Text(text))
+}
+
+/// Parses and compiles `text` as `mode` asks. Unlike the source of
+/// `Vm::run_source`, it is not kept for tracebacks.
+fn compile_text(vm: &mut Vm, text: &str, filename: &str, mode: Mode) -> PyResult<Arc<CodeObject>> {
+    let parsed = match mode {
+        Mode::Eval => parse_expression(text).map(|expr| Module {
+            body: vec![Stmt {
+                start: expr.start,
+                end: expr.end,
+                kind: StmtKind::Expr(expr),
+            }],
+            type_ignores: Vec::new(),
+        }),
+        Mode::Exec | Mode::Single => parse(text),
+    };
+    // CPython shows the line of a statement with its newline, and that of
+    // an expression without.
+    let source = if mode == Mode::Eval || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{}\n", text)
+    };
+    let mut module = match parsed {
+        Ok(module) => module,
+        Err(err) => return Err(vm.parse_error(&err, filename, &source)),
+    };
+    let class = vm.exceptions.syntax_error.clone();
+    if mode == Mode::Single && module.body.len() > 1 {
+        let message = "multiple statements found while compiling a single statement";
+        let location = module.body[0].start;
+        return Err(vm.new_syntax_error(&class, message, filename, location, &source));
+    }
+    if mode == Mode::Eval {
+        optimize_expression(&mut module);
+    } else {
+        optimize(&mut module);
+    }
+    let code = vm.compile_module(&module, filename, mode, &source)?;
+    Ok(Arc::new(code))
+}
+
+/// `compile(source, filename, mode, flags=0, dont_inherit=False,
+/// optimize=-1)`. No compiler flags are supported, and the code is the
+/// same at every optimization level.
+fn compile(vm: &mut Vm, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let names = [
+        "source",
+        "filename",
+        "mode",
+        "flags",
+        "dont_inherit",
+        "optimize",
+    ];
+    if positional.len() > names.len() {
+        let message = format!(
+            "compile() takes at most {} arguments ({} given)",
+            names.len(),
+            positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let mut values = keyword_arguments(vm, keywords, "compile", &names)?;
+    for (index, value) in positional.into_iter().enumerate() {
+        if values[index].is_some() {
+            let message = format!(
+                "argument for compile() given by name ('{}') and position ({})",
+                names[index],
+                index + 1
+            );
+            return Err(vm.new_type_error(message));
+        }
+        values[index] = Some(value);
+    }
+    for (index, name) in names[..3].iter().enumerate() {
+        if values[index].is_none() {
+            let message = format!(
+                "compile() missing required argument '{}' (pos {})",
+                name,
+                index + 1
+            );
+            return Err(vm.new_type_error(message));
+        }
+    }
+    let filename = values[1].take().unwrap();
+    let filename = match filename.payload {
+        Payload::Str(_) => filename.as_str().unwrap().to_string(),
+        Payload::Bytes(ref bytes) => String::from_utf8_lossy(bytes).into_owned(),
+        _ => {
+            let message = format!(
+                "expected str, bytes or os.PathLike object, not {}",
+                filename.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let mode = values[2].take().unwrap();
+    let mode = match mode.as_str() {
+        Some("exec") => Mode::Exec,
+        Some("eval") => Mode::Eval,
+        Some("single") => Mode::Single,
+        Some(_) => {
+            let message = "compile() mode must be 'exec', 'eval' or 'single'".to_string();
+            return Err(vm.new_value_error(message));
+        }
+        None => {
+            let message = format!(
+                "compile() argument 'mode' must be str, not {}",
+                mode.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    if let Some(ref flags) = values[3] {
+        if index_value(vm, flags)? != 0 {
+            let message = "compile(): unrecognised flags".to_string();
+            return Err(vm.new_value_error(message));
+        }
+    }
+    if let Some(ref dont_inherit) = values[4] {
+        vm.is_true(dont_inherit)?;
+    }
+    if let Some(ref level) = values[5] {
+        if !(-1..=2).contains(&index_value(vm, level)?) {
+            let message = "compile(): invalid optimize value".to_string();
+            return Err(vm.new_value_error(message));
+        }
+    }
+    let source = values[0].take().unwrap();
+    let message = "compile() arg 1 must be a string, bytes or AST object";
+    let text = match source_argument(vm, &source, false, message)? {
+        Source::Text(text) => text,
+        Source::Code(_) => unreachable!("compile() takes no code objects"),
+    };
+    let code = compile_text(vm, &text, &filename, mode)?;
+    Ok(PyObject::new(
+        Payload::Code(code),
+        vm.types.code.clone(),
+        None,
+    ))
+}
+
+/// The globals and locals `exec` or `eval` runs code in, from its
+/// `globals` and `locals` arguments. Without globals, they are those of
+/// the caller. The globals get the builtins as `__builtins__` unless they
+/// have their own.
+fn namespaces(
+    vm: &mut Vm,
+    function: &str,
+    globals: Option<ObjectRef>,
+    locals: Option<ObjectRef>,
+) -> PyResult<(ObjectRef, ObjectRef)> {
+    let none = vm.none();
+    let globals = globals.filter(|globals| !Vm::is(globals, &none));
+    let locals = locals.filter(|locals| !Vm::is(locals, &none));
+    if let Some(ref globals) = globals {
+        if globals.as_dict().is_none() {
+            let message = if function == "eval" {
+                "globals must be a real dict; try eval(expr, {}, mapping)".to_string()
+            } else {
+                format!(
+                    "{}() globals must be a dict, not {}",
+                    function,
+                    globals.type_name()
+                )
+            };
+            return Err(vm.new_type_error(message));
+        }
+    }
+    if let Some(ref locals) = locals {
+        if locals.as_dict().is_none() {
+            return Err(vm.new_type_error("locals must be a mapping".to_string()));
+        }
+    }
+    let (globals, locals) = match (globals, locals) {
+        (Some(globals), Some(locals)) => (globals, locals),
+        (Some(globals), None) => (globals.clone(), globals),
+        (None, locals) => {
+            let (globals, current) = vm.current_namespaces();
+            let locals = match locals.or(current) {
+                Some(locals) => locals,
+                None => vm.new_dict(),
+            };
+            (globals, locals)
+        }
+    };
+    let has_builtins = globals
+        .as_dict()
+        .unwrap()
+        .borrow()
+        .get_str("__builtins__")
+        .is_some();
+    if !has_builtins {
+        let builtins = vm.builtins().clone();
+        vm.dict_set_str(globals.as_dict().unwrap(), "__builtins__", builtins);
+    }
+    Ok((globals, locals))
+}
+
+/// Runs `code` for `exec` or `eval`.
+fn run(vm: &mut Vm, function: &str, code: Arc<CodeObject>, args: Vec<ObjectRef>) -> PyResult {
+    if !code.freevars.is_empty() {
+        let message = format!(
+            "code object passed to {}() may not contain free variables",
+            function
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let mut args = args.into_iter();
+    let (globals, locals) = namespaces(vm, function, args.next(), args.next())?;
+    vm.run_code_in(code, &globals, &locals)
+}
+
+/// `eval(source, globals=None, locals=None, /)`: the value of an
+/// expression, or of running a code object. Leading spaces and tabs of
+/// the source are ignored.
+fn eval(vm: &mut Vm, args: Args) -> PyResult {
+    no_keywords(vm, &args, "eval")?;
+    check_count(vm, &args, "eval", 1, 3)?;
+    let mut positional = args.positional;
+    let source = positional.remove(0);
+    let message = "eval() arg 1 must be a string, bytes or code object";
+    let code = match source_argument(vm, &source, true, message)? {
+        Source::Text(text) => {
+            let text = text.trim_start_matches([' ', '\t']);
+            compile_text(vm, text, "<string>", Mode::Eval)?
+        }
+        Source::Code(code) => code,
+    };
+    run(vm, "eval", code, positional)
+}
+
+/// `exec(source, globals=None, locals=None, /, *, closure=None)`: runs
+/// statements, or a code object, for their effects. `closure` may only be
+/// `None`, as code with free variables is refused.
+fn exec(vm: &mut Vm, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let closure = keyword_arguments(vm, keywords, "exec", &["closure"])?;
+    if let Some(closure) = closure.into_iter().next().unwrap() {
+        if !Vm::is(&closure, &vm.none()) {
+            let message = "cannot use a closure with this code object".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    }
+    let args = Args::new(positional);
+    check_count(vm, &args, "exec", 1, 3)?;
+    let mut positional = args.positional;
+    let source = positional.remove(0);
+    let message = "exec() arg 1 must be a string, bytes or code object";
+    let code = match source_argument(vm, &source, true, message)? {
+        Source::Text(text) => compile_text(vm, &text, "<string>", Mode::Exec)?,
+        Source::Code(code) => code,
+    };
+    run(vm, "exec", code, positional)?;
+    Ok(vm.none())
+}
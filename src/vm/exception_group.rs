@@ -0,0 +1,348 @@
+//! Exception groups: `BaseExceptionGroup` and `ExceptionGroup`, which
+//! raise several exceptions together, and the splitting of them that
+//! `except*` clauses do.
+
+use std::collections::HashSet;
+
+use super::builtins::is_callable;
+use super::object::{object_id, Args, ObjectRef, Payload, PyResult};
+use super::Vm;
+
+/// What `split` and `subgroup` take a group apart by.
+enum Matcher {
+    /// An exception class, or a tuple of them.
+    Type(ObjectRef),
+    /// A function called with each exception and group.
+    Predicate(ObjectRef),
+    /// The ids of the exceptions to keep, for `except*` to find again
+    /// those its handlers raised again.
+    Ids(HashSet<usize>),
+}
+
+/// `BaseExceptionGroup(message, exceptions)`. Made with `BaseExceptionGroup`
+/// itself, the group is an `ExceptionGroup` when all its exceptions are
+/// `Exception`s, which an `ExceptionGroup` must hold. The exceptions are
+/// the `exceptions` attribute, as a tuple, and the message `message`.
+pub(super) fn exception_group_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    args.check(vm, "BaseExceptionGroup.__new__", 2, 2)?;
+    let message = args.positional[0].clone();
+    if message.as_str().is_none() {
+        let text = format!(
+            "BaseExceptionGroup.__new__() argument 1 must be str, not {}",
+            message.type_name()
+        );
+        return Err(vm.new_type_error(text));
+    }
+    let exceptions = match args.positional[1].payload {
+        Payload::List(ref items) => items.borrow().clone(),
+        Payload::Tuple(ref items) => items.clone(),
+        _ => {
+            let text = "second argument (exceptions) must be a sequence".to_string();
+            return Err(vm.new_type_error(text));
+        }
+    };
+    if exceptions.is_empty() {
+        let text = "second argument (exceptions) must be a non-empty sequence".to_string();
+        return Err(vm.new_value_error(text));
+    }
+    if let Some(index) = exceptions
+        .iter()
+        .position(|exception| exception.as_exception().is_none())
+    {
+        let text = format!(
+            "Item {} of second argument (exceptions) is not an exception",
+            index
+        );
+        return Err(vm.new_value_error(text));
+    }
+    let nests_base_exceptions = exceptions
+        .iter()
+        .any(|exception| !Vm::is_instance(exception, &vm.exceptions.exception));
+    let mut class = class.clone();
+    if Vm::is(&class, &vm.exceptions.base_exception_group) {
+        if !nests_base_exceptions {
+            class = vm.exceptions.exception_group.clone();
+        }
+    } else if nests_base_exceptions && Vm::is_subclass(&class, &vm.exceptions.exception) {
+        let text = if Vm::is(&class, &vm.exceptions.exception_group) {
+            "Cannot nest BaseExceptions in an ExceptionGroup".to_string()
+        } else {
+            format!(
+                "Cannot nest BaseExceptions in '{}'",
+                class.as_type().unwrap().name
+            )
+        };
+        return Err(vm.new_type_error(text));
+    }
+    let group = vm.new_exception(&class, args.positional);
+    let dict = group.dict().unwrap().clone();
+    let exceptions = vm.new_tuple(exceptions);
+    vm.dict_set_str(dict.as_dict().unwrap(), "message", message);
+    vm.dict_set_str(dict.as_dict().unwrap(), "exceptions", exceptions);
+    Ok(group)
+}
+
+/// `BaseExceptionGroup.derive(excs)`: a group with the message of this one
+/// and the exceptions `excs`, which `split` and `subgroup` make their
+/// parts with. Subclasses override it to make groups of their own class.
+pub(super) fn derive(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "derive", 2, 2)?;
+    let message = vm.getattr(&args.positional[0], "message")?;
+    let class = vm.exceptions.base_exception_group.clone();
+    vm.call(&class, Args::new(vec![message, args.positional[1].clone()]))
+}
+
+/// `BaseExceptionGroup.split(condition)`: the part of the group that
+/// matches `condition` and the rest, each a group of the same shape or
+/// `None`.
+pub(super) fn split(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "split", 2, 2)?;
+    let matcher = matcher(vm, &args.positional[1])?;
+    let (matched, rest) = vm.split_group(&args.positional[0], &matcher, true)?;
+    let none = vm.none();
+    Ok(vm.new_tuple(vec![
+        matched.unwrap_or_else(|| none.clone()),
+        rest.unwrap_or(none),
+    ]))
+}
+
+/// `BaseExceptionGroup.subgroup(condition)`: the part of the group that
+/// matches `condition`, or `None`.
+pub(super) fn subgroup(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "subgroup", 2, 2)?;
+    let matcher = matcher(vm, &args.positional[1])?;
+    let (matched, _) = vm.split_group(&args.positional[0], &matcher, false)?;
+    Ok(matched.unwrap_or_else(|| vm.none()))
+}
+
+/// The matcher for the `condition` of `split` or `subgroup`: an exception
+/// class, a tuple of them, or a function that is not a class.
+fn matcher(vm: &mut Vm, condition: &ObjectRef) -> PyResult<Matcher> {
+    if condition.as_type().is_none() && is_callable(vm, condition) {
+        return Ok(Matcher::Predicate(condition.clone()));
+    }
+    let classes = match condition.payload {
+        Payload::Tuple(ref classes) => classes.clone(),
+        _ => vec![condition.clone()],
+    };
+    if classes.iter().all(|class| vm.is_exception_class(class)) {
+        return Ok(Matcher::Type(condition.clone()));
+    }
+    let message = "expected a function, exception type or tuple of exception types";
+    Err(vm.new_type_error(message.to_string()))
+}
+
+impl Vm {
+    /// Whether `exception` is a `BaseExceptionGroup`.
+    pub(super) fn is_exception_group(&self, exception: &ObjectRef) -> bool {
+        Vm::is_instance(exception, &self.exceptions.base_exception_group)
+    }
+
+    /// The exceptions a group holds.
+    pub(super) fn group_exceptions(&self, group: &ObjectRef) -> Vec<ObjectRef> {
+        let exceptions = group
+            .dict()
+            .and_then(|dict| dict.as_dict().unwrap().borrow().get_str("exceptions"));
+        match exceptions {
+            Some(ref exceptions) => match exceptions.payload {
+                Payload::Tuple(ref items) => items.clone(),
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        }
+    }
+
+    /// Splits `exception` by the class or classes of an `except*` clause
+    /// into the part that matches and the rest, either of which may be
+    /// missing. An exception that is not a group matches whole, wrapped
+    /// in a group of its own, so the clause always handles a group.
+    pub(super) fn exception_group_match(
+        &mut self,
+        exception: &ObjectRef,
+        class: &ObjectRef,
+    ) -> PyResult<(Option<ObjectRef>, Option<ObjectRef>)> {
+        let classes = match class.payload {
+            Payload::Tuple(ref classes) => classes.clone(),
+            _ => vec![class.clone()],
+        };
+        if !classes.iter().all(|class| self.is_exception_class(class)) {
+            let message = "catching classes that do not inherit from BaseException is not allowed";
+            return Err(self.new_type_error(message.to_string()));
+        }
+        if classes
+            .iter()
+            .any(|class| Vm::is_subclass(class, &self.exceptions.base_exception_group))
+        {
+            let message =
+                "catching ExceptionGroup with except* is not allowed. Use except instead.";
+            return Err(self.new_type_error(message.to_string()));
+        }
+        if Vm::is(exception, &self.none) {
+            return Ok((None, None));
+        }
+        if classes
+            .iter()
+            .any(|class| Vm::is_instance(exception, class))
+        {
+            if self.is_exception_group(exception) {
+                return Ok((Some(exception.clone()), None));
+            }
+            let message = self.new_str("");
+            let exceptions = self.new_tuple(vec![exception.clone()]);
+            let class = self.exceptions.base_exception_group.clone();
+            let group = self.call(&class, Args::new(vec![message, exceptions]))?;
+            let traceback = exception.as_exception().unwrap().borrow().traceback.clone();
+            group.as_exception().unwrap().borrow_mut().traceback = traceback;
+            return Ok((Some(group), None));
+        }
+        if self.is_exception_group(exception) {
+            return self.split_group(exception, &Matcher::Type(class.clone()), true);
+        }
+        Ok((None, Some(exception.clone())))
+    }
+
+    /// The exception a `try` statement with `except*` clauses raises at its
+    /// end for the exception `caught`, given `raised`: the exceptions its
+    /// handlers raised, and what they left unhandled, or `None`s. What was
+    /// raised again, unhandled or by a bare `raise`, keeps its place in the
+    /// group that was caught; new exceptions are grouped with that.
+    /// Returns `None` if there is nothing to raise.
+    pub(super) fn prep_reraise_star(
+        &mut self,
+        caught: &ObjectRef,
+        raised: Vec<ObjectRef>,
+    ) -> PyResult {
+        let raised: Vec<ObjectRef> = raised
+            .into_iter()
+            .filter(|exception| !Vm::is(exception, &self.none))
+            .collect();
+        // A bare exception that was caught was wrapped for one clause, which
+        // either handled it or raised something instead.
+        if !self.is_exception_group(caught) {
+            return Ok(raised.into_iter().next().unwrap_or_else(|| self.none()));
+        }
+        let (reraised, mut new): (Vec<ObjectRef>, Vec<ObjectRef>) = raised
+            .into_iter()
+            .partition(|exception| same_metadata(exception, caught));
+        let mut ids = HashSet::new();
+        for exception in &reraised {
+            self.collect_leaf_ids(exception, &mut ids);
+        }
+        let (reraised, _) = self.split_group(caught, &Matcher::Ids(ids), false)?;
+        if new.is_empty() {
+            return Ok(reraised.unwrap_or_else(|| self.none()));
+        }
+        new.extend(reraised);
+        if new.len() == 1 {
+            return Ok(new.pop().unwrap());
+        }
+        let message = self.new_str("");
+        let exceptions = self.new_list(new);
+        let class = self.exceptions.base_exception_group.clone();
+        self.call(&class, Args::new(vec![message, exceptions]))
+    }
+
+    /// Adds the ids of the exceptions in `exception` that are not groups.
+    fn collect_leaf_ids(&self, exception: &ObjectRef, ids: &mut HashSet<usize>) {
+        if !self.is_exception_group(exception) {
+            ids.insert(object_id(exception));
+            return;
+        }
+        for exception in self.group_exceptions(exception) {
+            self.collect_leaf_ids(&exception, ids);
+        }
+    }
+
+    /// The part of `exception` that `matcher` matches, and with
+    /// `construct_rest` the part it does not. A group that does not match
+    /// as a whole is split into new groups made by its `derive`, which
+    /// keep its traceback, cause and context.
+    fn split_group(
+        &mut self,
+        exception: &ObjectRef,
+        matcher: &Matcher,
+        construct_rest: bool,
+    ) -> PyResult<(Option<ObjectRef>, Option<ObjectRef>)> {
+        let matches = match *matcher {
+            Matcher::Type(ref class) => self.exception_matches(exception, class)?,
+            Matcher::Predicate(ref function) => {
+                let result = self.call(function, Args::new(vec![exception.clone()]))?;
+                self.is_true(&result)?
+            }
+            Matcher::Ids(ref ids) => ids.contains(&object_id(exception)),
+        };
+        if matches {
+            return Ok((Some(exception.clone()), None));
+        }
+        if !self.is_exception_group(exception) {
+            return Ok((None, Some(exception.clone()).filter(|_| construct_rest)));
+        }
+        let mut matched = Vec::new();
+        let mut rest = Vec::new();
+        for exception in self.group_exceptions(exception) {
+            let (part, other) = self.split_group(&exception, matcher, construct_rest)?;
+            matched.extend(part);
+            rest.extend(other);
+        }
+        let matched = self.derive_group(exception, matched)?;
+        let rest = match construct_rest {
+            true => self.derive_group(exception, rest)?,
+            false => None,
+        };
+        Ok((matched, rest))
+    }
+
+    /// A group like `group` of `exceptions`, or `None` for none.
+    fn derive_group(
+        &mut self,
+        group: &ObjectRef,
+        exceptions: Vec<ObjectRef>,
+    ) -> PyResult<Option<ObjectRef>> {
+        if exceptions.is_empty() {
+            return Ok(None);
+        }
+        let derive = self.getattr(group, "derive")?;
+        let exceptions = self.new_list(exceptions);
+        let derived = self.call(&derive, Args::new(vec![exceptions]))?;
+        if !self.is_exception_group(&derived) {
+            let message = "derive must return an instance of BaseExceptionGroup".to_string();
+            return Err(self.new_type_error(message));
+        }
+        let data = group.as_exception().unwrap().borrow();
+        let mut derived_data = derived.as_exception().unwrap().borrow_mut();
+        derived_data.traceback = data.traceback.clone();
+        derived_data.cause = data.cause.clone();
+        derived_data.context = data.context.clone();
+        derived_data.suppress_context = data.suppress_context;
+        drop(derived_data);
+        Ok(Some(derived.clone()))
+    }
+
+    /// The `str` of a group: its message and how many exceptions it holds.
+    pub(super) fn exception_group_str(&mut self, group: &ObjectRef) -> PyResult<String> {
+        let message = self.getattr(group, "message")?;
+        let message = self.str(&message)?;
+        let count = self.group_exceptions(group).len();
+        Ok(format!(
+            "{} ({} sub-exception{})",
+            message,
+            count,
+            if count == 1 { "" } else { "s" }
+        ))
+    }
+}
+
+/// Whether `exception` has the traceback, cause and context of `other`,
+/// as a part of it split off and raised again does.
+fn same_metadata(exception: &ObjectRef, other: &ObjectRef) -> bool {
+    let same = |a: &Option<ObjectRef>, b: &Option<ObjectRef>| match (a, b) {
+        (Some(a), Some(b)) => Vm::is(a, b),
+        (None, None) => true,
+        _ => false,
+    };
+    let (a, b) = match (exception.as_exception(), other.as_exception()) {
+        (Some(a), Some(b)) => (a.borrow(), b.borrow()),
+        _ => return false,
+    };
+    same(&a.traceback, &b.traceback) && same(&a.cause, &b.cause) && same(&a.context, &b.context)
+}
@@ -0,0 +1,1165 @@
+//! The builtin exception classes, exception instances and their chaining,
+//! and tracebacks.
+
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::io;
+use std::sync::Arc;
+
+use compiler::CodeObject;
+
+use super::exception_group::exception_group_new;
+use super::object::{
+    object_id, Args, ExceptionData, ObjectRef, Payload, PyObject, PyResult, Traceback,
+};
+use super::types::Types;
+use super::Vm;
+
+/// The builtin exception classes.
+pub struct Exceptions {
+    pub base_exception: ObjectRef,
+    pub base_exception_group: ObjectRef,
+    pub exception_group: ObjectRef,
+    pub system_exit: ObjectRef,
+    pub keyboard_interrupt: ObjectRef,
+    pub generator_exit: ObjectRef,
+    pub exception: ObjectRef,
+    pub stop_iteration: ObjectRef,
+    pub stop_async_iteration: ObjectRef,
+    pub arithmetic_error: ObjectRef,
+    pub floating_point_error: ObjectRef,
+    pub overflow_error: ObjectRef,
+    pub zero_division_error: ObjectRef,
+    pub assertion_error: ObjectRef,
+    pub attribute_error: ObjectRef,
+    pub buffer_error: ObjectRef,
+    pub eof_error: ObjectRef,
+    pub import_error: ObjectRef,
+    pub module_not_found_error: ObjectRef,
+    pub lookup_error: ObjectRef,
+    pub index_error: ObjectRef,
+    pub key_error: ObjectRef,
+    pub memory_error: ObjectRef,
+    pub name_error: ObjectRef,
+    pub unbound_local_error: ObjectRef,
+    pub os_error: ObjectRef,
+    pub blocking_io_error: ObjectRef,
+    pub child_process_error: ObjectRef,
+    pub connection_error: ObjectRef,
+    pub broken_pipe_error: ObjectRef,
+    pub connection_aborted_error: ObjectRef,
+    pub connection_refused_error: ObjectRef,
+    pub connection_reset_error: ObjectRef,
+    pub file_exists_error: ObjectRef,
+    pub file_not_found_error: ObjectRef,
+    pub interrupted_error: ObjectRef,
+    pub is_a_directory_error: ObjectRef,
+    pub not_a_directory_error: ObjectRef,
+    pub permission_error: ObjectRef,
+    pub process_lookup_error: ObjectRef,
+    pub timeout_error: ObjectRef,
+    pub reference_error: ObjectRef,
+    pub runtime_error: ObjectRef,
+    pub not_implemented_error: ObjectRef,
+    pub recursion_error: ObjectRef,
+    pub syntax_error: ObjectRef,
+    pub indentation_error: ObjectRef,
+    pub tab_error: ObjectRef,
+    pub system_error: ObjectRef,
+    pub type_error: ObjectRef,
+    pub value_error: ObjectRef,
+    pub unicode_error: ObjectRef,
+    pub unicode_decode_error: ObjectRef,
+    pub unicode_encode_error: ObjectRef,
+    pub unicode_translate_error: ObjectRef,
+    pub warning: ObjectRef,
+    pub bytes_warning: ObjectRef,
+    pub deprecation_warning: ObjectRef,
+    pub encoding_warning: ObjectRef,
+    pub future_warning: ObjectRef,
+    pub import_warning: ObjectRef,
+    pub pending_deprecation_warning: ObjectRef,
+    pub resource_warning: ObjectRef,
+    pub runtime_warning: ObjectRef,
+    pub syntax_warning: ObjectRef,
+    pub unicode_warning: ObjectRef,
+    pub user_warning: ObjectRef,
+}
+
+impl Exceptions {
+    pub fn new(types: &Types) -> Exceptions {
+        let base_exception = types.new_type("BaseException", &types.object, Some(exception_new));
+        let exception = types.new_type("Exception", &base_exception, None);
+        let base_exception_group = types.new_type(
+            "BaseExceptionGroup",
+            &base_exception,
+            Some(exception_group_new),
+        );
+        let exception_group = types.new_type_with_bases(
+            "ExceptionGroup",
+            &[base_exception_group.clone(), exception.clone()],
+            None,
+        );
+        let arithmetic_error = types.new_type("ArithmeticError", &exception, None);
+        let import_error = types.new_type("ImportError", &exception, None);
+        let lookup_error = types.new_type("LookupError", &exception, None);
+        let name_error = types.new_type("NameError", &exception, None);
+        let os_error = types.new_type("OSError", &exception, Some(os_error_new));
+        let connection_error = types.new_type("ConnectionError", &os_error, None);
+        let runtime_error = types.new_type("RuntimeError", &exception, None);
+        let syntax_error = types.new_type("SyntaxError", &exception, None);
+        let indentation_error = types.new_type("IndentationError", &syntax_error, None);
+        let value_error = types.new_type("ValueError", &exception, None);
+        let unicode_error = types.new_type("UnicodeError", &value_error, None);
+        let warning = types.new_type("Warning", &exception, None);
+        Exceptions {
+            system_exit: types.new_type("SystemExit", &base_exception, None),
+            keyboard_interrupt: types.new_type("KeyboardInterrupt", &base_exception, None),
+            generator_exit: types.new_type("GeneratorExit", &base_exception, None),
+            stop_iteration: types.new_type("StopIteration", &exception, None),
+            stop_async_iteration: types.new_type("StopAsyncIteration", &exception, None),
+            floating_point_error: types.new_type("FloatingPointError", &arithmetic_error, None),
+            overflow_error: types.new_type("OverflowError", &arithmetic_error, None),
+            zero_division_error: types.new_type("ZeroDivisionError", &arithmetic_error, None),
+            assertion_error: types.new_type("AssertionError", &exception, None),
+            attribute_error: types.new_type("AttributeError", &exception, None),
+            buffer_error: types.new_type("BufferError", &exception, None),
+            eof_error: types.new_type("EOFError", &exception, None),
+            module_not_found_error: types.new_type("ModuleNotFoundError", &import_error, None),
+            index_error: types.new_type("IndexError", &lookup_error, None),
+            key_error: types.new_type("KeyError", &lookup_error, None),
+            memory_error: types.new_type("MemoryError", &exception, None),
+            unbound_local_error: types.new_type("UnboundLocalError", &name_error, None),
+            blocking_io_error: types.new_type("BlockingIOError", &os_error, None),
+            child_process_error: types.new_type("ChildProcessError", &os_error, None),
+            broken_pipe_error: types.new_type("BrokenPipeError", &connection_error, None),
+            connection_aborted_error: types.new_type(
+                "ConnectionAbortedError",
+                &connection_error,
+                None,
+            ),
+            connection_refused_error: types.new_type(
+                "ConnectionRefusedError",
+                &connection_error,
+                None,
+            ),
+            connection_reset_error: types.new_type("ConnectionResetError", &connection_error, None),
+            file_exists_error: types.new_type("FileExistsError", &os_error, None),
+            file_not_found_error: types.new_type("FileNotFoundError", &os_error, None),
+            interrupted_error: types.new_type("InterruptedError", &os_error, None),
+            is_a_directory_error: types.new_type("IsADirectoryError", &os_error, None),
+            not_a_directory_error: types.new_type("NotADirectoryError", &os_error, None),
+            permission_error: types.new_type("PermissionError", &os_error, None),
+            process_lookup_error: types.new_type("ProcessLookupError", &os_error, None),
+            timeout_error: types.new_type("TimeoutError", &os_error, None),
+            reference_error: types.new_type("ReferenceError", &exception, None),
+            not_implemented_error: types.new_type("NotImplementedError", &runtime_error, None),
+            recursion_error: types.new_type("RecursionError", &runtime_error, None),
+            tab_error: types.new_type("TabError", &indentation_error, None),
+            system_error: types.new_type("SystemError", &exception, None),
+            type_error: types.new_type("TypeError", &exception, None),
+            unicode_decode_error: types.new_type("UnicodeDecodeError", &unicode_error, None),
+            unicode_encode_error: types.new_type("UnicodeEncodeError", &unicode_error, None),
+            unicode_translate_error: types.new_type("UnicodeTranslateError", &unicode_error, None),
+            bytes_warning: types.new_type("BytesWarning", &warning, None),
+            deprecation_warning: types.new_type("DeprecationWarning", &warning, None),
+            encoding_warning: types.new_type("EncodingWarning", &warning, None),
+            future_warning: types.new_type("FutureWarning", &warning, None),
+            import_warning: types.new_type("ImportWarning", &warning, None),
+            pending_deprecation_warning: types.new_type(
+                "PendingDeprecationWarning",
+                &warning,
+                None,
+            ),
+            resource_warning: types.new_type("ResourceWarning", &warning, None),
+            runtime_warning: types.new_type("RuntimeWarning", &warning, None),
+            syntax_warning: types.new_type("SyntaxWarning", &warning, None),
+            unicode_warning: types.new_type("UnicodeWarning", &warning, None),
+            user_warning: types.new_type("UserWarning", &warning, None),
+            base_exception,
+            base_exception_group,
+            exception_group,
+            exception,
+            arithmetic_error,
+            import_error,
+            lookup_error,
+            name_error,
+            os_error,
+            connection_error,
+            runtime_error,
+            syntax_error,
+            indentation_error,
+            value_error,
+            unicode_error,
+            warning,
+        }
+    }
+
+    /// Every class, for the builtins namespace.
+    pub fn all(&self) -> Vec<&ObjectRef> {
+        vec![
+            &self.base_exception,
+            &self.base_exception_group,
+            &self.system_exit,
+            &self.keyboard_interrupt,
+            &self.generator_exit,
+            &self.exception,
+            &self.exception_group,
+            &self.stop_iteration,
+            &self.stop_async_iteration,
+            &self.arithmetic_error,
+            &self.floating_point_error,
+            &self.overflow_error,
+            &self.zero_division_error,
+            &self.assertion_error,
+            &self.attribute_error,
+            &self.buffer_error,
+            &self.eof_error,
+            &self.import_error,
+            &self.module_not_found_error,
+            &self.lookup_error,
+            &self.index_error,
+            &self.key_error,
+            &self.memory_error,
+            &self.name_error,
+            &self.unbound_local_error,
+            &self.os_error,
+            &self.blocking_io_error,
+            &self.child_process_error,
+            &self.connection_error,
+            &self.broken_pipe_error,
+            &self.connection_aborted_error,
+            &self.connection_refused_error,
+            &self.connection_reset_error,
+            &self.file_exists_error,
+            &self.file_not_found_error,
+            &self.interrupted_error,
+            &self.is_a_directory_error,
+            &self.not_a_directory_error,
+            &self.permission_error,
+            &self.process_lookup_error,
+            &self.timeout_error,
+            &self.reference_error,
+            &self.runtime_error,
+            &self.not_implemented_error,
+            &self.recursion_error,
+            &self.syntax_error,
+            &self.indentation_error,
+            &self.tab_error,
+            &self.system_error,
+            &self.type_error,
+            &self.value_error,
+            &self.unicode_error,
+            &self.unicode_decode_error,
+            &self.unicode_encode_error,
+            &self.unicode_translate_error,
+            &self.warning,
+            &self.bytes_warning,
+            &self.deprecation_warning,
+            &self.encoding_warning,
+            &self.future_warning,
+            &self.import_warning,
+            &self.pending_deprecation_warning,
+            &self.resource_warning,
+            &self.runtime_warning,
+            &self.syntax_warning,
+            &self.unicode_warning,
+            &self.user_warning,
+        ]
+    }
+
+    /// The subclass of `OSError` that `OSError(errno, strerror)` makes for
+    /// the error number, as CPython picks it, if there is one. The kinds
+    /// of error std tells apart decide it, so `ChildProcessError` and
+    /// `ProcessLookupError` are never picked.
+    fn os_error_subclass(&self, errno: i64) -> Option<&ObjectRef> {
+        let errno = i32::try_from(errno).ok()?;
+        let class = match io::Error::from_raw_os_error(errno).kind() {
+            io::ErrorKind::WouldBlock => &self.blocking_io_error,
+            io::ErrorKind::BrokenPipe => &self.broken_pipe_error,
+            io::ErrorKind::ConnectionAborted => &self.connection_aborted_error,
+            io::ErrorKind::ConnectionRefused => &self.connection_refused_error,
+            io::ErrorKind::ConnectionReset => &self.connection_reset_error,
+            io::ErrorKind::AlreadyExists => &self.file_exists_error,
+            io::ErrorKind::NotFound => &self.file_not_found_error,
+            io::ErrorKind::Interrupted => &self.interrupted_error,
+            io::ErrorKind::IsADirectory => &self.is_a_directory_error,
+            io::ErrorKind::NotADirectory => &self.not_a_directory_error,
+            io::ErrorKind::PermissionDenied => &self.permission_error,
+            io::ErrorKind::TimedOut => &self.timeout_error,
+            _ => return None,
+        };
+        Some(class)
+    }
+}
+
+/// `BaseException(*args)`, the constructor every exception class inherits.
+fn exception_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    Ok(vm.new_exception(class, args.positional))
+}
+
+/// `OSError(errno, strerror, filename=None, winerror=None, filename2=None)`,
+/// or `OSError(*args)` with any other number of arguments. Given an error
+/// number, `OSError` itself makes the subclass for it, such as
+/// `FileNotFoundError` for `ENOENT`. The arguments become the `errno`,
+/// `strerror`, `filename` and `filename2` attributes, and only the first
+/// two stay in `args` when there is a file name.
+fn os_error_new(vm: &mut Vm, class: &ObjectRef, args: Args) -> PyResult {
+    let mut positional = args.positional;
+    let mut class = class.clone();
+    let none = vm.none();
+    let mut attributes = [none.clone(), none.clone(), none.clone(), none];
+    if (2..=5).contains(&positional.len()) {
+        if let Payload::Int(errno) = positional[0].payload {
+            if Vm::is(&class, &vm.exceptions.os_error) {
+                if let Some(subclass) = vm.exceptions.os_error_subclass(errno) {
+                    class = subclass.clone();
+                }
+            }
+        }
+        attributes[0] = positional[0].clone();
+        attributes[1] = positional[1].clone();
+        if let Some(filename) = positional.get(2) {
+            attributes[2] = filename.clone();
+        }
+        if let Some(filename2) = positional.get(4) {
+            attributes[3] = filename2.clone();
+        }
+        positional.truncate(2);
+    }
+    let exception = vm.new_exception(&class, positional);
+    let dict = exception.dict().unwrap().clone();
+    for (name, value) in ["errno", "strerror", "filename", "filename2"]
+        .iter()
+        .zip(attributes)
+    {
+        vm.dict_set_str(dict.as_dict().unwrap(), name, value);
+    }
+    Ok(exception)
+}
+
+/// `OSError.__init__(*args)`, which leaves the arguments as `__new__`
+/// took them apart.
+pub fn os_error_init(vm: &mut Vm, _args: Args) -> PyResult {
+    Ok(vm.none())
+}
+
+/// The attributes of a `SyntaxError` that `SyntaxError(msg, details)` sets,
+/// after `msg`, from the tuple `details`.
+const SYNTAX_ERROR_DETAILS: [&str; 6] = [
+    "filename",
+    "lineno",
+    "offset",
+    "text",
+    "end_lineno",
+    "end_offset",
+];
+
+/// `SyntaxError.__init__(*args)`, which sets the attributes of the error
+/// from its arguments, as `set_syntax_error_details` does.
+pub fn syntax_error_init(vm: &mut Vm, args: Args) -> PyResult {
+    let positional = args.positional.clone();
+    exception_init(vm, args)?;
+    set_syntax_error_details(vm, &positional[0], &positional[1..])?;
+    Ok(vm.none())
+}
+
+/// Sets the attributes of the `SyntaxError` `exception` made with the
+/// arguments `args`. The first is the message, in `msg`; with exactly
+/// two, the second is `(filename, lineno, offset, text[, end_lineno,
+/// end_offset])`, which set those attributes. Any attribute not given is
+/// `None`.
+fn set_syntax_error_details(
+    vm: &mut Vm,
+    exception: &ObjectRef,
+    args: &[ObjectRef],
+) -> PyResult<()> {
+    let none = vm.none();
+    let mut details = vec![none.clone(); SYNTAX_ERROR_DETAILS.len()];
+    if args.len() == 2 {
+        let info = vm.iterate(&args[1])?;
+        if info.len() < 4 || info.len() > 6 {
+            let message = format!(
+                "function takes at {} {} arguments ({} given)",
+                if info.len() < 4 { "least" } else { "most" },
+                if info.len() < 4 { 4 } else { 6 },
+                info.len()
+            );
+            return Err(vm.new_type_error(message));
+        }
+        for (slot, value) in details.iter_mut().zip(info) {
+            *slot = value;
+        }
+    }
+    let dict = exception.dict().unwrap().clone();
+    let dict = dict.as_dict().unwrap();
+    let message = args.first().cloned().unwrap_or_else(|| none.clone());
+    vm.dict_set_str(dict, "msg", message);
+    for (name, value) in SYNTAX_ERROR_DETAILS.iter().zip(details) {
+        vm.dict_set_str(dict, name, value);
+    }
+    vm.dict_set_str(dict, "print_file_and_line", none);
+    Ok(())
+}
+
+/// `BaseException.__init__(*args)`, which sets `args` again, for classes
+/// whose `__new__` was given other arguments.
+pub fn exception_init(vm: &mut Vm, args: Args) -> PyResult {
+    let exception = match args.positional.first() {
+        Some(exception) => exception.clone(),
+        None => {
+            let message = "descriptor '__init__' of 'BaseException' object needs an argument";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+    };
+    if !args.keywords.is_empty() {
+        let message = format!("{}() takes no keyword arguments", exception.type_name());
+        return Err(vm.new_type_error(message));
+    }
+    if let Some(data) = exception.as_exception() {
+        data.borrow_mut().args = vm.new_tuple(args.positional[1..].to_vec());
+    }
+    Ok(vm.none())
+}
+
+/// `BaseException.with_traceback(tb)`, which sets the traceback and returns
+/// the exception.
+pub fn with_traceback(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "with_traceback", 2, 2)?;
+    let exception = &args.positional[0];
+    vm.set_exception_attr(exception, "__traceback__", &args.positional[1])?;
+    Ok(exception.clone())
+}
+
+impl Vm {
+    pub fn new_exception(&mut self, class: &ObjectRef, args: Vec<ObjectRef>) -> ObjectRef {
+        let data = ExceptionData {
+            args: self.new_tuple(args),
+            traceback: None,
+            cause: None,
+            context: None,
+            suppress_context: false,
+        };
+        let dict = self.new_dict();
+        let exception = PyObject::new(
+            Payload::Exception(RefCell::new(data)),
+            class.clone(),
+            Some(dict),
+        );
+        // A `SyntaxError` made here rather than by calling its class still
+        // has its attributes; details that do not unpack leave them `None`,
+        // for `__init__` to reject.
+        if Vm::is_subclass(class, &self.exceptions.syntax_error) {
+            let args = exception.as_exception().unwrap().borrow().args.clone();
+            let args = match args.payload {
+                Payload::Tuple(ref items) => items.clone(),
+                _ => Vec::new(),
+            };
+            if set_syntax_error_details(self, &exception, &args).is_err() {
+                let _ = set_syntax_error_details(self, &exception, &args[..1]);
+            }
+        }
+        exception
+    }
+
+    /// An exception of class `class` with `message` as its only argument.
+    pub fn new_error(&mut self, class: &ObjectRef, message: String) -> ObjectRef {
+        let message = self.new_str(&message);
+        self.new_exception(class, vec![message])
+    }
+
+    pub fn new_type_error(&mut self, message: String) -> ObjectRef {
+        let class = self.exceptions.type_error.clone();
+        self.new_error(&class, message)
+    }
+
+    pub fn new_value_error(&mut self, message: String) -> ObjectRef {
+        let class = self.exceptions.value_error.clone();
+        self.new_error(&class, message)
+    }
+
+    pub fn new_attribute_error(&mut self, message: String) -> ObjectRef {
+        let class = self.exceptions.attribute_error.clone();
+        self.new_error(&class, message)
+    }
+
+    pub fn new_index_error(&mut self, message: String) -> ObjectRef {
+        let class = self.exceptions.index_error.clone();
+        self.new_error(&class, message)
+    }
+
+    pub fn new_name_error(&mut self, message: String) -> ObjectRef {
+        let class = self.exceptions.name_error.clone();
+        self.new_error(&class, message)
+    }
+
+    pub fn new_runtime_error(&mut self, message: String) -> ObjectRef {
+        let class = self.exceptions.runtime_error.clone();
+        self.new_error(&class, message)
+    }
+
+    pub fn new_zero_division_error(&mut self, message: String) -> ObjectRef {
+        let class = self.exceptions.zero_division_error.clone();
+        self.new_error(&class, message)
+    }
+
+    pub fn new_overflow_error(&mut self, message: String) -> ObjectRef {
+        let class = self.exceptions.overflow_error.clone();
+        self.new_error(&class, message)
+    }
+
+    /// A `KeyError` for `key`, which is its only argument.
+    pub fn new_key_error(&mut self, key: ObjectRef) -> ObjectRef {
+        let class = self.exceptions.key_error.clone();
+        self.new_exception(&class, vec![key])
+    }
+
+    /// Whether `class` is `base` or derives from it.
+    pub fn is_subclass(class: &ObjectRef, base: &ObjectRef) -> bool {
+        if Vm::is(class, base) {
+            return true;
+        }
+        match class.as_type() {
+            Some(data) => data.mro.iter().any(|c| Vm::is(c, base)),
+            None => false,
+        }
+    }
+
+    pub fn is_instance(object: &ObjectRef, class: &ObjectRef) -> bool {
+        Vm::is_subclass(&object.class(), class)
+    }
+
+    /// Whether `class` is a class deriving from `BaseException`.
+    pub fn is_exception_class(&self, class: &ObjectRef) -> bool {
+        class.as_type().is_some() && Vm::is_subclass(class, &self.exceptions.base_exception)
+    }
+
+    /// The exception to raise for `raise value`: an instance is raised as
+    /// it is, and a class is called without arguments.
+    pub fn make_exception(&mut self, value: &ObjectRef) -> PyResult {
+        if self.is_exception_class(value) {
+            let exception = self.call(value, Args::default())?;
+            if exception.as_exception().is_none() {
+                let message = format!(
+                    "calling {} should have returned an instance of BaseException, not {}",
+                    self.repr(value)?,
+                    exception.type_name()
+                );
+                return Err(self.new_type_error(message));
+            }
+            return Ok(exception);
+        }
+        if value.as_exception().is_some() {
+            return Ok(value.clone());
+        }
+        Err(self.new_type_error("exceptions must derive from BaseException".to_string()))
+    }
+
+    /// Sets the `__context__` of a newly raised exception to the exception
+    /// being handled, if any, breaking any cycle that would create.
+    pub fn set_context(&mut self, exception: &ObjectRef) {
+        let handled = match self.exc_info {
+            Some(ref handled) if !Vm::is(handled, exception) => handled.clone(),
+            _ => return,
+        };
+        let mut current = handled.clone();
+        loop {
+            let next = match current.as_exception() {
+                Some(data) => data.borrow().context.clone(),
+                None => None,
+            };
+            match next {
+                Some(next) if Vm::is(&next, exception) => {
+                    current.as_exception().unwrap().borrow_mut().context = None;
+                    break;
+                }
+                Some(next) => current = next,
+                None => break,
+            }
+        }
+        if let Some(data) = exception.as_exception() {
+            data.borrow_mut().context = Some(handled);
+        }
+    }
+
+    /// Adds the frame of `code`, stopped at instruction `lasti` with the
+    /// local variables `locals`, to the front of the traceback of
+    /// `exception`.
+    pub fn add_traceback(
+        &mut self,
+        exception: &ObjectRef,
+        code: &Arc<CodeObject>,
+        lasti: usize,
+        locals: Vec<(String, ObjectRef)>,
+    ) {
+        let data = match exception.as_exception() {
+            Some(data) => data,
+            None => return,
+        };
+        let next = data.borrow_mut().traceback.take();
+        let traceback = PyObject::new(
+            Payload::Traceback(Traceback {
+                next,
+                code: code.clone(),
+                lasti,
+                line: code
+                    .locations
+                    .get(lasti)
+                    .map_or(0, |location| location.line),
+                locals,
+            }),
+            self.types.traceback.clone(),
+            None,
+        );
+        data.borrow_mut().traceback = Some(traceback);
+    }
+
+    /// The `str` of an exception: nothing for no arguments, the `str` of a
+    /// single argument, or else the tuple of arguments. An `OSError` with an
+    /// error number and message shows those and its file names instead, a
+    /// `SyntaxError` its message and where the error is, and a group its
+    /// message and how many exceptions it holds.
+    pub fn exception_str(&mut self, exception: &ObjectRef) -> PyResult<String> {
+        if Vm::is_instance(exception, &self.exceptions.os_error) {
+            if let Some(text) = self.os_error_str(exception)? {
+                return Ok(text);
+            }
+        }
+        if Vm::is_instance(exception, &self.exceptions.syntax_error) {
+            return self.syntax_error_str(exception);
+        }
+        if self.is_exception_group(exception) {
+            return self.exception_group_str(exception);
+        }
+        let args = exception.as_exception().unwrap().borrow().args.clone();
+        let items = match args.payload {
+            Payload::Tuple(ref items) => items.clone(),
+            _ => return self.str(&args),
+        };
+        match items.len() {
+            0 => Ok(String::new()),
+            // A missing key reads better quoted.
+            1 if Vm::is_instance(exception, &self.exceptions.key_error) => self.repr(&items[0]),
+            1 => self.str(&items[0]),
+            _ => self.repr(&args),
+        }
+    }
+
+    /// The `str` of an `OSError` that has an error number and message, or
+    /// a file name, as `[Errno 2] No such file or directory: 'name'`.
+    fn os_error_str(&mut self, exception: &ObjectRef) -> PyResult<Option<String>> {
+        let dict = exception.dict().unwrap().clone();
+        let attribute = |name: &str| {
+            let value = dict.as_dict().unwrap().borrow().get_str(name);
+            value.filter(|value| !matches!(value.payload, Payload::None))
+        };
+        let (errno, strerror) = (attribute("errno"), attribute("strerror"));
+        let (filename, filename2) = (attribute("filename"), attribute("filename2"));
+        if filename.is_none() && (errno.is_none() || strerror.is_none()) {
+            return Ok(None);
+        }
+        let none = self.none();
+        let mut text = format!(
+            "[Errno {}] {}",
+            self.str(errno.as_ref().unwrap_or(&none))?,
+            self.str(strerror.as_ref().unwrap_or(&none))?
+        );
+        if let Some(filename) = filename {
+            text.push_str(&format!(": {}", self.repr(&filename)?));
+            if let Some(filename2) = filename2 {
+                text.push_str(&format!(" -> {}", self.repr(&filename2)?));
+            }
+        }
+        Ok(Some(text))
+    }
+
+    /// The `str` of a `SyntaxError`: its message, followed by the base name
+    /// of its file and its line number, if it has them, as
+    /// `invalid syntax (example.py, line 3)`.
+    fn syntax_error_str(&mut self, exception: &ObjectRef) -> PyResult<String> {
+        let message = self.exception_detail(exception, "msg");
+        let mut text = self.str(&message)?;
+        let filename = self.exception_detail(exception, "filename");
+        let filename = filename
+            .as_str()
+            .map(|filename| filename.rsplit('/').next().unwrap().to_string());
+        let lineno = self.exception_detail(exception, "lineno");
+        let lineno = match lineno.payload {
+            Payload::Int(line) if Vm::is(&lineno.class(), &self.types.int) => Some(line),
+            _ => None,
+        };
+        match (filename, lineno) {
+            (Some(filename), Some(lineno)) => {
+                text.push_str(&format!(" ({}, line {})", filename, lineno))
+            }
+            (Some(filename), None) => text.push_str(&format!(" ({})", filename)),
+            (None, Some(lineno)) => text.push_str(&format!(" (line {})", lineno)),
+            (None, None) => {}
+        }
+        Ok(text)
+    }
+
+    /// The attribute `name` of an exception, from its dict, or `None` if it
+    /// has none.
+    fn exception_detail(&self, exception: &ObjectRef, name: &str) -> ObjectRef {
+        exception
+            .dict()
+            .and_then(|dict| dict.as_dict().unwrap().borrow().get_str(name))
+            .unwrap_or_else(|| self.none())
+    }
+
+    pub fn exception_repr(&mut self, exception: &ObjectRef) -> PyResult<String> {
+        let args = exception.as_exception().unwrap().borrow().args.clone();
+        let name = exception.type_name();
+        match args.payload {
+            Payload::Tuple(ref items) if items.len() == 1 => {
+                Ok(format!("{}({})", name, self.repr(&items[0])?))
+            }
+            _ => Ok(format!("{}{}", name, self.repr(&args)?)),
+        }
+    }
+
+    /// The attributes every exception has, or `None` for other names.
+    pub fn exception_attr(&mut self, exception: &ObjectRef, name: &str) -> Option<ObjectRef> {
+        let data = exception.as_exception()?.borrow();
+        let value = match name {
+            "args" => data.args.clone(),
+            "__traceback__" => data.traceback.clone().unwrap_or_else(|| self.none()),
+            "__cause__" => data.cause.clone().unwrap_or_else(|| self.none()),
+            "__context__" => data.context.clone().unwrap_or_else(|| self.none()),
+            "__suppress_context__" => self.new_bool(data.suppress_context),
+            "value" if Vm::is_instance(exception, &self.exceptions.stop_iteration) => {
+                drop(data);
+                return Some(self.stop_iteration_value(exception));
+            }
+            "code" if Vm::is_instance(exception, &self.exceptions.system_exit) => {
+                drop(data);
+                return Some(self.system_exit_code(exception));
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Sets one of the attributes every exception has, returning `false`
+    /// for other names.
+    pub fn set_exception_attr(
+        &mut self,
+        exception: &ObjectRef,
+        name: &str,
+        value: &ObjectRef,
+    ) -> PyResult<bool> {
+        let data = exception.as_exception().unwrap();
+        let optional = if Vm::is(value, &self.none) {
+            None
+        } else {
+            Some(value.clone())
+        };
+        match name {
+            "args" => {
+                let items = self.iterate(value)?;
+                let args = self.new_tuple(items);
+                data.borrow_mut().args = args;
+            }
+            "__traceback__" => {
+                if optional.is_some() && !matches!(value.payload, Payload::Traceback(_)) {
+                    let message = "__traceback__ must be a traceback or None".to_string();
+                    return Err(self.new_type_error(message));
+                }
+                data.borrow_mut().traceback = optional;
+            }
+            "__cause__" | "__context__" => {
+                if optional.is_some() && value.as_exception().is_none() {
+                    let message = format!(
+                        "exception {} must be None or derive from BaseException",
+                        if name == "__cause__" {
+                            "cause"
+                        } else {
+                            "context"
+                        }
+                    );
+                    return Err(self.new_type_error(message));
+                }
+                let mut data = data.borrow_mut();
+                if name == "__cause__" {
+                    data.cause = optional;
+                    data.suppress_context = true;
+                } else {
+                    data.context = optional;
+                }
+            }
+            "__suppress_context__" => {
+                let suppress = self.is_true(value)?;
+                data.borrow_mut().suppress_context = suppress;
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+
+    /// The report the interpreter prints for an uncaught exception: the
+    /// exceptions it was raised from or while handling, then its traceback
+    /// and its class and message. The exceptions of a group follow it,
+    /// each reported in a frame of its own.
+    pub fn format_exception(&mut self, exception: &ObjectRef) -> String {
+        let mut out = String::new();
+        let mut context = PrintContext::default();
+        self.format_chain(exception, &mut context, &mut out);
+        out
+    }
+
+    /// Handles an exception that ended the program, as the interpreter does
+    /// on exit, and returns the process's exit status. `SystemExit` exits
+    /// with its code if that is an int, and otherwise prints it and exits
+    /// with 1, or 0 for `None`; other exceptions print their report to
+    /// stderr and exit with 1.
+    pub fn exit_status(&mut self, exception: &ObjectRef) -> i32 {
+        if !Vm::is_instance(exception, &self.exceptions.system_exit) {
+            eprint!("{}", self.format_exception(exception));
+            return 1;
+        }
+        let code = self.system_exit_code(exception);
+        match code.payload {
+            Payload::None => 0,
+            Payload::Int(status) => status as i32,
+            _ => {
+                match self.str(&code) {
+                    Ok(message) => eprintln!("{}", message),
+                    Err(_) => eprintln!("<exception str() failed>"),
+                }
+                1
+            }
+        }
+    }
+
+    /// The `code` of a `SystemExit`: `None` without arguments, its argument,
+    /// or the tuple of its arguments if it has several.
+    fn system_exit_code(&mut self, exception: &ObjectRef) -> ObjectRef {
+        let args = exception.as_exception().unwrap().borrow().args.clone();
+        match args.payload {
+            Payload::Tuple(ref items) if items.len() == 1 => items[0].clone(),
+            Payload::Tuple(ref items) if items.is_empty() => self.none(),
+            _ => args.clone(),
+        }
+    }
+
+    fn format_chain(
+        &mut self,
+        exception: &ObjectRef,
+        context: &mut PrintContext,
+        out: &mut String,
+    ) {
+        context.seen.push(object_id(exception));
+        let (cause, chained, suppress_context, traceback) = match exception.as_exception() {
+            Some(data) => {
+                let data = data.borrow();
+                (
+                    data.cause.clone(),
+                    data.context.clone(),
+                    data.suppress_context,
+                    data.traceback.clone(),
+                )
+            }
+            None => (None, None, false, None),
+        };
+        let unseen = |exception: &Option<ObjectRef>| match *exception {
+            Some(ref exception) if !context.seen.contains(&object_id(exception)) => {
+                Some(exception.clone())
+            }
+            _ => None,
+        };
+        if let Some(cause) = unseen(&cause) {
+            self.format_chain(&cause, context, out);
+            context.emit(
+                "\nThe above exception was the direct cause of the following exception:\n\n",
+                '|',
+                out,
+            );
+        } else if let Some(chained) = unseen(&chained).filter(|_| !suppress_context) {
+            self.format_chain(&chained, context, out);
+            context.emit(
+                "\nDuring handling of the above exception, another exception occurred:\n\n",
+                '|',
+                out,
+            );
+        }
+        if !self.is_exception_group(exception) {
+            let mut text = String::new();
+            if let Some(traceback) = traceback {
+                text.push_str("Traceback (most recent call last):\n");
+                self.format_traceback(&traceback, &mut text);
+            }
+            self.format_exception_only(exception, &mut text);
+            context.emit(&text, '|', out);
+            return;
+        }
+        if context.depth > MAX_GROUP_DEPTH {
+            let text = format!("... (max_group_depth is {})\n", MAX_GROUP_DEPTH);
+            context.emit(&text, '|', out);
+            return;
+        }
+        let outermost = context.depth == 0;
+        if outermost {
+            context.depth += 1;
+        }
+        if let Some(traceback) = traceback {
+            let margin = if outermost { '+' } else { '|' };
+            context.emit(
+                "Exception Group Traceback (most recent call last):\n",
+                margin,
+                out,
+            );
+            let mut text = String::new();
+            self.format_traceback(&traceback, &mut text);
+            context.emit(&text, '|', out);
+        }
+        let mut text = String::new();
+        self.format_exception_only(exception, &mut text);
+        context.emit(&text, '|', out);
+        let exceptions = self.group_exceptions(exception);
+        let count = exceptions.len().min(MAX_GROUP_WIDTH + 1);
+        context.need_close = false;
+        for index in 0..count {
+            let last = index + 1 == count;
+            if last {
+                // A nested group may close the frame instead.
+                context.need_close = true;
+            }
+            let truncated = index >= MAX_GROUP_WIDTH;
+            let title = if truncated {
+                "...".to_string()
+            } else {
+                (index + 1).to_string()
+            };
+            out.push_str(&format!(
+                "{}{}+---------------- {} ----------------\n",
+                context.indent(),
+                if index == 0 { "+-" } else { "  " },
+                title
+            ));
+            context.depth += 1;
+            if truncated {
+                let remaining = exceptions.len() - MAX_GROUP_WIDTH;
+                let text = format!(
+                    "and {} more exception{}\n",
+                    remaining,
+                    if remaining > 1 { "s" } else { "" }
+                );
+                context.emit(&text, '|', out);
+            } else {
+                self.format_chain(&exceptions[index], context, out);
+            }
+            if last && context.need_close {
+                out.push_str(&format!(
+                    "{}+------------------------------------\n",
+                    context.indent()
+                ));
+                context.need_close = false;
+            }
+            context.depth -= 1;
+        }
+        if outermost {
+            context.depth = 0;
+        }
+    }
+
+    /// The last line of the report of an exception: its class and message,
+    /// or for a `SyntaxError` where the error is, as well.
+    fn format_exception_only(&mut self, exception: &ObjectRef, out: &mut String) {
+        let name = exception.type_name();
+        if Vm::is_instance(exception, &self.exceptions.syntax_error)
+            && self.format_syntax_error(exception, &name, out)
+        {
+            return;
+        }
+        match self.str(exception) {
+            Ok(ref message) if message.is_empty() => out.push_str(&name),
+            Ok(message) => out.push_str(&format!("{}: {}", name, message)),
+            Err(_) => out.push_str(&format!("{}: <exception str() failed>", name)),
+        }
+        out.push('\n');
+    }
+
+    /// Reports a `SyntaxError` as CPython does, with where the error is,
+    /// the line of source with carets under the error, and the message,
+    /// returning `false` for an error without a line number, which is
+    /// reported as other exceptions are.
+    fn format_syntax_error(&mut self, exception: &ObjectRef, name: &str, out: &mut String) -> bool {
+        let number = |value: ObjectRef, default: i64| match value.payload {
+            Payload::Int(value) => Some(value),
+            Payload::None => Some(default),
+            _ => None,
+        };
+        let lineno = match self.exception_detail(exception, "lineno").payload {
+            Payload::Int(lineno) => lineno,
+            _ => return false,
+        };
+        let details = (
+            number(self.exception_detail(exception, "offset"), -1),
+            number(self.exception_detail(exception, "end_lineno"), lineno),
+            number(self.exception_detail(exception, "end_offset"), -1),
+        );
+        let (offset, end_lineno, mut end_offset) = match details {
+            (Some(offset), Some(end_lineno), Some(end_offset)) => (offset, end_lineno, end_offset),
+            _ => return false,
+        };
+        let filename = self.exception_detail(exception, "filename");
+        let filename = match filename.payload {
+            Payload::None => "<string>".to_string(),
+            _ => match self.str(&filename) {
+                Ok(filename) => filename,
+                Err(_) => return false,
+            },
+        };
+        out.push_str(&format!("  File \"{}\", line {}\n", filename, lineno));
+        if let Some(text) = self.exception_detail(exception, "text").as_str() {
+            let size = text.chars().count() as i64;
+            if end_lineno > lineno {
+                end_offset = size;
+            }
+            end_offset = end_offset.min(size + 1);
+            // Only `SyntaxError` itself marks the whole of the error.
+            if !Vm::is(&exception.class(), &self.exceptions.syntax_error) {
+                end_offset = -1;
+            }
+            out.push_str(&error_text(text, offset, end_offset));
+        }
+        let message = self.exception_detail(exception, "msg");
+        let message = match message.payload {
+            Payload::None => String::new(),
+            _ => self
+                .str(&message)
+                .unwrap_or_else(|_| "<exception str() failed>".to_string()),
+        };
+        if message.is_empty() {
+            out.push_str(name);
+        } else {
+            out.push_str(&format!("{}: {}", name, message));
+        }
+        out.push('\n');
+        true
+    }
+
+    fn format_traceback(&mut self, traceback: &ObjectRef, out: &mut String) {
+        let mut current = Some(traceback.clone());
+        while let Some(traceback) = current {
+            let entry = match traceback.payload {
+                Payload::Traceback(ref entry) => entry,
+                _ => break,
+            };
+            out.push_str(&format!(
+                "  File \"{}\", line {}, in {}\n",
+                entry.code.filename, entry.line, entry.code.name
+            ));
+            if let Some(line) = self.source_line(&entry.code.filename, entry.line) {
+                if !line.is_empty() {
+                    out.push_str(&format!("    {}\n", line));
+                }
+            }
+            if let Some(limits) = self.traceback_locals.clone() {
+                let mut locals = entry.locals.clone();
+                locals.sort_by(|a, b| a.0.cmp(&b.0));
+                for (name, value) in locals {
+                    let text = self
+                        .limited_repr(&value, &limits)
+                        .unwrap_or_else(|_| "<local repr() failed>".to_string());
+                    out.push_str(&format!("    {} = {}\n", name, text));
+                }
+            }
+            current = entry.next.clone();
+        }
+    }
+
+    /// Line `line` of file `filename`, stripped, from the source the VM ran
+    /// or else from the file.
+    fn source_line(&mut self, filename: &str, line: usize) -> Option<String> {
+        if !self.sources.contains_key(filename) {
+            let text = ::std::fs::read_to_string(filename).ok()?;
+            self.keep_source(filename, &text);
+        }
+        self.kept_line(filename, line)
+            .map(|text| text.trim().to_string())
+    }
+}
+
+/// How many exceptions of a group are reported, and how deep in groups.
+const MAX_GROUP_WIDTH: usize = 15;
+const MAX_GROUP_DEPTH: usize = 10;
+
+/// Where a report is in the groups of the exception reported, as CPython's
+/// `traceback` module keeps it.
+#[derive(Default)]
+struct PrintContext {
+    /// The ids of the exceptions reported, which are not reported again
+    /// as the cause or context of another.
+    seen: Vec<usize>,
+    /// How many groups deep the report is.
+    depth: usize,
+    /// Whether the frame of the last exception of a group is still to be
+    /// closed.
+    need_close: bool,
+}
+
+impl PrintContext {
+    fn indent(&self) -> String {
+        " ".repeat(2 * self.depth)
+    }
+
+    /// Adds `text` to `out`, with each line in the margin of the groups it
+    /// is in, which starts with `margin`.
+    fn emit(&self, text: &str, margin: char, out: &mut String) {
+        let mut prefix = self.indent();
+        if self.depth > 0 {
+            prefix.push(margin);
+            prefix.push(' ');
+        }
+        for line in text.split_inclusive('\n') {
+            out.push_str(&prefix);
+            out.push_str(line);
+        }
+    }
+}
+
+/// The line of source of a `SyntaxError`, without its indentation, and
+/// carets under the error, from column `offset` to before `end_offset`
+/// counting from 1, as CPython prints them. Of a text of several lines,
+/// the one with the error is shown; an offset left of the text shows no
+/// carets.
+fn error_text(text: &str, offset: i64, end_offset: i64) -> String {
+    let carets = if end_offset > 0 && end_offset > offset {
+        end_offset - offset
+    } else {
+        1
+    };
+    let mut text = text;
+    let mut offset = offset - 1;
+    while let Some(rest) = text.strip_prefix(|c| matches!(c, ' ' | '\t' | '\x0c')) {
+        text = rest;
+        offset -= 1;
+    }
+    let mut length = text.chars().count() as i64;
+    if text.ends_with('\n') {
+        length -= 1;
+    }
+    offset = offset.min(length);
+    while let Some(newline) = text.find('\n') {
+        let line_length = text[..newline].chars().count() as i64;
+        if line_length >= offset {
+            break;
+        }
+        text = &text[newline + 1..];
+        length -= line_length + 1;
+        offset -= line_length + 1;
+    }
+    let mut out = format!("    {}", text);
+    if text.chars().nth(length as usize) != Some('\n') {
+        out.push('\n');
+    }
+    if offset >= 0 {
+        out.push_str("    ");
+        out.extend(::std::iter::repeat_n(' ', offset as usize));
+        out.extend(::std::iter::repeat_n('^', carets as usize));
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,1796 @@
+//! The evaluation loop, and the block stack through which exceptions
+//! unwind to their handlers.
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+use compiler::{
+    CodeObject, Instruction, CO_VARARGS, CO_VARKEYWORDS, MAKE_ANNOTATIONS, MAKE_CLOSURE,
+    MAKE_DEFAULTS, MAKE_KWDEFAULTS,
+};
+
+use super::builtins;
+use super::cache::InlineCaches;
+use super::gc;
+use super::object::{Args, Function, ObjectRef, Payload, PyObject, PyResult};
+use super::tracer::{TraceEvent, TraceFrame};
+use super::Vm;
+
+/// An entry of a frame's block stack.
+#[derive(Debug, Clone, Copy)]
+enum Block {
+    /// Set up by `SetupFinally`: an exception raised while it is on top goes
+    /// to `handler`, with the value stack cut back to `level`.
+    Finally { handler: usize, level: usize },
+    /// Active while a handler runs. The previously handled exception is on
+    /// the value stack at `level`, to be restored when the handler ends.
+    ExceptHandler { level: usize },
+}
+
+/// How execution continues after an instruction.
+enum Flow {
+    Next,
+    Return(ObjectRef),
+    /// Suspend the frame, handing the value to whoever resumed it.
+    Yield(ObjectRef),
+    /// Raise an exception again as it is, without adding this frame to its
+    /// traceback or setting its context.
+    Reraise(ObjectRef),
+}
+
+/// How a frame stopped running without raising.
+pub(super) enum Completion {
+    Returned(ObjectRef),
+    /// Only the frames of generators yield. They can be resumed.
+    Yielded(ObjectRef),
+}
+
+/// What builtins such as `eval` and `warnings.warn` can see of a frame
+/// that is running: its code, its namespaces, and the instruction that made
+/// the call now in progress. A function keeps its locals in fast slots
+/// rather than a dict, so its `locals` are `None`.
+#[derive(Clone)]
+pub(super) struct RunningFrame {
+    pub code: Arc<CodeObject>,
+    pub globals: ObjectRef,
+    pub locals: Option<ObjectRef>,
+    pub lasti: usize,
+    /// The frame object tracers see, once the frame has been traced.
+    pub trace_object: Option<ObjectRef>,
+}
+
+/// The state of one execution of a code object.
+pub struct Frame {
+    code: Arc<CodeObject>,
+    constants: Rc<[ObjectRef]>,
+    caches: Rc<InlineCaches>,
+    globals: ObjectRef,
+    /// The namespace of module-level code; functions use `fast` instead.
+    locals: Option<ObjectRef>,
+    fast: Vec<Option<ObjectRef>>,
+    /// The cells of `code.cellvars`, then those of `code.freevars`.
+    cells: Vec<ObjectRef>,
+    stack: Vec<ObjectRef>,
+    blocks: Vec<Block>,
+    pc: usize,
+    /// The instruction last started, for tracebacks.
+    lasti: usize,
+    /// The line of the last `Line` trace event.
+    traced_line: usize,
+    /// The frame object tracers see, made for the first event traced.
+    trace_object: Option<ObjectRef>,
+}
+
+impl Frame {
+    pub fn new(
+        code: Arc<CodeObject>,
+        constants: Rc<[ObjectRef]>,
+        caches: Rc<InlineCaches>,
+        globals: ObjectRef,
+        locals: Option<ObjectRef>,
+    ) -> Frame {
+        Frame {
+            fast: vec![None; code.varnames.len()],
+            cells: Vec::new(),
+            code,
+            constants,
+            caches,
+            globals,
+            locals,
+            stack: Vec::new(),
+            blocks: Vec::new(),
+            pc: 0,
+            lasti: 0,
+            traced_line: 0,
+            trace_object: None,
+        }
+    }
+
+    pub(super) fn push(&mut self, value: ObjectRef) {
+        self.stack.push(value);
+    }
+
+    pub(super) fn pop(&mut self) -> ObjectRef {
+        self.stack.pop().expect("value stack underflow")
+    }
+
+    /// Whether the frame has run at all, which a generator's has once it
+    /// first yields.
+    pub(super) fn started(&self) -> bool {
+        self.pc > 0
+    }
+
+    /// Whether an exception handler is running in the frame.
+    pub(super) fn handling_exception(&self) -> bool {
+        self.blocks
+            .iter()
+            .any(|block| matches!(*block, Block::ExceptHandler { .. }))
+    }
+
+    /// The iterator a generator suspended in `yield from` is delegating to.
+    pub(super) fn delegate(&self) -> Option<&ObjectRef> {
+        match self.code.instructions.get(self.pc) {
+            Some(&Instruction::YieldFrom) if self.started() => self.stack.last(),
+            _ => None,
+        }
+    }
+
+    /// Finishes a `yield from` whose iterator returned `value`.
+    pub(super) fn end_delegation(&mut self, value: ObjectRef) {
+        self.pop();
+        self.push(value);
+        self.pc += 1;
+    }
+
+    /// The objects the frame refers to, named as attributes of the frame
+    /// object CPython would have, for heap snapshots.
+    pub(super) fn references(&self) -> Vec<(String, ObjectRef)> {
+        let mut references = vec![(".f_globals".to_string(), self.globals.clone())];
+        if let Some(ref locals) = self.locals {
+            references.push((".f_locals".to_string(), locals.clone()));
+        }
+        for (name, value) in self.code.varnames.iter().zip(&self.fast) {
+            if let Some(ref value) = *value {
+                references.push((format!(".f_locals['{}']", name), value.clone()));
+            }
+        }
+        for (index, cell) in self.cells.iter().enumerate() {
+            let name = self.code.cell_name(index).unwrap_or("?");
+            references.push((format!(".(cell '{}')", name), cell.clone()));
+        }
+        for (index, value) in self.stack.iter().enumerate() {
+            references.push((format!(".(stack)[{}]", index), value.clone()));
+        }
+        for (index, constant) in self.constants.iter().enumerate() {
+            references.push((format!(".f_code.co_consts[{}]", index), constant.clone()));
+        }
+        references
+    }
+
+    /// Calls `visit` for each object the frame holds a reference to, but
+    /// for the constants of its code, which the functions made from the
+    /// code share, for the cycle collector.
+    pub(super) fn traverse(&self, visit: &mut dyn FnMut(&ObjectRef)) {
+        visit(&self.globals);
+        if let Some(ref locals) = self.locals {
+            visit(locals);
+        }
+        for value in self.fast.iter().flatten() {
+            visit(value);
+        }
+        self.cells.iter().for_each(&mut *visit);
+        self.stack.iter().for_each(&mut *visit);
+        if let Some(ref object) = self.trace_object {
+            visit(object);
+        }
+    }
+
+    /// The frame's local variables that have values, as `f_locals` has
+    /// them, for tracebacks that show them.
+    pub(super) fn local_values(&self) -> Vec<(String, ObjectRef)> {
+        let mut values = Vec::new();
+        if let Some(ref locals) = self.locals {
+            if let Some(dict) = locals.as_dict() {
+                for entry in dict.borrow().iter() {
+                    if let Some(name) = entry.key.as_str() {
+                        values.push((name.to_string(), entry.value.clone()));
+                    }
+                }
+            }
+        }
+        for (name, value) in self.code.varnames.iter().zip(&self.fast) {
+            if let Some(ref value) = *value {
+                values.push((name.clone(), value.clone()));
+            }
+        }
+        for (index, cell) in self.cells.iter().enumerate() {
+            if let Payload::Cell(ref value) = cell.payload {
+                if let (Some(name), Some(value)) = (self.code.cell_name(index), &*value.borrow()) {
+                    values.push((name.to_string(), value.clone()));
+                }
+            }
+        }
+        values
+    }
+
+    /// The local namespace, as `locals()` gives it: that of module-level
+    /// code, or a new dict of the values of a function's variables.
+    fn locals_dict(&self, vm: &mut Vm) -> ObjectRef {
+        if let Some(ref locals) = self.locals {
+            return locals.clone();
+        }
+        let dict = vm.new_dict();
+        for (name, value) in self.local_values() {
+            vm.dict_set_str(dict.as_dict().unwrap(), &name, value);
+        }
+        dict
+    }
+
+    /// Moves the parameters that nested functions close over into their
+    /// cells, where the code of the function expects them.
+    fn fill_argument_cells(&mut self) {
+        for (index, name) in self.code.cellvars.iter().enumerate() {
+            if let Some(parameter) = self.code.varnames.iter().position(|other| other == name) {
+                if let Payload::Cell(ref cell) = self.cells[index].payload {
+                    *cell.borrow_mut() = self.fast[parameter].take();
+                }
+            }
+        }
+    }
+
+    /// Pops the top `count` values, in the order they were pushed.
+    fn pop_n(&mut self, count: usize) -> Vec<ObjectRef> {
+        let start = self.stack.len() - count;
+        self.stack.split_off(start)
+    }
+
+    fn top(&self) -> &ObjectRef {
+        self.stack.last().expect("value stack underflow")
+    }
+
+    /// The value `depth` places below the top, where the top is 1.
+    fn peek(&self, depth: usize) -> &ObjectRef {
+        &self.stack[self.stack.len() - depth]
+    }
+
+    fn name(&self, index: usize) -> &str {
+        &self.code.names[index]
+    }
+}
+
+impl Vm {
+    /// Runs a frame until it returns, or until an exception escapes it.
+    pub(super) fn execute(&mut self, frame: &mut Frame) -> PyResult {
+        match self.run_frame(frame, None)? {
+            Completion::Returned(value) => Ok(value),
+            Completion::Yielded(_) => unreachable!("yield outside a generator"),
+        }
+    }
+
+    /// Runs a frame until it returns or yields, or until an exception
+    /// escapes it. With `throw`, the frame first raises that exception
+    /// where it stopped.
+    pub(super) fn run_frame(
+        &mut self,
+        frame: &mut Frame,
+        throw: Option<ObjectRef>,
+    ) -> PyResult<Completion> {
+        self.maybe_collect_garbage();
+        self.frames.push(RunningFrame {
+            code: frame.code.clone(),
+            globals: frame.globals.clone(),
+            locals: frame.locals.clone(),
+            lasti: frame.lasti,
+            trace_object: frame.trace_object.clone(),
+        });
+        let result = match self.trace(frame, TraceEvent::Call, None) {
+            Ok(()) => self.run_steps(frame, throw),
+            Err(exception) => Err(exception),
+        };
+        self.frames.pop();
+        result
+    }
+
+    fn run_steps(&mut self, frame: &mut Frame, throw: Option<ObjectRef>) -> PyResult<Completion> {
+        let mut pending = throw.map(Err);
+        loop {
+            let result = match pending.take() {
+                Some(result) => result,
+                None => match self.trace_line(frame) {
+                    Ok(()) => self.step(frame),
+                    Err(exception) => Err(exception),
+                },
+            };
+            let exception = match result {
+                Ok(Flow::Next) => {
+                    if gc::has_unfinalized() {
+                        self.run_finalizers();
+                    }
+                    continue;
+                }
+                Ok(Flow::Return(value)) => {
+                    self.trace(frame, TraceEvent::Return, Some(&value))?;
+                    return Ok(Completion::Returned(value));
+                }
+                Ok(Flow::Yield(value)) => {
+                    self.trace(frame, TraceEvent::Return, Some(&value))?;
+                    return Ok(Completion::Yielded(value));
+                }
+                Ok(Flow::Reraise(exception)) => exception,
+                Err(exception) => {
+                    // An exception that has no traceback yet was raised by an
+                    // operation here rather than passed up from a call.
+                    let fresh = match exception.as_exception() {
+                        Some(data) => {
+                            let data = data.borrow();
+                            data.traceback.is_none() && data.context.is_none()
+                        }
+                        None => false,
+                    };
+                    if fresh {
+                        self.set_context(&exception);
+                    }
+                    let locals = match self.traceback_locals {
+                        Some(_) => frame.local_values(),
+                        None => Vec::new(),
+                    };
+                    self.add_traceback(&exception, &frame.code, frame.lasti, locals);
+                    match self.trace(frame, TraceEvent::Exception, Some(&exception)) {
+                        Ok(()) => exception,
+                        Err(error) => error,
+                    }
+                }
+            };
+            if !self.unwind(frame, exception.clone()) {
+                self.trace(frame, TraceEvent::Return, None)?;
+                return Err(exception);
+            }
+        }
+    }
+
+    /// Reports a `Line` event if the next instruction is on another line
+    /// than the last one reported, or was jumped back to, and a tracer is
+    /// set. The jump back at the end of a loop is left to report the line
+    /// it lands on, as CPython gives it no line of its own.
+    fn trace_line(&mut self, frame: &mut Frame) -> PyResult<()> {
+        if !self.tracing() {
+            return Ok(());
+        }
+        if let Instruction::Jump(target) = frame.code.instructions[frame.pc] {
+            if target <= frame.pc {
+                return Ok(());
+            }
+        }
+        let line = frame
+            .code
+            .locations
+            .get(frame.pc)
+            .map_or(0, |location| location.line);
+        let jumped_back = frame.started() && frame.pc <= frame.lasti;
+        if line == 0 || (line == frame.traced_line && !jumped_back) {
+            return Ok(());
+        }
+        frame.traced_line = line;
+        frame.lasti = frame.pc;
+        self.trace(frame, TraceEvent::Line, None)
+    }
+
+    /// Tells the tracer of an event in the frame on top of the stack of
+    /// running frames, if one is set and not already running. A tracer that
+    /// raises is removed.
+    fn trace(
+        &mut self,
+        frame: &mut Frame,
+        event: TraceEvent,
+        arg: Option<&ObjectRef>,
+    ) -> PyResult<()> {
+        if !self.tracing() {
+            return Ok(());
+        }
+        let tracer = self.tracer.clone().unwrap();
+        let line = match event {
+            TraceEvent::Call if !frame.started() => frame.code.first_line,
+            TraceEvent::Line => frame.traced_line,
+            _ => frame
+                .code
+                .locations
+                .get(frame.lasti)
+                .map_or(0, |location| location.line),
+        };
+        let object = self.trace_object(frame, line);
+        self.in_tracer = true;
+        let trace_frame = TraceFrame {
+            code: &frame.code,
+            line,
+            object: &object,
+        };
+        let result = tracer.trace(self, &trace_frame, event, arg);
+        self.in_tracer = false;
+        if result.is_err() {
+            self.set_tracer(None);
+        }
+        result
+    }
+
+    /// The frame object of a traced frame, made for its first event, with
+    /// its line and local variables as they are now.
+    fn trace_object(&mut self, frame: &mut Frame, line: usize) -> ObjectRef {
+        let object = match frame.trace_object {
+            Some(ref object) => object.clone(),
+            None => {
+                let namespace = self.new_dict();
+                let object = PyObject::new(
+                    Payload::Object,
+                    self.types.frame.clone(),
+                    Some(namespace.clone()),
+                );
+                let count = self.frames.len();
+                let back = match count {
+                    0 | 1 => None,
+                    _ => self.frames[count - 2].trace_object.clone(),
+                };
+                let code = PyObject::new(
+                    Payload::Code(frame.code.clone()),
+                    self.types.code.clone(),
+                    None,
+                );
+                let back = back.unwrap_or_else(|| self.none());
+                let none = self.none();
+                let dict = namespace.as_dict().unwrap();
+                self.dict_set_str(dict, "f_code", code);
+                self.dict_set_str(dict, "f_globals", frame.globals.clone());
+                self.dict_set_str(dict, "f_back", back);
+                self.dict_set_str(dict, "f_trace", none);
+                frame.trace_object = Some(object.clone());
+                if let Some(running) = self.frames.last_mut() {
+                    running.trace_object = Some(object.clone());
+                }
+                object
+            }
+        };
+        let locals = match frame.locals {
+            Some(ref locals) => locals.clone(),
+            None => {
+                let locals = self.new_dict();
+                for (name, value) in frame.local_values() {
+                    self.dict_set_str(locals.as_dict().unwrap(), &name, value);
+                }
+                locals
+            }
+        };
+        let line = self.new_int(line as i64);
+        let dict = object.dict().unwrap().as_dict().unwrap();
+        self.dict_set_str(dict, "f_lineno", line);
+        self.dict_set_str(dict, "f_locals", locals);
+        object
+    }
+
+    /// Pops blocks until one handles `exception`, and jumps to its handler.
+    /// Returns `false` if the exception escapes the frame.
+    fn unwind(&mut self, frame: &mut Frame, exception: ObjectRef) -> bool {
+        while let Some(block) = frame.blocks.pop() {
+            match block {
+                Block::ExceptHandler { level } => {
+                    frame.stack.truncate(level + 1);
+                    let previous = frame.pop();
+                    self.exc_info = self.optional(previous);
+                }
+                Block::Finally { handler, level } => {
+                    frame.stack.truncate(level);
+                    let previous = self.exc_info.clone().unwrap_or_else(|| self.none());
+                    frame.push(previous);
+                    frame.push(exception.clone());
+                    self.exc_info = Some(exception);
+                    frame.blocks.push(Block::ExceptHandler { level });
+                    frame.pc = handler;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// `None` for the `None` object, and the object otherwise.
+    fn optional(&self, value: ObjectRef) -> Option<ObjectRef> {
+        if Vm::is(&value, &self.none) {
+            None
+        } else {
+            Some(value)
+        }
+    }
+
+    fn step(&mut self, frame: &mut Frame) -> PyResult<Flow> {
+        let instruction = frame.code.instructions[frame.pc];
+        frame.lasti = frame.pc;
+        frame.pc += 1;
+        match instruction {
+            Instruction::Nop => {}
+            Instruction::PopTop => {
+                frame.pop();
+            }
+            Instruction::PrintExpr => {
+                let value = frame.pop();
+                let sys = self.import_module("sys")?;
+                let hook = match self.getattr(&sys, "displayhook") {
+                    Ok(hook) => hook,
+                    Err(_) => {
+                        let message = "lost sys.displayhook".to_string();
+                        return Err(self.new_runtime_error(message));
+                    }
+                };
+                self.call(&hook, Args::new(vec![value]))?;
+            }
+            Instruction::RotTwo => {
+                let len = frame.stack.len();
+                frame.stack.swap(len - 1, len - 2);
+            }
+            Instruction::RotThree => {
+                let top = frame.pop();
+                let len = frame.stack.len();
+                frame.stack.insert(len - 2, top);
+            }
+            Instruction::DupTop => {
+                let top = frame.top().clone();
+                frame.push(top);
+            }
+            Instruction::DupTopTwo => {
+                let second = frame.peek(2).clone();
+                let top = frame.top().clone();
+                frame.push(second);
+                frame.push(top);
+            }
+            Instruction::LoadConst(index) => {
+                let value = frame.constants[index].clone();
+                frame.push(value);
+            }
+            Instruction::LoadBuildClass => {
+                let build_class = self
+                    .builtins()
+                    .as_dict()
+                    .unwrap()
+                    .borrow()
+                    .get_str("__build_class__");
+                match build_class {
+                    Some(build_class) => frame.push(build_class),
+                    None => {
+                        let class = self.exceptions.name_error.clone();
+                        let message = "__build_class__ not found".to_string();
+                        return Err(self.new_error(&class, message));
+                    }
+                }
+            }
+            Instruction::SetupAnnotations => {
+                let locals = frame
+                    .locals
+                    .clone()
+                    .unwrap_or_else(|| frame.globals.clone());
+                let locals = locals.as_dict().unwrap();
+                if locals.borrow().get_str("__annotations__").is_none() {
+                    let annotations = self.new_dict();
+                    self.dict_set_str(locals, "__annotations__", annotations);
+                }
+            }
+            Instruction::LoadName(_)
+            | Instruction::StoreName(_)
+            | Instruction::DeleteName(_)
+            | Instruction::LoadGlobal(_)
+            | Instruction::StoreGlobal(_)
+            | Instruction::DeleteGlobal(_)
+            | Instruction::LoadFast(_)
+            | Instruction::StoreFast(_)
+            | Instruction::DeleteFast(_)
+            | Instruction::LoadClosure(_)
+            | Instruction::LoadDeref(_)
+            | Instruction::StoreDeref(_)
+            | Instruction::DeleteDeref(_)
+            | Instruction::LoadClassDeref(_) => self.name_op(frame, instruction)?,
+            Instruction::LoadAttr(_)
+            | Instruction::StoreAttr(_)
+            | Instruction::DeleteAttr(_)
+            | Instruction::BinarySubscr
+            | Instruction::StoreSubscr
+            | Instruction::DeleteSubscr
+            | Instruction::BinaryOp(_)
+            | Instruction::InplaceOp(_)
+            | Instruction::UnaryOp(_)
+            | Instruction::CompareOp(_) => self.operator(frame, instruction)?,
+            Instruction::BuildTuple(_)
+            | Instruction::BuildList(_)
+            | Instruction::BuildSet(_)
+            | Instruction::BuildMap(_)
+            | Instruction::BuildConstKeyMap(_)
+            | Instruction::BuildSlice(_)
+            | Instruction::BuildString(_)
+            | Instruction::ListExtend(_)
+            | Instruction::SetUpdate(_)
+            | Instruction::DictUpdate(_)
+            | Instruction::DictMerge(_)
+            | Instruction::ListToTuple
+            | Instruction::ListAppend(_)
+            | Instruction::SetAdd(_)
+            | Instruction::MapAdd(_)
+            | Instruction::UnpackSequence(_)
+            | Instruction::UnpackEx { .. }
+            | Instruction::FormatValue { .. } => self.build(frame, instruction)?,
+            Instruction::Jump(target) => {
+                // The end of a loop is a safe point to collect cycles at.
+                if target < frame.pc {
+                    self.maybe_collect_garbage();
+                }
+                frame.pc = target;
+            }
+            Instruction::PopJumpIfFalse(target) | Instruction::PopJumpIfTrue(target) => {
+                let value = frame.pop();
+                let jump_if = matches!(instruction, Instruction::PopJumpIfTrue(_));
+                if self.is_true(&value)? == jump_if {
+                    frame.pc = target;
+                }
+            }
+            Instruction::JumpIfFalseOrPop(target) | Instruction::JumpIfTrueOrPop(target) => {
+                let value = frame.top().clone();
+                let jump_if = matches!(instruction, Instruction::JumpIfTrueOrPop(_));
+                if self.is_true(&value)? == jump_if {
+                    frame.pc = target;
+                } else {
+                    frame.pop();
+                }
+            }
+            Instruction::GetIter => {
+                let iterable = frame.pop();
+                let iterator = self.iter(&iterable)?;
+                frame.push(iterator);
+            }
+            Instruction::ForIter(target) => {
+                let iterator = frame.top().clone();
+                match self.next(&iterator)? {
+                    Some(item) => frame.push(item),
+                    None => {
+                        frame.pop();
+                        frame.pc = target;
+                    }
+                }
+            }
+            Instruction::CallFunction(_)
+            | Instruction::CallFunctionKw(_)
+            | Instruction::CallFunctionEx(_)
+            | Instruction::MakeFunction(_) => self.call_op(frame, instruction)?,
+            Instruction::ReturnValue => return Ok(Flow::Return(frame.pop())),
+            Instruction::YieldValue => return Ok(Flow::Yield(frame.pop())),
+            Instruction::GetYieldFromIter => match frame.top().payload {
+                Payload::Generator(ref generator) if generator.is_coroutine() => {
+                    let message =
+                        "cannot 'yield from' a coroutine object in a non-coroutine generator";
+                    return Err(self.new_type_error(message.to_string()));
+                }
+                Payload::Generator(_) => {}
+                _ => {
+                    let iterable = frame.pop();
+                    let iterator = self.iter(&iterable)?;
+                    frame.push(iterator);
+                }
+            },
+            Instruction::GetAwaitable => {
+                let awaitable = frame.pop();
+                let iterator = self.awaitable_iter(&awaitable)?;
+                frame.push(iterator);
+            }
+            Instruction::YieldFrom => {
+                let value = frame.pop();
+                let iterator = frame.top().clone();
+                match self.send(&iterator, value)? {
+                    Completion::Yielded(value) => {
+                        frame.pc = frame.lasti;
+                        return Ok(Flow::Yield(value));
+                    }
+                    Completion::Returned(value) => {
+                        frame.pop();
+                        frame.push(value);
+                    }
+                }
+            }
+            Instruction::ImportName(index) => {
+                let fromlist = frame.pop();
+                let level = match frame.pop().payload {
+                    Payload::Int(level) => level as usize,
+                    _ => 0,
+                };
+                let fromlist = match fromlist.payload {
+                    Payload::Tuple(ref names) => names.clone(),
+                    _ => Vec::new(),
+                };
+                let globals = frame.globals.clone();
+                let module = self.import(frame.name(index), Some(&globals), &fromlist, level)?;
+                frame.push(module);
+            }
+            Instruction::ImportFrom(index) => {
+                let module = frame.top().clone();
+                let value = self.import_from(&module, frame.name(index))?;
+                frame.push(value);
+            }
+            Instruction::ImportStar => {
+                let module = frame.pop();
+                let locals = frame
+                    .locals
+                    .clone()
+                    .unwrap_or_else(|| frame.globals.clone());
+                self.import_star(&module, &locals)?;
+            }
+            Instruction::LoadAssertionError => {
+                frame.push(self.exceptions.assertion_error.clone());
+            }
+            Instruction::RaiseVarargs(count) => return self.raise(frame, count),
+            Instruction::SetupFinally(handler) => {
+                let level = frame.stack.len();
+                frame.blocks.push(Block::Finally { handler, level });
+            }
+            Instruction::SetupWith(_)
+            | Instruction::WithExceptStart
+            | Instruction::BeforeAsyncWith
+            | Instruction::SetupAsyncWith(_) => self.with_op(frame, instruction)?,
+            Instruction::PopBlock => {
+                frame.blocks.pop();
+            }
+            Instruction::PopExcept => {
+                frame.blocks.pop();
+                let previous = frame.pop();
+                self.exc_info = self.optional(previous);
+            }
+            Instruction::Reraise => return Ok(Flow::Reraise(frame.pop())),
+            Instruction::JumpIfNotExcMatch(target) => {
+                let class = frame.pop();
+                let exception = frame.pop();
+                if !self.exception_matches(&exception, &class)? {
+                    frame.pc = target;
+                }
+            }
+            Instruction::CheckEgMatch(target) => {
+                let class = frame.pop();
+                let exception = frame.pop();
+                match self.exception_group_match(&exception, &class)? {
+                    (Some(matched), rest) => {
+                        frame.push(rest.unwrap_or_else(|| self.none()));
+                        frame.push(matched.clone());
+                        self.exc_info = Some(matched);
+                    }
+                    (None, _) => {
+                        frame.push(exception);
+                        frame.pc = target;
+                    }
+                }
+            }
+            Instruction::PrepReraiseStar => {
+                let raised = frame.pop();
+                let caught = frame.pop();
+                let raised = match raised.payload {
+                    Payload::List(ref items) => items.borrow().clone(),
+                    _ => Vec::new(),
+                };
+                let exception = self.prep_reraise_star(&caught, raised)?;
+                frame.push(exception);
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Loads, stores and deletes names.
+    fn name_op(&mut self, frame: &mut Frame, instruction: Instruction) -> PyResult<()> {
+        match instruction {
+            Instruction::LoadName(index) => {
+                let name = frame.name(index);
+                let local = frame
+                    .locals
+                    .as_ref()
+                    .and_then(|locals| locals.as_dict().unwrap().borrow().get_str(name));
+                let value = match local {
+                    Some(value) => value,
+                    None => self.load_global(&frame.globals, name)?,
+                };
+                frame.push(value);
+            }
+            Instruction::StoreName(index) => {
+                let value = frame.pop();
+                let locals = frame
+                    .locals
+                    .clone()
+                    .unwrap_or_else(|| frame.globals.clone());
+                self.dict_set_str(locals.as_dict().unwrap(), frame.name(index), value);
+            }
+            Instruction::DeleteName(index) => {
+                let locals = frame
+                    .locals
+                    .clone()
+                    .unwrap_or_else(|| frame.globals.clone());
+                self.delete_name(&locals, frame.name(index))?;
+            }
+            Instruction::LoadGlobal(index) => {
+                let value = self.load_global_cached(
+                    &frame.caches,
+                    frame.lasti,
+                    &frame.globals,
+                    frame.name(index),
+                )?;
+                frame.push(value);
+            }
+            Instruction::StoreGlobal(index) => {
+                let value = frame.pop();
+                let globals = frame.globals.clone();
+                self.dict_set_str(globals.as_dict().unwrap(), frame.name(index), value);
+            }
+            Instruction::DeleteGlobal(index) => {
+                let globals = frame.globals.clone();
+                self.delete_name(&globals, frame.name(index))?;
+            }
+            Instruction::LoadFast(index) => match frame.fast[index] {
+                Some(ref value) => {
+                    let value = value.clone();
+                    frame.push(value);
+                }
+                None => return Err(self.unbound_local(&frame.code.varnames[index])),
+            },
+            Instruction::StoreFast(index) => {
+                frame.fast[index] = Some(frame.pop());
+            }
+            Instruction::DeleteFast(index) => {
+                if frame.fast[index].take().is_none() {
+                    return Err(self.unbound_local(&frame.code.varnames[index]));
+                }
+            }
+            Instruction::LoadClosure(index) => {
+                let cell = frame.cells[index].clone();
+                frame.push(cell);
+            }
+            Instruction::LoadDeref(index) => {
+                let value = match frame.cells[index].payload {
+                    Payload::Cell(ref value) => value.borrow().clone(),
+                    _ => None,
+                };
+                match value {
+                    Some(value) => frame.push(value),
+                    None => return Err(self.unbound_cell(frame, index)),
+                }
+            }
+            Instruction::StoreDeref(index) => {
+                let value = frame.pop();
+                if let Payload::Cell(ref cell) = frame.cells[index].payload {
+                    *cell.borrow_mut() = Some(value);
+                }
+            }
+            Instruction::DeleteDeref(index) => {
+                let value = match frame.cells[index].payload {
+                    Payload::Cell(ref cell) => cell.borrow_mut().take(),
+                    _ => None,
+                };
+                if value.is_none() {
+                    return Err(self.unbound_cell(frame, index));
+                }
+            }
+            Instruction::LoadClassDeref(index) => {
+                let name = frame.code.cell_name(index).unwrap_or_default();
+                let local = frame
+                    .locals
+                    .as_ref()
+                    .and_then(|locals| locals.as_dict().unwrap().borrow().get_str(name));
+                let value = local.or_else(|| match frame.cells[index].payload {
+                    Payload::Cell(ref value) => value.borrow().clone(),
+                    _ => None,
+                });
+                match value {
+                    Some(value) => frame.push(value),
+                    None => return Err(self.unbound_cell(frame, index)),
+                }
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Attribute and subscript access, and the operators.
+    fn operator(&mut self, frame: &mut Frame, instruction: Instruction) -> PyResult<()> {
+        match instruction {
+            Instruction::LoadAttr(index) => {
+                let object = frame.pop();
+                let value =
+                    self.load_attr_cached(&frame.caches, frame.lasti, &object, frame.name(index))?;
+                frame.push(value);
+            }
+            Instruction::StoreAttr(index) => {
+                let object = frame.pop();
+                let value = frame.pop();
+                self.setattr(&object, frame.name(index), value)?;
+            }
+            Instruction::DeleteAttr(index) => {
+                let object = frame.pop();
+                self.delattr(&object, frame.name(index))?;
+            }
+            Instruction::BinarySubscr => {
+                let key = frame.pop();
+                let object = frame.pop();
+                let value = self.getitem(&object, &key)?;
+                frame.push(value);
+            }
+            Instruction::StoreSubscr => {
+                let key = frame.pop();
+                let object = frame.pop();
+                let value = frame.pop();
+                self.setitem(&object, &key, value)?;
+            }
+            Instruction::DeleteSubscr => {
+                let key = frame.pop();
+                let object = frame.pop();
+                self.delitem(&object, &key)?;
+            }
+            Instruction::BinaryOp(op) | Instruction::InplaceOp(op) => {
+                let b = frame.pop();
+                let a = frame.pop();
+                let inplace = matches!(instruction, Instruction::InplaceOp(_));
+                let value = self.binary_op(&a, op, &b, inplace)?;
+                frame.push(value);
+            }
+            Instruction::UnaryOp(op) => {
+                let operand = frame.pop();
+                let value = self.unary_op(op, &operand)?;
+                frame.push(value);
+            }
+            Instruction::CompareOp(op) => {
+                let b = frame.pop();
+                let a = frame.pop();
+                let value = self.compare(&a, op, &b)?;
+                frame.push(value);
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Builds, extends, unpacks and formats values.
+    fn build(&mut self, frame: &mut Frame, instruction: Instruction) -> PyResult<()> {
+        match instruction {
+            Instruction::BuildTuple(count) => {
+                let items = frame.pop_n(count);
+                frame.push(self.new_tuple(items));
+            }
+            Instruction::BuildList(count) => {
+                let items = frame.pop_n(count);
+                frame.push(self.new_list(items));
+            }
+            Instruction::BuildSet(count) => {
+                let items = frame.pop_n(count);
+                let set = self.new_set(false);
+                for item in items {
+                    self.set_add(set_members(&set), item)?;
+                }
+                frame.push(set);
+            }
+            Instruction::BuildMap(count) => {
+                let items = frame.pop_n(count * 2);
+                let dict = self.new_dict();
+                for pair in items.chunks(2) {
+                    self.dict_set(dict.as_dict().unwrap(), pair[0].clone(), pair[1].clone())?;
+                }
+                frame.push(dict);
+            }
+            Instruction::BuildConstKeyMap(count) => {
+                let keys = frame.pop();
+                let values = frame.pop_n(count);
+                let dict = self.new_dict();
+                if let Payload::Tuple(ref keys) = keys.payload {
+                    for (key, value) in keys.iter().zip(values) {
+                        self.dict_set(dict.as_dict().unwrap(), key.clone(), value)?;
+                    }
+                }
+                frame.push(dict);
+            }
+            Instruction::BuildSlice(count) => {
+                let step = if count == 3 { frame.pop() } else { self.none() };
+                let stop = frame.pop();
+                let start = frame.pop();
+                let slice = self.new_slice(start, stop, step);
+                frame.push(slice);
+            }
+            Instruction::BuildString(count) => {
+                let parts = frame.pop_n(count);
+                let text: String = parts.iter().filter_map(|part| part.as_str()).collect();
+                frame.push(self.new_str(&text));
+            }
+            Instruction::ListExtend(depth) => {
+                let iterable = frame.pop();
+                let list = frame.peek(depth).clone();
+                let items = match self.iterate(&iterable) {
+                    Ok(items) => items,
+                    Err(_) if !self.is_iterable(&iterable) => {
+                        let message = format!(
+                            "Value after * must be an iterable, not {}",
+                            iterable.type_name()
+                        );
+                        return Err(self.new_type_error(message));
+                    }
+                    Err(err) => return Err(err),
+                };
+                if let Payload::List(ref list) = list.payload {
+                    list.borrow_mut().extend(items);
+                }
+            }
+            Instruction::SetUpdate(depth) => {
+                let iterable = frame.pop();
+                let set = frame.peek(depth).clone();
+                for item in self.iterate(&iterable)? {
+                    self.set_add(set_members(&set), item)?;
+                }
+            }
+            Instruction::DictUpdate(depth) | Instruction::DictMerge(depth) => {
+                let source = frame.pop();
+                let dict = frame.peek(depth).clone();
+                let merge = matches!(instruction, Instruction::DictMerge(_));
+                let source = match source.as_dict() {
+                    Some(source) => source,
+                    None => {
+                        let message = if merge {
+                            let function = frame.peek(depth + 2).clone();
+                            format!(
+                                "{} argument after ** must be a mapping, not {}",
+                                self.callable_name(&function),
+                                source.type_name()
+                            )
+                        } else {
+                            format!("'{}' object is not a mapping", source.type_name())
+                        };
+                        return Err(self.new_type_error(message));
+                    }
+                };
+                if merge {
+                    let keys: Vec<ObjectRef> = source
+                        .borrow()
+                        .iter()
+                        .map(|entry| entry.key.clone())
+                        .collect();
+                    for key in keys {
+                        if self.dict_contains(dict.as_dict().unwrap(), &key)? {
+                            let function = frame.peek(depth + 2).clone();
+                            let message = format!(
+                                "{} got multiple values for keyword argument {}",
+                                self.callable_name(&function),
+                                self.repr(&key)?
+                            );
+                            return Err(self.new_type_error(message));
+                        }
+                    }
+                }
+                self.dict_extend(dict.as_dict().unwrap(), source)?;
+            }
+            Instruction::ListToTuple => {
+                let list = frame.pop();
+                let items = self.iterate(&list)?;
+                frame.push(self.new_tuple(items));
+            }
+            Instruction::ListAppend(depth) => {
+                let item = frame.pop();
+                if let Payload::List(ref list) = frame.peek(depth).payload {
+                    list.borrow_mut().push(item);
+                }
+            }
+            Instruction::SetAdd(depth) => {
+                let item = frame.pop();
+                let set = frame.peek(depth).clone();
+                self.set_add(set_members(&set), item)?;
+            }
+            Instruction::MapAdd(depth) => {
+                let value = frame.pop();
+                let key = frame.pop();
+                let dict = frame.peek(depth).clone();
+                self.dict_set(dict.as_dict().unwrap(), key, value)?;
+            }
+            Instruction::UnpackSequence(count) => {
+                let items = self.unpack(frame.pop(), count, None)?;
+                frame.stack.extend(items.into_iter().rev());
+            }
+            Instruction::UnpackEx { before, after } => {
+                let items = self.unpack(frame.pop(), before + after, Some(before))?;
+                frame.stack.extend(items.into_iter().rev());
+            }
+            Instruction::FormatValue {
+                conversion,
+                has_spec,
+            } => {
+                let spec = if has_spec { Some(frame.pop()) } else { None };
+                let value = frame.pop();
+                let text = self.format_value(&value, conversion, spec.as_ref())?;
+                frame.push(text);
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Calls, and makes functions.
+    fn call_op(&mut self, frame: &mut Frame, instruction: Instruction) -> PyResult<()> {
+        self.frames.last_mut().unwrap().lasti = frame.lasti;
+        match instruction {
+            Instruction::CallFunction(count) => {
+                let positional = frame.pop_n(count);
+                let function = frame.pop();
+                let result = if count == 0 && Vm::is(&function, &self.types.super_) {
+                    self.zero_argument_super(frame)?
+                } else if let Some(name) = self.locals_function(&function, count) {
+                    let locals = frame.locals_dict(self);
+                    builtins::call_with_locals(self, name, locals)?
+                } else {
+                    self.call(&function, Args::new(positional))?
+                };
+                frame.push(result);
+            }
+            Instruction::CallFunctionKw(count) => {
+                let names = frame.pop();
+                let mut positional = frame.pop_n(count);
+                let function = frame.pop();
+                let names = match names.payload {
+                    Payload::Tuple(ref names) => names.clone(),
+                    _ => Vec::new(),
+                };
+                let values = positional.split_off(count - names.len());
+                let keywords = names
+                    .iter()
+                    .map(|name| name.as_str().unwrap_or_default().to_string())
+                    .zip(values)
+                    .collect();
+                let result = self.call(
+                    &function,
+                    Args {
+                        positional,
+                        keywords,
+                    },
+                )?;
+                frame.push(result);
+            }
+            Instruction::CallFunctionEx(has_keywords) => {
+                let keywords = if has_keywords {
+                    Some(frame.pop())
+                } else {
+                    None
+                };
+                let positional = frame.pop();
+                let function = frame.pop();
+                if !self.is_iterable(&positional) {
+                    let message = format!(
+                        "{} argument after * must be an iterable, not {}",
+                        self.callable_name(&function),
+                        positional.type_name()
+                    );
+                    return Err(self.new_type_error(message));
+                }
+                let positional = self.iterate(&positional)?;
+                let mut args = Args::new(positional);
+                if let Some(keywords) = keywords {
+                    for entry in keywords.as_dict().unwrap().borrow().iter() {
+                        match entry.key.as_str() {
+                            Some(name) => {
+                                args.keywords.push((name.to_string(), entry.value.clone()))
+                            }
+                            None => {
+                                let message = "keywords must be strings".to_string();
+                                return Err(self.new_type_error(message));
+                            }
+                        }
+                    }
+                }
+                let result = self.call(&function, args)?;
+                frame.push(result);
+            }
+            Instruction::MakeFunction(flags) => {
+                let qualname = frame.pop();
+                let code = frame.pop();
+                let closure = if flags & MAKE_CLOSURE != 0 {
+                    Some(frame.pop())
+                } else {
+                    None
+                };
+                // The annotations come as a tuple of names and values.
+                let annotations = if flags & MAKE_ANNOTATIONS != 0 {
+                    let pairs = frame.pop();
+                    let annotations = self.new_dict();
+                    if let Payload::Tuple(ref items) = pairs.payload {
+                        for pair in items.chunks(2) {
+                            let name = pair[0].as_str().unwrap_or_default();
+                            self.dict_set_str(
+                                annotations.as_dict().unwrap(),
+                                name,
+                                pair[1].clone(),
+                            );
+                        }
+                    }
+                    Some(annotations)
+                } else {
+                    None
+                };
+                let kwdefaults = if flags & MAKE_KWDEFAULTS != 0 {
+                    Some(frame.pop())
+                } else {
+                    None
+                };
+                let defaults = if flags & MAKE_DEFAULTS != 0 {
+                    Some(frame.pop())
+                } else {
+                    None
+                };
+                let code = match code.payload {
+                    Payload::Code(ref code) => code.clone(),
+                    _ => unreachable!("MakeFunction without a code object"),
+                };
+                let module = frame
+                    .globals
+                    .as_dict()
+                    .unwrap()
+                    .borrow()
+                    .get_str("__name__")
+                    .unwrap_or_else(|| self.none());
+                let constants = self.constants(&code)?;
+                let doc = match constants.first() {
+                    Some(doc) if doc.as_str().is_some() => doc.clone(),
+                    _ => self.none(),
+                };
+                let function = Function {
+                    constants,
+                    caches: self.inline_caches(&code),
+                    globals: frame.globals.clone(),
+                    name: ::std::cell::RefCell::new(code.name.clone()),
+                    qualname: ::std::cell::RefCell::new(
+                        qualname.as_str().unwrap_or(&code.qualname).to_string(),
+                    ),
+                    code,
+                    defaults: ::std::cell::RefCell::new(defaults),
+                    kwdefaults: ::std::cell::RefCell::new(kwdefaults),
+                    annotations: ::std::cell::RefCell::new(annotations),
+                    closure,
+                    doc: ::std::cell::RefCell::new(doc),
+                    module: ::std::cell::RefCell::new(module),
+                };
+                let dict = self.new_dict();
+                let function = PyObject::new(
+                    Payload::Function(function),
+                    self.types.function.clone(),
+                    Some(dict),
+                );
+                frame.push(function);
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Enters a context manager, and calls its `__exit__` for an exception.
+    /// An asynchronous one is entered in two steps, around the `await` of
+    /// what its `__aenter__` returns.
+    fn with_op(&mut self, frame: &mut Frame, instruction: Instruction) -> PyResult<()> {
+        match instruction {
+            Instruction::SetupWith(handler) => {
+                let manager = frame.pop();
+                let enter = self.special_method(&manager, "__enter__");
+                let exit = self.special_method(&manager, "__exit__");
+                let (enter, exit) = match (enter, exit) {
+                    (Some(enter), Some(exit)) => (enter, exit),
+                    (enter, _) => {
+                        let message = format!(
+                            "'{}' object does not support the context manager protocol{}",
+                            manager.type_name(),
+                            if enter.is_some() {
+                                " (missed __exit__ method)"
+                            } else {
+                                ""
+                            }
+                        );
+                        return Err(self.new_type_error(message));
+                    }
+                };
+                frame.push(exit);
+                let value = self.call(&enter, Args::default())?;
+                let level = frame.stack.len();
+                frame.blocks.push(Block::Finally { handler, level });
+                frame.push(value);
+            }
+            Instruction::BeforeAsyncWith => {
+                let manager = frame.pop();
+                let enter = self.special_method(&manager, "__aenter__");
+                let exit = self.special_method(&manager, "__aexit__");
+                let (enter, exit) = match (enter, exit) {
+                    (Some(enter), Some(exit)) => (enter, exit),
+                    (enter, _) => {
+                        let message = format!(
+                            "'{}' object does not support the asynchronous context manager \
+                             protocol{}",
+                            manager.type_name(),
+                            if enter.is_some() {
+                                " (missed __aexit__ method)"
+                            } else {
+                                ""
+                            }
+                        );
+                        return Err(self.new_type_error(message));
+                    }
+                };
+                frame.push(exit);
+                let awaitable = self.call(&enter, Args::default())?;
+                frame.push(awaitable);
+            }
+            Instruction::SetupAsyncWith(handler) => {
+                let level = frame.stack.len() - 1;
+                frame.blocks.push(Block::Finally { handler, level });
+            }
+            Instruction::WithExceptStart => {
+                let exception = frame.top().clone();
+                let exit = frame.peek(3).clone();
+                let traceback = self
+                    .exception_attr(&exception, "__traceback__")
+                    .unwrap_or_else(|| self.none());
+                let args = Args::new(vec![exception.class(), exception, traceback]);
+                let result = self.call(&exit, args)?;
+                frame.push(result);
+            }
+            _ => unreachable!(),
+        }
+        Ok(())
+    }
+
+    /// Looks `name` up in `globals`, then in the builtins. Code run by
+    /// `exec` or `eval` may bring its own builtins as a dict in the
+    /// `__builtins__` of its globals.
+    fn load_global(&mut self, globals: &ObjectRef, name: &str) -> PyResult {
+        let globals = globals.as_dict().unwrap().borrow();
+        let value = globals.get_str(name).or_else(|| {
+            let builtins = globals
+                .get_str("__builtins__")
+                .filter(|builtins| builtins.as_dict().is_some())
+                .unwrap_or_else(|| self.builtins.clone());
+            let value = builtins.as_dict().unwrap().borrow().get_str(name);
+            value
+        });
+        drop(globals);
+        match value {
+            Some(value) => Ok(value),
+            None => Err(self.new_name_error(format!("name '{}' is not defined", name))),
+        }
+    }
+
+    fn delete_name(&mut self, namespace: &ObjectRef, name: &str) -> PyResult<()> {
+        let key = self.new_str(name);
+        match self.dict_remove(namespace.as_dict().unwrap(), &key)? {
+            Some(_) => Ok(()),
+            None => Err(self.new_name_error(format!("name '{}' is not defined", name))),
+        }
+    }
+
+    /// The name of `function` if it is `locals`, `vars` or `dir` called
+    /// with no arguments, which answer from the locals of the frame.
+    fn locals_function(&self, function: &ObjectRef, count: usize) -> Option<&'static str> {
+        if count != 0
+            || !self
+                .locals_functions
+                .iter()
+                .any(|known| Vm::is(known, function))
+        {
+            return None;
+        }
+        match function.payload {
+            Payload::Builtin(ref builtin) => Some(builtin.name),
+            _ => None,
+        }
+    }
+
+    /// `super()` in a method, which takes the class from the `__class__`
+    /// cell of the method and the instance from its first argument.
+    fn zero_argument_super(&mut self, frame: &Frame) -> PyResult {
+        let code = frame.code.clone();
+        if code.argcount == 0 {
+            return Err(self.new_runtime_error("super(): no arguments".to_string()));
+        }
+        // The first argument is in a cell if a nested function closes over
+        // it.
+        let object = frame.fast[0].clone().or_else(|| {
+            let index = code
+                .cellvars
+                .iter()
+                .position(|name| *name == code.varnames[0])?;
+            match frame.cells[index].payload {
+                Payload::Cell(ref value) => value.borrow().clone(),
+                _ => None,
+            }
+        });
+        let object = match object {
+            Some(object) => object,
+            None => return Err(self.new_runtime_error("super(): arg[0] deleted".to_string())),
+        };
+        let index = match code.freevars.iter().position(|name| name == "__class__") {
+            Some(index) => code.cellvars.len() + index,
+            None => {
+                let message = "super(): __class__ cell not found".to_string();
+                return Err(self.new_runtime_error(message));
+            }
+        };
+        let class = match frame.cells[index].payload {
+            Payload::Cell(ref value) => value.borrow().clone(),
+            _ => None,
+        };
+        match class {
+            Some(ref class) if class.as_type().is_some() => self.new_super(class, &object),
+            Some(class) => {
+                let message = format!("super(): __class__ is not a type ({})", class.type_name());
+                Err(self.new_runtime_error(message))
+            }
+            None => Err(self.new_runtime_error("super(): empty __class__ cell".to_string())),
+        }
+    }
+
+    /// The error for using the empty cell at `index`: an `UnboundLocalError`
+    /// for a cell variable, and a `NameError` for a free one.
+    fn unbound_cell(&mut self, frame: &Frame, index: usize) -> ObjectRef {
+        let name = frame.code.cell_name(index).unwrap_or_default();
+        if index < frame.code.cellvars.len() {
+            return self.unbound_local(name);
+        }
+        let message = format!(
+            "cannot access free variable '{}' where it is not associated with a value in \
+             enclosing scope",
+            name
+        );
+        self.new_name_error(message)
+    }
+
+    fn unbound_local(&mut self, name: &str) -> ObjectRef {
+        let class = self.exceptions.unbound_local_error.clone();
+        let message = format!(
+            "cannot access local variable '{}' where it is not associated with a value",
+            name
+        );
+        self.new_error(&class, message)
+    }
+
+    /// The name of a callable as argument errors give it: its qualified
+    /// name after its module's, such as `__main__.f()`, or else its `str`.
+    fn callable_name(&mut self, callable: &ObjectRef) -> String {
+        let (qualname, module) = match callable.payload {
+            Payload::Function(ref function) => (
+                function.qualname.borrow().clone(),
+                function
+                    .globals
+                    .as_dict()
+                    .unwrap()
+                    .borrow()
+                    .get_str("__name__"),
+            ),
+            Payload::Builtin(ref builtin) => return format!("{}()", builtin.name),
+            Payload::Method { ref function, .. } => return self.callable_name(&function.clone()),
+            Payload::Type(ref data) => (
+                data.qualname.clone(),
+                callable
+                    .dict()
+                    .and_then(|dict| dict.as_dict()?.borrow().get_str("__module__")),
+            ),
+            _ => {
+                return self
+                    .str(callable)
+                    .unwrap_or_else(|_| format!("{} object", callable.type_name()))
+            }
+        };
+        match module.as_ref().and_then(|module| module.as_str()) {
+            Some(module) if module != "builtins" => format!("{}.{}()", module, qualname),
+            _ => format!("{}()", qualname),
+        }
+    }
+
+    /// The items of `value` for an unpacking assignment of `count` targets,
+    /// in order. With a starred target after `star` of them, the items it
+    /// takes are gathered into a list in its place.
+    fn unpack(
+        &mut self,
+        value: ObjectRef,
+        count: usize,
+        star: Option<usize>,
+    ) -> PyResult<Vec<ObjectRef>> {
+        if !self.is_iterable(&value) {
+            let message = format!("cannot unpack non-iterable {} object", value.type_name());
+            return Err(self.new_type_error(message));
+        }
+        let mut items = self.iterate(&value)?;
+        let length = items.len();
+        match star {
+            None if length == count => Ok(items),
+            None if length < count => {
+                let message = format!(
+                    "not enough values to unpack (expected {}, got {})",
+                    count, length
+                );
+                Err(self.new_value_error(message))
+            }
+            None => {
+                let message = format!("too many values to unpack (expected {})", count);
+                Err(self.new_value_error(message))
+            }
+            Some(before) if length >= count => {
+                let after = count - before;
+                let rest: Vec<ObjectRef> = items.drain(before..length - after).collect();
+                items.insert(before, self.new_list(rest));
+                Ok(items)
+            }
+            Some(_) => {
+                let message = format!(
+                    "not enough values to unpack (expected at least {}, got {})",
+                    count, length
+                );
+                Err(self.new_value_error(message))
+            }
+        }
+    }
+
+    /// `raise`, `raise exc` or `raise exc from cause`.
+    fn raise(&mut self, frame: &mut Frame, count: usize) -> PyResult<Flow> {
+        if count == 0 {
+            return match self.exc_info.clone() {
+                Some(exception) => Ok(Flow::Reraise(exception)),
+                None => Err(self.new_runtime_error("No active exception to reraise".to_string())),
+            };
+        }
+        let cause = if count == 2 { Some(frame.pop()) } else { None };
+        let exception = frame.pop();
+        let exception = self.make_exception(&exception)?;
+        if let Some(cause) = cause {
+            let cause = if Vm::is(&cause, &self.none) {
+                None
+            } else if self.is_exception_class(&cause) || cause.as_exception().is_some() {
+                Some(self.make_exception(&cause)?)
+            } else {
+                let message = "exception causes must derive from BaseException".to_string();
+                return Err(self.new_type_error(message));
+            };
+            let mut data = exception.as_exception().unwrap().borrow_mut();
+            data.cause = cause;
+            data.suppress_context = true;
+        }
+        self.set_context(&exception);
+        Err(exception)
+    }
+
+    /// Whether `exception` matches the class or tuple of classes of an
+    /// `except` clause.
+    pub(super) fn exception_matches(
+        &mut self,
+        exception: &ObjectRef,
+        class: &ObjectRef,
+    ) -> PyResult<bool> {
+        let classes = match class.payload {
+            Payload::Tuple(ref classes) => classes.clone(),
+            _ => vec![class.clone()],
+        };
+        if !classes.iter().all(|class| self.is_exception_class(class)) {
+            let message = "catching classes that do not inherit from BaseException is not allowed";
+            return Err(self.new_type_error(message.to_string()));
+        }
+        Ok(classes
+            .iter()
+            .any(|class| Vm::is_instance(exception, class)))
+    }
+
+    /// A frame to run the code of `function` in, with new cells for its
+    /// cell variables and those of its closure for its free variables.
+    pub(super) fn function_frame(
+        &mut self,
+        function: &Function,
+        locals: Option<ObjectRef>,
+    ) -> Frame {
+        let mut frame = Frame::new(
+            function.code.clone(),
+            function.constants.clone(),
+            function.caches.clone(),
+            function.globals.clone(),
+            locals,
+        );
+        frame.cells = function
+            .code
+            .cellvars
+            .iter()
+            .map(|_| self.new_cell(None))
+            .collect();
+        if let Some(ref closure) = function.closure {
+            if let Payload::Tuple(ref cells) = closure.payload {
+                frame.cells.extend(cells.iter().cloned());
+            }
+        }
+        frame
+    }
+
+    /// A frame for a call to `function`, with its parameters bound to
+    /// `args` as CPython binds them.
+    pub(super) fn bind_arguments(&mut self, function: &Function, args: Args) -> PyResult<Frame> {
+        let code = function.code.clone();
+        let mut frame = self.function_frame(function, None);
+        let qualname = function.qualname.borrow().clone();
+        let argcount = code.argcount;
+        let kwonlycount = code.kwonlyargcount;
+        let mut slot = argcount + kwonlycount;
+        let varargs = if code.flags & CO_VARARGS != 0 {
+            slot += 1;
+            Some(slot - 1)
+        } else {
+            None
+        };
+        let varkeywords = if code.flags & CO_VARKEYWORDS != 0 {
+            Some(slot)
+        } else {
+            None
+        };
+
+        let Args {
+            mut positional,
+            keywords,
+        } = args;
+        let given = positional.len();
+        let extra = if given > argcount {
+            positional.split_off(argcount)
+        } else {
+            Vec::new()
+        };
+        for (index, value) in positional.into_iter().enumerate() {
+            frame.fast[index] = Some(value);
+        }
+        if let Some(slot) = varargs {
+            frame.fast[slot] = Some(self.new_tuple(extra.clone()));
+        }
+
+        let kwargs = varkeywords.map(|_| self.new_dict());
+        let mut posonly_passed = Vec::new();
+        for (name, value) in keywords {
+            let position = code.varnames[code.posonlyargcount..argcount + kwonlycount]
+                .iter()
+                .position(|varname| *varname == name)
+                .map(|index| index + code.posonlyargcount);
+            match position {
+                Some(index) if frame.fast[index].is_some() => {
+                    let message =
+                        format!("{}() got multiple values for argument '{}'", qualname, name);
+                    return Err(self.new_type_error(message));
+                }
+                Some(index) => frame.fast[index] = Some(value),
+                None => match kwargs {
+                    Some(ref kwargs) => self.dict_set_str(kwargs.as_dict().unwrap(), &name, value),
+                    None if code.varnames[..code.posonlyargcount].contains(&name) => {
+                        posonly_passed.push(name)
+                    }
+                    None => {
+                        let message = format!(
+                            "{}() got an unexpected keyword argument '{}'",
+                            qualname, name
+                        );
+                        return Err(self.new_type_error(message));
+                    }
+                },
+            }
+        }
+        if !posonly_passed.is_empty() {
+            let message = format!(
+                "{}() got some positional-only arguments passed as keyword arguments: '{}'",
+                qualname,
+                posonly_passed.join(", ")
+            );
+            return Err(self.new_type_error(message));
+        }
+        if let (Some(slot), Some(kwargs)) = (varkeywords, kwargs) {
+            frame.fast[slot] = Some(kwargs);
+        }
+
+        let defaults = match *function.defaults.borrow() {
+            Some(ref defaults) => match defaults.payload {
+                Payload::Tuple(ref items) => items.clone(),
+                _ => Vec::new(),
+            },
+            None => Vec::new(),
+        };
+        if given > argcount && varargs.is_none() {
+            let kwonly_given = frame.fast[argcount..argcount + kwonlycount]
+                .iter()
+                .filter(|value| value.is_some())
+                .count();
+            let message =
+                too_many_positional(&qualname, argcount, defaults.len(), given, kwonly_given);
+            return Err(self.new_type_error(message));
+        }
+
+        let first_default = argcount - defaults.len().min(argcount);
+        let mut missing = Vec::new();
+        for index in 0..argcount {
+            if frame.fast[index].is_none() {
+                if index >= first_default {
+                    frame.fast[index] = Some(defaults[index - first_default].clone());
+                } else {
+                    missing.push(code.varnames[index].clone());
+                }
+            }
+        }
+        if !missing.is_empty() {
+            return Err(self.missing_arguments(&qualname, "positional", &missing));
+        }
+
+        for index in argcount..argcount + kwonlycount {
+            if frame.fast[index].is_some() {
+                continue;
+            }
+            let name = &code.varnames[index];
+            let default = function
+                .kwdefaults
+                .borrow()
+                .as_ref()
+                .and_then(|kwdefaults| kwdefaults.as_dict()?.borrow().get_str(name));
+            match default {
+                Some(default) => frame.fast[index] = Some(default),
+                None => missing.push(name.clone()),
+            }
+        }
+        if !missing.is_empty() {
+            return Err(self.missing_arguments(&qualname, "keyword-only", &missing));
+        }
+        frame.fill_argument_cells();
+        Ok(frame)
+    }
+
+    fn missing_arguments(&mut self, qualname: &str, kind: &str, names: &[String]) -> ObjectRef {
+        let quoted: Vec<String> = names.iter().map(|name| format!("'{}'", name)).collect();
+        let list = match quoted.len() {
+            1 => quoted[0].clone(),
+            2 => format!("{} and {}", quoted[0], quoted[1]),
+            n => format!("{}, and {}", quoted[..n - 1].join(", "), quoted[n - 1]),
+        };
+        let message = format!(
+            "{}() missing {} required {} argument{}: {}",
+            qualname,
+            names.len(),
+            kind,
+            if names.len() == 1 { "" } else { "s" },
+            list
+        );
+        self.new_type_error(message)
+    }
+}
+
+/// The message for a call with more positional arguments than parameters.
+fn too_many_positional(
+    qualname: &str,
+    argcount: usize,
+    defcount: usize,
+    given: usize,
+    kwonly_given: usize,
+) -> String {
+    let (signature, plural) = if defcount > 0 {
+        (
+            format!("from {} to {}", argcount - defcount, argcount),
+            true,
+        )
+    } else {
+        (argcount.to_string(), argcount != 1)
+    };
+    let kwonly = if kwonly_given > 0 {
+        format!(
+            " positional argument{} (and {} keyword-only argument{})",
+            if given == 1 { "" } else { "s" },
+            kwonly_given,
+            if kwonly_given == 1 { "" } else { "s" }
+        )
+    } else {
+        String::new()
+    };
+    format!(
+        "{}() takes {} positional argument{} but {}{} {} given",
+        qualname,
+        signature,
+        if plural { "s" } else { "" },
+        given,
+        kwonly,
+        if given == 1 && kwonly_given == 0 {
+            "was"
+        } else {
+            "were"
+        }
+    )
+}
+
+fn set_members(set: &ObjectRef) -> &::std::cell::RefCell<super::Dict> {
+    match set.payload {
+        Payload::Set(ref members) => members,
+        _ => unreachable!(),
+    }
+}
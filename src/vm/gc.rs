@@ -0,0 +1,716 @@
+//! The cycle collector, after CPython's `gc` module.
+//!
+//! Objects are freed by reference counting as soon as the last reference
+//! to them goes, but objects that refer to one another in a cycle keep
+//! each other alive. Every object that can refer to others is tracked as
+//! it is created, and a collection finds those among the objects it looks
+//! at that only the others refer to: for each one, the references from
+//! the others are subtracted from its reference count, and what is left
+//! counts references from elsewhere, from Rust code, frames, the
+//! interpreter's roots and objects not looked at. The objects with such
+//! references, and all they refer to, are alive; the rest are garbage, and
+//! their cycles are broken by clearing their containers, after which
+//! reference counting frees them.
+//!
+//! There are two generations, young and old. Most objects are freed young,
+//! and a collection of the young ones runs, when a frame starts or a loop
+//! jumps back, once more of them are alive than the first threshold;
+//! those that survive it become old. Every few young collections, as many
+//! as the second threshold, the old ones are looked at too, if they have
+//! grown by a quarter since they last were, as CPython's full collections
+//! wait for. `gc.collect()` looks at all of them. An object whose contents
+//! are borrowed by running code is not looked into, which only keeps more
+//! objects alive.
+//!
+//! An instance of a class with `__del__` that reference counting frees is
+//! moved into a new object, whose `__del__` is called once the running
+//! instruction is done; it is freed afterwards unless the method kept a
+//! reference to it. The garbage a collection finds has `__del__` called
+//! on it before any of it is cleared, and is collected again, so that what
+//! a finalizer resurrected stays alive. `__del__` is called once for each
+//! object, and an exception it raises is reported on `sys.stderr`, as in
+//! CPython. Unlike CPython, `id(self)` in `__del__` is not the id the
+//! object had when reference counting freed it, and objects left when the
+//! interpreter is dropped are not finalized.
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+use std::rc::{Rc, Weak};
+
+use super::modules::sys::write_standard;
+use super::object::{object_id, Args, IteratorState, ObjectRef, Payload, PyObject};
+use super::Vm;
+
+/// The number of young objects alive that starts a collection when none
+/// is set, as in CPython.
+const DEFAULT_THRESHOLD: usize = 700;
+
+/// The number of young collections between full ones when none is set.
+const DEFAULT_FULL_THRESHOLD: usize = 10;
+
+/// Marks the slot of an old object, to tell it from that of a young one.
+const OLD: usize = 1 << (usize::BITS - 1);
+
+thread_local! {
+    /// Every tracked object that is alive. Each object knows its slot here,
+    /// and leaves it when it is freed.
+    static TRACKED: RefCell<Registry> = const {
+        RefCell::new(Registry {
+            young: Vec::new(),
+            old: Vec::new(),
+        })
+    };
+    /// Whether a class with `__del__` has been made, so that objects being
+    /// freed are looked at for it.
+    static FINALIZERS: Cell<bool> = const { Cell::new(false) };
+    /// The ids of the objects alive whose `__del__` has been called.
+    static FINALIZED: RefCell<HashSet<usize>> = RefCell::new(HashSet::new());
+    /// The objects freed by reference counting whose `__del__` is still to
+    /// be called, each moved into a new object.
+    static UNFINALIZED: RefCell<Vec<ObjectRef>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Registry {
+    young: Vec<Weak<PyObject>>,
+    old: Vec<Weak<PyObject>>,
+}
+
+impl Registry {
+    fn generation(&mut self, slot: usize) -> (&mut Vec<Weak<PyObject>>, usize) {
+        if slot & OLD != 0 {
+            (&mut self.old, OLD)
+        } else {
+            (&mut self.young, 0)
+        }
+    }
+}
+
+/// The state of the collector of a VM.
+pub(super) struct Collector {
+    enabled: bool,
+    /// The number of young objects alive that starts a collection, or 0 for
+    /// none.
+    threshold: usize,
+    /// The number of young collections between full ones.
+    full_threshold: usize,
+    /// The third threshold of `gc.set_threshold`, kept for
+    /// `gc.get_threshold` only.
+    last_threshold: usize,
+    /// The young collections since the last full one.
+    young_collections: usize,
+    /// The old objects after the last full collection.
+    old_survivors: usize,
+    /// The collections made for each generation `gc.collect` was asked
+    /// for, young ones being of generation 0 and full ones of generation 2.
+    stats: [GenerationStats; 3],
+    /// Whether a collection is running.
+    collecting: bool,
+}
+
+impl Default for Collector {
+    fn default() -> Collector {
+        Collector {
+            enabled: true,
+            threshold: DEFAULT_THRESHOLD,
+            full_threshold: DEFAULT_FULL_THRESHOLD,
+            last_threshold: DEFAULT_FULL_THRESHOLD,
+            young_collections: 0,
+            old_survivors: 0,
+            stats: Default::default(),
+            collecting: false,
+        }
+    }
+}
+
+/// What `gc.get_stats` reports for a generation.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct GenerationStats {
+    pub collections: usize,
+    pub collected: usize,
+    pub uncollectable: usize,
+}
+
+/// Tracks a new object, if it may refer to other objects.
+pub(super) fn track(object: &ObjectRef) {
+    if !is_container(object) {
+        return;
+    }
+    TRACKED.with(|tracked| {
+        let mut tracked = tracked.borrow_mut();
+        object.gc_slot().set(tracked.young.len());
+        tracked.young.push(Rc::downgrade(object));
+    });
+}
+
+/// Stops tracking an object being freed, which was in `slot`. The last
+/// object of its generation takes its slot.
+pub(super) fn untrack(slot: usize) {
+    // Objects freed as the thread ends may outlive the registry.
+    let _ = TRACKED.try_with(|tracked| {
+        let mut tracked = tracked.borrow_mut();
+        let (objects, mark) = tracked.generation(slot);
+        let index = slot & !OLD;
+        objects.swap_remove(index);
+        if let Some(moved) = objects.get(index).and_then(Weak::upgrade) {
+            moved.gc_slot().set(index | mark);
+        }
+    });
+}
+
+/// Whether the object may refer to objects other than its class, so that
+/// it may be part of a cycle.
+pub(super) fn is_container(object: &PyObject) -> bool {
+    if object.dict().is_some() {
+        return true;
+    }
+    !matches!(
+        object.payload,
+        Payload::Object
+            | Payload::None
+            | Payload::NotImplemented
+            | Payload::Ellipsis
+            | Payload::Int(_)
+            | Payload::Float(_)
+            | Payload::Complex { .. }
+            | Payload::Str(_)
+            | Payload::Bytes(_)
+            | Payload::ByteArray(_)
+            | Payload::Range { .. }
+            | Payload::Builtin(_)
+            | Payload::Code(_)
+            | Payload::Module
+    )
+}
+
+/// The number of young objects alive.
+pub(super) fn young_count() -> usize {
+    TRACKED.with(|tracked| tracked.borrow().young.len())
+}
+
+/// Notes that a class has `__del__`, as a class is made with it or it is
+/// set on one.
+pub(super) fn define_finalizer() {
+    FINALIZERS.with(|defined| defined.set(true));
+}
+
+/// The `__del__` of the instances of `class`, if it is a class made by
+/// Python code that has one.
+fn finalizer(class: &ObjectRef) -> Option<ObjectRef> {
+    let data = class.as_type().filter(|data| data.heap)?;
+    std::iter::once(class).chain(&data.mro).find_map(|class| {
+        class
+            .dict()?
+            .as_dict()?
+            .try_borrow()
+            .ok()?
+            .get_str("__del__")
+    })
+}
+
+/// Whether `object`, which reference counting is freeing, has a `__del__`
+/// that has not been called.
+pub(super) fn needs_finalizer(object: &PyObject) -> bool {
+    // Objects freed as the thread ends may outlive the registries.
+    if !FINALIZERS.try_with(Cell::get).unwrap_or(false) {
+        return false;
+    }
+    let id = object as *const PyObject as usize;
+    match FINALIZED.try_with(|finalized| finalized.borrow_mut().remove(&id)) {
+        Ok(false) => {}
+        _ => return false,
+    }
+    if UNFINALIZED.try_with(|_| ()).is_err() || TRACKED.try_with(|_| ()).is_err() {
+        return false;
+    }
+    has_finalizer(object)
+}
+
+/// Whether the class of `object` has a `__del__`.
+fn has_finalizer(object: &PyObject) -> bool {
+    match object.class_cell().try_borrow() {
+        Ok(class) => class.as_ref().and_then(finalizer).is_some(),
+        Err(_) => false,
+    }
+}
+
+/// Keeps `object`, into which one being freed was moved, for its `__del__`
+/// to be called.
+pub(super) fn finalize_later(object: ObjectRef) {
+    FINALIZED.with(|finalized| finalized.borrow_mut().insert(object_id(&object)));
+    UNFINALIZED.with(|unfinalized| unfinalized.borrow_mut().push(object));
+}
+
+/// Whether there are objects whose `__del__` is still to be called.
+pub(super) fn has_unfinalized() -> bool {
+    FINALIZERS.with(Cell::get) && UNFINALIZED.with(|unfinalized| !unfinalized.borrow().is_empty())
+}
+
+impl Drop for Vm {
+    /// Forgets the objects whose `__del__` was still to be called, which
+    /// would otherwise be called by the next VM on the thread.
+    fn drop(&mut self) {
+        let _ = FINALIZERS.try_with(|defined| defined.set(false));
+        let _ = UNFINALIZED.try_with(|unfinalized| unfinalized.borrow_mut().clear());
+    }
+}
+
+impl Vm {
+    /// Calls the `__del__` of the objects reference counting has freed
+    /// since this was last called, and of any they free in turn.
+    pub(super) fn run_finalizers(&mut self) {
+        loop {
+            let objects = UNFINALIZED.with(|unfinalized| mem::take(&mut *unfinalized.borrow_mut()));
+            if objects.is_empty() {
+                return;
+            }
+            for object in objects {
+                self.finalize(&object);
+            }
+        }
+    }
+
+    /// Calls the `__del__` of `object`. An exception it raises has no one
+    /// to go to, so it is reported on `sys.stderr` and ignored.
+    fn finalize(&mut self, object: &ObjectRef) {
+        let class = object.class();
+        let method = match finalizer(&class) {
+            Some(method) => method,
+            None => return,
+        };
+        let result = self
+            .bind("__del__", method.clone(), Some(object), &class)
+            .and_then(|bound| self.call(&bound, Args::default()));
+        if let Err(exception) = result {
+            let name = self
+                .repr(&method)
+                .unwrap_or_else(|_| "<function __del__>".to_string());
+            let report = self.format_exception(&exception);
+            let header = format!("Exception ignored in: {}\n", name);
+            let _ = write_standard(self, "stderr", &[&header, &report], true);
+        }
+    }
+
+    /// Runs a full collection, and returns the number of unreachable
+    /// objects found and freed.
+    pub fn collect_garbage(&mut self) -> usize {
+        self.collect_generation(2)
+    }
+
+    /// Runs a collection, of the young objects only for generation 0, and
+    /// of all of them otherwise.
+    pub(super) fn collect_generation(&mut self, generation: usize) -> usize {
+        if self.gc.collecting {
+            return 0;
+        }
+        self.gc.collecting = true;
+        let full = generation > 0;
+        let mut collected = 0;
+        loop {
+            let (freed, unfinalized) = collect(full);
+            collected += freed;
+            if unfinalized.is_empty() {
+                break;
+            }
+            for object in &unfinalized {
+                FINALIZED.with(|finalized| finalized.borrow_mut().insert(object_id(object)));
+                self.finalize(object);
+            }
+        }
+        self.gc.collecting = false;
+        if full {
+            self.gc.young_collections = 0;
+            self.gc.old_survivors = TRACKED.with(|tracked| tracked.borrow().old.len());
+        } else {
+            self.gc.young_collections += 1;
+        }
+        let stats = &mut self.gc.stats[generation];
+        stats.collections += 1;
+        stats.collected += collected;
+        collected
+    }
+
+    /// Runs a collection if enough young objects are alive, and a full one
+    /// if there have been enough young ones and the old objects have grown
+    /// enough.
+    pub(super) fn maybe_collect_garbage(&mut self) {
+        if !self.gc.enabled || self.gc.threshold == 0 || young_count() <= self.gc.threshold {
+            return;
+        }
+        let old = TRACKED.with(|tracked| tracked.borrow().old.len());
+        let grown = old.saturating_sub(self.gc.old_survivors) > self.gc.old_survivors / 4;
+        if self.gc.young_collections >= self.gc.full_threshold && grown {
+            self.collect_generation(2);
+        } else {
+            self.collect_generation(0);
+        }
+    }
+
+    /// The collections made for each generation.
+    pub(super) fn gc_stats(&self) -> [GenerationStats; 3] {
+        self.gc.stats
+    }
+
+    /// What `gc.get_count` reports: the young objects alive and the young
+    /// collections since the last full one.
+    pub(super) fn gc_counts(&self) -> (usize, usize) {
+        (young_count(), self.gc.young_collections)
+    }
+
+    pub(super) fn gc_enabled(&self) -> bool {
+        self.gc.enabled
+    }
+
+    /// Turns automatic collection on or off.
+    pub fn set_gc_enabled(&mut self, enabled: bool) {
+        self.gc.enabled = enabled;
+    }
+
+    /// The thresholds `gc.get_threshold` reports.
+    pub(super) fn gc_thresholds(&self) -> (usize, usize, usize) {
+        (
+            self.gc.threshold,
+            self.gc.full_threshold,
+            self.gc.last_threshold,
+        )
+    }
+
+    /// Sets the number of young objects alive that starts a collection, 0
+    /// for none, the number of young collections between full ones, and
+    /// the third threshold of `gc.set_threshold`, which only is reported.
+    pub(super) fn set_gc_thresholds(&mut self, thresholds: (usize, usize, usize)) {
+        self.gc.threshold = thresholds.0;
+        self.gc.full_threshold = thresholds.1;
+        self.gc.last_threshold = thresholds.2;
+    }
+}
+
+/// Finds the objects looked at that only the others refer to, and breaks
+/// their cycles, then makes the young objects left old. Looks at all the
+/// tracked objects if `full`, and at the young ones otherwise. Returns the
+/// number of garbage objects found, or, leaving all of it alone, those of
+/// them whose `__del__` is still to be called.
+fn collect(full: bool) -> (usize, Vec<ObjectRef>) {
+    // Holding the objects adds one reference to each, which is not counted.
+    let objects: Vec<ObjectRef> = TRACKED.with(|tracked| {
+        let tracked = tracked.borrow();
+        let old = if full { &tracked.old[..] } else { &[] };
+        tracked
+            .young
+            .iter()
+            .chain(old)
+            .filter_map(Weak::upgrade)
+            .collect()
+    });
+    let indices: HashMap<usize, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(index, object)| (object_id(object), index))
+        .collect();
+
+    let mut external: Vec<usize> = objects
+        .iter()
+        .map(|object| Rc::strong_count(object) - 1)
+        .collect();
+    for object in &objects {
+        traverse(object, &mut |referent| {
+            if let Some(&index) = indices.get(&object_id(referent)) {
+                external[index] -= 1;
+            }
+        });
+    }
+
+    let mut reachable = vec![false; objects.len()];
+    let mut pending: Vec<usize> = Vec::new();
+    for (index, &count) in external.iter().enumerate() {
+        if count > 0 {
+            reachable[index] = true;
+            pending.push(index);
+        }
+    }
+    while let Some(index) = pending.pop() {
+        traverse(&objects[index], &mut |referent| {
+            if let Some(&index) = indices.get(&object_id(referent)) {
+                if !reachable[index] {
+                    reachable[index] = true;
+                    pending.push(index);
+                }
+            }
+        });
+    }
+
+    let garbage: Vec<&ObjectRef> = objects
+        .iter()
+        .zip(&reachable)
+        .filter(|&(_, &reachable)| !reachable)
+        .map(|(object, _)| object)
+        .collect();
+    if FINALIZERS.with(Cell::get) {
+        let unfinalized: Vec<ObjectRef> = garbage
+            .iter()
+            .filter(|object| {
+                let id = object_id(object);
+                !FINALIZED.with(|finalized| finalized.borrow().contains(&id))
+                    && has_finalizer(object)
+            })
+            .map(|object| (*object).clone())
+            .collect();
+        if !unfinalized.is_empty() {
+            return (0, unfinalized);
+        }
+    }
+    // What the garbage held is dropped after every object is cleared, so
+    // that nothing is freed while the others are being cleared.
+    let mut contents = Vec::new();
+    for object in &garbage {
+        clear(object, &mut contents);
+    }
+    drop(contents);
+    let collected = garbage.len();
+    drop(garbage);
+    drop(objects);
+
+    TRACKED.with(|tracked| {
+        let mut tracked = tracked.borrow_mut();
+        let young = mem::take(&mut tracked.young);
+        for object in young {
+            if let Some(object) = object.upgrade() {
+                object.gc_slot().set(tracked.old.len() | OLD);
+                tracked.old.push(Rc::downgrade(&object));
+            }
+        }
+    });
+    (collected, Vec::new())
+}
+
+/// Calls `visit` for each object `object` holds a reference to, with one
+/// call for each reference. The constants of code, which functions and
+/// frames share, are not visited, nor what is borrowed for changing.
+fn traverse(object: &PyObject, visit: &mut dyn FnMut(&ObjectRef)) {
+    if let Ok(class) = object.class_cell().try_borrow() {
+        if let Some(ref class) = *class {
+            visit(class);
+        }
+    }
+    if let Some(dict) = object.dict() {
+        visit(dict);
+    }
+    match object.payload {
+        Payload::Tuple(ref items) => items.iter().for_each(visit),
+        Payload::List(ref items) => {
+            if let Ok(items) = items.try_borrow() {
+                items.iter().for_each(visit);
+            }
+        }
+        Payload::Dict(ref dict) => {
+            if let Ok(dict) = dict.try_borrow() {
+                for entry in dict.iter() {
+                    visit(&entry.key);
+                    visit(&entry.value);
+                }
+            }
+        }
+        Payload::Set(ref members) => {
+            if let Ok(members) = members.try_borrow() {
+                for entry in members.iter() {
+                    visit(&entry.key);
+                    visit(&entry.value);
+                }
+            }
+        }
+        Payload::DictView { ref dict, .. } => visit(dict),
+        Payload::MemoryView { ref object, .. } => visit(object),
+        Payload::Slice {
+            ref start,
+            ref stop,
+            ref step,
+        } => {
+            visit(start);
+            visit(stop);
+            visit(step);
+        }
+        Payload::Iterator(ref state) => {
+            if let Ok(state) = state.try_borrow() {
+                traverse_iterator(&state, visit);
+            }
+        }
+        Payload::Function(ref function) => {
+            visit(&function.globals);
+            for cell in [
+                &function.defaults,
+                &function.kwdefaults,
+                &function.annotations,
+            ] {
+                if let Ok(value) = cell.try_borrow() {
+                    if let Some(ref value) = *value {
+                        visit(value);
+                    }
+                }
+            }
+            if let Some(ref closure) = function.closure {
+                visit(closure);
+            }
+            for cell in [&function.doc, &function.module] {
+                if let Ok(value) = cell.try_borrow() {
+                    visit(&value);
+                }
+            }
+        }
+        Payload::Generator(ref generator) => {
+            if let Ok(frame) = generator.frame.try_borrow() {
+                if let Some(ref frame) = *frame {
+                    frame.traverse(visit);
+                }
+            }
+            if let Ok(exception) = generator.exc_info.try_borrow() {
+                if let Some(ref exception) = *exception {
+                    visit(exception);
+                }
+            }
+        }
+        Payload::Method {
+            ref function,
+            ref instance,
+        } => {
+            visit(function);
+            visit(instance);
+        }
+        Payload::StaticMethod(ref function) | Payload::ClassMethod(ref function) => visit(function),
+        Payload::Property(ref property) => {
+            for function in [&property.fget, &property.fset, &property.fdel] {
+                if let Some(ref function) = *function {
+                    visit(function);
+                }
+            }
+            if let Ok(doc) = property.doc.try_borrow() {
+                visit(&doc);
+            }
+            if let Ok(name) = property.name.try_borrow() {
+                if let Some(ref name) = *name {
+                    visit(name);
+                }
+            }
+        }
+        Payload::Cell(ref contents) => {
+            if let Ok(contents) = contents.try_borrow() {
+                if let Some(ref contents) = *contents {
+                    visit(contents);
+                }
+            }
+        }
+        Payload::Super {
+            ref class,
+            ref object,
+        } => {
+            visit(class);
+            visit(object);
+        }
+        Payload::Type(ref data) => {
+            data.bases.iter().for_each(&mut *visit);
+            data.mro.iter().for_each(visit);
+        }
+        Payload::Exception(ref data) => {
+            if let Ok(data) = data.try_borrow() {
+                visit(&data.args);
+                for link in [&data.traceback, &data.cause, &data.context] {
+                    if let Some(ref link) = *link {
+                        visit(link);
+                    }
+                }
+            }
+        }
+        Payload::Traceback(ref traceback) => {
+            if let Some(ref next) = traceback.next {
+                visit(next);
+            }
+            for (_, value) in &traceback.locals {
+                visit(value);
+            }
+        }
+        Payload::Object
+        | Payload::None
+        | Payload::NotImplemented
+        | Payload::Ellipsis
+        | Payload::Int(_)
+        | Payload::Float(_)
+        | Payload::Complex { .. }
+        | Payload::Str(_)
+        | Payload::Bytes(_)
+        | Payload::ByteArray(_)
+        | Payload::Range { .. }
+        | Payload::Builtin(_)
+        | Payload::Code(_)
+        | Payload::Module => {}
+    }
+}
+
+fn traverse_iterator(state: &IteratorState, visit: &mut dyn FnMut(&ObjectRef)) {
+    match *state {
+        IteratorState::Sequence { ref sequence, .. }
+        | IteratorState::Reversed { ref sequence, .. } => visit(sequence),
+        IteratorState::Str { ref string, .. } => visit(string),
+        IteratorState::Keys { ref container, .. } => visit(container),
+        IteratorState::Entries { ref dict, .. } => visit(dict),
+        IteratorState::Enumerate { ref iterator, .. } => visit(iterator),
+        IteratorState::Zip(ref iterators) => iterators.iter().for_each(visit),
+        IteratorState::Map {
+            ref function,
+            ref iterators,
+        } => {
+            visit(function);
+            iterators.iter().for_each(visit);
+        }
+        IteratorState::Filter {
+            ref function,
+            ref iterator,
+        } => {
+            visit(function);
+            visit(iterator);
+        }
+        IteratorState::Range { .. } | IteratorState::Exhausted => {}
+    }
+}
+
+/// Empties the containers of a garbage object, moving what they held to
+/// `contents`. Objects that cannot change, such as tuples, are left as
+/// they are: a cycle through one also goes through an object that can.
+fn clear(object: &PyObject, contents: &mut Vec<Box<dyn Any>>) {
+    fn take<T: Default + 'static>(cell: &RefCell<T>, contents: &mut Vec<Box<dyn Any>>) {
+        if let Ok(mut value) = cell.try_borrow_mut() {
+            contents.push(Box::new(mem::take(&mut *value)));
+        }
+    }
+    match object.payload {
+        Payload::List(ref items) => take(items, contents),
+        Payload::Dict(ref dict) | Payload::Set(ref dict) => take(dict, contents),
+        Payload::Cell(ref value) => take(value, contents),
+        Payload::Iterator(ref state) => {
+            if let Ok(mut state) = state.try_borrow_mut() {
+                let state = mem::replace(&mut *state, IteratorState::Exhausted);
+                contents.push(Box::new(state));
+            }
+        }
+        Payload::Function(ref function) => {
+            take(&function.defaults, contents);
+            take(&function.kwdefaults, contents);
+            take(&function.annotations, contents);
+        }
+        Payload::Generator(ref generator) => {
+            take(&generator.frame, contents);
+            take(&generator.exc_info, contents);
+        }
+        Payload::Property(ref property) => take(&property.name, contents),
+        Payload::Exception(ref data) => {
+            if let Ok(mut data) = data.try_borrow_mut() {
+                let links = (
+                    data.traceback.take(),
+                    data.cause.take(),
+                    data.context.take(),
+                );
+                contents.push(Box::new(links));
+            }
+        }
+        _ => {}
+    }
+}
@@ -0,0 +1,424 @@
+//! Generators: frames that suspend at each `yield` and carry on from there
+//! when they are resumed. Coroutines, made by calling an `async def`, are
+//! generators of another class that suspend where an `await` yields.
+
+use std::cell::{Cell, RefCell};
+
+use compiler::CO_COROUTINE;
+
+use super::frame::{Completion, Frame};
+use super::object::{
+    Args, Function, Generator, NativeFunction, ObjectRef, Payload, PyObject, PyResult,
+};
+use super::Vm;
+
+/// Adds the methods of generators to their class.
+pub(super) fn add_methods(vm: &mut Vm) {
+    let methods: [(&'static str, NativeFunction); 5] = [
+        ("send", send),
+        ("throw", throw),
+        ("close", close),
+        ("__next__", next),
+        ("__iter__", iter),
+    ];
+    let dict = vm.types.generator.dict().unwrap().clone();
+    for &(name, function) in &methods {
+        let method = vm.new_builtin(name, function);
+        vm.dict_set_str(dict.as_dict().unwrap(), name, method);
+    }
+    // A coroutine is not an iterator, but is its own `__await__`.
+    let methods: [(&'static str, NativeFunction); 4] = [
+        ("send", send),
+        ("throw", throw),
+        ("close", close),
+        ("__await__", iter),
+    ];
+    let dict = vm.types.coroutine.dict().unwrap().clone();
+    for &(name, function) in &methods {
+        let method = vm.new_builtin(name, function);
+        vm.dict_set_str(dict.as_dict().unwrap(), name, method);
+    }
+}
+
+impl Generator {
+    /// Whether this is the coroutine of a call to an `async def`.
+    pub fn is_coroutine(&self) -> bool {
+        self.code.flags & CO_COROUTINE != 0
+    }
+
+    /// `generator` or `coroutine`, for messages.
+    pub(super) fn kind(&self) -> &'static str {
+        if self.is_coroutine() {
+            "coroutine"
+        } else {
+            "generator"
+        }
+    }
+}
+
+/// The generator a method was called on.
+fn instance<'a>(vm: &mut Vm, args: &'a Args, name: &str) -> PyResult<&'a Generator> {
+    match args.positional.first().map(|object| &object.payload) {
+        Some(Payload::Generator(ref generator)) => Ok(generator),
+        _ => {
+            let message = format!(
+                "descriptor '{}' for 'generator' objects doesn't apply to this object",
+                name
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// `generator.send(value)`, which resumes the generator with `value` as
+/// the result of the `yield` it stopped at.
+fn send(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "send", 2, 2)?;
+    let generator = instance(vm, &args, "send")?;
+    let completion = vm.resume(generator, Some(args.positional[1].clone()), None)?;
+    vm.yielded(completion)
+}
+
+fn next(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__next__", 1, 1)?;
+    let generator = instance(vm, &args, "__next__")?;
+    let none = vm.none();
+    let completion = vm.resume(generator, Some(none), None)?;
+    vm.yielded(completion)
+}
+
+fn iter(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__iter__", 1, 1)?;
+    instance(vm, &args, "__iter__")?;
+    Ok(args.positional[0].clone())
+}
+
+/// `generator.throw(type[, value[, traceback]])`, which raises an
+/// exception where the generator stopped.
+fn throw(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "throw", 2, 4)?;
+    let generator = instance(vm, &args, "throw")?;
+    let none = vm.none();
+    let kind = &args.positional[1];
+    let value = args
+        .positional
+        .get(2)
+        .filter(|value| !Vm::is(value, &none))
+        .cloned();
+    let exception = if vm.is_exception_class(kind) {
+        match value {
+            Some(ref value) if Vm::is_instance(value, kind) => value.clone(),
+            Some(value) => {
+                let positional = match value.payload {
+                    Payload::Tuple(ref items) => items.clone(),
+                    _ => vec![value.clone()],
+                };
+                let exception = vm.call(kind, Args::new(positional))?;
+                vm.make_exception(&exception)?
+            }
+            None => vm.make_exception(kind)?,
+        }
+    } else if kind.as_exception().is_some() {
+        if value.is_some() {
+            let message = "instance exception may not have a separate value".to_string();
+            return Err(vm.new_type_error(message));
+        }
+        kind.clone()
+    } else {
+        let message = format!(
+            "exceptions must be classes or instances deriving from BaseException, not {}",
+            kind.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    };
+    if let Some(traceback) = args.positional.get(3) {
+        if !Vm::is(traceback, &none) {
+            vm.set_exception_attr(&exception, "__traceback__", traceback)?;
+        }
+    }
+    let completion = vm.throw_into(generator, exception)?;
+    vm.yielded(completion)
+}
+
+/// `generator.close()`, which raises `GeneratorExit` where the generator
+/// stopped so that its `finally` clauses run.
+fn close(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "close", 1, 1)?;
+    let generator = instance(vm, &args, "close")?;
+    vm.close_generator(generator)?;
+    Ok(vm.none())
+}
+
+impl Vm {
+    /// A generator, or a coroutine for an `async def`, that runs `frame`, a
+    /// frame for a call to `function`, once it is first resumed.
+    pub(super) fn new_generator(&mut self, function: &Function, frame: Frame) -> ObjectRef {
+        let class = if function.code.flags & CO_COROUTINE != 0 {
+            self.types.coroutine.clone()
+        } else {
+            self.types.generator.clone()
+        };
+        PyObject::new(
+            Payload::Generator(Generator {
+                name: RefCell::new(function.name.borrow().clone()),
+                qualname: RefCell::new(function.qualname.borrow().clone()),
+                code: function.code.clone(),
+                frame: RefCell::new(Some(Box::new(frame))),
+                running: Cell::new(false),
+                exc_info: RefCell::new(None),
+            }),
+            class,
+            None,
+        )
+    }
+
+    /// The iterator that `await` drives for `awaitable`: a coroutine
+    /// itself, or the iterator its `__await__` returns.
+    pub(super) fn awaitable_iter(&mut self, awaitable: &ObjectRef) -> PyResult {
+        if let Payload::Generator(ref generator) = awaitable.payload {
+            if generator.is_coroutine() {
+                return Ok(awaitable.clone());
+            }
+        }
+        let method = match self.python_method(awaitable, "__await__") {
+            Some(method) => method,
+            None => {
+                let message = format!(
+                    "object {} can't be used in 'await' expression",
+                    awaitable.type_name()
+                );
+                return Err(self.new_type_error(message));
+            }
+        };
+        let iterator = self.call(&method, Args::default())?;
+        let is_iterator = match iterator.payload {
+            Payload::Generator(ref generator) if generator.is_coroutine() => {
+                return Err(self.new_type_error("__await__() returned a coroutine".to_string()));
+            }
+            Payload::Iterator(_) | Payload::Generator(_) => true,
+            _ => self.python_method(&iterator, "__next__").is_some(),
+        };
+        if !is_iterator {
+            let message = format!(
+                "__await__() returned non-iterator of type '{}'",
+                iterator.type_name()
+            );
+            return Err(self.new_type_error(message));
+        }
+        Ok(iterator)
+    }
+
+    /// Sends `value` to the iterator of a `yield from`. Iterators other
+    /// than generators are advanced for `None`, and have their `send`
+    /// method called otherwise.
+    pub(super) fn send(&mut self, iterator: &ObjectRef, value: ObjectRef) -> PyResult<Completion> {
+        if let Payload::Generator(ref generator) = iterator.payload {
+            return self.resume(generator, Some(value), None);
+        }
+        if Vm::is(&value, &self.none) {
+            return Ok(match self.next(iterator)? {
+                Some(item) => Completion::Yielded(item),
+                None => Completion::Returned(self.none()),
+            });
+        }
+        let send = self.getattr(iterator, "send")?;
+        match self.call(&send, Args::new(vec![value])) {
+            Ok(item) => Ok(Completion::Yielded(item)),
+            Err(exception) if Vm::is_instance(&exception, &self.exceptions.stop_iteration) => {
+                Ok(Completion::Returned(self.stop_iteration_value(&exception)))
+            }
+            Err(exception) => Err(exception),
+        }
+    }
+
+    /// Runs a generator until it next yields or finishes. `value` is the
+    /// result of the `yield` it stopped at, which must be `None` if it has
+    /// not started; with `throw`, that exception is raised there instead.
+    ///
+    /// A finished generator returns `None`, or raises `throw`.
+    pub(super) fn resume(
+        &mut self,
+        generator: &Generator,
+        value: Option<ObjectRef>,
+        throw: Option<ObjectRef>,
+    ) -> PyResult<Completion> {
+        if generator.running.get() {
+            let message = format!("{} already executing", generator.kind());
+            return Err(self.new_value_error(message));
+        }
+        if self.depth >= self.recursion_limit {
+            let class = self.exceptions.recursion_error.clone();
+            return Err(self.new_error(&class, "maximum recursion depth exceeded".to_string()));
+        }
+        let mut frame = match generator.frame.borrow_mut().take() {
+            Some(frame) => frame,
+            None if generator.is_coroutine() => {
+                let message = "cannot reuse already awaited coroutine".to_string();
+                return Err(self.new_runtime_error(message));
+            }
+            None => {
+                return match throw {
+                    Some(exception) => Err(exception),
+                    None => Ok(Completion::Returned(self.none())),
+                }
+            }
+        };
+        if let Some(value) = value {
+            if frame.started() {
+                frame.push(value);
+            } else if !Vm::is(&value, &self.none) {
+                *generator.frame.borrow_mut() = Some(frame);
+                let message = format!(
+                    "can't send non-None value to a just-started {}",
+                    generator.kind()
+                );
+                return Err(self.new_type_error(message));
+            }
+        }
+
+        let caller_exc_info = self.exc_info.clone();
+        if let Some(exc_info) = generator.exc_info.borrow_mut().take() {
+            self.exc_info = Some(exc_info);
+        }
+        generator.running.set(true);
+        self.depth += 1;
+        let result = self.run_frame(&mut frame, throw);
+        self.depth -= 1;
+        generator.running.set(false);
+        let result = match result {
+            Ok(Completion::Yielded(value)) => {
+                if frame.handling_exception() {
+                    *generator.exc_info.borrow_mut() = self.exc_info.clone();
+                }
+                *generator.frame.borrow_mut() = Some(frame);
+                Ok(Completion::Yielded(value))
+            }
+            // A `StopIteration` escaping a generator would end the loop
+            // over it as if it had finished, so it becomes a
+            // `RuntimeError`, as PEP 479 has it.
+            Err(exception) if Vm::is_instance(&exception, &self.exceptions.stop_iteration) => {
+                let message = format!("{} raised StopIteration", generator.kind());
+                let error = self.new_runtime_error(message);
+                {
+                    let mut data = error.as_exception().unwrap().borrow_mut();
+                    data.cause = Some(exception.clone());
+                    data.context = Some(exception);
+                    data.suppress_context = true;
+                }
+                Err(error)
+            }
+            result => result,
+        };
+        self.exc_info = caller_exc_info;
+        result
+    }
+
+    /// Raises `exception` where a generator stopped. A generator in
+    /// `yield from` another generator passes it on to that one, or closes
+    /// it first for `GeneratorExit`.
+    pub(super) fn throw_into(
+        &mut self,
+        generator: &Generator,
+        exception: ObjectRef,
+    ) -> PyResult<Completion> {
+        let delegate = match *generator.frame.borrow() {
+            Some(ref frame) => frame.delegate().cloned(),
+            None => None,
+        };
+        let inner = match delegate {
+            Some(ref delegate) => match delegate.payload {
+                Payload::Generator(ref inner) => inner,
+                _ => return self.resume(generator, None, Some(exception)),
+            },
+            None => return self.resume(generator, None, Some(exception)),
+        };
+        if generator.running.get() {
+            let message = format!("{} already executing", generator.kind());
+            return Err(self.new_value_error(message));
+        }
+
+        generator.running.set(true);
+        let result = if Vm::is_instance(&exception, &self.exceptions.generator_exit) {
+            self.close_generator(inner).map(|()| None)
+        } else {
+            self.throw_into(inner, exception.clone()).map(Some)
+        };
+        generator.running.set(false);
+        match result {
+            Ok(Some(Completion::Yielded(value))) => Ok(Completion::Yielded(value)),
+            Ok(Some(Completion::Returned(value))) => {
+                if let Some(ref mut frame) = *generator.frame.borrow_mut() {
+                    frame.end_delegation(value);
+                }
+                self.resume(generator, None, None)
+            }
+            Ok(None) => self.resume(generator, None, Some(exception)),
+            Err(error) => self.resume(generator, None, Some(error)),
+        }
+    }
+
+    /// Closes a generator, running its `finally` clauses if it has started.
+    pub(super) fn close_generator(&mut self, generator: &Generator) -> PyResult<()> {
+        if generator.running.get() {
+            let message = format!("{} already executing", generator.kind());
+            return Err(self.new_value_error(message));
+        }
+        let started = match *generator.frame.borrow() {
+            Some(ref frame) => frame.started(),
+            None => false,
+        };
+        if !started {
+            generator.frame.borrow_mut().take();
+            return Ok(());
+        }
+        let class = self.exceptions.generator_exit.clone();
+        let exit = self.new_exception(&class, Vec::new());
+        match self.throw_into(generator, exit) {
+            Ok(Completion::Yielded(_)) => {
+                let message = format!("{} ignored GeneratorExit", generator.kind());
+                Err(self.new_runtime_error(message))
+            }
+            Ok(Completion::Returned(_)) => Ok(()),
+            Err(exception)
+                if Vm::is_instance(&exception, &self.exceptions.generator_exit)
+                    || Vm::is_instance(&exception, &self.exceptions.stop_iteration) =>
+            {
+                Ok(())
+            }
+            Err(exception) => Err(exception),
+        }
+    }
+
+    /// The value a generator method returns for how the generator stopped:
+    /// what it yielded, or else `StopIteration` with its return value.
+    fn yielded(&mut self, completion: Completion) -> PyResult {
+        match completion {
+            Completion::Yielded(value) => Ok(value),
+            Completion::Returned(value) => Err(self.new_stop_iteration(value)),
+        }
+    }
+
+    /// A `StopIteration` for an iterator that returned `value`, which is
+    /// left out of its arguments if it is `None`.
+    pub fn new_stop_iteration(&mut self, value: ObjectRef) -> ObjectRef {
+        let class = self.exceptions.stop_iteration.clone();
+        let args = if Vm::is(&value, &self.none) {
+            Vec::new()
+        } else {
+            vec![value]
+        };
+        self.new_exception(&class, args)
+    }
+
+    /// The `value` of a `StopIteration`: its first argument, or `None`.
+    pub fn stop_iteration_value(&mut self, exception: &ObjectRef) -> ObjectRef {
+        let args = match exception.as_exception() {
+            Some(data) => data.borrow().args.clone(),
+            None => return self.none(),
+        };
+        match args.payload {
+            Payload::Tuple(ref items) if !items.is_empty() => items[0].clone(),
+            _ => self.none(),
+        }
+    }
+}
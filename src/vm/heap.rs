@@ -0,0 +1,481 @@
+//! Snapshots of the object graph, for finding what keeps objects alive in
+//! a long-running interpreter.
+//!
+//! A snapshot holds every object reachable from the interpreter's roots:
+//! the builtins, `sys.modules`, `__main__` and the exception being
+//! handled. Objects only Rust code holds are not in it, but the reference
+//! counts tell how many references come from outside the graph. Taking two
+//! snapshots and comparing their `type_counts` shows what grows;
+//! `retaining_path` then shows why an object is still alive.
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Write};
+use std::mem;
+use std::rc::Rc;
+
+use ast::repr_str;
+
+use super::dict::Entry;
+use super::object::{object_id, IteratorState, ObjectRef, Payload, PyObject};
+use super::Vm;
+
+/// The objects reachable from the roots of an interpreter and the
+/// references between them.
+#[derive(Debug, Clone, Default)]
+pub struct HeapSnapshot {
+    pub objects: Vec<HeapObject>,
+    pub edges: Vec<HeapEdge>,
+    pub roots: Vec<HeapRoot>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapObject {
+    /// The object's `id()`.
+    pub id: usize,
+    pub type_name: String,
+    /// A short description, such as the name of a module or function or
+    /// the start of a string.
+    pub label: Option<String>,
+    /// An estimate of the bytes the object itself takes, without the
+    /// objects it refers to.
+    pub size: usize,
+    /// The references to the object, from the graph and from elsewhere.
+    pub refcount: usize,
+}
+
+/// A reference from one object to another, by their indices in
+/// `HeapSnapshot::objects`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapEdge {
+    pub from: usize,
+    pub to: usize,
+    /// How `from` refers to `to`, written to follow an expression for
+    /// `from`: `.attribute`, `[key]` or `[index]`.
+    pub name: String,
+}
+
+/// A root of the graph, by its index in `HeapSnapshot::objects`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeapRoot {
+    pub name: String,
+    pub object: usize,
+}
+
+impl Vm {
+    /// Walks the objects reachable from the interpreter's roots.
+    pub fn heap_snapshot(&self) -> HeapSnapshot {
+        let mut roots = vec![
+            ("builtins".to_string(), self.builtins.clone()),
+            ("sys.modules".to_string(), self.modules.clone()),
+            ("__main__".to_string(), self.main.clone()),
+        ];
+        if let Some(ref exception) = self.exc_info {
+            roots.push(("sys.exception()".to_string(), exception.clone()));
+        }
+
+        let mut snapshot = HeapSnapshot::default();
+        let mut indices = HashMap::new();
+        // The objects in the order they were found, which is the order they
+        // are walked in. They are kept alive while the walk goes on, so that
+        // their ids stay unique.
+        let mut found: Vec<ObjectRef> = Vec::new();
+        let mut visit = |object: &ObjectRef, found: &mut Vec<ObjectRef>| {
+            *indices.entry(object_id(object)).or_insert_with(|| {
+                found.push(object.clone());
+                found.len() - 1
+            })
+        };
+        for (name, object) in roots {
+            let index = visit(&object, &mut found);
+            snapshot.roots.push(HeapRoot {
+                name,
+                object: index,
+            });
+        }
+        let mut from = 0;
+        while from < found.len() {
+            let object = found[from].clone();
+            for (name, to) in references(&object) {
+                let to = visit(&to, &mut found);
+                snapshot.edges.push(HeapEdge { from, to, name });
+            }
+            from += 1;
+        }
+        snapshot.objects = found
+            .iter()
+            .map(|object| HeapObject {
+                id: object_id(object),
+                type_name: object.type_name(),
+                label: label(object),
+                size: size(object),
+                // Less the reference in `found`.
+                refcount: Rc::strong_count(object) - 1,
+            })
+            .collect();
+        snapshot
+    }
+}
+
+impl HeapSnapshot {
+    /// The index of the object with the `id()` `id`.
+    pub fn find(&self, id: usize) -> Option<usize> {
+        self.objects.iter().position(|object| object.id == id)
+    }
+
+    /// The number of objects of each type, the most numerous first.
+    pub fn type_counts(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for object in &self.objects {
+            *counts.entry(&object.type_name).or_default() += 1;
+        }
+        let mut counts: Vec<(String, usize)> = counts
+            .into_iter()
+            .map(|(name, count)| (name.to_string(), count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        counts
+    }
+
+    /// How the number of objects of each type changed since `earlier`, for
+    /// the types whose number changed, the most grown first.
+    pub fn growth_since(&self, earlier: &HeapSnapshot) -> Vec<(String, isize)> {
+        let mut changes: HashMap<String, isize> = HashMap::new();
+        for (name, count) in self.type_counts() {
+            *changes.entry(name).or_default() += count as isize;
+        }
+        for (name, count) in earlier.type_counts() {
+            *changes.entry(name).or_default() -= count as isize;
+        }
+        let mut changes: Vec<(String, isize)> = changes
+            .into_iter()
+            .filter(|&(_, change)| change != 0)
+            .collect();
+        changes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        changes
+    }
+
+    /// A shortest chain of references from a root to the object at
+    /// `index`: the root and the indices of the edges. `None` if `index` is
+    /// out of range.
+    pub fn retaining_path(&self, index: usize) -> Option<(&HeapRoot, Vec<usize>)> {
+        if index >= self.objects.len() {
+            return None;
+        }
+        let mut outgoing = vec![Vec::new(); self.objects.len()];
+        for (edge_index, edge) in self.edges.iter().enumerate() {
+            outgoing[edge.from].push(edge_index);
+        }
+        // The edge each object was first reached by, from the roots.
+        let mut reached_by: Vec<Option<usize>> = vec![None; self.objects.len()];
+        let mut seen = vec![false; self.objects.len()];
+        let mut queue = VecDeque::new();
+        for root in &self.roots {
+            if !seen[root.object] {
+                seen[root.object] = true;
+                queue.push_back(root.object);
+            }
+        }
+        while let Some(from) = queue.pop_front() {
+            for &edge_index in &outgoing[from] {
+                let to = self.edges[edge_index].to;
+                if !seen[to] {
+                    seen[to] = true;
+                    reached_by[to] = Some(edge_index);
+                    queue.push_back(to);
+                }
+            }
+        }
+        let mut path = Vec::new();
+        let mut current = index;
+        while let Some(edge_index) = reached_by[current] {
+            path.push(edge_index);
+            current = self.edges[edge_index].from;
+        }
+        path.reverse();
+        let root = self.roots.iter().find(|root| root.object == current)?;
+        Some((root, path))
+    }
+
+    /// A retaining path as an expression, such as
+    /// `sys.modules['app'].__dict__['cache'][3]`.
+    pub fn format_path(&self, root: &HeapRoot, path: &[usize]) -> String {
+        let mut text = root.name.clone();
+        for &edge_index in path {
+            text.push_str(&self.edges[edge_index].name);
+        }
+        text
+    }
+
+    /// Writes the graph in Graphviz's DOT language, with each object
+    /// labelled by its type, description and size.
+    pub fn write_dot<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        writeln!(out, "digraph heap {{")?;
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];")?;
+        for (index, root) in self.roots.iter().enumerate() {
+            writeln!(
+                out,
+                "    root{} [label=\"{}\", shape=ellipse];",
+                index,
+                dot_escape(&root.name)
+            )?;
+            writeln!(out, "    root{} -> o{};", index, root.object)?;
+        }
+        for (index, object) in self.objects.iter().enumerate() {
+            let mut label = dot_escape(&object.type_name);
+            if let Some(ref description) = object.label {
+                label.push_str("\\n");
+                label.push_str(&dot_escape(description));
+            }
+            writeln!(
+                out,
+                "    o{} [label=\"{}\\n{} bytes, {} refs\"];",
+                index, label, object.size, object.refcount
+            )?;
+        }
+        for edge in &self.edges {
+            writeln!(
+                out,
+                "    o{} -> o{} [label=\"{}\"];",
+                edge.from,
+                edge.to,
+                dot_escape(&edge.name)
+            )?;
+        }
+        writeln!(out, "}}")
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// The longest string a label shows in full.
+const LABEL_LEN: usize = 40;
+
+/// A short description of an object, where its type alone says little.
+fn label(object: &ObjectRef) -> Option<String> {
+    Some(match object.payload {
+        Payload::Int(value) => value.to_string(),
+        Payload::Float(value) => value.to_string(),
+        Payload::Str(ref value) => {
+            let mut chars = value.chars();
+            let start: String = chars.by_ref().take(LABEL_LEN).collect();
+            match chars.next() {
+                Some(_) => format!("{}...", repr_str(&start)),
+                None => repr_str(&start),
+            }
+        }
+        Payload::Function(ref function) => function.qualname.borrow().clone(),
+        Payload::Generator(ref generator) => generator.qualname.borrow().clone(),
+        Payload::Builtin(ref builtin) => builtin.name.to_string(),
+        Payload::Code(ref code) => code.qualname.clone(),
+        Payload::Type(ref data) => data.qualname.clone(),
+        Payload::Module => {
+            let dict = object.dict()?.as_dict()?.borrow();
+            dict.get_str("__name__")?.as_str()?.to_string()
+        }
+        _ => return None,
+    })
+}
+
+/// An estimate of the memory an object takes, without what it refers to.
+fn size(object: &ObjectRef) -> usize {
+    let reference = mem::size_of::<ObjectRef>();
+    let contents = match object.payload {
+        Payload::Str(ref value) => value.capacity(),
+        Payload::Bytes(ref value) => value.capacity(),
+        Payload::ByteArray(ref value) => value.borrow().capacity(),
+        Payload::Tuple(ref items) => items.capacity() * reference,
+        Payload::List(ref items) => items.borrow().capacity() * reference,
+        Payload::Dict(ref dict) | Payload::Set(ref dict) => {
+            dict.borrow().len() * (mem::size_of::<Entry>() + mem::size_of::<usize>() * 2)
+        }
+        _ => 0,
+    };
+    // With the reference counts `Rc` keeps.
+    mem::size_of::<PyObject>() + 2 * mem::size_of::<usize>() + contents
+}
+
+/// How a dict key or set member appears in the name of an edge: its repr
+/// for strings and numbers, and its position otherwise.
+fn key_label(key: &ObjectRef, position: usize) -> String {
+    match key.payload {
+        Payload::Str(ref value) => repr_str(value),
+        Payload::Int(value) => value.to_string(),
+        _ => format!("<{} #{}>", key.type_name(), position),
+    }
+}
+
+/// The objects `object` refers to, with how it refers to each.
+fn references(object: &ObjectRef) -> Vec<(String, ObjectRef)> {
+    let mut references = vec![(".__class__".to_string(), object.class())];
+    if let Some(dict) = object.dict() {
+        references.push((".__dict__".to_string(), dict.clone()));
+    }
+    let mut add =
+        |name: &str, object: &ObjectRef| references.push((name.to_string(), object.clone()));
+    let sequence = |add: &mut dyn FnMut(&str, &ObjectRef), prefix: &str, items: &[ObjectRef]| {
+        for (index, item) in items.iter().enumerate() {
+            add(&format!("{}[{}]", prefix, index), item);
+        }
+    };
+    match object.payload {
+        Payload::Tuple(ref items) => sequence(&mut add, "", items),
+        Payload::List(ref items) => sequence(&mut add, "", &items.borrow()),
+        Payload::Dict(ref dict) => {
+            for (position, entry) in dict.borrow().iter().enumerate() {
+                let key = key_label(&entry.key, position);
+                add(&format!(".keys()[{}]", key), &entry.key);
+                add(&format!("[{}]", key), &entry.value);
+            }
+        }
+        Payload::Set(ref members) => {
+            for (position, entry) in members.borrow().iter().enumerate() {
+                add(
+                    &format!("{{{}}}", key_label(&entry.key, position)),
+                    &entry.key,
+                );
+            }
+        }
+        Payload::DictView { ref dict, .. } => add(".mapping", dict),
+        Payload::MemoryView { ref object, .. } => add(".obj", object),
+        Payload::Slice {
+            ref start,
+            ref stop,
+            ref step,
+        } => {
+            add(".start", start);
+            add(".stop", stop);
+            add(".step", step);
+        }
+        Payload::Iterator(ref state) => match *state.borrow() {
+            IteratorState::Sequence { ref sequence, .. }
+            | IteratorState::Reversed { ref sequence, .. } => add(".(sequence)", sequence),
+            IteratorState::Str { ref string, .. } => add(".(string)", string),
+            IteratorState::Keys { ref container, .. } => add(".(container)", container),
+            IteratorState::Entries { ref dict, .. } => add(".(dict)", dict),
+            IteratorState::Enumerate { ref iterator, .. } => add(".(iterator)", iterator),
+            IteratorState::Zip(ref iterators) => sequence(&mut add, ".(iterators)", iterators),
+            IteratorState::Map {
+                ref function,
+                ref iterators,
+            } => {
+                add(".(function)", function);
+                sequence(&mut add, ".(iterators)", iterators);
+            }
+            IteratorState::Filter {
+                ref function,
+                ref iterator,
+            } => {
+                add(".(function)", function);
+                add(".(iterator)", iterator);
+            }
+            IteratorState::Range { .. } | IteratorState::Exhausted => {}
+        },
+        Payload::Function(ref function) => {
+            add(".__globals__", &function.globals);
+            if let Some(ref defaults) = *function.defaults.borrow() {
+                add(".__defaults__", defaults);
+            }
+            if let Some(ref kwdefaults) = *function.kwdefaults.borrow() {
+                add(".__kwdefaults__", kwdefaults);
+            }
+            if let Some(ref annotations) = *function.annotations.borrow() {
+                add(".__annotations__", annotations);
+            }
+            if let Some(ref closure) = function.closure {
+                add(".__closure__", closure);
+            }
+            add(".__doc__", &function.doc.borrow());
+            add(".__module__", &function.module.borrow());
+            sequence(&mut add, ".__code__.co_consts", &function.constants);
+        }
+        Payload::Generator(ref generator) => {
+            if let Some(ref frame) = *generator.frame.borrow() {
+                for (name, object) in frame.references() {
+                    add(&format!(".gi_frame{}", name), &object);
+                }
+            }
+            if let Some(ref exception) = *generator.exc_info.borrow() {
+                add(".(handled exception)", exception);
+            }
+        }
+        Payload::Method {
+            ref function,
+            ref instance,
+        } => {
+            add(".__func__", function);
+            add(".__self__", instance);
+        }
+        Payload::StaticMethod(ref function) | Payload::ClassMethod(ref function) => {
+            add(".__func__", function)
+        }
+        Payload::Property(ref property) => {
+            for (name, function) in [
+                (".fget", &property.fget),
+                (".fset", &property.fset),
+                (".fdel", &property.fdel),
+            ] {
+                if let Some(ref function) = *function {
+                    add(name, function);
+                }
+            }
+            add(".__doc__", &property.doc.borrow());
+            if let Some(ref name) = *property.name.borrow() {
+                add(".(name)", name);
+            }
+        }
+        Payload::Cell(ref contents) => {
+            if let Some(ref contents) = *contents.borrow() {
+                add(".cell_contents", contents);
+            }
+        }
+        Payload::Super {
+            ref class,
+            ref object,
+        } => {
+            add(".__thisclass__", class);
+            add(".__self__", object);
+        }
+        Payload::Type(ref data) => {
+            sequence(&mut add, ".__bases__", &data.bases);
+            sequence(&mut add, ".__mro__", &data.mro);
+        }
+        Payload::Exception(ref data) => {
+            let data = data.borrow();
+            add(".args", &data.args);
+            if let Some(ref traceback) = data.traceback {
+                add(".__traceback__", traceback);
+            }
+            if let Some(ref cause) = data.cause {
+                add(".__cause__", cause);
+            }
+            if let Some(ref context) = data.context {
+                add(".__context__", context);
+            }
+        }
+        Payload::Traceback(ref traceback) => {
+            if let Some(ref next) = traceback.next {
+                add(".tb_next", next);
+            }
+            for (name, value) in &traceback.locals {
+                add(&format!(".tb_frame.f_locals['{}']", name), value);
+            }
+        }
+        Payload::Object
+        | Payload::None
+        | Payload::NotImplemented
+        | Payload::Ellipsis
+        | Payload::Int(_)
+        | Payload::Float(_)
+        | Payload::Complex { .. }
+        | Payload::Str(_)
+        | Payload::Bytes(_)
+        | Payload::ByteArray(_)
+        | Payload::Range { .. }
+        | Payload::Builtin(_)
+        | Payload::Code(_)
+        | Payload::Module => {}
+    }
+    references
+}
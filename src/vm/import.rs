@@ -0,0 +1,849 @@
+//! The import system, after `importlib`.
+//!
+//! Imported modules are cached in `sys.modules`. A module that is not there
+//! is looked for by each `Finder` in turn, by its full dotted name and in
+//! the directories of `sys.path`, or of its parent package's `__path__`
+//! for a submodule. The finder that locates it also supplies its source,
+//! which runs in the namespace of a new module, or else already compiled
+//! code, as for frozen modules. Packages are directories with an
+//! `__init__.py`; namespace packages are not supported. A directory searched
+//! may also be a zip archive, or a directory inside one, as for `zipimport`.
+//!
+//! `Vm::run_module` finds a module in the same way but runs it as
+//! `__main__`, for `rustpy -m`.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+
+use compiler::CodeObject;
+use tokenizer::decode;
+use trace;
+
+use super::archive;
+use super::dict::Dict;
+use super::modules;
+use super::object::{Args, ObjectRef, Payload, PyObject, PyResult};
+use super::Vm;
+
+/// Where the code of a module comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModuleSpec {
+    /// The full dotted name of the module.
+    pub name: String,
+    /// The file whose code is the module: its `.py` file, or the
+    /// `__init__.py` of a package. It becomes `__file__`.
+    pub origin: PathBuf,
+    /// For a package, the directories its submodules are found in, which
+    /// become its `__path__`. `None` for other modules.
+    pub submodule_search_locations: Option<Vec<PathBuf>>,
+}
+
+/// Locates modules and reads their source, as the finders of
+/// `sys.meta_path` and their loaders do.
+pub trait Finder {
+    /// The spec of the module `name`, searching the directories of `path`.
+    /// `None` if this finder does not know the module.
+    fn find_spec(&self, name: &str, path: &[PathBuf]) -> Option<ModuleSpec>;
+
+    /// The source of a module this finder found, as bytes to be decoded
+    /// like a source file.
+    fn get_source(&self, spec: &ModuleSpec) -> io::Result<Vec<u8>> {
+        fs::read(&spec.origin)
+    }
+
+    /// The compiled code of a module this finder found, which is run
+    /// instead of compiling its source. `None` to compile the source.
+    fn get_code(&self, _spec: &ModuleSpec) -> Option<Arc<CodeObject>> {
+        None
+    }
+}
+
+/// Finds modules as `.py` files, and packages as directories with an
+/// `__init__.py`, in the directories searched, whether they are on disk or
+/// in a zip archive.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PathFinder;
+
+impl Finder for PathFinder {
+    fn find_spec(&self, name: &str, path: &[PathBuf]) -> Option<ModuleSpec> {
+        let tail = name.rsplit('.').next().unwrap();
+        for directory in path {
+            // An empty entry is the current directory, as for `-c`.
+            let directory = if directory.as_os_str().is_empty() {
+                match env::current_dir() {
+                    Ok(directory) => directory,
+                    Err(_) => continue,
+                }
+            } else {
+                directory.clone()
+            };
+            if !directory.is_dir() {
+                if let Some(spec) = find_in_zip(name, tail, &directory) {
+                    return Some(spec);
+                }
+                continue;
+            }
+            let package = directory.join(tail);
+            let init = package.join("__init__.py");
+            if init.is_file() {
+                return Some(ModuleSpec {
+                    name: name.to_string(),
+                    origin: init,
+                    submodule_search_locations: Some(vec![package]),
+                });
+            }
+            let file = directory.join(format!("{}.py", tail));
+            if file.is_file() {
+                return Some(ModuleSpec {
+                    name: name.to_string(),
+                    origin: file,
+                    submodule_search_locations: None,
+                });
+            }
+        }
+        None
+    }
+
+    fn get_source(&self, spec: &ModuleSpec) -> io::Result<Vec<u8>> {
+        if spec.origin.is_file() {
+            return fs::read(&spec.origin);
+        }
+        let not_found = || io::Error::from(io::ErrorKind::NotFound);
+        let (path, member) = split_zip_path(&spec.origin).ok_or_else(not_found)?;
+        let data = fs::read(path)?;
+        let invalid = |message| io::Error::new(io::ErrorKind::InvalidData, message);
+        let (_, entries) = archive::read_zip(&data).map_err(invalid)?;
+        let entry = entries
+            .iter()
+            .find(|entry| entry.name == member)
+            .ok_or_else(not_found)?;
+        archive::read_zip_member(&data, entry).map_err(invalid)
+    }
+}
+
+/// The zip archive a path is in, and the name of the member, or of the
+/// directory, inside it that the rest of the path names. `None` if no
+/// directory of the path is a file.
+fn split_zip_path(path: &Path) -> Option<(&Path, String)> {
+    let archive = path.ancestors().find(|ancestor| ancestor.is_file())?;
+    let inside = path.strip_prefix(archive).ok()?;
+    let parts: Vec<_> = inside
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect();
+    Some((archive, parts.join("/")))
+}
+
+/// Finds the module `name`, whose last part is `tail`, in the zip archive
+/// `directory` is in.
+fn find_in_zip(name: &str, tail: &str, directory: &Path) -> Option<ModuleSpec> {
+    let (path, inside) = split_zip_path(directory)?;
+    let data = fs::read(path).ok()?;
+    let (_, entries) = archive::read_zip(&data).ok()?;
+    let prefix = if inside.is_empty() {
+        tail.to_string()
+    } else {
+        format!("{}/{}", inside, tail)
+    };
+    let has = |member: &str| entries.iter().any(|entry| entry.name == member);
+    if has(&format!("{}/__init__.py", prefix)) {
+        let package = directory.join(tail);
+        return Some(ModuleSpec {
+            name: name.to_string(),
+            origin: package.join("__init__.py"),
+            submodule_search_locations: Some(vec![package]),
+        });
+    }
+    if has(&format!("{}.py", prefix)) {
+        return Some(ModuleSpec {
+            name: name.to_string(),
+            origin: directory.join(format!("{}.py", tail)),
+            submodule_search_locations: None,
+        });
+    }
+    None
+}
+
+/// A module compiled ahead of time, whose code is run when it is imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrozenModule {
+    /// The full dotted name of the module.
+    pub name: String,
+    /// Whether the module is a package, whose submodules are frozen too.
+    pub is_package: bool,
+    pub code: Arc<CodeObject>,
+}
+
+/// Finds frozen modules by name, wherever they are imported from. Their
+/// `__file__` is the file they were compiled from.
+#[derive(Debug, Clone, Default)]
+pub struct FrozenFinder {
+    modules: HashMap<String, FrozenModule>,
+}
+
+impl FrozenFinder {
+    pub fn new<I: IntoIterator<Item = FrozenModule>>(modules: I) -> FrozenFinder {
+        FrozenFinder {
+            modules: modules
+                .into_iter()
+                .map(|module| (module.name.clone(), module))
+                .collect(),
+        }
+    }
+}
+
+impl Finder for FrozenFinder {
+    fn find_spec(&self, name: &str, _path: &[PathBuf]) -> Option<ModuleSpec> {
+        let module = self.modules.get(name)?;
+        let origin = PathBuf::from(&module.code.filename);
+        let submodule_search_locations = if module.is_package {
+            Some(origin.parent().map(Path::to_path_buf).into_iter().collect())
+        } else {
+            None
+        };
+        Some(ModuleSpec {
+            name: name.to_string(),
+            origin,
+            submodule_search_locations,
+        })
+    }
+
+    fn get_source(&self, spec: &ModuleSpec) -> io::Result<Vec<u8>> {
+        let message = format!("frozen module {} has no source", spec.name);
+        Err(io::Error::new(io::ErrorKind::NotFound, message))
+    }
+
+    fn get_code(&self, spec: &ModuleSpec) -> Option<Arc<CodeObject>> {
+        self.modules
+            .get(&spec.name)
+            .map(|module| module.code.clone())
+    }
+}
+
+/// Adds `__import__` to the builtins, and `sys` with `modules` and `path`
+/// and `builtins`, whose namespace is the builtins, to `sys.modules`.
+pub(super) fn add_builtins(vm: &mut Vm) {
+    let import = vm.new_builtin("__import__", builtin_import);
+    let builtins = vm.builtins().clone();
+    vm.dict_set_str(builtins.as_dict().unwrap(), "__import__", import);
+
+    let sys = modules::builtin_module("sys").unwrap();
+    let sys = sys(vm).expect("the sys module is created at startup");
+    let main = vm.main_module().clone();
+    let modules = vm.modules.clone();
+    vm.dict_set_str(modules.as_dict().unwrap(), "__main__", main);
+    vm.dict_set_str(modules.as_dict().unwrap(), "sys", sys);
+
+    let name = vm.new_str("builtins");
+    vm.dict_set_str(builtins.as_dict().unwrap(), "__name__", name);
+    let module = PyObject::new(
+        Payload::Module,
+        vm.types.module.clone(),
+        Some(builtins.clone()),
+    );
+    vm.dict_set_str(modules.as_dict().unwrap(), "builtins", module);
+}
+
+/// `__import__(name, globals=None, locals=None, fromlist=(), level=0)`.
+fn builtin_import(vm: &mut Vm, args: Args) -> PyResult {
+    let mut parameters = [None, None, None, None, None];
+    let names = ["name", "globals", "locals", "fromlist", "level"];
+    if args.positional.len() > parameters.len() {
+        let message = format!(
+            "__import__() takes at most 5 arguments ({} given)",
+            args.positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    for (slot, value) in parameters.iter_mut().zip(&args.positional) {
+        *slot = Some(value.clone());
+    }
+    for (key, value) in &args.keywords {
+        match names.iter().position(|name| name == key) {
+            Some(index) => parameters[index] = Some(value.clone()),
+            None => {
+                let message = format!("__import__() got an unexpected keyword argument '{}'", key);
+                return Err(vm.new_type_error(message));
+            }
+        }
+    }
+    let name = match parameters[0].as_ref().map(|name| (name, name.as_str())) {
+        Some((_, Some(name))) => name.to_string(),
+        Some((name, None)) => {
+            let message = format!("module name must be str, not {}", name.type_name());
+            return Err(vm.new_type_error(message));
+        }
+        None => {
+            let message = "__import__() missing required argument 'name' (pos 1)".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let globals = match parameters[1] {
+        Some(ref globals) if globals.as_dict().is_some() => Some(globals.clone()),
+        _ => None,
+    };
+    let fromlist = match parameters[3] {
+        Some(ref fromlist) if !Vm::is(fromlist, &vm.none()) => vm.iterate(fromlist)?,
+        _ => Vec::new(),
+    };
+    let level = match parameters[4].as_ref().map(|level| &level.payload) {
+        None => 0,
+        Some(&Payload::Int(level)) if level >= 0 => level as usize,
+        Some(&Payload::Int(_)) => {
+            return Err(vm.new_value_error("level must be >= 0".to_string()));
+        }
+        Some(_) => {
+            let message = "level must be an integer".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    };
+    vm.import(&name, globals.as_ref(), &fromlist, level)
+}
+
+impl Vm {
+    /// A new, empty module named `name`.
+    pub fn new_module(&mut self, name: &str) -> ObjectRef {
+        let dict = PyObject::new(
+            Payload::Dict(RefCell::new(Dict::new())),
+            self.types.dict.clone(),
+            None,
+        );
+        let module = PyObject::new(Payload::Module, self.types.module.clone(), Some(dict));
+        let name = self.new_str(name);
+        self.dict_set_str(module.dict().unwrap().as_dict().unwrap(), "__name__", name);
+        let none = self.none();
+        self.dict_set_str(module.dict().unwrap().as_dict().unwrap(), "__doc__", none);
+        module
+    }
+
+    /// `sys.modules`, the modules imported so far by name.
+    pub fn modules(&self) -> &ObjectRef {
+        &self.modules
+    }
+
+    /// Adds a finder, consulted before those already added and before the
+    /// `PathFinder` that searches `sys.path`.
+    pub fn add_finder<F: Finder + 'static>(&mut self, finder: F) {
+        self.finders.insert(0, Rc::new(finder));
+    }
+
+    /// Sets `sys.argv`, the name of the script and its arguments. It is
+    /// `['']` until set.
+    pub fn set_argv<S: AsRef<str>>(&mut self, argv: &[S]) {
+        let argv: Vec<_> = argv.iter().map(|arg| self.new_str(arg.as_ref())).collect();
+        if let Some(sys) = self.modules.as_dict().unwrap().borrow().get_str("sys") {
+            let current = sys
+                .dict()
+                .unwrap()
+                .as_dict()
+                .unwrap()
+                .borrow()
+                .get_str("argv");
+            if let Some(Payload::List(ref items)) = current.as_ref().map(|list| &list.payload) {
+                *items.borrow_mut() = argv;
+            }
+        }
+    }
+
+    /// Appends a directory to `sys.path`.
+    pub fn add_search_path<P: AsRef<Path>>(&mut self, path: P) {
+        let entry = self.new_str(&path.as_ref().to_string_lossy());
+        if let Some(sys) = self.modules.as_dict().unwrap().borrow().get_str("sys") {
+            let path = sys
+                .dict()
+                .unwrap()
+                .as_dict()
+                .unwrap()
+                .borrow()
+                .get_str("path");
+            if let Some(Payload::List(ref items)) = path.as_ref().map(|path| &path.payload) {
+                items.borrow_mut().push(entry);
+            }
+        }
+    }
+
+    /// `__import__`: imports the module `name`, relative to the package of
+    /// `globals` if `level` is positive, and returns the module the import
+    /// statement binds. That is the top-level package of `import a.b`, and
+    /// the module itself for `from a.b import c`, whose submodules named in
+    /// `fromlist` are imported too.
+    pub fn import(
+        &mut self,
+        name: &str,
+        globals: Option<&ObjectRef>,
+        fromlist: &[ObjectRef],
+        level: usize,
+    ) -> PyResult {
+        let full_name = if level > 0 {
+            let package = self.package_of(globals)?;
+            resolve_name(self, name, &package, level)?
+        } else if name.is_empty() {
+            return Err(self.new_value_error("Empty module name".to_string()));
+        } else {
+            name.to_string()
+        };
+        let module = self.import_module(&full_name)?;
+        if fromlist.is_empty() {
+            let top = name.split('.').next().unwrap();
+            if level == 0 {
+                return self.import_module(top);
+            }
+            if name.is_empty() {
+                return Ok(module);
+            }
+            let cut = full_name.len() - (name.len() - top.len());
+            return self.import_module(&full_name[..cut]);
+        }
+        if self.module_attr(&module, "__path__").is_some() {
+            self.import_fromlist(&module, &full_name, fromlist, false)?;
+        }
+        Ok(module)
+    }
+
+    /// The module `name`, a full dotted name, from `sys.modules` or else
+    /// found and loaded along with its parent packages.
+    pub fn import_module(&mut self, name: &str) -> PyResult {
+        match self.find_and_load(name)? {
+            Some(module) => Ok(module),
+            None => {
+                let message = format!("No module named '{}'", name);
+                Err(self.new_module_not_found_error(message))
+            }
+        }
+    }
+
+    /// Runs the module `name` as `__main__`, as `python -m` does. Its
+    /// parent packages are imported as usual, but the module itself runs in
+    /// the `__main__` module, with `__file__` set, rather than in a module
+    /// of its own; a package runs its `__main__` submodule. The module's
+    /// file becomes `sys.argv[0]`. A module that cannot be found or has no
+    /// source, such as a builtin module, raises `ImportError` before
+    /// anything runs.
+    pub fn run_module(&mut self, name: &str) -> PyResult {
+        let (finder, spec) = self.main_spec(name)?;
+        let code = self.module_code(&*finder, &spec)?;
+        let filename = self.new_str(&spec.origin.to_string_lossy());
+        let package = spec.name.rfind('.').map_or("", |dot| &spec.name[..dot]);
+        let package = self.new_str(package);
+        let globals = self.main.dict().unwrap().clone();
+        self.dict_set_str(globals.as_dict().unwrap(), "__file__", filename.clone());
+        self.dict_set_str(globals.as_dict().unwrap(), "__package__", package);
+        let sys = self.cached_module("sys")?;
+        if let Some(argv) = sys.and_then(|sys| self.module_attr(&sys, "argv")) {
+            if let Payload::List(ref items) = argv.payload {
+                if let Some(first) = items.borrow_mut().first_mut() {
+                    *first = filename;
+                }
+            }
+        }
+        self.run_code(code, &globals)
+    }
+
+    /// The finder and spec of the module `run_module` runs for `name`.
+    fn main_spec(&mut self, name: &str) -> PyResult<(Rc<dyn Finder>, ModuleSpec)> {
+        let import_error = self.exceptions.import_error.clone();
+        if name.starts_with('.') {
+            let message = "Relative module names not supported".to_string();
+            return Err(self.new_error(&import_error, message));
+        }
+        let path = match name.rfind('.') {
+            Some(dot) => {
+                let parent = self.import_module(&name[..dot])?;
+                match self.module_attr(&parent, "__path__") {
+                    Some(path) => self.iterate(&path)?,
+                    None => {
+                        let message = format!(
+                            "No module named '{}'; '{}' is not a package",
+                            name,
+                            &name[..dot]
+                        );
+                        return Err(self.new_module_not_found_error(message));
+                    }
+                }
+            }
+            None => self.sys_path()?,
+        };
+        if modules::builtin_module(name).is_some() || self.is_native_module(name) {
+            let message = format!("No code object available for {}", name);
+            return Err(self.new_error(&import_error, message));
+        }
+        match self.find_spec(name, &path) {
+            Some((_, ref spec)) if spec.submodule_search_locations.is_some() => {
+                if name.ends_with(".__main__") {
+                    let message = "Cannot use package as __main__ module".to_string();
+                    return Err(self.new_error(&import_error, message));
+                }
+                self.import_module(name)?;
+                let main = format!("{}.__main__", name);
+                match self.main_spec(&main) {
+                    Err(err) if Vm::is_instance(&err, &self.exceptions.module_not_found_error) => {
+                        let message = format!(
+                            "No module named {}; '{}' is a package and cannot be directly executed",
+                            main, name
+                        );
+                        Err(self.new_error(&import_error, message))
+                    }
+                    result => result,
+                }
+            }
+            Some(found) => Ok(found),
+            None => {
+                let message = format!("No module named {}", name);
+                Err(self.new_module_not_found_error(message))
+            }
+        }
+    }
+
+    /// Imports the submodules of the package `module` that `fromlist` names
+    /// and that are not already its attributes. `*` stands for the names in
+    /// its `__all__`.
+    fn import_fromlist(
+        &mut self,
+        module: &ObjectRef,
+        name: &str,
+        fromlist: &[ObjectRef],
+        recursive: bool,
+    ) -> PyResult<()> {
+        for item in fromlist {
+            let attribute = match item.as_str() {
+                Some(attribute) => attribute.to_string(),
+                None => {
+                    let place = if recursive {
+                        format!("{}.__all__", name)
+                    } else {
+                        "``from list''".to_string()
+                    };
+                    let message =
+                        format!("Item in {} must be str, not {}", place, item.type_name());
+                    return Err(self.new_type_error(message));
+                }
+            };
+            if attribute == "*" {
+                if recursive {
+                    continue;
+                }
+                if let Some(all) = self.module_attr(module, "__all__") {
+                    let all = self.iterate(&all)?;
+                    self.import_fromlist(module, name, &all, true)?;
+                }
+            } else if self.module_attr(module, &attribute).is_none() {
+                self.find_and_load(&format!("{}.{}", name, attribute))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The builtin module `name`, or the native module registered as
+    /// `name`, created and added to `sys.modules`, if there is one.
+    fn load_builtin_module(&mut self, name: &str) -> PyResult<Option<ObjectRef>> {
+        let module = match modules::builtin_module(name) {
+            Some(init) => init(self)?,
+            None => match self.create_native_module(name)? {
+                Some(module) => module,
+                None => return Ok(None),
+            },
+        };
+        let modules = self.modules.clone();
+        self.dict_set_str(modules.as_dict().unwrap(), name, module.clone());
+        Ok(Some(module))
+    }
+
+    /// The module `name` from `sys.modules`, or else found and loaded after
+    /// its parent packages. `None` if no finder finds it.
+    fn find_and_load(&mut self, name: &str) -> PyResult<Option<ObjectRef>> {
+        if let Some(module) = self.cached_module(name)? {
+            return Ok(Some(module));
+        }
+        let (parent, path) = match name.rfind('.') {
+            Some(dot) => {
+                let parent_name = &name[..dot];
+                let parent = self.import_module(parent_name)?;
+                // Importing the parent may have imported this module too.
+                if let Some(module) = self.cached_module(name)? {
+                    return Ok(Some(module));
+                }
+                if let Some(module) = self.load_builtin_module(name)? {
+                    self.setattr(&parent, &name[dot + 1..], module.clone())?;
+                    return Ok(Some(module));
+                }
+                let path = match self.module_attr(&parent, "__path__") {
+                    Some(path) => self.iterate(&path)?,
+                    None => {
+                        let message = format!(
+                            "No module named '{}'; '{}' is not a package",
+                            name, parent_name
+                        );
+                        return Err(self.new_module_not_found_error(message));
+                    }
+                };
+                (Some((parent, &name[dot + 1..])), path)
+            }
+            None => {
+                if let Some(module) = self.load_builtin_module(name)? {
+                    return Ok(Some(module));
+                }
+                (None, self.sys_path()?)
+            }
+        };
+        let (finder, spec) = match self.find_spec(name, &path) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        let module = self.load(&*finder, &spec)?;
+        if let Some((parent, child)) = parent {
+            self.setattr(&parent, child, module.clone())?;
+        }
+        Ok(Some(module))
+    }
+
+    /// The entries of `sys.path`.
+    fn sys_path(&mut self) -> PyResult<Vec<ObjectRef>> {
+        let sys = self.cached_module("sys")?;
+        match sys.and_then(|sys| self.module_attr(&sys, "path")) {
+            Some(path) => self.iterate(&path),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// The finder that finds the module `name` in the directories `path`,
+    /// the entries of `sys.path` or of its package's `__path__`, and its
+    /// spec.
+    fn find_spec(&self, name: &str, path: &[ObjectRef]) -> Option<(Rc<dyn Finder>, ModuleSpec)> {
+        let path: Vec<PathBuf> = path
+            .iter()
+            .filter_map(|entry| entry.as_str().map(PathBuf::from))
+            .collect();
+        self.finders.iter().find_map(|finder| {
+            finder
+                .find_spec(name, &path)
+                .map(|spec| (finder.clone(), spec))
+        })
+    }
+
+    /// The code of the module a finder found: its compiled code, or its
+    /// source compiled.
+    fn module_code(&mut self, finder: &dyn Finder, spec: &ModuleSpec) -> PyResult<Arc<CodeObject>> {
+        if let Some(code) = finder.get_code(spec) {
+            return Ok(code);
+        }
+        let filename = spec.origin.to_string_lossy().into_owned();
+        match finder.get_source(spec) {
+            Ok(bytes) => match decode(&bytes) {
+                Ok(decoded) => Ok(Arc::new(self.compile_source(&decoded.text, &filename)?)),
+                Err(err) => {
+                    let class = self.exceptions.syntax_error.clone();
+                    Err(self.new_error(&class, err.to_string()))
+                }
+            },
+            Err(err) => {
+                let message = format!("{}: '{}'", err, filename);
+                Err(self.new_error(&self.exceptions.import_error.clone(), message))
+            }
+        }
+    }
+
+    /// The module `name` in `sys.modules`, if it is there. `None` stored
+    /// there blocks the import.
+    fn cached_module(&mut self, name: &str) -> PyResult<Option<ObjectRef>> {
+        let module = self.modules.as_dict().unwrap().borrow().get_str(name);
+        match module {
+            Some(ref module) if Vm::is(module, &self.none()) => {
+                let message = format!("import of {} halted; None in sys.modules", name);
+                Err(self.new_module_not_found_error(message))
+            }
+            module => Ok(module),
+        }
+    }
+
+    /// Runs the code of a module in a new module, which is in
+    /// `sys.modules` while it runs so that circular imports find it, and
+    /// is removed from there again if it raises.
+    fn load(&mut self, finder: &dyn Finder, spec: &ModuleSpec) -> PyResult {
+        let _span = trace::file_span(&spec.origin);
+        let code = self.module_code(finder, spec)?;
+
+        let module = self.new_module(&spec.name);
+        let globals = module.dict().unwrap().clone();
+        let file = self.new_str(&spec.origin.to_string_lossy());
+        self.dict_set_str(globals.as_dict().unwrap(), "__file__", file);
+        let package = match spec.submodule_search_locations {
+            Some(ref locations) => {
+                let locations = locations
+                    .iter()
+                    .map(|location| self.new_str(&location.to_string_lossy()))
+                    .collect();
+                let path = self.new_list(locations);
+                self.dict_set_str(globals.as_dict().unwrap(), "__path__", path);
+                spec.name.as_str()
+            }
+            None => spec.name.rfind('.').map_or("", |dot| &spec.name[..dot]),
+        };
+        let package = self.new_str(package);
+        self.dict_set_str(globals.as_dict().unwrap(), "__package__", package);
+
+        let modules = self.modules.clone();
+        self.dict_set_str(modules.as_dict().unwrap(), &spec.name, module.clone());
+        self.initializing.push(spec.name.clone());
+        let result = self.run_code(code, &globals);
+        self.initializing.pop();
+        if let Err(err) = result {
+            let key = self.new_str(&spec.name);
+            self.dict_remove(modules.as_dict().unwrap(), &key)?;
+            return Err(err);
+        }
+        // The module may have replaced itself in `sys.modules`.
+        Ok(self.cached_module(&spec.name)?.unwrap_or(module))
+    }
+
+    /// The package that relative imports in the module of `globals` are
+    /// relative to: its `__package__`, or else worked out from its
+    /// `__name__`.
+    fn package_of(&mut self, globals: Option<&ObjectRef>) -> PyResult<String> {
+        let get = |name: &str| {
+            globals
+                .and_then(|globals| globals.as_dict())
+                .and_then(|globals| globals.borrow().get_str(name))
+        };
+        if let Some(package) = get("__package__") {
+            if let Some(package) = package.as_str() {
+                return Ok(package.to_string());
+            }
+            if !Vm::is(&package, &self.none()) {
+                let message = "package must be a string".to_string();
+                return Err(self.new_type_error(message));
+            }
+        }
+        let name = match get("__name__") {
+            Some(name) => match name.as_str() {
+                Some(name) => name.to_string(),
+                None => return Err(self.new_type_error("__name__ must be a string".to_string())),
+            },
+            None => {
+                let message = self.new_str("'__name__' not in globals");
+                return Err(self.new_key_error(message));
+            }
+        };
+        if get("__path__").is_some() {
+            return Ok(name);
+        }
+        Ok(name.rfind('.').map_or("", |dot| &name[..dot]).to_string())
+    }
+
+    fn new_module_not_found_error(&mut self, message: String) -> ObjectRef {
+        let class = self.exceptions.module_not_found_error.clone();
+        self.new_error(&class, message)
+    }
+
+    /// The attribute `name` of a module, from its namespace.
+    fn module_attr(&self, module: &ObjectRef, name: &str) -> Option<ObjectRef> {
+        module.dict()?.as_dict()?.borrow().get_str(name)
+    }
+
+    /// `from module import name`: the attribute, or else the submodule of
+    /// that name, which a circular import may not have bound yet.
+    pub(super) fn import_from(&mut self, module: &ObjectRef, name: &str) -> PyResult {
+        match self.getattr(module, name) {
+            Ok(value) => return Ok(value),
+            Err(err) if !Vm::is_instance(&err, &self.exceptions.attribute_error) => {
+                return Err(err)
+            }
+            Err(_) => {}
+        }
+        let module_name = self
+            .module_attr(module, "__name__")
+            .and_then(|name| name.as_str().map(str::to_string));
+        let module_name = match module_name {
+            Some(module_name) => module_name,
+            None => {
+                let message = format!("cannot import name '{}' from '<unknown module name>'", name);
+                return Err(self.new_error(&self.exceptions.import_error.clone(), message));
+            }
+        };
+        if let Some(submodule) = self.cached_module(&format!("{}.{}", module_name, name))? {
+            return Ok(submodule);
+        }
+        let location = match self
+            .module_attr(module, "__file__")
+            .and_then(|file| file.as_str().map(str::to_string))
+        {
+            Some(file) => file,
+            None => "unknown location".to_string(),
+        };
+        let message = if self.initializing.contains(&module_name) {
+            format!(
+                "cannot import name '{}' from partially initialized module '{}' (most likely due \
+                 to a circular import) ({})",
+                name, module_name, location
+            )
+        } else {
+            format!(
+                "cannot import name '{}' from '{}' ({})",
+                name, module_name, location
+            )
+        };
+        Err(self.new_error(&self.exceptions.import_error.clone(), message))
+    }
+
+    /// `from module import *`: binds the names in the module's `__all__`,
+    /// or else its names that do not start with an underscore, in `locals`.
+    pub(super) fn import_star(&mut self, module: &ObjectRef, locals: &ObjectRef) -> PyResult<()> {
+        let names = match self.module_attr(module, "__all__") {
+            Some(all) => self.iterate(&all)?,
+            None => match module.dict() {
+                Some(dict) => dict
+                    .as_dict()
+                    .unwrap()
+                    .borrow()
+                    .iter()
+                    .map(|entry| entry.key.clone())
+                    .filter(|key| key.as_str().is_some_and(|key| !key.starts_with('_')))
+                    .collect(),
+                None => Vec::new(),
+            },
+        };
+        for name in names {
+            let text = match name.as_str() {
+                Some(text) => text.to_string(),
+                None => {
+                    let message = format!(
+                        "Item in {}.__all__ must be str, not {}",
+                        self.module_attr(module, "__name__")
+                            .and_then(|name| name.as_str().map(str::to_string))
+                            .unwrap_or_default(),
+                        name.type_name()
+                    );
+                    return Err(self.new_type_error(message));
+                }
+            };
+            let value = self.getattr(module, &text)?;
+            self.dict_set(locals.as_dict().unwrap(), name, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// The absolute name of the module `name` imported `level` packages up
+/// from `package`.
+fn resolve_name(vm: &mut Vm, name: &str, package: &str, level: usize) -> PyResult<String> {
+    if package.is_empty() {
+        let message = "attempted relative import with no known parent package".to_string();
+        return Err(vm.new_error(&vm.exceptions.import_error.clone(), message));
+    }
+    let parts: Vec<&str> = package.split('.').collect();
+    if level > parts.len() {
+        let message = "attempted relative import beyond top-level package".to_string();
+        return Err(vm.new_error(&vm.exceptions.import_error.clone(), message));
+    }
+    let base = parts[..parts.len() - (level - 1)].join(".");
+    Ok(if name.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, name)
+    })
+}
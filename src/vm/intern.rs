@@ -0,0 +1,123 @@
+//! Shared immutable objects: the small ints, the empty string and the
+//! strings of one Latin-1 character, which are made once, and interned
+//! strings, of which there is one object for each text, as in CPython.
+//!
+//! The names the VM stores under, the identifiers among the constants of
+//! code and the strings passed to `sys.intern` are interned, so that equal
+//! names are the same object and storing under a name that was stored under
+//! before makes no new string. As before Python 3.12, an interned string is
+//! freed when nothing else refers to it; the table only notes it.
+
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use super::object::{ObjectRef, Payload, PyObject};
+use super::types::Types;
+use super::Vm;
+
+/// The smallest of the ints that are shared.
+const SMALL_INT_MIN: i64 = -5;
+
+/// The largest of the ints that are shared.
+const SMALL_INT_MAX: i64 = 256;
+
+/// The objects made once for the VM.
+pub(super) struct Shared {
+    small_ints: Vec<ObjectRef>,
+    empty_str: ObjectRef,
+    /// The strings of the characters U+0000 to U+00FF.
+    characters: Vec<ObjectRef>,
+    /// The interned strings by text. The entries of strings that have been
+    /// freed are dropped when the table has doubled since they last were.
+    interned: HashMap<String, Weak<PyObject>>,
+    pruned_len: usize,
+}
+
+impl Shared {
+    pub(super) fn new(types: &Types) -> Shared {
+        let new_str = |value: String| PyObject::new(Payload::Str(value), types.str.clone(), None);
+        Shared {
+            small_ints: (SMALL_INT_MIN..=SMALL_INT_MAX)
+                .map(|value| PyObject::new(Payload::Int(value), types.int.clone(), None))
+                .collect(),
+            empty_str: new_str(String::new()),
+            characters: (0..=255u8)
+                .map(|byte| new_str(char::from(byte).to_string()))
+                .collect(),
+            interned: HashMap::new(),
+            pruned_len: 0,
+        }
+    }
+
+    /// The shared int `value`, if it is a small one.
+    pub(super) fn small_int(&self, value: i64) -> Option<&ObjectRef> {
+        if (SMALL_INT_MIN..=SMALL_INT_MAX).contains(&value) {
+            Some(&self.small_ints[(value - SMALL_INT_MIN) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// The shared string `value`, if it is empty or one Latin-1 character.
+    pub(super) fn short_str(&self, value: &str) -> Option<&ObjectRef> {
+        if value.len() > 2 {
+            return None;
+        }
+        let mut chars = value.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => Some(&self.empty_str),
+            (Some(c), None) if (c as u32) < 256 => Some(&self.characters[c as usize]),
+            _ => None,
+        }
+    }
+
+    fn interned(&self, value: &str) -> Option<ObjectRef> {
+        self.short_str(value)
+            .cloned()
+            .or_else(|| self.interned.get(value).and_then(Weak::upgrade))
+    }
+
+    fn insert(&mut self, string: &ObjectRef) {
+        let value = string.as_str().unwrap().to_string();
+        self.interned.insert(value, Rc::downgrade(string));
+        if self.interned.len() > 2 * self.pruned_len.max(256) {
+            self.interned.retain(|_, string| string.strong_count() > 0);
+            self.pruned_len = self.interned.len();
+        }
+    }
+}
+
+impl Vm {
+    /// The interned string `value`, made if there is none.
+    pub fn intern(&mut self, value: &str) -> ObjectRef {
+        if let Some(string) = self.shared.interned(value) {
+            return string;
+        }
+        let string = PyObject::new(
+            Payload::Str(value.to_string()),
+            self.types.str.clone(),
+            None,
+        );
+        self.shared.insert(&string);
+        string
+    }
+
+    /// The interned string equal to `string`, a `str` rather than an
+    /// instance of a subclass, which is interned if there is none.
+    pub fn intern_object(&mut self, string: &ObjectRef) -> ObjectRef {
+        let value = string.as_str().expect("only strings are interned");
+        if let Some(interned) = self.shared.interned(value) {
+            return interned;
+        }
+        self.shared.insert(string);
+        string.clone()
+    }
+}
+
+/// Whether a string constant is interned: CPython interns those made of
+/// the characters of identifiers only.
+pub(super) fn is_identifier_like(value: &str) -> bool {
+    value
+        .bytes()
+        .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+}
@@ -0,0 +1,1332 @@
+//! An experimental tier that compiles hot functions to native code with
+//! Cranelift, behind the `jit` feature.
+//!
+//! Each function counts the calls whose arguments are all exact ints and
+//! floats. After `HOT_CALLS` of them it is compiled for the argument types
+//! of the call that tipped it over, so a function called with ints and
+//! with floats may get one specialization of each. Only a numeric subset
+//! of the bytecode is compiled: local variables, int and float arithmetic
+//! and comparisons, branches, `for` loops over `range`, and calls of the
+//! function itself. A function using anything else is never compiled.
+//!
+//! Compiled code has no side effects, so a guard that fails inside it, such
+//! as an int operation that would overflow or a division by zero, bails out
+//! and the call is run again from the start by the interpreter, which
+//! raises whatever should be raised. Guards on the globals the code relies
+//! on, `range` and the function's own name, are checked before each call.
+
+use std::collections::HashMap;
+use std::mem;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use cranelift_codegen::ir::condcodes::{FloatCC, IntCC};
+use cranelift_codegen::ir::types::{F64, I32, I64, I8};
+use cranelift_codegen::ir::{
+    AbiParam, Block, FuncRef, InstBuilder, MemFlags, StackSlotData, StackSlotKind, Type, Value,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, Linkage, Module};
+
+use ast::{CmpOperator, Constant, Operator, UnaryOperator};
+use compiler::{
+    CodeConstant, CodeObject, Instruction, CO_COROUTINE, CO_GENERATOR, CO_VARARGS, CO_VARKEYWORDS,
+};
+
+use super::{Args, Function, ObjectRef, Payload, Vm};
+
+/// Calls of a function with numeric arguments before it is compiled.
+const HOT_CALLS: u32 = 200;
+/// The most specializations of one function, by argument types.
+const MAX_SPECIALIZATIONS: usize = 4;
+/// Ints beyond this magnitude may not convert to a float exactly.
+const EXACT_FLOAT_INT: i64 = 1 << 53;
+
+/// Compiled code: the arguments, as bits, the calls it may still make
+/// before the recursion limit, and where to write the result. Returns 0
+/// once the result is written, or 1 to bail out to the interpreter.
+type Entry = unsafe extern "C" fn(*const u64, i64, *mut u64) -> u32;
+
+/// The JIT state of a `Vm`.
+pub(super) struct Jit {
+    enabled: bool,
+    /// Created by the first compilation; `None` after that only if the
+    /// host is not supported, which disables the tier.
+    module: Option<JITModule>,
+    /// Keyed by the address of the code, which the profile keeps alive.
+    profiles: HashMap<*const CodeObject, Profile>,
+    functions: usize,
+}
+
+struct Profile {
+    /// Keeps the address of the code from being reused by other code.
+    #[allow(dead_code)]
+    code: Arc<CodeObject>,
+    calls: u32,
+    /// Set once the code failed to compile, so that it is not tried again.
+    rejected: bool,
+    compiled: Vec<Compiled>,
+}
+
+struct Compiled {
+    params: Vec<Kind>,
+    returns: Kind,
+    guards: Vec<Guard>,
+    entry: Entry,
+}
+
+/// What compiled code assumes about the globals of its function.
+#[derive(Debug, Clone, PartialEq)]
+enum Guard {
+    /// `range` is the builtin.
+    Range,
+    /// The name is bound to the function itself.
+    Recursive(String),
+}
+
+/// The type of a value in compiled code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Kind {
+    Int,
+    Float,
+    Bool,
+    None,
+}
+
+impl Kind {
+    fn is_number(self) -> bool {
+        self != Kind::None
+    }
+
+    fn ty(self) -> Type {
+        match self {
+            Kind::Int => I64,
+            Kind::Float => F64,
+            Kind::Bool | Kind::None => I8,
+        }
+    }
+}
+
+/// What an entry of the value stack holds, as far as the analysis knows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Item {
+    Value(Kind),
+    /// The builtin `range`, about to be called.
+    Range,
+    /// A range object, about to be iterated.
+    RangeObject,
+    Iterator,
+    /// The function being compiled, about to be called.
+    Function,
+}
+
+impl Jit {
+    pub(super) fn new() -> Jit {
+        Jit {
+            enabled: true,
+            module: None,
+            profiles: HashMap::new(),
+            functions: 0,
+        }
+    }
+
+    fn module(&mut self) -> Option<&mut JITModule> {
+        if self.module.is_none() {
+            let flags = [("opt_level", "speed")];
+            match JITBuilder::with_flags(&flags, default_libcall_names()) {
+                Ok(builder) => self.module = Some(JITModule::new(builder)),
+                Err(_) => {
+                    self.enabled = false;
+                    return None;
+                }
+            }
+        }
+        self.module.as_mut()
+    }
+}
+
+impl Vm {
+    /// Turns the JIT tier on or off; it is on by default. Code already
+    /// compiled is kept for when it is turned on again.
+    pub fn set_jit_enabled(&mut self, enabled: bool) {
+        self.jit.enabled = enabled;
+    }
+
+    /// Runs a call of `function` in compiled code, compiling it first if it
+    /// has become hot. `None` means the interpreter should run the call.
+    pub(super) fn call_compiled(&mut self, function: &Function, args: &Args) -> Option<ObjectRef> {
+        if !self.jit.enabled || !compilable(function, args) {
+            return None;
+        }
+        let mut params = Vec::with_capacity(args.positional.len());
+        let mut bits = Vec::with_capacity(args.positional.len());
+        for arg in &args.positional {
+            let (kind, value) = self.argument(arg)?;
+            params.push(kind);
+            bits.push(value);
+        }
+
+        let key = Arc::as_ptr(&function.code);
+        let profile = self.jit.profiles.entry(key).or_insert_with(|| Profile {
+            code: function.code.clone(),
+            calls: 0,
+            rejected: false,
+            compiled: Vec::new(),
+        });
+        if profile.rejected {
+            return None;
+        }
+        let index = match profile
+            .compiled
+            .iter()
+            .position(|compiled| compiled.params == params)
+        {
+            Some(index) => index,
+            None => {
+                if profile.compiled.len() >= MAX_SPECIALIZATIONS {
+                    return None;
+                }
+                profile.calls += 1;
+                if profile.calls < HOT_CALLS {
+                    return None;
+                }
+                profile.calls = 0;
+                let compiled = self.compile(function, &params);
+                let profile = self.jit.profiles.get_mut(&key).unwrap();
+                match compiled {
+                    Some(compiled) => profile.compiled.push(compiled),
+                    None => {
+                        profile.rejected = true;
+                        return None;
+                    }
+                }
+                profile.compiled.len() - 1
+            }
+        };
+
+        let compiled = &self.jit.profiles[&key].compiled[index];
+        let (entry, returns) = (compiled.entry, compiled.returns);
+        if !compiled
+            .guards
+            .iter()
+            .all(|guard| self.guard_holds(function, guard))
+        {
+            return None;
+        }
+        let budget = self.recursion_limit as i64 - self.depth as i64;
+        let mut result = 0;
+        // The code was compiled for exactly these argument types.
+        if unsafe { entry(bits.as_ptr(), budget, &mut result) } != 0 {
+            return None;
+        }
+        Some(match returns {
+            Kind::Int => self.new_int(result as i64),
+            Kind::Float => self.new_float(f64::from_bits(result)),
+            Kind::Bool => self.new_bool(result != 0),
+            Kind::None => self.none(),
+        })
+    }
+
+    /// The kind and bits of an exact int or float argument.
+    fn argument(&self, arg: &ObjectRef) -> Option<(Kind, u64)> {
+        let class = arg.class();
+        match arg.payload {
+            Payload::Int(value) if Rc::ptr_eq(&class, &self.types.int) => {
+                Some((Kind::Int, value as u64))
+            }
+            Payload::Float(value) if Rc::ptr_eq(&class, &self.types.float) => {
+                Some((Kind::Float, value.to_bits()))
+            }
+            _ => None,
+        }
+    }
+
+    fn guard_holds(&self, function: &Function, guard: &Guard) -> bool {
+        let globals = function.globals.as_dict().unwrap().borrow();
+        match *guard {
+            Guard::Range => {
+                globals.get_str("range").is_none()
+                    && globals
+                        .get_str("__builtins__")
+                        .is_none_or(|builtins| builtins.as_dict().is_none())
+                    && match self.builtins.as_dict().unwrap().borrow().get_str("range") {
+                        Some(range) => Rc::ptr_eq(&range, &self.types.range),
+                        None => false,
+                    }
+            }
+            Guard::Recursive(ref name) => match globals.get_str(name) {
+                Some(value) => match value.payload {
+                    Payload::Function(ref bound) => Arc::ptr_eq(&bound.code, &function.code),
+                    _ => false,
+                },
+                None => false,
+            },
+        }
+    }
+
+    /// The item a global name stands for in compiled code, and the guard
+    /// that keeps it so.
+    fn resolve_global(&self, function: &Function, name: &str) -> Option<(Item, Guard)> {
+        let guard = if name == function.code.name {
+            Guard::Recursive(name.to_string())
+        } else if name == "range" {
+            Guard::Range
+        } else {
+            return None;
+        };
+        if !self.guard_holds(function, &guard) {
+            return None;
+        }
+        let item = match guard {
+            Guard::Range => Item::Range,
+            Guard::Recursive(_) => Item::Function,
+        };
+        Some((item, guard))
+    }
+
+    fn compile(&mut self, function: &Function, params: &[Kind]) -> Option<Compiled> {
+        let code = &function.code;
+        // A call of the function itself has the type the function returns,
+        // which is what is being worked out: each is tried in turn.
+        let (analysis, returns) = [Kind::Int, Kind::Float, Kind::Bool, Kind::None]
+            .iter()
+            .find_map(|&returns| {
+                let resolve = |name: &str| self.resolve_global(function, name);
+                Analysis::run(code, params, returns, &resolve).map(|analysis| (analysis, returns))
+            })?;
+
+        let name = format!("{}#{}", code.qualname, self.jit.functions);
+        self.jit.functions += 1;
+        let module = self.jit.module()?;
+        let entry = translate(module, &name, code, params, &analysis)?;
+        Some(Compiled {
+            params: params.to_vec(),
+            returns,
+            guards: analysis.guards,
+            entry,
+        })
+    }
+}
+
+/// Whether a call binds its arguments the way compiled code expects: each
+/// parameter positionally, with no cells, defaults or generator to set up.
+fn compilable(function: &Function, args: &Args) -> bool {
+    let code = &function.code;
+    args.keywords.is_empty()
+        && args.positional.len() == code.argcount
+        && code.kwonlyargcount == 0
+        && code.flags & (CO_VARARGS | CO_VARKEYWORDS | CO_GENERATOR | CO_COROUTINE) == 0
+        && code.cellvars.is_empty()
+        && code.freevars.is_empty()
+}
+
+/// The state of the value stack and the local variables before an
+/// instruction.
+#[derive(Debug, Clone, PartialEq)]
+struct State {
+    stack: Vec<Item>,
+    /// Whether each local variable is assigned on every path here.
+    assigned: Vec<bool>,
+}
+
+/// Type inference over the bytecode of a function, for one signature.
+struct Analysis {
+    /// `None` for unreachable instructions.
+    states: Vec<Option<State>>,
+    /// The one kind each local variable has wherever it is assigned.
+    locals: Vec<Option<Kind>>,
+    params: Vec<Kind>,
+    returns: Kind,
+    guards: Vec<Guard>,
+}
+
+impl Analysis {
+    /// Infers the kinds of the values in `code`, assuming that it returns
+    /// `returns`. `None` if the code cannot be compiled.
+    fn run(
+        code: &CodeObject,
+        params: &[Kind],
+        returns: Kind,
+        resolve: &dyn Fn(&str) -> Option<(Item, Guard)>,
+    ) -> Option<Analysis> {
+        let mut analysis = Analysis {
+            states: vec![None; code.instructions.len()],
+            locals: vec![None; code.varnames.len()],
+            params: params.to_vec(),
+            returns,
+            guards: Vec::new(),
+        };
+        let mut assigned = vec![false; code.varnames.len()];
+        for (index, &kind) in params.iter().enumerate() {
+            analysis.locals[index] = Some(kind);
+            assigned[index] = true;
+        }
+        analysis.merge(
+            0,
+            State {
+                stack: Vec::new(),
+                assigned,
+            },
+        )?;
+        let mut pending = vec![0];
+        while let Some(index) = pending.pop() {
+            let state = analysis.states[index].clone().unwrap();
+            for (successor, state) in analysis.step(code, index, state, resolve)? {
+                if analysis.merge(successor, state)? {
+                    pending.push(successor);
+                }
+            }
+        }
+        Some(analysis)
+    }
+
+    /// Joins `state` into the state before an instruction, returning
+    /// whether that changed it. The stacks of the two must agree.
+    fn merge(&mut self, index: usize, state: State) -> Option<bool> {
+        match *self.states.get_mut(index)? {
+            Some(ref mut existing) => {
+                if existing.stack != state.stack {
+                    return None;
+                }
+                let mut changed = false;
+                for (existing, assigned) in existing.assigned.iter_mut().zip(state.assigned) {
+                    if *existing && !assigned {
+                        *existing = false;
+                        changed = true;
+                    }
+                }
+                Some(changed)
+            }
+            ref mut empty => {
+                *empty = Some(state);
+                Some(true)
+            }
+        }
+    }
+
+    /// The states after the instruction at `index`, with the instructions
+    /// they lead to.
+    fn step(
+        &mut self,
+        code: &CodeObject,
+        index: usize,
+        mut state: State,
+        resolve: &dyn Fn(&str) -> Option<(Item, Guard)>,
+    ) -> Option<Vec<(usize, State)>> {
+        let stack = &mut state.stack;
+        let next = index + 1;
+        match code.instructions[index] {
+            Instruction::Nop => {}
+            Instruction::PopTop => {
+                stack.pop()?;
+            }
+            Instruction::RotTwo => {
+                let len = stack.len();
+                if len < 2 {
+                    return None;
+                }
+                stack.swap(len - 1, len - 2);
+            }
+            Instruction::RotThree => {
+                let top = stack.pop()?;
+                let len = stack.len();
+                if len < 2 {
+                    return None;
+                }
+                stack.insert(len - 2, top);
+            }
+            Instruction::DupTop => {
+                let top = *stack.last()?;
+                stack.push(top);
+            }
+            Instruction::BuildTuple(count) if unpacked_at_once(code, index) => {
+                let len = stack.len();
+                if len < count {
+                    return None;
+                }
+                stack[len - count..].reverse();
+            }
+            Instruction::UnpackSequence(_) if index > 0 && unpacked_at_once(code, index - 1) => {}
+            Instruction::LoadConst(constant) => {
+                let kind = match code.constants[constant] {
+                    CodeConstant::Value(ref value) => constant_kind(value)?,
+                    CodeConstant::Code(_) => return None,
+                };
+                stack.push(Item::Value(kind));
+            }
+            Instruction::LoadFast(local) => {
+                if !state.assigned[local] {
+                    return None;
+                }
+                stack.push(Item::Value(self.locals[local]?));
+            }
+            Instruction::StoreFast(local) => {
+                let kind = pop_value(stack)?;
+                match self.locals[local] {
+                    Some(existing) if existing != kind => return None,
+                    _ => self.locals[local] = Some(kind),
+                }
+                state.assigned[local] = true;
+            }
+            Instruction::LoadGlobal(name) => {
+                let (item, guard) = resolve(&code.names[name])?;
+                if !self.guards.contains(&guard) {
+                    self.guards.push(guard);
+                }
+                stack.push(item);
+            }
+            Instruction::BinaryOp(operator) | Instruction::InplaceOp(operator) => {
+                let right = pop_value(stack)?;
+                let left = pop_value(stack)?;
+                stack.push(Item::Value(binary_kind(operator, left, right)?));
+            }
+            Instruction::UnaryOp(operator) => {
+                let operand = pop_value(stack)?;
+                stack.push(Item::Value(unary_kind(operator, operand)?));
+            }
+            Instruction::CompareOp(operator) => {
+                let right = pop_value(stack)?;
+                let left = pop_value(stack)?;
+                if !left.is_number() || !right.is_number() || int_condition(operator).is_none() {
+                    return None;
+                }
+                stack.push(Item::Value(Kind::Bool));
+            }
+            Instruction::CallFunction(count) => {
+                if stack.len() <= count {
+                    return None;
+                }
+                let args = stack.split_off(stack.len() - count);
+                let result = match stack.pop()? {
+                    Item::Range if (1..=3).contains(&count) => {
+                        for arg in args {
+                            match arg {
+                                Item::Value(Kind::Int) | Item::Value(Kind::Bool) => {}
+                                _ => return None,
+                            }
+                        }
+                        Item::RangeObject
+                    }
+                    Item::Function => {
+                        let matches = args.len() == self.params.len()
+                            && args
+                                .iter()
+                                .zip(&self.params)
+                                .all(|(&arg, &param)| arg == Item::Value(param));
+                        if !matches {
+                            return None;
+                        }
+                        Item::Value(self.returns)
+                    }
+                    _ => return None,
+                };
+                stack.push(result);
+            }
+            Instruction::GetIter => match stack.pop()? {
+                Item::RangeObject => stack.push(Item::Iterator),
+                _ => return None,
+            },
+            Instruction::ForIter(target) => {
+                if stack.last() != Some(&Item::Iterator) {
+                    return None;
+                }
+                let mut exhausted = state.clone();
+                exhausted.stack.pop();
+                state.stack.push(Item::Value(Kind::Int));
+                return Some(vec![(target, exhausted), (next, state)]);
+            }
+            Instruction::Jump(target) => return Some(vec![(target, state)]),
+            Instruction::PopJumpIfFalse(target) | Instruction::PopJumpIfTrue(target) => {
+                pop_value(stack)?;
+                return Some(vec![(target, state.clone()), (next, state)]);
+            }
+            Instruction::JumpIfFalseOrPop(target) | Instruction::JumpIfTrueOrPop(target) => {
+                match stack.last() {
+                    Some(&Item::Value(_)) => {}
+                    _ => return None,
+                }
+                let jumped = state.clone();
+                state.stack.pop();
+                return Some(vec![(target, jumped), (next, state)]);
+            }
+            Instruction::ReturnValue => {
+                if pop_value(stack)? != self.returns {
+                    return None;
+                }
+                return Some(Vec::new());
+            }
+            _ => return None,
+        }
+        Some(vec![(next, state)])
+    }
+}
+
+/// Whether the tuple built at `index` is unpacked straight away, as in
+/// `a, b = b, a`, which only reverses the top of the stack.
+fn unpacked_at_once(code: &CodeObject, index: usize) -> bool {
+    match (code.instructions[index], code.instructions.get(index + 1)) {
+        (Instruction::BuildTuple(count), Some(&Instruction::UnpackSequence(unpacked))) => {
+            count == unpacked
+                && !code
+                    .instructions
+                    .iter()
+                    .any(|instruction| instruction.target() == Some(index + 1))
+        }
+        _ => false,
+    }
+}
+
+fn pop_value(stack: &mut Vec<Item>) -> Option<Kind> {
+    match stack.pop()? {
+        Item::Value(kind) => Some(kind),
+        _ => None,
+    }
+}
+
+fn constant_kind(constant: &Constant) -> Option<Kind> {
+    match *constant {
+        Constant::Int(_) => Some(Kind::Int),
+        Constant::Float(_) => Some(Kind::Float),
+        Constant::Bool(_) => Some(Kind::Bool),
+        Constant::None => Some(Kind::None),
+        _ => None,
+    }
+}
+
+/// The kind of the result of a binary operator, if it is compiled.
+fn binary_kind(operator: Operator, left: Kind, right: Kind) -> Option<Kind> {
+    if !left.is_number() || !right.is_number() {
+        return None;
+    }
+    if left == Kind::Float || right == Kind::Float {
+        return match operator {
+            Operator::Add | Operator::Sub | Operator::Mult | Operator::Div => Some(Kind::Float),
+            _ => None,
+        };
+    }
+    match operator {
+        Operator::Add
+        | Operator::Sub
+        | Operator::Mult
+        | Operator::FloorDiv
+        | Operator::Mod
+        | Operator::LShift
+        | Operator::RShift => Some(Kind::Int),
+        Operator::Div => Some(Kind::Float),
+        Operator::BitAnd | Operator::BitOr | Operator::BitXor => {
+            if left == Kind::Bool && right == Kind::Bool {
+                Some(Kind::Bool)
+            } else {
+                Some(Kind::Int)
+            }
+        }
+        Operator::MatMult | Operator::Pow => None,
+    }
+}
+
+fn unary_kind(operator: UnaryOperator, operand: Kind) -> Option<Kind> {
+    match (operator, operand) {
+        (UnaryOperator::Not, _) => Some(Kind::Bool),
+        (_, Kind::None) => None,
+        (UnaryOperator::Invert, Kind::Float) => None,
+        (_, Kind::Float) => Some(Kind::Float),
+        (_, _) => Some(Kind::Int),
+    }
+}
+
+fn int_condition(operator: CmpOperator) -> Option<IntCC> {
+    match operator {
+        CmpOperator::Eq => Some(IntCC::Equal),
+        CmpOperator::NotEq => Some(IntCC::NotEqual),
+        CmpOperator::Lt => Some(IntCC::SignedLessThan),
+        CmpOperator::LtE => Some(IntCC::SignedLessThanOrEqual),
+        CmpOperator::Gt => Some(IntCC::SignedGreaterThan),
+        CmpOperator::GtE => Some(IntCC::SignedGreaterThanOrEqual),
+        _ => None,
+    }
+}
+
+fn float_condition(operator: CmpOperator) -> FloatCC {
+    match operator {
+        CmpOperator::Eq => FloatCC::Equal,
+        CmpOperator::NotEq => FloatCC::NotEqual,
+        CmpOperator::Lt => FloatCC::LessThan,
+        CmpOperator::LtE => FloatCC::LessThanOrEqual,
+        CmpOperator::Gt => FloatCC::GreaterThan,
+        _ => FloatCC::GreaterThanOrEqual,
+    }
+}
+
+/// A value stack entry in generated code.
+#[derive(Clone, Copy)]
+enum Slot {
+    Value(Kind, Value),
+    Range,
+    /// The next value, the stop and the step of a range or its iterator.
+    RangeObject(Value, Value, Value),
+    Iterator(Value, Value, Value),
+    Function,
+}
+
+/// The values of a stack, as the arguments of a block.
+fn flatten(stack: &[Slot]) -> Vec<Value> {
+    let mut values = Vec::new();
+    for slot in stack {
+        match *slot {
+            Slot::Value(_, value) => values.push(value),
+            Slot::RangeObject(next, stop, step) | Slot::Iterator(next, stop, step) => {
+                values.extend_from_slice(&[next, stop, step])
+            }
+            Slot::Range | Slot::Function => {}
+        }
+    }
+    values
+}
+
+/// Compiles the analysed code and returns its entry point.
+fn translate(
+    module: &mut JITModule,
+    name: &str,
+    code: &CodeObject,
+    params: &[Kind],
+    analysis: &Analysis,
+) -> Option<Entry> {
+    let pointer = module.target_config().pointer_type();
+    let mut context = module.make_context();
+    let signature = &mut context.func.signature;
+    signature.params.push(AbiParam::new(pointer));
+    signature.params.push(AbiParam::new(I64));
+    signature.params.push(AbiParam::new(pointer));
+    signature.returns.push(AbiParam::new(I32));
+    let id = module
+        .declare_function(name, Linkage::Local, &context.func.signature)
+        .ok()?;
+
+    let mut builder_context = FunctionBuilderContext::new();
+    let mut builder = FunctionBuilder::new(&mut context.func, &mut builder_context);
+    let recurse = if analysis.guards.iter().any(|guard| match *guard {
+        Guard::Recursive(_) => true,
+        Guard::Range => false,
+    }) {
+        Some(module.declare_func_in_func(id, builder.func))
+    } else {
+        None
+    };
+
+    let start = builder.create_block();
+    builder.append_block_params_for_function_params(start);
+    let bail = builder.create_block();
+    builder.switch_to_block(start);
+    let arguments = builder.block_params(start)[0];
+    let budget = builder.block_params(start)[1];
+    let out = builder.block_params(start)[2];
+
+    let mut translator = Translator {
+        builder,
+        analysis,
+        blocks: vec![None; code.instructions.len()],
+        bail,
+        pointer,
+        out,
+        budget,
+        recurse,
+    };
+    translator.start(code, params, arguments)?;
+    translator.body(code)?;
+    translator.builder.switch_to_block(bail);
+    let failed = translator.builder.ins().iconst(I32, 1);
+    translator.builder.ins().return_(&[failed]);
+    translator.builder.seal_all_blocks();
+    translator.builder.finalize();
+
+    module.define_function(id, &mut context).ok()?;
+    module.clear_context(&mut context);
+    module.finalize_definitions().ok()?;
+    let address = module.get_finalized_function(id);
+    // The signature declared above is the one of `Entry`.
+    Some(unsafe { mem::transmute::<*const u8, Entry>(address) })
+}
+
+struct Translator<'a> {
+    builder: FunctionBuilder<'a>,
+    analysis: &'a Analysis,
+    /// The block of each instruction that is jumped to, and of the first.
+    blocks: Vec<Option<Block>>,
+    bail: Block,
+    pointer: Type,
+    out: Value,
+    /// The calls that may still be made; the function's own calls pass one
+    /// less.
+    budget: Value,
+    recurse: Option<FuncRef>,
+}
+
+impl<'a> Translator<'a> {
+    /// Creates the blocks, then checks the budget, loads the arguments
+    /// into their variables, and jumps to the first instruction.
+    fn start(&mut self, code: &CodeObject, params: &[Kind], arguments: Value) -> Option<()> {
+        self.blocks[0] = Some(self.builder.create_block());
+        for (index, state) in self.analysis.states.iter().enumerate() {
+            if state.is_none() {
+                continue;
+            }
+            if let Some(target) = code.instructions[index].target() {
+                if self.blocks[target].is_none() {
+                    self.blocks[target] = Some(self.builder.create_block());
+                }
+            }
+        }
+        for (index, block) in self.blocks.iter().enumerate() {
+            let block = match *block {
+                Some(block) => block,
+                None => continue,
+            };
+            for item in &self.analysis.states[index].as_ref()?.stack {
+                match *item {
+                    Item::Value(kind) => {
+                        self.builder.append_block_param(block, kind.ty());
+                    }
+                    Item::RangeObject | Item::Iterator => {
+                        for _ in 0..3 {
+                            self.builder.append_block_param(block, I64);
+                        }
+                    }
+                    Item::Range | Item::Function => {}
+                }
+            }
+        }
+
+        let exhausted = self
+            .builder
+            .ins()
+            .icmp_imm(IntCC::SignedLessThanOrEqual, self.budget, 0);
+        self.bail_if(exhausted);
+        for (index, local) in self.analysis.locals.iter().enumerate() {
+            if let Some(kind) = *local {
+                self.builder
+                    .declare_var(Variable::from_u32(index as u32), kind.ty());
+            }
+        }
+        for (index, kind) in params.iter().enumerate() {
+            let value = self.builder.ins().load(
+                kind.ty(),
+                MemFlags::trusted(),
+                arguments,
+                (index * 8) as i32,
+            );
+            self.builder
+                .def_var(Variable::from_u32(index as u32), value);
+        }
+        self.budget = self.builder.ins().iadd_imm(self.budget, -1);
+        self.builder.ins().jump(self.blocks[0].unwrap(), &[]);
+        Some(())
+    }
+
+    fn body(&mut self, code: &CodeObject) -> Option<()> {
+        let mut stack = Vec::new();
+        // Whether the previous instruction falls through to this one.
+        let mut open = false;
+        for index in 0..code.instructions.len() {
+            let state = match self.analysis.states[index] {
+                Some(ref state) => state,
+                None => {
+                    open = false;
+                    continue;
+                }
+            };
+            if let Some(block) = self.blocks[index] {
+                if open {
+                    self.builder.ins().jump(block, &flatten(&stack));
+                }
+                self.builder.switch_to_block(block);
+                stack = self.block_stack(block, &state.stack);
+            } else if !open {
+                return None;
+            }
+            open = self.instruction(code, index, &mut stack)?;
+        }
+        if open {
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    /// The stack at the start of a block, from its parameters.
+    fn block_stack(&self, block: Block, items: &[Item]) -> Vec<Slot> {
+        let mut params = self.builder.block_params(block).iter().cloned();
+        items
+            .iter()
+            .map(|item| match *item {
+                Item::Value(kind) => Slot::Value(kind, params.next().unwrap()),
+                Item::Range => Slot::Range,
+                Item::Function => Slot::Function,
+                Item::RangeObject | Item::Iterator => {
+                    let next = params.next().unwrap();
+                    let stop = params.next().unwrap();
+                    let step = params.next().unwrap();
+                    if *item == Item::Iterator {
+                        Slot::Iterator(next, stop, step)
+                    } else {
+                        Slot::RangeObject(next, stop, step)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Generates the code of an instruction, returning whether it falls
+    /// through to the next one.
+    fn instruction(
+        &mut self,
+        code: &CodeObject,
+        index: usize,
+        stack: &mut Vec<Slot>,
+    ) -> Option<bool> {
+        let instruction = code.instructions[index];
+        match instruction {
+            Instruction::Nop => {}
+            Instruction::PopTop => {
+                stack.pop();
+            }
+            Instruction::RotTwo => {
+                let len = stack.len();
+                stack.swap(len - 1, len - 2);
+            }
+            Instruction::RotThree => {
+                let top = stack.pop()?;
+                let len = stack.len();
+                stack.insert(len - 2, top);
+            }
+            Instruction::DupTop => {
+                let top = *stack.last()?;
+                stack.push(top);
+            }
+            Instruction::BuildTuple(count) if unpacked_at_once(code, index) => {
+                let len = stack.len();
+                stack[len - count..].reverse();
+            }
+            Instruction::UnpackSequence(_) if index > 0 && unpacked_at_once(code, index - 1) => {}
+            Instruction::LoadConst(constant) => {
+                let value = match code.constants[constant] {
+                    CodeConstant::Value(ref value) => value,
+                    CodeConstant::Code(_) => return None,
+                };
+                stack.push(match *value {
+                    Constant::Int(value) => {
+                        Slot::Value(Kind::Int, self.builder.ins().iconst(I64, value))
+                    }
+                    Constant::Float(value) => {
+                        Slot::Value(Kind::Float, self.builder.ins().f64const(value))
+                    }
+                    Constant::Bool(value) => {
+                        Slot::Value(Kind::Bool, self.builder.ins().iconst(I8, i64::from(value)))
+                    }
+                    _ => Slot::Value(Kind::None, self.builder.ins().iconst(I8, 0)),
+                });
+            }
+            Instruction::LoadFast(local) => {
+                let kind = self.analysis.locals[local]?;
+                let value = self.builder.use_var(Variable::from_u32(local as u32));
+                stack.push(Slot::Value(kind, value));
+            }
+            Instruction::StoreFast(local) => {
+                let (_, value) = pop_slot(stack)?;
+                self.builder
+                    .def_var(Variable::from_u32(local as u32), value);
+            }
+            Instruction::LoadGlobal(name) => {
+                // The analysis only accepts the function's own name and
+                // `range`.
+                if code.names[name] == code.name {
+                    stack.push(Slot::Function);
+                } else {
+                    stack.push(Slot::Range);
+                }
+            }
+            Instruction::BinaryOp(operator) | Instruction::InplaceOp(operator) => {
+                let right = pop_slot(stack)?;
+                let left = pop_slot(stack)?;
+                let result = self.binary(operator, left, right)?;
+                stack.push(result);
+            }
+            Instruction::UnaryOp(operator) => {
+                let operand = pop_slot(stack)?;
+                let result = self.unary(operator, operand)?;
+                stack.push(result);
+            }
+            Instruction::CompareOp(operator) => {
+                let right = pop_slot(stack)?;
+                let left = pop_slot(stack)?;
+                let result = self.compare(operator, left, right)?;
+                stack.push(Slot::Value(Kind::Bool, result));
+            }
+            Instruction::CallFunction(count) => {
+                let args = stack.split_off(stack.len() - count);
+                let result = match stack.pop()? {
+                    Slot::Range => self.range(&args),
+                    Slot::Function => self.recurse(&args)?,
+                    _ => return None,
+                };
+                stack.push(result);
+            }
+            Instruction::GetIter => match stack.pop()? {
+                Slot::RangeObject(next, stop, step) => stack.push(Slot::Iterator(next, stop, step)),
+                _ => return None,
+            },
+            Instruction::ForIter(target) => {
+                let (next, stop, step) = match stack.pop()? {
+                    Slot::Iterator(next, stop, step) => (next, stop, step),
+                    _ => return None,
+                };
+                let exhausted = self.blocks[target]?;
+                let body = self.builder.create_block();
+                let upwards = self
+                    .builder
+                    .ins()
+                    .icmp_imm(IntCC::SignedGreaterThan, step, 0);
+                let below = self.builder.ins().icmp(IntCC::SignedLessThan, next, stop);
+                let above = self
+                    .builder
+                    .ins()
+                    .icmp(IntCC::SignedGreaterThan, next, stop);
+                let more = self.builder.ins().select(upwards, below, above);
+                let values = flatten(stack);
+                self.builder.ins().brif(more, body, &[], exhausted, &values);
+                self.builder.switch_to_block(body);
+                let (advanced, overflow) = self.builder.ins().sadd_overflow(next, step);
+                self.bail_if(overflow);
+                stack.push(Slot::Iterator(advanced, stop, step));
+                stack.push(Slot::Value(Kind::Int, next));
+            }
+            Instruction::Jump(target) => {
+                let block = self.blocks[target]?;
+                self.builder.ins().jump(block, &flatten(stack));
+                return Some(false);
+            }
+            Instruction::PopJumpIfFalse(target) | Instruction::PopJumpIfTrue(target) => {
+                let (kind, value) = pop_slot(stack)?;
+                let condition = self.truthy(kind, value);
+                let jump_if = matches!(instruction, Instruction::PopJumpIfTrue(_));
+                self.branch(condition, jump_if, target, &flatten(stack))?;
+            }
+            Instruction::JumpIfFalseOrPop(target) | Instruction::JumpIfTrueOrPop(target) => {
+                let values = flatten(stack);
+                let (kind, value) = pop_slot(stack)?;
+                let condition = self.truthy(kind, value);
+                let jump_if = matches!(instruction, Instruction::JumpIfTrueOrPop(_));
+                self.branch(condition, jump_if, target, &values)?;
+            }
+            Instruction::ReturnValue => {
+                let (kind, value) = pop_slot(stack)?;
+                let value = match kind {
+                    Kind::Int | Kind::Float => value,
+                    Kind::Bool | Kind::None => self.builder.ins().uextend(I64, value),
+                };
+                self.builder
+                    .ins()
+                    .store(MemFlags::trusted(), value, self.out, 0);
+                let returned = self.builder.ins().iconst(I32, 0);
+                self.builder.ins().return_(&[returned]);
+                return Some(false);
+            }
+            _ => return None,
+        }
+        Some(true)
+    }
+
+    /// Jumps to `target` with `values` if `condition` is `jump_if`, and
+    /// carries on in a new block otherwise.
+    fn branch(
+        &mut self,
+        condition: Value,
+        jump_if: bool,
+        target: usize,
+        values: &[Value],
+    ) -> Option<()> {
+        let target = self.blocks[target]?;
+        let next = self.builder.create_block();
+        if jump_if {
+            self.builder
+                .ins()
+                .brif(condition, target, values, next, &[]);
+        } else {
+            self.builder
+                .ins()
+                .brif(condition, next, &[], target, values);
+        }
+        self.builder.switch_to_block(next);
+        Some(())
+    }
+
+    /// Leaves compiled code for the interpreter if `condition` holds.
+    fn bail_if(&mut self, condition: Value) {
+        let next = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(condition, self.bail, &[], next, &[]);
+        self.builder.switch_to_block(next);
+    }
+
+    fn truthy(&mut self, kind: Kind, value: Value) -> Value {
+        match kind {
+            Kind::Int => self.builder.ins().icmp_imm(IntCC::NotEqual, value, 0),
+            Kind::Float => {
+                let zero = self.builder.ins().f64const(0.0);
+                self.builder.ins().fcmp(FloatCC::NotEqual, value, zero)
+            }
+            Kind::Bool => value,
+            Kind::None => self.builder.ins().iconst(I8, 0),
+        }
+    }
+
+    fn int(&mut self, kind: Kind, value: Value) -> Value {
+        match kind {
+            Kind::Bool => self.builder.ins().uextend(I64, value),
+            _ => value,
+        }
+    }
+
+    fn float(&mut self, kind: Kind, value: Value) -> Value {
+        match kind {
+            Kind::Float => value,
+            _ => {
+                let value = self.int(kind, value);
+                self.builder.ins().fcvt_from_sint(F64, value)
+            }
+        }
+    }
+
+    fn binary(
+        &mut self,
+        operator: Operator,
+        left: (Kind, Value),
+        right: (Kind, Value),
+    ) -> Option<Slot> {
+        let kind = binary_kind(operator, left.0, right.0)?;
+        if kind == Kind::Float {
+            let a = self.float(left.0, left.1);
+            let b = self.float(right.0, right.1);
+            let value = match operator {
+                Operator::Add => self.builder.ins().fadd(a, b),
+                Operator::Sub => self.builder.ins().fsub(a, b),
+                Operator::Mult => self.builder.ins().fmul(a, b),
+                _ => {
+                    let zero = self.builder.ins().f64const(0.0);
+                    let by_zero = self.builder.ins().fcmp(FloatCC::Equal, b, zero);
+                    self.bail_if(by_zero);
+                    self.builder.ins().fdiv(a, b)
+                }
+            };
+            return Some(Slot::Value(kind, value));
+        }
+        if kind == Kind::Bool {
+            let value = match operator {
+                Operator::BitAnd => self.builder.ins().band(left.1, right.1),
+                Operator::BitOr => self.builder.ins().bor(left.1, right.1),
+                _ => self.builder.ins().bxor(left.1, right.1),
+            };
+            return Some(Slot::Value(kind, value));
+        }
+
+        let a = self.int(left.0, left.1);
+        let b = self.int(right.0, right.1);
+        let value = match operator {
+            Operator::Add | Operator::Sub | Operator::Mult => {
+                let (value, overflow) = match operator {
+                    Operator::Add => self.builder.ins().sadd_overflow(a, b),
+                    Operator::Sub => self.builder.ins().ssub_overflow(a, b),
+                    _ => self.builder.ins().smul_overflow(a, b),
+                };
+                self.bail_if(overflow);
+                value
+            }
+            Operator::FloorDiv | Operator::Mod => {
+                let by_zero = self.builder.ins().icmp_imm(IntCC::Equal, b, 0);
+                self.bail_if(by_zero);
+                // `MIN // -1` overflows, and traps in the division.
+                let min = self.builder.ins().icmp_imm(IntCC::Equal, a, i64::MIN);
+                let minus_one = self.builder.ins().icmp_imm(IntCC::Equal, b, -1);
+                let overflow = self.builder.ins().band(min, minus_one);
+                self.bail_if(overflow);
+                // Rounds the truncating division towards negative infinity,
+                // when there is a remainder of the other sign to `b`.
+                let quotient = self.builder.ins().sdiv(a, b);
+                let remainder = self.builder.ins().srem(a, b);
+                let inexact = self.builder.ins().icmp_imm(IntCC::NotEqual, remainder, 0);
+                let signs = self.builder.ins().bxor(remainder, b);
+                let opposite = self.builder.ins().icmp_imm(IntCC::SignedLessThan, signs, 0);
+                let adjust = self.builder.ins().band(inexact, opposite);
+                if operator == Operator::FloorDiv {
+                    let one = self.builder.ins().uextend(I64, adjust);
+                    self.builder.ins().isub(quotient, one)
+                } else {
+                    let adjusted = self.builder.ins().iadd(remainder, b);
+                    self.builder.ins().select(adjust, adjusted, remainder)
+                }
+            }
+            Operator::LShift => {
+                let negative = self.builder.ins().icmp_imm(IntCC::SignedLessThan, b, 0);
+                self.bail_if(negative);
+                // Shifting zero gives zero however far; anything else must
+                // shift back to itself.
+                let shifted = self.builder.ins().ishl(a, b);
+                let back = self.builder.ins().sshr(shifted, b);
+                let lost = self.builder.ins().icmp(IntCC::NotEqual, back, a);
+                let far = self
+                    .builder
+                    .ins()
+                    .icmp_imm(IntCC::SignedGreaterThanOrEqual, b, 63);
+                let overflow = self.builder.ins().bor(lost, far);
+                let nonzero = self.builder.ins().icmp_imm(IntCC::NotEqual, a, 0);
+                let overflow = self.builder.ins().band(overflow, nonzero);
+                self.bail_if(overflow);
+                let zero = self.builder.ins().iconst(I64, 0);
+                let is_zero = self.builder.ins().icmp_imm(IntCC::Equal, a, 0);
+                self.builder.ins().select(is_zero, zero, shifted)
+            }
+            Operator::RShift => {
+                let negative = self.builder.ins().icmp_imm(IntCC::SignedLessThan, b, 0);
+                self.bail_if(negative);
+                let most = self.builder.ins().iconst(I64, 63);
+                let far = self.builder.ins().icmp_imm(IntCC::SignedGreaterThan, b, 63);
+                let distance = self.builder.ins().select(far, most, b);
+                self.builder.ins().sshr(a, distance)
+            }
+            Operator::BitAnd => self.builder.ins().band(a, b),
+            Operator::BitOr => self.builder.ins().bor(a, b),
+            _ => self.builder.ins().bxor(a, b),
+        };
+        Some(Slot::Value(Kind::Int, value))
+    }
+
+    fn unary(&mut self, operator: UnaryOperator, (kind, value): (Kind, Value)) -> Option<Slot> {
+        let result = unary_kind(operator, kind)?;
+        let value = match (operator, kind) {
+            (UnaryOperator::Not, _) => {
+                let truthy = self.truthy(kind, value);
+                self.builder.ins().icmp_imm(IntCC::Equal, truthy, 0)
+            }
+            (UnaryOperator::USub, Kind::Float) => self.builder.ins().fneg(value),
+            (UnaryOperator::USub, Kind::Int) => {
+                let min = self.builder.ins().icmp_imm(IntCC::Equal, value, i64::MIN);
+                self.bail_if(min);
+                self.builder.ins().ineg(value)
+            }
+            (UnaryOperator::USub, _) => {
+                let value = self.int(kind, value);
+                self.builder.ins().ineg(value)
+            }
+            (UnaryOperator::Invert, _) => {
+                let value = self.int(kind, value);
+                self.builder.ins().bnot(value)
+            }
+            (_, Kind::Float) => value,
+            (_, _) => self.int(kind, value),
+        };
+        Some(Slot::Value(result, value))
+    }
+
+    fn compare(
+        &mut self,
+        operator: CmpOperator,
+        left: (Kind, Value),
+        right: (Kind, Value),
+    ) -> Option<Value> {
+        let condition = int_condition(operator)?;
+        if left.0 != Kind::Float && right.0 != Kind::Float {
+            let a = self.int(left.0, left.1);
+            let b = self.int(right.0, right.1);
+            return Some(self.builder.ins().icmp(condition, a, b));
+        }
+        // Ints compare with floats exactly, which converting them only
+        // does while they fit the float's mantissa.
+        for &(kind, value) in &[left, right] {
+            if kind == Kind::Int {
+                let above =
+                    self.builder
+                        .ins()
+                        .icmp_imm(IntCC::SignedGreaterThan, value, EXACT_FLOAT_INT);
+                let below =
+                    self.builder
+                        .ins()
+                        .icmp_imm(IntCC::SignedLessThan, value, -EXACT_FLOAT_INT);
+                let inexact = self.builder.ins().bor(above, below);
+                self.bail_if(inexact);
+            }
+        }
+        let a = self.float(left.0, left.1);
+        let b = self.float(right.0, right.1);
+        Some(self.builder.ins().fcmp(float_condition(operator), a, b))
+    }
+
+    /// `range(...)`, whose step must not be zero.
+    fn range(&mut self, args: &[Slot]) -> Slot {
+        let mut values = Vec::new();
+        for arg in args {
+            if let Slot::Value(kind, value) = *arg {
+                values.push(self.int(kind, value));
+            }
+        }
+        let (start, stop, step) = match values.len() {
+            1 => (
+                self.builder.ins().iconst(I64, 0),
+                values[0],
+                self.builder.ins().iconst(I64, 1),
+            ),
+            2 => (values[0], values[1], self.builder.ins().iconst(I64, 1)),
+            _ => (values[0], values[1], values[2]),
+        };
+        if values.len() == 3 {
+            let zero = self.builder.ins().icmp_imm(IntCC::Equal, step, 0);
+            self.bail_if(zero);
+        }
+        Slot::RangeObject(start, stop, step)
+    }
+
+    /// A call of the function itself, which bails out if its callee does.
+    fn recurse(&mut self, args: &[Slot]) -> Option<Slot> {
+        let callee = self.recurse?;
+        let arguments = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            (args.len().max(1) * 8) as u32,
+            3,
+        ));
+        let result = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            8,
+            3,
+        ));
+        for (index, arg) in args.iter().enumerate() {
+            if let Slot::Value(_, value) = *arg {
+                self.builder
+                    .ins()
+                    .stack_store(value, arguments, (index * 8) as i32);
+            }
+        }
+        let arguments = self.builder.ins().stack_addr(self.pointer, arguments, 0);
+        let out = self.builder.ins().stack_addr(self.pointer, result, 0);
+        let call = self
+            .builder
+            .ins()
+            .call(callee, &[arguments, self.budget, out]);
+        let status = self.builder.inst_results(call)[0];
+        self.bail_if(status);
+        let kind = self.analysis.returns;
+        let value = self.builder.ins().stack_load(I64, result, 0);
+        let value = match kind {
+            Kind::Int => value,
+            Kind::Float => self.builder.ins().bitcast(F64, MemFlags::new(), value),
+            Kind::Bool | Kind::None => self.builder.ins().ireduce(I8, value),
+        };
+        Some(Slot::Value(kind, value))
+    }
+}
+
+fn pop_slot(stack: &mut Vec<Slot>) -> Option<(Kind, Value)> {
+    match stack.pop()? {
+        Slot::Value(kind, value) => Some((kind, value)),
+        _ => None,
+    }
+}
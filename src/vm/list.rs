@@ -0,0 +1,342 @@
+//! The methods of `list`.
+//!
+//! Comparing items may run Python code that changes the list, so methods
+//! that search it borrow the items afresh for each one.
+
+use std::cell::RefCell;
+use std::mem;
+
+use super::builtins::{check_count, index_value, keyword_arguments};
+use super::object::{Args, NativeFunction, ObjectRef, Payload, PyResult};
+use super::Vm;
+
+/// Adds the methods of `list` to its class.
+pub(super) fn add_methods(vm: &mut Vm) {
+    let methods: [(&'static str, NativeFunction); 14] = [
+        ("__delitem__", delitem),
+        ("__getitem__", getitem),
+        ("__setitem__", setitem),
+        ("append", append),
+        ("clear", clear),
+        ("copy", copy),
+        ("count", count),
+        ("extend", extend),
+        ("index", index),
+        ("insert", insert),
+        ("pop", pop),
+        ("remove", remove),
+        ("reverse", reverse),
+        ("sort", sort),
+    ];
+    let dict = vm.types.list.dict().unwrap().clone();
+    for &(name, function) in &methods {
+        let method = vm.new_builtin(name, function);
+        vm.dict_set_str(dict.as_dict().unwrap(), name, method);
+    }
+}
+
+/// Splits the list a method was called on from the other arguments.
+fn receiver(vm: &mut Vm, mut args: Args, name: &str) -> PyResult<(ObjectRef, Args)> {
+    if args.positional.is_empty() {
+        let message = format!("unbound method list.{}() needs an argument", name);
+        return Err(vm.new_type_error(message));
+    }
+    let this = args.positional.remove(0);
+    if let Payload::List(_) = this.payload {
+        return Ok((this, args));
+    }
+    let message = format!(
+        "descriptor '{}' for 'list' objects doesn't apply to a '{}' object",
+        name,
+        this.type_name()
+    );
+    Err(vm.new_type_error(message))
+}
+
+fn items(list: &ObjectRef) -> &RefCell<Vec<ObjectRef>> {
+    match list.payload {
+        Payload::List(ref items) => items,
+        _ => unreachable!(),
+    }
+}
+
+fn reject_keywords(vm: &mut Vm, args: &Args, name: &str) -> PyResult<()> {
+    if args.keywords.is_empty() {
+        return Ok(());
+    }
+    let message = format!("list.{}() takes no keyword arguments", name);
+    Err(vm.new_type_error(message))
+}
+
+/// The list of a method that takes no arguments.
+fn no_arguments(vm: &mut Vm, args: Args, name: &str) -> PyResult<ObjectRef> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    if !args.positional.is_empty() {
+        let message = format!(
+            "list.{}() takes no arguments ({} given)",
+            name,
+            args.positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(this)
+}
+
+/// The list and the argument of a method that takes exactly one.
+fn single_argument(vm: &mut Vm, args: Args, name: &str) -> PyResult<(ObjectRef, ObjectRef)> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    if args.positional.len() != 1 {
+        let message = format!(
+            "list.{}() takes exactly one argument ({} given)",
+            name,
+            args.positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let argument = args.positional.into_iter().next().unwrap();
+    Ok((this, argument))
+}
+
+/// The list and the arguments of a method whose arguments are all
+/// positional-only.
+fn positional_arguments(
+    vm: &mut Vm,
+    args: Args,
+    name: &str,
+    min: usize,
+    max: usize,
+) -> PyResult<(ObjectRef, Vec<ObjectRef>)> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    check_count(vm, &args, name, min, max)?;
+    Ok((this, args.positional))
+}
+
+/// The position of the first item from `start` equal to `value`, before
+/// `stop` or the end of the list if it is shorter by then.
+fn find(
+    vm: &mut Vm,
+    list: &ObjectRef,
+    value: &ObjectRef,
+    start: usize,
+    stop: usize,
+) -> PyResult<Option<usize>> {
+    let mut position = start;
+    while position < stop {
+        let item = match items(list).borrow().get(position) {
+            Some(item) => item.clone(),
+            None => break,
+        };
+        if vm.same_or_eq(&item, value)? {
+            return Ok(Some(position));
+        }
+        position += 1;
+    }
+    Ok(None)
+}
+
+/// `list.append(object, /)`.
+fn append(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, item) = single_argument(vm, args, "append")?;
+    items(&this).borrow_mut().push(item);
+    Ok(vm.none())
+}
+
+/// `list.clear()`.
+fn clear(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "clear")?;
+    // The items are dropped once the list no longer holds them.
+    let removed = mem::take(&mut *items(&this).borrow_mut());
+    drop(removed);
+    Ok(vm.none())
+}
+
+/// `list.copy()`, a shallow copy.
+fn copy(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "copy")?;
+    let copied = items(&this).borrow().clone();
+    Ok(vm.new_list(copied))
+}
+
+/// `list.count(value, /)`.
+fn count(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, value) = single_argument(vm, args, "count")?;
+    let mut count = 0;
+    let mut position = 0;
+    loop {
+        let item = match items(&this).borrow().get(position) {
+            Some(item) => item.clone(),
+            None => break,
+        };
+        if vm.same_or_eq(&item, &value)? {
+            count += 1;
+        }
+        position += 1;
+    }
+    Ok(vm.new_int(count))
+}
+
+/// `list.extend(iterable, /)`.
+fn extend(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, iterable) = single_argument(vm, args, "extend")?;
+    // Collected first, so that a list extended by itself doubles once.
+    let added = vm.iterate(&iterable)?;
+    items(&this).borrow_mut().extend(added);
+    Ok(vm.none())
+}
+
+/// A `start` or `stop` argument of `list.index`, which may not be `None`,
+/// counted from the end if negative and clamped to the list.
+fn search_bound(
+    vm: &mut Vm,
+    bound: Option<&ObjectRef>,
+    len: usize,
+    default: usize,
+) -> PyResult<usize> {
+    let bound = match bound {
+        Some(bound) => bound,
+        None => return Ok(default),
+    };
+    let value = match bound.payload {
+        Payload::Int(value) => value,
+        _ if vm.python_method(bound, "__index__").is_some() => index_value(vm, bound)?,
+        _ => {
+            let message = "slice indices must be integers or have an __index__ method";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+    };
+    let len = len as i64;
+    let value = if value < 0 {
+        (value + len).max(0)
+    } else {
+        value.min(len)
+    };
+    Ok(value as usize)
+}
+
+/// `list.index(value, start=0, stop=sys.maxsize, /)`.
+fn index(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = positional_arguments(vm, args, "index", 1, 3)?;
+    let len = items(&this).borrow().len();
+    let start = search_bound(vm, arguments.get(1), len, 0)?;
+    let stop = search_bound(vm, arguments.get(2), len, len)?;
+    match find(vm, &this, &arguments[0], start, stop)? {
+        Some(position) => Ok(vm.new_int(position as i64)),
+        None => {
+            let message = format!("{} is not in list", vm.repr(&arguments[0])?);
+            Err(vm.new_value_error(message))
+        }
+    }
+}
+
+/// `list.insert(index, object, /)`, which puts the object before `index`,
+/// or at either end if `index` is past it.
+fn insert(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, mut arguments) = positional_arguments(vm, args, "insert", 2, 2)?;
+    let item = arguments.pop().unwrap();
+    let index = index_value(vm, &arguments[0])?;
+    let mut items = items(&this).borrow_mut();
+    let len = items.len() as i64;
+    let index = if index < 0 {
+        (index + len).max(0)
+    } else {
+        index.min(len)
+    };
+    items.insert(index as usize, item);
+    drop(items);
+    Ok(vm.none())
+}
+
+/// `list.pop(index=-1, /)`.
+fn pop(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, arguments) = positional_arguments(vm, args, "pop", 0, 1)?;
+    let index = match arguments.first() {
+        Some(index) => index_value(vm, index)?,
+        None => -1,
+    };
+    let len = items(&this).borrow().len() as i64;
+    if len == 0 {
+        return Err(vm.new_index_error("pop from empty list".to_string()));
+    }
+    let position = if index < 0 { index + len } else { index };
+    if position < 0 || position >= len {
+        return Err(vm.new_index_error("pop index out of range".to_string()));
+    }
+    let item = items(&this).borrow_mut().remove(position as usize);
+    Ok(item)
+}
+
+/// `list.remove(value, /)`, of the first item equal to `value`.
+fn remove(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, value) = single_argument(vm, args, "remove")?;
+    match find(vm, &this, &value, 0, usize::MAX)? {
+        Some(position) => {
+            let removed = items(&this).borrow_mut().remove(position);
+            drop(removed);
+            Ok(vm.none())
+        }
+        None => Err(vm.new_value_error("list.remove(x): x not in list".to_string())),
+    }
+}
+
+/// `list.reverse()`, in place.
+fn reverse(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "reverse")?;
+    items(&this).borrow_mut().reverse();
+    Ok(vm.none())
+}
+
+/// `list.sort(*, key=None, reverse=False)`, stable and in place.
+///
+/// As in CPython, the list is empty while it is sorted, so that a key
+/// function or comparison that looks at it sees no items, and changing it
+/// meanwhile is an error that discards the change.
+fn sort(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, args) = receiver(vm, args, "sort")?;
+    if !args.positional.is_empty() {
+        let message = "sort() takes no positional arguments".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    let mut keywords =
+        keyword_arguments(vm, args.keywords, "sort", &["key", "reverse"])?.into_iter();
+    let key = keywords.next().unwrap();
+    let reverse = match keywords.next().unwrap() {
+        Some(reverse) => index_value(vm, &reverse)? != 0,
+        None => false,
+    };
+    let saved = mem::take(&mut *items(&this).borrow_mut());
+    let mut sorted = saved.clone();
+    let result = vm.sort(&mut sorted, key.as_ref(), reverse);
+    let modified = !items(&this).borrow().is_empty();
+    let restored = if result.is_ok() { sorted } else { saved };
+    let discarded = mem::replace(&mut *items(&this).borrow_mut(), restored);
+    drop(discarded);
+    result?;
+    if modified {
+        return Err(vm.new_value_error("list modified during sort".to_string()));
+    }
+    Ok(vm.none())
+}
+
+/// `list.__getitem__(key, /)`, as `self[key]`.
+fn getitem(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, key) = single_argument(vm, args, "__getitem__")?;
+    vm.builtin_getitem(&this, &key)
+}
+
+/// `list.__setitem__(key, value, /)`, as `self[key] = value`.
+fn setitem(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, mut arguments) = positional_arguments(vm, args, "__setitem__", 2, 2)?;
+    let value = arguments.pop().unwrap();
+    vm.builtin_setitem(&this, &arguments[0], value)?;
+    Ok(vm.none())
+}
+
+/// `list.__delitem__(key, /)`, as `del self[key]`.
+fn delitem(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, key) = single_argument(vm, args, "__delitem__")?;
+    vm.builtin_delitem(&this, &key)?;
+    Ok(vm.none())
+}
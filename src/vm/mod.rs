@@ -0,0 +1,758 @@
+//! A bytecode interpreter for the code `compiler` generates, like CPython's
+//! `ceval.c`.
+//!
+//! Objects are reference counted `PyObject`s, each holding its class and
+//! the data of its builtin type. Operations that may raise return a
+//! `PyResult`, whose error is the exception instance; it unwinds through
+//! the block stack of each frame to the nearest handler.
+//!
+//! Integers are limited to 64 bits for now: arithmetic that overflows them
+//! raises `OverflowError`.
+
+mod archive;
+mod builtins;
+mod bytes;
+mod cache;
+mod classes;
+mod descriptors;
+mod dict;
+mod eval;
+mod exception_group;
+mod exceptions;
+mod frame;
+mod gc;
+mod generator;
+mod heap;
+mod import;
+mod intern;
+#[cfg(feature = "jit")]
+mod jit;
+mod list;
+mod modules;
+mod native;
+mod object;
+mod ops;
+mod printf;
+mod repr;
+mod string;
+mod tracer;
+mod types;
+mod unicode;
+mod xml;
+
+pub use self::cache::CacheStats;
+pub use self::dict::{Dict, Entry};
+pub use self::exceptions::Exceptions;
+pub use self::heap::{HeapEdge, HeapObject, HeapRoot, HeapSnapshot};
+pub use self::import::{Finder, FrozenFinder, FrozenModule, ModuleSpec, PathFinder};
+pub use self::native::{ModuleBuilder, NativeModule};
+pub use self::object::{
+    object_id, Args, Builtin, BuiltinFunction, ExceptionData, Function, Generator, IteratorState,
+    NativeConstructor, NativeFunction, ObjectRef, Payload, PyObject, PyResult, Traceback, TypeData,
+    ViewKind,
+};
+pub use self::repr::ReprLimits;
+pub use self::tracer::{TraceEvent, TraceFrame, Tracer};
+pub use self::types::Types;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::Path;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use ast::{Constant, Module};
+use compiler::{
+    compile_with_warnings, CodeConstant, CodeObject, CompilerConfig, Mode, CO_COROUTINE,
+    CO_GENERATOR,
+};
+use optimizer::optimize;
+use parser::{parse, ParseError};
+use tokenizer::{decode, LineIndex, Location};
+use trace;
+
+use self::frame::{Frame, RunningFrame};
+
+/// The most Python calls that may be active at once before
+/// `RecursionError`, as CPython's default recursion limit.
+const DEFAULT_RECURSION_LIMIT: usize = 1000;
+
+/// An interpreter: the builtin objects, the `__main__` module, and the
+/// state of the code running in it.
+///
+/// Objects are reference counted without atomics, so a `Vm` is neither
+/// `Send` nor `Sync` and stays on the thread that made it. To run code on
+/// several threads, make a `Vm` on each; they share nothing.
+pub struct Vm {
+    pub types: Types,
+    pub exceptions: Exceptions,
+    none: ObjectRef,
+    true_: ObjectRef,
+    false_: ObjectRef,
+    not_implemented: ObjectRef,
+    ellipsis: ObjectRef,
+    /// The builtins namespace, a dict.
+    builtins: ObjectRef,
+    main: ObjectRef,
+    /// The exception being handled, which a bare `raise` raises again and
+    /// which becomes the `__context__` of exceptions raised meanwhile.
+    exc_info: Option<ObjectRef>,
+    depth: usize,
+    recursion_limit: usize,
+    /// The ids of the containers whose reprs are being built.
+    repr_stack: Vec<usize>,
+    /// The limits for showing the local variables of frames in tracebacks,
+    /// which are captured only when set.
+    traceback_locals: Option<ReprLimits>,
+    /// The source of the code run by `run_source`, for tracebacks.
+    sources: HashMap<String, KeptSource>,
+    /// `sys.modules`, a dict.
+    modules: ObjectRef,
+    /// The finders `import` asks in turn, ending with a `PathFinder`.
+    finders: Vec<Rc<dyn Finder>>,
+    /// The modules written in Rust registered by the embedding program.
+    native_modules: HashMap<String, Rc<dyn NativeModule>>,
+    /// The state of the cycle collector.
+    gc: gc::Collector,
+    /// The inline caches of the code objects that have run.
+    inline_caches: cache::CacheTable,
+    /// The small ints, the short strings and the interned strings.
+    shared: intern::Shared,
+    /// The names of the modules whose code is running, innermost last.
+    initializing: Vec<String>,
+    /// The frames that are running, innermost last.
+    frames: Vec<RunningFrame>,
+    /// `locals`, `vars` and `dir`, which a frame calls with its own locals
+    /// when they are called without arguments.
+    locals_functions: Vec<ObjectRef>,
+    /// The filters and registries of the `warnings` module.
+    warnings: modules::warnings::Warnings,
+    /// Whether code that CPython warns about when compiling is refused.
+    strict: bool,
+    /// The files `open` has opened.
+    files: modules::io::Files,
+    /// The tracer that hears of the events of running frames.
+    tracer: Option<Rc<dyn Tracer>>,
+    /// The function `sys.settrace` set, for `sys.gettrace`.
+    trace_function: Option<ObjectRef>,
+    /// Whether the tracer is running, so that its own code is not traced.
+    in_tracer: bool,
+    #[cfg(feature = "jit")]
+    jit: jit::Jit,
+    /// The connections of the `sqlite3` module.
+    #[cfg(feature = "sqlite")]
+    sqlite: modules::sqlite3::Connections,
+}
+
+/// A source the VM ran, with where its lines are.
+struct KeptSource {
+    text: String,
+    lines: LineIndex,
+}
+
+impl Vm {
+    pub fn new() -> Vm {
+        let types = Types::new();
+        let exceptions = Exceptions::new(&types);
+        let builtins = PyObject::new(
+            Payload::Dict(RefCell::new(Dict::new())),
+            types.dict.clone(),
+            None,
+        );
+        let main = PyObject::new(
+            Payload::Module,
+            types.module.clone(),
+            Some(PyObject::new(
+                Payload::Dict(RefCell::new(Dict::new())),
+                types.dict.clone(),
+                None,
+            )),
+        );
+        let modules = PyObject::new(
+            Payload::Dict(RefCell::new(Dict::new())),
+            types.dict.clone(),
+            None,
+        );
+        let mut vm = Vm {
+            none: PyObject::new(Payload::None, types.none.clone(), None),
+            true_: PyObject::new(Payload::Int(1), types.bool.clone(), None),
+            false_: PyObject::new(Payload::Int(0), types.bool.clone(), None),
+            not_implemented: PyObject::new(
+                Payload::NotImplemented,
+                types.not_implemented.clone(),
+                None,
+            ),
+            ellipsis: PyObject::new(Payload::Ellipsis, types.ellipsis.clone(), None),
+            shared: intern::Shared::new(&types),
+            types,
+            exceptions,
+            builtins,
+            main,
+            exc_info: None,
+            depth: 0,
+            recursion_limit: DEFAULT_RECURSION_LIMIT,
+            repr_stack: Vec::new(),
+            traceback_locals: None,
+            sources: HashMap::new(),
+            modules,
+            finders: vec![Rc::new(PathFinder)],
+            native_modules: HashMap::new(),
+            gc: Default::default(),
+            inline_caches: Default::default(),
+            initializing: Vec::new(),
+            frames: Vec::new(),
+            locals_functions: Vec::new(),
+            warnings: Default::default(),
+            files: Default::default(),
+            tracer: None,
+            trace_function: None,
+            in_tracer: false,
+            strict: false,
+            #[cfg(feature = "jit")]
+            jit: jit::Jit::new(),
+            #[cfg(feature = "sqlite")]
+            sqlite: Default::default(),
+        };
+
+        let classes: Vec<ObjectRef> = vm.exceptions.all().into_iter().cloned().collect();
+        let builtins = vm.builtins.clone();
+        for class in classes {
+            let name = class.as_type().unwrap().name.clone();
+            vm.dict_set_str(builtins.as_dict().unwrap(), &name, class);
+        }
+        // The old names of `OSError`.
+        for name in ["EnvironmentError", "IOError"] {
+            let os_error = vm.exceptions.os_error.clone();
+            vm.dict_set_str(builtins.as_dict().unwrap(), name, os_error);
+        }
+        builtins::add_builtins(&mut vm);
+        classes::add_builtins(&mut vm);
+        descriptors::add_builtins(&mut vm);
+        eval::add_builtins(&mut vm);
+        import::add_builtins(&mut vm);
+        generator::add_methods(&mut vm);
+        string::add_methods(&mut vm);
+        bytes::add_methods(&mut vm);
+        list::add_methods(&mut vm);
+        dict::add_methods(&mut vm);
+        let base_exception = vm.exceptions.base_exception.clone();
+        let base_exception_group = vm.exceptions.base_exception_group.clone();
+        let os_error = vm.exceptions.os_error.clone();
+        let syntax_error = vm.exceptions.syntax_error.clone();
+        let exception_methods: [(&ObjectRef, &'static str, NativeFunction); 7] = [
+            (&base_exception, "__init__", exceptions::exception_init),
+            (
+                &base_exception,
+                "with_traceback",
+                exceptions::with_traceback,
+            ),
+            (&base_exception_group, "derive", exception_group::derive),
+            (&base_exception_group, "split", exception_group::split),
+            (&base_exception_group, "subgroup", exception_group::subgroup),
+            (&os_error, "__init__", exceptions::os_error_init),
+            (&syntax_error, "__init__", exceptions::syntax_error_init),
+        ];
+        for &(class, name, function) in &exception_methods {
+            let method = vm.new_builtin(name, function);
+            vm.dict_set_str(class.dict().unwrap().as_dict().unwrap(), name, method);
+        }
+
+        builtins::add_site_builtins(&mut vm);
+
+        let globals = vm.main.dict().unwrap().clone();
+        let name = vm.new_str("__main__");
+        vm.dict_set_str(globals.as_dict().unwrap(), "__name__", name);
+        let none = vm.none();
+        vm.dict_set_str(globals.as_dict().unwrap(), "__doc__", none);
+        vm
+    }
+
+    /// The `__main__` module, whose namespace `run_source` runs code in.
+    pub fn main_module(&self) -> &ObjectRef {
+        &self.main
+    }
+
+    /// The builtins namespace, a dict.
+    pub fn builtins(&self) -> &ObjectRef {
+        &self.builtins
+    }
+
+    /// Parses, compiles and runs `source` in the `__main__` module. A
+    /// program that does not compile raises `SyntaxError`.
+    pub fn run_source(&mut self, source: &str, filename: &str) -> PyResult {
+        let _span = trace::file_span(Path::new(filename));
+        let code = self.compile_source(source, filename)?;
+        let globals = self.main.dict().unwrap().clone();
+        self.run_code(Arc::new(code), &globals)
+    }
+
+    /// Runs the contents of the script file `path` as `__main__`, as the
+    /// interpreter runs the script it is given: decoded as its encoding
+    /// declaration says, with `__file__` set to its absolute path, and with
+    /// its directory added to `sys.path` so that it can import the modules
+    /// beside it. A script that does not decode raises `SyntaxError`.
+    pub fn run_script(&mut self, source: &[u8], path: &Path) -> PyResult {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        let filename = path.to_string_lossy().into_owned();
+        let source = match decode(source) {
+            Ok(decoded) => decoded.text,
+            Err(err) => {
+                let class = self.exceptions.syntax_error.clone();
+                return Err(self.new_error(&class, err.to_string()));
+            }
+        };
+        let directory = match path.parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory,
+            _ => Path::new("."),
+        };
+        let directory = directory
+            .canonicalize()
+            .unwrap_or_else(|_| directory.to_path_buf());
+        self.add_search_path(directory);
+        let file = self.new_str(&filename);
+        let globals = self.main.dict().unwrap().clone();
+        self.dict_set_str(globals.as_dict().unwrap(), "__file__", file);
+        self.run_source(&source, &filename)
+    }
+
+    /// Parses and compiles `source`, keeping it for tracebacks. Source that
+    /// does not compile raises `SyntaxError`.
+    fn compile_source(&mut self, source: &str, filename: &str) -> PyResult<CodeObject> {
+        self.keep_source(filename, source);
+        let mut module = match parse(source) {
+            Ok(module) => module,
+            Err(err) => return Err(self.parse_error(&err, filename, source)),
+        };
+        optimize(&mut module);
+        self.compile_module(&module, filename, Mode::Exec, source)
+    }
+
+    /// Compiles a parsed and optimized module of `source` as `mode` asks.
+    /// Its warnings are issued through the `warnings` filters, and one that
+    /// they make an error is raised as a `SyntaxError`, as CPython does.
+    pub(super) fn compile_module(
+        &mut self,
+        module: &Module,
+        filename: &str,
+        mode: Mode,
+        source: &str,
+    ) -> PyResult<CodeObject> {
+        let config = CompilerConfig {
+            strict: self.strict,
+            ..CompilerConfig::default()
+        };
+        let class = self.exceptions.syntax_error.clone();
+        let (code, warnings) = match compile_with_warnings(module, filename, mode, &config) {
+            Ok(compiled) => compiled,
+            Err(err) => {
+                return Err(self.new_syntax_error(
+                    &class,
+                    &err.message,
+                    filename,
+                    err.location,
+                    source,
+                ))
+            }
+        };
+        let category = self.exceptions.syntax_warning.clone();
+        for warning in warnings {
+            let message = self.new_str(&warning.message);
+            let line = warning.location.line;
+            let result = modules::warnings::warn_explicit_at(
+                self, &message, &category, filename, line, None, None,
+            );
+            match result {
+                Err(error) if Vm::is_instance(&error, &category) => {
+                    return Err(self.new_syntax_error(
+                        &class,
+                        &warning.message,
+                        filename,
+                        warning.location,
+                        source,
+                    ))
+                }
+                result => result?,
+            }
+        }
+        Ok(code)
+    }
+
+    /// Makes code that CPython warns about when compiling it, such as
+    /// `x is 1`, a `SyntaxError` instead, as `-W error::SyntaxWarning`
+    /// does.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// The exception CPython raises for the error `err` in parsing
+    /// `source`, read from `filename`: a `SyntaxError` or one of its
+    /// subclasses, with where the error is, or for the errors that are not
+    /// about the syntax, such as a parse that was cancelled, the class
+    /// CPython raises for those with just the message. A `TokenError`
+    /// converts to a `ParseError` first.
+    pub fn parse_error(&mut self, err: &ParseError, filename: &str, source: &str) -> ObjectRef {
+        let class = self.builtin_class(err.kind.exception_name());
+        if !Vm::is_subclass(&class, &self.exceptions.syntax_error) {
+            return self.new_error(&class, err.message.clone());
+        }
+        self.new_syntax_error(&class, &err.message, filename, err.location, source)
+    }
+
+    /// Keeps `source` as that of `filename`, for the lines tracebacks and
+    /// warnings show.
+    fn keep_source(&mut self, filename: &str, source: &str) {
+        let kept = KeptSource {
+            lines: LineIndex::new(source),
+            text: source.to_string(),
+        };
+        self.sources.insert(filename.to_string(), kept);
+    }
+
+    /// Line `line` of the source kept for `filename`, if there is one.
+    fn kept_line(&self, filename: &str, line: usize) -> Option<&str> {
+        let kept = self.sources.get(filename)?;
+        kept.lines.line_text(&kept.text, line)
+    }
+
+    /// A `SyntaxError` of the class `class` for the error `message` at
+    /// `location` in `source`, with the file name, line number, offset
+    /// from 1 and line of source that CPython's compiler gives it.
+    pub fn new_syntax_error(
+        &mut self,
+        class: &ObjectRef,
+        message: &str,
+        filename: &str,
+        location: Location,
+        source: &str,
+    ) -> ObjectRef {
+        let text = match source
+            .split_inclusive('\n')
+            .nth(location.line.wrapping_sub(1))
+        {
+            Some(line) => self.new_str(line),
+            None => self.none(),
+        };
+        let details = vec![
+            self.new_str(filename),
+            self.new_int(location.line as i64),
+            self.new_int(location.column as i64 + 1),
+            text,
+        ];
+        let args = vec![self.new_str(message), self.new_tuple(details)];
+        match self.call(class, Args::new(args)) {
+            Ok(exception) => exception,
+            Err(error) => error,
+        }
+    }
+
+    /// Runs module-level code with `globals`, a dict, as its namespace.
+    pub fn run_code(&mut self, code: Arc<CodeObject>, globals: &ObjectRef) -> PyResult {
+        self.run_code_in(code, globals, globals)
+    }
+
+    /// Runs module-level code with the dicts `globals` and `locals` as its
+    /// namespaces, as `exec` does.
+    pub fn run_code_in(
+        &mut self,
+        code: Arc<CodeObject>,
+        globals: &ObjectRef,
+        locals: &ObjectRef,
+    ) -> PyResult {
+        let _span = stage_span!("execute", filename = %code.filename);
+        let constants = self.constants(&code)?;
+        let caches = self.inline_caches(&code);
+        let mut frame = Frame::new(
+            code,
+            constants,
+            caches,
+            globals.clone(),
+            Some(locals.clone()),
+        );
+        let result = self.execute(&mut frame);
+        // What the frame held is freed with it.
+        drop(frame);
+        if gc::has_unfinalized() {
+            self.run_finalizers();
+        }
+        result
+    }
+
+    /// The globals and locals of the innermost running frame, or those of
+    /// `__main__` when no frame is running. The locals are `None` in a
+    /// function.
+    pub(super) fn current_namespaces(&self) -> (ObjectRef, Option<ObjectRef>) {
+        match self.frames.last() {
+            Some(frame) => (frame.globals.clone(), frame.locals.clone()),
+            None => {
+                let globals = self.main.dict().unwrap().clone();
+                (globals.clone(), Some(globals))
+            }
+        }
+    }
+
+    fn builtin_class(&self, name: &str) -> ObjectRef {
+        self.builtins
+            .as_dict()
+            .unwrap()
+            .borrow()
+            .get_str(name)
+            .unwrap_or_else(|| self.exceptions.syntax_error.clone())
+    }
+
+    /// The constants of `code` as objects.
+    fn constants(&mut self, code: &CodeObject) -> PyResult<Rc<[ObjectRef]>> {
+        let mut constants = Vec::with_capacity(code.constants.len());
+        for constant in &code.constants {
+            constants.push(match *constant {
+                CodeConstant::Value(ref value) => self.constant(value)?,
+                CodeConstant::Code(ref code) => {
+                    PyObject::new(Payload::Code(code.clone()), self.types.code.clone(), None)
+                }
+            });
+        }
+        Ok(Rc::from(constants))
+    }
+
+    fn constant(&mut self, constant: &Constant) -> PyResult {
+        Ok(match *constant {
+            Constant::None => self.none(),
+            Constant::Bool(value) => self.new_bool(value),
+            Constant::Str(ref value) if intern::is_identifier_like(value) => self.intern(value),
+            Constant::Str(ref value) => self.new_str(value),
+            Constant::Bytes(ref value) => self.new_bytes(value.clone()),
+            Constant::Int(value) => self.new_int(value),
+            Constant::LongInt(_) => return Err(self.int_overflow()),
+            Constant::Float(value) => self.new_float(value),
+            Constant::Complex { real, imag } => self.new_complex(real, imag),
+            Constant::Ellipsis => self.ellipsis.clone(),
+            Constant::Tuple(ref items) => {
+                let items = items
+                    .iter()
+                    .map(|item| self.constant(item))
+                    .collect::<PyResult<Vec<_>>>()?;
+                self.new_tuple(items)
+            }
+        })
+    }
+
+    /// The error for an integer that does not fit in 64 bits.
+    fn int_overflow(&mut self) -> ObjectRef {
+        self.new_overflow_error(
+            "integer overflow (integers beyond 64 bits are not supported yet)".to_string(),
+        )
+    }
+
+    /// Whether `a` and `b` are the same object, as `is` tests.
+    pub fn is(a: &ObjectRef, b: &ObjectRef) -> bool {
+        Rc::ptr_eq(a, b)
+    }
+
+    pub fn none(&self) -> ObjectRef {
+        self.none.clone()
+    }
+
+    pub fn not_implemented(&self) -> ObjectRef {
+        self.not_implemented.clone()
+    }
+
+    pub fn new_bool(&self, value: bool) -> ObjectRef {
+        if value {
+            self.true_.clone()
+        } else {
+            self.false_.clone()
+        }
+    }
+
+    /// An int, which is shared from -5 to 256, as in CPython.
+    pub fn new_int(&self, value: i64) -> ObjectRef {
+        match self.shared.small_int(value) {
+            Some(int) => int.clone(),
+            None => PyObject::new(Payload::Int(value), self.types.int.clone(), None),
+        }
+    }
+
+    pub fn new_float(&self, value: f64) -> ObjectRef {
+        PyObject::new(Payload::Float(value), self.types.float.clone(), None)
+    }
+
+    pub fn new_complex(&self, real: f64, imag: f64) -> ObjectRef {
+        PyObject::new(
+            Payload::Complex { real, imag },
+            self.types.complex.clone(),
+            None,
+        )
+    }
+
+    /// A string, which is shared if it is empty or one Latin-1 character.
+    pub fn new_str(&self, value: &str) -> ObjectRef {
+        if let Some(string) = self.shared.short_str(value) {
+            return string.clone();
+        }
+        PyObject::new(
+            Payload::Str(value.to_string()),
+            self.types.str.clone(),
+            None,
+        )
+    }
+
+    pub fn new_bytes(&self, value: Vec<u8>) -> ObjectRef {
+        PyObject::new(Payload::Bytes(value), self.types.bytes.clone(), None)
+    }
+
+    pub fn new_bytearray(&self, value: Vec<u8>) -> ObjectRef {
+        let payload = Payload::ByteArray(RefCell::new(value));
+        PyObject::new(payload, self.types.bytearray.clone(), None)
+    }
+
+    pub fn new_tuple(&self, items: Vec<ObjectRef>) -> ObjectRef {
+        PyObject::new(Payload::Tuple(items), self.types.tuple.clone(), None)
+    }
+
+    pub fn new_list(&self, items: Vec<ObjectRef>) -> ObjectRef {
+        PyObject::new(
+            Payload::List(RefCell::new(items)),
+            self.types.list.clone(),
+            None,
+        )
+    }
+
+    pub fn new_dict(&self) -> ObjectRef {
+        PyObject::new(
+            Payload::Dict(RefCell::new(Dict::new())),
+            self.types.dict.clone(),
+            None,
+        )
+    }
+
+    /// An empty set, or frozenset if `frozen`.
+    pub fn new_set(&self, frozen: bool) -> ObjectRef {
+        let class = if frozen {
+            &self.types.frozenset
+        } else {
+            &self.types.set
+        };
+        PyObject::new(Payload::Set(RefCell::new(Dict::new())), class.clone(), None)
+    }
+
+    pub fn new_range(&self, start: i64, stop: i64, step: i64) -> ObjectRef {
+        PyObject::new(
+            Payload::Range { start, stop, step },
+            self.types.range.clone(),
+            None,
+        )
+    }
+
+    pub fn new_slice(&self, start: ObjectRef, stop: ObjectRef, step: ObjectRef) -> ObjectRef {
+        PyObject::new(
+            Payload::Slice { start, stop, step },
+            self.types.slice.clone(),
+            None,
+        )
+    }
+
+    pub fn new_builtin(&self, name: &'static str, function: NativeFunction) -> ObjectRef {
+        self.new_builtin_closure(name, function)
+    }
+
+    /// A builtin function that runs a Rust closure, which may keep state of
+    /// its own.
+    pub fn new_builtin_closure<F>(&self, name: &'static str, function: F) -> ObjectRef
+    where
+        F: Fn(&mut Vm, Args) -> PyResult + 'static,
+    {
+        PyObject::new(
+            Payload::Builtin(Builtin {
+                name,
+                function: Rc::new(function),
+            }),
+            self.types.builtin_function.clone(),
+            None,
+        )
+    }
+
+    /// Calls `callable` with `args`.
+    pub fn call(&mut self, callable: &ObjectRef, mut args: Args) -> PyResult {
+        match callable.payload {
+            Payload::Function(ref function) => self.call_function(function, args),
+            Payload::Builtin(ref builtin) => (builtin.function)(self, args),
+            Payload::Method {
+                ref function,
+                ref instance,
+            } => {
+                args.positional.insert(0, instance.clone());
+                self.call(function, args)
+            }
+            Payload::Type(_) => self.call_type(callable, args),
+            Payload::StaticMethod(ref function) => self.call(function, args),
+            _ => match self.special_method(callable, "__call__") {
+                Some(call) => self.call(&call, args),
+                None => {
+                    let message = format!("'{}' object is not callable", callable.type_name());
+                    Err(self.new_type_error(message))
+                }
+            },
+        }
+    }
+    /// A method: `function` bound to `instance`.
+    pub fn new_method(&self, function: ObjectRef, instance: ObjectRef) -> ObjectRef {
+        PyObject::new(
+            Payload::Method { function, instance },
+            self.types.method.clone(),
+            None,
+        )
+    }
+
+    /// A cell for a variable shared between a scope and the scopes nested
+    /// in it, empty until the variable is assigned.
+    pub fn new_cell(&self, value: Option<ObjectRef>) -> ObjectRef {
+        PyObject::new(
+            Payload::Cell(RefCell::new(value)),
+            self.types.cell.clone(),
+            None,
+        )
+    }
+
+    /// Captures the local variables of the frames an exception passes
+    /// through, to show them in its report with reprs within `limits`, as
+    /// `traceback`'s `capture_locals`; `None` stops capturing.
+    pub fn set_traceback_locals(&mut self, limits: Option<ReprLimits>) {
+        self.traceback_locals = limits;
+    }
+
+    /// Runs a Python function in a new frame, counting it towards the
+    /// recursion limit. Calling a generator function or an `async def`
+    /// only creates the frame, in a new generator or coroutine.
+    fn call_function(&mut self, function: &Function, args: Args) -> PyResult {
+        #[cfg(feature = "jit")]
+        {
+            if self.tracer.is_none() {
+                if let Some(result) = self.call_compiled(function, &args) {
+                    return Ok(result);
+                }
+            }
+        }
+        let mut frame = self.bind_arguments(function, args)?;
+        if function.code.flags & (CO_GENERATOR | CO_COROUTINE) != 0 {
+            return Ok(self.new_generator(function, frame));
+        }
+        self.execute_call(&mut frame)
+    }
+
+    /// Runs the frame of a call, counting it towards the recursion limit.
+    fn execute_call(&mut self, frame: &mut Frame) -> PyResult {
+        if self.depth >= self.recursion_limit {
+            let class = self.exceptions.recursion_error.clone();
+            return Err(self.new_error(&class, "maximum recursion depth exceeded".to_string()));
+        }
+        self.depth += 1;
+        let result = self.execute(frame);
+        self.depth -= 1;
+        result
+    }
+}
+
+impl Default for Vm {
+    fn default() -> Vm {
+        Vm::new()
+    }
+}
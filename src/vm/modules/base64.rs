@@ -0,0 +1,282 @@
+//! `base64`: the base64, base32 and base16 encodings of RFC 4648, and the
+//! MIME line-wrapped variant of base64.
+
+use super::super::bytes::bytes_like;
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::binascii::{self, decode_base64, decode_hex, encode_base64, encode_hex};
+use super::{bind_arguments, bytes_argument, new_native_module};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    Ok(new_native_module(
+        vm,
+        "base64",
+        &[
+            ("b16decode", b16decode),
+            ("b16encode", b16encode),
+            ("b32decode", b32decode),
+            ("b32encode", b32encode),
+            ("b64decode", b64decode),
+            ("b64encode", b64encode),
+            ("decodebytes", decodebytes),
+            ("encodebytes", encodebytes),
+            ("standard_b64decode", standard_b64decode),
+            ("standard_b64encode", standard_b64encode),
+            ("urlsafe_b64decode", urlsafe_b64decode),
+            ("urlsafe_b64encode", urlsafe_b64encode),
+        ],
+    ))
+}
+
+/// The data of an argument to decode: bytes, or a str of ASCII characters.
+fn decode_argument(vm: &mut Vm, value: &ObjectRef) -> PyResult<Vec<u8>> {
+    if let Some(data) = bytes_like(value) {
+        return Ok(data.into_owned());
+    }
+    match value.payload {
+        Payload::Str(ref text) if text.is_ascii() => Ok(text.as_bytes().to_vec()),
+        Payload::Str(_) => {
+            let message = "string argument should contain only ASCII characters".to_string();
+            Err(vm.new_value_error(message))
+        }
+        _ => {
+            let message = format!(
+                "argument should be a bytes-like object or ASCII string, not '{}'",
+                value.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// The data of an argument to `encodebytes` or `decodebytes`, which take
+/// only bytes.
+fn bytes_only_argument(vm: &mut Vm, value: &ObjectRef) -> PyResult<Vec<u8>> {
+    match bytes_like(value) {
+        Some(data) => Ok(data.into_owned()),
+        None => {
+            let message = format!("expected bytes-like object, not {}", value.type_name());
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// Whether an optional flag argument is given and true.
+fn flag(vm: &mut Vm, value: &Option<ObjectRef>) -> PyResult<bool> {
+    match *value {
+        Some(ref value) => vm.is_true(value),
+        None => Ok(false),
+    }
+}
+
+/// The two characters `altchars` that stand for `+` and `/`, if given.
+fn alternative_characters(vm: &mut Vm, value: &Option<ObjectRef>) -> PyResult<Option<[u8; 2]>> {
+    let value = match *value {
+        Some(ref value) if !Vm::is(value, &vm.none()) => value.clone(),
+        _ => return Ok(None),
+    };
+    let characters = decode_argument(vm, &value)?;
+    match characters[..] {
+        [plus, slash] => Ok(Some([plus, slash])),
+        _ => {
+            let message = vm.repr(&value)?;
+            let class = vm.exceptions.assertion_error.clone();
+            Err(vm.new_error(&class, message))
+        }
+    }
+}
+
+/// `data` with each byte in `from` replaced by the one at the same place
+/// in `to`.
+fn translate(mut data: Vec<u8>, from: &[u8], to: &[u8]) -> Vec<u8> {
+    for byte in &mut data {
+        if let Some(index) = from.iter().position(|&other| other == *byte) {
+            *byte = to[index];
+        }
+    }
+    data
+}
+
+/// Decodes base64, failing with `binascii.Error`.
+fn decode(vm: &mut Vm, data: &[u8], strict: bool) -> PyResult {
+    match decode_base64(data, strict) {
+        Ok(decoded) => Ok(vm.new_bytes(decoded)),
+        Err(message) => Err(binascii::error(vm, &message)),
+    }
+}
+
+/// `b64encode(s, altchars=None)`.
+fn b64encode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "b64encode", &["s", "altchars"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let encoded = encode_base64(&data, false);
+    match alternative_characters(vm, &values[1])? {
+        Some(altchars) => Ok(vm.new_bytes(translate(encoded, b"+/", &altchars))),
+        None => Ok(vm.new_bytes(encoded)),
+    }
+}
+
+/// `b64decode(s, altchars=None, validate=False)`: unless `validate`,
+/// characters outside the alphabet are ignored.
+fn b64decode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "b64decode", &["s", "altchars", "validate"], 1)?;
+    let mut data = decode_argument(vm, values[0].as_ref().unwrap())?;
+    if let Some(altchars) = alternative_characters(vm, &values[1])? {
+        data = translate(data, &altchars, b"+/");
+    }
+    let strict = flag(vm, &values[2])?;
+    decode(vm, &data, strict)
+}
+
+/// `standard_b64encode(s)`.
+fn standard_b64encode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "standard_b64encode", &["s"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    Ok(vm.new_bytes(encode_base64(&data, false)))
+}
+
+/// `standard_b64decode(s)`.
+fn standard_b64decode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "standard_b64decode", &["s"], 1)?;
+    let data = decode_argument(vm, values[0].as_ref().unwrap())?;
+    decode(vm, &data, false)
+}
+
+/// `urlsafe_b64encode(s)`: base64 with `-` and `_` for `+` and `/`.
+fn urlsafe_b64encode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "urlsafe_b64encode", &["s"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    Ok(vm.new_bytes(translate(encode_base64(&data, false), b"+/", b"-_")))
+}
+
+/// `urlsafe_b64decode(s)`.
+fn urlsafe_b64decode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "urlsafe_b64decode", &["s"], 1)?;
+    let data = decode_argument(vm, values[0].as_ref().unwrap())?;
+    decode(vm, &translate(data, b"-_", b"+/"), false)
+}
+
+/// `encodebytes(s)`: base64 in lines of 76 characters, each ended by a
+/// newline.
+fn encodebytes(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "encodebytes", &["s"], 1)?;
+    let data = bytes_only_argument(vm, values[0].as_ref().unwrap())?;
+    let encoded = data
+        .chunks(57)
+        .flat_map(|line| encode_base64(line, true))
+        .collect();
+    Ok(vm.new_bytes(encoded))
+}
+
+/// `decodebytes(s)`.
+fn decodebytes(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "decodebytes", &["s"], 1)?;
+    let data = bytes_only_argument(vm, values[0].as_ref().unwrap())?;
+    decode(vm, &data, false)
+}
+
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+/// `b32encode(s)`.
+fn b32encode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "b32encode", &["s"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let mut encoded = Vec::with_capacity(data.len().div_ceil(5) * 8);
+    for chunk in data.chunks(5) {
+        let bits = chunk.iter().enumerate().fold(0u64, |bits, (index, &byte)| {
+            bits | u64::from(byte) << (32 - 8 * index)
+        });
+        // Each byte given shows in the digits that hold any of its bits.
+        let digits = (chunk.len() * 8).div_ceil(5);
+        for index in 0..8 {
+            if index < digits {
+                encoded.push(BASE32_ALPHABET[(bits >> (35 - 5 * index) & 0x1f) as usize]);
+            } else {
+                encoded.push(b'=');
+            }
+        }
+    }
+    Ok(vm.new_bytes(encoded))
+}
+
+/// `b32decode(s, casefold=False, map01=None)`: `casefold` accepts lowercase
+/// letters, and `map01` is the letter that the digit 1 stands for, with 0
+/// standing for O.
+fn b32decode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "b32decode", &["s", "casefold", "map01"], 1)?;
+    let mut data = decode_argument(vm, values[0].as_ref().unwrap())?;
+    if !data.len().is_multiple_of(8) {
+        return Err(binascii::error(vm, "Incorrect padding"));
+    }
+    match values[2] {
+        Some(ref map01) if !Vm::is(map01, &vm.none()) => {
+            let letter = decode_argument(vm, map01)?;
+            if letter.len() != 1 {
+                let message = vm.repr(map01)?;
+                let class = vm.exceptions.assertion_error.clone();
+                return Err(vm.new_error(&class, message));
+            }
+            data = translate(data, b"01", &[b'O', letter[0]]);
+        }
+        _ => {}
+    }
+    if flag(vm, &values[1])? {
+        data.make_ascii_uppercase();
+    }
+    let length = data.len();
+    while data.last() == Some(&b'=') {
+        data.pop();
+    }
+    let padding = length - data.len();
+    let mut decoded = Vec::with_capacity(length / 8 * 5);
+    let mut bits = 0u64;
+    for quantum in data.chunks(8) {
+        bits = 0;
+        for &byte in quantum {
+            match BASE32_ALPHABET.iter().position(|&letter| letter == byte) {
+                Some(value) => bits = bits << 5 | value as u64,
+                None => return Err(binascii::error(vm, "Non-base32 digit found")),
+            }
+        }
+        decoded.extend_from_slice(&bits.to_be_bytes()[3..]);
+    }
+    if ![0, 1, 3, 4, 6].contains(&padding) {
+        return Err(binascii::error(vm, "Incorrect padding"));
+    }
+    if padding != 0 && !decoded.is_empty() {
+        let last = (bits << (5 * padding)).to_be_bytes();
+        let kept = (43 - 5 * padding) / 8;
+        let start = decoded.len() - 5;
+        decoded.truncate(start);
+        decoded.extend_from_slice(&last[3..3 + kept]);
+    }
+    Ok(vm.new_bytes(decoded))
+}
+
+/// `b16encode(s)`: uppercase hexadecimal.
+fn b16encode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "b16encode", &["s"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let mut encoded = encode_hex(&data);
+    encoded.make_ascii_uppercase();
+    Ok(vm.new_bytes(encoded))
+}
+
+/// `b16decode(s, casefold=False)`.
+fn b16decode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "b16decode", &["s", "casefold"], 1)?;
+    let mut data = decode_argument(vm, values[0].as_ref().unwrap())?;
+    if flag(vm, &values[1])? {
+        data.make_ascii_uppercase();
+    }
+    if data
+        .iter()
+        .any(|byte| !byte.is_ascii_digit() && !(b'A'..=b'F').contains(byte))
+    {
+        return Err(binascii::error(vm, "Non-base16 digit found"));
+    }
+    match decode_hex(&data) {
+        Ok(decoded) => Ok(vm.new_bytes(decoded)),
+        Err(message) => Err(binascii::error(vm, message)),
+    }
+}
@@ -0,0 +1,227 @@
+//! `binascii`: conversions between binary data and hexadecimal or base64
+//! text. `base64` is built on the codecs here, as in CPython.
+
+use super::super::archive;
+use super::super::builtins::index_value;
+use super::super::bytes::{bytes_like, hex_separator, hex_with_separator};
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{
+    add_attribute, bind_arguments, bytes_argument, module_error, new_native_class,
+    new_native_module,
+};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        "binascii",
+        &[
+            ("a2b_base64", a2b_base64),
+            ("a2b_hex", unhexlify),
+            ("b2a_base64", b2a_base64),
+            ("b2a_hex", hexlify),
+            ("crc32", crc32),
+            ("hexlify", hexlify),
+            ("unhexlify", unhexlify),
+        ],
+    );
+    let value_error = vm.exceptions.value_error.clone();
+    let error = new_native_class(vm, "binascii", "Error", &value_error, &[])?;
+    add_attribute(vm, &module, "Error", error);
+    let exception = vm.exceptions.exception.clone();
+    let incomplete = new_native_class(vm, "binascii", "Incomplete", &exception, &[])?;
+    add_attribute(vm, &module, "Incomplete", incomplete);
+    Ok(module)
+}
+
+/// A `binascii.Error`.
+pub(super) fn error(vm: &mut Vm, message: &str) -> ObjectRef {
+    module_error(vm, "binascii", "Error", message.to_string())
+}
+
+/// The data of an argument that is text to decode: bytes, or a str of
+/// ASCII characters.
+fn ascii_argument(vm: &mut Vm, value: &ObjectRef) -> PyResult<Vec<u8>> {
+    if let Some(data) = bytes_like(value) {
+        return Ok(data.into_owned());
+    }
+    match value.payload {
+        Payload::Str(ref text) if text.is_ascii() => Ok(text.as_bytes().to_vec()),
+        Payload::Str(_) => {
+            let message = "string argument should contain only ASCII characters".to_string();
+            Err(vm.new_value_error(message))
+        }
+        _ => {
+            let message = format!(
+                "argument should be bytes, buffer or ASCII string, not '{}'",
+                value.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// `data` in base64, with a newline after it if `newline`.
+pub(super) fn encode_base64(data: &[u8], newline: bool) -> Vec<u8> {
+    let mut encoded = Vec::with_capacity(data.len().div_ceil(3) * 4 + 1);
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (index, &byte)| {
+            bits | u32::from(byte) << (16 - 8 * index)
+        });
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(bits >> (18 - 6 * index) & 0x3f) as usize]);
+            } else {
+                encoded.push(b'=');
+            }
+        }
+    }
+    if newline {
+        encoded.push(b'\n');
+    }
+    encoded
+}
+
+/// Decodes base64 as `binascii.a2b_base64` does. Characters outside the
+/// alphabet are skipped, and decoding stops at the padding that ends a
+/// group of four, unless `strict`, when both are errors.
+pub(super) fn decode_base64(data: &[u8], strict: bool) -> Result<Vec<u8>, String> {
+    if strict && data.first() == Some(&b'=') {
+        return Err("Leading padding not allowed".to_string());
+    }
+    let mut decoded = Vec::with_capacity(data.len() / 4 * 3);
+    let mut position = 0;
+    let mut left = 0u32;
+    let mut pads = 0;
+    let mut padding_started = false;
+    for (index, &byte) in data.iter().enumerate() {
+        if byte == b'=' {
+            padding_started = true;
+            if position >= 2 {
+                pads += 1;
+                if position + pads >= 4 {
+                    if strict && index + 1 < data.len() {
+                        return Err("Excess data after padding".to_string());
+                    }
+                    return Ok(decoded);
+                }
+            }
+            continue;
+        }
+        let value = match BASE64_ALPHABET.iter().position(|&letter| letter == byte) {
+            Some(value) => value as u32,
+            None if strict => return Err("Only base64 data is allowed".to_string()),
+            None => continue,
+        };
+        if strict && padding_started {
+            return Err("Discontinuous padding not allowed".to_string());
+        }
+        pads = 0;
+        match position {
+            0 => left = value,
+            1 => {
+                decoded.push((left << 2 | value >> 4) as u8);
+                left = value & 0x0f;
+            }
+            2 => {
+                decoded.push((left << 4 | value >> 2) as u8);
+                left = value & 0x03;
+            }
+            _ => decoded.push((left << 6 | value) as u8),
+        }
+        position = (position + 1) % 4;
+    }
+    match position {
+        0 => Ok(decoded),
+        1 => Err(format!(
+            "Invalid base64-encoded string: number of data characters ({}) cannot be 1 more \
+             than a multiple of 4",
+            decoded.len() / 3 * 4 + 1
+        )),
+        _ => Err("Incorrect padding".to_string()),
+    }
+}
+
+/// `data` in lowercase hexadecimal.
+pub(super) fn encode_hex(data: &[u8]) -> Vec<u8> {
+    hex_with_separator(data, None, 1)
+}
+
+/// The bytes written in hexadecimal as `text`.
+pub(super) fn decode_hex(text: &[u8]) -> Result<Vec<u8>, &'static str> {
+    if !text.len().is_multiple_of(2) {
+        return Err("Odd-length string");
+    }
+    text.chunks(2)
+        .map(|pair| {
+            let digit = |byte: u8| char::from(byte).to_digit(16);
+            match (digit(pair[0]), digit(pair[1])) {
+                (Some(high), Some(low)) => Ok((high << 4 | low) as u8),
+                _ => Err("Non-hexadecimal digit found"),
+            }
+        })
+        .collect()
+}
+
+/// `hexlify(data, sep=None, bytes_per_sep=1)`, also `b2a_hex`: the data in
+/// hexadecimal, with `sep` between each group of `bytes_per_sep` bytes,
+/// counted from the right, or from the left if it is negative.
+fn hexlify(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "hexlify", &["data", "sep", "bytes_per_sep"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let separator = hex_separator(vm, values[1].as_ref())?;
+    let group = match values[2] {
+        Some(ref group) => index_value(vm, group)?,
+        None => 1,
+    };
+    Ok(vm.new_bytes(hex_with_separator(&data, separator, group)))
+}
+
+/// `unhexlify(hexstr)`, also `a2b_hex`.
+fn unhexlify(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "unhexlify", &["hexstr"], 1)?;
+    let text = ascii_argument(vm, values[0].as_ref().unwrap())?;
+    match decode_hex(&text) {
+        Ok(data) => Ok(vm.new_bytes(data)),
+        Err(message) => Err(error(vm, message)),
+    }
+}
+
+/// `b2a_base64(data, *, newline=True)`.
+fn b2a_base64(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "b2a_base64", &["data", "newline"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let newline = match values[1] {
+        Some(ref newline) => vm.is_true(newline)?,
+        None => true,
+    };
+    Ok(vm.new_bytes(encode_base64(&data, newline)))
+}
+
+/// `a2b_base64(data, *, strict_mode=False)`.
+fn a2b_base64(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "a2b_base64", &["data", "strict_mode"], 1)?;
+    let data = ascii_argument(vm, values[0].as_ref().unwrap())?;
+    let strict = match values[1] {
+        Some(ref strict) => vm.is_true(strict)?,
+        None => false,
+    };
+    match decode_base64(&data, strict) {
+        Ok(decoded) => Ok(vm.new_bytes(decoded)),
+        Err(message) => Err(error(vm, &message)),
+    }
+}
+
+/// `crc32(data, crc=0, /)`.
+fn crc32(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "crc32", &["data", "crc"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let start = match values[1] {
+        Some(ref start) => index_value(vm, start)? as u32,
+        None => 0,
+    };
+    Ok(vm.new_int(i64::from(archive::crc32(&data, start))))
+}
@@ -0,0 +1,1984 @@
+//! `configparser`: INI files of sections of `name = value` options, with
+//! the `%(name)s` references of `BasicInterpolation` or the
+//! `${section:name}` ones of `ExtendedInterpolation` replaced on reading.
+//!
+//! A parser keeps its options in dicts in its attributes, as CPython's
+//! does: `_defaults` for the default section and `_sections` for the rest,
+//! each mapping section names to dicts of options. The interpolation
+//! classes only mark which kind a parser uses; their methods are the
+//! parser's own.
+
+use std::collections::HashSet;
+use std::fs;
+
+use ast::repr_str;
+
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{
+    add_attribute, bind_arguments, module_error, new_native_class, new_native_module, str_argument,
+    utf8_text,
+};
+
+/// How many references deep interpolation follows before giving up.
+const MAX_INTERPOLATION_DEPTH: usize = 10;
+
+/// The exception classes with their bases, `Error` deriving from
+/// `Exception`.
+const ERRORS: [(&str, &str); 10] = [
+    ("NoSectionError", "Error"),
+    ("DuplicateSectionError", "Error"),
+    ("DuplicateOptionError", "Error"),
+    ("NoOptionError", "Error"),
+    ("InterpolationError", "Error"),
+    ("InterpolationMissingOptionError", "InterpolationError"),
+    ("InterpolationSyntaxError", "InterpolationError"),
+    ("InterpolationDepthError", "InterpolationError"),
+    ("ParsingError", "Error"),
+    ("MissingSectionHeaderError", "ParsingError"),
+];
+
+/// The strings `getboolean` accepts, in lowercase.
+const BOOLEAN_STATES: [(&str, bool); 8] = [
+    ("1", true),
+    ("yes", true),
+    ("true", true),
+    ("on", true),
+    ("0", false),
+    ("no", false),
+    ("false", false),
+    ("off", false),
+];
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "configparser", &[]);
+    let exception = vm.exceptions.exception.clone();
+    let error = new_native_class(vm, "configparser", "Error", &exception, &[])?;
+    add_attribute(vm, &module, "Error", error);
+    for &(name, base) in &ERRORS {
+        let base = vm.getattr(&module, base)?;
+        let class = new_native_class(vm, "configparser", name, &base, &[])?;
+        add_attribute(vm, &module, name, class);
+    }
+    let object = vm.types.object.clone();
+    let interpolation = new_native_class(vm, "configparser", "Interpolation", &object, &[])?;
+    add_attribute(vm, &module, "Interpolation", interpolation.clone());
+    for name in &["BasicInterpolation", "ExtendedInterpolation"] {
+        let class = new_native_class(vm, "configparser", name, &interpolation, &[])?;
+        add_attribute(vm, &module, name, class);
+    }
+    let raw_parser = new_native_class(
+        vm,
+        "configparser",
+        "RawConfigParser",
+        &object,
+        &[
+            ("__contains__", contains),
+            ("__delitem__", delitem),
+            ("__getitem__", getitem),
+            ("__init__", raw_init),
+            ("__iter__", iter),
+            ("__len__", len),
+            ("__setitem__", setitem),
+            ("add_section", add_section),
+            ("defaults", defaults),
+            ("get", get),
+            ("getboolean", getboolean),
+            ("getfloat", getfloat),
+            ("getint", getint),
+            ("has_option", has_option),
+            ("has_section", has_section),
+            ("items", items),
+            ("options", options),
+            ("optionxform", optionxform_method),
+            ("read", read),
+            ("read_dict", read_dict),
+            ("read_file", read_file),
+            ("read_string", read_string),
+            ("remove_option", remove_option),
+            ("remove_section", remove_section),
+            ("sections", sections),
+            ("set", set),
+            ("write", write),
+        ],
+    )?;
+    let states = vm.new_dict();
+    for &(name, state) in &BOOLEAN_STATES {
+        let state = vm.new_bool(state);
+        vm.dict_set_str(states.as_dict().unwrap(), name, state);
+    }
+    vm.setattr(&raw_parser, "BOOLEAN_STATES", states)?;
+    add_attribute(vm, &module, "RawConfigParser", raw_parser.clone());
+    let parser = new_native_class(
+        vm,
+        "configparser",
+        "ConfigParser",
+        &raw_parser,
+        &[
+            ("__init__", init),
+            ("add_section", checked_add_section),
+            ("set", checked_set),
+        ],
+    )?;
+    add_attribute(vm, &module, "ConfigParser", parser);
+    let proxy = new_native_class(
+        vm,
+        "configparser",
+        "SectionProxy",
+        &object,
+        &[
+            ("__contains__", proxy_contains),
+            ("__delitem__", proxy_delitem),
+            ("__getitem__", proxy_getitem),
+            ("__init__", proxy_init),
+            ("__iter__", proxy_iter),
+            ("__len__", proxy_len),
+            ("__repr__", proxy_repr),
+            ("__setitem__", proxy_setitem),
+            ("get", proxy_get),
+            ("getboolean", proxy_getboolean),
+            ("getfloat", proxy_getfloat),
+            ("getint", proxy_getint),
+            ("items", proxy_items),
+            ("keys", proxy_keys),
+            ("values", proxy_values),
+        ],
+    )?;
+    add_attribute(vm, &module, "SectionProxy", proxy);
+    let default_section = vm.new_str("DEFAULT");
+    add_attribute(vm, &module, "DEFAULTSECT", default_section);
+    let depth = vm.new_int(MAX_INTERPOLATION_DEPTH as i64);
+    add_attribute(vm, &module, "MAX_INTERPOLATION_DEPTH", depth);
+    Ok(module)
+}
+
+fn class(vm: &mut Vm, name: &str) -> PyResult {
+    let module = vm.import_module("configparser")?;
+    vm.getattr(&module, name)
+}
+
+/// An exception of the class `class` of this module, with `message` in its
+/// `message` attribute as well, and the other `attributes` set.
+fn error(
+    vm: &mut Vm,
+    class: &str,
+    message: String,
+    attributes: Vec<(&str, ObjectRef)>,
+) -> ObjectRef {
+    let error = module_error(vm, "configparser", class, message.clone());
+    let message = vm.new_str(&message);
+    let _ = vm.setattr(&error, "message", message);
+    for (name, value) in attributes {
+        let _ = vm.setattr(&error, name, value);
+    }
+    error
+}
+
+fn no_section_error(vm: &mut Vm, section: &ObjectRef) -> PyResult<ObjectRef> {
+    let message = format!("No section: {}", vm.repr(section)?);
+    Ok(error(
+        vm,
+        "NoSectionError",
+        message,
+        vec![("section", section.clone())],
+    ))
+}
+
+fn no_option_error(vm: &mut Vm, option: &ObjectRef, section: &ObjectRef) -> PyResult<ObjectRef> {
+    let message = format!(
+        "No option {} in section: {}",
+        vm.repr(option)?,
+        vm.repr(section)?
+    );
+    let attributes = vec![("option", option.clone()), ("section", section.clone())];
+    Ok(error(vm, "NoOptionError", message, attributes))
+}
+
+/// Where a duplicate was found, as the duplicate errors word it.
+fn place(source: Option<&str>, lineno: Option<usize>) -> Option<String> {
+    source.map(|source| match lineno {
+        Some(lineno) => format!(
+            "While reading from {} [line {:2}]",
+            repr_str(source),
+            lineno
+        ),
+        None => format!("While reading from {}", repr_str(source)),
+    })
+}
+
+/// The source and line number attributes of the duplicate errors.
+fn place_attributes(
+    vm: &mut Vm,
+    source: Option<&str>,
+    lineno: Option<usize>,
+) -> Vec<(&'static str, ObjectRef)> {
+    let source = match source {
+        Some(source) => vm.new_str(source),
+        None => vm.none(),
+    };
+    let lineno = match lineno {
+        Some(lineno) => vm.new_int(lineno as i64),
+        None => vm.none(),
+    };
+    vec![("source", source), ("lineno", lineno)]
+}
+
+fn duplicate_section_error(
+    vm: &mut Vm,
+    section: &ObjectRef,
+    source: Option<&str>,
+    lineno: Option<usize>,
+) -> PyResult<ObjectRef> {
+    let duplicate = format!("{} already exists", vm.repr(section)?);
+    let message = match place(source, lineno) {
+        Some(place) => format!("{}: section {}", place, duplicate),
+        None => format!("Section {}", duplicate),
+    };
+    let mut attributes = place_attributes(vm, source, lineno);
+    attributes.push(("section", section.clone()));
+    Ok(error(vm, "DuplicateSectionError", message, attributes))
+}
+
+fn duplicate_option_error(
+    vm: &mut Vm,
+    section: &ObjectRef,
+    option: &ObjectRef,
+    source: Option<&str>,
+    lineno: Option<usize>,
+) -> PyResult<ObjectRef> {
+    let duplicate = format!(
+        "{} in section {} already exists",
+        vm.repr(option)?,
+        vm.repr(section)?
+    );
+    let message = match place(source, lineno) {
+        Some(place) => format!("{}: option {}", place, duplicate),
+        None => format!("Option {}", duplicate),
+    };
+    let mut attributes = place_attributes(vm, source, lineno);
+    attributes.push(("section", section.clone()));
+    attributes.push(("option", option.clone()));
+    Ok(error(vm, "DuplicateOptionError", message, attributes))
+}
+
+/// A dict attribute of a parser.
+fn dict_attribute(vm: &mut Vm, this: &ObjectRef, name: &str) -> PyResult {
+    let value = vm.getattr(this, name)?;
+    if value.as_dict().is_none() {
+        let message = format!("{} must be a dict, not {}", name, value.type_name());
+        return Err(vm.new_type_error(message));
+    }
+    Ok(value)
+}
+
+fn entries(dict: &ObjectRef) -> Vec<(ObjectRef, ObjectRef)> {
+    dict.as_dict()
+        .unwrap()
+        .borrow()
+        .iter()
+        .map(|entry| (entry.key.clone(), entry.value.clone()))
+        .collect()
+}
+
+fn keys(dict: &ObjectRef) -> Vec<ObjectRef> {
+    entries(dict).into_iter().map(|(key, _)| key).collect()
+}
+
+/// The `(key, value)` pairs of a mapping, from its `items()`.
+fn mapping_items(vm: &mut Vm, mapping: &ObjectRef) -> PyResult<Vec<(ObjectRef, ObjectRef)>> {
+    let items = vm.getattr(mapping, "items")?;
+    let items = vm.call(&items, Args::default())?;
+    let mut pairs = Vec::new();
+    for item in vm.iterate(&items)? {
+        match vm.iterate(&item)?[..] {
+            [ref key, ref value] => pairs.push((key.clone(), value.clone())),
+            _ => {
+                let message = "items() must give (key, value) pairs".to_string();
+                return Err(vm.new_value_error(message));
+            }
+        }
+    }
+    Ok(pairs)
+}
+
+/// The options of `section`, if there is such a section.
+fn section_options(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+) -> PyResult<Option<ObjectRef>> {
+    let sections = dict_attribute(vm, this, "_sections")?;
+    vm.dict_get(sections.as_dict().unwrap(), section)
+}
+
+fn is_default_section(vm: &mut Vm, this: &ObjectRef, section: &ObjectRef) -> PyResult<bool> {
+    let default_section = vm.getattr(this, "default_section")?;
+    vm.eq(section, &default_section)
+}
+
+/// Whether `section` stands for the defaults, as an empty name or `None`
+/// does in `set`, `has_option` and `remove_option`.
+fn is_defaults(vm: &mut Vm, this: &ObjectRef, section: &ObjectRef) -> PyResult<bool> {
+    Ok(!vm.is_true(section)? || is_default_section(vm, this, section)?)
+}
+
+/// The name of an option as it is stored, from the parser's `optionxform`,
+/// which is lowercasing unless replaced.
+fn optionxform(vm: &mut Vm, this: &ObjectRef, option: &ObjectRef) -> PyResult {
+    let transform = vm.getattr(this, "optionxform")?;
+    vm.call(&transform, Args::new(vec![option.clone()]))
+}
+
+/// `optionxform(optionstr)`.
+fn optionxform_method(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "optionxform", &["self", "optionstr"], 2)?;
+    let option = str_argument(vm, "optionxform", "optionstr", values[1].as_ref().unwrap())?;
+    Ok(vm.new_str(&option.to_lowercase()))
+}
+
+/// `_validate_value_types`: the checks of `ConfigParser` and of setting
+/// through a section, that names and values are strings.
+fn check_types(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: Option<&ObjectRef>,
+    option: Option<&ObjectRef>,
+    value: Option<&ObjectRef>,
+) -> PyResult<()> {
+    let message = if section.is_some_and(|section| section.as_str().is_none()) {
+        "section names must be strings"
+    } else if option.is_some_and(|option| option.as_str().is_none()) {
+        "option keys must be strings"
+    } else if let Some(value) = value {
+        let allow_no_value = vm.getattr(this, "_allow_no_value")?;
+        if (!vm.is_true(&allow_no_value)? || vm.is_true(value)?) && value.as_str().is_none() {
+            "option values must be strings"
+        } else {
+            return Ok(());
+        }
+    } else {
+        return Ok(());
+    };
+    Err(vm.new_type_error(message.to_string()))
+}
+
+/// The syntax a parser reads, from the arguments it was made with.
+struct Syntax {
+    delimiters: Vec<String>,
+    comment_prefixes: Vec<String>,
+    inline_comment_prefixes: Vec<String>,
+    strict: bool,
+    allow_no_value: bool,
+    empty_lines_in_values: bool,
+}
+
+impl Syntax {
+    fn of(vm: &mut Vm, this: &ObjectRef) -> PyResult<Syntax> {
+        let flag = |vm: &mut Vm, name| {
+            let value = vm.getattr(this, name)?;
+            vm.is_true(&value)
+        };
+        Ok(Syntax {
+            delimiters: strings_attribute(vm, this, "_delimiters")?,
+            comment_prefixes: strings_attribute(vm, this, "_comment_prefixes")?,
+            inline_comment_prefixes: strings_attribute(vm, this, "_inline_comment_prefixes")?,
+            strict: flag(vm, "_strict")?,
+            allow_no_value: flag(vm, "_allow_no_value")?,
+            empty_lines_in_values: flag(vm, "_empty_lines_in_values")?,
+        })
+    }
+}
+
+fn strings_attribute(vm: &mut Vm, this: &ObjectRef, name: &str) -> PyResult<Vec<String>> {
+    let value = vm.getattr(this, name)?;
+    vm.iterate(&value)?
+        .iter()
+        .map(|item| vm.str(item))
+        .collect()
+}
+
+/// The kind of interpolation a parser does.
+#[derive(Clone, Copy)]
+enum Interpolation {
+    Raw,
+    Basic,
+    Extended,
+}
+
+fn interpolation(vm: &mut Vm, this: &ObjectRef) -> PyResult<Interpolation> {
+    let interpolation = vm.getattr(this, "_interpolation")?;
+    let kinds = [
+        ("BasicInterpolation", Interpolation::Basic),
+        ("ExtendedInterpolation", Interpolation::Extended),
+    ];
+    for &(name, kind) in &kinds {
+        let class = class(vm, name)?;
+        if Vm::is_instance(&interpolation, &class) {
+            return Ok(kind);
+        }
+    }
+    Ok(Interpolation::Raw)
+}
+
+/// `RawConfigParser(defaults=None, dict_type=dict, allow_no_value=False,
+/// *, delimiters=('=', ':'), comment_prefixes=('#', ';'),
+/// inline_comment_prefixes=None, strict=True, empty_lines_in_values=True,
+/// default_section='DEFAULT', interpolation=Interpolation())`. Dicts keep
+/// their order, so `dict_type` is not used.
+fn raw_init(vm: &mut Vm, args: Args) -> PyResult {
+    initialize(vm, args, false)
+}
+
+/// `ConfigParser(...)`: as `RawConfigParser`, with `BasicInterpolation`
+/// unless another is given.
+fn init(vm: &mut Vm, args: Args) -> PyResult {
+    initialize(vm, args, true)
+}
+
+fn initialize(vm: &mut Vm, args: Args, basic: bool) -> PyResult {
+    let values = bind_arguments(
+        vm,
+        args,
+        "RawConfigParser.__init__",
+        &[
+            "self",
+            "defaults",
+            "dict_type",
+            "allow_no_value",
+            "delimiters",
+            "comment_prefixes",
+            "inline_comment_prefixes",
+            "strict",
+            "empty_lines_in_values",
+            "default_section",
+            "interpolation",
+        ],
+        1,
+    )?;
+    let this = values[0].clone().unwrap();
+    let none = vm.none();
+    let given = |index: usize| values[index].clone().filter(|value| !Vm::is(value, &none));
+    for name in &["_sections", "_defaults", "_proxies"] {
+        let dict = vm.new_dict();
+        vm.setattr(&this, name, dict)?;
+    }
+    let strings = |vm: &mut Vm, value: Option<ObjectRef>, default: &[&str]| -> PyResult {
+        let items = match value {
+            Some(value) => vm.iterate(&value)?,
+            None => default.iter().map(|item| vm.new_str(item)).collect(),
+        };
+        Ok(vm.new_tuple(items))
+    };
+    let delimiters = strings(vm, given(4), &["=", ":"])?;
+    vm.setattr(&this, "_delimiters", delimiters)?;
+    let comment_prefixes = strings(vm, given(5), &["#", ";"])?;
+    vm.setattr(&this, "_comment_prefixes", comment_prefixes)?;
+    let inline_comment_prefixes = strings(vm, given(6), &[])?;
+    vm.setattr(&this, "_inline_comment_prefixes", inline_comment_prefixes)?;
+    let flags = [
+        ("_allow_no_value", 3, false),
+        ("_strict", 7, true),
+        ("_empty_lines_in_values", 8, true),
+    ];
+    for &(name, index, default) in &flags {
+        let flag = match values[index] {
+            Some(ref value) => vm.is_true(value)?,
+            None => default,
+        };
+        let flag = vm.new_bool(flag);
+        vm.setattr(&this, name, flag)?;
+    }
+    let default_section = match values[9] {
+        Some(ref value) => value.clone(),
+        None => vm.new_str("DEFAULT"),
+    };
+    vm.setattr(&this, "default_section", default_section.clone())?;
+    let interpolation = match values[10] {
+        Some(ref value) => value.clone(),
+        None => {
+            let class = class(
+                vm,
+                if basic {
+                    "BasicInterpolation"
+                } else {
+                    "Interpolation"
+                },
+            )?;
+            vm.call(&class, Args::default())?
+        }
+    };
+    vm.setattr(&this, "_interpolation", interpolation)?;
+    let proxy = new_proxy(vm, &this, &default_section)?;
+    let proxies = dict_attribute(vm, &this, "_proxies")?;
+    vm.dict_set(proxies.as_dict().unwrap(), default_section.clone(), proxy)?;
+    if let Some(defaults) = given(1) {
+        // The defaults are read as they are, without the checks of
+        // interpolation syntax that setting them would do.
+        let sections = vm.new_dict();
+        vm.dict_set(sections.as_dict().unwrap(), default_section, defaults)?;
+        add_dict(vm, &this, &sections, "<dict>", false)?;
+    }
+    Ok(none)
+}
+
+fn new_proxy(vm: &mut Vm, this: &ObjectRef, section: &ObjectRef) -> PyResult {
+    let class = class(vm, "SectionProxy")?;
+    vm.call(&class, Args::new(vec![this.clone(), section.clone()]))
+}
+
+/// `defaults()`: the dict of the default section.
+fn defaults(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "defaults", &["self"], 1)?;
+    dict_attribute(vm, values[0].as_ref().unwrap(), "_defaults")
+}
+
+/// `sections()`: the names of the sections other than the default one.
+fn sections(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "sections", &["self"], 1)?;
+    let sections = dict_attribute(vm, values[0].as_ref().unwrap(), "_sections")?;
+    Ok(vm.new_list(keys(&sections)))
+}
+
+/// Adds an empty section, as `RawConfigParser.add_section` does.
+fn new_section(vm: &mut Vm, this: &ObjectRef, section: &ObjectRef) -> PyResult<()> {
+    if is_default_section(vm, this, section)? {
+        let message = format!("Invalid section name: {}", vm.repr(section)?);
+        return Err(vm.new_value_error(message));
+    }
+    let sections = dict_attribute(vm, this, "_sections")?;
+    if vm.dict_contains(sections.as_dict().unwrap(), section)? {
+        return Err(duplicate_section_error(vm, section, None, None)?);
+    }
+    let options = vm.new_dict();
+    vm.dict_set(sections.as_dict().unwrap(), section.clone(), options)?;
+    let proxy = new_proxy(vm, this, section)?;
+    let proxies = dict_attribute(vm, this, "_proxies")?;
+    vm.dict_set(proxies.as_dict().unwrap(), section.clone(), proxy)
+}
+
+/// `add_section(section)`.
+fn add_section(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "add_section", &["self", "section"], 2)?;
+    let (this, section) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    new_section(vm, this, section)?;
+    Ok(vm.none())
+}
+
+/// `ConfigParser.add_section(section)`: only str names.
+fn checked_add_section(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "add_section", &["self", "section"], 2)?;
+    let (this, section) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    check_types(vm, this, Some(section), None, None)?;
+    new_section(vm, this, section)?;
+    Ok(vm.none())
+}
+
+/// `has_section(section)`: the default section does not count.
+fn has_section(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "has_section", &["self", "section"], 2)?;
+    let (this, section) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    let found = section_options(vm, this, section)?.is_some();
+    Ok(vm.new_bool(found))
+}
+
+/// The names of the options of `section`, its own and then the defaults.
+fn option_names(vm: &mut Vm, this: &ObjectRef, section: &ObjectRef) -> PyResult<Vec<ObjectRef>> {
+    let options = match section_options(vm, this, section)? {
+        Some(options) => options,
+        None => return Err(no_section_error(vm, section)?),
+    };
+    let names = vm.new_dict();
+    let defaults = dict_attribute(vm, this, "_defaults")?;
+    vm.dict_update(names.as_dict().unwrap(), &options)?;
+    vm.dict_update(names.as_dict().unwrap(), &defaults)?;
+    Ok(keys(&names))
+}
+
+/// `options(section)`.
+fn options(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "options", &["self", "section"], 2)?;
+    let (this, section) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    let names = option_names(vm, this, section)?;
+    Ok(vm.new_list(names))
+}
+
+fn option_exists(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+    option: &ObjectRef,
+) -> PyResult<bool> {
+    let defaults = dict_attribute(vm, this, "_defaults")?;
+    if is_defaults(vm, this, section)? {
+        let option = optionxform(vm, this, option)?;
+        return vm.dict_contains(defaults.as_dict().unwrap(), &option);
+    }
+    let options = match section_options(vm, this, section)? {
+        Some(options) => options,
+        None => return Ok(false),
+    };
+    let option = optionxform(vm, this, option)?;
+    Ok(vm.dict_contains(options.as_dict().unwrap(), &option)?
+        || vm.dict_contains(defaults.as_dict().unwrap(), &option)?)
+}
+
+/// `has_option(section, option)`: a false `section` is the defaults.
+fn has_option(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "has_option", &["self", "section", "option"], 3)?;
+    let this = values[0].as_ref().unwrap();
+    let found = option_exists(
+        vm,
+        this,
+        values[1].as_ref().unwrap(),
+        values[2].as_ref().unwrap(),
+    )?;
+    Ok(vm.new_bool(found))
+}
+
+/// The options of `section` with those of `vars` over them and the
+/// defaults under them, merged into one dict as `_unify_values` chains
+/// them, or `None` if there is no such section.
+fn unify(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+    vars: Option<&ObjectRef>,
+) -> PyResult<Option<ObjectRef>> {
+    let unified = vm.new_dict();
+    let defaults = dict_attribute(vm, this, "_defaults")?;
+    vm.dict_update(unified.as_dict().unwrap(), &defaults)?;
+    match section_options(vm, this, section)? {
+        Some(options) => vm.dict_update(unified.as_dict().unwrap(), &options)?,
+        None if is_default_section(vm, this, section)? => {}
+        None => return Ok(None),
+    }
+    if let Some(vars) = vars {
+        for (key, value) in mapping_items(vm, vars)? {
+            let key = optionxform(vm, this, &key)?;
+            let value = if Vm::is(&value, &vm.none()) {
+                value
+            } else {
+                let text = vm.str(&value)?;
+                vm.new_str(&text)
+            };
+            vm.dict_set(unified.as_dict().unwrap(), key, value)?;
+        }
+    }
+    Ok(Some(unified))
+}
+
+/// What looking up an option found.
+enum Found {
+    /// The raw value, with the options it was found among and its name.
+    Value(ObjectRef, ObjectRef, ObjectRef),
+    NoSection,
+    NoOption(ObjectRef),
+}
+
+fn find(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+    option: &ObjectRef,
+    vars: Option<&ObjectRef>,
+) -> PyResult<Found> {
+    let unified = match unify(vm, this, section, vars)? {
+        Some(unified) => unified,
+        None => return Ok(Found::NoSection),
+    };
+    let option = optionxform(vm, this, option)?;
+    match vm.dict_get(unified.as_dict().unwrap(), &option)? {
+        Some(value) => Ok(Found::Value(value, unified, option)),
+        None => Ok(Found::NoOption(option)),
+    }
+}
+
+/// The arguments of the getters after `section` and `option`.
+struct Lookup {
+    raw: bool,
+    vars: Option<ObjectRef>,
+    fallback: Option<ObjectRef>,
+}
+
+impl Lookup {
+    fn new(
+        vm: &mut Vm,
+        raw: &Option<ObjectRef>,
+        vars: &Option<ObjectRef>,
+        fallback: &Option<ObjectRef>,
+    ) -> PyResult<Lookup> {
+        let none = vm.none();
+        Ok(Lookup {
+            raw: match *raw {
+                Some(ref raw) => vm.is_true(raw)?,
+                None => false,
+            },
+            vars: vars.clone().filter(|vars| !Vm::is(vars, &none)),
+            fallback: fallback.clone(),
+        })
+    }
+}
+
+/// The value of an option, interpolated unless `raw`, or else the
+/// fallback, as `get` finds it.
+fn lookup(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+    option: &ObjectRef,
+    lookup: &Lookup,
+) -> PyResult<Result<ObjectRef, ObjectRef>> {
+    match find(vm, this, section, option, lookup.vars.as_ref())? {
+        Found::Value(value, unified, option) => {
+            if lookup.raw || Vm::is(&value, &vm.none()) {
+                return Ok(Ok(value));
+            }
+            interpolate(vm, this, section, &option, &value, &unified).map(Ok)
+        }
+        _ if lookup.fallback.is_some() => Ok(Ok(lookup.fallback.clone().unwrap())),
+        Found::NoSection => Ok(Err(no_section_error(vm, section)?)),
+        Found::NoOption(option) => Ok(Err(no_option_error(vm, &option, section)?)),
+    }
+}
+
+/// The value of an option as `get` finds it: the error for a missing one
+/// is raised.
+fn get_value(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+    option: &ObjectRef,
+    options: &Lookup,
+) -> PyResult {
+    lookup(vm, this, section, option, options)?
+}
+
+/// Converts the value of an option for `getint`, `getfloat` and
+/// `getboolean`.
+type Converter = fn(&mut Vm, &ObjectRef, &ObjectRef) -> PyResult;
+
+/// The value of an option converted by `converter`, or the fallback, which
+/// is not converted.
+fn get_converted(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+    option: &ObjectRef,
+    options: &Lookup,
+    converter: Converter,
+) -> PyResult {
+    let without_fallback = Lookup {
+        raw: options.raw,
+        vars: options.vars.clone(),
+        fallback: None,
+    };
+    match lookup(vm, this, section, option, &without_fallback)? {
+        Ok(value) => converter(vm, this, &value),
+        Err(_) if options.fallback.is_some() => Ok(options.fallback.clone().unwrap()),
+        Err(error) => Err(error),
+    }
+}
+
+fn to_int(vm: &mut Vm, _: &ObjectRef, value: &ObjectRef) -> PyResult {
+    let int = vm.types.int.clone();
+    vm.call(&int, Args::new(vec![value.clone()]))
+}
+
+fn to_float(vm: &mut Vm, _: &ObjectRef, value: &ObjectRef) -> PyResult {
+    let float = vm.types.float.clone();
+    vm.call(&float, Args::new(vec![value.clone()]))
+}
+
+/// A boolean from the parser's `BOOLEAN_STATES`.
+fn to_boolean(vm: &mut Vm, this: &ObjectRef, value: &ObjectRef) -> PyResult {
+    let text = vm.str(value)?;
+    let states = vm.getattr(this, "BOOLEAN_STATES")?;
+    let key = vm.new_str(&text.to_lowercase());
+    if !vm.contains(&states, &key)? {
+        return Err(vm.new_value_error(format!("Not a boolean: {}", text)));
+    }
+    vm.getitem(&states, &key)
+}
+
+const GETTER_PARAMETERS: [&str; 6] = ["self", "section", "option", "raw", "vars", "fallback"];
+
+fn getter(vm: &mut Vm, args: Args, name: &str, converter: Option<Converter>) -> PyResult {
+    let values = bind_arguments(vm, args, name, &GETTER_PARAMETERS, 3)?;
+    let this = values[0].as_ref().unwrap();
+    let (section, option) = (values[1].as_ref().unwrap(), values[2].as_ref().unwrap());
+    let options = Lookup::new(vm, &values[3], &values[4], &values[5])?;
+    match converter {
+        Some(converter) => get_converted(vm, this, section, option, &options, converter),
+        None => get_value(vm, this, section, option, &options),
+    }
+}
+
+/// `get(section, option, *, raw=False, vars=None, fallback=<unset>)`: the
+/// option found first in `vars`, the section and the defaults.
+fn get(vm: &mut Vm, args: Args) -> PyResult {
+    getter(vm, args, "get", None)
+}
+
+/// `getint(section, option, *, raw=False, vars=None, fallback=<unset>)`.
+fn getint(vm: &mut Vm, args: Args) -> PyResult {
+    getter(vm, args, "getint", Some(to_int))
+}
+
+/// `getfloat(section, option, *, raw=False, vars=None, fallback=<unset>)`.
+fn getfloat(vm: &mut Vm, args: Args) -> PyResult {
+    getter(vm, args, "getfloat", Some(to_float))
+}
+
+/// `getboolean(section, option, *, raw=False, vars=None,
+/// fallback=<unset>)`.
+fn getboolean(vm: &mut Vm, args: Args) -> PyResult {
+    getter(vm, args, "getboolean", Some(to_boolean))
+}
+
+/// `items(section=<unset>, raw=False, vars=None)`: the `(name, value)`
+/// pairs of a section, the defaults first, or without a section the pairs
+/// of each section name and its proxy.
+fn items(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "items", &["self", "section", "raw", "vars"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let section = match values[1] {
+        Some(ref section) => section,
+        None => {
+            let proxies = dict_attribute(vm, this, "_proxies")?;
+            let pairs = section_names(vm, this)?
+                .into_iter()
+                .map(|name| {
+                    let proxy = vm.dict_get(proxies.as_dict().unwrap(), &name)?;
+                    let proxy = proxy.unwrap_or_else(|| vm.none());
+                    Ok(vm.new_tuple(vec![name, proxy]))
+                })
+                .collect::<PyResult<_>>()?;
+            return Ok(vm.new_list(pairs));
+        }
+    };
+    let options = Lookup::new(vm, &values[2], &values[3], &None)?;
+    let unified = match unify(vm, this, section, None)? {
+        Some(unified) => unified,
+        None => return Err(no_section_error(vm, section)?),
+    };
+    let names = keys(&unified);
+    if let Some(ref vars) = options.vars {
+        for (key, value) in mapping_items(vm, vars)? {
+            let key = optionxform(vm, this, &key)?;
+            vm.dict_set(unified.as_dict().unwrap(), key, value)?;
+        }
+    }
+    let mut pairs = Vec::with_capacity(names.len());
+    for name in names {
+        let value = vm.dict_get(unified.as_dict().unwrap(), &name)?.unwrap();
+        let value = if options.raw {
+            value
+        } else {
+            interpolate(vm, this, section, &name, &value, &unified)?
+        };
+        pairs.push(vm.new_tuple(vec![name, value]));
+    }
+    Ok(vm.new_list(pairs))
+}
+
+/// The default section's name and then the names of the sections, in the
+/// order the parser iterates over them.
+fn section_names(vm: &mut Vm, this: &ObjectRef) -> PyResult<Vec<ObjectRef>> {
+    let default_section = vm.getattr(this, "default_section")?;
+    let sections = dict_attribute(vm, this, "_sections")?;
+    let mut names = vec![default_section];
+    names.extend(keys(&sections));
+    Ok(names)
+}
+
+/// Stores an option, as `RawConfigParser.set` does.
+fn set_option(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+    option: &ObjectRef,
+    value: ObjectRef,
+    check_syntax: bool,
+) -> PyResult<()> {
+    if check_syntax && vm.is_true(&value)? {
+        check_interpolation_syntax(vm, this, &value)?;
+    }
+    let options = if is_defaults(vm, this, section)? {
+        dict_attribute(vm, this, "_defaults")?
+    } else {
+        match section_options(vm, this, section)? {
+            Some(options) => options,
+            None => return Err(no_section_error(vm, section)?),
+        }
+    };
+    let option = optionxform(vm, this, option)?;
+    vm.dict_set(options.as_dict().unwrap(), option, value)
+}
+
+/// `set(section, option, value=None)`.
+fn set(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "set", &["self", "section", "option", "value"], 3)?;
+    let this = values[0].as_ref().unwrap();
+    let (section, option) = (values[1].as_ref().unwrap(), values[2].as_ref().unwrap());
+    let value = values[3].clone().unwrap_or_else(|| vm.none());
+    set_option(vm, this, section, option, value, true)?;
+    Ok(vm.none())
+}
+
+/// `ConfigParser.set(section, option, value=None)`: only str options and
+/// values.
+fn checked_set(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "set", &["self", "section", "option", "value"], 3)?;
+    let this = values[0].as_ref().unwrap();
+    let (section, option) = (values[1].as_ref().unwrap(), values[2].as_ref().unwrap());
+    let value = values[3].clone().unwrap_or_else(|| vm.none());
+    check_types(vm, this, None, Some(option), Some(&value))?;
+    set_option(vm, this, section, option, value, true)?;
+    Ok(vm.none())
+}
+
+/// `remove_option(section, option)`: whether the option was there.
+fn remove_option(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "remove_option", &["self", "section", "option"], 3)?;
+    let this = values[0].as_ref().unwrap();
+    let (section, option) = (values[1].as_ref().unwrap(), values[2].as_ref().unwrap());
+    let removed = remove(vm, this, section, option)?;
+    Ok(vm.new_bool(removed))
+}
+
+fn remove(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+    option: &ObjectRef,
+) -> PyResult<bool> {
+    let options = if is_defaults(vm, this, section)? {
+        dict_attribute(vm, this, "_defaults")?
+    } else {
+        match section_options(vm, this, section)? {
+            Some(options) => options,
+            None => return Err(no_section_error(vm, section)?),
+        }
+    };
+    let option = optionxform(vm, this, option)?;
+    Ok(vm
+        .dict_remove(options.as_dict().unwrap(), &option)?
+        .is_some())
+}
+
+/// `remove_section(section)`: whether the section was there.
+fn remove_section(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "remove_section", &["self", "section"], 2)?;
+    let (this, section) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    let sections = dict_attribute(vm, this, "_sections")?;
+    let removed = vm
+        .dict_remove(sections.as_dict().unwrap(), section)?
+        .is_some();
+    if removed {
+        let proxies = dict_attribute(vm, this, "_proxies")?;
+        vm.dict_remove(proxies.as_dict().unwrap(), section)?;
+    }
+    Ok(vm.new_bool(removed))
+}
+
+/// `read(filenames, encoding=None)`: reads each file that can be opened,
+/// skipping the others, and returns the names of those read.
+fn read(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read", &["self", "filenames", "encoding"], 2)?;
+    let (this, filenames) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    let filenames = match filenames.payload {
+        Payload::Str(_) => vec![filenames.clone()],
+        _ => vm.iterate(filenames)?,
+    };
+    let mut read = Vec::new();
+    for filename in filenames {
+        let path = match filename.as_str() {
+            Some(path) => path.to_string(),
+            None => {
+                let message = format!(
+                    "expected str, bytes or os.PathLike object, not {}",
+                    filename.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        };
+        let data = match fs::read(&path) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        let text = utf8_text(vm, data)?
+            .replace("\r\n", "\n")
+            .replace('\r', "\n");
+        read_text(vm, this, &text, &path)?;
+        read.push(filename);
+    }
+    Ok(vm.new_list(read))
+}
+
+/// `read_string(string, source='<string>')`.
+fn read_string(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read_string", &["self", "string", "source"], 2)?;
+    let this = values[0].as_ref().unwrap();
+    let text = str_argument(vm, "read_string", "string", values[1].as_ref().unwrap())?;
+    let source = match values[2] {
+        Some(ref source) => vm.str(source)?,
+        None => "<string>".to_string(),
+    };
+    read_text(vm, this, &text, &source)?;
+    Ok(vm.none())
+}
+
+/// `read_file(f, source=None)`: reads the lines `f` gives, naming them by
+/// `source` or else by the `name` of `f`.
+fn read_file(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read_file", &["self", "f", "source"], 2)?;
+    let (this, file) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    let source = match values[2] {
+        Some(ref source) if !Vm::is(source, &vm.none()) => vm.str(source)?,
+        _ => match vm.getattr(file, "name") {
+            Ok(name) => vm.str(&name)?,
+            Err(_) => "<???>".to_string(),
+        },
+    };
+    let lines = vm
+        .iterate(file)?
+        .iter()
+        .map(|line| vm.str(line))
+        .collect::<PyResult<Vec<String>>>()?;
+    parse(vm, this, &lines, &source)?;
+    Ok(vm.none())
+}
+
+fn read_text(vm: &mut Vm, this: &ObjectRef, text: &str, source: &str) -> PyResult<()> {
+    let lines: Vec<String> = text.split_inclusive('\n').map(str::to_string).collect();
+    parse(vm, this, &lines, source)
+}
+
+/// `read_dict(dictionary, source='<dict>')`: adds the sections of a
+/// mapping of section names to mappings of options, with the names and
+/// values made strings.
+fn read_dict(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read_dict", &["self", "dictionary", "source"], 2)?;
+    let this = values[0].as_ref().unwrap();
+    let source = match values[2] {
+        Some(ref source) => vm.str(source)?,
+        None => "<dict>".to_string(),
+    };
+    add_dict(vm, this, values[1].as_ref().unwrap(), &source, true)?;
+    Ok(vm.none())
+}
+
+fn add_dict(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    dictionary: &ObjectRef,
+    source: &str,
+    check_syntax: bool,
+) -> PyResult<()> {
+    let strict = vm.getattr(this, "_strict")?;
+    let strict = vm.is_true(&strict)?;
+    let mut added_sections = HashSet::new();
+    let mut added_options = HashSet::new();
+    for (section, options) in mapping_items(vm, dictionary)? {
+        let name = vm.str(&section)?;
+        let section = vm.new_str(&name);
+        if let Err(error) = new_section(vm, this, &section) {
+            let duplicate = class(vm, "DuplicateSectionError")?;
+            let value_error = vm.exceptions.value_error.clone();
+            let expected =
+                Vm::is_instance(&error, &duplicate) || Vm::is_instance(&error, &value_error);
+            if !expected || (strict && added_sections.contains(&name)) {
+                return Err(error);
+            }
+        }
+        added_sections.insert(name.clone());
+        for (key, value) in mapping_items(vm, &options)? {
+            let key = vm.str(&key)?;
+            let key = vm.new_str(&key);
+            let key = optionxform(vm, this, &key)?;
+            let value = if Vm::is(&value, &vm.none()) {
+                value
+            } else {
+                let text = vm.str(&value)?;
+                vm.new_str(&text)
+            };
+            let identity = (name.clone(), vm.repr(&key)?);
+            if strict && added_options.contains(&identity) {
+                return Err(duplicate_option_error(
+                    vm,
+                    &section,
+                    &key,
+                    Some(source),
+                    None,
+                )?);
+            }
+            added_options.insert(identity);
+            set_option(vm, this, &section, &key, value, check_syntax)?;
+        }
+    }
+    Ok(())
+}
+
+/// Where the comment on `line` starts, if it has one: at the start for a
+/// line that begins with a comment prefix, or at an inline comment prefix
+/// that begins the line or follows whitespace.
+fn comment_start(line: &str, syntax: &Syntax) -> Option<usize> {
+    let mut start: Option<usize> = None;
+    let mut prefixes: Vec<(&str, Option<usize>)> = syntax
+        .inline_comment_prefixes
+        .iter()
+        .map(|prefix| (prefix.as_str(), None))
+        .collect();
+    // Each round looks at the next place each prefix appears, as
+    // CPython's parser does, so the earliest comment in the first round
+    // that finds one wins.
+    while start.is_none() && !prefixes.is_empty() {
+        let mut next = Vec::new();
+        for (prefix, previous) in prefixes {
+            let from = match previous {
+                Some(index) => index + line[index..].chars().next().map_or(1, char::len_utf8),
+                None => 0,
+            };
+            let index = match line.get(from..).and_then(|rest| rest.find(prefix)) {
+                Some(index) => from + index,
+                None => continue,
+            };
+            next.push((prefix, Some(index)));
+            if line[..index]
+                .chars()
+                .next_back()
+                .is_none_or(char::is_whitespace)
+            {
+                start = Some(start.map_or(index, |start| start.min(index)));
+            }
+        }
+        prefixes = next;
+    }
+    let stripped = line.trim();
+    if syntax
+        .comment_prefixes
+        .iter()
+        .any(|prefix| stripped.starts_with(prefix.as_str()))
+    {
+        start = Some(0);
+    }
+    start
+}
+
+/// The name in a section header, `[name]`, which may itself hold brackets.
+fn section_header(value: &str) -> Option<&str> {
+    let inside = value.strip_prefix('[')?;
+    match inside.rfind(']') {
+        Some(0) | None => None,
+        Some(end) => Some(&inside[..end]),
+    }
+}
+
+/// The name and value of an option line, split at the first delimiter. A
+/// line without one is an option without a value if the parser allows
+/// them.
+fn option_line<'a>(value: &'a str, syntax: &Syntax) -> Option<(&'a str, Option<&'a str>)> {
+    for (index, _) in value.char_indices() {
+        let delimiter = syntax.delimiters.iter().find(|delimiter| {
+            !delimiter.is_empty() && value[index..].starts_with(delimiter.as_str())
+        });
+        if let Some(delimiter) = delimiter {
+            let name = value[..index].trim_end();
+            return Some((name, Some(value[index + delimiter.len()..].trim())));
+        }
+    }
+    if syntax.allow_no_value {
+        Some((value, None))
+    } else {
+        None
+    }
+}
+
+/// Reads `lines`, which keep their line ends, as `_read` does: options go
+/// into the section of the header above them, lines indented further than
+/// an option continue its value, and the lines that are neither are
+/// reported together in a `ParsingError` at the end.
+fn parse(vm: &mut Vm, this: &ObjectRef, lines: &[String], source: &str) -> PyResult<()> {
+    let mut errors = Vec::new();
+    let parsed = parse_lines(vm, this, lines, source, &mut errors);
+    join_multiline_values(vm, this)?;
+    parsed?;
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let mut message = format!("Source contains parsing errors: {}", repr_str(source));
+    let mut reported = Vec::with_capacity(errors.len());
+    for (lineno, line) in errors {
+        let line = repr_str(&line);
+        message.push_str(&format!("\n\t[line {:2}]: {}", lineno, line));
+        let lineno = vm.new_int(lineno as i64);
+        let line = vm.new_str(&line);
+        reported.push(vm.new_tuple(vec![lineno, line]));
+    }
+    let source = vm.new_str(source);
+    let reported = vm.new_list(reported);
+    let attributes = vec![("source", source), ("errors", reported)];
+    Err(error(vm, "ParsingError", message, attributes))
+}
+
+fn parse_lines(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    lines: &[String],
+    source: &str,
+    errors: &mut Vec<(usize, String)>,
+) -> PyResult<()> {
+    let syntax = Syntax::of(vm, this)?;
+    let sections = dict_attribute(vm, this, "_sections")?;
+    let defaults = dict_attribute(vm, this, "_defaults")?;
+    let proxies = dict_attribute(vm, this, "_proxies")?;
+    let default_section = vm.getattr(this, "default_section")?;
+    let mut options: Option<ObjectRef> = None;
+    let mut section_name = String::new();
+    let mut option: Option<ObjectRef> = None;
+    let mut indent_level = 0;
+    let mut added_sections = HashSet::new();
+    let mut added_options = HashSet::new();
+    for (index, line) in lines.iter().enumerate() {
+        let lineno = index + 1;
+        let comment = comment_start(line, &syntax);
+        let value = line[..comment.unwrap_or(line.len())].trim();
+        if value.is_empty() {
+            if !syntax.empty_lines_in_values {
+                // An empty line ends the value before it.
+                indent_level = usize::MAX;
+            } else if let (None, Some(options), Some(option)) = (comment, &options, &option) {
+                let values = vm.dict_get(options.as_dict().unwrap(), option)?;
+                if let Some(values) = values.filter(|values| !Vm::is(values, &vm.none())) {
+                    append(vm, &values, "")?;
+                }
+            }
+            continue;
+        }
+        let indent = line.chars().take_while(|c| c.is_whitespace()).count();
+        if let (Some(options), Some(option), true) = (&options, &option, indent > indent_level) {
+            let values = vm.dict_get(options.as_dict().unwrap(), option)?;
+            let values = values.unwrap_or_else(|| vm.none());
+            append(vm, &values, value)?;
+            continue;
+        }
+        indent_level = indent;
+        if let Some(header) = section_header(value) {
+            let name = vm.new_str(header);
+            if let Some(existing) = vm.dict_get(sections.as_dict().unwrap(), &name)? {
+                if syntax.strict && added_sections.contains(header) {
+                    return Err(duplicate_section_error(
+                        vm,
+                        &name,
+                        Some(source),
+                        Some(lineno),
+                    )?);
+                }
+                options = Some(existing);
+                added_sections.insert(header.to_string());
+            } else if vm.eq(&name, &default_section)? {
+                options = Some(defaults.clone());
+            } else {
+                let new_options = vm.new_dict();
+                vm.dict_set(
+                    sections.as_dict().unwrap(),
+                    name.clone(),
+                    new_options.clone(),
+                )?;
+                let proxy = new_proxy(vm, this, &name)?;
+                vm.dict_set(proxies.as_dict().unwrap(), name, proxy)?;
+                options = Some(new_options);
+                added_sections.insert(header.to_string());
+            }
+            section_name = header.to_string();
+            // A section cannot start with a continuation line.
+            option = None;
+            continue;
+        }
+        let current = match options {
+            Some(ref options) => options.clone(),
+            None => {
+                let message = format!(
+                    "File contains no section headers.\nfile: {}, line: {}\n{}",
+                    repr_str(source),
+                    lineno,
+                    repr_str(line)
+                );
+                let attributes = vec![
+                    ("source", vm.new_str(source)),
+                    ("lineno", vm.new_int(lineno as i64)),
+                    ("line", vm.new_str(line)),
+                ];
+                return Err(error(vm, "MissingSectionHeaderError", message, attributes));
+            }
+        };
+        let (name, text) = match option_line(value, &syntax) {
+            Some(parts) => parts,
+            None => {
+                errors.push((lineno, line.clone()));
+                continue;
+            }
+        };
+        if name.is_empty() {
+            errors.push((lineno, line.clone()));
+        }
+        let name = vm.new_str(name);
+        let key = optionxform(vm, this, &name)?;
+        let identity = (section_name.clone(), vm.repr(&key)?);
+        if syntax.strict && added_options.contains(&identity) {
+            let section = vm.new_str(&section_name);
+            return Err(duplicate_option_error(
+                vm,
+                &section,
+                &key,
+                Some(source),
+                Some(lineno),
+            )?);
+        }
+        added_options.insert(identity);
+        let stored = match text {
+            Some(text) => {
+                let text = vm.new_str(text);
+                vm.new_list(vec![text])
+            }
+            None => vm.none(),
+        };
+        vm.dict_set(current.as_dict().unwrap(), key.clone(), stored)?;
+        option = if vm.is_true(&key)? { Some(key) } else { None };
+    }
+    Ok(())
+}
+
+/// Adds a line to the value of an option being read.
+fn append(vm: &mut Vm, values: &ObjectRef, line: &str) -> PyResult<()> {
+    let append = vm.getattr(values, "append")?;
+    let line = vm.new_str(line);
+    vm.call(&append, Args::new(vec![line]))?;
+    Ok(())
+}
+
+/// Joins the lines of each value read into one string.
+fn join_multiline_values(vm: &mut Vm, this: &ObjectRef) -> PyResult<()> {
+    let mut dicts = vec![dict_attribute(vm, this, "_defaults")?];
+    let sections = dict_attribute(vm, this, "_sections")?;
+    dicts.extend(entries(&sections).into_iter().map(|(_, options)| options));
+    for options in dicts.iter().filter(|options| options.as_dict().is_some()) {
+        for (key, value) in entries(options) {
+            let lines = match value.payload {
+                Payload::List(ref lines) => lines.borrow().clone(),
+                _ => continue,
+            };
+            let lines = lines
+                .iter()
+                .map(|line| vm.str(line))
+                .collect::<PyResult<Vec<String>>>()?;
+            let joined = vm.new_str(lines.join("\n").trim_end());
+            vm.dict_set(options.as_dict().unwrap(), key, joined)?;
+        }
+    }
+    Ok(())
+}
+
+/// `write(fp, space_around_delimiters=True)`: the defaults and then each
+/// section in INI format, with the lines of a value after the first
+/// indented by a tab.
+fn write(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(
+        vm,
+        args,
+        "write",
+        &["self", "fp", "space_around_delimiters"],
+        2,
+    )?;
+    let (this, file) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    let spaced = match values[2] {
+        Some(ref spaced) => vm.is_true(spaced)?,
+        None => true,
+    };
+    let syntax = Syntax::of(vm, this)?;
+    let delimiter = syntax.delimiters.first().cloned().unwrap_or_default();
+    let delimiter = if spaced {
+        format!(" {} ", delimiter)
+    } else {
+        delimiter
+    };
+    let mut sections = Vec::new();
+    let defaults = dict_attribute(vm, this, "_defaults")?;
+    if !defaults.as_dict().unwrap().borrow().is_empty() {
+        let default_section = vm.getattr(this, "default_section")?;
+        sections.push((default_section, defaults));
+    }
+    let named = dict_attribute(vm, this, "_sections")?;
+    sections.extend(entries(&named));
+    let write = vm.getattr(file, "write")?;
+    for (name, options) in sections {
+        let mut text = format!("[{}]\n", vm.str(&name)?);
+        for (key, value) in mapping_items(vm, &options)? {
+            text.push_str(&vm.str(&key)?);
+            if !Vm::is(&value, &vm.none()) || !syntax.allow_no_value {
+                text.push_str(&delimiter);
+                text.push_str(&vm.str(&value)?.replace('\n', "\n\t"));
+            }
+            text.push('\n');
+        }
+        text.push('\n');
+        let text = vm.new_str(&text);
+        vm.call(&write, Args::new(vec![text]))?;
+    }
+    Ok(vm.none())
+}
+
+/// `parser[section]`: the proxy of a section.
+fn getitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__getitem__", &["self", "key"], 2)?;
+    let (this, key) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    if !is_default_section(vm, this, key)? && section_options(vm, this, key)?.is_none() {
+        return Err(vm.new_key_error(key.clone()));
+    }
+    let proxies = dict_attribute(vm, this, "_proxies")?;
+    match vm.dict_get(proxies.as_dict().unwrap(), key)? {
+        Some(proxy) => Ok(proxy),
+        None => new_proxy(vm, this, key),
+    }
+}
+
+/// `parser[section] = options`: replaces the options of the section with
+/// those of a mapping.
+fn setitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__setitem__", &["self", "key", "value"], 3)?;
+    let this = values[0].as_ref().unwrap();
+    let (key, value) = (values[1].as_ref().unwrap(), values[2].as_ref().unwrap());
+    if is_default_section(vm, this, key)? {
+        let defaults = dict_attribute(vm, this, "_defaults")?;
+        defaults.as_dict().unwrap().borrow_mut().clear();
+    } else if let Some(options) = section_options(vm, this, key)? {
+        // Setting a section to its own proxy leaves it as it is.
+        let proxies = dict_attribute(vm, this, "_proxies")?;
+        if let Some(proxy) = vm.dict_get(proxies.as_dict().unwrap(), key)? {
+            if Vm::is(&proxy, value) {
+                return Ok(vm.none());
+            }
+        }
+        options.as_dict().unwrap().borrow_mut().clear();
+    }
+    let sections = vm.new_dict();
+    vm.dict_set(sections.as_dict().unwrap(), key.clone(), value.clone())?;
+    add_dict(vm, this, &sections, "<dict>", true)?;
+    Ok(vm.none())
+}
+
+/// `del parser[section]`.
+fn delitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__delitem__", &["self", "key"], 2)?;
+    let (this, key) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    if is_default_section(vm, this, key)? {
+        let message = "Cannot remove the default section.".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    let sections = dict_attribute(vm, this, "_sections")?;
+    if vm.dict_remove(sections.as_dict().unwrap(), key)?.is_none() {
+        return Err(vm.new_key_error(key.clone()));
+    }
+    let proxies = dict_attribute(vm, this, "_proxies")?;
+    vm.dict_remove(proxies.as_dict().unwrap(), key)?;
+    Ok(vm.none())
+}
+
+/// `section in parser`: the default section is always there.
+fn contains(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__contains__", &["self", "key"], 2)?;
+    let (this, key) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    let found = is_default_section(vm, this, key)? || section_options(vm, this, key)?.is_some();
+    Ok(vm.new_bool(found))
+}
+
+/// `len(parser)`: the sections and the default section.
+fn len(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__len__", &["self"], 1)?;
+    let names = section_names(vm, values[0].as_ref().unwrap())?;
+    Ok(vm.new_int(names.len() as i64))
+}
+
+/// `iter(parser)`: the default section's name, then the sections'.
+fn iter(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__iter__", &["self"], 1)?;
+    let names = section_names(vm, values[0].as_ref().unwrap())?;
+    let names = vm.new_list(names);
+    vm.iter(&names)
+}
+
+/// The `(parser, name)` of a `SectionProxy` method's `self`.
+fn proxy_parts(vm: &mut Vm, this: &ObjectRef) -> PyResult<(ObjectRef, ObjectRef)> {
+    Ok((vm.getattr(this, "parser")?, vm.getattr(this, "name")?))
+}
+
+/// `SectionProxy(parser, name)`: a section as a mapping of its options,
+/// the defaults included.
+fn proxy_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(
+        vm,
+        args,
+        "SectionProxy.__init__",
+        &["self", "parser", "name"],
+        3,
+    )?;
+    let this = values[0].as_ref().unwrap();
+    vm.setattr(this, "parser", values[1].clone().unwrap())?;
+    vm.setattr(this, "name", values[2].clone().unwrap())?;
+    Ok(vm.none())
+}
+
+fn proxy_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__repr__", &["self"], 1)?;
+    let name = vm.getattr(values[0].as_ref().unwrap(), "name")?;
+    let repr = format!("<Section: {}>", vm.str(&name)?);
+    Ok(vm.new_str(&repr))
+}
+
+/// The names of the options of a proxy's section.
+fn proxy_options(vm: &mut Vm, this: &ObjectRef) -> PyResult<Vec<ObjectRef>> {
+    let (parser, name) = proxy_parts(vm, this)?;
+    if is_default_section(vm, &parser, &name)? {
+        let defaults = dict_attribute(vm, &parser, "_defaults")?;
+        return Ok(keys(&defaults));
+    }
+    option_names(vm, &parser, &name)
+}
+
+fn proxy_getitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__getitem__", &["self", "key"], 2)?;
+    let key = values[1].as_ref().unwrap();
+    let (parser, name) = proxy_parts(vm, values[0].as_ref().unwrap())?;
+    if !option_exists(vm, &parser, &name, key)? {
+        return Err(vm.new_key_error(key.clone()));
+    }
+    let options = Lookup::new(vm, &None, &None, &None)?;
+    get_value(vm, &parser, &name, key, &options)
+}
+
+fn proxy_setitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__setitem__", &["self", "key", "value"], 3)?;
+    let (key, value) = (values[1].as_ref().unwrap(), values[2].as_ref().unwrap());
+    let (parser, name) = proxy_parts(vm, values[0].as_ref().unwrap())?;
+    check_types(vm, &parser, None, Some(key), Some(value))?;
+    let set = vm.getattr(&parser, "set")?;
+    vm.call(&set, Args::new(vec![name, key.clone(), value.clone()]))
+}
+
+fn proxy_delitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__delitem__", &["self", "key"], 2)?;
+    let key = values[1].as_ref().unwrap();
+    let (parser, name) = proxy_parts(vm, values[0].as_ref().unwrap())?;
+    if !option_exists(vm, &parser, &name, key)? || !remove(vm, &parser, &name, key)? {
+        return Err(vm.new_key_error(key.clone()));
+    }
+    Ok(vm.none())
+}
+
+fn proxy_contains(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__contains__", &["self", "key"], 2)?;
+    let (parser, name) = proxy_parts(vm, values[0].as_ref().unwrap())?;
+    let found = option_exists(vm, &parser, &name, values[1].as_ref().unwrap())?;
+    Ok(vm.new_bool(found))
+}
+
+fn proxy_len(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__len__", &["self"], 1)?;
+    let names = proxy_options(vm, values[0].as_ref().unwrap())?;
+    Ok(vm.new_int(names.len() as i64))
+}
+
+fn proxy_iter(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__iter__", &["self"], 1)?;
+    let names = proxy_options(vm, values[0].as_ref().unwrap())?;
+    let names = vm.new_list(names);
+    vm.iter(&names)
+}
+
+/// `keys()`: the names of the options, as a list.
+fn proxy_keys(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "keys", &["self"], 1)?;
+    let names = proxy_options(vm, values[0].as_ref().unwrap())?;
+    Ok(vm.new_list(names))
+}
+
+/// The interpolated values of a proxy's options, in order.
+fn proxy_option_values(vm: &mut Vm, this: &ObjectRef) -> PyResult<Vec<(ObjectRef, ObjectRef)>> {
+    let (parser, name) = proxy_parts(vm, this)?;
+    let options = Lookup::new(vm, &None, &None, &None)?;
+    let mut pairs = Vec::new();
+    for option in proxy_options(vm, this)? {
+        let value = get_value(vm, &parser, &name, &option, &options)?;
+        pairs.push((option, value));
+    }
+    Ok(pairs)
+}
+
+/// `items()`: the `(name, value)` pairs of the options, as a list.
+fn proxy_items(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "items", &["self"], 1)?;
+    let pairs = proxy_option_values(vm, values[0].as_ref().unwrap())?
+        .into_iter()
+        .map(|(name, value)| vm.new_tuple(vec![name, value]))
+        .collect();
+    Ok(vm.new_list(pairs))
+}
+
+/// `values()`: the values of the options, as a list.
+fn proxy_values(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "values", &["self"], 1)?;
+    let option_values = proxy_option_values(vm, values[0].as_ref().unwrap())?
+        .into_iter()
+        .map(|(_, value)| value)
+        .collect();
+    Ok(vm.new_list(option_values))
+}
+
+const PROXY_GETTER_PARAMETERS: [&str; 5] = ["self", "option", "fallback", "raw", "vars"];
+
+/// The getters of a section, whose `fallback` is `None` unless given.
+fn proxy_getter(vm: &mut Vm, args: Args, name: &str, converter: Option<Converter>) -> PyResult {
+    let values = bind_arguments(vm, args, name, &PROXY_GETTER_PARAMETERS, 2)?;
+    let option = values[1].as_ref().unwrap();
+    let (parser, section) = proxy_parts(vm, values[0].as_ref().unwrap())?;
+    let fallback = Some(values[2].clone().unwrap_or_else(|| vm.none()));
+    let options = Lookup::new(vm, &values[3], &values[4], &fallback)?;
+    match converter {
+        Some(converter) => get_converted(vm, &parser, &section, option, &options, converter),
+        None => get_value(vm, &parser, &section, option, &options),
+    }
+}
+
+/// `get(option, fallback=None, *, raw=False, vars=None)`.
+fn proxy_get(vm: &mut Vm, args: Args) -> PyResult {
+    proxy_getter(vm, args, "get", None)
+}
+
+/// `getint(option, fallback=None, *, raw=False, vars=None)`.
+fn proxy_getint(vm: &mut Vm, args: Args) -> PyResult {
+    proxy_getter(vm, args, "getint", Some(to_int))
+}
+
+/// `getfloat(option, fallback=None, *, raw=False, vars=None)`.
+fn proxy_getfloat(vm: &mut Vm, args: Args) -> PyResult {
+    proxy_getter(vm, args, "getfloat", Some(to_float))
+}
+
+/// `getboolean(option, fallback=None, *, raw=False, vars=None)`.
+fn proxy_getboolean(vm: &mut Vm, args: Args) -> PyResult {
+    proxy_getter(vm, args, "getboolean", Some(to_boolean))
+}
+
+/// The length of the reference `open name end tail` at the start of
+/// `text`, as the interpolations' `_KEYCRE` patterns match them.
+fn reference_length(text: &str, open: &str, end: char, tail: &str) -> Option<usize> {
+    let rest = text.strip_prefix(open)?;
+    let name = rest.find(end)?;
+    if name == 0 || !rest[name + end.len_utf8()..].starts_with(tail) {
+        return None;
+    }
+    Some(open.len() + name + end.len_utf8() + tail.len())
+}
+
+/// The references of an interpolation: what starts them, how their name
+/// ends, and what follows that.
+fn reference_syntax(kind: Interpolation) -> Option<(char, &'static str, char, &'static str)> {
+    match kind {
+        Interpolation::Raw => None,
+        Interpolation::Basic => Some(('%', "%(", ')', "s")),
+        Interpolation::Extended => Some(('$', "${", '}', "")),
+    }
+}
+
+/// `before_set`: a value to set may hold only doubled signs and well-formed
+/// references.
+fn check_interpolation_syntax(vm: &mut Vm, this: &ObjectRef, value: &ObjectRef) -> PyResult<()> {
+    let text = match value.as_str() {
+        Some(text) => text.to_string(),
+        None => return Ok(()),
+    };
+    let kind = interpolation(vm, this)?;
+    let (sign, open, end, tail) = match reference_syntax(kind) {
+        Some(syntax) => syntax,
+        None => return Ok(()),
+    };
+    let unescaped = text.replace(&format!("{0}{0}", sign), "");
+    let mut rest = unescaped.as_str();
+    let mut left = String::new();
+    while let Some(c) = rest.chars().next() {
+        match reference_length(rest, open, end, tail) {
+            Some(length) => rest = &rest[length..],
+            None => {
+                left.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    match left.find(sign) {
+        Some(index) => {
+            let message = format!(
+                "invalid interpolation syntax in {} at position {}",
+                repr_str(&text),
+                left[..index].chars().count()
+            );
+            Err(vm.new_value_error(message))
+        }
+        None => Ok(()),
+    }
+}
+
+/// The value of `option` with its references replaced, as the parser's
+/// interpolation's `before_get` does.
+fn interpolate(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    section: &ObjectRef,
+    option: &ObjectRef,
+    value: &ObjectRef,
+    unified: &ObjectRef,
+) -> PyResult {
+    let kind = interpolation(vm, this)?;
+    let text = match value.as_str() {
+        Some(text) => text.to_string(),
+        // A missing value interpolates to nothing.
+        None if Vm::is(value, &vm.none()) && !matches!(kind, Interpolation::Raw) => String::new(),
+        None => return Ok(value.clone()),
+    };
+    let mut result = String::with_capacity(text.len());
+    let reference = Reference {
+        section: section.clone(),
+        option: option.clone(),
+        value: &text,
+        map: unified.clone(),
+        depth: 1,
+    };
+    match kind {
+        Interpolation::Raw => return Ok(value.clone()),
+        Interpolation::Basic => interpolate_basic(vm, this, &reference, &mut result)?,
+        Interpolation::Extended => interpolate_extended(vm, this, &reference, &mut result)?,
+    }
+    Ok(vm.new_str(&result))
+}
+
+/// A value being interpolated: the option it is the value of, the options
+/// its references name, and how deep in references it is.
+struct Reference<'a> {
+    section: ObjectRef,
+    option: ObjectRef,
+    value: &'a str,
+    map: ObjectRef,
+    depth: usize,
+}
+
+impl<'a> Reference<'a> {
+    /// The raw value of the option, for errors, or else the value.
+    fn raw_value(&self, vm: &mut Vm, this: &ObjectRef) -> PyResult {
+        match find(vm, this, &self.section, &self.option, None)? {
+            Found::Value(value, _, _) => Ok(value),
+            _ => Ok(vm.new_str(self.value)),
+        }
+    }
+
+    fn depth_error(&self, vm: &mut Vm, this: &ObjectRef) -> PyResult<ObjectRef> {
+        let raw = self.raw_value(vm, this)?;
+        let message = format!(
+            "Recursion limit exceeded in value substitution: option {} in section {} contains \
+             an interpolation key which cannot be substituted in {} steps. Raw value: {}",
+            vm.repr(&self.option)?,
+            vm.repr(&self.section)?,
+            MAX_INTERPOLATION_DEPTH,
+            vm.repr(&raw)?
+        );
+        let attributes = vec![
+            ("option", self.option.clone()),
+            ("section", self.section.clone()),
+        ];
+        Ok(error(vm, "InterpolationDepthError", message, attributes))
+    }
+
+    fn syntax_error(&self, vm: &mut Vm, message: String) -> ObjectRef {
+        let attributes = vec![
+            ("option", self.option.clone()),
+            ("section", self.section.clone()),
+        ];
+        error(vm, "InterpolationSyntaxError", message, attributes)
+    }
+
+    fn missing_error(&self, vm: &mut Vm, this: &ObjectRef, name: &str) -> PyResult<ObjectRef> {
+        let raw = self.raw_value(vm, this)?;
+        let message = format!(
+            "Bad value substitution: option {} in section {} contains an interpolation key {} \
+             which is not a valid option name. Raw value: {}",
+            vm.repr(&self.option)?,
+            vm.repr(&self.section)?,
+            repr_str(name),
+            vm.repr(&raw)?
+        );
+        let attributes = vec![
+            ("option", self.option.clone()),
+            ("section", self.section.clone()),
+            ("reference", vm.new_str(name)),
+        ];
+        Ok(error(
+            vm,
+            "InterpolationMissingOptionError",
+            message,
+            attributes,
+        ))
+    }
+}
+
+/// `BasicInterpolation`: `%(name)s` is the option `name` of the same
+/// section or the defaults, and `%%` a percent sign.
+fn interpolate_basic(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    reference: &Reference,
+    result: &mut String,
+) -> PyResult<()> {
+    if reference.depth > MAX_INTERPOLATION_DEPTH {
+        return Err(reference.depth_error(vm, this)?);
+    }
+    let mut rest = reference.value;
+    while !rest.is_empty() {
+        let start = match rest.find('%') {
+            Some(start) => start,
+            None => break,
+        };
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest[1..].starts_with('%') {
+            result.push('%');
+            rest = &rest[2..];
+        } else if rest[1..].starts_with('(') {
+            let length = match reference_length(rest, "%(", ')', "s") {
+                Some(length) => length,
+                None => {
+                    let message =
+                        format!("bad interpolation variable reference {}", repr_str(rest));
+                    return Err(reference.syntax_error(vm, message));
+                }
+            };
+            let name = vm.new_str(&rest[2..length - 2]);
+            let name = optionxform(vm, this, &name)?;
+            rest = &rest[length..];
+            let value = match vm.dict_get(reference.map.as_dict().unwrap(), &name)? {
+                Some(value) => vm.str(&value)?,
+                None => {
+                    let name = vm.str(&name)?;
+                    return Err(reference.missing_error(vm, this, &name)?);
+                }
+            };
+            if value.contains('%') {
+                let inner = Reference {
+                    section: reference.section.clone(),
+                    option: reference.option.clone(),
+                    value: &value,
+                    map: reference.map.clone(),
+                    depth: reference.depth + 1,
+                };
+                interpolate_basic(vm, this, &inner, result)?;
+            } else {
+                result.push_str(&value);
+            }
+        } else {
+            let message = format!(
+                "'%' must be followed by '%' or '(', found: {}",
+                repr_str(rest)
+            );
+            return Err(reference.syntax_error(vm, message));
+        }
+    }
+    result.push_str(rest);
+    Ok(())
+}
+
+/// `ExtendedInterpolation`: `${name}` is the option `name` of the same
+/// section or the defaults, `${section:name}` one of another section, and
+/// `$$` a dollar sign.
+fn interpolate_extended(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    reference: &Reference,
+    result: &mut String,
+) -> PyResult<()> {
+    if reference.depth > MAX_INTERPOLATION_DEPTH {
+        return Err(reference.depth_error(vm, this)?);
+    }
+    let mut rest = reference.value;
+    while !rest.is_empty() {
+        let start = match rest.find('$') {
+            Some(start) => start,
+            None => break,
+        };
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+        if rest[1..].starts_with('$') {
+            result.push('$');
+            rest = &rest[2..];
+        } else if rest[1..].starts_with('{') {
+            let length = match reference_length(rest, "${", '}', "") {
+                Some(length) => length,
+                None => {
+                    let message =
+                        format!("bad interpolation variable reference {}", repr_str(rest));
+                    return Err(reference.syntax_error(vm, message));
+                }
+            };
+            let path: Vec<&str> = rest[2..length - 1].split(':').collect();
+            rest = &rest[length..];
+            let (section, option, value) = match path[..] {
+                [name] => {
+                    let name = vm.new_str(name);
+                    let option = optionxform(vm, this, &name)?;
+                    let value = vm.dict_get(reference.map.as_dict().unwrap(), &option)?;
+                    (reference.section.clone(), option, value)
+                }
+                [section, name] => {
+                    let section = vm.new_str(section);
+                    let name = vm.new_str(name);
+                    let option = optionxform(vm, this, &name)?;
+                    let value = match find(vm, this, &section, &option, None)? {
+                        Found::Value(value, _, _) => Some(value),
+                        _ => None,
+                    };
+                    (section, option, value)
+                }
+                _ => {
+                    let message = format!("More than one ':' found: {}", repr_str(rest));
+                    return Err(reference.syntax_error(vm, message));
+                }
+            };
+            let value = match value {
+                Some(value) => vm.str(&value)?,
+                None => return Err(reference.missing_error(vm, this, &path.join(":"))?),
+            };
+            if value.contains('$') {
+                // A value from elsewhere is interpolated among the options
+                // of its own section.
+                let map = match unify(vm, this, &section, None)? {
+                    Some(map) => map,
+                    None => vm.new_dict(),
+                };
+                let inner = Reference {
+                    section,
+                    option,
+                    value: &value,
+                    map,
+                    depth: reference.depth + 1,
+                };
+                interpolate_extended(vm, this, &inner, result)?;
+            } else {
+                result.push_str(&value);
+            }
+        } else {
+            let message = format!(
+                "'$' must be followed by '$' or '{{', found: {}",
+                repr_str(rest)
+            );
+            return Err(reference.syntax_error(vm, message));
+        }
+    }
+    result.push_str(rest);
+    Ok(())
+}
@@ -0,0 +1,862 @@
+//! `datetime`: the `date`, `time`, `datetime`, `timedelta` and `timezone`
+//! classes, with which `tomllib` gives the dates and times of TOML.
+//!
+//! The fields of a value are kept in the attributes of its instance. The
+//! values are made, compared, hashed and written out as CPython's are, but
+//! there is no arithmetic on them, no `strftime` or `fromisoformat`, and
+//! no clock to give `now()` or `today()`. `timedelta` takes whole numbers
+//! only.
+
+use std::cmp::Ordering;
+
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, new_native_class, new_native_module};
+
+/// The microseconds in a day.
+const DAY: i128 = 86_400_000_000;
+
+/// The most days a `timedelta` may have either way.
+const MAX_DAYS: i128 = 999_999_999;
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "datetime", &[]);
+    for &(name, value) in &[("MINYEAR", 1), ("MAXYEAR", 9999)] {
+        let value = vm.new_int(value);
+        add_attribute(vm, &module, name, value);
+    }
+    let object = vm.types.object.clone();
+    let compare_methods: [(&'static str, _); 6] = [
+        ("__eq__", value_eq as _),
+        ("__ge__", value_ge as _),
+        ("__gt__", value_gt as _),
+        ("__hash__", value_hash as _),
+        ("__le__", value_le as _),
+        ("__lt__", value_lt as _),
+    ];
+    let mut methods = compare_methods.to_vec();
+    methods.extend_from_slice(&[
+        ("__init__", date_init as _),
+        ("__repr__", date_repr as _),
+        ("__str__", date_isoformat as _),
+        ("isoformat", date_isoformat as _),
+        ("isoweekday", date_isoweekday as _),
+        ("toordinal", date_toordinal as _),
+        ("weekday", date_weekday as _),
+    ]);
+    let date = new_native_class(vm, "datetime", "date", &object, &methods)?;
+    add_attribute(vm, &module, "date", date.clone());
+    let datetime = new_native_class(
+        vm,
+        "datetime",
+        "datetime",
+        &date,
+        &[
+            ("__init__", datetime_init),
+            ("__repr__", datetime_repr),
+            ("__str__", datetime_str),
+            ("date", datetime_date),
+            ("isoformat", datetime_isoformat),
+            ("time", datetime_time),
+            ("utcoffset", datetime_utcoffset),
+        ],
+    )?;
+    add_attribute(vm, &module, "datetime", datetime);
+    let mut methods = compare_methods.to_vec();
+    methods.extend_from_slice(&[
+        ("__init__", time_init as _),
+        ("__repr__", time_repr as _),
+        ("__str__", time_isoformat as _),
+        ("isoformat", time_isoformat as _),
+        ("utcoffset", time_utcoffset as _),
+    ]);
+    let time = new_native_class(vm, "datetime", "time", &object, &methods)?;
+    add_attribute(vm, &module, "time", time);
+    let mut methods = compare_methods.to_vec();
+    methods.extend_from_slice(&[
+        ("__bool__", timedelta_bool as _),
+        ("__init__", timedelta_init as _),
+        ("__repr__", timedelta_repr as _),
+        ("__str__", timedelta_str as _),
+        ("total_seconds", timedelta_total_seconds as _),
+    ]);
+    let timedelta = new_native_class(vm, "datetime", "timedelta", &object, &methods)?;
+    add_attribute(vm, &module, "timedelta", timedelta.clone());
+    let tzinfo = new_native_class(
+        vm,
+        "datetime",
+        "tzinfo",
+        &object,
+        &[("utcoffset", tzinfo_utcoffset)],
+    )?;
+    add_attribute(vm, &module, "tzinfo", tzinfo.clone());
+    let timezone = new_native_class(
+        vm,
+        "datetime",
+        "timezone",
+        &tzinfo,
+        &[
+            ("__eq__", value_eq),
+            ("__hash__", value_hash),
+            ("__init__", timezone_init),
+            ("__repr__", timezone_repr),
+            ("__str__", timezone_str),
+            ("tzname", timezone_tzname),
+            ("utcoffset", timezone_utcoffset),
+        ],
+    )?;
+    add_attribute(vm, &module, "timezone", timezone.clone());
+    // `timezone()` finds `timedelta` in the module, which is not imported
+    // yet, so `utc` is made without it.
+    let zero = vm.call(&timedelta, Args::default())?;
+    let new = vm.getattr(&object, "__new__")?;
+    let utc = vm.call(&new, Args::new(vec![timezone.clone()]))?;
+    vm.setattr(&utc, "_offset", zero)?;
+    let none = vm.none();
+    vm.setattr(&utc, "_name", none)?;
+    vm.setattr(&timezone, "utc", utc.clone())?;
+    add_attribute(vm, &module, "UTC", utc);
+    Ok(module)
+}
+
+/// The class `name` of this module.
+fn class(vm: &mut Vm, name: &str) -> PyResult<ObjectRef> {
+    let module = vm.import_module("datetime")?;
+    vm.getattr(&module, name)
+}
+
+/// The int attributes `names` of `this`.
+fn fields(vm: &mut Vm, this: &ObjectRef, names: &[&str]) -> PyResult<Vec<i64>> {
+    let mut values = Vec::with_capacity(names.len());
+    for name in names {
+        let value = vm.getattr(this, name)?;
+        values.push(index_value(vm, &value)?);
+    }
+    Ok(values)
+}
+
+/// Sets the int attributes of `this` to `values`.
+fn set_fields(vm: &mut Vm, this: &ObjectRef, names: &[&str], values: &[i64]) -> PyResult<()> {
+    for (name, &value) in names.iter().zip(values) {
+        let value = vm.new_int(value);
+        vm.setattr(this, name, value)?;
+    }
+    Ok(())
+}
+
+/// The name a value's repr starts with: the qualified name of the classes
+/// of this module, and the name of others.
+fn type_name(vm: &mut Vm, this: &ObjectRef) -> PyResult<String> {
+    let class = this.class();
+    let name = vm.getattr(&class, "__name__")?;
+    let name = vm.str(&name)?;
+    let module = vm.getattr(&class, "__module__")?;
+    if module.as_str() == Some("datetime") {
+        Ok(format!("datetime.{}", name))
+    } else {
+        Ok(name)
+    }
+}
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The day of the proleptic Gregorian calendar, counting 1 January of
+/// year 1 as day 1.
+fn ordinal(year: i64, month: i64, day: i64) -> i64 {
+    let before = year - 1;
+    let days_before_month: i64 = (1..month).map(|month| days_in_month(year, month)).sum();
+    before * 365 + before / 4 - before / 100 + before / 400 + days_before_month + day
+}
+
+/// The ints `values` of the date arguments, checked.
+fn check_date(vm: &mut Vm, values: &[Option<ObjectRef>]) -> PyResult<Vec<i64>> {
+    let mut date = Vec::with_capacity(3);
+    for value in values {
+        date.push(index_value(vm, value.as_ref().unwrap())?);
+    }
+    let (year, month, day) = (date[0], date[1], date[2]);
+    let message = if !(1..=9999).contains(&year) {
+        format!("year {} is out of range", year)
+    } else if !(1..=12).contains(&month) {
+        "month must be in 1..12".to_string()
+    } else if day < 1 || day > days_in_month(year, month) {
+        "day is out of range for month".to_string()
+    } else {
+        return Ok(date);
+    };
+    Err(vm.new_value_error(message))
+}
+
+/// The ints of the time arguments `values`, which default to 0, checked.
+fn check_time(vm: &mut Vm, values: &[Option<ObjectRef>]) -> PyResult<Vec<i64>> {
+    const LIMITS: [(&str, i64); 4] = [
+        ("hour", 23),
+        ("minute", 59),
+        ("second", 59),
+        ("microsecond", 999_999),
+    ];
+    let mut time = Vec::with_capacity(LIMITS.len());
+    for (value, &(name, limit)) in values.iter().zip(&LIMITS) {
+        let value = match *value {
+            Some(ref value) => index_value(vm, value)?,
+            None => 0,
+        };
+        if !(0..=limit).contains(&value) {
+            return Err(vm.new_value_error(format!("{} must be in 0..{}", name, limit)));
+        }
+        time.push(value);
+    }
+    Ok(time)
+}
+
+/// Sets the `tzinfo` attribute of `this` to the argument `tzinfo`, which
+/// must be `None` or a `tzinfo`.
+fn set_tzinfo(vm: &mut Vm, this: &ObjectRef, tzinfo: Option<ObjectRef>) -> PyResult<()> {
+    let tzinfo = tzinfo.unwrap_or_else(|| vm.none());
+    let class = class(vm, "tzinfo")?;
+    if !Vm::is(&tzinfo, &vm.none()) && !Vm::is_instance(&tzinfo, &class) {
+        let message = format!(
+            "tzinfo argument must be None or of a tzinfo subclass, not type '{}'",
+            tzinfo.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    vm.setattr(this, "tzinfo", tzinfo)
+}
+
+/// What the `tzinfo` of `this` gives as its offset from UTC at `this`, in
+/// microseconds, or `None` for a naive value. A time asks for its offset
+/// at `None`, as CPython's does.
+fn utc_offset(vm: &mut Vm, this: &ObjectRef, at: ObjectRef) -> PyResult<Option<i128>> {
+    let tzinfo = vm.getattr(this, "tzinfo")?;
+    if Vm::is(&tzinfo, &vm.none()) {
+        return Ok(None);
+    }
+    let method = vm.getattr(&tzinfo, "utcoffset")?;
+    let offset = vm.call(&method, Args::new(vec![at]))?;
+    if Vm::is(&offset, &vm.none()) {
+        return Ok(None);
+    }
+    let timedelta = class(vm, "timedelta")?;
+    if !Vm::is_instance(&offset, &timedelta) {
+        let message = format!(
+            "tzinfo.utcoffset() must return None or timedelta, not '{}'",
+            offset.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(Some(timedelta_micros(vm, &offset)?))
+}
+
+/// `+HH:MM`, with the seconds and microseconds of the offset `micros` if
+/// it has them.
+fn offset_text(micros: i128) -> String {
+    let sign = if micros < 0 { '-' } else { '+' };
+    let micros = micros.abs();
+    let seconds = micros / 1_000_000;
+    let mut text = format!("{}{:02}:{:02}", sign, seconds / 3600, seconds / 60 % 60);
+    if micros % 60_000_000 != 0 {
+        text.push_str(&format!(":{:02}", seconds % 60));
+        if micros % 1_000_000 != 0 {
+            text.push_str(&format!(".{:06}", micros % 1_000_000));
+        }
+    }
+    text
+}
+
+/// The date of `this` as `isoformat()` writes it.
+fn date_text(vm: &mut Vm, this: &ObjectRef) -> PyResult<String> {
+    let date = fields(vm, this, &["year", "month", "day"])?;
+    Ok(format!("{:04}-{:02}-{:02}", date[0], date[1], date[2]))
+}
+
+/// The time of `this`, and its offset from UTC at `at`, as `isoformat()`
+/// writes them.
+fn time_text(vm: &mut Vm, this: &ObjectRef, at: ObjectRef) -> PyResult<String> {
+    let time = fields(vm, this, &["hour", "minute", "second", "microsecond"])?;
+    let mut text = format!("{:02}:{:02}:{:02}", time[0], time[1], time[2]);
+    if time[3] != 0 {
+        text.push_str(&format!(".{:06}", time[3]));
+    }
+    if let Some(offset) = utc_offset(vm, this, at)? {
+        text.push_str(&offset_text(offset));
+    }
+    Ok(text)
+}
+
+/// The arguments of a time's repr: the hour and minute, then the second
+/// and microsecond if they are not 0, then the `tzinfo` if there is one.
+fn time_arguments(vm: &mut Vm, this: &ObjectRef) -> PyResult<String> {
+    let time = fields(vm, this, &["hour", "minute", "second", "microsecond"])?;
+    let shown = if time[3] != 0 {
+        4
+    } else if time[2] != 0 {
+        3
+    } else {
+        2
+    };
+    let mut text: Vec<String> = time[..shown].iter().map(i64::to_string).collect();
+    let tzinfo = vm.getattr(this, "tzinfo")?;
+    if !Vm::is(&tzinfo, &vm.none()) {
+        text.push(format!("tzinfo={}", vm.repr(&tzinfo)?));
+    }
+    Ok(text.join(", "))
+}
+
+/// The kinds of value of this module, which compare with their own kind
+/// only.
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Date,
+    DateTime,
+    Time,
+    TimeDelta,
+    TimeZone,
+}
+
+/// What compares and hashes `this`: its kind, whether it is aware of its
+/// offset from UTC, and its value in days or microseconds. Aware values
+/// are counted from UTC.
+fn sort_key(vm: &mut Vm, this: &ObjectRef) -> PyResult<Option<(Kind, bool, i128)>> {
+    let kinds = [
+        ("datetime", Kind::DateTime),
+        ("date", Kind::Date),
+        ("time", Kind::Time),
+        ("timedelta", Kind::TimeDelta),
+        ("timezone", Kind::TimeZone),
+    ];
+    let mut kind = None;
+    for &(name, known) in &kinds {
+        let class = class(vm, name)?;
+        if Vm::is_instance(this, &class) {
+            kind = Some(known);
+            break;
+        }
+    }
+    let kind = match kind {
+        Some(kind) => kind,
+        None => return Ok(None),
+    };
+    let time_names = ["hour", "minute", "second", "microsecond"];
+    let time_micros = |time: &[i64]| {
+        i128::from(time[0] * 3600 + time[1] * 60 + time[2]) * 1_000_000 + i128::from(time[3])
+    };
+    let key = match kind {
+        Kind::Date => {
+            let date = fields(vm, this, &["year", "month", "day"])?;
+            (kind, false, i128::from(ordinal(date[0], date[1], date[2])))
+        }
+        Kind::DateTime => {
+            let date = fields(vm, this, &["year", "month", "day"])?;
+            let time = fields(vm, this, &time_names)?;
+            let value = i128::from(ordinal(date[0], date[1], date[2])) * DAY + time_micros(&time);
+            match utc_offset(vm, this, this.clone())? {
+                Some(offset) => (kind, true, value - offset),
+                None => (kind, false, value),
+            }
+        }
+        Kind::Time => {
+            let time = fields(vm, this, &time_names)?;
+            let none = vm.none();
+            match utc_offset(vm, this, none)? {
+                Some(offset) => (kind, true, time_micros(&time) - offset),
+                None => (kind, false, time_micros(&time)),
+            }
+        }
+        Kind::TimeDelta => (kind, false, timedelta_micros(vm, this)?),
+        Kind::TimeZone => {
+            let offset = vm.getattr(this, "_offset")?;
+            (kind, false, timedelta_micros(vm, &offset)?)
+        }
+    };
+    Ok(Some(key))
+}
+
+/// Compares two values of the same kind. Naive and aware values are never
+/// equal, and cannot be ordered.
+fn compare(vm: &mut Vm, args: Args, name: &str, test: fn(Ordering) -> bool) -> PyResult {
+    let values = bind_arguments(vm, args, name, &["self", "other"], 2)?;
+    let this = sort_key(vm, values[0].as_ref().unwrap())?;
+    let other = sort_key(vm, values[1].as_ref().unwrap())?;
+    let (this, other) = match (this, other) {
+        (Some(this), Some(other)) if this.0 == other.0 => (this, other),
+        // A date is not ordered with a datetime, though it is one's base.
+        (Some(this), Some(other))
+            if name != "__eq__"
+                && [this.0, other.0].contains(&Kind::Date)
+                && [this.0, other.0].contains(&Kind::DateTime) =>
+        {
+            let this = type_name(vm, values[0].as_ref().unwrap())?;
+            let other = type_name(vm, values[1].as_ref().unwrap())?;
+            let message = format!("can't compare {} to {}", this, other);
+            return Err(vm.new_type_error(message));
+        }
+        _ => return Ok(vm.not_implemented()),
+    };
+    if this.1 != other.1 {
+        if name == "__eq__" {
+            return Ok(vm.new_bool(false));
+        }
+        let what = if this.0 == Kind::Time {
+            "times"
+        } else {
+            "datetimes"
+        };
+        let message = format!("can't compare offset-naive and offset-aware {}", what);
+        return Err(vm.new_type_error(message));
+    }
+    Ok(vm.new_bool(test(this.2.cmp(&other.2))))
+}
+
+fn value_eq(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__eq__", |order| order == Ordering::Equal)
+}
+
+fn value_lt(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__lt__", |order| order == Ordering::Less)
+}
+
+fn value_le(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__le__", |order| order != Ordering::Greater)
+}
+
+fn value_gt(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__gt__", |order| order == Ordering::Greater)
+}
+
+fn value_ge(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__ge__", |order| order != Ordering::Less)
+}
+
+/// `__hash__()`: the hash of the value that compares, so that equal values
+/// hash alike.
+fn value_hash(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__hash__", 1, 1)?;
+    let key = match sort_key(vm, &args.positional[0])? {
+        Some(key) => key,
+        None => return Err(vm.new_type_error("__hash__ needs a datetime value".to_string())),
+    };
+    let value = vm.new_int(key.2 as i64);
+    let hash = vm.hash(&value)?;
+    Ok(vm.new_int(hash))
+}
+
+/// `date(year, month, day)`.
+fn date_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "date", &["self", "year", "month", "day"], 4)?;
+    let date = check_date(vm, &values[1..])?;
+    let this = values[0].as_ref().unwrap();
+    set_fields(vm, this, &["year", "month", "day"], &date)?;
+    Ok(vm.none())
+}
+
+fn date_repr(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__repr__", 1, 1)?;
+    let this = &args.positional[0];
+    let date = fields(vm, this, &["year", "month", "day"])?;
+    let name = type_name(vm, this)?;
+    let text = format!("{}({}, {}, {})", name, date[0], date[1], date[2]);
+    Ok(vm.new_str(&text))
+}
+
+/// `date.isoformat()`: `YYYY-MM-DD`.
+fn date_isoformat(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "isoformat", 1, 1)?;
+    let text = date_text(vm, &args.positional[0])?;
+    Ok(vm.new_str(&text))
+}
+
+/// `date.toordinal()`: the day of the proleptic Gregorian calendar.
+fn date_toordinal(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "toordinal", 1, 1)?;
+    let date = fields(vm, &args.positional[0], &["year", "month", "day"])?;
+    Ok(vm.new_int(ordinal(date[0], date[1], date[2])))
+}
+
+/// `date.weekday()`: 0 for Monday to 6 for Sunday.
+fn date_weekday(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "weekday", 1, 1)?;
+    let date = fields(vm, &args.positional[0], &["year", "month", "day"])?;
+    Ok(vm.new_int((ordinal(date[0], date[1], date[2]) + 6) % 7))
+}
+
+/// `date.isoweekday()`: 1 for Monday to 7 for Sunday.
+fn date_isoweekday(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "isoweekday", 1, 1)?;
+    let date = fields(vm, &args.positional[0], &["year", "month", "day"])?;
+    Ok(vm.new_int((ordinal(date[0], date[1], date[2]) + 6) % 7 + 1))
+}
+
+/// `datetime(year, month, day, hour=0, minute=0, second=0, microsecond=0,
+/// tzinfo=None)`.
+fn datetime_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(
+        vm,
+        args,
+        "datetime",
+        &[
+            "self",
+            "year",
+            "month",
+            "day",
+            "hour",
+            "minute",
+            "second",
+            "microsecond",
+            "tzinfo",
+        ],
+        4,
+    )?;
+    let date = check_date(vm, &values[1..4])?;
+    let time = check_time(vm, &values[4..8])?;
+    let this = values[0].as_ref().unwrap();
+    set_fields(vm, this, &["year", "month", "day"], &date)?;
+    set_fields(
+        vm,
+        this,
+        &["hour", "minute", "second", "microsecond"],
+        &time,
+    )?;
+    set_tzinfo(vm, this, values[8].clone())?;
+    Ok(vm.none())
+}
+
+fn datetime_repr(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__repr__", 1, 1)?;
+    let this = &args.positional[0];
+    let date = fields(vm, this, &["year", "month", "day"])?;
+    let time = time_arguments(vm, this)?;
+    let name = type_name(vm, this)?;
+    let text = format!("{}({}, {}, {}, {})", name, date[0], date[1], date[2], time);
+    Ok(vm.new_str(&text))
+}
+
+/// The date and time of `this`, and its offset, with `separator` between.
+fn datetime_text(vm: &mut Vm, this: &ObjectRef, separator: &str) -> PyResult {
+    let date = date_text(vm, this)?;
+    let time = time_text(vm, this, this.clone())?;
+    Ok(vm.new_str(&format!("{}{}{}", date, separator, time)))
+}
+
+/// `datetime.__str__()`: the ISO format with a space between the date and
+/// the time.
+fn datetime_str(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__str__", 1, 1)?;
+    datetime_text(vm, &args.positional[0], " ")
+}
+
+/// `datetime.isoformat(sep='T')`.
+fn datetime_isoformat(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "isoformat", &["self", "sep"], 1)?;
+    let separator = match values[1] {
+        Some(ref separator) => match separator.as_str() {
+            Some(text) if text.chars().count() == 1 => text.to_string(),
+            _ => {
+                let message = "isoformat() argument 1 must be a unicode character".to_string();
+                return Err(vm.new_type_error(message));
+            }
+        },
+        None => "T".to_string(),
+    };
+    datetime_text(vm, values[0].as_ref().unwrap(), &separator)
+}
+
+/// `datetime.date()`: the date, without the time.
+fn datetime_date(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "date", 1, 1)?;
+    let date = fields(vm, &args.positional[0], &["year", "month", "day"])?;
+    let class = class(vm, "date")?;
+    let date = date.into_iter().map(|value| vm.new_int(value)).collect();
+    vm.call(&class, Args::new(date))
+}
+
+/// `datetime.time()`: the time, without the date or the `tzinfo`.
+fn datetime_time(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "time", 1, 1)?;
+    let names = ["hour", "minute", "second", "microsecond"];
+    let time = fields(vm, &args.positional[0], &names)?;
+    let class = class(vm, "time")?;
+    let time = time.into_iter().map(|value| vm.new_int(value)).collect();
+    vm.call(&class, Args::new(time))
+}
+
+/// `datetime.utcoffset()`: what the `tzinfo` gives for the datetime, or
+/// `None` if it is naive.
+fn datetime_utcoffset(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "utcoffset", 1, 1)?;
+    let this = &args.positional[0];
+    let tzinfo = vm.getattr(this, "tzinfo")?;
+    if Vm::is(&tzinfo, &vm.none()) {
+        return Ok(vm.none());
+    }
+    let method = vm.getattr(&tzinfo, "utcoffset")?;
+    vm.call(&method, Args::new(vec![this.clone()]))
+}
+
+/// `time(hour=0, minute=0, second=0, microsecond=0, tzinfo=None)`.
+fn time_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(
+        vm,
+        args,
+        "time",
+        &["self", "hour", "minute", "second", "microsecond", "tzinfo"],
+        1,
+    )?;
+    let time = check_time(vm, &values[1..5])?;
+    let this = values[0].as_ref().unwrap();
+    set_fields(
+        vm,
+        this,
+        &["hour", "minute", "second", "microsecond"],
+        &time,
+    )?;
+    set_tzinfo(vm, this, values[5].clone())?;
+    Ok(vm.none())
+}
+
+fn time_repr(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__repr__", 1, 1)?;
+    let this = &args.positional[0];
+    let time = time_arguments(vm, this)?;
+    let name = type_name(vm, this)?;
+    Ok(vm.new_str(&format!("{}({})", name, time)))
+}
+
+/// `time.isoformat()`: `HH:MM:SS`, with the microseconds if they are not
+/// 0 and the offset if there is one.
+fn time_isoformat(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "isoformat", 1, 1)?;
+    let none = vm.none();
+    let text = time_text(vm, &args.positional[0], none)?;
+    Ok(vm.new_str(&text))
+}
+
+/// `time.utcoffset()`: what the `tzinfo` gives for `None`, or `None` if
+/// the time is naive.
+fn time_utcoffset(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "utcoffset", 1, 1)?;
+    let tzinfo = vm.getattr(&args.positional[0], "tzinfo")?;
+    if Vm::is(&tzinfo, &vm.none()) {
+        return Ok(vm.none());
+    }
+    let method = vm.getattr(&tzinfo, "utcoffset")?;
+    let none = vm.none();
+    vm.call(&method, Args::new(vec![none]))
+}
+
+/// The length of the `timedelta` `this` in microseconds.
+fn timedelta_micros(vm: &mut Vm, this: &ObjectRef) -> PyResult<i128> {
+    let parts = fields(vm, this, &["days", "seconds", "microseconds"])?;
+    Ok(i128::from(parts[0]) * DAY + i128::from(parts[1]) * 1_000_000 + i128::from(parts[2]))
+}
+
+/// `timedelta(days=0, seconds=0, microseconds=0, milliseconds=0,
+/// minutes=0, hours=0, weeks=0)`: the sum, kept as days, seconds from 0 to
+/// 86399 and microseconds from 0 to 999999.
+fn timedelta_init(vm: &mut Vm, args: Args) -> PyResult {
+    const UNITS: [(&str, i128); 7] = [
+        ("days", DAY),
+        ("seconds", 1_000_000),
+        ("microseconds", 1),
+        ("milliseconds", 1000),
+        ("minutes", 60_000_000),
+        ("hours", 3_600_000_000),
+        ("weeks", 7 * DAY),
+    ];
+    let mut names = vec!["self"];
+    names.extend(UNITS.iter().map(|&(name, _)| name));
+    let values = bind_arguments(vm, args, "timedelta", &names, 1)?;
+    let mut micros: i128 = 0;
+    for (value, &(_, unit)) in values[1..].iter().zip(&UNITS) {
+        if let Some(ref value) = *value {
+            micros += i128::from(index_value(vm, value)?) * unit;
+        }
+    }
+    let days = micros.div_euclid(DAY);
+    if days.abs() > MAX_DAYS {
+        let message = format!("days={}; must have magnitude <= {}", days, MAX_DAYS);
+        return Err(vm.new_overflow_error(message));
+    }
+    let rest = micros.rem_euclid(DAY);
+    let parts = [
+        days as i64,
+        (rest / 1_000_000) as i64,
+        (rest % 1_000_000) as i64,
+    ];
+    let this = values[0].as_ref().unwrap();
+    set_fields(vm, this, &["days", "seconds", "microseconds"], &parts)?;
+    Ok(vm.none())
+}
+
+/// The repr of a `timedelta` of the class named `name`: the fields that
+/// are not 0, by name.
+fn timedelta_repr_text(name: &str, parts: &[i64]) -> String {
+    let given: Vec<String> = ["days", "seconds", "microseconds"]
+        .iter()
+        .zip(parts)
+        .filter(|&(_, &value)| value != 0)
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect();
+    if given.is_empty() {
+        format!("{}(0)", name)
+    } else {
+        format!("{}({})", name, given.join(", "))
+    }
+}
+
+fn timedelta_repr(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__repr__", 1, 1)?;
+    let this = &args.positional[0];
+    let parts = fields(vm, this, &["days", "seconds", "microseconds"])?;
+    let name = type_name(vm, this)?;
+    Ok(vm.new_str(&timedelta_repr_text(&name, &parts)))
+}
+
+/// `timedelta.__str__()`: `[D day[s], ]H:MM:SS[.UUUUUU]`.
+fn timedelta_str(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__str__", 1, 1)?;
+    let parts = fields(
+        vm,
+        &args.positional[0],
+        &["days", "seconds", "microseconds"],
+    )?;
+    let (days, seconds, micros) = (parts[0], parts[1], parts[2]);
+    let mut text = String::new();
+    if days != 0 {
+        let plural = if days.abs() == 1 { "" } else { "s" };
+        text.push_str(&format!("{} day{}, ", days, plural));
+    }
+    text.push_str(&format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    ));
+    if micros != 0 {
+        text.push_str(&format!(".{:06}", micros));
+    }
+    Ok(vm.new_str(&text))
+}
+
+/// `timedelta.total_seconds()`.
+fn timedelta_total_seconds(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "total_seconds", 1, 1)?;
+    let micros = timedelta_micros(vm, &args.positional[0])?;
+    Ok(vm.new_float(micros as f64 / 1e6))
+}
+
+/// `timedelta.__bool__()`: whether the length is not 0.
+fn timedelta_bool(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__bool__", 1, 1)?;
+    let micros = timedelta_micros(vm, &args.positional[0])?;
+    Ok(vm.new_bool(micros != 0))
+}
+
+/// `tzinfo.utcoffset(dt)`, which subclasses implement.
+fn tzinfo_utcoffset(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "utcoffset", 2, 2)?;
+    let class = vm.exceptions.not_implemented_error.clone();
+    let message = "a tzinfo subclass must implement utcoffset()".to_string();
+    Err(vm.new_error(&class, message))
+}
+
+/// `timezone(offset, name=None)`: a fixed offset from UTC, of less than a
+/// day either way.
+fn timezone_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "timezone", &["self", "offset", "name"], 2)?;
+    let offset = values[1].clone().unwrap();
+    let timedelta = class(vm, "timedelta")?;
+    if !Vm::is_instance(&offset, &timedelta) {
+        let message = format!(
+            "timezone() argument 1 must be datetime.timedelta, not {}",
+            offset.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let micros = timedelta_micros(vm, &offset)?;
+    if micros.abs() >= DAY {
+        let message = format!(
+            "offset must be a timedelta strictly between -timedelta(hours=24) and \
+             timedelta(hours=24), not {}.",
+            vm.repr(&offset)?
+        );
+        return Err(vm.new_value_error(message));
+    }
+    let name = match values[2] {
+        Some(ref name) if name.as_str().is_some() => name.clone(),
+        Some(ref name) => {
+            let message = format!(
+                "timezone() argument 2 must be str, not {}",
+                name.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+        None => vm.none(),
+    };
+    let this = values[0].as_ref().unwrap();
+    vm.setattr(this, "_offset", offset)?;
+    vm.setattr(this, "_name", name)?;
+    Ok(vm.none())
+}
+
+/// The name of the timezone `this`: the one it was given, or `UTC` and
+/// its offset.
+fn timezone_name(vm: &mut Vm, this: &ObjectRef) -> PyResult<String> {
+    let name = vm.getattr(this, "_name")?;
+    if let Some(name) = name.as_str() {
+        return Ok(name.to_string());
+    }
+    let offset = vm.getattr(this, "_offset")?;
+    match timedelta_micros(vm, &offset)? {
+        0 => Ok("UTC".to_string()),
+        micros => Ok(format!("UTC{}", offset_text(micros))),
+    }
+}
+
+fn timezone_repr(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__repr__", 1, 1)?;
+    let this = &args.positional[0];
+    let name = type_name(vm, this)?;
+    let offset = vm.getattr(this, "_offset")?;
+    let given = vm.getattr(this, "_name")?;
+    let none = vm.none();
+    let text = if Vm::is(&given, &none) && timedelta_micros(vm, &offset)? == 0 {
+        format!("{}.utc", name)
+    } else if Vm::is(&given, &none) {
+        format!("{}({})", name, vm.repr(&offset)?)
+    } else {
+        format!("{}({}, {})", name, vm.repr(&offset)?, vm.repr(&given)?)
+    };
+    Ok(vm.new_str(&text))
+}
+
+fn timezone_str(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__str__", 1, 1)?;
+    let name = timezone_name(vm, &args.positional[0])?;
+    Ok(vm.new_str(&name))
+}
+
+/// `timezone.tzname(dt)`.
+fn timezone_tzname(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "tzname", 2, 2)?;
+    let name = timezone_name(vm, &args.positional[0])?;
+    Ok(vm.new_str(&name))
+}
+
+/// `timezone.utcoffset(dt)`: the offset, whatever `dt` is.
+fn timezone_utcoffset(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "utcoffset", 2, 2)?;
+    vm.getattr(&args.positional[0], "_offset")
+}
@@ -0,0 +1,433 @@
+//! `difflib`: `SequenceMatcher` and `unified_diff`, on the matcher of the
+//! crate's `diff` module. Items are compared as a dict compares keys, so
+//! sequences of any hashable objects can be matched, as in CPython.
+
+use std::collections::HashSet;
+
+use diff::{self, DiffHeader, Match, Opcode, SequenceMatcher};
+
+use super::super::builtins::index_value;
+use super::super::object::{Args, NativeFunction, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, new_native_class, new_native_module};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "difflib", &[("unified_diff", unified_diff)]);
+    let tuple = vm.types.tuple.clone();
+    let match_class =
+        new_native_class(vm, "difflib", "Match", &tuple, &[("__repr__", match_repr)])?;
+    let fields: [(&'static str, NativeFunction); 3] =
+        [("a", match_a), ("b", match_b), ("size", match_size)];
+    for &(name, getter) in &fields {
+        let getter = vm.new_builtin(name, getter);
+        let property = vm.types.property.clone();
+        let property = vm.call(&property, Args::new(vec![getter]))?;
+        vm.setattr(&match_class, name, property)?;
+    }
+    add_attribute(vm, &module, "Match", match_class);
+    let object = vm.types.object.clone();
+    let matcher = new_native_class(
+        vm,
+        "difflib",
+        "SequenceMatcher",
+        &object,
+        &[
+            ("__init__", init),
+            ("find_longest_match", find_longest_match),
+            ("get_grouped_opcodes", get_grouped_opcodes),
+            ("get_matching_blocks", get_matching_blocks),
+            ("get_opcodes", get_opcodes),
+            ("quick_ratio", quick_ratio),
+            ("ratio", ratio),
+            ("real_quick_ratio", real_quick_ratio),
+            ("set_seq1", set_seq1),
+            ("set_seq2", set_seq2),
+            ("set_seqs", set_seqs),
+        ],
+    )?;
+    add_attribute(vm, &module, "SequenceMatcher", matcher);
+    Ok(module)
+}
+
+/// The sequences of a `SequenceMatcher`, with each item replaced by a
+/// number that is the same for equal items.
+struct Sequences {
+    a: Vec<usize>,
+    b: Vec<usize>,
+    junk: HashSet<usize>,
+    autojunk: bool,
+}
+
+impl Sequences {
+    fn matcher(&self) -> SequenceMatcher<'_, usize> {
+        SequenceMatcher::with_junk(
+            &self.a,
+            &self.b,
+            |item| self.junk.contains(item),
+            self.autojunk,
+        )
+    }
+}
+
+/// Numbers the items of `sequence`, giving each new item the next number.
+fn number_items(vm: &mut Vm, numbers: &ObjectRef, sequence: &ObjectRef) -> PyResult<Vec<usize>> {
+    let numbers = numbers.as_dict().unwrap();
+    let mut result = Vec::new();
+    for item in vm.iterate(sequence)? {
+        let number = match vm.dict_get(numbers, &item)? {
+            Some(number) => number,
+            None => {
+                let number = vm.new_int(numbers.borrow().len() as i64);
+                vm.dict_set(numbers, item, number.clone())?;
+                number
+            }
+        };
+        result.push(index_value(vm, &number)? as usize);
+    }
+    Ok(result)
+}
+
+fn sequences(vm: &mut Vm, this: &ObjectRef) -> PyResult<Sequences> {
+    let numbers = vm.new_dict();
+    let a = vm.getattr(this, "a")?;
+    let a = number_items(vm, &numbers, &a)?;
+    let b = vm.getattr(this, "b")?;
+    let b = number_items(vm, &numbers, &b)?;
+    let junk = vm.getattr(this, "bjunk")?;
+    let junk = number_items(vm, &numbers, &junk)?.into_iter().collect();
+    let autojunk = vm.getattr(this, "autojunk")?;
+    let autojunk = vm.is_true(&autojunk)?;
+    Ok(Sequences {
+        a,
+        b,
+        junk,
+        autojunk,
+    })
+}
+
+/// `SequenceMatcher(isjunk=None, a='', b='', autojunk=True)`.
+fn init(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["self", "isjunk", "a", "b", "autojunk"];
+    let values = bind_arguments(vm, args, "__init__", &names, 1)?;
+    let this = values[0].as_ref().unwrap();
+    let isjunk = values[1].clone().unwrap_or_else(|| vm.none());
+    vm.setattr(this, "isjunk", isjunk)?;
+    let autojunk = values[4].clone().unwrap_or_else(|| vm.new_bool(true));
+    vm.setattr(this, "autojunk", autojunk)?;
+    let none = vm.none();
+    vm.setattr(this, "a", none.clone())?;
+    vm.setattr(this, "b", none)?;
+    let a = values[2].clone().unwrap_or_else(|| vm.new_str(""));
+    let b = values[3].clone().unwrap_or_else(|| vm.new_str(""));
+    store_seq1(vm, this, a)?;
+    store_seq2(vm, this, b)?;
+    Ok(vm.none())
+}
+
+fn store_seq1(vm: &mut Vm, this: &ObjectRef, a: ObjectRef) -> PyResult<()> {
+    vm.setattr(this, "a", a)
+}
+
+/// Sets `b`, and finds its junk, asking `isjunk` about each item once, and
+/// its popular items, as CPython does when `b` is set.
+fn store_seq2(vm: &mut Vm, this: &ObjectRef, b: ObjectRef) -> PyResult<()> {
+    vm.setattr(this, "b", b.clone())?;
+    let counts = vm.new_dict();
+    let mut items = Vec::new();
+    for item in vm.iterate(&b)? {
+        let count = match vm.dict_get(counts.as_dict().unwrap(), &item)? {
+            Some(count) => index_value(vm, &count)?,
+            None => {
+                items.push(item.clone());
+                0
+            }
+        };
+        let count = vm.new_int(count + 1);
+        vm.dict_set(counts.as_dict().unwrap(), item, count)?;
+    }
+    let junk = vm.new_set(false);
+    let isjunk = vm.getattr(this, "isjunk")?;
+    let mut common = Vec::new();
+    for item in items {
+        let is_junk = if matches!(isjunk.payload, Payload::None) {
+            false
+        } else {
+            let result = vm.call(&isjunk, Args::new(vec![item.clone()]))?;
+            vm.is_true(&result)?
+        };
+        if is_junk {
+            vm.set_add(set_entries(&junk), item)?;
+        } else {
+            common.push(item);
+        }
+    }
+    let popular = vm.new_set(false);
+    let length = vm.len(&b)?;
+    let autojunk = vm.getattr(this, "autojunk")?;
+    if vm.is_true(&autojunk)? && length >= 200 {
+        let limit = (length / 100 + 1) as i64;
+        for item in common {
+            let count = vm.dict_get(counts.as_dict().unwrap(), &item)?.unwrap();
+            if index_value(vm, &count)? > limit {
+                vm.set_add(set_entries(&popular), item)?;
+            }
+        }
+    }
+    vm.setattr(this, "bjunk", junk)?;
+    vm.setattr(this, "bpopular", popular)
+}
+
+fn set_entries(set: &ObjectRef) -> &::std::cell::RefCell<super::super::Dict> {
+    match set.payload {
+        Payload::Set(ref entries) => entries,
+        _ => unreachable!(),
+    }
+}
+
+/// `matcher.set_seqs(a, b)`.
+fn set_seqs(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "set_seqs", &["self", "a", "b"], 3)?;
+    let this = values[0].as_ref().unwrap();
+    store_seq1(vm, this, values[1].clone().unwrap())?;
+    store_seq2(vm, this, values[2].clone().unwrap())?;
+    Ok(vm.none())
+}
+
+/// `matcher.set_seq1(a)`.
+fn set_seq1(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "set_seq1", &["self", "a"], 2)?;
+    store_seq1(vm, values[0].as_ref().unwrap(), values[1].clone().unwrap())?;
+    Ok(vm.none())
+}
+
+/// `matcher.set_seq2(b)`.
+fn set_seq2(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "set_seq2", &["self", "b"], 2)?;
+    store_seq2(vm, values[0].as_ref().unwrap(), values[1].clone().unwrap())?;
+    Ok(vm.none())
+}
+
+/// A `Match` tuple for `block`.
+fn new_match(vm: &mut Vm, block: Match) -> PyResult {
+    let module = vm.import_module("difflib")?;
+    let class = vm.getattr(&module, "Match")?;
+    let fields = [block.a, block.b, block.size]
+        .iter()
+        .map(|&field| vm.new_int(field as i64))
+        .collect();
+    let fields = vm.new_tuple(fields);
+    vm.call(&class, Args::new(vec![fields]))
+}
+
+fn match_field(vm: &mut Vm, args: Args, name: &str, index: i64) -> PyResult {
+    let values = bind_arguments(vm, args, name, &["self"], 1)?;
+    let index = vm.new_int(index);
+    vm.getitem(values[0].as_ref().unwrap(), &index)
+}
+
+fn match_a(vm: &mut Vm, args: Args) -> PyResult {
+    match_field(vm, args, "a", 0)
+}
+
+fn match_b(vm: &mut Vm, args: Args) -> PyResult {
+    match_field(vm, args, "b", 1)
+}
+
+fn match_size(vm: &mut Vm, args: Args) -> PyResult {
+    match_field(vm, args, "size", 2)
+}
+
+fn match_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__repr__", &["self"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let mut fields = Vec::new();
+    for (index, name) in ["a", "b", "size"].iter().enumerate() {
+        let index = vm.new_int(index as i64);
+        let value = vm.getitem(this, &index)?;
+        fields.push(format!("{}={}", name, vm.repr(&value)?));
+    }
+    Ok(vm.new_str(&format!("Match({})", fields.join(", "))))
+}
+
+/// An optional bound of `find_longest_match`, within `0..=length`.
+fn bound(vm: &mut Vm, value: &Option<ObjectRef>, default: usize, length: usize) -> PyResult<usize> {
+    match *value {
+        Some(ref value) if !matches!(value.payload, Payload::None) => {
+            Ok(index_value(vm, value)?.max(0).min(length as i64) as usize)
+        }
+        _ => Ok(default),
+    }
+}
+
+/// `matcher.find_longest_match(alo=0, ahi=None, blo=0, bhi=None)`.
+fn find_longest_match(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["self", "alo", "ahi", "blo", "bhi"];
+    let values = bind_arguments(vm, args, "find_longest_match", &names, 1)?;
+    let sequences = sequences(vm, values[0].as_ref().unwrap())?;
+    let (length_a, length_b) = (sequences.a.len(), sequences.b.len());
+    let a_low = bound(vm, &values[1], 0, length_a)?;
+    let a_high = bound(vm, &values[2], length_a, length_a)?;
+    let b_low = bound(vm, &values[3], 0, length_b)?;
+    let b_high = bound(vm, &values[4], length_b, length_b)?;
+    let block = sequences
+        .matcher()
+        .find_longest_match(a_low, a_high, b_low, b_high);
+    new_match(vm, block)
+}
+
+/// `matcher.get_matching_blocks()`: a list of `Match` tuples.
+fn get_matching_blocks(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "get_matching_blocks", &["self"], 1)?;
+    let blocks = sequences(vm, values[0].as_ref().unwrap())?
+        .matcher()
+        .matching_blocks();
+    let mut items = Vec::new();
+    for block in blocks {
+        items.push(new_match(vm, block)?);
+    }
+    Ok(vm.new_list(items))
+}
+
+/// An opcode as `difflib` gives it: `(tag, i1, i2, j1, j2)`.
+fn opcode_tuple(vm: &mut Vm, code: &Opcode) -> ObjectRef {
+    let items = vec![
+        vm.new_str(code.tag.name()),
+        vm.new_int(code.a_start as i64),
+        vm.new_int(code.a_end as i64),
+        vm.new_int(code.b_start as i64),
+        vm.new_int(code.b_end as i64),
+    ];
+    vm.new_tuple(items)
+}
+
+/// `matcher.get_opcodes()`.
+fn get_opcodes(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "get_opcodes", &["self"], 1)?;
+    let codes = sequences(vm, values[0].as_ref().unwrap())?
+        .matcher()
+        .opcodes();
+    let items = codes.iter().map(|code| opcode_tuple(vm, code)).collect();
+    Ok(vm.new_list(items))
+}
+
+/// `matcher.get_grouped_opcodes(n=3)`: an iterator over lists of opcodes.
+fn get_grouped_opcodes(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "get_grouped_opcodes", &["self", "n"], 1)?;
+    let context = context_argument(vm, &values[1])?;
+    let groups = sequences(vm, values[0].as_ref().unwrap())?
+        .matcher()
+        .grouped_opcodes(context);
+    let groups = groups
+        .iter()
+        .map(|group| {
+            let codes = group.iter().map(|code| opcode_tuple(vm, code)).collect();
+            vm.new_list(codes)
+        })
+        .collect();
+    let groups = vm.new_list(groups);
+    vm.iter(&groups)
+}
+
+fn context_argument(vm: &mut Vm, value: &Option<ObjectRef>) -> PyResult<usize> {
+    match *value {
+        Some(ref value) => Ok(index_value(vm, value)?.max(0) as usize),
+        None => Ok(3),
+    }
+}
+
+/// `matcher.ratio()`.
+fn ratio(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "ratio", &["self"], 1)?;
+    let ratio = sequences(vm, values[0].as_ref().unwrap())?
+        .matcher()
+        .ratio();
+    Ok(vm.new_float(ratio))
+}
+
+/// `matcher.quick_ratio()`.
+fn quick_ratio(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "quick_ratio", &["self"], 1)?;
+    let ratio = sequences(vm, values[0].as_ref().unwrap())?
+        .matcher()
+        .quick_ratio();
+    Ok(vm.new_float(ratio))
+}
+
+/// `matcher.real_quick_ratio()`.
+fn real_quick_ratio(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "real_quick_ratio", &["self"], 1)?;
+    let ratio = sequences(vm, values[0].as_ref().unwrap())?
+        .matcher()
+        .real_quick_ratio();
+    Ok(vm.new_float(ratio))
+}
+
+/// The lines `sequence`, which must be strings, as CPython checks them.
+fn lines_argument(vm: &mut Vm, sequence: &ObjectRef) -> PyResult<Vec<String>> {
+    let mut lines = Vec::new();
+    for line in vm.iterate(sequence)? {
+        match line.as_str() {
+            Some(text) => lines.push(text.to_string()),
+            None => {
+                let message = format!(
+                    "lines to compare must be str, not {} ({})",
+                    line.type_name(),
+                    vm.repr(&line)?
+                );
+                return Err(vm.new_type_error(message));
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// `unified_diff(a, b, fromfile='', tofile='', fromfiledate='',
+/// tofiledate='', n=3, lineterm='\n')`: an iterator over the lines of the
+/// diff.
+fn unified_diff(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "a",
+        "b",
+        "fromfile",
+        "tofile",
+        "fromfiledate",
+        "tofiledate",
+        "n",
+        "lineterm",
+    ];
+    let values = bind_arguments(vm, args, "unified_diff", &names, 2)?;
+    let a = lines_argument(vm, values[0].as_ref().unwrap())?;
+    let b = lines_argument(vm, values[1].as_ref().unwrap())?;
+    let mut texts = Vec::new();
+    for (value, default) in [
+        (&values[2], ""),
+        (&values[3], ""),
+        (&values[4], ""),
+        (&values[5], ""),
+        (&values[7], "\n"),
+    ] {
+        match *value {
+            Some(ref value) => match value.as_str() {
+                Some(text) => texts.push(text.to_string()),
+                None => {
+                    let message = format!("all arguments must be str, not: {}", vm.repr(value)?);
+                    return Err(vm.new_type_error(message));
+                }
+            },
+            None => texts.push(default.to_string()),
+        }
+    }
+    let context = context_argument(vm, &values[6])?;
+    let header = DiffHeader {
+        from_file: &texts[0],
+        to_file: &texts[1],
+        from_date: &texts[2],
+        to_date: &texts[3],
+    };
+    let lines = diff::unified_diff(&a, &b, &header, context, &texts[4])
+        .iter()
+        .map(|line| vm.new_str(line))
+        .collect();
+    let lines = vm.new_list(lines);
+    vm.iter(&lines)
+}
@@ -0,0 +1,2198 @@
+//! `xml.etree.ElementTree`: XML documents as trees of `Element`s, parsed
+//! from strings and files, searched with ElementTree's subset of XPath,
+//! and written out as XML, HTML or text.
+//!
+//! An element keeps its tag, attributes, text and tail in the attributes
+//! of those names, and its children in the list `_children`, as in
+//! CPython's Python implementation. Documents are parsed whole by the
+//! VM's `xml` parser, so there is no `XMLParser`, `TreeBuilder` or
+//! `iterparse`.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+
+use ast::repr_str;
+
+use super::super::builtins::index_value;
+use super::super::object::{object_id, Args, ObjectRef, Payload, PyResult};
+use super::super::xml::{self, Node, XmlError};
+use super::super::Vm;
+use super::{
+    add_attribute, bind_arguments, module_error, new_native_class, new_native_module,
+    new_native_package, os_error,
+};
+
+const MODULE: &str = "xml.etree.ElementTree";
+
+/// The prefixes that namespaces are written with, until
+/// `register_namespace` changes them.
+const NAMESPACE_PREFIXES: [(&str, &str); 7] = [
+    ("http://www.w3.org/XML/1998/namespace", "xml"),
+    ("http://www.w3.org/1999/xhtml", "html"),
+    ("http://www.w3.org/1999/02/22-rdf-syntax-ns#", "rdf"),
+    ("http://schemas.xmlsoap.org/wsdl/", "wsdl"),
+    ("http://www.w3.org/2001/XMLSchema", "xs"),
+    ("http://www.w3.org/2001/XMLSchema-instance", "xsi"),
+    ("http://purl.org/dc/elements/1.1/", "dc"),
+];
+
+/// The HTML elements that are written without an end tag.
+const HTML_EMPTY: [&str; 17] = [
+    "area", "base", "basefont", "br", "col", "embed", "frame", "hr", "img", "input", "isindex",
+    "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// The package `xml`, which holds only `xml.etree`.
+pub(super) fn xml_package(vm: &mut Vm) -> PyResult<ObjectRef> {
+    Ok(new_native_package(vm, "xml"))
+}
+
+/// The package `xml.etree`, which holds only `xml.etree.ElementTree`.
+pub(super) fn etree_package(vm: &mut Vm) -> PyResult<ObjectRef> {
+    Ok(new_native_package(vm, "xml.etree"))
+}
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        MODULE,
+        &[
+            ("Comment", comment),
+            ("ProcessingInstruction", processing_instruction),
+            ("SubElement", sub_element),
+            ("dump", dump),
+            ("fromstring", fromstring),
+            ("fromstringlist", fromstringlist),
+            ("indent", indent),
+            ("iselement", iselement),
+            ("parse", parse),
+            ("register_namespace", register_namespace),
+            ("tostring", tostring),
+            ("tostringlist", tostringlist),
+        ],
+    );
+    for &(alias, name) in &[("PI", "ProcessingInstruction"), ("XML", "fromstring")] {
+        let value = vm.getattr(&module, name)?;
+        add_attribute(vm, &module, alias, value);
+    }
+    let syntax_error = vm.exceptions.syntax_error.clone();
+    let parse_error = new_native_class(vm, MODULE, "ParseError", &syntax_error, &[])?;
+    add_attribute(vm, &module, "ParseError", parse_error);
+    let object = vm.types.object.clone();
+    let element = new_native_class(
+        vm,
+        MODULE,
+        "Element",
+        &object,
+        &[
+            ("__delitem__", delitem),
+            ("__getitem__", getitem),
+            ("__init__", init),
+            ("__iter__", iter),
+            ("__len__", len),
+            ("__repr__", repr),
+            ("__setitem__", setitem),
+            ("append", append),
+            ("clear", clear),
+            ("extend", extend),
+            ("find", find),
+            ("findall", findall),
+            ("findtext", findtext),
+            ("get", get),
+            ("insert", insert),
+            ("items", items),
+            ("iter", iter_method),
+            ("iterfind", iterfind),
+            ("itertext", itertext),
+            ("keys", keys),
+            ("makeelement", makeelement),
+            ("remove", remove),
+            ("set", set),
+        ],
+    )?;
+    add_attribute(vm, &module, "Element", element);
+    let tree = new_native_class(
+        vm,
+        MODULE,
+        "ElementTree",
+        &object,
+        &[
+            ("__init__", tree_init),
+            ("_setroot", tree_setroot),
+            ("find", tree_find),
+            ("findall", tree_findall),
+            ("findtext", tree_findtext),
+            ("getroot", tree_getroot),
+            ("iter", tree_iter),
+            ("iterfind", tree_iterfind),
+            ("parse", tree_parse),
+            ("write", tree_write),
+        ],
+    )?;
+    add_attribute(vm, &module, "ElementTree", tree);
+    let prefixes = vm.new_dict();
+    for &(uri, prefix) in &NAMESPACE_PREFIXES {
+        let prefix = vm.new_str(prefix);
+        vm.dict_set_str(prefixes.as_dict().unwrap(), uri, prefix);
+    }
+    add_attribute(vm, &module, "_namespace_map", prefixes);
+    let version = vm.new_str("1.3.0");
+    add_attribute(vm, &module, "VERSION", version);
+    Ok(module)
+}
+
+fn class(vm: &mut Vm, name: &str) -> PyResult {
+    let module = vm.import_module(MODULE)?;
+    vm.getattr(&module, name)
+}
+
+fn is_element(vm: &mut Vm, value: &ObjectRef) -> PyResult<bool> {
+    let element = class(vm, "Element")?;
+    Ok(Vm::is_instance(value, &element))
+}
+
+/// Fails unless `value` is an element, as the C accelerator checks the
+/// arguments of `append`, `insert` and `SubElement`. `argument` is the
+/// number the message gives it, if any.
+fn check_element(vm: &mut Vm, value: &ObjectRef, function: &str, argument: &str) -> PyResult<()> {
+    if is_element(vm, value)? {
+        return Ok(());
+    }
+    let message = format!(
+        "{}() argument {}must be {}.Element, not {}",
+        function,
+        argument,
+        MODULE,
+        value.type_name()
+    );
+    Err(vm.new_type_error(message))
+}
+
+/// A `ParseError` for a failed parse, with expat's error code in `code`
+/// and the `(line, column)` of the error in `position`. Its `SyntaxError`
+/// attributes other than `msg` are `None`.
+fn parse_error(vm: &mut Vm, error: &XmlError) -> ObjectRef {
+    let exception = module_error(vm, MODULE, "ParseError", error.message());
+    let code = vm.new_int(error.kind.code());
+    let _ = vm.setattr(&exception, "code", code);
+    let line = vm.new_int(error.line as i64);
+    let column = vm.new_int(error.column as i64);
+    let position = vm.new_tuple(vec![line, column]);
+    let _ = vm.setattr(&exception, "position", position);
+    exception
+}
+
+/// The list of the children of an element.
+fn children_list(vm: &mut Vm, element: &ObjectRef) -> PyResult {
+    vm.getattr(element, "_children")
+}
+
+fn children(vm: &mut Vm, element: &ObjectRef) -> PyResult<Vec<ObjectRef>> {
+    let list = children_list(vm, element)?;
+    match list.payload {
+        Payload::List(ref items) => Ok(items.borrow().clone()),
+        _ => vm.iterate(&list),
+    }
+}
+
+/// Runs `change` on the list of the children of an element.
+fn change_children<F, T>(vm: &mut Vm, element: &ObjectRef, change: F) -> PyResult<T>
+where
+    F: FnOnce(&mut Vec<ObjectRef>) -> T,
+{
+    let list = children_list(vm, element)?;
+    match list.payload {
+        Payload::List(ref items) => Ok(change(&mut items.borrow_mut())),
+        _ => Err(vm.new_type_error("_children must be a list".to_string())),
+    }
+}
+
+fn push_child(vm: &mut Vm, element: &ObjectRef, child: ObjectRef) -> PyResult<()> {
+    change_children(vm, element, |children| children.push(child))
+}
+
+/// The attributes of an element, from its `attrib` dict.
+fn attributes(vm: &mut Vm, element: &ObjectRef) -> PyResult<Vec<(ObjectRef, ObjectRef)>> {
+    let attrib = vm.getattr(element, "attrib")?;
+    match attrib.as_dict() {
+        Some(dict) => Ok(dict
+            .borrow()
+            .iter()
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect()),
+        None => {
+            let message = format!("attrib must be dict, not {}", attrib.type_name());
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// The value of the attribute `key` of an element, if it has one.
+fn attribute(vm: &mut Vm, element: &ObjectRef, key: &str) -> PyResult<Option<ObjectRef>> {
+    let attrib = vm.getattr(element, "attrib")?;
+    let key = vm.new_str(key);
+    match attrib.as_dict() {
+        Some(dict) => vm.dict_get(dict, &key),
+        None => Ok(None),
+    }
+}
+
+/// Whether an element's tag is the string `tag`.
+fn has_tag(vm: &mut Vm, element: &ObjectRef, tag: &str) -> PyResult<bool> {
+    let actual = vm.getattr(element, "tag")?;
+    Ok(actual.as_str() == Some(tag))
+}
+
+/// An element and its descendants in document order, those whose tag
+/// equals `tag` if it is given and is not `*`.
+fn descendants(
+    vm: &mut Vm,
+    element: &ObjectRef,
+    tag: Option<&ObjectRef>,
+) -> PyResult<Vec<ObjectRef>> {
+    let tag = tag.filter(|tag| tag.as_str() != Some("*") && !Vm::is(tag, &vm.none()));
+    let mut found = Vec::new();
+    let mut pending = vec![element.clone()];
+    while let Some(element) = pending.pop() {
+        let matches = match tag {
+            Some(tag) => {
+                let actual = vm.getattr(&element, "tag")?;
+                Vm::is(&actual, tag) || vm.eq(&actual, tag)?
+            }
+            None => true,
+        };
+        let mut inner = children(vm, &element)?;
+        if matches {
+            found.push(element);
+        }
+        inner.reverse();
+        pending.extend(inner);
+    }
+    Ok(found)
+}
+
+/// The text of an element and of its descendants, with the tails of the
+/// descendants, in document order.
+fn text_parts(vm: &mut Vm, element: &ObjectRef, parts: &mut Vec<String>) -> PyResult<()> {
+    let text = vm.getattr(element, "text")?;
+    if let Some(text) = text.as_str().filter(|text| !text.is_empty()) {
+        parts.push(text.to_string());
+    }
+    for child in children(vm, element)? {
+        text_parts(vm, &child, parts)?;
+        let tail = vm.getattr(&child, "tail")?;
+        if let Some(tail) = tail.as_str().filter(|tail| !tail.is_empty()) {
+            parts.push(tail.to_string());
+        }
+    }
+    Ok(())
+}
+
+fn text_of(vm: &mut Vm, element: &ObjectRef) -> PyResult<String> {
+    let mut parts = Vec::new();
+    text_parts(vm, element, &mut parts)?;
+    Ok(parts.concat())
+}
+
+/// A new element of the class `class`.
+fn new_element(vm: &mut Vm, class: &ObjectRef, tag: ObjectRef, attrib: ObjectRef) -> PyResult {
+    vm.call(class, Args::new(vec![tag, attrib]))
+}
+
+/// The elements of a parsed document, made by calling `Element` as
+/// ElementTree's tree builder does.
+fn build(vm: &mut Vm, root: Node) -> PyResult {
+    let class = class(vm, "Element")?;
+    let make = |vm: &mut Vm, node: Node| -> PyResult<(ObjectRef, Vec<Node>)> {
+        let tag = vm.new_str(&node.tag);
+        let attrib = vm.new_dict();
+        for (key, value) in &node.attributes {
+            let value = vm.new_str(value);
+            vm.dict_set_str(attrib.as_dict().unwrap(), key, value);
+        }
+        let element = new_element(vm, &class, tag, attrib)?;
+        for (name, text) in [("text", &node.text), ("tail", &node.tail)] {
+            if let Some(ref text) = *text {
+                let text = vm.new_str(text);
+                vm.setattr(&element, name, text)?;
+            }
+        }
+        Ok((element, node.children))
+    };
+    let (root, nodes) = make(vm, root)?;
+    let mut pending = vec![(root.clone(), nodes.into_iter())];
+    while let Some(last) = pending.last_mut() {
+        let parent = last.0.clone();
+        match last.1.next() {
+            Some(node) => {
+                let (element, nodes) = make(vm, node)?;
+                push_child(vm, &parent, element.clone())?;
+                pending.push((element, nodes.into_iter()));
+            }
+            None => {
+                pending.pop();
+            }
+        }
+    }
+    Ok(root)
+}
+
+/// Parses a document given as a string or as bytes.
+fn parse_document(vm: &mut Vm, source: &ObjectRef) -> PyResult {
+    let parsed = match source.payload {
+        Payload::Str(ref text) => xml::parse(text),
+        Payload::Bytes(ref data) => xml::decode(data).and_then(|text| xml::parse(&text)),
+        _ => {
+            let message = format!(
+                "a bytes-like object is required, not '{}'",
+                source.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    match parsed {
+        Ok(root) => build(vm, root),
+        Err(error) => Err(parse_error(vm, &error)),
+    }
+}
+
+/// `Element(tag, attrib={}, **extra)`.
+fn init(vm: &mut Vm, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    if positional.len() < 2 {
+        let message = "Element() takes at least 1 argument (0 given)".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    if positional.len() > 3 {
+        let message = format!(
+            "Element() takes at most 2 arguments ({} given)",
+            positional.len() - 1
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let this = &positional[0];
+    let attrib = vm.new_dict();
+    if let Some(given) = positional.get(2) {
+        let entries: Vec<(ObjectRef, ObjectRef)> = match given.as_dict() {
+            Some(dict) => dict
+                .borrow()
+                .iter()
+                .map(|entry| (entry.key.clone(), entry.value.clone()))
+                .collect(),
+            None => {
+                let message = format!(
+                    "Element() argument 2 must be dict, not {}",
+                    given.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        };
+        for (key, value) in entries {
+            vm.dict_set(attrib.as_dict().unwrap(), key, value)?;
+        }
+    }
+    for (key, value) in keywords {
+        vm.dict_set_str(attrib.as_dict().unwrap(), &key, value);
+    }
+    vm.setattr(this, "tag", positional[1].clone())?;
+    vm.setattr(this, "attrib", attrib)?;
+    let none = vm.none();
+    vm.setattr(this, "text", none.clone())?;
+    vm.setattr(this, "tail", none)?;
+    let children = vm.new_list(Vec::new());
+    vm.setattr(this, "_children", children)?;
+    Ok(vm.none())
+}
+
+fn repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__repr__", &["self"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let tag = vm.getattr(this, "tag")?;
+    let text = format!("<Element {} at {:#x}>", vm.repr(&tag)?, object_id(this));
+    Ok(vm.new_str(&text))
+}
+
+fn len(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__len__", &["self"], 1)?;
+    let count = children(vm, values[0].as_ref().unwrap())?.len();
+    Ok(vm.new_int(count as i64))
+}
+
+fn iter(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__iter__", &["self"], 1)?;
+    let list = children_list(vm, values[0].as_ref().unwrap())?;
+    vm.iter(&list)
+}
+
+/// The position of the child `index` among `count`, counting from the end
+/// if it is negative.
+fn child_index(index: i64, count: usize) -> Option<usize> {
+    let index = if index < 0 {
+        index + count as i64
+    } else {
+        index
+    };
+    if index >= 0 && (index as usize) < count {
+        Some(index as usize)
+    } else {
+        None
+    }
+}
+
+/// `element[index]`, a child or a list of them for a slice.
+fn getitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__getitem__", &["self", "index"], 2)?;
+    let (this, index) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    if let Payload::Int(number) = index.payload {
+        let children = children(vm, this)?;
+        return match child_index(number, children.len()) {
+            Some(position) => Ok(children[position].clone()),
+            None => Err(vm.new_index_error("child index out of range".to_string())),
+        };
+    }
+    let list = children_list(vm, this)?;
+    vm.getitem(&list, index)
+}
+
+/// Fails if `index` is an integer that is not the position of a child, as
+/// assigning and deleting children does.
+fn check_assignment_index(vm: &mut Vm, this: &ObjectRef, index: &ObjectRef) -> PyResult<()> {
+    if let Payload::Int(number) = index.payload {
+        let count = children(vm, this)?.len();
+        if child_index(number, count).is_none() {
+            let message = "child assignment index out of range".to_string();
+            return Err(vm.new_index_error(message));
+        }
+    }
+    Ok(())
+}
+
+fn setitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__setitem__", &["self", "index", "value"], 3)?;
+    let this = values[0].as_ref().unwrap();
+    let index = values[1].as_ref().unwrap();
+    check_assignment_index(vm, this, index)?;
+    let list = children_list(vm, this)?;
+    vm.setitem(&list, index, values[2].clone().unwrap())?;
+    Ok(vm.none())
+}
+
+fn delitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__delitem__", &["self", "index"], 2)?;
+    let (this, index) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    check_assignment_index(vm, this, index)?;
+    let list = children_list(vm, this)?;
+    vm.delitem(&list, index)?;
+    Ok(vm.none())
+}
+
+fn append(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "append", &["self", "subelement"], 2)?;
+    let child = values[1].clone().unwrap();
+    check_element(vm, &child, "append", "")?;
+    push_child(vm, values[0].as_ref().unwrap(), child)?;
+    Ok(vm.none())
+}
+
+fn extend(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "extend", &["self", "elements"], 2)?;
+    let this = values[0].as_ref().unwrap();
+    for element in vm.iterate(values[1].as_ref().unwrap())? {
+        if !is_element(vm, &element)? {
+            let message = format!("expected an Element, not \"{}\"", element.type_name());
+            return Err(vm.new_type_error(message));
+        }
+        push_child(vm, this, element)?;
+    }
+    Ok(vm.none())
+}
+
+fn insert(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "insert", &["self", "index", "subelement"], 3)?;
+    let this = values[0].as_ref().unwrap();
+    let index = index_value(vm, values[1].as_ref().unwrap())?;
+    let child = values[2].clone().unwrap();
+    check_element(vm, &child, "insert", "2 ")?;
+    change_children(vm, this, |children| {
+        let count = children.len() as i64;
+        let position = if index < 0 {
+            (index + count).max(0)
+        } else {
+            index.min(count)
+        };
+        children.insert(position as usize, child);
+    })?;
+    Ok(vm.none())
+}
+
+fn remove(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "remove", &["self", "subelement"], 2)?;
+    let this = values[0].as_ref().unwrap();
+    let child = values[1].as_ref().unwrap();
+    let mut position = None;
+    for (index, other) in children(vm, this)?.iter().enumerate() {
+        if Vm::is(other, child) || vm.eq(other, child)? {
+            position = Some(index);
+            break;
+        }
+    }
+    match position {
+        Some(position) => {
+            change_children(vm, this, |children| children.remove(position))?;
+            Ok(vm.none())
+        }
+        None => Err(vm.new_value_error("list.remove(x): x not in list".to_string())),
+    }
+}
+
+/// `element.clear()`: removes the attributes, children, text and tail.
+fn clear(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "clear", &["self"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let attrib = vm.new_dict();
+    vm.setattr(this, "attrib", attrib)?;
+    change_children(vm, this, |children| children.clear())?;
+    let none = vm.none();
+    vm.setattr(this, "text", none.clone())?;
+    vm.setattr(this, "tail", none)?;
+    Ok(vm.none())
+}
+
+fn get(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "get", &["self", "key", "default"], 2)?;
+    let attrib = vm.getattr(values[0].as_ref().unwrap(), "attrib")?;
+    let value = match attrib.as_dict() {
+        Some(dict) => vm.dict_get(dict, values[1].as_ref().unwrap())?,
+        None => None,
+    };
+    Ok(value
+        .or_else(|| values[2].clone())
+        .unwrap_or_else(|| vm.none()))
+}
+
+fn set(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "set", &["self", "key", "value"], 3)?;
+    let attrib = vm.getattr(values[0].as_ref().unwrap(), "attrib")?;
+    vm.setitem(
+        &attrib,
+        values[1].as_ref().unwrap(),
+        values[2].clone().unwrap(),
+    )?;
+    Ok(vm.none())
+}
+
+fn keys(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "keys", &["self"], 1)?;
+    let keys = attributes(vm, values[0].as_ref().unwrap())?
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect();
+    Ok(vm.new_list(keys))
+}
+
+fn items(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "items", &["self"], 1)?;
+    let items = attributes(vm, values[0].as_ref().unwrap())?
+        .into_iter()
+        .map(|(key, value)| vm.new_tuple(vec![key, value]))
+        .collect();
+    Ok(vm.new_list(items))
+}
+
+/// `element.makeelement(tag, attrib)`: a new `Element`, whatever the
+/// class of this one, as in the C accelerator.
+fn makeelement(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "makeelement", &["self", "tag", "attrib"], 3)?;
+    let class = class(vm, "Element")?;
+    let attrib = values[2].as_ref().unwrap();
+    if attrib.as_dict().is_none() {
+        let message = format!(
+            "makeelement() argument 2 must be dict, not {}",
+            attrib.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let copy = vm.new_dict();
+    vm.dict_update(copy.as_dict().unwrap(), attrib)?;
+    new_element(vm, &class, values[1].clone().unwrap(), copy)
+}
+
+/// `element.iter(tag=None)`: the element and its descendants, with the
+/// tag if one is given other than `*`.
+fn iter_method(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "iter", &["self", "tag"], 1)?;
+    let found = descendants(vm, values[0].as_ref().unwrap(), values[1].as_ref())?;
+    let list = vm.new_list(found);
+    vm.iter(&list)
+}
+
+fn itertext(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "itertext", &["self"], 1)?;
+    let mut parts = Vec::new();
+    text_parts(vm, values[0].as_ref().unwrap(), &mut parts)?;
+    let parts = parts.iter().map(|part| vm.new_str(part)).collect();
+    let list = vm.new_list(parts);
+    vm.iter(&list)
+}
+
+/// A path argument, which must be a string.
+fn path_argument(vm: &mut Vm, path: &ObjectRef) -> PyResult<String> {
+    match path.as_str() {
+        Some(path) => Ok(path.to_string()),
+        None => {
+            let message = format!(
+                "expected string or bytes-like object, got '{}'",
+                path.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// The elements `path` selects from `element`.
+fn select_path(
+    vm: &mut Vm,
+    element: &ObjectRef,
+    path: &ObjectRef,
+    namespaces: Option<&ObjectRef>,
+) -> PyResult<Vec<ObjectRef>> {
+    let path = path_argument(vm, path)?;
+    let namespaces = namespaces.filter(|namespaces| !Vm::is(namespaces, &vm.none()));
+    // A plain tag names children, without compiling a path.
+    if namespaces.is_none() && !is_path(&path) {
+        let mut found = Vec::new();
+        for child in children(vm, element)? {
+            if has_tag(vm, &child, &path)? {
+                found.push(child);
+            }
+        }
+        return Ok(found);
+    }
+    let prefixes = match namespaces {
+        Some(namespaces) => prefix_map(vm, namespaces)?,
+        None => HashMap::new(),
+    };
+    let steps = compile_path(vm, &path, &prefixes)?;
+    select(vm, element, &steps)
+}
+
+fn find(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "find", &["self", "path", "namespaces"], 2)?;
+    let found = select_path(
+        vm,
+        values[0].as_ref().unwrap(),
+        values[1].as_ref().unwrap(),
+        values[2].as_ref(),
+    )?;
+    Ok(found.into_iter().next().unwrap_or_else(|| vm.none()))
+}
+
+fn findall(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "findall", &["self", "path", "namespaces"], 2)?;
+    let found = select_path(
+        vm,
+        values[0].as_ref().unwrap(),
+        values[1].as_ref().unwrap(),
+        values[2].as_ref(),
+    )?;
+    Ok(vm.new_list(found))
+}
+
+fn iterfind(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "iterfind", &["self", "path", "namespaces"], 2)?;
+    let found = select_path(
+        vm,
+        values[0].as_ref().unwrap(),
+        values[1].as_ref().unwrap(),
+        values[2].as_ref(),
+    )?;
+    let list = vm.new_list(found);
+    vm.iter(&list)
+}
+
+/// `element.findtext(path, default=None, namespaces=None)`: the text of
+/// the first element `path` selects, empty if it has none.
+fn findtext(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["self", "path", "default", "namespaces"];
+    let values = bind_arguments(vm, args, "findtext", &names, 2)?;
+    let found = select_path(
+        vm,
+        values[0].as_ref().unwrap(),
+        values[1].as_ref().unwrap(),
+        values[3].as_ref(),
+    )?;
+    match found.first() {
+        Some(element) => {
+            let text = vm.getattr(element, "text")?;
+            if Vm::is(&text, &vm.none()) {
+                Ok(vm.new_str(""))
+            } else {
+                Ok(text)
+            }
+        }
+        None => Ok(values[2].clone().unwrap_or_else(|| vm.none())),
+    }
+}
+
+// ElementPath: the paths of `find` and its relatives.
+
+/// Whether a path needs compiling rather than naming children by tag, as
+/// the C accelerator decides: it has a path character outside `{...}`, or
+/// starts with the wildcard `{}` or `{*}`.
+fn is_path(path: &str) -> bool {
+    if path.starts_with("{}") || path.starts_with("{*}") {
+        return path.chars().count() >= 3;
+    }
+    let mut outside = true;
+    for c in path.chars() {
+        match c {
+            '{' => outside = false,
+            '}' => outside = true,
+            '/' | '*' | '[' | '@' | '.' if outside => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+/// The prefixes of a `namespaces` mapping, with the URIs they stand for.
+fn prefix_map(vm: &mut Vm, namespaces: &ObjectRef) -> PyResult<HashMap<String, String>> {
+    let mut prefixes = HashMap::new();
+    for key in vm.iterate(namespaces)? {
+        let value = vm.getitem(namespaces, &key)?;
+        if let (Some(prefix), Some(uri)) = (key.as_str(), value.as_str()) {
+            prefixes.insert(prefix.to_string(), uri.to_string());
+        }
+    }
+    Ok(prefixes)
+}
+
+/// The tokens of a path, as `(operator, tag)` pairs of which one is
+/// empty, as ElementPath's tokenizer finds them.
+fn tokenize_path(path: &str) -> Vec<(String, String)> {
+    let is_tag_char =
+        |c: char| !matches!(c, '/' | '[' | ']' | '(' | ')' | '@' | '!' | '=') && !c.is_whitespace();
+    let chars: Vec<char> = path.chars().collect();
+    let mut tokens = Vec::new();
+    let mut at = 0;
+    while at < chars.len() {
+        let c = chars[at];
+        if c == '\'' || c == '"' {
+            if let Some(length) = chars[at + 1..].iter().position(|&other| other == c) {
+                let end = at + length + 2;
+                tokens.push((chars[at..end].iter().collect(), String::new()));
+                at = end;
+                continue;
+            }
+        }
+        let pair: String = chars[at..(at + 2).min(chars.len())].iter().collect();
+        if ["::", "//", "..", "()", "!="].contains(&pair.as_str()) {
+            tokens.push((pair, String::new()));
+            at += 2;
+            continue;
+        }
+        if "/.*:[]()@=".contains(c) {
+            tokens.push((c.to_string(), String::new()));
+            at += 1;
+            continue;
+        }
+        // A tag, with its namespace in braces, which may hold any character
+        // but a closing brace.
+        let mut start_of_name = at;
+        if c == '{' {
+            if let Some(length) = chars[at + 1..].iter().position(|&other| other == '}') {
+                if length > 0 && chars.get(at + length + 2).is_some_and(|&c| is_tag_char(c)) {
+                    start_of_name = at + length + 2;
+                }
+            }
+        }
+        let mut end = start_of_name;
+        while end < chars.len() && is_tag_char(chars[end]) {
+            end += 1;
+        }
+        if end > at {
+            tokens.push((String::new(), chars[at..end].iter().collect()));
+            at = end;
+        } else if c.is_whitespace() {
+            while at < chars.len() && chars[at].is_whitespace() {
+                at += 1;
+            }
+            tokens.push((String::new(), String::new()));
+        } else {
+            at += 1;
+        }
+    }
+    tokens
+}
+
+/// A syntax error in a path, which is a plain `SyntaxError`.
+fn path_error(vm: &mut Vm, message: &str) -> ObjectRef {
+    let class = vm.exceptions.syntax_error.clone();
+    vm.new_error(&class, message.to_string())
+}
+
+/// The tokens of a path with namespace prefixes replaced by their URIs in
+/// braces, and tags without a prefix put in the default namespace, the
+/// one of the empty prefix.
+fn path_tokens(
+    vm: &mut Vm,
+    path: &str,
+    prefixes: &HashMap<String, String>,
+) -> PyResult<Vec<(String, String)>> {
+    let default = prefixes.get("").filter(|uri| !uri.is_empty());
+    let mut tokens = Vec::new();
+    let mut attribute = false;
+    for (operator, tag) in tokenize_path(path) {
+        if tag.is_empty() || tag.starts_with('{') {
+            attribute = operator == "@";
+            tokens.push((operator, tag));
+            continue;
+        }
+        if let Some(colon) = tag.find(':') {
+            let (prefix, name) = (&tag[..colon], &tag[colon + 1..]);
+            match prefixes.get(prefix) {
+                Some(uri) => tokens.push((operator, format!("{{{}}}{}", uri, name))),
+                None => {
+                    let message = format!("prefix {} not found in prefix map", repr_str(prefix));
+                    return Err(path_error(vm, &message));
+                }
+            }
+        } else {
+            match default {
+                Some(uri) if !attribute => {
+                    tokens.push((operator, format!("{{{}}}{}", uri, tag)));
+                }
+                _ => tokens.push((operator, tag)),
+            }
+        }
+        attribute = false;
+    }
+    Ok(tokens)
+}
+
+/// A test of tags with a wildcard in them.
+enum Wildcard {
+    /// `{*}*`: any tag that is a string, unlike `*`, which takes comments
+    /// and processing instructions as well.
+    AnyTag,
+    /// `{}*`: any tag without a namespace.
+    NoNamespace,
+    /// `{*}name`: the name in any namespace or none.
+    AnyNamespace { name: String, suffix: String },
+    /// `{uri}*`: any tag in the namespace, which is kept as `{uri}`.
+    InNamespace(String),
+}
+
+impl Wildcard {
+    fn of(tag: &str) -> Option<Wildcard> {
+        if tag == "{*}*" {
+            Some(Wildcard::AnyTag)
+        } else if tag == "{}*" {
+            Some(Wildcard::NoNamespace)
+        } else if let Some(name) = tag.strip_prefix("{*}") {
+            Some(Wildcard::AnyNamespace {
+                name: name.to_string(),
+                suffix: tag[2..].to_string(),
+            })
+        } else {
+            tag.strip_suffix('*')
+                .filter(|namespace| namespace.ends_with('}'))
+                .map(|namespace| Wildcard::InNamespace(namespace.to_string()))
+        }
+    }
+
+    fn matches(&self, tag: &ObjectRef) -> bool {
+        let tag = match tag.as_str() {
+            Some(tag) => tag,
+            None => return false,
+        };
+        match *self {
+            Wildcard::AnyTag => true,
+            Wildcard::NoNamespace => !tag.starts_with('{'),
+            Wildcard::AnyNamespace {
+                ref name,
+                ref suffix,
+            } => tag == name || tag.ends_with(suffix.as_str()),
+            Wildcard::InNamespace(ref namespace) => tag.starts_with(namespace.as_str()),
+        }
+    }
+}
+
+/// A step of a compiled path, which selects elements from those the steps
+/// before it selected.
+enum Step {
+    /// The children with the tag.
+    Child(String),
+    /// The children whose tags match the wildcard.
+    ChildMatching(Wildcard),
+    /// `*`: all the children.
+    AllChildren,
+    /// `.`: the elements themselves.
+    Same,
+    /// `//tag`: the descendants with the tag, or all of them for `*`.
+    Descendant(String),
+    DescendantMatching(Wildcard),
+    /// `..`: the parents, each once.
+    Parent,
+    /// `[@key]`.
+    HasAttribute(String),
+    /// `[@key='value']`, or `[@key!='value']`.
+    AttributeEquals {
+        key: String,
+        value: String,
+        negated: bool,
+    },
+    /// `[tag]`: those with a child with the tag.
+    HasChild(String),
+    /// `[tag='text']`, or `[.='text']` with an empty tag, or with `!=`.
+    TextEquals {
+        tag: String,
+        value: String,
+        negated: bool,
+    },
+    /// `[index]`, `[last()]` or `[last()-offset]`: those at the position
+    /// among their siblings with the same tag, from 0, or from the end if
+    /// negative.
+    Position(i64),
+    /// What ElementPath's selector is for a path that ends in the middle
+    /// of a step: `None`, which fails when it is called.
+    Missing,
+}
+
+/// The steps of a path, as ElementPath compiles them.
+fn compile_path(
+    vm: &mut Vm,
+    path: &str,
+    prefixes: &HashMap<String, String>,
+) -> PyResult<Vec<Step>> {
+    let path = if path.ends_with('/') {
+        format!("{}*", path)
+    } else {
+        path.to_string()
+    };
+    if path.starts_with('/') {
+        return Err(path_error(vm, "cannot use absolute path on element"));
+    }
+    let mut tokens = path_tokens(vm, &path, prefixes)?.into_iter();
+    let mut token = match tokens.next() {
+        Some(token) => token,
+        None => return Ok(Vec::new()),
+    };
+    let mut steps = Vec::new();
+    loop {
+        let step = match token.0.as_str() {
+            "" => match Wildcard::of(&token.1) {
+                Some(wildcard) => Step::ChildMatching(wildcard),
+                None => Step::Child(token.1.strip_prefix("{}").unwrap_or(&token.1).to_string()),
+            },
+            "*" => Step::AllChildren,
+            "." => Step::Same,
+            ".." => Step::Parent,
+            "//" => descendant_step(vm, &mut tokens)?,
+            "[" => predicate_step(vm, &mut tokens)?,
+            operator => {
+                let key = vm.new_str(operator);
+                return Err(vm.new_key_error(key));
+            }
+        };
+        steps.push(step);
+        token = match tokens.next() {
+            Some(ref next) if next.0 == "/" => match tokens.next() {
+                Some(next) => next,
+                None => break,
+            },
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(steps)
+}
+
+fn descendant_step<I>(vm: &mut Vm, tokens: &mut I) -> PyResult<Step>
+where
+    I: Iterator<Item = (String, String)>,
+{
+    let tag = match tokens.next() {
+        None => return Ok(Step::Missing),
+        Some((ref operator, _)) if operator == "*" => "*".to_string(),
+        Some((ref operator, ref tag)) if operator.is_empty() => tag.clone(),
+        Some(_) => return Err(path_error(vm, "invalid descendant")),
+    };
+    Ok(match Wildcard::of(&tag) {
+        Some(wildcard) => Step::DescendantMatching(wildcard),
+        None => Step::Descendant(tag.strip_prefix("{}").unwrap_or(&tag).to_string()),
+    })
+}
+
+/// Whether a predicate is a number, possibly negative.
+fn is_integer(text: &str) -> bool {
+    let digits = text.strip_prefix('-').unwrap_or(text);
+    !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit())
+}
+
+/// The step of a predicate, told apart by its signature: its operators,
+/// with `-` for tags and `'` for strings.
+fn predicate_step<I>(vm: &mut Vm, tokens: &mut I) -> PyResult<Step>
+where
+    I: Iterator<Item = (String, String)>,
+{
+    let mut signature = String::new();
+    let mut predicate = Vec::new();
+    loop {
+        let (operator, tag) = match tokens.next() {
+            Some(token) => token,
+            None => return Ok(Step::Missing),
+        };
+        if operator == "]" {
+            break;
+        }
+        if operator.is_empty() && tag.is_empty() {
+            continue;
+        }
+        if operator.starts_with('\'') || operator.starts_with('"') {
+            signature.push('\'');
+            predicate.push(operator[1..operator.len() - 1].to_string());
+        } else if operator.is_empty() {
+            signature.push('-');
+            predicate.push(tag);
+        } else {
+            signature.push_str(&operator);
+            predicate.push(tag);
+        }
+    }
+    let negated = signature.contains("!=");
+    match signature.as_str() {
+        "@-" => return Ok(Step::HasAttribute(predicate.swap_remove(1))),
+        "@-='" | "@-!='" => {
+            return Ok(Step::AttributeEquals {
+                key: predicate[1].clone(),
+                value: predicate.pop().unwrap(),
+                negated,
+            });
+        }
+        "-" if !is_integer(&predicate[0]) => return Ok(Step::HasChild(predicate.swap_remove(0))),
+        ".='" | ".!='" => {
+            return Ok(Step::TextEquals {
+                tag: String::new(),
+                value: predicate.pop().unwrap(),
+                negated,
+            });
+        }
+        "-='" | "-!='" if !is_integer(&predicate[0]) => {
+            return Ok(Step::TextEquals {
+                tag: predicate[0].clone(),
+                value: predicate.pop().unwrap(),
+                negated,
+            });
+        }
+        _ => {}
+    }
+    let index = match signature.as_str() {
+        "-" => {
+            let index = predicate[0].parse::<i64>().unwrap_or(0) - 1;
+            if index < 0 {
+                return Err(path_error(vm, "XPath position >= 1 expected"));
+            }
+            index
+        }
+        "-()" | "-()-" => {
+            if predicate[0] != "last" {
+                return Err(path_error(vm, "unsupported function"));
+            }
+            if signature == "-()-" {
+                let index = match predicate[2].parse::<i64>() {
+                    Ok(offset) => offset - 1,
+                    Err(_) => return Err(path_error(vm, "unsupported expression")),
+                };
+                if index > -2 {
+                    return Err(path_error(vm, "XPath offset from last() must be negative"));
+                }
+                index
+            } else {
+                -1
+            }
+        }
+        _ => return Err(path_error(vm, "invalid predicate")),
+    };
+    Ok(Step::Position(index))
+}
+
+/// The parent of each element under `root`, by the identity of the
+/// element.
+fn parent_map(vm: &mut Vm, root: &ObjectRef) -> PyResult<HashMap<usize, ObjectRef>> {
+    let mut parents = HashMap::new();
+    for parent in descendants(vm, root, None)? {
+        for child in children(vm, &parent)? {
+            parents.insert(object_id(&child), parent.clone());
+        }
+    }
+    Ok(parents)
+}
+
+/// Runs the steps of a path from `root`.
+fn select(vm: &mut Vm, root: &ObjectRef, steps: &[Step]) -> PyResult<Vec<ObjectRef>> {
+    let mut parents = None;
+    let mut result = vec![root.clone()];
+    for step in steps {
+        let mut selected = Vec::new();
+        match *step {
+            Step::Child(ref tag) => {
+                for element in &result {
+                    for child in children(vm, element)? {
+                        if has_tag(vm, &child, tag)? {
+                            selected.push(child);
+                        }
+                    }
+                }
+            }
+            Step::ChildMatching(ref wildcard) => {
+                for element in &result {
+                    for child in children(vm, element)? {
+                        let tag = vm.getattr(&child, "tag")?;
+                        if wildcard.matches(&tag) {
+                            selected.push(child);
+                        }
+                    }
+                }
+            }
+            Step::AllChildren => {
+                for element in &result {
+                    selected.extend(children(vm, element)?);
+                }
+            }
+            Step::Same => selected = result,
+            Step::Descendant(ref tag) => {
+                let tag = vm.new_str(tag);
+                for element in &result {
+                    for descendant in descendants(vm, element, Some(&tag))? {
+                        if !Vm::is(&descendant, element) {
+                            selected.push(descendant);
+                        }
+                    }
+                }
+            }
+            Step::DescendantMatching(ref wildcard) => {
+                for element in &result {
+                    for descendant in descendants(vm, element, None)? {
+                        let tag = vm.getattr(&descendant, "tag")?;
+                        if !Vm::is(&descendant, element) && wildcard.matches(&tag) {
+                            selected.push(descendant);
+                        }
+                    }
+                }
+            }
+            Step::Parent => {
+                if parents.is_none() {
+                    parents = Some(parent_map(vm, root)?);
+                }
+                let parents = parents.as_ref().unwrap();
+                for element in &result {
+                    if let Some(parent) = parents.get(&object_id(element)) {
+                        if !selected.iter().any(|known| Vm::is(known, parent)) {
+                            selected.push(parent.clone());
+                        }
+                    }
+                }
+            }
+            Step::HasAttribute(ref key) => {
+                for element in result {
+                    let value = attribute(vm, &element, key)?;
+                    if value.is_some_and(|value| !Vm::is(&value, &vm.none())) {
+                        selected.push(element);
+                    }
+                }
+            }
+            Step::AttributeEquals {
+                ref key,
+                ref value,
+                negated,
+            } => {
+                for element in result {
+                    let actual = attribute(vm, &element, key)?;
+                    let keep = match actual {
+                        Some(ref actual) if Vm::is(actual, &vm.none()) => false,
+                        Some(ref actual) => (actual.as_str() == Some(value)) != negated,
+                        None => false,
+                    };
+                    if keep {
+                        selected.push(element);
+                    }
+                }
+            }
+            Step::HasChild(ref tag) => {
+                let path = vm.new_str(tag);
+                for element in result {
+                    if !select_path(vm, &element, &path, None)?.is_empty() {
+                        selected.push(element);
+                    }
+                }
+            }
+            Step::TextEquals {
+                ref tag,
+                ref value,
+                negated,
+            } => {
+                let path = vm.new_str(tag);
+                for element in result {
+                    let candidates = if tag.is_empty() {
+                        vec![element.clone()]
+                    } else {
+                        select_path(vm, &element, &path, None)?
+                    };
+                    for candidate in candidates {
+                        if (text_of(vm, &candidate)? == *value) != negated {
+                            selected.push(element);
+                            break;
+                        }
+                    }
+                }
+            }
+            Step::Position(index) => {
+                if parents.is_none() {
+                    parents = Some(parent_map(vm, root)?);
+                }
+                let parents = parents.as_ref().unwrap();
+                for element in result {
+                    let parent = match parents.get(&object_id(&element)) {
+                        Some(parent) => parent.clone(),
+                        None => continue,
+                    };
+                    let tag = vm.getattr(&element, "tag")?;
+                    if tag.as_str().is_none() {
+                        continue;
+                    }
+                    let siblings = select_path(vm, &parent, &tag, None)?;
+                    let position = if index < 0 {
+                        index + siblings.len() as i64
+                    } else {
+                        index
+                    };
+                    let same = usize::try_from(position)
+                        .ok()
+                        .and_then(|position| siblings.get(position))
+                        .is_some_and(|sibling| Vm::is(sibling, &element));
+                    if same {
+                        selected.push(element);
+                    }
+                }
+            }
+            Step::Missing => {
+                let message = "'NoneType' object is not callable".to_string();
+                return Err(vm.new_type_error(message));
+            }
+        }
+        result = selected;
+    }
+    Ok(result)
+}
+
+// The functions of the module.
+
+/// `Comment(text=None)`: an element that is written as a comment.
+fn comment(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "Comment", &["text"], 0)?;
+    let tag = class(vm, "Comment")?;
+    let element = class(vm, "Element")?;
+    let attrib = vm.new_dict();
+    let comment = new_element(vm, &element, tag, attrib)?;
+    let text = values[0].clone().unwrap_or_else(|| vm.none());
+    vm.setattr(&comment, "text", text)?;
+    Ok(comment)
+}
+
+/// `ProcessingInstruction(target, text=None)`: an element that is written
+/// as a processing instruction.
+fn processing_instruction(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "ProcessingInstruction", &["target", "text"], 1)?;
+    let tag = class(vm, "ProcessingInstruction")?;
+    let element = class(vm, "Element")?;
+    let attrib = vm.new_dict();
+    let instruction = new_element(vm, &element, tag, attrib)?;
+    let mut text = values[0].clone().unwrap();
+    if let Some(ref extra) = values[1] {
+        if vm.is_true(extra)? {
+            let joined = format!("{} {}", vm.str(&text)?, vm.str(extra)?);
+            text = vm.new_str(&joined);
+        }
+    }
+    vm.setattr(&instruction, "text", text)?;
+    Ok(instruction)
+}
+
+/// `SubElement(parent, tag, attrib={}, **extra)`: a new element appended
+/// to `parent`.
+fn sub_element(vm: &mut Vm, args: Args) -> PyResult {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    if positional.len() < 2 || positional.len() > 3 {
+        let message = format!(
+            "SubElement expected {} arguments, got {}",
+            if positional.len() < 2 {
+                "at least 2"
+            } else {
+                "at most 3"
+            },
+            positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let parent = &positional[0];
+    check_element(vm, parent, "SubElement", "1 ")?;
+    let attrib = vm.new_dict();
+    if let Some(given) = positional.get(2) {
+        if given.as_dict().is_none() {
+            let message = format!(
+                "SubElement() argument 3 must be dict, not {}",
+                given.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+        vm.dict_update(attrib.as_dict().unwrap(), given)?;
+    }
+    for (key, value) in keywords {
+        vm.dict_set_str(attrib.as_dict().unwrap(), &key, value);
+    }
+    let class = class(vm, "Element")?;
+    let element = new_element(vm, &class, positional[1].clone(), attrib)?;
+    push_child(vm, parent, element.clone())?;
+    Ok(element)
+}
+
+fn iselement(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "iselement", &["element"], 1)?;
+    let result = vm.getattr(values[0].as_ref().unwrap(), "tag").is_ok();
+    Ok(vm.new_bool(result))
+}
+
+/// `fromstring(text, parser=None)`, also `XML`: the root element of a
+/// document in a string or in bytes.
+fn fromstring(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "XML", &["text", "parser"], 1)?;
+    parse_document(vm, values[0].as_ref().unwrap())
+}
+
+/// `fromstringlist(sequence, parser=None)`: the root element of a document
+/// given in parts.
+fn fromstringlist(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "fromstringlist", &["sequence", "parser"], 1)?;
+    let parts = vm.iterate(values[0].as_ref().unwrap())?;
+    let document = join_parts(vm, parts)?;
+    parse_document(vm, &document)
+}
+
+/// Parts of a document, all strings or all bytes, joined into one.
+fn join_parts(vm: &mut Vm, parts: Vec<ObjectRef>) -> PyResult {
+    if parts.iter().all(|part| part.as_str().is_some()) {
+        let text: String = parts.iter().map(|part| part.as_str().unwrap()).collect();
+        return Ok(vm.new_str(&text));
+    }
+    let mut data = Vec::new();
+    for part in &parts {
+        match part.payload {
+            Payload::Bytes(ref bytes) => data.extend_from_slice(bytes),
+            _ => {
+                let message = format!(
+                    "a bytes-like object is required, not '{}'",
+                    part.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        }
+    }
+    Ok(vm.new_bytes(data))
+}
+
+/// The root element of the document in a file, given by name or as an
+/// object with a `read` method.
+fn parse_source(vm: &mut Vm, source: &ObjectRef) -> PyResult {
+    if let Ok(read) = vm.getattr(source, "read") {
+        let mut parts = Vec::new();
+        loop {
+            let size = vm.new_int(64 * 1024);
+            let part = vm.call(&read, Args::new(vec![size]))?;
+            if !vm.is_true(&part)? {
+                break;
+            }
+            parts.push(part);
+        }
+        let document = join_parts(vm, parts)?;
+        return parse_document(vm, &document);
+    }
+    let filename = match source.as_str() {
+        Some(filename) => filename.to_string(),
+        None => {
+            let message = format!(
+                "expected str, bytes or os.PathLike object, not {}",
+                source.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    match fs::read(&filename) {
+        Ok(data) => {
+            let data = vm.new_bytes(data);
+            parse_document(vm, &data)
+        }
+        Err(error) => Err(os_error(vm, &error, &filename)),
+    }
+}
+
+/// `parse(source, parser=None)`: an `ElementTree` of the document in a
+/// file.
+fn parse(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "parse", &["source", "parser"], 1)?;
+    let class = class(vm, "ElementTree")?;
+    let tree = vm.call(&class, Args::default())?;
+    let parse = vm.getattr(&tree, "parse")?;
+    vm.call(&parse, Args::new(vec![values[0].clone().unwrap()]))?;
+    Ok(tree)
+}
+
+/// `indent(tree, space="  ", level=0)`: indents the elements under the
+/// root of a tree or an element by setting the whitespace of their text
+/// and tails.
+fn indent(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "indent", &["tree", "space", "level"], 1)?;
+    let mut tree = values[0].clone().unwrap();
+    let tree_class = class(vm, "ElementTree")?;
+    if Vm::is_instance(&tree, &tree_class) {
+        let getroot = vm.getattr(&tree, "getroot")?;
+        tree = vm.call(&getroot, Args::default())?;
+    }
+    let space = match values[1] {
+        Some(ref space) => vm.str(space)?,
+        None => "  ".to_string(),
+    };
+    let level = match values[2] {
+        Some(ref level) => index_value(vm, level)?,
+        None => 0,
+    };
+    if level < 0 {
+        let message = format!("Initial indentation level must be >= 0, got {}", level);
+        return Err(vm.new_value_error(message));
+    }
+    if children(vm, &tree)?.is_empty() {
+        return Ok(vm.none());
+    }
+    let indentation = format!("\n{}", space.repeat(level as usize));
+    indent_children(vm, &tree, &indentation, &space)?;
+    Ok(vm.none())
+}
+
+/// Whether a text or tail is missing or only whitespace, which indenting
+/// replaces.
+fn is_blank(text: &ObjectRef) -> bool {
+    match text.as_str() {
+        Some(text) => text.trim().is_empty(),
+        None => text.as_dict().is_none() && text.type_name() == "NoneType",
+    }
+}
+
+fn indent_children(
+    vm: &mut Vm,
+    element: &ObjectRef,
+    indentation: &str,
+    space: &str,
+) -> PyResult<()> {
+    let child_indentation = format!("{}{}", indentation, space);
+    let text = vm.getattr(element, "text")?;
+    if is_blank(&text) {
+        let text = vm.new_str(&child_indentation);
+        vm.setattr(element, "text", text)?;
+    }
+    let children = children(vm, element)?;
+    for child in &children {
+        if !self::children(vm, child)?.is_empty() {
+            indent_children(vm, child, &child_indentation, space)?;
+        }
+        let tail = vm.getattr(child, "tail")?;
+        if is_blank(&tail) {
+            let tail = vm.new_str(&child_indentation);
+            vm.setattr(child, "tail", tail)?;
+        }
+    }
+    // The last child's tail goes back to the level of the element.
+    if let Some(last) = children.last() {
+        let tail = vm.getattr(last, "tail")?;
+        if is_blank(&tail) {
+            let tail = vm.new_str(indentation);
+            vm.setattr(last, "tail", tail)?;
+        }
+    }
+    Ok(())
+}
+
+/// `register_namespace(prefix, uri)`: the prefix to write the namespace
+/// with, in place of any other for it or any other namespace with it.
+fn register_namespace(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "register_namespace", &["prefix", "uri"], 2)?;
+    let (prefix, uri) = (values[0].clone().unwrap(), values[1].clone().unwrap());
+    if let Some(number) = prefix.as_str().and_then(|prefix| prefix.strip_prefix("ns")) {
+        if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+            let message = "Prefix format reserved for internal use".to_string();
+            return Err(vm.new_value_error(message));
+        }
+    }
+    let map = class(vm, "_namespace_map")?;
+    let entries: Vec<(ObjectRef, ObjectRef)> = match map.as_dict() {
+        Some(dict) => dict
+            .borrow()
+            .iter()
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect(),
+        None => Vec::new(),
+    };
+    for (key, value) in entries {
+        if vm.eq(&key, &uri)? || vm.eq(&value, &prefix)? {
+            vm.delitem(&map, &key)?;
+        }
+    }
+    vm.setitem(&map, &uri, prefix)?;
+    Ok(vm.none())
+}
+
+// Serializing.
+
+/// How to write a tree, from the arguments of `write` and `tostring`.
+struct WriteOptions {
+    encoding: String,
+    xml_declaration: Option<ObjectRef>,
+    default_namespace: Option<String>,
+    method: String,
+    short_empty_elements: bool,
+}
+
+impl WriteOptions {
+    /// The options from the values of `encoding`, `xml_declaration`,
+    /// `default_namespace`, `method` and `short_empty_elements`.
+    fn new(vm: &mut Vm, values: &[Option<ObjectRef>]) -> PyResult<WriteOptions> {
+        let none = vm.none();
+        let given = |index: usize| values[index].clone().filter(|value| !Vm::is(value, &none));
+        let method = match given(3) {
+            Some(method) => match method.as_str() {
+                Some("") => "xml".to_string(),
+                Some(name @ "xml") | Some(name @ "html") | Some(name @ "text") => name.to_string(),
+                _ => {
+                    let message = format!("unknown method {}", vm.repr(&method)?);
+                    return Err(vm.new_value_error(message));
+                }
+            },
+            None => "xml".to_string(),
+        };
+        let encoding = match given(0) {
+            Some(encoding) => vm.str(&encoding)?,
+            None => String::new(),
+        };
+        let encoding = if encoding.is_empty() {
+            "us-ascii".to_string()
+        } else {
+            encoding
+        };
+        let default_namespace = match given(2) {
+            Some(namespace) => Some(vm.str(&namespace)?).filter(|namespace| !namespace.is_empty()),
+            None => None,
+        };
+        let short_empty_elements = match values[4] {
+            Some(ref short) => vm.is_true(short)?,
+            None => true,
+        };
+        Ok(WriteOptions {
+            encoding,
+            xml_declaration: given(1),
+            default_namespace,
+            method,
+            short_empty_elements,
+        })
+    }
+
+    fn is_unicode(&self) -> bool {
+        self.encoding.eq_ignore_ascii_case("unicode")
+    }
+}
+
+/// `text` encoded as `encoding`, with the characters it cannot encode
+/// written as character references. `None` for an unknown encoding.
+fn encode(text: &str, encoding: &str) -> Option<Vec<u8>> {
+    let name = encoding.to_ascii_lowercase().replace('_', "-");
+    let limit = match name.as_str() {
+        "utf-8" | "utf8" | "u8" => return Some(text.as_bytes().to_vec()),
+        "us-ascii" | "ascii" | "646" => 0x7f,
+        "latin-1" | "latin1" | "iso-8859-1" | "iso8859-1" | "l1" => 0xff,
+        _ => return None,
+    };
+    let mut data = Vec::with_capacity(text.len());
+    for c in text.chars() {
+        if (c as u32) <= limit {
+            data.push(c as u8);
+        } else {
+            data.extend_from_slice(format!("&#{};", c as u32).as_bytes());
+        }
+    }
+    Some(data)
+}
+
+fn unknown_encoding(vm: &mut Vm, encoding: &str) -> ObjectRef {
+    let class = vm.exceptions.lookup_error.clone();
+    vm.new_error(&class, format!("unknown encoding: {}", encoding))
+}
+
+fn serialization_error(vm: &mut Vm, value: &ObjectRef) -> PyResult<ObjectRef> {
+    let message = format!(
+        "cannot serialize {} (type {})",
+        vm.repr(value)?,
+        value.type_name()
+    );
+    Ok(vm.new_type_error(message))
+}
+
+/// Text with the characters XML gives meaning to escaped, for element
+/// content or, with `attribute`, for attribute values.
+fn escape(vm: &mut Vm, text: &ObjectRef, attribute: bool, html: bool) -> PyResult<String> {
+    let text = match text.as_str() {
+        Some(text) => text,
+        None => return Err(serialization_error(vm, text)?),
+    };
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' if !(attribute && html) => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' if attribute => escaped.push_str("&quot;"),
+            '\r' if attribute && !html => escaped.push_str("&#13;"),
+            '\n' if attribute && !html => escaped.push_str("&#10;"),
+            '\t' if attribute && !html => escaped.push_str("&#09;"),
+            _ => escaped.push(c),
+        }
+    }
+    Ok(escaped)
+}
+
+/// The names that tags and attribute keys are written as, and the
+/// namespaces to declare on the root, as `(uri, prefix)`.
+struct QualifiedNames {
+    names: HashMap<String, String>,
+    namespaces: Vec<(String, String)>,
+}
+
+impl QualifiedNames {
+    /// Adds the name to write `qname` as, giving its namespace a prefix if
+    /// it is the first name in it.
+    fn add(
+        &mut self,
+        vm: &mut Vm,
+        qname: &str,
+        prefixes: &[(String, String)],
+        default_namespace: Option<&str>,
+    ) -> PyResult<()> {
+        let name = match qname.strip_prefix('{') {
+            Some(rest) => {
+                let close = match rest.rfind('}') {
+                    Some(close) => close,
+                    None => {
+                        let message = "not enough values to unpack (expected 2, got 1)";
+                        return Err(vm.new_value_error(message.to_string()));
+                    }
+                };
+                let (uri, tag) = (&rest[..close], &rest[close + 1..]);
+                let known = self
+                    .namespaces
+                    .iter()
+                    .find(|(known, _)| known == uri)
+                    .map(|(_, prefix)| prefix.clone());
+                let prefix = match known {
+                    Some(prefix) => prefix,
+                    None => {
+                        let prefix = prefixes
+                            .iter()
+                            .find(|(known, _)| known == uri)
+                            .map(|(_, prefix)| prefix.clone())
+                            .unwrap_or_else(|| format!("ns{}", self.namespaces.len()));
+                        if prefix != "xml" {
+                            self.namespaces.push((uri.to_string(), prefix.clone()));
+                        }
+                        prefix
+                    }
+                };
+                if prefix.is_empty() {
+                    tag.to_string()
+                } else {
+                    format!("{}:{}", prefix, tag)
+                }
+            }
+            None => {
+                if default_namespace.is_some() {
+                    let message = "cannot use non-qualified names with default_namespace option";
+                    return Err(vm.new_value_error(message.to_string()));
+                }
+                qname.to_string()
+            }
+        };
+        self.names.insert(qname.to_string(), name);
+        Ok(())
+    }
+
+    /// The names in a tree, as ElementTree's `_namespaces` finds them.
+    fn of(
+        vm: &mut Vm,
+        root: &ObjectRef,
+        default_namespace: Option<&str>,
+    ) -> PyResult<QualifiedNames> {
+        let mut names = QualifiedNames {
+            names: HashMap::new(),
+            namespaces: Vec::new(),
+        };
+        if let Some(namespace) = default_namespace {
+            names
+                .namespaces
+                .push((namespace.to_string(), String::new()));
+        }
+        let prefixes = namespace_prefixes(vm)?;
+        let comment = class(vm, "Comment")?;
+        let instruction = class(vm, "ProcessingInstruction")?;
+        for element in descendants(vm, root, None)? {
+            let tag = vm.getattr(&element, "tag")?;
+            match tag.as_str() {
+                Some(tag) => {
+                    if !names.names.contains_key(tag) {
+                        names.add(vm, tag, &prefixes, default_namespace)?;
+                    }
+                }
+                None => {
+                    let special = Vm::is(&tag, &vm.none())
+                        || Vm::is(&tag, &comment)
+                        || Vm::is(&tag, &instruction);
+                    if !special {
+                        return Err(serialization_error(vm, &tag)?);
+                    }
+                }
+            }
+            for (key, _) in attributes(vm, &element)? {
+                match key.as_str() {
+                    Some(key) => {
+                        if !names.names.contains_key(key) {
+                            names.add(vm, key, &prefixes, default_namespace)?;
+                        }
+                    }
+                    None => return Err(serialization_error(vm, &key)?),
+                }
+            }
+        }
+        Ok(names)
+    }
+}
+
+/// The prefixes of `register_namespace`, as `(uri, prefix)`.
+fn namespace_prefixes(vm: &mut Vm) -> PyResult<Vec<(String, String)>> {
+    let map = class(vm, "_namespace_map")?;
+    let mut prefixes = Vec::new();
+    if let Some(dict) = map.as_dict() {
+        for entry in dict.borrow().iter() {
+            if let (Some(uri), Some(prefix)) = (entry.key.as_str(), entry.value.as_str()) {
+                prefixes.push((uri.to_string(), prefix.to_string()));
+            }
+        }
+    }
+    Ok(prefixes)
+}
+
+/// What a serializer needs besides the element it writes.
+struct Serializer<'a> {
+    names: &'a QualifiedNames,
+    comment: ObjectRef,
+    instruction: ObjectRef,
+    html: bool,
+    short_empty_elements: bool,
+}
+
+impl<'a> Serializer<'a> {
+    /// Writes an element, its content and its tail, as XML or HTML. The
+    /// root declares the namespaces.
+    fn element(
+        &self,
+        vm: &mut Vm,
+        out: &mut String,
+        element: &ObjectRef,
+        root: bool,
+    ) -> PyResult<()> {
+        let tag = vm.getattr(element, "tag")?;
+        let text = vm.getattr(element, "text")?;
+        if Vm::is(&tag, &self.comment) || Vm::is(&tag, &self.instruction) {
+            let (open, close) = if Vm::is(&tag, &self.comment) {
+                ("<!--", "-->")
+            } else {
+                ("<?", "?>")
+            };
+            out.push_str(open);
+            if self.html {
+                out.push_str(&escape(vm, &text, false, false)?);
+            } else {
+                out.push_str(&vm.str(&text)?);
+            }
+            out.push_str(close);
+        } else if let Some(tag) = tag.as_str() {
+            let name = &self.names.names[tag];
+            out.push('<');
+            out.push_str(name);
+            if root {
+                let mut namespaces = self.names.namespaces.clone();
+                namespaces.sort_by(|a, b| a.1.cmp(&b.1));
+                for (uri, prefix) in namespaces {
+                    let uri = vm.new_str(&uri);
+                    let separator = if prefix.is_empty() { "" } else { ":" };
+                    let uri = escape(vm, &uri, true, false)?;
+                    out.push_str(&format!(" xmlns{}{}=\"{}\"", separator, prefix, uri));
+                }
+            }
+            for (key, value) in attributes(vm, element)? {
+                let key = &self.names.names[key.as_str().unwrap()];
+                let value = escape(vm, &value, true, self.html)?;
+                out.push_str(&format!(" {}=\"{}\"", key, value));
+            }
+            let children = children(vm, element)?;
+            let has_text = vm.is_true(&text)?;
+            if self.html {
+                out.push('>');
+                let lowered = name.to_lowercase();
+                if has_text {
+                    if lowered == "script" || lowered == "style" {
+                        out.push_str(&vm.str(&text)?);
+                    } else {
+                        out.push_str(&escape(vm, &text, false, true)?);
+                    }
+                }
+                for child in &children {
+                    self.element(vm, out, child, false)?;
+                }
+                if !HTML_EMPTY.contains(&lowered.as_str()) {
+                    out.push_str(&format!("</{}>", name));
+                }
+            } else if has_text || !children.is_empty() || !self.short_empty_elements {
+                out.push('>');
+                if has_text {
+                    out.push_str(&escape(vm, &text, false, false)?);
+                }
+                for child in &children {
+                    self.element(vm, out, child, false)?;
+                }
+                out.push_str(&format!("</{}>", name));
+            } else {
+                out.push_str(" />");
+            }
+        } else {
+            // An element without a tag writes only its content.
+            if vm.is_true(&text)? {
+                out.push_str(&escape(vm, &text, false, self.html)?);
+            }
+            for child in children(vm, element)? {
+                self.element(vm, out, &child, false)?;
+            }
+        }
+        let tail = vm.getattr(element, "tail")?;
+        if vm.is_true(&tail)? {
+            out.push_str(&escape(vm, &tail, false, self.html)?);
+        }
+        Ok(())
+    }
+}
+
+/// A tree written with `options`, with an XML declaration naming
+/// `declared_encoding` if one is asked for or the encoding needs one.
+fn serialize(
+    vm: &mut Vm,
+    root: &ObjectRef,
+    options: &WriteOptions,
+    declared_encoding: &str,
+) -> PyResult<String> {
+    let mut out = String::new();
+    let declare = match options.xml_declaration {
+        Some(ref declaration) => vm.is_true(declaration)?,
+        None => {
+            let declared = declared_encoding.to_ascii_lowercase();
+            !options.is_unicode() && declared != "utf-8" && declared != "us-ascii"
+        }
+    };
+    if options.method == "xml" && declare {
+        out.push_str(&format!(
+            "<?xml version='1.0' encoding='{}'?>\n",
+            declared_encoding
+        ));
+    }
+    if options.method == "text" {
+        out.push_str(&text_of(vm, root)?);
+        let tail = vm.getattr(root, "tail")?;
+        if vm.is_true(&tail)? {
+            out.push_str(&vm.str(&tail)?);
+        }
+        return Ok(out);
+    }
+    let names = QualifiedNames::of(vm, root, options.default_namespace.as_deref())?;
+    let serializer = Serializer {
+        names: &names,
+        comment: class(vm, "Comment")?,
+        instruction: class(vm, "ProcessingInstruction")?,
+        html: options.method == "html",
+        short_empty_elements: options.short_empty_elements,
+    };
+    serializer.element(vm, &mut out, root, true)?;
+    Ok(out)
+}
+
+/// A tree written to `file`, a file name or an object with a `write`
+/// method, which gets a string for the encoding `unicode` and bytes for
+/// the others.
+fn write_tree(
+    vm: &mut Vm,
+    root: &ObjectRef,
+    file: &ObjectRef,
+    options: &WriteOptions,
+) -> PyResult<()> {
+    if let Ok(write) = vm.getattr(file, "write") {
+        let output = if options.is_unicode() {
+            let declared = match vm.getattr(file, "encoding") {
+                Ok(ref encoding) if vm.is_true(encoding)? => vm.str(encoding)?,
+                _ => "utf-8".to_string(),
+            };
+            let text = serialize(vm, root, options, &declared)?;
+            vm.new_str(&text)
+        } else {
+            if encode("", &options.encoding).is_none() {
+                return Err(unknown_encoding(vm, &options.encoding));
+            }
+            let text = serialize(vm, root, options, &options.encoding)?;
+            vm.new_bytes(encode(&text, &options.encoding).unwrap())
+        };
+        vm.call(&write, Args::new(vec![output]))?;
+        return Ok(());
+    }
+    let filename = match file.as_str() {
+        Some(filename) => filename.to_string(),
+        None => {
+            let message = format!(
+                "expected str, bytes or os.PathLike object, not {}",
+                file.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let encoding = if options.is_unicode() {
+        "utf-8".to_string()
+    } else {
+        options.encoding.clone()
+    };
+    if encode("", &encoding).is_none() {
+        return Err(unknown_encoding(vm, &encoding));
+    }
+    let text = serialize(vm, root, options, &encoding)?;
+    match fs::write(&filename, encode(&text, &encoding).unwrap()) {
+        Ok(()) => Ok(()),
+        Err(error) => Err(os_error(vm, &error, &filename)),
+    }
+}
+
+/// The parameters of `tostring` and `tostringlist`.
+const TOSTRING_PARAMETERS: [&str; 6] = [
+    "element",
+    "encoding",
+    "method",
+    "xml_declaration",
+    "default_namespace",
+    "short_empty_elements",
+];
+
+/// An element written as a string for the encoding `unicode`, and as
+/// bytes for the others.
+fn element_to_string(vm: &mut Vm, args: Args, function: &str) -> PyResult {
+    let values = bind_arguments(vm, args, function, &TOSTRING_PARAMETERS, 1)?;
+    let options = WriteOptions::new(
+        vm,
+        &[
+            values[1].clone(),
+            values[3].clone(),
+            values[4].clone(),
+            values[2].clone(),
+            values[5].clone(),
+        ],
+    )?;
+    let element = values[0].as_ref().unwrap();
+    if values[1].as_ref().and_then(|encoding| encoding.as_str()) == Some("unicode") {
+        let text = serialize(vm, element, &options, "utf-8")?;
+        return Ok(vm.new_str(&text));
+    }
+    match encode("", &options.encoding) {
+        Some(_) => {
+            let text = serialize(vm, element, &options, &options.encoding)?;
+            Ok(vm.new_bytes(encode(&text, &options.encoding).unwrap()))
+        }
+        None => Err(unknown_encoding(vm, &options.encoding)),
+    }
+}
+
+/// `tostring(element, encoding="us-ascii", method="xml", *,
+/// xml_declaration=None, default_namespace=None,
+/// short_empty_elements=True)`.
+fn tostring(vm: &mut Vm, args: Args) -> PyResult {
+    element_to_string(vm, args, "tostring")
+}
+
+/// `tostringlist(...)`: `tostring` as a list, of a single part.
+fn tostringlist(vm: &mut Vm, args: Args) -> PyResult {
+    let text = element_to_string(vm, args, "tostringlist")?;
+    Ok(vm.new_list(vec![text]))
+}
+
+/// `dump(elem)`: prints an element or tree, for debugging.
+fn dump(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "dump", &["elem"], 1)?;
+    let mut element = values[0].clone().unwrap();
+    let tree_class = class(vm, "ElementTree")?;
+    if Vm::is_instance(&element, &tree_class) {
+        let getroot = vm.getattr(&element, "getroot")?;
+        element = vm.call(&getroot, Args::default())?;
+    }
+    let unicode = vm.new_str("unicode");
+    let options = WriteOptions::new(vm, &[Some(unicode), None, None, None, None])?;
+    let mut text = serialize(vm, &element, &options, "utf-8")?;
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+    let print = vm.builtins().as_dict().unwrap().borrow().get_str("print");
+    if let Some(print) = print {
+        let text = vm.new_str(&text);
+        let end = vm.new_str("");
+        vm.call(
+            &print,
+            Args {
+                positional: vec![text],
+                keywords: vec![("end".to_string(), end)],
+            },
+        )?;
+    }
+    Ok(vm.none())
+}
+
+// ElementTree.
+
+fn tree_root(vm: &mut Vm, this: &ObjectRef) -> PyResult {
+    vm.getattr(this, "_root")
+}
+
+/// `ElementTree(element=None, file=None)`: a tree with the root `element`,
+/// or of the document in `file`.
+fn tree_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__init__", &["self", "element", "file"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let root = values[1].clone().unwrap_or_else(|| vm.none());
+    vm.setattr(this, "_root", root)?;
+    if let Some(ref file) = values[2] {
+        if !Vm::is(file, &vm.none()) {
+            let parse = vm.getattr(this, "parse")?;
+            vm.call(&parse, Args::new(vec![file.clone()]))?;
+        }
+    }
+    Ok(vm.none())
+}
+
+fn tree_getroot(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "getroot", &["self"], 1)?;
+    tree_root(vm, values[0].as_ref().unwrap())
+}
+
+fn tree_setroot(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "_setroot", &["self", "element"], 2)?;
+    vm.setattr(
+        values[0].as_ref().unwrap(),
+        "_root",
+        values[1].clone().unwrap(),
+    )?;
+    Ok(vm.none())
+}
+
+/// `tree.parse(source, parser=None)`: replaces the root with that of the
+/// document in `source`, and returns it.
+fn tree_parse(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "parse", &["self", "source", "parser"], 2)?;
+    let root = parse_source(vm, values[1].as_ref().unwrap())?;
+    vm.setattr(values[0].as_ref().unwrap(), "_root", root.clone())?;
+    Ok(root)
+}
+
+/// Calls the method `name` of the root with `args`.
+fn call_root(vm: &mut Vm, this: &ObjectRef, name: &str, args: Vec<ObjectRef>) -> PyResult {
+    let root = tree_root(vm, this)?;
+    let method = vm.getattr(&root, name)?;
+    vm.call(&method, Args::new(args))
+}
+
+fn tree_iter(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "iter", &["self", "tag"], 1)?;
+    let tag = values[1].clone().unwrap_or_else(|| vm.none());
+    call_root(vm, values[0].as_ref().unwrap(), "iter", vec![tag])
+}
+
+/// A path for the root to search: a path from the tree's root starts
+/// with `/`, and is searched relative to the root.
+fn tree_path(vm: &mut Vm, path: &ObjectRef) -> ObjectRef {
+    match path.as_str() {
+        Some(text) if text.starts_with('/') => vm.new_str(&format!(".{}", text)),
+        _ => path.clone(),
+    }
+}
+
+/// Calls a search method of the root with the values of `path`, then
+/// `default` for `findtext`, then `namespaces`.
+fn tree_search(vm: &mut Vm, args: Args, name: &str) -> PyResult {
+    let mut names = vec!["self", "path"];
+    if name == "findtext" {
+        names.push("default");
+    }
+    names.push("namespaces");
+    let values = bind_arguments(vm, args, name, &names, 2)?;
+    let path = tree_path(vm, values[1].as_ref().unwrap());
+    let mut arguments = vec![path];
+    for value in &values[2..] {
+        arguments.push(value.clone().unwrap_or_else(|| vm.none()));
+    }
+    call_root(vm, values[0].as_ref().unwrap(), name, arguments)
+}
+
+fn tree_find(vm: &mut Vm, args: Args) -> PyResult {
+    tree_search(vm, args, "find")
+}
+
+fn tree_findall(vm: &mut Vm, args: Args) -> PyResult {
+    tree_search(vm, args, "findall")
+}
+
+fn tree_findtext(vm: &mut Vm, args: Args) -> PyResult {
+    tree_search(vm, args, "findtext")
+}
+
+fn tree_iterfind(vm: &mut Vm, args: Args) -> PyResult {
+    tree_search(vm, args, "iterfind")
+}
+
+/// `tree.write(file_or_filename, encoding=None, xml_declaration=None,
+/// default_namespace=None, method=None, *, short_empty_elements=True)`.
+fn tree_write(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "self",
+        "file_or_filename",
+        "encoding",
+        "xml_declaration",
+        "default_namespace",
+        "method",
+        "short_empty_elements",
+    ];
+    let values = bind_arguments(vm, args, "write", &names, 2)?;
+    let options = WriteOptions::new(vm, &values[2..])?;
+    let root = tree_root(vm, values[0].as_ref().unwrap())?;
+    write_tree(vm, &root, values[1].as_ref().unwrap(), &options)?;
+    Ok(vm.none())
+}
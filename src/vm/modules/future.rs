@@ -0,0 +1,187 @@
+//! `__future__`: a record of each feature a future statement can turn on,
+//! with the releases that made it optional and mandatory and its compiler
+//! flag. The compiler reads the statements themselves, so importing from
+//! here only binds the records.
+
+use compiler::{CO_FUTURE_ANNOTATIONS, CO_FUTURE_BARRY_AS_BDFL, CO_NESTED};
+
+use super::super::object::{Args, ObjectRef, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, new_native_class, new_native_module};
+
+/// A release, as `sys.version_info` gives it.
+type Release = (i64, i64, i64, &'static str, i64);
+
+/// The compiler flags `__future__` names. Those of the features that are
+/// always on in Python 3 are kept as CPython keeps them.
+const FLAGS: [(&str, u32); 10] = [
+    ("CO_NESTED", CO_NESTED),
+    ("CO_GENERATOR_ALLOWED", 0),
+    ("CO_FUTURE_DIVISION", 0x2_0000),
+    ("CO_FUTURE_ABSOLUTE_IMPORT", 0x4_0000),
+    ("CO_FUTURE_WITH_STATEMENT", 0x8_0000),
+    ("CO_FUTURE_PRINT_FUNCTION", 0x10_0000),
+    ("CO_FUTURE_UNICODE_LITERALS", 0x20_0000),
+    ("CO_FUTURE_BARRY_AS_BDFL", CO_FUTURE_BARRY_AS_BDFL),
+    ("CO_FUTURE_GENERATOR_STOP", 0x80_0000),
+    ("CO_FUTURE_ANNOTATIONS", CO_FUTURE_ANNOTATIONS),
+];
+
+/// Each feature, with its optional and mandatory releases and the name of
+/// its flag.
+const FEATURES: [(&str, Release, Option<Release>, &str); 10] = [
+    (
+        "nested_scopes",
+        (2, 1, 0, "beta", 1),
+        Some((2, 2, 0, "alpha", 0)),
+        "CO_NESTED",
+    ),
+    (
+        "generators",
+        (2, 2, 0, "alpha", 1),
+        Some((2, 3, 0, "final", 0)),
+        "CO_GENERATOR_ALLOWED",
+    ),
+    (
+        "division",
+        (2, 2, 0, "alpha", 2),
+        Some((3, 0, 0, "alpha", 0)),
+        "CO_FUTURE_DIVISION",
+    ),
+    (
+        "absolute_import",
+        (2, 5, 0, "alpha", 1),
+        Some((3, 0, 0, "alpha", 0)),
+        "CO_FUTURE_ABSOLUTE_IMPORT",
+    ),
+    (
+        "with_statement",
+        (2, 5, 0, "alpha", 1),
+        Some((2, 6, 0, "alpha", 0)),
+        "CO_FUTURE_WITH_STATEMENT",
+    ),
+    (
+        "print_function",
+        (2, 6, 0, "alpha", 2),
+        Some((3, 0, 0, "alpha", 0)),
+        "CO_FUTURE_PRINT_FUNCTION",
+    ),
+    (
+        "unicode_literals",
+        (2, 6, 0, "alpha", 2),
+        Some((3, 0, 0, "alpha", 0)),
+        "CO_FUTURE_UNICODE_LITERALS",
+    ),
+    (
+        "barry_as_FLUFL",
+        (3, 1, 0, "alpha", 2),
+        Some((4, 0, 0, "alpha", 0)),
+        "CO_FUTURE_BARRY_AS_BDFL",
+    ),
+    (
+        "generator_stop",
+        (3, 5, 0, "beta", 1),
+        Some((3, 7, 0, "alpha", 0)),
+        "CO_FUTURE_GENERATOR_STOP",
+    ),
+    (
+        "annotations",
+        (3, 7, 0, "beta", 1),
+        None,
+        "CO_FUTURE_ANNOTATIONS",
+    ),
+];
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "__future__", &[]);
+    for &(name, flag) in FLAGS.iter() {
+        let flag = vm.new_int(i64::from(flag));
+        add_attribute(vm, &module, name, flag);
+    }
+    let object = vm.types.object.clone();
+    let feature = new_native_class(
+        vm,
+        "__future__",
+        "_Feature",
+        &object,
+        &[
+            ("__init__", feature_init),
+            ("__repr__", feature_repr),
+            ("getMandatoryRelease", get_mandatory_release),
+            ("getOptionalRelease", get_optional_release),
+        ],
+    )?;
+    add_attribute(vm, &module, "_Feature", feature.clone());
+    let mut names = Vec::with_capacity(FEATURES.len());
+    for &(name, optional, mandatory, flag) in FEATURES.iter() {
+        let optional = release(vm, optional);
+        let mandatory = match mandatory {
+            Some(mandatory) => release(vm, mandatory),
+            None => vm.none(),
+        };
+        let flag = FLAGS.iter().find(|&&(known, _)| known == flag).unwrap().1;
+        let flag = vm.new_int(i64::from(flag));
+        let value = vm.call(&feature, Args::new(vec![optional, mandatory, flag]))?;
+        add_attribute(vm, &module, name, value);
+        names.push(vm.new_str(name));
+    }
+    let names = vm.new_list(names);
+    add_attribute(vm, &module, "all_feature_names", names);
+    Ok(module)
+}
+
+fn release(vm: &mut Vm, (major, minor, micro, level, serial): Release) -> ObjectRef {
+    let items = vec![
+        vm.new_int(major),
+        vm.new_int(minor),
+        vm.new_int(micro),
+        vm.new_str(level),
+        vm.new_int(serial),
+    ];
+    vm.new_tuple(items)
+}
+
+/// `_Feature(optionalRelease, mandatoryRelease, compiler_flag)`.
+fn feature_init(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "self",
+        "optionalRelease",
+        "mandatoryRelease",
+        "compiler_flag",
+    ];
+    let values = bind_arguments(vm, args, "__init__", &names, 4)?;
+    let this = values[0].clone().unwrap();
+    for (name, value) in ["optional", "mandatory", "compiler_flag"]
+        .iter()
+        .zip(&values[1..])
+    {
+        vm.setattr(&this, name, value.clone().unwrap())?;
+    }
+    Ok(vm.none())
+}
+
+fn feature_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__repr__", &["self"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let mut items = Vec::with_capacity(3);
+    for name in ["optional", "mandatory", "compiler_flag"] {
+        items.push(vm.getattr(this, name)?);
+    }
+    let items = vm.new_tuple(items);
+    let text = vm.repr(&items)?;
+    Ok(vm.new_str(&format!("_Feature{}", text)))
+}
+
+/// `getOptionalRelease()`: the first release in which the feature could
+/// be turned on.
+fn get_optional_release(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "getOptionalRelease", &["self"], 1)?;
+    vm.getattr(values[0].as_ref().unwrap(), "optional")
+}
+
+/// `getMandatoryRelease()`: the release in which the feature is always
+/// on, or `None` if none is planned.
+fn get_mandatory_release(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "getMandatoryRelease", &["self"], 1)?;
+    vm.getattr(values[0].as_ref().unwrap(), "mandatory")
+}
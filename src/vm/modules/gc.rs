@@ -0,0 +1,124 @@
+//! `gc`: control of the cycle collector.
+
+use super::super::builtins::index_value;
+use super::super::gc::is_container;
+use super::super::object::{Args, ObjectRef, PyResult};
+use super::super::Vm;
+use super::{bind_arguments, new_native_module};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    Ok(new_native_module(
+        vm,
+        "gc",
+        &[
+            ("collect", collect),
+            ("disable", disable),
+            ("enable", enable),
+            ("get_count", get_count),
+            ("get_stats", get_stats),
+            ("get_threshold", get_threshold),
+            ("is_tracked", is_tracked),
+            ("isenabled", isenabled),
+            ("set_threshold", set_threshold),
+        ],
+    ))
+}
+
+/// `collect(generation=2)`: the number of unreachable objects found.
+fn collect(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "collect", &["generation"], 0)?;
+    let generation = match values[0] {
+        Some(ref generation) => index_value(vm, generation)?,
+        None => 2,
+    };
+    if !(0..=2).contains(&generation) {
+        return Err(vm.new_value_error("invalid generation".to_string()));
+    }
+    let collected = vm.collect_generation(generation as usize);
+    Ok(vm.new_int(collected as i64))
+}
+
+fn enable(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "enable", 0, 0)?;
+    vm.set_gc_enabled(true);
+    Ok(vm.none())
+}
+
+fn disable(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "disable", 0, 0)?;
+    vm.set_gc_enabled(false);
+    Ok(vm.none())
+}
+
+fn isenabled(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "isenabled", 0, 0)?;
+    let enabled = vm.gc_enabled();
+    Ok(vm.new_bool(enabled))
+}
+
+/// `get_count()`: the young objects alive and the young collections since
+/// the last full one. There are two generations, so the last count is 0.
+fn get_count(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "get_count", 0, 0)?;
+    let (young, collections) = vm.gc_counts();
+    let counts = vec![
+        vm.new_int(young as i64),
+        vm.new_int(collections as i64),
+        vm.new_int(0),
+    ];
+    Ok(vm.new_tuple(counts))
+}
+
+fn get_threshold(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "get_threshold", 0, 0)?;
+    let (first, second, third) = vm.gc_thresholds();
+    let thresholds = vec![
+        vm.new_int(first as i64),
+        vm.new_int(second as i64),
+        vm.new_int(third as i64),
+    ];
+    Ok(vm.new_tuple(thresholds))
+}
+
+/// `set_threshold(threshold0[, threshold1[, threshold2]])`. The first is
+/// the number of young objects alive that starts a collection, 0 for none,
+/// and the second the number of young collections between full ones; the
+/// third is only reported.
+fn set_threshold(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "set_threshold", 1, 3)?;
+    let (_, second, third) = vm.gc_thresholds();
+    let mut thresholds = [0, second, third];
+    for (threshold, value) in thresholds.iter_mut().zip(&args.positional) {
+        *threshold = index_value(vm, value)?.max(0) as usize;
+    }
+    vm.set_gc_thresholds((thresholds[0], thresholds[1], thresholds[2]));
+    Ok(vm.none())
+}
+
+/// `get_stats()`: a dict of `collections`, `collected` and `uncollectable`
+/// for each generation.
+fn get_stats(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "get_stats", 0, 0)?;
+    let mut generations = Vec::new();
+    for stats in vm.gc_stats().iter() {
+        let dict = vm.new_dict();
+        for (name, value) in [
+            ("collections", stats.collections),
+            ("collected", stats.collected),
+            ("uncollectable", stats.uncollectable),
+        ] {
+            let value = vm.new_int(value as i64);
+            vm.dict_set_str(dict.as_dict().unwrap(), name, value);
+        }
+        generations.push(dict);
+    }
+    Ok(vm.new_list(generations))
+}
+
+/// `is_tracked(obj)`: whether the collector looks at the object, which it
+/// does for those that may refer to others.
+fn is_tracked(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "is_tracked", 1, 1)?;
+    let tracked = is_container(&args.positional[0]);
+    Ok(vm.new_bool(tracked))
+}
@@ -0,0 +1,118 @@
+//! `getpass`: reading a password from the terminal without echoing it.
+
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::process::{Command, Stdio};
+
+use super::super::object::{Args, ObjectRef, PyResult};
+use super::super::Vm;
+use super::{bind_arguments, new_native_module, str_argument};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    Ok(new_native_module(
+        vm,
+        "getpass",
+        &[("getpass", getpass), ("getuser", getuser)],
+    ))
+}
+
+/// Turns echoing of the terminal `tty` on or off with `stty`.
+fn set_echo(tty: &File, echo: bool) -> io::Result<()> {
+    let status = Command::new("stty")
+        .arg(if echo { "echo" } else { "-echo" })
+        .stdin(Stdio::from(tty.try_clone()?))
+        .stderr(Stdio::null())
+        .status()?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(io::Error::other("stty failed"))
+    }
+}
+
+/// Writes `prompt` to the terminal `tty` and reads a line from it.
+fn prompt_line(tty: &mut File, prompt: &str) -> io::Result<String> {
+    tty.write_all(prompt.as_bytes())?;
+    tty.flush()?;
+    let mut line = String::new();
+    BufReader::new(tty.try_clone()?).read_line(&mut line)?;
+    Ok(line)
+}
+
+/// Reads a line from the controlling terminal with echoing off, after
+/// writing `prompt` to it.
+fn read_from_tty(prompt: &str) -> io::Result<String> {
+    let mut tty = OpenOptions::new().read(true).write(true).open("/dev/tty")?;
+    set_echo(&tty, false)?;
+    let result = prompt_line(&mut tty, prompt);
+    let restored = set_echo(&tty, true);
+    let _ = tty.write_all(b"\n");
+    let line = result?;
+    restored?;
+    Ok(line)
+}
+
+/// Reads a line from stdin after writing `prompt` to stderr, as `getpass`
+/// falls back to when there is no terminal to turn echoing off for.
+fn read_from_stdin(prompt: &str) -> io::Result<String> {
+    let stderr = io::stderr();
+    let mut stderr = stderr.lock();
+    stderr.write_all(b"Warning: Password input may be echoed.\n")?;
+    stderr.write_all(prompt.as_bytes())?;
+    stderr.flush()?;
+    let mut line = String::new();
+    io::stdin().lock().read_line(&mut line)?;
+    Ok(line)
+}
+
+/// `getpass.getpass(prompt='Password: ', stream=None)`: a line read from
+/// the terminal without echoing it, without its newline.
+fn getpass(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "getpass", &["prompt", "stream"], 0)?;
+    let prompt = match values[0] {
+        Some(ref prompt) => str_argument(vm, "getpass", "prompt", prompt)?,
+        None => "Password: ".to_string(),
+    };
+    if let Some(ref stream) = values[1] {
+        if !Vm::is(stream, &vm.none()) {
+            let class = vm.exceptions.not_implemented_error.clone();
+            let message = "getpass() supports only the default stream".to_string();
+            return Err(vm.new_error(&class, message));
+        }
+    }
+    let line = match read_from_tty(&prompt).or_else(|_| read_from_stdin(&prompt)) {
+        Ok(line) => line,
+        Err(err) => {
+            let class = vm.exceptions.os_error.clone();
+            return Err(vm.new_error(&class, err.to_string()));
+        }
+    };
+    if line.is_empty() {
+        let class = vm.exceptions.eof_error.clone();
+        return Err(vm.new_error(&class, String::new()));
+    }
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    Ok(vm.new_str(line))
+}
+
+/// `getpass.getuser()`: the login name, from the environment variables
+/// CPython checks, or else from `id`.
+fn getuser(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "getuser", &[], 0)?;
+    for name in &["LOGNAME", "USER", "LNAME", "USERNAME"] {
+        if let Some(user) = env::var(name).ok().filter(|user| !user.is_empty()) {
+            return Ok(vm.new_str(&user));
+        }
+    }
+    let output = Command::new("id").arg("-un").stderr(Stdio::null()).output();
+    if let Ok(output) = output {
+        let user = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if output.status.success() && !user.is_empty() {
+            return Ok(vm.new_str(&user));
+        }
+    }
+    let class = vm.exceptions.os_error.clone();
+    let message = "No username set in the environment".to_string();
+    Err(vm.new_error(&class, message))
+}
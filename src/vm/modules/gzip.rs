@@ -0,0 +1,536 @@
+//! `gzip`: compressing data in the format of the gzip program, in memory
+//! or in files.
+//!
+//! A `GzipFile` reads its whole file, and decompresses it the first time
+//! it is read; one being written keeps what is written, and compresses it
+//! into the file when closed.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+
+use super::super::archive::{self, GzipError};
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{
+    add_attribute, bind_arguments, bytes_argument, module_error, new_native_class,
+    new_native_module, os_error, str_argument, utf8_text,
+};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        "gzip",
+        &[
+            ("compress", compress),
+            ("decompress", decompress),
+            ("open", open),
+        ],
+    );
+    let os_error = vm.exceptions.os_error.clone();
+    let bad_gzip_file = new_native_class(vm, "gzip", "BadGzipFile", &os_error, &[])?;
+    add_attribute(vm, &module, "BadGzipFile", bad_gzip_file);
+    let object = vm.types.object.clone();
+    let gzip_file = new_native_class(
+        vm,
+        "gzip",
+        "GzipFile",
+        &object,
+        &[
+            ("__enter__", enter),
+            ("__exit__", exit),
+            ("__init__", init),
+            ("__iter__", enter),
+            ("__next__", next),
+            ("close", close),
+            ("flush", flush),
+            ("read", read),
+            ("readable", readable),
+            ("readline", readline),
+            ("readlines", readlines),
+            ("writable", writable),
+            ("write", write),
+        ],
+    )?;
+    add_attribute(vm, &module, "GzipFile", gzip_file);
+    for &(name, mode) in &[("READ", "rb"), ("WRITE", "wb")] {
+        let mode = vm.new_str(mode);
+        add_attribute(vm, &module, name, mode);
+    }
+    Ok(module)
+}
+
+/// The error for data that could not be decompressed.
+fn decompress_error(vm: &mut Vm, error: GzipError) -> ObjectRef {
+    match error {
+        GzipError::NotGzip(magic) => {
+            let magic = vm.new_bytes(magic.to_vec());
+            let message = match vm.repr(&magic) {
+                Ok(magic) => format!("Not a gzipped file ({})", magic),
+                Err(error) => return error,
+            };
+            module_error(vm, "gzip", "BadGzipFile", message)
+        }
+        GzipError::Bad(message) => module_error(vm, "gzip", "BadGzipFile", message),
+        GzipError::Truncated => {
+            let class = vm.exceptions.eof_error.clone();
+            let message =
+                "Compressed file ended before the end-of-stream marker was reached".to_string();
+            vm.new_error(&class, message)
+        }
+        GzipError::Inflate(error) => {
+            let message = format!(
+                "Error {} while decompressing data: {}",
+                error.code, error.message
+            );
+            module_error(vm, "zlib", "error", message)
+        }
+    }
+}
+
+/// The compression level given, from 0 to 9.
+fn level_argument(vm: &mut Vm, level: Option<&ObjectRef>) -> PyResult<u8> {
+    let level = match level {
+        Some(level) => index_value(vm, level)?,
+        None => 9,
+    };
+    match level {
+        -1 => Ok(6),
+        0..=9 => Ok(level as u8),
+        _ => Err(module_error(
+            vm,
+            "zlib",
+            "error",
+            "Bad compression level".to_string(),
+        )),
+    }
+}
+
+/// The modification time to record: `mtime`, or else now.
+fn mtime_argument(vm: &mut Vm, mtime: Option<&ObjectRef>) -> PyResult<u32> {
+    match mtime {
+        Some(mtime) if !Vm::is(mtime, &vm.none()) => Ok(index_value(vm, mtime)? as u32),
+        _ => Ok(archive::now()),
+    }
+}
+
+/// `gzip.compress(data, compresslevel=9, *, mtime=None)`.
+fn compress(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "compress", &["data", "compresslevel", "mtime"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let level = level_argument(vm, values[1].as_ref())?;
+    let mtime = mtime_argument(vm, values[2].as_ref())?;
+    Ok(vm.new_bytes(archive::gzip_compress(&data, level, mtime)))
+}
+
+/// `gzip.decompress(data)`: the data of all the gzip members in `data`.
+fn decompress(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "decompress", &["data"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    match archive::gzip_decompress(&data) {
+        Ok(data) => Ok(vm.new_bytes(data)),
+        Err(error) => Err(decompress_error(vm, error)),
+    }
+}
+
+/// `gzip.open(filename, mode='rb', compresslevel=9, encoding=None,
+/// errors=None, newline=None)`: a `GzipFile`, reading and writing text in
+/// UTF-8 if `mode` has `t`.
+fn open(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "filename",
+        "mode",
+        "compresslevel",
+        "encoding",
+        "errors",
+        "newline",
+    ];
+    let values = bind_arguments(vm, args, "open", &names, 1)?;
+    let mode = match values[1] {
+        Some(ref mode) => str_argument(vm, "open", "mode", mode)?,
+        None => "rb".to_string(),
+    };
+    let text = mode.contains('t');
+    if text && mode.contains('b') {
+        return Err(vm.new_value_error(format!("Invalid mode: '{}'", mode)));
+    }
+    if !text {
+        for (index, name) in [(3, "encoding"), (4, "errors"), (5, "newline")] {
+            if values[index]
+                .as_ref()
+                .is_some_and(|value| !Vm::is(value, &vm.none()))
+            {
+                let message = format!("Argument '{}' not supported in binary mode", name);
+                return Err(vm.new_value_error(message));
+            }
+        }
+    }
+    if let Some(ref encoding) = values[3] {
+        if !Vm::is(encoding, &vm.none()) {
+            let encoding = str_argument(vm, "open", "encoding", encoding)?;
+            let normalized = encoding.to_lowercase().replace(['-', '_'], "");
+            if normalized != "utf8" {
+                let class = vm.exceptions.not_implemented_error.clone();
+                let message = format!("gzip.open() supports only UTF-8, not '{}'", encoding);
+                return Err(vm.new_error(&class, message));
+            }
+        }
+    }
+    let file_mode = vm.new_str(&mode.replace('t', ""));
+    let mut positional = vec![values[0].clone().unwrap(), file_mode];
+    if let Some(ref level) = values[2] {
+        positional.push(level.clone());
+    }
+    let module = vm.import_module("gzip")?;
+    let class = vm.getattr(&module, "GzipFile")?;
+    let file = vm.call(&class, Args::new(positional))?;
+    let text = vm.new_bool(text);
+    vm.setattr(&file, "_text", text)?;
+    Ok(file)
+}
+
+/// `GzipFile(filename=None, mode=None, compresslevel=9, fileobj=None,
+/// mtime=None)`.
+fn init(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "self",
+        "filename",
+        "mode",
+        "compresslevel",
+        "fileobj",
+        "mtime",
+    ];
+    let values = bind_arguments(vm, args, "GzipFile.__init__", &names, 1)?;
+    let this = values[0].clone().unwrap();
+    if values[4]
+        .as_ref()
+        .is_some_and(|fileobj| !Vm::is(fileobj, &vm.none()))
+    {
+        let class = vm.exceptions.not_implemented_error.clone();
+        let message = "GzipFile supports only file names, not file objects".to_string();
+        return Err(vm.new_error(&class, message));
+    }
+    let filename = match values[1] {
+        Some(ref filename) if filename.as_str().is_some() => filename.as_str().unwrap().to_string(),
+        _ => {
+            let message = "filename must be a str or bytes object, or a file".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let mut mode = match values[2] {
+        Some(ref mode) if !Vm::is(mode, &vm.none()) => str_argument(vm, "GzipFile", "mode", mode)?,
+        _ => "rb".to_string(),
+    };
+    if mode.contains('t') || mode.contains('U') {
+        return Err(vm.new_value_error(format!("Invalid mode: '{}'", mode)));
+    }
+    if !mode.contains('b') {
+        mode.push('b');
+    }
+    let level = level_argument(vm, values[3].as_ref())?;
+    let reading = match mode.chars().next() {
+        Some('r') => true,
+        Some('w') | Some('a') | Some('x') => false,
+        _ => return Err(vm.new_value_error(format!("invalid mode: '{}'", mode))),
+    };
+    if reading {
+        let data = match fs::read(&filename) {
+            Ok(data) => data,
+            Err(error) => return Err(os_error(vm, &error, &filename)),
+        };
+        let data = vm.new_bytes(data);
+        vm.setattr(&this, "_raw", data)?;
+        let none = vm.none();
+        vm.setattr(&this, "_buffer", none)?;
+    } else {
+        // Create the file now, as CPython does, so that errors show here.
+        let mut options = OpenOptions::new();
+        match mode.chars().next() {
+            Some('w') => options.write(true).create(true).truncate(true),
+            Some('a') => options.append(true).create(true),
+            _ => options.write(true).create_new(true),
+        };
+        if let Err(error) = options.open(&filename) {
+            return Err(os_error(vm, &error, &filename));
+        }
+        let chunks = vm.new_list(Vec::new());
+        vm.setattr(&this, "_chunks", chunks)?;
+        let mtime = mtime_argument(vm, values[5].as_ref())?;
+        let mtime = vm.new_int(i64::from(mtime));
+        vm.setattr(&this, "mtime", mtime)?;
+    }
+    let name = vm.new_str(&filename);
+    vm.setattr(&this, "name", name)?;
+    let append = vm.new_bool(mode.starts_with('a'));
+    let mode = vm.new_str(if reading { "rb" } else { "wb" });
+    vm.setattr(&this, "mode", mode)?;
+    vm.setattr(&this, "_append", append)?;
+    let level = vm.new_int(i64::from(level));
+    vm.setattr(&this, "compresslevel", level)?;
+    let offset = vm.new_int(0);
+    vm.setattr(&this, "_offset", offset)?;
+    let text = vm.new_bool(false);
+    vm.setattr(&this, "_text", text)?;
+    let closed = vm.new_bool(false);
+    vm.setattr(&this, "closed", closed)?;
+    Ok(vm.none())
+}
+
+/// The file a method was called on, checking that it is open and, if
+/// `reading` is given, that it was opened for reading or for writing.
+fn open_file(vm: &mut Vm, this: &ObjectRef, reading: Option<bool>, method: &str) -> PyResult<()> {
+    let closed = vm.getattr(this, "closed")?;
+    if vm.is_true(&closed)? {
+        let message = "I/O operation on closed file".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    if let Some(reading) = reading {
+        let mode = vm.getattr(this, "mode")?;
+        let is_reading = mode.as_str() == Some("rb");
+        if is_reading != reading {
+            let message = format!(
+                "[Errno 9] {}() on {} GzipFile object",
+                method,
+                if is_reading {
+                    "read-only"
+                } else {
+                    "write-only"
+                }
+            );
+            let class = vm.exceptions.os_error.clone();
+            return Err(vm.new_error(&class, message));
+        }
+    }
+    Ok(())
+}
+
+/// Whether the file reads and writes text.
+fn is_text(vm: &mut Vm, this: &ObjectRef) -> PyResult<bool> {
+    let text = vm.getattr(this, "_text")?;
+    vm.is_true(&text)
+}
+
+/// The decompressed data of a file being read: bytes, or for text a str
+/// with its line endings made `\n`.
+fn buffer(vm: &mut Vm, this: &ObjectRef) -> PyResult {
+    let buffer = vm.getattr(this, "_buffer")?;
+    if !Vm::is(&buffer, &vm.none()) {
+        return Ok(buffer);
+    }
+    let raw = vm.getattr(this, "_raw")?;
+    let raw = bytes_argument(vm, &raw)?;
+    let data = match archive::gzip_decompress(&raw) {
+        Ok(data) => data,
+        Err(error) => return Err(decompress_error(vm, error)),
+    };
+    let buffer = if is_text(vm, this)? {
+        let text = utf8_text(vm, data)?;
+        vm.new_str(&text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        vm.new_bytes(data)
+    };
+    let none = vm.none();
+    vm.setattr(this, "_raw", none)?;
+    vm.setattr(this, "_buffer", buffer.clone())?;
+    Ok(buffer)
+}
+
+/// Takes from the buffer from the offset to `end`, a function of the rest
+/// of the data that gives how many bytes to take.
+fn take(vm: &mut Vm, this: &ObjectRef, end: impl Fn(&[u8], bool) -> usize) -> PyResult {
+    let buffer = buffer(vm, this)?;
+    let offset = vm.getattr(this, "_offset")?;
+    let offset = index_value(vm, &offset)? as usize;
+    let (rest, text) = match buffer.payload {
+        Payload::Str(ref text) => (&text.as_bytes()[offset..], true),
+        Payload::Bytes(ref data) => (&data[offset..], false),
+        _ => unreachable!(),
+    };
+    let count = end(rest, text);
+    let taken = if text {
+        vm.new_str(::std::str::from_utf8(&rest[..count]).unwrap())
+    } else {
+        vm.new_bytes(rest[..count].to_vec())
+    };
+    let offset = vm.new_int((offset + count) as i64);
+    vm.setattr(this, "_offset", offset)?;
+    Ok(taken)
+}
+
+/// The number of bytes of the first `size` characters of `rest`, or of
+/// the first `size` bytes, or of all of it if `size` is negative.
+fn prefix_length(rest: &[u8], text: bool, size: i64) -> usize {
+    if size < 0 {
+        return rest.len();
+    }
+    let size = size as usize;
+    if !text {
+        return size.min(rest.len());
+    }
+    let text = ::std::str::from_utf8(rest).unwrap();
+    text.char_indices()
+        .nth(size)
+        .map_or(rest.len(), |(at, _)| at)
+}
+
+fn size_argument(vm: &mut Vm, size: Option<&ObjectRef>) -> PyResult<i64> {
+    match size {
+        Some(size) if !Vm::is(size, &vm.none()) => index_value(vm, size),
+        _ => Ok(-1),
+    }
+}
+
+/// `GzipFile.read(size=-1)`.
+fn read(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    open_file(vm, &this, Some(true), "read")?;
+    let size = size_argument(vm, values[1].as_ref())?;
+    take(vm, &this, |rest, text| prefix_length(rest, text, size))
+}
+
+/// `GzipFile.readline(size=-1)`: the next line, with its newline.
+fn readline(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readline", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    open_file(vm, &this, Some(true), "read")?;
+    let size = size_argument(vm, values[1].as_ref())?;
+    take(vm, &this, |rest, text| {
+        let line = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(rest.len(), |end| end + 1);
+        prefix_length(&rest[..line], text, size)
+    })
+}
+
+/// `GzipFile.readlines()`: the rest of the lines.
+fn readlines(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readlines", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    open_file(vm, &this, Some(true), "read")?;
+    let mut lines = Vec::new();
+    loop {
+        let line = readline(vm, Args::new(vec![this.clone()]))?;
+        if vm.len(&line)? == 0 {
+            break;
+        }
+        lines.push(line);
+    }
+    Ok(vm.new_list(lines))
+}
+
+/// `GzipFile.__next__()`, the next line.
+fn next(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__next__", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    let line = readline(vm, Args::new(vec![this]))?;
+    if vm.len(&line)? == 0 {
+        let class = vm.exceptions.stop_iteration.clone();
+        return Err(vm.new_exception(&class, Vec::new()));
+    }
+    Ok(line)
+}
+
+/// `GzipFile.write(data)`: the number of bytes, or characters, written.
+fn write(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "write", &["self", "data"], 2)?;
+    let this = values[0].clone().unwrap();
+    open_file(vm, &this, Some(false), "write")?;
+    let data = values[1].clone().unwrap();
+    let (data, written) = if is_text(vm, &this)? {
+        let text = match data.as_str() {
+            Some(text) => text.to_string(),
+            None => {
+                let message = format!("write() argument must be str, not {}", data.type_name());
+                return Err(vm.new_type_error(message));
+            }
+        };
+        let count = text.chars().count();
+        (vm.new_bytes(text.into_bytes()), count)
+    } else {
+        let bytes = bytes_argument(vm, &data)?;
+        (data, bytes.len())
+    };
+    let chunks = vm.getattr(&this, "_chunks")?;
+    if let Payload::List(ref chunks) = chunks.payload {
+        chunks.borrow_mut().push(data);
+    }
+    Ok(vm.new_int(written as i64))
+}
+
+/// `GzipFile.close()`: for a file being written, compresses what was
+/// written into it.
+fn close(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "close", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    let closed = vm.getattr(&this, "closed")?;
+    if vm.is_true(&closed)? {
+        return Ok(vm.none());
+    }
+    let closed = vm.new_bool(true);
+    vm.setattr(&this, "closed", closed)?;
+    let mode = vm.getattr(&this, "mode")?;
+    if mode.as_str() == Some("rb") {
+        let none = vm.none();
+        vm.setattr(&this, "_buffer", none)?;
+        return Ok(vm.none());
+    }
+    let chunks = vm.getattr(&this, "_chunks")?;
+    let mut data = Vec::new();
+    for chunk in vm.iterate(&chunks)? {
+        data.extend(bytes_argument(vm, &chunk)?);
+    }
+    let level = vm.getattr(&this, "compresslevel")?;
+    let level = index_value(vm, &level)? as u8;
+    let mtime = vm.getattr(&this, "mtime")?;
+    let mtime = index_value(vm, &mtime)? as u32;
+    let name = vm.getattr(&this, "name")?;
+    let name = str_argument(vm, "close", "name", &name)?;
+    let append = vm.getattr(&this, "_append")?;
+    let member = archive::gzip_compress(&data, level, mtime);
+    let written = OpenOptions::new()
+        .write(true)
+        .append(vm.is_true(&append)?)
+        .truncate(!vm.is_true(&append)?)
+        .open(&name)
+        .and_then(|mut file| file.write_all(&member));
+    if let Err(error) = written {
+        return Err(os_error(vm, &error, &name));
+    }
+    Ok(vm.none())
+}
+
+/// `GzipFile.flush()`, which has nothing to do, as the data is written on
+/// closing.
+fn flush(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "flush", &["self"], 1)?;
+    open_file(vm, values[0].as_ref().unwrap(), None, "flush")?;
+    Ok(vm.none())
+}
+
+fn readable(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readable", &["self"], 1)?;
+    let mode = vm.getattr(values[0].as_ref().unwrap(), "mode")?;
+    Ok(vm.new_bool(mode.as_str() == Some("rb")))
+}
+
+fn writable(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "writable", &["self"], 1)?;
+    let mode = vm.getattr(values[0].as_ref().unwrap(), "mode")?;
+    Ok(vm.new_bool(mode.as_str() == Some("wb")))
+}
+
+/// `GzipFile.__enter__()` and `__iter__()`, which return the file.
+fn enter(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__enter__", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    open_file(vm, &this, None, "__enter__")?;
+    Ok(this)
+}
+
+/// `GzipFile.__exit__(*exc_info)`, which closes the file.
+fn exit(vm: &mut Vm, mut args: Args) -> PyResult {
+    args.positional.truncate(1);
+    close(vm, args)
+}
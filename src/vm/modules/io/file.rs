@@ -0,0 +1,841 @@
+//! Files on disk, as `open` opens them.
+//!
+//! The files a VM has open are kept in its `Files`, by handle, and a file
+//! object holds the handle of its own in `_handle` until it is closed. A
+//! file object that is never closed keeps its file open until the VM is
+//! dropped. Reads go through bytes read ahead of the position, which are
+//! given back to the file before it is written, moved in or truncated, so
+//! that reads and writes can be mixed as in CPython.
+//!
+//! Text is UTF-8 unless `encoding` names ASCII or Latin-1. With the default
+//! `newline=None`, `\r\n` and `\r` are read as `\n`; with any other newline
+//! nothing is translated, and lines end at `\n`.
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+use super::super::super::builtins::index_value;
+use super::super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::super::Vm;
+use super::super::{
+    add_attribute, bind_arguments, new_native_class, os_error, str_argument, utf8_text,
+};
+use super::{
+    add_properties, closed_error, size_argument, this_argument, unsupported, SEEK_CUR, SEEK_END,
+    SEEK_SET,
+};
+
+/// How much is read ahead at a time.
+const CHUNK_SIZE: usize = 8192;
+
+/// The open files of a VM, by handle.
+#[derive(Default)]
+pub(crate) struct Files {
+    open: HashMap<i64, OpenFile>,
+    next_handle: i64,
+}
+
+/// A file, and the bytes read from it ahead of the position.
+struct OpenFile {
+    file: File,
+    ahead: Vec<u8>,
+}
+
+impl OpenFile {
+    /// Reads more of the file ahead, returning false at its end.
+    fn fill(&mut self) -> io::Result<bool> {
+        let mut chunk = [0; CHUNK_SIZE];
+        let count = loop {
+            match self.file.read(&mut chunk) {
+                Err(ref error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                result => break result?,
+            }
+        };
+        self.ahead.extend_from_slice(&chunk[..count]);
+        Ok(count > 0)
+    }
+
+    /// Gives the bytes read ahead back to the file, so that the position
+    /// of the file is that of the reader.
+    fn unread(&mut self) -> io::Result<()> {
+        if !self.ahead.is_empty() {
+            self.file
+                .seek(SeekFrom::Current(-(self.ahead.len() as i64)))?;
+            self.ahead.clear();
+        }
+        Ok(())
+    }
+
+    /// Up to `size` bytes, or the rest of the file.
+    fn read(&mut self, size: Option<usize>) -> io::Result<Vec<u8>> {
+        match size {
+            None => {
+                let mut data = std::mem::take(&mut self.ahead);
+                self.file.read_to_end(&mut data)?;
+                Ok(data)
+            }
+            Some(size) => {
+                while self.ahead.len() < size && self.fill()? {}
+                let size = size.min(self.ahead.len());
+                Ok(self.ahead.drain(..size).collect())
+            }
+        }
+    }
+
+    /// The next line, with its ending: `\n`, or also `\r\n` or `\r` with
+    /// `universal`. Empty at the end of the file.
+    fn read_line(&mut self, universal: bool) -> io::Result<Vec<u8>> {
+        let mut searched = 0;
+        loop {
+            let end = self.ahead[searched..]
+                .iter()
+                .position(|&byte| byte == b'\n' || (universal && byte == b'\r'))
+                .map(|at| searched + at);
+            match end {
+                Some(at) if self.ahead[at] == b'\r' => {
+                    if at + 1 == self.ahead.len() && self.fill()? {
+                        searched = at;
+                        continue;
+                    }
+                    let length = if self.ahead.get(at + 1) == Some(&b'\n') {
+                        at + 2
+                    } else {
+                        at + 1
+                    };
+                    return Ok(self.ahead.drain(..length).collect());
+                }
+                Some(at) => return Ok(self.ahead.drain(..at + 1).collect()),
+                None => {
+                    searched = self.ahead.len();
+                    if !self.fill()? {
+                        return Ok(std::mem::take(&mut self.ahead));
+                    }
+                }
+            }
+        }
+    }
+
+    fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        self.unread()?;
+        self.file.write_all(data)
+    }
+
+    fn seek(&mut self, position: SeekFrom) -> io::Result<u64> {
+        self.unread()?;
+        self.file.seek(position)
+    }
+
+    fn tell(&mut self) -> io::Result<u64> {
+        Ok(self.file.stream_position()? - self.ahead.len() as u64)
+    }
+
+    fn truncate(&mut self, size: u64) -> io::Result<()> {
+        self.unread()?;
+        self.file.set_len(size)
+    }
+}
+
+/// The encodings of text files.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Utf8,
+    Ascii,
+    Latin1,
+}
+
+impl Encoding {
+    /// The encoding named `name`, with the aliases Python accepts.
+    fn from_name(name: &str) -> Option<Encoding> {
+        match name.to_ascii_lowercase().replace(['_', ' '], "-").as_str() {
+            "utf-8" | "utf8" | "u8" | "utf" | "cp65001" => Some(Encoding::Utf8),
+            "ascii" | "us-ascii" | "646" => Some(Encoding::Ascii),
+            "latin-1" | "latin1" | "latin" | "l1" | "iso-8859-1" | "iso8859-1" | "8859"
+            | "cp819" => Some(Encoding::Latin1),
+            _ => None,
+        }
+    }
+
+    fn decode(self, vm: &mut Vm, data: Vec<u8>) -> PyResult<String> {
+        let limit = match self {
+            Encoding::Utf8 => return utf8_text(vm, data),
+            Encoding::Ascii => 0x80,
+            Encoding::Latin1 => return Ok(data.iter().map(|&byte| byte as char).collect()),
+        };
+        match data.iter().position(|&byte| byte >= limit) {
+            Some(position) => {
+                let message = format!(
+                    "'ascii' codec can't decode byte {:#04x} in position {}: ordinal not in range(128)",
+                    data[position], position
+                );
+                let class = vm.exceptions.unicode_decode_error.clone();
+                Err(vm.new_error(&class, message))
+            }
+            None => Ok(data.iter().map(|&byte| byte as char).collect()),
+        }
+    }
+
+    fn encode(self, vm: &mut Vm, text: &str) -> PyResult<Vec<u8>> {
+        let (codec, limit) = match self {
+            Encoding::Utf8 => return Ok(text.as_bytes().to_vec()),
+            Encoding::Ascii => ("ascii", 0x80),
+            Encoding::Latin1 => ("latin-1", 0x100),
+        };
+        let mut data = Vec::with_capacity(text.len());
+        for (position, c) in text.chars().enumerate() {
+            if c as u32 >= limit {
+                let message = format!(
+                    "'{}' codec can't encode character '\\u{:04x}' in position {}: ordinal not in range({})",
+                    codec, c as u32, position, limit
+                );
+                let class = vm.exceptions.unicode_encode_error.clone();
+                return Err(vm.new_error(&class, message));
+            }
+            data.push(c as u8);
+        }
+        Ok(data)
+    }
+}
+
+/// Adds the classes of files on disk to the `io` module.
+pub(super) fn add_classes(
+    vm: &mut Vm,
+    module: &ObjectRef,
+    text_io_base: &ObjectRef,
+    buffered_io_base: &ObjectRef,
+) -> PyResult<()> {
+    let shared = [
+        ("__repr__", file_repr as fn(&mut Vm, Args) -> PyResult),
+        ("close", file_close),
+        ("fileno", file_fileno),
+        ("flush", file_flush),
+        ("isatty", file_isatty),
+        ("readable", file_readable),
+        ("seek", file_seek),
+        ("seekable", file_seekable),
+        ("tell", file_tell),
+        ("truncate", file_truncate),
+        ("writable", file_writable),
+    ];
+    let mut methods = shared.to_vec();
+    methods.extend_from_slice(&[
+        ("read", text_read as fn(&mut Vm, Args) -> PyResult),
+        ("readline", text_readline),
+        ("write", text_write),
+    ]);
+    let text = new_native_class(vm, "_io", "TextIOWrapper", text_io_base, &methods)?;
+    add_properties(
+        vm,
+        &text,
+        &[
+            ("closed", file_closed),
+            ("encoding", text_encoding),
+            ("errors", text_errors),
+        ],
+    )?;
+    add_attribute(vm, module, "TextIOWrapper", text);
+    let mut methods = shared.to_vec();
+    methods.extend_from_slice(&[
+        ("read", binary_read as fn(&mut Vm, Args) -> PyResult),
+        ("read1", binary_read),
+        ("readline", binary_readline),
+        ("write", binary_write),
+    ]);
+    for name in ["BufferedReader", "BufferedWriter", "BufferedRandom"] {
+        let class = new_native_class(vm, "_io", name, buffered_io_base, &methods)?;
+        add_properties(vm, &class, &[("closed", file_closed)])?;
+        add_attribute(vm, module, name, class);
+    }
+    Ok(())
+}
+
+/// What the mode of `open` asks for.
+struct Mode {
+    reading: bool,
+    writing: bool,
+    appending: bool,
+    creating: bool,
+    updating: bool,
+    binary: bool,
+}
+
+impl Mode {
+    fn parse(mode: &str) -> Result<Mode, String> {
+        let invalid = || format!("invalid mode: '{}'", mode);
+        let mut seen = String::new();
+        for c in mode.chars() {
+            if !"rwxab+t".contains(c) || seen.contains(c) {
+                return Err(invalid());
+            }
+            seen.push(c);
+        }
+        let has = |c| seen.contains(c);
+        if has('t') && has('b') {
+            return Err("can't have text and binary mode at once".to_string());
+        }
+        let kinds = ['r', 'w', 'a', 'x'].iter().filter(|&&c| has(c)).count();
+        if kinds > 1 {
+            return Err("must have exactly one of create/read/write/append mode".to_string());
+        }
+        if kinds == 0 {
+            return Err(
+                "Must have exactly one of create/read/write/append mode and at most one plus"
+                    .to_string(),
+            );
+        }
+        Ok(Mode {
+            reading: has('r'),
+            writing: has('w'),
+            appending: has('a'),
+            creating: has('x'),
+            updating: has('+'),
+            binary: has('b'),
+        })
+    }
+
+    fn readable(&self) -> bool {
+        self.reading || self.updating
+    }
+
+    fn writable(&self) -> bool {
+        !self.reading || self.updating
+    }
+
+    /// The mode of the raw file, which binary files report.
+    fn raw_mode(&self) -> &'static str {
+        match (
+            self.creating,
+            self.appending,
+            self.readable(),
+            self.writable(),
+        ) {
+            (true, _, false, _) => "xb",
+            (true, _, true, _) => "xb+",
+            (_, true, false, _) => "ab",
+            (_, true, true, _) => "ab+",
+            (_, _, true, true) => "rb+",
+            (_, _, true, false) => "rb",
+            _ => "wb",
+        }
+    }
+
+    fn options(&self) -> OpenOptions {
+        let mut options = OpenOptions::new();
+        options
+            .read(self.readable())
+            .write(self.writing || self.creating || self.updating && !self.appending)
+            .append(self.appending)
+            .truncate(self.writing)
+            .create(self.writing || self.appending)
+            .create_new(self.creating);
+        options
+    }
+}
+
+/// `open(file, mode='r', buffering=-1, encoding=None, errors=None,
+/// newline=None, closefd=True, opener=None)`: a `TextIOWrapper`, or for a
+/// binary mode a `BufferedReader`, `BufferedWriter` or `BufferedRandom`.
+/// `buffering`, `closefd` and `opener` are accepted and ignored, and only
+/// strict `errors` are supported.
+pub(crate) fn open(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "file",
+        "mode",
+        "buffering",
+        "encoding",
+        "errors",
+        "newline",
+        "closefd",
+        "opener",
+    ];
+    let values = bind_arguments(vm, args, "open", &names, 1)?;
+    let none = vm.none();
+    let given = |index: usize| values[index].clone().filter(|value| !Vm::is(value, &none));
+    let file = values[0].clone().unwrap();
+    let path = match file.payload {
+        Payload::Str(ref path) => path.clone(),
+        Payload::Bytes(ref path) => String::from_utf8_lossy(path).into_owned(),
+        _ => {
+            let message = format!(
+                "expected str, bytes or os.PathLike object, not {}",
+                file.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let mode_text = match values[1] {
+        None => "r".to_string(),
+        Some(ref mode) => match mode.as_str() {
+            Some(mode) => mode.to_string(),
+            None => {
+                let message = format!(
+                    "open() argument 'mode' must be str, not {}",
+                    mode.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        },
+    };
+    let mode = Mode::parse(&mode_text).map_err(|message| vm.new_value_error(message))?;
+    let (encoding, errors, newline) = (given(3), given(4), given(5));
+    if mode.binary {
+        for (value, name) in [
+            (&encoding, "an encoding"),
+            (&errors, "an errors"),
+            (&newline, "a newline"),
+        ] {
+            if value.is_some() {
+                let message = format!("binary mode doesn't take {} argument", name);
+                return Err(vm.new_value_error(message));
+            }
+        }
+    }
+    let encoding_name = match encoding {
+        Some(ref encoding) => str_argument(vm, "open", "encoding", encoding)?,
+        None => "utf-8".to_string(),
+    };
+    if Encoding::from_name(&encoding_name).is_none() {
+        let class = vm.exceptions.lookup_error.clone();
+        return Err(vm.new_error(&class, format!("unknown encoding: {}", encoding_name)));
+    }
+    if let Some(ref errors) = errors {
+        if str_argument(vm, "open", "errors", errors)? != "strict" {
+            let message = "open() supports only strict errors".to_string();
+            return Err(vm.new_value_error(message));
+        }
+    }
+    if let Some(ref newline) = newline {
+        match newline.as_str() {
+            Some("") | Some("\n") | Some("\r") | Some("\r\n") => {}
+            Some(_) => {
+                let message = format!("illegal newline value: {}", vm.repr(newline)?);
+                return Err(vm.new_value_error(message));
+            }
+            None => {
+                let message = format!(
+                    "open() argument 'newline' must be str or None, not {}",
+                    newline.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        }
+    }
+
+    let opened = match mode.options().open(&path) {
+        Ok(opened) => opened,
+        Err(error) => return Err(os_error(vm, &error, &path)),
+    };
+    if opened.metadata().is_ok_and(|metadata| metadata.is_dir()) {
+        // Only Unix opens directories, and there `EISDIR` is 21.
+        let error = io::Error::from_raw_os_error(21);
+        return Err(os_error(vm, &error, &path));
+    }
+    let handle = vm.files.next_handle;
+    vm.files.next_handle += 1;
+    vm.files.open.insert(
+        handle,
+        OpenFile {
+            file: opened,
+            ahead: Vec::new(),
+        },
+    );
+
+    let class_name = match (mode.binary, mode.readable(), mode.writable()) {
+        (false, _, _) => "TextIOWrapper",
+        (true, true, true) => "BufferedRandom",
+        (true, true, false) => "BufferedReader",
+        (true, false, _) => "BufferedWriter",
+    };
+    let io = vm.import_module("io")?;
+    let class = vm.getattr(&io, class_name)?;
+    let this = vm.call(&class, Args::default())?;
+    let mut attributes = vec![
+        ("name", file.clone()),
+        ("_handle", vm.new_int(handle)),
+        ("_closed", vm.new_bool(false)),
+        ("_readable", vm.new_bool(mode.readable())),
+        ("_writable", vm.new_bool(mode.writable())),
+    ];
+    if mode.binary {
+        attributes.push(("mode", vm.new_str(mode.raw_mode())));
+    } else {
+        attributes.push(("mode", vm.new_str(&mode_text)));
+        attributes.push(("_encoding", vm.new_str(&encoding_name)));
+        attributes.push(("_errors", vm.new_str("strict")));
+        attributes.push(("_newline", newline.unwrap_or(none)));
+    }
+    for (name, value) in attributes {
+        vm.setattr(&this, name, value)?;
+    }
+    Ok(this)
+}
+
+/// Runs `operation` on the file of a file object, failing if it is
+/// closed.
+fn with_file<T>(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    operation: impl FnOnce(&mut OpenFile) -> io::Result<T>,
+) -> PyResult<T> {
+    let handle = vm.getattr(this, "_handle")?;
+    let handle = match handle.payload {
+        Payload::Int(handle) => handle,
+        _ => return Err(closed_error(vm, true)),
+    };
+    let file = vm
+        .files
+        .open
+        .get_mut(&handle)
+        .expect("an open file object has an open file");
+    match operation(file) {
+        Ok(value) => Ok(value),
+        Err(error) => {
+            let name = vm.getattr(this, "name")?;
+            let name = vm.str(&name)?;
+            Err(os_error(vm, &error, &name))
+        }
+    }
+}
+
+/// Fails with `io.UnsupportedOperation` unless the file can be read, or
+/// written, as `attribute` says; or if it is closed.
+fn check_mode(vm: &mut Vm, this: &ObjectRef, attribute: &str) -> PyResult<()> {
+    with_file(vm, this, |_| Ok(()))?;
+    let allowed = vm.getattr(this, attribute)?;
+    if !vm.is_true(&allowed)? {
+        let message = if attribute == "_readable" {
+            "not readable"
+        } else {
+            "not writable"
+        };
+        return Err(unsupported(vm, message));
+    }
+    Ok(())
+}
+
+/// The encoding of a text file.
+fn encoding(vm: &mut Vm, this: &ObjectRef) -> PyResult<Encoding> {
+    let name = vm.getattr(this, "encoding")?;
+    Ok(Encoding::from_name(name.as_str().unwrap_or_default()).unwrap_or(Encoding::Utf8))
+}
+
+/// Whether a text file ends lines at `\r` and `\r\n` as well as `\n`.
+fn universal_newlines(vm: &mut Vm, this: &ObjectRef) -> PyResult<bool> {
+    let newline = vm.getattr(this, "_newline")?;
+    Ok(Vm::is(&newline, &vm.none()) || newline.as_str() == Some(""))
+}
+
+/// Whether lines read from a text file have their endings made `\n`, as
+/// they do when `open` was given no `newline`.
+fn translates_newlines(vm: &mut Vm, this: &ObjectRef) -> PyResult<bool> {
+    let newline = vm.getattr(this, "_newline")?;
+    Ok(Vm::is(&newline, &vm.none()))
+}
+
+fn file_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "__repr__")?;
+    let class = this.class();
+    let mut text = format!("<_io.{}", class.as_type().unwrap().name);
+    let attributes: &[&str] = if class.as_type().unwrap().name == "TextIOWrapper" {
+        &["name", "mode", "encoding"]
+    } else {
+        &["name"]
+    };
+    for &attribute in attributes {
+        let value = vm.getattr(&this, attribute)?;
+        text.push_str(&format!(" {}={}", attribute, vm.repr(&value)?));
+    }
+    text.push('>');
+    Ok(vm.new_str(&text))
+}
+
+fn file_closed(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "closed")?;
+    vm.getattr(&this, "_closed")
+}
+
+fn text_encoding(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "encoding")?;
+    vm.getattr(&this, "_encoding")
+}
+
+fn text_errors(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "errors")?;
+    vm.getattr(&this, "_errors")
+}
+
+/// `close()`: closes the file, if it is not closed already.
+fn file_close(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "close")?;
+    let handle = vm.getattr(&this, "_handle")?;
+    if let Payload::Int(handle) = handle.payload {
+        let file = vm.files.open.remove(&handle);
+        let none = vm.none();
+        vm.setattr(&this, "_handle", none)?;
+        let closed = vm.new_bool(true);
+        vm.setattr(&this, "_closed", closed)?;
+        if let Some(mut file) = file {
+            if let Err(error) = file.file.flush() {
+                let name = vm.getattr(&this, "name")?;
+                let name = vm.str(&name)?;
+                return Err(os_error(vm, &error, &name));
+            }
+        }
+    }
+    Ok(vm.none())
+}
+
+fn file_flush(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "flush")?;
+    with_file(vm, &this, |file| file.file.flush())?;
+    Ok(vm.none())
+}
+
+/// `fileno()`: the file descriptor, on systems that have them.
+fn file_fileno(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "fileno")?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::io::AsRawFd;
+        let fd = with_file(vm, &this, |file| Ok(file.file.as_raw_fd()))?;
+        Ok(vm.new_int(i64::from(fd)))
+    }
+    #[cfg(not(unix))]
+    {
+        with_file(vm, &this, |_| Ok(()))?;
+        Err(unsupported(vm, "fileno"))
+    }
+}
+
+fn file_isatty(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "isatty")?;
+    with_file(vm, &this, |_| Ok(()))?;
+    Ok(vm.new_bool(false))
+}
+
+fn file_readable(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "readable")?;
+    with_file(vm, &this, |_| Ok(()))?;
+    vm.getattr(&this, "_readable")
+}
+
+fn file_writable(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "writable")?;
+    with_file(vm, &this, |_| Ok(()))?;
+    vm.getattr(&this, "_writable")
+}
+
+fn file_seekable(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "seekable")?;
+    with_file(vm, &this, |_| Ok(()))?;
+    Ok(vm.new_bool(true))
+}
+
+/// `seek(offset, whence=0)`: the new position. A text file can only seek
+/// to a position `tell` returned, or to its start or end.
+fn file_seek(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "seek", &["self", "cookie", "whence"], 2)?;
+    let this = values[0].clone().unwrap();
+    let offset = index_value(vm, values[1].as_ref().unwrap())?;
+    let whence = match values[2] {
+        Some(ref whence) => index_value(vm, whence)?,
+        None => SEEK_SET,
+    };
+    with_file(vm, &this, |_| Ok(()))?;
+    let text = this.class().as_type().unwrap().name == "TextIOWrapper";
+    if text && whence != SEEK_SET && offset != 0 {
+        let message = if whence == SEEK_CUR {
+            "can't do nonzero cur-relative seeks"
+        } else {
+            "can't do nonzero end-relative seeks"
+        };
+        return Err(unsupported(vm, message));
+    }
+    let position = match whence {
+        SEEK_SET if offset < 0 => {
+            let message = format!("negative seek position {}", offset);
+            return Err(vm.new_value_error(message));
+        }
+        SEEK_SET => SeekFrom::Start(offset as u64),
+        SEEK_CUR => SeekFrom::Current(offset),
+        SEEK_END => SeekFrom::End(offset),
+        _ => {
+            let message = format!("invalid whence ({}, should be 0, 1 or 2)", whence);
+            return Err(vm.new_value_error(message));
+        }
+    };
+    let position = with_file(vm, &this, |file| {
+        if let SeekFrom::Current(offset) = position {
+            let start = file.tell()? as i64;
+            return file.seek(SeekFrom::Start((start + offset).max(0) as u64));
+        }
+        file.seek(position)
+    })?;
+    Ok(vm.new_int(position as i64))
+}
+
+fn file_tell(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "tell")?;
+    let position = with_file(vm, &this, OpenFile::tell)?;
+    Ok(vm.new_int(position as i64))
+}
+
+/// `truncate(size=None)`: cuts the file to `size` bytes, or at the
+/// position, which does not move.
+fn file_truncate(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "truncate", &["self", "pos"], 1)?;
+    let this = values[0].clone().unwrap();
+    check_mode(vm, &this, "_writable")?;
+    let size = match values[1] {
+        Some(ref size) if !Vm::is(size, &vm.none()) => index_value(vm, size)?,
+        _ => with_file(vm, &this, OpenFile::tell)? as i64,
+    };
+    if size < 0 {
+        let message = format!("negative size value {}", size);
+        return Err(vm.new_value_error(message));
+    }
+    with_file(vm, &this, |file| {
+        let position = file.tell()?;
+        file.truncate(size as u64)?;
+        file.seek(SeekFrom::Start(position))
+    })?;
+    Ok(vm.new_int(size))
+}
+
+/// `TextIOWrapper.read(size=-1)`: up to `size` characters, or the rest of
+/// the file.
+fn text_read(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let size = size_argument(vm, values[1].as_ref(), -1)?;
+    check_mode(vm, &this, "_readable")?;
+    if size < 0 {
+        let data = with_file(vm, &this, |file| file.read(None))?;
+        let text = encoding(vm, &this)?.decode(vm, data)?;
+        let text = if translates_newlines(vm, &this)? {
+            text.replace("\r\n", "\n").replace('\r', "\n")
+        } else {
+            text
+        };
+        return Ok(vm.new_str(&text));
+    }
+    let mut text = String::new();
+    let mut count = 0;
+    while count < size as usize {
+        let line = read_text_line(vm, &this, Some(size as usize - count))?;
+        if line.is_empty() {
+            break;
+        }
+        count += line.chars().count();
+        text.push_str(&line);
+    }
+    Ok(vm.new_str(&text))
+}
+
+/// `TextIOWrapper.readline(size=-1)`.
+fn text_readline(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readline", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let size = size_argument(vm, values[1].as_ref(), -1)?;
+    check_mode(vm, &this, "_readable")?;
+    let limit = if size < 0 { None } else { Some(size as usize) };
+    let line = read_text_line(vm, &this, limit)?;
+    Ok(vm.new_str(&line))
+}
+
+/// The next line of a text file, or its first `limit` characters, with the
+/// rest left to be read.
+fn read_text_line(vm: &mut Vm, this: &ObjectRef, limit: Option<usize>) -> PyResult<String> {
+    let universal = universal_newlines(vm, this)?;
+    let encoding = encoding(vm, this)?;
+    let data = with_file(vm, this, |file| file.read_line(universal))?;
+    let mut line = encoding.decode(vm, data)?;
+    let translate = translates_newlines(vm, this)?;
+    if translate && line.ends_with('\r') {
+        line.pop();
+        line.push('\n');
+    } else if translate && line.ends_with("\r\n") {
+        line.truncate(line.len() - 2);
+        line.push('\n');
+    }
+    if let Some(limit) = limit {
+        if let Some((at, _)) = line.char_indices().nth(limit) {
+            let rest = encoding.encode(vm, &line[at..])?;
+            line.truncate(at);
+            with_file(vm, this, |file| {
+                file.ahead.splice(..0, rest);
+                Ok(())
+            })?;
+        }
+    }
+    Ok(line)
+}
+
+/// `TextIOWrapper.write(s)`: the number of characters written. `\n` is
+/// written as the line ending of the system.
+fn text_write(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "write", &["self", "s"], 2)?;
+    let this = values[0].clone().unwrap();
+    check_mode(vm, &this, "_writable")?;
+    let text = values[1].clone().unwrap();
+    let text = match text.as_str() {
+        Some(text) => text.to_string(),
+        None => {
+            let message = format!("write() argument must be str, not {}", text.type_name());
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let newline = vm.getattr(&this, "_newline")?;
+    let ending = match newline.as_str() {
+        Some(ending) if !ending.is_empty() => ending,
+        Some(_) => "\n",
+        None if cfg!(windows) => "\r\n",
+        None => "\n",
+    };
+    let written = if ending == "\n" {
+        text.clone()
+    } else {
+        text.replace('\n', ending)
+    };
+    let data = encoding(vm, &this)?.encode(vm, &written)?;
+    with_file(vm, &this, |file| file.write(&data))?;
+    Ok(vm.new_int(text.chars().count() as i64))
+}
+
+/// `read(size=-1)` of a binary file, and `read1`.
+fn binary_read(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let size = size_argument(vm, values[1].as_ref(), -1)?;
+    check_mode(vm, &this, "_readable")?;
+    let size = if size < 0 { None } else { Some(size as usize) };
+    let data = with_file(vm, &this, |file| file.read(size))?;
+    Ok(vm.new_bytes(data))
+}
+
+/// `readline(size=-1)` of a binary file.
+fn binary_readline(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readline", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let size = size_argument(vm, values[1].as_ref(), -1)?;
+    check_mode(vm, &this, "_readable")?;
+    let line = with_file(vm, &this, |file| {
+        let mut line = file.read_line(false)?;
+        if size >= 0 && line.len() > size as usize {
+            let rest = line.split_off(size as usize);
+            file.ahead.splice(..0, rest);
+        }
+        Ok(line)
+    })?;
+    Ok(vm.new_bytes(line))
+}
+
+/// `write(b)` of a binary file: the number of bytes written.
+fn binary_write(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "write", &["self", "buffer"], 2)?;
+    let this = values[0].clone().unwrap();
+    check_mode(vm, &this, "_writable")?;
+    let data = super::bytes_like(vm, values[1].as_ref().unwrap())?;
+    with_file(vm, &this, |file| file.write(&data))?;
+    Ok(vm.new_int(data.len() as i64))
+}
@@ -0,0 +1,896 @@
+//! `io`: the classes of file objects, `open` and the files on disk it
+//! opens, and `StringIO` and `BytesIO`, files kept in memory.
+//!
+//! The methods of `IOBase` are written in terms of the methods a file
+//! class defines, such as `readline` and `seek`, as in CPython, so that
+//! other file classes get them by deriving from it. A `StringIO` or
+//! `BytesIO` keeps its contents and position in its attributes; a position
+//! past the end is allowed, and writing there fills the gap with zeros.
+
+mod file;
+
+pub(crate) use self::file::{open, Files};
+
+use super::super::builtins::index_value;
+use super::super::bytes;
+use super::super::object::{Args, NativeFunction, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, module_error, new_native_class, new_native_module};
+
+/// The `whence` of `seek` for a position from the start, from the current
+/// position, and from the end.
+const SEEK_SET: i64 = 0;
+const SEEK_CUR: i64 = 1;
+const SEEK_END: i64 = 2;
+
+/// The size of the buffers of buffered files, as CPython reports it.
+const DEFAULT_BUFFER_SIZE: i64 = 8192;
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "io", &[("open", open)]);
+    for &(name, value) in &[
+        ("SEEK_SET", SEEK_SET),
+        ("SEEK_CUR", SEEK_CUR),
+        ("SEEK_END", SEEK_END),
+        ("DEFAULT_BUFFER_SIZE", DEFAULT_BUFFER_SIZE),
+    ] {
+        let value = vm.new_int(value);
+        add_attribute(vm, &module, name, value);
+    }
+    // `UnsupportedOperation` derives from both `OSError` and `ValueError`.
+    let namespace = vm.new_dict();
+    let module_name = vm.new_str("io");
+    vm.dict_set_str(namespace.as_dict().unwrap(), "__module__", module_name);
+    let bases = vec![
+        vm.exceptions.os_error.clone(),
+        vm.exceptions.value_error.clone(),
+    ];
+    let bases = vm.new_tuple(bases);
+    let name = vm.new_str("UnsupportedOperation");
+    let class = vm.types.type_.clone();
+    let unsupported = vm.call(&class, Args::new(vec![name, bases, namespace]))?;
+    add_attribute(vm, &module, "UnsupportedOperation", unsupported);
+
+    let object = vm.types.object.clone();
+    let io_base = new_native_class(
+        vm,
+        "io",
+        "IOBase",
+        &object,
+        &[
+            ("__enter__", enter),
+            ("__exit__", exit),
+            ("__iter__", iter),
+            ("__next__", next),
+            ("close", close),
+            ("fileno", fileno),
+            ("flush", flush),
+            ("isatty", isatty),
+            ("readable", readable),
+            ("readline", readline),
+            ("readlines", readlines),
+            ("seek", seek),
+            ("seekable", seekable),
+            ("tell", tell),
+            ("truncate", truncate),
+            ("writable", writable),
+            ("writelines", writelines),
+        ],
+    )?;
+    add_properties(vm, &io_base, &[("closed", closed)])?;
+    add_attribute(vm, &module, "IOBase", io_base.clone());
+    let raw_io_base = new_native_class(vm, "io", "RawIOBase", &io_base, &[])?;
+    add_attribute(vm, &module, "RawIOBase", raw_io_base);
+    let buffered_io_base = new_native_class(
+        vm,
+        "io",
+        "BufferedIOBase",
+        &io_base,
+        &[
+            ("detach", detach),
+            ("read", read),
+            ("read1", read1),
+            ("readinto", readinto),
+            ("write", write),
+        ],
+    )?;
+    add_attribute(vm, &module, "BufferedIOBase", buffered_io_base.clone());
+    let text_io_base = new_native_class(
+        vm,
+        "io",
+        "TextIOBase",
+        &io_base,
+        &[
+            ("detach", detach),
+            ("read", read),
+            ("readline", text_readline),
+            ("write", write),
+        ],
+    )?;
+    add_properties(
+        vm,
+        &text_io_base,
+        &[
+            ("encoding", no_attribute),
+            ("errors", no_attribute),
+            ("newlines", no_attribute),
+        ],
+    )?;
+    add_attribute(vm, &module, "TextIOBase", text_io_base.clone());
+    file::add_classes(vm, &module, &text_io_base, &buffered_io_base)?;
+
+    let string_io = new_native_class(
+        vm,
+        "_io",
+        "StringIO",
+        &text_io_base,
+        &[
+            ("__init__", string_init),
+            ("close", memory_close),
+            ("getvalue", string_getvalue),
+            ("read", string_read),
+            ("readable", memory_readable),
+            ("readline", string_readline),
+            ("seek", string_seek),
+            ("seekable", memory_readable),
+            ("tell", string_tell),
+            ("truncate", string_truncate),
+            ("writable", memory_readable),
+            ("write", string_write),
+        ],
+    )?;
+    add_properties(
+        vm,
+        &string_io,
+        &[
+            ("closed", memory_closed),
+            ("line_buffering", line_buffering),
+        ],
+    )?;
+    add_attribute(vm, &module, "StringIO", string_io);
+    let bytes_io = new_native_class(
+        vm,
+        "_io",
+        "BytesIO",
+        &buffered_io_base,
+        &[
+            ("__init__", bytes_init),
+            ("close", memory_close),
+            ("getvalue", bytes_getvalue),
+            ("read", bytes_read),
+            ("read1", bytes_read),
+            ("readable", bytes_readable),
+            ("readline", bytes_readline),
+            ("seek", bytes_seek),
+            ("seekable", bytes_readable),
+            ("tell", bytes_tell),
+            ("truncate", bytes_truncate),
+            ("writable", bytes_readable),
+            ("write", bytes_write),
+        ],
+    )?;
+    add_properties(vm, &bytes_io, &[("closed", memory_closed)])?;
+    add_attribute(vm, &module, "BytesIO", bytes_io);
+    Ok(module)
+}
+
+/// Adds read-only properties to a class.
+fn add_properties(
+    vm: &mut Vm,
+    class: &ObjectRef,
+    getters: &[(&'static str, NativeFunction)],
+) -> PyResult<()> {
+    for &(name, getter) in getters {
+        let getter = vm.new_builtin(name, getter);
+        let property = vm.types.property.clone();
+        let property = vm.call(&property, Args::new(vec![getter]))?;
+        vm.setattr(class, name, property)?;
+    }
+    Ok(())
+}
+
+/// The `self` of a method that takes no other arguments.
+fn this_argument(vm: &mut Vm, args: Args, method: &str) -> PyResult<ObjectRef> {
+    let values = bind_arguments(vm, args, method, &["self"], 1)?;
+    Ok(values[0].clone().unwrap())
+}
+
+/// An `io.UnsupportedOperation` for a method a file does not have.
+fn unsupported(vm: &mut Vm, method: &str) -> ObjectRef {
+    module_error(vm, "io", "UnsupportedOperation", method.to_string())
+}
+
+/// The error for an operation on a closed file. CPython words it with a
+/// full stop in some classes and without in others.
+fn closed_error(vm: &mut Vm, full_stop: bool) -> ObjectRef {
+    let message = if full_stop {
+        "I/O operation on closed file."
+    } else {
+        "I/O operation on closed file"
+    };
+    vm.new_value_error(message.to_string())
+}
+
+/// Fails if the file is closed, as its `closed` attribute says.
+fn check_closed(vm: &mut Vm, this: &ObjectRef) -> PyResult<()> {
+    let closed = vm.getattr(this, "closed")?;
+    if vm.is_true(&closed)? {
+        return Err(closed_error(vm, true));
+    }
+    Ok(())
+}
+
+/// Whether `IOBase.close` has closed the file.
+fn base_closed(this: &ObjectRef) -> bool {
+    this.dict()
+        .and_then(|dict| dict.as_dict().unwrap().borrow().get_str("__IOBase_closed"))
+        .is_some_and(|closed| matches!(closed.payload, Payload::Int(1)))
+}
+
+/// `IOBase.closed`.
+fn closed(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "closed")?;
+    Ok(vm.new_bool(base_closed(&this)))
+}
+
+/// `IOBase.close()`: flushes the file, the first time it is closed.
+fn close(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "close")?;
+    if !base_closed(&this) {
+        let flush = vm.getattr(&this, "flush")?;
+        let flushed = vm.call(&flush, Args::default());
+        let closed = vm.new_bool(true);
+        vm.setattr(&this, "__IOBase_closed", closed)?;
+        flushed?;
+    }
+    Ok(vm.none())
+}
+
+/// `IOBase.flush()`, which has nothing to write.
+fn flush(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "flush")?;
+    if base_closed(&this) {
+        return Err(closed_error(vm, true));
+    }
+    Ok(vm.none())
+}
+
+fn enter(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "__enter__")?;
+    check_closed(vm, &this)?;
+    Ok(this)
+}
+
+fn exit(vm: &mut Vm, mut args: Args) -> PyResult {
+    args.positional.truncate(1);
+    let this = this_argument(vm, args, "__exit__")?;
+    let close = vm.getattr(&this, "close")?;
+    vm.call(&close, Args::default())
+}
+
+fn iter(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "__iter__")?;
+    check_closed(vm, &this)?;
+    Ok(this)
+}
+
+/// `IOBase.__next__()`: the next line.
+fn next(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "__next__")?;
+    let readline = vm.getattr(&this, "readline")?;
+    let line = vm.call(&readline, Args::default())?;
+    if vm.len(&line)? == 0 {
+        let class = vm.exceptions.stop_iteration.clone();
+        return Err(vm.new_exception(&class, Vec::new()));
+    }
+    Ok(line)
+}
+
+fn fileno(vm: &mut Vm, args: Args) -> PyResult {
+    this_argument(vm, args, "fileno")?;
+    Err(unsupported(vm, "fileno"))
+}
+
+fn isatty(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "isatty")?;
+    check_closed(vm, &this)?;
+    Ok(vm.new_bool(false))
+}
+
+/// `IOBase.readable()`, and `seekable` and `writable`: false, unless a
+/// file class says otherwise.
+fn readable(vm: &mut Vm, args: Args) -> PyResult {
+    this_argument(vm, args, "readable")?;
+    Ok(vm.new_bool(false))
+}
+
+fn seekable(vm: &mut Vm, args: Args) -> PyResult {
+    this_argument(vm, args, "seekable")?;
+    Ok(vm.new_bool(false))
+}
+
+fn writable(vm: &mut Vm, args: Args) -> PyResult {
+    this_argument(vm, args, "writable")?;
+    Ok(vm.new_bool(false))
+}
+
+fn seek(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "seek", &["self", "offset", "whence"], 2)?;
+    Err(unsupported(vm, "seek"))
+}
+
+/// `IOBase.tell()`: `seek(0, SEEK_CUR)`.
+fn tell(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "tell")?;
+    let seek = vm.getattr(&this, "seek")?;
+    let (offset, whence) = (vm.new_int(0), vm.new_int(SEEK_CUR));
+    vm.call(&seek, Args::new(vec![offset, whence]))
+}
+
+fn truncate(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "truncate", &["self", "size"], 1)?;
+    Err(unsupported(vm, "truncate"))
+}
+
+/// `IOBase.readline(size=-1)`: the next line, read a byte at a time.
+fn readline(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readline", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let size = size_argument(vm, values[1].as_ref(), -1)?;
+    let read = vm.getattr(&this, "read")?;
+    let mut line = Vec::new();
+    while size < 0 || (line.len() as i64) < size {
+        let one = vm.new_int(1);
+        let byte = vm.call(&read, Args::new(vec![one]))?;
+        match byte.payload {
+            Payload::Bytes(ref data) if data.is_empty() => break,
+            Payload::Bytes(ref data) => line.extend_from_slice(data),
+            _ => {
+                let message = format!(
+                    "read() should have returned a bytes object, not '{}'",
+                    byte.type_name()
+                );
+                let class = vm.exceptions.os_error.clone();
+                return Err(vm.new_error(&class, message));
+            }
+        }
+        if line.last() == Some(&b'\n') {
+            break;
+        }
+    }
+    Ok(vm.new_bytes(line))
+}
+
+/// `IOBase.readlines(hint=-1)`: the rest of the lines, or lines until
+/// their length reaches `hint`.
+fn readlines(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readlines", &["self", "hint"], 1)?;
+    let this = values[0].clone().unwrap();
+    let hint = size_argument(vm, values[1].as_ref(), -1)?;
+    check_closed(vm, &this)?;
+    let readline = vm.getattr(&this, "readline")?;
+    let mut lines = Vec::new();
+    let mut total = 0;
+    loop {
+        let line = vm.call(&readline, Args::default())?;
+        let length = vm.len(&line)? as i64;
+        if length == 0 {
+            break;
+        }
+        lines.push(line);
+        total += length;
+        if hint > 0 && total >= hint {
+            break;
+        }
+    }
+    Ok(vm.new_list(lines))
+}
+
+/// `IOBase.writelines(lines)`: writes each line, adding no line endings.
+fn writelines(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "writelines", &["self", "lines"], 2)?;
+    let this = values[0].clone().unwrap();
+    check_closed(vm, &this)?;
+    let lines = vm.iterate(values[1].as_ref().unwrap())?;
+    let write = vm.getattr(&this, "write")?;
+    for line in lines {
+        vm.call(&write, Args::new(vec![line]))?;
+    }
+    Ok(vm.none())
+}
+
+/// The methods of `BufferedIOBase` and `TextIOBase` that a file class must
+/// define itself.
+fn detach(vm: &mut Vm, args: Args) -> PyResult {
+    this_argument(vm, args, "detach")?;
+    Err(unsupported(vm, "detach"))
+}
+
+fn read(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "read", &["self", "size"], 1)?;
+    Err(unsupported(vm, "read"))
+}
+
+fn read1(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "read1", &["self", "size"], 1)?;
+    Err(unsupported(vm, "read1"))
+}
+
+fn readinto(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "readinto", &["self", "buffer"], 2)?;
+    Err(unsupported(vm, "readinto"))
+}
+
+fn text_readline(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "readline", &["self", "size"], 1)?;
+    Err(unsupported(vm, "readline"))
+}
+
+fn write(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "write", &["self", "data"], 2)?;
+    Err(unsupported(vm, "write"))
+}
+
+/// `TextIOBase.encoding`, `errors` and `newlines`: `None` unless a file
+/// class knows them.
+fn no_attribute(vm: &mut Vm, args: Args) -> PyResult {
+    this_argument(vm, args, "encoding")?;
+    Ok(vm.none())
+}
+
+/// A size or position argument: an int, or `None` for `default`.
+fn size_argument(vm: &mut Vm, size: Option<&ObjectRef>, default: i64) -> PyResult<i64> {
+    match size {
+        None => Ok(default),
+        Some(size) if Vm::is(size, &vm.none()) => Ok(default),
+        Some(size) => match size.payload {
+            Payload::Int(size) => Ok(size),
+            _ => {
+                let message = format!(
+                    "argument should be integer or None, not '{}'",
+                    size.type_name()
+                );
+                Err(vm.new_type_error(message))
+            }
+        },
+    }
+}
+
+/// The position of a `StringIO` or `BytesIO`.
+fn position(vm: &mut Vm, this: &ObjectRef) -> PyResult<usize> {
+    let position = vm.getattr(this, "_position")?;
+    Ok(index_value(vm, &position)? as usize)
+}
+
+fn set_position(vm: &mut Vm, this: &ObjectRef, position: usize) -> PyResult<()> {
+    let position = vm.new_int(position as i64);
+    vm.setattr(this, "_position", position)
+}
+
+/// `StringIO.closed` and `BytesIO.closed`, which keep their own state
+/// rather than `IOBase`'s.
+fn memory_closed(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "closed")?;
+    vm.getattr(&this, "_closed")
+}
+
+/// `StringIO.close()` and `BytesIO.close()`: drops the contents.
+fn memory_close(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "close")?;
+    let closed = vm.new_bool(true);
+    vm.setattr(&this, "_closed", closed)?;
+    let none = vm.none();
+    vm.setattr(&this, "_buffer", none)?;
+    Ok(vm.none())
+}
+
+/// The contents of a `StringIO` or `BytesIO`, checking that it is open.
+fn contents(vm: &mut Vm, this: &ObjectRef, full_stop: bool) -> PyResult {
+    let closed = vm.getattr(this, "_closed")?;
+    if vm.is_true(&closed)? {
+        return Err(closed_error(vm, full_stop));
+    }
+    vm.getattr(this, "_buffer")
+}
+
+/// The text of a `StringIO`, as characters, checking that it is open.
+fn text(vm: &mut Vm, this: &ObjectRef) -> PyResult<Vec<char>> {
+    let buffer = contents(vm, this, false)?;
+    Ok(buffer.as_str().unwrap().chars().collect())
+}
+
+fn set_text(vm: &mut Vm, this: &ObjectRef, text: &[char]) -> PyResult<()> {
+    let text: String = text.iter().collect();
+    let text = vm.new_str(&text);
+    vm.setattr(this, "_buffer", text)
+}
+
+/// `StringIO.readable()`, and `seekable` and `writable`: true while the
+/// file is open.
+fn memory_readable(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "readable")?;
+    contents(vm, &this, false)?;
+    Ok(vm.new_bool(true))
+}
+
+fn line_buffering(vm: &mut Vm, args: Args) -> PyResult {
+    this_argument(vm, args, "line_buffering")?;
+    Ok(vm.new_bool(false))
+}
+
+/// `StringIO(initial_value='', newline='\n')`.
+fn string_init(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["self", "initial_value", "newline"];
+    let values = bind_arguments(vm, args, "StringIO", &names, 1)?;
+    let this = values[0].clone().unwrap();
+    let newline = match values[2] {
+        None => vm.new_str("\n"),
+        Some(ref newline) => match newline.payload {
+            Payload::None => newline.clone(),
+            Payload::Str(ref text) => {
+                if !["", "\n", "\r", "\r\n"].contains(&text.as_str()) {
+                    let message = format!("illegal newline value: {}", vm.repr(newline)?);
+                    return Err(vm.new_value_error(message));
+                }
+                newline.clone()
+            }
+            _ => {
+                let message = format!("newline must be str or None, not {}", newline.type_name());
+                return Err(vm.new_type_error(message));
+            }
+        },
+    };
+    let initial = match values[1] {
+        Some(ref value) if !Vm::is(value, &vm.none()) => match value.as_str() {
+            Some(text) => text.to_string(),
+            None => {
+                let message = format!(
+                    "initial_value must be str or None, not {}",
+                    value.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        },
+        _ => String::new(),
+    };
+    vm.setattr(&this, "_newline", newline.clone())?;
+    let initial = translate_newlines(&initial, newline.as_str());
+    let buffer = vm.new_str(&initial);
+    vm.setattr(&this, "_buffer", buffer)?;
+    let closed = vm.new_bool(false);
+    vm.setattr(&this, "_closed", closed)?;
+    set_position(vm, &this, 0)?;
+    Ok(vm.none())
+}
+
+/// Text as a `StringIO` with the newline mode `newline` stores it: with
+/// any line ending made `\n` for `None`, and `\n` made the line ending
+/// given for `\r` or `\r\n`.
+fn translate_newlines(text: &str, newline: Option<&str>) -> String {
+    match newline {
+        None => text.replace("\r\n", "\n").replace('\r', "\n"),
+        Some(ending @ "\r") | Some(ending @ "\r\n") => text.replace('\n', ending),
+        Some(_) => text.to_string(),
+    }
+}
+
+fn string_getvalue(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "getvalue")?;
+    contents(vm, &this, false)
+}
+
+/// `StringIO.read(size=-1)`.
+fn string_read(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let size = size_argument(vm, values[1].as_ref(), -1)?;
+    let text = text(vm, &this)?;
+    let start = position(vm, &this)?.min(text.len());
+    let end = if size < 0 {
+        text.len()
+    } else {
+        (start + size as usize).min(text.len())
+    };
+    if end > start {
+        set_position(vm, &this, end)?;
+    }
+    let taken: String = text[start..end].iter().collect();
+    Ok(vm.new_str(&taken))
+}
+
+/// `StringIO.readline(size=-1)`: the next line, ending where the newline
+/// mode says lines end.
+fn string_readline(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readline", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let size = size_argument(vm, values[1].as_ref(), -1)?;
+    let text = text(vm, &this)?;
+    let newline = vm.getattr(&this, "_newline")?;
+    let start = position(vm, &this)?.min(text.len());
+    let rest = &text[start..];
+    let line = match newline.as_str() {
+        Some("") => rest.iter().position(|&c| c == '\r' || c == '\n').map(|at| {
+            match (rest[at], rest.get(at + 1)) {
+                ('\r', Some('\n')) => at + 2,
+                _ => at + 1,
+            }
+        }),
+        Some("\r") => rest.iter().position(|&c| c == '\r').map(|at| at + 1),
+        Some("\r\n") => rest
+            .windows(2)
+            .position(|pair| pair == ['\r', '\n'])
+            .map(|at| at + 2),
+        _ => rest.iter().position(|&c| c == '\n').map(|at| at + 1),
+    };
+    let mut length = line.unwrap_or(rest.len());
+    if size >= 0 {
+        length = length.min(size as usize);
+    }
+    if start < text.len() {
+        set_position(vm, &this, start + length)?;
+    }
+    let taken: String = rest[..length].iter().collect();
+    Ok(vm.new_str(&taken))
+}
+
+/// `StringIO.write(s)`: the number of characters written.
+fn string_write(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "write", &["self", "s"], 2)?;
+    let this = values[0].clone().unwrap();
+    let written = values[1].clone().unwrap();
+    let written = match written.as_str() {
+        Some(written) => written.to_string(),
+        None => {
+            let message = format!("string argument expected, got '{}'", written.type_name());
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let mut text = text(vm, &this)?;
+    let newline = vm.getattr(&this, "_newline")?;
+    let stored: Vec<char> = translate_newlines(&written, newline.as_str())
+        .chars()
+        .collect();
+    let start = position(vm, &this)?;
+    if !stored.is_empty() {
+        if start > text.len() {
+            text.resize(start, '\0');
+        }
+        let end = (start + stored.len()).min(text.len());
+        text.splice(start..end, stored.iter().cloned());
+        set_text(vm, &this, &text)?;
+        set_position(vm, &this, start + stored.len())?;
+    }
+    Ok(vm.new_int(written.chars().count() as i64))
+}
+
+/// `StringIO.seek(pos, whence=0)`: only to the start or end, or to a
+/// position from the start, as for any text file.
+fn string_seek(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "seek", &["self", "pos", "whence"], 2)?;
+    let this = values[0].clone().unwrap();
+    let offset = index_value(vm, values[1].as_ref().unwrap())?;
+    let whence = match values[2] {
+        Some(ref whence) => index_value(vm, whence)?,
+        None => SEEK_SET,
+    };
+    let text = text(vm, &this)?;
+    if ![SEEK_SET, SEEK_CUR, SEEK_END].contains(&whence) {
+        let message = format!("Invalid whence ({}, should be 0, 1 or 2)", whence);
+        return Err(vm.new_value_error(message));
+    }
+    if offset < 0 && whence == SEEK_SET {
+        let message = format!("Negative seek position {}", offset);
+        return Err(vm.new_value_error(message));
+    }
+    if whence != SEEK_SET && offset != 0 {
+        let class = vm.exceptions.os_error.clone();
+        let message = "Can't do nonzero cur-relative seeks".to_string();
+        return Err(vm.new_error(&class, message));
+    }
+    let position = match whence {
+        SEEK_SET => offset as usize,
+        SEEK_CUR => position(vm, &this)?,
+        _ => text.len(),
+    };
+    set_position(vm, &this, position)?;
+    Ok(vm.new_int(position as i64))
+}
+
+fn string_tell(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "tell")?;
+    text(vm, &this)?;
+    let position = position(vm, &this)?;
+    Ok(vm.new_int(position as i64))
+}
+
+/// `StringIO.truncate(size=None)`: cuts the text to `size` characters, or
+/// at the position, which does not move.
+fn string_truncate(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "truncate", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let mut text = text(vm, &this)?;
+    let size = match values[1] {
+        Some(ref size) if !Vm::is(size, &vm.none()) => index_value(vm, size)?,
+        _ => position(vm, &this)? as i64,
+    };
+    if size < 0 {
+        let message = format!("Negative size value {}", size);
+        return Err(vm.new_value_error(message));
+    }
+    if (size as usize) < text.len() {
+        text.truncate(size as usize);
+        set_text(vm, &this, &text)?;
+    }
+    Ok(vm.new_int(size))
+}
+
+/// `BytesIO(initial_bytes=b'')`.
+fn bytes_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "BytesIO", &["self", "initial_bytes"], 1)?;
+    let this = values[0].clone().unwrap();
+    let initial = match values[1] {
+        Some(ref value) if !Vm::is(value, &vm.none()) => bytes_like(vm, value)?,
+        _ => Vec::new(),
+    };
+    let buffer = vm.new_bytes(initial);
+    vm.setattr(&this, "_buffer", buffer)?;
+    let closed = vm.new_bool(false);
+    vm.setattr(&this, "_closed", closed)?;
+    set_position(vm, &this, 0)?;
+    Ok(vm.none())
+}
+
+/// The data of a bytes-like argument.
+fn bytes_like(vm: &mut Vm, value: &ObjectRef) -> PyResult<Vec<u8>> {
+    match bytes::bytes_like(value) {
+        Some(data) => Ok(data.into_owned()),
+        None => {
+            let message = format!(
+                "a bytes-like object is required, not '{}'",
+                value.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// The data of a `BytesIO`, checking that it is open.
+fn data(vm: &mut Vm, this: &ObjectRef) -> PyResult<Vec<u8>> {
+    let buffer = contents(vm, this, true)?;
+    match buffer.payload {
+        Payload::Bytes(ref data) => Ok(data.clone()),
+        _ => unreachable!(),
+    }
+}
+
+fn bytes_getvalue(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "getvalue")?;
+    contents(vm, &this, true)
+}
+
+fn bytes_readable(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "readable")?;
+    contents(vm, &this, true)?;
+    Ok(vm.new_bool(true))
+}
+
+/// Takes bytes from the position of a `BytesIO`, as many as `length`
+/// gives of the rest of the data.
+fn take_bytes(vm: &mut Vm, this: &ObjectRef, length: impl Fn(&[u8]) -> usize) -> PyResult {
+    let data = data(vm, this)?;
+    let start = position(vm, this)?.min(data.len());
+    let end = start + length(&data[start..]);
+    if end > start {
+        set_position(vm, this, end)?;
+    }
+    Ok(vm.new_bytes(data[start..end].to_vec()))
+}
+
+/// `BytesIO.read(size=-1)`, and `read1`.
+fn bytes_read(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let size = size_argument(vm, values[1].as_ref(), -1)?;
+    take_bytes(vm, &this, |rest| {
+        if size < 0 {
+            rest.len()
+        } else {
+            rest.len().min(size as usize)
+        }
+    })
+}
+
+/// `BytesIO.readline(size=-1)`.
+fn bytes_readline(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readline", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let size = size_argument(vm, values[1].as_ref(), -1)?;
+    take_bytes(vm, &this, |rest| {
+        let line = rest
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(rest.len(), |at| at + 1);
+        if size < 0 {
+            line
+        } else {
+            line.min(size as usize)
+        }
+    })
+}
+
+/// `BytesIO.write(b)`: the number of bytes written.
+fn bytes_write(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "write", &["self", "b"], 2)?;
+    let this = values[0].clone().unwrap();
+    let mut data = data(vm, &this)?;
+    let written = bytes_like(vm, values[1].as_ref().unwrap())?;
+    let start = position(vm, &this)?;
+    if !written.is_empty() {
+        if start > data.len() {
+            data.resize(start, 0);
+        }
+        let end = (start + written.len()).min(data.len());
+        data.splice(start..end, written.iter().cloned());
+        let buffer = vm.new_bytes(data);
+        vm.setattr(&this, "_buffer", buffer)?;
+        set_position(vm, &this, start + written.len())?;
+    }
+    Ok(vm.new_int(written.len() as i64))
+}
+
+/// `BytesIO.seek(pos, whence=0)`: a position before the start is taken as
+/// the start.
+fn bytes_seek(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "seek", &["self", "pos", "whence"], 2)?;
+    let this = values[0].clone().unwrap();
+    let offset = index_value(vm, values[1].as_ref().unwrap())?;
+    let whence = match values[2] {
+        Some(ref whence) => index_value(vm, whence)?,
+        None => SEEK_SET,
+    };
+    let data = data(vm, &this)?;
+    if offset < 0 && whence == SEEK_SET {
+        let message = format!("negative seek value {}", offset);
+        return Err(vm.new_value_error(message));
+    }
+    let position = match whence {
+        SEEK_SET => offset,
+        SEEK_CUR => position(vm, &this)? as i64 + offset,
+        SEEK_END => data.len() as i64 + offset,
+        _ => {
+            let message = format!("invalid whence ({}, should be 0, 1 or 2)", whence);
+            return Err(vm.new_value_error(message));
+        }
+    };
+    let position = position.max(0) as usize;
+    set_position(vm, &this, position)?;
+    Ok(vm.new_int(position as i64))
+}
+
+fn bytes_tell(vm: &mut Vm, args: Args) -> PyResult {
+    let this = this_argument(vm, args, "tell")?;
+    data(vm, &this)?;
+    let position = position(vm, &this)?;
+    Ok(vm.new_int(position as i64))
+}
+
+/// `BytesIO.truncate(size=None)`.
+fn bytes_truncate(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "truncate", &["self", "size"], 1)?;
+    let this = values[0].clone().unwrap();
+    let mut data = data(vm, &this)?;
+    let size = match values[1] {
+        Some(ref size) if !Vm::is(size, &vm.none()) => index_value(vm, size)?,
+        _ => position(vm, &this)? as i64,
+    };
+    if size < 0 {
+        let message = format!("negative size value {}", size);
+        return Err(vm.new_value_error(message));
+    }
+    if (size as usize) < data.len() {
+        data.truncate(size as usize);
+        let buffer = vm.new_bytes(data);
+        vm.setattr(&this, "_buffer", buffer)?;
+    }
+    Ok(vm.new_int(size))
+}
@@ -0,0 +1,289 @@
+//! Modules of the standard library written in Rust. `import` finds them
+//! before searching `sys.path`, as CPython finds its builtin modules, and
+//! creates each the first time it is imported.
+
+mod base64;
+mod binascii;
+mod configparser;
+mod datetime;
+mod difflib;
+mod elementtree;
+mod future;
+mod gc;
+mod getpass;
+mod gzip;
+pub(super) mod io;
+mod os;
+mod reprlib;
+mod secrets;
+mod shlex;
+mod sitebuiltins;
+#[cfg(feature = "sqlite")]
+pub(super) mod sqlite3;
+mod statistics;
+pub(super) mod sys;
+mod textwrap;
+mod tomllib;
+pub(super) mod types;
+mod uuid;
+pub(super) mod warnings;
+mod zipfile;
+mod zlib;
+
+use std::fs::File;
+use std::io::{Error, Read};
+
+use super::bytes::{bytes_like, decode_utf8};
+use super::object::{Args, NativeFunction, ObjectRef, PyResult};
+use super::Vm;
+
+/// Creates a builtin module.
+type ModuleInit = fn(&mut Vm) -> PyResult<ObjectRef>;
+
+/// The builtin modules by name.
+const BUILTIN_MODULES: &[(&str, ModuleInit)] = &[
+    ("__future__", future::module),
+    ("_sitebuiltins", sitebuiltins::module),
+    ("base64", base64::module),
+    ("binascii", binascii::module),
+    ("configparser", configparser::module),
+    ("datetime", datetime::module),
+    ("difflib", difflib::module),
+    ("gc", gc::module),
+    ("getpass", getpass::module),
+    ("gzip", gzip::module),
+    ("io", io::module),
+    ("os", os::module),
+    ("os.path", os::path_module),
+    ("reprlib", reprlib::module),
+    ("secrets", secrets::module),
+    ("shlex", shlex::module),
+    #[cfg(feature = "sqlite")]
+    ("sqlite3", sqlite3::module),
+    ("statistics", statistics::module),
+    ("sys", sys::module),
+    ("textwrap", textwrap::module),
+    ("tomllib", tomllib::module),
+    ("types", types::module),
+    ("uuid", uuid::module),
+    ("warnings", warnings::module),
+    ("xml", elementtree::xml_package),
+    ("xml.etree", elementtree::etree_package),
+    ("xml.etree.ElementTree", elementtree::module),
+    ("zipfile", zipfile::module),
+    ("zlib", zlib::module),
+];
+
+/// The function that creates the builtin module `name`, if there is one.
+pub(super) fn builtin_module(name: &str) -> Option<ModuleInit> {
+    BUILTIN_MODULES
+        .iter()
+        .find(|&&(known, _)| known == name)
+        .map(|&(_, init)| init)
+}
+
+/// A new module named `name` with the native functions `functions`.
+fn new_native_module(
+    vm: &mut Vm,
+    name: &str,
+    functions: &[(&'static str, NativeFunction)],
+) -> ObjectRef {
+    let module = vm.new_module(name);
+    for &(function_name, function) in functions {
+        let function = vm.new_builtin(function_name, function);
+        vm.dict_set_str(
+            module.dict().unwrap().as_dict().unwrap(),
+            function_name,
+            function,
+        );
+    }
+    module
+}
+
+/// A new package named `name`, for builtin modules to be found in. Its
+/// `__path__` is empty, so that `import` looks for no others in it.
+fn new_native_package(vm: &mut Vm, name: &str) -> ObjectRef {
+    let package = vm.new_module(name);
+    let path = vm.new_list(Vec::new());
+    add_attribute(vm, &package, "__path__", path);
+    package
+}
+
+/// Sets the attribute `name` of a module being created.
+pub(super) fn add_attribute(vm: &mut Vm, module: &ObjectRef, name: &str, value: ObjectRef) {
+    vm.dict_set_str(module.dict().unwrap().as_dict().unwrap(), name, value);
+}
+
+/// A class of the module `module`, made as a `class` statement makes one,
+/// with the native functions `methods` as its methods. Their state is in
+/// the attributes of their instances, as it would be in Python.
+pub(super) fn new_native_class(
+    vm: &mut Vm,
+    module: &str,
+    name: &str,
+    base: &ObjectRef,
+    methods: &[(&'static str, NativeFunction)],
+) -> PyResult<ObjectRef> {
+    let namespace = vm.new_dict();
+    let module = vm.new_str(module);
+    vm.dict_set_str(namespace.as_dict().unwrap(), "__module__", module);
+    for &(method_name, method) in methods {
+        let method = vm.new_builtin(method_name, method);
+        vm.dict_set_str(namespace.as_dict().unwrap(), method_name, method);
+    }
+    let class = vm.types.type_.clone();
+    let name = vm.new_str(name);
+    let bases = vm.new_tuple(vec![base.clone()]);
+    vm.call(&class, Args::new(vec![name, bases, namespace]))
+}
+
+/// An instance of the exception class `class` of the builtin module
+/// `module`, to raise.
+fn module_error(vm: &mut Vm, module: &str, class: &str, message: String) -> ObjectRef {
+    let class = match vm.import_module(module) {
+        Ok(module) => match vm.getattr(&module, class) {
+            Ok(class) => class,
+            Err(error) => return error,
+        },
+        Err(error) => return error,
+    };
+    vm.new_error(&class, message)
+}
+
+/// An `OSError` for a failed operation on the file `filename`, of the
+/// subclass for its error number, worded as CPython words them.
+fn os_error(vm: &mut Vm, error: &Error, filename: &str) -> ObjectRef {
+    let text = error.to_string();
+    let class = vm.exceptions.os_error.clone();
+    let code = match error.raw_os_error() {
+        Some(code) => code,
+        None => return vm.new_error(&class, format!("{}: '{}'", text, filename)),
+    };
+    let suffix = format!(" (os error {})", code);
+    let reason = text.strip_suffix(&suffix).unwrap_or(&text);
+    let args = vec![
+        vm.new_int(i64::from(code)),
+        vm.new_str(reason),
+        vm.new_str(filename),
+    ];
+    match vm.call(&class, Args::new(args)) {
+        Ok(exception) => exception,
+        Err(error) => error,
+    }
+}
+
+/// Fills `bytes` from the operating system's source of randomness, as
+/// `os.urandom` does.
+fn urandom(bytes: &mut [u8]) -> Result<(), Error> {
+    File::open("/dev/urandom").and_then(|mut file| file.read_exact(bytes))
+}
+
+/// The arguments of a function with the parameters `names`, given by
+/// position or by name, of which the first `required` must be given. The
+/// errors are those of a function defined in Python, as the functions of
+/// these modules are in CPython.
+fn bind_arguments(
+    vm: &mut Vm,
+    args: Args,
+    function: &str,
+    names: &[&str],
+    required: usize,
+) -> PyResult<Vec<Option<ObjectRef>>> {
+    let given = args.positional.len();
+    if given > names.len() {
+        let expected = if required == names.len() {
+            names.len().to_string()
+        } else {
+            format!("from {} to {}", required, names.len())
+        };
+        let message = format!(
+            "{}() takes {} positional argument{} but {} {} given",
+            function,
+            expected,
+            if names.len() == 1 { "" } else { "s" },
+            given,
+            if given == 1 { "was" } else { "were" }
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let mut values: Vec<Option<ObjectRef>> = args.positional.into_iter().map(Some).collect();
+    values.resize(names.len(), None);
+    for (name, value) in args.keywords {
+        let index = match names.iter().position(|&known| known == name) {
+            Some(index) => index,
+            None => {
+                let message = format!(
+                    "{}() got an unexpected keyword argument '{}'",
+                    function, name
+                );
+                return Err(vm.new_type_error(message));
+            }
+        };
+        if values[index].is_some() {
+            let message = format!("{}() got multiple values for argument '{}'", function, name);
+            return Err(vm.new_type_error(message));
+        }
+        values[index] = Some(value);
+    }
+    let missing: Vec<String> = names[..required]
+        .iter()
+        .zip(&values)
+        .filter(|&(_, value)| value.is_none())
+        .map(|(name, _)| format!("'{}'", name))
+        .collect();
+    if !missing.is_empty() {
+        let list = match missing.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, [first])) => format!("{} and {}", first, last),
+            Some((last, rest)) => format!("{}, and {}", rest.join(", "), last),
+            None => unreachable!(),
+        };
+        let message = format!(
+            "{}() missing {} required positional argument{}: {}",
+            function,
+            missing.len(),
+            if missing.len() == 1 { "" } else { "s" },
+            list
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(values)
+}
+
+/// The data of a bytes-like argument.
+fn bytes_argument(vm: &mut Vm, value: &ObjectRef) -> PyResult<Vec<u8>> {
+    match bytes_like(value) {
+        Some(data) => Ok(data.into_owned()),
+        None => {
+            let message = format!(
+                "a bytes-like object is required, not '{}'",
+                value.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// `data` decoded as UTF-8, failing as CPython's decoder does.
+fn utf8_text(vm: &mut Vm, data: Vec<u8>) -> PyResult<String> {
+    match String::from_utf8(data) {
+        Ok(text) => Ok(text),
+        Err(error) => decode_utf8(vm, error.as_bytes(), "strict"),
+    }
+}
+
+/// The text of a string argument.
+fn str_argument(vm: &mut Vm, function: &str, name: &str, value: &ObjectRef) -> PyResult<String> {
+    match value.as_str() {
+        Some(text) => Ok(text.to_string()),
+        None => {
+            let message = format!(
+                "{}() argument '{}' must be str, not {}",
+                function,
+                name,
+                value.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
@@ -0,0 +1,347 @@
+//! `os` and `os.path`: a few operations on files and directories, and the
+//! manipulation of paths.
+//!
+//! `os.path` is `posixpath` on every system, so paths are taken apart at
+//! `/` only. Paths are strings; bytes paths are not supported.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, new_native_module, os_error};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        "os",
+        &[
+            ("getcwd", getcwd),
+            ("listdir", listdir),
+            ("mkdir", mkdir),
+            ("remove", remove),
+            ("rename", rename),
+            ("rmdir", rmdir),
+            ("unlink", remove),
+        ],
+    );
+    let name = if cfg!(windows) { "nt" } else { "posix" };
+    let linesep = if cfg!(windows) { "\r\n" } else { "\n" };
+    for (attribute, value) in [
+        ("name", name),
+        ("linesep", linesep),
+        ("sep", "/"),
+        ("curdir", "."),
+        ("pardir", ".."),
+        ("extsep", "."),
+    ] {
+        let value = vm.new_str(value);
+        add_attribute(vm, &module, attribute, value);
+    }
+    // `os.path` is made here rather than imported, as importing it would
+    // import `os` first.
+    let path = path_module(vm)?;
+    let modules = vm.modules().clone();
+    vm.dict_set_str(modules.as_dict().unwrap(), "os.path", path.clone());
+    add_attribute(vm, &module, "path", path);
+    Ok(module)
+}
+
+/// `os.path`.
+pub(super) fn path_module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        "posixpath",
+        &[
+            ("abspath", abspath),
+            ("basename", basename),
+            ("dirname", dirname),
+            ("exists", exists),
+            ("getsize", getsize),
+            ("isabs", isabs),
+            ("isdir", isdir),
+            ("isfile", isfile),
+            ("join", join),
+            ("normpath", normpath),
+            ("split", split),
+            ("splitext", splitext),
+        ],
+    );
+    for (attribute, value) in [
+        ("sep", "/"),
+        ("curdir", "."),
+        ("pardir", ".."),
+        ("extsep", "."),
+    ] {
+        let value = vm.new_str(value);
+        add_attribute(vm, &module, attribute, value);
+    }
+    Ok(module)
+}
+
+/// The text of a path argument.
+fn path_argument(vm: &mut Vm, value: &ObjectRef) -> PyResult<String> {
+    match value.as_str() {
+        Some(path) => Ok(path.to_string()),
+        None => {
+            let message = format!(
+                "expected str, bytes or os.PathLike object, not {}",
+                value.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// The one path argument of a function.
+fn one_path(vm: &mut Vm, args: Args, function: &str, name: &str) -> PyResult<String> {
+    let values = bind_arguments(vm, args, function, &[name], 1)?;
+    path_argument(vm, values[0].as_ref().unwrap())
+}
+
+/// The path argument of `exists`, `isfile`, `isdir` and `getsize`, which
+/// word their errors as `os.stat` does.
+fn stat_path(vm: &mut Vm, args: Args, function: &str) -> PyResult<String> {
+    let values = bind_arguments(vm, args, function, &["path"], 1)?;
+    let path = values[0].as_ref().unwrap();
+    match path.as_str() {
+        Some(path) => Ok(path.to_string()),
+        None => {
+            let message = format!(
+                "stat: path should be string, bytes, os.PathLike or integer, not {}",
+                path.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// `getcwd()`: the current directory.
+fn getcwd(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "getcwd", &[], 0)?;
+    match env::current_dir() {
+        Ok(directory) => Ok(vm.new_str(&directory.to_string_lossy())),
+        Err(error) => Err(os_error(vm, &error, ".")),
+    }
+}
+
+/// `listdir(path='.')`: the names of the entries of a directory, in no
+/// particular order.
+fn listdir(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "listdir", &["path"], 0)?;
+    let path = match values[0] {
+        Some(ref path) if !Vm::is(path, &vm.none()) => path_argument(vm, path)?,
+        _ => ".".to_string(),
+    };
+    let entries = match fs::read_dir(&path) {
+        Ok(entries) => entries,
+        Err(error) => return Err(os_error(vm, &error, &path)),
+    };
+    let mut names = Vec::new();
+    for entry in entries {
+        match entry {
+            Ok(entry) => names.push(vm.new_str(&entry.file_name().to_string_lossy())),
+            Err(error) => return Err(os_error(vm, &error, &path)),
+        }
+    }
+    Ok(vm.new_list(names))
+}
+
+/// `mkdir(path, mode=0o777)`. The mode is not applied.
+fn mkdir(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "mkdir", &["path", "mode"], 1)?;
+    let path = path_argument(vm, values[0].as_ref().unwrap())?;
+    match fs::create_dir(&path) {
+        Ok(()) => Ok(vm.none()),
+        Err(error) => Err(os_error(vm, &error, &path)),
+    }
+}
+
+/// `remove(path)`, and `unlink`.
+fn remove(vm: &mut Vm, args: Args) -> PyResult {
+    let path = one_path(vm, args, "remove", "path")?;
+    match fs::remove_file(&path) {
+        Ok(()) => Ok(vm.none()),
+        Err(error) => Err(os_error(vm, &error, &path)),
+    }
+}
+
+fn rmdir(vm: &mut Vm, args: Args) -> PyResult {
+    let path = one_path(vm, args, "rmdir", "path")?;
+    match fs::remove_dir(&path) {
+        Ok(()) => Ok(vm.none()),
+        Err(error) => Err(os_error(vm, &error, &path)),
+    }
+}
+
+/// `rename(src, dst)`.
+fn rename(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "rename", &["src", "dst"], 2)?;
+    let source = path_argument(vm, values[0].as_ref().unwrap())?;
+    let destination = path_argument(vm, values[1].as_ref().unwrap())?;
+    match fs::rename(&source, &destination) {
+        Ok(()) => Ok(vm.none()),
+        Err(error) => Err(os_error(vm, &error, &source)),
+    }
+}
+
+/// `exists(path)`: whether the path names anything.
+fn exists(vm: &mut Vm, args: Args) -> PyResult {
+    let path = stat_path(vm, args, "exists")?;
+    Ok(vm.new_bool(fs::metadata(path).is_ok()))
+}
+
+fn isfile(vm: &mut Vm, args: Args) -> PyResult {
+    let path = stat_path(vm, args, "isfile")?;
+    Ok(vm.new_bool(Path::new(&path).is_file()))
+}
+
+fn isdir(vm: &mut Vm, args: Args) -> PyResult {
+    let path = stat_path(vm, args, "isdir")?;
+    Ok(vm.new_bool(Path::new(&path).is_dir()))
+}
+
+/// `getsize(filename)`: the size of a file in bytes.
+fn getsize(vm: &mut Vm, args: Args) -> PyResult {
+    let path = stat_path(vm, args, "getsize")?;
+    match fs::metadata(&path) {
+        Ok(metadata) => Ok(vm.new_int(metadata.len() as i64)),
+        Err(error) => Err(os_error(vm, &error, &path)),
+    }
+}
+
+fn isabs(vm: &mut Vm, args: Args) -> PyResult {
+    let path = one_path(vm, args, "isabs", "s")?;
+    Ok(vm.new_bool(path.starts_with('/')))
+}
+
+/// `join(a, *p)`: the paths joined with `/`, starting again at any that is
+/// absolute.
+fn join(vm: &mut Vm, args: Args) -> PyResult {
+    if !args.keywords.is_empty() {
+        let message = "join() takes no keyword arguments".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    if args.positional.is_empty() {
+        let message = "join() missing 1 required positional argument: 'a'".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    let mut joined = String::new();
+    for part in &args.positional {
+        let part = match part.payload {
+            Payload::Str(ref part) => part,
+            _ => {
+                let message = format!(
+                    "join() argument must be str, bytes, or os.PathLike object, not '{}'",
+                    part.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        };
+        if part.starts_with('/') {
+            joined.clear();
+        } else if !joined.is_empty() && !joined.ends_with('/') {
+            joined.push('/');
+        }
+        joined.push_str(part);
+    }
+    Ok(vm.new_str(&joined))
+}
+
+/// A path split before its last component. The directory keeps no
+/// trailing slashes unless it is only slashes.
+fn split_path(path: &str) -> (&str, &str) {
+    let at = path.rfind('/').map_or(0, |at| at + 1);
+    let (head, tail) = path.split_at(at);
+    if head.trim_end_matches('/').is_empty() {
+        (head, tail)
+    } else {
+        (head.trim_end_matches('/'), tail)
+    }
+}
+
+fn split(vm: &mut Vm, args: Args) -> PyResult {
+    let path = one_path(vm, args, "split", "p")?;
+    let (head, tail) = split_path(&path);
+    let items = vec![vm.new_str(head), vm.new_str(tail)];
+    Ok(vm.new_tuple(items))
+}
+
+fn basename(vm: &mut Vm, args: Args) -> PyResult {
+    let path = one_path(vm, args, "basename", "p")?;
+    Ok(vm.new_str(split_path(&path).1))
+}
+
+fn dirname(vm: &mut Vm, args: Args) -> PyResult {
+    let path = one_path(vm, args, "dirname", "p")?;
+    Ok(vm.new_str(split_path(&path).0))
+}
+
+/// `splitext(p)`: the path and its extension, which starts at the last dot
+/// of the last component unless only dots come before it.
+fn splitext(vm: &mut Vm, args: Args) -> PyResult {
+    let path = one_path(vm, args, "splitext", "p")?;
+    let name_start = path.rfind('/').map_or(0, |at| at + 1);
+    let (root, extension) = match path.rfind('.') {
+        Some(dot) if dot > name_start && path[name_start..dot].chars().any(|c| c != '.') => {
+            path.split_at(dot)
+        }
+        _ => (path.as_str(), ""),
+    };
+    let items = vec![vm.new_str(root), vm.new_str(extension)];
+    Ok(vm.new_tuple(items))
+}
+
+/// A path with `.` components, `..` components that follow a directory,
+/// and repeated slashes removed, as `posixpath.normpath` makes it.
+fn normalize(path: &str) -> String {
+    if path.is_empty() {
+        return ".".to_string();
+    }
+    let slashes = if path.starts_with("//") && !path.starts_with("///") {
+        2
+    } else if path.starts_with('/') {
+        1
+    } else {
+        0
+    };
+    let mut components: Vec<&str> = Vec::new();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." if slashes == 0 && components.last().is_none_or(|&last| last == "..") => {
+                components.push(component)
+            }
+            ".." => {
+                components.pop();
+            }
+            _ => components.push(component),
+        }
+    }
+    let normalized = "/".repeat(slashes) + &components.join("/");
+    if normalized.is_empty() {
+        ".".to_string()
+    } else {
+        normalized
+    }
+}
+
+fn normpath(vm: &mut Vm, args: Args) -> PyResult {
+    let path = one_path(vm, args, "normpath", "path")?;
+    Ok(vm.new_str(&normalize(&path)))
+}
+
+/// `abspath(path)`: the path from the root, normalized.
+fn abspath(vm: &mut Vm, args: Args) -> PyResult {
+    let path = one_path(vm, args, "abspath", "path")?;
+    let path = if path.starts_with('/') {
+        path
+    } else {
+        match env::current_dir() {
+            Ok(directory) => format!("{}/{}", directory.to_string_lossy(), path),
+            Err(error) => return Err(os_error(vm, &error, ".")),
+        }
+    };
+    Ok(vm.new_str(&normalize(&path)))
+}
@@ -0,0 +1,77 @@
+//! `reprlib`: reprs cut down to a size, with the limits kept in the
+//! attributes of a `Repr`, for showing values whose full repr could be
+//! huge, as at the interactive prompt through `sys.displayhook`.
+
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, PyResult};
+use super::super::repr::ReprLimits;
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, new_native_class, new_native_module};
+
+/// The attributes of a `Repr`, with the defaults `__init__` gives them.
+const LIMITS: [(&str, usize); 9] = [
+    ("maxlevel", 6),
+    ("maxtuple", 6),
+    ("maxlist", 6),
+    ("maxdict", 4),
+    ("maxset", 6),
+    ("maxfrozenset", 6),
+    ("maxstring", 30),
+    ("maxlong", 40),
+    ("maxother", 30),
+];
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "reprlib", &[]);
+    let object = vm.types.object.clone();
+    let class = new_native_class(
+        vm,
+        "reprlib",
+        "Repr",
+        &object,
+        &[("__init__", init), ("repr", repr)],
+    )?;
+    add_attribute(vm, &module, "Repr", class.clone());
+    let shared = vm.call(&class, Args::default())?;
+    let repr = vm.getattr(&shared, "repr")?;
+    add_attribute(vm, &module, "aRepr", shared);
+    add_attribute(vm, &module, "repr", repr);
+    Ok(module)
+}
+
+/// `Repr()`: the default limits, each an attribute to change.
+fn init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "Repr.__init__", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    for &(name, limit) in &LIMITS {
+        let limit = vm.new_int(limit as i64);
+        vm.setattr(&this, name, limit)?;
+    }
+    Ok(vm.none())
+}
+
+/// `Repr.repr(x)`: the repr of `x` within the limits of the `Repr`.
+fn repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "Repr.repr", &["self", "x"], 2)?;
+    let this = values[0].clone().unwrap();
+    let mut limits = [0; 9];
+    for (limit, &(name, _)) in limits.iter_mut().zip(&LIMITS) {
+        let value = vm.getattr(&this, name)?;
+        *limit = index_value(vm, &value)?.max(0) as usize;
+    }
+    let [max_level, max_tuple, max_list, max_dict, max_set, max_frozenset, max_string, max_long, max_other] =
+        limits;
+    let limits = ReprLimits {
+        max_level,
+        max_tuple,
+        max_list,
+        max_dict,
+        max_set,
+        max_frozenset,
+        max_string,
+        max_long,
+        max_other,
+    };
+    let text = vm.limited_repr(values[1].as_ref().unwrap(), &limits)?;
+    Ok(vm.new_str(&text))
+}
@@ -0,0 +1,191 @@
+//! `secrets`: random tokens and choices for security, from the operating
+//! system's source of randomness rather than a seeded generator.
+
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::binascii::{encode_base64, encode_hex};
+use super::{add_attribute, bind_arguments, new_native_module, os_error, urandom};
+
+/// The number of bytes in a token when none is given.
+const DEFAULT_ENTROPY: usize = 32;
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        "secrets",
+        &[
+            ("choice", choice),
+            ("compare_digest", compare_digest),
+            ("randbelow", randbelow),
+            ("token_bytes", token_bytes),
+            ("token_hex", token_hex),
+            ("token_urlsafe", token_urlsafe),
+        ],
+    );
+    // `randbits` is `SystemRandom().getrandbits` in CPython.
+    let randbits = vm.new_builtin("getrandbits", getrandbits);
+    add_attribute(vm, &module, "randbits", randbits);
+    let entropy = vm.new_int(DEFAULT_ENTROPY as i64);
+    add_attribute(vm, &module, "DEFAULT_ENTROPY", entropy);
+    Ok(module)
+}
+
+/// `count` random bytes.
+fn random_bytes(vm: &mut Vm, count: usize) -> PyResult<Vec<u8>> {
+    let mut bytes = vec![0; count];
+    match urandom(&mut bytes) {
+        Ok(()) => Ok(bytes),
+        Err(error) => Err(os_error(vm, &error, "/dev/urandom")),
+    }
+}
+
+/// A random number of `bits` bits, below 2**63.
+fn random_bits(vm: &mut Vm, bits: u32) -> PyResult<i64> {
+    if bits == 0 {
+        return Ok(0);
+    }
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&random_bytes(vm, 8)?);
+    Ok((u64::from_le_bytes(bytes) >> (64 - bits)) as i64)
+}
+
+/// A random number in `0..bound`, with `bound` positive, drawn as CPython
+/// draws it: numbers of as many bits as `bound` until one is below it.
+fn random_below(vm: &mut Vm, bound: i64) -> PyResult<i64> {
+    let bits = 64 - bound.leading_zeros();
+    loop {
+        let value = random_bits(vm, bits)?;
+        if value < bound {
+            return Ok(value);
+        }
+    }
+}
+
+/// The size of a token, `DEFAULT_ENTROPY` for `None`.
+fn token_size(vm: &mut Vm, value: &Option<ObjectRef>) -> PyResult<usize> {
+    let size = match *value {
+        Some(ref value) if !matches!(value.payload, Payload::None) => index_value(vm, value)?,
+        _ => return Ok(DEFAULT_ENTROPY),
+    };
+    if size < 0 {
+        return Err(vm.new_value_error("negative argument not allowed".to_string()));
+    }
+    Ok(size as usize)
+}
+
+/// `token_bytes(nbytes=None)`.
+fn token_bytes(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "token_bytes", &["nbytes"], 0)?;
+    let size = token_size(vm, &values[0])?;
+    let bytes = random_bytes(vm, size)?;
+    Ok(vm.new_bytes(bytes))
+}
+
+/// `token_hex(nbytes=None)`: random bytes in hexadecimal.
+fn token_hex(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "token_hex", &["nbytes"], 0)?;
+    let size = token_size(vm, &values[0])?;
+    let bytes = random_bytes(vm, size)?;
+    let text = String::from_utf8(encode_hex(&bytes)).unwrap();
+    Ok(vm.new_str(&text))
+}
+
+/// `token_urlsafe(nbytes=None)`: random bytes in URL-safe base64,
+/// without padding.
+fn token_urlsafe(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "token_urlsafe", &["nbytes"], 0)?;
+    let size = token_size(vm, &values[0])?;
+    let bytes = random_bytes(vm, size)?;
+    let text: String = encode_base64(&bytes, false)
+        .into_iter()
+        .filter(|&byte| byte != b'=')
+        .map(|byte| match byte {
+            b'+' => '-',
+            b'/' => '_',
+            byte => char::from(byte),
+        })
+        .collect();
+    Ok(vm.new_str(&text))
+}
+
+/// `randbelow(exclusive_upper_bound)`.
+fn randbelow(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "randbelow", &["exclusive_upper_bound"], 1)?;
+    let bound = index_value(vm, values[0].as_ref().unwrap())?;
+    if bound <= 0 {
+        return Err(vm.new_value_error("Upper bound must be positive.".to_string()));
+    }
+    let value = random_below(vm, bound)?;
+    Ok(vm.new_int(value))
+}
+
+/// `randbits(k)`: a random number of `k` bits.
+fn getrandbits(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "getrandbits", &["k"], 1)?;
+    let bits = index_value(vm, values[0].as_ref().unwrap())?;
+    if bits < 0 {
+        return Err(vm.new_value_error("number of bits must be non-negative".to_string()));
+    }
+    if bits > 63 {
+        return Err(vm.int_overflow());
+    }
+    let value = random_bits(vm, bits as u32)?;
+    Ok(vm.new_int(value))
+}
+
+/// `choice(seq)`: a random item of a sequence.
+fn choice(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "choice", &["seq"], 1)?;
+    let sequence = values[0].as_ref().unwrap();
+    let length = vm.len(sequence)?;
+    if length == 0 {
+        let message = "Cannot choose from an empty sequence".to_string();
+        return Err(vm.new_index_error(message));
+    }
+    let index = random_below(vm, length as i64)?;
+    let index = vm.new_int(index);
+    vm.getitem(sequence, &index)
+}
+
+/// `compare_digest(a, b)`: whether two strings or bytes are equal, taking
+/// as long to find out wherever they differ.
+fn compare_digest(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "compare_digest", &["a", "b"], 2)?;
+    let (a, b) = (values[0].as_ref().unwrap(), values[1].as_ref().unwrap());
+    let (a, b) = match (&a.payload, &b.payload) {
+        (Payload::Bytes(a), Payload::Bytes(b)) => (a.clone(), b.clone()),
+        (Payload::Str(_), Payload::Str(_)) => {
+            let (a, b) = (a.as_str().unwrap(), b.as_str().unwrap());
+            if !a.is_ascii() || !b.is_ascii() {
+                let message = "comparing strings with non-ASCII characters is not supported";
+                return Err(vm.new_type_error(message.to_string()));
+            }
+            (a.as_bytes().to_vec(), b.as_bytes().to_vec())
+        }
+        (Payload::Str(_), Payload::Bytes(_)) | (Payload::Bytes(_), Payload::Str(_)) => {
+            let text = if a.as_str().is_some() { a } else { b };
+            let message = format!(
+                "a bytes-like object is required, not '{}'",
+                text.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+        _ => {
+            let message = format!(
+                "unsupported operand types(s) or combination of types: '{}' and '{}'",
+                a.type_name(),
+                b.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    // Every byte is compared, so that the time taken does not tell where
+    // the first difference is.
+    let mut difference = (a.len() != b.len()) as u8;
+    let other = if a.len() == b.len() { &b } else { &a };
+    for (x, y) in a.iter().zip(other) {
+        difference |= x ^ y;
+    }
+    Ok(vm.new_bool(difference == 0))
+}
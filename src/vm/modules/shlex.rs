@@ -0,0 +1,163 @@
+//! `shlex`: splitting and quoting command lines as a POSIX shell does.
+
+use super::super::object::{Args, ObjectRef, PyResult};
+use super::super::Vm;
+use super::{bind_arguments, new_native_module, str_argument};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    Ok(new_native_module(
+        vm,
+        "shlex",
+        &[("join", join), ("quote", quote), ("split", split)],
+    ))
+}
+
+const WHITESPACE: &str = " \t\r\n";
+
+/// What `split` is reading.
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    /// Whitespace between words.
+    Between,
+    /// A word outside quotes.
+    Word,
+    /// Inside the quote `'` or `"`.
+    Quoted(char),
+    /// After a backslash, in the state to return to.
+    Escaped(EscapedFrom),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum EscapedFrom {
+    Word,
+    DoubleQuoted,
+}
+
+/// Splits `text` into words with the rules of a POSIX shell, as `shlex`
+/// does with `posix` and `whitespace_split` set. With `comments`, a `#`
+/// starts a comment that runs to the end of the line.
+fn split_words(text: &str, comments: bool) -> Result<Vec<String>, &'static str> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    // Whether the word had quotes, which makes it a word even if empty.
+    let mut quoted = false;
+    let mut state = State::Between;
+    let mut chars = text.chars();
+    loop {
+        let c = chars.next();
+        match state {
+            State::Between | State::Word => match c {
+                None => break,
+                Some(c) if WHITESPACE.contains(c) => {
+                    if state == State::Word || quoted {
+                        words.push(::std::mem::take(&mut word));
+                        quoted = false;
+                    }
+                    state = State::Between;
+                }
+                Some('#') if comments => {
+                    for c in chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                    if state == State::Word || quoted {
+                        words.push(::std::mem::take(&mut word));
+                        quoted = false;
+                    }
+                    state = State::Between;
+                }
+                Some('\\') => state = State::Escaped(EscapedFrom::Word),
+                Some(quote @ '\'') | Some(quote @ '"') => state = State::Quoted(quote),
+                Some(c) => {
+                    word.push(c);
+                    state = State::Word;
+                }
+            },
+            State::Quoted(quote) => {
+                quoted = true;
+                match c {
+                    None => return Err("No closing quotation"),
+                    Some(c) if c == quote => state = State::Word,
+                    Some('\\') if quote == '"' => state = State::Escaped(EscapedFrom::DoubleQuoted),
+                    Some(c) => word.push(c),
+                }
+            }
+            State::Escaped(from) => {
+                let c = match c {
+                    Some(c) => c,
+                    None => return Err("No escaped character"),
+                };
+                // Within double quotes, a backslash escapes only itself and
+                // the quote.
+                if from == EscapedFrom::DoubleQuoted && c != '\\' && c != '"' {
+                    word.push('\\');
+                }
+                word.push(c);
+                state = match from {
+                    EscapedFrom::Word => State::Word,
+                    EscapedFrom::DoubleQuoted => State::Quoted('"'),
+                };
+            }
+        }
+    }
+    if state == State::Word || quoted {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+/// `shlex.split(s, comments=False, posix=True)`.
+fn split(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "split", &["s", "comments", "posix"], 1)?;
+    let text = str_argument(vm, "split", "s", values[0].as_ref().unwrap())?;
+    let comments = match values[1] {
+        Some(ref comments) => vm.is_true(comments)?,
+        None => false,
+    };
+    if let Some(ref posix) = values[2] {
+        if !vm.is_true(posix)? {
+            let class = vm.exceptions.not_implemented_error.clone();
+            let message = "shlex.split() supports only POSIX rules".to_string();
+            return Err(vm.new_error(&class, message));
+        }
+    }
+    match split_words(&text, comments) {
+        Ok(words) => {
+            let words = words.iter().map(|word| vm.new_str(word)).collect();
+            Ok(vm.new_list(words))
+        }
+        Err(message) => Err(vm.new_value_error(message.to_string())),
+    }
+}
+
+/// Quotes `text` for a POSIX shell, unless it is made only of characters
+/// that need no quoting.
+fn quote_word(text: &str) -> String {
+    if text.is_empty() {
+        return "''".to_string();
+    }
+    let safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+    if text.chars().all(safe) {
+        return text.to_string();
+    }
+    format!("'{}'", text.replace('\'', "'\"'\"'"))
+}
+
+/// `shlex.quote(s)`.
+fn quote(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "quote", &["s"], 1)?;
+    let text = str_argument(vm, "quote", "s", values[0].as_ref().unwrap())?;
+    Ok(vm.new_str(&quote_word(&text)))
+}
+
+/// `shlex.join(split_command)`.
+fn join(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "join", &["split_command"], 1)?;
+    let mut words = Vec::new();
+    for word in vm.iterate(values[0].as_ref().unwrap())? {
+        let word = str_argument(vm, "join", "split_command", &word)?;
+        words.push(quote_word(&word));
+    }
+    Ok(vm.new_str(&words.join(" ")))
+}
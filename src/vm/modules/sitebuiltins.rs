@@ -0,0 +1,101 @@
+//! `_sitebuiltins`: the classes of `exit`, `quit` and `help`, which the
+//! interpreter adds to the builtins namespace as CPython's `site` does.
+
+use super::super::modules::sys::write_standard;
+use super::super::object::{Args, ObjectRef, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, new_native_class};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = vm.new_module("_sitebuiltins");
+    let object = vm.types.object.clone();
+    let quitter = new_native_class(
+        vm,
+        "_sitebuiltins",
+        "Quitter",
+        &object,
+        &[
+            ("__call__", quitter_call),
+            ("__init__", quitter_init),
+            ("__repr__", quitter_repr),
+        ],
+    )?;
+    add_attribute(vm, &module, "Quitter", quitter);
+    let helper = new_native_class(
+        vm,
+        "_sitebuiltins",
+        "_Helper",
+        &object,
+        &[("__call__", helper_call), ("__repr__", helper_repr)],
+    )?;
+    add_attribute(vm, &module, "_Helper", helper);
+    Ok(module)
+}
+
+/// `Quitter(name, eof)`, the class of `exit` and `quit`.
+fn quitter_init(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "Quitter", 3, 3)?;
+    let this = &args.positional[0];
+    vm.setattr(this, "name", args.positional[1].clone())?;
+    vm.setattr(this, "eof", args.positional[2].clone())?;
+    Ok(vm.none())
+}
+
+fn quitter_repr(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__repr__", 1, 1)?;
+    let this = &args.positional[0];
+    let name = vm.getattr(this, "name")?;
+    let name = vm.str(&name)?;
+    let eof = vm.getattr(this, "eof")?;
+    let eof = vm.str(&eof)?;
+    Ok(vm.new_str(&format!("Use {}() or {} to exit", name, eof)))
+}
+
+/// `exit(code=None)`: raises `SystemExit`.
+fn quitter_call(vm: &mut Vm, mut args: Args) -> PyResult {
+    if args.positional.is_empty() {
+        return Err(vm.new_type_error("Quitter.__call__ needs an argument".to_string()));
+    }
+    args.positional.remove(0);
+    let values = bind_arguments(vm, args, "__call__", &["code"], 0)?;
+    let args = values[0].iter().cloned().collect();
+    let class = vm.exceptions.system_exit.clone();
+    Err(vm.new_exception(&class, args))
+}
+
+const HELPER_REPR: &str =
+    "Type help() for interactive help, or help(object) for help about object.";
+
+fn helper_repr(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "__repr__", 1, 1)?;
+    Ok(vm.new_str(HELPER_REPR))
+}
+
+/// `help(request)`: prints the docstring of `request`. The interactive
+/// help utility, which `help()` starts in CPython, is not supported.
+fn helper_call(vm: &mut Vm, mut args: Args) -> PyResult {
+    if args.positional.is_empty() {
+        return Err(vm.new_type_error("_Helper.__call__ needs an argument".to_string()));
+    }
+    args.positional.remove(0);
+    let values = bind_arguments(vm, args, "__call__", &["request"], 0)?;
+    let request = match values[0] {
+        Some(ref request) => request.clone(),
+        None => {
+            let text = "The interactive help utility is not supported.\n";
+            write_standard(vm, "stdout", &[text], false)?;
+            return Ok(vm.none());
+        }
+    };
+    let name = match vm.getattr(&request, "__qualname__") {
+        Ok(name) => vm.str(&name)?,
+        Err(_) => request.type_name().to_string(),
+    };
+    let doc = match vm.getattr(&request, "__doc__") {
+        Ok(doc) if !Vm::is(&doc, &vm.none()) => vm.str(&doc)?,
+        _ => format!("No Python documentation found for '{}'.", name),
+    };
+    let text = format!("Help on {}:\n\n{}\n\n", name, doc);
+    write_standard(vm, "stdout", &[&text], false)?;
+    Ok(vm.none())
+}
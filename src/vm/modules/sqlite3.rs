@@ -0,0 +1,851 @@
+//! `sqlite3`: the DB-API interface to SQLite, built in through rusqlite
+//! behind the `sqlite` feature.
+//!
+//! The SQLite connections of a VM are kept in its `Connections`, and a
+//! `Connection` object holds the handle of its own in `_handle`. Closing
+//! the connection drops it. A cursor runs each statement to completion
+//! when it is executed and keeps the rows it returned in `_rows` for the
+//! `fetch` methods, rather than stepping through them as they are
+//! fetched.
+//!
+//! Transactions are handled as they are by default in CPython 3.11: a
+//! `BEGIN` is run before an `INSERT`, `UPDATE`, `DELETE` or `REPLACE`
+//! outside a transaction, unless `isolation_level` is `None`, and is ended
+//! by `commit` and `rollback`.
+
+use std::collections::HashMap;
+
+use rusqlite::types::Value;
+use rusqlite::{ffi, Batch, Connection};
+
+use super::super::object::{Args, NativeFunction, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, module_error, new_native_class, new_native_module};
+
+/// The open connections of a VM, by handle.
+#[derive(Default)]
+pub(crate) struct Connections {
+    open: HashMap<i64, Connection>,
+    next_handle: i64,
+}
+
+/// The exception classes of the module, each with its base.
+const ERRORS: [(&str, &str); 10] = [
+    ("Warning", "Exception"),
+    ("Error", "Exception"),
+    ("InterfaceError", "Error"),
+    ("DatabaseError", "Error"),
+    ("DataError", "DatabaseError"),
+    ("OperationalError", "DatabaseError"),
+    ("IntegrityError", "DatabaseError"),
+    ("InternalError", "DatabaseError"),
+    ("ProgrammingError", "DatabaseError"),
+    ("NotSupportedError", "DatabaseError"),
+];
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "sqlite3", &[("connect", connect)]);
+    for &(name, base) in &ERRORS {
+        let base = match base {
+            "Exception" => vm.exceptions.exception.clone(),
+            base => vm.getattr(&module, base)?,
+        };
+        let class = new_native_class(vm, "sqlite3", name, &base, &[])?;
+        add_attribute(vm, &module, name, class);
+    }
+    let object = vm.types.object.clone();
+    let connection = new_native_class(
+        vm,
+        "sqlite3",
+        "Connection",
+        &object,
+        &[
+            ("__enter__", connection_enter),
+            ("__exit__", connection_exit),
+            ("__init__", connection_init),
+            ("close", connection_close),
+            ("commit", connection_commit),
+            ("cursor", connection_cursor),
+            ("execute", connection_execute),
+            ("executemany", connection_executemany),
+            ("executescript", connection_executescript),
+            ("rollback", connection_rollback),
+        ],
+    )?;
+    let properties: [(&'static str, NativeFunction); 2] = [
+        ("in_transaction", connection_in_transaction),
+        ("total_changes", connection_total_changes),
+    ];
+    for &(name, getter) in &properties {
+        let getter = vm.new_builtin(name, getter);
+        let property = vm.types.property.clone();
+        let property = vm.call(&property, Args::new(vec![getter]))?;
+        vm.setattr(&connection, name, property)?;
+    }
+    add_attribute(vm, &module, "Connection", connection);
+    let cursor = new_native_class(
+        vm,
+        "sqlite3",
+        "Cursor",
+        &object,
+        &[
+            ("__init__", cursor_init),
+            ("__iter__", cursor_iter),
+            ("__next__", cursor_next),
+            ("close", cursor_close),
+            ("execute", cursor_execute),
+            ("executemany", cursor_executemany),
+            ("executescript", cursor_executescript),
+            ("fetchall", cursor_fetchall),
+            ("fetchmany", cursor_fetchmany),
+            ("fetchone", cursor_fetchone),
+        ],
+    )?;
+    add_attribute(vm, &module, "Cursor", cursor);
+    for &(name, value) in &[
+        ("apilevel", "2.0"),
+        ("paramstyle", "qmark"),
+        ("sqlite_version", rusqlite::version()),
+    ] {
+        let value = vm.new_str(value);
+        add_attribute(vm, &module, name, value);
+    }
+    let threadsafety = vm.new_int(3);
+    add_attribute(vm, &module, "threadsafety", threadsafety);
+    Ok(module)
+}
+
+fn programming_error(vm: &mut Vm, message: &str) -> ObjectRef {
+    module_error(vm, "sqlite3", "ProgrammingError", message.to_string())
+}
+
+/// The exception for an error from SQLite, of the class CPython raises for
+/// its error code.
+fn sqlite_error(vm: &mut Vm, error: rusqlite::Error) -> ObjectRef {
+    let (class, message) = match error {
+        rusqlite::Error::SqliteFailure(failure, message) => {
+            let message = message.unwrap_or_else(|| failure.to_string());
+            (error_class(&failure), message)
+        }
+        rusqlite::Error::SqlInputError { error, msg, .. } => (error_class(&error), msg),
+        error => ("DatabaseError", error.to_string()),
+    };
+    module_error(vm, "sqlite3", class, message)
+}
+
+/// The exception class CPython raises for an error of SQLite, by its
+/// primary result code.
+fn error_class(error: &ffi::Error) -> &'static str {
+    match error.extended_code & 0xff {
+        ffi::SQLITE_INTERNAL | ffi::SQLITE_NOTFOUND => "InternalError",
+        ffi::SQLITE_TOOBIG => "DataError",
+        ffi::SQLITE_CONSTRAINT | ffi::SQLITE_MISMATCH => "IntegrityError",
+        ffi::SQLITE_MISUSE | ffi::SQLITE_RANGE => "InterfaceError",
+        // CPython raises `MemoryError` for SQLITE_NOMEM, which this VM lacks.
+        ffi::SQLITE_ERROR
+        | ffi::SQLITE_PERM
+        | ffi::SQLITE_ABORT
+        | ffi::SQLITE_BUSY
+        | ffi::SQLITE_LOCKED
+        | ffi::SQLITE_READONLY
+        | ffi::SQLITE_INTERRUPT
+        | ffi::SQLITE_IOERR
+        | ffi::SQLITE_FULL
+        | ffi::SQLITE_CANTOPEN
+        | ffi::SQLITE_PROTOCOL
+        | ffi::SQLITE_EMPTY
+        | ffi::SQLITE_SCHEMA
+        | ffi::SQLITE_NOMEM => "OperationalError",
+        _ => "DatabaseError",
+    }
+}
+
+/// The handle of an open connection, or the error for a closed one.
+fn handle(vm: &mut Vm, connection: &ObjectRef) -> PyResult<i64> {
+    let handle = match vm.getattr(connection, "_handle")?.payload {
+        Payload::Int(handle) => handle,
+        _ => -1,
+    };
+    if vm.sqlite.open.contains_key(&handle) {
+        Ok(handle)
+    } else {
+        Err(programming_error(
+            vm,
+            "Cannot operate on a closed database.",
+        ))
+    }
+}
+
+/// `connect(database, timeout=5.0, detect_types=0, isolation_level="",
+/// ...)`: a connection to the database file, or to a new database in
+/// memory for `:memory:`.
+fn connect(vm: &mut Vm, args: Args) -> PyResult {
+    let class = module_attribute(vm, "Connection")?;
+    vm.call(&class, args)
+}
+
+fn module_attribute(vm: &mut Vm, name: &str) -> PyResult {
+    let module = vm.import_module("sqlite3")?;
+    vm.getattr(&module, name)
+}
+
+fn connection_init(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "self",
+        "database",
+        "timeout",
+        "detect_types",
+        "isolation_level",
+        "check_same_thread",
+        "factory",
+        "cached_statements",
+        "uri",
+    ];
+    let values = bind_arguments(vm, args, "Connection", &names, 2)?;
+    let this = values[0].as_ref().unwrap();
+    let database = values[1].as_ref().unwrap();
+    let path = match database.payload {
+        Payload::Str(ref path) => path.clone(),
+        Payload::Bytes(ref path) => String::from_utf8_lossy(path).into_owned(),
+        _ => {
+            let message = format!(
+                "expected str, bytes or os.PathLike object, not {}",
+                database.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let timeout = match values[2] {
+        Some(ref timeout) => match timeout.payload {
+            Payload::Float(seconds) => seconds,
+            Payload::Int(seconds) => seconds as f64,
+            _ => {
+                let message = format!("must be real number, not {}", timeout.type_name());
+                return Err(vm.new_type_error(message));
+            }
+        },
+        None => 5.0,
+    };
+    let isolation_level = match values[4] {
+        Some(ref level) => {
+            check_isolation_level(vm, level)?;
+            level.clone()
+        }
+        None => vm.new_str(""),
+    };
+    let connection = Connection::open(&path).and_then(|connection| {
+        let timeout = ::std::time::Duration::from_millis((timeout.max(0.0) * 1000.0) as u64);
+        connection.busy_timeout(timeout)?;
+        Ok(connection)
+    });
+    let connection = match connection {
+        Ok(connection) => connection,
+        // rusqlite adds the path to the message of a failed open.
+        Err(rusqlite::Error::SqliteFailure(failure, Some(message))) => {
+            let suffix = format!(": {}", path);
+            let message = message
+                .strip_suffix(&suffix)
+                .unwrap_or(&message)
+                .to_string();
+            let error = rusqlite::Error::SqliteFailure(failure, Some(message));
+            return Err(sqlite_error(vm, error));
+        }
+        Err(error) => return Err(sqlite_error(vm, error)),
+    };
+    let handle = vm.sqlite.next_handle;
+    vm.sqlite.next_handle += 1;
+    vm.sqlite.open.insert(handle, connection);
+    let handle = vm.new_int(handle);
+    vm.setattr(this, "_handle", handle)?;
+    vm.setattr(this, "isolation_level", isolation_level)?;
+    let none = vm.none();
+    vm.setattr(this, "row_factory", none)?;
+    Ok(vm.none())
+}
+
+/// Fails unless `level` is `None` or one of the kinds of transaction
+/// SQLite begins.
+fn check_isolation_level(vm: &mut Vm, level: &ObjectRef) -> PyResult<()> {
+    if Vm::is(level, &vm.none()) {
+        return Ok(());
+    }
+    match level.as_str() {
+        Some(text) => {
+            let kind = text.to_ascii_uppercase();
+            if ["", "DEFERRED", "IMMEDIATE", "EXCLUSIVE"].contains(&kind.as_str()) {
+                Ok(())
+            } else {
+                let message =
+                    "isolation_level string must be '', 'DEFERRED', 'IMMEDIATE', or 'EXCLUSIVE'";
+                Err(vm.new_value_error(message.to_string()))
+            }
+        }
+        None => {
+            let message = format!(
+                "isolation_level must be str or None, not {}",
+                level.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// Runs SQL that returns no rows, such as `COMMIT`, on a connection.
+fn run_on(vm: &mut Vm, handle: i64, sql: &str) -> PyResult<()> {
+    match vm.sqlite.open[&handle].execute_batch(sql) {
+        Ok(()) => Ok(()),
+        Err(error) => Err(sqlite_error(vm, error)),
+    }
+}
+
+fn connection_close(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "close", &["self"], 1)?;
+    if let Ok(handle) = handle(vm, values[0].as_ref().unwrap()) {
+        let connection = vm.sqlite.open.remove(&handle).unwrap();
+        if let Err((_, error)) = connection.close() {
+            return Err(sqlite_error(vm, error));
+        }
+    }
+    Ok(vm.none())
+}
+
+/// `commit()`: ends the transaction, if one is open.
+fn connection_commit(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "commit", &["self"], 1)?;
+    let handle = handle(vm, values[0].as_ref().unwrap())?;
+    if !vm.sqlite.open[&handle].is_autocommit() {
+        run_on(vm, handle, "COMMIT")?;
+    }
+    Ok(vm.none())
+}
+
+/// `rollback()`: undoes the transaction, if one is open.
+fn connection_rollback(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "rollback", &["self"], 1)?;
+    let handle = handle(vm, values[0].as_ref().unwrap())?;
+    if !vm.sqlite.open[&handle].is_autocommit() {
+        run_on(vm, handle, "ROLLBACK")?;
+    }
+    Ok(vm.none())
+}
+
+fn connection_in_transaction(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "in_transaction", &["self"], 1)?;
+    let handle = handle(vm, values[0].as_ref().unwrap())?;
+    let in_transaction = !vm.sqlite.open[&handle].is_autocommit();
+    Ok(vm.new_bool(in_transaction))
+}
+
+fn connection_total_changes(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "total_changes", &["self"], 1)?;
+    let handle = handle(vm, values[0].as_ref().unwrap())?;
+    let changes = vm.sqlite.open[&handle].total_changes();
+    Ok(vm.new_int(changes as i64))
+}
+
+fn connection_enter(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__enter__", &["self"], 1)?;
+    Ok(values[0].clone().unwrap())
+}
+
+/// `__exit__(type, value, traceback)`: commits the transaction if the
+/// `with` block finished, and rolls it back if it raised.
+fn connection_exit(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["self", "type", "value", "traceback"];
+    let values = bind_arguments(vm, args, "__exit__", &names, 4)?;
+    let this = values[0].clone().unwrap();
+    let method = if Vm::is(values[1].as_ref().unwrap(), &vm.none()) {
+        "commit"
+    } else {
+        "rollback"
+    };
+    let method = vm.getattr(&this, method)?;
+    vm.call(&method, Args::default())?;
+    Ok(vm.new_bool(false))
+}
+
+/// `cursor()`: a new cursor on the connection.
+fn connection_cursor(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "cursor", &["self", "factory"], 1)?;
+    let this = values[0].clone().unwrap();
+    handle(vm, &this)?;
+    let factory = match values[1] {
+        Some(ref factory) => factory.clone(),
+        None => module_attribute(vm, "Cursor")?,
+    };
+    vm.call(&factory, Args::new(vec![this]))
+}
+
+/// Calls the method `name` of a new cursor with the arguments after the
+/// connection, and returns the cursor.
+fn on_new_cursor(vm: &mut Vm, args: Args, name: &str) -> PyResult {
+    let Args {
+        mut positional,
+        keywords,
+    } = args;
+    if positional.is_empty() {
+        let message = format!("{}() missing 1 required positional argument: 'self'", name);
+        return Err(vm.new_type_error(message));
+    }
+    let this = positional.remove(0);
+    let cursor = vm.getattr(&this, "cursor")?;
+    let cursor = vm.call(&cursor, Args::default())?;
+    let method = vm.getattr(&cursor, name)?;
+    vm.call(
+        &method,
+        Args {
+            positional,
+            keywords,
+        },
+    )
+}
+
+fn connection_execute(vm: &mut Vm, args: Args) -> PyResult {
+    on_new_cursor(vm, args, "execute")
+}
+
+fn connection_executemany(vm: &mut Vm, args: Args) -> PyResult {
+    on_new_cursor(vm, args, "executemany")
+}
+
+fn connection_executescript(vm: &mut Vm, args: Args) -> PyResult {
+    on_new_cursor(vm, args, "executescript")
+}
+
+// Cursors.
+
+fn cursor_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "Cursor", &["self", "connection"], 2)?;
+    let this = values[0].as_ref().unwrap();
+    let connection = values[1].clone().unwrap();
+    let row_factory = vm.getattr(&connection, "row_factory")?;
+    vm.setattr(this, "connection", connection)?;
+    vm.setattr(this, "row_factory", row_factory)?;
+    let none = vm.none();
+    vm.setattr(this, "description", none.clone())?;
+    vm.setattr(this, "lastrowid", none)?;
+    let rowcount = vm.new_int(-1);
+    vm.setattr(this, "rowcount", rowcount)?;
+    let arraysize = vm.new_int(1);
+    vm.setattr(this, "arraysize", arraysize)?;
+    let rows = vm.new_list(Vec::new());
+    vm.setattr(this, "_rows", rows)?;
+    let closed = vm.new_bool(false);
+    vm.setattr(this, "_closed", closed)?;
+    Ok(vm.none())
+}
+
+/// The handle of the connection of an open cursor.
+fn cursor_handle(vm: &mut Vm, cursor: &ObjectRef) -> PyResult<i64> {
+    let connection = vm.getattr(cursor, "connection")?;
+    let handle = handle(vm, &connection)?;
+    let closed = vm.getattr(cursor, "_closed")?;
+    if vm.is_true(&closed)? {
+        return Err(programming_error(vm, "Cannot operate on a closed cursor."));
+    }
+    Ok(handle)
+}
+
+fn cursor_close(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "close", &["self"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let closed = vm.new_bool(true);
+    vm.setattr(this, "_closed", closed)?;
+    let rows = vm.new_list(Vec::new());
+    vm.setattr(this, "_rows", rows)?;
+    Ok(vm.none())
+}
+
+/// The parameters of a statement: a sequence by position, or a mapping by
+/// name.
+enum Parameters {
+    Positional(Vec<Value>),
+    Named(HashMap<String, Value>),
+}
+
+/// A value of Python as a value of SQLite. `position` counts from 1.
+fn sql_value(vm: &mut Vm, value: &ObjectRef, position: usize) -> PyResult<Value> {
+    Ok(match value.payload {
+        Payload::None => Value::Null,
+        Payload::Int(number) => Value::Integer(number),
+        Payload::Float(number) => Value::Real(number),
+        Payload::Str(ref text) => Value::Text(text.clone()),
+        Payload::Bytes(ref data) => Value::Blob(data.clone()),
+        _ => {
+            let message = format!(
+                "Error binding parameter {}: type '{}' is not supported",
+                position,
+                value.type_name()
+            );
+            return Err(programming_error(vm, &message));
+        }
+    })
+}
+
+fn parameters(vm: &mut Vm, parameters: Option<&ObjectRef>) -> PyResult<Parameters> {
+    let parameters = match parameters {
+        Some(parameters) => parameters,
+        None => return Ok(Parameters::Positional(Vec::new())),
+    };
+    if let Some(dict) = parameters.as_dict() {
+        let entries: Vec<(ObjectRef, ObjectRef)> = dict
+            .borrow()
+            .iter()
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect();
+        let mut named = HashMap::new();
+        for (position, (key, value)) in entries.into_iter().enumerate() {
+            if let Some(key) = key.as_str() {
+                let value = sql_value(vm, &value, position + 1)?;
+                named.insert(key.to_string(), value);
+            }
+        }
+        return Ok(Parameters::Named(named));
+    }
+    match parameters.payload {
+        Payload::Tuple(_) | Payload::List(_) => {}
+        _ => return Err(programming_error(vm, "parameters are of unsupported type")),
+    }
+    let mut positional = Vec::new();
+    for (position, value) in vm.iterate(parameters)?.iter().enumerate() {
+        positional.push(sql_value(vm, value, position + 1)?);
+    }
+    Ok(Parameters::Positional(positional))
+}
+
+/// The first keyword of a statement, after any whitespace and comments.
+fn first_keyword(sql: &str) -> String {
+    let mut rest = sql;
+    loop {
+        rest = rest.trim_start();
+        if let Some(comment) = rest.strip_prefix("--") {
+            rest = comment.find('\n').map_or("", |end| &comment[end..]);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.find("*/").map_or("", |end| &comment[end + 2..]);
+        } else {
+            break;
+        }
+    }
+    rest.chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_ascii_uppercase()
+}
+
+/// What running a statement gave.
+struct Outcome {
+    columns: Vec<String>,
+    rows: Vec<Vec<Value>>,
+    changes: u64,
+}
+
+/// Why a statement could not be run.
+enum Failure {
+    Sqlite(rusqlite::Error),
+    Programming(String),
+}
+
+impl From<rusqlite::Error> for Failure {
+    fn from(error: rusqlite::Error) -> Failure {
+        Failure::Sqlite(error)
+    }
+}
+
+/// Runs a single statement with its parameters, returning all its rows.
+fn run_statement(
+    connection: &Connection,
+    sql: &str,
+    parameters: &Parameters,
+) -> Result<Outcome, Failure> {
+    let mut batch = Batch::new(connection, sql);
+    let mut statement = match batch.next()? {
+        Some(statement) => statement,
+        None => {
+            return Ok(Outcome {
+                columns: Vec::new(),
+                rows: Vec::new(),
+                changes: 0,
+            })
+        }
+    };
+    if !matches!(batch.next(), Ok(None)) {
+        let message = "You can only execute one statement at a time.";
+        return Err(Failure::Programming(message.to_string()));
+    }
+    let count = statement.parameter_count();
+    match *parameters {
+        Parameters::Positional(ref values) => {
+            if values.len() != count {
+                return Err(Failure::Programming(format!(
+                    "Incorrect number of bindings supplied. The current statement uses {}, and there are {} supplied.",
+                    count,
+                    values.len()
+                )));
+            }
+            for (index, value) in values.iter().enumerate() {
+                statement.raw_bind_parameter(index + 1, value)?;
+            }
+        }
+        Parameters::Named(ref values) => {
+            for index in 1..=count {
+                let name = statement.parameter_name(index).unwrap_or("?").to_string();
+                match values.get(&name[1..]) {
+                    Some(value) => statement.raw_bind_parameter(index, value)?,
+                    None => {
+                        return Err(Failure::Programming(format!(
+                            "You did not supply a value for binding parameter {}.",
+                            name
+                        )))
+                    }
+                }
+            }
+        }
+    }
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(String::from)
+        .collect();
+    let mut rows = Vec::new();
+    let mut query = statement.raw_query();
+    while let Some(row) = query.next()? {
+        let mut values = Vec::with_capacity(columns.len());
+        for index in 0..columns.len() {
+            values.push(row.get_ref(index)?.into());
+        }
+        rows.push(values);
+    }
+    Ok(Outcome {
+        columns,
+        rows,
+        changes: connection.changes(),
+    })
+}
+
+fn python_value(vm: &mut Vm, value: Value) -> ObjectRef {
+    match value {
+        Value::Null => vm.none(),
+        Value::Integer(number) => vm.new_int(number),
+        Value::Real(number) => vm.new_float(number),
+        Value::Text(text) => vm.new_str(&text),
+        Value::Blob(data) => vm.new_bytes(data),
+    }
+}
+
+/// Runs a statement for each set of parameters, beginning a transaction
+/// first for a DML statement if the connection is not in one. Sets the
+/// attributes of the cursor from what the statement gave.
+fn execute_on_cursor(
+    vm: &mut Vm,
+    cursor: &ObjectRef,
+    sql: &ObjectRef,
+    parameter_sets: Vec<Option<ObjectRef>>,
+    many: bool,
+) -> PyResult<()> {
+    let handle = cursor_handle(vm, cursor)?;
+    let sql = match sql.as_str() {
+        Some(sql) => sql.to_string(),
+        None => {
+            let function = if many { "executemany" } else { "execute" };
+            let message = format!(
+                "{}() argument 1 must be str, not {}",
+                function,
+                sql.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let keyword = first_keyword(&sql);
+    let is_dml = ["INSERT", "UPDATE", "DELETE", "REPLACE"].contains(&keyword.as_str());
+    if many && !is_dml {
+        return Err(programming_error(
+            vm,
+            "executemany() can only execute DML statements.",
+        ));
+    }
+    let none = vm.none();
+    vm.setattr(cursor, "description", none)?;
+    let rows = vm.new_list(Vec::new());
+    vm.setattr(cursor, "_rows", rows.clone())?;
+    let connection = vm.getattr(cursor, "connection")?;
+    let isolation_level = vm.getattr(&connection, "isolation_level")?;
+    let mut rowcount = if is_dml { 0 } else { -1 };
+    for parameter_set in parameter_sets {
+        let parameters = parameters(vm, parameter_set.as_ref())?;
+        if is_dml && vm.sqlite.open[&handle].is_autocommit() {
+            if let Some(level) = isolation_level.as_str() {
+                run_on(vm, handle, &format!("BEGIN {}", level))?;
+            }
+        }
+        let outcome = match run_statement(&vm.sqlite.open[&handle], &sql, &parameters) {
+            Ok(outcome) => outcome,
+            Err(Failure::Sqlite(error)) => return Err(sqlite_error(vm, error)),
+            Err(Failure::Programming(message)) => return Err(programming_error(vm, &message)),
+        };
+        if is_dml {
+            rowcount += outcome.changes as i64;
+        }
+        if !outcome.columns.is_empty() {
+            let description = outcome
+                .columns
+                .iter()
+                .map(|name| {
+                    let mut column = vec![vm.new_str(name)];
+                    column.resize(7, vm.none());
+                    vm.new_tuple(column)
+                })
+                .collect();
+            let description = vm.new_tuple(description);
+            vm.setattr(cursor, "description", description)?;
+        }
+        let found: Vec<ObjectRef> = outcome
+            .rows
+            .into_iter()
+            .map(|row| {
+                let values = row
+                    .into_iter()
+                    .map(|value| python_value(vm, value))
+                    .collect();
+                vm.new_tuple(values)
+            })
+            .collect();
+        if let Payload::List(ref rows) = rows.payload {
+            rows.borrow_mut().extend(found);
+        }
+    }
+    let rowcount = vm.new_int(rowcount);
+    vm.setattr(cursor, "rowcount", rowcount)?;
+    if !many {
+        let lastrowid = vm.sqlite.open[&handle].last_insert_rowid();
+        let lastrowid = vm.new_int(lastrowid);
+        vm.setattr(cursor, "lastrowid", lastrowid)?;
+    }
+    Ok(())
+}
+
+/// `execute(sql, parameters=(), /)`: runs a statement, and returns the
+/// cursor.
+fn cursor_execute(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "execute", &["self", "sql", "parameters"], 2)?;
+    let this = values[0].clone().unwrap();
+    execute_on_cursor(
+        vm,
+        &this,
+        values[1].as_ref().unwrap(),
+        vec![values[2].clone()],
+        false,
+    )?;
+    Ok(this)
+}
+
+/// `executemany(sql, seq_of_parameters, /)`: runs a DML statement once for
+/// each set of parameters.
+fn cursor_executemany(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["self", "sql", "seq_of_parameters"];
+    let values = bind_arguments(vm, args, "executemany", &names, 3)?;
+    let this = values[0].clone().unwrap();
+    let parameter_sets = vm
+        .iterate(values[2].as_ref().unwrap())?
+        .into_iter()
+        .map(Some)
+        .collect();
+    execute_on_cursor(vm, &this, values[1].as_ref().unwrap(), parameter_sets, true)?;
+    Ok(this)
+}
+
+/// `executescript(sql_script, /)`: commits any open transaction, then runs
+/// the statements of the script, which take no parameters.
+fn cursor_executescript(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "executescript", &["self", "sql_script"], 2)?;
+    let this = values[0].clone().unwrap();
+    let handle = cursor_handle(vm, &this)?;
+    let script = values[1].as_ref().unwrap();
+    let script = match script.as_str() {
+        Some(script) => script.to_string(),
+        None => {
+            let message = "script argument must be unicode.".to_string();
+            return Err(vm.new_value_error(message));
+        }
+    };
+    if !vm.sqlite.open[&handle].is_autocommit() {
+        run_on(vm, handle, "COMMIT")?;
+    }
+    run_on(vm, handle, &script)?;
+    Ok(this)
+}
+
+/// Takes up to `limit` of the rows a cursor has left, made by its
+/// `row_factory` if it has one.
+fn take_rows(vm: &mut Vm, cursor: &ObjectRef, limit: Option<usize>) -> PyResult<Vec<ObjectRef>> {
+    cursor_handle(vm, cursor)?;
+    let rows = vm.getattr(cursor, "_rows")?;
+    let taken: Vec<ObjectRef> = match rows.payload {
+        Payload::List(ref rows) => {
+            let mut rows = rows.borrow_mut();
+            let count = limit.unwrap_or(rows.len()).min(rows.len());
+            rows.drain(..count).collect()
+        }
+        _ => Vec::new(),
+    };
+    let row_factory = vm.getattr(cursor, "row_factory")?;
+    if Vm::is(&row_factory, &vm.none()) {
+        return Ok(taken);
+    }
+    let mut made = Vec::with_capacity(taken.len());
+    for row in taken {
+        made.push(vm.call(&row_factory, Args::new(vec![cursor.clone(), row]))?);
+    }
+    Ok(made)
+}
+
+/// `fetchone()`: the next row, or `None` if there are no more.
+fn cursor_fetchone(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "fetchone", &["self"], 1)?;
+    let row = take_rows(vm, values[0].as_ref().unwrap(), Some(1))?.pop();
+    Ok(row.unwrap_or_else(|| vm.none()))
+}
+
+/// `fetchmany(size=cursor.arraysize)`: a list of up to `size` rows.
+fn cursor_fetchmany(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "fetchmany", &["self", "size"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let size = match values[1] {
+        Some(ref size) => size.clone(),
+        None => vm.getattr(this, "arraysize")?,
+    };
+    let size = match size.payload {
+        Payload::Int(size) => size.max(0) as usize,
+        _ => {
+            let message = format!(
+                "'{}' object cannot be interpreted as an integer",
+                size.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let rows = take_rows(vm, this, Some(size))?;
+    Ok(vm.new_list(rows))
+}
+
+/// `fetchall()`: a list of the rows left.
+fn cursor_fetchall(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "fetchall", &["self"], 1)?;
+    let rows = take_rows(vm, values[0].as_ref().unwrap(), None)?;
+    Ok(vm.new_list(rows))
+}
+
+fn cursor_iter(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__iter__", &["self"], 1)?;
+    Ok(values[0].clone().unwrap())
+}
+
+fn cursor_next(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__next__", &["self"], 1)?;
+    match take_rows(vm, values[0].as_ref().unwrap(), Some(1))?.pop() {
+        Some(row) => Ok(row),
+        None => {
+            let class = vm.exceptions.stop_iteration.clone();
+            Err(vm.new_exception(&class, Vec::new()))
+        }
+    }
+}
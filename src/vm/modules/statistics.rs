@@ -0,0 +1,955 @@
+//! `statistics`: averages and measures of spread. Means and variances are
+//! found exactly, as CPython finds them with fractions, and then rounded
+//! once, so results match CPython's to the last bit.
+
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+
+use ast::Operator;
+
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, module_error, new_native_class, new_native_module};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        "statistics",
+        &[
+            ("fmean", fmean),
+            ("mean", mean),
+            ("median", median),
+            ("median_high", median_high),
+            ("median_low", median_low),
+            ("mode", mode),
+            ("multimode", multimode),
+            ("pstdev", pstdev),
+            ("pvariance", pvariance),
+            ("quantiles", quantiles),
+            ("stdev", stdev),
+            ("variance", variance),
+        ],
+    );
+    let value_error = vm.exceptions.value_error.clone();
+    let error = new_native_class(vm, "statistics", "StatisticsError", &value_error, &[])?;
+    add_attribute(vm, &module, "StatisticsError", error);
+    Ok(module)
+}
+
+/// A `statistics.StatisticsError`.
+fn error(vm: &mut Vm, message: &str) -> ObjectRef {
+    module_error(vm, "statistics", "StatisticsError", message.to_string())
+}
+
+/// A natural number of any size, in base 2**32, least significant digit
+/// first and without leading zeros.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Natural(Vec<u32>);
+
+impl Natural {
+    fn from_u64(value: u64) -> Natural {
+        let mut number = Natural(vec![value as u32, (value >> 32) as u32]);
+        number.trim();
+        number
+    }
+
+    fn trim(&mut self) {
+        while self.0.last() == Some(&0) {
+            self.0.pop();
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    fn digit(&self, index: usize) -> u32 {
+        self.0.get(index).cloned().unwrap_or(0)
+    }
+
+    /// The number of bits, as `int.bit_length` counts them.
+    fn bits(&self) -> u64 {
+        match self.0.last() {
+            Some(&top) => (self.0.len() as u64 - 1) * 32 + u64::from(32 - top.leading_zeros()),
+            None => 0,
+        }
+    }
+
+    fn bit(&self, index: u64) -> bool {
+        (self.digit((index / 32) as usize) >> (index % 32)) & 1 == 1
+    }
+
+    fn trailing_zeros(&self) -> u64 {
+        for (index, &digit) in self.0.iter().enumerate() {
+            if digit != 0 {
+                return index as u64 * 32 + u64::from(digit.trailing_zeros());
+            }
+        }
+        0
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        if self.0.len() > 2 {
+            return None;
+        }
+        Some(u64::from(self.digit(0)) | u64::from(self.digit(1)) << 32)
+    }
+
+    fn add(&self, other: &Natural) -> Natural {
+        let length = self.0.len().max(other.0.len());
+        let mut digits = Vec::with_capacity(length + 1);
+        let mut carry = 0;
+        for index in 0..length {
+            let sum = u64::from(self.digit(index)) + u64::from(other.digit(index)) + carry;
+            digits.push(sum as u32);
+            carry = sum >> 32;
+        }
+        if carry != 0 {
+            digits.push(carry as u32);
+        }
+        Natural(digits)
+    }
+
+    /// Subtracts `other`, which is no greater.
+    fn sub_assign(&mut self, other: &Natural) {
+        let mut borrow = 0;
+        for index in 0..self.0.len() {
+            let difference = i64::from(self.0[index]) - i64::from(other.digit(index)) - borrow;
+            borrow = (difference < 0) as i64;
+            self.0[index] = (difference + (borrow << 32)) as u32;
+        }
+        self.trim();
+    }
+
+    fn sub(&self, other: &Natural) -> Natural {
+        let mut difference = self.clone();
+        difference.sub_assign(other);
+        difference
+    }
+
+    fn mul(&self, other: &Natural) -> Natural {
+        if self.is_zero() || other.is_zero() {
+            return Natural(Vec::new());
+        }
+        let mut digits = vec![0u32; self.0.len() + other.0.len()];
+        for (i, &a) in self.0.iter().enumerate() {
+            let mut carry = 0;
+            for (j, &b) in other.0.iter().enumerate() {
+                let product = u64::from(a) * u64::from(b) + u64::from(digits[i + j]) + carry;
+                digits[i + j] = product as u32;
+                carry = product >> 32;
+            }
+            digits[i + other.0.len()] = carry as u32;
+        }
+        let mut product = Natural(digits);
+        product.trim();
+        product
+    }
+
+    fn shl(&self, shift: u64) -> Natural {
+        if self.is_zero() {
+            return Natural(Vec::new());
+        }
+        let bits = (shift % 32) as u32;
+        let mut digits = vec![0; (shift / 32) as usize];
+        if bits == 0 {
+            digits.extend_from_slice(&self.0);
+        } else {
+            let mut carry = 0;
+            for &digit in &self.0 {
+                digits.push(digit << bits | carry);
+                carry = digit >> (32 - bits);
+            }
+            if carry != 0 {
+                digits.push(carry);
+            }
+        }
+        Natural(digits)
+    }
+
+    fn shr(&self, shift: u64) -> Natural {
+        let skipped = (shift / 32) as usize;
+        if skipped >= self.0.len() {
+            return Natural(Vec::new());
+        }
+        let bits = (shift % 32) as u32;
+        let mut digits = self.0[skipped..].to_vec();
+        if bits != 0 {
+            for index in 0..digits.len() {
+                let high = digits.get(index + 1).cloned().unwrap_or(0);
+                digits[index] = digits[index] >> bits | high << (32 - bits);
+            }
+        }
+        let mut number = Natural(digits);
+        number.trim();
+        number
+    }
+
+    /// Doubles the number and adds `bit`.
+    fn push_bit(&mut self, bit: bool) {
+        let mut carry = bit as u32;
+        for digit in &mut self.0 {
+            let high = *digit >> 31;
+            *digit = *digit << 1 | carry;
+            carry = high;
+        }
+        if carry != 0 {
+            self.0.push(carry);
+        }
+    }
+
+    /// The quotient and remainder of division by a non-zero `divisor`.
+    fn div_rem(&self, divisor: &Natural) -> (Natural, Natural) {
+        if divisor.0.len() == 1 {
+            let divisor = u64::from(divisor.0[0]);
+            let mut digits = vec![0; self.0.len()];
+            let mut remainder = 0;
+            for index in (0..self.0.len()).rev() {
+                let dividend = remainder << 32 | u64::from(self.0[index]);
+                digits[index] = (dividend / divisor) as u32;
+                remainder = dividend % divisor;
+            }
+            let mut quotient = Natural(digits);
+            quotient.trim();
+            return (quotient, Natural::from_u64(remainder));
+        }
+        let mut digits = vec![0u32; self.0.len()];
+        let mut remainder = Natural(Vec::new());
+        for index in (0..self.bits()).rev() {
+            remainder.push_bit(self.bit(index));
+            if remainder >= *divisor {
+                remainder.sub_assign(divisor);
+                digits[(index / 32) as usize] |= 1 << (index % 32);
+            }
+        }
+        let mut quotient = Natural(digits);
+        quotient.trim();
+        (quotient, remainder)
+    }
+
+    /// The integer square root, as `math.isqrt` finds it.
+    fn isqrt(&self) -> Natural {
+        if self.is_zero() {
+            return Natural(Vec::new());
+        }
+        let mut root = Natural::from_u64(1).shl(self.bits().div_ceil(2));
+        loop {
+            let next = root.add(&self.div_rem(&root).0).shr(1);
+            if next >= root {
+                return root;
+            }
+            root = next;
+        }
+    }
+
+    fn gcd(&self, other: &Natural) -> Natural {
+        if self.is_zero() {
+            return other.clone();
+        }
+        if other.is_zero() {
+            return self.clone();
+        }
+        let shift = self.trailing_zeros().min(other.trailing_zeros());
+        let mut a = self.shr(self.trailing_zeros());
+        let mut b = other.clone();
+        loop {
+            b = b.shr(b.trailing_zeros());
+            if a > b {
+                ::std::mem::swap(&mut a, &mut b);
+            }
+            b.sub_assign(&a);
+            if b.is_zero() {
+                return a.shl(shift);
+            }
+        }
+    }
+}
+
+impl Ord for Natural {
+    fn cmp(&self, other: &Natural) -> Ordering {
+        self.0
+            .len()
+            .cmp(&other.0.len())
+            .then_with(|| self.0.iter().rev().cmp(other.0.iter().rev()))
+    }
+}
+
+impl PartialOrd for Natural {
+    fn partial_cmp(&self, other: &Natural) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// An exact binary fraction, `mantissa * 2**exponent`, as every int and
+/// finite float is.
+#[derive(Clone, Debug)]
+struct Dyadic {
+    negative: bool,
+    mantissa: Natural,
+    exponent: i64,
+}
+
+impl Dyadic {
+    fn from_int(value: i64) -> Dyadic {
+        Dyadic {
+            negative: value < 0,
+            mantissa: Natural::from_u64(value.unsigned_abs()),
+            exponent: 0,
+        }
+    }
+
+    /// The value of a finite float.
+    fn from_float(value: f64) -> Dyadic {
+        let bits = value.to_bits();
+        let biased = ((bits >> 52) & 0x7ff) as i64;
+        let fraction = bits & ((1 << 52) - 1);
+        let (mantissa, exponent) = if biased == 0 {
+            (fraction, -1074)
+        } else {
+            (fraction | 1 << 52, biased - 1075)
+        };
+        Dyadic {
+            negative: bits >> 63 == 1,
+            mantissa: Natural::from_u64(mantissa),
+            exponent,
+        }
+    }
+
+    fn add(&self, other: &Dyadic) -> Dyadic {
+        let exponent = self.exponent.min(other.exponent);
+        let a = self.mantissa.shl((self.exponent - exponent) as u64);
+        let b = other.mantissa.shl((other.exponent - exponent) as u64);
+        let (negative, mantissa) = if self.negative == other.negative {
+            (self.negative, a.add(&b))
+        } else if a >= b {
+            (self.negative, a.sub(&b))
+        } else {
+            (other.negative, b.sub(&a))
+        };
+        Dyadic {
+            negative,
+            mantissa,
+            exponent,
+        }
+    }
+
+    fn sub(&self, other: &Dyadic) -> Dyadic {
+        self.add(&Dyadic {
+            negative: !other.negative,
+            ..other.clone()
+        })
+    }
+
+    fn mul(&self, other: &Dyadic) -> Dyadic {
+        Dyadic {
+            negative: self.negative != other.negative,
+            mantissa: self.mantissa.mul(&other.mantissa),
+            exponent: self.exponent + other.exponent,
+        }
+    }
+}
+
+/// An exact rational number.
+struct Fraction {
+    negative: bool,
+    numerator: Natural,
+    denominator: Natural,
+}
+
+impl Fraction {
+    fn new(value: &Dyadic) -> Fraction {
+        let one = Natural::from_u64(1);
+        let (numerator, denominator) = if value.exponent >= 0 {
+            (value.mantissa.shl(value.exponent as u64), one)
+        } else {
+            (
+                value.mantissa.clone(),
+                one.shl(value.exponent.unsigned_abs()),
+            )
+        };
+        Fraction {
+            negative: value.negative && !numerator.is_zero(),
+            numerator,
+            denominator,
+        }
+    }
+
+    fn divide(&mut self, divisor: usize) {
+        self.denominator = self.denominator.mul(&Natural::from_u64(divisor as u64));
+    }
+
+    /// The nearest float, or `None` if the fraction is too large for one.
+    fn to_f64(&self) -> Option<f64> {
+        let magnitude = ratio_to_f64(&self.numerator, &self.denominator)?;
+        Some(if self.negative { -magnitude } else { magnitude })
+    }
+}
+
+/// `2**exponent` times `value`, rounded once.
+fn ldexp(mut value: f64, mut exponent: i64) -> f64 {
+    let power = |exponent: i64| f64::from_bits(((exponent + 1023) as u64) << 52);
+    while exponent > 1000 && value.is_finite() {
+        value *= power(1000);
+        exponent -= 1000;
+    }
+    while exponent < -1000 && value != 0.0 {
+        value *= power(-1000);
+        exponent += 1000;
+    }
+    value * power(exponent.clamp(-1000, 1000))
+}
+
+/// `numerator / denominator`, with a non-zero denominator, as the nearest
+/// float, halfway cases going to the even float, as `int / int` rounds;
+/// `None` if the quotient is too large for a float.
+fn ratio_to_f64(numerator: &Natural, denominator: &Natural) -> Option<f64> {
+    if numerator.is_zero() {
+        return Some(0.0);
+    }
+    // Scale the quotient to 55 or 56 bits: two more than a float keeps,
+    // and whether anything is left over decides ties.
+    let shift = 55 - (numerator.bits() as i64 - denominator.bits() as i64);
+    let (quotient, remainder) = if shift >= 0 {
+        numerator.shl(shift as u64).div_rem(denominator)
+    } else {
+        numerator.div_rem(&denominator.shl(shift.unsigned_abs()))
+    };
+    let quotient = quotient.to_u64().unwrap();
+    let exponent = -shift;
+    let bits = i64::from(64 - quotient.leading_zeros());
+    // The place of the last bit kept, lower for subnormal floats.
+    let low = (exponent + bits - 53).max(-1074);
+    let dropped = low - exponent;
+    if dropped >= 100 {
+        return Some(0.0);
+    }
+    let quotient = u128::from(quotient);
+    let mut kept = quotient >> dropped;
+    let rest = quotient & ((1 << dropped) - 1);
+    let half = 1 << (dropped - 1);
+    if rest > half || (rest == half && (!remainder.is_zero() || kept & 1 == 1)) {
+        kept += 1;
+    }
+    if 128 - i64::from(kept.leading_zeros()) + low > 1024 {
+        return None;
+    }
+    Some(ldexp(kept as f64, low))
+}
+
+/// The square root of `numerator / denominator`, correctly rounded, found
+/// as `statistics._float_sqrt_of_frac` finds it.
+fn float_sqrt_of_frac(numerator: &Natural, denominator: &Natural) -> Option<f64> {
+    // The integer square root is rounded to odd with at least 55 bits, so
+    // that rounding it again to a float is correct.
+    let rounded_sqrt = |n: &Natural, m: &Natural| {
+        let mut root = n.div_rem(m).0.isqrt();
+        if root.mul(&root).mul(m) != *n {
+            if root.is_zero() {
+                root = Natural::from_u64(1);
+            } else {
+                root.0[0] |= 1;
+            }
+        }
+        root
+    };
+    let q = (numerator.bits() as i64 - denominator.bits() as i64 - 109).div_euclid(2);
+    let one = Natural::from_u64(1);
+    if q >= 0 {
+        let root = rounded_sqrt(numerator, &denominator.shl(2 * q as u64));
+        ratio_to_f64(&root.shl(q as u64), &one)
+    } else {
+        let root = rounded_sqrt(&numerator.shl(2 * q.unsigned_abs()), denominator);
+        ratio_to_f64(&root, &one.shl(q.unsigned_abs()))
+    }
+}
+
+/// An exact total, or the float that an infinity or NaN among the data
+/// makes it.
+enum Total {
+    Exact(Fraction),
+    NonFinite(f64),
+}
+
+/// A total of data, with how many there were and whether any was a float,
+/// as `statistics._sum` finds it.
+struct Sum {
+    total: Total,
+    count: usize,
+    float: bool,
+}
+
+/// A datum's value: exact, or a float for an infinity or NaN.
+enum Datum {
+    Finite(Dyadic),
+    NonFinite(f64),
+}
+
+fn exact_value(vm: &mut Vm, item: &ObjectRef, float: &mut bool) -> PyResult<Datum> {
+    match item.payload {
+        Payload::Int(value) => Ok(Datum::Finite(Dyadic::from_int(value))),
+        Payload::Float(value) => {
+            *float = true;
+            Ok(if value.is_finite() {
+                Datum::Finite(Dyadic::from_float(value))
+            } else {
+                Datum::NonFinite(value)
+            })
+        }
+        _ => {
+            let message = format!(
+                "can't convert type '{}' to numerator/denominator",
+                item.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+fn sum(vm: &mut Vm, data: &[ObjectRef]) -> PyResult<Sum> {
+    let mut float = false;
+    let mut total = Dyadic::from_int(0);
+    let mut non_finite = None;
+    for item in data {
+        match exact_value(vm, item, &mut float)? {
+            Datum::Finite(value) => total = total.add(&value),
+            Datum::NonFinite(value) => non_finite = Some(non_finite.unwrap_or(0.0) + value),
+        }
+    }
+    let total = match non_finite {
+        Some(value) => Total::NonFinite(value),
+        None => Total::Exact(Fraction::new(&total)),
+    };
+    Ok(Sum {
+        total,
+        count: data.len(),
+        float,
+    })
+}
+
+/// The sum of squared deviations of the data from `center`, or from their
+/// mean if it is `None`, as `statistics._ss` finds it.
+fn sum_of_squares(vm: &mut Vm, data: &[ObjectRef], center: Option<&ObjectRef>) -> PyResult<Sum> {
+    if let Some(center) = center {
+        let mut squares = Vec::with_capacity(data.len());
+        for item in data {
+            let deviation = vm.binary_op(item, Operator::Sub, center, false)?;
+            squares.push(vm.binary_op(&deviation, Operator::Mult, &deviation, false)?);
+        }
+        return sum(vm, &squares);
+    }
+    let mut float = false;
+    let mut total = Dyadic::from_int(0);
+    let mut squares = Dyadic::from_int(0);
+    let mut non_finite = None;
+    for item in data {
+        match exact_value(vm, item, &mut float)? {
+            Datum::Finite(value) => {
+                squares = squares.add(&value.mul(&value));
+                total = total.add(&value);
+            }
+            Datum::NonFinite(value) => non_finite = Some(non_finite.unwrap_or(0.0) + value),
+        }
+    }
+    let total = match non_finite {
+        Some(value) => Total::NonFinite(value),
+        None if data.is_empty() => Total::Exact(Fraction::new(&total)),
+        None => {
+            // (n * Σx² - (Σx)²) / n, exact with fractions.
+            let count = Dyadic::from_int(data.len() as i64);
+            let difference = count.mul(&squares).sub(&total.mul(&total));
+            let mut deviation = Fraction::new(&difference);
+            deviation.divide(data.len());
+            Total::Exact(deviation)
+        }
+    };
+    Ok(Sum {
+        total,
+        count: data.len(),
+        float,
+    })
+}
+
+/// An exact result as `statistics._convert` gives it: an int if the data
+/// are ints and the result is whole, or else a float.
+fn convert(vm: &mut Vm, value: &Fraction, float: bool) -> PyResult {
+    if !float {
+        let (quotient, remainder) = value.numerator.div_rem(&value.denominator);
+        if remainder.is_zero() {
+            let magnitude = match quotient.to_u64() {
+                Some(magnitude) => i128::from(magnitude),
+                None => return Err(vm.int_overflow()),
+            };
+            let signed = if value.negative {
+                -magnitude
+            } else {
+                magnitude
+            };
+            return match i64::try_from(signed) {
+                Ok(value) => Ok(vm.new_int(value)),
+                Err(_) => Err(vm.int_overflow()),
+            };
+        }
+    }
+    float_result(vm, value.to_f64())
+}
+
+/// A float result, or the error for one too large, worded as CPython's
+/// division of ints words it.
+fn float_result(vm: &mut Vm, value: Option<f64>) -> PyResult {
+    match value {
+        Some(value) => Ok(vm.new_float(value)),
+        None => {
+            let message = "integer division result too large for a float".to_string();
+            Err(vm.new_overflow_error(message))
+        }
+    }
+}
+
+/// `mean(data)`: the arithmetic mean, exactly rounded.
+fn mean(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "mean", &["data"], 1)?;
+    let data = vm.iterate(values[0].as_ref().unwrap())?;
+    let sum = sum(vm, &data)?;
+    if sum.count < 1 {
+        return Err(error(vm, "mean requires at least one data point"));
+    }
+    match sum.total {
+        Total::Exact(mut total) => {
+            total.divide(sum.count);
+            convert(vm, &total, sum.float)
+        }
+        Total::NonFinite(total) => Ok(vm.new_float(total / sum.count as f64)),
+    }
+}
+
+/// The value of a datum for `fmean`, which takes anything that converts
+/// to a float.
+fn real_value(vm: &mut Vm, item: &ObjectRef) -> PyResult<f64> {
+    match item.payload {
+        Payload::Int(value) => Ok(value as f64),
+        Payload::Float(value) => Ok(value),
+        _ => {
+            let message = format!("must be real number, not {}", item.type_name());
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// The correctly rounded sum of floats, with CPython's `math.fsum`
+/// algorithm and errors.
+fn fsum(vm: &mut Vm, items: &[ObjectRef]) -> PyResult<f64> {
+    let mut partials: Vec<f64> = Vec::new();
+    let mut special_sum = 0.0;
+    let mut inf_sum = 0.0;
+    for item in items {
+        let original = real_value(vm, item)?;
+        let mut x = original;
+        let mut kept = 0;
+        for index in 0..partials.len() {
+            let mut y = partials[index];
+            if x.abs() < y.abs() {
+                ::std::mem::swap(&mut x, &mut y);
+            }
+            let high = x + y;
+            let low = y - (high - x);
+            if low != 0.0 {
+                partials[kept] = low;
+                kept += 1;
+            }
+            x = high;
+        }
+        partials.truncate(kept);
+        if x != 0.0 {
+            if !x.is_finite() {
+                // An infinity or NaN in the data, or an overflow.
+                if original.is_finite() {
+                    let message = "intermediate overflow in fsum".to_string();
+                    return Err(vm.new_overflow_error(message));
+                }
+                if original.is_infinite() {
+                    inf_sum += original;
+                }
+                special_sum += original;
+                partials.clear();
+            } else {
+                partials.push(x);
+            }
+        }
+    }
+    if special_sum != 0.0 {
+        if inf_sum.is_nan() {
+            return Err(vm.new_value_error("-inf + inf in fsum".to_string()));
+        }
+        return Ok(special_sum);
+    }
+    let mut index = partials.len();
+    let mut high = 0.0;
+    if index > 0 {
+        index -= 1;
+        high = partials[index];
+        let mut low = 0.0;
+        while index > 0 {
+            let x = high;
+            index -= 1;
+            let y = partials[index];
+            high = x + y;
+            low = y - (high - x);
+            if low != 0.0 {
+                break;
+            }
+        }
+        // Round half-even correctly when the partials below would tip a
+        // tie one way.
+        if index > 0
+            && ((low < 0.0 && partials[index - 1] < 0.0)
+                || (low > 0.0 && partials[index - 1] > 0.0))
+        {
+            let y = low * 2.0;
+            let x = high + y;
+            if y == x - high {
+                high = x;
+            }
+        }
+    }
+    Ok(high)
+}
+
+/// `fmean(data, weights=None)`: the mean as a float, faster than `mean`.
+fn fmean(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "fmean", &["data", "weights"], 1)?;
+    let data = vm.iterate(values[0].as_ref().unwrap())?;
+    let weights = match values[1] {
+        Some(ref weights) if !matches!(weights.payload, Payload::None) => weights.clone(),
+        _ => {
+            let total = fsum(vm, &data)?;
+            if data.is_empty() {
+                return Err(error(vm, "fmean requires at least one data point"));
+            }
+            return Ok(vm.new_float(total / data.len() as f64));
+        }
+    };
+    let weights = vm.iterate(&weights)?;
+    let mut products = Vec::with_capacity(data.len());
+    for (item, weight) in data.iter().zip(&weights) {
+        products.push(vm.binary_op(item, Operator::Mult, weight, false)?);
+    }
+    let total = fsum(vm, &products)?;
+    if data.len() != weights.len() {
+        return Err(error(vm, "data and weights must be the same length"));
+    }
+    let weight = fsum(vm, &weights)?;
+    if weight == 0.0 {
+        return Err(error(vm, "sum of weights must be non-zero"));
+    }
+    Ok(vm.new_float(total / weight))
+}
+
+/// The data of a `median` function, sorted.
+fn sorted_data(vm: &mut Vm, args: Args, function: &str) -> PyResult<Vec<ObjectRef>> {
+    let values = bind_arguments(vm, args, function, &["data"], 1)?;
+    let mut data = vm.iterate(values[0].as_ref().unwrap())?;
+    vm.sort(&mut data, None, false)?;
+    if data.is_empty() {
+        return Err(error(vm, "no median for empty data"));
+    }
+    Ok(data)
+}
+
+/// `median(data)`: the middle datum, or the mean of the middle two.
+fn median(vm: &mut Vm, args: Args) -> PyResult {
+    let data = sorted_data(vm, args, "median")?;
+    let middle = data.len() / 2;
+    if data.len() % 2 == 1 {
+        return Ok(data[middle].clone());
+    }
+    let total = vm.binary_op(&data[middle - 1], Operator::Add, &data[middle], false)?;
+    let two = vm.new_int(2);
+    vm.binary_op(&total, Operator::Div, &two, false)
+}
+
+/// `median_low(data)`: the middle datum, or the lower of the middle two.
+fn median_low(vm: &mut Vm, args: Args) -> PyResult {
+    let data = sorted_data(vm, args, "median_low")?;
+    let middle = data.len() / 2;
+    if data.len() % 2 == 1 {
+        Ok(data[middle].clone())
+    } else {
+        Ok(data[middle - 1].clone())
+    }
+}
+
+/// `median_high(data)`: the middle datum, or the higher of the middle two.
+fn median_high(vm: &mut Vm, args: Args) -> PyResult {
+    let data = sorted_data(vm, args, "median_high")?;
+    Ok(data[data.len() / 2].clone())
+}
+
+/// The distinct data with how often each occurs, in order of first
+/// occurrence, as a `Counter` has them.
+fn counts(vm: &mut Vm, data: &ObjectRef) -> PyResult<Vec<(ObjectRef, i64)>> {
+    let data = vm.iterate(data)?;
+    // The table maps each datum to its place in `counts`.
+    let places = vm.new_dict();
+    let table = places.as_dict().unwrap();
+    let mut counts: Vec<(ObjectRef, i64)> = Vec::new();
+    for item in data {
+        match vm.dict_get(table, &item)? {
+            Some(place) => match place.payload {
+                Payload::Int(place) => counts[place as usize].1 += 1,
+                _ => unreachable!(),
+            },
+            None => {
+                let place = vm.new_int(counts.len() as i64);
+                vm.dict_set(table, item.clone(), place)?;
+                counts.push((item, 1));
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// `mode(data)`: the most common datum, the first seen of any tied.
+fn mode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "mode", &["data"], 1)?;
+    let counts = counts(vm, values[0].as_ref().unwrap())?;
+    let mut most = None;
+    for (item, count) in counts {
+        match most {
+            Some((_, highest)) if highest >= count => {}
+            _ => most = Some((item, count)),
+        }
+    }
+    match most {
+        Some((item, _)) => Ok(item),
+        None => Err(error(vm, "no mode for empty data")),
+    }
+}
+
+/// `multimode(data)`: the most common data, in order of first occurrence.
+fn multimode(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "multimode", &["data"], 1)?;
+    let counts = counts(vm, values[0].as_ref().unwrap())?;
+    let highest = counts.iter().map(|&(_, count)| count).max();
+    let modes = counts
+        .into_iter()
+        .filter(|&(_, count)| Some(count) == highest)
+        .map(|(item, _)| item)
+        .collect();
+    Ok(vm.new_list(modes))
+}
+
+/// The variance of the data, or the standard deviation if `root`, of a
+/// sample or else of a whole population.
+fn spread(vm: &mut Vm, args: Args, function: &str, sample: bool, root: bool) -> PyResult {
+    let center = if sample { "xbar" } else { "mu" };
+    let values = bind_arguments(vm, args, function, &["data", center], 1)?;
+    let data = vm.iterate(values[0].as_ref().unwrap())?;
+    let center = values[1]
+        .as_ref()
+        .filter(|center| !matches!(center.payload, Payload::None));
+    let squares = sum_of_squares(vm, &data, center)?;
+    let (divisor, needed) = if sample {
+        (squares.count.saturating_sub(1), "two data points")
+    } else {
+        (squares.count, "one data point")
+    };
+    if divisor == 0 {
+        let message = format!("{} requires at least {}", function, needed);
+        return Err(error(vm, &message));
+    }
+    match squares.total {
+        Total::Exact(mut total) => {
+            total.divide(divisor);
+            if !root {
+                return convert(vm, &total, squares.float);
+            }
+            let common = total.numerator.gcd(&total.denominator);
+            let numerator = total.numerator.div_rem(&common).0;
+            let denominator = total.denominator.div_rem(&common).0;
+            let root = float_sqrt_of_frac(&numerator, &denominator);
+            float_result(vm, root)
+        }
+        // CPython fails in the same way, looking for the fraction's parts.
+        Total::NonFinite(_) if root => {
+            let message = "'float' object has no attribute 'numerator'".to_string();
+            Err(vm.new_attribute_error(message))
+        }
+        Total::NonFinite(total) => Ok(vm.new_float(total / divisor as f64)),
+    }
+}
+
+/// `variance(data, xbar=None)`: the sample variance.
+fn variance(vm: &mut Vm, args: Args) -> PyResult {
+    spread(vm, args, "variance", true, false)
+}
+
+/// `pvariance(data, mu=None)`: the population variance.
+fn pvariance(vm: &mut Vm, args: Args) -> PyResult {
+    spread(vm, args, "pvariance", false, false)
+}
+
+/// `stdev(data, xbar=None)`: the sample standard deviation.
+fn stdev(vm: &mut Vm, args: Args) -> PyResult {
+    spread(vm, args, "stdev", true, true)
+}
+
+/// `pstdev(data, mu=None)`: the population standard deviation.
+fn pstdev(vm: &mut Vm, args: Args) -> PyResult {
+    spread(vm, args, "pstdev", false, true)
+}
+
+/// `quantiles(data, *, n=4, method='exclusive')`: the `n - 1` cut points
+/// dividing the data into `n` intervals of equal probability.
+fn quantiles(vm: &mut Vm, args: Args) -> PyResult {
+    if args.positional.len() > 1 {
+        let message = format!(
+            "quantiles() takes 1 positional argument but {} were given",
+            args.positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let values = bind_arguments(vm, args, "quantiles", &["data", "n", "method"], 1)?;
+    let n = match values[1] {
+        Some(ref n) => index_value(vm, n)?,
+        None => 4,
+    };
+    if n < 1 {
+        return Err(error(vm, "n must be at least 1"));
+    }
+    let mut data = vm.iterate(values[0].as_ref().unwrap())?;
+    vm.sort(&mut data, None, false)?;
+    let length = data.len() as i64;
+    if length < 2 {
+        return Err(error(vm, "must have at least two data points"));
+    }
+    let method = values[2].as_ref().cloned();
+    let inclusive = match method.as_ref().map(|method| method.as_str()) {
+        None | Some(Some("exclusive")) => false,
+        Some(Some("inclusive")) => true,
+        Some(_) => {
+            let method = method.unwrap();
+            let message = format!("Unknown method: {}", vm.repr(&method)?);
+            return Err(vm.new_value_error(message));
+        }
+    };
+    let divisor = vm.new_int(n);
+    let mut result = Vec::with_capacity(n as usize - 1);
+    for i in 1..n {
+        let (j, delta) = if inclusive {
+            let m = length - 1;
+            (i * m / n, i * m % n)
+        } else {
+            let m = length + 1;
+            let j = (i * m / n).max(1).min(length - 1);
+            (j - 1, i * m - j * n)
+        };
+        // (data[j] * (n - delta) + data[j + 1] * delta) / n
+        let (low, high) = (vm.new_int(n - delta), vm.new_int(delta));
+        let low = vm.binary_op(&data[j as usize], Operator::Mult, &low, false)?;
+        let high = vm.binary_op(&data[j as usize + 1], Operator::Mult, &high, false)?;
+        let total = vm.binary_op(&low, Operator::Add, &high, false)?;
+        result.push(vm.binary_op(&total, Operator::Div, &divisor, false)?);
+    }
+    Ok(vm.new_list(result))
+}
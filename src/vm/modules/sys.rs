@@ -0,0 +1,641 @@
+//! `sys`: the interpreter's view of the program it runs, and the standard
+//! streams.
+//!
+//! `sys.stdout` and `sys.stderr` start as file objects that write straight
+//! to the process's streams. Output meant for them, such as that of
+//! `print` and of warnings, goes through `write_standard`, which writes to
+//! the process's stream directly while a stream is still the one the VM
+//! started with, and calls `write` on whatever a program put in its place
+//! otherwise.
+
+use std::env;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+use std::rc::Rc;
+
+use super::super::builtins::{self, index_value};
+use super::super::object::{Args, NativeFunction, ObjectRef, Payload, PyResult};
+use super::super::tracer::{TraceEvent, TraceFrame, Tracer};
+use super::super::Vm;
+use super::warnings::warn_at_level;
+use super::{add_attribute, bind_arguments, new_native_class, new_native_module, os_error};
+
+/// The version of Python that rustpy follows, as `sys.version_info`
+/// reports it.
+const VERSION: (i64, i64, i64) = (3, 11, 0);
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        "sys",
+        &[
+            ("_inline_cache_stats", inline_cache_stats),
+            ("breakpointhook", breakpointhook),
+            ("displayhook", displayhook),
+            ("exc_info", exc_info),
+            ("exit", exit),
+            ("getrecursionlimit", getrecursionlimit),
+            ("gettrace", gettrace),
+            ("intern", intern),
+            ("setrecursionlimit", setrecursionlimit),
+            ("settrace", settrace),
+        ],
+    );
+    let displayhook = vm.getattr(&module, "displayhook")?;
+    add_attribute(vm, &module, "__displayhook__", displayhook);
+    let modules = vm.modules().clone();
+    add_attribute(vm, &module, "modules", modules);
+    let path = vm.new_list(Vec::new());
+    add_attribute(vm, &module, "path", path);
+    let argv = vec![vm.new_str("")];
+    let argv = vm.new_list(argv);
+    add_attribute(vm, &module, "argv", argv);
+
+    let (major, minor, micro) = VERSION;
+    let version = format!(
+        "{}.{}.{} (rustpy {})",
+        major,
+        minor,
+        micro,
+        env!("CARGO_PKG_VERSION")
+    );
+    let version = vm.new_str(&version);
+    add_attribute(vm, &module, "version", version);
+    let hexversion = vm.new_int(major << 24 | minor << 16 | micro << 8 | 0xf0);
+    add_attribute(vm, &module, "hexversion", hexversion);
+    let version_info = version_info(vm)?;
+    add_attribute(vm, &module, "version_info", version_info.clone());
+    let object = vm.types.object.clone();
+    let namespace = new_native_class(vm, "types", "SimpleNamespace", &object, &[])?;
+    let implementation = vm.call(&namespace, Args::default())?;
+    let attributes = [
+        ("name", vm.new_str("rustpy")),
+        ("version", version_info),
+        ("cache_tag", vm.none()),
+    ];
+    for (attribute, value) in attributes {
+        vm.setattr(&implementation, attribute, value)?;
+    }
+    add_attribute(vm, &module, "implementation", implementation);
+    let maxsize = vm.new_int(i64::MAX);
+    add_attribute(vm, &module, "maxsize", maxsize);
+    let maxunicode = vm.new_int(char::MAX as i64);
+    add_attribute(vm, &module, "maxunicode", maxunicode);
+    let byteorder = if cfg!(target_endian = "little") {
+        "little"
+    } else {
+        "big"
+    };
+    let byteorder = vm.new_str(byteorder);
+    add_attribute(vm, &module, "byteorder", byteorder);
+    let platform = match std::env::consts::OS {
+        "macos" => "darwin",
+        "windows" => "win32",
+        os => os,
+    };
+    let platform = vm.new_str(platform);
+    add_attribute(vm, &module, "platform", platform);
+
+    let stream = new_native_class(
+        vm,
+        "_io",
+        "TextIOWrapper",
+        &object,
+        &[
+            ("__repr__", stream_repr),
+            ("close", stream_flush),
+            ("fileno", stream_fileno),
+            ("flush", stream_flush),
+            ("isatty", stream_isatty),
+            ("read", stream_read),
+            ("readable", stream_readable),
+            ("readline", stream_readline),
+            ("seekable", stream_seekable),
+            ("writable", stream_writable),
+            ("write", stream_write),
+            ("writelines", stream_writelines),
+        ],
+    )?;
+    for (fd, name, mode) in [(0, "stdin", "r"), (1, "stdout", "w"), (2, "stderr", "w")] {
+        let file = vm.call(&stream, Args::default())?;
+        let attributes = [
+            ("_fd", vm.new_int(fd)),
+            ("name", vm.new_str(&format!("<{}>", name))),
+            ("mode", vm.new_str(mode)),
+            ("encoding", vm.new_str("utf-8")),
+            ("errors", vm.new_str("strict")),
+            ("closed", vm.new_bool(false)),
+        ];
+        for (attribute, value) in attributes {
+            vm.setattr(&file, attribute, value)?;
+        }
+        add_attribute(vm, &module, name, file.clone());
+        add_attribute(vm, &module, &format!("__{}__", name), file);
+    }
+    Ok(module)
+}
+
+/// `sys.version_info`, a tuple whose items are also attributes.
+fn version_info(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let tuple = vm.types.tuple.clone();
+    let class = new_native_class(
+        vm,
+        "sys",
+        "version_info",
+        &tuple,
+        &[("__repr__", version_repr)],
+    )?;
+    let getters: [(&str, NativeFunction); 5] = [
+        ("major", version_major),
+        ("minor", version_minor),
+        ("micro", version_micro),
+        ("releaselevel", version_releaselevel),
+        ("serial", version_serial),
+    ];
+    for (name, getter) in getters {
+        let getter = vm.new_builtin(name, getter);
+        let property = vm.types.property.clone();
+        let property = vm.call(&property, Args::new(vec![getter]))?;
+        vm.setattr(&class, name, property)?;
+    }
+    let (major, minor, micro) = VERSION;
+    let items = vec![
+        vm.new_int(major),
+        vm.new_int(minor),
+        vm.new_int(micro),
+        vm.new_str("final"),
+        vm.new_int(0),
+    ];
+    let items = vm.new_tuple(items);
+    vm.call(&class, Args::new(vec![items]))
+}
+
+const VERSION_FIELDS: [&str; 5] = ["major", "minor", "micro", "releaselevel", "serial"];
+
+fn version_field(vm: &mut Vm, args: Args, index: usize) -> PyResult {
+    let values = bind_arguments(vm, args, VERSION_FIELDS[index], &["self"], 1)?;
+    let index = vm.new_int(index as i64);
+    vm.getitem(values[0].as_ref().unwrap(), &index)
+}
+
+fn version_major(vm: &mut Vm, args: Args) -> PyResult {
+    version_field(vm, args, 0)
+}
+
+fn version_minor(vm: &mut Vm, args: Args) -> PyResult {
+    version_field(vm, args, 1)
+}
+
+fn version_micro(vm: &mut Vm, args: Args) -> PyResult {
+    version_field(vm, args, 2)
+}
+
+fn version_releaselevel(vm: &mut Vm, args: Args) -> PyResult {
+    version_field(vm, args, 3)
+}
+
+fn version_serial(vm: &mut Vm, args: Args) -> PyResult {
+    version_field(vm, args, 4)
+}
+
+fn version_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__repr__", &["self"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let mut fields = Vec::with_capacity(VERSION_FIELDS.len());
+    for (index, name) in VERSION_FIELDS.iter().enumerate() {
+        let index = vm.new_int(index as i64);
+        let value = vm.getitem(this, &index)?;
+        fields.push(format!("{}={}", name, vm.repr(&value)?));
+    }
+    let text = format!("sys.version_info({})", fields.join(", "));
+    Ok(vm.new_str(&text))
+}
+
+/// Writes `pieces` to `sys.stdout` or `sys.stderr`, as `name` says: to the
+/// process's stream while it is the one the VM started with, with one
+/// `write` call each to a file a program put there, and nowhere if it put
+/// `None` there.
+pub(crate) fn write_standard(
+    vm: &mut Vm,
+    name: &str,
+    pieces: &[&str],
+    flush: bool,
+) -> PyResult<()> {
+    let sys = vm.modules().as_dict().unwrap().borrow().get_str("sys");
+    let (stream, original) = match sys.as_ref().and_then(|sys| sys.dict()) {
+        Some(namespace) => {
+            let namespace = namespace.as_dict().unwrap().borrow();
+            (
+                namespace.get_str(name),
+                namespace.get_str(&format!("__{}__", name)),
+            )
+        }
+        None => (None, None),
+    };
+    match stream {
+        Some(ref stream) if Vm::is(stream, &vm.none()) => Ok(()),
+        Some(stream) if !original.is_some_and(|original| Vm::is(&original, &stream)) => {
+            write_file(vm, &stream, pieces, flush)
+        }
+        _ => {
+            write_process(
+                if name == "stderr" { 2 } else { 1 },
+                &pieces.concat(),
+                flush,
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Writes `pieces` to the file object `file`, with one `write` call each,
+/// as `print` does.
+pub(crate) fn write_file(
+    vm: &mut Vm,
+    file: &ObjectRef,
+    pieces: &[&str],
+    flush: bool,
+) -> PyResult<()> {
+    let write = vm.getattr(file, "write")?;
+    for piece in pieces {
+        let piece = vm.new_str(piece);
+        vm.call(&write, Args::new(vec![piece]))?;
+    }
+    if flush {
+        let flush = vm.getattr(file, "flush")?;
+        vm.call(&flush, Args::default())?;
+    }
+    Ok(())
+}
+
+/// Writes to the process's standard output, or its standard error for
+/// `fd` 2. Like CPython, a closed or broken stream is not an error worth
+/// stopping the program for.
+fn write_process(fd: i64, text: &str, flush: bool) {
+    if fd == 2 {
+        let _ = io::stderr().write_all(text.as_bytes());
+    } else {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        let _ = stdout.write_all(text.as_bytes());
+        if flush {
+            let _ = stdout.flush();
+        }
+    }
+}
+
+/// The file descriptor of a standard stream.
+fn stream_fd(vm: &mut Vm, args: Args, name: &str) -> PyResult<(ObjectRef, i64)> {
+    let values = bind_arguments(vm, args, name, &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    let fd = vm.getattr(&this, "_fd")?;
+    let fd = index_value(vm, &fd)?;
+    Ok((this, fd))
+}
+
+fn stream_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, _) = stream_fd(vm, args, "__repr__")?;
+    let mut fields = Vec::with_capacity(3);
+    for attribute in ["name", "mode", "encoding"] {
+        let value = vm.getattr(&this, attribute)?;
+        fields.push(format!("{}={}", attribute, vm.repr(&value)?));
+    }
+    let text = format!("<_io.TextIOWrapper {}>", fields.join(" "));
+    Ok(vm.new_str(&text))
+}
+
+fn stream_fileno(vm: &mut Vm, args: Args) -> PyResult {
+    let (_, fd) = stream_fd(vm, args, "fileno")?;
+    Ok(vm.new_int(fd))
+}
+
+fn stream_flush(vm: &mut Vm, args: Args) -> PyResult {
+    let (_, fd) = stream_fd(vm, args, "flush")?;
+    match fd {
+        1 => write_process(1, "", true),
+        2 => {
+            let _ = io::stderr().flush();
+        }
+        _ => {}
+    }
+    Ok(vm.none())
+}
+
+fn stream_isatty(vm: &mut Vm, args: Args) -> PyResult {
+    let (_, fd) = stream_fd(vm, args, "isatty")?;
+    let terminal = match fd {
+        0 => io::stdin().is_terminal(),
+        1 => io::stdout().is_terminal(),
+        _ => io::stderr().is_terminal(),
+    };
+    Ok(vm.new_bool(terminal))
+}
+
+fn stream_readable(vm: &mut Vm, args: Args) -> PyResult {
+    let (_, fd) = stream_fd(vm, args, "readable")?;
+    Ok(vm.new_bool(fd == 0))
+}
+
+fn stream_writable(vm: &mut Vm, args: Args) -> PyResult {
+    let (_, fd) = stream_fd(vm, args, "writable")?;
+    Ok(vm.new_bool(fd != 0))
+}
+
+fn stream_seekable(vm: &mut Vm, args: Args) -> PyResult {
+    stream_fd(vm, args, "seekable")?;
+    Ok(vm.new_bool(false))
+}
+
+/// Raises `io.UnsupportedOperation` for an operation a stream lacks.
+fn unsupported(vm: &mut Vm, operation: &str) -> ObjectRef {
+    super::module_error(vm, "io", "UnsupportedOperation", operation.to_string())
+}
+
+/// `write(s)`: the number of characters written.
+fn stream_write(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "write", &["self", "s"], 2)?;
+    let this = values[0].as_ref().unwrap();
+    let fd = vm.getattr(this, "_fd")?;
+    let fd = index_value(vm, &fd)?;
+    if fd == 0 {
+        return Err(unsupported(vm, "not writable"));
+    }
+    let text = values[1].as_ref().unwrap();
+    let text = match text.as_str() {
+        Some(text) => text,
+        None => {
+            let message = format!("write() argument must be str, not {}", text.type_name());
+            return Err(vm.new_type_error(message));
+        }
+    };
+    write_process(fd, text, false);
+    Ok(vm.new_int(text.chars().count() as i64))
+}
+
+/// `writelines(lines)`.
+fn stream_writelines(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "writelines", &["self", "lines"], 2)?;
+    let this = values[0].as_ref().unwrap();
+    let write = vm.getattr(this, "write")?;
+    for line in vm.iterate(values[1].as_ref().unwrap())? {
+        vm.call(&write, Args::new(vec![line]))?;
+    }
+    Ok(vm.none())
+}
+
+/// The size argument of `read` and `readline`, `None` for all.
+fn read_size(vm: &mut Vm, size: &Option<ObjectRef>) -> PyResult<Option<usize>> {
+    match *size {
+        Some(ref size) if !Vm::is(size, &vm.none()) => {
+            let size = index_value(vm, size)?;
+            Ok(if size < 0 { None } else { Some(size as usize) })
+        }
+        _ => Ok(None),
+    }
+}
+
+/// `read(size=-1)`: up to `size` characters of the standard input, or all
+/// of it.
+fn stream_read(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read", &["self", "size"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let fd = vm.getattr(this, "_fd")?;
+    if index_value(vm, &fd)? != 0 {
+        return Err(unsupported(vm, "not readable"));
+    }
+    let size = read_size(vm, &values[1])?;
+    let mut text = String::new();
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    let result = match size {
+        None => stdin.read_to_string(&mut text).map(|_| ()),
+        Some(size) => {
+            let mut result = Ok(());
+            while text.chars().count() < size {
+                let mut line = String::new();
+                match stdin.read_line(&mut line) {
+                    Ok(0) => break,
+                    Ok(_) => text.push_str(&line),
+                    Err(error) => {
+                        result = Err(error);
+                        break;
+                    }
+                }
+            }
+            // What was read past `size` is lost, as the input is not
+            // buffered here.
+            if let Some((index, _)) = text.char_indices().nth(size) {
+                text.truncate(index);
+            }
+            result
+        }
+    };
+    drop(stdin);
+    if let Err(error) = result {
+        return Err(os_error(vm, &error, "<stdin>"));
+    }
+    Ok(vm.new_str(&text))
+}
+
+/// `readline(size=-1)`: the next line of the standard input, with its
+/// newline, or `''` at its end.
+fn stream_readline(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "readline", &["self", "size"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let fd = vm.getattr(this, "_fd")?;
+    if index_value(vm, &fd)? != 0 {
+        return Err(unsupported(vm, "not readable"));
+    }
+    let size = read_size(vm, &values[1])?;
+    let mut line = String::new();
+    if let Err(error) = io::stdin().lock().read_line(&mut line) {
+        return Err(os_error(vm, &error, "<stdin>"));
+    }
+    if let Some((index, _)) = size.and_then(|size| line.char_indices().nth(size)) {
+        line.truncate(index);
+    }
+    Ok(vm.new_str(&line))
+}
+
+/// `displayhook(object)`: shows the value of an expression statement at
+/// the interactive prompt, with its whole repr. `reprlib.repr` in a hook
+/// of one's own shows it cut down instead.
+fn displayhook(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "displayhook", &["object"], 1)?;
+    builtins::display_value(vm, values[0].as_ref().unwrap())?;
+    Ok(vm.none())
+}
+
+/// `exc_info()`: the class, value and traceback of the exception being
+/// handled, or three `None`s.
+/// `breakpointhook(*args, **kws)`: calls the function that
+/// `$PYTHONBREAKPOINT` names, `pdb.set_trace` by default, or nothing if it
+/// is `0`. A function that cannot be imported is warned about and skipped.
+fn breakpointhook(vm: &mut Vm, args: Args) -> PyResult {
+    let name = match env::var("PYTHONBREAKPOINT") {
+        Ok(ref name) if name == "0" => return Ok(vm.none()),
+        Ok(name) if !name.is_empty() => name,
+        _ => "pdb.set_trace".to_string(),
+    };
+    let (module, function) = match name.rsplit_once('.') {
+        Some((module, function)) => (module, function),
+        None => ("builtins", name.as_str()),
+    };
+    let hook = match vm.import_module(module) {
+        Ok(module) => vm.getattr(&module, function),
+        Err(error) => Err(error),
+    };
+    match hook {
+        Ok(hook) => vm.call(&hook, args),
+        Err(_) => {
+            let message = format!("Ignoring unimportable $PYTHONBREAKPOINT: \"{}\"", name);
+            let message = vm.new_str(&message);
+            let category = vm.exceptions.runtime_warning.clone();
+            warn_at_level(vm, &message, &category, 1)?;
+            Ok(vm.none())
+        }
+    }
+}
+
+fn exc_info(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "exc_info", &[], 0)?;
+    let items = match vm.exc_info.clone() {
+        Some(exception) => {
+            let traceback = vm.getattr(&exception, "__traceback__")?;
+            vec![exception.class(), exception, traceback]
+        }
+        None => vec![vm.none(), vm.none(), vm.none()],
+    };
+    Ok(vm.new_tuple(items))
+}
+
+/// `exit(status=None)`: raises `SystemExit`.
+fn exit(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "exit", &["status"], 0)?;
+    let args = values[0].iter().cloned().collect();
+    let class = vm.exceptions.system_exit.clone();
+    Err(vm.new_exception(&class, args))
+}
+
+/// `intern(string)`: the one string of its text that names share.
+fn intern(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "intern", 1, 1)?;
+    let string = &args.positional[0];
+    if string.as_str().is_none() {
+        let message = format!("intern() argument must be str, not {}", string.type_name());
+        return Err(vm.new_type_error(message));
+    }
+    if !Vm::is(&string.class(), &vm.types.str) {
+        let message = format!("can't intern {}", string.type_name());
+        return Err(vm.new_type_error(message));
+    }
+    Ok(vm.intern_object(string))
+}
+
+/// `_inline_cache_stats()`: a dict of how often the inline caches of
+/// attribute and global loads have been used, and missed.
+fn inline_cache_stats(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "_inline_cache_stats", 0, 0)?;
+    let stats = vm.inline_cache_stats();
+    let dict = vm.new_dict();
+    for (name, value) in [
+        ("attribute_hits", stats.attribute_hits),
+        ("attribute_misses", stats.attribute_misses),
+        ("global_hits", stats.global_hits),
+        ("global_misses", stats.global_misses),
+    ] {
+        let value = vm.new_int(value as i64);
+        vm.dict_set_str(dict.as_dict().unwrap(), name, value);
+    }
+    Ok(dict)
+}
+
+fn getrecursionlimit(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "getrecursionlimit", &[], 0)?;
+    Ok(vm.new_int(vm.recursion_limit as i64))
+}
+
+/// `setrecursionlimit(limit)`: how deep calls may nest.
+fn setrecursionlimit(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "setrecursionlimit", &["limit"], 1)?;
+    let limit = values[0].as_ref().unwrap();
+    if let Payload::Float(_) = limit.payload {
+        let message = "'float' object cannot be interpreted as an integer".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    let limit = index_value(vm, limit)?;
+    if limit < 1 {
+        let message = "recursion limit must be greater or equal than 1".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    if limit as usize <= vm.depth {
+        let message = format!(
+            "cannot set the recursion limit to {} at the recursion depth {}: the limit is too low",
+            limit, vm.depth
+        );
+        let class = vm.exceptions.recursion_error.clone();
+        return Err(vm.new_error(&class, message));
+    }
+    vm.recursion_limit = limit as usize;
+    Ok(vm.none())
+}
+
+/// `settrace(function)`: calls `function(frame, event, arg)` for each
+/// frame that starts running, and its result, the frame's `f_trace`, for
+/// the later events of the frame. `None` stops tracing.
+fn settrace(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "settrace", &["function"], 1)?;
+    let function = values[0].clone().unwrap();
+    if Vm::is(&function, &vm.none()) {
+        vm.set_tracer(None);
+    } else {
+        vm.set_tracer(Some(Rc::new(TraceFunction {
+            function: function.clone(),
+        })));
+        vm.trace_function = Some(function);
+    }
+    Ok(vm.none())
+}
+
+/// `gettrace()`: the function `settrace` set, or `None`.
+fn gettrace(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "gettrace", &[], 0)?;
+    Ok(vm.trace_function.clone().unwrap_or_else(|| vm.none()))
+}
+
+/// The tracer `settrace` sets, which calls a Python trace function as
+/// CPython's trampoline does.
+struct TraceFunction {
+    function: ObjectRef,
+}
+
+impl Tracer for TraceFunction {
+    fn trace(
+        &self,
+        vm: &mut Vm,
+        frame: &TraceFrame,
+        event: TraceEvent,
+        arg: Option<&ObjectRef>,
+    ) -> PyResult<()> {
+        let function = match event {
+            TraceEvent::Call => self.function.clone(),
+            _ => vm.getattr(frame.object, "f_trace")?,
+        };
+        if Vm::is(&function, &vm.none()) {
+            return Ok(());
+        }
+        let arg = match (event, arg) {
+            (TraceEvent::Exception, Some(exception)) => {
+                let traceback = vm.getattr(exception, "__traceback__")?;
+                vm.new_tuple(vec![exception.class(), exception.clone(), traceback])
+            }
+            (_, Some(arg)) => arg.clone(),
+            (_, None) => vm.none(),
+        };
+        let args = vec![frame.object.clone(), vm.new_str(event.name()), arg];
+        let result = vm.call(&function, Args::new(args))?;
+        if !Vm::is(&result, &vm.none()) {
+            vm.setattr(frame.object, "f_trace", result)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,491 @@
+//! `textwrap`: wrapping paragraphs to a width, and adjusting the
+//! indentation of lines, with the algorithms of CPython's `textwrap.py`.
+
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, PyResult};
+use super::super::string::{expand_tabs, split_lines};
+use super::super::unicode::{decimal_value, is_space};
+use super::super::Vm;
+use super::{bind_arguments, new_native_module, str_argument};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    Ok(new_native_module(
+        vm,
+        "textwrap",
+        &[
+            ("dedent", dedent),
+            ("fill", fill),
+            ("indent", indent),
+            ("shorten", shorten),
+            ("wrap", wrap),
+        ],
+    ))
+}
+
+/// The options of `textwrap.TextWrapper`.
+struct Wrapper {
+    width: i64,
+    initial_indent: Vec<char>,
+    subsequent_indent: Vec<char>,
+    expand_tabs: bool,
+    replace_whitespace: bool,
+    fix_sentence_endings: bool,
+    break_long_words: bool,
+    drop_whitespace: bool,
+    break_on_hyphens: bool,
+    tab_size: i64,
+    max_lines: Option<i64>,
+    placeholder: Vec<char>,
+}
+
+/// The whitespace `textwrap` breaks lines at.
+fn is_break_space(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\x0b' | '\x0c' | '\r' | ' ')
+}
+
+/// `\w` of a regular expression.
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// `[^\d\W]`: a word character that is not a digit.
+fn is_letter(c: char) -> bool {
+    is_word(c) && decimal_value(c).is_none()
+}
+
+/// A character that may end the word before an em-dash.
+fn is_word_punct(c: char) -> bool {
+    is_word(c) || "!\"'&.,?".contains(c)
+}
+
+/// Whether a chunk is all whitespace, as `chunk.strip() == ''`.
+fn is_blank(chunk: &[char]) -> bool {
+    chunk.iter().all(|&c| is_space(c))
+}
+
+/// The end of an em-dash of two or more hyphens at `start` that a word
+/// follows.
+fn em_dash_end(text: &[char], start: usize) -> Option<usize> {
+    let end = start + text[start..].iter().take_while(|&&c| c == '-').count();
+    match text.get(end) {
+        Some(&c) if end - start >= 2 && is_word(c) => Some(end),
+        _ => None,
+    }
+}
+
+/// Whether the word being read can break after the hyphen at `position`:
+/// it follows two letters, or a letter after another hyphenated letter, and
+/// letters follow it.
+fn hyphen_breaks(text: &[char], position: usize) -> bool {
+    let at = |index: Option<usize>, test: fn(char) -> bool| {
+        index
+            .and_then(|index| text.get(index))
+            .is_some_and(|&c| test(c))
+    };
+    let before = |offset: usize| position.checked_sub(offset);
+    let hyphen = |c: char| c == '-';
+    if !at(Some(position), hyphen) {
+        return false;
+    }
+    let after_letters = at(before(1), is_letter)
+        && (at(before(2), is_letter) || at(before(2), hyphen) && at(before(3), is_letter));
+    let letters_follow = at(Some(position + 1), is_letter)
+        && (at(Some(position + 2), is_letter)
+            || at(Some(position + 2), hyphen) && at(Some(position + 3), is_letter));
+    after_letters && letters_follow
+}
+
+impl Wrapper {
+    /// Splits text into the chunks lines are made of: runs of whitespace,
+    /// and words, which are split after hyphens and before em-dashes if
+    /// `break_on_hyphens`, as `TextWrapper.wordsep_re`.
+    fn split(&self, text: &[char]) -> Vec<Vec<char>> {
+        let mut chunks = Vec::new();
+        let mut position = 0;
+        while position < text.len() {
+            let start = position;
+            let space = is_break_space(text[position]);
+            let em_dash = match position.checked_sub(1) {
+                Some(before) if self.break_on_hyphens && is_word_punct(text[before]) => {
+                    em_dash_end(text, position)
+                }
+                _ => None,
+            };
+            if space || !self.break_on_hyphens {
+                while position < text.len() && is_break_space(text[position]) == space {
+                    position += 1;
+                }
+            } else if let Some(end) = em_dash {
+                position = end;
+            } else {
+                position += 1;
+                loop {
+                    if hyphen_breaks(text, position) {
+                        position += 1;
+                        break;
+                    }
+                    if position == text.len() || is_break_space(text[position]) {
+                        break;
+                    }
+                    if is_word_punct(text[position - 1]) && em_dash_end(text, position).is_some() {
+                        break;
+                    }
+                    position += 1;
+                }
+            }
+            chunks.push(text[start..position].to_vec());
+        }
+        chunks
+    }
+
+    /// The text with tabs expanded and whitespace made spaces, then split
+    /// into chunks, with two spaces after the ends of sentences if
+    /// `fix_sentence_endings`.
+    fn chunks(&self, text: &str) -> Vec<Vec<char>> {
+        let text = if self.expand_tabs {
+            expand_tabs(text, self.tab_size)
+        } else {
+            text.to_string()
+        };
+        let text: Vec<char> = text
+            .chars()
+            .map(|c| {
+                if self.replace_whitespace && is_break_space(c) {
+                    ' '
+                } else {
+                    c
+                }
+            })
+            .collect();
+        let mut chunks = self.split(&text);
+        if self.fix_sentence_endings {
+            let mut index = 0;
+            while index + 1 < chunks.len() {
+                if chunks[index + 1] == [' '] && ends_sentence(&chunks[index]) {
+                    chunks[index + 1] = vec![' ', ' '];
+                    index += 2;
+                } else {
+                    index += 1;
+                }
+            }
+        }
+        chunks
+    }
+
+    /// Lays the chunks out in lines, as `TextWrapper._wrap_chunks`.
+    fn wrap_chunks(&self, mut chunks: Vec<Vec<char>>) -> Result<Vec<String>, String> {
+        if self.width <= 0 {
+            return Err(format!("invalid width {} (must be > 0)", self.width));
+        }
+        if let Some(max_lines) = self.max_lines {
+            let indent = if max_lines > 1 {
+                &self.subsequent_indent
+            } else {
+                &self.initial_indent
+            };
+            let placeholder = self
+                .placeholder
+                .iter()
+                .skip_while(|&&c| is_space(c))
+                .count();
+            if (indent.len() + placeholder) as i64 > self.width {
+                return Err("placeholder too large for max width".to_string());
+            }
+        }
+        let mut lines: Vec<Vec<char>> = Vec::new();
+        chunks.reverse();
+        while !chunks.is_empty() {
+            let mut line: Vec<Vec<char>> = Vec::new();
+            let mut len = 0;
+            let indent = if lines.is_empty() {
+                &self.initial_indent
+            } else {
+                &self.subsequent_indent
+            };
+            let width = self.width - indent.len() as i64;
+            if self.drop_whitespace && !lines.is_empty() && is_blank(chunks.last().unwrap()) {
+                chunks.pop();
+            }
+            while let Some(chunk) = chunks.last() {
+                if len + chunk.len() as i64 <= width {
+                    len += chunk.len() as i64;
+                    line.push(chunks.pop().unwrap());
+                } else {
+                    break;
+                }
+            }
+            if chunks
+                .last()
+                .is_some_and(|chunk| chunk.len() as i64 > width)
+            {
+                self.handle_long_word(&mut chunks, &mut line, len, width);
+                len = line.iter().map(|chunk| chunk.len() as i64).sum();
+            }
+            if self.drop_whitespace && line.last().is_some_and(|chunk| is_blank(chunk)) {
+                len -= line.pop().unwrap().len() as i64;
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let rest_is_blank = chunks.is_empty()
+                || self.drop_whitespace && chunks.len() == 1 && is_blank(&chunks[0]);
+            let fits = match self.max_lines {
+                None => true,
+                Some(max_lines) => {
+                    (lines.len() as i64) + 1 < max_lines || rest_is_blank && len <= width
+                }
+            };
+            if fits {
+                lines.push(indent.iter().chain(line.concat().iter()).cloned().collect());
+                continue;
+            }
+            // The last line allowed, which ends with the placeholder.
+            loop {
+                match line.last() {
+                    Some(chunk)
+                        if !is_blank(chunk) && len + self.placeholder.len() as i64 <= width =>
+                    {
+                        line.push(self.placeholder.clone());
+                        lines.push(indent.iter().chain(line.concat().iter()).cloned().collect());
+                        break;
+                    }
+                    Some(_) => len -= line.pop().unwrap().len() as i64,
+                    None => {
+                        if let Some(previous) = lines.last_mut() {
+                            while previous.last().is_some_and(|&c| is_space(c)) {
+                                previous.pop();
+                            }
+                            if (previous.len() + self.placeholder.len()) as i64 <= self.width {
+                                previous.extend(self.placeholder.iter().cloned());
+                                break;
+                            }
+                        }
+                        let placeholder = self.placeholder.iter().skip_while(|&&c| is_space(c));
+                        lines.push(indent.iter().chain(placeholder).cloned().collect());
+                        break;
+                    }
+                }
+            }
+            break;
+        }
+        Ok(lines.iter().map(|line| line.iter().collect()).collect())
+    }
+
+    /// Breaks the chunk too long for any line, as
+    /// `TextWrapper._handle_long_word`.
+    fn handle_long_word(
+        &self,
+        chunks: &mut Vec<Vec<char>>,
+        line: &mut Vec<Vec<char>>,
+        len: i64,
+        width: i64,
+    ) {
+        let space_left = if width < 1 { 1 } else { (width - len) as usize };
+        if self.break_long_words {
+            let chunk = chunks.pop().unwrap();
+            let mut end = space_left.min(chunk.len());
+            if self.break_on_hyphens && chunk.len() > space_left {
+                let hyphen = chunk[..space_left].iter().rposition(|&c| c == '-');
+                if let Some(hyphen) = hyphen {
+                    if hyphen > 0 && chunk[..hyphen].iter().any(|&c| c != '-') {
+                        end = hyphen + 1;
+                    }
+                }
+            }
+            line.push(chunk[..end].to_vec());
+            chunks.push(chunk[end..].to_vec());
+        } else if line.is_empty() {
+            line.push(chunks.pop().unwrap());
+        }
+    }
+
+    fn wrap(&self, vm: &mut Vm, text: &str) -> PyResult<Vec<String>> {
+        let chunks = self.chunks(text);
+        self.wrap_chunks(chunks)
+            .map_err(|message| vm.new_value_error(message))
+    }
+}
+
+/// Whether a chunk ends a sentence: a lowercase letter, then `.`, `!` or
+/// `?`, then perhaps a quote.
+fn ends_sentence(chunk: &[char]) -> bool {
+    let chunk = match chunk.last() {
+        Some('"') | Some('\'') => &chunk[..chunk.len() - 1],
+        _ => chunk,
+    };
+    match *chunk {
+        [.., letter, mark] => letter.is_ascii_lowercase() && matches!(mark, '.' | '!' | '?'),
+        _ => false,
+    }
+}
+
+/// The text and the wrapper of `wrap(text, width=70, **kwargs)` and the
+/// functions like it. `shorten` has no default width, and `max_lines` of 1.
+fn wrapper_arguments(vm: &mut Vm, args: Args, function: &str) -> PyResult<(String, Wrapper)> {
+    let Args {
+        positional,
+        keywords,
+    } = args;
+    let (named, options): (Vec<_>, Vec<_>) = keywords
+        .into_iter()
+        .partition(|(name, _)| name == "text" || name == "width");
+    let shorten = function == "shorten";
+    let args = Args {
+        positional,
+        keywords: named,
+    };
+    let required = if shorten { 2 } else { 1 };
+    let values = bind_arguments(vm, args, function, &["text", "width"], required)?;
+    let text = str_argument(vm, function, "text", values[0].as_ref().unwrap())?;
+    let width = match values[1] {
+        Some(ref width) => index_value(vm, width)?,
+        None => 70,
+    };
+    let mut wrapper = Wrapper {
+        width,
+        initial_indent: Vec::new(),
+        subsequent_indent: Vec::new(),
+        expand_tabs: true,
+        replace_whitespace: true,
+        fix_sentence_endings: false,
+        break_long_words: true,
+        drop_whitespace: true,
+        break_on_hyphens: true,
+        tab_size: 8,
+        max_lines: if shorten { Some(1) } else { None },
+        placeholder: " [...]".chars().collect(),
+    };
+    for (name, value) in options {
+        let text = |vm: &mut Vm| -> PyResult<Vec<char>> {
+            Ok(str_argument(vm, function, &name, &value)?.chars().collect())
+        };
+        match name.as_str() {
+            "initial_indent" => wrapper.initial_indent = text(vm)?,
+            "subsequent_indent" => wrapper.subsequent_indent = text(vm)?,
+            "placeholder" => wrapper.placeholder = text(vm)?,
+            "expand_tabs" => wrapper.expand_tabs = vm.is_true(&value)?,
+            "replace_whitespace" => wrapper.replace_whitespace = vm.is_true(&value)?,
+            "fix_sentence_endings" => wrapper.fix_sentence_endings = vm.is_true(&value)?,
+            "break_long_words" => wrapper.break_long_words = vm.is_true(&value)?,
+            "drop_whitespace" => wrapper.drop_whitespace = vm.is_true(&value)?,
+            "break_on_hyphens" => wrapper.break_on_hyphens = vm.is_true(&value)?,
+            "tabsize" => wrapper.tab_size = index_value(vm, &value)?,
+            "max_lines" if shorten => {
+                let message =
+                    "TextWrapper.__init__() got multiple values for keyword argument 'max_lines'";
+                return Err(vm.new_type_error(message.to_string()));
+            }
+            "max_lines" if Vm::is(&value, &vm.none()) => wrapper.max_lines = None,
+            "max_lines" => wrapper.max_lines = Some(index_value(vm, &value)?),
+            _ => {
+                let message = format!(
+                    "TextWrapper.__init__() got an unexpected keyword argument '{}'",
+                    name
+                );
+                return Err(vm.new_type_error(message));
+            }
+        }
+    }
+    Ok((text, wrapper))
+}
+
+/// `textwrap.wrap(text, width=70, **kwargs)`.
+fn wrap(vm: &mut Vm, args: Args) -> PyResult {
+    let (text, wrapper) = wrapper_arguments(vm, args, "wrap")?;
+    let lines = wrapper.wrap(vm, &text)?;
+    let lines = lines.iter().map(|line| vm.new_str(line)).collect();
+    Ok(vm.new_list(lines))
+}
+
+/// `textwrap.fill(text, width=70, **kwargs)`.
+fn fill(vm: &mut Vm, args: Args) -> PyResult {
+    let (text, wrapper) = wrapper_arguments(vm, args, "fill")?;
+    let lines = wrapper.wrap(vm, &text)?;
+    Ok(vm.new_str(&lines.join("\n")))
+}
+
+/// `textwrap.shorten(text, width, **kwargs)`: the text with its whitespace
+/// collapsed, cut to fit on one line of `width`, ending with the
+/// placeholder if cut.
+fn shorten(vm: &mut Vm, args: Args) -> PyResult {
+    let (text, wrapper) = wrapper_arguments(vm, args, "shorten")?;
+    let words: Vec<&str> = text
+        .split(is_space)
+        .filter(|word| !word.is_empty())
+        .collect();
+    let lines = wrapper.wrap(vm, &words.join(" "))?;
+    Ok(vm.new_str(&lines.join("\n")))
+}
+
+/// `textwrap.dedent(text)`: the text with the whitespace all its lines
+/// start with removed. Lines of only spaces and tabs are emptied, and do
+/// not count.
+fn dedent(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "dedent", &["text"], 1)?;
+    let text = str_argument(vm, "dedent", "text", values[0].as_ref().unwrap())?;
+    let is_indent = |c: char| c == ' ' || c == '\t';
+    let lines: Vec<&str> = text
+        .split('\n')
+        .map(|line| {
+            if !line.is_empty() && line.chars().all(is_indent) {
+                ""
+            } else {
+                line
+            }
+        })
+        .collect();
+    let mut margin: Option<&str> = None;
+    for line in &lines {
+        let indent = &line[..line.len() - line.trim_start_matches(is_indent).len()];
+        if indent.len() == line.len() {
+            continue;
+        }
+        margin = Some(match margin {
+            None => indent,
+            Some(margin) if indent.starts_with(margin) => margin,
+            Some(margin) if margin.starts_with(indent) => indent,
+            Some(margin) => {
+                let common = margin
+                    .bytes()
+                    .zip(indent.bytes())
+                    .take_while(|(a, b)| a == b)
+                    .count();
+                &margin[..common]
+            }
+        });
+    }
+    let margin = margin.unwrap_or("");
+    let lines: Vec<&str> = lines
+        .iter()
+        .map(|line| line.strip_prefix(margin).unwrap_or(line))
+        .collect();
+    Ok(vm.new_str(&lines.join("\n")))
+}
+
+/// `textwrap.indent(text, prefix, predicate=None)`: the text with `prefix`
+/// before the lines `predicate` accepts, by default those that are not
+/// all whitespace.
+fn indent(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "indent", &["text", "prefix", "predicate"], 2)?;
+    let text = str_argument(vm, "indent", "text", values[0].as_ref().unwrap())?;
+    let prefix = str_argument(vm, "indent", "prefix", values[1].as_ref().unwrap())?;
+    let predicate = values[2]
+        .clone()
+        .filter(|predicate| !Vm::is(predicate, &vm.none()));
+    let mut out = String::with_capacity(text.len());
+    for line in split_lines(&text, true) {
+        let accepted = match predicate {
+            Some(ref predicate) => {
+                let line = vm.new_str(line);
+                let result = vm.call(predicate, Args::new(vec![line]))?;
+                vm.is_true(&result)?
+            }
+            None => !line.chars().all(is_space),
+        };
+        if accepted {
+            out.push_str(&prefix);
+        }
+        out.push_str(line);
+    }
+    Ok(vm.new_str(&out))
+}
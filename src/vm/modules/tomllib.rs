@@ -0,0 +1,180 @@
+//! `tomllib`: reading TOML 1.0 documents into dicts, lists, strings,
+//! ints, floats, bools and the dates and times of `datetime`. The crate's
+//! `toml` reads the document, as CPython's `tomllib._parser` does, so that
+//! the errors are the same.
+
+use toml::{self, Table, Value};
+
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{
+    add_attribute, bind_arguments, module_error, new_native_class, new_native_module, utf8_text,
+};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "tomllib", &[("load", load), ("loads", loads)]);
+    let value_error = vm.exceptions.value_error.clone();
+    let error = new_native_class(vm, "tomllib", "TOMLDecodeError", &value_error, &[])?;
+    add_attribute(vm, &module, "TOMLDecodeError", error);
+    Ok(module)
+}
+
+/// `loads(s, /, *, parse_float=float)`.
+fn loads(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "loads", &["s", "parse_float"], 1)?;
+    let source = values[0].as_ref().unwrap();
+    let text = match source.as_str() {
+        Some(text) => text.to_string(),
+        // The errors are those of `s.replace("\r\n", "\n")`.
+        None => match source.payload {
+            Payload::Bytes(_) => {
+                let message = "a bytes-like object is required, not 'str'".to_string();
+                return Err(vm.new_type_error(message));
+            }
+            _ => return Err(vm.getattr(source, "replace").unwrap_err()),
+        },
+    };
+    parse(vm, &text, values[1].clone())
+}
+
+/// `load(fp, /, *, parse_float=float)`: the document in the bytes that
+/// `fp.read()` gives.
+fn load(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "load", &["fp", "parse_float"], 1)?;
+    let read = vm.getattr(values[0].as_ref().unwrap(), "read")?;
+    let data = vm.call(&read, Args::default())?;
+    let data = match data.payload {
+        Payload::Bytes(ref data) => data.clone(),
+        _ => {
+            let message =
+                "File must be opened in binary mode, e.g. use `open('foo.toml', 'rb')`".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let text = utf8_text(vm, data)?;
+    parse(vm, &text, values[1].clone())
+}
+
+fn parse(vm: &mut Vm, text: &str, parse_float: Option<ObjectRef>) -> PyResult {
+    let document = match toml::parse(text) {
+        Ok(document) => document,
+        Err(err) => {
+            return Err(module_error(
+                vm,
+                "tomllib",
+                "TOMLDecodeError",
+                err.to_string(),
+            ))
+        }
+    };
+    let parse_float = match parse_float {
+        Some(parse_float) => parse_float,
+        None => vm.types.float.clone(),
+    };
+    table(vm, &document, &parse_float)
+}
+
+fn table(vm: &mut Vm, table: &Table, parse_float: &ObjectRef) -> PyResult {
+    let dict = vm.new_dict();
+    for (key, value) in table {
+        let key = vm.new_str(key);
+        let value = object(vm, value, parse_float)?;
+        vm.dict_set(dict.as_dict().unwrap(), key, value)?;
+    }
+    Ok(dict)
+}
+
+fn object(vm: &mut Vm, value: &Value, parse_float: &ObjectRef) -> PyResult {
+    match *value {
+        Value::String(ref text) => Ok(vm.new_str(text)),
+        Value::Integer(ref text) => {
+            let int = vm.types.int.clone();
+            let text = vm.new_str(text);
+            let base = vm.new_int(0);
+            vm.call(&int, Args::new(vec![text, base]))
+        }
+        Value::Float(ref text) => {
+            let text = vm.new_str(text);
+            let value = vm.call(parse_float, Args::new(vec![text]))?;
+            // `parse_float` may not give a dict or a list.
+            if let Payload::Dict(_) | Payload::List(_) = value.payload {
+                let message = "parse_float must not return dicts or lists".to_string();
+                return Err(vm.new_value_error(message));
+            }
+            Ok(value)
+        }
+        Value::Boolean(value) => Ok(vm.new_bool(value)),
+        Value::Datetime(ref text) => datetime(vm, text),
+        Value::Array(ref values) => {
+            let mut items = Vec::with_capacity(values.len());
+            for value in values {
+                items.push(object(vm, value, parse_float)?);
+            }
+            Ok(vm.new_list(items))
+        }
+        Value::Table(ref value) => table(vm, value, parse_float),
+    }
+}
+
+/// The `datetime`, `date` or `time` written as `text`, which the parser
+/// has checked: `YYYY-MM-DD`, then `HH:MM:SS`, a fraction and an offset
+/// from UTC if there are any. Digits of the fraction after the sixth are
+/// dropped, as `tomllib` drops them.
+fn datetime(vm: &mut Vm, text: &str) -> PyResult {
+    let module = vm.import_module("datetime")?;
+    let number = |start: usize, end: usize| text[start..end].parse::<i64>().unwrap();
+    let mut fields = Vec::new();
+    let mut rest = text;
+    if text.len() >= 10 && &text[4..5] == "-" {
+        fields.extend([number(0, 4), number(5, 7), number(8, 10)]);
+        if text.len() == 10 {
+            let class = vm.getattr(&module, "date")?;
+            let args = fields.into_iter().map(|value| vm.new_int(value)).collect();
+            return vm.call(&class, Args::new(args));
+        }
+        rest = &text[11..];
+    }
+    fields.extend([
+        rest[0..2].parse::<i64>().unwrap(),
+        rest[3..5].parse().unwrap(),
+        rest[6..8].parse().unwrap(),
+    ]);
+    rest = &rest[8..];
+    let mut microsecond = 0;
+    if let Some(fraction) = rest.strip_prefix('.') {
+        let digits = fraction
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(fraction.len());
+        let mut micros = fraction[..digits.min(6)].to_string();
+        while micros.len() < 6 {
+            micros.push('0');
+        }
+        microsecond = micros.parse().unwrap();
+        rest = &fraction[digits..];
+    }
+    fields.push(microsecond);
+    let mut args: Vec<ObjectRef> = fields.into_iter().map(|value| vm.new_int(value)).collect();
+    if !rest.is_empty() {
+        args.push(timezone(vm, &module, rest)?);
+    }
+    let name = if args.len() > 4 { "datetime" } else { "time" };
+    let class = vm.getattr(&module, name)?;
+    vm.call(&class, Args::new(args))
+}
+
+/// The timezone of the offset `Z` or `+HH:MM`.
+fn timezone(vm: &mut Vm, module: &ObjectRef, offset: &str) -> PyResult {
+    let timezone = vm.getattr(module, "timezone")?;
+    if offset == "Z" || offset == "z" {
+        return vm.getattr(&timezone, "utc");
+    }
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let hours = vm.new_int(sign * offset[1..3].parse::<i64>().unwrap());
+    let minutes = vm.new_int(sign * offset[4..6].parse::<i64>().unwrap());
+    let timedelta = vm.getattr(module, "timedelta")?;
+    let mut args = Args::default();
+    args.keywords.push(("hours".to_string(), hours));
+    args.keywords.push(("minutes".to_string(), minutes));
+    let delta = vm.call(&timedelta, args)?;
+    vm.call(&timezone, Args::new(vec![delta]))
+}
@@ -0,0 +1,146 @@
+//! `types`: `GenericAlias`, what subscripting a builtin class such as
+//! `list[int]` gives, for annotations that are evaluated.
+//!
+//! An alias keeps the class in `__origin__` and the subscript in
+//! `__args__`; calling it calls the class. Type variables are not
+//! collected, so `__parameters__` is always empty.
+
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, new_native_class, new_native_module};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "types", &[]);
+    let object = vm.types.object.clone();
+    let class = new_native_class(
+        vm,
+        "types",
+        "GenericAlias",
+        &object,
+        &[
+            ("__call__", alias_call),
+            ("__eq__", alias_eq),
+            ("__hash__", alias_hash),
+            ("__init__", alias_init),
+            ("__repr__", alias_repr),
+        ],
+    )?;
+    add_attribute(vm, &module, "GenericAlias", class);
+    Ok(module)
+}
+
+/// `X.__class_getitem__(item)` for the builtin classes that take a
+/// subscript: `types.GenericAlias(X, item)`.
+pub(in super::super) fn class_getitem(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__class_getitem__", &["cls", "item"], 2)?;
+    let types = vm.import_module("types")?;
+    let class = vm.getattr(&types, "GenericAlias")?;
+    let args = values.into_iter().map(Option::unwrap).collect();
+    vm.call(&class, Args::new(args))
+}
+
+/// `GenericAlias(origin, args)`: `args` is made a tuple if it is not one.
+fn alias_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(
+        vm,
+        args,
+        "GenericAlias.__init__",
+        &["self", "origin", "args"],
+        3,
+    )?;
+    let this = values[0].clone().unwrap();
+    let origin = values[1].clone().unwrap();
+    let mut args = values[2].clone().unwrap();
+    if !matches!(args.payload, Payload::Tuple(_)) {
+        args = vm.new_tuple(vec![args]);
+    }
+    let parameters = vm.new_tuple(Vec::new());
+    vm.setattr(&this, "__origin__", origin)?;
+    vm.setattr(&this, "__args__", args)?;
+    vm.setattr(&this, "__parameters__", parameters)?;
+    Ok(vm.none())
+}
+
+/// The `__origin__` and `__args__` of `alias`, if it is a `GenericAlias`.
+fn parts(vm: &mut Vm, alias: &ObjectRef) -> PyResult<Option<(ObjectRef, ObjectRef)>> {
+    let types = vm.import_module("types")?;
+    let class = vm.getattr(&types, "GenericAlias")?;
+    if !Vm::is_instance(alias, &class) {
+        return Ok(None);
+    }
+    let origin = vm.getattr(alias, "__origin__")?;
+    let args = vm.getattr(alias, "__args__")?;
+    Ok(Some((origin, args)))
+}
+
+/// `GenericAlias.__call__(*args, **kwargs)`: calls the origin.
+fn alias_call(vm: &mut Vm, mut args: Args) -> PyResult {
+    if args.positional.is_empty() {
+        let message = "GenericAlias.__call__() needs an argument".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    let this = args.positional.remove(0);
+    let origin = vm.getattr(&this, "__origin__")?;
+    vm.call(&origin, args)
+}
+
+/// `GenericAlias.__eq__(other)`: the same origin and the same arguments.
+fn alias_eq(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "GenericAlias.__eq__", &["self", "other"], 2)?;
+    let this = parts(vm, values[0].as_ref().unwrap())?;
+    let other = parts(vm, values[1].as_ref().unwrap())?;
+    match (this, other) {
+        (Some((origin, args)), Some((other_origin, other_args))) => {
+            let equal = vm.eq(&origin, &other_origin)? && vm.eq(&args, &other_args)?;
+            Ok(vm.new_bool(equal))
+        }
+        _ => Ok(vm.not_implemented()),
+    }
+}
+
+/// `GenericAlias.__hash__()`: from the origin and the arguments, so that
+/// equal aliases hash alike.
+fn alias_hash(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "GenericAlias.__hash__", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    let origin = vm.getattr(&this, "__origin__")?;
+    let args = vm.getattr(&this, "__args__")?;
+    let hash = vm.hash(&origin)? ^ vm.hash(&args)?;
+    Ok(vm.new_int(hash))
+}
+
+/// `GenericAlias.__repr__()`: `list[int]`, with classes named as in
+/// source and `...` for an ellipsis, as CPython writes it.
+fn alias_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "GenericAlias.__repr__", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    let origin = vm.getattr(&this, "__origin__")?;
+    let args = vm.getattr(&this, "__args__")?;
+    let args = match args.payload {
+        Payload::Tuple(ref items) => items.clone(),
+        _ => vec![args.clone()],
+    };
+    let mut text = format!("{}[", argument_repr(vm, &origin)?);
+    if args.is_empty() {
+        text.push_str("()");
+    }
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            text.push_str(", ");
+        }
+        text.push_str(&argument_repr(vm, arg)?);
+    }
+    text.push(']');
+    Ok(vm.new_str(&text))
+}
+
+/// How an alias writes its origin and each of its arguments.
+fn argument_repr(vm: &mut Vm, value: &ObjectRef) -> PyResult<String> {
+    if matches!(value.payload, Payload::Ellipsis) {
+        return Ok("...".to_string());
+    }
+    if value.as_type().is_some() {
+        return Ok(vm.class_name(value));
+    }
+    vm.repr(value)
+}
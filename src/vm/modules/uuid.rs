@@ -0,0 +1,470 @@
+//! `uuid`: the UUID objects of RFC 4122, random ones from `uuid4` and
+//! name-based ones from `uuid5`.
+//!
+//! A UUID is its 16 bytes, kept in the `bytes` attribute; the attributes
+//! derived from them are set when it is made. Ints have 64 bits here, so
+//! there is no `int` attribute, and `int=` takes only values below 2**63.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{add_attribute, bind_arguments, new_native_class, new_native_module, urandom};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "uuid", &[("uuid4", uuid4), ("uuid5", uuid5)]);
+    let object = vm.types.object.clone();
+    let class = new_native_class(
+        vm,
+        "uuid",
+        "UUID",
+        &object,
+        &[
+            ("__eq__", uuid_eq),
+            ("__ge__", uuid_ge),
+            ("__gt__", uuid_gt),
+            ("__hash__", uuid_hash),
+            ("__init__", uuid_init),
+            ("__le__", uuid_le),
+            ("__lt__", uuid_lt),
+            ("__repr__", uuid_repr),
+            ("__str__", uuid_str),
+        ],
+    )?;
+    add_attribute(vm, &module, "UUID", class.clone());
+    for &(name, value) in &VARIANTS {
+        let value = vm.new_str(value);
+        add_attribute(vm, &module, name, value);
+    }
+    let namespaces = [
+        ("NAMESPACE_DNS", "6ba7b810-9dad-11d1-80b4-00c04fd430c8"),
+        ("NAMESPACE_URL", "6ba7b811-9dad-11d1-80b4-00c04fd430c8"),
+        ("NAMESPACE_OID", "6ba7b812-9dad-11d1-80b4-00c04fd430c8"),
+        ("NAMESPACE_X500", "6ba7b814-9dad-11d1-80b4-00c04fd430c8"),
+    ];
+    for &(name, hex) in &namespaces {
+        let hex = vm.new_str(hex);
+        let namespace = vm.call(&class, Args::new(vec![hex]))?;
+        add_attribute(vm, &module, name, namespace);
+    }
+    Ok(module)
+}
+
+/// The names of the variants, which are also their values.
+const VARIANTS: [(&str, &str); 4] = [
+    ("RESERVED_NCS", "reserved for NCS compatibility"),
+    ("RFC_4122", "specified in RFC 4122"),
+    ("RESERVED_MICROSOFT", "reserved for Microsoft compatibility"),
+    ("RESERVED_FUTURE", "reserved for future definition"),
+];
+
+/// `UUID(hex=None, bytes=None, bytes_le=None, fields=None, int=None,
+/// version=None)`: exactly one of the first five gives the value, and
+/// `version` overwrites the variant and version bits of it.
+fn uuid_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(
+        vm,
+        args,
+        "UUID.__init__",
+        &[
+            "self", "hex", "bytes", "bytes_le", "fields", "int", "version",
+        ],
+        1,
+    )?;
+    let none = vm.none();
+    let given: Vec<Option<ObjectRef>> = values
+        .into_iter()
+        .map(|value| value.filter(|value| !Vm::is(value, &none)))
+        .collect();
+    let this = given[0].clone().unwrap();
+    if given[1..6].iter().filter(|value| value.is_some()).count() != 1 {
+        let message =
+            "one of the hex, bytes, bytes_le, fields, or int arguments must be given".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    let mut bytes = if let Some(ref hex) = given[1] {
+        let hex = match hex.as_str() {
+            Some(hex) => hex.replace("urn:", "").replace("uuid:", ""),
+            None => {
+                let message = "a str is required for hex".to_string();
+                return Err(vm.new_type_error(message));
+            }
+        };
+        let hex = hex.trim_matches(|c| c == '{' || c == '}').replace('-', "");
+        match parse_hex(&hex) {
+            Some(bytes) => bytes,
+            None => {
+                let message = "badly formed hexadecimal UUID string".to_string();
+                return Err(vm.new_value_error(message));
+            }
+        }
+    } else if let Some(ref bytes) = given[2] {
+        sixteen_bytes(vm, bytes, "bytes")?
+    } else if let Some(ref bytes_le) = given[3] {
+        let little = sixteen_bytes(vm, bytes_le, "bytes_le")?;
+        swap_fields(little)
+    } else if let Some(ref fields) = given[4] {
+        from_fields(vm, fields)?
+    } else {
+        let int = index_value(vm, given[5].as_ref().unwrap())?;
+        if int < 0 {
+            let message = "int is out of range (need a 128-bit value)".to_string();
+            return Err(vm.new_value_error(message));
+        }
+        let mut bytes = [0; 16];
+        bytes[8..].copy_from_slice(&int.to_be_bytes());
+        bytes
+    };
+    if let Some(ref version) = given[6] {
+        let version = index_value(vm, version)?;
+        if !(1..=5).contains(&version) {
+            return Err(vm.new_value_error("illegal version number".to_string()));
+        }
+        set_version(&mut bytes, version as u8);
+    }
+    set_attributes(vm, &this, bytes)?;
+    Ok(vm.none())
+}
+
+/// The bytes written as the 32 hexadecimal digits `hex`.
+fn parse_hex(hex: &str) -> Option<[u8; 16]> {
+    if hex.len() != 32 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0; 16];
+    for (index, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hex[2 * index..2 * index + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+/// The data of the bytes argument `name`, which must be 16 long.
+fn sixteen_bytes(vm: &mut Vm, value: &ObjectRef, name: &str) -> PyResult<[u8; 16]> {
+    let data = match value.payload {
+        Payload::Bytes(ref data) => data.clone(),
+        _ => {
+            let message = vm.repr(value)?;
+            let class = vm.exceptions.assertion_error.clone();
+            return Err(vm.new_error(&class, message));
+        }
+    };
+    if data.len() != 16 {
+        return Err(vm.new_value_error(format!("{} is not a 16-char string", name)));
+    }
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&data);
+    Ok(bytes)
+}
+
+/// The bytes with the first three fields reversed, which converts between
+/// `bytes` and `bytes_le`.
+fn swap_fields(mut bytes: [u8; 16]) -> [u8; 16] {
+    bytes[0..4].reverse();
+    bytes[4..6].reverse();
+    bytes[6..8].reverse();
+    bytes
+}
+
+/// The bytes of the six fields `fields`.
+fn from_fields(vm: &mut Vm, fields: &ObjectRef) -> PyResult<[u8; 16]> {
+    let fields = vm.iterate(fields)?;
+    if fields.len() != 6 {
+        return Err(vm.new_value_error("fields is not a 6-tuple".to_string()));
+    }
+    let widths = [32, 16, 16, 8, 8, 48];
+    let mut bytes = [0; 16];
+    let mut start = 0;
+    for (index, (field, &width)) in fields.iter().zip(&widths).enumerate() {
+        let value = index_value(vm, field)?;
+        if value < 0 || value >= 1 << width {
+            let message = format!(
+                "field {} out of range (need a {}-bit value)",
+                index + 1,
+                width
+            );
+            return Err(vm.new_value_error(message));
+        }
+        let end = start + width / 8;
+        bytes[start..end].copy_from_slice(&value.to_be_bytes()[8 - width / 8..]);
+        start = end;
+    }
+    Ok(bytes)
+}
+
+/// Marks `bytes` as an RFC 4122 UUID of the version `version`.
+fn set_version(bytes: &mut [u8; 16], version: u8) {
+    bytes[8] = bytes[8] & 0x3f | 0x80;
+    bytes[6] = bytes[6] & 0x0f | version << 4;
+}
+
+/// The index in `VARIANTS` of the variant of a UUID.
+fn variant(bytes: &[u8; 16]) -> usize {
+    bytes[8].leading_ones().min(3) as usize
+}
+
+/// Sets the attributes of the UUID `this`, whose value is `bytes`.
+fn set_attributes(vm: &mut Vm, this: &ObjectRef, bytes: [u8; 16]) -> PyResult<()> {
+    let field = |range: ::std::ops::Range<usize>| {
+        bytes[range]
+            .iter()
+            .fold(0i64, |value, &byte| value << 8 | i64::from(byte))
+    };
+    let fields = [
+        ("time_low", field(0..4)),
+        ("time_mid", field(4..6)),
+        ("time_hi_version", field(6..8)),
+        ("clock_seq_hi_variant", field(8..9)),
+        ("clock_seq_low", field(9..10)),
+        ("node", field(10..16)),
+    ];
+    let mut values = Vec::with_capacity(fields.len());
+    for &(name, value) in &fields {
+        let value = vm.new_int(value);
+        vm.setattr(this, name, value.clone())?;
+        values.push(value);
+    }
+    let values = vm.new_tuple(values);
+    vm.setattr(this, "fields", values)?;
+    let time = vm.new_int((field(6..8) & 0x0fff) << 48 | field(4..6) << 32 | field(0..4));
+    vm.setattr(this, "time", time)?;
+    let clock_seq = vm.new_int((field(8..9) & 0x3f) << 8 | field(9..10));
+    vm.setattr(this, "clock_seq", clock_seq)?;
+    let value = vm.new_bytes(bytes.to_vec());
+    vm.setattr(this, "bytes", value)?;
+    let value = vm.new_bytes(swap_fields(bytes).to_vec());
+    vm.setattr(this, "bytes_le", value)?;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    let urn = vm.new_str(&format!("urn:uuid:{}", hyphenated(&hex)));
+    vm.setattr(this, "urn", urn)?;
+    let hex = vm.new_str(&hex);
+    vm.setattr(this, "hex", hex)?;
+    let variant = variant(&bytes);
+    let name = vm.new_str(VARIANTS[variant].1);
+    vm.setattr(this, "variant", name)?;
+    let version = if variant == 1 {
+        vm.new_int(i64::from(bytes[6] >> 4))
+    } else {
+        vm.none()
+    };
+    vm.setattr(this, "version", version)
+}
+
+/// The 32 hexadecimal digits `hex` in the 8-4-4-4-12 form of UUIDs.
+fn hyphenated(hex: &str) -> String {
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// The bytes of a UUID, or `None` for other objects.
+fn uuid_bytes(vm: &mut Vm, object: &ObjectRef) -> PyResult<Option<Vec<u8>>> {
+    let uuid = vm.import_module("uuid")?;
+    let class = vm.getattr(&uuid, "UUID")?;
+    if !Vm::is_instance(object, &class) {
+        return Ok(None);
+    }
+    let bytes = vm.getattr(object, "bytes")?;
+    match bytes.payload {
+        Payload::Bytes(ref data) => Ok(Some(data.clone())),
+        _ => Ok(None),
+    }
+}
+
+/// The bytes of the UUID a method is called on.
+fn self_bytes(vm: &mut Vm, args: &Args, name: &str) -> PyResult<Vec<u8>> {
+    let this = match args.positional.first() {
+        Some(this) => this.clone(),
+        None => {
+            let message = format!("UUID.{}() needs an argument", name);
+            return Err(vm.new_type_error(message));
+        }
+    };
+    match uuid_bytes(vm, &this)? {
+        Some(bytes) => Ok(bytes),
+        None => {
+            let message = format!(
+                "descriptor '{}' requires a 'UUID' object but received a '{}'",
+                name,
+                this.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// Compares two UUIDs by their values, which their bytes order as the
+/// big-endian ints order.
+fn compare(vm: &mut Vm, args: Args, name: &str, test: fn(&Vec<u8>, &Vec<u8>) -> bool) -> PyResult {
+    let values = bind_arguments(vm, args, name, &["self", "other"], 2)?;
+    let this = uuid_bytes(vm, values[0].as_ref().unwrap())?;
+    let other = uuid_bytes(vm, values[1].as_ref().unwrap())?;
+    match (this, other) {
+        (Some(this), Some(other)) => Ok(vm.new_bool(test(&this, &other))),
+        _ => Ok(vm.not_implemented()),
+    }
+}
+
+fn uuid_eq(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__eq__", |a, b| a == b)
+}
+
+fn uuid_lt(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__lt__", |a, b| a < b)
+}
+
+fn uuid_le(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__le__", |a, b| a <= b)
+}
+
+fn uuid_gt(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__gt__", |a, b| a > b)
+}
+
+fn uuid_ge(vm: &mut Vm, args: Args) -> PyResult {
+    compare(vm, args, "__ge__", |a, b| a >= b)
+}
+
+/// `UUID.__hash__()`: the hash of the bytes, so that equal UUIDs hash
+/// alike.
+fn uuid_hash(vm: &mut Vm, args: Args) -> PyResult {
+    let bytes = self_bytes(vm, &args, "__hash__")?;
+    let bytes = vm.new_bytes(bytes);
+    let hash = vm.hash(&bytes)?;
+    Ok(vm.new_int(hash))
+}
+
+/// `UUID.__str__()`: the 8-4-4-4-12 form.
+fn uuid_str(vm: &mut Vm, args: Args) -> PyResult {
+    let bytes = self_bytes(vm, &args, "__str__")?;
+    let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+    Ok(vm.new_str(&hyphenated(&hex)))
+}
+
+/// `UUID.__repr__()`.
+fn uuid_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let this = args.positional.first().cloned();
+    let text = uuid_str(vm, args)?;
+    let class = this.unwrap().class();
+    let name = vm.getattr(&class, "__name__")?;
+    let (name, text) = (vm.str(&name)?, vm.repr(&text)?);
+    Ok(vm.new_str(&format!("{}({})", name, text)))
+}
+
+/// A new `UUID` whose value is `bytes`.
+fn new_uuid(vm: &mut Vm, bytes: [u8; 16]) -> PyResult {
+    let uuid = vm.import_module("uuid")?;
+    let class = vm.getattr(&uuid, "UUID")?;
+    let bytes = vm.new_bytes(bytes.to_vec());
+    let mut args = Args::new(Vec::new());
+    args.keywords.push(("bytes".to_string(), bytes));
+    vm.call(&class, args)
+}
+
+/// `uuid4()`: a random UUID.
+fn uuid4(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "uuid4", &[], 0)?;
+    let mut bytes = [0; 16];
+    if urandom(&mut bytes).is_err() {
+        // Without a source of randomness, the randomly keyed hashers of
+        // the standard library stand in for one.
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+        for half in bytes.chunks_mut(8) {
+            let mut hasher = RandomState::new().build_hasher();
+            hasher.write_u64(nanos);
+            half.copy_from_slice(&hasher.finish().to_be_bytes());
+        }
+    }
+    set_version(&mut bytes, 4);
+    new_uuid(vm, bytes)
+}
+
+/// `uuid5(namespace, name)`: the UUID made from the SHA-1 hash of a name
+/// in a namespace.
+fn uuid5(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "uuid5", &["namespace", "name"], 2)?;
+    let namespace = vm.getattr(values[0].as_ref().unwrap(), "bytes")?;
+    let mut data = match namespace.payload {
+        Payload::Bytes(ref data) => data.clone(),
+        _ => {
+            let message = format!("can't concat {} to bytes", namespace.type_name());
+            return Err(vm.new_type_error(message));
+        }
+    };
+    match values[1].as_ref().unwrap().as_str() {
+        Some(name) => data.extend_from_slice(name.as_bytes()),
+        None => {
+            let message = "encoding without a string argument".to_string();
+            return Err(vm.new_type_error(message));
+        }
+    }
+    let mut bytes = [0; 16];
+    bytes.copy_from_slice(&sha1(&data)[..16]);
+    set_version(&mut bytes, 5);
+    new_uuid(vm, bytes)
+}
+
+/// The SHA-1 digest of `data`.
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [
+        0x6745_2301,
+        0xefcd_ab89,
+        0x98ba_dcfe,
+        0x1032_5476,
+        0xc3d2_e1f0,
+    ];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&(data.len() as u64 * 8).to_be_bytes());
+    for block in message.chunks(64) {
+        let mut words = [0u32; 80];
+        for (index, word) in block.chunks(4).enumerate() {
+            words[index] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for index in 16..80 {
+            words[index] =
+                (words[index - 3] ^ words[index - 8] ^ words[index - 14] ^ words[index - 16])
+                    .rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (index, &word) in words.iter().enumerate() {
+            let (f, k) = match index {
+                0..=19 => (b & c | !b & d, 0x5a82_7999),
+                20..=39 => (b ^ c ^ d, 0x6ed9_eba1),
+                40..=59 => (b & c | b & d | c & d, 0x8f1b_bcdc),
+                _ => (b ^ c ^ d, 0xca62_c1d6),
+            };
+            let next = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = next;
+        }
+        for (value, added) in state.iter_mut().zip(&[a, b, c, d, e]) {
+            *value = value.wrapping_add(*added);
+        }
+    }
+    let mut digest = [0; 20];
+    for (bytes, value) in digest.chunks_mut(4).zip(&state) {
+        bytes.copy_from_slice(&value.to_be_bytes());
+    }
+    digest
+}
@@ -0,0 +1,927 @@
+//! `warnings`: the filters that decide what becomes of a warning, and the
+//! showing of those that pass them.
+//!
+//! The filters are a list of `(action, message, category, module, lineno)`
+//! tuples, as in CPython. `warn` consults them, and so does the compiler
+//! for its `SyntaxWarning`s, which become `SyntaxError`s when a filter
+//! turns them into errors. There is no `re` module, so the message and
+//! module patterns are kept as strings, and matched with a subset of
+//! regular expressions: characters, `.`, classes such as `[a-z]` and `\d`,
+//! the repetitions `*`, `+` and `?`, and `$` or `\Z` at the end.
+
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::sys::{write_file, write_standard};
+use super::{add_attribute, bind_arguments, new_native_class, new_native_module};
+
+/// What the filters may do with a warning.
+const ACTIONS: [&str; 6] = ["error", "ignore", "always", "default", "module", "once"];
+
+/// The filters and registries of a VM.
+#[derive(Default)]
+pub(crate) struct Warnings {
+    /// `warnings.filters`, made on first use.
+    filters: Option<ObjectRef>,
+    /// `warnings.onceregistry`: the warnings shown under the `once` action.
+    once: Option<ObjectRef>,
+    /// Changed whenever the filters are, so that the registries forget
+    /// which warnings they have seen.
+    version: i64,
+    /// The list that `catch_warnings(record=True)` collects warnings in,
+    /// instead of showing them.
+    log: Option<ObjectRef>,
+}
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        "warnings",
+        &[
+            ("filterwarnings", filterwarnings),
+            ("formatwarning", formatwarning),
+            ("resetwarnings", resetwarnings),
+            ("showwarning", showwarning),
+            ("simplefilter", simplefilter),
+            ("warn", warn),
+            ("warn_explicit", warn_explicit),
+        ],
+    );
+    let filters = filters(vm);
+    add_attribute(vm, &module, "filters", filters);
+    let once = once_registry(vm);
+    add_attribute(vm, &module, "onceregistry", once);
+    let action = vm.new_str("default");
+    add_attribute(vm, &module, "defaultaction", action);
+    let object = vm.types.object.clone();
+    let message = new_native_class(
+        vm,
+        "warnings",
+        "WarningMessage",
+        &object,
+        &[("__init__", message_init), ("__str__", message_str)],
+    )?;
+    add_attribute(vm, &module, "WarningMessage", message);
+    let catch_warnings = new_native_class(
+        vm,
+        "warnings",
+        "catch_warnings",
+        &object,
+        &[
+            ("__enter__", catch_enter),
+            ("__exit__", catch_exit),
+            ("__init__", catch_init),
+            ("__repr__", catch_repr),
+        ],
+    )?;
+    add_attribute(vm, &module, "catch_warnings", catch_warnings);
+    Ok(module)
+}
+
+/// `warnings.filters`, with CPython's defaults when first asked for.
+fn filters(vm: &mut Vm) -> ObjectRef {
+    if let Some(ref filters) = vm.warnings.filters {
+        return filters.clone();
+    }
+    let none = vm.none();
+    let zero = vm.new_int(0);
+    let main = vm.new_str("__main__");
+    let defaults = [
+        ("default", vm.exceptions.deprecation_warning.clone(), main),
+        (
+            "ignore",
+            vm.exceptions.deprecation_warning.clone(),
+            none.clone(),
+        ),
+        (
+            "ignore",
+            vm.exceptions.pending_deprecation_warning.clone(),
+            none.clone(),
+        ),
+        ("ignore", vm.exceptions.import_warning.clone(), none.clone()),
+        (
+            "ignore",
+            vm.exceptions.resource_warning.clone(),
+            none.clone(),
+        ),
+    ];
+    let mut items = Vec::with_capacity(defaults.len());
+    for (action, category, module) in defaults {
+        let action = vm.new_str(action);
+        let filter = vec![action, none.clone(), category, module, zero.clone()];
+        items.push(vm.new_tuple(filter));
+    }
+    let filters = vm.new_list(items);
+    vm.warnings.filters = Some(filters.clone());
+    filters
+}
+
+fn once_registry(vm: &mut Vm) -> ObjectRef {
+    if let Some(ref once) = vm.warnings.once {
+        return once.clone();
+    }
+    let once = vm.new_dict();
+    vm.warnings.once = Some(once.clone());
+    once
+}
+
+fn filters_mutated(vm: &mut Vm) {
+    vm.warnings.version += 1;
+}
+
+/// Issues the warning `message`, a string or a `Warning`, of the class
+/// `category`, as if raised at line `lineno` of `filename` in the module
+/// `module`. The module defaults to the file's name without `.py`.
+/// `registry` is the dict that remembers which warnings were shown, if
+/// any. A filter with the `error` action makes it raise the warning.
+pub(crate) fn warn_explicit_at(
+    vm: &mut Vm,
+    message: &ObjectRef,
+    category: &ObjectRef,
+    filename: &str,
+    lineno: usize,
+    module: Option<&str>,
+    registry: Option<&ObjectRef>,
+) -> PyResult<()> {
+    let module = match module {
+        Some(module) => module.to_string(),
+        None if filename.is_empty() => "<unknown>".to_string(),
+        None => filename.strip_suffix(".py").unwrap_or(filename).to_string(),
+    };
+    let (message, category) = if Vm::is_instance(message, &vm.exceptions.warning) {
+        (message.clone(), message.class())
+    } else {
+        let message = vm.call(category, Args::new(vec![message.clone()]))?;
+        (message, category.clone())
+    };
+    let text = vm.str(&message)?;
+    let text_object = vm.new_str(&text);
+    let line = vm.new_int(lineno as i64);
+    let key = vm.new_tuple(vec![text_object.clone(), category.clone(), line]);
+    if let Some(registry) = registry {
+        let version = vm.new_int(vm.warnings.version);
+        let version_key = vm.new_str("version");
+        let seen = vm.dict_get(registry.as_dict().unwrap(), &version_key)?;
+        let current = match seen {
+            Some(ref seen) => vm.eq(seen, &version)?,
+            None => false,
+        };
+        if !current {
+            registry.as_dict().unwrap().borrow_mut().clear();
+            vm.dict_set(registry.as_dict().unwrap(), version_key, version)?;
+        }
+        if let Some(seen) = vm.dict_get(registry.as_dict().unwrap(), &key)? {
+            if vm.is_true(&seen)? {
+                return Ok(());
+            }
+        }
+    }
+
+    let action = find_action(vm, &text, &category, &module, lineno)?;
+    let true_ = vm.new_bool(true);
+    match action.as_str() {
+        "error" => return Err(message),
+        "ignore" => return Ok(()),
+        "always" => {}
+        "once" => {
+            if let Some(registry) = registry {
+                vm.dict_set(registry.as_dict().unwrap(), key, true_.clone())?;
+            }
+            let once = once_registry(vm);
+            let once_key = vm.new_tuple(vec![text_object, category.clone()]);
+            if vm.dict_get(once.as_dict().unwrap(), &once_key)?.is_some() {
+                return Ok(());
+            }
+            vm.dict_set(once.as_dict().unwrap(), once_key, true_)?;
+        }
+        "module" => {
+            if let Some(registry) = registry {
+                vm.dict_set(registry.as_dict().unwrap(), key, true_.clone())?;
+                let zero = vm.new_int(0);
+                let module_key = vm.new_tuple(vec![text_object, category.clone(), zero]);
+                if vm
+                    .dict_get(registry.as_dict().unwrap(), &module_key)?
+                    .is_some()
+                {
+                    return Ok(());
+                }
+                vm.dict_set(registry.as_dict().unwrap(), module_key, true_)?;
+            }
+        }
+        "default" => {
+            if let Some(registry) = registry {
+                vm.dict_set(registry.as_dict().unwrap(), key, true_)?;
+            }
+        }
+        action => {
+            let message = format!("Unrecognized action ({:?}) in warnings.filters", action);
+            return Err(vm.new_runtime_error(message));
+        }
+    }
+    show(vm, message, category, filename, lineno)
+}
+
+/// The action of the first filter that `text`, of the class `category`,
+/// raised in `module` at line `lineno`, passes, or `default` for none.
+fn find_action(
+    vm: &mut Vm,
+    text: &str,
+    category: &ObjectRef,
+    module: &str,
+    lineno: usize,
+) -> PyResult<String> {
+    let filters = filters(vm);
+    let filters = match filters.payload {
+        Payload::List(ref items) => items.borrow().clone(),
+        _ => Vec::new(),
+    };
+    for filter in filters {
+        let items = match filter.payload {
+            Payload::Tuple(ref items) if items.len() == 5 => items.clone(),
+            _ => {
+                let message = "warnings.filters item must be a 5-tuple".to_string();
+                return Err(vm.new_value_error(message));
+            }
+        };
+        let matches = |pattern: &ObjectRef, text: &str, ignore_case: bool| match pattern.as_str() {
+            Some(pattern) => pattern_matches(pattern, text, ignore_case),
+            None => true,
+        };
+        let line = match items[4].payload {
+            Payload::Int(line) => line,
+            _ => 0,
+        };
+        if matches(&items[1], text, true)
+            && Vm::is_subclass(category, &items[2])
+            && matches(&items[3], module, false)
+            && (line == 0 || line == lineno as i64)
+        {
+            return Ok(items[0].as_str().unwrap_or_default().to_string());
+        }
+    }
+    Ok("default".to_string())
+}
+
+/// Shows a warning that passed the filters, or keeps it in the list of
+/// `catch_warnings(record=True)`.
+fn show(
+    vm: &mut Vm,
+    message: ObjectRef,
+    category: ObjectRef,
+    filename: &str,
+    lineno: usize,
+) -> PyResult<()> {
+    if let Some(log) = vm.warnings.log.clone() {
+        let module = vm.import_module("warnings")?;
+        let class = vm.getattr(&module, "WarningMessage")?;
+        let args = vec![
+            message,
+            category,
+            vm.new_str(filename),
+            vm.new_int(lineno as i64),
+        ];
+        let record = vm.call(&class, Args::new(args))?;
+        if let Payload::List(ref items) = log.payload {
+            items.borrow_mut().push(record);
+        }
+        return Ok(());
+    }
+    let text = vm.str(&message)?;
+    let line = source_line(vm, filename, lineno);
+    let name = category.as_type().unwrap().name.clone();
+    let report = format_warning(&text, &name, filename, lineno, line.as_deref());
+    write_standard(vm, "stderr", &[&report], false)
+}
+
+/// Line `lineno` of the source of `filename`, if the VM ran it.
+fn source_line(vm: &Vm, filename: &str, lineno: usize) -> Option<String> {
+    vm.kept_line(filename, lineno).map(str::to_string)
+}
+
+/// A warning as CPython shows it: where it was raised, its class and
+/// message, and the line of source if there is one.
+fn format_warning(
+    text: &str,
+    category: &str,
+    filename: &str,
+    lineno: usize,
+    line: Option<&str>,
+) -> String {
+    let mut report = format!("{}:{}: {}: {}\n", filename, lineno, category, text);
+    if let Some(line) = line {
+        let line = line.trim();
+        if !line.is_empty() {
+            report.push_str(&format!("  {}\n", line));
+        }
+    }
+    report
+}
+
+/// Issues a warning as raised by the running frame `stacklevel` frames
+/// out, counting the innermost as 1.
+pub(crate) fn warn_at_level(
+    vm: &mut Vm,
+    message: &ObjectRef,
+    category: &ObjectRef,
+    stacklevel: usize,
+) -> PyResult<()> {
+    let frame = vm
+        .frames
+        .len()
+        .checked_sub(stacklevel.max(1))
+        .map(|index| vm.frames[index].clone());
+    let frame = match frame {
+        Some(frame) => frame,
+        None => return warn_explicit_at(vm, message, category, "sys", 1, Some("sys"), None),
+    };
+    let lineno = frame
+        .code
+        .locations
+        .get(frame.lasti)
+        .map_or(frame.code.first_line, |location| location.line);
+    let globals = frame.globals.as_dict().unwrap();
+    let module = globals.borrow().get_str("__name__");
+    let module = module
+        .as_ref()
+        .and_then(|module| module.as_str())
+        .unwrap_or("<string>")
+        .to_string();
+    let registry = globals.borrow().get_str("__warningregistry__");
+    let registry = match registry {
+        Some(registry) if registry.as_dict().is_some() => registry,
+        _ => {
+            let registry = vm.new_dict();
+            vm.dict_set_str(globals, "__warningregistry__", registry.clone());
+            registry
+        }
+    };
+    let filename = frame.code.filename.clone();
+    warn_explicit_at(
+        vm,
+        message,
+        category,
+        &filename,
+        lineno,
+        Some(&module),
+        Some(&registry),
+    )
+}
+
+/// A `category` argument: a subclass of `Warning`, `UserWarning` for
+/// `None`.
+fn category_argument(vm: &mut Vm, category: Option<ObjectRef>) -> PyResult<ObjectRef> {
+    let category = match category {
+        Some(category) if !Vm::is(&category, &vm.none()) => category,
+        _ => return Ok(vm.exceptions.user_warning.clone()),
+    };
+    if category.as_type().is_none() || !Vm::is_subclass(&category, &vm.exceptions.warning) {
+        let message = format!(
+            "category must be a Warning subclass, not '{}'",
+            category.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(category)
+}
+
+/// `warn(message, category=None, stacklevel=1, source=None)`.
+fn warn(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["message", "category", "stacklevel", "source"];
+    let values = bind_arguments(vm, args, "warn", &names, 1)?;
+    let message = values[0].clone().unwrap();
+    let category = if Vm::is_instance(&message, &vm.exceptions.warning) {
+        message.class()
+    } else {
+        category_argument(vm, values[1].clone())?
+    };
+    let stacklevel = match values[2] {
+        Some(ref level) => index_value(vm, level)?,
+        None => 1,
+    };
+    warn_at_level(vm, &message, &category, stacklevel.max(1) as usize)?;
+    Ok(vm.none())
+}
+
+/// `warn_explicit(message, category, filename, lineno, module=None,
+/// registry=None, module_globals=None, source=None)`.
+fn warn_explicit(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "message",
+        "category",
+        "filename",
+        "lineno",
+        "module",
+        "registry",
+        "module_globals",
+        "source",
+    ];
+    let values = bind_arguments(vm, args, "warn_explicit", &names, 4)?;
+    let message = values[0].clone().unwrap();
+    let category = category_argument(vm, values[1].clone())?;
+    let filename = str_value(vm, values[2].as_ref().unwrap(), "filename")?;
+    let lineno = index_value(vm, values[3].as_ref().unwrap())?.max(0) as usize;
+    let none = vm.none();
+    let module = match values[4] {
+        Some(ref module) if !Vm::is(module, &none) => Some(str_value(vm, module, "module")?),
+        _ => None,
+    };
+    let registry = values[5]
+        .clone()
+        .filter(|registry| registry.as_dict().is_some());
+    warn_explicit_at(
+        vm,
+        &message,
+        &category,
+        &filename,
+        lineno,
+        module.as_deref(),
+        registry.as_ref(),
+    )?;
+    Ok(vm.none())
+}
+
+fn str_value(vm: &mut Vm, value: &ObjectRef, name: &str) -> PyResult<String> {
+    match value.as_str() {
+        Some(text) => Ok(text.to_string()),
+        None => {
+            let message = format!("{} must be a str, not {}", name, value.type_name());
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// Raises `AssertionError` with `message` unless `condition` holds, as the
+/// asserts of CPython's `warnings.py` do.
+fn check(vm: &mut Vm, condition: bool, message: String) -> PyResult<()> {
+    if condition {
+        return Ok(());
+    }
+    let class = vm.exceptions.assertion_error.clone();
+    Err(vm.new_error(&class, message))
+}
+
+fn check_action(vm: &mut Vm, action: &ObjectRef) -> PyResult<()> {
+    let valid = action
+        .as_str()
+        .is_some_and(|action| ACTIONS.contains(&action));
+    let message = format!("invalid action: {}", vm.repr(action)?);
+    check(vm, valid, message)
+}
+
+fn check_lineno(vm: &mut Vm, lineno: &Option<ObjectRef>) -> PyResult<ObjectRef> {
+    let lineno = match *lineno {
+        Some(ref lineno) => lineno.clone(),
+        None => return Ok(vm.new_int(0)),
+    };
+    let valid = matches!(lineno.payload, Payload::Int(line) if line >= 0);
+    check(vm, valid, "lineno must be an int >= 0".to_string())?;
+    Ok(lineno)
+}
+
+/// Puts a filter first, or last with `append`, taking out an equal one.
+fn add_filter(vm: &mut Vm, filter: Vec<ObjectRef>, append: bool) -> PyResult<()> {
+    let filter = vm.new_tuple(filter);
+    let filters = filters(vm);
+    let items = match filters.payload {
+        Payload::List(ref items) => items.borrow().clone(),
+        _ => Vec::new(),
+    };
+    let mut existing = None;
+    for (index, item) in items.iter().enumerate() {
+        if vm.eq(item, &filter)? {
+            existing = Some(index);
+            break;
+        }
+    }
+    if let Payload::List(ref items) = filters.payload {
+        let mut items = items.borrow_mut();
+        match existing {
+            Some(_) if append => {}
+            Some(index) => {
+                items.remove(index);
+                items.insert(0, filter);
+            }
+            None if append => items.push(filter),
+            None => items.insert(0, filter),
+        }
+    }
+    filters_mutated(vm);
+    Ok(())
+}
+
+/// `filterwarnings(action, message='', category=Warning, module='',
+/// lineno=0, append=False)`.
+fn filterwarnings(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "action", "message", "category", "module", "lineno", "append",
+    ];
+    let values = bind_arguments(vm, args, "filterwarnings", &names, 1)?;
+    let action = values[0].clone().unwrap();
+    check_action(vm, &action)?;
+    let mut patterns = Vec::with_capacity(2);
+    for (index, name) in [(1, "message"), (3, "module")] {
+        let pattern = match values[index] {
+            Some(ref pattern) => pattern.clone(),
+            None => vm.new_str(""),
+        };
+        let text = pattern.as_str().map(str::to_string);
+        check(vm, text.is_some(), format!("{} must be a string", name))?;
+        patterns.push(match text {
+            Some(ref text) if !text.is_empty() => pattern,
+            _ => vm.none(),
+        });
+    }
+    let category = match values[2] {
+        Some(ref category) => category.clone(),
+        None => vm.exceptions.warning.clone(),
+    };
+    let is_class = category.as_type().is_some();
+    check(vm, is_class, "category must be a class".to_string())?;
+    let is_warning = Vm::is_subclass(&category, &vm.exceptions.warning);
+    check(
+        vm,
+        is_warning,
+        "category must be a Warning subclass".to_string(),
+    )?;
+    let lineno = check_lineno(vm, &values[4])?;
+    let append = match values[5] {
+        Some(ref append) => vm.is_true(append)?,
+        None => false,
+    };
+    let module = patterns.pop().unwrap();
+    let message = patterns.pop().unwrap();
+    add_filter(vm, vec![action, message, category, module, lineno], append)?;
+    Ok(vm.none())
+}
+
+/// `simplefilter(action, category=Warning, lineno=0, append=False)`.
+fn simplefilter(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["action", "category", "lineno", "append"];
+    let values = bind_arguments(vm, args, "simplefilter", &names, 1)?;
+    let action = values[0].clone().unwrap();
+    check_action(vm, &action)?;
+    let category = match values[1] {
+        Some(ref category) => category.clone(),
+        None => vm.exceptions.warning.clone(),
+    };
+    let lineno = check_lineno(vm, &values[2])?;
+    let append = match values[3] {
+        Some(ref append) => vm.is_true(append)?,
+        None => false,
+    };
+    let none = vm.none();
+    add_filter(
+        vm,
+        vec![action, none.clone(), category, none, lineno],
+        append,
+    )?;
+    Ok(vm.none())
+}
+
+/// `resetwarnings()`: removes every filter.
+fn resetwarnings(vm: &mut Vm, args: Args) -> PyResult {
+    bind_arguments(vm, args, "resetwarnings", &[], 0)?;
+    let filters = filters(vm);
+    if let Payload::List(ref items) = filters.payload {
+        items.borrow_mut().clear();
+    }
+    filters_mutated(vm);
+    Ok(vm.none())
+}
+
+/// The arguments of `formatwarning` and `showwarning` as a report.
+fn report(vm: &mut Vm, values: &[Option<ObjectRef>], line: &Option<ObjectRef>) -> PyResult<String> {
+    let text = vm.str(values[0].as_ref().unwrap())?;
+    let category = values[1].as_ref().unwrap();
+    let category = match category.as_type() {
+        Some(class) => class.name.clone(),
+        None => vm.str(category)?,
+    };
+    let filename = vm.str(values[2].as_ref().unwrap())?;
+    let lineno = index_value(vm, values[3].as_ref().unwrap())?.max(0) as usize;
+    let line = match *line {
+        Some(ref line) if !Vm::is(line, &vm.none()) => Some(vm.str(line)?),
+        _ => source_line(vm, &filename, lineno),
+    };
+    Ok(format_warning(
+        &text,
+        &category,
+        &filename,
+        lineno,
+        line.as_deref(),
+    ))
+}
+
+/// `formatwarning(message, category, filename, lineno, line=None)`.
+fn formatwarning(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["message", "category", "filename", "lineno", "line"];
+    let values = bind_arguments(vm, args, "formatwarning", &names, 4)?;
+    let report = report(vm, &values, &values[4])?;
+    Ok(vm.new_str(&report))
+}
+
+/// `showwarning(message, category, filename, lineno, file=None,
+/// line=None)`: writes a warning to `file`, or to `sys.stderr`.
+fn showwarning(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["message", "category", "filename", "lineno", "file", "line"];
+    let values = bind_arguments(vm, args, "showwarning", &names, 4)?;
+    let report = report(vm, &values, &values[5])?;
+    match values[4] {
+        Some(ref file) if !Vm::is(file, &vm.none()) => write_file(vm, file, &[&report], false)?,
+        _ => write_standard(vm, "stderr", &[&report], false)?,
+    }
+    Ok(vm.none())
+}
+
+/// `WarningMessage(message, category, filename, lineno, file=None,
+/// line=None, source=None)`: a warning kept by `catch_warnings`.
+fn message_init(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "self", "message", "category", "filename", "lineno", "file", "line", "source",
+    ];
+    let values = bind_arguments(vm, args, "__init__", &names, 5)?;
+    let this = values[0].clone().unwrap();
+    for (name, value) in names[1..].iter().zip(&values[1..]) {
+        let value = value.clone().unwrap_or_else(|| vm.none());
+        vm.setattr(&this, name, value)?;
+    }
+    let category = values[2].clone().unwrap();
+    let category_name = match category.as_type() {
+        Some(class) => vm.new_str(&class.name),
+        None => vm.none(),
+    };
+    vm.setattr(&this, "_category_name", category_name)?;
+    Ok(vm.none())
+}
+
+fn message_str(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__str__", &["self"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let mut fields = Vec::with_capacity(5);
+    for (label, name) in [
+        ("message", "message"),
+        ("category", "_category_name"),
+        ("filename", "filename"),
+        ("lineno", "lineno"),
+        ("line", "line"),
+    ] {
+        let value = vm.getattr(this, name)?;
+        let value = if name == "lineno" {
+            vm.str(&value)?
+        } else {
+            vm.repr(&value)?
+        };
+        fields.push(format!("{} : {}", label, value));
+    }
+    Ok(vm.new_str(&format!("{{{}}}", fields.join(", "))))
+}
+
+/// `catch_warnings(*, record=False, module=None)`: a context manager that
+/// restores the filters when it exits, and with `record` collects the
+/// warnings shown meanwhile in a list instead.
+fn catch_init(vm: &mut Vm, args: Args) -> PyResult {
+    if args.positional.len() > 1 {
+        let message = format!(
+            "__init__() takes 1 positional argument but {} were given",
+            args.positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let values = bind_arguments(vm, args, "__init__", &["self", "record", "module"], 1)?;
+    let this = values[0].clone().unwrap();
+    let record = match values[1] {
+        Some(ref record) => vm.is_true(record)?,
+        None => false,
+    };
+    let record = vm.new_bool(record);
+    vm.setattr(&this, "_record", record)?;
+    let entered = vm.new_bool(false);
+    vm.setattr(&this, "_entered", entered)?;
+    Ok(vm.none())
+}
+
+fn catch_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__repr__", &["self"], 1)?;
+    let this = values[0].as_ref().unwrap();
+    let record = vm.getattr(this, "_record")?;
+    let text = if vm.is_true(&record)? {
+        "catch_warnings(record=True)"
+    } else {
+        "catch_warnings()"
+    };
+    Ok(vm.new_str(text))
+}
+
+fn catch_enter(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__enter__", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    let entered = vm.getattr(&this, "_entered")?;
+    if vm.is_true(&entered)? {
+        let message = format!("Cannot enter {} twice", vm.repr(&this)?);
+        return Err(vm.new_runtime_error(message));
+    }
+    let entered = vm.new_bool(true);
+    vm.setattr(&this, "_entered", entered)?;
+    let filters = filters(vm);
+    let saved = match filters.payload {
+        Payload::List(ref items) => items.borrow().clone(),
+        _ => Vec::new(),
+    };
+    let saved = vm.new_list(saved);
+    vm.setattr(&this, "_filters", saved)?;
+    let log = vm.warnings.log.clone().unwrap_or_else(|| vm.none());
+    vm.setattr(&this, "_log", log)?;
+    filters_mutated(vm);
+    let record = vm.getattr(&this, "_record")?;
+    if vm.is_true(&record)? {
+        let log = vm.new_list(Vec::new());
+        vm.warnings.log = Some(log.clone());
+        Ok(log)
+    } else {
+        vm.warnings.log = None;
+        Ok(vm.none())
+    }
+}
+
+fn catch_exit(vm: &mut Vm, args: Args) -> PyResult {
+    let this = match args.positional.first() {
+        Some(this) => this.clone(),
+        None => {
+            let message = "__exit__() missing 1 required positional argument: 'self'";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+    };
+    let entered = vm.getattr(&this, "_entered")?;
+    if !vm.is_true(&entered)? {
+        let message = format!("Cannot exit {} without entering first", vm.repr(&this)?);
+        return Err(vm.new_runtime_error(message));
+    }
+    let saved = vm.getattr(&this, "_filters")?;
+    let filters = filters(vm);
+    if let (Payload::List(ref items), Payload::List(ref saved)) = (&filters.payload, &saved.payload)
+    {
+        *items.borrow_mut() = saved.borrow().clone();
+    }
+    let log = vm.getattr(&this, "_log")?;
+    vm.warnings.log = Some(log).filter(|log| !Vm::is(log, &vm.none()));
+    filters_mutated(vm);
+    Ok(vm.none())
+}
+
+/// One element of a pattern, and how many times it may repeat.
+#[derive(Debug)]
+enum Atom {
+    Char(char),
+    Any,
+    /// A class such as `[a-z]` or `\d`, as ranges.
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Repeat {
+    One,
+    Optional,
+    Star,
+    Plus,
+}
+
+/// Whether the pattern `pattern` matches at the start of `text`, as the
+/// `match` of a compiled regular expression does.
+fn pattern_matches(pattern: &str, text: &str, ignore_case: bool) -> bool {
+    let items = parse_pattern(pattern);
+    let text: Vec<char> = text.chars().collect();
+    match_items(&items, &text, ignore_case)
+}
+
+fn parse_pattern(pattern: &str) -> Vec<(Atom, Repeat)> {
+    let mut items = Vec::new();
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        let atom = match c {
+            '.' => Atom::Any,
+            '$' if chars.peek().is_none() => Atom::End,
+            '^' if items.is_empty() => continue,
+            '\\' => match chars.next() {
+                Some('Z') => Atom::End,
+                Some(c @ ('d' | 'D' | 'w' | 'W' | 's' | 'S')) => escape_class(c),
+                Some(c) => Atom::Char(c),
+                None => Atom::Char('\\'),
+            },
+            '[' => {
+                let negated = chars.next_if_eq(&'^').is_some();
+                let mut ranges = Vec::new();
+                let mut first = true;
+                while let Some(c) = chars.next() {
+                    if c == ']' && !first {
+                        break;
+                    }
+                    first = false;
+                    let low = match c {
+                        '\\' => chars.next().unwrap_or('\\'),
+                        c => c,
+                    };
+                    if chars.peek() == Some(&'-') {
+                        chars.next();
+                        match chars.next() {
+                            Some(']') | None => {
+                                ranges.push((low, low));
+                                ranges.push(('-', '-'));
+                                break;
+                            }
+                            Some(high) => ranges.push((low, high)),
+                        }
+                    } else {
+                        ranges.push((low, low));
+                    }
+                }
+                Atom::Class { negated, ranges }
+            }
+            c => Atom::Char(c),
+        };
+        let repeat = match chars.peek() {
+            Some('*') => Repeat::Star,
+            Some('+') => Repeat::Plus,
+            Some('?') => Repeat::Optional,
+            _ => Repeat::One,
+        };
+        if repeat != Repeat::One {
+            chars.next();
+        }
+        items.push((atom, repeat));
+    }
+    items
+}
+
+/// The class of an escape such as `\d`.
+fn escape_class(c: char) -> Atom {
+    let ranges = match c.to_ascii_lowercase() {
+        'd' => vec![('0', '9')],
+        'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+        _ => vec![(' ', ' '), ('\t', '\r')],
+    };
+    Atom::Class {
+        negated: c.is_ascii_uppercase(),
+        ranges,
+    }
+}
+
+fn match_items(items: &[(Atom, Repeat)], text: &[char], ignore_case: bool) -> bool {
+    let (&(ref atom, repeat), rest) = match items.split_first() {
+        Some(first) => first,
+        None => return true,
+    };
+    if let Atom::End = *atom {
+        return text.is_empty() && match_items(rest, text, ignore_case);
+    }
+    let one = |text: &[char]| {
+        text.first()
+            .is_some_and(|&c| atom_matches(atom, c, ignore_case))
+    };
+    match repeat {
+        Repeat::One => one(text) && match_items(rest, &text[1..], ignore_case),
+        Repeat::Optional => {
+            (one(text) && match_items(rest, &text[1..], ignore_case))
+                || match_items(rest, text, ignore_case)
+        }
+        Repeat::Star | Repeat::Plus => {
+            let least = if repeat == Repeat::Plus { 1 } else { 0 };
+            let most = text
+                .iter()
+                .take_while(|&&c| atom_matches(atom, c, ignore_case))
+                .count();
+            (least..=most)
+                .rev()
+                .any(|count| match_items(rest, &text[count..], ignore_case))
+        }
+    }
+}
+
+fn atom_matches(atom: &Atom, c: char, ignore_case: bool) -> bool {
+    let cases = if ignore_case {
+        [
+            c,
+            c.to_lowercase().next().unwrap_or(c),
+            c.to_uppercase().next().unwrap_or(c),
+        ]
+    } else {
+        [c; 3]
+    };
+    match *atom {
+        Atom::Char(expected) => cases.contains(&expected),
+        Atom::Any => c != '\n',
+        Atom::Class {
+            negated,
+            ref ranges,
+        } => {
+            let inside = cases
+                .iter()
+                .any(|&c| ranges.iter().any(|&(low, high)| low <= c && c <= high));
+            inside != negated
+        }
+        Atom::End => false,
+    }
+}
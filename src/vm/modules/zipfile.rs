@@ -0,0 +1,994 @@
+//! `zipfile`: reading and writing zip archives.
+//!
+//! A `ZipFile` keeps the bytes of its archive in memory: all of the file
+//! when reading, and the members written so far when writing, which go
+//! into the file with the central directory when it is closed. A
+//! `ZipInfo` describes a member in its attributes, as in CPython.
+
+use std::env;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::Path;
+
+use super::super::archive::{self, ZipEntry, ZIP_DEFLATED, ZIP_STORED};
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, Payload, PyResult};
+use super::super::Vm;
+use super::{
+    add_attribute, bind_arguments, bytes_argument, module_error, new_native_class,
+    new_native_module, os_error, str_argument,
+};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(vm, "zipfile", &[("is_zipfile", is_zipfile)]);
+    let exception = vm.exceptions.exception.clone();
+    let bad_zip_file = new_native_class(vm, "zipfile", "BadZipFile", &exception, &[])?;
+    for name in &["BadZipFile", "BadZipfile", "error"] {
+        add_attribute(vm, &module, name, bad_zip_file.clone());
+    }
+    let large_zip_file = new_native_class(vm, "zipfile", "LargeZipFile", &exception, &[])?;
+    add_attribute(vm, &module, "LargeZipFile", large_zip_file);
+    let object = vm.types.object.clone();
+    let zip_info = new_native_class(
+        vm,
+        "zipfile",
+        "ZipInfo",
+        &object,
+        &[
+            ("__init__", info_init),
+            ("__repr__", info_repr),
+            ("is_dir", is_dir),
+        ],
+    )?;
+    add_attribute(vm, &module, "ZipInfo", zip_info);
+    let zip_file = new_native_class(
+        vm,
+        "zipfile",
+        "ZipFile",
+        &object,
+        &[
+            ("__enter__", enter),
+            ("__exit__", exit),
+            ("__init__", init),
+            ("__repr__", repr),
+            ("close", close),
+            ("extract", extract),
+            ("extractall", extractall),
+            ("getinfo", getinfo),
+            ("infolist", infolist),
+            ("mkdir", mkdir),
+            ("namelist", namelist),
+            ("open", open),
+            ("read", read),
+            ("testzip", testzip),
+            ("write", write),
+            ("writestr", writestr),
+        ],
+    )?;
+    add_attribute(vm, &module, "ZipFile", zip_file);
+    for &(name, method) in &[("ZIP_STORED", ZIP_STORED), ("ZIP_DEFLATED", ZIP_DEFLATED)] {
+        let method = vm.new_int(i64::from(method));
+        add_attribute(vm, &module, name, method);
+    }
+    Ok(module)
+}
+
+/// A `zipfile.BadZipFile`.
+fn bad_zip_file(vm: &mut Vm, message: String) -> ObjectRef {
+    module_error(vm, "zipfile", "BadZipFile", message)
+}
+
+fn zip_info_class(vm: &mut Vm) -> PyResult {
+    let module = vm.import_module("zipfile")?;
+    vm.getattr(&module, "ZipInfo")
+}
+
+fn is_zip_info(vm: &mut Vm, value: &ObjectRef) -> PyResult<bool> {
+    let class = zip_info_class(vm)?;
+    Ok(Vm::is_instance(value, &class))
+}
+
+fn int_attribute(vm: &mut Vm, object: &ObjectRef, name: &str) -> PyResult<i64> {
+    let value = vm.getattr(object, name)?;
+    index_value(vm, &value)
+}
+
+fn bytes_attribute(vm: &mut Vm, object: &ObjectRef, name: &str) -> PyResult<Vec<u8>> {
+    let value = vm.getattr(object, name)?;
+    bytes_argument(vm, &value)
+}
+
+/// `is_zipfile(filename)`: whether the file is a zip archive.
+fn is_zipfile(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "is_zipfile", &["filename"], 1)?;
+    let filename = str_argument(vm, "is_zipfile", "filename", values[0].as_ref().unwrap())?;
+    let is_zip = fs::read(&filename).is_ok_and(|data| archive::read_zip(&data).is_ok());
+    Ok(vm.new_bool(is_zip))
+}
+
+/// `ZipInfo(filename='NoName', date_time=(1980, 1, 1, 0, 0, 0))`.
+fn info_init(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(
+        vm,
+        args,
+        "ZipInfo.__init__",
+        &["self", "filename", "date_time"],
+        1,
+    )?;
+    let this = values[0].clone().unwrap();
+    let filename = match values[1] {
+        Some(ref filename) => str_argument(vm, "ZipInfo", "filename", filename)?,
+        None => "NoName".to_string(),
+    };
+    // Names end at a null byte, as they would in C.
+    let name = filename.split('\0').next().unwrap().to_string();
+    let date_time = match values[2] {
+        Some(ref date_time) => date_time.clone(),
+        None => {
+            let fields = [1980, 1, 1, 0, 0, 0]
+                .iter()
+                .map(|&field| vm.new_int(field))
+                .collect();
+            vm.new_tuple(fields)
+        }
+    };
+    let year = vm.iterate(&date_time)?.first().cloned();
+    if let Some(year) = year {
+        if index_value(vm, &year)? < 1980 {
+            let message = "ZIP does not support timestamps before 1980".to_string();
+            return Err(vm.new_value_error(message));
+        }
+    }
+    let original = vm.new_str(&filename);
+    vm.setattr(&this, "orig_filename", original)?;
+    let name = vm.new_str(&name);
+    vm.setattr(&this, "filename", name)?;
+    vm.setattr(&this, "date_time", date_time)?;
+    let none = vm.none();
+    vm.setattr(&this, "_compresslevel", none)?;
+    for name in &["comment", "extra"] {
+        let empty = vm.new_bytes(Vec::new());
+        vm.setattr(&this, name, empty)?;
+    }
+    let fields = [
+        ("compress_type", i64::from(ZIP_STORED)),
+        ("create_system", 3),
+        ("create_version", 20),
+        ("extract_version", 20),
+        ("reserved", 0),
+        ("flag_bits", 0),
+        ("volume", 0),
+        ("internal_attr", 0),
+        ("external_attr", 0),
+        ("header_offset", 0),
+        ("CRC", 0),
+        ("compress_size", 0),
+        ("file_size", 0),
+    ];
+    for &(name, value) in &fields {
+        let value = vm.new_int(value);
+        vm.setattr(&this, name, value)?;
+    }
+    Ok(vm.none())
+}
+
+/// The member a `ZipInfo` describes.
+fn entry(vm: &mut Vm, info: &ObjectRef) -> PyResult<ZipEntry> {
+    let name = vm.getattr(info, "filename")?;
+    let name = str_argument(vm, "ZipInfo", "filename", &name)?;
+    let mut entry = ZipEntry::new(&name, 0);
+    let date_time = vm.getattr(info, "date_time")?;
+    let fields = vm.iterate(&date_time)?;
+    if fields.len() < 6 {
+        let message = "date_time must have 6 fields".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    for (field, value) in entry.date_time.iter_mut().zip(&fields) {
+        *field = index_value(vm, value)? as u16;
+    }
+    entry.method = int_attribute(vm, info, "compress_type")? as u16;
+    entry.flags = int_attribute(vm, info, "flag_bits")? as u16;
+    entry.create_system = int_attribute(vm, info, "create_system")? as u8;
+    entry.create_version = int_attribute(vm, info, "create_version")? as u8;
+    entry.extract_version = int_attribute(vm, info, "extract_version")? as u16;
+    entry.external_attr = int_attribute(vm, info, "external_attr")? as u32;
+    entry.crc = int_attribute(vm, info, "CRC")? as u32;
+    entry.compressed_size = int_attribute(vm, info, "compress_size")? as u32;
+    entry.size = int_attribute(vm, info, "file_size")? as u32;
+    entry.header_offset = int_attribute(vm, info, "header_offset")? as u32;
+    entry.comment = bytes_attribute(vm, info, "comment")?;
+    entry.extra = bytes_attribute(vm, info, "extra")?;
+    Ok(entry)
+}
+
+/// Sets the attributes of a `ZipInfo` that change when its member is
+/// written.
+fn store_entry(vm: &mut Vm, info: &ObjectRef, entry: &ZipEntry) -> PyResult<()> {
+    let fields = [
+        ("flag_bits", i64::from(entry.flags)),
+        ("external_attr", i64::from(entry.external_attr)),
+        ("extract_version", i64::from(entry.extract_version)),
+        ("CRC", i64::from(entry.crc)),
+        ("compress_size", i64::from(entry.compressed_size)),
+        ("file_size", i64::from(entry.size)),
+        ("header_offset", i64::from(entry.header_offset)),
+    ];
+    for &(name, value) in &fields {
+        let value = vm.new_int(value);
+        vm.setattr(info, name, value)?;
+    }
+    Ok(())
+}
+
+/// A new `ZipInfo` describing `entry`.
+fn new_info(vm: &mut Vm, entry: &ZipEntry) -> PyResult {
+    let class = zip_info_class(vm)?;
+    let name = vm.new_str(&entry.name);
+    let fields = entry
+        .date_time
+        .iter()
+        .map(|&field| vm.new_int(i64::from(field)))
+        .collect();
+    let date_time = vm.new_tuple(fields);
+    let info = vm.call(&class, Args::new(vec![name, date_time]))?;
+    store_entry(vm, &info, entry)?;
+    let fields = [
+        ("compress_type", i64::from(entry.method)),
+        ("create_system", i64::from(entry.create_system)),
+        ("create_version", i64::from(entry.create_version)),
+    ];
+    for &(name, value) in &fields {
+        let value = vm.new_int(value);
+        vm.setattr(&info, name, value)?;
+    }
+    let comment = vm.new_bytes(entry.comment.clone());
+    vm.setattr(&info, "comment", comment)?;
+    let extra = vm.new_bytes(entry.extra.clone());
+    vm.setattr(&info, "extra", extra)?;
+    Ok(info)
+}
+
+/// The name of a member's `ZipInfo`.
+fn info_name(vm: &mut Vm, info: &ObjectRef) -> PyResult<String> {
+    let name = vm.getattr(info, "filename")?;
+    str_argument(vm, "ZipInfo", "filename", &name)
+}
+
+/// `ZipInfo.is_dir()`: whether the member is a directory, which its name
+/// ends with a slash to say.
+fn is_dir(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "is_dir", &["self"], 1)?;
+    let name = info_name(vm, values[0].as_ref().unwrap())?;
+    Ok(vm.new_bool(name.ends_with('/')))
+}
+
+/// The mode of a file as `ls -l` shows it, as `stat.filemode` gives it.
+fn file_mode(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o100000 => '-',
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o060000 => 'b',
+        0o020000 => 'c',
+        0o010000 => 'p',
+        0o140000 => 's',
+        _ => '?',
+    };
+    let mut text = kind.to_string();
+    // The special bit that shows in place of each class's execute bit.
+    let classes = [(6, 0o4000, 's'), (3, 0o2000, 's'), (0, 0o1000, 't')];
+    for &(shift, special, letter) in &classes {
+        let bits = mode >> shift;
+        text.push(if bits & 4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 2 != 0 { 'w' } else { '-' });
+        text.push(match (bits & 1 != 0, mode & special != 0) {
+            (true, true) => letter,
+            (false, true) => letter.to_ascii_uppercase(),
+            (true, false) => 'x',
+            (false, false) => '-',
+        });
+    }
+    text
+}
+
+/// `ZipInfo.__repr__()`.
+fn info_repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__repr__", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    let name = vm.getattr(&this, "filename")?;
+    let mut text = format!("<ZipInfo filename={}", vm.repr(&name)?);
+    let method = int_attribute(vm, &this, "compress_type")?;
+    match method {
+        0 => {}
+        8 => text.push_str(" compress_type=deflate"),
+        12 => text.push_str(" compress_type=bzip2"),
+        14 => text.push_str(" compress_type=lzma"),
+        _ => text.push_str(&format!(" compress_type={}", method)),
+    }
+    let attributes = int_attribute(vm, &this, "external_attr")?;
+    if attributes >> 16 != 0 {
+        text.push_str(&format!(
+            " filemode='{}'",
+            file_mode((attributes >> 16) as u32)
+        ));
+    }
+    if attributes & 0xffff != 0 {
+        text.push_str(&format!(" external_attr={:#x}", attributes & 0xffff));
+    }
+    let is_dir = info_name(vm, &this)?.ends_with('/');
+    let size = int_attribute(vm, &this, "file_size")?;
+    let compressed_size = int_attribute(vm, &this, "compress_size")?;
+    if !is_dir || size != 0 {
+        text.push_str(&format!(" file_size={}", size));
+    }
+    if (!is_dir || compressed_size != 0) && (method != 0 || size != compressed_size) {
+        text.push_str(&format!(" compress_size={}", compressed_size));
+    }
+    text.push('>');
+    Ok(vm.new_str(&text))
+}
+
+/// `ZipFile(file, mode='r', compression=ZIP_STORED, allowZip64=True,
+/// compresslevel=None, *, strict_timestamps=True)`.
+fn init(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "self",
+        "file",
+        "mode",
+        "compression",
+        "allowZip64",
+        "compresslevel",
+        "strict_timestamps",
+    ];
+    let values = bind_arguments(vm, args, "ZipFile.__init__", &names, 2)?;
+    let this = values[0].clone().unwrap();
+    let mode = match values[2] {
+        Some(ref mode) => str_argument(vm, "ZipFile", "mode", mode)?,
+        None => "r".to_string(),
+    };
+    if !["r", "w", "x", "a"].contains(&mode.as_str()) {
+        let message = "ZipFile requires mode 'r', 'w', 'x', or 'a'".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    let compression = match values[3] {
+        Some(ref compression) => index_value(vm, compression)?,
+        None => i64::from(ZIP_STORED),
+    };
+    check_compression(vm, compression)?;
+    let filename = match values[1].as_ref().unwrap().as_str() {
+        Some(filename) => filename.to_string(),
+        None => {
+            let class = vm.exceptions.not_implemented_error.clone();
+            let message = "ZipFile supports only file names, not file objects".to_string();
+            return Err(vm.new_error(&class, message));
+        }
+    };
+
+    let (data, entries) = match mode.as_str() {
+        "r" => {
+            let data = match fs::read(&filename) {
+                Ok(data) => data,
+                Err(error) => return Err(os_error(vm, &error, &filename)),
+            };
+            match archive::read_zip(&data) {
+                Ok((_, entries)) => (data, entries),
+                Err(message) => return Err(bad_zip_file(vm, message)),
+            }
+        }
+        "a" => match fs::read(&filename) {
+            // Members are added where the central directory was, or after
+            // a file that is not an archive yet.
+            Ok(mut data) => match archive::read_zip(&data) {
+                Ok((directory, entries)) => {
+                    data.truncate(directory);
+                    (data, entries)
+                }
+                Err(_) => (data, Vec::new()),
+            },
+            Err(ref error) if error.kind() == ErrorKind::NotFound => {
+                create_file(vm, &filename, false)?;
+                (Vec::new(), Vec::new())
+            }
+            Err(error) => return Err(os_error(vm, &error, &filename)),
+        },
+        _ => {
+            create_file(vm, &filename, mode == "x")?;
+            (Vec::new(), Vec::new())
+        }
+    };
+    let mut infos = Vec::with_capacity(entries.len());
+    for entry in &entries {
+        infos.push(new_info(vm, entry)?);
+    }
+
+    let filename = vm.new_str(&filename);
+    vm.setattr(&this, "filename", filename)?;
+    let mode = vm.new_str(&mode);
+    vm.setattr(&this, "mode", mode)?;
+    let compression = vm.new_int(compression);
+    vm.setattr(&this, "compression", compression)?;
+    let level = match values[5] {
+        Some(ref level) => level.clone(),
+        None => vm.none(),
+    };
+    vm.setattr(&this, "compresslevel", level)?;
+    let comment = vm.new_bytes(Vec::new());
+    vm.setattr(&this, "comment", comment)?;
+    let debug = vm.new_int(0);
+    vm.setattr(&this, "debug", debug)?;
+    let data = vm.new_bytes(data);
+    vm.setattr(&this, "_data", data)?;
+    let infos = vm.new_list(infos);
+    vm.setattr(&this, "_infos", infos)?;
+    let closed = vm.new_bool(false);
+    vm.setattr(&this, "_closed", closed)?;
+    Ok(vm.none())
+}
+
+/// Creates or empties the file of an archive to be written, as CPython
+/// does on opening it, so that errors show there.
+fn create_file(vm: &mut Vm, filename: &str, exclusive: bool) -> PyResult<()> {
+    let mut options = OpenOptions::new();
+    if exclusive {
+        options.write(true).create_new(true);
+    } else {
+        options.write(true).create(true).truncate(true);
+    }
+    match options.open(filename) {
+        Ok(_) => Ok(()),
+        Err(error) => Err(os_error(vm, &error, filename)),
+    }
+}
+
+fn check_compression(vm: &mut Vm, compression: i64) -> PyResult<()> {
+    if compression == i64::from(ZIP_STORED) || compression == i64::from(ZIP_DEFLATED) {
+        return Ok(());
+    }
+    let class = vm.exceptions.not_implemented_error.clone();
+    let message = "That compression method is not supported".to_string();
+    Err(vm.new_error(&class, message))
+}
+
+fn is_closed(vm: &mut Vm, this: &ObjectRef) -> PyResult<bool> {
+    let closed = vm.getattr(this, "_closed")?;
+    vm.is_true(&closed)
+}
+
+/// The `ZipInfo`s of the members, in the order they are in the archive.
+fn infos(vm: &mut Vm, this: &ObjectRef) -> PyResult<Vec<ObjectRef>> {
+    let infos = vm.getattr(this, "_infos")?;
+    vm.iterate(&infos)
+}
+
+/// The `ZipInfo` of the member named `name`, the last of that name.
+fn info_named(vm: &mut Vm, this: &ObjectRef, name: &str) -> PyResult {
+    for info in infos(vm, this)?.into_iter().rev() {
+        if info_name(vm, &info)? == name {
+            return Ok(info);
+        }
+    }
+    let class = vm.exceptions.key_error.clone();
+    let message = format!("There is no item named '{}' in the archive", name);
+    Err(vm.new_error(&class, message))
+}
+
+/// The `ZipInfo` of a member given by name or by its `ZipInfo`.
+fn member_info(vm: &mut Vm, this: &ObjectRef, member: &ObjectRef) -> PyResult {
+    if is_zip_info(vm, member)? {
+        return Ok(member.clone());
+    }
+    let name = str_argument(vm, "getinfo", "name", member)?;
+    info_named(vm, this, &name)
+}
+
+/// The decompressed data of the member `info`.
+fn member_data(vm: &mut Vm, this: &ObjectRef, info: &ObjectRef) -> PyResult<Vec<u8>> {
+    if is_closed(vm, this)? {
+        let message = "Attempt to use ZIP archive that was already closed".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    let entry = entry(vm, info)?;
+    if entry.flags & 1 != 0 {
+        let class = vm.exceptions.runtime_error.clone();
+        let message = format!(
+            "File '{}' is encrypted, password required for extraction",
+            entry.name
+        );
+        return Err(vm.new_error(&class, message));
+    }
+    if check_compression(vm, i64::from(entry.method)).is_err() {
+        let class = vm.exceptions.not_implemented_error.clone();
+        let message = format!("compression type {} (unknown)", entry.method);
+        return Err(vm.new_error(&class, message));
+    }
+    let data = vm.getattr(this, "_data")?;
+    let member = match data.payload {
+        Payload::Bytes(ref data) => archive::read_zip_member(data, &entry),
+        _ => unreachable!(),
+    };
+    member.map_err(|message| bad_zip_file(vm, message))
+}
+
+/// `ZipFile.namelist()`.
+fn namelist(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "namelist", &["self"], 1)?;
+    let mut names = Vec::new();
+    for info in infos(vm, values[0].as_ref().unwrap())? {
+        names.push(vm.getattr(&info, "filename")?);
+    }
+    Ok(vm.new_list(names))
+}
+
+/// `ZipFile.infolist()`.
+fn infolist(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "infolist", &["self"], 1)?;
+    let infos = infos(vm, values[0].as_ref().unwrap())?;
+    Ok(vm.new_list(infos))
+}
+
+/// `ZipFile.getinfo(name)`.
+fn getinfo(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "getinfo", &["self", "name"], 2)?;
+    let name = str_argument(vm, "getinfo", "name", values[1].as_ref().unwrap())?;
+    info_named(vm, values[0].as_ref().unwrap(), &name)
+}
+
+/// `ZipFile.read(name, pwd=None)`: the data of a member.
+fn read(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "read", &["self", "name", "pwd"], 2)?;
+    let this = values[0].clone().unwrap();
+    let info = member_info(vm, &this, values[1].as_ref().unwrap())?;
+    let data = member_data(vm, &this, &info)?;
+    Ok(vm.new_bytes(data))
+}
+
+/// `ZipFile.open(name, mode='r', pwd=None)`: a binary stream of the data
+/// of a member, an `io.BytesIO` holding all of it. Members can be written
+/// only with `writestr` and `write`.
+fn open(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "open", &["self", "name", "mode", "pwd"], 2)?;
+    let this = values[0].clone().unwrap();
+    let mode = match values[2] {
+        Some(ref mode) => str_argument(vm, "open", "mode", mode)?,
+        None => "r".to_string(),
+    };
+    match mode.as_str() {
+        "r" => {}
+        "w" => {
+            let class = vm.exceptions.not_implemented_error.clone();
+            let message =
+                "ZipFile.open() supports only reading; use writestr() to add a member".to_string();
+            return Err(vm.new_error(&class, message));
+        }
+        _ => {
+            let message = "open() requires mode \"r\" or \"w\"".to_string();
+            return Err(vm.new_value_error(message));
+        }
+    }
+    let info = member_info(vm, &this, values[1].as_ref().unwrap())?;
+    let data = member_data(vm, &this, &info)?;
+    let io = vm.import_module("io")?;
+    let class = vm.getattr(&io, "BytesIO")?;
+    let data = vm.new_bytes(data);
+    let stream = vm.call(&class, Args::new(vec![data]))?;
+    let name = vm.getattr(&info, "filename")?;
+    vm.setattr(&stream, "name", name)?;
+    Ok(stream)
+}
+
+/// `ZipFile.testzip()`: the name of the first member whose data is bad,
+/// or `None`.
+fn testzip(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "testzip", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    let class = vm.import_module("zipfile")?;
+    let class = vm.getattr(&class, "BadZipFile")?;
+    for info in infos(vm, &this)? {
+        match member_data(vm, &this, &info) {
+            Ok(_) => {}
+            Err(ref error) if Vm::is_instance(error, &class) => {
+                return vm.getattr(&info, "filename");
+            }
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(vm.none())
+}
+
+/// A path normalized as `os.path.normpath` does it.
+fn normalize(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." if parts.last().is_some_and(|&last| last != "..") => {
+                parts.pop();
+            }
+            ".." if absolute => {}
+            part => parts.push(part),
+        }
+    }
+    let joined = parts.join("/");
+    match (absolute, joined.is_empty()) {
+        (true, _) => format!("/{}", joined),
+        (false, true) => ".".to_string(),
+        (false, false) => joined,
+    }
+}
+
+/// The directory extracted members go in: `path`, or the current one.
+fn target_directory(vm: &mut Vm, path: Option<&ObjectRef>) -> PyResult<String> {
+    match path {
+        Some(path) if !Vm::is(path, &vm.none()) => str_argument(vm, "extract", "path", path),
+        _ => match env::current_dir() {
+            Ok(directory) => Ok(directory.to_string_lossy().into_owned()),
+            Err(error) => Err(os_error(vm, &error, ".")),
+        },
+    }
+}
+
+/// Extracts the member `info` into the directory `directory`, returning
+/// the path it was written to. Leading slashes and `.` and `..` are left
+/// out of the member's name, so that it stays in `directory`.
+fn extract_member(
+    vm: &mut Vm,
+    this: &ObjectRef,
+    info: &ObjectRef,
+    directory: &str,
+) -> PyResult<String> {
+    let name = info_name(vm, info)?;
+    let relative: Vec<&str> = name
+        .split('/')
+        .filter(|&part| !["", ".", ".."].contains(&part))
+        .collect();
+    let target = normalize(&format!("{}/{}", directory, relative.join("/")));
+    let is_dir = name.ends_with('/');
+    let created = if is_dir {
+        fs::create_dir_all(&target)
+    } else {
+        match Path::new(&target).parent() {
+            Some(parent) => fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+    };
+    if let Err(error) = created {
+        return Err(os_error(vm, &error, &target));
+    }
+    if !is_dir {
+        let data = member_data(vm, this, info)?;
+        if let Err(error) = fs::write(&target, data) {
+            return Err(os_error(vm, &error, &target));
+        }
+    }
+    Ok(target)
+}
+
+/// `ZipFile.extract(member, path=None, pwd=None)`: extracts a member,
+/// returning the path it was written to.
+fn extract(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "extract", &["self", "member", "path", "pwd"], 2)?;
+    let this = values[0].clone().unwrap();
+    let info = member_info(vm, &this, values[1].as_ref().unwrap())?;
+    let directory = target_directory(vm, values[2].as_ref())?;
+    let target = extract_member(vm, &this, &info, &directory)?;
+    Ok(vm.new_str(&target))
+}
+
+/// `ZipFile.extractall(path=None, members=None, pwd=None)`.
+fn extractall(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(
+        vm,
+        args,
+        "extractall",
+        &["self", "path", "members", "pwd"],
+        1,
+    )?;
+    let this = values[0].clone().unwrap();
+    let directory = target_directory(vm, values[1].as_ref())?;
+    let infos = match values[2] {
+        Some(ref members) if !Vm::is(members, &vm.none()) => {
+            let mut infos = Vec::new();
+            for member in vm.iterate(members)? {
+                infos.push(member_info(vm, &this, &member)?);
+            }
+            infos
+        }
+        _ => infos(vm, &this)?,
+    };
+    for info in infos {
+        extract_member(vm, &this, &info, &directory)?;
+    }
+    Ok(vm.none())
+}
+
+/// Checks that members can be written to the archive.
+fn check_writable(vm: &mut Vm, this: &ObjectRef) -> PyResult<()> {
+    if is_closed(vm, this)? {
+        let message = "Attempt to write to ZIP archive that was already closed".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    let mode = vm.getattr(this, "mode")?;
+    if mode.as_str() == Some("r") {
+        let message = "write() requires mode 'w', 'x', or 'a'".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    Ok(())
+}
+
+/// The compression level for a member, from its `_compresslevel`.
+fn member_level(vm: &mut Vm, info: &ObjectRef) -> PyResult<u8> {
+    let level = vm.getattr(info, "_compresslevel")?;
+    if Vm::is(&level, &vm.none()) {
+        return Ok(6);
+    }
+    match index_value(vm, &level)? {
+        -1 => Ok(6),
+        level @ 0..=9 => Ok(level as u8),
+        _ => Err(module_error(
+            vm,
+            "zlib",
+            "error",
+            "Bad compression level".to_string(),
+        )),
+    }
+}
+
+/// Adds a member described by `info` with the data `member` to the
+/// archive.
+fn add_member(vm: &mut Vm, this: &ObjectRef, info: &ObjectRef, member: &[u8]) -> PyResult<()> {
+    check_writable(vm, this)?;
+    let mut entry = entry(vm, info)?;
+    check_compression(vm, i64::from(entry.method))?;
+    entry.flags = 0;
+    if entry.external_attr == 0 {
+        entry.external_attr = 0o600 << 16;
+    }
+    let level = member_level(vm, info)?;
+    let data = vm.getattr(this, "_data")?;
+    let mut data = bytes_argument(vm, &data)?;
+    archive::write_zip_member(&mut data, &mut entry, member, level);
+    store_entry(vm, info, &entry)?;
+    let data = vm.new_bytes(data);
+    vm.setattr(this, "_data", data)?;
+    let infos = vm.getattr(this, "_infos")?;
+    if let Payload::List(ref infos) = infos.payload {
+        infos.borrow_mut().push(info.clone());
+    }
+    Ok(())
+}
+
+/// A new `ZipInfo` for a member named `name` written now, compressed as
+/// the archive's members are by default.
+fn new_member_info(vm: &mut Vm, this: &ObjectRef, name: &str) -> PyResult {
+    let mut entry = ZipEntry::new(name, archive::now());
+    entry.method = int_attribute(vm, this, "compression")? as u16;
+    let info = new_info(vm, &entry)?;
+    let level = vm.getattr(this, "compresslevel")?;
+    vm.setattr(&info, "_compresslevel", level)?;
+    Ok(info)
+}
+
+/// Applies the `compress_type` and `compresslevel` arguments of a write
+/// to the member's `ZipInfo`.
+fn override_compression(
+    vm: &mut Vm,
+    info: &ObjectRef,
+    compress_type: Option<&ObjectRef>,
+    level: Option<&ObjectRef>,
+) -> PyResult<()> {
+    if let Some(compress_type) = compress_type.filter(|value| !Vm::is(value, &vm.none())) {
+        vm.setattr(info, "compress_type", compress_type.clone())?;
+    }
+    if let Some(level) = level.filter(|value| !Vm::is(value, &vm.none())) {
+        vm.setattr(info, "_compresslevel", level.clone())?;
+    }
+    Ok(())
+}
+
+/// `ZipFile.writestr(zinfo_or_arcname, data, compress_type=None,
+/// compresslevel=None)`: adds a member with the data `data`, a str being
+/// encoded as UTF-8.
+fn writestr(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "self",
+        "zinfo_or_arcname",
+        "data",
+        "compress_type",
+        "compresslevel",
+    ];
+    let values = bind_arguments(vm, args, "writestr", &names, 3)?;
+    let this = values[0].clone().unwrap();
+    let data = values[2].as_ref().unwrap();
+    let data = match data.as_str() {
+        Some(text) => text.as_bytes().to_vec(),
+        None => bytes_argument(vm, data)?,
+    };
+    let member = values[1].clone().unwrap();
+    let info = if is_zip_info(vm, &member)? {
+        member
+    } else {
+        let name = str_argument(vm, "writestr", "zinfo_or_arcname", &member)?;
+        let info = new_member_info(vm, &this, &name)?;
+        let attributes = if name.ends_with('/') {
+            0o40775 << 16 | 0x10
+        } else {
+            0o600 << 16
+        };
+        let attributes = vm.new_int(attributes);
+        vm.setattr(&info, "external_attr", attributes)?;
+        info
+    };
+    check_writable(vm, &this)?;
+    override_compression(vm, &info, values[3].as_ref(), values[4].as_ref())?;
+    add_member(vm, &this, &info, &data)?;
+    Ok(vm.none())
+}
+
+/// `ZipFile.mkdir(zinfo_or_directory_name, mode=511)`: adds a directory.
+fn mkdir(vm: &mut Vm, args: Args) -> PyResult {
+    let names = ["self", "zinfo_or_directory_name", "mode"];
+    let values = bind_arguments(vm, args, "mkdir", &names, 2)?;
+    let this = values[0].clone().unwrap();
+    let member = values[1].clone().unwrap();
+    let info = if is_zip_info(vm, &member)? {
+        if !info_name(vm, &member)?.ends_with('/') {
+            let message = "The given ZipInfo does not describe a directory".to_string();
+            return Err(vm.new_value_error(message));
+        }
+        member
+    } else if let Some(name) = member.as_str() {
+        let name = if name.ends_with('/') {
+            name.to_string()
+        } else {
+            format!("{}/", name)
+        };
+        let mode = match values[2] {
+            Some(ref mode) => index_value(vm, mode)?,
+            None => 0o777,
+        };
+        let info = new_info(vm, &ZipEntry::new(&name, archive::now()))?;
+        let attributes = vm.new_int(((0o40000 | mode) & 0xffff) << 16 | 0x10);
+        vm.setattr(&info, "external_attr", attributes)?;
+        info
+    } else {
+        return Err(vm.new_type_error("Expected type str or ZipInfo".to_string()));
+    };
+    add_member(vm, &this, &info, &[])?;
+    Ok(vm.none())
+}
+
+/// The mode of a file from its metadata, with the bits of its type.
+#[cfg(unix)]
+fn metadata_mode(metadata: &fs::Metadata) -> u32 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.mode()
+}
+
+#[cfg(not(unix))]
+fn metadata_mode(metadata: &fs::Metadata) -> u32 {
+    if metadata.is_dir() {
+        0o40755
+    } else {
+        0o100644
+    }
+}
+
+/// `ZipFile.write(filename, arcname=None, compress_type=None,
+/// compresslevel=None)`: adds the file `filename`, or a directory entry
+/// for a directory, named `arcname` or else after the file.
+fn write(vm: &mut Vm, args: Args) -> PyResult {
+    let names = [
+        "self",
+        "filename",
+        "arcname",
+        "compress_type",
+        "compresslevel",
+    ];
+    let values = bind_arguments(vm, args, "write", &names, 2)?;
+    let this = values[0].clone().unwrap();
+    let filename = str_argument(vm, "write", "filename", values[1].as_ref().unwrap())?;
+    if is_closed(vm, &this)? {
+        let message = "Attempt to write to ZIP archive that was already closed".to_string();
+        return Err(vm.new_value_error(message));
+    }
+    let metadata = match fs::metadata(&filename) {
+        Ok(metadata) => metadata,
+        Err(error) => return Err(os_error(vm, &error, &filename)),
+    };
+    let name = match values[2] {
+        Some(ref name) if !Vm::is(name, &vm.none()) => str_argument(vm, "write", "arcname", name)?,
+        _ => filename.clone(),
+    };
+    let mut name = normalize(&name).trim_start_matches('/').to_string();
+    if metadata.is_dir() {
+        name.push('/');
+    }
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(::std::time::UNIX_EPOCH).ok())
+        .map_or(0, |since| since.as_secs() as u32);
+    let mut entry = ZipEntry::new(&name, mtime);
+    entry.external_attr = (metadata_mode(&metadata) & 0xffff) << 16;
+    if metadata.is_dir() {
+        entry.external_attr |= 0x10;
+        let info = new_info(vm, &entry)?;
+        add_member(vm, &this, &info, &[])?;
+        return Ok(vm.none());
+    }
+    entry.method = int_attribute(vm, &this, "compression")? as u16;
+    let info = new_info(vm, &entry)?;
+    let level = vm.getattr(&this, "compresslevel")?;
+    vm.setattr(&info, "_compresslevel", level)?;
+    override_compression(vm, &info, values[3].as_ref(), values[4].as_ref())?;
+    let data = match fs::read(&filename) {
+        Ok(data) => data,
+        Err(error) => return Err(os_error(vm, &error, &filename)),
+    };
+    add_member(vm, &this, &info, &data)?;
+    Ok(vm.none())
+}
+
+/// `ZipFile.close()`: for an archive being written, writes its members
+/// and central directory to its file.
+fn close(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "close", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    if is_closed(vm, &this)? {
+        return Ok(vm.none());
+    }
+    let closed = vm.new_bool(true);
+    vm.setattr(&this, "_closed", closed)?;
+    let mode = vm.getattr(&this, "mode")?;
+    if mode.as_str() != Some("r") {
+        let mut entries = Vec::new();
+        for info in infos(vm, &this)? {
+            entries.push(entry(vm, &info)?);
+        }
+        let data = vm.getattr(&this, "_data")?;
+        let mut data = bytes_argument(vm, &data)?;
+        archive::finish_zip(&mut data, &entries);
+        let filename = vm.getattr(&this, "filename")?;
+        let filename = str_argument(vm, "close", "filename", &filename)?;
+        let written = OpenOptions::new()
+            .write(true)
+            .truncate(true)
+            .open(&filename)
+            .and_then(|mut file| file.write_all(&data));
+        if let Err(error) = written {
+            return Err(os_error(vm, &error, &filename));
+        }
+    }
+    let empty = vm.new_bytes(Vec::new());
+    vm.setattr(&this, "_data", empty)?;
+    Ok(vm.none())
+}
+
+/// `ZipFile.__repr__()`.
+fn repr(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__repr__", &["self"], 1)?;
+    let this = values[0].clone().unwrap();
+    if is_closed(vm, &this)? {
+        return Ok(vm.new_str("<zipfile.ZipFile [closed]>"));
+    }
+    let filename = vm.getattr(&this, "filename")?;
+    let mode = vm.getattr(&this, "mode")?;
+    let text = format!(
+        "<zipfile.ZipFile filename={} mode={}>",
+        vm.repr(&filename)?,
+        vm.repr(&mode)?
+    );
+    Ok(vm.new_str(&text))
+}
+
+fn enter(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "__enter__", &["self"], 1)?;
+    Ok(values[0].clone().unwrap())
+}
+
+/// `ZipFile.__exit__(*exc_info)`, which closes the archive.
+fn exit(vm: &mut Vm, mut args: Args) -> PyResult {
+    args.positional.truncate(1);
+    close(vm, args)
+}
@@ -0,0 +1,164 @@
+//! `zlib`: DEFLATE compression in zlib, gzip or raw streams, and the
+//! checksums that go with them.
+
+use super::super::archive::{self, GzipError, InflateError};
+use super::super::builtins::index_value;
+use super::super::object::{Args, ObjectRef, PyResult};
+use super::super::Vm;
+use super::{
+    add_attribute, bind_arguments, bytes_argument, module_error, new_native_class,
+    new_native_module,
+};
+
+pub(super) fn module(vm: &mut Vm) -> PyResult<ObjectRef> {
+    let module = new_native_module(
+        vm,
+        "zlib",
+        &[
+            ("adler32", adler32),
+            ("compress", compress),
+            ("crc32", crc32),
+            ("decompress", decompress),
+        ],
+    );
+    let exception = vm.exceptions.exception.clone();
+    let error = new_native_class(vm, "zlib", "error", &exception, &[])?;
+    add_attribute(vm, &module, "error", error);
+    let constants = [
+        ("DEFLATED", 8),
+        ("DEF_BUF_SIZE", 16384),
+        ("DEF_MEM_LEVEL", 8),
+        ("MAX_WBITS", 15),
+        ("Z_BEST_COMPRESSION", 9),
+        ("Z_BEST_SPEED", 1),
+        ("Z_DEFAULT_COMPRESSION", -1),
+        ("Z_NO_COMPRESSION", 0),
+    ];
+    for &(name, value) in &constants {
+        let value = vm.new_int(value);
+        add_attribute(vm, &module, name, value);
+    }
+    Ok(module)
+}
+
+/// A `zlib.error`.
+fn error(vm: &mut Vm, message: String) -> ObjectRef {
+    module_error(vm, "zlib", "error", message)
+}
+
+fn inflate_error(vm: &mut Vm, error: &InflateError) -> ObjectRef {
+    let message = format!(
+        "Error {} while decompressing data: {}",
+        error.code, error.message
+    );
+    self::error(vm, message)
+}
+
+/// The compression level given, where -1 is the default, 6.
+fn level_argument(vm: &mut Vm, level: Option<&ObjectRef>) -> PyResult<u8> {
+    let level = match level {
+        Some(level) => index_value(vm, level)?,
+        None => -1,
+    };
+    match level {
+        -1 => Ok(6),
+        0..=9 => Ok(level as u8),
+        _ => Err(error(vm, "Bad compression level".to_string())),
+    }
+}
+
+/// `zlib.compress(data, /, level=-1, wbits=15)`. The window size of
+/// `wbits` picks the format: 9 to 15 for zlib, -9 to -15 for raw DEFLATE
+/// and 25 to 31 for gzip.
+fn compress(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "compress", &["data", "level", "wbits"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let level = level_argument(vm, values[1].as_ref())?;
+    let wbits = match values[2] {
+        Some(ref wbits) => index_value(vm, wbits)?,
+        None => 15,
+    };
+    let compressed = match wbits {
+        9..=15 => archive::zlib_compress(&data, level),
+        -15..=-9 => archive::deflate(&data, level),
+        25..=31 => archive::gzip_compress(&data, level, 0),
+        _ => return Err(error(vm, "Bad compression level".to_string())),
+    };
+    Ok(vm.new_bytes(compressed))
+}
+
+/// `zlib.decompress(data, /, wbits=15, bufsize=16384)`. `wbits` picks the
+/// format as for `compress`, and 40 to 47 accepts zlib or gzip. Data after
+/// the end of the stream is ignored.
+fn decompress(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "decompress", &["data", "wbits", "bufsize"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let wbits = match values[1] {
+        Some(ref wbits) => index_value(vm, wbits)?,
+        None => 15,
+    };
+    if let Some(ref bufsize) = values[2] {
+        if index_value(vm, bufsize)? < 0 {
+            let message = "bufsize must be non-negative".to_string();
+            return Err(vm.new_value_error(message));
+        }
+    }
+    let gzip = match wbits {
+        8..=15 | -15..=-8 => false,
+        24..=31 => true,
+        40..=47 => data.starts_with(&[0x1f, 0x8b]),
+        _ => {
+            let message = "Error -2 while preparing to decompress data: inconsistent stream state";
+            return Err(error(vm, message.to_string()));
+        }
+    };
+    let decompressed = if gzip {
+        archive::gzip_decompress(&data).map_err(|failure| match failure {
+            GzipError::Inflate(failure) => inflate_error(vm, &failure),
+            GzipError::Truncated => inflate_error(
+                vm,
+                &InflateError {
+                    code: -5,
+                    message: "incomplete or truncated stream",
+                },
+            ),
+            GzipError::NotGzip(_) | GzipError::Bad(_) => inflate_error(
+                vm,
+                &InflateError {
+                    code: -3,
+                    message: "incorrect header check",
+                },
+            ),
+        })?
+    } else {
+        match archive::inflate(&data, wbits > 0) {
+            Ok((decompressed, _)) => decompressed,
+            Err(failure) => return Err(inflate_error(vm, &failure)),
+        }
+    };
+    Ok(vm.new_bytes(decompressed))
+}
+
+/// The running value of a checksum, of which only the low 32 bits count.
+fn checksum_start(vm: &mut Vm, value: Option<&ObjectRef>, default: u32) -> PyResult<u32> {
+    match value {
+        Some(value) => Ok(index_value(vm, value)? as u32),
+        None => Ok(default),
+    }
+}
+
+/// `zlib.crc32(data, value=0, /)`.
+fn crc32(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "crc32", &["data", "value"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let start = checksum_start(vm, values[1].as_ref(), 0)?;
+    Ok(vm.new_int(i64::from(archive::crc32(&data, start))))
+}
+
+/// `zlib.adler32(data, value=1, /)`.
+fn adler32(vm: &mut Vm, args: Args) -> PyResult {
+    let values = bind_arguments(vm, args, "adler32", &["data", "value"], 1)?;
+    let data = bytes_argument(vm, values[0].as_ref().unwrap())?;
+    let start = checksum_start(vm, values[1].as_ref(), 1)?;
+    Ok(vm.new_int(i64::from(archive::adler32(&data, start))))
+}
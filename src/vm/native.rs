@@ -0,0 +1,129 @@
+//! Modules written in Rust by programs that embed the interpreter, as C
+//! extension modules are for CPython.
+//!
+//! A `NativeModule` registered with `Vm::add_native_module` is imported as
+//! the builtin modules of the standard library are: `import` finds it
+//! before searching `sys.path`, and creates it the first time it is
+//! imported, by calling its `init` with a `ModuleBuilder` for the new
+//! module, to fill in with functions, classes and constants. A module
+//! registered as a package has an empty `__path__`, and its submodules are
+//! registered under their full dotted names.
+
+use std::rc::Rc;
+
+use super::modules;
+use super::object::{Args, NativeFunction, ObjectRef, PyResult};
+use super::Vm;
+
+/// Creates the contents of a module written in Rust.
+pub trait NativeModule {
+    /// Fills in the new module, which has only its `__name__` so far. An
+    /// error raised here is raised by the `import` statement, and the
+    /// module is not added to `sys.modules`.
+    fn init(&self, module: &mut ModuleBuilder) -> PyResult<()>;
+}
+
+impl<F: Fn(&mut ModuleBuilder) -> PyResult<()>> NativeModule for F {
+    fn init(&self, module: &mut ModuleBuilder) -> PyResult<()> {
+        self(module)
+    }
+}
+
+/// A module being created by a `NativeModule`.
+pub struct ModuleBuilder<'a> {
+    vm: &'a mut Vm,
+    name: String,
+    module: ObjectRef,
+}
+
+impl<'a> ModuleBuilder<'a> {
+    /// The VM the module is created in, to make objects with.
+    pub fn vm(&mut self) -> &mut Vm {
+        self.vm
+    }
+
+    /// The full dotted name of the module.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The module object.
+    pub fn module(&self) -> &ObjectRef {
+        &self.module
+    }
+
+    /// Sets the attribute `name` of the module, for a constant, or an
+    /// object made with `vm`.
+    pub fn attribute(&mut self, name: &str, value: ObjectRef) {
+        modules::add_attribute(self.vm, &self.module, name, value);
+    }
+
+    /// Adds the native function `function` under its name.
+    pub fn function(&mut self, name: &'static str, function: NativeFunction) {
+        let function = self.vm.new_builtin(name, function);
+        self.attribute(name, function);
+    }
+
+    /// Adds a function that runs a Rust closure, which may keep state of
+    /// its own.
+    pub fn closure<F>(&mut self, name: &'static str, function: F)
+    where
+        F: Fn(&mut Vm, Args) -> PyResult + 'static,
+    {
+        let function = self.vm.new_builtin_closure(name, function);
+        self.attribute(name, function);
+    }
+
+    /// Adds a class deriving from `base`, with the native functions
+    /// `methods` as its methods, and returns it. It is made as a `class`
+    /// statement makes one, so Python code may subclass it, and its
+    /// instances keep their state in their attributes.
+    pub fn class(
+        &mut self,
+        name: &str,
+        base: &ObjectRef,
+        methods: &[(&'static str, NativeFunction)],
+    ) -> PyResult<ObjectRef> {
+        let class = modules::new_native_class(self.vm, &self.name, name, base, methods)?;
+        self.attribute(name, class.clone());
+        Ok(class)
+    }
+
+    /// Makes the module a package, so that its submodules may be imported.
+    pub fn package(&mut self) {
+        let path = self.vm.new_list(Vec::new());
+        self.attribute("__path__", path);
+    }
+}
+
+impl Vm {
+    /// Registers `module` to be imported as the module `name`, a full
+    /// dotted name. A module of the standard library of the same name is
+    /// imported instead, as it is found first, and registering a name
+    /// again replaces the module registered before.
+    pub fn add_native_module<M: NativeModule + 'static>(&mut self, name: &str, module: M) {
+        self.native_modules
+            .insert(name.to_string(), Rc::new(module));
+    }
+
+    /// Whether `name` is that of a registered native module.
+    pub(super) fn is_native_module(&self, name: &str) -> bool {
+        self.native_modules.contains_key(name)
+    }
+
+    /// The registered native module `name`, created, if there is one.
+    pub(super) fn create_native_module(&mut self, name: &str) -> PyResult<Option<ObjectRef>> {
+        let native = match self.native_modules.get(name) {
+            Some(native) => native.clone(),
+            None => return Ok(None),
+        };
+        let module = self.new_module(name);
+        let mut builder = ModuleBuilder {
+            vm: self,
+            name: name.to_string(),
+            module: module.clone(),
+        };
+        native.init(&mut builder)?;
+        Ok(Some(module))
+    }
+}
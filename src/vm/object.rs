@@ -0,0 +1,478 @@
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use compiler::CodeObject;
+
+use super::cache::InlineCaches;
+use super::dict::{self, Dict};
+use super::frame::Frame;
+use super::gc;
+use super::Vm;
+
+/// A reference to a Python object. Cloning it adds a reference.
+pub type ObjectRef = Rc<PyObject>;
+
+/// The outcome of an operation that may raise: the error is the exception
+/// instance.
+pub type PyResult<T = ObjectRef> = Result<T, ObjectRef>;
+
+/// A builtin function or method. Methods get their instance as the first
+/// positional argument.
+pub type NativeFunction = fn(&mut Vm, Args) -> PyResult;
+
+/// The body of a builtin function, which may be a closure with state.
+pub type BuiltinFunction = Rc<dyn Fn(&mut Vm, Args) -> PyResult>;
+
+/// Creates an instance of the given class, or of one of its subclasses, for
+/// a call to a builtin type.
+pub type NativeConstructor = fn(&mut Vm, &ObjectRef, Args) -> PyResult;
+
+/// A Python object: its class, its attribute dictionary if it has one, and
+/// the data of its builtin type.
+pub struct PyObject {
+    class: RefCell<Option<ObjectRef>>,
+    dict: Option<ObjectRef>,
+    pub payload: Payload,
+    /// The object's slot among those the cycle collector tracks, or
+    /// `UNTRACKED`.
+    gc_slot: Cell<usize>,
+}
+
+const UNTRACKED: usize = usize::MAX;
+
+impl PyObject {
+    pub fn new(payload: Payload, class: ObjectRef, dict: Option<ObjectRef>) -> ObjectRef {
+        let object = Rc::new(PyObject {
+            class: RefCell::new(Some(class)),
+            dict,
+            payload,
+            gc_slot: Cell::new(UNTRACKED),
+        });
+        object.watch_namespace();
+        gc::track(&object);
+        object
+    }
+
+    /// An object whose class is set later, for bootstrapping `type`.
+    pub(super) fn without_class(payload: Payload, dict: Option<ObjectRef>) -> ObjectRef {
+        let object = Rc::new(PyObject {
+            class: RefCell::new(None),
+            dict,
+            payload,
+            gc_slot: Cell::new(UNTRACKED),
+        });
+        object.watch_namespace();
+        gc::track(&object);
+        object
+    }
+
+    pub(super) fn set_class(&self, class: ObjectRef) {
+        *self.class.borrow_mut() = Some(class);
+        // Whether an attribute is a data descriptor depends on its class.
+        dict::types_changed();
+    }
+
+    /// Has the namespace of a class report its changes to the inline
+    /// caches of attribute lookups.
+    fn watch_namespace(&self) {
+        if let Payload::Type(_) = self.payload {
+            if let Some(dict) = self.dict.as_ref().and_then(|dict| dict.as_dict()) {
+                dict.borrow_mut().watch();
+            }
+        }
+    }
+
+    pub fn class(&self) -> ObjectRef {
+        self.class
+            .borrow()
+            .clone()
+            .expect("object used before its class was set")
+    }
+
+    /// The class, which is `None` only while `type` is being created, for
+    /// the cycle collector.
+    pub(super) fn class_cell(&self) -> &RefCell<Option<ObjectRef>> {
+        &self.class
+    }
+
+    pub(super) fn gc_slot(&self) -> &Cell<usize> {
+        &self.gc_slot
+    }
+
+    /// The attribute dictionary, a `dict` object.
+    pub fn dict(&self) -> Option<&ObjectRef> {
+        self.dict.as_ref()
+    }
+
+    /// The name of the object's class.
+    pub fn type_name(&self) -> String {
+        match self.class().payload {
+            Payload::Type(ref data) => data.name.clone(),
+            _ => "?".to_string(),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self.payload {
+            Payload::Str(ref value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_type(&self) -> Option<&TypeData> {
+        match self.payload {
+            Payload::Type(ref data) => Some(data),
+            _ => None,
+        }
+    }
+
+    pub fn as_dict(&self) -> Option<&RefCell<Dict>> {
+        match self.payload {
+            Payload::Dict(ref dict) => Some(dict),
+            _ => None,
+        }
+    }
+
+    pub fn as_exception(&self) -> Option<&RefCell<ExceptionData>> {
+        match self.payload {
+            Payload::Exception(ref data) => Some(data),
+            _ => None,
+        }
+    }
+}
+
+impl Drop for PyObject {
+    fn drop(&mut self) {
+        if self.gc_slot.get() != UNTRACKED {
+            gc::untrack(self.gc_slot.get());
+        }
+        if gc::needs_finalizer(self) {
+            // What the object holds moves into a new object, which lives on
+            // until its `__del__` has been called.
+            let class = self.class.get_mut().take().unwrap();
+            let payload = mem::replace(&mut self.payload, Payload::Object);
+            gc::finalize_later(PyObject::new(payload, class, self.dict.take()));
+        }
+    }
+}
+
+impl fmt::Debug for PyObject {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.payload {
+            Payload::Int(value) => write!(f, "{}", value),
+            Payload::Float(value) => write!(f, "{:?}", value),
+            Payload::Str(ref value) => write!(f, "{:?}", value),
+            Payload::Type(ref data) => write!(f, "<class '{}'>", data.name),
+            _ => write!(f, "<{} object at {:p}>", self.type_name(), self),
+        }
+    }
+}
+
+/// The data of each builtin type. `bool` shares `Int`, and `set` and
+/// `frozenset` share `Set`; the class tells them apart.
+pub enum Payload {
+    /// An instance whose state is all in its attribute dictionary.
+    Object,
+    None,
+    NotImplemented,
+    Ellipsis,
+    Int(i64),
+    Float(f64),
+    Complex {
+        real: f64,
+        imag: f64,
+    },
+    Str(String),
+    Bytes(Vec<u8>),
+    ByteArray(RefCell<Vec<u8>>),
+    Tuple(Vec<ObjectRef>),
+    List(RefCell<Vec<ObjectRef>>),
+    Dict(RefCell<Dict>),
+    /// The keys, values or items of a dict, which follow its changes.
+    DictView {
+        dict: ObjectRef,
+        kind: ViewKind,
+    },
+    /// The keys of the dictionary are the members; the values are unused.
+    Set(RefCell<Dict>),
+    Slice {
+        start: ObjectRef,
+        stop: ObjectRef,
+        step: ObjectRef,
+    },
+    /// `range(start, stop, step)`; `step` is never zero.
+    Range {
+        start: i64,
+        stop: i64,
+        step: i64,
+    },
+    Iterator(RefCell<IteratorState>),
+    Function(Function),
+    Generator(Generator),
+    Builtin(Builtin),
+    /// A function bound to the instance it was looked up on.
+    Method {
+        function: ObjectRef,
+        instance: ObjectRef,
+    },
+    /// A function that is not bound to the instance or class it is looked
+    /// up on.
+    StaticMethod(ObjectRef),
+    /// A function bound to the class it is looked up on, or to the class
+    /// of the instance.
+    ClassMethod(ObjectRef),
+    Property(Property),
+    Code(Arc<CodeObject>),
+    /// A variable that a function closes over, empty until it is assigned.
+    Cell(RefCell<Option<ObjectRef>>),
+    /// A `super` object: attributes are looked up on the classes after
+    /// `class` in the MRO of `object`'s class, or of `object` if it is a
+    /// class, and bound to `object`.
+    Super {
+        class: ObjectRef,
+        object: ObjectRef,
+    },
+    /// A view of the data of a bytes or bytearray object, which reads it
+    /// afresh for each operation until the view is released.
+    MemoryView {
+        object: ObjectRef,
+        released: Cell<bool>,
+    },
+    Module,
+    Type(TypeData),
+    Exception(RefCell<ExceptionData>),
+    Traceback(Traceback),
+}
+
+/// The position of an iterator over a builtin container, or the state of
+/// an iterator that draws its items from others.
+#[derive(Clone)]
+pub enum IteratorState {
+    /// Over a list or tuple, by index.
+    Sequence {
+        sequence: ObjectRef,
+        index: usize,
+    },
+    /// Over the characters of a string, by byte offset.
+    Str {
+        string: ObjectRef,
+        offset: usize,
+    },
+    /// Over the keys of a dict or the members of a set, by entry position.
+    /// `len` is the size when iteration started, to detect changes.
+    Keys {
+        container: ObjectRef,
+        position: usize,
+        len: usize,
+    },
+    /// Over the keys, values or items of a dict, by entry position. When
+    /// `reversed`, `position` is just past the next entry.
+    Entries {
+        dict: ObjectRef,
+        kind: ViewKind,
+        position: usize,
+        len: usize,
+        reversed: bool,
+    },
+    /// Over a range: the next value, the step and how many values are left.
+    Range {
+        next: i64,
+        step: i64,
+        remaining: usize,
+    },
+    /// Over a list, tuple or string from the end. `index` is the number of
+    /// items left, so the next item is the one before it.
+    Reversed {
+        sequence: ObjectRef,
+        index: usize,
+    },
+    /// `enumerate(iterable, start)`.
+    Enumerate {
+        iterator: ObjectRef,
+        count: i64,
+    },
+    /// `zip(*iterables)`.
+    Zip(Vec<ObjectRef>),
+    /// `map(function, *iterables)`.
+    Map {
+        function: ObjectRef,
+        iterators: Vec<ObjectRef>,
+    },
+    /// `filter(function, iterable)`; a `None` function keeps true items.
+    Filter {
+        function: ObjectRef,
+        iterator: ObjectRef,
+    },
+    Exhausted,
+}
+
+/// What a view of a dict, or an iterator over one, gives for each entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ViewKind {
+    Keys,
+    Values,
+    /// `(key, value)` tuples.
+    Items,
+}
+
+pub struct TypeData {
+    pub name: String,
+    pub qualname: String,
+    /// Whether the class was made by a class statement or `type()` rather
+    /// than built in. Only such classes may have their attributes changed.
+    pub heap: bool,
+    pub bases: Vec<ObjectRef>,
+    /// The method resolution order, without the type itself.
+    pub mro: Vec<ObjectRef>,
+    /// Creates instances when the type is called. Inherited from the first
+    /// class in the MRO that has one.
+    pub constructor: Option<NativeConstructor>,
+}
+
+/// A function defined in Python.
+pub struct Function {
+    pub code: Arc<CodeObject>,
+    /// The code's constants as objects, shared by every call.
+    pub constants: Rc<[ObjectRef]>,
+    /// The inline caches of the code, shared by every function made from
+    /// it.
+    pub(super) caches: Rc<InlineCaches>,
+    pub globals: ObjectRef,
+    pub name: RefCell<String>,
+    pub qualname: RefCell<String>,
+    /// A tuple of the defaults of the last positional parameters.
+    pub defaults: RefCell<Option<ObjectRef>>,
+    /// A dict of the defaults of keyword-only parameters.
+    pub kwdefaults: RefCell<Option<ObjectRef>>,
+    /// A dict of the annotations of the parameters and return value, made
+    /// empty when first asked for if the function has none.
+    pub annotations: RefCell<Option<ObjectRef>>,
+    /// A tuple of the cells of the code's free variables.
+    pub closure: Option<ObjectRef>,
+    pub doc: RefCell<ObjectRef>,
+    /// The `__name__` of the globals the function was defined in.
+    pub module: RefCell<ObjectRef>,
+}
+
+/// A `property`: the functions that get, set and delete an attribute of
+/// the instances of the class it is assigned in.
+pub struct Property {
+    pub fget: Option<ObjectRef>,
+    pub fset: Option<ObjectRef>,
+    pub fdel: Option<ObjectRef>,
+    pub doc: RefCell<ObjectRef>,
+    /// Whether `doc` is the getter's docstring.
+    pub getter_doc: bool,
+    /// The name the property is assigned to, for error messages.
+    pub name: RefCell<Option<ObjectRef>>,
+}
+
+/// The suspended frame of a call to a generator function.
+pub struct Generator {
+    pub name: RefCell<String>,
+    pub qualname: RefCell<String>,
+    pub code: Arc<CodeObject>,
+    /// `None` once the generator has finished, and while it runs.
+    pub(super) frame: RefCell<Option<Box<Frame>>>,
+    pub(super) running: Cell<bool>,
+    /// The exception being handled where the generator is suspended, which
+    /// is restored when it resumes.
+    pub(super) exc_info: RefCell<Option<ObjectRef>>,
+}
+
+pub struct Builtin {
+    pub name: &'static str,
+    pub function: BuiltinFunction,
+}
+
+pub struct ExceptionData {
+    /// A tuple.
+    pub args: ObjectRef,
+    pub traceback: Option<ObjectRef>,
+    pub cause: Option<ObjectRef>,
+    pub context: Option<ObjectRef>,
+    pub suppress_context: bool,
+}
+
+/// One frame of a traceback. `next` is the frame the exception came from.
+pub struct Traceback {
+    pub next: Option<ObjectRef>,
+    pub code: Arc<CodeObject>,
+    /// The index of the instruction that raised.
+    pub lasti: usize,
+    pub line: usize,
+    /// The frame's local variables when the exception passed through it,
+    /// if the VM was set to capture them.
+    pub locals: Vec<(String, ObjectRef)>,
+}
+
+/// The arguments of a call to a builtin.
+#[derive(Default)]
+pub struct Args {
+    pub positional: Vec<ObjectRef>,
+    pub keywords: Vec<(String, ObjectRef)>,
+}
+
+impl Args {
+    pub fn new(positional: Vec<ObjectRef>) -> Args {
+        Args {
+            positional,
+            keywords: Vec::new(),
+        }
+    }
+
+    /// Checks that there are between `min` and `max` positional arguments
+    /// and no keyword arguments.
+    pub fn check(&self, vm: &mut Vm, name: &str, min: usize, max: usize) -> PyResult<()> {
+        if !self.keywords.is_empty() {
+            let message = format!("{}() takes no keyword arguments", name);
+            return Err(vm.new_type_error(message));
+        }
+        let count = self.positional.len();
+        if count >= min && count <= max {
+            return Ok(());
+        }
+        let message = if min == max {
+            format!(
+                "{}() takes exactly {} argument{} ({} given)",
+                name,
+                min,
+                plural(min),
+                count
+            )
+        } else if count < min {
+            format!(
+                "{}() takes at least {} argument{} ({} given)",
+                name,
+                min,
+                plural(min),
+                count
+            )
+        } else {
+            format!(
+                "{}() takes at most {} argument{} ({} given)",
+                name,
+                max,
+                plural(max),
+                count
+            )
+        };
+        Err(vm.new_type_error(message))
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// The address of an object, which is its `id()`.
+pub fn object_id(object: &ObjectRef) -> usize {
+    Rc::as_ptr(object) as *const u8 as usize
+}
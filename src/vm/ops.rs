@@ -0,0 +1,3308 @@
+//! The operations of the builtin types: hashing, `str` and `repr`,
+//! comparison, arithmetic, attributes, subscripts and iteration.
+
+use std::cell::Cell;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::convert::TryFrom;
+use std::hash::{Hash, Hasher};
+
+use ast::{float_literal, repr_bytes, repr_str, CmpOperator, Operator, UnaryOperator};
+use optimizer::{float_divmod, format_float, format_int, format_str, FormatError};
+
+use super::bytes::{byte_value, bytes_like, released_error};
+use super::frame::Completion;
+use super::gc;
+use super::object::{
+    object_id, Args, Function, IteratorState, ObjectRef, Payload, PyObject, PyResult, ViewKind,
+};
+use super::Vm;
+
+/// Hashes of numbers are taken modulo this prime, so that equal ints and
+/// floats hash alike, as in CPython.
+const HASH_MODULUS: u64 = (1 << 61) - 1;
+const HASH_BITS: u32 = 61;
+const HASH_INF: i64 = 314_159;
+
+#[derive(Clone, Copy)]
+pub(super) enum Number {
+    Int(i64),
+    Float(f64),
+    Complex(f64, f64),
+}
+
+impl Number {
+    pub(super) fn of(object: &PyObject) -> Option<Number> {
+        match object.payload {
+            Payload::Int(value) => Some(Number::Int(value)),
+            Payload::Float(value) => Some(Number::Float(value)),
+            Payload::Complex { real, imag } => Some(Number::Complex(real, imag)),
+            _ => None,
+        }
+    }
+
+    fn to_float(self) -> f64 {
+        match self {
+            Number::Int(value) => value as f64,
+            Number::Float(value) => value,
+            Number::Complex(real, _) => real,
+        }
+    }
+
+    fn to_complex(self) -> (f64, f64) {
+        match self {
+            Number::Complex(real, imag) => (real, imag),
+            other => (other.to_float(), 0.0),
+        }
+    }
+}
+
+/// The hash of a string, also used by `Dict::get_str`.
+pub fn hash_str(value: &str) -> i64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    fix_hash(hasher.finish() as i64)
+}
+
+fn hash_bytes(value: &[u8]) -> i64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    fix_hash(hasher.finish() as i64)
+}
+
+/// `-1` is not a valid hash in CPython, which uses it for errors.
+fn fix_hash(hash: i64) -> i64 {
+    if hash == -1 {
+        -2
+    } else {
+        hash
+    }
+}
+
+fn hash_int(value: i64) -> i64 {
+    let hash = (value.unsigned_abs() % HASH_MODULUS) as i64;
+    fix_hash(if value < 0 { -hash } else { hash })
+}
+
+/// The hash of a finite float, equal to that of the int it equals if it
+/// is integral, as CPython's `_Py_HashDouble`.
+fn hash_float(value: f64) -> i64 {
+    if value.is_infinite() {
+        return if value > 0.0 { HASH_INF } else { -HASH_INF };
+    }
+    let (mut mantissa, mut exponent) = frexp(value);
+    let sign: i64 = if mantissa < 0.0 {
+        mantissa = -mantissa;
+        -1
+    } else {
+        1
+    };
+    let mut hash: u64 = 0;
+    while mantissa != 0.0 {
+        hash = ((hash << 28) & HASH_MODULUS) | hash >> (HASH_BITS - 28);
+        mantissa *= 268_435_456.0;
+        exponent -= 28;
+        let whole = mantissa as u64;
+        mantissa -= whole as f64;
+        hash += whole;
+        if hash >= HASH_MODULUS {
+            hash -= HASH_MODULUS;
+        }
+    }
+    let exponent = if exponent >= 0 {
+        exponent as u32 % HASH_BITS
+    } else {
+        HASH_BITS - 1 - ((-1 - exponent) as u32 % HASH_BITS)
+    };
+    hash = ((hash << exponent) & HASH_MODULUS) | hash >> (HASH_BITS - exponent);
+    fix_hash((hash as i64).wrapping_mul(sign))
+}
+
+/// Splits a float into a mantissa in `[0.5, 1)` and a power of two.
+fn frexp(value: f64) -> (f64, i32) {
+    if value == 0.0 || !value.is_finite() {
+        return (value, 0);
+    }
+    let bits = value.to_bits();
+    let exponent = ((bits >> 52) & 0x7ff) as i32;
+    if exponent == 0 {
+        let (mantissa, exponent) = frexp(value * 2f64.powi(64));
+        return (mantissa, exponent - 64);
+    }
+    let mantissa = f64::from_bits((bits & !(0x7ff << 52)) | (1022 << 52));
+    (mantissa, exponent - 1022)
+}
+
+/// The hash of an object that is only equal to itself, from its address.
+pub(super) fn hash_pointer(object: &ObjectRef) -> i64 {
+    fix_hash((object_id(object) as u64).rotate_right(4) as i64)
+}
+
+/// The hash of a tuple from the hashes of its items, CPython's xxHash-based
+/// `tuplehash`.
+fn hash_tuple(hashes: &[i64]) -> i64 {
+    const PRIME_1: u64 = 11_400_714_785_074_694_791;
+    const PRIME_2: u64 = 14_029_467_366_897_019_727;
+    const PRIME_5: u64 = 2_870_177_450_012_600_261;
+    let mut acc = PRIME_5;
+    for &hash in hashes {
+        acc = acc.wrapping_add((hash as u64).wrapping_mul(PRIME_2));
+        acc = acc.rotate_left(31);
+        acc = acc.wrapping_mul(PRIME_1);
+    }
+    acc = acc.wrapping_add(hashes.len() as u64 ^ (PRIME_5 ^ 3_527_539));
+    if acc == u64::MAX {
+        return 1_546_275_796;
+    }
+    acc as i64
+}
+
+/// The hash of a frozenset from the hashes of its members, which does not
+/// depend on their order, as CPython's `frozenset_hash`.
+fn hash_frozenset(hashes: &[i64]) -> i64 {
+    let shuffle = |hash: u64| ((hash ^ 89_869_747) ^ (hash << 16)).wrapping_mul(3_644_798_167);
+    let mut hash = hashes
+        .iter()
+        .fold(0u64, |acc, &hash| acc ^ shuffle(hash as u64));
+    hash ^= (hashes.len() as u64 + 1).wrapping_mul(1_927_868_237);
+    hash ^= (hash >> 11) ^ (hash >> 25);
+    hash = hash.wrapping_mul(69_069).wrapping_add(907_133_923);
+    if hash == u64::MAX {
+        return 590_923_713;
+    }
+    hash as i64
+}
+
+/// The `repr` of a float: the shortest digits that read back as the same
+/// float, positional for exponents from -4 to 15 and scientific otherwise,
+/// as CPython gives them.
+fn float_repr(value: f64) -> String {
+    if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        float_literal(value, true)
+    }
+}
+
+/// The name of the methods that implement `op`, without underscores or
+/// the `r` and `i` of the reflected and in-place forms.
+fn operator_method(op: Operator) -> &'static str {
+    match op {
+        Operator::Add => "add",
+        Operator::Sub => "sub",
+        Operator::Mult => "mul",
+        Operator::MatMult => "matmul",
+        Operator::Div => "truediv",
+        Operator::Mod => "mod",
+        Operator::Pow => "pow",
+        Operator::LShift => "lshift",
+        Operator::RShift => "rshift",
+        Operator::BitOr => "or",
+        Operator::BitXor => "xor",
+        Operator::BitAnd => "and",
+        Operator::FloorDiv => "floordiv",
+    }
+}
+
+/// One part of the `repr` of a complex number, which has no `.0`.
+fn complex_part(value: f64, sign: bool) -> String {
+    let text = if value.is_nan() {
+        "nan".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "inf" } else { "-inf" }.to_string()
+    } else {
+        float_literal(value, false)
+    };
+    if sign && !text.starts_with('-') {
+        format!("+{}", text)
+    } else {
+        text
+    }
+}
+
+fn complex_repr(real: f64, imag: f64) -> String {
+    if real == 0.0 && real.is_sign_positive() {
+        format!("{}j", complex_part(imag, false))
+    } else {
+        format!(
+            "({}{}j)",
+            complex_part(real, false),
+            complex_part(imag, true)
+        )
+    }
+}
+
+/// The `repr` of a string with every character outside ASCII escaped, as
+/// `ascii()` writes it.
+fn ascii_escape(repr: &str) -> String {
+    let mut out = String::with_capacity(repr.len());
+    for c in repr.chars() {
+        match c as u32 {
+            0..=0x7f => out.push(c),
+            code @ 0x80..=0xff => out.push_str(&format!("\\x{:02x}", code)),
+            code @ 0x100..=0xffff => out.push_str(&format!("\\u{:04x}", code)),
+            code => out.push_str(&format!("\\U{:08x}", code)),
+        }
+    }
+    out
+}
+
+/// Compares an int and a float exactly, even where the int has no exact
+/// float value. `None` if the float is NaN.
+fn compare_int_float(int: i64, float: f64) -> Option<Ordering> {
+    if float.is_nan() {
+        return None;
+    }
+    if float >= 9_223_372_036_854_775_808.0 {
+        return Some(Ordering::Less);
+    }
+    if float < -9_223_372_036_854_775_808.0 {
+        return Some(Ordering::Greater);
+    }
+    let whole = float.trunc();
+    match int.cmp(&(whole as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&(float - whole)),
+        ordering => Some(ordering),
+    }
+}
+
+fn compare_numbers(a: Number, b: Number) -> Option<Ordering> {
+    match (a, b) {
+        (Number::Int(a), Number::Int(b)) => Some(a.cmp(&b)),
+        (Number::Int(a), Number::Float(b)) => compare_int_float(a, b),
+        (Number::Float(a), Number::Int(b)) => compare_int_float(b, a).map(Ordering::reverse),
+        (a, b) => a.to_float().partial_cmp(&b.to_float()),
+    }
+}
+
+fn ordering_matches(op: CmpOperator, ordering: Option<Ordering>) -> bool {
+    match ordering {
+        None => op == CmpOperator::NotEq,
+        Some(ordering) => match op {
+            CmpOperator::Eq => ordering == Ordering::Equal,
+            CmpOperator::NotEq => ordering != Ordering::Equal,
+            CmpOperator::Lt => ordering == Ordering::Less,
+            CmpOperator::LtE => ordering != Ordering::Greater,
+            CmpOperator::Gt => ordering == Ordering::Greater,
+            CmpOperator::GtE => ordering != Ordering::Less,
+            _ => false,
+        },
+    }
+}
+
+/// Resolves slice bounds against a sequence of `length` items, as
+/// CPython's `PySlice_AdjustIndices`, giving the start, step and number of
+/// items selected.
+fn adjust_slice(
+    length: i64,
+    start: Option<i64>,
+    stop: Option<i64>,
+    step: i64,
+) -> (i64, i64, usize) {
+    let (start, stop) = clamp_slice(length, start, stop, step);
+    let count = if step < 0 {
+        if stop < start {
+            (start - stop - 1) / -step + 1
+        } else {
+            0
+        }
+    } else if start < stop {
+        (stop - start - 1) / step + 1
+    } else {
+        0
+    };
+    (start, step, count as usize)
+}
+
+/// The start and stop of a slice over `length` items, with defaults
+/// filled in and indices clamped as `slice.indices` gives them.
+fn clamp_slice(length: i64, start: Option<i64>, stop: Option<i64>, step: i64) -> (i64, i64) {
+    let clamp = |index: Option<i64>, default: i64| match index {
+        None => default,
+        Some(index) if index < 0 => {
+            let index = index.saturating_add(length);
+            if index < 0 {
+                if step < 0 {
+                    -1
+                } else {
+                    0
+                }
+            } else {
+                index
+            }
+        }
+        Some(index) if index >= length => {
+            if step < 0 {
+                length - 1
+            } else {
+                length
+            }
+        }
+        Some(index) => index,
+    };
+    if step < 0 {
+        (clamp(start, length - 1), clamp(stop, -1))
+    } else {
+        (clamp(start, 0), clamp(stop, length))
+    }
+}
+
+/// The number of values in `range(start, stop, step)`.
+pub(super) fn range_len(start: i64, stop: i64, step: i64) -> usize {
+    let (start, stop, step) = (i128::from(start), i128::from(stop), i128::from(step));
+    let len = if step > 0 && start < stop {
+        (stop - start - 1) / step + 1
+    } else if step < 0 && stop < start {
+        (start - stop - 1) / -step + 1
+    } else {
+        0
+    };
+    len as usize
+}
+
+/// The positions a slice selects, in order.
+fn slice_positions(start: i64, step: i64, count: usize) -> Vec<usize> {
+    (0..count as i64)
+        .map(|i| (start + i * step) as usize)
+        .collect()
+}
+
+impl Vm {
+    pub fn hash(&mut self, object: &ObjectRef) -> PyResult<i64> {
+        let class = object.class();
+        if class.as_type().is_some_and(|data| data.heap) {
+            if let Some(ref hash) = self.lookup(&class, "__hash__") {
+                if Vm::is(hash, &self.none) {
+                    let message = format!("unhashable type: '{}'", object.type_name());
+                    return Err(self.new_type_error(message));
+                }
+            }
+            if let Some(method) = self.python_method(object, "__hash__") {
+                let result = self.call(&method, Args::default())?;
+                return match result.payload {
+                    Payload::Int(value) => Ok(fix_hash(value)),
+                    _ => {
+                        let message = "__hash__ method should return an integer".to_string();
+                        Err(self.new_type_error(message))
+                    }
+                };
+            }
+        }
+        Ok(match object.payload {
+            Payload::Int(value) => hash_int(value),
+            Payload::Float(value) if value.is_nan() => hash_pointer(object),
+            Payload::Float(value) => hash_float(value),
+            Payload::Complex { real, imag } => {
+                let real = if real.is_nan() { 0 } else { hash_float(real) };
+                let imag = if imag.is_nan() { 0 } else { hash_float(imag) };
+                fix_hash(real.wrapping_add(imag.wrapping_mul(1_000_003)))
+            }
+            Payload::Str(ref value) => hash_str(value),
+            Payload::Bytes(ref value) => hash_bytes(value),
+            Payload::MemoryView {
+                object: ref viewed, ..
+            } => match bytes_like(object) {
+                None => return Err(released_error(self)),
+                Some(_) if matches!(viewed.payload, Payload::ByteArray(_)) => {
+                    let message = "cannot hash writable memoryview object".to_string();
+                    return Err(self.new_value_error(message));
+                }
+                Some(data) => hash_bytes(&data),
+            },
+            Payload::Tuple(ref items) => {
+                let hashes = items
+                    .iter()
+                    .map(|item| self.hash(item))
+                    .collect::<PyResult<Vec<_>>>()?;
+                hash_tuple(&hashes)
+            }
+            Payload::Set(ref members) if Vm::is(&object.class(), &self.types.frozenset) => {
+                let hashes: Vec<i64> = members.borrow().iter().map(|entry| entry.hash).collect();
+                hash_frozenset(&hashes)
+            }
+            Payload::Range { start, stop, step } => {
+                let none = hash_pointer(&self.none);
+                let len = range_len(start, stop, step);
+                let hashes = match len {
+                    0 => [hash_int(0), none, none],
+                    1 => [hash_int(1), hash_int(start), none],
+                    _ => [hash_int(len as i64), hash_int(start), hash_int(step)],
+                };
+                hash_tuple(&hashes)
+            }
+            Payload::List(_)
+            | Payload::ByteArray(_)
+            | Payload::Dict(_)
+            | Payload::Set(_)
+            | Payload::DictView { .. }
+            | Payload::Slice { .. } => {
+                let message = format!("unhashable type: '{}'", object.type_name());
+                return Err(self.new_type_error(message));
+            }
+            _ => hash_pointer(object),
+        })
+    }
+
+    /// Whether an object is true in a boolean context.
+    pub fn is_true(&mut self, object: &ObjectRef) -> PyResult<bool> {
+        if let Some(method) = self.python_method(object, "__bool__") {
+            let result = self.call(&method, Args::default())?;
+            return match result.payload {
+                Payload::Int(value) if Vm::is(&result.class(), &self.types.bool) => Ok(value != 0),
+                _ => {
+                    let message = format!(
+                        "__bool__ should return bool, returned {}",
+                        result.type_name()
+                    );
+                    Err(self.new_type_error(message))
+                }
+            };
+        }
+        if self.python_method(object, "__len__").is_some() {
+            return Ok(self.len(object)? != 0);
+        }
+        Ok(match object.payload {
+            Payload::None => false,
+            Payload::Int(value) => value != 0,
+            Payload::Float(value) => value != 0.0,
+            Payload::Complex { real, imag } => real != 0.0 || imag != 0.0,
+            Payload::Str(ref value) => !value.is_empty(),
+            Payload::Bytes(ref value) => !value.is_empty(),
+            Payload::ByteArray(ref value) => !value.borrow().is_empty(),
+            Payload::MemoryView { .. } => self.len(object)? != 0,
+            Payload::Tuple(ref items) => !items.is_empty(),
+            Payload::List(ref items) => !items.borrow().is_empty(),
+            Payload::Dict(ref dict) | Payload::Set(ref dict) => !dict.borrow().is_empty(),
+            Payload::DictView { ref dict, .. } => !dict.as_dict().unwrap().borrow().is_empty(),
+            Payload::Range { start, stop, step } => range_len(start, stop, step) != 0,
+            _ => true,
+        })
+    }
+
+    /// `len(object)`.
+    pub fn len(&mut self, object: &ObjectRef) -> PyResult<usize> {
+        if let Some(method) = self.python_method(object, "__len__") {
+            let result = self.call(&method, Args::default())?;
+            return match result.payload {
+                Payload::Int(value) if value >= 0 => Ok(value as usize),
+                Payload::Int(_) => {
+                    let message = "__len__() should return >= 0".to_string();
+                    Err(self.new_value_error(message))
+                }
+                _ => {
+                    let message = format!(
+                        "'{}' object cannot be interpreted as an integer",
+                        result.type_name()
+                    );
+                    Err(self.new_type_error(message))
+                }
+            };
+        }
+        Ok(match object.payload {
+            Payload::Str(ref value) => value.chars().count(),
+            Payload::Bytes(ref value) => value.len(),
+            Payload::ByteArray(ref value) => value.borrow().len(),
+            Payload::MemoryView { .. } => match bytes_like(object) {
+                Some(data) => data.len(),
+                None => return Err(released_error(self)),
+            },
+            Payload::Tuple(ref items) => items.len(),
+            Payload::List(ref items) => items.borrow().len(),
+            Payload::Dict(ref dict) | Payload::Set(ref dict) => dict.borrow().len(),
+            Payload::DictView { ref dict, .. } => dict.as_dict().unwrap().borrow().len(),
+            Payload::Range { start, stop, step } => {
+                let len = range_len(start, stop, step);
+                if len > isize::MAX as usize {
+                    let message = "Python int too large to convert to C ssize_t".to_string();
+                    return Err(self.new_overflow_error(message));
+                }
+                len
+            }
+            _ => {
+                let message = format!("object of type '{}' has no len()", object.type_name());
+                return Err(self.new_type_error(message));
+            }
+        })
+    }
+
+    /// `str(object)`.
+    pub fn str(&mut self, object: &ObjectRef) -> PyResult<String> {
+        if let Some(text) = self.call_str_method(object, "__str__")? {
+            return Ok(text);
+        }
+        match object.payload {
+            Payload::Str(ref value) => Ok(value.clone()),
+            Payload::Exception(_) => self.exception_str(object),
+            _ => self.repr(object),
+        }
+    }
+
+    /// `repr(object)`.
+    pub fn repr(&mut self, object: &ObjectRef) -> PyResult<String> {
+        if let Some(text) = self.call_str_method(object, "__repr__")? {
+            return Ok(text);
+        }
+        match object.payload {
+            Payload::Tuple(_)
+            | Payload::List(_)
+            | Payload::Dict(_)
+            | Payload::DictView { .. }
+            | Payload::Set(_) => self.container_repr(object),
+            _ => self.scalar_repr(object),
+        }
+    }
+
+    /// The repr of an object of a builtin type that holds no items, which
+    /// is kept out of `repr` so that the frames of nested reprs are small.
+    fn scalar_repr(&mut self, object: &ObjectRef) -> PyResult<String> {
+        let address = object_id(object);
+        Ok(match object.payload {
+            Payload::Tuple(_)
+            | Payload::List(_)
+            | Payload::Dict(_)
+            | Payload::DictView { .. }
+            | Payload::Set(_) => unreachable!(),
+            Payload::Object => format!(
+                "<{} object at {:#x}>",
+                self.class_name(&object.class()),
+                address
+            ),
+            Payload::None => "None".to_string(),
+            Payload::NotImplemented => "NotImplemented".to_string(),
+            Payload::Ellipsis => "Ellipsis".to_string(),
+            Payload::Int(value) if Vm::is(&object.class(), &self.types.bool) => {
+                if value != 0 { "True" } else { "False" }.to_string()
+            }
+            Payload::Int(value) => value.to_string(),
+            Payload::Float(value) => float_repr(value),
+            Payload::Complex { real, imag } => complex_repr(real, imag),
+            Payload::Str(ref value) => repr_str(value),
+            Payload::Bytes(ref value) => repr_bytes(value),
+            Payload::ByteArray(ref value) => {
+                format!("{}({})", object.type_name(), repr_bytes(&value.borrow()))
+            }
+            Payload::MemoryView { ref released, .. } if released.get() => {
+                format!("<released memory at {:#x}>", address)
+            }
+            Payload::MemoryView { .. } => format!("<memory at {:#x}>", address),
+            Payload::Slice {
+                ref start,
+                ref stop,
+                ref step,
+            } => format!(
+                "slice({}, {}, {})",
+                self.repr(start)?,
+                self.repr(stop)?,
+                self.repr(step)?
+            ),
+            Payload::Range {
+                start,
+                stop,
+                step: 1,
+            } => format!("range({}, {})", start, stop),
+            Payload::Range { start, stop, step } => format!("range({}, {}, {})", start, stop, step),
+            Payload::Function(ref function) => format!(
+                "<function {} at {:#x}>",
+                function.qualname.borrow(),
+                address
+            ),
+            Payload::Generator(ref generator) => format!(
+                "<{} object {} at {:#x}>",
+                generator.kind(),
+                generator.qualname.borrow(),
+                address
+            ),
+            Payload::Builtin(ref builtin) => format!("<built-in function {}>", builtin.name),
+            Payload::Method {
+                ref function,
+                ref instance,
+            } => {
+                let name = match function.payload {
+                    Payload::Function(ref function) => function.qualname.borrow().clone(),
+                    Payload::Builtin(ref builtin) => builtin.name.to_string(),
+                    _ => "?".to_string(),
+                };
+                match function.payload {
+                    Payload::Builtin(_) => format!(
+                        "<built-in method {} of {} object at {:#x}>",
+                        name,
+                        instance.type_name(),
+                        object_id(instance)
+                    ),
+                    _ => format!("<bound method {} of {}>", name, self.repr(instance)?),
+                }
+            }
+            Payload::StaticMethod(ref function) => {
+                format!("<staticmethod({})>", self.repr(function)?)
+            }
+            Payload::ClassMethod(ref function) => {
+                format!("<classmethod({})>", self.repr(function)?)
+            }
+            Payload::Code(ref code) => format!(
+                "<code object {} at {:#x}, file \"{}\", line {}>",
+                code.name, address, code.filename, code.first_line
+            ),
+            Payload::Module => {
+                let name = self.module_name(object);
+                let file = object
+                    .dict()
+                    .and_then(|dict| dict.as_dict().unwrap().borrow().get_str("__file__"));
+                match file.as_ref().and_then(|file| file.as_str()) {
+                    Some(file) => format!("<module {} from {}>", repr_str(&name), repr_str(file)),
+                    None => format!("<module {}>", repr_str(&name)),
+                }
+            }
+            Payload::Type(_) => format!("<class '{}'>", self.class_name(object)),
+            Payload::Exception(_) => return self.exception_repr(object),
+            Payload::Cell(ref value) => match *value.borrow() {
+                Some(ref value) => format!(
+                    "<cell at {:#x}: {} object at {:#x}>",
+                    address,
+                    value.type_name(),
+                    object_id(value)
+                ),
+                None => format!("<cell at {:#x}: empty>", address),
+            },
+            Payload::Super {
+                ref class,
+                object: ref instance,
+            } => format!(
+                "<super: {}, <{} object>>",
+                self.repr(class)?,
+                instance.type_name()
+            ),
+            Payload::Iterator(_) | Payload::Traceback(_) | Payload::Property(_) => {
+                format!("<{} object at {:#x}>", object.type_name(), address)
+            }
+        })
+    }
+
+    /// The repr of a tuple, list, dict, dict view or set. One that contains
+    /// itself shows as `[...]` or the like there, and nesting counts
+    /// towards the recursion limit, as CPython's `Py_ReprEnter`.
+    fn container_repr(&mut self, object: &ObjectRef) -> PyResult<String> {
+        let address = object_id(object);
+        if self.repr_stack.contains(&address) {
+            return Ok(match object.payload {
+                Payload::Tuple(_) => "(...)".to_string(),
+                Payload::List(_) => "[...]".to_string(),
+                Payload::Dict(_) => "{...}".to_string(),
+                Payload::Set(_) => format!("{}(...)", object.type_name()),
+                _ => "...".to_string(),
+            });
+        }
+        if self.depth >= self.recursion_limit {
+            let class = self.exceptions.recursion_error.clone();
+            let message =
+                "maximum recursion depth exceeded while getting the repr of an object".to_string();
+            return Err(self.new_error(&class, message));
+        }
+        self.depth += 1;
+        self.repr_stack.push(address);
+        let result = self.items_repr(object);
+        self.repr_stack.pop();
+        self.depth -= 1;
+        result
+    }
+
+    fn items_repr(&mut self, object: &ObjectRef) -> PyResult<String> {
+        Ok(match object.payload {
+            Payload::Tuple(ref items) => {
+                let items = self.reprs(items)?;
+                if items.len() == 1 {
+                    format!("({},)", items[0])
+                } else {
+                    format!("({})", items.join(", "))
+                }
+            }
+            Payload::List(ref items) => {
+                let items = items.borrow().clone();
+                format!("[{}]", self.reprs(&items)?.join(", "))
+            }
+            Payload::Dict(ref dict) => {
+                let entries: Vec<(ObjectRef, ObjectRef)> = dict
+                    .borrow()
+                    .iter()
+                    .map(|entry| (entry.key.clone(), entry.value.clone()))
+                    .collect();
+                let mut items = Vec::with_capacity(entries.len());
+                for (key, value) in entries {
+                    items.push(format!("{}: {}", self.repr(&key)?, self.repr(&value)?));
+                }
+                format!("{{{}}}", items.join(", "))
+            }
+            Payload::DictView { ref dict, kind } => {
+                let items = self.view_items(dict, kind);
+                format!(
+                    "{}([{}])",
+                    object.type_name(),
+                    self.reprs(&items)?.join(", ")
+                )
+            }
+            Payload::Set(ref members) => {
+                let members: Vec<ObjectRef> = members
+                    .borrow()
+                    .iter()
+                    .map(|entry| entry.key.clone())
+                    .collect();
+                let name = object.type_name();
+                let frozen = Vm::is(&object.class(), &self.types.frozenset);
+                match (members.is_empty(), frozen) {
+                    (true, _) => format!("{}()", name),
+                    (false, false) => format!("{{{}}}", self.reprs(&members)?.join(", ")),
+                    (false, true) => format!("{}({{{}}})", name, self.reprs(&members)?.join(", ")),
+                }
+            }
+            _ => unreachable!(),
+        })
+    }
+
+    /// The result of a `__str__` or `__repr__` a class statement defines,
+    /// which must be a string.
+    fn call_str_method(&mut self, object: &ObjectRef, name: &str) -> PyResult<Option<String>> {
+        let method = match self.python_method(object, name) {
+            Some(method) => method,
+            None => return Ok(None),
+        };
+        let result = self.call(&method, Args::default())?;
+        match result.as_str() {
+            Some(text) => Ok(Some(text.to_string())),
+            None => {
+                let message = format!("{} returned non-string (type {})", name, result.type_name());
+                Err(self.new_type_error(message))
+            }
+        }
+    }
+
+    /// `ascii(object)`, the `repr` with non-ASCII characters escaped.
+    pub fn ascii(&mut self, object: &ObjectRef) -> PyResult<String> {
+        Ok(ascii_escape(&self.repr(object)?))
+    }
+
+    fn reprs(&mut self, items: &[ObjectRef]) -> PyResult<Vec<String>> {
+        items.iter().map(|item| self.repr(item)).collect()
+    }
+
+    fn module_name(&self, module: &ObjectRef) -> String {
+        module
+            .dict()
+            .and_then(|dict| dict.as_dict().unwrap().borrow().get_str("__name__"))
+            .and_then(|name| name.as_str().map(str::to_string))
+            .unwrap_or_else(|| "?".to_string())
+    }
+
+    /// `a == b`, as a bool.
+    pub fn eq(&mut self, a: &ObjectRef, b: &ObjectRef) -> PyResult<bool> {
+        self.rich_compare(a, CmpOperator::Eq, b)
+    }
+
+    /// Whether two items are equal as containers compare them: identical
+    /// objects are equal without asking.
+    pub(super) fn same_or_eq(&mut self, a: &ObjectRef, b: &ObjectRef) -> PyResult<bool> {
+        Ok(Vm::is(a, b) || self.eq(a, b)?)
+    }
+
+    /// A comparison operator, giving a bool object.
+    pub fn compare(&mut self, a: &ObjectRef, op: CmpOperator, b: &ObjectRef) -> PyResult {
+        let result = match op {
+            CmpOperator::Is => Vm::is(a, b),
+            CmpOperator::IsNot => !Vm::is(a, b),
+            CmpOperator::In => self.contains(b, a)?,
+            CmpOperator::NotIn => !self.contains(b, a)?,
+            _ => match self.compare_with_methods(a, op, b)? {
+                Some(result) => return Ok(result),
+                None => self.builtin_compare(a, op, b)?,
+            },
+        };
+        Ok(self.new_bool(result))
+    }
+
+    fn rich_compare(&mut self, a: &ObjectRef, op: CmpOperator, b: &ObjectRef) -> PyResult<bool> {
+        match self.compare_with_methods(a, op, b)? {
+            Some(result) => self.is_true(&result),
+            None => self.builtin_compare(a, op, b),
+        }
+    }
+
+    /// Compares objects of the builtin types, and others by identity.
+    pub(super) fn builtin_compare(
+        &mut self,
+        a: &ObjectRef,
+        op: CmpOperator,
+        b: &ObjectRef,
+    ) -> PyResult<bool> {
+        let equality = op == CmpOperator::Eq || op == CmpOperator::NotEq;
+        if let (Some(x), Some(y)) = (Number::of(a), Number::of(b)) {
+            let complex = matches!(x, Number::Complex(..)) || matches!(y, Number::Complex(..));
+            if !complex {
+                return Ok(ordering_matches(op, compare_numbers(x, y)));
+            }
+            if equality {
+                let equal = x.to_complex() == y.to_complex();
+                return Ok(equal == (op == CmpOperator::Eq));
+            }
+        }
+        match (&a.payload, &b.payload) {
+            (Payload::Str(x), Payload::Str(y)) => return Ok(ordering_matches(op, Some(x.cmp(y)))),
+            (
+                Payload::Bytes(_) | Payload::ByteArray(_),
+                Payload::Bytes(_) | Payload::ByteArray(_),
+            ) => {
+                let (x, y) = (bytes_like(a).unwrap(), bytes_like(b).unwrap());
+                return Ok(ordering_matches(op, Some(x.cmp(&y))));
+            }
+            (
+                Payload::Slice {
+                    start: x_start,
+                    stop: x_stop,
+                    step: x_step,
+                },
+                Payload::Slice {
+                    start: y_start,
+                    stop: y_stop,
+                    step: y_step,
+                },
+            ) => {
+                let x = [x_start.clone(), x_stop.clone(), x_step.clone()];
+                let y = [y_start.clone(), y_stop.clone(), y_step.clone()];
+                return self.compare_sequences(&x, op, &y);
+            }
+            (Payload::MemoryView { .. }, _) | (_, Payload::MemoryView { .. }) if equality => {
+                if let (Some(x), Some(y)) = (bytes_like(a), bytes_like(b)) {
+                    return Ok((x == y) == (op == CmpOperator::Eq));
+                }
+            }
+            (Payload::Tuple(x), Payload::Tuple(y)) => return self.compare_sequences(x, op, y),
+            (Payload::List(x), Payload::List(y)) => {
+                let (x, y) = (x.borrow().clone(), y.borrow().clone());
+                return self.compare_sequences(&x, op, &y);
+            }
+            (
+                &Payload::Range { start, stop, step },
+                &Payload::Range {
+                    start: other_start,
+                    stop: other_stop,
+                    step: other_step,
+                },
+            ) if equality => {
+                let len = range_len(start, stop, step);
+                let equal = len == range_len(other_start, other_stop, other_step)
+                    && (len == 0 || (start == other_start && (len == 1 || step == other_step)));
+                return Ok(equal == (op == CmpOperator::Eq));
+            }
+            (Payload::Dict(x), Payload::Dict(y)) if equality => {
+                let equal = self.dicts_equal(x, y)?;
+                return Ok(equal == (op == CmpOperator::Eq));
+            }
+            _ if self.is_set_like(a) && self.is_set_like(b) && self.is_view(a, b) => {
+                let (a_len, b_len) = (self.len(a)?, self.len(b)?);
+                let result = match op {
+                    CmpOperator::Eq => a_len == b_len && self.all_contained(a, b)?,
+                    CmpOperator::NotEq => !(a_len == b_len && self.all_contained(a, b)?),
+                    CmpOperator::LtE => a_len <= b_len && self.all_contained(a, b)?,
+                    CmpOperator::Lt => a_len < b_len && self.all_contained(a, b)?,
+                    CmpOperator::GtE => a_len >= b_len && self.all_contained(b, a)?,
+                    _ => a_len > b_len && self.all_contained(b, a)?,
+                };
+                return Ok(result);
+            }
+            (Payload::Set(x), Payload::Set(y)) => {
+                let (x_len, y_len) = (x.borrow().len(), y.borrow().len());
+                let result = match op {
+                    CmpOperator::Eq => x_len == y_len && self.is_subset(x, y)?,
+                    CmpOperator::NotEq => !(x_len == y_len && self.is_subset(x, y)?),
+                    CmpOperator::LtE => self.is_subset(x, y)?,
+                    CmpOperator::Lt => x_len < y_len && self.is_subset(x, y)?,
+                    CmpOperator::GtE => self.is_subset(y, x)?,
+                    _ => x_len > y_len && self.is_subset(y, x)?,
+                };
+                return Ok(result);
+            }
+            _ => {}
+        }
+        match op {
+            CmpOperator::Eq => Ok(Vm::is(a, b)),
+            CmpOperator::NotEq => Ok(!Vm::is(a, b)),
+            _ => {
+                let message = format!(
+                    "'{}' not supported between instances of '{}' and '{}'",
+                    op.symbol(),
+                    a.type_name(),
+                    b.type_name()
+                );
+                Err(self.new_type_error(message))
+            }
+        }
+    }
+
+    /// Compares objects with the comparison methods their classes define,
+    /// as CPython does: with the method of `a`, then the reflected one of
+    /// `b`, trying `b` first if its class is a subclass of that of `a`.
+    /// `None` if neither gives an answer.
+    fn compare_with_methods(
+        &mut self,
+        a: &ObjectRef,
+        op: CmpOperator,
+        b: &ObjectRef,
+    ) -> PyResult<Option<ObjectRef>> {
+        let (name, reflected) = match op {
+            CmpOperator::Eq => ("__eq__", "__eq__"),
+            CmpOperator::NotEq => ("__ne__", "__ne__"),
+            CmpOperator::Lt => ("__lt__", "__gt__"),
+            CmpOperator::LtE => ("__le__", "__ge__"),
+            CmpOperator::Gt => ("__gt__", "__lt__"),
+            CmpOperator::GtE => ("__ge__", "__le__"),
+            _ => return Ok(None),
+        };
+        let (a_class, b_class) = (a.class(), b.class());
+        let mut attempts = [(a, name, b), (b, reflected, a)];
+        if !Vm::is(&a_class, &b_class) && Vm::is_subclass(&b_class, &a_class) {
+            attempts.swap(0, 1);
+        }
+        for &(object, name, other) in &attempts {
+            if let Some(result) = self.call_comparison(object, name, other)? {
+                return Ok(Some(result));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The result of the comparison method `name` of `object`, or `None`
+    /// if it has none or it returns `NotImplemented`. Without `__ne__`,
+    /// `!=` is the opposite of `__eq__`.
+    fn call_comparison(
+        &mut self,
+        object: &ObjectRef,
+        name: &str,
+        other: &ObjectRef,
+    ) -> PyResult<Option<ObjectRef>> {
+        let (method, invert) = match self.python_method(object, name) {
+            Some(method) => (method, false),
+            None if name == "__ne__" => match self.python_method(object, "__eq__") {
+                Some(method) => (method, true),
+                None => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        let result = self.call(&method, Args::new(vec![other.clone()]))?;
+        if Vm::is(&result, &self.not_implemented()) {
+            return Ok(None);
+        }
+        if invert {
+            let equal = self.is_true(&result)?;
+            return Ok(Some(self.new_bool(!equal)));
+        }
+        Ok(Some(result))
+    }
+
+    /// Compares sequences item by item, and by length if one is a prefix
+    /// of the other.
+    fn compare_sequences(
+        &mut self,
+        a: &[ObjectRef],
+        op: CmpOperator,
+        b: &[ObjectRef],
+    ) -> PyResult<bool> {
+        if a.len() != b.len() && (op == CmpOperator::Eq || op == CmpOperator::NotEq) {
+            return Ok(op == CmpOperator::NotEq);
+        }
+        for (x, y) in a.iter().zip(b) {
+            if !self.same_or_eq(x, y)? {
+                return match op {
+                    CmpOperator::Eq => Ok(false),
+                    CmpOperator::NotEq => Ok(true),
+                    _ => self.rich_compare(x, op, y),
+                };
+            }
+        }
+        Ok(ordering_matches(op, Some(a.len().cmp(&b.len()))))
+    }
+
+    fn dicts_equal(
+        &mut self,
+        a: &::std::cell::RefCell<super::Dict>,
+        b: &::std::cell::RefCell<super::Dict>,
+    ) -> PyResult<bool> {
+        if a.borrow().len() != b.borrow().len() {
+            return Ok(false);
+        }
+        let entries: Vec<(ObjectRef, ObjectRef)> = a
+            .borrow()
+            .iter()
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect();
+        for (key, value) in entries {
+            match self.dict_get(b, &key)? {
+                Some(other) if self.same_or_eq(&value, &other)? => {}
+                _ => return Ok(false),
+            }
+        }
+        Ok(true)
+    }
+
+    fn is_subset(
+        &mut self,
+        a: &::std::cell::RefCell<super::Dict>,
+        b: &::std::cell::RefCell<super::Dict>,
+    ) -> PyResult<bool> {
+        let members: Vec<ObjectRef> = a.borrow().iter().map(|entry| entry.key.clone()).collect();
+        for member in members {
+            if !self.dict_contains(b, &member)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Whether an object is a set, or a view of the keys or items of a dict,
+    /// which compare and combine like sets.
+    fn is_set_like(&self, object: &ObjectRef) -> bool {
+        match object.payload {
+            Payload::Set(_) => true,
+            Payload::DictView { kind, .. } => kind != ViewKind::Values,
+            _ => false,
+        }
+    }
+
+    /// Whether either of two objects is a view of a dict.
+    fn is_view(&self, a: &ObjectRef, b: &ObjectRef) -> bool {
+        matches!(a.payload, Payload::DictView { .. })
+            || matches!(b.payload, Payload::DictView { .. })
+    }
+
+    /// Whether every item of `a` is in `b`.
+    fn all_contained(&mut self, a: &ObjectRef, b: &ObjectRef) -> PyResult<bool> {
+        for item in self.iterate(a)? {
+            if !self.contains(b, &item)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// `item in container`.
+    pub fn contains(&mut self, container: &ObjectRef, item: &ObjectRef) -> PyResult<bool> {
+        if let Some(method) = self.python_method(container, "__contains__") {
+            let result = self.call(&method, Args::new(vec![item.clone()]))?;
+            return self.is_true(&result);
+        }
+        match container.payload {
+            Payload::Str(ref haystack) => match item.payload {
+                Payload::Str(ref needle) => Ok(haystack.contains(needle.as_str())),
+                _ => {
+                    let message = format!(
+                        "'in <string>' requires string as left operand, not {}",
+                        item.type_name()
+                    );
+                    Err(self.new_type_error(message))
+                }
+            },
+            Payload::Bytes(_) | Payload::ByteArray(_) => {
+                let haystack = bytes_like(container).unwrap();
+                if let Some(needle) = bytes_like(item) {
+                    return Ok(needle.is_empty()
+                        || haystack
+                            .windows(needle.len())
+                            .any(|window| window == &needle[..]));
+                }
+                match item.payload {
+                    Payload::Int(byte) if (0..256).contains(&byte) => {
+                        Ok(haystack.contains(&(byte as u8)))
+                    }
+                    Payload::Int(_) => {
+                        Err(self.new_value_error("byte must be in range(0, 256)".to_string()))
+                    }
+                    _ => {
+                        let message = format!(
+                            "a bytes-like object is required, not '{}'",
+                            item.type_name()
+                        );
+                        Err(self.new_type_error(message))
+                    }
+                }
+            }
+            Payload::Dict(ref dict) | Payload::Set(ref dict) => self.dict_contains(dict, item),
+            Payload::DictView { ref dict, kind } => self.view_contains(dict, kind, item),
+            Payload::Range { start, stop, step } if matches!(item.payload, Payload::Int(_)) => {
+                let value = Vm::index(item).unwrap();
+                let in_bounds = if step > 0 {
+                    start <= value && value < stop
+                } else {
+                    stop < value && value <= start
+                };
+                Ok(in_bounds && (i128::from(value) - i128::from(start)) % i128::from(step) == 0)
+            }
+            _ => {
+                if !self.is_iterable(container) {
+                    let message = format!(
+                        "argument of type '{}' is not iterable",
+                        container.type_name()
+                    );
+                    return Err(self.new_type_error(message));
+                }
+                let iterator = self.iter(container)?;
+                while let Some(value) = self.next(&iterator)? {
+                    if self.same_or_eq(&value, item)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    pub fn unary_op(&mut self, op: UnaryOperator, operand: &ObjectRef) -> PyResult {
+        if op == UnaryOperator::Not {
+            let value = self.is_true(operand)?;
+            return Ok(self.new_bool(!value));
+        }
+        let name = match op {
+            UnaryOperator::UAdd => "__pos__",
+            UnaryOperator::USub => "__neg__",
+            _ => "__invert__",
+        };
+        if let Some(method) = self.python_method(operand, name) {
+            return self.call(&method, Args::default());
+        }
+        match (op, Number::of(operand)) {
+            (UnaryOperator::UAdd, Some(Number::Int(value))) => Ok(self.new_int(value)),
+            (UnaryOperator::UAdd, Some(_)) => Ok(operand.clone()),
+            (UnaryOperator::USub, Some(Number::Int(value))) => match value.checked_neg() {
+                Some(value) => Ok(self.new_int(value)),
+                None => Err(self.int_overflow()),
+            },
+            (UnaryOperator::USub, Some(Number::Float(value))) => Ok(self.new_float(-value)),
+            (UnaryOperator::USub, Some(Number::Complex(real, imag))) => {
+                Ok(self.new_complex(-real, -imag))
+            }
+            (UnaryOperator::Invert, Some(Number::Int(value))) => Ok(self.new_int(!value)),
+            _ => {
+                let message = format!(
+                    "bad operand type for unary {}: '{}'",
+                    op.symbol(),
+                    operand.type_name()
+                );
+                Err(self.new_type_error(message))
+            }
+        }
+    }
+
+    /// A binary operator, or its augmented assignment form if `inplace`,
+    /// which changes a list in place.
+    pub fn binary_op(
+        &mut self,
+        a: &ObjectRef,
+        op: Operator,
+        b: &ObjectRef,
+        inplace: bool,
+    ) -> PyResult {
+        if let Some(result) = self.binary_op_with_methods(a, op, b, inplace)? {
+            return Ok(result);
+        }
+        if let (Some(x), Some(y)) = (Number::of(a), Number::of(b)) {
+            let bools =
+                Vm::is(&a.class(), &self.types.bool) && Vm::is(&b.class(), &self.types.bool);
+            if let (true, Number::Int(x), Number::Int(y)) = (bools, x, y) {
+                match op {
+                    Operator::BitAnd => return Ok(self.new_bool(x & y != 0)),
+                    Operator::BitOr => return Ok(self.new_bool(x | y != 0)),
+                    Operator::BitXor => return Ok(self.new_bool(x ^ y != 0)),
+                    _ => {}
+                }
+            }
+            if let Some(result) = self.numeric_op(x, op, y)? {
+                return Ok(result);
+            }
+        } else if let Some(result) = self.sequence_op(a, op, b, inplace)? {
+            return Ok(result);
+        }
+        let symbol = match (op, inplace) {
+            (Operator::Pow, false) => "** or pow()".to_string(),
+            (op, false) => op.symbol().to_string(),
+            (op, true) => format!("{}=", op.symbol()),
+        };
+        let message = format!(
+            "unsupported operand type(s) for {}: '{}' and '{}'",
+            symbol,
+            a.type_name(),
+            b.type_name()
+        );
+        Err(self.new_type_error(message))
+    }
+
+    /// A binary operator with the methods the operands' classes define, as
+    /// CPython applies them: the in-place method of `a` if `inplace`, then
+    /// that of `a`, then the reflected one of `b`, tried first if its class
+    /// is a subclass of that of `a`. `None` if none gives an answer.
+    fn binary_op_with_methods(
+        &mut self,
+        a: &ObjectRef,
+        op: Operator,
+        b: &ObjectRef,
+        inplace: bool,
+    ) -> PyResult<Option<ObjectRef>> {
+        let name = operator_method(op);
+        if inplace {
+            let inplace_name = format!("__i{}__", name);
+            if let Some(result) = self.call_operator(a, &inplace_name, b)? {
+                return Ok(Some(result));
+            }
+        }
+        let forward = format!("__{}__", name);
+        let reflected = format!("__r{}__", name);
+        let (a_class, b_class) = (a.class(), b.class());
+        let mut attempts = vec![(a, &forward, b)];
+        if !Vm::is(&a_class, &b_class) {
+            if Vm::is_subclass(&b_class, &a_class) {
+                attempts.insert(0, (b, &reflected, a));
+            } else {
+                attempts.push((b, &reflected, a));
+            }
+        }
+        for (object, name, other) in attempts {
+            if let Some(result) = self.call_operator(object, name, other)? {
+                return Ok(Some(result));
+            }
+        }
+        Ok(None)
+    }
+
+    /// The result of the operator method `name` of `object`, or `None` if
+    /// it has none or it returns `NotImplemented`.
+    fn call_operator(
+        &mut self,
+        object: &ObjectRef,
+        name: &str,
+        other: &ObjectRef,
+    ) -> PyResult<Option<ObjectRef>> {
+        let method = match self.python_method(object, name) {
+            Some(method) => method,
+            None => return Ok(None),
+        };
+        let result = self.call(&method, Args::new(vec![other.clone()]))?;
+        if Vm::is(&result, &self.not_implemented()) {
+            return Ok(None);
+        }
+        Ok(Some(result))
+    }
+
+    /// An operator on two numbers, or `None` if it does not apply to them.
+    fn numeric_op(&mut self, a: Number, op: Operator, b: Number) -> PyResult<Option<ObjectRef>> {
+        match (a, b) {
+            (Number::Int(a), Number::Int(b)) => self.int_op(a, op, b),
+            (Number::Complex(..), _) | (_, Number::Complex(..)) => {
+                self.complex_op(a.to_complex(), op, b.to_complex())
+            }
+            _ => self.float_op(a.to_float(), op, b.to_float()),
+        }
+    }
+
+    fn int_op(&mut self, a: i64, op: Operator, b: i64) -> PyResult<Option<ObjectRef>> {
+        let value = match op {
+            Operator::Add => a.checked_add(b),
+            Operator::Sub => a.checked_sub(b),
+            Operator::Mult => a.checked_mul(b),
+            Operator::Div => {
+                if b == 0 {
+                    return Err(self.new_zero_division_error("division by zero".to_string()));
+                }
+                return Ok(Some(self.new_float(a as f64 / b as f64)));
+            }
+            Operator::FloorDiv | Operator::Mod => {
+                if b == 0 {
+                    let message = "integer division or modulo by zero".to_string();
+                    return Err(self.new_zero_division_error(message));
+                }
+                let (quotient, remainder) = match (a.checked_div(b), a.checked_rem(b)) {
+                    (Some(quotient), Some(remainder)) => (quotient, remainder),
+                    _ => return Err(self.int_overflow()),
+                };
+                let (quotient, remainder) = if remainder != 0 && (remainder < 0) != (b < 0) {
+                    (quotient - 1, remainder + b)
+                } else {
+                    (quotient, remainder)
+                };
+                Some(if op == Operator::Mod {
+                    remainder
+                } else {
+                    quotient
+                })
+            }
+            Operator::Pow if b < 0 => return self.float_op(a as f64, op, b as f64),
+            Operator::Pow => u32::try_from(b).ok().and_then(|b| a.checked_pow(b)),
+            Operator::LShift | Operator::RShift if b < 0 => {
+                return Err(self.new_value_error("negative shift count".to_string()))
+            }
+            Operator::LShift => {
+                if a == 0 {
+                    Some(0)
+                } else if b >= 63 || (a << b) >> b != a {
+                    None
+                } else {
+                    Some(a << b)
+                }
+            }
+            Operator::RShift => Some(a >> b.min(63)),
+            Operator::BitAnd => Some(a & b),
+            Operator::BitOr => Some(a | b),
+            Operator::BitXor => Some(a ^ b),
+            Operator::MatMult => return Ok(None),
+        };
+        match value {
+            Some(value) => Ok(Some(self.new_int(value))),
+            None => Err(self.int_overflow()),
+        }
+    }
+
+    fn float_op(&mut self, a: f64, op: Operator, b: f64) -> PyResult<Option<ObjectRef>> {
+        let value = match op {
+            Operator::Add => a + b,
+            Operator::Sub => a - b,
+            Operator::Mult => a * b,
+            Operator::Div => {
+                if b == 0.0 {
+                    let message = "float division by zero".to_string();
+                    return Err(self.new_zero_division_error(message));
+                }
+                a / b
+            }
+            Operator::FloorDiv | Operator::Mod => match float_divmod(a, b) {
+                Some((quotient, remainder)) => {
+                    if op == Operator::Mod {
+                        remainder
+                    } else {
+                        quotient
+                    }
+                }
+                None => {
+                    let message = if op == Operator::Mod {
+                        "float modulo"
+                    } else {
+                        "float floor division by zero"
+                    };
+                    return Err(self.new_zero_division_error(message.to_string()));
+                }
+            },
+            Operator::Pow => {
+                if a == 0.0 && b < 0.0 {
+                    let message = "0.0 cannot be raised to a negative power".to_string();
+                    return Err(self.new_zero_division_error(message));
+                }
+                // A negative number to a fractional power is complex.
+                if a < 0.0 && b.is_finite() && b.fract() != 0.0 {
+                    return self.complex_op((a, 0.0), op, (b, 0.0));
+                }
+                let value = a.powf(b);
+                if value.is_infinite() && a.is_finite() && b.is_finite() {
+                    return Err(self.range_error());
+                }
+                value
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(self.new_float(value)))
+    }
+
+    /// The `OverflowError` for a float result too large to represent.
+    fn range_error(&mut self) -> ObjectRef {
+        let class = self.exceptions.overflow_error.clone();
+        let args = vec![
+            self.new_int(34),
+            self.new_str("Numerical result out of range"),
+        ];
+        self.new_exception(&class, args)
+    }
+
+    fn complex_op(
+        &mut self,
+        a: (f64, f64),
+        op: Operator,
+        b: (f64, f64),
+    ) -> PyResult<Option<ObjectRef>> {
+        let (real, imag) = match op {
+            Operator::Add => (a.0 + b.0, a.1 + b.1),
+            Operator::Sub => (a.0 - b.0, a.1 - b.1),
+            Operator::Mult => complex_mul(a, b),
+            Operator::Div => match complex_div(a, b) {
+                Some(value) => value,
+                None => {
+                    let message = "complex division by zero".to_string();
+                    return Err(self.new_zero_division_error(message));
+                }
+            },
+            Operator::Pow => {
+                if b == (0.0, 0.0) {
+                    (1.0, 0.0)
+                } else if a == (0.0, 0.0) {
+                    if b.1 != 0.0 || b.0 < 0.0 {
+                        let message = "0.0 to a negative or complex power".to_string();
+                        return Err(self.new_zero_division_error(message));
+                    }
+                    (0.0, 0.0)
+                } else if b.1 == 0.0 && b.0 == b.0.trunc() && b.0.abs() <= 100.0 {
+                    complex_powi(a, b.0 as i64)
+                } else {
+                    let length = a.0.hypot(a.1);
+                    let angle = a.1.atan2(a.0);
+                    let mut magnitude = length.powf(b.0);
+                    let mut phase = angle * b.0;
+                    if b.1 != 0.0 {
+                        magnitude /= (angle * b.1).exp();
+                        phase += b.1 * length.ln();
+                    }
+                    (magnitude * phase.cos(), magnitude * phase.sin())
+                }
+            }
+            _ => return Ok(None),
+        };
+        if (real.is_infinite() || imag.is_infinite())
+            && op == Operator::Pow
+            && [a.0, a.1, b.0, b.1].iter().all(|part| part.is_finite())
+        {
+            let message = "complex exponentiation".to_string();
+            return Err(self.new_overflow_error(message));
+        }
+        Ok(Some(self.new_complex(real, imag)))
+    }
+
+    /// The operators of strings, bytes, tuples, lists, sets and dicts, or
+    /// `None` if none applies.
+    fn sequence_op(
+        &mut self,
+        a: &ObjectRef,
+        op: Operator,
+        b: &ObjectRef,
+        inplace: bool,
+    ) -> PyResult<Option<ObjectRef>> {
+        if op == Operator::Mult {
+            if let Payload::Int(count) = b.payload {
+                return self.repeat(a, count, inplace);
+            }
+            if let Payload::Int(count) = a.payload {
+                return self.repeat(b, count, false);
+            }
+            if matches!(
+                a.payload,
+                Payload::Str(_)
+                    | Payload::Bytes(_)
+                    | Payload::ByteArray(_)
+                    | Payload::Tuple(_)
+                    | Payload::List(_)
+            ) {
+                let message = format!(
+                    "can't multiply sequence by non-int of type '{}'",
+                    b.type_name()
+                );
+                return Err(self.new_type_error(message));
+            }
+        }
+        let set_operator = matches!(
+            op,
+            Operator::BitAnd | Operator::BitOr | Operator::Sub | Operator::BitXor
+        );
+        if set_operator && self.is_view(a, b) {
+            return self.view_op(a, op, b);
+        }
+        let result = match (&a.payload, op, &b.payload) {
+            (Payload::Str(_), Operator::Mod, _) | (Payload::Bytes(_), Operator::Mod, _) => {
+                self.percent_format(a, b)?
+            }
+            (Payload::Str(x), Operator::Add, Payload::Str(y)) => {
+                self.new_str(&format!("{}{}", x, y))
+            }
+            (Payload::Str(_), Operator::Add, _) => {
+                let message = format!(
+                    "can only concatenate str (not \"{}\") to str",
+                    b.type_name()
+                );
+                return Err(self.new_type_error(message));
+            }
+            (Payload::Bytes(x), Operator::Add, _) if bytes_like(b).is_some() => {
+                let y = bytes_like(b).unwrap();
+                self.new_bytes([&x[..], &y[..]].concat())
+            }
+            (Payload::ByteArray(x), Operator::Add, _) if bytes_like(b).is_some() => {
+                let y = bytes_like(b).unwrap().into_owned();
+                if inplace {
+                    x.borrow_mut().extend_from_slice(&y);
+                    a.clone()
+                } else {
+                    let joined = [&x.borrow()[..], &y[..]].concat();
+                    self.new_bytearray(joined)
+                }
+            }
+            (Payload::Bytes(_), Operator::Add, _) | (Payload::ByteArray(_), Operator::Add, _) => {
+                let message = format!("can't concat {} to {}", b.type_name(), a.type_name());
+                return Err(self.new_type_error(message));
+            }
+            (Payload::Tuple(x), Operator::Add, Payload::Tuple(y)) => {
+                self.new_tuple([&x[..], &y[..]].concat())
+            }
+            (Payload::Tuple(_), Operator::Add, _) => {
+                let message = format!(
+                    "can only concatenate tuple (not \"{}\") to tuple",
+                    b.type_name()
+                );
+                return Err(self.new_type_error(message));
+            }
+            (Payload::List(x), Operator::Add, _) if inplace => {
+                let items = self.iterate(b)?;
+                x.borrow_mut().extend(items);
+                a.clone()
+            }
+            (Payload::List(x), Operator::Add, Payload::List(y)) => {
+                let items = [&x.borrow()[..], &y.borrow()[..]].concat();
+                self.new_list(items)
+            }
+            (Payload::List(_), Operator::Add, _) => {
+                let message = format!(
+                    "can only concatenate list (not \"{}\") to list",
+                    b.type_name()
+                );
+                return Err(self.new_type_error(message));
+            }
+            (Payload::Set(x), _, Payload::Set(y)) => {
+                let members = match op {
+                    Operator::BitOr => {
+                        let mut members = self.members(x);
+                        members.extend(self.members(y));
+                        members
+                    }
+                    Operator::BitAnd | Operator::Sub => {
+                        let mut members = Vec::new();
+                        for member in self.members(x) {
+                            if self.dict_contains(y, &member)? == (op == Operator::BitAnd) {
+                                members.push(member);
+                            }
+                        }
+                        members
+                    }
+                    Operator::BitXor => {
+                        let mut members = Vec::new();
+                        for member in self.members(x) {
+                            if !self.dict_contains(y, &member)? {
+                                members.push(member);
+                            }
+                        }
+                        for member in self.members(y) {
+                            if !self.dict_contains(x, &member)? {
+                                members.push(member);
+                            }
+                        }
+                        members
+                    }
+                    _ => return Ok(None),
+                };
+                if inplace && Vm::is(&a.class(), &self.types.set) {
+                    x.borrow_mut().clear();
+                    for member in members {
+                        self.set_add(x, member)?;
+                    }
+                    return Ok(Some(a.clone()));
+                }
+                let frozen = Vm::is(&a.class(), &self.types.frozenset);
+                let set = self.new_set(frozen);
+                for member in members {
+                    self.set_add(set_members(&set), member)?;
+                }
+                set
+            }
+            (Payload::Dict(x), Operator::BitOr, Payload::Dict(y)) => {
+                let target = if inplace { a.clone() } else { self.new_dict() };
+                if !inplace {
+                    self.dict_extend(target.as_dict().unwrap(), x)?;
+                }
+                self.dict_extend(target.as_dict().unwrap(), y)?;
+                target
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+
+    /// A set operator with a view of the keys or items of a dict, which
+    /// gives a set of the items of `a` combined with those of any iterable
+    /// `b`, either way round.
+    fn view_op(
+        &mut self,
+        a: &ObjectRef,
+        op: Operator,
+        b: &ObjectRef,
+    ) -> PyResult<Option<ObjectRef>> {
+        let values = |object: &ObjectRef| {
+            matches!(
+                object.payload,
+                Payload::DictView {
+                    kind: ViewKind::Values,
+                    ..
+                }
+            )
+        };
+        if values(a) || values(b) || !self.is_iterable(a) || !self.is_iterable(b) {
+            return Ok(None);
+        }
+        let result = self.new_set(false);
+        let members = set_members(&result);
+        let (items, others) = (self.iterate(a)?, self.iterate(b)?);
+        let other = self.new_set(false);
+        for item in others.iter().cloned() {
+            self.set_add(set_members(&other), item)?;
+        }
+        for item in items.iter().cloned() {
+            let in_other = self.dict_contains(set_members(&other), &item)?;
+            let keep = match op {
+                Operator::BitAnd => in_other,
+                Operator::Sub | Operator::BitXor => !in_other,
+                _ => true,
+            };
+            if keep {
+                self.set_add(members, item)?;
+            }
+        }
+        if op == Operator::BitOr || op == Operator::BitXor {
+            let own = self.new_set(false);
+            for item in items {
+                self.set_add(set_members(&own), item)?;
+            }
+            for item in others {
+                if op == Operator::BitOr || !self.dict_contains(set_members(&own), &item)? {
+                    self.set_add(members, item)?;
+                }
+            }
+        }
+        Ok(Some(result))
+    }
+
+    /// `sequence * count`.
+    fn repeat(
+        &mut self,
+        sequence: &ObjectRef,
+        count: i64,
+        inplace: bool,
+    ) -> PyResult<Option<ObjectRef>> {
+        let count = count.max(0) as usize;
+        let too_long = |length: usize| length.checked_mul(count).is_none();
+        let result = match sequence.payload {
+            Payload::Str(ref value) if !too_long(value.len()) => self.new_str(&value.repeat(count)),
+            Payload::Bytes(ref value) if !too_long(value.len()) => {
+                self.new_bytes(value.repeat(count))
+            }
+            Payload::ByteArray(ref value) if !too_long(value.borrow().len()) => {
+                let repeated = value.borrow().repeat(count);
+                if inplace {
+                    *value.borrow_mut() = repeated;
+                    sequence.clone()
+                } else {
+                    self.new_bytearray(repeated)
+                }
+            }
+            Payload::Tuple(ref items) if !too_long(items.len()) => {
+                self.new_tuple(repeat_items(items, count))
+            }
+            Payload::List(ref items) if !too_long(items.borrow().len()) => {
+                let repeated = repeat_items(&items.borrow(), count);
+                if inplace {
+                    *items.borrow_mut() = repeated;
+                    sequence.clone()
+                } else {
+                    self.new_list(repeated)
+                }
+            }
+            Payload::Str(_)
+            | Payload::Bytes(_)
+            | Payload::ByteArray(_)
+            | Payload::Tuple(_)
+            | Payload::List(_) => {
+                let class = self.exceptions.overflow_error.clone();
+                let message = "repeated sequence is too long".to_string();
+                return Err(self.new_error(&class, message));
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(result))
+    }
+
+    fn members(&self, set: &::std::cell::RefCell<super::Dict>) -> Vec<ObjectRef> {
+        set.borrow().iter().map(|entry| entry.key.clone()).collect()
+    }
+
+    pub fn set_add(
+        &mut self,
+        set: &::std::cell::RefCell<super::Dict>,
+        member: ObjectRef,
+    ) -> PyResult<()> {
+        let none = self.none();
+        self.dict_set(set, member, none)
+    }
+
+    /// Copies the entries of `source` into `target`.
+    pub fn dict_extend(
+        &mut self,
+        target: &::std::cell::RefCell<super::Dict>,
+        source: &::std::cell::RefCell<super::Dict>,
+    ) -> PyResult<()> {
+        let entries: Vec<(ObjectRef, ObjectRef)> = source
+            .borrow()
+            .iter()
+            .map(|entry| (entry.key.clone(), entry.value.clone()))
+            .collect();
+        for (key, value) in entries {
+            self.dict_set(target, key, value)?;
+        }
+        Ok(())
+    }
+
+    /// The class attribute `name` of `class`, searching its MRO.
+    pub fn lookup(&self, class: &ObjectRef, name: &str) -> Option<ObjectRef> {
+        let data = class.as_type()?;
+        ::std::iter::once(class)
+            .chain(data.mro.iter())
+            .find_map(|class| class.dict()?.as_dict()?.borrow().get_str(name))
+    }
+
+    /// The attribute `name` of an object's class, bound to the object if it
+    /// is a function. Special methods such as `__enter__` are looked up
+    /// this way, skipping the object's own dictionary.
+    pub fn special_method(&mut self, object: &ObjectRef, name: &str) -> Option<ObjectRef> {
+        let value = self.lookup(&object.class(), name)?;
+        Some(match value.payload {
+            Payload::Function(_) | Payload::Builtin(_) => self.new_method(value, object.clone()),
+            _ => value,
+        })
+    }
+
+    pub fn getattr(&mut self, object: &ObjectRef, name: &str) -> PyResult {
+        if name == "__class__" {
+            return Ok(object.class());
+        }
+        if let Some(value) = self.builtin_attr(object, name) {
+            return Ok(value);
+        }
+        match object.payload {
+            Payload::Type(_) => {
+                let metatype = object.class();
+                let meta_attribute = self.lookup(&metatype, name);
+                if let Some(ref attribute) = meta_attribute {
+                    if self.is_data_descriptor(attribute) {
+                        return self.bind(name, attribute.clone(), Some(object), &metatype);
+                    }
+                }
+                if let Some(value) = self.lookup(object, name) {
+                    return self.bind(name, value, None, object);
+                }
+                if let Some(attribute) = meta_attribute {
+                    return self.bind(name, attribute, Some(object), &metatype);
+                }
+            }
+            Payload::Super {
+                ref class,
+                object: ref instance,
+            } => {
+                if let Some(value) = self.super_getattr(class, instance, name)? {
+                    return Ok(value);
+                }
+            }
+            Payload::Module => {
+                if let Some(value) = object
+                    .dict()
+                    .unwrap()
+                    .as_dict()
+                    .unwrap()
+                    .borrow()
+                    .get_str(name)
+                {
+                    return Ok(value);
+                }
+                let message = format!(
+                    "module {} has no attribute {}",
+                    repr_str(&self.module_name(object)),
+                    repr_str(name)
+                );
+                return Err(self.new_attribute_error(message));
+            }
+            Payload::Cell(ref value) if name == "cell_contents" => {
+                return match *value.borrow() {
+                    Some(ref value) => Ok(value.clone()),
+                    None => Err(self.new_value_error("Cell is empty".to_string())),
+                };
+            }
+            _ => {
+                let class = object.class();
+                let attribute = self.lookup(&class, name);
+                let data_descriptor = attribute
+                    .as_ref()
+                    .is_some_and(|attribute| self.is_data_descriptor(attribute));
+                return self.instance_getattr(object, &class, name, attribute, data_descriptor);
+            }
+        }
+        Err(self.no_attribute(object, name))
+    }
+
+    /// The attribute `name` of an instance of `class`, given what looking
+    /// it up in the class found, and whether that is a data descriptor.
+    pub(super) fn instance_getattr(
+        &mut self,
+        object: &ObjectRef,
+        class: &ObjectRef,
+        name: &str,
+        attribute: Option<ObjectRef>,
+        data_descriptor: bool,
+    ) -> PyResult {
+        if let Some(ref attribute) = attribute {
+            if data_descriptor {
+                return self.bind(name, attribute.clone(), Some(object), class);
+            }
+        }
+        if let Some(dict) = object.dict() {
+            if name == "__dict__" {
+                return Ok(dict.clone());
+            }
+            if let Some(value) = dict.as_dict().unwrap().borrow().get_str(name) {
+                return Ok(value);
+            }
+        }
+        if let Some(attribute) = attribute {
+            return self.bind(name, attribute, Some(object), class);
+        }
+        if let Some(getattr) = self.special_method(object, "__getattr__") {
+            let name = self.new_str(name);
+            return self.call(&getattr, Args::new(vec![name]));
+        }
+        Err(self.no_attribute(object, name))
+    }
+
+    fn no_attribute(&mut self, object: &ObjectRef, name: &str) -> ObjectRef {
+        let message = match object.payload {
+            Payload::Type(ref data) => {
+                format!("type object '{}' has no attribute '{}'", data.name, name)
+            }
+            _ => format!(
+                "'{}' object has no attribute '{}'",
+                object.type_name(),
+                name
+            ),
+        };
+        self.new_attribute_error(message)
+    }
+
+    /// The attributes builtin types compute rather than store.
+    fn builtin_attr(&mut self, object: &ObjectRef, name: &str) -> Option<ObjectRef> {
+        match object.payload {
+            Payload::Exception(_) => self.exception_attr(object, name),
+            Payload::Function(ref function) => Some(match name {
+                "__name__" => self.new_str(&function.name.borrow()),
+                "__qualname__" => self.new_str(&function.qualname.borrow()),
+                "__defaults__" => function
+                    .defaults
+                    .borrow()
+                    .clone()
+                    .unwrap_or_else(|| self.none()),
+                "__kwdefaults__" => function
+                    .kwdefaults
+                    .borrow()
+                    .clone()
+                    .unwrap_or_else(|| self.none()),
+                "__annotations__" => function
+                    .annotations
+                    .borrow_mut()
+                    .get_or_insert_with(|| self.new_dict())
+                    .clone(),
+                "__globals__" => function.globals.clone(),
+                "__closure__" => function.closure.clone().unwrap_or_else(|| self.none()),
+                "__code__" => PyObject::new(
+                    Payload::Code(function.code.clone()),
+                    self.types.code.clone(),
+                    None,
+                ),
+                "__doc__" => function.doc.borrow().clone(),
+                "__module__" => function.module.borrow().clone(),
+                _ => return None,
+            }),
+            Payload::StaticMethod(ref function) | Payload::ClassMethod(ref function) => {
+                match name {
+                    "__func__" | "__wrapped__" => Some(function.clone()),
+                    _ => None,
+                }
+            }
+            Payload::Property(ref property) => Some(match name {
+                "fget" | "fset" | "fdel" => {
+                    let function = match name {
+                        "fget" => &property.fget,
+                        "fset" => &property.fset,
+                        _ => &property.fdel,
+                    };
+                    function.clone().unwrap_or_else(|| self.none())
+                }
+                "__doc__" => property.doc.borrow().clone(),
+                _ => return None,
+            }),
+            Payload::Generator(ref generator) => {
+                // The attributes of a coroutine start `cr_` where those of
+                // a generator start `gi_`.
+                let prefix = if generator.is_coroutine() {
+                    "cr_"
+                } else {
+                    "gi_"
+                };
+                Some(match (name, name.strip_prefix(prefix)) {
+                    ("__name__", _) => self.new_str(&generator.name.borrow()),
+                    ("__qualname__", _) => self.new_str(&generator.qualname.borrow()),
+                    (_, Some("running")) => self.new_bool(generator.running.get()),
+                    (_, Some("code")) => PyObject::new(
+                        Payload::Code(generator.code.clone()),
+                        self.types.code.clone(),
+                        None,
+                    ),
+                    _ => return None,
+                })
+            }
+            Payload::Builtin(ref builtin) => match name {
+                "__name__" | "__qualname__" => Some(self.new_str(builtin.name)),
+                _ => None,
+            },
+            Payload::Method {
+                ref function,
+                ref instance,
+            } => match name {
+                "__func__" => Some(function.clone()),
+                "__self__" => Some(instance.clone()),
+                _ => None,
+            },
+            Payload::Type(ref data) => Some(match name {
+                "__name__" => self.new_str(&data.name),
+                "__qualname__" => self.new_str(&data.qualname),
+                "__module__" if !data.heap => self.new_str("builtins"),
+                "__dict__" => object.dict().unwrap().clone(),
+                "__mro__" => {
+                    let mut mro = vec![object.clone()];
+                    mro.extend(data.mro.iter().cloned());
+                    self.new_tuple(mro)
+                }
+                "__bases__" => self.new_tuple(data.bases.clone()),
+                "__base__" => data.bases.first().cloned().unwrap_or_else(|| self.none()),
+                _ => return None,
+            }),
+            Payload::Traceback(ref traceback) => Some(match name {
+                "tb_next" => traceback.next.clone().unwrap_or_else(|| self.none()),
+                "tb_lineno" => self.new_int(traceback.line as i64),
+                "tb_lasti" => self.new_int(traceback.lasti as i64),
+                _ => return None,
+            }),
+            Payload::Code(ref code) => Some(match name {
+                "co_name" => self.new_str(&code.name),
+                "co_qualname" => self.new_str(&code.qualname),
+                "co_filename" => self.new_str(&code.filename),
+                "co_firstlineno" => self.new_int(code.first_line as i64),
+                "co_flags" => self.new_int(code.flags as i64),
+                "co_varnames" | "co_cellvars" | "co_freevars" => {
+                    let names = match name {
+                        "co_varnames" => &code.varnames,
+                        "co_cellvars" => &code.cellvars,
+                        _ => &code.freevars,
+                    };
+                    let names = names.iter().map(|name| self.new_str(name)).collect();
+                    self.new_tuple(names)
+                }
+                _ => return None,
+            }),
+            Payload::Super {
+                ref class,
+                object: ref instance,
+            } => match name {
+                "__thisclass__" => Some(class.clone()),
+                "__self__" => Some(instance.clone()),
+                "__self_class__" => Some(match instance.as_type() {
+                    Some(_) if Vm::is_subclass(instance, class) => instance.clone(),
+                    _ => instance.class(),
+                }),
+                _ => None,
+            },
+            Payload::Complex { real, imag } => match name {
+                "real" => Some(self.new_float(real)),
+                "imag" => Some(self.new_float(imag)),
+                _ => None,
+            },
+            Payload::Slice {
+                ref start,
+                ref stop,
+                ref step,
+            } => match name {
+                "start" => Some(start.clone()),
+                "stop" => Some(stop.clone()),
+                "step" => Some(step.clone()),
+                _ => None,
+            },
+            Payload::MemoryView {
+                object: ref viewed, ..
+            } => Some(match name {
+                "obj" => viewed.clone(),
+                "readonly" => self.new_bool(!matches!(viewed.payload, Payload::ByteArray(_))),
+                "nbytes" => {
+                    let length = bytes_like(object).map_or(0, |data| data.len());
+                    self.new_int(length as i64)
+                }
+                "format" => self.new_str("B"),
+                "itemsize" | "ndim" => self.new_int(1),
+                _ => return None,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn setattr(&mut self, object: &ObjectRef, name: &str, value: ObjectRef) -> PyResult<()> {
+        if object.as_exception().is_some() && self.set_exception_attr(object, name, &value)? {
+            return Ok(());
+        }
+        match object.payload {
+            Payload::Function(ref function) if name == "__name__" || name == "__qualname__" => {
+                let text = match value.as_str() {
+                    Some(text) => text.to_string(),
+                    None => {
+                        let message = format!("{} must be set to a string object", name);
+                        return Err(self.new_type_error(message));
+                    }
+                };
+                if name == "__name__" {
+                    *function.name.borrow_mut() = text;
+                } else {
+                    *function.qualname.borrow_mut() = text;
+                }
+                return Ok(());
+            }
+            Payload::Function(ref function) if name == "__doc__" => {
+                *function.doc.borrow_mut() = value;
+                return Ok(());
+            }
+            Payload::Function(ref function) if name == "__module__" => {
+                *function.module.borrow_mut() = value;
+                return Ok(());
+            }
+            Payload::Property(ref property) if name == "__doc__" => {
+                *property.doc.borrow_mut() = value;
+                return Ok(());
+            }
+            Payload::Function(ref function)
+                if name == "__defaults__"
+                    || name == "__kwdefaults__"
+                    || name == "__annotations__" =>
+            {
+                let value = if Vm::is(&value, &self.none()) {
+                    None
+                } else {
+                    Some(value)
+                };
+                return self.set_function_slot(function, name, value);
+            }
+            Payload::Type(ref data) if !data.heap => {
+                let message = format!(
+                    "cannot set '{}' attribute of immutable type '{}'",
+                    name, data.name
+                );
+                return Err(self.new_type_error(message));
+            }
+            Payload::Type(_) => {
+                if name == "__del__" {
+                    gc::define_finalizer();
+                }
+            }
+            _ => {
+                if let Some((set, descriptor)) =
+                    self.descriptor_method(&object.class(), name, "__set__")
+                {
+                    self.call(&set, Args::new(vec![descriptor, object.clone(), value]))?;
+                    return Ok(());
+                }
+            }
+        }
+        match object.dict() {
+            Some(dict) => {
+                let dict = dict.clone();
+                self.dict_set_str(dict.as_dict().unwrap(), name, value);
+                Ok(())
+            }
+            None => Err(self.no_attribute(object, name)),
+        }
+    }
+
+    pub fn delattr(&mut self, object: &ObjectRef, name: &str) -> PyResult<()> {
+        match object.payload {
+            Payload::Function(ref function)
+                if name == "__defaults__"
+                    || name == "__kwdefaults__"
+                    || name == "__annotations__" =>
+            {
+                return self.set_function_slot(function, name, None);
+            }
+            Payload::Function(ref function) if name == "__doc__" || name == "__module__" => {
+                let none = self.none();
+                match name {
+                    "__doc__" => *function.doc.borrow_mut() = none,
+                    _ => *function.module.borrow_mut() = none,
+                }
+                return Ok(());
+            }
+            Payload::Type(ref data) if !data.heap => {
+                let message = format!(
+                    "cannot delete '{}' attribute of immutable type '{}'",
+                    name, data.name
+                );
+                return Err(self.new_type_error(message));
+            }
+            Payload::Type(_) => {}
+            _ => {
+                if let Some((delete, descriptor)) =
+                    self.descriptor_method(&object.class(), name, "__delete__")
+                {
+                    self.call(&delete, Args::new(vec![descriptor, object.clone()]))?;
+                    return Ok(());
+                }
+            }
+        }
+        if let Some(dict) = object.dict() {
+            let key = self.new_str(name);
+            if self.dict_remove(dict.as_dict().unwrap(), &key)?.is_some() {
+                return Ok(());
+            }
+        }
+        Err(self.no_attribute(object, name))
+    }
+
+    /// Sets `__defaults__`, `__kwdefaults__` or `__annotations__` of a
+    /// function, which must be a tuple or a dict, or `None` to have none.
+    fn set_function_slot(
+        &mut self,
+        function: &Function,
+        name: &str,
+        value: Option<ObjectRef>,
+    ) -> PyResult<()> {
+        let (slot, expected) = match name {
+            "__defaults__" => (&function.defaults, "tuple"),
+            "__kwdefaults__" => (&function.kwdefaults, "dict"),
+            _ => (&function.annotations, "dict"),
+        };
+        let valid = value.as_ref().is_none_or(|value| match value.payload {
+            Payload::Tuple(_) => expected == "tuple",
+            Payload::Dict(_) => expected == "dict",
+            _ => false,
+        });
+        if !valid {
+            let message = format!("{} must be set to a {} object", name, expected);
+            return Err(self.new_type_error(message));
+        }
+        *slot.borrow_mut() = value;
+        Ok(())
+    }
+
+    /// An int used as an index, or `None` for other types.
+    fn index(object: &ObjectRef) -> Option<i64> {
+        match object.payload {
+            Payload::Int(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// The position `index` refers to in a sequence of `length` items,
+    /// counting negative indices from the end.
+    fn position(index: i64, length: usize) -> Option<usize> {
+        let index = if index < 0 {
+            index.checked_add(length as i64)?
+        } else {
+            index
+        };
+        if index >= 0 && (index as usize) < length {
+            Some(index as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The start, step and count of a slice object over `length` items.
+    fn slice(&mut self, slice: &ObjectRef, length: usize) -> PyResult<(i64, i64, usize)> {
+        let (start, stop, step) = self.slice_arguments(slice)?;
+        Ok(adjust_slice(length as i64, start, stop, step))
+    }
+
+    /// `slice.indices(length)`: the start, stop and step of a slice object
+    /// over `length` items.
+    pub(super) fn slice_bounds(
+        &mut self,
+        slice: &ObjectRef,
+        length: i64,
+    ) -> PyResult<(i64, i64, i64)> {
+        let (start, stop, step) = self.slice_arguments(slice)?;
+        let (start, stop) = clamp_slice(length, start, stop, step);
+        Ok((start, stop, step))
+    }
+
+    /// The bounds and step of a slice object, checked to be indices.
+    fn slice_arguments(&mut self, slice: &ObjectRef) -> PyResult<(Option<i64>, Option<i64>, i64)> {
+        let (start, stop, step) = match slice.payload {
+            Payload::Slice {
+                ref start,
+                ref stop,
+                ref step,
+            } => (start.clone(), stop.clone(), step.clone()),
+            _ => unreachable!(),
+        };
+        let bound = |vm: &mut Vm, value: &ObjectRef| -> PyResult<Option<i64>> {
+            if Vm::is(value, &vm.none) {
+                return Ok(None);
+            }
+            match Vm::index(value) {
+                Some(value) => Ok(Some(value)),
+                None => {
+                    let message =
+                        "slice indices must be integers or None or have an __index__ method";
+                    Err(vm.new_type_error(message.to_string()))
+                }
+            }
+        };
+        let step = bound(self, &step)?.unwrap_or(1);
+        if step == 0 {
+            return Err(self.new_value_error("slice step cannot be zero".to_string()));
+        }
+        // Keeps the step negatable.
+        let step = step.max(-i64::MAX);
+        let start = bound(self, &start)?;
+        let stop = bound(self, &stop)?;
+        Ok((start, stop, step))
+    }
+
+    /// `object[key]`.
+    pub fn getitem(&mut self, object: &ObjectRef, key: &ObjectRef) -> PyResult {
+        if let Some(method) = self.python_method(object, "__getitem__") {
+            return self.call(&method, Args::new(vec![key.clone()]));
+        }
+        self.builtin_getitem(object, key)
+    }
+
+    /// `object[key]` as the builtin type `object` is an instance of does
+    /// it, whatever its class defines: `list.__getitem__` and
+    /// `dict.__getitem__`. A dict subclass's `__missing__` gives the value
+    /// of a key not in it.
+    pub(super) fn builtin_getitem(&mut self, object: &ObjectRef, key: &ObjectRef) -> PyResult {
+        let is_slice = matches!(key.payload, Payload::Slice { .. });
+        match object.payload {
+            Payload::Dict(ref dict) => match self.dict_get(dict, key)? {
+                Some(value) => Ok(value),
+                None => match self.python_method(object, "__missing__") {
+                    Some(missing) => self.call(&missing, Args::new(vec![key.clone()])),
+                    None => Err(self.new_key_error(key.clone())),
+                },
+            },
+            Payload::List(ref items) if is_slice => {
+                let items = items.borrow().clone();
+                let (start, step, count) = self.slice(key, items.len())?;
+                let selected = slice_positions(start, step, count)
+                    .into_iter()
+                    .map(|i| items[i].clone())
+                    .collect();
+                Ok(self.new_list(selected))
+            }
+            Payload::Tuple(ref items) if is_slice => {
+                let (start, step, count) = self.slice(key, items.len())?;
+                if step == 1 && count == items.len() {
+                    return Ok(object.clone());
+                }
+                let selected = slice_positions(start, step, count)
+                    .into_iter()
+                    .map(|i| items[i].clone())
+                    .collect();
+                Ok(self.new_tuple(selected))
+            }
+            Payload::Str(ref value) if is_slice => {
+                let chars: Vec<char> = value.chars().collect();
+                let (start, step, count) = self.slice(key, chars.len())?;
+                let selected: String = slice_positions(start, step, count)
+                    .into_iter()
+                    .map(|i| chars[i])
+                    .collect();
+                Ok(self.new_str(&selected))
+            }
+            Payload::ByteArray(_) | Payload::MemoryView { .. } => self.buffer_getitem(object, key),
+            Payload::Bytes(ref value) if is_slice => {
+                let (start, step, count) = self.slice(key, value.len())?;
+                let selected = slice_positions(start, step, count)
+                    .into_iter()
+                    .map(|i| value[i])
+                    .collect();
+                Ok(self.new_bytes(selected))
+            }
+            Payload::Range { start, stop, step } => {
+                let length = range_len(start, stop, step);
+                if is_slice {
+                    let (first, slice_step, count) = self.slice(key, length)?;
+                    let new_start = i128::from(start) + i128::from(first) * i128::from(step);
+                    let new_step = i128::from(step) * i128::from(slice_step);
+                    let new_stop = new_start + count as i128 * new_step;
+                    return match (
+                        i64::try_from(new_start),
+                        i64::try_from(new_stop),
+                        i64::try_from(new_step),
+                    ) {
+                        (Ok(start), Ok(stop), Ok(step)) => Ok(self.new_range(start, stop, step)),
+                        _ => Err(self.int_overflow()),
+                    };
+                }
+                let index = match Vm::index(key) {
+                    Some(index) => index,
+                    None => {
+                        let message = format!(
+                            "range indices must be integers or slices, not {}",
+                            key.type_name()
+                        );
+                        return Err(self.new_type_error(message));
+                    }
+                };
+                match Vm::position(index, length) {
+                    Some(position) => Ok(self.new_int(start + position as i64 * step)),
+                    None => {
+                        Err(self.new_index_error("range object index out of range".to_string()))
+                    }
+                }
+            }
+            Payload::List(_) | Payload::Tuple(_) | Payload::Str(_) | Payload::Bytes(_) => {
+                let (kind, integers) = match object.payload {
+                    Payload::List(_) => ("list", "list indices must be integers or slices"),
+                    Payload::Tuple(_) => ("tuple", "tuple indices must be integers or slices"),
+                    Payload::Str(_) => ("string", "string indices must be integers"),
+                    _ => ("index", "byte indices must be integers or slices"),
+                };
+                let index = match Vm::index(key) {
+                    Some(index) => index,
+                    None => {
+                        let message = format!("{}, not {}", integers, key.type_name());
+                        return Err(self.new_type_error(message));
+                    }
+                };
+                let item = match object.payload {
+                    Payload::List(ref items) => {
+                        let items = items.borrow();
+                        Vm::position(index, items.len()).map(|i| items[i].clone())
+                    }
+                    Payload::Tuple(ref items) => {
+                        Vm::position(index, items.len()).map(|i| items[i].clone())
+                    }
+                    Payload::Str(ref value) => {
+                        let length = value.chars().count();
+                        Vm::position(index, length)
+                            .map(|i| self.new_str(&value.chars().nth(i).unwrap().to_string()))
+                    }
+                    Payload::Bytes(ref value) => {
+                        Vm::position(index, value.len()).map(|i| self.new_int(value[i] as i64))
+                    }
+                    _ => unreachable!(),
+                };
+                match item {
+                    Some(item) => Ok(item),
+                    None => {
+                        let message = if kind == "index" {
+                            "index out of range".to_string()
+                        } else {
+                            format!("{} index out of range", kind)
+                        };
+                        Err(self.new_index_error(message))
+                    }
+                }
+            }
+            Payload::Type(_) if self.lookup(object, "__class_getitem__").is_some() => {
+                let method = self.lookup(object, "__class_getitem__").unwrap();
+                let method = self.bind("__class_getitem__", method, None, object)?;
+                self.call(&method, Args::new(vec![key.clone()]))
+            }
+            _ => {
+                let message = format!("'{}' object is not subscriptable", object.type_name());
+                Err(self.new_type_error(message))
+            }
+        }
+    }
+
+    /// `object[key] = value`.
+    pub fn setitem(
+        &mut self,
+        object: &ObjectRef,
+        key: &ObjectRef,
+        value: ObjectRef,
+    ) -> PyResult<()> {
+        if let Some(method) = self.python_method(object, "__setitem__") {
+            return self
+                .call(&method, Args::new(vec![key.clone(), value]))
+                .map(|_| ());
+        }
+        self.builtin_setitem(object, key, value)
+    }
+
+    /// `object[key] = value` as the builtin type `object` is an instance of
+    /// does it: `list.__setitem__` and `dict.__setitem__`.
+    pub(super) fn builtin_setitem(
+        &mut self,
+        object: &ObjectRef,
+        key: &ObjectRef,
+        value: ObjectRef,
+    ) -> PyResult<()> {
+        match object.payload {
+            Payload::Dict(ref dict) => self.dict_set(dict, key.clone(), value),
+            Payload::List(ref items) => {
+                if let Payload::Slice { .. } = key.payload {
+                    let values = self.iterate(&value)?;
+                    let length = items.borrow().len();
+                    let (start, step, count) = self.slice(key, length)?;
+                    if step == 1 {
+                        let start = start as usize;
+                        items.borrow_mut().splice(start..start + count, values);
+                        return Ok(());
+                    }
+                    if values.len() != count {
+                        let message = format!(
+                            "attempt to assign sequence of size {} to extended slice of size {}",
+                            values.len(),
+                            count
+                        );
+                        return Err(self.new_value_error(message));
+                    }
+                    let mut items = items.borrow_mut();
+                    for (i, value) in slice_positions(start, step, count).into_iter().zip(values) {
+                        items[i] = value;
+                    }
+                    return Ok(());
+                }
+                let index = match Vm::index(key) {
+                    Some(index) => index,
+                    None => {
+                        let message = format!(
+                            "list indices must be integers or slices, not {}",
+                            key.type_name()
+                        );
+                        return Err(self.new_type_error(message));
+                    }
+                };
+                let length = items.borrow().len();
+                match Vm::position(index, length) {
+                    Some(i) => {
+                        items.borrow_mut()[i] = value;
+                        Ok(())
+                    }
+                    None => {
+                        Err(self.new_index_error("list assignment index out of range".to_string()))
+                    }
+                }
+            }
+            Payload::ByteArray(_) => self.bytearray_setitem(object, key, value),
+            Payload::MemoryView { .. } => self.memoryview_setitem(object, key, value),
+            _ => {
+                let message = format!(
+                    "'{}' object does not support item assignment",
+                    object.type_name()
+                );
+                Err(self.new_type_error(message))
+            }
+        }
+    }
+
+    /// `del object[key]`.
+    pub fn delitem(&mut self, object: &ObjectRef, key: &ObjectRef) -> PyResult<()> {
+        if let Some(method) = self.python_method(object, "__delitem__") {
+            return self.call(&method, Args::new(vec![key.clone()])).map(|_| ());
+        }
+        self.builtin_delitem(object, key)
+    }
+
+    /// `del object[key]` as the builtin type `object` is an instance of does
+    /// it: `list.__delitem__` and `dict.__delitem__`.
+    pub(super) fn builtin_delitem(&mut self, object: &ObjectRef, key: &ObjectRef) -> PyResult<()> {
+        match object.payload {
+            Payload::Dict(ref dict) => match self.dict_remove(dict, key)? {
+                Some(_) => Ok(()),
+                None => Err(self.new_key_error(key.clone())),
+            },
+            Payload::List(ref items) => {
+                if let Payload::Slice { .. } = key.payload {
+                    let length = items.borrow().len();
+                    let (start, step, count) = self.slice(key, length)?;
+                    let mut positions = slice_positions(start, step, count);
+                    positions.sort_unstable();
+                    let mut items = items.borrow_mut();
+                    for i in positions.into_iter().rev() {
+                        items.remove(i);
+                    }
+                    return Ok(());
+                }
+                let index = match Vm::index(key) {
+                    Some(index) => index,
+                    None => {
+                        let message = format!(
+                            "list indices must be integers or slices, not {}",
+                            key.type_name()
+                        );
+                        return Err(self.new_type_error(message));
+                    }
+                };
+                let length = items.borrow().len();
+                match Vm::position(index, length) {
+                    Some(i) => {
+                        items.borrow_mut().remove(i);
+                        Ok(())
+                    }
+                    None => {
+                        Err(self.new_index_error("list assignment index out of range".to_string()))
+                    }
+                }
+            }
+            Payload::ByteArray(ref data) => {
+                let length = data.borrow().len();
+                if let Payload::Slice { .. } = key.payload {
+                    let (start, step, count) = self.slice(key, length)?;
+                    let mut positions = slice_positions(start, step, count);
+                    positions.sort_unstable();
+                    let mut data = data.borrow_mut();
+                    for i in positions.into_iter().rev() {
+                        data.remove(i);
+                    }
+                    return Ok(());
+                }
+                let position = self.buffer_position(object, key, length)?;
+                data.borrow_mut().remove(position);
+                Ok(())
+            }
+            Payload::MemoryView { .. } => {
+                Err(self.new_type_error("cannot delete memory".to_string()))
+            }
+            _ => {
+                let message = format!(
+                    "'{}' object doesn't support item deletion",
+                    object.type_name()
+                );
+                Err(self.new_type_error(message))
+            }
+        }
+    }
+
+    /// The position `key` selects in a bytearray or memoryview of `length`
+    /// bytes.
+    fn buffer_position(
+        &mut self,
+        object: &ObjectRef,
+        key: &ObjectRef,
+        length: usize,
+    ) -> PyResult<usize> {
+        let bytearray = matches!(object.payload, Payload::ByteArray(_));
+        let index = match Vm::index(key) {
+            Some(index) => index,
+            None if bytearray => {
+                let message = format!(
+                    "bytearray indices must be integers or slices, not {}",
+                    key.type_name()
+                );
+                return Err(self.new_type_error(message));
+            }
+            None => return Err(self.new_type_error("memoryview: invalid slice key".to_string())),
+        };
+        match Vm::position(index, length) {
+            Some(position) => Ok(position),
+            None if bytearray => {
+                Err(self.new_index_error("bytearray index out of range".to_string()))
+            }
+            None => Err(self.new_index_error("index out of bounds on dimension 1".to_string())),
+        }
+    }
+
+    /// `object[key]` for a bytearray or a memoryview. A slice of a view is
+    /// a view of a copy of the bytes it selects.
+    fn buffer_getitem(&mut self, object: &ObjectRef, key: &ObjectRef) -> PyResult {
+        let data = match bytes_like(object) {
+            Some(data) => data.into_owned(),
+            None => return Err(released_error(self)),
+        };
+        if let Payload::Slice { .. } = key.payload {
+            let (start, step, count) = self.slice(key, data.len())?;
+            let selected = slice_positions(start, step, count)
+                .into_iter()
+                .map(|i| data[i])
+                .collect();
+            if let Payload::ByteArray(_) = object.payload {
+                return Ok(self.new_bytearray(selected));
+            }
+            let payload = Payload::MemoryView {
+                object: self.new_bytes(selected),
+                released: Cell::new(false),
+            };
+            return Ok(PyObject::new(payload, self.types.memoryview.clone(), None));
+        }
+        let position = self.buffer_position(object, key, data.len())?;
+        Ok(self.new_int(i64::from(data[position])))
+    }
+
+    /// `object[key] = value` for a bytearray.
+    fn bytearray_setitem(
+        &mut self,
+        object: &ObjectRef,
+        key: &ObjectRef,
+        value: ObjectRef,
+    ) -> PyResult<()> {
+        let data = match object.payload {
+            Payload::ByteArray(ref data) => data,
+            _ => unreachable!(),
+        };
+        let length = data.borrow().len();
+        if let Payload::Slice { .. } = key.payload {
+            let values = match bytes_like(&value) {
+                Some(values) => values.into_owned(),
+                None if matches!(value.payload, Payload::Int(_)) || !self.is_iterable(&value) => {
+                    let message =
+                        "can assign only bytes, buffers, or iterables of ints in range(0, 256)";
+                    return Err(self.new_type_error(message.to_string()));
+                }
+                None => {
+                    let mut values = Vec::new();
+                    for item in self.iterate(&value)? {
+                        values.push(byte_value(self, &item)?);
+                    }
+                    values
+                }
+            };
+            let (start, step, count) = self.slice(key, length)?;
+            if step == 1 {
+                let start = start as usize;
+                data.borrow_mut().splice(start..start + count, values);
+                return Ok(());
+            }
+            if values.len() != count {
+                let message = format!(
+                    "attempt to assign bytes of size {} to extended slice of size {}",
+                    values.len(),
+                    count
+                );
+                return Err(self.new_value_error(message));
+            }
+            let mut data = data.borrow_mut();
+            for (i, value) in slice_positions(start, step, count).into_iter().zip(values) {
+                data[i] = value;
+            }
+            return Ok(());
+        }
+        let position = self.buffer_position(object, key, length)?;
+        let byte = byte_value(self, &value)?;
+        data.borrow_mut()[position] = byte;
+        Ok(())
+    }
+
+    /// `object[key] = value` for a memoryview, which may change the bytes
+    /// of a bytearray but not their number.
+    fn memoryview_setitem(
+        &mut self,
+        object: &ObjectRef,
+        key: &ObjectRef,
+        value: ObjectRef,
+    ) -> PyResult<()> {
+        let length = match bytes_like(object) {
+            Some(data) => data.len(),
+            None => return Err(released_error(self)),
+        };
+        let data = match object.payload {
+            Payload::MemoryView {
+                object: ref viewed, ..
+            } => match viewed.payload {
+                Payload::ByteArray(ref data) => data,
+                _ => return Err(self.new_type_error("cannot modify read-only memory".to_string())),
+            },
+            _ => unreachable!(),
+        };
+        if let Payload::Slice { .. } = key.payload {
+            let (start, step, count) = self.slice(key, length)?;
+            let values = match bytes_like(&value) {
+                Some(values) => values.into_owned(),
+                None => {
+                    let message = format!(
+                        "a bytes-like object is required, not '{}'",
+                        value.type_name()
+                    );
+                    return Err(self.new_type_error(message));
+                }
+            };
+            if values.len() != count {
+                let message = "memoryview assignment: lvalue and rvalue have different structures";
+                return Err(self.new_value_error(message.to_string()));
+            }
+            let mut data = data.borrow_mut();
+            for (i, value) in slice_positions(start, step, count).into_iter().zip(values) {
+                data[i] = value;
+            }
+            return Ok(());
+        }
+        let position = self.buffer_position(object, key, length)?;
+        let byte = match Vm::index(&value).map(u8::try_from) {
+            Some(Ok(byte)) => byte,
+            _ => {
+                let message = "memoryview: invalid value for format 'B'".to_string();
+                return Err(self.new_value_error(message));
+            }
+        };
+        data.borrow_mut()[position] = byte;
+        Ok(())
+    }
+
+    /// Whether `iter()` accepts `object`.
+    pub fn is_iterable(&mut self, object: &ObjectRef) -> bool {
+        match object.payload {
+            Payload::Str(_)
+            | Payload::Bytes(_)
+            | Payload::ByteArray(_)
+            | Payload::MemoryView { .. }
+            | Payload::Tuple(_)
+            | Payload::List(_)
+            | Payload::Dict(_)
+            | Payload::Set(_)
+            | Payload::DictView { .. }
+            | Payload::Range { .. }
+            | Payload::Iterator(_) => true,
+            Payload::Generator(ref generator) => !generator.is_coroutine(),
+            _ => self.python_method(object, "__iter__").is_some(),
+        }
+    }
+
+    /// `iter(object)`.
+    pub fn iter(&mut self, object: &ObjectRef) -> PyResult {
+        if let Some(method) = self.python_method(object, "__iter__") {
+            let iterator = self.call(&method, Args::default())?;
+            let is_iterator = match iterator.payload {
+                Payload::Iterator(_) => true,
+                Payload::Generator(ref generator) => !generator.is_coroutine(),
+                _ => self.python_method(&iterator, "__next__").is_some(),
+            };
+            if !is_iterator {
+                let message = format!(
+                    "iter() returned non-iterator of type '{}'",
+                    iterator.type_name()
+                );
+                return Err(self.new_type_error(message));
+            }
+            return Ok(iterator);
+        }
+        let (state, class) = match object.payload {
+            Payload::Iterator(_) => return Ok(object.clone()),
+            Payload::Generator(ref generator) if !generator.is_coroutine() => {
+                return Ok(object.clone())
+            }
+            Payload::List(_) => (
+                IteratorState::Sequence {
+                    sequence: object.clone(),
+                    index: 0,
+                },
+                &self.types.list_iterator,
+            ),
+            Payload::Tuple(_) => (
+                IteratorState::Sequence {
+                    sequence: object.clone(),
+                    index: 0,
+                },
+                &self.types.tuple_iterator,
+            ),
+            Payload::Bytes(_) => (
+                IteratorState::Sequence {
+                    sequence: object.clone(),
+                    index: 0,
+                },
+                &self.types.bytes_iterator,
+            ),
+            Payload::ByteArray(_) => (
+                IteratorState::Sequence {
+                    sequence: object.clone(),
+                    index: 0,
+                },
+                &self.types.bytearray_iterator,
+            ),
+            Payload::MemoryView { ref released, .. } if released.get() => {
+                return Err(released_error(self))
+            }
+            Payload::MemoryView { .. } => (
+                IteratorState::Sequence {
+                    sequence: object.clone(),
+                    index: 0,
+                },
+                &self.types.memory_iterator,
+            ),
+            Payload::Str(_) => (
+                IteratorState::Str {
+                    string: object.clone(),
+                    offset: 0,
+                },
+                &self.types.str_iterator,
+            ),
+            Payload::Dict(ref dict) | Payload::Set(ref dict) => (
+                IteratorState::Keys {
+                    container: object.clone(),
+                    position: 0,
+                    len: dict.borrow().len(),
+                },
+                if let Payload::Dict(_) = object.payload {
+                    &self.types.dict_keyiterator
+                } else {
+                    &self.types.set_iterator
+                },
+            ),
+            Payload::DictView { ref dict, kind } => {
+                let state = IteratorState::Entries {
+                    dict: dict.clone(),
+                    kind,
+                    position: 0,
+                    len: dict.as_dict().unwrap().borrow().len(),
+                    reversed: false,
+                };
+                let class = self.entries_iterator_class(kind, false);
+                return Ok(self.new_iterator(state, class));
+            }
+            Payload::Range { start, stop, step } => (
+                IteratorState::Range {
+                    next: start,
+                    step,
+                    remaining: range_len(start, stop, step),
+                },
+                &self.types.range_iterator,
+            ),
+            _ => {
+                let message = format!("'{}' object is not iterable", object.type_name());
+                return Err(self.new_type_error(message));
+            }
+        };
+        Ok(self.new_iterator(state, class.clone()))
+    }
+
+    /// The class of an iterator over the entries of a dict.
+    pub(super) fn entries_iterator_class(&self, kind: ViewKind, reversed: bool) -> ObjectRef {
+        let types = &self.types;
+        match (kind, reversed) {
+            (ViewKind::Keys, false) => types.dict_keyiterator.clone(),
+            (ViewKind::Values, false) => types.dict_valueiterator.clone(),
+            (ViewKind::Items, false) => types.dict_itemiterator.clone(),
+            (ViewKind::Keys, true) => types.dict_reversekeyiterator.clone(),
+            (ViewKind::Values, true) => types.dict_reversevalueiterator.clone(),
+            (ViewKind::Items, true) => types.dict_reverseitemiterator.clone(),
+        }
+    }
+
+    /// An iterator of class `class` starting in `state`.
+    pub fn new_iterator(&self, state: IteratorState, class: ObjectRef) -> ObjectRef {
+        PyObject::new(
+            Payload::Iterator(::std::cell::RefCell::new(state)),
+            class,
+            None,
+        )
+    }
+
+    /// The next item of an iterator, or `None` once it is exhausted.
+    pub fn next(&mut self, iterator: &ObjectRef) -> PyResult<Option<ObjectRef>> {
+        let state = match iterator.payload {
+            Payload::Iterator(ref state) => state,
+            Payload::Generator(ref generator) if !generator.is_coroutine() => {
+                let none = self.none();
+                return match self.resume(generator, Some(none), None)? {
+                    Completion::Yielded(item) => Ok(Some(item)),
+                    Completion::Returned(_) => Ok(None),
+                };
+            }
+            _ => {
+                let method = match self.python_method(iterator, "__next__") {
+                    Some(method) => method,
+                    None => {
+                        let message =
+                            format!("'{}' object is not an iterator", iterator.type_name());
+                        return Err(self.new_type_error(message));
+                    }
+                };
+                return match self.call(&method, Args::default()) {
+                    Ok(item) => Ok(Some(item)),
+                    Err(err) if Vm::is_instance(&err, &self.exceptions.stop_iteration) => Ok(None),
+                    Err(err) => Err(err),
+                };
+            }
+        };
+        // Iterators over other iterators call back into Python, which may
+        // use this iterator again, so they work on a copy of the state.
+        let adapter = match *state.borrow() {
+            IteratorState::Enumerate { .. }
+            | IteratorState::Zip(_)
+            | IteratorState::Map { .. }
+            | IteratorState::Filter { .. } => Some(state.borrow().clone()),
+            _ => None,
+        };
+        if let Some(adapter) = adapter {
+            let item = self.next_adapted(state, adapter)?;
+            if item.is_none() {
+                *state.borrow_mut() = IteratorState::Exhausted;
+            }
+            return Ok(item);
+        }
+        let mut state = state.borrow_mut();
+        let item = match *state {
+            IteratorState::Sequence {
+                ref sequence,
+                ref mut index,
+            } => {
+                let item = match sequence.payload {
+                    Payload::List(ref items) => items.borrow().get(*index).cloned(),
+                    Payload::Tuple(ref items) => items.get(*index).cloned(),
+                    Payload::Bytes(_) | Payload::ByteArray(_) | Payload::MemoryView { .. } => {
+                        byte_at(sequence, *index).map(|byte| self.new_int(i64::from(byte)))
+                    }
+                    _ => None,
+                };
+                *index += 1;
+                item
+            }
+            IteratorState::Str {
+                ref string,
+                ref mut offset,
+            } => {
+                let value = string.as_str().unwrap();
+                value[*offset..].chars().next().map(|c| {
+                    *offset += c.len_utf8();
+                    self.new_str(&c.to_string())
+                })
+            }
+            IteratorState::Keys {
+                ref container,
+                ref mut position,
+                len,
+            } => {
+                let (dict, kind) = match container.payload {
+                    Payload::Dict(ref dict) => (dict, "dictionary"),
+                    Payload::Set(ref dict) => (dict, "Set"),
+                    _ => unreachable!(),
+                };
+                let dict = dict.borrow();
+                if dict.len() != len {
+                    drop(dict);
+                    *state = IteratorState::Exhausted;
+                    let message = format!("{} changed size during iteration", kind);
+                    return Err(self.new_runtime_error(message));
+                }
+                dict.entry_from(*position).map(|(index, entry)| {
+                    *position = index + 1;
+                    entry.key.clone()
+                })
+            }
+            IteratorState::Entries {
+                ref dict,
+                kind,
+                ref mut position,
+                len,
+                reversed,
+            } => {
+                let entries = dict.as_dict().unwrap().borrow();
+                if entries.len() != len {
+                    drop(entries);
+                    *state = IteratorState::Exhausted;
+                    let message = "dictionary changed size during iteration".to_string();
+                    return Err(self.new_runtime_error(message));
+                }
+                let entry = if reversed {
+                    entries.entry_before(*position)
+                } else {
+                    entries.entry_from(*position)
+                };
+                entry.map(|(index, entry)| {
+                    *position = if reversed { index } else { index + 1 };
+                    self.view_item(entry, kind)
+                })
+            }
+            IteratorState::Range {
+                ref mut next,
+                step,
+                ref mut remaining,
+            } => {
+                if *remaining == 0 {
+                    None
+                } else {
+                    let value = *next;
+                    *remaining -= 1;
+                    if *remaining > 0 {
+                        *next += step;
+                    }
+                    Some(self.new_int(value))
+                }
+            }
+            IteratorState::Reversed {
+                ref sequence,
+                ref mut index,
+            } => {
+                let item = match (*index).checked_sub(1) {
+                    Some(position) => match sequence.payload {
+                        Payload::List(ref items) => items.borrow().get(position).cloned(),
+                        Payload::Tuple(ref items) => items.get(position).cloned(),
+                        Payload::Str(ref value) => value
+                            .chars()
+                            .nth(position)
+                            .map(|c| self.new_str(&c.to_string())),
+                        _ => None,
+                    },
+                    None => None,
+                };
+                *index = index.saturating_sub(1);
+                item
+            }
+            IteratorState::Enumerate { .. }
+            | IteratorState::Zip(_)
+            | IteratorState::Map { .. }
+            | IteratorState::Filter { .. } => unreachable!(),
+            IteratorState::Exhausted => None,
+        };
+        if item.is_none() {
+            *state = IteratorState::Exhausted;
+        }
+        Ok(item)
+    }
+
+    /// The next item of `enumerate`, `zip`, `map` or `filter`, whose state
+    /// is `adapter`, a copy of `state`.
+    fn next_adapted(
+        &mut self,
+        state: &::std::cell::RefCell<IteratorState>,
+        adapter: IteratorState,
+    ) -> PyResult<Option<ObjectRef>> {
+        match adapter {
+            IteratorState::Enumerate { iterator, count } => {
+                let item = match self.next(&iterator)? {
+                    Some(item) => item,
+                    None => return Ok(None),
+                };
+                if let IteratorState::Enumerate { ref mut count, .. } = *state.borrow_mut() {
+                    *count = count.checked_add(1).unwrap_or(i64::MAX);
+                }
+                let count = self.new_int(count);
+                Ok(Some(self.new_tuple(vec![count, item])))
+            }
+            IteratorState::Zip(iterators) => {
+                let mut items = Vec::with_capacity(iterators.len());
+                for iterator in &iterators {
+                    match self.next(iterator)? {
+                        Some(item) => items.push(item),
+                        None => return Ok(None),
+                    }
+                }
+                if items.is_empty() {
+                    return Ok(None);
+                }
+                Ok(Some(self.new_tuple(items)))
+            }
+            IteratorState::Map {
+                function,
+                iterators,
+            } => {
+                let mut items = Vec::with_capacity(iterators.len());
+                for iterator in &iterators {
+                    match self.next(iterator)? {
+                        Some(item) => items.push(item),
+                        None => return Ok(None),
+                    }
+                }
+                Ok(Some(self.call(&function, Args::new(items))?))
+            }
+            IteratorState::Filter { function, iterator } => {
+                while let Some(item) = self.next(&iterator)? {
+                    let keep = if Vm::is(&function, &self.none) {
+                        self.is_true(&item)?
+                    } else {
+                        let result = self.call(&function, Args::new(vec![item.clone()]))?;
+                        self.is_true(&result)?
+                    };
+                    if keep {
+                        return Ok(Some(item));
+                    }
+                }
+                Ok(None)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// The items of an iterable, as `list(object)` collects them.
+    pub fn iterate(&mut self, object: &ObjectRef) -> PyResult<Vec<ObjectRef>> {
+        // A subclass may iterate in its own way.
+        let exact = !object.class().as_type().is_some_and(|data| data.heap);
+        match object.payload {
+            Payload::Tuple(ref items) if exact => Ok(items.clone()),
+            Payload::List(ref items) if exact => Ok(items.borrow().clone()),
+            _ => {
+                let iterator = self.iter(object)?;
+                let mut items = Vec::new();
+                while let Some(item) = self.next(&iterator)? {
+                    items.push(item);
+                }
+                Ok(items)
+            }
+        }
+    }
+
+    /// Formats a value for an f-string replacement field, after applying
+    /// the conversion.
+    pub fn format_value(
+        &mut self,
+        value: &ObjectRef,
+        conversion: Option<char>,
+        spec: Option<&ObjectRef>,
+    ) -> PyResult {
+        let converted;
+        let value = match conversion {
+            Some('r') => {
+                converted = self.repr(value)?;
+                return self.format_converted(&converted, spec);
+            }
+            Some('a') => {
+                converted = self.ascii(value)?;
+                return self.format_converted(&converted, spec);
+            }
+            Some(_) => {
+                converted = self.str(value)?;
+                return self.format_converted(&converted, spec);
+            }
+            None => value,
+        };
+        let spec = match spec {
+            None if value.as_str().is_some() => return Ok(value.clone()),
+            None => "",
+            Some(spec) => match spec.as_str() {
+                Some(spec) => spec,
+                None => return Err(self.new_type_error("format spec must be a str".to_string())),
+            },
+        };
+        let text = self.format(value, spec)?;
+        Ok(self.new_str(&text))
+    }
+
+    fn format_converted(&mut self, text: &str, spec: Option<&ObjectRef>) -> PyResult {
+        let text = match spec.and_then(|spec| spec.as_str()) {
+            Some(spec) if !spec.is_empty() => {
+                format_str(text, spec, "str").map_err(|error| self.format_error(error))?
+            }
+            _ => text.to_string(),
+        };
+        Ok(self.new_str(&text))
+    }
+
+    /// `format(value, spec)`: the value's `__format__`, or the format
+    /// specification mini-language for strings and numbers.
+    pub fn format(&mut self, value: &ObjectRef, spec: &str) -> PyResult<String> {
+        if let Some(method) = self.python_method(value, "__format__") {
+            let spec = self.new_str(spec);
+            let result = self.call(&method, Args::new(vec![spec]))?;
+            return match result.as_str() {
+                Some(text) => Ok(text.to_string()),
+                None => {
+                    let message =
+                        format!("__format__ must return a str, not {}", result.type_name());
+                    Err(self.new_type_error(message))
+                }
+            };
+        }
+        if spec.is_empty() {
+            return self.str(value);
+        }
+        let type_name = value.type_name();
+        let formatted = match value.payload {
+            Payload::Int(number) => format_int(number, spec, &type_name),
+            Payload::Float(number) => format_float(number, spec, &type_name),
+            Payload::Str(ref text) => format_str(text, spec, &type_name),
+            Payload::Complex { .. } => {
+                let class = self.exceptions.not_implemented_error.clone();
+                let message = "format specifications for complex are not supported".to_string();
+                return Err(self.new_error(&class, message));
+            }
+            _ => {
+                let message = format!(
+                    "unsupported format string passed to {}.__format__",
+                    type_name
+                );
+                return Err(self.new_type_error(message));
+            }
+        };
+        formatted.map_err(|error| self.format_error(error))
+    }
+
+    pub(super) fn format_error(&mut self, error: FormatError) -> ObjectRef {
+        let (class, message) = match error {
+            FormatError::Value(message) => (self.exceptions.value_error.clone(), message),
+            FormatError::Overflow(message) => (self.exceptions.overflow_error.clone(), message),
+        };
+        self.new_error(&class, message)
+    }
+}
+
+/// The byte at `index` in a bytes-like object, if there is one.
+fn byte_at(object: &ObjectRef, index: usize) -> Option<u8> {
+    match object.payload {
+        Payload::Bytes(ref data) => data.get(index).copied(),
+        Payload::ByteArray(ref data) => data.borrow().get(index).copied(),
+        Payload::MemoryView {
+            ref object,
+            ref released,
+        } if !released.get() => byte_at(object, index),
+        _ => None,
+    }
+}
+
+fn repeat_items(items: &[ObjectRef], count: usize) -> Vec<ObjectRef> {
+    let mut repeated = Vec::with_capacity(items.len() * count);
+    for _ in 0..count {
+        repeated.extend(items.iter().cloned());
+    }
+    repeated
+}
+
+fn set_members(set: &ObjectRef) -> &::std::cell::RefCell<super::Dict> {
+    match set.payload {
+        Payload::Set(ref members) => members,
+        _ => unreachable!(),
+    }
+}
+
+fn complex_mul(a: (f64, f64), b: (f64, f64)) -> (f64, f64) {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+/// Complex division by Smith's method, as CPython's `_Py_c_quot`.
+fn complex_div(a: (f64, f64), b: (f64, f64)) -> Option<(f64, f64)> {
+    if b.0.abs() >= b.1.abs() {
+        if b.0 == 0.0 {
+            return None;
+        }
+        let ratio = b.1 / b.0;
+        let denominator = b.0 + b.1 * ratio;
+        Some((
+            (a.0 + a.1 * ratio) / denominator,
+            (a.1 - a.0 * ratio) / denominator,
+        ))
+    } else if b.1.abs() >= b.0.abs() {
+        let ratio = b.0 / b.1;
+        let denominator = b.0 * ratio + b.1;
+        Some((
+            (a.0 * ratio + a.1) / denominator,
+            (a.1 * ratio - a.0) / denominator,
+        ))
+    } else {
+        Some((f64::NAN, f64::NAN))
+    }
+}
+
+/// A complex number to a small integral power, by repeated squaring as
+/// CPython's `c_powi`.
+fn complex_powi(base: (f64, f64), exponent: i64) -> (f64, f64) {
+    let power = |mut base: (f64, f64), mut exponent: u64| {
+        let mut result = (1.0, 0.0);
+        while exponent > 0 {
+            if exponent & 1 == 1 {
+                result = complex_mul(result, base);
+            }
+            base = complex_mul(base, base);
+            exponent >>= 1;
+        }
+        result
+    };
+    if exponent >= 0 {
+        power(base, exponent as u64)
+    } else {
+        complex_div((1.0, 0.0), power(base, exponent.unsigned_abs()))
+            .unwrap_or((f64::NAN, f64::NAN))
+    }
+}
@@ -0,0 +1,449 @@
+//! `%` formatting of `str` and `bytes`, as CPython's `PyUnicode_Format`
+//! and `_PyBytes_FormatEx`.
+//!
+//! A `bytes` format is handled as text whose characters are its bytes, and
+//! turned back into bytes at the end; everything it inserts is ASCII or
+//! comes from other `bytes`.
+
+use optimizer::format_float;
+
+use super::object::{ObjectRef, Payload, PyResult};
+use super::ops::Number;
+use super::Vm;
+
+/// The flags, width and precision of a conversion specifier.
+#[derive(Default)]
+struct Spec {
+    /// `-`: padded on the right.
+    left: bool,
+    /// `+`: a plus sign before positive numbers.
+    plus: bool,
+    /// ` `: a space before positive numbers.
+    space: bool,
+    /// `#`: the alternate form.
+    alternate: bool,
+    /// `0`: numbers padded with zeros after the sign.
+    zero: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Spec {
+    /// Pads `sign` and `body` to the width, with zeros between them if
+    /// asked to.
+    fn pad(&self, sign: &str, body: &str, numeric: bool) -> String {
+        let len = sign.chars().count() + body.chars().count();
+        let padding = self.width.saturating_sub(len);
+        if self.left {
+            format!("{}{}{}", sign, body, " ".repeat(padding))
+        } else if self.zero && numeric {
+            format!("{}{}{}", sign, "0".repeat(padding), body)
+        } else {
+            format!("{}{}{}", " ".repeat(padding), sign, body)
+        }
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        if negative {
+            "-"
+        } else if self.plus {
+            "+"
+        } else if self.space {
+            " "
+        } else {
+            ""
+        }
+    }
+}
+
+/// The arguments of a `%` operation, taken in turn by the specifiers.
+struct Arguments {
+    positional: Vec<ObjectRef>,
+    next: usize,
+    /// The mapping that `%(key)s` specifiers look keys up in.
+    mapping: Option<ObjectRef>,
+}
+
+impl Vm {
+    /// `format % args`, where `format` is a `str` or `bytes`.
+    pub(super) fn percent_format(&mut self, format: &ObjectRef, args: &ObjectRef) -> PyResult {
+        let (chars, bytes): (Vec<char>, bool) = match format.payload {
+            Payload::Str(ref text) => (text.chars().collect(), false),
+            Payload::Bytes(ref data) => (data.iter().map(|&byte| char::from(byte)).collect(), true),
+            _ => unreachable!(),
+        };
+        // A single argument of the format's own type is never the mapping.
+        let single = !matches!(
+            (&args.payload, bytes),
+            (Payload::Tuple(_), _) | (Payload::Str(_), false) | (Payload::Bytes(_), true)
+        );
+        let mut arguments = Arguments {
+            positional: match args.payload {
+                Payload::Tuple(ref items) => items.clone(),
+                _ => vec![args.clone()],
+            },
+            next: 0,
+            mapping: if single && self.has_getitem(args) {
+                Some(args.clone())
+            } else {
+                None
+            },
+        };
+        let mut out = String::new();
+        let mut position = 0;
+        while position < chars.len() {
+            let c = chars[position];
+            position += 1;
+            if c != '%' {
+                out.push(c);
+                continue;
+            }
+            if chars.get(position) == Some(&'%') {
+                out.push('%');
+                position += 1;
+                continue;
+            }
+            let formatted = self.conversion(&chars, &mut position, &mut arguments, bytes)?;
+            out.push_str(&formatted);
+        }
+        if arguments.next < arguments.positional.len() && arguments.mapping.is_none() {
+            let message = "not all arguments converted during string formatting".to_string();
+            return Err(self.new_type_error(message));
+        }
+        if bytes {
+            Ok(self.new_bytes(out.chars().map(|c| c as u8).collect()))
+        } else {
+            Ok(self.new_str(&out))
+        }
+    }
+
+    /// Whether `object` supports `[]`, which makes it the mapping for
+    /// `%(key)s` specifiers, as CPython's `PyMapping_Check`.
+    fn has_getitem(&mut self, object: &ObjectRef) -> bool {
+        match object.payload {
+            Payload::Dict(_)
+            | Payload::List(_)
+            | Payload::Tuple(_)
+            | Payload::Str(_)
+            | Payload::Bytes(_)
+            | Payload::ByteArray(_)
+            | Payload::MemoryView { .. }
+            | Payload::Range { .. } => true,
+            _ => self.python_method(object, "__getitem__").is_some(),
+        }
+    }
+
+    /// Parses the specifier after a `%` and formats its argument.
+    fn conversion(
+        &mut self,
+        chars: &[char],
+        position: &mut usize,
+        arguments: &mut Arguments,
+        bytes: bool,
+    ) -> PyResult<String> {
+        let mut keyed = None;
+        if chars.get(*position) == Some(&'(') {
+            let start = *position + 1;
+            let mut depth = 1;
+            let mut end = start;
+            while depth > 0 {
+                match chars.get(end) {
+                    Some('(') => depth += 1,
+                    Some(')') => depth -= 1,
+                    Some(_) => {}
+                    None => return Err(self.new_value_error("incomplete format key".to_string())),
+                }
+                end += 1;
+            }
+            let key: String = chars[start..end - 1].iter().collect();
+            let mapping = match arguments.mapping {
+                Some(ref mapping) => mapping.clone(),
+                None => return Err(self.new_type_error("format requires a mapping".to_string())),
+            };
+            let key = if bytes {
+                self.new_bytes(key.chars().map(|c| c as u8).collect())
+            } else {
+                self.new_str(&key)
+            };
+            keyed = Some(self.getitem(&mapping, &key)?);
+            // Once a key is used, there are no positional arguments left.
+            arguments.positional.clear();
+            arguments.next = 0;
+            *position = end;
+        }
+        let mut spec = Spec::default();
+        while let Some(&flag) = chars.get(*position) {
+            match flag {
+                '-' => spec.left = true,
+                '+' => spec.plus = true,
+                ' ' => spec.space = true,
+                '#' => spec.alternate = true,
+                '0' => spec.zero = true,
+                _ => break,
+            }
+            *position += 1;
+        }
+        if chars.get(*position) == Some(&'*') {
+            *position += 1;
+            let width = self.star_argument(arguments)?;
+            spec.left |= width < 0;
+            spec.width = width.unsigned_abs() as usize;
+        } else {
+            spec.width = self.spec_number(chars, position, "width")?.unwrap_or(0);
+        }
+        if chars.get(*position) == Some(&'.') {
+            *position += 1;
+            if chars.get(*position) == Some(&'*') {
+                *position += 1;
+                spec.precision = Some(self.star_argument(arguments)?.max(0) as usize);
+            } else {
+                let precision = self.spec_number(chars, position, "precision")?;
+                spec.precision = Some(precision.unwrap_or(0));
+            }
+        }
+        while matches!(chars.get(*position), Some('h') | Some('l') | Some('L')) {
+            *position += 1;
+        }
+        let conversion = match chars.get(*position) {
+            Some(&conversion) => conversion,
+            None => return Err(self.new_value_error("incomplete format".to_string())),
+        };
+        *position += 1;
+        let value = match keyed {
+            Some(value) => value,
+            None => self.next_argument(arguments)?,
+        };
+        match conversion {
+            's' | 'r' | 'a' | 'b' if bytes || conversion != 'b' => {
+                let mut text = match conversion {
+                    's' | 'b' if bytes => self.bytes_text(&value)?,
+                    's' => self.str(&value)?,
+                    'r' if !bytes => self.repr(&value)?,
+                    _ => self.ascii(&value)?,
+                };
+                if let Some(precision) = spec.precision {
+                    text = text.chars().take(precision).collect();
+                }
+                Ok(spec.pad("", &text, false))
+            }
+            'd' | 'i' | 'u' | 'o' | 'x' | 'X' => self.percent_int(&value, conversion, &spec),
+            'e' | 'E' | 'f' | 'F' | 'g' | 'G' => self.percent_float(&value, conversion, &spec),
+            'c' => {
+                let c = self.percent_char(&value, bytes)?;
+                Ok(spec.pad("", &c.to_string(), false))
+            }
+            _ => {
+                let shown = if (' '..='~').contains(&conversion) {
+                    conversion
+                } else {
+                    '?'
+                };
+                let message = format!(
+                    "unsupported format character '{}' ({:#x}) at index {}",
+                    shown,
+                    conversion as u32,
+                    *position - 1
+                );
+                Err(self.new_value_error(message))
+            }
+        }
+    }
+
+    fn next_argument(&mut self, arguments: &mut Arguments) -> PyResult {
+        match arguments.positional.get(arguments.next) {
+            Some(value) => {
+                arguments.next += 1;
+                Ok(value.clone())
+            }
+            None => {
+                let message = "not enough arguments for format string".to_string();
+                Err(self.new_type_error(message))
+            }
+        }
+    }
+
+    /// The argument a `*` width or precision takes.
+    fn star_argument(&mut self, arguments: &mut Arguments) -> PyResult<i64> {
+        let value = self.next_argument(arguments)?;
+        match value.payload {
+            Payload::Int(value) => Ok(value),
+            _ => Err(self.new_type_error("* wants int".to_string())),
+        }
+    }
+
+    /// A width or precision written in digits, or `None` if there are none.
+    fn spec_number(
+        &mut self,
+        chars: &[char],
+        position: &mut usize,
+        what: &str,
+    ) -> PyResult<Option<usize>> {
+        let start = *position;
+        let mut value: usize = 0;
+        while let Some(digit) = chars.get(*position).and_then(|c| c.to_digit(10)) {
+            value = match value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add(digit as usize))
+            {
+                Some(value) if value <= i32::MAX as usize => value,
+                _ => return Err(self.new_value_error(format!("{} too big", what))),
+            };
+            *position += 1;
+        }
+        Ok(if *position == start {
+            None
+        } else {
+            Some(value)
+        })
+    }
+
+    /// What `%s` inserts in a `bytes` format: the object's bytes, as
+    /// characters.
+    fn bytes_text(&mut self, value: &ObjectRef) -> PyResult<String> {
+        let value = match self.python_method(value, "__bytes__") {
+            Some(method) => self.call(&method, Default::default())?,
+            None => value.clone(),
+        };
+        match value.payload {
+            Payload::Bytes(ref data) => Ok(data.iter().map(|&byte| char::from(byte)).collect()),
+            _ => {
+                let message = format!(
+                    "%b requires a bytes-like object, or an object that implements __bytes__, \
+                     not '{}'",
+                    value.type_name()
+                );
+                Err(self.new_type_error(message))
+            }
+        }
+    }
+
+    /// `%d`, `%i`, `%u`, `%o`, `%x` or `%X`. The decimal ones also take
+    /// floats, truncated.
+    fn percent_int(
+        &mut self,
+        value: &ObjectRef,
+        conversion: char,
+        spec: &Spec,
+    ) -> PyResult<String> {
+        let decimal = matches!(conversion, 'd' | 'i' | 'u');
+        let (negative, digits) = match Number::of(value) {
+            Some(Number::Int(value)) => {
+                let magnitude = value.unsigned_abs();
+                let digits = match conversion {
+                    'o' => format!("{:o}", magnitude),
+                    'x' => format!("{:x}", magnitude),
+                    'X' => format!("{:X}", magnitude),
+                    _ => magnitude.to_string(),
+                };
+                (value < 0, digits)
+            }
+            Some(Number::Float(value)) if decimal => {
+                if value.is_nan() {
+                    let message = "cannot convert float NaN to integer".to_string();
+                    return Err(self.new_value_error(message));
+                }
+                if value.is_infinite() {
+                    let message = "cannot convert float infinity to integer".to_string();
+                    return Err(self.new_overflow_error(message));
+                }
+                let value = value.trunc();
+                (value < 0.0, format!("{:.0}", value.abs()))
+            }
+            _ => {
+                let required = if decimal {
+                    "a real number"
+                } else {
+                    "an integer"
+                };
+                let message = format!(
+                    "%{} format: {} is required, not {}",
+                    conversion,
+                    required,
+                    value.type_name()
+                );
+                return Err(self.new_type_error(message));
+            }
+        };
+        let digits = match spec.precision {
+            Some(precision) if precision > digits.len() => {
+                format!("{}{}", "0".repeat(precision - digits.len()), digits)
+            }
+            _ => digits,
+        };
+        let prefix = match conversion {
+            'o' if spec.alternate => "0o",
+            'x' if spec.alternate => "0x",
+            'X' if spec.alternate => "0X",
+            _ => "",
+        };
+        let sign = format!("{}{}", spec.sign(negative), prefix);
+        Ok(spec.pad(&sign, &digits, true))
+    }
+
+    /// `%e`, `%f` or `%g` and their uppercase forms, which are the
+    /// `format()` types of the same names.
+    fn percent_float(
+        &mut self,
+        value: &ObjectRef,
+        conversion: char,
+        spec: &Spec,
+    ) -> PyResult<String> {
+        let value = match Number::of(value) {
+            Some(Number::Int(value)) => value as f64,
+            Some(Number::Float(value)) => value,
+            _ => {
+                let message = format!("must be real number, not {}", value.type_name());
+                return Err(self.new_type_error(message));
+            }
+        };
+        let align = if spec.left { "<" } else { "" };
+        let sign = if spec.plus {
+            "+"
+        } else if spec.space {
+            " "
+        } else {
+            ""
+        };
+        let alternate = if spec.alternate { "#" } else { "" };
+        let zero = if spec.zero && !spec.left { "0" } else { "" };
+        let format_spec = format!(
+            "{}{}{}{}{}.{}{}",
+            align,
+            sign,
+            alternate,
+            zero,
+            spec.width,
+            spec.precision.unwrap_or(6),
+            conversion
+        );
+        format_float(value, &format_spec, "float").map_err(|error| self.format_error(error))
+    }
+
+    /// The character `%c` inserts: a code point, or a one-character string,
+    /// or for a `bytes` format a byte value or a one-byte `bytes`.
+    fn percent_char(&mut self, value: &ObjectRef, bytes: bool) -> PyResult<char> {
+        let limit = if bytes { 0x100 } else { 0x110000 };
+        match value.payload {
+            Payload::Int(code) if (0..limit).contains(&code) => match char::from_u32(code as u32) {
+                Some(c) => Ok(c),
+                None => Err(self.new_value_error("surrogates not allowed".to_string())),
+            },
+            Payload::Int(_) => {
+                let message = format!(
+                    "%c arg not in range({})",
+                    if bytes { "256" } else { "0x110000" }
+                );
+                Err(self.new_overflow_error(message))
+            }
+            Payload::Bytes(ref data) if bytes && data.len() == 1 => Ok(char::from(data[0])),
+            Payload::Str(ref text) if !bytes && text.chars().count() == 1 => {
+                Ok(text.chars().next().unwrap())
+            }
+            _ if bytes => {
+                let message = "%c requires an integer in range(256) or a single byte".to_string();
+                Err(self.new_type_error(message))
+            }
+            _ => Err(self.new_type_error("%c requires int or char".to_string())),
+        }
+    }
+}
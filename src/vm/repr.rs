@@ -0,0 +1,197 @@
+//! Reprs cut down to a size, as `reprlib.Repr` makes them, for showing
+//! values where a full repr could be huge: by a REPL, or for the local
+//! variables in tracebacks.
+
+use super::object::{object_id, ObjectRef, Payload, PyResult};
+use super::Vm;
+
+/// How much of a value a limited repr shows, as the attributes of
+/// `reprlib.Repr`. Containers nested deeper than `max_level` show as
+/// `...`, and each kind shows at most its limit of items; strings, ints
+/// and the reprs of other objects longer than their limits lose their
+/// middle to `...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReprLimits {
+    pub max_level: usize,
+    pub max_tuple: usize,
+    pub max_list: usize,
+    pub max_dict: usize,
+    pub max_set: usize,
+    pub max_frozenset: usize,
+    pub max_string: usize,
+    pub max_long: usize,
+    pub max_other: usize,
+}
+
+impl Default for ReprLimits {
+    /// The defaults of `reprlib.Repr`.
+    fn default() -> ReprLimits {
+        ReprLimits {
+            max_level: 6,
+            max_tuple: 6,
+            max_list: 6,
+            max_dict: 4,
+            max_set: 6,
+            max_frozenset: 6,
+            max_string: 30,
+            max_long: 40,
+            max_other: 30,
+        }
+    }
+}
+
+/// Replaces the middle of `text` with `...` if it is longer than `max`.
+fn elide(text: String, max: usize) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    if chars.len() <= max {
+        return text;
+    }
+    let start = max.saturating_sub(3) / 2;
+    let end = max.saturating_sub(3) - start;
+    let head: String = chars[..start].iter().collect();
+    let tail: String = chars[chars.len() - end..].iter().collect();
+    format!("{}...{}", head, tail)
+}
+
+impl Vm {
+    /// The repr of `object` within `limits`, as `reprlib.Repr.repr`.
+    /// Only exact builtin types are shortened item by item; anything else
+    /// is its whole repr, elided.
+    pub fn limited_repr(&mut self, object: &ObjectRef, limits: &ReprLimits) -> PyResult<String> {
+        self.limited_repr_at(object, limits, limits.max_level)
+    }
+
+    fn limited_repr_at(
+        &mut self,
+        object: &ObjectRef,
+        limits: &ReprLimits,
+        level: usize,
+    ) -> PyResult<String> {
+        let class = object.class();
+        let exact = |types: &ObjectRef| Vm::is(&class, types);
+        match object.payload {
+            Payload::Tuple(ref items) if exact(&self.types.tuple) => {
+                let trail = if items.len() == 1 { "," } else { "" };
+                let pieces = self.limited_items(items, limits, level, limits.max_tuple)?;
+                Ok(format!("({}{})", pieces, trail))
+            }
+            Payload::List(ref items) if exact(&self.types.list) => {
+                let items = items.borrow().clone();
+                let pieces = self.limited_items(&items, limits, level, limits.max_list)?;
+                Ok(format!("[{}]", pieces))
+            }
+            Payload::Set(ref members) if exact(&self.types.set) || exact(&self.types.frozenset) => {
+                let members: Vec<ObjectRef> = members
+                    .borrow()
+                    .iter()
+                    .map(|entry| entry.key.clone())
+                    .collect();
+                let frozen = exact(&self.types.frozenset);
+                if members.is_empty() {
+                    return Ok(if frozen { "frozenset()" } else { "set()" }.to_string());
+                }
+                let members = self.possibly_sorted(members);
+                if frozen {
+                    let pieces =
+                        self.limited_items(&members, limits, level, limits.max_frozenset)?;
+                    Ok(format!("frozenset({{{}}})", pieces))
+                } else {
+                    let pieces = self.limited_items(&members, limits, level, limits.max_set)?;
+                    Ok(format!("{{{}}}", pieces))
+                }
+            }
+            Payload::Dict(ref dict) if exact(&self.types.dict) => {
+                let keys: Vec<ObjectRef> = dict
+                    .borrow()
+                    .iter()
+                    .map(|entry| entry.key.clone())
+                    .collect();
+                if keys.is_empty() {
+                    return Ok("{}".to_string());
+                }
+                if level == 0 {
+                    return Ok("{...}".to_string());
+                }
+                let count = keys.len();
+                let mut pieces = Vec::new();
+                for key in self.possibly_sorted(keys).iter().take(limits.max_dict) {
+                    let value = self.getitem(object, key)?;
+                    pieces.push(format!(
+                        "{}: {}",
+                        self.limited_repr_at(key, limits, level - 1)?,
+                        self.limited_repr_at(&value, limits, level - 1)?
+                    ));
+                }
+                if count > limits.max_dict {
+                    pieces.push("...".to_string());
+                }
+                Ok(format!("{{{}}}", pieces.join(", ")))
+            }
+            Payload::Str(ref text) if exact(&self.types.str) => {
+                let chars: Vec<char> = text.chars().collect();
+                let prefix: String = chars.iter().take(limits.max_string).collect();
+                let shown = self.repr(&self.new_str(&prefix))?;
+                if shown.chars().count() <= limits.max_string {
+                    return Ok(shown);
+                }
+                // The ends of the string itself, not of its prefix.
+                let start = limits.max_string.saturating_sub(3) / 2;
+                let end = limits.max_string.saturating_sub(3) - start;
+                let ends: String = chars[..start.min(chars.len())]
+                    .iter()
+                    .chain(&chars[chars.len().saturating_sub(end)..])
+                    .collect();
+                let shown: Vec<char> = self.repr(&self.new_str(&ends))?.chars().collect();
+                let head: String = shown[..start.min(shown.len())].iter().collect();
+                let tail: String = shown[shown.len().saturating_sub(end)..].iter().collect();
+                Ok(format!("{}...{}", head, tail))
+            }
+            Payload::Int(_) if exact(&self.types.int) => {
+                let text = self.repr(object)?;
+                Ok(elide(text, limits.max_long))
+            }
+            _ => {
+                let text = match self.repr(object) {
+                    Ok(text) => text,
+                    Err(_) => format!(
+                        "<{} instance at {:#x}>",
+                        object.type_name(),
+                        object_id(object)
+                    ),
+                };
+                Ok(elide(text, limits.max_other))
+            }
+        }
+    }
+
+    /// The limited reprs of the first `max` items, joined by commas, and
+    /// `...` if there are more.
+    fn limited_items(
+        &mut self,
+        items: &[ObjectRef],
+        limits: &ReprLimits,
+        level: usize,
+        max: usize,
+    ) -> PyResult<String> {
+        if level == 0 && !items.is_empty() {
+            return Ok("...".to_string());
+        }
+        let mut pieces = Vec::new();
+        for item in items.iter().take(max) {
+            pieces.push(self.limited_repr_at(item, limits, level - 1)?);
+        }
+        if items.len() > max {
+            pieces.push("...".to_string());
+        }
+        Ok(pieces.join(", "))
+    }
+
+    /// The items sorted if they can be compared, and otherwise as they are.
+    fn possibly_sorted(&mut self, items: Vec<ObjectRef>) -> Vec<ObjectRef> {
+        let mut sorted = items.clone();
+        match self.sort(&mut sorted, None, false) {
+            Ok(()) => sorted,
+            Err(_) => items,
+        }
+    }
+}
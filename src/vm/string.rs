@@ -0,0 +1,1553 @@
+//! The methods of `str`. Positions, lengths and widths count code points,
+//! as Python's do, although strings are stored as UTF-8.
+
+use std::convert::TryFrom;
+
+use ast::is_printable;
+use tokenizer::{is_identifier_continue, is_identifier_start};
+
+use super::builtins::{check_count, index_value, keyword_arguments};
+use super::object::{Args, NativeFunction, ObjectRef, Payload, PyObject, PyResult};
+use super::unicode;
+use super::Vm;
+
+/// How deeply replacement fields may nest in the format specifications of
+/// `str.format`, as in CPython.
+const FORMAT_RECURSION_DEPTH: usize = 2;
+
+/// Adds the methods of `str` to its class.
+pub(super) fn add_methods(vm: &mut Vm) {
+    let methods: [(&'static str, NativeFunction); 46] = [
+        ("capitalize", capitalize),
+        ("casefold", casefold),
+        ("center", center),
+        ("count", count),
+        ("encode", encode),
+        ("endswith", endswith),
+        ("expandtabs", expandtabs),
+        ("find", find),
+        ("format", format),
+        ("format_map", format_map),
+        ("index", index),
+        ("isalnum", isalnum),
+        ("isalpha", isalpha),
+        ("isascii", isascii),
+        ("isdecimal", isdecimal),
+        ("isdigit", isdigit),
+        ("isidentifier", isidentifier),
+        ("islower", islower),
+        ("isnumeric", isnumeric),
+        ("isprintable", isprintable),
+        ("isspace", isspace),
+        ("istitle", istitle),
+        ("isupper", isupper),
+        ("join", join),
+        ("ljust", ljust),
+        ("lower", lower),
+        ("lstrip", lstrip),
+        ("partition", partition),
+        ("removeprefix", removeprefix),
+        ("removesuffix", removesuffix),
+        ("replace", replace),
+        ("rfind", rfind),
+        ("rindex", rindex),
+        ("rjust", rjust),
+        ("rpartition", rpartition),
+        ("rsplit", rsplit),
+        ("rstrip", rstrip),
+        ("split", split),
+        ("splitlines", splitlines),
+        ("startswith", startswith),
+        ("strip", strip),
+        ("swapcase", swapcase),
+        ("title", title),
+        ("translate", translate),
+        ("upper", upper),
+        ("zfill", zfill),
+    ];
+    let dict = vm.types.str.dict().unwrap().clone();
+    for &(name, function) in &methods {
+        let method = vm.new_builtin(name, function);
+        vm.dict_set_str(dict.as_dict().unwrap(), name, method);
+    }
+    let function = vm.new_builtin("maketrans", maketrans);
+    let maketrans = PyObject::new(
+        Payload::StaticMethod(function),
+        vm.types.staticmethod.clone(),
+        None,
+    );
+    vm.dict_set_str(dict.as_dict().unwrap(), "maketrans", maketrans);
+}
+
+/// Splits the string a method was called on from the other arguments.
+fn receiver(vm: &mut Vm, mut args: Args, name: &str) -> PyResult<(ObjectRef, Args)> {
+    if args.positional.is_empty() {
+        let message = format!("unbound method str.{}() needs an argument", name);
+        return Err(vm.new_type_error(message));
+    }
+    let this = args.positional.remove(0);
+    if this.as_str().is_none() {
+        let message = format!(
+            "descriptor '{}' for 'str' objects doesn't apply to a '{}' object",
+            name,
+            this.type_name()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok((this, args))
+}
+
+fn reject_keywords(vm: &mut Vm, args: &Args, name: &str) -> PyResult<()> {
+    if args.keywords.is_empty() {
+        return Ok(());
+    }
+    let message = format!("str.{}() takes no keyword arguments", name);
+    Err(vm.new_type_error(message))
+}
+
+/// The string of a method that takes no arguments.
+fn no_arguments(vm: &mut Vm, args: Args, name: &str) -> PyResult<ObjectRef> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    if !args.positional.is_empty() {
+        let message = format!(
+            "str.{}() takes no arguments ({} given)",
+            name,
+            args.positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    Ok(this)
+}
+
+/// The string and the argument of a method that takes exactly one.
+fn single_argument(vm: &mut Vm, args: Args, name: &str) -> PyResult<(ObjectRef, ObjectRef)> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    if args.positional.len() != 1 {
+        let message = format!(
+            "str.{}() takes exactly one argument ({} given)",
+            name,
+            args.positional.len()
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let argument = args.positional.into_iter().next().unwrap();
+    Ok((this, argument))
+}
+
+/// The string and the arguments of a method whose arguments are all
+/// positional-only.
+fn positional_arguments(
+    vm: &mut Vm,
+    args: Args,
+    name: &str,
+    min: usize,
+    max: usize,
+) -> PyResult<(ObjectRef, Vec<ObjectRef>)> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    check_count(vm, &args, name, min, max)?;
+    Ok((this, args.positional))
+}
+
+/// The same, for the searching methods, which report a wrong number of
+/// arguments the older way.
+fn search_arguments(vm: &mut Vm, args: Args, name: &str) -> PyResult<(ObjectRef, Vec<ObjectRef>)> {
+    let (this, args) = receiver(vm, args, name)?;
+    reject_keywords(vm, &args, name)?;
+    args.check(vm, name, 1, 3)?;
+    Ok((this, args.positional))
+}
+
+/// The string and the arguments, by position or by name, of a method that
+/// takes the optional arguments `names`.
+fn named_arguments(
+    vm: &mut Vm,
+    args: Args,
+    name: &str,
+    names: &[&str],
+) -> PyResult<(ObjectRef, Vec<Option<ObjectRef>>)> {
+    let (this, args) = receiver(vm, args, name)?;
+    let given = args.positional.len() + args.keywords.len();
+    if given > names.len() {
+        let message = format!(
+            "{}() takes at most {} argument{} ({} given)",
+            name,
+            names.len(),
+            if names.len() == 1 { "" } else { "s" },
+            given
+        );
+        return Err(vm.new_type_error(message));
+    }
+    let mut values = keyword_arguments(vm, args.keywords, name, names)?;
+    for (position, value) in args.positional.into_iter().enumerate() {
+        if values[position].is_some() {
+            let message = format!(
+                "argument for {}() given by name ('{}') and position ({})",
+                name,
+                names[position],
+                position + 1
+            );
+            return Err(vm.new_type_error(message));
+        }
+        values[position] = Some(value);
+    }
+    Ok((this, values))
+}
+
+fn str_argument<'a>(vm: &mut Vm, object: &'a ObjectRef) -> PyResult<&'a str> {
+    match object.as_str() {
+        Some(value) => Ok(value),
+        None => {
+            let message = format!("must be str, not {}", object.type_name());
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// A str argument of `function` described by `argument`, such as
+/// "argument 1".
+fn named_str_argument<'a>(
+    vm: &mut Vm,
+    object: &'a ObjectRef,
+    function: &str,
+    argument: &str,
+) -> PyResult<&'a str> {
+    match object.as_str() {
+        Some(value) => Ok(value),
+        None => {
+            let message = format!(
+                "{}() {} must be str, not {}",
+                function,
+                argument,
+                object.type_name()
+            );
+            Err(vm.new_type_error(message))
+        }
+    }
+}
+
+/// The `start` or `end` argument of a search, which may be `None`.
+fn slice_index(vm: &mut Vm, object: Option<&ObjectRef>) -> PyResult<Option<i64>> {
+    let object = match object {
+        Some(object) => object,
+        None => return Ok(None),
+    };
+    match object.payload {
+        Payload::None => Ok(None),
+        Payload::Int(value) => Ok(Some(value)),
+        _ if vm.python_method(object, "__index__").is_some() => index_value(vm, object).map(Some),
+        _ => {
+            let message = "slice indices must be integers or None or have an __index__ method";
+            Err(vm.new_type_error(message.to_string()))
+        }
+    }
+}
+
+/// The character range `start..end` of a string of `len` characters, with
+/// negative bounds counted from the end. `start` may be past the end, when
+/// nothing is found there, not even an empty string.
+fn search_range(len: usize, start: Option<i64>, end: Option<i64>) -> (usize, usize) {
+    let len = len as i64;
+    let adjust = |bound: i64| {
+        if bound < 0 {
+            (bound + len).max(0)
+        } else {
+            bound
+        }
+    };
+    let start = start.map_or(0, adjust);
+    let end = end.map_or(len, adjust).min(len);
+    (start as usize, end as usize)
+}
+
+/// The byte offset of the character at `index`, or the length of the text
+/// if it has no more characters.
+fn byte_offset(text: &str, index: usize) -> usize {
+    if text.is_ascii() {
+        return index.min(text.len());
+    }
+    text.char_indices()
+        .nth(index)
+        .map_or(text.len(), |(offset, _)| offset)
+}
+
+fn char_count(text: &str) -> usize {
+    if text.is_ascii() {
+        text.len()
+    } else {
+        text.chars().count()
+    }
+}
+
+/// The text of a search between the character positions of the optional
+/// `start` and `end` arguments, and the position it starts at; `None` if
+/// the range is empty and so holds nothing, or too short for `needle`.
+fn search_slice<'a>(
+    vm: &mut Vm,
+    text: &'a str,
+    bounds: &[ObjectRef],
+    needle: &str,
+) -> PyResult<Option<(&'a str, usize)>> {
+    let start = slice_index(vm, bounds.first())?;
+    let end = slice_index(vm, bounds.get(1))?;
+    let (start, end) = search_range(char_count(text), start, end);
+    if end < start || end - start < char_count(needle) {
+        return Ok(None);
+    }
+    let (from, to) = (byte_offset(text, start), byte_offset(text, end));
+    Ok(Some((&text[from..to], start)))
+}
+
+/// The character position of `needle` in the string, searching from the
+/// end if `reverse`.
+fn find_position(vm: &mut Vm, args: Args, name: &str, reverse: bool) -> PyResult<Option<usize>> {
+    let (this, rest) = search_arguments(vm, args, name)?;
+    let needle = str_argument(vm, &rest[0])?;
+    let text = this.as_str().unwrap();
+    let (slice, start) = match search_slice(vm, text, &rest[1..], needle)? {
+        Some(found) => found,
+        None => return Ok(None),
+    };
+    let offset = if reverse {
+        slice.rfind(needle)
+    } else {
+        slice.find(needle)
+    };
+    Ok(offset.map(|offset| start + char_count(&slice[..offset])))
+}
+
+/// `str.find(sub[, start[, end]])`.
+fn find(vm: &mut Vm, args: Args) -> PyResult {
+    let position = find_position(vm, args, "find", false)?;
+    Ok(vm.new_int(position.map_or(-1, |position| position as i64)))
+}
+
+/// `str.rfind(sub[, start[, end]])`.
+fn rfind(vm: &mut Vm, args: Args) -> PyResult {
+    let position = find_position(vm, args, "rfind", true)?;
+    Ok(vm.new_int(position.map_or(-1, |position| position as i64)))
+}
+
+/// `str.index(sub[, start[, end]])`, which is `find` raising `ValueError`
+/// if the substring is not there.
+fn index(vm: &mut Vm, args: Args) -> PyResult {
+    match find_position(vm, args, "index", false)? {
+        Some(position) => Ok(vm.new_int(position as i64)),
+        None => Err(vm.new_value_error("substring not found".to_string())),
+    }
+}
+
+fn rindex(vm: &mut Vm, args: Args) -> PyResult {
+    match find_position(vm, args, "rindex", true)? {
+        Some(position) => Ok(vm.new_int(position as i64)),
+        None => Err(vm.new_value_error("substring not found".to_string())),
+    }
+}
+
+/// `str.count(sub[, start[, end]])`, the number of non-overlapping
+/// occurrences.
+fn count(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, rest) = search_arguments(vm, args, "count")?;
+    let needle = str_argument(vm, &rest[0])?;
+    let text = this.as_str().unwrap();
+    let count = match search_slice(vm, text, &rest[1..], needle)? {
+        None => 0,
+        Some((slice, _)) if needle.is_empty() => char_count(slice) + 1,
+        Some((slice, _)) => slice.matches(needle).count(),
+    };
+    Ok(vm.new_int(count as i64))
+}
+
+/// `str.startswith(prefix[, start[, end]])`, where `prefix` may be a
+/// tuple of strings to try.
+fn startswith(vm: &mut Vm, args: Args) -> PyResult {
+    affix_match(vm, args, "startswith", |text, affix| {
+        text.starts_with(affix)
+    })
+}
+
+fn endswith(vm: &mut Vm, args: Args) -> PyResult {
+    affix_match(vm, args, "endswith", |text, affix| text.ends_with(affix))
+}
+
+fn affix_match(vm: &mut Vm, args: Args, name: &str, matches: fn(&str, &str) -> bool) -> PyResult {
+    let (this, rest) = search_arguments(vm, args, name)?;
+    let text = this.as_str().unwrap();
+    let affixes = match rest[0].payload {
+        Payload::Tuple(ref items) => items.clone(),
+        Payload::Str(_) => vec![rest[0].clone()],
+        _ => {
+            let message = format!(
+                "{} first arg must be str or a tuple of str, not {}",
+                name,
+                rest[0].type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    for affix in &affixes {
+        let affix = match affix.as_str() {
+            Some(affix) => affix,
+            None => {
+                let message = format!(
+                    "tuple for {} must only contain str, not {}",
+                    name,
+                    affix.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        };
+        if let Some((slice, _)) = search_slice(vm, text, &rest[1..], affix)? {
+            if matches(slice, affix) {
+                return Ok(vm.new_bool(true));
+            }
+        }
+    }
+    Ok(vm.new_bool(false))
+}
+
+/// The `maxsplit` argument of `split` and `rsplit`: the most splits to
+/// make, or `None` for no limit.
+fn max_splits(vm: &mut Vm, object: Option<&ObjectRef>) -> PyResult<Option<usize>> {
+    match object {
+        None => Ok(None),
+        Some(object) => Ok(usize::try_from(index_value(vm, object)?).ok()),
+    }
+}
+
+/// The `sep` argument of `split` and `rsplit`, `None` for runs of
+/// whitespace.
+fn separator<'a>(vm: &mut Vm, object: Option<&'a ObjectRef>) -> PyResult<Option<&'a str>> {
+    match object {
+        None => Ok(None),
+        Some(object) => match object.payload {
+            Payload::None => Ok(None),
+            Payload::Str(ref separator) if separator.is_empty() => {
+                Err(vm.new_value_error("empty separator".to_string()))
+            }
+            Payload::Str(ref separator) => Ok(Some(separator)),
+            _ => {
+                let message = format!("must be str or None, not {}", object.type_name());
+                Err(vm.new_type_error(message))
+            }
+        },
+    }
+}
+
+/// `str.split(sep=None, maxsplit=-1)`.
+fn split(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "split", &["sep", "maxsplit"])?;
+    let separator = separator(vm, values[0].as_ref())?;
+    let limit = max_splits(vm, values[1].as_ref())?;
+    let text = this.as_str().unwrap();
+    let parts: Vec<&str> = match (separator, limit) {
+        (Some(separator), Some(limit)) => text.splitn(limit + 1, separator).collect(),
+        (Some(separator), None) => text.split(separator).collect(),
+        (None, limit) => {
+            let mut parts = Vec::new();
+            let mut rest = text.trim_start_matches(unicode::is_space);
+            while !rest.is_empty() {
+                if Some(parts.len()) == limit {
+                    parts.push(rest);
+                    break;
+                }
+                let end = rest.find(unicode::is_space).unwrap_or(rest.len());
+                parts.push(&rest[..end]);
+                rest = rest[end..].trim_start_matches(unicode::is_space);
+            }
+            parts
+        }
+    };
+    let parts = parts.into_iter().map(|part| vm.new_str(part)).collect();
+    Ok(vm.new_list(parts))
+}
+
+/// `str.rsplit(sep=None, maxsplit=-1)`, which splits from the end.
+fn rsplit(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "rsplit", &["sep", "maxsplit"])?;
+    let separator = separator(vm, values[0].as_ref())?;
+    let limit = max_splits(vm, values[1].as_ref())?;
+    let text = this.as_str().unwrap();
+    let mut parts: Vec<&str> = match (separator, limit) {
+        (Some(separator), Some(limit)) => text.rsplitn(limit + 1, separator).collect(),
+        (Some(separator), None) => text.rsplit(separator).collect(),
+        (None, limit) => {
+            let mut parts = Vec::new();
+            let mut rest = text.trim_end_matches(unicode::is_space);
+            while !rest.is_empty() {
+                if Some(parts.len()) == limit {
+                    parts.push(rest);
+                    break;
+                }
+                let start = rest
+                    .rfind(unicode::is_space)
+                    .map_or(0, |at| at + rest[at..].chars().next().unwrap().len_utf8());
+                parts.push(&rest[start..]);
+                rest = rest[..start].trim_end_matches(unicode::is_space);
+            }
+            parts
+        }
+    };
+    parts.reverse();
+    let parts = parts.into_iter().map(|part| vm.new_str(part)).collect();
+    Ok(vm.new_list(parts))
+}
+
+/// `str.splitlines(keepends=False)`, which splits at every line boundary
+/// Python recognizes, not just `\n`.
+fn splitlines(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "splitlines", &["keepends"])?;
+    let keep_ends = match values[0] {
+        Some(ref value) => index_value(vm, value)? != 0,
+        None => false,
+    };
+    let lines = split_lines(this.as_str().unwrap(), keep_ends)
+        .into_iter()
+        .map(|line| vm.new_str(line))
+        .collect();
+    Ok(vm.new_list(lines))
+}
+
+/// The lines of `text`, split at every line boundary `str.splitlines`
+/// recognizes, with their line breaks if `keep_ends`.
+pub(super) fn split_lines(text: &str, keep_ends: bool) -> Vec<&str> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((offset, c)) = chars.next() {
+        let mut end = offset + c.len_utf8();
+        match c {
+            '\r' => {
+                if let Some(&(_, '\n')) = chars.peek() {
+                    chars.next();
+                    end += 1;
+                }
+            }
+            '\n' | '\x0b' | '\x0c' | '\x1c' | '\x1d' | '\x1e' | '\u{85}' | '\u{2028}'
+            | '\u{2029}' => {}
+            _ => continue,
+        }
+        lines.push(if keep_ends {
+            &text[start..end]
+        } else {
+            &text[start..offset]
+        });
+        start = end;
+    }
+    if start < text.len() {
+        lines.push(&text[start..]);
+    }
+    lines
+}
+
+/// `str.join(iterable)`.
+fn join(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, iterable) = single_argument(vm, args, "join")?;
+    if !vm.is_iterable(&iterable) {
+        return Err(vm.new_type_error("can only join an iterable".to_string()));
+    }
+    let items = vm.iterate(&iterable)?;
+    let separator = this.as_str().unwrap();
+    let mut joined = String::new();
+    for (index, item) in items.iter().enumerate() {
+        let item = match item.as_str() {
+            Some(item) => item,
+            None => {
+                let message = format!(
+                    "sequence item {}: expected str instance, {} found",
+                    index,
+                    item.type_name()
+                );
+                return Err(vm.new_type_error(message));
+            }
+        };
+        if index > 0 {
+            joined.push_str(separator);
+        }
+        joined.push_str(item);
+    }
+    Ok(vm.new_str(&joined))
+}
+
+/// The characters `strip` and its variants remove: those of the argument,
+/// or whitespace.
+fn strip_with(vm: &mut Vm, args: Args, name: &str, left: bool, right: bool) -> PyResult {
+    let (this, rest) = positional_arguments(vm, args, name, 0, 1)?;
+    let text = this.as_str().unwrap();
+    let chars: Option<Vec<char>> = match rest.first().map(|chars| &chars.payload) {
+        None | Some(Payload::None) => None,
+        Some(Payload::Str(ref chars)) => Some(chars.chars().collect()),
+        Some(_) => {
+            let message = format!("{} arg must be None or str", name);
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let strips = |c: char| match chars {
+        Some(ref chars) => chars.contains(&c),
+        None => unicode::is_space(c),
+    };
+    let mut stripped = text;
+    if left {
+        stripped = stripped.trim_start_matches(strips);
+    }
+    if right {
+        stripped = stripped.trim_end_matches(strips);
+    }
+    Ok(vm.new_str(stripped))
+}
+
+/// `str.strip(chars=None)`.
+fn strip(vm: &mut Vm, args: Args) -> PyResult {
+    strip_with(vm, args, "strip", true, true)
+}
+
+fn lstrip(vm: &mut Vm, args: Args) -> PyResult {
+    strip_with(vm, args, "lstrip", true, false)
+}
+
+fn rstrip(vm: &mut Vm, args: Args) -> PyResult {
+    strip_with(vm, args, "rstrip", false, true)
+}
+
+/// `str.replace(old, new, count=-1)`.
+fn replace(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, rest) = positional_arguments(vm, args, "replace", 2, 3)?;
+    let old = named_str_argument(vm, &rest[0], "replace", "argument 1")?;
+    let new = named_str_argument(vm, &rest[1], "replace", "argument 2")?;
+    let limit = match rest.get(2) {
+        Some(count) => usize::try_from(index_value(vm, count)?).ok(),
+        None => None,
+    };
+    let text = this.as_str().unwrap();
+    let replaced = match limit {
+        Some(limit) => text.replacen(old, new, limit),
+        None => text.replace(old, new),
+    };
+    Ok(vm.new_str(&replaced))
+}
+
+/// `str.partition(sep)`: the text before the first `sep`, `sep` and the
+/// text after it.
+fn partition(vm: &mut Vm, args: Args) -> PyResult {
+    partition_with(vm, args, "partition", false)
+}
+
+fn rpartition(vm: &mut Vm, args: Args) -> PyResult {
+    partition_with(vm, args, "rpartition", true)
+}
+
+fn partition_with(vm: &mut Vm, args: Args, name: &str, reverse: bool) -> PyResult {
+    let (this, separator) = single_argument(vm, args, name)?;
+    let separator = str_argument(vm, &separator)?;
+    if separator.is_empty() {
+        return Err(vm.new_value_error("empty separator".to_string()));
+    }
+    let text = this.as_str().unwrap();
+    let found = if reverse {
+        text.rfind(separator)
+    } else {
+        text.find(separator)
+    };
+    let parts = match found {
+        Some(at) => [&text[..at], separator, &text[at + separator.len()..]],
+        None if reverse => ["", "", text],
+        None => [text, "", ""],
+    };
+    let parts = parts.iter().map(|part| vm.new_str(part)).collect();
+    Ok(vm.new_tuple(parts))
+}
+
+/// `str.removeprefix(prefix)`.
+fn removeprefix(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, prefix) = single_argument(vm, args, "removeprefix")?;
+    let prefix = match prefix.as_str() {
+        Some(prefix) => prefix,
+        None => return Err(affix_type_error(vm, "removeprefix", &prefix)),
+    };
+    let text = this.as_str().unwrap();
+    Ok(vm.new_str(text.strip_prefix(prefix).unwrap_or(text)))
+}
+
+fn removesuffix(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, suffix) = single_argument(vm, args, "removesuffix")?;
+    let suffix = match suffix.as_str() {
+        Some(suffix) => suffix,
+        None => return Err(affix_type_error(vm, "removesuffix", &suffix)),
+    };
+    let text = this.as_str().unwrap();
+    Ok(vm.new_str(text.strip_suffix(suffix).unwrap_or(text)))
+}
+
+fn affix_type_error(vm: &mut Vm, name: &str, argument: &ObjectRef) -> ObjectRef {
+    let message = format!(
+        "{}() argument must be str, not {}",
+        name,
+        argument.type_name()
+    );
+    vm.new_type_error(message)
+}
+
+/// `str.center(width, fillchar=' ')`.
+fn center(vm: &mut Vm, args: Args) -> PyResult {
+    pad(vm, args, "center", |margin, width| {
+        let left = margin / 2 + (margin & width & 1);
+        (left, margin - left)
+    })
+}
+
+fn ljust(vm: &mut Vm, args: Args) -> PyResult {
+    pad(vm, args, "ljust", |margin, _| (0, margin))
+}
+
+fn rjust(vm: &mut Vm, args: Args) -> PyResult {
+    pad(vm, args, "rjust", |margin, _| (margin, 0))
+}
+
+/// Pads the string to a width with the fill character, putting as much of
+/// the padding on each side as `sides` says.
+fn pad(vm: &mut Vm, args: Args, name: &str, sides: fn(usize, usize) -> (usize, usize)) -> PyResult {
+    let (this, rest) = positional_arguments(vm, args, name, 1, 2)?;
+    let width = index_value(vm, &rest[0])?;
+    let fill = match rest.get(1) {
+        Some(fill) => fill_char(vm, fill)?,
+        None => ' ',
+    };
+    let text = this.as_str().unwrap();
+    let len = char_count(text);
+    let width = usize::try_from(width).unwrap_or(0);
+    if width <= len {
+        return Ok(vm.new_str(text));
+    }
+    let (left, right) = sides(width - len, width);
+    let mut padded = String::with_capacity(text.len() + (left + right) * fill.len_utf8());
+    padded.extend((0..left).map(|_| fill));
+    padded.push_str(text);
+    padded.extend((0..right).map(|_| fill));
+    Ok(vm.new_str(&padded))
+}
+
+fn fill_char(vm: &mut Vm, fill: &ObjectRef) -> PyResult<char> {
+    let fill_text = match fill.as_str() {
+        Some(fill) => fill,
+        None => {
+            let message = format!(
+                "The fill character must be a unicode character, not {}",
+                fill.type_name()
+            );
+            return Err(vm.new_type_error(message));
+        }
+    };
+    let mut chars = fill_text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => {
+            let message = "The fill character must be exactly one character long";
+            Err(vm.new_type_error(message.to_string()))
+        }
+    }
+}
+
+/// `str.zfill(width)`, which pads with zeros after any sign.
+fn zfill(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, width) = single_argument(vm, args, "zfill")?;
+    let width = index_value(vm, &width)?;
+    let text = this.as_str().unwrap();
+    let len = char_count(text);
+    let width = usize::try_from(width).unwrap_or(0);
+    if width <= len {
+        return Ok(vm.new_str(text));
+    }
+    let mut filled = String::with_capacity(width);
+    let digits = match text.chars().next() {
+        Some(sign @ '+') | Some(sign @ '-') => {
+            filled.push(sign);
+            &text[1..]
+        }
+        _ => text,
+    };
+    filled.extend((len..width).map(|_| '0'));
+    filled.push_str(digits);
+    Ok(vm.new_str(&filled))
+}
+
+/// `str.expandtabs(tabsize=8)`, which replaces each tab with the spaces up
+/// to the next tab stop. Columns restart after each line break.
+fn expandtabs(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "expandtabs", &["tabsize"])?;
+    let tab_size = match values[0] {
+        Some(ref size) => index_value(vm, size)?,
+        None => 8,
+    };
+    let expanded = expand_tabs(this.as_str().unwrap(), tab_size);
+    Ok(vm.new_str(&expanded))
+}
+
+/// `text` with each tab replaced by spaces up to the next multiple of
+/// `tab_size` columns, as `str.expandtabs`.
+pub(super) fn expand_tabs(text: &str, tab_size: i64) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    let mut column = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                if tab_size > 0 {
+                    let spaces = tab_size - column % tab_size;
+                    expanded.extend((0..spaces).map(|_| ' '));
+                    column += spaces;
+                }
+            }
+            '\n' | '\r' => {
+                expanded.push(c);
+                column = 0;
+            }
+            _ => {
+                expanded.push(c);
+                column += 1;
+            }
+        }
+    }
+    expanded
+}
+
+/// `str.lower()`.
+fn lower(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "lower")?;
+    Ok(vm.new_str(&this.as_str().unwrap().to_lowercase()))
+}
+
+fn upper(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "upper")?;
+    Ok(vm.new_str(&this.as_str().unwrap().to_uppercase()))
+}
+
+/// `str.casefold()`, a lowercase form for comparing strings without
+/// regard to case, in which `ß` is `ss`.
+fn casefold(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "casefold")?;
+    let mut folded = String::new();
+    for c in this.as_str().unwrap().chars() {
+        unicode::push_casefold(c, &mut folded);
+    }
+    Ok(vm.new_str(&folded))
+}
+
+/// `str.capitalize()`: the first character in titlecase and the rest in
+/// lowercase.
+fn capitalize(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "capitalize")?;
+    let chars: Vec<char> = this.as_str().unwrap().chars().collect();
+    let mut capitalized = String::new();
+    for (index, &c) in chars.iter().enumerate() {
+        if index == 0 {
+            unicode::push_title(c, &mut capitalized);
+        } else {
+            unicode::push_lower(&chars, index, &mut capitalized);
+        }
+    }
+    Ok(vm.new_str(&capitalized))
+}
+
+/// `str.title()`: each character after an uncased one in titlecase, and
+/// the others in lowercase.
+fn title(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "title")?;
+    let chars: Vec<char> = this.as_str().unwrap().chars().collect();
+    let mut titled = String::new();
+    let mut previous_is_cased = false;
+    for (index, &c) in chars.iter().enumerate() {
+        if previous_is_cased {
+            unicode::push_lower(&chars, index, &mut titled);
+        } else {
+            unicode::push_title(c, &mut titled);
+        }
+        previous_is_cased = unicode::is_cased(c);
+    }
+    Ok(vm.new_str(&titled))
+}
+
+fn swapcase(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "swapcase")?;
+    let chars: Vec<char> = this.as_str().unwrap().chars().collect();
+    let mut swapped = String::new();
+    for (index, &c) in chars.iter().enumerate() {
+        if c.is_uppercase() {
+            unicode::push_lower(&chars, index, &mut swapped);
+        } else if c.is_lowercase() {
+            swapped.extend(c.to_uppercase());
+        } else {
+            swapped.push(c);
+        }
+    }
+    Ok(vm.new_str(&swapped))
+}
+
+/// A predicate method that holds if the string is not empty and every
+/// character passes `test`.
+fn every_char(vm: &mut Vm, args: Args, name: &str, test: fn(char) -> bool) -> PyResult {
+    let this = no_arguments(vm, args, name)?;
+    let text = this.as_str().unwrap();
+    Ok(vm.new_bool(!text.is_empty() && text.chars().all(test)))
+}
+
+fn isalnum(vm: &mut Vm, args: Args) -> PyResult {
+    every_char(vm, args, "isalnum", |c| {
+        unicode::is_alpha(c) || unicode::is_numeric(c)
+    })
+}
+
+fn isalpha(vm: &mut Vm, args: Args) -> PyResult {
+    every_char(vm, args, "isalpha", unicode::is_alpha)
+}
+
+fn isdecimal(vm: &mut Vm, args: Args) -> PyResult {
+    every_char(vm, args, "isdecimal", unicode::is_decimal)
+}
+
+fn isdigit(vm: &mut Vm, args: Args) -> PyResult {
+    every_char(vm, args, "isdigit", unicode::is_digit)
+}
+
+fn isnumeric(vm: &mut Vm, args: Args) -> PyResult {
+    every_char(vm, args, "isnumeric", unicode::is_numeric)
+}
+
+fn isspace(vm: &mut Vm, args: Args) -> PyResult {
+    every_char(vm, args, "isspace", unicode::is_space)
+}
+
+/// `str.isascii()`, which also holds for the empty string.
+fn isascii(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "isascii")?;
+    Ok(vm.new_bool(this.as_str().unwrap().is_ascii()))
+}
+
+/// `str.isprintable()`, which also holds for the empty string.
+fn isprintable(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "isprintable")?;
+    let printable = this.as_str().unwrap().chars().all(is_printable);
+    Ok(vm.new_bool(printable))
+}
+
+/// `str.isidentifier()`, which holds for keywords too.
+fn isidentifier(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "isidentifier")?;
+    let mut chars = this.as_str().unwrap().chars();
+    let identifier =
+        chars.next().is_some_and(is_identifier_start) && chars.all(is_identifier_continue);
+    Ok(vm.new_bool(identifier))
+}
+
+/// `str.islower()`: the string has a lowercase character and no uppercase
+/// or titlecase ones.
+fn islower(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "islower")?;
+    let mut cased = false;
+    for c in this.as_str().unwrap().chars() {
+        if c.is_uppercase() || unicode::is_titlecase(c) {
+            return Ok(vm.new_bool(false));
+        }
+        cased |= c.is_lowercase();
+    }
+    Ok(vm.new_bool(cased))
+}
+
+fn isupper(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "isupper")?;
+    let mut cased = false;
+    for c in this.as_str().unwrap().chars() {
+        if c.is_lowercase() || unicode::is_titlecase(c) {
+            return Ok(vm.new_bool(false));
+        }
+        cased |= c.is_uppercase();
+    }
+    Ok(vm.new_bool(cased))
+}
+
+/// `str.istitle()`: uppercase and titlecase characters follow only uncased
+/// ones, lowercase characters only cased ones, and there is a cased one.
+fn istitle(vm: &mut Vm, args: Args) -> PyResult {
+    let this = no_arguments(vm, args, "istitle")?;
+    let mut cased = false;
+    let mut previous_is_cased = false;
+    for c in this.as_str().unwrap().chars() {
+        if c.is_uppercase() || unicode::is_titlecase(c) {
+            if previous_is_cased {
+                return Ok(vm.new_bool(false));
+            }
+            previous_is_cased = true;
+            cased = true;
+        } else if c.is_lowercase() {
+            if !previous_is_cased {
+                return Ok(vm.new_bool(false));
+            }
+            previous_is_cased = true;
+            cased = true;
+        } else {
+            previous_is_cased = false;
+        }
+    }
+    Ok(vm.new_bool(cased))
+}
+
+/// `str.translate(table)`: each character whose code point `table` maps
+/// is replaced by the string or code point it maps to, or removed if that
+/// is `None`.
+fn translate(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, table) = single_argument(vm, args, "translate")?;
+    let mut translated = String::new();
+    for c in this.as_str().unwrap().chars() {
+        let key = vm.new_int(c as i64);
+        let mapped = match vm.getitem(&table, &key) {
+            Ok(mapped) => mapped,
+            Err(error) if Vm::is_instance(&error, &vm.exceptions.lookup_error) => {
+                translated.push(c);
+                continue;
+            }
+            Err(error) => return Err(error),
+        };
+        match mapped.payload {
+            Payload::None => {}
+            Payload::Str(ref replacement) => translated.push_str(replacement),
+            Payload::Int(code) => {
+                if !(0..0x11_0000).contains(&code) {
+                    let message = "character mapping must be in range(0x110000)";
+                    return Err(vm.new_value_error(message.to_string()));
+                }
+                match ::std::char::from_u32(code as u32) {
+                    Some(c) => translated.push(c),
+                    None => {
+                        let message = "surrogate code points are not supported";
+                        return Err(vm.new_value_error(message.to_string()));
+                    }
+                }
+            }
+            _ => {
+                let message = "character mapping must return integer, None or str";
+                return Err(vm.new_type_error(message.to_string()));
+            }
+        }
+    }
+    Ok(vm.new_str(&translated))
+}
+
+/// `str.maketrans(x[, y[, z]])`, a static method that makes a table for
+/// `translate`: from a dict whose keys may be characters, or mapping the
+/// characters of `x` to those of `y` and those of `z` to `None`.
+fn maketrans(vm: &mut Vm, args: Args) -> PyResult {
+    if !args.keywords.is_empty() {
+        let message = "maketrans() takes no keyword arguments".to_string();
+        return Err(vm.new_type_error(message));
+    }
+    check_count(vm, &args, "maketrans", 1, 3)?;
+    let table = vm.new_dict();
+    let positional = &args.positional;
+    if positional.len() == 1 {
+        let entries: Vec<(ObjectRef, ObjectRef)> = match positional[0].as_dict() {
+            Some(dict) => dict
+                .borrow()
+                .iter()
+                .map(|entry| (entry.key.clone(), entry.value.clone()))
+                .collect(),
+            None => {
+                let message = "if you give only one argument to maketrans it must be a dict";
+                return Err(vm.new_type_error(message.to_string()));
+            }
+        };
+        for (key, value) in entries {
+            let key = match key.payload {
+                Payload::Int(_) => key.clone(),
+                Payload::Str(ref text) => {
+                    let mut chars = text.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(c), None) => vm.new_int(c as i64),
+                        _ => {
+                            let message = "string keys in translate table must be of length 1";
+                            return Err(vm.new_value_error(message.to_string()));
+                        }
+                    }
+                }
+                _ => {
+                    let message = "keys in translate table must be strings or integers";
+                    return Err(vm.new_type_error(message.to_string()));
+                }
+            };
+            vm.dict_set(table.as_dict().unwrap(), key, value)?;
+        }
+        return Ok(table);
+    }
+    let from = match positional[0].as_str() {
+        Some(from) => from,
+        None => {
+            let message = "first maketrans argument must be a string if there is a second argument";
+            return Err(vm.new_type_error(message.to_string()));
+        }
+    };
+    let to = named_str_argument(vm, &positional[1], "maketrans", "argument 2")?;
+    if char_count(from) != char_count(to) {
+        let message = "the first two maketrans arguments must have equal length";
+        return Err(vm.new_value_error(message.to_string()));
+    }
+    for (from, to) in from.chars().zip(to.chars()) {
+        let key = vm.new_int(from as i64);
+        let value = vm.new_int(to as i64);
+        vm.dict_set(table.as_dict().unwrap(), key, value)?;
+    }
+    if let Some(deleted) = positional.get(2) {
+        let deleted = named_str_argument(vm, deleted, "maketrans", "argument 3")?;
+        for c in deleted.chars() {
+            let key = vm.new_int(c as i64);
+            let none = vm.none();
+            vm.dict_set(table.as_dict().unwrap(), key, none)?;
+        }
+    }
+    Ok(table)
+}
+
+/// `str.encode(encoding='utf-8', errors='strict')`.
+fn encode(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, values) = named_arguments(vm, args, "encode", &["encoding", "errors"])?;
+    let encoding = match values[0] {
+        Some(ref encoding) => named_str_argument(vm, encoding, "encode", "argument 'encoding'")?,
+        None => "utf-8",
+    };
+    let errors = match values[1] {
+        Some(ref errors) => named_str_argument(vm, errors, "encode", "argument 'errors'")?,
+        None => "strict",
+    };
+    let text = this.as_str().unwrap();
+    let normalized = encoding.to_ascii_lowercase().replace(['_', ' '], "-");
+    let bytes = match normalized.as_str() {
+        "utf-8" | "utf8" | "u8" | "utf" | "cp65001" => text.as_bytes().to_vec(),
+        "ascii" | "us-ascii" | "646" => encode_narrow(vm, text, "ascii", 0x80, errors)?,
+        "latin-1" | "latin1" | "latin" | "l1" | "iso-8859-1" | "iso8859-1" | "8859" | "cp819" => {
+            encode_narrow(vm, text, "latin-1", 0x100, errors)?
+        }
+        "utf-16" | "utf16" => {
+            let mut bytes = vec![0xff, 0xfe];
+            bytes.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+            bytes
+        }
+        "utf-16-le" | "utf-16le" => text.encode_utf16().flat_map(u16::to_le_bytes).collect(),
+        "utf-16-be" | "utf-16be" => text.encode_utf16().flat_map(u16::to_be_bytes).collect(),
+        "utf-32" | "utf32" => {
+            let mut bytes = vec![0xff, 0xfe, 0, 0];
+            bytes.extend(text.chars().flat_map(|c| (c as u32).to_le_bytes()));
+            bytes
+        }
+        "utf-32-le" | "utf-32le" => text
+            .chars()
+            .flat_map(|c| (c as u32).to_le_bytes())
+            .collect(),
+        "utf-32-be" | "utf-32be" => text
+            .chars()
+            .flat_map(|c| (c as u32).to_be_bytes())
+            .collect(),
+        _ => {
+            let class = vm.exceptions.lookup_error.clone();
+            return Err(vm.new_error(&class, format!("unknown encoding: {}", encoding)));
+        }
+    };
+    Ok(vm.new_bytes(bytes))
+}
+
+/// Encodes `text` one byte per character, for an encoding of the first
+/// `limit` code points, handling the others as `errors` says.
+fn encode_narrow(
+    vm: &mut Vm,
+    text: &str,
+    codec: &str,
+    limit: u32,
+    errors: &str,
+) -> PyResult<Vec<u8>> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut bytes = Vec::with_capacity(chars.len());
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        if (c as u32) < limit {
+            bytes.push(c as u8);
+            index += 1;
+            continue;
+        }
+        match errors {
+            "strict" => {
+                let end = chars[index..]
+                    .iter()
+                    .position(|&c| (c as u32) < limit)
+                    .map_or(chars.len(), |length| index + length);
+                let message = if end - index == 1 {
+                    format!(
+                        "'{}' codec can't encode character '{}' in position {}: ordinal not in range({})",
+                        codec,
+                        escape_char(c),
+                        index,
+                        limit
+                    )
+                } else {
+                    format!(
+                        "'{}' codec can't encode characters in position {}-{}: ordinal not in range({})",
+                        codec,
+                        index,
+                        end - 1,
+                        limit
+                    )
+                };
+                let class = vm.exceptions.unicode_encode_error.clone();
+                return Err(vm.new_error(&class, message));
+            }
+            "ignore" => {}
+            "replace" => bytes.push(b'?'),
+            "xmlcharrefreplace" => bytes.extend(format!("&#{};", c as u32).bytes()),
+            "backslashreplace" => bytes.extend(escape_char(c).bytes()),
+            _ => {
+                let class = vm.exceptions.lookup_error.clone();
+                let message = format!("unknown error handler name '{}'", errors);
+                return Err(vm.new_error(&class, message));
+            }
+        }
+        index += 1;
+    }
+    Ok(bytes)
+}
+
+/// A character as a `\x`, `\u` or `\U` escape.
+fn escape_char(c: char) -> String {
+    match c as u32 {
+        code @ 0..=0xff => format!("\\x{:02x}", code),
+        code @ 0x100..=0xffff => format!("\\u{:04x}", code),
+        code => format!("\\U{:08x}", code),
+    }
+}
+
+/// Where `str.format` and `str.format_map` find the values of replacement
+/// fields.
+struct FormatArguments<'a> {
+    /// `None` for `format_map`, which has no positional arguments.
+    positional: Option<&'a [ObjectRef]>,
+    named: Named<'a>,
+    /// Whether fields are numbered automatically, as `{}`, or by hand, as
+    /// `{0}`, once the first numbered field decides; and the next
+    /// automatic number.
+    numbering: Option<bool>,
+    next_number: usize,
+}
+
+enum Named<'a> {
+    Keywords(&'a [(String, ObjectRef)]),
+    Mapping(&'a ObjectRef),
+}
+
+/// `str.format(*args, **kwargs)`.
+fn format(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, args) = receiver(vm, args, "format")?;
+    let mut arguments = FormatArguments {
+        positional: Some(&args.positional),
+        named: Named::Keywords(&args.keywords),
+        numbering: None,
+        next_number: 0,
+    };
+    let formatted = format_string(
+        vm,
+        this.as_str().unwrap(),
+        &mut arguments,
+        FORMAT_RECURSION_DEPTH,
+    )?;
+    Ok(vm.new_str(&formatted))
+}
+
+/// `str.format_map(mapping)`, which takes named fields from `mapping`.
+fn format_map(vm: &mut Vm, args: Args) -> PyResult {
+    let (this, mapping) = single_argument(vm, args, "format_map")?;
+    let mut arguments = FormatArguments {
+        positional: None,
+        named: Named::Mapping(&mapping),
+        numbering: None,
+        next_number: 0,
+    };
+    let formatted = format_string(
+        vm,
+        this.as_str().unwrap(),
+        &mut arguments,
+        FORMAT_RECURSION_DEPTH,
+    )?;
+    Ok(vm.new_str(&formatted))
+}
+
+/// Replaces the fields of a format string, or of a format specification
+/// that has fields of its own, which may nest `depth` more levels.
+fn format_string(
+    vm: &mut Vm,
+    text: &str,
+    arguments: &mut FormatArguments,
+    depth: usize,
+) -> PyResult<String> {
+    if depth == 0 {
+        return Err(vm.new_value_error("Max string recursion exceeded".to_string()));
+    }
+    let bytes = text.as_bytes();
+    let mut formatted = String::new();
+    let mut position = 0;
+    while position < bytes.len() {
+        let start = position;
+        let brace = bytes[position..]
+            .iter()
+            .position(|&b| b == b'{' || b == b'}')
+            .map(|at| position + at);
+        let brace = match brace {
+            Some(brace) => brace,
+            None => {
+                formatted.push_str(&text[start..]);
+                break;
+            }
+        };
+        formatted.push_str(&text[start..brace]);
+        let c = bytes[brace];
+        position = brace + 1;
+        if bytes.get(position) == Some(&c) {
+            formatted.push(c as char);
+            position += 1;
+            continue;
+        }
+        if c == b'}' {
+            let message = "Single '}' encountered in format string";
+            return Err(vm.new_value_error(message.to_string()));
+        }
+        if position == bytes.len() {
+            let message = "Single '{' encountered in format string";
+            return Err(vm.new_value_error(message.to_string()));
+        }
+        let field = parse_field(vm, text, &mut position)?;
+        let value = field_value(vm, field.name, arguments)?;
+        let conversion = match field.conversion {
+            None | Some('r') | Some('s') | Some('a') => field.conversion,
+            Some(other) => {
+                let message = if ('!'..'\x7f').contains(&other) {
+                    format!("Unknown conversion specifier {}", other)
+                } else {
+                    format!("Unknown conversion specifier \\x{:x}", other as u32)
+                };
+                return Err(vm.new_value_error(message));
+            }
+        };
+        let spec = if field.spec_has_fields {
+            format_string(vm, field.spec, arguments, depth - 1)?
+        } else {
+            field.spec.to_string()
+        };
+        let spec = if spec.is_empty() {
+            None
+        } else {
+            Some(vm.new_str(&spec))
+        };
+        let value = vm.format_value(&value, conversion, spec.as_ref())?;
+        formatted.push_str(value.as_str().unwrap());
+    }
+    Ok(formatted)
+}
+
+/// A replacement field of a format string: `{name!conversion:spec}`.
+struct Field<'a> {
+    name: &'a str,
+    conversion: Option<char>,
+    spec: &'a str,
+    /// Whether the specification has replacement fields of its own.
+    spec_has_fields: bool,
+}
+
+/// Parses the replacement field after the `{` at `position - 1`, leaving
+/// `position` after its `}`.
+fn parse_field<'a>(vm: &mut Vm, text: &'a str, position: &mut usize) -> PyResult<Field<'a>> {
+    let bytes = text.as_bytes();
+    let name_start = *position;
+    let mut c = 0;
+    while *position < bytes.len() {
+        c = bytes[*position];
+        *position += 1;
+        match c {
+            b'{' => {
+                let message = "unexpected '{' in field name";
+                return Err(vm.new_value_error(message.to_string()));
+            }
+            b'[' => {
+                while *position < bytes.len() && bytes[*position] != b']' {
+                    *position += 1;
+                }
+            }
+            b'}' | b':' | b'!' => break,
+            _ => {}
+        }
+    }
+    let name = &text[name_start..*position - 1];
+    let mut field = Field {
+        name,
+        conversion: None,
+        spec: "",
+        spec_has_fields: false,
+    };
+    if c != b'!' && c != b':' {
+        if c != b'}' {
+            let message = "expected '}' before end of string";
+            return Err(vm.new_value_error(message.to_string()));
+        }
+        return Ok(field);
+    }
+    if c == b'!' {
+        let conversion = match text[*position..].chars().next() {
+            Some(conversion) => conversion,
+            None => {
+                let message = "end of string while looking for conversion specifier";
+                return Err(vm.new_value_error(message.to_string()));
+            }
+        };
+        field.conversion = Some(conversion);
+        *position += conversion.len_utf8();
+        if *position < bytes.len() {
+            let next = bytes[*position];
+            *position += 1;
+            if next == b'}' {
+                return Ok(field);
+            }
+            if next != b':' {
+                let message = "expected ':' after conversion specifier";
+                return Err(vm.new_value_error(message.to_string()));
+            }
+        }
+    }
+    let spec_start = *position;
+    let mut depth = 1;
+    while *position < bytes.len() {
+        let c = bytes[*position];
+        *position += 1;
+        match c {
+            b'{' => {
+                field.spec_has_fields = true;
+                depth += 1;
+            }
+            b'}' => {
+                depth -= 1;
+                if depth == 0 {
+                    field.spec = &text[spec_start..*position - 1];
+                    return Ok(field);
+                }
+            }
+            _ => {}
+        }
+    }
+    let message = "unmatched '{' in format spec";
+    Err(vm.new_value_error(message.to_string()))
+}
+
+/// A field name's number, if it is all ASCII digits.
+fn field_number(vm: &mut Vm, name: &str) -> PyResult<Option<usize>> {
+    if name.is_empty() || !name.bytes().all(|b| b.is_ascii_digit()) {
+        return Ok(None);
+    }
+    match name.parse() {
+        Ok(number) => Ok(Some(number)),
+        Err(_) => {
+            let message = "Too many decimal digits in format string";
+            Err(vm.new_value_error(message.to_string()))
+        }
+    }
+}
+
+/// The value a field name refers to: an argument, by number or name, and
+/// then its attributes and items.
+fn field_value(vm: &mut Vm, name: &str, arguments: &mut FormatArguments) -> PyResult {
+    let first_end = name.find(['.', '[']).unwrap_or(name.len());
+    let (first, mut rest) = name.split_at(first_end);
+    let mut number = field_number(vm, first)?;
+    if first.is_empty() || number.is_some() {
+        let automatic = first.is_empty();
+        match arguments.numbering {
+            None => arguments.numbering = Some(automatic),
+            Some(true) if !automatic => {
+                let message =
+                    "cannot switch from automatic field numbering to manual field specification";
+                return Err(vm.new_value_error(message.to_string()));
+            }
+            Some(false) if automatic => {
+                let message =
+                    "cannot switch from manual field specification to automatic field numbering";
+                return Err(vm.new_value_error(message.to_string()));
+            }
+            Some(_) => {}
+        }
+        if automatic {
+            number = Some(arguments.next_number);
+            arguments.next_number += 1;
+        }
+    }
+    let mut value = match number {
+        Some(number) => {
+            let positional = match arguments.positional {
+                Some(positional) => positional,
+                None => {
+                    let message = "Format string contains positional fields";
+                    return Err(vm.new_value_error(message.to_string()));
+                }
+            };
+            match positional.get(number) {
+                Some(value) => value.clone(),
+                None => {
+                    let message = format!(
+                        "Replacement index {} out of range for positional args tuple",
+                        number
+                    );
+                    return Err(vm.new_index_error(message));
+                }
+            }
+        }
+        None => match arguments.named {
+            Named::Keywords(keywords) => {
+                match keywords.iter().find(|(keyword, _)| keyword == first) {
+                    Some((_, value)) => value.clone(),
+                    None => {
+                        let key = vm.new_str(first);
+                        return Err(vm.new_key_error(key));
+                    }
+                }
+            }
+            Named::Mapping(mapping) => {
+                let key = vm.new_str(first);
+                vm.getitem(mapping, &key)?
+            }
+        },
+    };
+    while !rest.is_empty() {
+        let (is_attribute, part) = if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            rest = &after[end..];
+            (true, &after[..end])
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = match after.find(']') {
+                Some(end) => end,
+                None => {
+                    let message = "Missing ']' in format string";
+                    return Err(vm.new_value_error(message.to_string()));
+                }
+            };
+            rest = &after[end + 1..];
+            (false, &after[..end])
+        } else {
+            let message = "Only '.' or '[' may follow ']' in format field specifier";
+            return Err(vm.new_value_error(message.to_string()));
+        };
+        if part.is_empty() {
+            let message = "Empty attribute in format string";
+            return Err(vm.new_value_error(message.to_string()));
+        }
+        value = if is_attribute {
+            vm.getattr(&value, part)?
+        } else {
+            let key = match field_number(vm, part)? {
+                Some(index) => vm.new_int(index as i64),
+                None => vm.new_str(part),
+            };
+            vm.getitem(&value, &key)?
+        };
+    }
+    Ok(value)
+}
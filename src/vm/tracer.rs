@@ -0,0 +1,100 @@
+//! Hooks in the evaluation loop for debuggers, profilers and coverage
+//! tools, as `sys.settrace` has them.
+//!
+//! A `Tracer` set with `Vm::set_tracer` hears of every frame that starts or
+//! resumes, of each new line it runs, of each exception raised in it, and
+//! of its return, with a frame object as Python code sees it. Tracing is
+//! off while the tracer runs, so the Python code it calls is not traced,
+//! and a tracer that raises is removed, as CPython removes a trace function
+//! that raises. Functions compiled by the JIT are not run while tracing.
+
+use std::fmt;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use compiler::CodeObject;
+
+use super::object::{ObjectRef, PyResult};
+use super::Vm;
+
+/// What happened in a traced frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// The frame started running, or a generator's frame resumed.
+    Call,
+    /// The frame is about to run a new line, or to run a line again after
+    /// jumping back to it.
+    Line,
+    /// The frame is returning or yielding a value, or an exception is
+    /// leaving it.
+    Return,
+    /// An exception was raised in the frame, or passed up to it from a
+    /// call.
+    Exception,
+}
+
+impl TraceEvent {
+    /// The name `sys.settrace` gives the event.
+    pub fn name(self) -> &'static str {
+        match self {
+            TraceEvent::Call => "call",
+            TraceEvent::Line => "line",
+            TraceEvent::Return => "return",
+            TraceEvent::Exception => "exception",
+        }
+    }
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The frame an event happened in.
+pub struct TraceFrame<'a> {
+    pub code: &'a Arc<CodeObject>,
+    /// The line the frame is at: the first line of its code for `Call`, and
+    /// the line about to run for `Line`.
+    pub line: usize,
+    /// The frame as Python code sees it, with `f_code`, `f_lineno`,
+    /// `f_globals`, `f_locals`, `f_back` and `f_trace`. It is the same
+    /// object for every event of the frame.
+    pub object: &'a ObjectRef,
+}
+
+/// Hears of the events of the frames that run while it is set.
+pub trait Tracer {
+    /// Called for each event. `arg` is the value returned or yielded for
+    /// `Return`, `None` when an exception is leaving the frame, and the
+    /// exception for `Exception`. An error raised here is raised in the
+    /// traced frame, and the tracer is removed.
+    fn trace(
+        &self,
+        vm: &mut Vm,
+        frame: &TraceFrame,
+        event: TraceEvent,
+        arg: Option<&ObjectRef>,
+    ) -> PyResult<()>;
+}
+
+impl Vm {
+    /// Sets the tracer that hears of the events of every frame, replacing
+    /// any trace function set by `sys.settrace`, or stops tracing for
+    /// `None`.
+    pub fn set_tracer(&mut self, tracer: Option<Rc<dyn Tracer>>) {
+        self.tracer = tracer;
+        self.trace_function = None;
+    }
+
+    /// The tracer set, if any.
+    pub fn tracer(&self) -> Option<&Rc<dyn Tracer>> {
+        self.tracer.as_ref()
+    }
+
+    /// Whether events are to be reported now: a tracer is set and is not
+    /// running.
+    pub(super) fn tracing(&self) -> bool {
+        self.tracer.is_some() && !self.in_tracer
+    }
+}
@@ -0,0 +1,272 @@
+use std::cell::RefCell;
+use std::iter;
+use std::rc::Rc;
+use std::slice;
+
+use super::builtins::{
+    bool_new, bytearray_new, bytes_new, complex_new, dict_new, enumerate_new, filter_new,
+    float_new, int_new, list_new, map_new, memoryview_new, range_new, reversed_new, set_new,
+    slice_new, str_new, tuple_new, zip_new,
+};
+use super::classes::{object_new, super_new, type_new};
+use super::descriptors::{classmethod_new, property_new, staticmethod_new};
+use super::dict::Dict;
+use super::object::{NativeConstructor, ObjectRef, Payload, PyObject, TypeData};
+
+/// The builtin types.
+pub struct Types {
+    pub object: ObjectRef,
+    pub type_: ObjectRef,
+    pub none: ObjectRef,
+    pub not_implemented: ObjectRef,
+    pub ellipsis: ObjectRef,
+    pub int: ObjectRef,
+    pub bool: ObjectRef,
+    pub float: ObjectRef,
+    pub complex: ObjectRef,
+    pub str: ObjectRef,
+    pub bytes: ObjectRef,
+    pub bytearray: ObjectRef,
+    pub memoryview: ObjectRef,
+    pub tuple: ObjectRef,
+    pub list: ObjectRef,
+    pub dict: ObjectRef,
+    pub set: ObjectRef,
+    pub frozenset: ObjectRef,
+    pub slice: ObjectRef,
+    pub range: ObjectRef,
+    pub function: ObjectRef,
+    pub generator: ObjectRef,
+    pub coroutine: ObjectRef,
+    pub builtin_function: ObjectRef,
+    pub method: ObjectRef,
+    pub staticmethod: ObjectRef,
+    pub classmethod: ObjectRef,
+    pub property: ObjectRef,
+    pub code: ObjectRef,
+    pub cell: ObjectRef,
+    pub super_: ObjectRef,
+    pub module: ObjectRef,
+    pub traceback: ObjectRef,
+    pub frame: ObjectRef,
+    pub list_iterator: ObjectRef,
+    pub tuple_iterator: ObjectRef,
+    pub str_iterator: ObjectRef,
+    pub bytes_iterator: ObjectRef,
+    pub bytearray_iterator: ObjectRef,
+    pub memory_iterator: ObjectRef,
+    pub dict_keys: ObjectRef,
+    pub dict_values: ObjectRef,
+    pub dict_items: ObjectRef,
+    pub dict_keyiterator: ObjectRef,
+    pub dict_valueiterator: ObjectRef,
+    pub dict_itemiterator: ObjectRef,
+    pub dict_reversekeyiterator: ObjectRef,
+    pub dict_reversevalueiterator: ObjectRef,
+    pub dict_reverseitemiterator: ObjectRef,
+    pub set_iterator: ObjectRef,
+    pub range_iterator: ObjectRef,
+    pub list_reverseiterator: ObjectRef,
+    pub reversed: ObjectRef,
+    pub enumerate: ObjectRef,
+    pub zip: ObjectRef,
+    pub map: ObjectRef,
+    pub filter: ObjectRef,
+}
+
+impl Types {
+    pub fn new() -> Types {
+        // `object`, `type` and `dict` refer to each other, so their classes
+        // are set once all three exist.
+        let object_dict = PyObject::without_class(Payload::Dict(RefCell::new(Dict::new())), None);
+        let object = PyObject::without_class(
+            Payload::Type(TypeData {
+                name: "object".to_string(),
+                qualname: "object".to_string(),
+                heap: false,
+                bases: Vec::new(),
+                mro: Vec::new(),
+                constructor: Some(object_new),
+            }),
+            Some(object_dict.clone()),
+        );
+        let bootstrap = |name: &str, constructor: Option<NativeConstructor>| {
+            let dict = PyObject::without_class(Payload::Dict(RefCell::new(Dict::new())), None);
+            let class = PyObject::without_class(
+                Payload::Type(TypeData {
+                    name: name.to_string(),
+                    qualname: name.to_string(),
+                    heap: false,
+                    bases: vec![object.clone()],
+                    mro: vec![object.clone()],
+                    constructor,
+                }),
+                Some(dict.clone()),
+            );
+            (class, dict)
+        };
+        let (type_, type_dict) = bootstrap("type", Some(type_new));
+        let (dict, dict_dict) = bootstrap("dict", Some(dict_new));
+        for class in &[&object, &type_, &dict] {
+            class.set_class(type_.clone());
+        }
+        for namespace in &[object_dict, type_dict, dict_dict] {
+            namespace.set_class(dict.clone());
+        }
+
+        let new_type = |name: &str| new_type(&type_, &dict, name, slice::from_ref(&object), None);
+        let with_constructor = |name: &str, constructor: NativeConstructor| {
+            self::new_type(
+                &type_,
+                &dict,
+                name,
+                slice::from_ref(&object),
+                Some(constructor),
+            )
+        };
+        let int = with_constructor("int", int_new);
+        Types {
+            none: new_type("NoneType"),
+            not_implemented: new_type("NotImplementedType"),
+            ellipsis: new_type("ellipsis"),
+            bool: self::new_type(&type_, &dict, "bool", slice::from_ref(&int), Some(bool_new)),
+            int,
+            float: with_constructor("float", float_new),
+            complex: with_constructor("complex", complex_new),
+            str: with_constructor("str", str_new),
+            bytes: with_constructor("bytes", bytes_new),
+            bytearray: with_constructor("bytearray", bytearray_new),
+            memoryview: with_constructor("memoryview", memoryview_new),
+            tuple: with_constructor("tuple", tuple_new),
+            list: with_constructor("list", list_new),
+            set: with_constructor("set", set_new),
+            frozenset: with_constructor("frozenset", set_new),
+            slice: with_constructor("slice", slice_new),
+            range: with_constructor("range", range_new),
+            function: new_type("function"),
+            generator: new_type("generator"),
+            coroutine: new_type("coroutine"),
+            builtin_function: new_type("builtin_function_or_method"),
+            method: new_type("method"),
+            staticmethod: with_constructor("staticmethod", staticmethod_new),
+            classmethod: with_constructor("classmethod", classmethod_new),
+            property: with_constructor("property", property_new),
+            code: new_type("code"),
+            cell: new_type("cell"),
+            super_: self::new_type(
+                &type_,
+                &dict,
+                "super",
+                slice::from_ref(&object),
+                Some(super_new),
+            ),
+            module: new_type("module"),
+            traceback: new_type("traceback"),
+            frame: new_type("frame"),
+            list_iterator: new_type("list_iterator"),
+            tuple_iterator: new_type("tuple_iterator"),
+            str_iterator: new_type("str_iterator"),
+            bytes_iterator: new_type("bytes_iterator"),
+            bytearray_iterator: new_type("bytearray_iterator"),
+            memory_iterator: new_type("memory_iterator"),
+            dict_keys: new_type("dict_keys"),
+            dict_values: new_type("dict_values"),
+            dict_items: new_type("dict_items"),
+            dict_keyiterator: new_type("dict_keyiterator"),
+            dict_valueiterator: new_type("dict_valueiterator"),
+            dict_itemiterator: new_type("dict_itemiterator"),
+            dict_reversekeyiterator: new_type("dict_reversekeyiterator"),
+            dict_reversevalueiterator: new_type("dict_reversevalueiterator"),
+            dict_reverseitemiterator: new_type("dict_reverseitemiterator"),
+            set_iterator: new_type("set_iterator"),
+            range_iterator: new_type("range_iterator"),
+            list_reverseiterator: new_type("list_reverseiterator"),
+            reversed: with_constructor("reversed", reversed_new),
+            enumerate: with_constructor("enumerate", enumerate_new),
+            zip: with_constructor("zip", zip_new),
+            map: with_constructor("map", map_new),
+            filter: with_constructor("filter", filter_new),
+            object,
+            type_,
+            dict,
+        }
+    }
+
+    /// A new class with a single base, whose instances are made by
+    /// `constructor` or else by the base's constructor.
+    pub fn new_type(
+        &self,
+        name: &str,
+        base: &ObjectRef,
+        constructor: Option<NativeConstructor>,
+    ) -> ObjectRef {
+        new_type(
+            &self.type_,
+            &self.dict,
+            name,
+            slice::from_ref(base),
+            constructor,
+        )
+    }
+
+    /// A new class with several bases, for the builtin classes that have
+    /// them. Its MRO keeps each class where it last comes in the MROs of
+    /// the bases, which is the C3 order for bases that only share their
+    /// roots.
+    pub fn new_type_with_bases(
+        &self,
+        name: &str,
+        bases: &[ObjectRef],
+        constructor: Option<NativeConstructor>,
+    ) -> ObjectRef {
+        new_type(&self.type_, &self.dict, name, bases, constructor)
+    }
+}
+
+impl Default for Types {
+    fn default() -> Types {
+        Types::new()
+    }
+}
+
+fn new_type(
+    metatype: &ObjectRef,
+    dict_type: &ObjectRef,
+    name: &str,
+    bases: &[ObjectRef],
+    constructor: Option<NativeConstructor>,
+) -> ObjectRef {
+    let dict = PyObject::new(
+        Payload::Dict(RefCell::new(Dict::new())),
+        dict_type.clone(),
+        None,
+    );
+    let lines: Vec<ObjectRef> = bases
+        .iter()
+        .flat_map(|base| {
+            let mro = base.as_type().map_or(&[][..], |data| &data.mro[..]);
+            iter::once(base).chain(mro).cloned()
+        })
+        .collect();
+    let mut mro = Vec::new();
+    for (index, class) in lines.iter().enumerate() {
+        if !lines[index + 1..]
+            .iter()
+            .any(|later| Rc::ptr_eq(later, class))
+        {
+            mro.push(class.clone());
+        }
+    }
+    PyObject::new(
+        Payload::Type(TypeData {
+            name: name.to_string(),
+            qualname: name.to_string(),
+            heap: false,
+            bases: bases.to_vec(),
+            mro,
+            constructor,
+        }),
+        metatype.clone(),
+        Some(dict),
+    )
+}
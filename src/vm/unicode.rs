@@ -0,0 +1,526 @@
+//! The character properties behind `str` methods that Rust's `char` does
+//! not have, following the Unicode database CPython uses.
+
+/// Whether `str.isspace` holds for a character: Rust's whitespace and the
+/// information separators.
+pub(super) fn is_space(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '\x1c'..='\x1f')
+}
+
+pub(super) fn is_decimal(c: char) -> bool {
+    in_table(DECIMAL, c)
+}
+
+/// The value of a decimal digit. The digits of each script come in runs
+/// of ten from zero, so this is its place in the run.
+pub(super) fn decimal_value(c: char) -> Option<u32> {
+    table_index(DECIMAL, c).map(|index| (c as u32 - DECIMAL[index].0 as u32) % 10)
+}
+
+/// A numeric string with its decimal digits in ASCII and its whitespace as
+/// spaces, which is how `int` and `float` read numbers in any script, as
+/// CPython's `_PyUnicode_TransformDecimalAndSpaceToASCII` does. Other
+/// characters are kept, to be rejected by the parser.
+pub(super) fn ascii_digits_and_spaces(text: &str) -> String {
+    text.chars()
+        .map(|c| match c {
+            _ if c.is_ascii() => c,
+            _ if is_space(c) => ' ',
+            _ => decimal_value(c).map_or(c, |value| (b'0' + value as u8) as char),
+        })
+        .collect()
+}
+
+pub(super) fn is_digit(c: char) -> bool {
+    is_decimal(c) || in_table(DIGIT, c)
+}
+
+pub(super) fn is_numeric(c: char) -> bool {
+    c.is_numeric() || in_table(NUMERIC, c)
+}
+
+/// Whether a character is a letter, as `str.isalpha` sees it. Letter
+/// numbers, such as Roman numerals, and marks are not.
+pub(super) fn is_alpha(c: char) -> bool {
+    c.is_alphabetic() && !c.is_numeric() && !in_table(MARKS, c)
+}
+
+/// Whether a character is in category `Lt`, such as `ǅ`.
+pub(super) fn is_titlecase(c: char) -> bool {
+    matches!(
+        c,
+        '\u{1c5}'
+            | '\u{1c8}'
+            | '\u{1cb}'
+            | '\u{1f2}'
+            | '\u{1f88}'..='\u{1f8f}'
+            | '\u{1f98}'..='\u{1f9f}'
+            | '\u{1fa8}'..='\u{1faf}'
+            | '\u{1fbc}'
+            | '\u{1fcc}'
+            | '\u{1ffc}'
+    )
+}
+
+pub(super) fn is_cased(c: char) -> bool {
+    c.is_lowercase() || c.is_uppercase() || is_titlecase(c)
+}
+
+/// Whether a character is skipped when deciding if a sigma ends a word:
+/// combining marks, modifiers and the apostrophes and periods inside words.
+fn is_case_ignorable(c: char) -> bool {
+    matches!(
+        c,
+        '\'' | '.'
+            | ':'
+            | '^'
+            | '`'
+            | '\u{a8}'
+            | '\u{ad}'
+            | '\u{af}'
+            | '\u{b4}'
+            | '\u{b7}'
+            | '\u{b8}'
+            | '\u{2b0}'..='\u{36f}'
+            | '\u{483}'..='\u{489}'
+            | '\u{1ab0}'..='\u{1aff}'
+            | '\u{1dc0}'..='\u{1dff}'
+            | '\u{2018}'
+            | '\u{2019}'
+            | '\u{2024}'
+            | '\u{2027}'
+            | '\u{20d0}'..='\u{20ff}'
+            | '\u{fe00}'..='\u{fe0f}'
+            | '\u{fe20}'..='\u{fe2f}'
+    )
+}
+
+/// Appends the lowercase of `chars[index]` to `out`. A capital sigma that
+/// ends a word becomes a final sigma.
+pub(super) fn push_lower(chars: &[char], index: usize, out: &mut String) {
+    let c = chars[index];
+    if c != 'Σ' {
+        out.extend(c.to_lowercase());
+        return;
+    }
+    let significant = |c: &&char| !is_case_ignorable(**c);
+    let preceded = chars[..index]
+        .iter()
+        .rev()
+        .find(significant)
+        .is_some_and(|&c| is_cased(c));
+    let followed = chars[index + 1..]
+        .iter()
+        .find(significant)
+        .is_some_and(|&c| is_cased(c));
+    out.push(if preceded && !followed { 'ς' } else { 'σ' });
+}
+
+/// Appends the titlecase of a character to `out`: usually its uppercase,
+/// with any further characters of that in lowercase.
+pub(super) fn push_title(c: char, out: &mut String) {
+    let special = match c {
+        '\u{1c4}'..='\u{1c6}' => Some("\u{1c5}"),
+        '\u{1c7}'..='\u{1c9}' => Some("\u{1c8}"),
+        '\u{1ca}'..='\u{1cc}' => Some("\u{1cb}"),
+        '\u{1f1}'..='\u{1f3}' => Some("\u{1f2}"),
+        '\u{149}' => Some("\u{2bc}N"),
+        '\u{1fb3}' | '\u{1fbc}' => Some("\u{1fbc}"),
+        '\u{1fc3}' | '\u{1fcc}' => Some("\u{1fcc}"),
+        '\u{1ff3}' | '\u{1ffc}' => Some("\u{1ffc}"),
+        '\u{1fb2}' => Some("\u{1fba}\u{345}"),
+        '\u{1fb4}' => Some("\u{386}\u{345}"),
+        '\u{1fb7}' => Some("\u{391}\u{342}\u{345}"),
+        '\u{1fc2}' => Some("\u{1fca}\u{345}"),
+        '\u{1fc4}' => Some("\u{389}\u{345}"),
+        '\u{1fc7}' => Some("\u{397}\u{342}\u{345}"),
+        '\u{1ff2}' => Some("\u{1ffa}\u{345}"),
+        '\u{1ff4}' => Some("\u{38f}\u{345}"),
+        '\u{1ff7}' => Some("\u{3a9}\u{342}\u{345}"),
+        _ => None,
+    };
+    if let Some(title) = special {
+        out.push_str(title);
+        return;
+    }
+    match c {
+        // Georgian letters have no titlecase.
+        '\u{10d0}'..='\u{10fa}' | '\u{10fd}'..='\u{10ff}' => out.push(c),
+        // Greek letters with a iota subscript keep it below the capital.
+        '\u{1f80}'..='\u{1faf}' => out.push(::std::char::from_u32(c as u32 | 0x8).unwrap()),
+        _ => {
+            let mut upper = c.to_uppercase();
+            out.extend(upper.next());
+            for rest in upper {
+                out.extend(rest.to_lowercase());
+            }
+        }
+    }
+}
+
+/// Appends the case folding of a character to `out`, for caseless
+/// comparison.
+pub(super) fn push_casefold(c: char, out: &mut String) {
+    match c {
+        'ı' => out.push(c),
+        // Cherokee folds to its older, uppercase letters.
+        '\u{13a0}'..='\u{13f5}' | '\u{13f8}'..='\u{13fd}' | '\u{ab70}'..='\u{abbf}' => {
+            out.extend(c.to_uppercase())
+        }
+        _ => {
+            for lower in c.to_lowercase() {
+                for upper in lower.to_uppercase() {
+                    out.extend(upper.to_lowercase());
+                }
+            }
+        }
+    }
+}
+
+fn in_table(table: &[(char, char)], c: char) -> bool {
+    table_index(table, c).is_some()
+}
+
+/// The index of the range of `table` that holds `c`.
+fn table_index(table: &[(char, char)], c: char) -> Option<usize> {
+    table
+        .binary_search_by(|&(start, end)| {
+            if end < c {
+                ::std::cmp::Ordering::Less
+            } else if start > c {
+                ::std::cmp::Ordering::Greater
+            } else {
+                ::std::cmp::Ordering::Equal
+            }
+        })
+        .ok()
+}
+
+/// The decimal digits, category `Nd`.
+const DECIMAL: &[(char, char)] = &[
+    ('\u{30}', '\u{39}'),
+    ('\u{660}', '\u{669}'),
+    ('\u{6f0}', '\u{6f9}'),
+    ('\u{7c0}', '\u{7c9}'),
+    ('\u{966}', '\u{96f}'),
+    ('\u{9e6}', '\u{9ef}'),
+    ('\u{a66}', '\u{a6f}'),
+    ('\u{ae6}', '\u{aef}'),
+    ('\u{b66}', '\u{b6f}'),
+    ('\u{be6}', '\u{bef}'),
+    ('\u{c66}', '\u{c6f}'),
+    ('\u{ce6}', '\u{cef}'),
+    ('\u{d66}', '\u{d6f}'),
+    ('\u{de6}', '\u{def}'),
+    ('\u{e50}', '\u{e59}'),
+    ('\u{ed0}', '\u{ed9}'),
+    ('\u{f20}', '\u{f29}'),
+    ('\u{1040}', '\u{1049}'),
+    ('\u{1090}', '\u{1099}'),
+    ('\u{17e0}', '\u{17e9}'),
+    ('\u{1810}', '\u{1819}'),
+    ('\u{1946}', '\u{194f}'),
+    ('\u{19d0}', '\u{19d9}'),
+    ('\u{1a80}', '\u{1a89}'),
+    ('\u{1a90}', '\u{1a99}'),
+    ('\u{1b50}', '\u{1b59}'),
+    ('\u{1bb0}', '\u{1bb9}'),
+    ('\u{1c40}', '\u{1c49}'),
+    ('\u{1c50}', '\u{1c59}'),
+    ('\u{a620}', '\u{a629}'),
+    ('\u{a8d0}', '\u{a8d9}'),
+    ('\u{a900}', '\u{a909}'),
+    ('\u{a9d0}', '\u{a9d9}'),
+    ('\u{a9f0}', '\u{a9f9}'),
+    ('\u{aa50}', '\u{aa59}'),
+    ('\u{abf0}', '\u{abf9}'),
+    ('\u{ff10}', '\u{ff19}'),
+    ('\u{104a0}', '\u{104a9}'),
+    ('\u{10d30}', '\u{10d39}'),
+    ('\u{11066}', '\u{1106f}'),
+    ('\u{110f0}', '\u{110f9}'),
+    ('\u{11136}', '\u{1113f}'),
+    ('\u{111d0}', '\u{111d9}'),
+    ('\u{112f0}', '\u{112f9}'),
+    ('\u{11450}', '\u{11459}'),
+    ('\u{114d0}', '\u{114d9}'),
+    ('\u{11650}', '\u{11659}'),
+    ('\u{116c0}', '\u{116c9}'),
+    ('\u{11730}', '\u{11739}'),
+    ('\u{118e0}', '\u{118e9}'),
+    ('\u{11950}', '\u{11959}'),
+    ('\u{11c50}', '\u{11c59}'),
+    ('\u{11d50}', '\u{11d59}'),
+    ('\u{11da0}', '\u{11da9}'),
+    ('\u{16a60}', '\u{16a69}'),
+    ('\u{16ac0}', '\u{16ac9}'),
+    ('\u{16b50}', '\u{16b59}'),
+    ('\u{1d7ce}', '\u{1d7ff}'),
+    ('\u{1e140}', '\u{1e149}'),
+    ('\u{1e2f0}', '\u{1e2f9}'),
+    ('\u{1e950}', '\u{1e959}'),
+    ('\u{1fbf0}', '\u{1fbf9}'),
+];
+
+/// The other characters with a digit value, such as superscripts.
+const DIGIT: &[(char, char)] = &[
+    ('\u{b2}', '\u{b3}'),
+    ('\u{b9}', '\u{b9}'),
+    ('\u{1369}', '\u{1371}'),
+    ('\u{19da}', '\u{19da}'),
+    ('\u{2070}', '\u{2070}'),
+    ('\u{2074}', '\u{2079}'),
+    ('\u{2080}', '\u{2089}'),
+    ('\u{2460}', '\u{2468}'),
+    ('\u{2474}', '\u{247c}'),
+    ('\u{2488}', '\u{2490}'),
+    ('\u{24ea}', '\u{24ea}'),
+    ('\u{24f5}', '\u{24fd}'),
+    ('\u{24ff}', '\u{24ff}'),
+    ('\u{2776}', '\u{277e}'),
+    ('\u{2780}', '\u{2788}'),
+    ('\u{278a}', '\u{2792}'),
+    ('\u{10a40}', '\u{10a43}'),
+    ('\u{10e60}', '\u{10e68}'),
+    ('\u{11052}', '\u{1105a}'),
+    ('\u{1f100}', '\u{1f10a}'),
+];
+
+/// The characters outside the `N` categories with a numeric value, such as
+/// CJK numerals.
+const NUMERIC: &[(char, char)] = &[
+    ('\u{3405}', '\u{3405}'),
+    ('\u{3483}', '\u{3483}'),
+    ('\u{382a}', '\u{382a}'),
+    ('\u{3b4d}', '\u{3b4d}'),
+    ('\u{4e00}', '\u{4e00}'),
+    ('\u{4e03}', '\u{4e03}'),
+    ('\u{4e07}', '\u{4e07}'),
+    ('\u{4e09}', '\u{4e09}'),
+    ('\u{4e5d}', '\u{4e5d}'),
+    ('\u{4e8c}', '\u{4e8c}'),
+    ('\u{4e94}', '\u{4e94}'),
+    ('\u{4e96}', '\u{4e96}'),
+    ('\u{4ebf}', '\u{4ec0}'),
+    ('\u{4edf}', '\u{4edf}'),
+    ('\u{4ee8}', '\u{4ee8}'),
+    ('\u{4f0d}', '\u{4f0d}'),
+    ('\u{4f70}', '\u{4f70}'),
+    ('\u{5104}', '\u{5104}'),
+    ('\u{5146}', '\u{5146}'),
+    ('\u{5169}', '\u{5169}'),
+    ('\u{516b}', '\u{516b}'),
+    ('\u{516d}', '\u{516d}'),
+    ('\u{5341}', '\u{5341}'),
+    ('\u{5343}', '\u{5345}'),
+    ('\u{534c}', '\u{534c}'),
+    ('\u{53c1}', '\u{53c4}'),
+    ('\u{56db}', '\u{56db}'),
+    ('\u{58f1}', '\u{58f1}'),
+    ('\u{58f9}', '\u{58f9}'),
+    ('\u{5e7a}', '\u{5e7a}'),
+    ('\u{5efe}', '\u{5eff}'),
+    ('\u{5f0c}', '\u{5f0e}'),
+    ('\u{5f10}', '\u{5f10}'),
+    ('\u{62fe}', '\u{62fe}'),
+    ('\u{634c}', '\u{634c}'),
+    ('\u{67d2}', '\u{67d2}'),
+    ('\u{6f06}', '\u{6f06}'),
+    ('\u{7396}', '\u{7396}'),
+    ('\u{767e}', '\u{767e}'),
+    ('\u{8086}', '\u{8086}'),
+    ('\u{842c}', '\u{842c}'),
+    ('\u{8cae}', '\u{8cae}'),
+    ('\u{8cb3}', '\u{8cb3}'),
+    ('\u{8d30}', '\u{8d30}'),
+    ('\u{9621}', '\u{9621}'),
+    ('\u{9646}', '\u{9646}'),
+    ('\u{964c}', '\u{964c}'),
+    ('\u{9678}', '\u{9678}'),
+    ('\u{96f6}', '\u{96f6}'),
+    ('\u{f96b}', '\u{f96b}'),
+    ('\u{f973}', '\u{f973}'),
+    ('\u{f978}', '\u{f978}'),
+    ('\u{f9b2}', '\u{f9b2}'),
+    ('\u{f9d1}', '\u{f9d1}'),
+    ('\u{f9d3}', '\u{f9d3}'),
+    ('\u{f9fd}', '\u{f9fd}'),
+    ('\u{20001}', '\u{20001}'),
+    ('\u{20064}', '\u{20064}'),
+    ('\u{200e2}', '\u{200e2}'),
+    ('\u{20121}', '\u{20121}'),
+    ('\u{2092a}', '\u{2092a}'),
+    ('\u{20983}', '\u{20983}'),
+    ('\u{2098c}', '\u{2098c}'),
+    ('\u{2099c}', '\u{2099c}'),
+    ('\u{20aea}', '\u{20aea}'),
+    ('\u{20afd}', '\u{20afd}'),
+    ('\u{20b19}', '\u{20b19}'),
+    ('\u{22390}', '\u{22390}'),
+    ('\u{22998}', '\u{22998}'),
+    ('\u{23b1b}', '\u{23b1b}'),
+    ('\u{2626d}', '\u{2626d}'),
+    ('\u{2f890}', '\u{2f890}'),
+];
+
+/// The marks and symbols Rust counts as alphabetic, such as vowel signs,
+/// which are not letters.
+const MARKS: &[(char, char)] = &[
+    ('\u{345}', '\u{36f}'),
+    ('\u{5b0}', '\u{5c7}'),
+    ('\u{610}', '\u{61a}'),
+    ('\u{64b}', '\u{65f}'),
+    ('\u{670}', '\u{670}'),
+    ('\u{6d6}', '\u{6e4}'),
+    ('\u{6e7}', '\u{6ed}'),
+    ('\u{711}', '\u{711}'),
+    ('\u{730}', '\u{73f}'),
+    ('\u{7a6}', '\u{7b0}'),
+    ('\u{816}', '\u{817}'),
+    ('\u{81b}', '\u{823}'),
+    ('\u{825}', '\u{827}'),
+    ('\u{829}', '\u{82c}'),
+    ('\u{8d4}', '\u{903}'),
+    ('\u{93a}', '\u{93b}'),
+    ('\u{93e}', '\u{94f}'),
+    ('\u{955}', '\u{957}'),
+    ('\u{962}', '\u{963}'),
+    ('\u{981}', '\u{983}'),
+    ('\u{9be}', '\u{9cc}'),
+    ('\u{9d7}', '\u{9d7}'),
+    ('\u{9e2}', '\u{9e3}'),
+    ('\u{a01}', '\u{a03}'),
+    ('\u{a3e}', '\u{a51}'),
+    ('\u{a70}', '\u{a71}'),
+    ('\u{a75}', '\u{a83}'),
+    ('\u{abe}', '\u{acc}'),
+    ('\u{ae2}', '\u{ae3}'),
+    ('\u{afa}', '\u{b03}'),
+    ('\u{b3e}', '\u{b57}'),
+    ('\u{b62}', '\u{b63}'),
+    ('\u{b82}', '\u{b82}'),
+    ('\u{bbe}', '\u{bcc}'),
+    ('\u{bd7}', '\u{c04}'),
+    ('\u{c3e}', '\u{c56}'),
+    ('\u{c62}', '\u{c63}'),
+    ('\u{c81}', '\u{c83}'),
+    ('\u{cbe}', '\u{cd6}'),
+    ('\u{ce2}', '\u{ce3}'),
+    ('\u{d00}', '\u{d03}'),
+    ('\u{d3e}', '\u{d4c}'),
+    ('\u{d57}', '\u{d57}'),
+    ('\u{d62}', '\u{d63}'),
+    ('\u{d81}', '\u{d83}'),
+    ('\u{dcf}', '\u{df3}'),
+    ('\u{e31}', '\u{e31}'),
+    ('\u{e34}', '\u{e3a}'),
+    ('\u{e4d}', '\u{e4d}'),
+    ('\u{eb1}', '\u{eb1}'),
+    ('\u{eb4}', '\u{ebc}'),
+    ('\u{ecd}', '\u{ecd}'),
+    ('\u{f71}', '\u{f83}'),
+    ('\u{f8d}', '\u{fbc}'),
+    ('\u{102b}', '\u{103e}'),
+    ('\u{1056}', '\u{1059}'),
+    ('\u{105e}', '\u{1060}'),
+    ('\u{1062}', '\u{1064}'),
+    ('\u{1067}', '\u{106d}'),
+    ('\u{1071}', '\u{1074}'),
+    ('\u{1082}', '\u{108d}'),
+    ('\u{108f}', '\u{109d}'),
+    ('\u{1712}', '\u{1713}'),
+    ('\u{1732}', '\u{1733}'),
+    ('\u{1752}', '\u{1753}'),
+    ('\u{1772}', '\u{1773}'),
+    ('\u{17b6}', '\u{17c8}'),
+    ('\u{1885}', '\u{1886}'),
+    ('\u{18a9}', '\u{18a9}'),
+    ('\u{1920}', '\u{1938}'),
+    ('\u{1a17}', '\u{1a1b}'),
+    ('\u{1a55}', '\u{1a74}'),
+    ('\u{1abf}', '\u{1b04}'),
+    ('\u{1b35}', '\u{1b43}'),
+    ('\u{1b80}', '\u{1b82}'),
+    ('\u{1ba1}', '\u{1bad}'),
+    ('\u{1be7}', '\u{1bf1}'),
+    ('\u{1c24}', '\u{1c36}'),
+    ('\u{1dd3}', '\u{1df4}'),
+    ('\u{24b6}', '\u{24e9}'),
+    ('\u{2de0}', '\u{2dff}'),
+    ('\u{a674}', '\u{a67b}'),
+    ('\u{a69e}', '\u{a69f}'),
+    ('\u{a802}', '\u{a802}'),
+    ('\u{a80b}', '\u{a80b}'),
+    ('\u{a823}', '\u{a827}'),
+    ('\u{a880}', '\u{a881}'),
+    ('\u{a8b4}', '\u{a8c5}'),
+    ('\u{a8ff}', '\u{a8ff}'),
+    ('\u{a926}', '\u{a92a}'),
+    ('\u{a947}', '\u{a952}'),
+    ('\u{a980}', '\u{a983}'),
+    ('\u{a9b4}', '\u{a9bf}'),
+    ('\u{a9e5}', '\u{a9e5}'),
+    ('\u{aa29}', '\u{aa36}'),
+    ('\u{aa43}', '\u{aa43}'),
+    ('\u{aa4c}', '\u{aa4d}'),
+    ('\u{aa7b}', '\u{aa7d}'),
+    ('\u{aab0}', '\u{aab0}'),
+    ('\u{aab2}', '\u{aab4}'),
+    ('\u{aab7}', '\u{aab8}'),
+    ('\u{aabe}', '\u{aabe}'),
+    ('\u{aaeb}', '\u{aaef}'),
+    ('\u{aaf5}', '\u{aaf5}'),
+    ('\u{abe3}', '\u{abea}'),
+    ('\u{fb1e}', '\u{fb1e}'),
+    ('\u{10376}', '\u{1037a}'),
+    ('\u{10a01}', '\u{10a0f}'),
+    ('\u{10d24}', '\u{10d27}'),
+    ('\u{10eab}', '\u{10eac}'),
+    ('\u{11000}', '\u{11002}'),
+    ('\u{11038}', '\u{11045}'),
+    ('\u{11073}', '\u{11074}'),
+    ('\u{11080}', '\u{11082}'),
+    ('\u{110b0}', '\u{110c2}'),
+    ('\u{11100}', '\u{11102}'),
+    ('\u{11127}', '\u{11132}'),
+    ('\u{11145}', '\u{11146}'),
+    ('\u{11180}', '\u{11182}'),
+    ('\u{111b3}', '\u{111bf}'),
+    ('\u{111ce}', '\u{111cf}'),
+    ('\u{1122c}', '\u{1123e}'),
+    ('\u{112df}', '\u{11303}'),
+    ('\u{1133e}', '\u{1134c}'),
+    ('\u{11357}', '\u{11357}'),
+    ('\u{11362}', '\u{11363}'),
+    ('\u{11435}', '\u{11445}'),
+    ('\u{114b0}', '\u{114c1}'),
+    ('\u{115af}', '\u{115be}'),
+    ('\u{115dc}', '\u{115dd}'),
+    ('\u{11630}', '\u{11640}'),
+    ('\u{116ab}', '\u{116b5}'),
+    ('\u{1171d}', '\u{1172a}'),
+    ('\u{1182c}', '\u{11838}'),
+    ('\u{11930}', '\u{1193c}'),
+    ('\u{11940}', '\u{11940}'),
+    ('\u{11942}', '\u{11942}'),
+    ('\u{119d1}', '\u{119df}'),
+    ('\u{119e4}', '\u{119e4}'),
+    ('\u{11a01}', '\u{11a0a}'),
+    ('\u{11a35}', '\u{11a39}'),
+    ('\u{11a3b}', '\u{11a3e}'),
+    ('\u{11a51}', '\u{11a5b}'),
+    ('\u{11a8a}', '\u{11a97}'),
+    ('\u{11c2f}', '\u{11c3e}'),
+    ('\u{11c92}', '\u{11cb6}'),
+    ('\u{11d31}', '\u{11d43}'),
+    ('\u{11d47}', '\u{11d47}'),
+    ('\u{11d8a}', '\u{11d96}'),
+    ('\u{11ef3}', '\u{11ef6}'),
+    ('\u{16f4f}', '\u{16f4f}'),
+    ('\u{16f51}', '\u{16f92}'),
+    ('\u{16ff0}', '\u{16ff1}'),
+    ('\u{1bc9e}', '\u{1bc9e}'),
+    ('\u{1e000}', '\u{1e02a}'),
+    ('\u{1e947}', '\u{1e947}'),
+    ('\u{1f130}', '\u{1f189}'),
+];
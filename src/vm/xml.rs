@@ -0,0 +1,786 @@
+//! Parsing XML documents into trees of elements, for the
+//! `xml.etree.ElementTree` module. Names in a namespace come out as
+//! `{uri}name`, as from expat with the separator ElementTree gives it, and
+//! the errors are expat's, with its codes and positions.
+//!
+//! Comments, processing instructions and the document type are skipped,
+//! as ElementTree's default tree builder skips them. The entities declared
+//! in the internal subset of the document type are replaced as text.
+
+use std::collections::HashMap;
+
+/// The namespace the `xml` prefix is bound to in every document.
+const XML_NAMESPACE: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// An element of a parsed document, with the text before its first child
+/// and the text after it, which are `None` rather than empty.
+pub(super) struct Node {
+    pub tag: String,
+    pub attributes: Vec<(String, String)>,
+    pub text: Option<String>,
+    pub tail: Option<String>,
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    fn new(tag: String, attributes: Vec<(String, String)>) -> Node {
+        Node {
+            tag,
+            attributes,
+            text: None,
+            tail: None,
+            children: Vec::new(),
+        }
+    }
+}
+
+/// The errors of expat that parsing can fail with.
+#[derive(Clone, Copy)]
+pub(super) enum ErrorKind {
+    Syntax,
+    NoElements,
+    InvalidToken,
+    UnclosedToken,
+    TagMismatch,
+    DuplicateAttribute,
+    JunkAfterDocumentElement,
+    UndefinedEntity,
+    BadCharacterReference,
+    MisplacedXmlDeclaration,
+    UnboundPrefix,
+    UndeclaringPrefix,
+}
+
+impl ErrorKind {
+    /// The number of the error in expat.
+    pub(super) fn code(self) -> i64 {
+        match self {
+            ErrorKind::Syntax => 2,
+            ErrorKind::NoElements => 3,
+            ErrorKind::InvalidToken => 4,
+            ErrorKind::UnclosedToken => 5,
+            ErrorKind::TagMismatch => 7,
+            ErrorKind::DuplicateAttribute => 8,
+            ErrorKind::JunkAfterDocumentElement => 9,
+            ErrorKind::UndefinedEntity => 11,
+            ErrorKind::BadCharacterReference => 14,
+            ErrorKind::MisplacedXmlDeclaration => 17,
+            ErrorKind::UnboundPrefix => 27,
+            ErrorKind::UndeclaringPrefix => 28,
+        }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            ErrorKind::Syntax => "syntax error",
+            ErrorKind::NoElements => "no element found",
+            ErrorKind::InvalidToken => "not well-formed (invalid token)",
+            ErrorKind::UnclosedToken => "unclosed token",
+            ErrorKind::TagMismatch => "mismatched tag",
+            ErrorKind::DuplicateAttribute => "duplicate attribute",
+            ErrorKind::JunkAfterDocumentElement => "junk after document element",
+            ErrorKind::UndefinedEntity => "undefined entity",
+            ErrorKind::BadCharacterReference => "reference to invalid character number",
+            ErrorKind::MisplacedXmlDeclaration => "XML or text declaration not at start of entity",
+            ErrorKind::UnboundPrefix => "unbound prefix",
+            ErrorKind::UndeclaringPrefix => "must not undeclare prefix",
+        }
+    }
+}
+
+/// A parse error at a line, counted from 1, and a column, from 0.
+pub(super) struct XmlError {
+    pub kind: ErrorKind,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl XmlError {
+    /// The message of the error, as `ParseError` words it.
+    pub(super) fn message(&self) -> String {
+        format!(
+            "{}: line {}, column {}",
+            self.kind.message(),
+            self.line,
+            self.column
+        )
+    }
+}
+
+/// Decodes the bytes of a document by its byte order mark or the encoding
+/// its XML declaration names, UTF-8 by default.
+pub(super) fn decode(data: &[u8]) -> Result<String, XmlError> {
+    let data = data.strip_prefix(b"\xef\xbb\xbf").unwrap_or(data);
+    let declared = declared_encoding(data).map(|name| name.to_ascii_lowercase());
+    match declared.as_deref() {
+        Some("iso-8859-1") | Some("latin-1") | Some("latin1") => {
+            return Ok(data.iter().map(|&byte| char::from(byte)).collect());
+        }
+        Some("us-ascii") | Some("ascii") => {
+            if let Some(position) = data.iter().position(|byte| !byte.is_ascii()) {
+                let text = String::from_utf8_lossy(&data[..position]);
+                return Err(error_at(&text, ErrorKind::InvalidToken));
+            }
+        }
+        _ => {}
+    }
+    match String::from_utf8(data.to_vec()) {
+        Ok(text) => Ok(text),
+        Err(error) => {
+            let valid = error.utf8_error().valid_up_to();
+            let text = String::from_utf8_lossy(&data[..valid]);
+            Err(error_at(&text, ErrorKind::InvalidToken))
+        }
+    }
+}
+
+/// The encoding named in the XML declaration at the start of `data`.
+fn declared_encoding(data: &[u8]) -> Option<String> {
+    let end = data.windows(2).position(|pair| pair == b"?>")?;
+    let declaration = String::from_utf8_lossy(&data[..end]);
+    let rest = declaration.strip_prefix("<?xml")?;
+    let at = rest.find("encoding")?;
+    let rest = rest[at + "encoding".len()..].trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    let quote = rest.chars().next()?;
+    if quote != '"' && quote != '\'' {
+        return None;
+    }
+    let value = &rest[1..];
+    Some(value[..value.find(quote)?].to_string())
+}
+
+/// An error at the end of `text`.
+fn error_at(text: &str, kind: ErrorKind) -> XmlError {
+    let chars: Vec<char> = text.chars().collect();
+    let (line, column) = line_and_column(&chars, chars.len());
+    XmlError { kind, line, column }
+}
+
+/// The line and column of the character at `at`, taking `\r\n` and `\r`
+/// as line ends as well.
+fn line_and_column(chars: &[char], at: usize) -> (usize, usize) {
+    let (mut line, mut column) = (1, 0);
+    let mut previous = None;
+    for &c in &chars[..at] {
+        match c {
+            '\n' if previous == Some('\r') => {}
+            '\n' | '\r' => {
+                line += 1;
+                column = 0;
+            }
+            _ => column += 1,
+        }
+        previous = Some(c);
+    }
+    (line, column)
+}
+
+/// Parses the document `text` into its root element.
+pub(super) fn parse(text: &str) -> Result<Node, XmlError> {
+    let mut parser = Parser {
+        chars: text.chars().collect(),
+        position: 0,
+        entities: HashMap::new(),
+        namespaces: Vec::new(),
+    };
+    parser.document()
+}
+
+fn is_whitespace(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\r')
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == ':'
+}
+
+fn is_name_char(c: char) -> bool {
+    is_name_start(c) || c.is_alphanumeric() || matches!(c, '-' | '.' | '\u{b7}')
+}
+
+/// Whether XML allows the character in a document at all.
+fn is_valid_char(c: char) -> bool {
+    matches!(c, '\t' | '\n' | '\r') || (c >= ' ' && c != '\u{fffe}' && c != '\u{ffff}')
+}
+
+struct Parser {
+    chars: Vec<char>,
+    position: usize,
+    /// The entities declared in the document type, with their text.
+    entities: HashMap<String, String>,
+    /// The namespace declarations in scope, innermost last, by prefix:
+    /// empty for the default namespace.
+    namespaces: Vec<(String, String)>,
+}
+
+/// An element whose end tag is yet to come.
+struct Open {
+    node: Node,
+    /// The name in its start tag, which its end tag must repeat.
+    name: String,
+    /// How many namespace declarations were in scope outside it.
+    namespaces: usize,
+}
+
+impl Parser {
+    fn error(&self, kind: ErrorKind, at: usize) -> XmlError {
+        let (line, column) = line_and_column(&self.chars, at);
+        XmlError { kind, line, column }
+    }
+
+    /// An invalid token at the current position, or an unclosed token
+    /// that started at `start` if the document ends there.
+    fn invalid(&self, start: usize) -> XmlError {
+        if self.position >= self.chars.len() {
+            self.error(ErrorKind::UnclosedToken, start)
+        } else {
+            self.error(ErrorKind::InvalidToken, self.position)
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).cloned()
+    }
+
+    fn starts_with(&self, prefix: &str) -> bool {
+        (self.position..)
+            .zip(prefix.chars())
+            .all(|(at, c)| self.chars.get(at) == Some(&c))
+    }
+
+    /// Skips whitespace, returning whether there was any.
+    fn skip_whitespace(&mut self) -> bool {
+        let start = self.position;
+        while self.peek().is_some_and(is_whitespace) {
+            self.position += 1;
+        }
+        self.position > start
+    }
+
+    fn name(&mut self) -> Option<String> {
+        if !self.peek().is_some_and(is_name_start) {
+            return None;
+        }
+        let start = self.position;
+        while self.peek().is_some_and(is_name_char) {
+            self.position += 1;
+        }
+        Some(self.chars[start..self.position].iter().collect())
+    }
+
+    fn document(&mut self) -> Result<Node, XmlError> {
+        if self.peek() == Some('\u{feff}') {
+            self.position += 1;
+        }
+        let root = loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None => return Err(self.error(ErrorKind::NoElements, self.position)),
+                Some('<') => {}
+                Some(_) => return Err(self.error(ErrorKind::Syntax, self.position)),
+            }
+            if self.starts_with("<?") {
+                self.processing_instruction()?;
+            } else if self.starts_with("<!--") {
+                self.comment()?;
+            } else if self.starts_with("<!DOCTYPE") {
+                self.doctype()?;
+            } else if self.chars.get(self.position + 1) == Some(&'/') {
+                return Err(self.error(ErrorKind::InvalidToken, self.position + 1));
+            } else {
+                break self.element()?;
+            }
+        };
+        loop {
+            self.skip_whitespace();
+            if self.peek().is_none() {
+                return Ok(root);
+            }
+            if self.starts_with("<?") {
+                self.processing_instruction()?;
+            } else if self.starts_with("<!--") {
+                self.comment()?;
+            } else {
+                return Err(self.error(ErrorKind::JunkAfterDocumentElement, self.position));
+            }
+        }
+    }
+
+    /// Skips a processing instruction, or the XML declaration if it is at
+    /// the start of the document.
+    fn processing_instruction(&mut self) -> Result<(), XmlError> {
+        let start = self.position;
+        self.position += 2;
+        let target = match self.name() {
+            Some(target) => target,
+            None => return Err(self.invalid(start)),
+        };
+        if target.eq_ignore_ascii_case("xml") {
+            let at_start = start == 0 || (start == 1 && self.chars[0] == '\u{feff}');
+            if !at_start {
+                return Err(self.error(ErrorKind::MisplacedXmlDeclaration, start));
+            }
+        }
+        if !self.skip_whitespace() && !self.starts_with("?>") {
+            return Err(self.invalid(start));
+        }
+        while !self.starts_with("?>") {
+            match self.peek() {
+                None => return Err(self.error(ErrorKind::UnclosedToken, start)),
+                Some(c) if !is_valid_char(c) => return Err(self.invalid(start)),
+                Some(_) => self.position += 1,
+            }
+        }
+        self.position += 2;
+        Ok(())
+    }
+
+    fn comment(&mut self) -> Result<(), XmlError> {
+        let start = self.position;
+        self.position += 4;
+        loop {
+            if self.starts_with("--") {
+                self.position += 2;
+                if self.peek() == Some('>') {
+                    self.position += 1;
+                    return Ok(());
+                }
+                return Err(self.invalid(start));
+            }
+            match self.peek() {
+                None => return Err(self.error(ErrorKind::UnclosedToken, start)),
+                Some(c) if !is_valid_char(c) => return Err(self.invalid(start)),
+                Some(_) => self.position += 1,
+            }
+        }
+    }
+
+    /// Skips the document type, keeping the general entities its internal
+    /// subset declares.
+    fn doctype(&mut self) -> Result<(), XmlError> {
+        let start = self.position;
+        self.position += "<!DOCTYPE".len();
+        let mut in_subset = false;
+        loop {
+            if in_subset && self.starts_with("<!ENTITY") {
+                self.entity_declaration(start)?;
+                continue;
+            }
+            if in_subset && self.starts_with("<!--") {
+                self.comment()?;
+                continue;
+            }
+            match self.peek() {
+                None => return Err(self.error(ErrorKind::UnclosedToken, start)),
+                Some(quote @ '"') | Some(quote @ '\'') => {
+                    self.position += 1;
+                    while self.peek().is_some_and(|c| c != quote) {
+                        self.position += 1;
+                    }
+                    if self.peek().is_none() {
+                        return Err(self.error(ErrorKind::UnclosedToken, start));
+                    }
+                    self.position += 1;
+                }
+                Some('[') if !in_subset => {
+                    in_subset = true;
+                    self.position += 1;
+                }
+                Some(']') if in_subset => {
+                    in_subset = false;
+                    self.position += 1;
+                }
+                Some('>') if !in_subset => {
+                    self.position += 1;
+                    return Ok(());
+                }
+                Some(_) => self.position += 1,
+            }
+        }
+    }
+
+    /// Reads `<!ENTITY name "value">`. Parameter entities and external ones
+    /// are skipped.
+    fn entity_declaration(&mut self, start: usize) -> Result<(), XmlError> {
+        self.position += "<!ENTITY".len();
+        self.skip_whitespace();
+        let parameter = self.peek() == Some('%');
+        if parameter {
+            self.position += 1;
+            self.skip_whitespace();
+        }
+        let name = self.name();
+        self.skip_whitespace();
+        let mut value = None;
+        if let Some(quote @ '"') | Some(quote @ '\'') = self.peek() {
+            self.position += 1;
+            let value_start = self.position;
+            while self.peek().is_some_and(|c| c != quote) {
+                self.position += 1;
+            }
+            if self.peek().is_none() {
+                return Err(self.error(ErrorKind::UnclosedToken, start));
+            }
+            value = Some(
+                self.chars[value_start..self.position]
+                    .iter()
+                    .collect::<String>(),
+            );
+            self.position += 1;
+        }
+        while self.peek().is_some_and(|c| c != '>') {
+            self.position += 1;
+        }
+        if self.peek().is_none() {
+            return Err(self.error(ErrorKind::UnclosedToken, start));
+        }
+        self.position += 1;
+        if let (false, Some(name), Some(value)) = (parameter, name, value) {
+            // The first declaration of an entity is the one that counts.
+            self.entities.entry(name).or_insert(value);
+        }
+        Ok(())
+    }
+
+    /// The root element, whose start tag is at the current position.
+    fn element(&mut self) -> Result<Node, XmlError> {
+        let mut stack: Vec<Open> = Vec::new();
+        let mut text = String::new();
+        let mut opened = Some(self.start_tag()?);
+        loop {
+            if let Some((node, name, empty, namespaces)) = opened.take() {
+                if empty {
+                    self.namespaces.truncate(namespaces);
+                    match stack.last_mut() {
+                        Some(parent) => parent.node.children.push(node),
+                        None => return Ok(node),
+                    }
+                } else {
+                    stack.push(Open {
+                        node,
+                        name,
+                        namespaces,
+                    });
+                }
+            }
+            let start = self.position;
+            match self.peek() {
+                None => return Err(self.error(ErrorKind::NoElements, start)),
+                Some('<') => {}
+                Some('&') => {
+                    let replacement = self.reference(None)?;
+                    text.push_str(&replacement);
+                    continue;
+                }
+                Some('\r') => {
+                    self.position += 1;
+                    if self.peek() == Some('\n') {
+                        self.position += 1;
+                    }
+                    text.push('\n');
+                    continue;
+                }
+                Some(']') if self.starts_with("]]>") => {
+                    return Err(self.error(ErrorKind::InvalidToken, start + 2));
+                }
+                Some(c) if !is_valid_char(c) => {
+                    return Err(self.error(ErrorKind::InvalidToken, start));
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.position += 1;
+                    continue;
+                }
+            }
+            if self.starts_with("<!--") {
+                self.comment()?;
+                continue;
+            }
+            if self.starts_with("<?") {
+                self.processing_instruction()?;
+                continue;
+            }
+            if self.starts_with("<![CDATA[") {
+                self.position += "<![CDATA[".len();
+                while !self.starts_with("]]>") {
+                    match self.peek() {
+                        None => return Err(self.error(ErrorKind::UnclosedToken, start)),
+                        Some(c) if !is_valid_char(c) => return Err(self.invalid(start)),
+                        Some(c) => {
+                            text.push(c);
+                            self.position += 1;
+                        }
+                    }
+                }
+                self.position += 3;
+                continue;
+            }
+            // The text so far belongs before the tag.
+            if !text.is_empty() {
+                let parent = &mut stack.last_mut().unwrap().node;
+                let text = Some(::std::mem::take(&mut text));
+                match parent.children.last_mut() {
+                    Some(child) => child.tail = text,
+                    None => parent.text = text,
+                }
+            }
+            if self.starts_with("</") {
+                self.position += 2;
+                let name_start = self.position;
+                let name = match self.name() {
+                    Some(name) => name,
+                    None => return Err(self.invalid(start)),
+                };
+                self.skip_whitespace();
+                if self.peek() != Some('>') {
+                    return Err(self.invalid(start));
+                }
+                self.position += 1;
+                let open = stack.pop().unwrap();
+                if open.name != name {
+                    return Err(self.error(ErrorKind::TagMismatch, name_start));
+                }
+                self.namespaces.truncate(open.namespaces);
+                match stack.last_mut() {
+                    Some(parent) => parent.node.children.push(open.node),
+                    None => return Ok(open.node),
+                }
+            } else if self.starts_with("<!") {
+                self.position += 2;
+                return Err(self.invalid(start));
+            } else {
+                opened = Some(self.start_tag()?);
+            }
+        }
+    }
+
+    /// A start tag: its element, its name, whether it is an empty-element
+    /// tag, and how many namespace declarations were in scope before it.
+    fn start_tag(&mut self) -> Result<(Node, String, bool, usize), XmlError> {
+        let start = self.position;
+        self.position += 1;
+        let name = match self.name() {
+            Some(name) => name,
+            None => return Err(self.invalid(start)),
+        };
+        let mut attributes: Vec<(String, String)> = Vec::new();
+        let empty = loop {
+            let spaced = self.skip_whitespace();
+            match self.peek() {
+                None => return Err(self.error(ErrorKind::UnclosedToken, start)),
+                Some('>') => {
+                    self.position += 1;
+                    break false;
+                }
+                Some('/') => {
+                    self.position += 1;
+                    if self.peek() != Some('>') {
+                        return Err(self.invalid(start));
+                    }
+                    self.position += 1;
+                    break true;
+                }
+                Some(_) if !spaced => return Err(self.invalid(start)),
+                Some(_) => {}
+            }
+            let name_start = self.position;
+            let attribute = match self.name() {
+                Some(attribute) => attribute,
+                None => return Err(self.invalid(start)),
+            };
+            self.skip_whitespace();
+            if self.peek() != Some('=') {
+                return Err(self.invalid(start));
+            }
+            self.position += 1;
+            self.skip_whitespace();
+            let quote = match self.peek() {
+                Some(quote @ '"') | Some(quote @ '\'') => quote,
+                _ => return Err(self.invalid(start)),
+            };
+            self.position += 1;
+            let mut value = String::new();
+            loop {
+                match self.peek() {
+                    None => return Err(self.error(ErrorKind::UnclosedToken, start)),
+                    Some(c) if c == quote => break,
+                    Some('<') => return Err(self.error(ErrorKind::InvalidToken, self.position)),
+                    Some('&') => {
+                        let replacement = self.reference(Some(start))?;
+                        value.push_str(&replacement);
+                        continue;
+                    }
+                    Some('\r') => {
+                        if self.chars.get(self.position + 1) == Some(&'\n') {
+                            self.position += 1;
+                        }
+                        value.push(' ');
+                    }
+                    Some('\t') | Some('\n') => value.push(' '),
+                    Some(c) if !is_valid_char(c) => {
+                        return Err(self.error(ErrorKind::InvalidToken, self.position));
+                    }
+                    Some(c) => value.push(c),
+                }
+                self.position += 1;
+            }
+            self.position += 1;
+            if attributes.iter().any(|(known, _)| *known == attribute) {
+                return Err(self.error(ErrorKind::DuplicateAttribute, name_start));
+            }
+            attributes.push((attribute, value));
+        };
+
+        let scope = self.namespaces.len();
+        let mut plain = Vec::with_capacity(attributes.len());
+        for (attribute, value) in attributes {
+            if attribute == "xmlns" {
+                self.namespaces.push((String::new(), value));
+            } else if let Some(prefix) = attribute.strip_prefix("xmlns:") {
+                if value.is_empty() {
+                    return Err(self.error(ErrorKind::UndeclaringPrefix, start));
+                }
+                self.namespaces.push((prefix.to_string(), value));
+            } else {
+                plain.push((attribute, value));
+            }
+        }
+        let tag = match self.qualify(&name, true) {
+            Some(tag) => tag,
+            None => return Err(self.error(ErrorKind::UnboundPrefix, start)),
+        };
+        let mut resolved = Vec::with_capacity(plain.len());
+        for (attribute, value) in plain {
+            match self.qualify(&attribute, false) {
+                Some(attribute) => resolved.push((attribute, value)),
+                None => return Err(self.error(ErrorKind::UnboundPrefix, start)),
+            }
+        }
+        Ok((Node::new(tag, resolved), name, empty, scope))
+    }
+
+    /// A name with its prefix replaced by `{uri}`, or `None` if the prefix
+    /// is not declared. Elements without a prefix are in the default
+    /// namespace; attributes without one are in none.
+    fn qualify(&self, name: &str, element: bool) -> Option<String> {
+        let (prefix, local) = match name.find(':') {
+            Some(colon) => (&name[..colon], &name[colon + 1..]),
+            None if !element => return Some(name.to_string()),
+            None => ("", name),
+        };
+        let uri = match self
+            .namespaces
+            .iter()
+            .rev()
+            .find(|(known, _)| known == prefix)
+        {
+            Some((_, uri)) => uri.as_str(),
+            None if prefix == "xml" => XML_NAMESPACE,
+            None if prefix.is_empty() => "",
+            None => return None,
+        };
+        if uri.is_empty() {
+            Some(local.to_string())
+        } else {
+            Some(format!("{{{}}}{}", uri, local))
+        }
+    }
+
+    /// The text an entity or character reference stands for. An undefined
+    /// entity in an attribute is reported at the start of its tag.
+    fn reference(&mut self, tag_start: Option<usize>) -> Result<String, XmlError> {
+        let start = self.position;
+        self.position += 1;
+        if self.peek() == Some('#') {
+            self.position += 1;
+            let radix = if self.peek() == Some('x') {
+                self.position += 1;
+                16
+            } else {
+                10
+            };
+            let digits_start = self.position;
+            while self.peek().is_some_and(|c| c.is_digit(radix)) {
+                self.position += 1;
+            }
+            if self.position == digits_start || self.peek() != Some(';') {
+                return Err(self.invalid(start));
+            }
+            let digits: String = self.chars[digits_start..self.position].iter().collect();
+            self.position += 1;
+            return match u32::from_str_radix(&digits, radix)
+                .ok()
+                .and_then(char::from_u32)
+            {
+                Some(c) if is_valid_char(c) => Ok(c.to_string()),
+                _ => Err(self.error(ErrorKind::BadCharacterReference, start)),
+            };
+        }
+        let name = match self.name() {
+            Some(name) => name,
+            None => return Err(self.invalid(start)),
+        };
+        if self.peek() != Some(';') {
+            return Err(self.invalid(start));
+        }
+        self.position += 1;
+        let replacement = match name.as_str() {
+            "lt" => "<".to_string(),
+            "gt" => ">".to_string(),
+            "amp" => "&".to_string(),
+            "quot" => "\"".to_string(),
+            "apos" => "'".to_string(),
+            _ => match self.entities.get(&name) {
+                Some(value) => expand_character_references(value),
+                None => {
+                    let at = tag_start.unwrap_or(start);
+                    return Err(self.error(ErrorKind::UndefinedEntity, at));
+                }
+            },
+        };
+        Ok(replacement)
+    }
+}
+
+/// The text of an entity declared in the document type, with its
+/// character references and predefined entities replaced.
+fn expand_character_references(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(at) = rest.find('&') {
+        text.push_str(&rest[..at]);
+        rest = &rest[at..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let name = &rest[1..end];
+        let replacement = match name {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match name.strip_prefix("#x") {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => name
+                    .strip_prefix('#')
+                    .and_then(|digits| digits.parse().ok())
+                    .and_then(char::from_u32),
+            },
+        };
+        match replacement {
+            Some(c) => {
+                text.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
@@ -0,0 +1,160 @@
+use std::fmt;
+
+/// A gitignore-style glob pattern.
+///
+/// `*` and `?` never match `/`, `**` as a whole segment matches any number of
+/// directories, and `[...]` is a character class (`[!...]` negates it). A
+/// pattern containing a `/` other than a trailing one is anchored to the
+/// directory it is evaluated against; otherwise it matches a file name at any
+/// depth. A trailing `/` restricts the pattern to directories.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    source: String,
+    segments: Vec<String>,
+    anchored: bool,
+    directory_only: bool,
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Glob {
+        let source = pattern.to_string();
+        let mut pattern = pattern;
+        let directory_only = pattern.len() > 1 && pattern.ends_with('/');
+        if directory_only {
+            pattern = &pattern[..pattern.len() - 1];
+        }
+        let anchored = pattern.contains('/');
+        let pattern = pattern.trim_start_matches('/');
+        let segments = pattern.split('/').map(|s| s.to_string()).collect();
+        Glob {
+            source,
+            segments,
+            anchored,
+            directory_only,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    /// Tests a `/`-separated path relative to the pattern's base directory.
+    pub fn is_match(&self, path: &str, is_dir: bool) -> bool {
+        if self.directory_only && !is_dir {
+            return false;
+        }
+        let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+        if self.anchored {
+            match_segments(&self.segments, &parts)
+        } else {
+            parts
+                .last()
+                .is_some_and(|name| match_segment(self.segments[0].as_bytes(), name.as_bytes()))
+        }
+    }
+}
+
+impl fmt::Display for Glob {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+fn match_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path_rest)) => {
+                match_segment(first.as_bytes(), name.as_bytes()) && match_segments(rest, path_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+fn match_segment(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some(b'*') => (0..=name.len()).any(|skip| match_segment(&pattern[1..], &name[skip..])),
+        Some(b'?') => !name.is_empty() && match_segment(&pattern[1..], utf8_tail(name)),
+        Some(b'[') => match parse_class(&pattern[1..]) {
+            Some((class, consumed)) => match name.first() {
+                Some(_) => {
+                    let c = first_char(name);
+                    class.contains(c) && match_segment(&pattern[1 + consumed..], utf8_tail(name))
+                }
+                None => false,
+            },
+            None => name.first() == Some(&b'[') && match_segment(&pattern[1..], &name[1..]),
+        },
+        Some(b'\\') if pattern.len() > 1 => {
+            name.first() == Some(&pattern[1]) && match_segment(&pattern[2..], &name[1..])
+        }
+        Some(&c) => name.first() == Some(&c) && match_segment(&pattern[1..], &name[1..]),
+    }
+}
+
+fn first_char(bytes: &[u8]) -> char {
+    String::from_utf8_lossy(&bytes[..utf8_width(bytes[0]).min(bytes.len())])
+        .chars()
+        .next()
+        .unwrap_or('\u{fffd}')
+}
+
+fn utf8_tail(bytes: &[u8]) -> &[u8] {
+    &bytes[utf8_width(bytes[0]).min(bytes.len())..]
+}
+
+fn utf8_width(first: u8) -> usize {
+    match first {
+        0x00..=0x7f => 1,
+        0xc0..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf7 => 4,
+        _ => 1,
+    }
+}
+
+struct CharClass {
+    negated: bool,
+    ranges: Vec<(char, char)>,
+}
+
+impl CharClass {
+    fn contains(&self, c: char) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != self.negated
+    }
+}
+
+/// Parses the body of a `[...]` class, returning it and the number of pattern
+/// bytes consumed including the closing `]`.
+fn parse_class(pattern: &[u8]) -> Option<(CharClass, usize)> {
+    let end = pattern
+        .iter()
+        .enumerate()
+        .skip(1)
+        .find(|&(_, &b)| b == b']')
+        .map(|(i, _)| i)?;
+    let body = String::from_utf8_lossy(&pattern[..end]).into_owned();
+    let (negated, body) = if body.starts_with('!') || body.starts_with('^') {
+        (true, &body[1..])
+    } else {
+        (false, &body[..])
+    };
+    let chars: Vec<char> = body.chars().collect();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 2 < chars.len() && chars[i + 1] == '-' {
+            ranges.push((chars[i], chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((chars[i], chars[i]));
+            i += 1;
+        }
+    }
+    Some((CharClass { negated, ranges }, end + 1))
+}
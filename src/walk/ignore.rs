@@ -0,0 +1,121 @@
+use std::sync::Arc;
+
+use super::glob::Glob;
+
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    negated: bool,
+}
+
+/// The rules of one `.gitignore` file, evaluated relative to the directory
+/// containing it.
+#[derive(Debug, Clone)]
+pub struct IgnoreFile {
+    base: String,
+    /// For a file above the walk root, the root's path relative to the
+    /// file's directory.
+    root: String,
+    rules: Vec<Rule>,
+}
+
+impl IgnoreFile {
+    /// Parses gitignore text. `base` is the `/`-separated directory, relative
+    /// to the walk root, that the file lives in ("" for the root itself).
+    pub fn parse(base: &str, text: &str) -> IgnoreFile {
+        IgnoreFile {
+            base: base.to_string(),
+            root: String::new(),
+            rules: rules(text),
+        }
+    }
+
+    /// Parses the gitignore text of a directory above the walk root. `root`
+    /// is the `/`-separated path of the walk root relative to it.
+    pub fn above(root: &str, text: &str) -> IgnoreFile {
+        IgnoreFile {
+            base: String::new(),
+            root: root.to_string(),
+            rules: rules(text),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// `Some(true)` if the last matching rule ignores the path, `Some(false)`
+    /// if it re-includes it, and `None` if no rule applies.
+    pub fn matched(&self, path: &str, is_dir: bool) -> Option<bool> {
+        let joined;
+        let relative = if !self.root.is_empty() {
+            joined = format!("{}/{}", self.root, path);
+            &joined
+        } else if self.base.is_empty() {
+            path
+        } else if path.starts_with(&self.base) && path[self.base.len()..].starts_with('/') {
+            &path[self.base.len() + 1..]
+        } else {
+            return None;
+        };
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| rule.glob.is_match(relative, is_dir))
+            .map(|rule| !rule.negated)
+    }
+}
+
+fn rules(text: &str) -> Vec<Rule> {
+    let mut rules = Vec::new();
+    for line in text.lines() {
+        let line = line.trim_end_matches('\r');
+        let line = if line.ends_with("\\ ") {
+            line
+        } else {
+            line.trim_end_matches(' ')
+        };
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (negated, pattern) = if let Some(pattern) = line.strip_prefix('!') {
+            (true, pattern)
+        } else if line.starts_with("\\!") || line.starts_with("\\#") {
+            (false, &line[1..])
+        } else {
+            (false, line)
+        };
+        rules.push(Rule {
+            glob: Glob::new(pattern),
+            negated,
+        });
+    }
+    rules
+}
+
+/// The chain of ignore files in effect for a directory, innermost first.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreStack {
+    file: Option<IgnoreFile>,
+    parent: Option<Arc<IgnoreStack>>,
+}
+
+impl IgnoreStack {
+    pub fn push(parent: &Arc<IgnoreStack>, file: IgnoreFile) -> Arc<IgnoreStack> {
+        Arc::new(IgnoreStack {
+            file: Some(file),
+            parent: Some(parent.clone()),
+        })
+    }
+
+    pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+        let mut current = Some(self);
+        while let Some(stack) = current {
+            if let Some(ignored) = stack.file.as_ref().and_then(|f| f.matched(path, is_dir)) {
+                return ignored;
+            }
+            current = stack.parent.as_deref();
+        }
+        false
+    }
+}
@@ -0,0 +1,429 @@
+mod glob;
+mod ignore;
+
+pub use self::glob::Glob;
+pub use self::ignore::{IgnoreFile, IgnoreStack};
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+pub const DEFAULT_INCLUDE: &[&str] = &["*.py", "*.pyi"];
+pub const DEFAULT_EXCLUDE: &[&str] = &[
+    ".git/",
+    ".hg/",
+    ".svn/",
+    ".tox/",
+    ".nox/",
+    ".venv/",
+    "__pycache__/",
+    "node_modules/",
+];
+
+/// What to do with symbolic links met while walking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SymlinkPolicy {
+    Skip,
+    /// Follow links, visiting each real directory at most once.
+    Follow,
+}
+
+#[derive(Debug)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl ::std::error::Error for WalkError {}
+
+#[derive(Debug, Default)]
+pub struct Walk {
+    /// Discovered files, sorted.
+    pub files: Vec<PathBuf>,
+    pub errors: Vec<WalkError>,
+}
+
+/// Walks one or more roots in parallel, yielding the source files to process.
+///
+/// Files passed directly as roots are always included. Inside directories,
+/// `.gitignore` files are honoured (unless disabled), those of the directories
+/// above a root up to its repository's included, then the exclude globs
+/// prune files and whole directories, and finally a file must match one of
+/// the include globs. Globs are matched against the path relative to its
+/// root, except those given to `exclude_under`.
+#[derive(Debug, Clone)]
+pub struct Walker {
+    roots: Vec<PathBuf>,
+    include: Vec<Glob>,
+    exclude: Vec<Glob>,
+    anchored: Vec<(PathBuf, Glob)>,
+    gitignore: bool,
+    symlinks: SymlinkPolicy,
+    threads: usize,
+}
+
+impl Walker {
+    pub fn new<P: AsRef<Path>>(root: P) -> Walker {
+        Walker {
+            roots: vec![root.as_ref().to_path_buf()],
+            include: Vec::new(),
+            exclude: DEFAULT_EXCLUDE.iter().map(|p| Glob::new(p)).collect(),
+            anchored: Vec::new(),
+            gitignore: true,
+            symlinks: SymlinkPolicy::Skip,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+
+    pub fn root<P: AsRef<Path>>(mut self, root: P) -> Walker {
+        self.roots.push(root.as_ref().to_path_buf());
+        self
+    }
+
+    /// Adds an include glob. Once any is given, the defaults (`*.py`, `*.pyi`)
+    /// no longer apply.
+    pub fn include(mut self, pattern: &str) -> Walker {
+        self.include.push(Glob::new(pattern));
+        self
+    }
+
+    pub fn exclude(mut self, pattern: &str) -> Walker {
+        self.exclude.push(Glob::new(pattern));
+        self
+    }
+
+    /// Adds an exclude glob matched against paths relative to `directory`
+    /// instead of to the root, as a project's settings give them. It applies
+    /// to the roots in `directory` or below it.
+    pub fn exclude_under<P: AsRef<Path>>(mut self, directory: P, pattern: &str) -> Walker {
+        self.anchored
+            .push((directory.as_ref().to_path_buf(), Glob::new(pattern)));
+        self
+    }
+
+    /// Drops the built-in excludes such as `.git/` and `__pycache__/`.
+    pub fn clear_excludes(mut self) -> Walker {
+        self.exclude.clear();
+        self
+    }
+
+    pub fn gitignore(mut self, enabled: bool) -> Walker {
+        self.gitignore = enabled;
+        self
+    }
+
+    pub fn symlinks(mut self, policy: SymlinkPolicy) -> Walker {
+        self.symlinks = policy;
+        self
+    }
+
+    pub fn threads(mut self, threads: usize) -> Walker {
+        self.threads = threads.max(1);
+        self
+    }
+
+    fn is_included(&self, relative: &str) -> bool {
+        if self.include.is_empty() {
+            DEFAULT_INCLUDE
+                .iter()
+                .any(|p| Glob::new(p).is_match(relative, false))
+        } else {
+            self.include.iter().any(|g| g.is_match(relative, false))
+        }
+    }
+
+    fn is_excluded(&self, relative: &str, is_dir: bool) -> bool {
+        self.exclude.iter().any(|g| g.is_match(relative, is_dir))
+    }
+
+    /// The `exclude_under` globs that apply below `root`, each with the path
+    /// of `root` relative to the glob's directory.
+    fn anchored_under(&self, root: &Path) -> Vec<(String, Glob)> {
+        let mut anchored = Vec::new();
+        for (directory, glob) in &self.anchored {
+            let directory = match fs::canonicalize(directory) {
+                Ok(directory) => directory,
+                Err(_) => continue,
+            };
+            if let Ok(prefix) = root.strip_prefix(&directory) {
+                anchored.push((slashed(prefix), glob.clone()));
+            }
+        }
+        anchored
+    }
+
+    pub fn walk(&self) -> Walk {
+        let shared = Shared {
+            walker: self,
+            queue: Mutex::new(Queue::default()),
+            ready: Condvar::new(),
+            visited: Mutex::new(HashSet::new()),
+            output: Mutex::new(Walk::default()),
+        };
+
+        for root in &self.roots {
+            match fs::metadata(root) {
+                Ok(ref metadata) if metadata.is_dir() => {
+                    let mut anchored = Vec::new();
+                    let mut ignores = Arc::new(IgnoreStack::default());
+                    if let Ok(canonical) = fs::canonicalize(root) {
+                        anchored = self.anchored_under(&canonical);
+                        if self.gitignore {
+                            ignores = shared.ancestor_ignores(&canonical);
+                        }
+                        if !shared.visited.lock().unwrap().insert(canonical) {
+                            continue;
+                        }
+                    }
+                    shared.queue.lock().unwrap().jobs.push(Job {
+                        dir: root.clone(),
+                        relative: String::new(),
+                        ignores,
+                        anchored: Arc::new(anchored),
+                    });
+                }
+                Ok(_) => shared.output.lock().unwrap().files.push(root.clone()),
+                Err(error) => shared.output.lock().unwrap().errors.push(WalkError {
+                    path: root.clone(),
+                    error,
+                }),
+            }
+        }
+
+        thread::scope(|scope| {
+            for _ in 0..self.threads {
+                scope.spawn(|| shared.work());
+            }
+        });
+
+        let mut output = shared.output.into_inner().unwrap();
+        output.files.sort();
+        output.files.dedup();
+        output
+    }
+}
+
+struct Job {
+    dir: PathBuf,
+    relative: String,
+    ignores: Arc<IgnoreStack>,
+    /// The root's `exclude_under` globs, after the root's path relative to
+    /// their directory.
+    anchored: Arc<Vec<(String, Glob)>>,
+}
+
+#[derive(Default)]
+struct Queue {
+    jobs: Vec<Job>,
+    active: usize,
+}
+
+struct Shared<'a> {
+    walker: &'a Walker,
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    visited: Mutex<HashSet<PathBuf>>,
+    output: Mutex<Walk>,
+}
+
+impl<'a> Shared<'a> {
+    fn work(&self) {
+        loop {
+            let job = {
+                let mut queue = self.queue.lock().unwrap();
+                loop {
+                    if let Some(job) = queue.jobs.pop() {
+                        queue.active += 1;
+                        break job;
+                    }
+                    if queue.active == 0 {
+                        self.ready.notify_all();
+                        return;
+                    }
+                    queue = self.ready.wait(queue).unwrap();
+                }
+            };
+
+            self.visit(job);
+
+            let mut queue = self.queue.lock().unwrap();
+            queue.active -= 1;
+            if queue.active == 0 && queue.jobs.is_empty() {
+                self.ready.notify_all();
+            }
+        }
+    }
+
+    fn error(&self, path: PathBuf, error: io::Error) {
+        self.output
+            .lock()
+            .unwrap()
+            .errors
+            .push(WalkError { path, error });
+    }
+
+    /// The `.gitignore` files of the directories above `root` in its
+    /// repository, outermost first, for a root inside one. Those above the
+    /// directory holding `.git` do not apply.
+    fn ancestor_ignores(&self, root: &Path) -> Arc<IgnoreStack> {
+        let mut ignores = Arc::new(IgnoreStack::default());
+        if root.join(".git").exists() {
+            return ignores;
+        }
+        let ancestors: Vec<&Path> = root.ancestors().skip(1).collect();
+        let top = match ancestors.iter().position(|dir| dir.join(".git").exists()) {
+            Some(top) => top,
+            None => return ignores,
+        };
+        for dir in ancestors[..=top].iter().rev() {
+            let path = dir.join(".gitignore");
+            match fs::read_to_string(&path) {
+                Ok(text) => {
+                    let file = IgnoreFile::above(&slashed(root.strip_prefix(dir).unwrap()), &text);
+                    if !file.is_empty() {
+                        ignores = IgnoreStack::push(&ignores, file);
+                    }
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => self.error(path, err),
+            }
+        }
+        ignores
+    }
+
+    fn visit(&self, job: Job) {
+        let walker = self.walker;
+        let mut ignores = job.ignores.clone();
+        if walker.gitignore {
+            let path = job.dir.join(".gitignore");
+            match fs::read_to_string(&path) {
+                Ok(text) => {
+                    let file = IgnoreFile::parse(&job.relative, &text);
+                    if !file.is_empty() {
+                        ignores = IgnoreStack::push(&ignores, file);
+                    }
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+                Err(err) => self.error(path, err),
+            }
+        }
+
+        let entries = match fs::read_dir(&job.dir) {
+            Ok(entries) => entries,
+            Err(err) => return self.error(job.dir, err),
+        };
+
+        let mut files = Vec::new();
+        let mut subdirs = Vec::new();
+        let mut links = Vec::new();
+        for entry in entries {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    self.error(job.dir.clone(), err);
+                    continue;
+                }
+            };
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let relative = if job.relative.is_empty() {
+                name
+            } else {
+                format!("{}/{}", job.relative, name)
+            };
+
+            let file_type = match entry.file_type() {
+                Ok(file_type) => file_type,
+                Err(err) => {
+                    self.error(path, err);
+                    continue;
+                }
+            };
+            let is_dir = if file_type.is_symlink() {
+                if walker.symlinks == SymlinkPolicy::Skip {
+                    continue;
+                }
+                match fs::metadata(&path) {
+                    Ok(metadata) => metadata.is_dir(),
+                    Err(err) => {
+                        self.error(path, err);
+                        continue;
+                    }
+                }
+            } else {
+                file_type.is_dir()
+            };
+
+            if walker.is_excluded(&relative, is_dir)
+                || job.anchored.iter().any(|(prefix, glob)| {
+                    if prefix.is_empty() {
+                        glob.is_match(&relative, is_dir)
+                    } else {
+                        glob.is_match(&format!("{}/{}", prefix, relative), is_dir)
+                    }
+                })
+                || (walker.gitignore && ignores.is_ignored(&relative, is_dir))
+            {
+                continue;
+            }
+
+            if is_dir {
+                let job = Job {
+                    dir: path,
+                    relative,
+                    ignores: ignores.clone(),
+                    anchored: job.anchored.clone(),
+                };
+                if file_type.is_symlink() {
+                    links.push(job);
+                } else {
+                    subdirs.push(job);
+                }
+            } else if walker.is_included(&relative) {
+                files.push(path);
+            }
+        }
+
+        // Every directory is recorded, not only those reached through links,
+        // so that a directory and a link to it are not both visited. Links
+        // come last, so that a directory beside a link to it is visited by
+        // its own name.
+        let mut dirs = Vec::with_capacity(subdirs.len() + links.len());
+        for job in subdirs.into_iter().chain(links) {
+            match fs::canonicalize(&job.dir) {
+                Ok(canonical) => {
+                    if self.visited.lock().unwrap().insert(canonical) {
+                        dirs.push(job);
+                    }
+                }
+                Err(err) => self.error(job.dir, err),
+            }
+        }
+
+        if !files.is_empty() {
+            self.output.lock().unwrap().files.extend(files);
+        }
+        if !dirs.is_empty() {
+            self.queue.lock().unwrap().jobs.extend(dirs);
+            self.ready.notify_all();
+        }
+    }
+}
+
+/// `path` with its components separated by `/`, as globs are matched.
+fn slashed(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,84 @@
+//! Annotations are kept in `__annotations__`: evaluated, where subscripting
+//! a builtin class gives a `types.GenericAlias`, or as strings under
+//! `from __future__ import annotations`.
+
+extern crate rustpy;
+
+mod common;
+
+use common::output;
+
+#[test]
+fn annotations_are_evaluated() {
+    let source = "\
+z: list[int] = []
+w: int
+class C:
+    a: dict[str, int]
+    b: int = 1
+def f(x: tuple[int, str], *args: int, y: str = '', **kw: float) -> list[int]: pass
+print(__annotations__)
+print(C.__annotations__)
+print(f.__annotations__)
+";
+    let expected = "\
+{'z': list[int], 'w': <class 'int'>}
+{'a': dict[str, int], 'b': <class 'int'>}
+{'x': tuple[int, str], 'args': <class 'int'>, 'y': <class 'str'>, 'kw': <class 'float'>, 'return': list[int]}
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
+
+#[test]
+fn annotations_are_strings_under_the_future_import() {
+    let source = "\
+from __future__ import annotations
+z: list[int] = []
+w: undefined_name
+class C:
+    a: dict[str, C]
+    b: int = 1
+def f(x: Undefined, *args: int, y: str = '', **kw: float) -> list[int]: pass
+print(__annotations__)
+print(C.__annotations__)
+print(f.__annotations__)
+";
+    let expected = "\
+{'z': 'list[int]', 'w': 'undefined_name'}
+{'a': 'dict[str, C]', 'b': 'int'}
+{'x': 'Undefined', 'args': 'int', 'y': 'str', 'kw': 'float', 'return': 'list[int]'}
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
+
+#[test]
+fn subscripted_builtin_classes_are_generic_aliases() {
+    let source = "\
+import types
+print(list[int], dict[str, list[int]], tuple[int, ...], type[int], tuple[()])
+print(isinstance(set[int], types.GenericAlias), frozenset[str].__origin__)
+print(list[int] == list[int], list[int] == list[str], hash(set[int]) == hash(set[int]))
+print(list[int]('ab'), dict[str, int].__args__)
+class C: pass
+class L(list): pass
+print(list[C], L[int])
+class G:
+    def __class_getitem__(cls, item):
+        return (cls.__name__, item)
+print(G[int])
+try:
+    C[int]
+except TypeError as error:
+    print(error)
+";
+    let expected = "\
+list[int] dict[str, list[int]] tuple[int, ...] type[int] tuple[()]
+True <class 'frozenset'>
+True False True
+['a', 'b'] (<class 'str'>, <class 'int'>)
+list[__main__.C] __main__.L[int]
+('G', <class 'int'>)
+'type' object is not subscriptable
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
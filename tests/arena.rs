@@ -0,0 +1,201 @@
+//! `ast::Arena` stores the nodes of a parsed module in preorder, each
+//! referring to its children by id in the order of their fields, and puts
+//! the same tree back together.
+
+extern crate rustpy;
+
+mod common;
+
+use std::fs;
+use std::path::Path;
+
+use rustpy::ast::arena::NodeKind;
+use rustpy::ast::{Arena, NodeId};
+use rustpy::{parser, tokenizer};
+
+use common::python_files;
+
+fn arena(source: &str) -> Arena {
+    Arena::new(parser::parse(source).unwrap())
+}
+
+/// The name of the kind of a node, as its class is named in Python.
+fn kind(kind: &NodeKind) -> String {
+    let debug = format!("{:?}", kind);
+    debug.chars().take_while(|c| c.is_alphanumeric()).collect()
+}
+
+fn kinds(arena: &Arena, ids: &[NodeId]) -> Vec<String> {
+    ids.iter().map(|&id| kind(&arena[id].kind)).collect()
+}
+
+/// A module with a node of every kind.
+const EVERY_KIND: &str = r#"
+import a.b as c, d
+from .. import e as f
+@dec(1, *g, k=2, **h)
+async def func[T: int, *Ts, **P](x, /, y: int = 1, *args, z=None, w, **kw) -> None:
+    global i
+    nonlocal j
+    del k[0], l.m
+    n: int = 1
+    n += await o
+    async for p in q:
+        break
+    else:
+        continue
+    async with r as s, t:
+        yield
+        yield from u
+    while v:
+        pass
+    return lambda *a, **k: a if k else not k
+class C(B, metaclass=M):
+    x = {1: 2, **y}, {1, 2}, [*z], (w := 3)
+    y = [i async for i in j if i], {i for i in j}, {i: i for i in j}, (i for i in j)
+    z = a < b <= c, f"{a!r:>{b}}", x[1:2:3], -x, a and b or c, b"\x00", 1.5j, ...
+type Alias[T] = list[T]
+try:
+    raise E from None
+except* (E, F) as e:
+    assert e, "message"
+finally:
+    pass
+try:
+    pass
+except E:
+    pass
+else:
+    pass
+if a:
+    pass
+elif b:
+    pass
+match p:
+    case 1 | 2 | None:
+        pass
+    case [a, *rest] if rest:
+        pass
+    case {"k": v, **kw}:
+        pass
+    case Point(0, y=y) as point:
+        pass
+    case _:
+        pass
+"#;
+
+#[test]
+fn the_tree_is_put_back_together_as_it_was() {
+    let module = parser::parse(EVERY_KIND).unwrap();
+    assert!(Arena::new(module.clone()).to_module() == module);
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut files = Vec::new();
+    for directory in &["benches", "python", "tests"] {
+        python_files(&root.join(directory), &mut files);
+    }
+    assert!(!files.is_empty());
+    for path in files {
+        let source = fs::read_to_string(&path).unwrap();
+        let module = match parser::parse(tokenizer::strip_bom(&source)) {
+            Ok(module) => module,
+            Err(_) => continue,
+        };
+        let arena = Arena::new(module.clone());
+        assert!(arena.to_module() == module, "{}", path.display());
+    }
+}
+
+#[test]
+fn nodes_are_numbered_in_preorder() {
+    let source = "\
+def f(a, *, b=1) -> int:
+    return a + b
+x = [f(i, b=2) for i in range(3) if i]
+";
+    let arena = arena(source);
+    let ids: Vec<NodeId> = arena.iter().map(|(id, _)| id).collect();
+    assert_eq!(
+        kinds(&arena, &ids),
+        [
+            "FunctionDef",
+            "Arg",
+            "Arg",
+            "Constant",
+            "Return",
+            "BinOp",
+            "Name",
+            "Name",
+            "Name",
+            "Assign",
+            "Name",
+            "ListComp",
+            "Call",
+            "Name",
+            "Name",
+            "Keyword",
+            "Constant",
+            "Name",
+            "Call",
+            "Name",
+            "Constant",
+            "Name",
+        ]
+    );
+    for (index, &id) in ids.iter().enumerate() {
+        assert_eq!(id.index(), index);
+        for child in arena.children(id) {
+            assert_eq!(arena.parent(child), Some(id));
+        }
+        for descendant in arena.descendants(id) {
+            assert!(arena.ancestors(descendant).any(|ancestor| ancestor == id));
+        }
+    }
+    assert_eq!(kinds(&arena, arena.body()), ["FunctionDef", "Assign"]);
+    assert_eq!(arena.parent(arena.body()[0]), None);
+}
+
+#[test]
+fn children_come_in_the_order_of_their_fields() {
+    let arena = arena("{a: b, **c}\n@d\ndef f[T](x): pass\n");
+    let dict = arena.children(arena.body()[0]).next().unwrap();
+    let children: Vec<NodeId> = arena.children(dict).collect();
+    let names: Vec<String> = children
+        .iter()
+        .map(|&id| match arena[id].kind {
+            NodeKind::Name { ref id, .. } => id.clone(),
+            ref other => kind(other),
+        })
+        .collect();
+    assert_eq!(names, ["a", "b", "c"]);
+    match arena[dict].kind {
+        NodeKind::Dict {
+            ref keys,
+            ref values,
+        } => {
+            assert_eq!(keys, &[Some(children[0]), None]);
+            assert_eq!(values, &[children[1], children[2]]);
+        }
+        ref other => panic!("{:?}", other),
+    }
+    let function: Vec<NodeId> = arena.children(arena.body()[1]).collect();
+    assert_eq!(kinds(&arena, &function), ["Arg", "Pass", "Name", "TypeVar"]);
+}
+
+#[test]
+fn ancestors_lead_to_the_statement_of_the_module() {
+    let arena = arena("if x:\n    while y:\n        z.w = 1\n");
+    let (z, _) = arena
+        .iter()
+        .find(|&(_, node)| match node.kind {
+            NodeKind::Name { ref id, .. } => id == "z",
+            _ => false,
+        })
+        .unwrap();
+    let ancestors: Vec<NodeId> = arena.ancestors(z).collect();
+    assert_eq!(
+        kinds(&arena, &ancestors),
+        ["Attribute", "Assign", "While", "If"]
+    );
+    assert_eq!(arena[z].start.line, 3);
+    assert_eq!(ancestors.last(), arena.body().last());
+}
@@ -0,0 +1,39 @@
+//! `rustpy build` makes an executable that runs a script, with the modules
+//! it imports from its directory frozen into it, and needs nothing else.
+
+extern crate rustpy;
+
+mod common;
+
+use std::env;
+use std::process::Command;
+
+use rustpy::bundle::build_executable;
+
+use common::{project, write};
+
+#[test]
+fn a_bundled_script_runs_with_its_modules() {
+    let directory = project("bundle");
+    write(
+        &directory,
+        "hb.py",
+        "import sys\nfrom greeting import hello\nprint(hello(sys.argv[1]))\n",
+    );
+    write(
+        &directory,
+        "greeting.py",
+        "def hello(name):\n    return 'hello, ' + name\n",
+    );
+    let output = directory.join(format!("hb{}", env::consts::EXE_SUFFIX));
+    build_executable(&directory.join("hb.py"), &output).unwrap();
+    // The modules are in the executable, not read from beside it.
+    std::fs::remove_file(directory.join("greeting.py")).unwrap();
+    let run = Command::new(&output).arg("world").output().unwrap();
+    assert!(
+        run.status.success(),
+        "{}",
+        String::from_utf8_lossy(&run.stderr)
+    );
+    assert_eq!(String::from_utf8_lossy(&run.stdout), "hello, world\n");
+}
@@ -0,0 +1,71 @@
+//! Calls keep their positional and unpacked arguments in one list and
+//! their keyword and `**` arguments in another, each in source order, and
+//! pass them that way.
+
+extern crate rustpy;
+
+mod common;
+
+use common::output;
+use rustpy::ast::{ExprKind, StmtKind};
+use rustpy::parser;
+
+/// The arguments of the call `source`, written as CPython's `ast.dump`
+/// names them.
+fn call_arguments(source: &str) -> (Vec<String>, Vec<Option<String>>) {
+    let module = parser::parse(source).unwrap();
+    let call = match module.body[0].kind {
+        StmtKind::Expr(ref expr) => &expr.kind,
+        ref other => panic!("{:?}", other),
+    };
+    match *call {
+        ExprKind::Call {
+            ref args,
+            ref keywords,
+            ..
+        } => {
+            let args = args
+                .iter()
+                .map(|arg| match arg.kind {
+                    ExprKind::Starred { .. } => "Starred".to_string(),
+                    ExprKind::GeneratorExp { .. } => "GeneratorExp".to_string(),
+                    ExprKind::Name { ref id, .. } => id.clone(),
+                    ref other => format!("{:?}", other),
+                })
+                .collect();
+            let keywords = keywords.iter().map(|keyword| keyword.arg.clone()).collect();
+            (args, keywords)
+        }
+        ref other => panic!("{:?}", other),
+    }
+}
+
+#[test]
+fn calls_keep_their_arguments_in_order() {
+    let (args, keywords) = call_arguments("f(*a, b, *c, d=1, **e, g=2)");
+    assert_eq!(args, ["Starred", "b", "Starred"]);
+    assert_eq!(
+        keywords,
+        [Some("d".to_string()), None, Some("g".to_string())]
+    );
+    let (args, keywords) = call_arguments("f(x for x in y)");
+    assert_eq!(args, ["GeneratorExp"]);
+    assert!(keywords.is_empty());
+}
+
+#[test]
+fn unpacked_arguments_are_passed_in_order() {
+    let source = "\
+def f(*args, **kwargs):
+    print(args, sorted(kwargs.items()))
+a, c, e = [1, 2], (3,), {'e': 5}
+f(*a, 0, *c, d=4, **e, g=6)
+f(x * 2 for x in a)
+f(d=4, *a)
+";
+    let printed = output(source).unwrap();
+    let lines: Vec<&str> = printed.lines().collect();
+    assert_eq!(lines[0], "(1, 2, 0, 3) [('d', 4), ('e', 5), ('g', 6)]");
+    assert!(lines[1].starts_with("(<generator object"), "{}", lines[1]);
+    assert_eq!(lines[2], "(1, 2) [('d', 4)]");
+}
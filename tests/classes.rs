@@ -0,0 +1,284 @@
+//! Classes run as in CPython: bodies run in their own namespace, bases are
+//! ordered by C3 linearization, `super()` and descriptors find what they
+//! should, metaclasses make classes, and the methods a class defines for
+//! operators, comparisons, hashing and containers are the ones applied, with
+//! those of `object` behind them.
+
+extern crate rustpy;
+
+mod common;
+
+use common::output;
+
+#[test]
+fn a_class_body_runs_in_its_own_namespace() {
+    let source = "\
+x = 'module'
+class A:
+    x = 'class'
+    y = x + '!'
+    def f(self):
+        return x
+print(A.y, A().f(), x)
+";
+    assert_eq!(output(source).unwrap(), "class! module module\n");
+}
+
+#[test]
+fn the_mro_is_the_c3_linearization() {
+    let source = "\
+class O: pass
+class A(O): pass
+class B(O): pass
+class C(A, B): pass
+print([k.__name__ for k in C.__mro__])
+";
+    assert_eq!(output(source).unwrap(), "['C', 'A', 'B', 'O', 'object']\n");
+    let inconsistent = "\
+class A: pass
+class B(A): pass
+class C(A, B): pass
+";
+    assert_eq!(
+        output(inconsistent).unwrap_err(),
+        "TypeError: Cannot create a consistent method resolution\norder (MRO) for bases A, B"
+    );
+}
+
+#[test]
+fn super_follows_the_mro_of_the_instance() {
+    let source = "\
+class A:
+    def who(self): return ['A']
+class B(A):
+    def who(self): return ['B'] + super().who()
+class C(A):
+    def who(self): return ['C'] + super().who()
+class D(B, C):
+    def who(self): return ['D'] + super().who()
+print(D().who())
+";
+    assert_eq!(output(source).unwrap(), "['D', 'B', 'C', 'A']\n");
+}
+
+#[test]
+fn descriptors_are_bound_through_the_class() {
+    let source = "\
+class Celsius:
+    def __get__(self, obj, owner):
+        return obj.kelvin - 273
+    def __set__(self, obj, value):
+        obj.kelvin = value + 273
+class T:
+    c = Celsius()
+    @property
+    def double(self): return self.kelvin * 2
+    @staticmethod
+    def s(): return 's'
+    @classmethod
+    def k(cls): return cls.__name__
+t = T()
+t.c = 10
+print(t.kelvin, t.c, t.double, T.s(), t.k())
+";
+    assert_eq!(output(source).unwrap(), "283 10 566 s T\n");
+}
+
+#[test]
+fn type_makes_classes_and_metaclasses_are_called() {
+    let source = "\
+P = type('P', (), {'x': 1})
+print(P.__name__, P().x, type(P()) is P)
+class Meta(type):
+    def __new__(mcs, name, bases, namespace):
+        namespace['tag'] = name.lower()
+        return super().__new__(mcs, name, bases, namespace)
+class Q(metaclass=Meta): pass
+print(Q.tag, type(Q).__name__)
+";
+    assert_eq!(output(source).unwrap(), "P 1 True\nq Meta\n");
+}
+
+#[test]
+fn comparisons_and_hashing_use_the_methods_classes_define() {
+    let source = "\
+class K:
+    def __init__(self, k): self.k = k
+    def __eq__(self, other): return self.k == other.k
+    def __lt__(self, other): return self.k < other.k
+    def __hash__(self): return hash(self.k)
+print(K(1) == K(1), K(1) != K(2), K(2) > K(1), len({K(1), K(1)}))
+print(sorted([K(3), K(1), K(2)])[0].k)
+";
+    assert_eq!(output(source).unwrap(), "True True True 1\n1\n");
+}
+
+#[test]
+fn comparisons_fall_back_as_in_cpython() {
+    let source = "\
+class A:
+    def __eq__(self, o): return True
+print(A.__hash__)
+try:
+    hash(A())
+except TypeError as e:
+    print(e)
+class B:
+    def __lt__(self, o): return 'B.lt'
+class C(B):
+    def __gt__(self, o): return 'C.gt'
+print(B() < C(), C() > B())
+class D:
+    def __eq__(self, o): return NotImplemented
+d = D()
+print(d == d, d != D(), d == 1)
+";
+    let expected = "\
+None
+unhashable type: 'A'
+C.gt C.gt
+True True False
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
+
+const NUMBER: &str = "\
+class W:
+    def __init__(self, x): self.x = x
+    def __add__(self, o):
+        if isinstance(o, W): return W(self.x + o.x)
+        if isinstance(o, int): return W(self.x + o)
+        return NotImplemented
+    def __radd__(self, o): return W(o + self.x)
+    def __iadd__(self, o): self.x += o.x; return self
+    def __neg__(self): return W(-self.x)
+    def __invert__(self): return 'inv'
+    def __repr__(self): return f'W({self.x})'
+";
+
+#[test]
+fn operators_use_the_methods_classes_define() {
+    let source = format!(
+        "{}print(W(1) + W(2), 3 + W(1), W(1) + 3, -W(5), ~W(1))\n",
+        NUMBER
+    );
+    assert_eq!(output(&source).unwrap(), "W(3) W(4) W(4) W(-5) inv\n");
+}
+
+#[test]
+fn augmented_assignment_uses_the_in_place_method() {
+    let source = format!("{}w = W(1)\nv = w\nw += W(4)\nprint(w, w is v)\n", NUMBER);
+    assert_eq!(output(&source).unwrap(), "W(5) True\n");
+}
+
+#[test]
+fn a_subclass_reflected_method_is_tried_first() {
+    let source = format!(
+        "{}class S(W):\n    def __radd__(self, o): return 'S radd'\nprint(W(1) + S(2))\n",
+        NUMBER
+    );
+    assert_eq!(output(&source).unwrap(), "S radd\n");
+    let subclass_of_int = "\
+class I(int):
+    def __add__(self, o): return 'I add'
+print(I(1) + 1, 1 + I(1), I(2) * 3)
+";
+    assert_eq!(output(subclass_of_int).unwrap(), "I add 2 6\n");
+}
+
+#[test]
+fn not_implemented_from_both_sides_is_a_type_error() {
+    let source = format!("{}W(1) + 'a'\n", NUMBER);
+    assert_eq!(
+        output(&source).unwrap_err(),
+        "TypeError: unsupported operand type(s) for +: 'W' and 'str'"
+    );
+    assert_eq!(
+        output("-object()\n").unwrap_err(),
+        "TypeError: bad operand type for unary -: 'object'"
+    );
+}
+
+#[test]
+fn object_methods_are_there_for_super() {
+    let source = "\
+class P:
+    def __init__(self, x): self.x = x
+    def __repr__(self): return 'P ' + super().__repr__().split(' at ')[0]
+    def __str__(self): return 'str ' + super().__str__()
+    def __eq__(self, o): return isinstance(o, P) and self.x == o.x or super().__eq__(o)
+    def __hash__(self): return super().__hash__() - object.__hash__(self) + self.x
+p = P(7)
+print(repr(p), str(p), p == P(7), p != P(7), p == 7, hash(p))
+print(object.__repr__(1).startswith('<int object at 0x'), object.__eq__(p, p))
+print(object.__eq__(p, 1), object.__ne__(1, 1), object.__ne__([1], [1]))
+";
+    assert_eq!(
+        output(source).unwrap(),
+        "P <__main__.P object str P <__main__.P object True False False 7\n\
+         True True\n\
+         NotImplemented False False\n"
+    );
+}
+
+#[test]
+fn container_methods_of_list_subclasses_are_applied() {
+    let source = "\
+class L(list):
+    def __len__(self): return 42
+    def __getitem__(self, i): return ('item', super().__getitem__(i))
+    def __setitem__(self, i, v): super().__setitem__(i, -v)
+    def __delitem__(self, i): print('del', i)
+    def __contains__(self, x): return x == 'magic'
+    def __iter__(self): return iter('ab')
+l = L([1, 2, 3])
+l[0] = 5
+del l[1]
+print(len(l), l[0], 'magic' in l, 1 in l, list(l), [x for x in l], bool(L()))
+class M(list): pass
+m = M([1, 2])
+m[0] = 3
+print(m, len(m), m[0], 2 in m, list(m), m == [3, 2], hash(M) == hash(M))
+";
+    assert_eq!(
+        output(source).unwrap(),
+        "del 1\n42 ('item', -5) True False ['a', 'b'] ['a', 'b'] True\n\
+         [3, 2] 2 3 True [3, 2] True True\n"
+    );
+}
+
+#[test]
+fn item_methods_of_dict_subclasses_are_applied() {
+    let source = "\
+class K(dict):
+    def __missing__(self, key): return key * 2
+k = K(a=1)
+print(k['a'], k['ab'], k.get('ab'), 'ab' in k, k)
+class S(dict):
+    def __setitem__(self, key, value): super().__setitem__(key, value * 10)
+    def __getitem__(self, key): return ('got', super().__getitem__(key))
+    def __delitem__(self, key): print('del', key)
+s = S()
+s['a'] = 1
+del s['a']
+print(s, s['a'])
+class D(dict):
+    def __missing__(self, key):
+        self[key] = []
+        return self[key]
+d = D()
+d['x'].append(1)
+print(d, D.__getitem__(d, 'y'), d)
+";
+    assert_eq!(
+        output(source).unwrap(),
+        "1 abab None False {'a': 1}\n\
+         del a\n\
+         {'a': 10} ('got', 10)\n\
+         {'x': [1], 'y': []} [] {'x': [1], 'y': []}\n"
+    );
+    assert_eq!(
+        output("class E(dict): pass\nE()['k']\n").unwrap_err(),
+        "KeyError: 'k'"
+    );
+}
@@ -0,0 +1,52 @@
+//! Helpers shared by the integration tests. Each test crate uses some of
+//! them, so the rest would be dead code in it.
+
+#![allow(dead_code)]
+
+use std::convert::TryFrom;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustpy::Interpreter;
+
+/// The `.py` files below `directory`, leaving out build output.
+pub fn python_files(directory: &Path, files: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            if path.file_name().is_some_and(|name| name != "target") {
+                python_files(&path, files);
+            }
+        } else if path.extension().is_some_and(|extension| extension == "py") {
+            files.push(path);
+        }
+    }
+}
+
+/// What `source` prints when run in a new interpreter, or the exception it
+/// raises as `Class: message`.
+pub fn output(source: &str) -> Result<String, String> {
+    let mut python = Interpreter::new();
+    python
+        .exec("import io, sys\nsys.stdout = io.StringIO()\n")
+        .unwrap();
+    python.exec(source).map_err(|error| error.to_string())?;
+    let printed = python.eval("sys.stdout.getvalue()").unwrap();
+    Ok(String::try_from(printed).unwrap())
+}
+
+/// A new empty directory for a test to build a project in, under the
+/// system's temporary directory.
+pub fn project(name: &str) -> PathBuf {
+    let directory = std::env::temp_dir().join(format!("rustpy-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&directory);
+    fs::create_dir_all(&directory).unwrap();
+    directory
+}
+
+/// Writes `text` to `path` below `directory`, making its directories.
+pub fn write(directory: &Path, path: &str, text: &str) {
+    let path = directory.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, text).unwrap();
+}
@@ -0,0 +1,30 @@
+//! A concrete syntax tree renders back to the source it was parsed from,
+//! a byte order mark included, and keeps doing so through edits.
+
+extern crate rustpy;
+
+use rustpy::cst::Tree;
+use rustpy::tokenizer::{Span, TextEdit};
+
+#[test]
+fn a_byte_order_mark_round_trips() {
+    let source = "\u{feff}x = 1  # one\n\ny = (2,\n  3)\n";
+    let tree = Tree::parse(source).unwrap();
+    assert_eq!(tree.render(), source);
+    assert_eq!(tree.root().leading_trivia(), "\u{feff}");
+    assert_eq!(Tree::parse("\u{feff}").unwrap().render(), "\u{feff}");
+}
+
+#[test]
+fn edits_after_a_byte_order_mark_keep_it() {
+    let source = "\u{feff}x = 1\ny = 2\n";
+    for &(old, new) in &[("x", "z"), ("2", "(3, 4)"), ("1\n", "1\nw = 0\n")] {
+        let start = source.find(old).unwrap();
+        let edit = TextEdit::new(Span::new(start, start + old.len()), new);
+        let edited = edit.apply(source);
+        let mut tree = Tree::parse(source).unwrap();
+        tree.edit(&edit).unwrap();
+        assert_eq!(tree.render(), edited);
+        assert_eq!(tree, Tree::parse(&edited).unwrap());
+    }
+}
@@ -0,0 +1,39 @@
+# -*- coding: utf-8 -*-
+"""Tokens of each kind, for comparing with CPython's tokenize."""
+
+import os, sys  # comment
+
+
+class Point(object):
+    x: int = 0x_ff + 0o17 + 0b1 + 1_000 + 1.5e-3 + 2j
+
+    def __init__(self, *args, **kwargs) -> None:
+        self.values = [
+            1,  # one
+
+            2,
+        ]
+        if args and not kwargs:
+            pass
+        elif (a := 1) >= 2 or a != 3:
+            return
+        # dedented comment
+        def tabbed(self): ...
+
+
+@decorator
+async def f(x=lambda: (yield)):
+    s = r"raw\n" + b"bytes" + f"{x!r:>{10}}" + u"\N{BULLET}" + """triple
+line"""
+    total = 1 + \
+        2
+    return x[1:2, ::3] @ y ** -z // 4 << 5 >> 6 & 7 | 8 ^ ~9
+
+match command:
+    case [Point(x=0), *rest] if rest:
+        print(f"{rest=}")
+    case {"key": value, **other}:
+        pass
+
+non_ascii = "héllo" + αβ
+# no newline at the end
\ No newline at end of file
@@ -0,0 +1,72 @@
+//! Docstrings are found as `ast.get_docstring` finds them, and compiled
+//! code gives modules, classes and functions the `__doc__` CPython does,
+//! without making one of a string that optimization leaves first.
+
+extern crate rustpy;
+
+mod common;
+
+use rustpy::ast::{self, StmtKind};
+use rustpy::parser;
+
+use common::output;
+
+const SOURCE: &str = "\
+\"\"\"The module.\"\"\"
+
+def f():
+    \"\"\"First line.
+
+        Indented more.
+    Body.
+    \"\"\"
+
+class C:
+    x = 1
+
+def g():
+    f'{1}'
+";
+
+#[test]
+fn docstrings_are_found_in_the_syntax_tree() {
+    let module = parser::parse(SOURCE).unwrap();
+    assert_eq!(module.docstring(), Some("The module."));
+    assert_eq!(
+        module.body[1].docstring(),
+        Some("First line.\n\n        Indented more.\n    Body.\n    ")
+    );
+    assert_eq!(
+        ast::get_docstring(
+            match module.body[1].kind {
+                StmtKind::FunctionDef { ref body, .. } => body,
+                _ => unreachable!(),
+            },
+            true
+        ),
+        Some("First line.\n\n    Indented more.\nBody.".to_string())
+    );
+    assert_eq!(module.body[2].docstring(), None);
+    assert_eq!(module.body[3].docstring(), None);
+}
+
+#[test]
+fn compiled_code_sets_doc() {
+    let source = format!(
+        "{}print(__doc__, repr(f.__doc__.splitlines()[0]), C.__doc__, g.__doc__)\n",
+        SOURCE
+    );
+    assert_eq!(
+        output(&source).unwrap(),
+        "The module. 'First line.' None None\n"
+    );
+}
+
+#[test]
+fn a_folded_string_is_the_value_of_an_expression() {
+    let source = "\
+print(eval('f\"a\"'), eval('(f\"a\")'), eval('\"a\" + \"b\"'))
+print(eval(compile('f\"a{1}\"', 's', 'eval')))
+";
+    assert_eq!(output(source).unwrap(), "a a ab\na1\n");
+}
@@ -0,0 +1,123 @@
+//! Formatting is idempotent and keeps the meaning of the code: over the
+//! Python files in the repository, and the directory named by
+//! `RUSTPY_FORMAT_CORPUS` if it is set (such as CPython's `Lib`),
+//! formatting twice gives what formatting once does, and `format::verify`
+//! accepts it, which it does only if the result parses to the same syntax
+//! tree.
+
+extern crate rustpy;
+
+mod common;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustpy::format::{self, FormatConfig};
+use rustpy::{parser, tokenizer};
+
+use common::python_files;
+
+fn fixtures() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut files = Vec::new();
+    for directory in &["benches", "python"] {
+        python_files(&root.join(directory), &mut files);
+    }
+    if let Some(corpus) = env::var_os("RUSTPY_FORMAT_CORPUS") {
+        python_files(Path::new(&corpus), &mut files);
+    }
+    files.sort();
+    files
+}
+
+/// What is wrong with formatting `source`, if anything.
+fn check(source: &str) -> Option<String> {
+    parser::parse(tokenizer::strip_bom(source)).ok()?;
+    let once = match format::format(source) {
+        Ok(once) => once,
+        Err(err) => return Some(format!("cannot format: {}", err)),
+    };
+    match format::format(&once) {
+        Ok(ref twice) if *twice == once => {}
+        Ok(_) => return Some("a second pass changes the output".to_string()),
+        Err(err) => return Some(format!("cannot format the output: {}", err)),
+    }
+    match format::verify(source, &FormatConfig::default()) {
+        Ok(ref verified) if *verified == once => None,
+        Ok(_) => Some("verify gives other output than format".to_string()),
+        Err(err) => Some(format!("verify fails: {}", err)),
+    }
+}
+
+#[test]
+fn formatting_is_idempotent_and_keeps_the_syntax_tree() {
+    let files = fixtures();
+    assert!(!files.is_empty());
+    let mut failures = Vec::new();
+    for path in files {
+        let bytes = fs::read(&path).unwrap();
+        // Files that do not decode or parse are not formatted.
+        let source = match tokenizer::decode(&bytes) {
+            Ok(decoded) => decoded.text,
+            Err(_) => continue,
+        };
+        if let Some(problem) = check(&source) {
+            failures.push(format!("{}: {}", path.display(), problem));
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+#[test]
+fn formatting_snippets_is_idempotent() {
+    let snippets = [
+        "x=[1,2,\n3]\n",
+        "def f(a,b=1,*args,c:int=2,**kw)->None:\n  return  a\n",
+        "s = u'it''s' + R\"raw\\n\" + f'{x!r:>{w}}'\n",
+        "if x:\n\n\n\n    pass # comment\nelse :\n\tpass\n",
+        "call(argument_number_one, argument_number_two, argument_number_three, four)\n",
+        "items = {\n    'a': 1,\n    'b': 2,\n}\n",
+        "x = (  # comment\n    1 +\n    2\n)\n",
+        "\u{feff}x = 1\r\ny = 2\r\n",
+        "class A(B, metaclass=M):\n    '''Doc.'''\n    a: int = 1\n",
+        "lambda: (yield)\nassert x, 'message'\n",
+    ];
+    for snippet in &snippets {
+        assert!(
+            parser::parse(tokenizer::strip_bom(snippet)).is_ok(),
+            "{:?} does not parse",
+            snippet
+        );
+        if let Some(problem) = check(snippet) {
+            panic!("{:?}: {}", snippet, problem);
+        }
+    }
+}
+
+#[test]
+fn magic_trailing_commas_split_one_element_per_line() {
+    let cases = [
+        ("x = [1, 2,]\n", "x = [\n    1,\n    2,\n]\n"),
+        ("x = [\n    1,2,\n]\n", "x = [\n    1,\n    2,\n]\n"),
+        (
+            "f(a,\n  g(b, c,))\n",
+            "f(\n    a,\n    g(\n        b,\n        c,\n    )\n)\n",
+        ),
+        (
+            "def f(a, b,) -> (int): pass\n",
+            "def f(\n    a,\n    b,\n) -> (int): pass\n",
+        ),
+        ("t = (a,)\nx[a,]\n", "t = (a,)\nx[a,]\n"),
+        ("x[a, b,]\n", "x[\n    a,\n    b,\n]\n"),
+        (
+            "x = [\n    1, 2,  # two\n]\n",
+            "x = [\n    1, 2,  # two\n]\n",
+        ),
+        ("x = [\n    1,\n    2\n]\n", "x = [\n    1,\n    2\n]\n"),
+    ];
+    for &(source, expected) in &cases {
+        assert_eq!(format::format(source).unwrap(), expected, "{:?}", source);
+        assert!(check(source).is_none(), "{:?}", source);
+    }
+}
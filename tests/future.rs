@@ -0,0 +1,64 @@
+//! Future statements must come first in a module and name a feature the
+//! `__future__` module knows, and those that are set show in the
+//! `co_flags` of the module's code and of the code nested in it.
+
+extern crate rustpy;
+
+mod common;
+
+use common::output;
+
+#[test]
+fn future_features_are_code_flags() {
+    let source = "\
+import __future__
+c = compile('from __future__ import annotations\\nx: int\\n', 's', 'exec')
+print(hex(c.co_flags), c.co_flags & __future__.annotations.compiler_flag != 0)
+print(compile('x = 1', 's', 'exec').co_flags)
+source = '''
+from __future__ import annotations
+def f(x: undefined) -> list[int]:
+    def g(y: int): pass
+    return g
+'''
+namespace = {}
+exec(source, namespace)
+f = namespace['f']
+print(hex(f.__code__.co_flags), hex(f(0).__code__.co_flags))
+print(f.__annotations__, f(0).__annotations__)
+";
+    let expected = "\
+0x1000000 True
+0
+0x1000003 0x1000013
+{'x': 'undefined', 'return': 'list[int]'} {'y': 'int'}
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
+
+#[test]
+fn future_statements_must_come_first_and_name_a_feature() {
+    let source = r#"
+def error(source):
+    try:
+        compile(source, 's.py', 'exec')
+        print('compiled')
+    except SyntaxError as e:
+        print(e.msg, e.lineno, e.offset)
+error('x = 1\nfrom __future__ import annotations\n')
+error('import __future__\nfrom __future__ import annotations\n')
+error('"""doc"""\nfrom __future__ import annotations\nimport os\nfrom __future__ import division\n')
+error('"""doc"""\nfrom __future__ import annotations, division\n')
+error('from __future__ import spam\n')
+error('from __future__ import braces\n')
+"#;
+    let expected = "\
+from __future__ imports must occur at the beginning of the file 2 1
+from __future__ imports must occur at the beginning of the file 2 1
+from __future__ imports must occur at the beginning of the file 4 1
+compiled
+future feature spam is not defined 1 1
+not a chance 1 1
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
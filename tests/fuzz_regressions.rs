@@ -0,0 +1,82 @@
+//! Replays the inputs in `fuzz/regressions` through the bodies of the
+//! fuzz targets, so that a crash found once stays fixed without a nightly
+//! toolchain or cargo-fuzz. Each directory there is named after the target
+//! that found its inputs; every input also goes through the byte target,
+//! which takes anything.
+
+extern crate rustpy;
+
+use std::fs;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::str;
+
+use rustpy::tokenizer::{self, Tokenizer, TokenizerConfig};
+
+/// The body of `fuzz_targets/tokenize.rs`.
+fn tokenize(source: &str) {
+    if let Ok(tokens) = tokenizer::tokenize(source) {
+        let text = Tokenizer::new(source).source();
+        for token in &tokens {
+            assert_eq!(&text[token.span.start..token.span.end], token.value);
+        }
+    }
+    let config = TokenizerConfig {
+        interactive: true,
+        ..TokenizerConfig::default()
+    };
+    for _ in Tokenizer::with_config(source, config) {}
+    let _ = tokenizer::input_status(source);
+    let _ = tokenizer::shebang(source);
+}
+
+/// The body of `fuzz_targets/tokenize_bytes.rs`.
+fn tokenize_bytes(bytes: &[u8]) {
+    let _ = tokenizer::detect_encoding(bytes);
+    if let Ok(decoded) = tokenizer::decode(bytes) {
+        let _ = tokenizer::tokenize(&decoded.text);
+    }
+}
+
+/// The files below `directory`, in a stable order.
+fn inputs(directory: &Path) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(directory).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            paths.extend(inputs(&path));
+        } else {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    paths
+}
+
+#[test]
+fn regressions_do_not_panic() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("fuzz/regressions");
+    let paths = inputs(&root);
+    assert!(!paths.is_empty(), "no inputs in {}", root.display());
+    for path in paths {
+        let bytes = fs::read(&path).unwrap();
+        let target = path.strip_prefix(&root).unwrap().components().next();
+        let target = target.unwrap().as_os_str().to_str().unwrap();
+        assert!(
+            ["tokenize", "tokenize_bytes"].contains(&target),
+            "{}: no fuzz target {:?}",
+            path.display(),
+            target
+        );
+        let replayed = panic::catch_unwind(|| {
+            // libFuzzer only hands the string target valid UTF-8.
+            if target == "tokenize" {
+                if let Ok(source) = str::from_utf8(&bytes) {
+                    tokenize(source);
+                }
+            }
+            tokenize_bytes(&bytes);
+        });
+        assert!(replayed.is_ok(), "{} panicked", path.display());
+    }
+}
@@ -0,0 +1,124 @@
+//! The cycle collector frees objects that only refer to one another, and
+//! `__del__` is called once for each object freed, whether by reference
+//! counting or by the collector.
+
+extern crate rustpy;
+
+mod common;
+
+use common::output;
+
+#[test]
+fn collector_frees_cycles() {
+    let source = "\
+import gc
+class Node:
+    pass
+gc.disable()
+print(gc.isenabled())
+a = Node()
+b = Node()
+a.other = b
+b.other = a
+del a, b
+before = gc.get_stats()[2]['collections']
+print(gc.collect() >= 2, gc.collect())
+print(gc.get_stats()[2]['collections'] - before)
+gc.enable()
+print(gc.isenabled(), gc.is_tracked([]), gc.is_tracked(1))
+";
+    assert_eq!(
+        output(source).unwrap(),
+        "False\nTrue 0\n2\nTrue True False\n"
+    );
+}
+
+#[test]
+fn del_is_called_once_when_an_object_is_freed() {
+    let source = "\
+class C:
+    def __init__(self, name):
+        self.name = name
+    def __del__(self):
+        print('del', self.name)
+a = C('a')
+del a
+print('after a')
+def f():
+    b = C('b')
+f()
+print('after f')
+kept = []
+class Resurrected:
+    def __del__(self):
+        print('resurrected')
+        kept.append(self)
+r = Resurrected()
+del r
+print(len(kept))
+kept.clear()
+print('cleared')
+class Counted(list):
+    def __del__(self):
+        print('list of', len(self))
+Counted([1, 2])
+C.__del__ = lambda self: print('replaced', self.name)
+C('c')
+";
+    let expected = "\
+del a
+after a
+del b
+after f
+resurrected
+1
+cleared
+list of 2
+replaced c
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
+
+#[test]
+fn del_is_called_on_cycles_before_they_are_cleared() {
+    let source = "\
+import gc
+class Node:
+    def __del__(self):
+        print('del', self.name, self.other.name)
+a = Node()
+b = Node()
+a.name, b.name = 'a', 'b'
+a.other, b.other = b, a
+del a, b
+print(gc.collect() >= 2)
+print(gc.collect())
+";
+    let printed = output(source).unwrap();
+    let mut lines: Vec<&str> = printed.lines().collect();
+    lines[..2].sort();
+    assert_eq!(lines, ["del a b", "del b a", "True", "0"]);
+}
+
+#[test]
+fn exceptions_in_del_are_reported_and_ignored() {
+    let source = "\
+import io, sys
+sys.stderr = io.StringIO()
+class Bad:
+    def __del__(self):
+        raise ValueError('boom')
+x = Bad()
+del x
+print('after')
+report = sys.stderr.getvalue()
+print(report.startswith('Exception ignored in: <function Bad.__del__ at '))
+print(report.splitlines()[1:2], report.splitlines()[-1])
+";
+    let expected = "\
+after
+True
+['Traceback (most recent call last):'] ValueError: boom
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
@@ -0,0 +1,78 @@
+//! The inline caches of attribute and global loads give what a lookup
+//! would, whatever changes after they filled, and count their hits and
+//! misses.
+
+extern crate rustpy;
+
+mod common;
+
+use common::output;
+use rustpy::Interpreter;
+
+#[test]
+fn caches_follow_changes_to_globals_and_attributes() {
+    let source = "\
+import sys
+sys.marker = 0
+real_len = len
+def get_limit():
+    return limit
+def get_len():
+    return len
+def get_marker():
+    return sys.marker
+class Base:
+    kind = 'base'
+class Item(Base):
+    pass
+def kind(item):
+    return item.kind
+seen = []
+for step in range(4):
+    limit = step
+    if step == 2:
+        len = 'shadowed'
+        Base.kind = 'changed'
+        sys.marker = 3
+    if step == 3:
+        del len
+        Item.kind = 'item'
+    item = Item()
+    if step == 1:
+        item.kind = 'own'
+    seen.append((get_limit(), get_len() is real_len, get_marker() == 3, kind(item)))
+for entry in seen:
+    print(*entry)
+";
+    let expected = "\
+0 True False base
+1 True False own
+2 False True changed
+3 True True item
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
+
+#[test]
+fn cache_counters_count_hits_and_misses() {
+    let mut python = Interpreter::new();
+    python.exec("class P:\n    x = 1\n").unwrap();
+    let before = python.vm().inline_cache_stats();
+    python
+        .exec("def f(p):\n    total = 0\n    for i in range(100):\n        total += p.x + abs(i)\n    return total\nf(P())\n")
+        .unwrap();
+    let after = python.vm().inline_cache_stats();
+    assert!(after.attribute_hits >= before.attribute_hits + 99);
+    assert!(after.global_hits >= before.global_hits + 99);
+    assert!(after.attribute_misses > before.attribute_misses);
+    assert!(after.global_misses > before.global_misses);
+    let source = "\
+import sys
+stats = sys._inline_cache_stats()
+print(sorted(stats), all(count >= 0 for count in stats.values()))
+";
+    assert_eq!(
+        output(source).unwrap(),
+        "['attribute_hits', 'attribute_misses', 'global_hits', 'global_misses'] True\n"
+    );
+}
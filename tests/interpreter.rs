@@ -0,0 +1,113 @@
+//! The embedding interface copies values between Python and Rust, and
+//! reports what cannot be copied as an error rather than failing the host.
+//! Rust functions registered with it convert their arguments and results
+//! the same way.
+
+#[macro_use]
+extern crate rustpy;
+
+use std::cell::Cell;
+use std::convert::TryInto;
+use std::rc::Rc;
+
+use rustpy::interpreter::{Error, Result, Value};
+use rustpy::Interpreter;
+
+fn double(x: i64) -> i64 {
+    x * 2
+}
+
+fn greet(name: String) -> String {
+    format!("hello, {}", name)
+}
+
+#[test]
+fn a_container_inside_itself_is_a_conversion_error() {
+    let mut python = Interpreter::new();
+    python
+        .exec("x = []\nx.append(x)\nd = {}\nd['self'] = [d]\n")
+        .unwrap();
+    for name in &["x", "d"] {
+        match python.get(name) {
+            Err(Error::Conversion(message)) => assert!(message.contains("contains itself")),
+            other => panic!("{}: {:?}", name, other),
+        }
+    }
+}
+
+#[test]
+fn a_container_in_several_places_is_copied_to_each() {
+    let mut python = Interpreter::new();
+    python.exec("a = [1]\nx = (a, a, {'k': a})\n").unwrap();
+    let a = Value::List(vec![Value::Int(1)]);
+    assert_eq!(
+        python.get("x").unwrap(),
+        Value::Tuple(vec![
+            a.clone(),
+            a.clone(),
+            Value::Dict(vec![(Value::Str("k".to_string()), a)]),
+        ])
+    );
+}
+
+#[test]
+fn registered_functions_convert_their_arguments_and_results() {
+    let mut python = Interpreter::new();
+    python.register("clamp", |x: f64, low: f64, high: f64| x.max(low).min(high));
+    python.register("words", |text: String| -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    });
+    register_functions!(python, double, greet);
+    let calls = Rc::new(Cell::new(0));
+    let counter = calls.clone();
+    python.register("tick", move || counter.set(counter.get() + 1));
+    python
+        .exec("r = (clamp(5, 0, 2.5), words('a b'), double(4), greet('you'))\ntick()\ntick()\n")
+        .unwrap();
+    assert_eq!(
+        python.get("r").unwrap(),
+        Value::Tuple(vec![
+            Value::Float(2.5),
+            Value::List(vec![Value::Str("a".into()), Value::Str("b".into())]),
+            Value::Int(8),
+            Value::Str("hello, you".into()),
+        ])
+    );
+    assert_eq!(calls.get(), 2);
+}
+
+#[test]
+fn registered_functions_raise_python_exceptions() {
+    let mut python = Interpreter::new();
+    python.register("parse", |text: String| -> Result<i64> {
+        text.parse()
+            .map_err(|_| Error::exception("ValueError", "not a number"))
+    });
+    python.register("fail", || -> Result<()> {
+        Err(Error::exception("NoSuchError", "failed"))
+    });
+    register_functions!(python, double);
+    let source = "\
+errors = []
+for call in (lambda: parse('x'), lambda: double('x'), lambda: double(1, 2),
+             lambda: double(x=1), fail):
+    try:
+        call()
+    except Exception as error:
+        errors.append(type(error).__name__ + ': ' + str(error))
+value = parse('12')
+";
+    python.exec(source).unwrap();
+    assert_eq!(python.get("value").unwrap(), Value::Int(12));
+    let errors: Vec<String> = python.get("errors").unwrap().try_into().unwrap();
+    assert_eq!(errors[0], "ValueError: not a number");
+    assert!(errors[1].starts_with("TypeError: "), "{}", errors[1]);
+    assert_eq!(
+        errors[2..],
+        [
+            "TypeError: double() takes exactly one argument (2 given)",
+            "TypeError: double() takes no keyword arguments",
+            "RuntimeError: failed",
+        ]
+    );
+}
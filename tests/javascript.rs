@@ -0,0 +1,77 @@
+//! The JavaScript backend lowers the operators whose meaning depends on
+//! the operands' types by the types it can tell they have, and refuses
+//! them where it cannot tell.
+
+extern crate rustpy;
+
+use std::process::Command;
+
+use rustpy::backend::javascript::{transpile, TranspileError};
+use rustpy::parser::parse;
+
+fn lower(source: &str) -> Result<String, TranspileError> {
+    transpile(&parse(source).unwrap())
+}
+
+/// The last line of the program, which the helpers come before.
+fn last_line(source: &str) -> String {
+    lower(source).unwrap().lines().last().unwrap().to_string()
+}
+
+#[test]
+fn membership_follows_the_container_type() {
+    let source =
+        "d = {'a': 1}\ns = {1, 2}\nwords = ['x']\ndef f(text: str, k):\n    return k in text\n";
+    let lowered = lower(source).unwrap();
+    assert!(lowered.contains("return text.includes(k);"), "{}", lowered);
+    let cases = [
+        ("'a' in d", "print(Object.hasOwn(d, \"a\"));"),
+        ("'b' not in d", "print(!Object.hasOwn(d, \"b\"));"),
+        ("2 in s", "print(s.has(2));"),
+        ("'x' in words", "print(words.includes(\"x\"));"),
+        ("i in range(3)", "print(range(3).includes(i));"),
+    ];
+    for &(test, expected) in &cases {
+        assert_eq!(last_line(&format!("{}print({})\n", source, test)), expected);
+    }
+
+    let err = lower("def f(c, k):\n    return k in c\n").unwrap_err();
+    assert_eq!((err.location.line, err.location.column), (2, 16));
+}
+
+#[test]
+fn strs_repeat_and_lists_concatenate() {
+    assert_eq!(last_line("x = 'ab' * 2\n"), "x = \"ab\".repeat(2);");
+    assert_eq!(
+        last_line("n = 3\nx = n * '-'\n"),
+        "x = \"-\".repeat(Math.max(n, 0));"
+    );
+    assert_eq!(last_line("s = 'a'\ns *= 2\n"), "s = s.repeat(2);");
+    assert_eq!(last_line("x = [1] + [2]\n"), "x = [...[1], ...[2]];");
+    let typed = lower("def f(w: float, h: int):\n    return w * h\n").unwrap();
+    assert!(typed.contains("return w * h;"), "{}", typed);
+    assert!(lower("def f(w, h):\n    return w * h\n").is_err());
+    assert!(lower("x = [0] * 3\n").is_err());
+}
+
+#[test]
+fn floats_are_shown_as_python_shows_them() {
+    let source = "x = 3.0\ndef half(n: int) -> float:\n    return n / 2\nprint(x, 1e20, 0.1, 1e-05, 1e16, -0.0, half(4), 7 // 2)\nprint(f'{x} {x!r} {x:.2f}', str(2.5e-7))\n";
+    let lowered = lower(source).unwrap();
+    assert!(
+        lowered.contains("print(reprFloat(x), reprFloat(1e+20), reprFloat(0.1),"),
+        "{}",
+        lowered
+    );
+    assert!(lowered.contains("Math.floor(7 / 2));"), "{}", lowered);
+
+    // Run the program where Node.js is installed.
+    let path = std::env::temp_dir().join(format!("rustpy-floats-{}.js", std::process::id()));
+    std::fs::write(&path, &lowered).unwrap();
+    if let Ok(output) = Command::new("node").arg(&path).output() {
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            "3.0 1e+20 0.1 1e-05 1e+16 -0.0 2.0 3\n3.0 3.0 3.00 2.5e-07\n"
+        );
+    }
+}
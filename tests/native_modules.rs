@@ -0,0 +1,128 @@
+//! Modules written in Rust are registered with the interpreter and imported
+//! from Python code as the modules of the standard library are.
+
+extern crate rustpy;
+
+use std::cell::Cell;
+use std::convert::TryInto;
+use std::rc::Rc;
+
+use rustpy::ast::Operator;
+use rustpy::interpreter::{new_function, Error, Value};
+use rustpy::vm::{Args, ModuleBuilder, PyResult, Vm};
+use rustpy::Interpreter;
+
+fn hello(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "hello", 0, 0)?;
+    Ok(vm.new_str("hello"))
+}
+
+fn area(vm: &mut Vm, args: Args) -> PyResult {
+    args.check(vm, "area", 1, 1)?;
+    let this = &args.positional[0];
+    let width = vm.getattr(this, "width")?;
+    let height = vm.getattr(this, "height")?;
+    vm.binary_op(&width, Operator::Mult, &height, false)
+}
+
+#[test]
+fn native_modules_are_imported_with_their_contents() {
+    let mut python = Interpreter::new();
+    let inits = Rc::new(Cell::new(0));
+    let counted = inits.clone();
+    python.add_module("geometry", move |module: &mut ModuleBuilder| {
+        counted.set(counted.get() + 1);
+        module.function("hello", hello);
+        let clamp = new_function(module.vm(), "clamp", |x: f64, low: f64, high: f64| {
+            x.max(low).min(high)
+        });
+        module.attribute("clamp", clamp);
+        let calls = Cell::new(0);
+        module.closure("count", move |vm, _| {
+            calls.set(calls.get() + 1);
+            Ok(vm.new_int(calls.get()))
+        });
+        let origin = module.vm().new_int(0);
+        module.attribute("ORIGIN", origin);
+        let object = module.vm().types.object.clone();
+        module.class("Shape", &object, &[("area", area)])?;
+        Ok(())
+    });
+    let source = "\
+import geometry
+from geometry import clamp, count
+class Rectangle(geometry.Shape):
+    def __init__(self, width, height):
+        self.width, self.height = width, height
+import geometry as again
+r = (geometry.hello(), clamp(9, 0, 4), count(), count(), geometry.ORIGIN,
+     Rectangle(2, 3).area(), geometry.Shape.__module__, again is geometry)
+";
+    python.exec(source).unwrap();
+    assert_eq!(
+        python.get("r").unwrap(),
+        Value::Tuple(vec![
+            Value::Str("hello".into()),
+            Value::Float(4.0),
+            Value::Int(1),
+            Value::Int(2),
+            Value::Int(0),
+            Value::Int(6),
+            Value::Str("geometry".into()),
+            Value::Bool(true),
+        ])
+    );
+    assert_eq!(inits.get(), 1);
+}
+
+#[test]
+fn native_packages_have_submodules() {
+    let mut python = Interpreter::new();
+    python.add_module("shapes", |module: &mut ModuleBuilder| {
+        module.package();
+        Ok(())
+    });
+    python.add_module("shapes.circle", |module: &mut ModuleBuilder| {
+        let name = module.name().to_string();
+        let name = module.vm().new_str(&name);
+        module.attribute("NAME", name);
+        Ok(())
+    });
+    python
+        .exec("import shapes.circle\nfrom shapes import circle\nr = (shapes.circle.NAME, circle.NAME, shapes.__path__)\n")
+        .unwrap();
+    assert_eq!(
+        python.get("r").unwrap(),
+        Value::Tuple(vec![
+            Value::Str("shapes.circle".into()),
+            Value::Str("shapes.circle".into()),
+            Value::List(Vec::new()),
+        ])
+    );
+}
+
+#[test]
+fn an_error_in_init_is_raised_by_import() {
+    let mut python = Interpreter::new();
+    python.add_module("broken", |module: &mut ModuleBuilder| {
+        Err(module.vm().new_value_error("cannot load".to_string()))
+    });
+    let source = "\
+import sys
+errors = []
+for attempt in range(2):
+    try:
+        import broken
+    except ValueError as error:
+        errors.append(str(error))
+loaded = 'broken' in sys.modules
+";
+    python.exec(source).unwrap();
+    let errors: Vec<String> = python.get("errors").unwrap().try_into().unwrap();
+    assert_eq!(errors, ["cannot load", "cannot load"]);
+    assert_eq!(python.get("loaded").unwrap(), Value::Bool(false));
+    match python.exec("import unregistered") {
+        Err(Error::Exception { class, .. }) => assert_eq!(class, "ModuleNotFoundError"),
+        other => panic!("{:?}", other),
+    }
+}
@@ -0,0 +1,158 @@
+//! Code nested ten thousand deep fails with an error at the stage that
+//! limits it, never by overflowing the stack: brackets in the tokenizer,
+//! other nesting in the parser, trees deeper than the parser's default
+//! limit in the compiler, and recursion in the VM. Each test runs on a
+//! thread with `rustpy::STACK_SIZE` bytes of stack, as `rustpy` does its
+//! work.
+
+extern crate rustpy;
+
+use std::thread;
+
+use rustpy::parser::{self, ParserConfig};
+use rustpy::{compiler, tokenizer, Interpreter, STACK_SIZE};
+
+const DEPTH: usize = 10_000;
+
+fn on_large_stack<F: FnOnce() + Send + 'static>(test: F) {
+    thread::Builder::new()
+        .stack_size(STACK_SIZE)
+        .spawn(test)
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+/// Tokenizes, parses, compiles and runs `source`, and gives the error of
+/// the first stage that fails, with the name of its exception.
+fn run(source: &str) -> Result<(), String> {
+    tokenizer::tokenize(source)
+        .map_err(|err| format!("{}: {}", err.kind.exception_name(), err.message))?;
+    let module = parser::parse(source)
+        .map_err(|err| format!("{}: {}", err.kind.exception_name(), err.message))?;
+    compiler::compile(&module, "<test>").map_err(|err| format!("SyntaxError: {}", err.message))?;
+    Interpreter::new()
+        .exec(source)
+        .map_err(|err| err.to_string())
+}
+
+fn assert_fails(source: &str, expected: &str) {
+    match run(source) {
+        Err(ref message) if message.starts_with(expected) => {}
+        outcome => {
+            let start: String = source.chars().take(40).collect();
+            panic!("{:?}...: expected {}, got {:?}", start, expected, outcome);
+        }
+    }
+}
+
+#[test]
+fn deep_brackets_are_too_many_nested_parentheses() {
+    on_large_stack(|| {
+        for &(open, close) in &[("(", ")"), ("[", "]"), ("{", "}"), ("f(", ")")] {
+            let source = format!("{}{}", open.repeat(DEPTH), close.repeat(DEPTH));
+            assert_fails(&source, "SyntaxError: too many nested parentheses");
+        }
+        let source = format!("{}1{}", "(".repeat(DEPTH), ")".repeat(DEPTH));
+        assert_fails(&source, "SyntaxError: too many nested parentheses");
+    });
+}
+
+#[test]
+fn deep_nesting_without_brackets_is_a_recursion_error() {
+    on_large_stack(|| {
+        let ones = vec!["1"; DEPTH];
+        let sources = [
+            format!("{}1", "-".repeat(DEPTH)),
+            format!("{}x", "not ".repeat(DEPTH)),
+            format!("x = {}", ones.join(" + ")),
+            format!("x = {}", ones.join(" ** ")),
+            format!("x = {}", ones.join(" if x else ")),
+            format!("x = {}1", "lambda: ".repeat(DEPTH)),
+            format!("f{}", "()".repeat(DEPTH)),
+            format!("x{}", ".a".repeat(DEPTH)),
+            format!("x{}", "[0]".repeat(DEPTH)),
+            format!("if x:\n    pass\n{}", "elif x:\n    pass\n".repeat(DEPTH)),
+        ];
+        for source in &sources {
+            assert_fails(source, "RecursionError");
+        }
+    });
+}
+
+#[test]
+fn long_flat_chains_parse_and_run() {
+    on_large_stack(|| {
+        let terms = vec!["x"; 1500];
+        let branches: String = (0..1500)
+            .map(|branch| format!("elif x == {}:\n    y = {}\n", branch, branch))
+            .collect();
+        let sources = [
+            format!("x = 1\ny = {}\nassert y == 1500\n", terms.join(" + ")),
+            format!("x = 1\ny = {}\nassert y == 1\n", terms.join(" * ")),
+            format!(
+                "x = 1499\nif x < 0:\n    pass\n{}assert y == 1499\n",
+                branches
+            ),
+            format!("def f():\n    return f\nf{}\n", "()".repeat(1500)),
+            format!(
+                "class A:\n    pass\nx = A()\nx.a = x\nx{}\n",
+                ".a".repeat(1500)
+            ),
+            format!("x = [[0]]\nx[0] = x\nx{}\n", "[0]".repeat(1500)),
+        ];
+        for source in &sources {
+            if let Err(message) = run(source) {
+                let start: String = source.chars().take(40).collect();
+                panic!("{:?}...: {}", start, message);
+            }
+        }
+    });
+}
+
+#[test]
+fn deep_indentation_is_an_indentation_error() {
+    on_large_stack(|| {
+        let source: String = (0..DEPTH)
+            .map(|level| format!("{}if x:\n", " ".repeat(level)))
+            .collect();
+        let source = source + &" ".repeat(DEPTH) + "pass\n";
+        assert_fails(&source, "IndentationError: too many levels of indentation");
+    });
+}
+
+#[test]
+fn trees_deeper_than_the_parser_allows_fail_to_compile() {
+    on_large_stack(|| {
+        let config = ParserConfig {
+            max_depth: Some(2 * DEPTH),
+            ..ParserConfig::default()
+        };
+        let source = format!("{}1", "-".repeat(DEPTH));
+        let module = parser::parse_with_config(&source, &config).unwrap();
+        let err = compiler::compile(&module, "<test>").unwrap_err();
+        assert_eq!(
+            err.message,
+            "maximum recursion depth exceeded during compilation"
+        );
+    });
+}
+
+#[test]
+fn deep_recursion_at_run_time_is_a_recursion_error() {
+    on_large_stack(|| {
+        let sources = [
+            "def f(n):\n    return f(n + 1)\nf(0)\n",
+            "x = []\nfor _ in range(10000):\n    x = [x]\nrepr(x)\n",
+            "x = {}\nfor _ in range(10000):\n    x = {'a': x}\nstr(x)\n",
+            "class A:\n    def __getattr__(self, name):\n        return self.x\nA().y\n",
+            "eval('-' * 10000 + '1')\n",
+            "compile('x' + '.a' * 10000, '<string>', 'eval')\n",
+        ];
+        for source in &sources {
+            assert_fails(source, "RecursionError");
+        }
+        let source = "eval('(' * 10000 + ')' * 10000)\n";
+        assert_fails(source, "SyntaxError: too many nested parentheses");
+    });
+}
@@ -0,0 +1,42 @@
+//! Refactorings return edits that apply cleanly to the source they were
+//! computed from.
+
+extern crate rustpy;
+
+use rustpy::refactor::{apply_edits, rename, RefactorError, TextEdit};
+use rustpy::tokenizer::Span;
+
+#[test]
+fn an_augmented_assignment_is_renamed_once() {
+    let source = "x = 0\nx += 1\nprint(x)\n";
+    let edits = rename(source, 0, "y").unwrap();
+    assert_eq!(edits.len(), 3);
+    assert_eq!(
+        apply_edits(source, &edits).unwrap(),
+        "y = 0\ny += 1\nprint(y)\n"
+    );
+}
+
+#[test]
+fn overlapping_edits_are_an_error() {
+    let source = "abcdef";
+    let same = [
+        TextEdit::new(Span::new(1, 3), "x"),
+        TextEdit::new(Span::new(1, 3), "x"),
+    ];
+    assert_eq!(
+        apply_edits(source, &same),
+        Err(RefactorError::Overlapping(Span::new(1, 3), Span::new(1, 3)))
+    );
+    let inside = [
+        TextEdit::new(Span::new(1, 4), "x"),
+        TextEdit::new(Span::new(2, 5), "y"),
+    ];
+    assert!(apply_edits(source, &inside).is_err());
+    let apart = [
+        TextEdit::insert(1, "<"),
+        TextEdit::new(Span::new(1, 3), "x"),
+        TextEdit::insert(3, ">"),
+    ];
+    assert_eq!(apply_edits(source, &apart).unwrap(), "a<x>def");
+}
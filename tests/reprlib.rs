@@ -0,0 +1,81 @@
+//! `reprlib` cuts reprs down as CPython's does, to limits kept in the
+//! attributes of a `Repr`, and `sys.displayhook` decides how the values of
+//! expression statements at the interactive prompt are shown, so a hook
+//! can show them cut down.
+
+extern crate rustpy;
+
+mod common;
+
+use common::output;
+
+#[test]
+fn reprs_are_cut_down_to_the_default_limits() {
+    let source = "\
+import reprlib
+print(reprlib.repr(list(range(100))))
+print(reprlib.repr('x' * 100))
+print(reprlib.repr({i: [i] * 10 for i in range(10)}))
+a = [1]
+a.append(a)
+print(reprlib.repr(a))
+";
+    let expected = "\
+[0, 1, 2, 3, 4, 5, ...]
+'xxxxxxxxxxxx...xxxxxxxxxxxxx'
+{0: [0, 0, 0, 0, 0, 0, ...], 1: [1, 1, 1, 1, 1, 1, ...], 2: [2, 2, 2, 2, 2, 2, ...], 3: [3, 3, 3, 3, 3, 3, ...], ...}
+[1, [1, [1, [1, [1, [1, [...]]]]]]]
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
+
+#[test]
+fn the_limits_are_attributes_of_a_repr() {
+    let source = "\
+import reprlib
+r = reprlib.Repr()
+print(r.maxlevel, r.maxlist, r.maxstring, reprlib.aRepr.maxdict)
+r.maxlist = 2
+r.maxstring = 10
+r.maxlevel = 1
+r.maxlong = 8
+print(r.repr([1, 2, 3]), r.repr('abcdefghijklmnop'), r.repr([[1], 2]), r.repr(1234567890123))
+print(reprlib.repr([1, 2, 3]))
+";
+    let expected = "\
+6 6 30 4
+[1, 2, ...] 'ab...nop' [[...], 2] 12...123
+[1, 2, 3]
+";
+    assert_eq!(output(source).unwrap(), expected);
+}
+
+#[test]
+fn the_interactive_prompt_shows_values_through_sys_displayhook() {
+    let source = "\
+import builtins, reprlib, sys
+print(sys.displayhook is sys.__displayhook__)
+exec(compile('[1] * 10', '<stdin>', 'single'))
+exec(compile('None', '<stdin>', 'single'))
+print(builtins._)
+short = reprlib.Repr()
+short.maxlist = 3
+sys.displayhook = lambda value: print(short.repr(value))
+exec(compile('[1] * 10', '<stdin>', 'single'))
+del sys.displayhook
+exec(compile('1', '<stdin>', 'single'))
+";
+    assert_eq!(
+        output(source).unwrap_err(),
+        "RuntimeError: lost sys.displayhook"
+    );
+    let printed = output(&source.replace("del sys.displayhook\n", "")).unwrap();
+    let expected = "\
+True
+[1, 1, 1, 1, 1, 1, 1, 1, 1, 1]
+[1, 1, 1, 1, 1, 1, 1, 1, 1, 1]
+[1, 1, 1, ...]
+1
+";
+    assert_eq!(printed, expected);
+}
@@ -0,0 +1,378 @@
+//! Sources that do not compile raise `SyntaxError` and its subclasses with
+//! CPython's message and location, and `rustpy tokenize` keeps reporting
+//! the tokenizer's own error as `python -m tokenize` does.
+
+extern crate rustpy;
+
+use std::convert::TryFrom;
+
+use rustpy::Interpreter;
+use rustpy::{parser, tokenizer};
+
+/// The class, message, line and offset of the error `compile` raises for
+/// `source`.
+fn compile_error(source: &str) -> String {
+    let mut python = Interpreter::new();
+    python.set("source", source).unwrap();
+    python
+        .exec(
+            "try:\n    compile(source, 's.py', 'exec')\n    result = 'compiled'\n\
+             except SyntaxError as e:\n    \
+             result = '%s: %s (%s, %s)' % (type(e).__name__, e.msg, e.lineno, e.offset)\n",
+        )
+        .unwrap();
+    String::try_from(python.get("result").unwrap()).unwrap()
+}
+
+#[test]
+fn an_unclosed_bracket_is_reported_where_it_opened() {
+    assert_eq!(
+        compile_error("f(a,\n"),
+        "SyntaxError: '(' was never closed (1, 2)"
+    );
+    assert_eq!(
+        compile_error("x = [1,\n  {2: (3\n"),
+        "SyntaxError: '(' was never closed (2, 7)"
+    );
+}
+
+#[test]
+fn an_error_before_the_end_is_reported_over_an_unclosed_bracket() {
+    assert_eq!(
+        compile_error("def f(:\n"),
+        "SyntaxError: invalid syntax (1, 7)"
+    );
+}
+
+#[test]
+fn the_tokenizer_reports_the_end_of_the_input() {
+    let err = tokenizer::tokenize("f(a,\n").unwrap_err();
+    assert_eq!(err.message, "EOF in multi-line statement");
+    assert_eq!(
+        (err.location.line, err.location.column),
+        (2, 0),
+        "python -m tokenize gives 2,0"
+    );
+    assert_eq!(err.unclosed.map(|(bracket, _)| bracket), Some('('));
+}
+
+#[test]
+fn subclasses_are_raised_for_indentation_and_tabs() {
+    assert_eq!(
+        compile_error("if x\n    pass\n"),
+        "SyntaxError: expected ':' (1, 5)"
+    );
+    assert!(compile_error("  x = 1\n").starts_with("IndentationError: unexpected indent"));
+    assert_eq!(
+        compile_error("if x:\n\tif y:\n        pass\n\tpass\n"),
+        "TabError: inconsistent use of tabs and spaces in indentation (3, 1)"
+    );
+}
+
+#[test]
+fn only_the_byte_order_mark_a_file_starts_with_is_removed() {
+    let decoded = tokenizer::decode(b"\xef\xbb\xbfx = 1\n").unwrap();
+    assert!(tokenizer::tokenize(&decoded.text).is_ok());
+    let decoded = tokenizer::decode(b"\xef\xbb\xbf\xef\xbb\xbfx = 1\n").unwrap();
+    let err = tokenizer::tokenize(&decoded.text).unwrap_err();
+    assert_eq!(err.message, "invalid non-printable character U+FEFF");
+    assert_eq!((err.location.line, err.location.column), (1, 0));
+    assert_eq!(
+        compile_error("\u{feff}x = 1\n"),
+        "SyntaxError: invalid non-printable character U+FEFF (1, 1)"
+    );
+}
+
+#[test]
+fn errors_after_byte_order_marks_are_recovered_from() {
+    let (module, errors) = parser::parse_recovering("\u{feff}\u{feff}x\ny = 1\n");
+    assert_eq!(errors[0].message, "invalid non-printable character U+FEFF");
+    // The line in error, then `y = 1`.
+    assert_eq!(module.body.len(), 2);
+    assert_eq!(module.body[1].start.line, 2);
+    let (tokens, skipped) =
+        tokenizer::tokenize_recovering("x = 1\n\u{feff}y\nz\n", Default::default());
+    assert_eq!(skipped.len(), 1);
+    let names: Vec<&str> = tokens
+        .iter()
+        .filter(|token| token.kind == tokenizer::TokenType::Name)
+        .map(|token| token.value.as_str())
+        .collect();
+    assert_eq!(names, ["x", "z"]);
+}
+
+#[test]
+fn each_bracket_left_open_is_reported_once() {
+    let (_, errors) = parser::parse_recovering("x = (1,\ny = [2,\n");
+    let reported: Vec<(String, usize)> = errors
+        .iter()
+        .map(|error| (error.message.clone(), error.location.line))
+        .collect();
+    assert_eq!(
+        reported,
+        [
+            ("'(' was never closed".to_string(), 1),
+            ("'[' was never closed".to_string(), 2),
+        ]
+    );
+}
+
+#[test]
+fn literals_cannot_be_assigned_to() {
+    for &(source, expected) in &[
+        ("1 = x", "SyntaxError: cannot assign to literal here. Maybe you meant '==' instead of '='? (1, 1)"),
+        ("'a' = x", "SyntaxError: cannot assign to literal here. Maybe you meant '==' instead of '='? (1, 1)"),
+        ("f'{x}' = 1", "SyntaxError: cannot assign to f-string expression here. Maybe you meant '==' instead of '='? (1, 1)"),
+        ("x, 2 = y", "SyntaxError: cannot assign to literal here. Maybe you meant '==' instead of '='? (1, 4)"),
+        ("for 1 in x: pass", "SyntaxError: cannot assign to literal (1, 5)"),
+        ("with a as 1: pass", "SyntaxError: cannot assign to literal (1, 11)"),
+        ("(1 := 2)", "SyntaxError: cannot use assignment expressions with literal (1, 2)"),
+        ("del 1", "SyntaxError: cannot delete literal (1, 5)"),
+        ("1 += 1", "SyntaxError: 'literal' is an illegal expression for augmented assignment (1, 1)"),
+        ("1: int", "SyntaxError: illegal target for annotation (1, 1)"),
+        ("[x for 1 in y]", "SyntaxError: cannot assign to literal (1, 8)"),
+        ("...= 1", "SyntaxError: cannot assign to ellipsis here. Maybe you meant '==' instead of '='? (1, 1)"),
+    ] {
+        assert_eq!(compile_error(source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn keywords_and_debug_cannot_be_assigned_to() {
+    for &(source, expected) in &[
+        ("True = 1", "SyntaxError: cannot assign to True (1, 1)"),
+        ("None = 1", "SyntaxError: cannot assign to None (1, 1)"),
+        ("x, False = y", "SyntaxError: cannot assign to False (1, 4)"),
+        (
+            "(True := 1)",
+            "SyntaxError: cannot use assignment expressions with True (1, 2)",
+        ),
+        (
+            "[None := 1]",
+            "SyntaxError: cannot use assignment expressions with None (1, 2)",
+        ),
+        ("f(True := 1)", "SyntaxError: invalid syntax (1, 8)"),
+        ("del None", "SyntaxError: cannot delete None (1, 5)"),
+        (
+            "True += 1",
+            "SyntaxError: 'True' is an illegal expression for augmented assignment (1, 1)",
+        ),
+        ("f(True=1)", "SyntaxError: cannot assign to True (1, 3)"),
+        (
+            "__debug__ = 1",
+            "SyntaxError: cannot assign to __debug__ (1, 1)",
+        ),
+        (
+            "def f(__debug__): pass",
+            "SyntaxError: cannot assign to __debug__ (1, 1)",
+        ),
+        (
+            "lambda __debug__: 0",
+            "SyntaxError: cannot assign to __debug__ (1, 1)",
+        ),
+        (
+            "def f(*, __debug__): pass",
+            "SyntaxError: cannot assign to __debug__ (1, 1)",
+        ),
+        (
+            "class __debug__: pass",
+            "SyntaxError: cannot assign to __debug__ (1, 1)",
+        ),
+        (
+            "import x as __debug__",
+            "SyntaxError: cannot assign to __debug__ (1, 1)",
+        ),
+        (
+            "from x import __debug__",
+            "SyntaxError: cannot assign to __debug__ (1, 1)",
+        ),
+        (
+            "del __debug__",
+            "SyntaxError: cannot delete __debug__ (1, 5)",
+        ),
+        (
+            "f(__debug__=1)",
+            "SyntaxError: cannot assign to __debug__ (1, 1)",
+        ),
+        (
+            "x.__debug__ = 1",
+            "SyntaxError: cannot assign to __debug__ (1, 1)",
+        ),
+    ] {
+        assert_eq!(compile_error(source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn comparisons_cannot_be_assigned_to() {
+    for &(source, expected) in &[
+        (
+            "a < b = 1",
+            "SyntaxError: cannot assign to comparison (1, 1)",
+        ),
+        (
+            "x, a == b = 1",
+            "SyntaxError: cannot assign to comparison (1, 4)",
+        ),
+        ("for a in b in c: pass", "compiled"),
+        (
+            "a < b += 1",
+            "SyntaxError: 'comparison' is an illegal expression for augmented assignment (1, 1)",
+        ),
+        ("del a < b", "SyntaxError: cannot delete comparison (1, 5)"),
+        (
+            "(a < b := 1)",
+            "SyntaxError: cannot use assignment expressions with comparison (1, 2)",
+        ),
+        (
+            "a = b = c = 1 == 2 = x",
+            "SyntaxError: cannot assign to comparison (1, 13)",
+        ),
+        ("x = 1 = 2", "SyntaxError: cannot assign to literal (1, 5)"),
+        (
+            "a.b = 1 = 2",
+            "SyntaxError: cannot assign to literal (1, 7)",
+        ),
+    ] {
+        assert_eq!(compile_error(source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn function_calls_cannot_be_deleted() {
+    for &(source, expected) in &[
+        ("del f()", "SyntaxError: cannot delete function call (1, 5)"),
+        ("del x, f()", "SyntaxError: cannot delete function call (1, 8)"),
+        ("del (a, [f()])", "SyntaxError: cannot delete function call (1, 10)"),
+        ("del x.y()", "SyntaxError: cannot delete function call (1, 5)"),
+        ("f() = 1", "SyntaxError: cannot assign to function call here. Maybe you meant '==' instead of '='? (1, 1)"),
+        ("f() += 1", "SyntaxError: 'function call' is an illegal expression for augmented assignment (1, 1)"),
+        ("(f() := 1)", "SyntaxError: cannot use assignment expressions with function call (1, 2)"),
+        ("for f() in x: pass", "SyntaxError: cannot assign to function call (1, 5)"),
+        ("del f(), g", "SyntaxError: cannot delete function call (1, 5)"),
+    ] {
+        assert_eq!(compile_error(source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn starred_targets_are_limited() {
+    for &(source, expected) in &[
+        (
+            "*a = 1",
+            "SyntaxError: starred assignment target must be in a list or tuple (1, 1)",
+        ),
+        (
+            "*a, *b = c",
+            "SyntaxError: multiple starred expressions in assignment (1, 1)",
+        ),
+        (
+            "a, *b, *c = d",
+            "SyntaxError: multiple starred expressions in assignment (1, 1)",
+        ),
+        (
+            "[*a, *b] = c",
+            "SyntaxError: multiple starred expressions in assignment (1, 1)",
+        ),
+        (
+            "(*a) = 1",
+            "SyntaxError: cannot use starred expression here (1, 2)",
+        ),
+        ("del *a", "SyntaxError: cannot delete starred (1, 5)"),
+        ("del (*a, b)", "SyntaxError: cannot delete starred (1, 6)"),
+        ("*a, = 1", "compiled"),
+        (
+            "for *a, *b in c: pass",
+            "SyntaxError: multiple starred expressions in assignment (1, 5)",
+        ),
+        (
+            "*a += 1",
+            "SyntaxError: 'starred' is an illegal expression for augmented assignment (1, 1)",
+        ),
+        ("*a: int", "SyntaxError: invalid syntax (1, 3)"),
+    ] {
+        assert_eq!(compile_error(source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn at_most_255_targets_come_before_a_starred_one() {
+    let names = |count: usize| (0..count).map(|i| format!("a{}, ", i)).collect::<String>();
+    assert_eq!(compile_error(&format!("{}*z = y", names(255))), "compiled");
+    assert_eq!(
+        compile_error(&format!("{}*z = y", names(256))),
+        "SyntaxError: too many expressions in star-unpacking assignment (1, 1)"
+    );
+    assert_eq!(
+        compile_error(&format!("x = {}*z = y", names(256))),
+        "SyntaxError: too many expressions in star-unpacking assignment (1, 5)"
+    );
+}
+
+#[test]
+fn call_arguments_come_in_order() {
+    for &(source, expected) in &[
+        (
+            "f(a=1, b)",
+            "SyntaxError: positional argument follows keyword argument (1, 9)",
+        ),
+        (
+            "f(**k, b)",
+            "SyntaxError: positional argument follows keyword argument unpacking (1, 9)",
+        ),
+        (
+            "f(a=1, *a, b)",
+            "SyntaxError: positional argument follows keyword argument (1, 13)",
+        ),
+        (
+            "f(**k, *a)",
+            "SyntaxError: iterable argument unpacking follows keyword argument unpacking (1, 8)",
+        ),
+        (
+            "f(a, a=1, **k, *b)",
+            "SyntaxError: iterable argument unpacking follows keyword argument unpacking (1, 16)",
+        ),
+        ("f(a=1, *a)", "compiled"),
+        (
+            "f(x=1, x=2)",
+            "SyntaxError: keyword argument repeated: x (1, 8)",
+        ),
+        ("f(True=1)", "SyntaxError: cannot assign to True (1, 3)"),
+        (
+            "f(a.b=1)",
+            "SyntaxError: expression cannot contain assignment, perhaps you meant \"==\"? (1, 3)",
+        ),
+    ] {
+        assert_eq!(compile_error(source), expected, "{}", source);
+    }
+}
+
+#[test]
+fn only_a_sole_call_argument_can_be_an_unparenthesized_generator() {
+    for &(source, expected) in &[
+        ("f(x for x in y)", "compiled"),
+        ("f((x for x in y), 1)", "compiled"),
+        (
+            "f(x for x in y, 1)",
+            "SyntaxError: Generator expression must be parenthesized (1, 3)",
+        ),
+        (
+            "f(1, x for x in y)",
+            "SyntaxError: Generator expression must be parenthesized (1, 6)",
+        ),
+        (
+            "class C(x for x in y): pass",
+            "SyntaxError: invalid syntax (1, 11)",
+        ),
+        (
+            "f(a=1 for a in b)",
+            "SyntaxError: invalid syntax. Maybe you meant '==' or ':=' instead of '='? (1, 3)",
+        ),
+        (
+            "f(*a for a in b)",
+            "SyntaxError: iterable unpacking cannot be used in comprehension (1, 3)",
+        ),
+    ] {
+        assert_eq!(compile_error(source), expected, "{}", source);
+    }
+}
@@ -0,0 +1,47 @@
+//! The types a multi-threaded analysis server would move or share between
+//! threads are `Send` and `Sync`: the tokenizer, tokens, syntax trees,
+//! diagnostics and their configurations hold no `Rc` or `Cell`. The `Vm`
+//! and `Interpreter` do, and stay on their thread. This file fails to
+//! compile if one of them stops being thread-safe.
+
+extern crate rustpy;
+
+use rustpy::{
+    ast, compiler, config, cst, diagnostics, format, interpreter, parser, refactor, tokenizer, walk,
+};
+
+fn send_sync<T: Send + Sync>() {}
+
+#[test]
+fn analysis_types_are_send_and_sync() {
+    send_sync::<tokenizer::Tokenizer>();
+    send_sync::<tokenizer::TokenizerConfig>();
+    send_sync::<tokenizer::Token>();
+    send_sync::<tokenizer::TokenError>();
+    send_sync::<tokenizer::CancellationToken>();
+    send_sync::<tokenizer::LineIndex>();
+    send_sync::<tokenizer::DecodedSource>();
+    send_sync::<tokenizer::SourceError>();
+    send_sync::<tokenizer::SkippedLines>();
+    #[cfg(feature = "serde")]
+    send_sync::<tokenizer::TokenCache>();
+    send_sync::<parser::ParserConfig>();
+    send_sync::<parser::ParseError>();
+    send_sync::<parser::TokenStream>();
+    send_sync::<parser::CompileConfig>();
+    send_sync::<parser::CompileError>();
+    send_sync::<ast::Module>();
+    send_sync::<ast::Arena>();
+    send_sync::<cst::Tree>();
+    send_sync::<diagnostics::Diagnostic>();
+    send_sync::<diagnostics::Baseline>();
+    send_sync::<format::FormatConfig>();
+    send_sync::<refactor::TextEdit>();
+    send_sync::<refactor::RefactorError>();
+    send_sync::<compiler::CodeObject>();
+    send_sync::<compiler::CompilerConfig>();
+    send_sync::<compiler::CompilerError>();
+    send_sync::<walk::Walker>();
+    send_sync::<config::Config>();
+    send_sync::<interpreter::Value>();
+}
@@ -0,0 +1,84 @@
+//! `TokenStream` looks any distance ahead, consumes tokens only when asked,
+//! and goes back to a checkpoint, for parsers outside the crate as for its
+//! own.
+
+extern crate rustpy;
+
+use rustpy::parser::{ParseErrorKind, TokenStream};
+use rustpy::tokenizer::{Location, TokenType};
+
+#[test]
+fn peek_looks_ahead_without_consuming() {
+    let mut tokens = TokenStream::tokenize("# a directive\nname: str = 'x'  # note\n").unwrap();
+    let kinds: Vec<TokenType> = (0..7).map(|n| tokens.peek(n).kind).collect();
+    assert_eq!(
+        kinds,
+        [
+            TokenType::Name,
+            TokenType::Operator,
+            TokenType::Name,
+            TokenType::Operator,
+            TokenType::String,
+            TokenType::NewlineLogical,
+            TokenType::EndMarker,
+        ]
+    );
+    assert_eq!(tokens.peek(100).kind, TokenType::EndMarker);
+    assert_eq!(tokens.position(), 0);
+    assert!(tokens.previous().is_none());
+
+    assert_eq!(tokens.expect(TokenType::Name).unwrap().value, "name");
+    assert!(tokens.eat_operator(":"));
+    assert!(!tokens.eat_name("int"));
+    assert!(tokens.eat_name("str"));
+    assert!(tokens.eat(TokenType::Number).is_none());
+    assert!(tokens.at_operator("="));
+    assert_eq!(tokens.previous().unwrap().value, "str");
+    assert_eq!(tokens.position(), 3);
+}
+
+#[test]
+fn the_end_marker_stays_put() {
+    let mut tokens = TokenStream::new(Vec::new());
+    assert!(tokens.is_at_end());
+    assert_eq!(tokens.peek(0).start, Location::new(1, 0));
+    for _ in 0..3 {
+        assert_eq!(tokens.advance().kind, TokenType::EndMarker);
+    }
+    assert_eq!(tokens.position(), 0);
+
+    let mut tokens = TokenStream::tokenize("x").unwrap();
+    tokens.advance();
+    tokens.advance();
+    assert!(tokens.is_at_end());
+    assert_eq!(tokens.advance().kind, TokenType::EndMarker);
+    assert!(tokens.is_at_end());
+}
+
+#[test]
+fn expect_fails_at_the_token_found() {
+    let mut tokens = TokenStream::tokenize("x = 1\n").unwrap();
+    tokens.advance();
+    let error = tokens.expect(TokenType::Name).unwrap_err();
+    assert_eq!(error.kind, ParseErrorKind::InvalidSyntax);
+    assert_eq!(error.message, "expected NAME, found EQUAL");
+    assert_eq!(error.location, Location::new(1, 2));
+    assert_eq!(tokens.position(), 1);
+}
+
+#[test]
+fn rollback_returns_to_a_checkpoint() {
+    let mut tokens = TokenStream::tokenize("f(a, b)\n").unwrap();
+    tokens.expect(TokenType::Name).unwrap();
+    let checkpoint = tokens.checkpoint();
+    // Try reading a subscript, which does not fit, then a call.
+    assert!(!tokens.eat_operator("["));
+    assert!(tokens.eat_operator("("));
+    assert!(tokens.eat_name("a"));
+    assert!(tokens.eat_operator(","));
+    tokens.rollback(checkpoint);
+    assert_eq!(tokens.position(), 1);
+    assert!(tokens.at_operator("("));
+    let values: Vec<String> = (0..5).map(|_| tokens.advance().value).collect();
+    assert_eq!(values, ["(", "a", ",", "b", ")"]);
+}
@@ -0,0 +1,138 @@
+//! `rustpy tokenize` lists tokens as `python -m tokenize` does. A sample
+//! with tokens of each kind is checked against the listing CPython 3.11
+//! gave for it, recorded in `tests/data`. The vendored standard library
+//! sample and the other Python files in the repository, and the directory
+//! named by `RUSTPY_TOKENIZE_CORPUS` if it is set, are checked against
+//! `python -m tokenize` itself when a Python 3.11 is found: the one named
+//! by `RUSTPY_PYTHON`, or `python3`.
+
+extern crate rustpy;
+
+mod common;
+
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::Path;
+
+use common::python_files;
+use std::process::Command;
+
+/// The Python release whose tokenizer the listings match. Later releases
+/// split f-strings into several tokens.
+const PYTHON_VERSION: &str = "3.11";
+
+/// What `rustpy tokenize` prints for `path`, with `-e` if `exact`.
+fn rustpy_listing(path: &Path, exact: bool) -> String {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_rustpy"));
+    command.arg("tokenize");
+    if exact {
+        command.arg("-e");
+    }
+    let output = command.arg(path).output().unwrap();
+    assert!(
+        output.status.success(),
+        "rustpy tokenize {} failed: {}",
+        path.display(),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// The Python to compare with, if it is the release the listings match.
+fn python() -> Option<OsString> {
+    let python = env::var_os("RUSTPY_PYTHON").unwrap_or_else(|| "python3".into());
+    let output = Command::new(&python)
+        .args(["-c", "import sys; print('%d.%d' % sys.version_info[:2])"])
+        .output()
+        .ok()?;
+    let version = String::from_utf8_lossy(&output.stdout);
+    if output.status.success() && version.trim() == PYTHON_VERSION {
+        Some(python)
+    } else {
+        None
+    }
+}
+
+/// The first line where two listings differ, for a failure message.
+fn first_difference(expected: &str, got: &str) -> String {
+    let mut got_lines = got.lines();
+    for (number, line) in expected.lines().enumerate() {
+        match got_lines.next() {
+            Some(other) if other == line => {}
+            other => {
+                return format!(
+                    "line {}: expected {:?}, got {:?}",
+                    number + 1,
+                    line,
+                    other.unwrap_or("")
+                )
+            }
+        }
+    }
+    format!("extra line {:?}", got_lines.next().unwrap_or(""))
+}
+
+#[test]
+fn listing_matches_the_recorded_cpython_listing() {
+    let data = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+    let expected = fs::read_to_string(data.join("tokenize_sample.txt")).unwrap();
+    let got = rustpy_listing(&data.join("tokenize_sample.py"), true);
+    if got != expected {
+        panic!("{}", first_difference(&expected, &got));
+    }
+}
+
+#[test]
+fn listing_matches_python_tokenize() {
+    let python = match python() {
+        Some(python) => python,
+        None => {
+            eprintln!("skipped: no Python {} to compare with", PYTHON_VERSION);
+            return;
+        }
+    };
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut files = Vec::new();
+    for directory in &["benches", "python", "tests"] {
+        python_files(&root.join(directory), &mut files);
+    }
+    if let Some(corpus) = env::var_os("RUSTPY_TOKENIZE_CORPUS") {
+        python_files(Path::new(&corpus), &mut files);
+    }
+    files.sort();
+    assert!(files.contains(&root.join("benches/data/typing.py")));
+    let mut failures = Vec::new();
+    for path in files {
+        // Both listings are compared for the sample of the standard
+        // library, and the exact one for the rest.
+        let modes: &[bool] = if path.ends_with("benches/data/typing.py") {
+            &[false, true]
+        } else {
+            &[true]
+        };
+        for &exact in modes {
+            let mut command = Command::new(&python);
+            command.args(["-m", "tokenize"]);
+            if exact {
+                command.arg("-e");
+            }
+            let output = command.arg(&path).output().unwrap();
+            // Files CPython cannot tokenize are left out.
+            if !output.status.success() {
+                continue;
+            }
+            let expected = String::from_utf8(output.stdout).unwrap();
+            let got = rustpy_listing(&path, exact);
+            if got != expected {
+                failures.push(format!(
+                    "{}{}: {}",
+                    path.display(),
+                    if exact { " (-e)" } else { "" },
+                    first_difference(&expected, &got)
+                ));
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,64 @@
+//! The walker leaves out the files a project's settings exclude and its
+//! `.gitignore` files ignore, wherever in the project the walk starts.
+
+extern crate rustpy;
+
+mod common;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rustpy::config::Config;
+use rustpy::walk::Walker;
+
+/// The files found walking `root` with the settings discovered for it,
+/// relative to `project`.
+fn walk(project: &Path, root: &Path) -> Vec<PathBuf> {
+    let config = Config::discover(root).unwrap().unwrap().1;
+    let walk = config.exclude_from(Walker::new(root)).walk();
+    assert!(walk.errors.is_empty(), "{:?}", walk.errors);
+    walk.files
+        .iter()
+        .map(|file| file.strip_prefix(project).unwrap().to_path_buf())
+        .collect()
+}
+
+#[test]
+fn excludes_are_relative_to_the_project() {
+    let project = common::project("excludes");
+    common::write(
+        &project,
+        "pyproject.toml",
+        "[tool.rustpy]\nexclude = [\"src/a.py\", \"gen/\"]\n",
+    );
+    common::write(&project, "a.py", "");
+    common::write(&project, "src/a.py", "");
+    common::write(&project, "src/b.py", "");
+    common::write(&project, "src/gen/c.py", "");
+    common::write(&project, "gen/d.py", "");
+
+    let files = vec![PathBuf::from("a.py"), PathBuf::from("src/b.py")];
+    assert_eq!(walk(&project, &project), files);
+    assert_eq!(walk(&project, &project.join("src")), files[1..]);
+}
+
+#[test]
+fn ignore_files_above_the_root_apply_up_to_the_repository() {
+    let project = common::project("ignores");
+    fs::create_dir_all(project.join("repo/.git")).unwrap();
+    common::write(&project, ".gitignore", "b.py\n");
+    common::write(&project, "repo/.gitignore", "*.gen.py\n/src/c.py\n");
+    common::write(&project, "repo/src/.gitignore", "!d.gen.py\n");
+    for file in &["a.py", "b.py", "c.py", "d.gen.py", "e.gen.py"] {
+        common::write(&project, &format!("repo/src/{}", file), "");
+    }
+
+    let walk = Walker::new(project.join("repo/src")).walk();
+    assert!(walk.errors.is_empty(), "{:?}", walk.errors);
+    let names: Vec<_> = walk
+        .files
+        .iter()
+        .map(|file| file.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(names, ["a.py", "b.py", "d.gen.py"]);
+}
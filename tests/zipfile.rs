@@ -0,0 +1,47 @@
+//! `zipfile` reads back what it writes, and `ZipFile.open` gives a binary
+//! stream of a member's data.
+
+extern crate rustpy;
+
+mod common;
+
+use common::{output, project};
+
+#[test]
+fn members_are_read_through_a_binary_stream() {
+    let archive = project("zipfile-open").join("a.zip");
+    let source = format!(
+        r#"
+import zipfile
+with zipfile.ZipFile({:?}, 'w', zipfile.ZIP_DEFLATED) as z:
+    z.writestr('a.txt', 'one\ntwo\nthree\n')
+    z.writestr('b.bin', bytes(range(10)))
+with zipfile.ZipFile({:?}) as z:
+    print(z.namelist(), z.read('a.txt'))
+    with z.open('a.txt') as f:
+        print(f.name, f.readline(), f.read())
+    print(f.closed)
+    f = z.open(z.getinfo('b.bin'))
+    print(f.read(3), f.seek(8), f.read())
+    for mode in ['w', 'x']:
+        try:
+            z.open('a.txt', mode)
+        except Exception as e:
+            print(type(e).__name__, e)
+    try:
+        z.open('missing')
+    except KeyError as e:
+        print(e)
+"#,
+        archive, archive
+    );
+    let expected = r#"['a.txt', 'b.bin'] b'one\ntwo\nthree\n'
+a.txt b'one\n' b'two\nthree\n'
+True
+b'\x00\x01\x02' 8 b'\x08\t'
+NotImplementedError ZipFile.open() supports only reading; use writestr() to add a member
+ValueError open() requires mode "r" or "w"
+"There is no item named 'missing' in the archive"
+"#;
+    assert_eq!(output(&source).unwrap(), expected);
+}